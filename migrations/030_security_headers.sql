@@ -0,0 +1,4 @@
+-- Browser-facing security settings: CORS origin allowlist and CDN CSP
+
+ALTER TABLE server_settings ADD COLUMN cors_allowed_origins TEXT;
+ALTER TABLE server_settings ADD COLUMN cdn_content_security_policy TEXT;
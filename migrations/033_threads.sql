@@ -0,0 +1,29 @@
+-- First-class thread objects. A thread's ID is the ID of the message that
+-- starts it, so replies keep pointing at it through messages.thread_id.
+-- Standalone threads get a titled starter message, like a forum post.
+CREATE TABLE IF NOT EXISTS threads (
+    id                    TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    channel_id            TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    space_id              TEXT REFERENCES spaces(id) ON DELETE CASCADE,
+    owner_id              TEXT NOT NULL REFERENCES users(id),
+    name                  TEXT NOT NULL,
+    -- Minutes of inactivity before the thread is archived automatically.
+    auto_archive_after INTEGER NOT NULL DEFAULT 1440,
+    archived              INTEGER NOT NULL DEFAULT 0,
+    archived_at           TEXT,
+    locked                INTEGER NOT NULL DEFAULT 0,
+    last_activity_at      TEXT NOT NULL DEFAULT (datetime('now')),
+    created_at            TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_threads_channel ON threads(channel_id);
+CREATE INDEX idx_threads_archive ON threads(archived, last_activity_at);
+
+CREATE TABLE IF NOT EXISTS thread_members (
+    thread_id TEXT NOT NULL REFERENCES threads(id) ON DELETE CASCADE,
+    user_id   TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    joined_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (thread_id, user_id)
+);
+
+CREATE INDEX idx_thread_members_user ON thread_members(user_id);
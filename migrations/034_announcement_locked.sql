@@ -0,0 +1,4 @@
+-- View-only ("announcement-locked") channels: @everyone can read but not post
+-- or react. The flag mirrors the channel's @everyone overwrite and is
+-- recomputed whenever overwrites change.
+ALTER TABLE channels ADD COLUMN announcement_locked INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,17 @@
+-- Shared ban lists. Spaces that opt into the same sync group copy each
+-- other's new bans; each space belongs to at most one group.
+CREATE TABLE IF NOT EXISTS ban_sync_groups (
+    id         TEXT PRIMARY KEY,
+    name       TEXT NOT NULL,
+    owner_id   TEXT NOT NULL REFERENCES users(id),
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE TABLE IF NOT EXISTS ban_sync_group_spaces (
+    space_id  TEXT PRIMARY KEY REFERENCES spaces(id) ON DELETE CASCADE,
+    group_id  TEXT NOT NULL REFERENCES ban_sync_groups(id) ON DELETE CASCADE,
+    added_by  TEXT REFERENCES users(id) ON DELETE SET NULL,
+    joined_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_ban_sync_group_spaces_group ON ban_sync_group_spaces(group_id);
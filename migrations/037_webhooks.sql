@@ -0,0 +1,19 @@
+-- Incoming webhooks. External services post into a channel with the
+-- webhook's secret token instead of a bot token. Only the token's hash is
+-- stored. Webhook messages are attributed to the webhook's creator and carry
+-- the display name/avatar they were posted with.
+CREATE TABLE IF NOT EXISTS webhooks (
+    id         TEXT PRIMARY KEY,
+    channel_id TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    space_id   TEXT NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    creator_id TEXT NOT NULL REFERENCES users(id),
+    name       TEXT NOT NULL,
+    avatar     TEXT,
+    token_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_webhooks_channel ON webhooks(channel_id);
+
+ALTER TABLE messages ADD COLUMN webhook_username TEXT;
+ALTER TABLE messages ADD COLUMN webhook_avatar TEXT;
@@ -0,0 +1,18 @@
+-- Instance-wide trust & safety blocklists maintained by server admins.
+-- `kind` is one of:
+--   email_domain  registration is refused for emails on this domain
+--                 (subdomains included)
+--   file_hash     uploads whose SHA-256 (lowercase hex) matches are refused
+--   user          the user ID may not join spaces or upload files
+CREATE TABLE IF NOT EXISTS instance_blocklist (
+    kind       TEXT NOT NULL,
+    value      TEXT NOT NULL,
+    reason     TEXT,
+    created_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (kind, value)
+);
+
+-- Optional contact email given at registration, checked against the
+-- email_domain blocklist.
+ALTER TABLE users ADD COLUMN email TEXT;
@@ -0,0 +1,4 @@
+-- CIDR ranges an application's bot token may be used from, as a JSON
+-- array. Empty means no restriction. Kept on the application rather than
+-- the token so it survives token resets.
+ALTER TABLE applications ADD COLUMN ip_allowlist TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,16 @@
+-- Per-space AutoMod rules, checked against every new message. `trigger_data`
+-- is the JSON trigger (`{"type": "keyword" | "regex" | "mention_spam" |
+-- "link", ...}`) and `actions` a JSON array of `block`, `flag` (to a log
+-- channel) and `timeout` actions.
+CREATE TABLE IF NOT EXISTS automod_rules (
+    id           TEXT PRIMARY KEY,
+    space_id     TEXT NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    creator_id   TEXT NOT NULL REFERENCES users(id),
+    name         TEXT NOT NULL,
+    trigger_data TEXT NOT NULL,
+    actions      TEXT NOT NULL,
+    enabled      INTEGER NOT NULL DEFAULT 1,
+    created_at   TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_automod_rules_space ON automod_rules(space_id);
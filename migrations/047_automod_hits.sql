@@ -0,0 +1,16 @@
+-- One row per AutoMod rule match, for tuning rules. `sample` is a short,
+-- mention-redacted excerpt around the match rather than the whole message.
+-- Moderators mark false positives by setting `overridden`.
+CREATE TABLE IF NOT EXISTS automod_hits (
+    id            TEXT PRIMARY KEY,
+    rule_id       TEXT NOT NULL REFERENCES automod_rules(id) ON DELETE CASCADE,
+    space_id      TEXT NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    channel_id    TEXT NOT NULL,
+    user_id       TEXT NOT NULL,
+    sample        TEXT NOT NULL,
+    overridden    INTEGER NOT NULL DEFAULT 0,
+    overridden_by TEXT,
+    created_at    TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_automod_hits_space ON automod_hits(space_id, created_at);
@@ -0,0 +1,3 @@
+-- Roles and channels new spaces start with (JSON); NULL uses the built-in
+-- layout.
+ALTER TABLE server_settings ADD COLUMN default_space_template TEXT;
@@ -0,0 +1,5 @@
+-- Profile pronouns and IANA timezone, on the account and per space.
+ALTER TABLE users ADD COLUMN pronouns TEXT;
+ALTER TABLE users ADD COLUMN timezone TEXT;
+ALTER TABLE members ADD COLUMN pronouns TEXT;
+ALTER TABLE members ADD COLUMN timezone TEXT;
@@ -0,0 +1,3 @@
+-- Bumped on every channel reorder so clients can reorder against the order
+-- they last saw (optimistic concurrency).
+ALTER TABLE spaces ADD COLUMN channel_order_version INTEGER NOT NULL DEFAULT 0;
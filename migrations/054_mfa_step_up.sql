@@ -0,0 +1,4 @@
+-- Spaces with mfa_level 'elevated' require moderators to have verified a
+-- TOTP or backup code recently before destructive actions.
+ALTER TABLE spaces ADD COLUMN mfa_level TEXT NOT NULL DEFAULT 'none';
+ALTER TABLE users ADD COLUMN mfa_verified_at TEXT;
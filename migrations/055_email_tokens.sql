@@ -0,0 +1,15 @@
+-- Password reset and email verification codes, stored hashed. `email` is the
+-- address a code was sent to, so a verification code stops working if the
+-- user's address changes in the meantime.
+ALTER TABLE users ADD COLUMN email_verified INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS email_tokens (
+    token_hash TEXT PRIMARY KEY,
+    user_id    TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind       TEXT NOT NULL,
+    email      TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_email_tokens_user ON email_tokens(user_id, kind);
@@ -0,0 +1,153 @@
+-- Permissions move from JSON name lists to integer bitfields. Bit N is the
+-- Nth entry of PERMISSION_CATALOG in src/models/permission.rs; unknown names
+-- get no bit. SUM(DISTINCT ...) over distinct powers of two is a bitwise OR.
+--
+-- The server stops reading and writing the name lists (roles.permissions,
+-- permission_overwrites.allow/deny) but they stay as they were, so anything
+-- this conversion missed can still be recovered. A later migration drops
+-- them once the bitfields have been verified in a release.
+
+ALTER TABLE roles ADD COLUMN permission_bits INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE permission_overwrites ADD COLUMN allow_bits INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE permission_overwrites ADD COLUMN deny_bits INTEGER NOT NULL DEFAULT 0;
+
+UPDATE roles SET permission_bits = COALESCE((
+    SELECT SUM(DISTINCT CASE value
+            WHEN 'create_invites' THEN 1
+            WHEN 'kick_members' THEN 2
+            WHEN 'ban_members' THEN 4
+            WHEN 'administrator' THEN 8
+            WHEN 'manage_channels' THEN 16
+            WHEN 'manage_space' THEN 32
+            WHEN 'add_reactions' THEN 64
+            WHEN 'view_audit_log' THEN 128
+            WHEN 'priority_speaker' THEN 256
+            WHEN 'stream' THEN 512
+            WHEN 'view_channel' THEN 1024
+            WHEN 'send_messages' THEN 2048
+            WHEN 'send_messages_in_voice' THEN 4096
+            WHEN 'send_tts' THEN 8192
+            WHEN 'manage_messages' THEN 16384
+            WHEN 'embed_links' THEN 32768
+            WHEN 'attach_files' THEN 65536
+            WHEN 'read_history' THEN 131072
+            WHEN 'mention_everyone' THEN 262144
+            WHEN 'use_external_emojis' THEN 524288
+            WHEN 'connect' THEN 1048576
+            WHEN 'speak' THEN 2097152
+            WHEN 'mute_members' THEN 4194304
+            WHEN 'deafen_members' THEN 8388608
+            WHEN 'move_members' THEN 16777216
+            WHEN 'use_vad' THEN 33554432
+            WHEN 'change_nickname' THEN 67108864
+            WHEN 'manage_nicknames' THEN 134217728
+            WHEN 'manage_roles' THEN 268435456
+            WHEN 'manage_webhooks' THEN 536870912
+            WHEN 'manage_emojis' THEN 1073741824
+            WHEN 'manage_soundboard' THEN 2147483648
+            WHEN 'use_soundboard' THEN 4294967296
+            WHEN 'use_commands' THEN 8589934592
+            WHEN 'manage_events' THEN 17179869184
+            WHEN 'manage_threads' THEN 34359738368
+            WHEN 'create_threads' THEN 68719476736
+            WHEN 'use_external_stickers' THEN 137438953472
+            WHEN 'send_in_threads' THEN 274877906944
+            WHEN 'moderate_members' THEN 549755813888
+            ELSE 0
+        END)
+    FROM json_each(roles.permissions)
+), 0);
+
+UPDATE permission_overwrites SET allow_bits = COALESCE((
+    SELECT SUM(DISTINCT CASE value
+            WHEN 'create_invites' THEN 1
+            WHEN 'kick_members' THEN 2
+            WHEN 'ban_members' THEN 4
+            WHEN 'administrator' THEN 8
+            WHEN 'manage_channels' THEN 16
+            WHEN 'manage_space' THEN 32
+            WHEN 'add_reactions' THEN 64
+            WHEN 'view_audit_log' THEN 128
+            WHEN 'priority_speaker' THEN 256
+            WHEN 'stream' THEN 512
+            WHEN 'view_channel' THEN 1024
+            WHEN 'send_messages' THEN 2048
+            WHEN 'send_messages_in_voice' THEN 4096
+            WHEN 'send_tts' THEN 8192
+            WHEN 'manage_messages' THEN 16384
+            WHEN 'embed_links' THEN 32768
+            WHEN 'attach_files' THEN 65536
+            WHEN 'read_history' THEN 131072
+            WHEN 'mention_everyone' THEN 262144
+            WHEN 'use_external_emojis' THEN 524288
+            WHEN 'connect' THEN 1048576
+            WHEN 'speak' THEN 2097152
+            WHEN 'mute_members' THEN 4194304
+            WHEN 'deafen_members' THEN 8388608
+            WHEN 'move_members' THEN 16777216
+            WHEN 'use_vad' THEN 33554432
+            WHEN 'change_nickname' THEN 67108864
+            WHEN 'manage_nicknames' THEN 134217728
+            WHEN 'manage_roles' THEN 268435456
+            WHEN 'manage_webhooks' THEN 536870912
+            WHEN 'manage_emojis' THEN 1073741824
+            WHEN 'manage_soundboard' THEN 2147483648
+            WHEN 'use_soundboard' THEN 4294967296
+            WHEN 'use_commands' THEN 8589934592
+            WHEN 'manage_events' THEN 17179869184
+            WHEN 'manage_threads' THEN 34359738368
+            WHEN 'create_threads' THEN 68719476736
+            WHEN 'use_external_stickers' THEN 137438953472
+            WHEN 'send_in_threads' THEN 274877906944
+            WHEN 'moderate_members' THEN 549755813888
+            ELSE 0
+        END)
+    FROM json_each(permission_overwrites.allow)
+), 0);
+
+UPDATE permission_overwrites SET deny_bits = COALESCE((
+    SELECT SUM(DISTINCT CASE value
+            WHEN 'create_invites' THEN 1
+            WHEN 'kick_members' THEN 2
+            WHEN 'ban_members' THEN 4
+            WHEN 'administrator' THEN 8
+            WHEN 'manage_channels' THEN 16
+            WHEN 'manage_space' THEN 32
+            WHEN 'add_reactions' THEN 64
+            WHEN 'view_audit_log' THEN 128
+            WHEN 'priority_speaker' THEN 256
+            WHEN 'stream' THEN 512
+            WHEN 'view_channel' THEN 1024
+            WHEN 'send_messages' THEN 2048
+            WHEN 'send_messages_in_voice' THEN 4096
+            WHEN 'send_tts' THEN 8192
+            WHEN 'manage_messages' THEN 16384
+            WHEN 'embed_links' THEN 32768
+            WHEN 'attach_files' THEN 65536
+            WHEN 'read_history' THEN 131072
+            WHEN 'mention_everyone' THEN 262144
+            WHEN 'use_external_emojis' THEN 524288
+            WHEN 'connect' THEN 1048576
+            WHEN 'speak' THEN 2097152
+            WHEN 'mute_members' THEN 4194304
+            WHEN 'deafen_members' THEN 8388608
+            WHEN 'move_members' THEN 16777216
+            WHEN 'use_vad' THEN 33554432
+            WHEN 'change_nickname' THEN 67108864
+            WHEN 'manage_nicknames' THEN 134217728
+            WHEN 'manage_roles' THEN 268435456
+            WHEN 'manage_webhooks' THEN 536870912
+            WHEN 'manage_emojis' THEN 1073741824
+            WHEN 'manage_soundboard' THEN 2147483648
+            WHEN 'use_soundboard' THEN 4294967296
+            WHEN 'use_commands' THEN 8589934592
+            WHEN 'manage_events' THEN 17179869184
+            WHEN 'manage_threads' THEN 34359738368
+            WHEN 'create_threads' THEN 68719476736
+            WHEN 'use_external_stickers' THEN 137438953472
+            WHEN 'send_in_threads' THEN 274877906944
+            WHEN 'moderate_members' THEN 549755813888
+            ELSE 0
+        END)
+    FROM json_each(permission_overwrites.deny)
+), 0);
@@ -0,0 +1,7 @@
+-- Caps on custom emoji and soundboard sounds per space. The server settings
+-- hold the instance default; a space's own value, set by an instance admin,
+-- overrides it. 0 means unlimited.
+ALTER TABLE server_settings ADD COLUMN max_emojis_per_space INTEGER NOT NULL DEFAULT 50;
+ALTER TABLE server_settings ADD COLUMN max_sounds_per_space INTEGER NOT NULL DEFAULT 8;
+ALTER TABLE spaces ADD COLUMN max_emojis INTEGER;
+ALTER TABLE spaces ADD COLUMN max_sounds INTEGER;
@@ -0,0 +1,3 @@
+-- Screen share quality caps handed to streamers with their stream grant.
+ALTER TABLE spaces ADD COLUMN stream_max_height INTEGER NOT NULL DEFAULT 720;
+ALTER TABLE spaces ADD COLUMN stream_max_fps INTEGER NOT NULL DEFAULT 30;
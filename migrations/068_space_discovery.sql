@@ -0,0 +1,7 @@
+-- Directory listing for public spaces: a category from a fixed list,
+-- free-form tags (comma-separated, lowercase) and an admin-set featured flag.
+ALTER TABLE spaces ADD COLUMN category TEXT;
+ALTER TABLE spaces ADD COLUMN tags TEXT NOT NULL DEFAULT '';
+ALTER TABLE spaces ADD COLUMN featured INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_spaces_public_category ON spaces(public, category);
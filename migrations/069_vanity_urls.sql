@@ -0,0 +1,12 @@
+-- Vanity invites: a space's claimed `vanity_url_code` works as an invite
+-- code. Uses are counted on the space; codes instance admins revoke can't
+-- be claimed again.
+ALTER TABLE spaces ADD COLUMN vanity_url_uses INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS revoked_vanity_codes (
+    code       TEXT PRIMARY KEY NOT NULL,
+    space_id   TEXT,
+    revoked_by TEXT NOT NULL,
+    reason     TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
@@ -0,0 +1,12 @@
+-- Invite attribution: the invite (or vanity code) each member joined
+-- through, and a log of every join by invite for per-invite stats.
+ALTER TABLE members ADD COLUMN source_invite TEXT;
+
+CREATE TABLE IF NOT EXISTS invite_uses (
+    space_id TEXT NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    code     TEXT NOT NULL,
+    user_id  TEXT NOT NULL,
+    used_at  TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_invite_uses_code ON invite_uses(space_id, code, used_at);
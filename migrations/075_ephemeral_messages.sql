@@ -0,0 +1,10 @@
+-- Ephemeral interaction responses: messages (flagged `FLAG_EPHEMERAL`)
+-- that only the invoking user sees. They stay out of channel history and
+-- the retention reaper deletes them once `expires_at` passes.
+CREATE TABLE IF NOT EXISTS ephemeral_messages (
+    message_id TEXT PRIMARY KEY NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id    TEXT NOT NULL,
+    expires_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_ephemeral_messages_expires ON ephemeral_messages(expires_at);
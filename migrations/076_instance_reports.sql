@@ -0,0 +1,32 @@
+-- Reports filed outside a space (users in DMs, spaces themselves) and a
+-- `reviewing` step for the instance moderation queue: space_id becomes
+-- nullable, spaces can be reported, and the queue is listed by status.
+
+PRAGMA foreign_keys = OFF;
+
+ALTER TABLE reports RENAME TO _reports_old;
+
+CREATE TABLE reports (
+    id TEXT PRIMARY KEY,
+    space_id TEXT REFERENCES spaces(id) ON DELETE CASCADE,
+    reporter_id TEXT NOT NULL REFERENCES users(id),
+    target_type TEXT NOT NULL CHECK (target_type IN ('message', 'user', 'space')),
+    target_id TEXT NOT NULL,
+    channel_id TEXT,
+    category TEXT NOT NULL CHECK (category IN ('csam', 'terrorism', 'fraud', 'hate', 'violence', 'self_harm', 'other')),
+    description TEXT,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'reviewing', 'actioned', 'dismissed')),
+    actioned_by TEXT REFERENCES users(id),
+    action_taken TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    resolved_at TEXT
+);
+
+INSERT INTO reports SELECT * FROM _reports_old;
+DROP TABLE _reports_old;
+
+CREATE INDEX idx_reports_space_status ON reports(space_id, status);
+CREATE INDEX idx_reports_space_created ON reports(space_id, created_at DESC);
+CREATE INDEX idx_reports_status_created ON reports(status, created_at DESC);
+
+PRAGMA foreign_keys = ON;
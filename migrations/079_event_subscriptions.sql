@@ -0,0 +1,30 @@
+-- Outgoing webhooks: applications subscribe a URL to gateway event types
+-- and each matching event is queued as a delivery, retried with backoff.
+CREATE TABLE IF NOT EXISTS event_subscriptions (
+    id             TEXT PRIMARY KEY,
+    application_id TEXT NOT NULL REFERENCES applications(id) ON DELETE CASCADE,
+    url            TEXT NOT NULL,
+    event_types    TEXT NOT NULL DEFAULT '[]',
+    space_ids      TEXT NOT NULL DEFAULT '[]',
+    enabled        INTEGER NOT NULL DEFAULT 1,
+    created_at     TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_event_subscriptions_app ON event_subscriptions(application_id);
+
+CREATE TABLE IF NOT EXISTS event_deliveries (
+    id              TEXT PRIMARY KEY,
+    subscription_id TEXT NOT NULL REFERENCES event_subscriptions(id) ON DELETE CASCADE,
+    event_type      TEXT NOT NULL,
+    payload         TEXT NOT NULL,
+    status          TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'succeeded', 'failed')),
+    attempts        INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT,
+    last_status     INTEGER,
+    last_error      TEXT,
+    created_at      TEXT NOT NULL DEFAULT (datetime('now')),
+    delivered_at    TEXT
+);
+
+CREATE INDEX idx_event_deliveries_due ON event_deliveries(status, next_attempt_at);
+CREATE INDEX idx_event_deliveries_subscription ON event_deliveries(subscription_id, id);
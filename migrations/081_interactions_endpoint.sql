@@ -0,0 +1,6 @@
+-- HTTP interactions: an application can have interactions POSTed to a URL
+-- instead of sent over the gateway. Those requests carry an Ed25519
+-- signature; the public half is shown as the application's `verify_key`.
+ALTER TABLE applications ADD COLUMN interactions_endpoint_url TEXT;
+ALTER TABLE applications ADD COLUMN verify_key TEXT;
+ALTER TABLE applications ADD COLUMN interactions_private_key TEXT;
@@ -0,0 +1,5 @@
+-- Auto-thread mode: every top-level message in the channel gets its own
+-- thread, named from `auto_thread_name` and archived after the channel's
+-- existing `auto_archive_after` window.
+ALTER TABLE channels ADD COLUMN auto_thread INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE channels ADD COLUMN auto_thread_name TEXT;
@@ -0,0 +1,34 @@
+-- Content-safety scanning (see src/content_safety.rs). New messages are
+-- handed to the providers named in `content_safety_providers`; a match is
+-- recorded as a flag and, with the `quarantine` action, the message is
+-- hidden until an instance admin reviews it.
+ALTER TABLE server_settings ADD COLUMN content_safety_providers TEXT;
+ALTER TABLE server_settings ADD COLUMN content_safety_endpoint TEXT;
+ALTER TABLE server_settings ADD COLUMN content_safety_action TEXT NOT NULL DEFAULT 'quarantine';
+
+-- Known-bad attachment hashes for the `hash_list` provider.
+CREATE TABLE IF NOT EXISTS content_safety_hashes (
+    hash       TEXT PRIMARY KEY,
+    label      TEXT,
+    created_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Flags outlive the message they're about, so they don't reference it.
+CREATE TABLE IF NOT EXISTS content_safety_flags (
+    id          TEXT PRIMARY KEY,
+    message_id  TEXT NOT NULL,
+    channel_id  TEXT NOT NULL,
+    space_id    TEXT,
+    author_id   TEXT NOT NULL,
+    provider    TEXT NOT NULL,
+    label       TEXT NOT NULL,
+    action      TEXT NOT NULL,
+    status      TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'released', 'removed')),
+    reviewed_by TEXT,
+    reviewed_at TEXT,
+    created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_content_safety_flags_status ON content_safety_flags(status, id);
+CREATE INDEX idx_content_safety_flags_message ON content_safety_flags(message_id);
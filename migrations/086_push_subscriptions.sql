@@ -0,0 +1,13 @@
+-- Web Push subscriptions: where to send a user's notifications while they
+-- have no gateway session. An endpoint belongs to one browser profile, so
+-- registering it again (from any account) replaces the old row.
+CREATE TABLE IF NOT EXISTS push_subscriptions (
+    id         TEXT PRIMARY KEY,
+    user_id    TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    endpoint   TEXT NOT NULL UNIQUE,
+    p256dh     TEXT NOT NULL,
+    auth       TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_push_subscriptions_user ON push_subscriptions(user_id);
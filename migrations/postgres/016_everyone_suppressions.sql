@@ -0,0 +1,10 @@
+-- Per-user, per-space opt-out from @everyone announcement mentions.
+-- Suppressed members still receive the message but get no mention badge.
+CREATE TABLE IF NOT EXISTS everyone_suppressions (
+    user_id    TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    space_id   TEXT NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS')),
+    PRIMARY KEY (user_id, space_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_everyone_suppressions_space ON everyone_suppressions(space_id);
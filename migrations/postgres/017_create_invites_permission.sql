@@ -0,0 +1,7 @@
+-- Invite creation is gated on its own `create_invites` permission. Roles that
+-- could manage channels were implicitly trusted with invites before, so grant
+-- it to them explicitly to keep their behaviour unchanged.
+UPDATE roles
+SET permissions = (permissions::jsonb || '["create_invites"]'::jsonb)::text
+WHERE permissions LIKE '%"manage_channels"%'
+  AND permissions NOT LIKE '%"create_invites"%';
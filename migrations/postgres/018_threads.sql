@@ -0,0 +1,29 @@
+-- First-class thread objects. A thread's ID is the ID of the message that
+-- starts it, so replies keep pointing at it through messages.thread_id.
+-- Standalone threads get a titled starter message, like a forum post.
+CREATE TABLE IF NOT EXISTS threads (
+    id                    TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    channel_id            TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    space_id              TEXT REFERENCES spaces(id) ON DELETE CASCADE,
+    owner_id              TEXT NOT NULL REFERENCES users(id),
+    name                  TEXT NOT NULL,
+    -- Minutes of inactivity before the thread is archived automatically.
+    auto_archive_after INTEGER NOT NULL DEFAULT 1440,
+    archived              BOOLEAN NOT NULL DEFAULT FALSE,
+    archived_at           TEXT,
+    locked                BOOLEAN NOT NULL DEFAULT FALSE,
+    last_activity_at      TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS')),
+    created_at            TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_threads_channel ON threads(channel_id);
+CREATE INDEX IF NOT EXISTS idx_threads_archive ON threads(archived, last_activity_at);
+
+CREATE TABLE IF NOT EXISTS thread_members (
+    thread_id TEXT NOT NULL REFERENCES threads(id) ON DELETE CASCADE,
+    user_id   TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    joined_at TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS')),
+    PRIMARY KEY (thread_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_thread_members_user ON thread_members(user_id);
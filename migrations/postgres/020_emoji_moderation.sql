@@ -0,0 +1,7 @@
+-- Optional approval queue for member-uploaded emoji. When a space turns on
+-- `emoji_moderation`, members without manage_emojis may upload, but their
+-- emoji stay pending until a moderator approves them.
+ALTER TABLE spaces ADD COLUMN emoji_moderation BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE emojis ADD COLUMN pending BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_emojis_pending ON emojis(space_id, pending);
@@ -0,0 +1,31 @@
+-- Slash commands registered by applications. Global commands have no
+-- space_id and are available in every space the application's bot is in;
+-- space commands only show up in that space. Names are unique per
+-- (application, scope), enforced by the API.
+CREATE TABLE IF NOT EXISTS application_commands (
+    id             TEXT PRIMARY KEY,
+    application_id TEXT NOT NULL REFERENCES applications(id) ON DELETE CASCADE,
+    space_id       TEXT REFERENCES spaces(id) ON DELETE CASCADE,
+    name           TEXT NOT NULL,
+    description    TEXT NOT NULL DEFAULT '',
+    options        TEXT NOT NULL DEFAULT '[]',
+    type           TEXT NOT NULL DEFAULT 'chat_input',
+    created_at     TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_application_commands_app ON application_commands(application_id, space_id);
+
+-- Command invocations awaiting (or holding) the bot's response. The bot
+-- answers through the callback endpoint with the interaction's token; only
+-- its hash is stored.
+CREATE TABLE IF NOT EXISTS interactions (
+    id             TEXT PRIMARY KEY,
+    application_id TEXT NOT NULL REFERENCES applications(id) ON DELETE CASCADE,
+    command_id     TEXT NOT NULL,
+    space_id       TEXT REFERENCES spaces(id) ON DELETE CASCADE,
+    channel_id     TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    user_id        TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash     TEXT NOT NULL,
+    response_type  TEXT,
+    created_at     TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS'))
+);
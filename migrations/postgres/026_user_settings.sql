@@ -0,0 +1,10 @@
+-- Per-user client settings (theme, locale, notification defaults, collapsed
+-- categories, ...) synced across devices. `settings` is a JSON object the
+-- server doesn't interpret; `version` increments on every write so clients
+-- can detect conflicting updates from another device.
+CREATE TABLE IF NOT EXISTS user_settings (
+    user_id    TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    settings   TEXT NOT NULL DEFAULT '{}',
+    version    INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS'))
+);
@@ -0,0 +1,8 @@
+-- Per-user sidebar layout: the order of a user's spaces and how they are
+-- grouped into folders, stored as a JSON array of entries so every client
+-- renders the same sidebar.
+CREATE TABLE IF NOT EXISTS user_space_layouts (
+    user_id    TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    entries    TEXT NOT NULL DEFAULT '[]',
+    updated_at TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS'))
+);
@@ -0,0 +1,16 @@
+-- Per-space AutoMod rules, checked against every new message. `trigger_data`
+-- is the JSON trigger (`{"type": "keyword" | "regex" | "mention_spam" |
+-- "link", ...}`) and `actions` a JSON array of `block`, `flag` (to a log
+-- channel) and `timeout` actions.
+CREATE TABLE IF NOT EXISTS automod_rules (
+    id           TEXT PRIMARY KEY,
+    space_id     TEXT NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    creator_id   TEXT NOT NULL REFERENCES users(id),
+    name         TEXT NOT NULL,
+    trigger_data TEXT NOT NULL,
+    actions      TEXT NOT NULL,
+    enabled      BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at   TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_automod_rules_space ON automod_rules(space_id);
@@ -0,0 +1,10 @@
+-- Per-user collapsed categories/channels in the sidebar, synced across
+-- devices next to channel_mutes.
+CREATE TABLE IF NOT EXISTS channel_collapses (
+    user_id    TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    channel_id TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS')),
+    PRIMARY KEY (user_id, channel_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_channel_collapses_user ON channel_collapses(user_id);
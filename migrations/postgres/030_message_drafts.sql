@@ -0,0 +1,12 @@
+-- Per-user, per-channel message drafts so a message started on one device
+-- can be finished on another. Drafts untouched for DRAFT_TTL are swept.
+CREATE TABLE IF NOT EXISTS message_drafts (
+    user_id    TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    channel_id TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    content    TEXT NOT NULL,
+    reply_to   TEXT,
+    updated_at TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS')),
+    PRIMARY KEY (user_id, channel_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_drafts_updated ON message_drafts(updated_at);
@@ -0,0 +1,13 @@
+-- Earlier versions of edited messages, for moderators. Each row is what the
+-- message said before the edit made by editor_id at revised_at.
+CREATE TABLE IF NOT EXISTS message_revisions (
+    id         TEXT PRIMARY KEY,
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    editor_id  TEXT NOT NULL,
+    content    TEXT NOT NULL,
+    title      TEXT,
+    embeds     TEXT NOT NULL DEFAULT '[]',
+    revised_at TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_revisions_message ON message_revisions(message_id, id);
@@ -0,0 +1,9 @@
+-- Text chat in voice channels: how long its messages are kept
+-- ('keep', 'clear_on_empty' or 'off'), and a separate send permission.
+ALTER TABLE channels ADD COLUMN IF NOT EXISTS voice_chat_retention TEXT NOT NULL DEFAULT 'keep';
+
+-- Roles that could send messages keep being able to chat in voice.
+UPDATE roles
+SET permissions = REPLACE(permissions, '"send_messages"', '"send_messages","send_messages_in_voice"')
+WHERE permissions LIKE '%"send_messages"%'
+  AND permissions NOT LIKE '%"send_messages_in_voice"%';
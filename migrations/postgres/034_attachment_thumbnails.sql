@@ -0,0 +1,3 @@
+-- Preview image for image attachments (a WebP thumbnail, or the original
+-- when it's already small enough).
+ALTER TABLE attachments ADD COLUMN IF NOT EXISTS proxy_url TEXT;
@@ -0,0 +1,5 @@
+-- Profile pronouns and IANA timezone, on the account and per space.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS pronouns TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS timezone TEXT;
+ALTER TABLE members ADD COLUMN IF NOT EXISTS pronouns TEXT;
+ALTER TABLE members ADD COLUMN IF NOT EXISTS timezone TEXT;
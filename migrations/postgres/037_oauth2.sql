@@ -0,0 +1,26 @@
+-- OAuth2 authorization server. Applications get a client secret (hash only)
+-- and registered redirect URIs; users grant scopes through an authorization
+-- code, which the application trades for an access/refresh token pair.
+ALTER TABLE applications ADD COLUMN IF NOT EXISTS client_secret_hash TEXT;
+ALTER TABLE applications ADD COLUMN IF NOT EXISTS redirect_uris TEXT NOT NULL DEFAULT '[]';
+
+CREATE TABLE IF NOT EXISTS oauth2_codes (
+    code_hash      TEXT PRIMARY KEY,
+    application_id TEXT NOT NULL REFERENCES applications(id) ON DELETE CASCADE,
+    user_id        TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    redirect_uri   TEXT NOT NULL,
+    scopes         TEXT NOT NULL,
+    expires_at     TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS oauth2_tokens (
+    token_hash         TEXT PRIMARY KEY,
+    refresh_token_hash TEXT NOT NULL UNIQUE,
+    application_id     TEXT NOT NULL REFERENCES applications(id) ON DELETE CASCADE,
+    user_id            TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    scopes             TEXT NOT NULL,
+    expires_at         TEXT NOT NULL,
+    created_at         TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_oauth2_tokens_user ON oauth2_tokens(user_id, application_id);
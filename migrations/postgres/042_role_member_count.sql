@@ -0,0 +1,7 @@
+-- Number of members holding each role, kept up to date as roles are granted
+-- and revoked. @everyone is implicit and isn't counted.
+ALTER TABLE roles ADD COLUMN IF NOT EXISTS member_count BIGINT NOT NULL DEFAULT 0;
+
+UPDATE roles SET member_count = (
+    SELECT COUNT(*) FROM member_roles mr WHERE mr.role_id = roles.id
+);
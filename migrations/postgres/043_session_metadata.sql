@@ -0,0 +1,6 @@
+-- Each user token is a login session the user can review and revoke. The
+-- session ID is the first 16 characters of the token hash; these columns say
+-- where it was signed in and when it was last used.
+ALTER TABLE user_tokens ADD COLUMN IF NOT EXISTS device TEXT;
+ALTER TABLE user_tokens ADD COLUMN IF NOT EXISTS ip TEXT;
+ALTER TABLE user_tokens ADD COLUMN IF NOT EXISTS last_used_at TEXT;
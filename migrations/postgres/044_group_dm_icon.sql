@@ -0,0 +1,2 @@
+-- Group DM icon, as a /cdn/ path. Only group DMs set it.
+ALTER TABLE channels ADD COLUMN IF NOT EXISTS icon TEXT;
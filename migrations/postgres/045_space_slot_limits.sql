@@ -0,0 +1,7 @@
+-- Caps on custom emoji and soundboard sounds per space. The server settings
+-- hold the instance default; a space's own value, set by an instance admin,
+-- overrides it. 0 means unlimited.
+ALTER TABLE server_settings ADD COLUMN IF NOT EXISTS max_emojis_per_space BIGINT NOT NULL DEFAULT 50;
+ALTER TABLE server_settings ADD COLUMN IF NOT EXISTS max_sounds_per_space BIGINT NOT NULL DEFAULT 8;
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS max_emojis BIGINT;
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS max_sounds BIGINT;
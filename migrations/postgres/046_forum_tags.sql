@@ -0,0 +1,21 @@
+-- Tags a forum channel offers, and the ones applied to each post (a thread
+-- in the forum). Moderated tags can only be applied by manage_threads.
+CREATE TABLE IF NOT EXISTS forum_tags (
+    id         TEXT PRIMARY KEY,
+    channel_id TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    name       TEXT NOT NULL,
+    emoji      TEXT,
+    moderated  BOOLEAN NOT NULL DEFAULT FALSE,
+    position   BIGINT NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_forum_tags_channel ON forum_tags(channel_id);
+
+CREATE TABLE IF NOT EXISTS thread_tags (
+    thread_id TEXT NOT NULL REFERENCES threads(id) ON DELETE CASCADE,
+    tag_id    TEXT NOT NULL REFERENCES forum_tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (thread_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_thread_tags_tag ON thread_tags(tag_id);
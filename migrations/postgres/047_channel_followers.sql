@@ -0,0 +1,6 @@
+-- Following an announcement channel creates a webhook in the follower's
+-- channel that points back at the source. Crossposting a message delivers a
+-- copy through every such webhook.
+ALTER TABLE webhooks ADD COLUMN IF NOT EXISTS source_channel_id TEXT REFERENCES channels(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_webhooks_source_channel ON webhooks(source_channel_id);
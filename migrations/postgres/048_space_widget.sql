@@ -0,0 +1,4 @@
+-- Spaces can opt in to a public widget at /spaces/{id}/widget.json. When
+-- `widget_channel_id` is set, the widget offers an invite to that channel.
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS widget_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS widget_channel_id TEXT REFERENCES channels(id) ON DELETE SET NULL;
@@ -0,0 +1,16 @@
+-- WebAuthn credentials. `credential_id` is the base64url ID authenticators
+-- identify themselves by; `public_key` is the base64url key assertions are
+-- verified with, in the form `algorithm` (a COSE identifier) expects.
+CREATE TABLE IF NOT EXISTS passkeys (
+    id            TEXT PRIMARY KEY,
+    user_id       TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    credential_id TEXT NOT NULL UNIQUE,
+    public_key    TEXT NOT NULL,
+    algorithm     BIGINT NOT NULL,
+    sign_count    BIGINT NOT NULL DEFAULT 0,
+    name          TEXT NOT NULL,
+    created_at    TEXT NOT NULL,
+    last_used_at  TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_passkeys_user ON passkeys(user_id);
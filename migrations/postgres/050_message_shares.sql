@@ -0,0 +1,15 @@
+-- Opt-in public links to single messages. A share's token is signed with
+-- `server_settings.share_link_key` (generated on first use); deleting the
+-- row revokes it.
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS message_sharing BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE server_settings ADD COLUMN IF NOT EXISTS share_link_key TEXT;
+
+CREATE TABLE IF NOT EXISTS message_shares (
+    id         TEXT PRIMARY KEY,
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    channel_id TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    creator_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_shares_message ON message_shares(message_id);
@@ -0,0 +1,10 @@
+-- Channels locked with `POST /channels/{id}/lock`, and what the @everyone
+-- overwrite held for the locked permissions beforehand so unlocking can put
+-- it back.
+CREATE TABLE IF NOT EXISTS channel_locks (
+    channel_id TEXT PRIMARY KEY REFERENCES channels(id) ON DELETE CASCADE,
+    prev_allow BIGINT NOT NULL DEFAULT 0,
+    prev_deny  BIGINT NOT NULL DEFAULT 0,
+    locked_by  TEXT REFERENCES users(id) ON DELETE SET NULL,
+    created_at TEXT NOT NULL
+);
@@ -0,0 +1,3 @@
+-- Screen share quality caps handed to streamers with their stream grant.
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS stream_max_height BIGINT NOT NULL DEFAULT 720;
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS stream_max_fps BIGINT NOT NULL DEFAULT 30;
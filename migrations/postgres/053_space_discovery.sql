@@ -0,0 +1,7 @@
+-- Directory listing for public spaces: a category from a fixed list,
+-- free-form tags (comma-separated, lowercase) and an admin-set featured flag.
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS category TEXT;
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS tags TEXT NOT NULL DEFAULT '';
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS featured BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_spaces_public_category ON spaces(public, category);
@@ -0,0 +1,3 @@
+-- Archived spaces are read-only: set when the owner freezes the space,
+-- cleared when they unarchive it.
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS archived_at TEXT;
@@ -0,0 +1,18 @@
+-- Welcome screens (a description and featured channels shown to new
+-- members) and membership screening (rules new members accept before
+-- they can send messages; until then `members.pending` is set).
+CREATE TABLE IF NOT EXISTS welcome_screens (
+    space_id    TEXT PRIMARY KEY NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    enabled     BOOLEAN NOT NULL DEFAULT FALSE,
+    description TEXT,
+    channels    TEXT NOT NULL DEFAULT '[]',
+    updated_at  TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS'))
+);
+
+CREATE TABLE IF NOT EXISTS member_screenings (
+    space_id    TEXT PRIMARY KEY NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    enabled     BOOLEAN NOT NULL DEFAULT FALSE,
+    description TEXT,
+    rules       TEXT NOT NULL DEFAULT '[]',
+    updated_at  TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS'))
+);
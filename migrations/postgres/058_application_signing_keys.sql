@@ -0,0 +1,14 @@
+-- Keys an application's outgoing requests (event webhooks, HTTP
+-- interactions) are signed with. Several can be live at once: rotating
+-- adds a key and gives the old ones an `expires_at`, so receivers have an
+-- overlap window to pick up the new secret.
+CREATE TABLE IF NOT EXISTS application_signing_keys (
+    id             TEXT PRIMARY KEY NOT NULL,
+    application_id TEXT NOT NULL REFERENCES applications(id) ON DELETE CASCADE,
+    secret         TEXT NOT NULL,
+    created_at     TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS')),
+    expires_at     TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_application_signing_keys_app
+    ON application_signing_keys(application_id);
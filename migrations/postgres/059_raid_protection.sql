@@ -0,0 +1,17 @@
+-- Raid protection. A space in lockdown either turns away every new member
+-- (`block_joins`) or only accounts younger than `min_account_age_secs`
+-- (`account_age`). Lockdowns are started by hand or, with
+-- `auto_lockdown` on, when `join_threshold` or more members join within
+-- `join_window_secs`; automatic ones lift after `lockdown_secs`.
+CREATE TABLE IF NOT EXISTS space_raid_protection (
+    space_id             TEXT PRIMARY KEY NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    auto_lockdown        BOOLEAN NOT NULL DEFAULT FALSE,
+    join_threshold       BIGINT NOT NULL DEFAULT 10,
+    join_window_secs     BIGINT NOT NULL DEFAULT 60,
+    lockdown_secs        BIGINT NOT NULL DEFAULT 3600,
+    mode                 TEXT NOT NULL DEFAULT 'account_age',
+    min_account_age_secs BIGINT NOT NULL DEFAULT 604800,
+    lockdown_since       TEXT,
+    lockdown_until       TEXT,
+    lockdown_automatic   BOOLEAN NOT NULL DEFAULT FALSE
+);
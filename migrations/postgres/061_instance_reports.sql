@@ -0,0 +1,14 @@
+-- Reports filed outside a space (users in DMs, spaces themselves) and a
+-- `reviewing` step for the instance moderation queue.
+
+ALTER TABLE reports ALTER COLUMN space_id DROP NOT NULL;
+
+ALTER TABLE reports DROP CONSTRAINT IF EXISTS reports_target_type_check;
+ALTER TABLE reports ADD CONSTRAINT reports_target_type_check
+    CHECK (target_type IN ('message', 'user', 'space'));
+
+ALTER TABLE reports DROP CONSTRAINT IF EXISTS reports_status_check;
+ALTER TABLE reports ADD CONSTRAINT reports_status_check
+    CHECK (status IN ('pending', 'reviewing', 'actioned', 'dismissed'));
+
+CREATE INDEX IF NOT EXISTS idx_reports_status_created ON reports(status, created_at DESC);
@@ -0,0 +1,8 @@
+-- Spaces that only admit users with a verified email on one of these
+-- domains (or a subdomain of one). No rows means anyone can join.
+CREATE TABLE IF NOT EXISTS space_email_domains (
+    space_id   TEXT NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    domain     TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS')),
+    PRIMARY KEY (space_id, domain)
+);
@@ -0,0 +1,3 @@
+-- Per-space banner and bio, overriding the account's in one space.
+ALTER TABLE members ADD COLUMN IF NOT EXISTS banner TEXT;
+ALTER TABLE members ADD COLUMN IF NOT EXISTS bio TEXT;
@@ -0,0 +1,15 @@
+-- Two-step invite accepts: previewing an invite issues a short-lived join
+-- token for that user, which accepting hands back. Spaces can require it so
+-- a leaked link can't be turned into joins without anyone seeing the space
+-- first. Only the token's hash is stored.
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS invite_preview_required BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS invite_join_tokens (
+    token_hash  TEXT PRIMARY KEY,
+    invite_code TEXT NOT NULL,
+    space_id    TEXT NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    user_id     TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at  TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_invite_join_tokens_expires ON invite_join_tokens(expires_at);
@@ -0,0 +1,2 @@
+-- Per-channel pin limit; NULL means the default (`DEFAULT_MAX_PINS`).
+ALTER TABLE channels ADD COLUMN IF NOT EXISTS max_pins BIGINT;
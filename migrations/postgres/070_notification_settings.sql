@@ -0,0 +1,16 @@
+-- Per-user mute for a whole space. Every channel in it counts as muted
+-- unless the user keeps the channel unmuted with a channel_unmutes row.
+CREATE TABLE IF NOT EXISTS space_mutes (
+    user_id    TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    space_id   TEXT NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS')),
+    PRIMARY KEY (user_id, space_id)
+);
+
+-- Channels a user keeps unmuted even though their space or category is muted.
+CREATE TABLE IF NOT EXISTS channel_unmutes (
+    user_id    TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    channel_id TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS')),
+    PRIMARY KEY (user_id, channel_id)
+);
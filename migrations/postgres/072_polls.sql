@@ -0,0 +1,21 @@
+-- Polls attached to messages. `answers` is a JSON array of
+-- {"id", "text"} with ids 1..n; `finalized_at` is set once the poll has
+-- expired and its results were announced.
+CREATE TABLE IF NOT EXISTS polls (
+    message_id        TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    question          TEXT NOT NULL,
+    answers           TEXT NOT NULL DEFAULT '[]',
+    allow_multiselect BOOLEAN NOT NULL DEFAULT FALSE,
+    expires_at        TEXT NOT NULL,
+    finalized_at      TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_polls_open ON polls(finalized_at, expires_at);
+
+CREATE TABLE IF NOT EXISTS poll_votes (
+    message_id TEXT NOT NULL REFERENCES polls(message_id) ON DELETE CASCADE,
+    user_id    TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    answer_id  INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS')),
+    PRIMARY KEY (message_id, user_id, answer_id)
+);
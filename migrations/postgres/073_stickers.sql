@@ -0,0 +1,34 @@
+-- Custom stickers per space. `format` is `png`, `apng`, or `lottie`; the file
+-- lives at `image_path` under /cdn. `tags` is a comma-separated list of
+-- words clients suggest the sticker for.
+CREATE TABLE IF NOT EXISTS stickers (
+    id                 TEXT PRIMARY KEY NOT NULL,
+    space_id           TEXT NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    name               TEXT NOT NULL,
+    description        TEXT,
+    tags               TEXT NOT NULL DEFAULT '',
+    format             TEXT NOT NULL,
+    image_path         TEXT NOT NULL,
+    image_content_type TEXT NOT NULL,
+    image_size         BIGINT NOT NULL,
+    creator_id         TEXT REFERENCES users(id) ON DELETE SET NULL,
+    created_at         TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS')),
+    updated_at         TEXT NOT NULL DEFAULT (to_char(now() at time zone 'UTC', 'YYYY-MM-DD HH24:MI:SS'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_stickers_space_id ON stickers(space_id);
+
+-- Stickers sent with a message, in the order given.
+CREATE TABLE IF NOT EXISTS message_stickers (
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    sticker_id TEXT NOT NULL REFERENCES stickers(id) ON DELETE CASCADE,
+    position   BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (message_id, sticker_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_stickers_sticker_id ON message_stickers(sticker_id);
+
+-- Sticker file size and slot limits, overridable per space like emoji.
+ALTER TABLE server_settings ADD COLUMN IF NOT EXISTS max_sticker_size BIGINT NOT NULL DEFAULT 524288;
+ALTER TABLE server_settings ADD COLUMN IF NOT EXISTS max_stickers_per_space BIGINT NOT NULL DEFAULT 20;
+ALTER TABLE spaces ADD COLUMN IF NOT EXISTS max_stickers BIGINT;
@@ -0,0 +1,4 @@
+-- MFA step-up is tracked per login session: verifying a code on one device
+-- no longer clears destructive actions on every other session of the user.
+-- users.mfa_verified_at is no longer read.
+ALTER TABLE user_tokens ADD COLUMN IF NOT EXISTS mfa_verified_at TEXT;
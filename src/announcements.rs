@@ -0,0 +1,191 @@
+//! Server-side fan-out for @everyone announcements.
+//!
+//! Ordinary `@everyone` is left to clients (the message carries
+//! `mention_everyone: true`). When a `manage_space` holder uses it in an
+//! announcement channel, the server instead badges every member itself so the
+//! unread/mention count is correct on reconnect and on clients that never saw
+//! the live event. Large spaces are walked in batches on a background task so
+//! the sending request isn't held up.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::db;
+use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::permissions::{
+    apply_channel_overwrites, resolve_member_permissions_with_admin,
+};
+use crate::models::message::MessageRow;
+use crate::models::permission::{PermissionOverwrite, Permissions};
+use crate::models::role::RoleRow;
+use crate::state::AppState;
+
+/// Members processed per DB round trip / gateway push.
+pub const EVERYONE_BATCH_SIZE: i64 = 500;
+
+/// Whether [msg] qualifies for a server-side @everyone fan-out: it mentions
+/// everyone, was posted in an announcement channel, and its author may manage
+/// the space.
+pub async fn is_everyone_announcement(
+    state: &AppState,
+    msg: &MessageRow,
+    channel_type: &str,
+    space_id: &str,
+    author_is_admin: bool,
+) -> bool {
+    if !msg.mention_everyone || channel_type != "announcement" {
+        return false;
+    }
+    resolve_member_permissions_with_admin(&state.db, space_id, &msg.author_id, author_is_admin)
+        .await
+        .map(|perms| perms.has(Permissions::MANAGE_SPACE))
+        .unwrap_or(false)
+}
+
+/// Spawn the batched fan-out for [msg] in the background.
+pub fn spawn_everyone_fanout(state: AppState, space_id: String, msg: MessageRow) {
+    tokio::spawn(async move {
+        if let Err(e) = fan_out_everyone(&state, &space_id, &msg).await {
+            tracing::warn!("@everyone fan-out failed for message {}: {e}", msg.id);
+        }
+    });
+}
+
+/// Bump the mention count of every eligible member of [space_id] for [msg]
+/// and push a `mention.create` event to each batch of recipients.
+///
+/// Skips the author, bots, members who suppressed @everyone for the space,
+/// members who can't see the channel, and users already counted through an
+/// explicit `<@id>` mention on the same message.
+pub async fn fan_out_everyone(
+    state: &AppState,
+    space_id: &str,
+    msg: &MessageRow,
+) -> Result<(), crate::error::AppError> {
+    let explicit: HashSet<String> = serde_json::from_str::<Vec<String>>(&msg.mentions)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    // Only pay for permission resolution when the channel can actually hide
+    // itself from some members. The space's roles and the channel's
+    // overwrites are loaded once; each page then costs one role lookup.
+    let overwrites = db::permission_overwrites::list_overwrites(&state.db, &msg.channel_id).await?;
+    let visibility = if overwrites.is_empty() {
+        None
+    } else {
+        let space = db::spaces::get_space_row(&state.db, space_id).await?;
+        let roles = db::roles::list_roles(&state.db, space_id).await?;
+        Some(ChannelVisibility {
+            owner_id: space.owner_id,
+            roles,
+            overwrites,
+        })
+    };
+
+    let mut after: Option<String> = None;
+    loop {
+        let page = db::members::list_everyone_recipient_ids(
+            &state.db,
+            space_id,
+            after.as_deref(),
+            EVERYONE_BATCH_SIZE,
+        )
+        .await?;
+        let Some(last) = page.last().cloned() else {
+            break;
+        };
+        let page_len = page.len() as i64;
+
+        let mut role_ids = match visibility {
+            Some(_) => db::members::get_role_ids_for_members(&state.db, space_id, &page).await?,
+            None => HashMap::new(),
+        };
+        let mut recipients = Vec::with_capacity(page.len());
+        for uid in page {
+            if uid == msg.author_id || explicit.contains(&uid) {
+                continue;
+            }
+            if let Some(ref visibility) = visibility {
+                let member_role_ids = role_ids.remove(&uid).unwrap_or_default();
+                if !visibility.can_view(&uid, &member_role_ids) {
+                    continue;
+                }
+            }
+            recipients.push(uid);
+        }
+
+        if !recipients.is_empty() {
+            db::read_states::increment_mention_counts(
+                &state.db,
+                &recipients,
+                &msg.channel_id,
+                state.db_is_postgres,
+            )
+            .await?;
+
+            if let Some(ref gtx) = *state.gateway_tx.read().await {
+                let event = serde_json::json!({
+                    "op": 0,
+                    "type": "mention.create",
+                    "data": {
+                        "channel_id": msg.channel_id,
+                        "space_id": space_id,
+                        "message_id": msg.id,
+                        "everyone": true,
+                    }
+                });
+                let _ = gtx.send(GatewayBroadcast {
+                    space_id: None,
+                    target_user_ids: Some(recipients),
+                    event,
+                    intent: "messages".to_string(),
+                    role_mention_user_ids: None,
+                });
+            }
+        }
+
+        if page_len < EVERYONE_BATCH_SIZE {
+            break;
+        }
+        after = Some(last);
+    }
+
+    Ok(())
+}
+
+/// What decides whether a member can see a channel with overwrites, loaded
+/// once per fan-out.
+struct ChannelVisibility {
+    owner_id: String,
+    roles: Vec<RoleRow>,
+    overwrites: Vec<PermissionOverwrite>,
+}
+
+impl ChannelVisibility {
+    /// Same result as `resolve_channel_permissions(..).has(VIEW_CHANNEL)`
+    /// for a member holding `member_role_ids`.
+    fn can_view(&self, user_id: &str, member_role_ids: &[String]) -> bool {
+        if user_id == self.owner_id {
+            return true;
+        }
+        let everyone = self.roles.iter().find(|r| r.position == 0);
+        let mut perms = everyone.map(|r| r.permissions).unwrap_or_default();
+        for role in self
+            .roles
+            .iter()
+            .filter(|r| member_role_ids.contains(&r.id))
+        {
+            perms |= role.permissions;
+        }
+        if perms.is_admin() {
+            return true;
+        }
+        apply_channel_overwrites(
+            perms,
+            &self.overwrites,
+            everyone.map(|r| r.id.as_str()),
+            member_role_ids,
+            user_id,
+        )
+        .has(Permissions::VIEW_CHANNEL)
+    }
+}
@@ -0,0 +1,527 @@
+//! Per-space AutoMod: rules configured under `/spaces/{id}/automod/rules` are
+//! checked against every new message before it is stored.
+//!
+//! Each matching rule runs all of its actions: `flag` posts a report in the
+//! rule's log channel, `timeout` times the author out, and `block` rejects
+//! the message with a 403 `automod_blocked`. Every match is announced to the
+//! space's moderators as an `automod.action` event. Members with
+//! `manage_space` (and instance admins) are exempt.
+//!
+//! Matches are also logged as hits (with a short redacted excerpt, kept for
+//! [HIT_RETENTION_DAYS]) so moderators can see which rules fire most and how
+//! often they are overridden as false positives.
+
+use std::sync::OnceLock;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::db;
+use crate::error::AppError;
+use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::{
+    list_member_ids_with_permission, resolve_member_permissions_with_admin,
+};
+use crate::models::automod::{AutomodAction, AutomodRule, AutomodTrigger};
+use crate::models::permission::Permissions;
+use crate::state::AppState;
+
+/// Most rules a single space may have.
+pub const MAX_RULES_PER_SPACE: usize = 25;
+/// Most keywords in a keyword trigger.
+pub const MAX_KEYWORDS: usize = 1000;
+/// Most patterns in a regex trigger.
+pub const MAX_PATTERNS: usize = 10;
+/// Longest regex pattern, in bytes.
+pub const MAX_PATTERN_LEN: usize = 260;
+/// Longest timeout a rule may hand out (28 days).
+pub const MAX_TIMEOUT_SECONDS: i64 = 28 * 24 * 60 * 60;
+/// Compiled size cap per pattern, so a rule can't make matching expensive.
+const REGEX_SIZE_LIMIT: usize = 256 * 1024;
+/// How long rule hits are kept for statistics.
+pub const HIT_RETENTION_DAYS: i64 = 90;
+/// Characters of context kept on each side of a match in a hit's sample.
+const SAMPLE_CONTEXT_CHARS: usize = 30;
+/// Longest sample stored for a hit.
+const MAX_SAMPLE_CHARS: usize = 200;
+
+fn compile(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+}
+
+/// Check a trigger and its actions before storing them.
+pub fn validate_rule(trigger: &AutomodTrigger, actions: &[AutomodAction]) -> Result<(), AppError> {
+    match trigger {
+        AutomodTrigger::Keyword { keywords } => {
+            if keywords.is_empty() || keywords.len() > MAX_KEYWORDS {
+                return Err(AppError::BadRequest(format!(
+                    "keyword triggers need between 1 and {MAX_KEYWORDS} keywords"
+                )));
+            }
+            if keywords.iter().any(|k| k.trim().is_empty() || k.len() > 60) {
+                return Err(AppError::BadRequest(
+                    "keywords must be between 1 and 60 characters".into(),
+                ));
+            }
+        }
+        AutomodTrigger::Regex { patterns } => {
+            if patterns.is_empty() || patterns.len() > MAX_PATTERNS {
+                return Err(AppError::BadRequest(format!(
+                    "regex triggers need between 1 and {MAX_PATTERNS} patterns"
+                )));
+            }
+            for pattern in patterns {
+                if pattern.len() > MAX_PATTERN_LEN {
+                    return Err(AppError::BadRequest(format!(
+                        "patterns must be at most {MAX_PATTERN_LEN} characters"
+                    )));
+                }
+                compile(pattern)
+                    .map_err(|e| AppError::BadRequest(format!("invalid pattern: {e}")))?;
+            }
+        }
+        AutomodTrigger::MentionSpam { mention_limit } => {
+            if !(1..=50).contains(mention_limit) {
+                return Err(AppError::BadRequest(
+                    "mention_limit must be between 1 and 50".into(),
+                ));
+            }
+        }
+        AutomodTrigger::Link { allowed_domains } => {
+            if allowed_domains.len() > 100 {
+                return Err(AppError::BadRequest("at most 100 allowed domains".into()));
+            }
+        }
+    }
+
+    if actions.is_empty() {
+        return Err(AppError::BadRequest(
+            "a rule needs at least one action".into(),
+        ));
+    }
+    for action in actions {
+        if let AutomodAction::Timeout { duration_seconds } = action {
+            if !(1..=MAX_TIMEOUT_SECONDS).contains(duration_seconds) {
+                return Err(AppError::BadRequest(format!(
+                    "timeout duration must be between 1 and {MAX_TIMEOUT_SECONDS} seconds"
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether [host] is [domain] or one of its subdomains.
+fn host_allowed(host: &str, domain: &str) -> bool {
+    let domain = domain.trim().trim_start_matches('.').to_ascii_lowercase();
+    host == domain || host.ends_with(&format!(".{domain}"))
+}
+
+/// What in [content] matched [trigger], if anything.
+pub fn evaluate(trigger: &AutomodTrigger, content: &str) -> Option<String> {
+    match trigger {
+        AutomodTrigger::Keyword { keywords } => {
+            let lower = content.to_lowercase();
+            keywords
+                .iter()
+                .find(|k| lower.contains(&k.trim().to_lowercase()))
+                .cloned()
+        }
+        AutomodTrigger::Regex { patterns } => patterns.iter().find_map(|p| {
+            compile(p)
+                .ok()
+                .and_then(|re| re.find(content).map(|m| m.as_str().to_string()))
+        }),
+        AutomodTrigger::MentionSpam { mention_limit } => {
+            let parsed = crate::mentions::parse_mentions(content);
+            let count = parsed.usernames.len()
+                + parsed.user_ids.len()
+                + parsed.role_ids.len()
+                + usize::from(parsed.everyone);
+            (count > *mention_limit).then(|| format!("{count} mentions"))
+        }
+        AutomodTrigger::Link { allowed_domains } => content
+            .split_whitespace()
+            .map(|w| w.trim_matches(|c: char| "<>()[]\"'".contains(c)))
+            .find(|w| {
+                let Ok(url) = reqwest::Url::parse(w) else {
+                    return false;
+                };
+                if !matches!(url.scheme(), "http" | "https") {
+                    return false;
+                }
+                let host = url.host_str().unwrap_or("").to_ascii_lowercase();
+                !allowed_domains.iter().any(|d| host_allowed(&host, d))
+            })
+            .map(str::to_string),
+    }
+}
+
+/// A short excerpt of [content] around [matched] for the hit log, with
+/// `@username` mentions redacted. Falls back to [matched] itself when it
+/// isn't literally in the content (e.g. mention counts).
+pub fn sample_excerpt(content: &str, matched: &str) -> String {
+    // ASCII lowercasing keeps byte offsets, so the index is valid in content.
+    let start = content.find(matched).or_else(|| {
+        content
+            .to_ascii_lowercase()
+            .find(&matched.to_ascii_lowercase())
+    });
+    let excerpt = match start {
+        Some(start) => {
+            let end = start + matched.len();
+            let before: Vec<char> = content[..start]
+                .chars()
+                .rev()
+                .take(SAMPLE_CONTEXT_CHARS)
+                .collect();
+            let before: String = before.into_iter().rev().collect();
+            let after: String = content[end..].chars().take(SAMPLE_CONTEXT_CHARS).collect();
+            let lead = if before.len() < start { "…" } else { "" };
+            let trail = if after.len() < content.len() - end {
+                "…"
+            } else {
+                ""
+            };
+            format!("{lead}{before}{}{after}{trail}", &content[start..end])
+        }
+        None => matched.to_string(),
+    };
+
+    static MENTION: OnceLock<Regex> = OnceLock::new();
+    let mention = MENTION.get_or_init(|| {
+        Regex::new(r"(^|[^A-Za-z0-9])@([A-Za-z0-9_]+)").expect("valid mention pattern")
+    });
+    let redacted = mention.replace_all(&excerpt, |caps: &regex::Captures| {
+        match caps[2].to_ascii_lowercase().as_str() {
+            "everyone" | "here" => caps[0].to_string(),
+            _ => format!("{}@user", &caps[1]),
+        }
+    });
+    redacted.chars().take(MAX_SAMPLE_CHARS).collect()
+}
+
+/// Run the space's AutoMod rules against a new message from [auth] in
+/// [channel_id]. Returns `automod_blocked` if any matching rule blocks it.
+pub async fn check_message(
+    state: &AppState,
+    space_id: &str,
+    channel_id: &str,
+    auth: &AuthUser,
+    content: &str,
+) -> Result<(), AppError> {
+    let rules = db::automod::list_enabled_rules(&state.db, space_id).await?;
+    if rules.is_empty() || content.is_empty() {
+        return Ok(());
+    }
+    let exempt =
+        resolve_member_permissions_with_admin(&state.db, space_id, &auth.user_id, auth.is_admin)
+            .await
+            .map(|perms| perms.has(Permissions::MANAGE_SPACE))
+            .unwrap_or(false);
+    if exempt {
+        return Ok(());
+    }
+
+    let mut blocked = false;
+    let mut pruned = false;
+    for rule in &rules {
+        let Some(matched) = evaluate(&rule.trigger, content) else {
+            continue;
+        };
+        if !pruned {
+            let cutoff = (chrono::Utc::now() - chrono::Duration::days(HIT_RETENTION_DAYS))
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string();
+            if let Err(e) = db::automod::prune_hits(&state.db, space_id, &cutoff).await {
+                tracing::warn!("automod: failed to prune hits in {space_id}: {e}");
+            }
+            pruned = true;
+        }
+        let sample = sample_excerpt(content, &matched);
+        let hit_id = match db::automod::record_hit(
+            &state.db,
+            rule,
+            channel_id,
+            &auth.user_id,
+            &sample,
+        )
+        .await
+        {
+            Ok(id) => Some(id),
+            Err(e) => {
+                tracing::warn!("automod: failed to record hit for rule {}: {e}", rule.id);
+                None
+            }
+        };
+        for action in &rule.actions {
+            match action {
+                AutomodAction::Block => blocked = true,
+                AutomodAction::Flag {
+                    channel_id: log_channel_id,
+                } => {
+                    flag_message(
+                        state,
+                        rule,
+                        log_channel_id,
+                        channel_id,
+                        auth,
+                        content,
+                        &matched,
+                    )
+                    .await;
+                }
+                AutomodAction::Timeout { duration_seconds } => {
+                    timeout_author(state, space_id, &auth.user_id, *duration_seconds).await;
+                }
+            }
+        }
+        broadcast_action(
+            state,
+            rule,
+            hit_id.as_deref(),
+            channel_id,
+            &auth.user_id,
+            content,
+            &matched,
+        )
+        .await;
+    }
+
+    if blocked {
+        return Err(AppError::ForbiddenCode(
+            "automod_blocked",
+            "your message was blocked by this space's AutoMod rules".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Post an `automod_flag` system message in the rule's log channel.
+async fn flag_message(
+    state: &AppState,
+    rule: &AutomodRule,
+    log_channel_id: &str,
+    channel_id: &str,
+    auth: &AuthUser,
+    content: &str,
+    matched: &str,
+) {
+    // The log channel must still belong to this space.
+    match db::channels::get_channel_row(&state.db, log_channel_id).await {
+        Ok(c) if c.space_id.as_deref() == Some(rule.space_id.as_str()) => {}
+        _ => {
+            tracing::warn!(
+                "automod: rule {} flags to missing channel {log_channel_id}",
+                rule.id
+            );
+            return;
+        }
+    }
+    let quoted: String = content
+        .chars()
+        .take(1000)
+        .collect::<String>()
+        .lines()
+        .map(|l| format!("> {l}\n"))
+        .collect();
+    let locale = db::spaces::get_space_row(&state.db, &rule.space_id)
+        .await
+        .map(|s| s.preferred_locale)
+        .unwrap_or_else(|_| crate::locale::DEFAULT_LOCALE.to_string());
+    let report = format!(
+        "{}\n{quoted}",
+        crate::locale::automod_flagged(&locale, &rule.name, &auth.user_id, channel_id, matched)
+    );
+    let msg = match db::messages::create_system_message(
+        &state.db,
+        log_channel_id,
+        &auth.user_id,
+        Some(&rule.space_id),
+        &report,
+        "automod_flag",
+        None,
+    )
+    .await
+    {
+        Ok(m) => m,
+        Err(e) => {
+            tracing::warn!("automod: failed to post flag for rule {}: {e}", rule.id);
+            return;
+        }
+    };
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "message.create",
+            "data": crate::routes::messages::message_row_to_json(&msg)
+        });
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: Some(rule.space_id.clone()),
+            target_user_ids: None,
+            event,
+            intent: "messages".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
+
+async fn timeout_author(state: &AppState, space_id: &str, user_id: &str, duration_seconds: i64) {
+    let until = (chrono::Utc::now() + chrono::Duration::seconds(duration_seconds))
+        .format("%Y-%m-%dT%H:%M:%S+00:00")
+        .to_string();
+    let input = crate::models::member::UpdateMember {
+        nickname: None,
+        avatar: None,
+        banner: None,
+        bio: None,
+        pronouns: None,
+        timezone: None,
+        roles: None,
+        mute: None,
+        deaf: None,
+        communication_disabled_until: Some(Some(until)),
+    };
+    let row = match db::members::update_member(&state.db, space_id, user_id, &input).await {
+        Ok(row) => row,
+        Err(e) => {
+            tracing::warn!("automod: failed to time out {user_id} in {space_id}: {e}");
+            return;
+        }
+    };
+    let role_ids = db::members::get_member_role_ids(&state.db, space_id, user_id)
+        .await
+        .unwrap_or_default();
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "member.update",
+            "data": crate::routes::members::member_row_to_json(&row, &role_ids)
+        });
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: Some(space_id.to_string()),
+            target_user_ids: None,
+            event,
+            intent: "members".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
+
+/// Send `automod.action` to the space's moderators (`manage_messages`).
+async fn broadcast_action(
+    state: &AppState,
+    rule: &AutomodRule,
+    hit_id: Option<&str>,
+    channel_id: &str,
+    user_id: &str,
+    content: &str,
+    matched: &str,
+) {
+    let targets = list_member_ids_with_permission(&state.db, &rule.space_id, "manage_messages")
+        .await
+        .unwrap_or_default();
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "automod.action",
+            "data": {
+                "space_id": rule.space_id,
+                "hit_id": hit_id,
+                "channel_id": channel_id,
+                "user_id": user_id,
+                "rule_id": rule.id,
+                "rule_name": rule.name,
+                "trigger_type": serde_json::to_value(&rule.trigger).unwrap_or_default()["type"],
+                "actions": rule.actions,
+                "content": content,
+                "matched_content": matched,
+            }
+        });
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: None,
+            target_user_ids: Some(targets),
+            event,
+            intent: "moderation".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyword_and_regex_triggers() {
+        let keyword = AutomodTrigger::Keyword {
+            keywords: vec!["Free Nitro".into()],
+        };
+        assert_eq!(
+            evaluate(&keyword, "get FREE nitro here").as_deref(),
+            Some("Free Nitro")
+        );
+        assert!(evaluate(&keyword, "hello").is_none());
+
+        let regex = AutomodTrigger::Regex {
+            patterns: vec![r"\bb[a@]d\b".into()],
+        };
+        assert_eq!(evaluate(&regex, "so B@D").as_deref(), Some("B@D"));
+        assert!(evaluate(&regex, "badge").is_none());
+    }
+
+    #[test]
+    fn mention_and_link_triggers() {
+        let mentions = AutomodTrigger::MentionSpam { mention_limit: 2 };
+        assert!(evaluate(&mentions, "@a @b").is_none());
+        assert!(evaluate(&mentions, "@a @b @everyone").is_some());
+
+        let links = AutomodTrigger::Link {
+            allowed_domains: vec!["example.com".into()],
+        };
+        assert!(evaluate(&links, "see https://docs.example.com/x").is_none());
+        assert_eq!(
+            evaluate(&links, "see <https://evil.test/x>").as_deref(),
+            Some("https://evil.test/x")
+        );
+        assert!(evaluate(&links, "not a link: example.org").is_none());
+    }
+
+    #[test]
+    fn samples_are_trimmed_and_redacted() {
+        let content = format!(
+            "{}hey @alice, buy FREE nitro now @everyone {}",
+            "a".repeat(50),
+            "b".repeat(50)
+        );
+        let sample = sample_excerpt(&content, "free nitro");
+        assert!(sample.starts_with('…') && sample.ends_with('…'));
+        assert!(sample.contains("FREE nitro"));
+        assert!(sample.contains("@user") && !sample.contains("alice"));
+        assert!(sample.contains("@everyone"));
+        assert_eq!(sample_excerpt("@a @b @c", "3 mentions"), "3 mentions");
+    }
+
+    #[test]
+    fn rejects_bad_rules() {
+        let block = [AutomodAction::Block];
+        assert!(validate_rule(
+            &AutomodTrigger::Regex {
+                patterns: vec!["(".into()]
+            },
+            &block
+        )
+        .is_err());
+        assert!(validate_rule(&AutomodTrigger::MentionSpam { mention_limit: 5 }, &[]).is_err());
+        assert!(validate_rule(
+            &AutomodTrigger::MentionSpam { mention_limit: 5 },
+            &[AutomodAction::Timeout {
+                duration_seconds: 0
+            }]
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,45 @@
+//! `accord-doctor` — look for inconsistent records in an Accord database.
+//!
+//! Usage:
+//!   DATABASE_URL=sqlite:data/accord.db?mode=rwc cargo run --bin accord-doctor [-- --repair]
+//!
+//! Reads the same environment as the server (`DATABASE_URL`,
+//! `ACCORD_STORAGE_PATH`, `S3_*`) and prints a JSON report. Without
+//! `--repair` nothing is changed. Voice states live in the running server's
+//! memory, so that check is only available from `GET /admin/doctor`.
+
+use std::error::Error;
+
+use accordserver::config::Config;
+use accordserver::db;
+use accordserver::storage::{LocalStorage, S3Storage, Storage};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let repair = std::env::args().skip(1).any(|a| a == "--repair");
+    let config = Config::from_env();
+
+    eprintln!("accord-doctor: connecting to {}", config.database_url);
+    let pool = db::create_pool(&config.database_url).await?;
+    let storage: Box<dyn Storage> = match config.s3 {
+        Some(s3) => Box::new(S3Storage::new(s3)),
+        None => Box::new(LocalStorage::new(config.storage_path.clone())),
+    };
+
+    let report = accordserver::doctor::run(&pool, storage.as_ref(), None, repair).await?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if repair {
+        eprintln!(
+            "accord-doctor: found {} inconsistent records, repaired {}",
+            report.found, report.repaired
+        );
+    } else if report.found > 0 {
+        eprintln!(
+            "accord-doctor: found {} inconsistent records; run with --repair to delete them",
+            report.found
+        );
+    } else {
+        eprintln!("accord-doctor: no inconsistencies found");
+    }
+    Ok(())
+}
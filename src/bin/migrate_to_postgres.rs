@@ -16,7 +16,9 @@
 
 use std::error::Error;
 
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use accordserver::db::DbPool;
 
 use accordserver::db;
 
@@ -32,6 +34,8 @@ const TABLES: &[TableDef] = &[
             "avatar",
             "banner",
             "bio",
+            "pronouns",
+            "timezone",
             "bot",
             "system",
             "flags",
@@ -89,6 +93,7 @@ const TABLES: &[TableDef] = &[
             "last_message_id",
             "archived",
             "auto_archive_after",
+            "icon",
             "created_at",
         ],
     },
@@ -102,7 +107,9 @@ const TABLES: &[TableDef] = &[
             "hoist",
             "position",
             "permissions",
+            "permission_bits",
             "mentionable",
+            "member_count",
             "created_at",
         ],
     },
@@ -147,6 +154,7 @@ const TABLES: &[TableDef] = &[
             "content_type",
             "size",
             "url",
+            "proxy_url",
             "width",
             "height",
             "created_at",
@@ -216,7 +224,15 @@ const TABLES: &[TableDef] = &[
     },
     TableDef {
         name: "user_tokens",
-        columns: &["token_hash", "user_id", "created_at", "expires_at"],
+        columns: &[
+            "token_hash",
+            "user_id",
+            "created_at",
+            "expires_at",
+            "device",
+            "ip",
+            "last_used_at",
+        ],
     },
     TableDef {
         name: "dm_participants",
@@ -228,7 +244,15 @@ const TABLES: &[TableDef] = &[
     },
     TableDef {
         name: "permission_overwrites",
-        columns: &["id", "channel_id", "type", "allow", "deny"],
+        columns: &[
+            "id",
+            "channel_id",
+            "type",
+            "allow",
+            "deny",
+            "allow_bits",
+            "deny_bits",
+        ],
     },
     TableDef {
         name: "soundboard_sounds",
@@ -343,8 +367,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
 }
 
 async fn migrate_table(
-    sqlite: &AnyPool,
-    pg: &AnyPool,
+    sqlite: &DbPool,
+    pg: &DbPool,
     table: &TableDef,
 ) -> Result<usize, Box<dyn Error>> {
     let col_list = table.columns.join(", ");
@@ -35,7 +35,7 @@ fn hash_password(password: &str) -> Result<String, Box<dyn Error>> {
 
 /// Generate a bearer token and insert it into user_tokens.
 async fn create_bearer_token(
-    pool: &sqlx::AnyPool,
+    pool: &accordserver::db::DbPool,
     user_id: &str,
 ) -> Result<String, Box<dyn Error>> {
     use rand::Rng;
@@ -160,6 +160,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
             description: Some("The official Accord community space. Welcome!".to_string()),
             public: Some(true),
             allow_guest_access: None,
+            preferred_locale: None,
+            template: None,
         },
     )
     .await?;
@@ -526,7 +528,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     // ── Helper to create a message and return its ID ───────────────
     async fn msg(
-        pool: &sqlx::AnyPool,
+        pool: &accordserver::db::DbPool,
         channel_id: &str,
         author_id: &str,
         space_id: &str,
@@ -546,6 +548,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 reply_to: reply_to.map(|s| s.to_string()),
                 thread_id: thread_id.map(|s| s.to_string()),
                 title: None,
+                poll: None,
+                sticker_ids: None,
             },
         )
         .await?;
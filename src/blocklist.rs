@@ -0,0 +1,142 @@
+//! Instance-wide trust & safety blocklists.
+//!
+//! Server admins maintain three lists (see `migrations/038_instance_blocklists.sql`):
+//! banned email domains, banned file hashes, and globally banned users. The
+//! checks here are called from registration, upload, and space-join paths and
+//! fail with a [AppError::ForbiddenCode] naming the list that matched.
+
+use crate::db::DbPool;
+use sha2::{Digest, Sha256};
+
+use crate::db;
+use crate::error::AppError;
+
+pub const KIND_EMAIL_DOMAIN: &str = "email_domain";
+pub const KIND_FILE_HASH: &str = "file_hash";
+pub const KIND_USER: &str = "user";
+
+pub const KINDS: &[&str] = &[KIND_EMAIL_DOMAIN, KIND_FILE_HASH, KIND_USER];
+
+/// Lowercase hex SHA-256, the form file hashes are listed in.
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Normalize a blocklist value for [kind]: domains and hashes are
+/// case-insensitive and domains drop a leading `@` or `.`.
+pub fn normalize_value(kind: &str, value: &str) -> Result<String, AppError> {
+    let value = value.trim();
+    let normalized = match kind {
+        KIND_EMAIL_DOMAIN => value.trim_start_matches(['@', '.']).to_ascii_lowercase(),
+        KIND_FILE_HASH => {
+            let hash = value.to_ascii_lowercase();
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(AppError::BadRequest(
+                    "file hashes must be hex-encoded SHA-256".into(),
+                ));
+            }
+            hash
+        }
+        _ => value.to_string(),
+    };
+    if normalized.is_empty() {
+        return Err(AppError::BadRequest("value must not be empty".into()));
+    }
+    Ok(normalized)
+}
+
+/// The domain of [email] and each of its parent domains, so blocking
+/// `example.com` also covers `mail.example.com`.
+pub(crate) fn email_domain_candidates(email: &str) -> Vec<String> {
+    let Some((_, domain)) = email.rsplit_once('@') else {
+        return Vec::new();
+    };
+    let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+    let labels: Vec<&str> = domain.split('.').collect();
+    (0..labels.len())
+        .map(|i| labels[i..].join("."))
+        .filter(|d| !d.is_empty())
+        .collect()
+}
+
+/// Refuse registration with an email on a blocked domain.
+pub async fn check_email(pool: &DbPool, email: &str) -> Result<(), AppError> {
+    let candidates = email_domain_candidates(email);
+    if db::blocklist::contains_any(pool, KIND_EMAIL_DOMAIN, &candidates).await? {
+        return Err(AppError::ForbiddenCode(
+            "email_domain_blocked",
+            "registrations from this email domain are not allowed".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Refuse globally banned users.
+pub async fn check_user(pool: &DbPool, user_id: &str) -> Result<(), AppError> {
+    if db::blocklist::contains_any(pool, KIND_USER, &[user_id.to_string()]).await? {
+        return Err(AppError::ForbiddenCode(
+            "user_globally_banned",
+            "this account has been banned from this server".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Refuse an upload of [bytes] by [user_id] if the user is globally banned
+/// or the file's hash is blocked.
+pub async fn check_upload(pool: &DbPool, user_id: &str, bytes: &[u8]) -> Result<(), AppError> {
+    check_user(pool, user_id).await?;
+    if db::blocklist::contains_any(pool, KIND_FILE_HASH, &[hash_bytes(bytes)]).await? {
+        return Err(AppError::ForbiddenCode(
+            "file_blocked",
+            "this file is not allowed on this server".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// [check_upload] for a `data:<mime>;base64,<data>` URI. Anything that isn't
+/// a base64 data URI is left for the storage layer to reject.
+pub async fn check_data_uri_upload(
+    pool: &DbPool,
+    user_id: &str,
+    data: &str,
+) -> Result<(), AppError> {
+    let bytes = data
+        .split_once(";base64,")
+        .and_then(|(_, b64)| crate::storage::base64_decode(b64).ok());
+    match bytes {
+        Some(bytes) => check_upload(pool, user_id, &bytes).await,
+        None => check_user(pool, user_id).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn email_domain_candidates_include_parents() {
+        assert_eq!(
+            email_domain_candidates("a@Mail.Example.COM"),
+            vec!["mail.example.com", "example.com", "com"]
+        );
+        assert!(email_domain_candidates("no-at-sign").is_empty());
+    }
+
+    #[test]
+    fn normalize_strips_domain_prefix_and_checks_hashes() {
+        assert_eq!(
+            normalize_value(KIND_EMAIL_DOMAIN, " @Spam.Example ").unwrap(),
+            "spam.example"
+        );
+        assert!(normalize_value(KIND_FILE_HASH, "abc").is_err());
+        assert_eq!(
+            normalize_value(KIND_FILE_HASH, &"AB".repeat(32)).unwrap(),
+            "ab".repeat(32)
+        );
+    }
+}
@@ -23,12 +23,130 @@ pub struct FederationConfig {
     pub enabled: bool,
 }
 
+/// S3-compatible object storage for uploads. Present only when `S3_BUCKET` is
+/// set; otherwise files are written under `storage_path`.
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub bucket: String,
+    pub region: String,
+    /// Base URL of the S3 API, e.g. `https://s3.us-east-1.amazonaws.com` or a
+    /// MinIO address.
+    pub endpoint: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Address objects as `<endpoint>/<bucket>/<key>` instead of
+    /// `<bucket>.<endpoint host>/<key>`. MinIO and most self-hosted stores
+    /// need this.
+    pub path_style: bool,
+    /// Lifetime of the presigned URLs `/cdn` redirects to, in seconds.
+    pub presign_expiry_secs: u64,
+}
+
+/// Redis pub/sub for running several main servers behind one load balancer.
+/// Present only when `REDIS_URL` is set; see `gateway::cluster`.
+#[derive(Debug, Clone)]
+pub struct RedisConfig {
+    pub url: String,
+    /// Prepended to every pub/sub channel so several deployments can share
+    /// one Redis.
+    pub channel_prefix: String,
+}
+
+/// How to secure the SMTP connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpTls {
+    /// Implicit TLS, usually port 465.
+    Tls,
+    /// Upgrade a plain connection with STARTTLS, usually port 587.
+    StartTls,
+    /// No encryption; only for a relay on localhost.
+    None,
+}
+
+/// Outgoing mail for password resets and email verification. Present only
+/// when `SMTP_HOST` is set; without it those endpoints are unavailable.
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// `From` address, e.g. `Accord <noreply@example.com>`.
+    pub from: String,
+    pub tls: SmtpTls,
+}
+
 #[derive(Debug, Clone)]
 pub struct LiveKitConfig {
     pub internal_url: String,
     pub external_url: String,
     pub api_key: String,
     pub api_secret: String,
+    /// Base URL LiveKit can fetch this server's `/cdn` files from, for
+    /// soundboard playback when uploads are on local disk.
+    pub media_url: Option<String>,
+}
+
+/// Web Push (VAPID) identity. Present only when `VAPID_PRIVATE_KEY` is set;
+/// without it push subscriptions can't be registered.
+#[derive(Debug, Clone)]
+pub struct PushConfig {
+    /// The application server's P-256 private key: the 32-byte scalar,
+    /// base64url encoded, as `web-push generate-vapid-keys` prints it.
+    pub vapid_private_key: String,
+    /// Contact for push services, a `mailto:` or `https:` URL. Some
+    /// services (Apple's among them) reject requests without one.
+    pub subject: Option<String>,
+}
+
+/// Passkey (WebAuthn) relying party. Present only when `WEBAUTHN_RP_ID` is
+/// set; passkeys are bound to this domain and can't move to another.
+#[derive(Debug, Clone)]
+pub struct WebAuthnConfig {
+    /// Registrable domain passkeys are scoped to, e.g. `chat.example.com`.
+    pub rp_id: String,
+    /// Name authenticators show next to the passkey.
+    pub rp_name: String,
+    /// Origins ceremonies may run on, e.g. `https://chat.example.com`.
+    pub origins: Vec<String>,
+}
+
+/// Browser CORS policy. App routes (`/api`, `/ws`, pages) use
+/// `allowed_origins`; `/cdn` has its own, more permissive list so uploaded
+/// media can be embedded anywhere.
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    /// Origins allowed on app routes. Empty or `*` allows any origin (unless
+    /// credentials are enabled, in which case `*` is ignored and cross-origin
+    /// requests are refused until origins are listed). The
+    /// `cors_allowed_origins` server setting overrides this at runtime.
+    pub allowed_origins: Vec<String>,
+    /// Origins allowed on `/cdn`. Empty or `*` allows any origin.
+    pub cdn_allowed_origins: Vec<String>,
+    /// Send `Access-Control-Allow-Credentials: true` on app routes so browser
+    /// clients can use cookies/credentialed fetches.
+    pub allow_credentials: bool,
+    /// How long browsers may cache preflight responses, in seconds.
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            cdn_allowed_origins: vec!["*".to_string()],
+            allow_credentials: false,
+            max_age_secs: 600,
+        }
+    }
+}
+
+/// Parse a comma-separated origin list, dropping blanks and trailing slashes.
+pub fn parse_origin_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|o| o.trim().trim_end_matches('/').to_string())
+        .filter(|o| !o.is_empty())
+        .collect()
 }
 
 /// Command-line flags. Used by the desktop installer's tray app to launch the
@@ -73,11 +191,19 @@ pub struct Config {
     pub master_server: Option<MasterServerConfig>,
     pub federation: Option<FederationConfig>,
     pub storage_path: std::path::PathBuf,
+    pub s3: Option<S3Config>,
+    pub redis: Option<RedisConfig>,
+    pub smtp: Option<SmtpConfig>,
+    pub webauthn: Option<WebAuthnConfig>,
+    pub push: Option<PushConfig>,
+    /// Refuse to let users join public spaces until they verify their email.
+    pub require_verified_email: bool,
     /// AES-256-GCM key for encrypting TOTP secrets at rest.
     /// Derived from TOTP_ENCRYPTION_KEY env var via SHA-256.
     pub totp_key: Option<[u8; 32]>,
     /// Optional API key for MCP endpoint authentication.
     pub mcp_api_key: Option<String>,
+    pub cors: CorsConfig,
 }
 
 /// Resolves the master server ID: env var > persisted file > generate and save.
@@ -138,6 +264,7 @@ impl Config {
                 external_url,
                 api_key,
                 api_secret,
+                media_url: std::env::var("LIVEKIT_MEDIA_URL").ok(),
             }
         });
 
@@ -148,6 +275,97 @@ impl Config {
                 None => std::path::PathBuf::from("./data/cdn"),
             });
 
+        let s3 = std::env::var("S3_BUCKET")
+            .ok()
+            .filter(|b| !b.is_empty())
+            .map(|bucket| {
+                let region = std::env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+                let endpoint = std::env::var("S3_ENDPOINT").ok().filter(|e| !e.is_empty());
+                S3Config {
+                    bucket,
+                    path_style: std::env::var("S3_PATH_STYLE")
+                        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                        .unwrap_or(endpoint.is_some()),
+                    endpoint: endpoint
+                        .map(|e| e.trim_end_matches('/').to_string())
+                        .unwrap_or_else(|| format!("https://s3.{region}.amazonaws.com")),
+                    region,
+                    access_key_id: std::env::var("S3_ACCESS_KEY_ID").unwrap_or_default(),
+                    secret_access_key: std::env::var("S3_SECRET_ACCESS_KEY").unwrap_or_default(),
+                    presign_expiry_secs: std::env::var("S3_PRESIGN_EXPIRY")
+                        .ok()
+                        .and_then(|v| v.parse::<u64>().ok())
+                        // SigV4 presigned URLs may live at most 7 days.
+                        .map(|v| v.clamp(1, 604_800))
+                        .unwrap_or(3600),
+                }
+            });
+
+        let redis = std::env::var("REDIS_URL")
+            .ok()
+            .filter(|u| !u.is_empty())
+            .map(|url| RedisConfig {
+                url,
+                channel_prefix: std::env::var("REDIS_CHANNEL_PREFIX")
+                    .ok()
+                    .filter(|p| !p.is_empty())
+                    .unwrap_or_else(|| "accord".to_string()),
+            });
+
+        let smtp = std::env::var("SMTP_HOST")
+            .ok()
+            .filter(|h| !h.is_empty())
+            .map(|host| {
+                let tls = match std::env::var("SMTP_TLS")
+                    .unwrap_or_default()
+                    .to_ascii_lowercase()
+                    .as_str()
+                {
+                    "tls" => SmtpTls::Tls,
+                    "none" => SmtpTls::None,
+                    _ => SmtpTls::StartTls,
+                };
+                SmtpConfig {
+                    port: std::env::var("SMTP_PORT")
+                        .ok()
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(match tls {
+                            SmtpTls::Tls => 465,
+                            SmtpTls::StartTls => 587,
+                            SmtpTls::None => 25,
+                        }),
+                    username: std::env::var("SMTP_USERNAME")
+                        .ok()
+                        .filter(|u| !u.is_empty()),
+                    password: std::env::var("SMTP_PASSWORD").ok(),
+                    from: std::env::var("SMTP_FROM")
+                        .unwrap_or_else(|_| format!("Accord <noreply@{host}>")),
+                    host,
+                    tls,
+                }
+            });
+
+        let webauthn = std::env::var("WEBAUTHN_RP_ID")
+            .ok()
+            .filter(|id| !id.is_empty())
+            .map(|rp_id| WebAuthnConfig {
+                rp_name: std::env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| "Accord".to_string()),
+                origins: std::env::var("WEBAUTHN_ORIGINS")
+                    .map(|v| parse_origin_list(&v))
+                    .unwrap_or_else(|_| vec![format!("https://{rp_id}")]),
+                rp_id,
+            });
+
+        let push = std::env::var("VAPID_PRIVATE_KEY")
+            .ok()
+            .filter(|k| !k.is_empty())
+            .map(|vapid_private_key| PushConfig {
+                vapid_private_key,
+                subject: std::env::var("VAPID_SUBJECT")
+                    .ok()
+                    .filter(|s| !s.is_empty()),
+            });
+
         let database_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| match &cli.data_dir {
             Some(dir) => format!("sqlite:{}?mode=rwc", dir.join("accord.db").display()),
             None => "sqlite:data/accord.db?mode=rwc".to_string(),
@@ -201,6 +419,32 @@ impl Config {
 
         let mcp_api_key = std::env::var("MCP_API_KEY").ok().filter(|k| !k.is_empty());
 
+        let cors_defaults = CorsConfig::default();
+        let mut cors = CorsConfig {
+            allowed_origins: std::env::var("CORS_ALLOWED_ORIGINS")
+                .map(|v| parse_origin_list(&v))
+                .unwrap_or(cors_defaults.allowed_origins),
+            cdn_allowed_origins: std::env::var("CORS_CDN_ALLOWED_ORIGINS")
+                .map(|v| parse_origin_list(&v))
+                .unwrap_or(cors_defaults.cdn_allowed_origins),
+            allow_credentials: std::env::var("CORS_ALLOW_CREDENTIALS")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(cors_defaults.allow_credentials),
+            max_age_secs: std::env::var("CORS_MAX_AGE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(cors_defaults.max_age_secs),
+        };
+
+        // A credentialed wildcard would let any site act as the user.
+        if cors.allow_credentials && cors.allowed_origins.iter().any(|o| o == "*") {
+            tracing::warn!("CORS_ALLOWED_ORIGINS contains * while CORS_ALLOW_CREDENTIALS is set — ignoring *; list origins explicitly.");
+            cors.allowed_origins.retain(|o| o != "*");
+        }
+        if cors.allow_credentials && cors.allowed_origins.is_empty() {
+            tracing::warn!("CORS_ALLOW_CREDENTIALS is set without CORS_ALLOWED_ORIGINS — cross-origin browser requests will be refused until origins are listed.");
+        }
+
         let port = cli
             .port
             .or_else(|| std::env::var("PORT").ok().and_then(|p| p.parse().ok()))
@@ -223,8 +467,17 @@ impl Config {
             master_server,
             federation,
             storage_path,
+            s3,
+            redis,
+            smtp,
+            webauthn,
+            push,
+            require_verified_email: std::env::var("REQUIRE_VERIFIED_EMAIL")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
             totp_key,
             mcp_api_key,
+            cors,
         }
     }
 }
@@ -245,6 +498,7 @@ mod tests {
         std::env::remove_var("LIVEKIT_EXTERNAL_URL");
         std::env::remove_var("LIVEKIT_API_KEY");
         std::env::remove_var("LIVEKIT_API_SECRET");
+        std::env::remove_var("LIVEKIT_MEDIA_URL");
         std::env::remove_var("MASTER_SERVER_URL");
         std::env::remove_var("MASTER_SERVER_ID");
         std::env::remove_var("MASTER_SERVER_NAME");
@@ -254,6 +508,28 @@ mod tests {
         std::env::remove_var("FEDERATION_DOMAIN");
         std::env::remove_var("FEDERATION_PUBLIC_URL");
         std::env::remove_var("FEDERATION_ENABLED");
+        std::env::remove_var("CORS_ALLOWED_ORIGINS");
+        std::env::remove_var("CORS_CDN_ALLOWED_ORIGINS");
+        std::env::remove_var("CORS_ALLOW_CREDENTIALS");
+        std::env::remove_var("CORS_MAX_AGE");
+        std::env::remove_var("S3_BUCKET");
+        std::env::remove_var("S3_REGION");
+        std::env::remove_var("S3_ENDPOINT");
+        std::env::remove_var("S3_PATH_STYLE");
+        std::env::remove_var("REDIS_URL");
+        std::env::remove_var("REDIS_CHANNEL_PREFIX");
+        std::env::remove_var("SMTP_HOST");
+        std::env::remove_var("SMTP_PORT");
+        std::env::remove_var("SMTP_USERNAME");
+        std::env::remove_var("SMTP_PASSWORD");
+        std::env::remove_var("SMTP_FROM");
+        std::env::remove_var("SMTP_TLS");
+        std::env::remove_var("REQUIRE_VERIFIED_EMAIL");
+        std::env::remove_var("WEBAUTHN_RP_ID");
+        std::env::remove_var("WEBAUTHN_RP_NAME");
+        std::env::remove_var("WEBAUTHN_ORIGINS");
+        std::env::remove_var("VAPID_PRIVATE_KEY");
+        std::env::remove_var("VAPID_SUBJECT");
     }
 
     #[test]
@@ -282,6 +558,7 @@ mod tests {
         assert_eq!(lk.external_url, "wss://livekit.example.com");
         assert_eq!(lk.api_key, "my-api-key");
         assert_eq!(lk.api_secret, "my-api-secret");
+        assert_eq!(lk.media_url, None);
     }
 
     #[test]
@@ -336,4 +613,157 @@ mod tests {
         assert_eq!(config.port, 54321);
         assert_eq!(config.bind, "127.0.0.1");
     }
+
+    #[test]
+    #[serial]
+    fn test_s3_config() {
+        clear_env();
+        assert!(Config::from_env().s3.is_none());
+
+        std::env::set_var("S3_BUCKET", "accord");
+        std::env::set_var("S3_REGION", "eu-west-1");
+        let s3 = Config::from_env().s3.unwrap();
+        assert_eq!(s3.endpoint, "https://s3.eu-west-1.amazonaws.com");
+        assert!(!s3.path_style);
+
+        std::env::set_var("S3_ENDPOINT", "http://minio:9000/");
+        let s3 = Config::from_env().s3.unwrap();
+        assert_eq!(s3.endpoint, "http://minio:9000");
+        assert!(s3.path_style);
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn test_redis_config() {
+        clear_env();
+        assert!(Config::from_env().redis.is_none());
+
+        std::env::set_var("REDIS_URL", "redis://cache:6379");
+        let redis = Config::from_env().redis.unwrap();
+        assert_eq!(redis.url, "redis://cache:6379");
+        assert_eq!(redis.channel_prefix, "accord");
+
+        std::env::set_var("REDIS_CHANNEL_PREFIX", "staging");
+        assert_eq!(Config::from_env().redis.unwrap().channel_prefix, "staging");
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn test_smtp_config() {
+        clear_env();
+        let config = Config::from_env();
+        assert!(config.smtp.is_none());
+        assert!(!config.require_verified_email);
+
+        std::env::set_var("SMTP_HOST", "mail.example.com");
+        std::env::set_var("SMTP_USERNAME", "accord");
+        let smtp = Config::from_env().smtp.unwrap();
+        assert_eq!(smtp.tls, SmtpTls::StartTls);
+        assert_eq!(smtp.port, 587);
+        assert_eq!(smtp.username.as_deref(), Some("accord"));
+        assert_eq!(smtp.from, "Accord <noreply@mail.example.com>");
+
+        std::env::set_var("SMTP_TLS", "tls");
+        assert_eq!(Config::from_env().smtp.unwrap().port, 465);
+        std::env::set_var("REQUIRE_VERIFIED_EMAIL", "true");
+        assert!(Config::from_env().require_verified_email);
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn test_webauthn_config() {
+        clear_env();
+        assert!(Config::from_env().webauthn.is_none());
+
+        std::env::set_var("WEBAUTHN_RP_ID", "chat.example.com");
+        let webauthn = Config::from_env().webauthn.unwrap();
+        assert_eq!(webauthn.rp_name, "Accord");
+        assert_eq!(webauthn.origins, vec!["https://chat.example.com"]);
+
+        std::env::set_var(
+            "WEBAUTHN_ORIGINS",
+            "https://chat.example.com/, http://localhost:5173",
+        );
+        assert_eq!(
+            Config::from_env().webauthn.unwrap().origins,
+            vec!["https://chat.example.com", "http://localhost:5173"]
+        );
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn test_push_config() {
+        clear_env();
+        assert!(Config::from_env().push.is_none());
+
+        std::env::set_var("VAPID_PRIVATE_KEY", "c2VjcmV0");
+        let push = Config::from_env().push.unwrap();
+        assert_eq!(push.vapid_private_key, "c2VjcmV0");
+        assert!(push.subject.is_none());
+
+        std::env::set_var("VAPID_SUBJECT", "mailto:admin@example.com");
+        assert_eq!(
+            Config::from_env().push.unwrap().subject.as_deref(),
+            Some("mailto:admin@example.com")
+        );
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn test_cors_defaults() {
+        clear_env();
+        let config = Config::from_env();
+        assert!(config.cors.allowed_origins.is_empty());
+        assert_eq!(config.cors.cdn_allowed_origins, vec!["*".to_string()]);
+        assert!(!config.cors.allow_credentials);
+    }
+
+    #[test]
+    #[serial]
+    fn test_cors_from_env() {
+        clear_env();
+        std::env::set_var(
+            "CORS_ALLOWED_ORIGINS",
+            "https://app.example.com/, https://admin.example.com",
+        );
+        std::env::set_var("CORS_CDN_ALLOWED_ORIGINS", "https://app.example.com");
+        std::env::set_var("CORS_ALLOW_CREDENTIALS", "true");
+        std::env::set_var("CORS_MAX_AGE", "60");
+
+        let config = Config::from_env();
+        assert_eq!(
+            config.cors.allowed_origins,
+            vec!["https://app.example.com", "https://admin.example.com"]
+        );
+        assert_eq!(
+            config.cors.cdn_allowed_origins,
+            vec!["https://app.example.com"]
+        );
+        assert!(config.cors.allow_credentials);
+        assert_eq!(config.cors.max_age_secs, 60);
+        clear_env();
+    }
+
+    #[test]
+    #[serial]
+    fn test_cors_credentials_ignore_wildcard() {
+        clear_env();
+        std::env::set_var("CORS_ALLOWED_ORIGINS", "*, https://app.example.com");
+        std::env::set_var("CORS_ALLOW_CREDENTIALS", "true");
+        let config = Config::from_env();
+        assert_eq!(config.cors.allowed_origins, vec!["https://app.example.com"]);
+
+        std::env::remove_var("CORS_ALLOW_CREDENTIALS");
+        let config = Config::from_env();
+        assert_eq!(
+            config.cors.allowed_origins,
+            vec!["*", "https://app.example.com"]
+        );
+        clear_env();
+    }
 }
@@ -0,0 +1,391 @@
+//! Content-safety scanning: an optional pipeline every new message goes
+//! through once it's been sent, for instances that have to find and pull
+//! illegal or abusive material.
+//!
+//! Instance admins turn providers on with `content_safety_providers` in
+//! `/admin/settings`. Each one implements [ScanProvider]: `hash_list` matches
+//! attachments' SHA-256 hashes against the list kept under
+//! `/admin/content-safety/hashes` (an imported set of known illegal imagery,
+//! say), and `http` hands the message to an external classifier at
+//! `content_safety_endpoint`. The first match is recorded as a flag. Under
+//! the `quarantine` action the message is also hidden
+//! ([FLAG_QUARANTINED]) until an admin releases or removes it; under `alert`
+//! it stays up. Either way instance admins get `admin.content_safety_flag`
+//! and the space's moderators get `content_safety.flag`.
+//!
+//! Unlike the upload blocklist ([crate::blocklist]), nothing here holds up
+//! sending: scans run in the background after the message is stored.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::db;
+use crate::error::AppError;
+use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::permissions::list_member_ids_with_permission;
+use crate::models::content_safety::{ContentSafetyFlag, PROVIDERS};
+use crate::models::message::{MessageRow, FLAG_QUARANTINED};
+use crate::models::settings::ServerSettings;
+use crate::safe_fetch::{self, FetchOptions};
+use crate::state::AppState;
+use crate::storage;
+
+/// How long the `http` provider waits for a verdict.
+pub const HTTP_SCAN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An attachment as providers see it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScannedAttachment {
+    pub id: String,
+    pub url: String,
+    pub content_type: Option<String>,
+    /// Lowercase hex SHA-256 of the file.
+    pub sha256: String,
+}
+
+/// A message waiting to be scanned.
+#[derive(Debug, Clone)]
+pub struct ScanItem {
+    pub message: MessageRow,
+    pub attachments: Vec<ScannedAttachment>,
+}
+
+/// A provider's match.
+#[derive(Debug, Clone)]
+pub struct Verdict {
+    /// What matched, for the admins reviewing the flag.
+    pub label: String,
+}
+
+pub type ScanFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Option<Verdict>, AppError>> + Send + 'a>>;
+
+/// Something that can look at a message and say whether it's unsafe.
+pub trait ScanProvider: Send + Sync {
+    /// The name admins enable the provider by (see [PROVIDERS]).
+    fn name(&self) -> &'static str;
+
+    /// `Some` if [item] matched.
+    fn scan<'a>(&'a self, state: &'a AppState, item: &'a ScanItem) -> ScanFuture<'a>;
+}
+
+/// Matches attachments against `content_safety_hashes`.
+pub struct HashListProvider;
+
+impl ScanProvider for HashListProvider {
+    fn name(&self) -> &'static str {
+        "hash_list"
+    }
+
+    fn scan<'a>(&'a self, state: &'a AppState, item: &'a ScanItem) -> ScanFuture<'a> {
+        Box::pin(async move {
+            let hashes: Vec<String> = item.attachments.iter().map(|a| a.sha256.clone()).collect();
+            let listed = db::content_safety::find_listed_hash(&state.db, &hashes).await?;
+            Ok(listed.map(|entry| Verdict {
+                label: entry
+                    .label
+                    .unwrap_or_else(|| format!("listed hash {}", entry.hash)),
+            }))
+        })
+    }
+}
+
+/// POSTs the message to an external classifier, which answers
+/// `{"flagged": bool, "label": "..."}`.
+pub struct HttpProvider {
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct HttpVerdict {
+    flagged: bool,
+    label: Option<String>,
+}
+
+impl ScanProvider for HttpProvider {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn scan<'a>(&'a self, _state: &'a AppState, item: &'a ScanItem) -> ScanFuture<'a> {
+        Box::pin(async move {
+            let msg = &item.message;
+            let body = serde_json::json!({
+                "message": {
+                    "id": msg.id,
+                    "channel_id": msg.channel_id,
+                    "space_id": msg.space_id,
+                    "author_id": msg.author_id,
+                    "content": msg.content,
+                },
+                "attachments": item.attachments,
+            })
+            .to_string();
+            let options = FetchOptions {
+                timeout: HTTP_SCAN_TIMEOUT,
+                ..FetchOptions::default()
+            };
+            let resp = safe_fetch::request(
+                Method::POST,
+                &self.url,
+                &[("Content-Type", "application/json".to_string())],
+                Some(body.as_bytes()),
+                &options,
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("content-safety endpoint: {e}")))?;
+            if !(200..300).contains(&resp.status) {
+                return Err(AppError::Internal(format!(
+                    "content-safety endpoint answered {}",
+                    resp.status
+                )));
+            }
+            let verdict: HttpVerdict = serde_json::from_slice(&resp.body).map_err(|e| {
+                AppError::Internal(format!("content-safety endpoint sent bad JSON: {e}"))
+            })?;
+            Ok(verdict.flagged.then(|| Verdict {
+                label: verdict.label.unwrap_or_else(|| "flagged".to_string()),
+            }))
+        })
+    }
+}
+
+/// Check a comma-separated provider list and return it tidied up.
+pub fn normalize_providers(value: &str) -> Result<String, AppError> {
+    let mut names: Vec<&str> = Vec::new();
+    for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        if !PROVIDERS.contains(&name) {
+            return Err(AppError::BadRequest(format!(
+                "content_safety_providers must be drawn from: {}",
+                PROVIDERS.join(", ")
+            )));
+        }
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    Ok(names.join(","))
+}
+
+/// The providers the instance has on, in the order they're listed.
+pub fn enabled_providers(settings: &ServerSettings) -> Vec<Box<dyn ScanProvider>> {
+    settings
+        .content_safety_providers
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter_map(|name| -> Option<Box<dyn ScanProvider>> {
+            match name {
+                "hash_list" => Some(Box::new(HashListProvider)),
+                "http" => settings
+                    .content_safety_endpoint
+                    .clone()
+                    .map(|url| Box::new(HttpProvider { url }) as Box<dyn ScanProvider>),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Whether new messages should be scanned at all.
+pub fn is_enabled(state: &AppState) -> bool {
+    state.settings.load().content_safety_providers.is_some()
+}
+
+/// Scan [msg] in the background, if scanning is on.
+pub fn spawn_scan(state: &AppState, msg: &MessageRow, attachments: Vec<ScannedAttachment>) {
+    if !is_enabled(state) {
+        return;
+    }
+    let state = state.clone();
+    let item = ScanItem {
+        message: msg.clone(),
+        attachments,
+    };
+    tokio::spawn(async move {
+        if let Err(e) = scan(&state, &item).await {
+            tracing::warn!(
+                "content-safety scan of message {} failed: {e:?}",
+                item.message.id
+            );
+        }
+    });
+}
+
+/// Run the enabled providers over [item] until one matches, and flag it.
+/// A provider that fails is logged and skipped.
+pub async fn scan(
+    state: &AppState,
+    item: &ScanItem,
+) -> Result<Option<ContentSafetyFlag>, AppError> {
+    let settings = state.settings.load_full();
+    for provider in enabled_providers(&settings) {
+        let verdict = match provider.scan(state, item).await {
+            Ok(Some(verdict)) => verdict,
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::warn!(
+                    "content-safety provider {} failed on message {}: {e:?}",
+                    provider.name(),
+                    item.message.id
+                );
+                continue;
+            }
+        };
+        let flag = db::content_safety::create_flag(
+            &state.db,
+            &item.message,
+            provider.name(),
+            &verdict.label,
+            &settings.content_safety_action,
+        )
+        .await?;
+        if flag.action == "quarantine" {
+            db::messages::add_message_flags(&state.db, &item.message.id, FLAG_QUARANTINED).await?;
+            broadcast_to_channel(state, &item.message, "message.delete", delete_event(&flag)).await;
+        }
+        alert(state, &flag).await;
+        return Ok(Some(flag));
+    }
+    Ok(None)
+}
+
+/// Put a quarantined message back.
+pub async fn release(state: &AppState, flag: &ContentSafetyFlag) -> Result<(), AppError> {
+    if flag.action != "quarantine" {
+        return Ok(());
+    }
+    let msg =
+        match db::messages::remove_message_flags(&state.db, &flag.message_id, FLAG_QUARANTINED)
+            .await
+        {
+            Ok(msg) => msg,
+            Err(AppError::NotFound(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+    let attachments = db::attachments::get_attachments_for_message(&state.db, &msg.id).await?;
+    let json =
+        crate::routes::messages::message_row_to_json_with_attachments(&msg, &attachments, None);
+    broadcast_to_channel(state, &msg, "message.create", json).await;
+    Ok(())
+}
+
+/// Delete a flagged message and its files for good.
+pub async fn remove(state: &AppState, flag: &ContentSafetyFlag) -> Result<(), AppError> {
+    let msg = match db::messages::get_message_row(&state.db, &flag.message_id).await {
+        Ok(msg) => msg,
+        Err(AppError::NotFound(_)) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let attachments = db::attachments::get_attachments_for_message(&state.db, &msg.id).await?;
+    for att in &attachments {
+        let _ = storage::delete_file(state.storage.as_ref(), &att.url).await;
+    }
+    db::messages::delete_message(&state.db, &msg.id).await?;
+    if flag.action != "quarantine" {
+        broadcast_to_channel(state, &msg, "message.delete", delete_event(flag)).await;
+    }
+    Ok(())
+}
+
+fn delete_event(flag: &ContentSafetyFlag) -> serde_json::Value {
+    serde_json::json!({
+        "id": flag.message_id,
+        "channel_id": flag.channel_id,
+        "space_id": flag.space_id,
+    })
+}
+
+/// Send a message event to whoever can see [msg]'s channel.
+async fn broadcast_to_channel(
+    state: &AppState,
+    msg: &MessageRow,
+    event_type: &str,
+    data: serde_json::Value,
+) {
+    let target_user_ids = match msg.space_id {
+        Some(_) => None,
+        None => Some(
+            db::dm_participants::list_participant_ids(&state.db, &msg.channel_id)
+                .await
+                .unwrap_or_default(),
+        ),
+    };
+    let role_mention_user_ids = match event_type {
+        "message.create" => crate::gateway::events::resolve_role_mentions(&state.db, &data).await,
+        _ => None,
+    };
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: msg.space_id.clone(),
+            target_user_ids,
+            event: serde_json::json!({
+                "op": 0,
+                "type": event_type,
+                "data": data
+            }),
+            intent: "messages".to_string(),
+            role_mention_user_ids,
+        });
+    }
+}
+
+/// Tell instance admins about [flag], and the space's moderators
+/// (`manage_messages`) that one of their messages was flagged. Moderators
+/// aren't told the label; reviewing it is the admins' job.
+async fn alert(state: &AppState, flag: &ContentSafetyFlag) {
+    let moderators = match flag.space_id {
+        Some(ref space_id) => {
+            list_member_ids_with_permission(&state.db, space_id, "manage_messages")
+                .await
+                .unwrap_or_default()
+        }
+        None => Vec::new(),
+    };
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let _ = dispatcher.send(GatewayBroadcast::admin(
+            "admin.content_safety_flag",
+            serde_json::to_value(flag).unwrap_or_default(),
+        ));
+        if !moderators.is_empty() {
+            let _ = dispatcher.send(GatewayBroadcast {
+                space_id: None,
+                target_user_ids: Some(moderators),
+                event: serde_json::json!({
+                    "op": 0,
+                    "type": "content_safety.flag",
+                    "data": {
+                        "id": flag.id,
+                        "message_id": flag.message_id,
+                        "channel_id": flag.channel_id,
+                        "space_id": flag.space_id,
+                        "author_id": flag.author_id,
+                        "action": flag.action,
+                    }
+                }),
+                intent: "moderation".to_string(),
+                role_mention_user_ids: None,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provider_lists_are_checked_and_deduplicated() {
+        assert_eq!(
+            normalize_providers(" http, hash_list ,http").unwrap(),
+            "http,hash_list"
+        );
+        assert_eq!(normalize_providers("").unwrap(), "");
+        assert!(normalize_providers("hash_list,antivirus").is_err());
+    }
+}
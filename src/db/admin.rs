@@ -1,4 +1,6 @@
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::models::space::AdminSpaceRow;
@@ -11,7 +13,7 @@ use super::users::get_user;
 // -------------------------------------------------------------------------
 
 pub async fn list_all_spaces(
-    pool: &AnyPool,
+    pool: &DbPool,
     after: Option<&str>,
     limit: i64,
     search: Option<&str>,
@@ -68,7 +70,7 @@ pub async fn list_all_spaces(
 }
 
 pub async fn admin_update_space(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     input: &crate::models::space::AdminUpdateSpace,
     is_postgres: bool,
@@ -102,7 +104,25 @@ pub async fn admin_update_space(
             .await?;
     }
 
-    if sets.is_empty() && input.public.is_none() && input.allow_guest_access.is_none() {
+    let mut int_binds: Vec<Option<i64>> = Vec::new();
+    if let Some(max_emojis) = input.max_emojis {
+        sets.push("max_emojis = ?");
+        int_binds.push(max_emojis);
+    }
+    if let Some(max_sounds) = input.max_sounds {
+        sets.push("max_sounds = ?");
+        int_binds.push(max_sounds);
+    }
+    if let Some(max_stickers) = input.max_stickers {
+        sets.push("max_stickers = ?");
+        int_binds.push(max_stickers);
+    }
+
+    if sets.is_empty()
+        && input.public.is_none()
+        && input.allow_guest_access.is_none()
+        && input.featured.is_none()
+    {
         return Ok(());
     }
 
@@ -115,6 +135,10 @@ pub async fn admin_update_space(
         sets.push("allow_guest_access = ?");
         bool_binds.push(allow_guest_access);
     }
+    if let Some(featured) = input.featured {
+        sets.push("featured = ?");
+        bool_binds.push(featured);
+    }
 
     let updated_at_set = format!("updated_at = {now_fn}");
     sets.push(&updated_at_set);
@@ -125,6 +149,9 @@ pub async fn admin_update_space(
     for v in &str_values {
         query = query.bind(v);
     }
+    for v in &int_binds {
+        query = query.bind(v);
+    }
     for v in &bool_binds {
         query = query.bind(v);
     }
@@ -139,7 +166,7 @@ pub async fn admin_update_space(
 // -------------------------------------------------------------------------
 
 pub async fn list_all_users(
-    pool: &AnyPool,
+    pool: &DbPool,
     after: Option<&str>,
     limit: i64,
     search: Option<&str>,
@@ -201,7 +228,7 @@ pub async fn list_all_users(
 }
 
 pub async fn admin_update_user(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
     input: &AdminUpdateUser,
     is_postgres: bool,
@@ -256,14 +283,14 @@ pub async fn admin_update_user(
     get_user(pool, user_id).await
 }
 
-pub async fn count_admins(pool: &AnyPool) -> Result<i64, AppError> {
+pub async fn count_admins(pool: &DbPool) -> Result<i64, AppError> {
     let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE is_admin = TRUE")
         .fetch_one(pool)
         .await?;
     Ok(count)
 }
 
-pub async fn delete_user(pool: &AnyPool, user_id: &str) -> Result<(), AppError> {
+pub async fn delete_user(pool: &DbPool, user_id: &str) -> Result<(), AppError> {
     // Check the user doesn't own any spaces
     let owned: i64 =
         sqlx::query_scalar(&super::q("SELECT COUNT(*) FROM spaces WHERE owner_id = ?"))
@@ -294,45 +321,45 @@ pub async fn delete_user(pool: &AnyPool, user_id: &str) -> Result<(), AppError>
         "DELETE FROM plugin_session_participants WHERE user_id = ?",
     ))
     .bind(user_id)
-    .execute(&mut *tx)
+    .execute(&mut tx)
     .await?;
     sqlx::query(&super::q(
         "DELETE FROM plugin_sessions WHERE host_user_id = ?",
     ))
     .bind(user_id)
-    .execute(&mut *tx)
+    .execute(&mut tx)
     .await?;
     sqlx::query(&super::q("DELETE FROM plugins WHERE creator_id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
 
     // Reports: reporter_id is NOT NULL so reports filed by the user are
     // deleted; actioned_by is nullable so it is detached instead.
     sqlx::query(&super::q("DELETE FROM reports WHERE reporter_id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
     sqlx::query(&super::q(
         "UPDATE reports SET actioned_by = NULL WHERE actioned_by = ?",
     ))
     .bind(user_id)
-    .execute(&mut *tx)
+    .execute(&mut tx)
     .await?;
 
     // Audit log entries (user_id is NOT NULL) are removed with the user.
     sqlx::query(&super::q("DELETE FROM audit_log WHERE user_id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
 
     sqlx::query(&super::q("DELETE FROM user_tokens WHERE user_id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
     sqlx::query(&super::q("DELETE FROM bot_tokens WHERE user_id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
     // Detach this user as a bot identity before owned applications are removed
     // (an application may be owned by someone else but bound to this bot user).
@@ -340,69 +367,82 @@ pub async fn delete_user(pool: &AnyPool, user_id: &str) -> Result<(), AppError>
         "UPDATE applications SET bot_user_id = NULL WHERE bot_user_id = ?",
     ))
     .bind(user_id)
-    .execute(&mut *tx)
+    .execute(&mut tx)
     .await?;
     sqlx::query(&super::q("DELETE FROM applications WHERE owner_id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
     sqlx::query(&super::q("DELETE FROM reactions WHERE user_id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
     sqlx::query(&super::q("DELETE FROM dm_participants WHERE user_id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
+    sqlx::query(&super::q(
+        "UPDATE roles SET member_count = member_count - 1 WHERE id IN \
+         (SELECT role_id FROM member_roles WHERE user_id = ?)",
+    ))
+    .bind(user_id)
+    .execute(&mut tx)
+    .await?;
     sqlx::query(&super::q("DELETE FROM member_roles WHERE user_id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
     sqlx::query(&super::q("DELETE FROM members WHERE user_id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
     sqlx::query(&super::q("DELETE FROM bans WHERE user_id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
     sqlx::query(&super::q(
         "UPDATE bans SET banned_by = NULL WHERE banned_by = ?",
     ))
     .bind(user_id)
-    .execute(&mut *tx)
+    .execute(&mut tx)
     .await?;
     sqlx::query(&super::q(
         "UPDATE invites SET inviter_id = NULL WHERE inviter_id = ?",
     ))
     .bind(user_id)
-    .execute(&mut *tx)
+    .execute(&mut tx)
     .await?;
     sqlx::query(&super::q(
         "UPDATE emojis SET creator_id = NULL WHERE creator_id = ?",
     ))
     .bind(user_id)
-    .execute(&mut *tx)
+    .execute(&mut tx)
+    .await?;
+    sqlx::query(&super::q(
+        "UPDATE stickers SET creator_id = NULL WHERE creator_id = ?",
+    ))
+    .bind(user_id)
+    .execute(&mut tx)
     .await?;
     sqlx::query(&super::q(
         "UPDATE soundboard_sounds SET creator_id = NULL WHERE creator_id = ?",
     ))
     .bind(user_id)
-    .execute(&mut *tx)
+    .execute(&mut tx)
     .await?;
     sqlx::query(&super::q(
         "UPDATE channels SET owner_id = NULL WHERE owner_id = ?",
     ))
     .bind(user_id)
-    .execute(&mut *tx)
+    .execute(&mut tx)
     .await?;
     sqlx::query(&super::q("DELETE FROM messages WHERE author_id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
     sqlx::query(&super::q("DELETE FROM users WHERE id = ?"))
         .bind(user_id)
-        .execute(&mut *tx)
+        .execute(&mut tx)
         .await?;
 
     tx.commit().await?;
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::models::attachment::Attachment;
@@ -12,26 +14,30 @@ use crate::models::attachment::Attachment;
 /// the file path on disk is the single source of truth.
 #[allow(clippy::too_many_arguments)]
 pub async fn insert_attachment(
-    pool: &AnyPool,
+    pool: &DbPool,
     attachment_id: &str,
     message_id: &str,
     filename: &str,
+    description: Option<&str>,
     content_type: Option<&str>,
     size: i64,
     url: &str,
+    proxy_url: Option<&str>,
     width: Option<i64>,
     height: Option<i64>,
 ) -> Result<Attachment, AppError> {
     sqlx::query(
-        &super::q("INSERT INTO attachments (id, message_id, filename, content_type, size, url, width, height) \
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?)"),
+        &super::q("INSERT INTO attachments (id, message_id, filename, description, content_type, size, url, proxy_url, width, height) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"),
     )
     .bind(attachment_id)
     .bind(message_id)
     .bind(filename)
+    .bind(description)
     .bind(content_type)
     .bind(size)
     .bind(url)
+    .bind(proxy_url)
     .bind(width)
     .bind(height)
     .execute(pool)
@@ -40,21 +46,40 @@ pub async fn insert_attachment(
     Ok(Attachment {
         id: attachment_id.to_string(),
         filename: filename.to_string(),
-        description: None,
+        description: description.map(|s| s.to_string()),
         content_type: content_type.map(|s| s.to_string()),
         size,
         url: url.to_string(),
+        proxy_url: proxy_url.map(|s| s.to_string()),
         width,
         height,
     })
 }
 
+/// Set or clear the alt text of one of [message_id]'s attachments.
+pub async fn set_description(
+    pool: &DbPool,
+    message_id: &str,
+    attachment_id: &str,
+    description: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "UPDATE attachments SET description = ? WHERE id = ? AND message_id = ?",
+    ))
+    .bind(description)
+    .bind(attachment_id)
+    .bind(message_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
 pub async fn get_attachments_for_message(
-    pool: &AnyPool,
+    pool: &DbPool,
     message_id: &str,
 ) -> Result<Vec<Attachment>, AppError> {
     let rows = sqlx::query(&super::q(
-        "SELECT id, filename, description, content_type, size, url, width, height \
+        "SELECT id, filename, description, content_type, size, url, proxy_url, width, height \
          FROM attachments WHERE message_id = ?",
     ))
     .bind(message_id)
@@ -64,8 +89,31 @@ pub async fn get_attachments_for_message(
     Ok(rows.into_iter().map(row_to_attachment).collect())
 }
 
+/// Storage URLs of every attachment (and thumbnail) on a message in
+/// [channel_id].
+pub async fn list_channel_attachment_urls(
+    pool: &DbPool,
+    channel_id: &str,
+) -> Result<Vec<String>, AppError> {
+    let rows: Vec<(String, Option<String>)> = sqlx::query_as(&super::q(
+        "SELECT a.url, a.proxy_url FROM attachments a JOIN messages m ON m.id = a.message_id \
+         WHERE m.channel_id = ?",
+    ))
+    .bind(channel_id)
+    .fetch_all(pool)
+    .await?;
+    let mut urls = Vec::new();
+    for (url, proxy_url) in rows {
+        if let Some(proxy_url) = proxy_url.filter(|p| *p != url) {
+            urls.push(proxy_url);
+        }
+        urls.push(url);
+    }
+    Ok(urls)
+}
+
 pub async fn get_attachments_for_messages(
-    pool: &AnyPool,
+    pool: &DbPool,
     message_ids: &[String],
 ) -> Result<HashMap<String, Vec<Attachment>>, AppError> {
     if message_ids.is_empty() {
@@ -75,7 +123,7 @@ pub async fn get_attachments_for_messages(
     let placeholders: Vec<&str> = message_ids.iter().map(|_| "?").collect();
     let in_clause = placeholders.join(", ");
     let sql = format!(
-        "SELECT id, message_id, filename, description, content_type, size, url, width, height \
+        "SELECT id, message_id, filename, description, content_type, size, url, proxy_url, width, height \
          FROM attachments WHERE message_id IN ({in_clause}) ORDER BY id ASC"
     );
 
@@ -104,6 +152,7 @@ fn row_to_attachment(row: sqlx::any::AnyRow) -> Attachment {
         content_type: row.get("content_type"),
         size: row.get("size"),
         url: row.get("url"),
+        proxy_url: row.get("proxy_url"),
         width: row.get("width"),
         height: row.get("height"),
     }
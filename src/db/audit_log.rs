@@ -1,4 +1,4 @@
-use sqlx::AnyPool;
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::snowflake;
@@ -18,7 +18,7 @@ pub struct AuditLogRow {
 
 #[allow(clippy::too_many_arguments)]
 pub async fn create_entry(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
     action_type: &str,
@@ -64,11 +64,12 @@ pub async fn create_entry(
 }
 
 pub async fn list_entries(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     action_type: Option<&str>,
     user_id: Option<&str>,
     before: Option<&str>,
+    after: Option<&str>,
     limit: i64,
 ) -> Result<Vec<AuditLogRow>, AppError> {
     let mut query = String::from("SELECT id, space_id, user_id, action_type, target_id, target_type, reason, changes, created_at FROM audit_log WHERE space_id = ?");
@@ -82,7 +83,17 @@ pub async fn list_entries(
     if before.is_some() {
         query.push_str(" AND id < ?");
     }
-    query.push_str(" ORDER BY id DESC LIMIT ?");
+    if after.is_some() {
+        query.push_str(" AND id > ?");
+    }
+    // Paging forward from `after` takes the oldest entries past the cursor;
+    // results are flipped back to newest-first below.
+    let ascending = after.is_some() && before.is_none();
+    if ascending {
+        query.push_str(" ORDER BY id ASC LIMIT ?");
+    } else {
+        query.push_str(" ORDER BY id DESC LIMIT ?");
+    }
 
     let query = super::q(&query);
     let mut q = sqlx::query_as::<
@@ -110,9 +121,15 @@ pub async fn list_entries(
     if let Some(b) = before {
         q = q.bind(b);
     }
+    if let Some(a) = after {
+        q = q.bind(a);
+    }
     q = q.bind(limit);
 
-    let rows = q.fetch_all(pool).await?;
+    let mut rows = q.fetch_all(pool).await?;
+    if ascending {
+        rows.reverse();
+    }
 
     Ok(rows
         .into_iter()
@@ -1,4 +1,4 @@
-use sqlx::AnyPool;
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::middleware::auth::{create_token_hash, generate_token};
@@ -7,7 +7,7 @@ use crate::models::user::CreateUser;
 use crate::snowflake;
 
 pub async fn create_application(
-    pool: &AnyPool,
+    pool: &DbPool,
     owner_id: &str,
     name: &str,
     description: &str,
@@ -30,14 +30,18 @@ pub async fn create_application(
         .execute(pool)
         .await?;
 
+    let (private_key, verify_key) = crate::signing::generate_interaction_key();
     sqlx::query(&super::q(
-        "INSERT INTO applications (id, name, description, owner_id, bot_user_id) VALUES (?, ?, ?, ?, ?)"
+        "INSERT INTO applications (id, name, description, owner_id, bot_user_id, verify_key, interactions_private_key) \
+         VALUES (?, ?, ?, ?, ?, ?, ?)"
     ))
     .bind(&app_id)
     .bind(name)
     .bind(description)
     .bind(owner_id)
     .bind(&bot_user.id)
+    .bind(&verify_key)
+    .bind(&private_key)
     .execute(pool)
     .await?;
 
@@ -60,6 +64,8 @@ pub async fn create_application(
 
 fn row_to_application(row: sqlx::any::AnyRow) -> Application {
     use sqlx::Row;
+    let ip_allowlist: String = row.get("ip_allowlist");
+    let redirect_uris: String = row.get("redirect_uris");
     Application {
         id: row.get("id"),
         name: row.get("name"),
@@ -68,13 +74,18 @@ fn row_to_application(row: sqlx::any::AnyRow) -> Application {
         bot_public: crate::db::get_bool(&row, "bot_public"),
         owner_id: row.get("owner_id"),
         flags: row.get("flags"),
+        bot_user_id: row.get("bot_user_id"),
+        ip_allowlist: serde_json::from_str(&ip_allowlist).unwrap_or_default(),
+        redirect_uris: serde_json::from_str(&redirect_uris).unwrap_or_default(),
+        interactions_endpoint_url: row.get("interactions_endpoint_url"),
+        verify_key: row.get("verify_key"),
     }
 }
 
 const SELECT_APPLICATIONS: &str =
-    "SELECT id, name, icon, description, bot_public, owner_id, flags FROM applications";
+    "SELECT id, name, icon, description, bot_public, owner_id, flags, bot_user_id, ip_allowlist, redirect_uris, interactions_endpoint_url, verify_key FROM applications";
 
-pub async fn get_application(pool: &AnyPool, app_id: &str) -> Result<Application, AppError> {
+pub async fn get_application(pool: &DbPool, app_id: &str) -> Result<Application, AppError> {
     let row = sqlx::query(&super::q(&format!("{SELECT_APPLICATIONS} WHERE id = ?")))
         .bind(app_id)
         .fetch_optional(pool)
@@ -85,7 +96,7 @@ pub async fn get_application(pool: &AnyPool, app_id: &str) -> Result<Application
 }
 
 pub async fn get_application_by_owner(
-    pool: &AnyPool,
+    pool: &DbPool,
     owner_id: &str,
 ) -> Result<Application, AppError> {
     let row = sqlx::query(&super::q(&format!(
@@ -99,7 +110,84 @@ pub async fn get_application_by_owner(
     Ok(row_to_application(row))
 }
 
-pub async fn reset_bot_token(pool: &AnyPool, app_id: &str) -> Result<String, AppError> {
+/// The Ed25519 key an application's HTTP interactions are signed with,
+/// generated for applications made before there was one.
+pub async fn interaction_private_key(pool: &DbPool, app_id: &str) -> Result<String, AppError> {
+    let existing: Option<String> = sqlx::query_scalar(&super::q(
+        "SELECT interactions_private_key FROM applications WHERE id = ?",
+    ))
+    .bind(app_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("application not found".to_string()))?;
+    if let Some(key) = existing {
+        return Ok(key);
+    }
+    let (private_key, verify_key) = crate::signing::generate_interaction_key();
+    // Only the first caller's key is kept if two race.
+    sqlx::query(&super::q(
+        "UPDATE applications SET interactions_private_key = ?, verify_key = ? \
+         WHERE id = ? AND interactions_private_key IS NULL",
+    ))
+    .bind(&private_key)
+    .bind(&verify_key)
+    .bind(app_id)
+    .execute(pool)
+    .await?;
+    let key: String = sqlx::query_scalar(&super::q(
+        "SELECT interactions_private_key FROM applications WHERE id = ?",
+    ))
+    .bind(app_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(key)
+}
+
+pub async fn set_interactions_endpoint(
+    pool: &DbPool,
+    app_id: &str,
+    url: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "UPDATE applications SET interactions_endpoint_url = ? WHERE id = ?",
+    ))
+    .bind(url)
+    .bind(app_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_ip_allowlist(
+    pool: &DbPool,
+    app_id: &str,
+    ranges: &[String],
+) -> Result<Application, AppError> {
+    sqlx::query(&super::q(
+        "UPDATE applications SET ip_allowlist = ? WHERE id = ?",
+    ))
+    .bind(serde_json::to_string(ranges).unwrap())
+    .bind(app_id)
+    .execute(pool)
+    .await?;
+    get_application(pool, app_id).await
+}
+
+/// The application a bot token belongs to, if the token exists.
+pub async fn bot_token_application_id(
+    pool: &DbPool,
+    token: &str,
+) -> Result<Option<String>, AppError> {
+    let app_id = sqlx::query_scalar(&super::q(
+        "SELECT application_id FROM bot_tokens WHERE token_hash = ?",
+    ))
+    .bind(create_token_hash(token))
+    .fetch_optional(pool)
+    .await?;
+    Ok(app_id)
+}
+
+pub async fn reset_bot_token(pool: &DbPool, app_id: &str) -> Result<String, AppError> {
     // Find the bot user for this application
     let bot_user_id: String = sqlx::query_scalar(&super::q(
         "SELECT bot_user_id FROM applications WHERE id = ?",
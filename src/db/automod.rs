@@ -0,0 +1,240 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::automod::{AutomodRule, CreateAutomodRule, UpdateAutomodRule};
+use crate::snowflake;
+
+/// Rows whose stored trigger or actions no longer parse are skipped rather
+/// than failing the whole list.
+fn row_to_rule(row: sqlx::any::AnyRow) -> Option<AutomodRule> {
+    let trigger: String = row.get("trigger_data");
+    let actions: String = row.get("actions");
+    Some(AutomodRule {
+        id: row.get("id"),
+        space_id: row.get("space_id"),
+        creator_id: row.get("creator_id"),
+        name: row.get("name"),
+        trigger: serde_json::from_str(&trigger).ok()?,
+        actions: serde_json::from_str(&actions).ok()?,
+        enabled: super::get_bool(&row, "enabled"),
+        created_at: row.get("created_at"),
+    })
+}
+
+const SELECT_RULES: &str =
+    "SELECT id, space_id, creator_id, name, trigger_data, actions, enabled, created_at FROM automod_rules";
+
+pub async fn create_rule(
+    pool: &DbPool,
+    space_id: &str,
+    creator_id: &str,
+    input: &CreateAutomodRule,
+) -> Result<AutomodRule, AppError> {
+    let id = snowflake::generate();
+    sqlx::query(&super::q(
+        "INSERT INTO automod_rules (id, space_id, creator_id, name, trigger_data, actions, enabled) VALUES (?, ?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(space_id)
+    .bind(creator_id)
+    .bind(input.name.trim())
+    .bind(serde_json::to_string(&input.trigger).unwrap_or_default())
+    .bind(serde_json::to_string(&input.actions).unwrap_or_default())
+    .bind(input.enabled.unwrap_or(true))
+    .execute(pool)
+    .await?;
+    get_rule(pool, space_id, &id).await
+}
+
+pub async fn get_rule(
+    pool: &DbPool,
+    space_id: &str,
+    rule_id: &str,
+) -> Result<AutomodRule, AppError> {
+    sqlx::query(&super::q(&format!(
+        "{SELECT_RULES} WHERE id = ? AND space_id = ?"
+    )))
+    .bind(rule_id)
+    .bind(space_id)
+    .fetch_optional(pool)
+    .await?
+    .and_then(row_to_rule)
+    .ok_or_else(|| AppError::NotFound("unknown_automod_rule".to_string()))
+}
+
+pub async fn list_rules(pool: &DbPool, space_id: &str) -> Result<Vec<AutomodRule>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_RULES} WHERE space_id = ? ORDER BY id ASC"
+    )))
+    .bind(space_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().filter_map(row_to_rule).collect())
+}
+
+/// The rules checked against new messages in [space_id].
+pub async fn list_enabled_rules(
+    pool: &DbPool,
+    space_id: &str,
+) -> Result<Vec<AutomodRule>, AppError> {
+    Ok(list_rules(pool, space_id)
+        .await?
+        .into_iter()
+        .filter(|r| r.enabled)
+        .collect())
+}
+
+pub async fn update_rule(
+    pool: &DbPool,
+    space_id: &str,
+    rule_id: &str,
+    input: &UpdateAutomodRule,
+) -> Result<AutomodRule, AppError> {
+    let mut sets: Vec<&str> = Vec::new();
+    if input.name.is_some() {
+        sets.push("name = ?");
+    }
+    if input.trigger.is_some() {
+        sets.push("trigger_data = ?");
+    }
+    if input.actions.is_some() {
+        sets.push("actions = ?");
+    }
+    if input.enabled.is_some() {
+        sets.push("enabled = ?");
+    }
+    if sets.is_empty() {
+        return get_rule(pool, space_id, rule_id).await;
+    }
+
+    let sql = super::q(&format!(
+        "UPDATE automod_rules SET {} WHERE id = ? AND space_id = ?",
+        sets.join(", ")
+    ));
+    let mut query = sqlx::query(&sql);
+    if let Some(ref name) = input.name {
+        query = query.bind(name.trim());
+    }
+    if let Some(ref trigger) = input.trigger {
+        query = query.bind(serde_json::to_string(trigger).unwrap_or_default());
+    }
+    if let Some(ref actions) = input.actions {
+        query = query.bind(serde_json::to_string(actions).unwrap_or_default());
+    }
+    if let Some(enabled) = input.enabled {
+        query = query.bind(enabled);
+    }
+    query.bind(rule_id).bind(space_id).execute(pool).await?;
+
+    get_rule(pool, space_id, rule_id).await
+}
+
+pub async fn delete_rule(pool: &DbPool, space_id: &str, rule_id: &str) -> Result<(), AppError> {
+    let result = sqlx::query(&super::q(
+        "DELETE FROM automod_rules WHERE id = ? AND space_id = ?",
+    ))
+    .bind(rule_id)
+    .bind(space_id)
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("unknown_automod_rule".to_string()));
+    }
+    Ok(())
+}
+
+/// Record that [rule] matched a message; returns the hit ID.
+pub async fn record_hit(
+    pool: &DbPool,
+    rule: &AutomodRule,
+    channel_id: &str,
+    user_id: &str,
+    sample: &str,
+) -> Result<String, AppError> {
+    let id = snowflake::generate();
+    sqlx::query(&super::q(
+        "INSERT INTO automod_hits (id, rule_id, space_id, channel_id, user_id, sample) VALUES (?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(&rule.id)
+    .bind(&rule.space_id)
+    .bind(channel_id)
+    .bind(user_id)
+    .bind(sample)
+    .execute(pool)
+    .await?;
+    Ok(id)
+}
+
+/// Drop hits in [space_id] older than [cutoff].
+pub async fn prune_hits(pool: &DbPool, space_id: &str, cutoff: &str) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "DELETE FROM automod_hits WHERE space_id = ? AND created_at < ?",
+    ))
+    .bind(space_id)
+    .bind(cutoff)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Mark a hit as a false positive.
+pub async fn override_hit(
+    pool: &DbPool,
+    space_id: &str,
+    hit_id: &str,
+    moderator_id: &str,
+) -> Result<(), AppError> {
+    let result = sqlx::query(&super::q(
+        "UPDATE automod_hits SET overridden = ?, overridden_by = ? WHERE id = ? AND space_id = ?",
+    ))
+    .bind(true)
+    .bind(moderator_id)
+    .bind(hit_id)
+    .bind(space_id)
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("unknown_automod_hit".to_string()));
+    }
+    Ok(())
+}
+
+/// `(rule_id, hits, overrides)` per rule with hits in [space_id] since
+/// [since].
+pub async fn hit_counts(
+    pool: &DbPool,
+    space_id: &str,
+    since: &str,
+) -> Result<Vec<(String, i64, i64)>, AppError> {
+    let rows = sqlx::query_as::<_, (String, i64, i64)>(&super::q(
+        "SELECT rule_id, COUNT(*), COUNT(CASE WHEN overridden THEN 1 END) \
+         FROM automod_hits WHERE space_id = ? AND created_at >= ? GROUP BY rule_id",
+    ))
+    .bind(space_id)
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// The latest [limit] samples for [rule_id] since [since], newest first.
+pub async fn recent_samples(
+    pool: &DbPool,
+    rule_id: &str,
+    since: &str,
+    limit: i64,
+) -> Result<Vec<String>, AppError> {
+    let rows: Vec<(String,)> = sqlx::query_as(&super::q(
+        "SELECT sample FROM automod_hits WHERE rule_id = ? AND created_at >= ? \
+         ORDER BY id DESC LIMIT ?",
+    ))
+    .bind(rule_id)
+    .bind(since)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|(s,)| s).collect())
+}
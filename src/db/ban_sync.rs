@@ -0,0 +1,116 @@
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::snowflake;
+
+#[derive(Debug, Clone)]
+pub struct BanSyncGroupRow {
+    pub id: String,
+    pub name: String,
+    pub owner_id: String,
+    pub created_at: String,
+}
+
+pub async fn get_group(pool: &DbPool, group_id: &str) -> Result<BanSyncGroupRow, AppError> {
+    let row = sqlx::query_as::<_, (String, String, String, String)>(&super::q(
+        "SELECT id, name, owner_id, created_at FROM ban_sync_groups WHERE id = ?",
+    ))
+    .bind(group_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("unknown_ban_sync_group".to_string()))?;
+
+    Ok(BanSyncGroupRow {
+        id: row.0,
+        name: row.1,
+        owner_id: row.2,
+        created_at: row.3,
+    })
+}
+
+/// The sync group [space_id] belongs to, if any.
+pub async fn get_group_id_for_space(
+    pool: &DbPool,
+    space_id: &str,
+) -> Result<Option<String>, AppError> {
+    let row: Option<(String,)> = sqlx::query_as(&super::q(
+        "SELECT group_id FROM ban_sync_group_spaces WHERE space_id = ?",
+    ))
+    .bind(space_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|(id,)| id))
+}
+
+/// Member spaces of a group, in the order they joined.
+pub async fn list_group_space_ids(pool: &DbPool, group_id: &str) -> Result<Vec<String>, AppError> {
+    let rows: Vec<(String,)> = sqlx::query_as(&super::q(
+        "SELECT space_id FROM ban_sync_group_spaces WHERE group_id = ? ORDER BY joined_at ASC, space_id ASC",
+    ))
+    .bind(group_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// Create a group with [space_id] as its first member.
+pub async fn create_group(
+    pool: &DbPool,
+    name: &str,
+    owner_id: &str,
+    space_id: &str,
+) -> Result<BanSyncGroupRow, AppError> {
+    let id = snowflake::generate();
+    sqlx::query(&super::q(
+        "INSERT INTO ban_sync_groups (id, name, owner_id) VALUES (?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(name)
+    .bind(owner_id)
+    .execute(pool)
+    .await?;
+    add_space(pool, &id, space_id, owner_id).await?;
+    get_group(pool, &id).await
+}
+
+/// Add [space_id] to a group. Fails with `Conflict` if it is already in one.
+pub async fn add_space(
+    pool: &DbPool,
+    group_id: &str,
+    space_id: &str,
+    added_by: &str,
+) -> Result<(), AppError> {
+    if get_group_id_for_space(pool, space_id).await?.is_some() {
+        return Err(AppError::Conflict("space_already_in_ban_sync_group".into()));
+    }
+    sqlx::query(&super::q(
+        "INSERT INTO ban_sync_group_spaces (space_id, group_id, added_by) VALUES (?, ?, ?)",
+    ))
+    .bind(space_id)
+    .bind(group_id)
+    .bind(added_by)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Take [space_id] out of its group. The group is deleted once it has no
+/// spaces left.
+pub async fn remove_space(pool: &DbPool, space_id: &str) -> Result<(), AppError> {
+    let Some(group_id) = get_group_id_for_space(pool, space_id).await? else {
+        return Ok(());
+    };
+    sqlx::query(&super::q(
+        "DELETE FROM ban_sync_group_spaces WHERE space_id = ?",
+    ))
+    .bind(space_id)
+    .execute(pool)
+    .await?;
+    if list_group_space_ids(pool, &group_id).await?.is_empty() {
+        sqlx::query(&super::q("DELETE FROM ban_sync_groups WHERE id = ?"))
+            .bind(&group_id)
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
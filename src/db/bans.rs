@@ -1,4 +1,4 @@
-use sqlx::AnyPool;
+use crate::db::DbPool;
 
 use crate::error::AppError;
 
@@ -11,7 +11,7 @@ pub struct BanRow {
     pub created_at: String,
 }
 
-pub async fn get_ban(pool: &AnyPool, space_id: &str, user_id: &str) -> Result<BanRow, AppError> {
+pub async fn get_ban(pool: &DbPool, space_id: &str, user_id: &str) -> Result<BanRow, AppError> {
     let row = sqlx::query_as::<_, (String, String, Option<String>, Option<String>, String)>(
         &super::q("SELECT user_id, space_id, reason, banned_by, created_at FROM bans WHERE space_id = ? AND user_id = ?")
     )
@@ -30,7 +30,7 @@ pub async fn get_ban(pool: &AnyPool, space_id: &str, user_id: &str) -> Result<Ba
     })
 }
 
-pub async fn list_bans(pool: &AnyPool, space_id: &str) -> Result<Vec<BanRow>, AppError> {
+pub async fn list_bans(pool: &DbPool, space_id: &str) -> Result<Vec<BanRow>, AppError> {
     let rows = sqlx::query_as::<_, (String, String, Option<String>, Option<String>, String)>(
         &super::q("SELECT user_id, space_id, reason, banned_by, created_at FROM bans WHERE space_id = ? ORDER BY created_at DESC")
     )
@@ -51,7 +51,7 @@ pub async fn list_bans(pool: &AnyPool, space_id: &str) -> Result<Vec<BanRow>, Ap
 }
 
 pub async fn create_ban(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
     reason: Option<&str>,
@@ -59,6 +59,7 @@ pub async fn create_ban(
     is_postgres: bool,
 ) -> Result<BanRow, AppError> {
     // Remove member first
+    super::members::release_member_roles(pool, space_id, user_id).await?;
     sqlx::query(&super::q(
         "DELETE FROM members WHERE space_id = ? AND user_id = ?",
     ))
@@ -83,7 +84,7 @@ pub async fn create_ban(
     get_ban(pool, space_id, user_id).await
 }
 
-pub async fn delete_ban(pool: &AnyPool, space_id: &str, user_id: &str) -> Result<(), AppError> {
+pub async fn delete_ban(pool: &DbPool, space_id: &str, user_id: &str) -> Result<(), AppError> {
     sqlx::query(&super::q(
         "DELETE FROM bans WHERE space_id = ? AND user_id = ?",
     ))
@@ -0,0 +1,84 @@
+use crate::db::DbPool;
+
+use crate::error::AppError;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BlocklistEntry {
+    pub kind: String,
+    pub value: String,
+    pub reason: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: String,
+}
+
+pub async fn list_entries(pool: &DbPool, kind: &str) -> Result<Vec<BlocklistEntry>, AppError> {
+    let rows = sqlx::query_as::<_, (String, String, Option<String>, Option<String>, String)>(
+        &super::q("SELECT kind, value, reason, created_by, created_at FROM instance_blocklist WHERE kind = ? ORDER BY created_at DESC, value ASC"),
+    )
+    .bind(kind)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| BlocklistEntry {
+            kind: row.0,
+            value: row.1,
+            reason: row.2,
+            created_by: row.3,
+            created_at: row.4,
+        })
+        .collect())
+}
+
+/// Add or update an entry. Re-adding an existing value replaces its reason.
+pub async fn upsert_entry(
+    pool: &DbPool,
+    kind: &str,
+    value: &str,
+    reason: Option<&str>,
+    created_by: &str,
+    is_postgres: bool,
+) -> Result<(), AppError> {
+    let sql = if is_postgres {
+        "INSERT INTO instance_blocklist (kind, value, reason, created_by) VALUES (?, ?, ?, ?) ON CONFLICT (kind, value) DO UPDATE SET reason = EXCLUDED.reason, created_by = EXCLUDED.created_by"
+    } else {
+        "INSERT OR REPLACE INTO instance_blocklist (kind, value, reason, created_by) VALUES (?, ?, ?, ?)"
+    };
+    sqlx::query(&super::q(sql))
+        .bind(kind)
+        .bind(value)
+        .bind(reason)
+        .bind(created_by)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns whether a row was removed.
+pub async fn delete_entry(pool: &DbPool, kind: &str, value: &str) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "DELETE FROM instance_blocklist WHERE kind = ? AND value = ?",
+    ))
+    .bind(kind)
+    .bind(value)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Whether any of [values] is on the [kind] list.
+pub async fn contains_any(pool: &DbPool, kind: &str, values: &[String]) -> Result<bool, AppError> {
+    if values.is_empty() {
+        return Ok(false);
+    }
+    let placeholders = vec!["?"; values.len()].join(", ");
+    let sql = super::q(&format!(
+        "SELECT COUNT(*) FROM instance_blocklist WHERE kind = ? AND value IN ({placeholders})"
+    ));
+    let mut query = sqlx::query_as::<_, (i64,)>(&sql).bind(kind);
+    for value in values {
+        query = query.bind(value);
+    }
+    let (count,) = query.fetch_one(pool).await?;
+    Ok(count > 0)
+}
@@ -1,4 +1,6 @@
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::models::channel::{ChannelRow, CreateChannel, UpdateChannel};
@@ -23,13 +25,19 @@ fn row_to_channel(row: sqlx::any::AnyRow) -> ChannelRow {
         archived: crate::db::get_bool(&row, "archived"),
         auto_archive_after: row.get("auto_archive_after"),
         allow_anonymous_read: crate::db::get_bool(&row, "allow_anonymous_read"),
+        announcement_locked: crate::db::get_bool(&row, "announcement_locked"),
+        voice_chat_retention: row.get("voice_chat_retention"),
+        icon: row.get("icon"),
+        auto_thread: crate::db::get_bool(&row, "auto_thread"),
+        auto_thread_name: row.get("auto_thread_name"),
+        max_pins: row.get("max_pins"),
         created_at: row.get("created_at"),
     }
 }
 
-const SELECT_CHANNELS: &str = "SELECT id, type, space_id, name, description, topic, position, parent_id, nsfw, rate_limit, bitrate, user_limit, owner_id, last_message_id, archived, auto_archive_after, allow_anonymous_read, created_at FROM channels";
+const SELECT_CHANNELS: &str = "SELECT id, type, space_id, name, description, topic, position, parent_id, nsfw, rate_limit, bitrate, user_limit, owner_id, last_message_id, archived, auto_archive_after, allow_anonymous_read, announcement_locked, voice_chat_retention, icon, auto_thread, auto_thread_name, max_pins, created_at FROM channels";
 
-pub async fn get_channel_row(pool: &AnyPool, channel_id: &str) -> Result<ChannelRow, AppError> {
+pub async fn get_channel_row(pool: &DbPool, channel_id: &str) -> Result<ChannelRow, AppError> {
     let row = sqlx::query(&super::q(&format!("{SELECT_CHANNELS} WHERE id = ?")))
         .bind(channel_id)
         .fetch_optional(pool)
@@ -40,7 +48,7 @@ pub async fn get_channel_row(pool: &AnyPool, channel_id: &str) -> Result<Channel
 }
 
 pub async fn list_channels_in_space(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
 ) -> Result<Vec<ChannelRow>, AppError> {
     let rows = sqlx::query(&super::q(&format!(
@@ -54,7 +62,7 @@ pub async fn list_channels_in_space(
 }
 
 pub async fn create_channel(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     input: &CreateChannel,
 ) -> Result<ChannelRow, AppError> {
@@ -83,7 +91,7 @@ pub async fn create_channel(
 }
 
 pub async fn update_channel(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     input: &UpdateChannel,
     is_postgres: bool,
@@ -110,6 +118,22 @@ pub async fn update_channel(
         sets.push("parent_id = ?".to_string());
         str_values.push(parent_id.clone());
     }
+    if let Some(ref retention) = input.voice_chat_retention {
+        sets.push("voice_chat_retention = ?".to_string());
+        str_values.push(Some(retention.clone()));
+    }
+    if let Some(ref icon) = input.icon {
+        sets.push("icon = ?".to_string());
+        str_values.push((!icon.is_empty()).then(|| icon.clone()));
+    }
+    if let Some(ref owner_id) = input.owner_id {
+        sets.push("owner_id = ?".to_string());
+        str_values.push(Some(owner_id.clone()));
+    }
+    if let Some(ref template) = input.auto_thread_name {
+        sets.push("auto_thread_name = ?".to_string());
+        str_values.push((!template.is_empty()).then(|| template.clone()));
+    }
 
     if let Some(position) = input.position {
         int_values.push(("position".to_string(), position));
@@ -132,6 +156,15 @@ pub async fn update_channel(
     if let Some(allow_anonymous_read) = input.allow_anonymous_read {
         bool_values.push(("allow_anonymous_read".to_string(), allow_anonymous_read));
     }
+    if let Some(auto_thread) = input.auto_thread {
+        bool_values.push(("auto_thread".to_string(), auto_thread));
+    }
+    if let Some(auto_archive_after) = input.auto_archive_after {
+        int_values.push(("auto_archive_after".to_string(), auto_archive_after));
+    }
+    if let Some(max_pins) = input.max_pins {
+        int_values.push(("max_pins".to_string(), max_pins));
+    }
 
     for (col, _) in &int_values {
         sets.push(format!("{col} = ?"));
@@ -164,7 +197,7 @@ pub async fn update_channel(
     get_channel_row(pool, channel_id).await
 }
 
-pub async fn delete_channel(pool: &AnyPool, channel_id: &str) -> Result<(), AppError> {
+pub async fn delete_channel(pool: &DbPool, channel_id: &str) -> Result<(), AppError> {
     sqlx::query(&super::q("DELETE FROM channels WHERE id = ?"))
         .bind(channel_id)
         .execute(pool)
@@ -172,20 +205,105 @@ pub async fn delete_channel(pool: &AnyPool, channel_id: &str) -> Result<(), AppE
     Ok(())
 }
 
+/// Move channels to new indexes in the space's order. `order` is every
+/// channel id, current order first to last. Moved channels are taken out
+/// and reinserted in target order, each at its requested index (clamped), so
+/// the result never has duplicate positions.
+pub fn apply_moves(mut order: Vec<String>, moves: &[(String, i64)]) -> Vec<String> {
+    let mut moves: Vec<&(String, i64)> =
+        moves.iter().filter(|(id, _)| order.contains(id)).collect();
+    // Last request for a channel wins.
+    let mut seen = std::collections::HashSet::new();
+    moves.reverse();
+    moves.retain(|(id, _)| seen.insert(id.clone()));
+    moves.sort_by_key(|(_, position)| *position);
+
+    order.retain(|id| !moves.iter().any(|(moved, _)| moved == id));
+    for (id, position) in moves {
+        let index = (*position).clamp(0, order.len() as i64) as usize;
+        order.insert(index, id.clone());
+    }
+    order
+}
+
+/// Reorder a space's channels in one transaction, renumbering every channel
+/// to positions 0..n. With `expected_version`, fails with 409
+/// `channel_order_conflict` if another reorder landed since that version.
+/// Ids from other spaces are ignored. Returns the new version and the
+/// `(id, position)` of each channel whose position changed.
 pub async fn reorder_channels(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     updates: &[(String, i64)],
-) -> Result<(), AppError> {
-    for (id, position) in updates {
-        sqlx::query(&super::q(
-            "UPDATE channels SET position = ? WHERE id = ? AND space_id = ?",
+    expected_version: Option<i64>,
+) -> Result<(i64, Vec<(String, i64)>), AppError> {
+    let mut tx = pool.begin().await?;
+
+    // Bumping the version first also serializes concurrent reorders on the
+    // space row.
+    let bump_sql = super::q(if expected_version.is_some() {
+        "UPDATE spaces SET channel_order_version = channel_order_version + 1 \
+         WHERE id = ? AND channel_order_version = ?"
+    } else {
+        "UPDATE spaces SET channel_order_version = channel_order_version + 1 WHERE id = ?"
+    });
+    let mut bump = sqlx::query(&bump_sql).bind(space_id);
+    if let Some(version) = expected_version {
+        bump = bump.bind(version);
+    }
+    let bumped = bump.execute(&mut tx).await?;
+    if bumped.rows_affected() == 0 {
+        let current: Option<i64> = sqlx::query_scalar(&super::q(
+            "SELECT channel_order_version FROM spaces WHERE id = ?",
         ))
-        .bind(position)
-        .bind(id)
         .bind(space_id)
-        .execute(pool)
+        .fetch_optional(&mut tx)
         .await?;
+        let current = current.ok_or_else(|| AppError::NotFound("space not found".into()))?;
+        return Err(AppError::ConflictCode(
+            "channel_order_conflict",
+            format!(
+                "channels were reordered since version {}; current version is {current}",
+                expected_version.unwrap_or_default()
+            ),
+        ));
     }
-    Ok(())
+    let version: i64 = sqlx::query_scalar(&super::q(
+        "SELECT channel_order_version FROM spaces WHERE id = ?",
+    ))
+    .bind(space_id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    let rows = sqlx::query(&super::q(
+        "SELECT id, position FROM channels WHERE space_id = ? ORDER BY position, id",
+    ))
+    .bind(space_id)
+    .fetch_all(&mut tx)
+    .await?;
+    let current: Vec<(String, i64)> = rows
+        .iter()
+        .map(|row| (row.get("id"), row.get("position")))
+        .collect();
+    let order = apply_moves(current.iter().map(|(id, _)| id.clone()).collect(), updates);
+
+    let mut changed = Vec::new();
+    for (index, id) in order.into_iter().enumerate() {
+        let position = index as i64;
+        if current
+            .iter()
+            .any(|(cid, old)| *cid == id && *old == position)
+        {
+            continue;
+        }
+        sqlx::query(&super::q("UPDATE channels SET position = ? WHERE id = ?"))
+            .bind(position)
+            .bind(&id)
+            .execute(&mut tx)
+            .await?;
+        changed.push((id, position));
+    }
+
+    tx.commit().await?;
+    Ok((version, changed))
 }
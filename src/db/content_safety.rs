@@ -0,0 +1,201 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::content_safety::{ContentSafetyFlag, ContentSafetyHash};
+use crate::models::message::MessageRow;
+use crate::snowflake;
+
+const SELECT_HASHES: &str = "SELECT hash, label, created_by, created_at FROM content_safety_hashes";
+
+fn row_to_hash(row: &sqlx::any::AnyRow) -> ContentSafetyHash {
+    ContentSafetyHash {
+        hash: row.get("hash"),
+        label: row.get("label"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Add or relabel a hash.
+pub async fn upsert_hash(
+    pool: &DbPool,
+    hash: &str,
+    label: Option<&str>,
+    created_by: &str,
+    is_postgres: bool,
+) -> Result<ContentSafetyHash, AppError> {
+    let sql = if is_postgres {
+        "INSERT INTO content_safety_hashes (hash, label, created_by) VALUES (?, ?, ?) \
+         ON CONFLICT (hash) DO UPDATE SET label = EXCLUDED.label, created_by = EXCLUDED.created_by"
+    } else {
+        "INSERT OR REPLACE INTO content_safety_hashes (hash, label, created_by) VALUES (?, ?, ?)"
+    };
+    sqlx::query(&super::q(sql))
+        .bind(hash)
+        .bind(label)
+        .bind(created_by)
+        .execute(pool)
+        .await?;
+    let row = sqlx::query(&super::q(&format!("{SELECT_HASHES} WHERE hash = ?")))
+        .bind(hash)
+        .fetch_one(pool)
+        .await?;
+    Ok(row_to_hash(&row))
+}
+
+/// Returns whether a row was removed.
+pub async fn delete_hash(pool: &DbPool, hash: &str) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "DELETE FROM content_safety_hashes WHERE hash = ?",
+    ))
+    .bind(hash)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Hashes in order, starting after `after`.
+pub async fn list_hashes(
+    pool: &DbPool,
+    after: Option<&str>,
+    limit: i64,
+) -> Result<Vec<ContentSafetyHash>, AppError> {
+    let mut sql = SELECT_HASHES.to_string();
+    if after.is_some() {
+        sql.push_str(" WHERE hash > ?");
+    }
+    sql.push_str(" ORDER BY hash ASC LIMIT ?");
+    let sql = super::q(&sql);
+    let mut query = sqlx::query(&sql);
+    if let Some(after) = after {
+        query = query.bind(after);
+    }
+    let rows = query.bind(limit).fetch_all(pool).await?;
+    Ok(rows.iter().map(row_to_hash).collect())
+}
+
+/// The first of `hashes` on the list, if any is.
+pub async fn find_listed_hash(
+    pool: &DbPool,
+    hashes: &[String],
+) -> Result<Option<ContentSafetyHash>, AppError> {
+    if hashes.is_empty() {
+        return Ok(None);
+    }
+    let in_clause = vec!["?"; hashes.len()].join(", ");
+    let sql = super::q(&format!(
+        "{SELECT_HASHES} WHERE hash IN ({in_clause}) ORDER BY hash ASC LIMIT 1"
+    ));
+    let mut query = sqlx::query(&sql);
+    for hash in hashes {
+        query = query.bind(hash);
+    }
+    Ok(query.fetch_optional(pool).await?.as_ref().map(row_to_hash))
+}
+
+const SELECT_FLAGS: &str = "SELECT id, message_id, channel_id, space_id, author_id, provider, label, action, status, reviewed_by, reviewed_at, created_at FROM content_safety_flags";
+
+fn row_to_flag(row: &sqlx::any::AnyRow) -> ContentSafetyFlag {
+    ContentSafetyFlag {
+        id: row.get("id"),
+        message_id: row.get("message_id"),
+        channel_id: row.get("channel_id"),
+        space_id: row.get("space_id"),
+        author_id: row.get("author_id"),
+        provider: row.get("provider"),
+        label: row.get("label"),
+        action: row.get("action"),
+        status: row.get("status"),
+        reviewed_by: row.get("reviewed_by"),
+        reviewed_at: row.get("reviewed_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+pub async fn create_flag(
+    pool: &DbPool,
+    msg: &MessageRow,
+    provider: &str,
+    label: &str,
+    action: &str,
+) -> Result<ContentSafetyFlag, AppError> {
+    let id = snowflake::generate();
+    sqlx::query(&super::q(
+        "INSERT INTO content_safety_flags (id, message_id, channel_id, space_id, author_id, provider, label, action) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(&msg.id)
+    .bind(&msg.channel_id)
+    .bind(&msg.space_id)
+    .bind(&msg.author_id)
+    .bind(provider)
+    .bind(label)
+    .bind(action)
+    .execute(pool)
+    .await?;
+    get_flag(pool, &id).await
+}
+
+pub async fn get_flag(pool: &DbPool, id: &str) -> Result<ContentSafetyFlag, AppError> {
+    let row = sqlx::query(&super::q(&format!("{SELECT_FLAGS} WHERE id = ?")))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("unknown_content_safety_flag".to_string()))?;
+    Ok(row_to_flag(&row))
+}
+
+/// Flags newest first.
+pub async fn list_flags(
+    pool: &DbPool,
+    status: Option<&str>,
+    before: Option<&str>,
+    limit: i64,
+) -> Result<Vec<ContentSafetyFlag>, AppError> {
+    let mut conditions = Vec::new();
+    if status.is_some() {
+        conditions.push("status = ?");
+    }
+    if before.is_some() {
+        conditions.push("id < ?");
+    }
+    let mut sql = SELECT_FLAGS.to_string();
+    if !conditions.is_empty() {
+        sql.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+    }
+    sql.push_str(" ORDER BY id DESC LIMIT ?");
+    let sql = super::q(&sql);
+    let mut query = sqlx::query(&sql);
+    if let Some(status) = status {
+        query = query.bind(status);
+    }
+    if let Some(before) = before {
+        query = query.bind(before);
+    }
+    let rows = query.bind(limit).fetch_all(pool).await?;
+    Ok(rows.iter().map(row_to_flag).collect())
+}
+
+/// Resolve a pending flag. Returns false if it had already been reviewed.
+pub async fn review_flag(
+    pool: &DbPool,
+    id: &str,
+    status: &str,
+    reviewed_by: &str,
+    is_postgres: bool,
+) -> Result<bool, AppError> {
+    let now_fn = crate::db::now_sql(is_postgres);
+    let result = sqlx::query(&super::q(&format!(
+        "UPDATE content_safety_flags SET status = ?, reviewed_by = ?, reviewed_at = {now_fn} \
+         WHERE id = ? AND status = 'pending'"
+    )))
+    .bind(status)
+    .bind(reviewed_by)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
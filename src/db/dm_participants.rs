@@ -1,4 +1,4 @@
-use sqlx::AnyPool;
+use crate::db::DbPool;
 
 use crate::db;
 use crate::error::AppError;
@@ -8,7 +8,7 @@ use crate::snowflake;
 
 /// Check whether a user is a participant in a DM channel.
 pub async fn is_participant(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     user_id: &str,
 ) -> Result<bool, AppError> {
@@ -24,7 +24,7 @@ pub async fn is_participant(
 
 /// List all participant user IDs for a DM channel.
 pub async fn list_participant_ids(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
 ) -> Result<Vec<String>, AppError> {
     let rows = sqlx::query_as::<_, (String,)>(&super::q(
@@ -37,10 +37,7 @@ pub async fn list_participant_ids(
 }
 
 /// Get full User objects for all participants in a DM channel.
-pub async fn get_participant_users(
-    pool: &AnyPool,
-    channel_id: &str,
-) -> Result<Vec<User>, AppError> {
+pub async fn get_participant_users(pool: &DbPool, channel_id: &str) -> Result<Vec<User>, AppError> {
     let ids = list_participant_ids(pool, channel_id).await?;
     let mut users = Vec::with_capacity(ids.len());
     for id in ids {
@@ -53,7 +50,7 @@ pub async fn get_participant_users(
 
 /// Add a participant to a DM channel. No-op if already present.
 pub async fn add_participant(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     user_id: &str,
     is_postgres: bool,
@@ -74,7 +71,7 @@ pub async fn add_participant(
 
 /// Remove a participant from a DM channel.
 pub async fn remove_participant(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     user_id: &str,
 ) -> Result<(), AppError> {
@@ -88,9 +85,19 @@ pub async fn remove_participant(
     Ok(())
 }
 
+/// Hand a group DM to a new owner.
+pub async fn set_owner(pool: &DbPool, channel_id: &str, owner_id: &str) -> Result<(), AppError> {
+    sqlx::query(&super::q("UPDATE channels SET owner_id = ? WHERE id = ?"))
+        .bind(owner_id)
+        .bind(channel_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 /// Find an existing 1:1 DM channel between two users.
 pub async fn find_existing_dm(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_a: &str,
     user_b: &str,
 ) -> Result<Option<ChannelRow>, AppError> {
@@ -121,15 +128,21 @@ pub async fn find_existing_dm(
             topic: r.get("topic"),
             position: r.get("position"),
             parent_id: r.get("parent_id"),
-            nsfw: r.get("nsfw"),
+            nsfw: crate::db::get_bool(&r, "nsfw"),
             rate_limit: r.get("rate_limit"),
             bitrate: r.get("bitrate"),
             user_limit: r.get("user_limit"),
             owner_id: r.get("owner_id"),
             last_message_id: r.get("last_message_id"),
-            archived: r.get("archived"),
+            archived: crate::db::get_bool(&r, "archived"),
             auto_archive_after: r.get("auto_archive_after"),
             allow_anonymous_read: false,
+            announcement_locked: false,
+            voice_chat_retention: "keep".to_string(),
+            icon: None,
+            auto_thread: false,
+            auto_thread_name: None,
+            max_pins: None,
             created_at: r.get("created_at"),
         }
     }))
@@ -139,7 +152,7 @@ pub async fn find_existing_dm(
 /// channel if one already exists. For group DMs (multiple recipients), always
 /// creates a new channel.
 pub async fn create_dm_channel(
-    pool: &AnyPool,
+    pool: &DbPool,
     creator_id: &str,
     recipient_ids: &[String],
     is_postgres: bool,
@@ -186,7 +199,7 @@ pub async fn create_dm_channel(
 }
 
 /// Count the number of participants in a DM channel.
-pub async fn count_participants(pool: &AnyPool, channel_id: &str) -> Result<i64, AppError> {
+pub async fn count_participants(pool: &DbPool, channel_id: &str) -> Result<i64, AppError> {
     let count: i64 = sqlx::query_scalar(&super::q(
         "SELECT COUNT(*) FROM dm_participants WHERE channel_id = ?",
     ))
@@ -0,0 +1,114 @@
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::draft::Draft;
+
+fn to_draft(
+    (channel_id, content, reply_to, updated_at): (String, String, Option<String>, String),
+) -> Draft {
+    Draft {
+        channel_id,
+        content,
+        reply_to,
+        updated_at,
+    }
+}
+
+/// Save (or overwrite) the user's draft in a channel.
+pub async fn upsert_draft(
+    pool: &DbPool,
+    user_id: &str,
+    channel_id: &str,
+    content: &str,
+    reply_to: Option<&str>,
+    is_postgres: bool,
+) -> Result<Draft, AppError> {
+    let now_fn = super::now_sql(is_postgres);
+    sqlx::query(&super::q(&format!(
+        "INSERT INTO message_drafts (user_id, channel_id, content, reply_to) VALUES (?, ?, ?, ?)
+         ON CONFLICT (user_id, channel_id)
+         DO UPDATE SET content = excluded.content, reply_to = excluded.reply_to, updated_at = {now_fn}"
+    )))
+    .bind(user_id)
+    .bind(channel_id)
+    .bind(content)
+    .bind(reply_to)
+    .execute(pool)
+    .await?;
+
+    get_draft(pool, user_id, channel_id, None)
+        .await?
+        .ok_or_else(|| AppError::NotFound("unknown_draft".to_string()))
+}
+
+/// The user's draft in a channel, ignoring drafts last touched before
+/// [not_before] (they are due to be swept).
+pub async fn get_draft(
+    pool: &DbPool,
+    user_id: &str,
+    channel_id: &str,
+    not_before: Option<&str>,
+) -> Result<Option<Draft>, AppError> {
+    let row = sqlx::query_as::<_, (String, String, Option<String>, String)>(&super::q(
+        "SELECT channel_id, content, reply_to, updated_at FROM message_drafts
+         WHERE user_id = ? AND channel_id = ? AND updated_at >= ?",
+    ))
+    .bind(user_id)
+    .bind(channel_id)
+    .bind(not_before.unwrap_or(""))
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(to_draft))
+}
+
+/// Every live draft of the user, most recently edited first.
+pub async fn list_drafts(
+    pool: &DbPool,
+    user_id: &str,
+    not_before: &str,
+) -> Result<Vec<Draft>, AppError> {
+    let rows = sqlx::query_as::<_, (String, String, Option<String>, String)>(&super::q(
+        "SELECT channel_id, content, reply_to, updated_at FROM message_drafts
+         WHERE user_id = ? AND updated_at >= ? ORDER BY updated_at DESC",
+    ))
+    .bind(user_id)
+    .bind(not_before)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(to_draft).collect())
+}
+
+pub async fn count_drafts(pool: &DbPool, user_id: &str) -> Result<i64, AppError> {
+    let (count,): (i64,) = sqlx::query_as(&super::q(
+        "SELECT COUNT(*) FROM message_drafts WHERE user_id = ?",
+    ))
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(count)
+}
+
+/// Returns whether there was a draft to delete.
+pub async fn delete_draft(
+    pool: &DbPool,
+    user_id: &str,
+    channel_id: &str,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "DELETE FROM message_drafts WHERE user_id = ? AND channel_id = ?",
+    ))
+    .bind(user_id)
+    .bind(channel_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Delete drafts last touched before [cutoff]; returns how many went.
+pub async fn delete_expired_drafts(pool: &DbPool, cutoff: &str) -> Result<u64, AppError> {
+    let result = sqlx::query(&super::q("DELETE FROM message_drafts WHERE updated_at < ?"))
+        .bind(cutoff)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
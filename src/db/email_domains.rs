@@ -0,0 +1,52 @@
+use crate::db::DbPool;
+
+use crate::error::AppError;
+
+/// The email domains a space admits, alphabetically. Empty when the space
+/// doesn't restrict joins.
+pub async fn list_domains(pool: &DbPool, space_id: &str) -> Result<Vec<String>, AppError> {
+    let rows = sqlx::query_as::<_, (String,)>(&super::q(
+        "SELECT domain FROM space_email_domains WHERE space_id = ? ORDER BY domain ASC",
+    ))
+    .bind(space_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|r| r.0).collect())
+}
+
+/// Replace a space's allowed domains.
+pub async fn set_domains(
+    pool: &DbPool,
+    space_id: &str,
+    domains: &[String],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(&super::q(
+        "DELETE FROM space_email_domains WHERE space_id = ?",
+    ))
+    .bind(space_id)
+    .execute(&mut tx)
+    .await?;
+    for domain in domains {
+        sqlx::query(&super::q(
+            "INSERT INTO space_email_domains (space_id, domain) VALUES (?, ?)",
+        ))
+        .bind(space_id)
+        .bind(domain)
+        .execute(&mut tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// The user's email address, if they've verified it.
+pub async fn verified_email(pool: &DbPool, user_id: &str) -> Result<Option<String>, AppError> {
+    let row = sqlx::query_as::<_, (Option<String>,)>(&super::q(
+        "SELECT email FROM users WHERE id = ? AND email_verified = TRUE",
+    ))
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.and_then(|r| r.0))
+}
@@ -0,0 +1,117 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::middleware::auth::{create_token_hash, generate_token};
+
+pub const KIND_PASSWORD_RESET: &str = "password_reset";
+pub const KIND_VERIFY_EMAIL: &str = "verify_email";
+
+fn timestamp_in(secs: i64) -> String {
+    (chrono::Utc::now() + chrono::Duration::seconds(secs))
+        .format("%Y-%m-%dT%H:%M:%S+00:00")
+        .to_string()
+}
+
+/// Issue a code of `kind` for `user_id`, sent to `email` and valid for
+/// `ttl_secs`. Replaces any earlier code of the same kind. Only the hash is
+/// stored.
+pub async fn create_token(
+    pool: &DbPool,
+    user_id: &str,
+    kind: &str,
+    email: &str,
+    ttl_secs: i64,
+) -> Result<String, AppError> {
+    let token = generate_token();
+    let mut tx = pool.begin().await?;
+    sqlx::query(&super::q(
+        "DELETE FROM email_tokens WHERE user_id = ? AND kind = ?",
+    ))
+    .bind(user_id)
+    .bind(kind)
+    .execute(&mut tx)
+    .await?;
+    sqlx::query(&super::q(
+        "INSERT INTO email_tokens (token_hash, user_id, kind, email, expires_at, created_at) \
+         VALUES (?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(create_token_hash(&token))
+    .bind(user_id)
+    .bind(kind)
+    .bind(email)
+    .bind(timestamp_in(ttl_secs))
+    .bind(timestamp_in(0))
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    Ok(token)
+}
+
+/// Whether a code of `kind` was issued to `user_id` in the last `secs`.
+pub async fn issued_within(
+    pool: &DbPool,
+    user_id: &str,
+    kind: &str,
+    secs: i64,
+) -> Result<bool, AppError> {
+    let count: i64 = sqlx::query_scalar(&super::q(
+        "SELECT COUNT(*) FROM email_tokens WHERE user_id = ? AND kind = ? AND created_at > ?",
+    ))
+    .bind(user_id)
+    .bind(kind)
+    .bind(timestamp_in(-secs))
+    .fetch_one(pool)
+    .await?;
+    Ok(count > 0)
+}
+
+/// Redeem a code of `kind`. Returns `(user_id, email)`, or `None` if the code
+/// is unknown, expired or already used.
+pub async fn consume_token(
+    pool: &DbPool,
+    token: &str,
+    kind: &str,
+) -> Result<Option<(String, String)>, AppError> {
+    let token_hash = create_token_hash(token);
+    let Some(row) = sqlx::query(&super::q(
+        "SELECT user_id, email FROM email_tokens \
+         WHERE token_hash = ? AND kind = ? AND expires_at > ?",
+    ))
+    .bind(&token_hash)
+    .bind(kind)
+    .bind(timestamp_in(0))
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    // Only whoever deletes the row gets to use it.
+    let deleted = sqlx::query(&super::q("DELETE FROM email_tokens WHERE token_hash = ?"))
+        .bind(&token_hash)
+        .execute(pool)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Ok(None);
+    }
+    Ok(Some((row.get("user_id"), row.get("email"))))
+}
+
+/// Mark `user_id`'s email verified, provided it is still `email`. Returns
+/// whether it was.
+pub async fn mark_email_verified(
+    pool: &DbPool,
+    user_id: &str,
+    email: &str,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "UPDATE users SET email_verified = TRUE WHERE id = ? AND email = ?",
+    ))
+    .bind(user_id)
+    .bind(email)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
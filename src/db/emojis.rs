@@ -1,4 +1,6 @@
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::models::emoji::{CreateEmoji, Emoji, UpdateEmoji};
@@ -15,12 +17,13 @@ fn row_to_emoji(row: sqlx::any::AnyRow, role_ids: Vec<String>) -> Emoji {
         role_ids,
         creator_id: row.get("creator_id"),
         image_url: row.get("image_path"),
+        pending: crate::db::get_bool(&row, "pending"),
     }
 }
 
 /// Verify an emoji belongs to the given space. Returns an error if it doesn't.
 pub async fn require_emoji_in_space(
-    pool: &AnyPool,
+    pool: &DbPool,
     emoji_id: &str,
     space_id: &str,
 ) -> Result<(), AppError> {
@@ -38,9 +41,9 @@ pub async fn require_emoji_in_space(
     }
 }
 
-pub async fn get_emoji(pool: &AnyPool, emoji_id: &str) -> Result<Emoji, AppError> {
+pub async fn get_emoji(pool: &DbPool, emoji_id: &str) -> Result<Emoji, AppError> {
     let row = sqlx::query(
-        &super::q("SELECT id, name, animated, managed, available, require_colons, creator_id, image_path FROM emojis WHERE id = ?")
+        &super::q("SELECT id, name, animated, managed, available, require_colons, creator_id, image_path, pending FROM emojis WHERE id = ?")
     )
     .bind(emoji_id)
     .fetch_optional(pool)
@@ -60,11 +63,26 @@ pub async fn get_emoji(pool: &AnyPool, emoji_id: &str) -> Result<Emoji, AppError
     Ok(row_to_emoji(row, role_ids))
 }
 
-pub async fn list_emojis(pool: &AnyPool, space_id: &str) -> Result<Vec<Emoji>, AppError> {
+/// Approved emoji in a space.
+pub async fn list_emojis(pool: &DbPool, space_id: &str) -> Result<Vec<Emoji>, AppError> {
+    list_emojis_by_status(pool, space_id, false).await
+}
+
+/// Emoji awaiting moderator approval in a space.
+pub async fn list_pending_emojis(pool: &DbPool, space_id: &str) -> Result<Vec<Emoji>, AppError> {
+    list_emojis_by_status(pool, space_id, true).await
+}
+
+async fn list_emojis_by_status(
+    pool: &DbPool,
+    space_id: &str,
+    pending: bool,
+) -> Result<Vec<Emoji>, AppError> {
     let rows = sqlx::query(
-        &super::q("SELECT id, name, animated, managed, available, require_colons, creator_id, image_path FROM emojis WHERE space_id = ?")
+        &super::q("SELECT id, name, animated, managed, available, require_colons, creator_id, image_path, pending FROM emojis WHERE space_id = ? AND pending = ?")
     )
     .bind(space_id)
+    .bind(pending)
     .fetch_all(pool)
     .await?;
 
@@ -86,9 +104,22 @@ pub async fn list_emojis(pool: &AnyPool, space_id: &str) -> Result<Vec<Emoji>, A
     Ok(emojis)
 }
 
+/// Emoji taking up one of the space's slots. Pending uploads don't count
+/// until approved.
+pub async fn count_emojis(pool: &DbPool, space_id: &str) -> Result<i64, AppError> {
+    let count: i64 = sqlx::query_scalar(&super::q(
+        "SELECT COUNT(*) FROM emojis WHERE space_id = ? AND pending = ?",
+    ))
+    .bind(space_id)
+    .bind(false)
+    .fetch_one(pool)
+    .await?;
+    Ok(count)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn create_emoji(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     creator_id: &str,
     input: &CreateEmoji,
@@ -96,11 +127,12 @@ pub async fn create_emoji(
     image_content_type: Option<&str>,
     image_size: Option<usize>,
     animated: bool,
+    pending: bool,
 ) -> Result<Emoji, AppError> {
     let id = snowflake::generate();
 
     sqlx::query(
-        &super::q("INSERT INTO emojis (id, space_id, name, creator_id, animated, image_path, image_content_type, image_size) VALUES (?, ?, ?, ?, ?, ?, ?, ?)")
+        &super::q("INSERT INTO emojis (id, space_id, name, creator_id, animated, image_path, image_content_type, image_size, pending) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)")
     )
     .bind(&id)
     .bind(space_id)
@@ -110,12 +142,30 @@ pub async fn create_emoji(
     .bind(image_path)
     .bind(image_content_type)
     .bind(image_size.map(|s| s as i64))
+    .bind(pending)
     .execute(pool)
     .await?;
 
     get_emoji(pool, &id).await
 }
 
+/// Move a pending emoji into the space's emoji list.
+pub async fn approve_emoji(
+    pool: &DbPool,
+    emoji_id: &str,
+    is_postgres: bool,
+) -> Result<Emoji, AppError> {
+    let now_fn = crate::db::now_sql(is_postgres);
+    sqlx::query(&super::q(&format!(
+        "UPDATE emojis SET pending = ?, updated_at = {now_fn} WHERE id = ?"
+    )))
+    .bind(false)
+    .bind(emoji_id)
+    .execute(pool)
+    .await?;
+    get_emoji(pool, emoji_id).await
+}
+
 /// Returns the emoji ID (for use in generating the ID). Used by the route
 /// to get the snowflake before saving the file.
 pub fn generate_emoji_id() -> String {
@@ -123,7 +173,7 @@ pub fn generate_emoji_id() -> String {
 }
 
 pub async fn update_emoji(
-    pool: &AnyPool,
+    pool: &DbPool,
     emoji_id: &str,
     input: &UpdateEmoji,
     is_postgres: bool,
@@ -143,7 +193,7 @@ pub async fn update_emoji(
 /// The home domain of an emoji, or `None` if it is locally homed. Used by the
 /// inbound applier to confirm a delete only ever touches a replica row homed on
 /// the signing peer (S2).
-pub async fn emoji_origin(pool: &AnyPool, emoji_id: &str) -> Result<Option<String>, AppError> {
+pub async fn emoji_origin(pool: &DbPool, emoji_id: &str) -> Result<Option<String>, AppError> {
     let row = sqlx::query(&super::q("SELECT origin FROM emojis WHERE id = ?"))
         .bind(emoji_id)
         .fetch_optional(pool)
@@ -151,7 +201,7 @@ pub async fn emoji_origin(pool: &AnyPool, emoji_id: &str) -> Result<Option<Strin
     Ok(row.and_then(|r| r.try_get::<String, _>("origin").ok()))
 }
 
-pub async fn emoji_space_id(pool: &AnyPool, emoji_id: &str) -> Result<Option<String>, AppError> {
+pub async fn emoji_space_id(pool: &DbPool, emoji_id: &str) -> Result<Option<String>, AppError> {
     let row = sqlx::query(&super::q("SELECT space_id FROM emojis WHERE id = ?"))
         .bind(emoji_id)
         .fetch_optional(pool)
@@ -166,7 +216,7 @@ pub async fn emoji_space_id(pool: &AnyPool, emoji_id: &str) -> Result<Option<Str
 /// silently dropped from the restriction set.
 #[allow(clippy::too_many_arguments)]
 pub async fn upsert_remote_emoji(
-    pool: &AnyPool,
+    pool: &DbPool,
     id: &str,
     origin: &str,
     space_id: &str,
@@ -213,7 +263,7 @@ pub async fn upsert_remote_emoji(
 }
 
 /// Delete an emoji. Returns the image_path for file cleanup.
-pub async fn delete_emoji(pool: &AnyPool, emoji_id: &str) -> Result<Option<String>, AppError> {
+pub async fn delete_emoji(pool: &DbPool, emoji_id: &str) -> Result<Option<String>, AppError> {
     let image_path: Option<String> =
         sqlx::query_scalar(&super::q("SELECT image_path FROM emojis WHERE id = ?"))
             .bind(emoji_id)
@@ -0,0 +1,297 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::event_subscription::{EventDelivery, EventSubscription};
+use crate::snowflake;
+
+/// Timestamps here share the `created_at` column format so they compare as
+/// strings on both backends.
+pub fn timestamp_in(secs: i64) -> String {
+    (chrono::Utc::now() + chrono::Duration::seconds(secs))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+const SELECT_SUBSCRIPTIONS: &str = "SELECT id, application_id, url, event_types, space_ids, enabled, created_at FROM event_subscriptions";
+
+fn row_to_subscription(row: &sqlx::any::AnyRow) -> EventSubscription {
+    let list = |col: &str| -> Vec<String> {
+        row.try_get::<String, _>(col)
+            .ok()
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default()
+    };
+    EventSubscription {
+        id: row.get("id"),
+        application_id: row.get("application_id"),
+        url: row.get("url"),
+        event_types: list("event_types"),
+        space_ids: list("space_ids"),
+        enabled: crate::db::get_bool(row, "enabled"),
+        created_at: row.get("created_at"),
+    }
+}
+
+pub async fn create_subscription(
+    pool: &DbPool,
+    application_id: &str,
+    url: &str,
+    event_types: &[String],
+    space_ids: &[String],
+) -> Result<EventSubscription, AppError> {
+    let id = snowflake::generate();
+    sqlx::query(&super::q(
+        "INSERT INTO event_subscriptions (id, application_id, url, event_types, space_ids) \
+         VALUES (?, ?, ?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(application_id)
+    .bind(url)
+    .bind(serde_json::to_string(event_types).unwrap_or_else(|_| "[]".into()))
+    .bind(serde_json::to_string(space_ids).unwrap_or_else(|_| "[]".into()))
+    .execute(pool)
+    .await?;
+    get_subscription(pool, &id).await
+}
+
+pub async fn get_subscription(pool: &DbPool, id: &str) -> Result<EventSubscription, AppError> {
+    let row = sqlx::query(&super::q(&format!("{SELECT_SUBSCRIPTIONS} WHERE id = ?")))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("unknown_event_subscription".to_string()))?;
+    Ok(row_to_subscription(&row))
+}
+
+pub async fn list_subscriptions(
+    pool: &DbPool,
+    application_id: &str,
+) -> Result<Vec<EventSubscription>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_SUBSCRIPTIONS} WHERE application_id = ? ORDER BY id ASC"
+    )))
+    .bind(application_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(row_to_subscription).collect())
+}
+
+/// Enabled subscriptions to `event_type`, with the subscribing
+/// application's bot user.
+pub async fn subscribers(
+    pool: &DbPool,
+    event_type: &str,
+) -> Result<Vec<(EventSubscription, Option<String>)>, AppError> {
+    let rows = sqlx::query(&super::q(
+        "SELECT s.id, s.application_id, s.url, s.event_types, s.space_ids, s.enabled, \
+         s.created_at, a.bot_user_id FROM event_subscriptions s \
+         JOIN applications a ON a.id = s.application_id \
+         WHERE s.enabled = ? AND s.event_types LIKE ?",
+    ))
+    .bind(true)
+    .bind(format!("%\"{event_type}\"%"))
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let bot_user_id: Option<String> = row.try_get("bot_user_id").ok().flatten();
+            (row_to_subscription(row), bot_user_id)
+        })
+        .filter(|(sub, _)| sub.event_types.iter().any(|t| t == event_type))
+        .collect())
+}
+
+pub async fn update_subscription(pool: &DbPool, sub: &EventSubscription) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "UPDATE event_subscriptions SET url = ?, event_types = ?, space_ids = ?, enabled = ? \
+         WHERE id = ?",
+    ))
+    .bind(&sub.url)
+    .bind(serde_json::to_string(&sub.event_types).unwrap_or_else(|_| "[]".into()))
+    .bind(serde_json::to_string(&sub.space_ids).unwrap_or_else(|_| "[]".into()))
+    .bind(sub.enabled)
+    .bind(&sub.id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_subscription(pool: &DbPool, id: &str) -> Result<(), AppError> {
+    sqlx::query(&super::q("DELETE FROM event_subscriptions WHERE id = ?"))
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// =========================================================================
+// Deliveries
+// =========================================================================
+
+const SELECT_DELIVERIES: &str = "SELECT id, subscription_id, event_type, payload, status, attempts, next_attempt_at, last_status, last_error, created_at, delivered_at FROM event_deliveries";
+
+fn row_to_delivery(row: &sqlx::any::AnyRow) -> EventDelivery {
+    EventDelivery {
+        id: row.get("id"),
+        subscription_id: row.get("subscription_id"),
+        event_type: row.get("event_type"),
+        status: row.get("status"),
+        attempts: row.get("attempts"),
+        next_attempt_at: row.try_get("next_attempt_at").ok().flatten(),
+        last_status: row.try_get("last_status").ok().flatten(),
+        last_error: row.try_get("last_error").ok().flatten(),
+        created_at: row.get("created_at"),
+        delivered_at: row.try_get("delivered_at").ok().flatten(),
+        payload: row
+            .try_get::<String, _>("payload")
+            .ok()
+            .and_then(|p| serde_json::from_str(&p).ok())
+            .unwrap_or_default(),
+    }
+}
+
+/// Queue `payload` for delivery now.
+pub async fn enqueue_delivery(
+    pool: &DbPool,
+    id: &str,
+    subscription_id: &str,
+    event_type: &str,
+    payload: &serde_json::Value,
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "INSERT INTO event_deliveries (id, subscription_id, event_type, payload, next_attempt_at) \
+         VALUES (?, ?, ?, ?, ?)",
+    ))
+    .bind(id)
+    .bind(subscription_id)
+    .bind(event_type)
+    .bind(payload.to_string())
+    .bind(timestamp_in(0))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Pending deliveries whose next attempt is due, oldest first.
+pub async fn due_deliveries(pool: &DbPool, limit: i64) -> Result<Vec<EventDelivery>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_DELIVERIES} WHERE status = 'pending' AND next_attempt_at <= ? \
+         ORDER BY next_attempt_at ASC, id ASC LIMIT ?"
+    )))
+    .bind(timestamp_in(0))
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(row_to_delivery).collect())
+}
+
+/// Push a due delivery's next attempt out to `lease_until` so no other node
+/// picks it up while this one is sending. Returns whether this call won it.
+pub async fn claim_delivery(
+    pool: &DbPool,
+    delivery: &EventDelivery,
+    lease_until: &str,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "UPDATE event_deliveries SET next_attempt_at = ? \
+         WHERE id = ? AND status = 'pending' AND next_attempt_at = ?",
+    ))
+    .bind(lease_until)
+    .bind(&delivery.id)
+    .bind(&delivery.next_attempt_at)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Record an attempt. `next_attempt_at` is `None` once the delivery is done:
+/// `succeeded`, or `failed` after its last attempt.
+pub async fn record_attempt(
+    pool: &DbPool,
+    id: &str,
+    status: &str,
+    attempts: i64,
+    next_attempt_at: Option<&str>,
+    last_status: Option<i64>,
+    last_error: Option<&str>,
+) -> Result<(), AppError> {
+    let delivered_at = (status == "succeeded").then(|| timestamp_in(0));
+    sqlx::query(&super::q(
+        "UPDATE event_deliveries SET status = ?, attempts = ?, next_attempt_at = ?, \
+         last_status = ?, last_error = ?, delivered_at = ? WHERE id = ?",
+    ))
+    .bind(status)
+    .bind(attempts)
+    .bind(next_attempt_at)
+    .bind(last_status)
+    .bind(last_error)
+    .bind(delivered_at)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// A subscription's delivery log, newest first.
+pub async fn list_deliveries(
+    pool: &DbPool,
+    subscription_id: &str,
+    status: Option<&str>,
+    before: Option<&str>,
+    limit: i64,
+) -> Result<Vec<EventDelivery>, AppError> {
+    let mut sql = format!("{SELECT_DELIVERIES} WHERE subscription_id = ?");
+    if status.is_some() {
+        sql.push_str(" AND status = ?");
+    }
+    if before.is_some() {
+        sql.push_str(" AND id < ?");
+    }
+    sql.push_str(" ORDER BY id DESC LIMIT ?");
+    let sql = super::q(&sql);
+    let mut query = sqlx::query(&sql).bind(subscription_id);
+    if let Some(status) = status {
+        query = query.bind(status);
+    }
+    if let Some(before) = before {
+        query = query.bind(before);
+    }
+    let rows = query.bind(limit).fetch_all(pool).await?;
+    Ok(rows.iter().map(row_to_delivery).collect())
+}
+
+pub async fn get_delivery(pool: &DbPool, id: &str) -> Result<EventDelivery, AppError> {
+    let row = sqlx::query(&super::q(&format!("{SELECT_DELIVERIES} WHERE id = ?")))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("unknown_event_delivery".to_string()))?;
+    Ok(row_to_delivery(&row))
+}
+
+/// Send a delivery again from scratch, whatever became of it.
+pub async fn redeliver(pool: &DbPool, id: &str) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "UPDATE event_deliveries SET status = 'pending', attempts = 0, next_attempt_at = ?, \
+         delivered_at = NULL WHERE id = ?",
+    ))
+    .bind(timestamp_in(0))
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Delete finished deliveries created before `before`.
+pub async fn prune_deliveries(pool: &DbPool, before: &str) -> Result<u64, AppError> {
+    let result = sqlx::query(&super::q(
+        "DELETE FROM event_deliveries WHERE status <> 'pending' AND created_at < ?",
+    ))
+    .bind(before)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
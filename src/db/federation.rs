@@ -1,9 +1,12 @@
 //! Database access for federation state: known peers, inbound dedup, and the
 //! durable outbound delivery queue.
 
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
+use crate::models::permission::Permissions;
 
 /// Timestamp format shared by all federation columns: UTC `YYYY-MM-DD HH:MM:SS`,
 /// matching [`crate::db::now_sql`] so lexicographic comparison against the
@@ -41,7 +44,7 @@ fn row_to_peer(row: sqlx::any::AnyRow) -> Peer {
     }
 }
 
-pub async fn get_peer(pool: &AnyPool, domain: &str) -> Result<Option<Peer>, AppError> {
+pub async fn get_peer(pool: &DbPool, domain: &str) -> Result<Option<Peer>, AppError> {
     let row = sqlx::query(&crate::db::q(
         "SELECT domain, public_key, inbox_url, trust_state FROM federation_peers WHERE domain = ?",
     ))
@@ -51,7 +54,7 @@ pub async fn get_peer(pool: &AnyPool, domain: &str) -> Result<Option<Peer>, AppE
     Ok(row.map(row_to_peer))
 }
 
-pub async fn list_peers(pool: &AnyPool) -> Result<Vec<Peer>, AppError> {
+pub async fn list_peers(pool: &DbPool) -> Result<Vec<Peer>, AppError> {
     let rows = sqlx::query(&crate::db::q(
         "SELECT domain, public_key, inbox_url, trust_state FROM federation_peers ORDER BY domain",
     ))
@@ -65,7 +68,7 @@ pub async fn list_peers(pool: &AnyPool) -> Result<Vec<Peer>, AppError> {
 /// so re-fetching a peer's `.well-known` can refresh its key without silently
 /// re-granting trust.
 pub async fn upsert_peer(
-    pool: &AnyPool,
+    pool: &DbPool,
     domain: &str,
     public_key: &str,
     inbox_url: &str,
@@ -88,7 +91,7 @@ pub async fn upsert_peer(
 }
 
 pub async fn set_peer_trust(
-    pool: &AnyPool,
+    pool: &DbPool,
     domain: &str,
     trust_state: &str,
 ) -> Result<(), AppError> {
@@ -102,7 +105,7 @@ pub async fn set_peer_trust(
     Ok(())
 }
 
-pub async fn delete_peer(pool: &AnyPool, domain: &str) -> Result<(), AppError> {
+pub async fn delete_peer(pool: &DbPool, domain: &str) -> Result<(), AppError> {
     sqlx::query(&crate::db::q(
         "DELETE FROM federation_peers WHERE domain = ?",
     ))
@@ -116,7 +119,7 @@ pub async fn delete_peer(pool: &AnyPool, domain: &str) -> Result<(), AppError> {
 /// first time we have seen `(event_id, origin)` (i.e. the caller should apply
 /// it), `false` if it is a duplicate that should be skipped.
 pub async fn dedup_first_seen(
-    pool: &AnyPool,
+    pool: &DbPool,
     event_id: &str,
     origin: &str,
 ) -> Result<bool, AppError> {
@@ -137,7 +140,7 @@ pub async fn dedup_first_seen(
 /// Called when an inbound event passed dedup but its apply step failed: without
 /// this the retry would be silently acknowledged as a duplicate and the event
 /// lost.
-pub async fn dedup_remove(pool: &AnyPool, event_id: &str, origin: &str) -> Result<(), AppError> {
+pub async fn dedup_remove(pool: &DbPool, event_id: &str, origin: &str) -> Result<(), AppError> {
     sqlx::query(&crate::db::q(
         "DELETE FROM federation_inbox_dedup WHERE event_id = ? AND origin = ?",
     ))
@@ -156,7 +159,7 @@ pub async fn dedup_remove(pool: &AnyPool, event_id: &str, origin: &str) -> Resul
 /// Mirror a remote space. `owner_id` (a qualified remote user ID) must already
 /// be upserted via [`crate::db::users::upsert_remote_user`] to satisfy the FK.
 pub async fn upsert_remote_space(
-    pool: &AnyPool,
+    pool: &DbPool,
     id: &str,
     origin: &str,
     name: &str,
@@ -187,7 +190,7 @@ pub async fn upsert_remote_space(
 
 /// Mirror a remote channel.
 pub async fn upsert_remote_channel(
-    pool: &AnyPool,
+    pool: &DbPool,
     id: &str,
     origin: &str,
     space_id: &str,
@@ -212,23 +215,23 @@ pub async fn upsert_remote_channel(
 
 /// Mirror a remote role.
 pub async fn upsert_remote_role(
-    pool: &AnyPool,
+    pool: &DbPool,
     id: &str,
     origin: &str,
     space_id: &str,
     name: &str,
     position: i64,
-    permissions_json: &str,
+    permissions: Permissions,
 ) -> Result<(), AppError> {
     sqlx::query(&crate::db::q(
-        "INSERT INTO roles (id, space_id, name, position, permissions, origin) VALUES (?, ?, ?, ?, ?, ?) \
-         ON CONFLICT (id) DO UPDATE SET name = excluded.name, position = excluded.position, permissions = excluded.permissions, origin = excluded.origin",
+        "INSERT INTO roles (id, space_id, name, position, permission_bits, origin) VALUES (?, ?, ?, ?, ?, ?) \
+         ON CONFLICT (id) DO UPDATE SET name = excluded.name, position = excluded.position, permission_bits = excluded.permission_bits, origin = excluded.origin",
     ))
     .bind(id)
     .bind(space_id)
     .bind(name)
     .bind(position)
-    .bind(permissions_json)
+    .bind(permissions.bits())
     .bind(origin)
     .execute(pool)
     .await?;
@@ -239,7 +242,7 @@ pub async fn upsert_remote_role(
 /// `origin`. `origin = NULL` for a local user joining a remote space; the home
 /// domain for a remote user mirrored into a local space.
 pub async fn add_member_with_origin(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
     origin: Option<&str>,
@@ -258,7 +261,7 @@ pub async fn add_member_with_origin(
 /// The home domain of a space, or `None` if it is locally homed (the common
 /// case). Used to decide whether we are authoritative for a write (local) or
 /// must forward it to the home server (remote).
-pub async fn space_origin(pool: &AnyPool, space_id: &str) -> Result<Option<String>, AppError> {
+pub async fn space_origin(pool: &DbPool, space_id: &str) -> Result<Option<String>, AppError> {
     let row = sqlx::query(&crate::db::q("SELECT origin FROM spaces WHERE id = ?"))
         .bind(space_id)
         .fetch_optional(pool)
@@ -269,7 +272,7 @@ pub async fn space_origin(pool: &AnyPool, space_id: &str) -> Result<Option<Strin
 /// The home domain of a channel, or `None` if it is locally homed. The DM
 /// analogue of [`space_origin`]: DM channels have no space, so federation
 /// routing for them keys on the channel's own `origin`.
-pub async fn channel_origin(pool: &AnyPool, channel_id: &str) -> Result<Option<String>, AppError> {
+pub async fn channel_origin(pool: &DbPool, channel_id: &str) -> Result<Option<String>, AppError> {
     let row = sqlx::query(&crate::db::q("SELECT origin FROM channels WHERE id = ?"))
         .bind(channel_id)
         .fetch_optional(pool)
@@ -280,7 +283,7 @@ pub async fn channel_origin(pool: &AnyPool, channel_id: &str) -> Result<Option<S
 /// Mirror a remote DM channel (`origin = <home domain>`). Idempotent on the
 /// qualified channel ID. `owner_id` must already be upserted to satisfy the FK.
 pub async fn upsert_remote_dm_channel(
-    pool: &AnyPool,
+    pool: &DbPool,
     id: &str,
     origin: &str,
     channel_type: &str,
@@ -302,7 +305,7 @@ pub async fn upsert_remote_dm_channel(
 
 /// Whether a space has opted in to federation (S9). The home server refuses
 /// join handshakes for spaces with this off.
-pub async fn space_federation_enabled(pool: &AnyPool, space_id: &str) -> Result<bool, AppError> {
+pub async fn space_federation_enabled(pool: &DbPool, space_id: &str) -> Result<bool, AppError> {
     let row = sqlx::query(&crate::db::q(
         "SELECT federation_enabled FROM spaces WHERE id = ?",
     ))
@@ -316,7 +319,7 @@ pub async fn space_federation_enabled(pool: &AnyPool, space_id: &str) -> Result<
 
 /// Enable/disable federation for a locally-homed space.
 pub async fn set_space_federation_enabled(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     enabled: bool,
 ) -> Result<(), AppError> {
@@ -333,7 +336,7 @@ pub async fn set_space_federation_enabled(
 /// The set of peer domains "interested" in a space: the distinct `origin`s of
 /// its members. Used to fan out events for a locally-homed space to exactly the
 /// servers that have a member there.
-pub async fn interested_servers(pool: &AnyPool, space_id: &str) -> Result<Vec<String>, AppError> {
+pub async fn interested_servers(pool: &DbPool, space_id: &str) -> Result<Vec<String>, AppError> {
     let rows = sqlx::query(&crate::db::q(
         "SELECT DISTINCT origin FROM members WHERE space_id = ? AND origin IS NOT NULL",
     ))
@@ -348,7 +351,7 @@ pub async fn interested_servers(pool: &AnyPool, space_id: &str) -> Result<Vec<St
 
 /// Delete inbound dedup rows older than `older_than_secs` to bound table growth
 /// (S3). The retention window must comfortably exceed any peer's retry horizon.
-pub async fn cleanup_dedup(pool: &AnyPool, older_than_secs: i64) -> Result<u64, AppError> {
+pub async fn cleanup_dedup(pool: &DbPool, older_than_secs: i64) -> Result<u64, AppError> {
     let cutoff = (chrono::Utc::now() - chrono::Duration::seconds(older_than_secs))
         .format("%Y-%m-%d %H:%M:%S")
         .to_string();
@@ -371,7 +374,7 @@ pub struct OutboxItem {
 }
 
 pub async fn outbox_enqueue(
-    pool: &AnyPool,
+    pool: &DbPool,
     id: &str,
     target_domain: &str,
     payload: &str,
@@ -392,7 +395,7 @@ pub async fn outbox_enqueue(
 }
 
 /// Fetch up to `limit` deliveries whose `next_attempt_at` is due.
-pub async fn outbox_claim_due(pool: &AnyPool, limit: i64) -> Result<Vec<OutboxItem>, AppError> {
+pub async fn outbox_claim_due(pool: &DbPool, limit: i64) -> Result<Vec<OutboxItem>, AppError> {
     let now = crate::db::now_sql(crate::db::is_pg());
     let sql = crate::db::q(&format!(
         "SELECT id, target_domain, payload, attempts FROM federation_outbox \
@@ -410,7 +413,7 @@ pub async fn outbox_claim_due(pool: &AnyPool, limit: i64) -> Result<Vec<OutboxIt
         .collect())
 }
 
-pub async fn outbox_delete(pool: &AnyPool, id: &str) -> Result<(), AppError> {
+pub async fn outbox_delete(pool: &DbPool, id: &str) -> Result<(), AppError> {
     sqlx::query(&crate::db::q("DELETE FROM federation_outbox WHERE id = ?"))
         .bind(id)
         .execute(pool)
@@ -420,7 +423,7 @@ pub async fn outbox_delete(pool: &AnyPool, id: &str) -> Result<(), AppError> {
 
 /// Bump the attempt count and schedule the next retry `delay_secs` from now.
 pub async fn outbox_reschedule(
-    pool: &AnyPool,
+    pool: &DbPool,
     id: &str,
     attempts: i64,
     delay_secs: i64,
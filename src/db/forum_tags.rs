@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::forum::{CreateForumTag, ForumTag, UpdateForumTag};
+use crate::snowflake;
+
+fn row_to_tag(row: sqlx::any::AnyRow) -> ForumTag {
+    ForumTag {
+        id: row.get("id"),
+        channel_id: row.get("channel_id"),
+        name: row.get("name"),
+        emoji: row.get("emoji"),
+        moderated: crate::db::get_bool(&row, "moderated"),
+        position: row.get("position"),
+        created_at: row.get("created_at"),
+    }
+}
+
+const SELECT_TAGS: &str =
+    "SELECT id, channel_id, name, emoji, moderated, position, created_at FROM forum_tags";
+
+/// A forum's tags in display order.
+pub async fn list_tags(pool: &DbPool, channel_id: &str) -> Result<Vec<ForumTag>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_TAGS} WHERE channel_id = ? ORDER BY position, id"
+    )))
+    .bind(channel_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(row_to_tag).collect())
+}
+
+/// Load a tag, checking it belongs to `channel_id`.
+pub async fn get_tag(pool: &DbPool, channel_id: &str, tag_id: &str) -> Result<ForumTag, AppError> {
+    let row = sqlx::query(&super::q(&format!(
+        "{SELECT_TAGS} WHERE id = ? AND channel_id = ?"
+    )))
+    .bind(tag_id)
+    .bind(channel_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("unknown_tag".to_string()))?;
+    Ok(row_to_tag(row))
+}
+
+pub async fn count_tags(pool: &DbPool, channel_id: &str) -> Result<i64, AppError> {
+    let count: i64 = sqlx::query_scalar(&super::q(
+        "SELECT COUNT(*) FROM forum_tags WHERE channel_id = ?",
+    ))
+    .bind(channel_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(count)
+}
+
+pub async fn create_tag(
+    pool: &DbPool,
+    channel_id: &str,
+    input: &CreateForumTag,
+) -> Result<ForumTag, AppError> {
+    let id = snowflake::generate();
+    sqlx::query(&super::q(
+        "INSERT INTO forum_tags (id, channel_id, name, emoji, moderated, position) VALUES (?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(channel_id)
+    .bind(input.name.trim())
+    .bind(input.emoji.as_deref().filter(|e| !e.is_empty()))
+    .bind(input.moderated.unwrap_or(false))
+    .bind(input.position.unwrap_or(0))
+    .execute(pool)
+    .await?;
+    get_tag(pool, channel_id, &id).await
+}
+
+pub async fn update_tag(
+    pool: &DbPool,
+    channel_id: &str,
+    tag_id: &str,
+    input: &UpdateForumTag,
+) -> Result<ForumTag, AppError> {
+    let mut sets = Vec::new();
+    if input.name.is_some() {
+        sets.push("name = ?");
+    }
+    if input.emoji.is_some() {
+        sets.push("emoji = ?");
+    }
+    if input.moderated.is_some() {
+        sets.push("moderated = ?");
+    }
+    if input.position.is_some() {
+        sets.push("position = ?");
+    }
+    if sets.is_empty() {
+        return get_tag(pool, channel_id, tag_id).await;
+    }
+
+    let sql = super::q(&format!(
+        "UPDATE forum_tags SET {} WHERE id = ? AND channel_id = ?",
+        sets.join(", ")
+    ));
+    let mut query = sqlx::query(&sql);
+    if let Some(ref name) = input.name {
+        query = query.bind(name.trim().to_string());
+    }
+    if let Some(ref emoji) = input.emoji {
+        query = query.bind((!emoji.is_empty()).then(|| emoji.clone()));
+    }
+    if let Some(moderated) = input.moderated {
+        query = query.bind(moderated);
+    }
+    if let Some(position) = input.position {
+        query = query.bind(position);
+    }
+    query.bind(tag_id).bind(channel_id).execute(pool).await?;
+    get_tag(pool, channel_id, tag_id).await
+}
+
+/// Delete a tag; posts that carried it lose it.
+pub async fn delete_tag(pool: &DbPool, tag_id: &str) -> Result<(), AppError> {
+    sqlx::query(&super::q("DELETE FROM thread_tags WHERE tag_id = ?"))
+        .bind(tag_id)
+        .execute(pool)
+        .await?;
+    sqlx::query(&super::q("DELETE FROM forum_tags WHERE id = ?"))
+        .bind(tag_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Replace the tags applied to a post.
+pub async fn set_thread_tags(
+    pool: &DbPool,
+    thread_id: &str,
+    tag_ids: &[String],
+) -> Result<(), AppError> {
+    sqlx::query(&super::q("DELETE FROM thread_tags WHERE thread_id = ?"))
+        .bind(thread_id)
+        .execute(pool)
+        .await?;
+    for tag_id in tag_ids {
+        sqlx::query(&super::q(
+            "INSERT INTO thread_tags (thread_id, tag_id) VALUES (?, ?)",
+        ))
+        .bind(thread_id)
+        .bind(tag_id)
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Tag IDs applied to each of the given threads, in the forum's tag order.
+pub async fn applied_tag_ids(
+    pool: &DbPool,
+    thread_ids: &[String],
+) -> Result<HashMap<String, Vec<String>>, AppError> {
+    let mut result: HashMap<String, Vec<String>> = HashMap::new();
+    if thread_ids.is_empty() {
+        return Ok(result);
+    }
+    let placeholders = vec!["?"; thread_ids.len()].join(", ");
+    let sql = super::q(&format!(
+        "SELECT tt.thread_id, tt.tag_id FROM thread_tags tt \
+         JOIN forum_tags ft ON ft.id = tt.tag_id \
+         WHERE tt.thread_id IN ({placeholders}) ORDER BY ft.position, ft.id"
+    ));
+    let mut query = sqlx::query(&sql);
+    for id in thread_ids {
+        query = query.bind(id);
+    }
+    for row in query.fetch_all(pool).await? {
+        result
+            .entry(row.get("thread_id"))
+            .or_default()
+            .push(row.get("tag_id"));
+    }
+    Ok(result)
+}
@@ -0,0 +1,50 @@
+//! Per-request query counting for test mode.
+//!
+//! Every statement executed on a [DbPool](super::DbPool) or
+//! [DbTransaction](super::DbTransaction) is noted here when a recorder is
+//! active on the current task. Work spawned off the request (unfurls,
+//! gateway fan-out) runs on other tasks and isn't counted.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+tokio::task_local! {
+    static RECORDER: Arc<Mutex<Vec<String>>>;
+}
+
+/// The statements a recorded future issued, in order.
+#[derive(Debug, Default)]
+pub struct QueryLog {
+    pub statements: Vec<String>,
+}
+
+impl QueryLog {
+    pub fn count(&self) -> usize {
+        self.statements.len()
+    }
+
+    /// Statements issued more than once, most repeated first — the usual
+    /// sign of a per-row lookup inside a loop.
+    pub fn repeated(&self) -> Vec<(&str, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for sql in &self.statements {
+            *counts.entry(sql.as_str()).or_default() += 1;
+        }
+        let mut repeated: Vec<_> = counts.into_iter().filter(|(_, n)| *n > 1).collect();
+        repeated.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        repeated
+    }
+}
+
+/// Run [fut], recording every query it issues on this task.
+pub async fn record<F: Future>(fut: F) -> (F::Output, QueryLog) {
+    let statements = Arc::new(Mutex::new(Vec::new()));
+    let output = RECORDER.scope(statements.clone(), fut).await;
+    let statements = std::mem::take(&mut *statements.lock().unwrap());
+    (output, QueryLog { statements })
+}
+
+pub(super) fn note(sql: &str) {
+    let _ = RECORDER.try_with(|statements| statements.lock().unwrap().push(sql.to_string()));
+}
@@ -0,0 +1,74 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::integration::{SpaceBot, SpaceWebhook};
+
+/// Bot members of [space_id], oldest first.
+pub async fn list_space_bots(pool: &DbPool, space_id: &str) -> Result<Vec<SpaceBot>, AppError> {
+    let rows = sqlx::query(&super::q(
+        "SELECT u.id AS user_id, u.username, a.id AS application_id, a.name AS application_name, \
+                a.owner_id, m.joined_at, \
+                (SELECT COUNT(*) FROM application_commands c \
+                 WHERE c.application_id = a.id AND c.space_id = m.space_id) AS space_command_count, \
+                (SELECT MAX(msg.created_at) FROM messages msg \
+                 WHERE msg.author_id = u.id AND msg.space_id = m.space_id \
+                   AND msg.webhook_id IS NULL) AS last_message_at, \
+                (SELECT MAX(i.created_at) FROM interactions i \
+                 WHERE i.application_id = a.id AND i.space_id = m.space_id) AS last_interaction_at \
+         FROM members m \
+         JOIN users u ON u.id = m.user_id \
+         LEFT JOIN applications a ON a.bot_user_id = u.id \
+         WHERE m.space_id = ? AND u.bot = TRUE \
+         ORDER BY m.joined_at ASC, u.id ASC",
+    ))
+    .bind(space_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let last_message: Option<String> = row.get("last_message_at");
+            let last_interaction: Option<String> = row.get("last_interaction_at");
+            SpaceBot {
+                user_id: row.get("user_id"),
+                username: row.get("username"),
+                application_id: row.get("application_id"),
+                application_name: row.get("application_name"),
+                owner_id: row.get("owner_id"),
+                joined_at: row.get("joined_at"),
+                space_command_count: row.get("space_command_count"),
+                last_activity: last_message.max(last_interaction),
+            }
+        })
+        .collect())
+}
+
+/// Webhooks in [space_id] with when each last posted.
+pub async fn list_space_webhooks(
+    pool: &DbPool,
+    space_id: &str,
+) -> Result<Vec<SpaceWebhook>, AppError> {
+    let rows = sqlx::query(&super::q(
+        "SELECT w.id, w.channel_id, w.name, w.creator_id, w.created_at, \
+                (SELECT MAX(msg.created_at) FROM messages msg WHERE msg.webhook_id = w.id) AS last_activity \
+         FROM webhooks w WHERE w.space_id = ? ORDER BY w.id ASC",
+    ))
+    .bind(space_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| SpaceWebhook {
+            id: row.get("id"),
+            channel_id: row.get("channel_id"),
+            name: row.get("name"),
+            creator_id: row.get("creator_id"),
+            created_at: row.get("created_at"),
+            last_activity: row.get("last_activity"),
+        })
+        .collect())
+}
@@ -0,0 +1,213 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::middleware::auth::create_token_hash;
+use crate::models::interaction::{Command, CreateCommand, InteractionRow};
+use crate::snowflake;
+
+fn row_to_command(row: sqlx::any::AnyRow) -> Command {
+    let options: String = row.get("options");
+    Command {
+        id: row.get("id"),
+        application_id: row.get("application_id"),
+        space_id: row.get("space_id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        options: serde_json::from_str(&options).ok(),
+        command_type: row.get("type"),
+    }
+}
+
+const SELECT_COMMANDS: &str = "SELECT c.id, c.application_id, c.space_id, c.name, c.description, c.options, c.type FROM application_commands c";
+
+pub async fn get_command(pool: &DbPool, command_id: &str) -> Result<Command, AppError> {
+    let row = sqlx::query(&super::q(&format!("{SELECT_COMMANDS} WHERE c.id = ?")))
+        .bind(command_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("unknown_command".to_string()))?;
+    Ok(row_to_command(row))
+}
+
+/// Commands an application registered in one scope: global when [space_id]
+/// is `None`, otherwise that space's.
+pub async fn list_commands(
+    pool: &DbPool,
+    application_id: &str,
+    space_id: Option<&str>,
+) -> Result<Vec<Command>, AppError> {
+    let scope = if space_id.is_some() {
+        "c.space_id = ?"
+    } else {
+        "c.space_id IS NULL"
+    };
+    let sql = super::q(&format!(
+        "{SELECT_COMMANDS} WHERE c.application_id = ? AND {scope} ORDER BY c.name ASC"
+    ));
+    let mut query = sqlx::query(&sql).bind(application_id);
+    if let Some(sid) = space_id {
+        query = query.bind(sid);
+    }
+    let rows = query.fetch_all(pool).await?;
+    Ok(rows.into_iter().map(row_to_command).collect())
+}
+
+/// Every command usable in [space_id]: global commands of applications whose
+/// bot is a member, plus commands registered for the space itself.
+pub async fn list_space_commands(pool: &DbPool, space_id: &str) -> Result<Vec<Command>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_COMMANDS} \
+         JOIN applications a ON a.id = c.application_id \
+         JOIN members m ON m.user_id = a.bot_user_id AND m.space_id = ? \
+         WHERE c.space_id IS NULL OR c.space_id = ? \
+         ORDER BY c.name ASC, c.id ASC"
+    )))
+    .bind(space_id)
+    .bind(space_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(row_to_command).collect())
+}
+
+/// Register a command, replacing the one with the same name in the same
+/// scope if there is one. The command keeps its ID when replaced.
+pub async fn upsert_command(
+    pool: &DbPool,
+    application_id: &str,
+    space_id: Option<&str>,
+    input: &CreateCommand,
+) -> Result<Command, AppError> {
+    let options_json = serde_json::to_string(&input.options.as_deref().unwrap_or(&[])).unwrap();
+    let command_type = input.command_type.as_deref().unwrap_or("chat_input");
+    let existing = list_commands(pool, application_id, space_id)
+        .await?
+        .into_iter()
+        .find(|c| c.name == input.name);
+
+    let id = match existing {
+        Some(command) => {
+            sqlx::query(&super::q(
+                "UPDATE application_commands SET description = ?, options = ?, type = ? WHERE id = ?",
+            ))
+            .bind(&input.description)
+            .bind(&options_json)
+            .bind(command_type)
+            .bind(&command.id)
+            .execute(pool)
+            .await?;
+            command.id
+        }
+        None => {
+            let id = snowflake::generate();
+            sqlx::query(&super::q(
+                "INSERT INTO application_commands (id, application_id, space_id, name, description, options, type) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            ))
+            .bind(&id)
+            .bind(application_id)
+            .bind(space_id)
+            .bind(&input.name)
+            .bind(&input.description)
+            .bind(&options_json)
+            .bind(command_type)
+            .execute(pool)
+            .await?;
+            id
+        }
+    };
+    get_command(pool, &id).await
+}
+
+pub async fn delete_command(pool: &DbPool, command_id: &str) -> Result<(), AppError> {
+    sqlx::query(&super::q("DELETE FROM application_commands WHERE id = ?"))
+        .bind(command_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+fn row_to_interaction(row: sqlx::any::AnyRow) -> InteractionRow {
+    InteractionRow {
+        id: row.get("id"),
+        application_id: row.get("application_id"),
+        command_id: row.get("command_id"),
+        space_id: row.get("space_id"),
+        channel_id: row.get("channel_id"),
+        user_id: row.get("user_id"),
+        response_type: row.get("response_type"),
+        created_at: row.get("created_at"),
+    }
+}
+
+const SELECT_INTERACTIONS: &str = "SELECT id, application_id, command_id, space_id, channel_id, user_id, response_type, created_at FROM interactions";
+
+/// Record an invocation. Only the hash of [token] is stored.
+pub async fn create_interaction(
+    pool: &DbPool,
+    command: &Command,
+    space_id: Option<&str>,
+    channel_id: &str,
+    user_id: &str,
+    token: &str,
+) -> Result<InteractionRow, AppError> {
+    let id = snowflake::generate();
+    sqlx::query(&super::q(
+        "INSERT INTO interactions (id, application_id, command_id, space_id, channel_id, user_id, token_hash) VALUES (?, ?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(&command.application_id)
+    .bind(&command.id)
+    .bind(space_id)
+    .bind(channel_id)
+    .bind(user_id)
+    .bind(create_token_hash(token))
+    .execute(pool)
+    .await?;
+    let row = sqlx::query(&super::q(&format!("{SELECT_INTERACTIONS} WHERE id = ?")))
+        .bind(&id)
+        .fetch_one(pool)
+        .await?;
+    Ok(row_to_interaction(row))
+}
+
+/// Look up an interaction by ID and token. A wrong token is reported the
+/// same as an unknown interaction.
+pub async fn get_interaction_with_token(
+    pool: &DbPool,
+    interaction_id: &str,
+    token: &str,
+) -> Result<InteractionRow, AppError> {
+    let row = sqlx::query(&super::q(&format!(
+        "{SELECT_INTERACTIONS} WHERE id = ? AND token_hash = ?"
+    )))
+    .bind(interaction_id)
+    .bind(create_token_hash(token))
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("unknown_interaction".to_string()))?;
+    Ok(row_to_interaction(row))
+}
+
+/// Record the bot's response to an interaction. Returns `false` if it can't
+/// take that response any more: a message was already posted, or it is
+/// being deferred a second time.
+pub async fn claim_response(
+    pool: &DbPool,
+    interaction_id: &str,
+    response_type: &str,
+) -> Result<bool, AppError> {
+    let open = if response_type == "deferred" {
+        "response_type IS NULL"
+    } else {
+        "(response_type IS NULL OR response_type = 'deferred')"
+    };
+    let result = sqlx::query(&super::q(&format!(
+        "UPDATE interactions SET response_type = ? WHERE id = ? AND {open}"
+    )))
+    .bind(response_type)
+    .bind(interaction_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
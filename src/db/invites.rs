@@ -1,7 +1,10 @@
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
-use crate::models::invite::{CreateInvite, Invite};
+use crate::middleware::auth::{create_token_hash, generate_token};
+use crate::models::invite::{CreateInvite, Invite, InviteJoin, InviteUsesDay};
 
 fn row_to_invite(row: sqlx::any::AnyRow) -> Invite {
     Invite {
@@ -15,12 +18,41 @@ fn row_to_invite(row: sqlx::any::AnyRow) -> Invite {
         temporary: crate::db::get_bool(&row, "temporary"),
         created_at: row.get("created_at"),
         expires_at: row.get("expires_at"),
+        vanity: false,
     }
 }
 
 const SELECT_INVITES: &str = "SELECT code, space_id, channel_id, inviter_id, max_uses, uses, max_age, temporary, created_at, expires_at FROM invites";
 
-pub async fn get_invite(pool: &AnyPool, code: &str) -> Result<Invite, AppError> {
+/// Invites that can still be accepted, given the current time as the bound
+/// parameter. A max_uses or max_age of 0 (or null) means unlimited.
+const LIVE_INVITE: &str = "(expires_at IS NULL OR expires_at > ?) AND (max_uses IS NULL OR max_uses = 0 OR uses < max_uses)";
+
+fn now() -> String {
+    chrono::Utc::now()
+        .format("%Y-%m-%dT%H:%M:%S+00:00")
+        .to_string()
+}
+
+/// Why an invite can no longer be used, if it can't.
+fn expiry_reason(invite: &Invite) -> Option<&'static str> {
+    if invite.expires_at.as_ref().is_some_and(|at| *at <= now()) {
+        return Some("invite has expired");
+    }
+    if invite
+        .max_uses
+        .is_some_and(|max| max > 0 && invite.uses >= max)
+    {
+        return Some("invite has reached max uses");
+    }
+    None
+}
+
+fn expired(reason: &str) -> AppError {
+    AppError::GoneCode("invite_expired", reason.to_string())
+}
+
+pub async fn get_invite(pool: &DbPool, code: &str) -> Result<Invite, AppError> {
     let row = sqlx::query(&super::q(&format!("{SELECT_INVITES} WHERE code = ?")))
         .bind(code)
         .fetch_optional(pool)
@@ -30,23 +62,45 @@ pub async fn get_invite(pool: &AnyPool, code: &str) -> Result<Invite, AppError>
     Ok(row_to_invite(row))
 }
 
-pub async fn list_space_invites(pool: &AnyPool, space_id: &str) -> Result<Vec<Invite>, AppError> {
-    let rows = sqlx::query(&super::q(&format!("{SELECT_INVITES} WHERE space_id = ?")))
-        .bind(space_id)
-        .fetch_all(pool)
-        .await?;
+/// Like [get_invite], but an expired or used-up invite is a 410
+/// `invite_expired` rather than something to show.
+/// A space's vanity code falls back in when no created invite has [code].
+pub async fn get_live_invite(pool: &DbPool, code: &str) -> Result<Invite, AppError> {
+    let invite = match get_invite(pool, code).await {
+        Err(AppError::NotFound(msg)) => get_vanity_invite(pool, code)
+            .await?
+            .ok_or(AppError::NotFound(msg))?,
+        other => other?,
+    };
+    match expiry_reason(&invite) {
+        Some(reason) => Err(expired(reason)),
+        None => Ok(invite),
+    }
+}
+
+pub async fn list_space_invites(pool: &DbPool, space_id: &str) -> Result<Vec<Invite>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_INVITES} WHERE space_id = ? AND {LIVE_INVITE}"
+    )))
+    .bind(space_id)
+    .bind(now())
+    .fetch_all(pool)
+    .await?;
 
     Ok(rows.into_iter().map(row_to_invite).collect())
 }
 
 pub async fn list_channel_invites(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
 ) -> Result<Vec<Invite>, AppError> {
-    let rows = sqlx::query(&super::q(&format!("{SELECT_INVITES} WHERE channel_id = ?")))
-        .bind(channel_id)
-        .fetch_all(pool)
-        .await?;
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_INVITES} WHERE channel_id = ? AND {LIVE_INVITE}"
+    )))
+    .bind(channel_id)
+    .bind(now())
+    .fetch_all(pool)
+    .await?;
 
     Ok(rows.into_iter().map(row_to_invite).collect())
 }
@@ -64,7 +118,7 @@ fn generate_code() -> String {
 }
 
 pub async fn create_invite(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     channel_id: Option<&str>,
     inviter_id: &str,
@@ -96,10 +150,42 @@ pub async fn create_invite(
     get_invite(pool, &code).await
 }
 
+/// A permanent invite to [channel_id] for the space widget, created on
+/// behalf of [inviter_id] the first time it's needed.
+pub async fn get_or_create_widget_invite(
+    pool: &DbPool,
+    space_id: &str,
+    channel_id: &str,
+    inviter_id: &str,
+) -> Result<Invite, AppError> {
+    let existing: Option<(String,)> = sqlx::query_as(&super::q(
+        "SELECT code FROM invites WHERE space_id = ? AND channel_id = ? AND max_uses IS NULL AND expires_at IS NULL AND temporary = FALSE LIMIT 1",
+    ))
+    .bind(space_id)
+    .bind(channel_id)
+    .fetch_optional(pool)
+    .await?;
+    if let Some((code,)) = existing {
+        return get_invite(pool, &code).await;
+    }
+    create_invite(
+        pool,
+        space_id,
+        Some(channel_id),
+        inviter_id,
+        &CreateInvite {
+            max_uses: None,
+            max_age: None,
+            temporary: None,
+        },
+    )
+    .await
+}
+
 /// Ensures a default permanent invite exists for the first space.
 /// If no spaces exist, creates a system user and a default "Accord" space.
 /// Returns the invite code.
-pub async fn ensure_default_invite(pool: &AnyPool) -> Result<String, AppError> {
+pub async fn ensure_default_invite(pool: &DbPool) -> Result<String, AppError> {
     // Find the first space
     let space: Option<(String,)> =
         sqlx::query_as("SELECT id FROM spaces ORDER BY created_at ASC LIMIT 1")
@@ -134,6 +220,8 @@ pub async fn ensure_default_invite(pool: &AnyPool) -> Result<String, AppError> {
                     description: Some("Default space".to_string()),
                     public: Some(true),
                     allow_guest_access: None,
+                    preferred_locale: None,
+                    template: None,
                 },
             )
             .await?;
@@ -187,7 +275,7 @@ pub async fn ensure_default_invite(pool: &AnyPool) -> Result<String, AppError> {
     Ok(code)
 }
 
-pub async fn delete_invite(pool: &AnyPool, code: &str) -> Result<(), AppError> {
+pub async fn delete_invite(pool: &DbPool, code: &str) -> Result<(), AppError> {
     sqlx::query(&super::q("DELETE FROM invites WHERE code = ?"))
         .bind(code)
         .execute(pool)
@@ -195,35 +283,313 @@ pub async fn delete_invite(pool: &AnyPool, code: &str) -> Result<(), AppError> {
     Ok(())
 }
 
-pub async fn use_invite(pool: &AnyPool, code: &str) -> Result<Invite, AppError> {
-    let invite = get_invite(pool, code).await?;
+pub async fn use_invite(pool: &DbPool, code: &str) -> Result<Invite, AppError> {
+    let invite = get_live_invite(pool, code).await?;
+    if invite.vanity {
+        sqlx::query(&super::q(
+            "UPDATE spaces SET vanity_url_uses = vanity_url_uses + 1 WHERE id = ?",
+        ))
+        .bind(&invite.space_id)
+        .execute(pool)
+        .await?;
+        return Ok(Invite {
+            uses: invite.uses + 1,
+            ..invite
+        });
+    }
 
-    // Check if expired
-    if let Some(ref expires_at) = invite.expires_at {
-        let now = chrono::Utc::now()
-            .format("%Y-%m-%dT%H:%M:%S+00:00")
-            .to_string();
-        if *expires_at < now {
-            return Err(AppError::BadRequest("invite has expired".to_string()));
-        }
+    // Conditional so two joins racing for the last use can't both get it.
+    let updated = sqlx::query(&super::q(&format!(
+        "UPDATE invites SET uses = uses + 1 WHERE code = ? AND {LIVE_INVITE}"
+    )))
+    .bind(&invite.code)
+    .bind(now())
+    .execute(pool)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Err(expired("invite has reached max uses"));
     }
 
-    // Check max uses. A max_uses of 0 (or null) means unlimited.
-    if let Some(max_uses) = invite.max_uses {
-        if max_uses > 0 && invite.uses >= max_uses {
-            return Err(AppError::BadRequest(
-                "invite has reached max uses".to_string(),
-            ));
-        }
+    get_invite(pool, code).await
+}
+
+/// Delete invites that have expired or run out of uses. Returns how many
+/// were removed.
+pub async fn delete_expired_invites(pool: &DbPool) -> Result<u64, AppError> {
+    let result = sqlx::query(&super::q(&format!(
+        "DELETE FROM invites WHERE NOT ({LIVE_INVITE})"
+    )))
+    .bind(now())
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// -------------------------------------------------------------------------
+// Join tokens
+// -------------------------------------------------------------------------
+
+/// Issue a join token letting `user_id` accept invite [code] into
+/// `space_id` for the next `ttl_secs`. Only the hash is stored.
+pub async fn create_join_token(
+    pool: &DbPool,
+    code: &str,
+    space_id: &str,
+    user_id: &str,
+    ttl_secs: i64,
+) -> Result<(String, String), AppError> {
+    let token = generate_token();
+    let expires_at = (chrono::Utc::now() + chrono::Duration::seconds(ttl_secs))
+        .format("%Y-%m-%dT%H:%M:%S+00:00")
+        .to_string();
+    sqlx::query(&super::q(
+        "INSERT INTO invite_join_tokens (token_hash, invite_code, space_id, user_id, expires_at) \
+         VALUES (?, ?, ?, ?, ?)",
+    ))
+    .bind(create_token_hash(&token))
+    .bind(code)
+    .bind(space_id)
+    .bind(user_id)
+    .bind(&expires_at)
+    .execute(pool)
+    .await?;
+    Ok((token, expires_at))
+}
+
+/// Redeem a join token for `user_id` accepting [code]. Returns whether it
+/// was valid; a token only works once.
+pub async fn consume_join_token(
+    pool: &DbPool,
+    token: &str,
+    code: &str,
+    user_id: &str,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "DELETE FROM invite_join_tokens \
+         WHERE token_hash = ? AND invite_code = ? AND user_id = ? AND expires_at > ?",
+    ))
+    .bind(create_token_hash(token))
+    .bind(code)
+    .bind(user_id)
+    .bind(now())
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn delete_expired_join_tokens(pool: &DbPool) -> Result<u64, AppError> {
+    let result = sqlx::query(&super::q(
+        "DELETE FROM invite_join_tokens WHERE expires_at <= ?",
+    ))
+    .bind(now())
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// -------------------------------------------------------------------------
+// Vanity codes
+// -------------------------------------------------------------------------
+
+/// The space claiming vanity [code], as an invite that never runs out.
+pub async fn get_vanity_invite(pool: &DbPool, code: &str) -> Result<Option<Invite>, AppError> {
+    let row = sqlx::query(&super::q(
+        "SELECT id, vanity_url_code, vanity_url_uses, created_at FROM spaces WHERE vanity_url_code = ?",
+    ))
+    .bind(code)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| Invite {
+        code: row.get("vanity_url_code"),
+        space_id: row.get("id"),
+        channel_id: None,
+        inviter_id: None,
+        max_uses: None,
+        uses: row.get("vanity_url_uses"),
+        max_age: None,
+        temporary: false,
+        created_at: row.get("created_at"),
+        expires_at: None,
+        vanity: true,
+    }))
+}
+
+/// Whether [code] is already an invite code, or another space's vanity
+/// code.
+pub async fn vanity_code_taken(
+    pool: &DbPool,
+    code: &str,
+    space_id: &str,
+) -> Result<bool, AppError> {
+    let (count,): (i64,) = sqlx::query_as(&super::q(
+        "SELECT (SELECT COUNT(*) FROM invites WHERE code = ?) \
+         + (SELECT COUNT(*) FROM spaces WHERE vanity_url_code = ? AND id != ?)",
+    ))
+    .bind(code)
+    .bind(code)
+    .bind(space_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(count > 0)
+}
+
+pub async fn is_vanity_code_revoked(pool: &DbPool, code: &str) -> Result<bool, AppError> {
+    let row = sqlx::query(&super::q(
+        "SELECT code FROM revoked_vanity_codes WHERE code = ?",
+    ))
+    .bind(code)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.is_some())
+}
+
+/// Set or clear (with `None`) a space's vanity code, starting its use count
+/// over.
+pub async fn set_vanity_code(
+    pool: &DbPool,
+    space_id: &str,
+    code: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "UPDATE spaces SET vanity_url_code = ?, vanity_url_uses = 0 WHERE id = ?",
+    ))
+    .bind(code)
+    .bind(space_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Revoke vanity [code] for good: the space holding it, if any, loses it,
+/// and nobody can claim it again. Returns that space's ID.
+pub async fn revoke_vanity_code(
+    pool: &DbPool,
+    code: &str,
+    revoked_by: &str,
+    reason: Option<&str>,
+) -> Result<Option<String>, AppError> {
+    let space_id: Option<String> =
+        sqlx::query_scalar(&super::q("SELECT id FROM spaces WHERE vanity_url_code = ?"))
+            .bind(code)
+            .fetch_optional(pool)
+            .await?;
+    if let Some(ref space_id) = space_id {
+        set_vanity_code(pool, space_id, None).await?;
     }
+    sqlx::query(&super::q(
+        "INSERT INTO revoked_vanity_codes (code, space_id, revoked_by, reason, created_at) \
+         VALUES (?, ?, ?, ?, ?) ON CONFLICT (code) DO NOTHING",
+    ))
+    .bind(code)
+    .bind(&space_id)
+    .bind(revoked_by)
+    .bind(reason)
+    .bind(now())
+    .execute(pool)
+    .await?;
+    Ok(space_id)
+}
+
+// -------------------------------------------------------------------------
+// Attribution
+// -------------------------------------------------------------------------
 
-    // Increment uses
+/// Record that [user_id] joined [space_id] through invite [code]: the
+/// member's `source_invite`, and a row for the invite's stats.
+pub async fn record_invite_use(
+    pool: &DbPool,
+    space_id: &str,
+    code: &str,
+    user_id: &str,
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "UPDATE members SET source_invite = ? WHERE space_id = ? AND user_id = ?",
+    ))
+    .bind(code)
+    .bind(space_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
     sqlx::query(&super::q(
-        "UPDATE invites SET uses = uses + 1 WHERE code = ?",
+        "INSERT INTO invite_uses (space_id, code, user_id, used_at) VALUES (?, ?, ?, ?)",
     ))
+    .bind(space_id)
     .bind(code)
+    .bind(user_id)
+    .bind(now())
     .execute(pool)
     .await?;
+    Ok(())
+}
 
-    get_invite(pool, code).await
+/// Joins through [code] per day since [since] (an RFC 3339 timestamp),
+/// oldest first. Days without joins are left out.
+pub async fn invite_uses_by_day(
+    pool: &DbPool,
+    space_id: &str,
+    code: &str,
+    since: &str,
+) -> Result<Vec<InviteUsesDay>, AppError> {
+    let rows = sqlx::query(&super::q(
+        "SELECT SUBSTR(used_at, 1, 10) AS day, COUNT(*) AS uses FROM invite_uses \
+         WHERE space_id = ? AND code = ? AND used_at >= ? \
+         GROUP BY SUBSTR(used_at, 1, 10) ORDER BY day",
+    ))
+    .bind(space_id)
+    .bind(code)
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| InviteUsesDay {
+            date: row.get("day"),
+            uses: row.get("uses"),
+        })
+        .collect())
+}
+
+/// Every join through [code], and how many of those members are still in
+/// the space.
+pub async fn count_invite_uses(
+    pool: &DbPool,
+    space_id: &str,
+    code: &str,
+) -> Result<(i64, i64), AppError> {
+    let (total, remaining): (i64, i64) = sqlx::query_as(&super::q(
+        "SELECT (SELECT COUNT(*) FROM invite_uses WHERE space_id = ? AND code = ?), \
+         (SELECT COUNT(*) FROM members WHERE space_id = ? AND source_invite = ?)",
+    ))
+    .bind(space_id)
+    .bind(code)
+    .bind(space_id)
+    .bind(code)
+    .fetch_one(pool)
+    .await?;
+    Ok((total, remaining))
+}
+
+/// The latest joins through [code], newest first.
+pub async fn list_invite_joins(
+    pool: &DbPool,
+    space_id: &str,
+    code: &str,
+    limit: i64,
+) -> Result<Vec<InviteJoin>, AppError> {
+    let rows = sqlx::query(&super::q(
+        "SELECT user_id, used_at FROM invite_uses WHERE space_id = ? AND code = ? \
+         ORDER BY used_at DESC LIMIT ?",
+    ))
+    .bind(space_id)
+    .bind(code)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| InviteJoin {
+            user_id: row.get("user_id"),
+            used_at: row.get("used_at"),
+        })
+        .collect())
 }
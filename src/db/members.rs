@@ -1,4 +1,6 @@
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::models::member::{MemberRow, UpdateMember};
@@ -9,19 +11,24 @@ fn row_to_member(row: sqlx::any::AnyRow) -> MemberRow {
         space_id: row.get("space_id"),
         nickname: row.get("nickname"),
         avatar: row.get("avatar"),
+        banner: row.get("banner"),
+        bio: row.get("bio"),
+        pronouns: row.get("pronouns"),
+        timezone: row.get("timezone"),
         joined_at: row.get("joined_at"),
         premium_since: row.get("premium_since"),
         deaf: crate::db::get_bool(&row, "deaf"),
         mute: crate::db::get_bool(&row, "mute"),
         pending: crate::db::get_bool(&row, "pending"),
         timed_out_until: row.get("timed_out_until"),
+        source_invite: row.get("source_invite"),
     }
 }
 
-const SELECT_MEMBERS: &str = "SELECT user_id, space_id, nickname, avatar, joined_at, premium_since, deaf, mute, pending, timed_out_until FROM members";
+const SELECT_MEMBERS: &str = "SELECT user_id, space_id, nickname, avatar, banner, bio, pronouns, timezone, joined_at, premium_since, deaf, mute, pending, timed_out_until, source_invite FROM members";
 
 pub async fn get_member_row(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
 ) -> Result<MemberRow, AppError> {
@@ -38,13 +45,13 @@ pub async fn get_member_row(
 }
 
 pub async fn list_members(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     after: Option<&str>,
     limit: i64,
 ) -> Result<Vec<MemberRow>, AppError> {
     // Join users so we can hide the System user from the sidebar.
-    let select = "SELECT m.user_id, m.space_id, m.nickname, m.avatar, m.joined_at, m.premium_since, m.deaf, m.mute, m.pending, m.timed_out_until FROM members m INNER JOIN users u ON m.user_id = u.id";
+    let select = "SELECT m.user_id, m.space_id, m.nickname, m.avatar, m.banner, m.bio, m.pronouns, m.timezone, m.joined_at, m.premium_since, m.deaf, m.mute, m.pending, m.timed_out_until, m.source_invite FROM members m INNER JOIN users u ON m.user_id = u.id";
     let rows = if let Some(after_id) = after {
         sqlx::query(&super::q(&format!(
             "{select} WHERE m.space_id = ? AND u.system = FALSE AND m.user_id > ? ORDER BY m.user_id ASC LIMIT ?"
@@ -67,15 +74,195 @@ pub async fn list_members(
     Ok(rows.into_iter().map(row_to_member).collect())
 }
 
+/// Several members of a space in one query. Users who aren't members are
+/// left out.
+pub async fn get_members_by_ids(
+    pool: &DbPool,
+    space_id: &str,
+    user_ids: &[String],
+) -> Result<Vec<MemberRow>, AppError> {
+    if user_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; user_ids.len()].join(", ");
+    let sql = super::q(&format!(
+        "{SELECT_MEMBERS} WHERE space_id = ? AND user_id IN ({placeholders})"
+    ));
+    let mut query = sqlx::query(&sql).bind(space_id);
+    for id in user_ids {
+        query = query.bind(id);
+    }
+    let rows = query.fetch_all(pool).await?;
+    Ok(rows.into_iter().map(row_to_member).collect())
+}
+
+/// Members holding `role_id`, a page at a time in user ID order. Fetches
+/// `limit + 1` rows so the caller can tell whether there are more.
+pub async fn list_members_with_role(
+    pool: &DbPool,
+    space_id: &str,
+    role_id: &str,
+    after: Option<&str>,
+    limit: i64,
+) -> Result<Vec<MemberRow>, AppError> {
+    let rows = sqlx::query(&super::q(
+        "SELECT m.user_id, m.space_id, m.nickname, m.avatar, m.banner, m.bio, m.pronouns, m.timezone, m.joined_at, m.premium_since, m.deaf, m.mute, m.pending, m.timed_out_until, m.source_invite \
+         FROM members m \
+         INNER JOIN member_roles mr ON mr.space_id = m.space_id AND mr.user_id = m.user_id \
+         INNER JOIN users u ON m.user_id = u.id \
+         WHERE m.space_id = ? AND mr.role_id = ? AND u.system = FALSE AND m.user_id > ? \
+         ORDER BY m.user_id ASC LIMIT ?",
+    ))
+    .bind(space_id)
+    .bind(role_id)
+    .bind(after.unwrap_or(""))
+    .bind(limit + 1)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(row_to_member).collect())
+}
+
+/// WHERE clause shared by the gateway member-chunk queries. Binds the space
+/// ID, then (when a prefix is given) the username and nickname patterns.
+fn matching_members_filter(prefix: Option<&str>) -> &'static str {
+    if prefix.is_some() {
+        "m.space_id = ? AND u.system = FALSE \
+         AND (LOWER(u.username) LIKE ? ESCAPE '\\' OR LOWER(m.nickname) LIKE ? ESCAPE '\\')"
+    } else {
+        "m.space_id = ? AND u.system = FALSE"
+    }
+}
+
+/// Number of members in a space, optionally restricted to those whose
+/// username or nickname starts with `prefix` (case-insensitive).
+pub async fn count_members_matching(
+    pool: &DbPool,
+    space_id: &str,
+    prefix: Option<&str>,
+) -> Result<i64, AppError> {
+    let sql = super::q(&format!(
+        "SELECT COUNT(*) FROM members m INNER JOIN users u ON m.user_id = u.id WHERE {}",
+        matching_members_filter(prefix)
+    ));
+    let mut query = sqlx::query_as::<_, (i64,)>(&sql).bind(space_id);
+    if let Some(prefix) = prefix {
+        let pattern = format!("{}%", super::escape_like(&prefix.to_lowercase()));
+        query = query.bind(pattern.clone()).bind(pattern);
+    }
+    Ok(query.fetch_one(pool).await?.0)
+}
+
+/// Keyset page of members ordered by user ID, with the same filter as
+/// [count_members_matching].
+pub async fn list_members_matching(
+    pool: &DbPool,
+    space_id: &str,
+    prefix: Option<&str>,
+    after: Option<&str>,
+    limit: i64,
+) -> Result<Vec<MemberRow>, AppError> {
+    let sql = super::q(&format!(
+        "SELECT m.user_id, m.space_id, m.nickname, m.avatar, m.banner, m.bio, m.pronouns, m.timezone, m.joined_at, m.premium_since, m.deaf, m.mute, m.pending, m.timed_out_until, m.source_invite \
+         FROM members m INNER JOIN users u ON m.user_id = u.id \
+         WHERE {} AND m.user_id > ? ORDER BY m.user_id ASC LIMIT ?",
+        matching_members_filter(prefix)
+    ));
+    let mut query = sqlx::query(&sql).bind(space_id);
+    if let Some(prefix) = prefix {
+        let pattern = format!("{}%", super::escape_like(&prefix.to_lowercase()));
+        query = query.bind(pattern.clone()).bind(pattern);
+    }
+    let rows = query
+        .bind(after.unwrap_or(""))
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(row_to_member).collect())
+}
+
+/// Role IDs for many members of one space in a single query, keyed by user ID.
+/// Members with no roles are absent from the map.
+pub async fn get_role_ids_for_members(
+    pool: &DbPool,
+    space_id: &str,
+    user_ids: &[String],
+) -> Result<std::collections::HashMap<String, Vec<String>>, AppError> {
+    let mut map: std::collections::HashMap<String, Vec<String>> = std::collections::HashMap::new();
+    if user_ids.is_empty() {
+        return Ok(map);
+    }
+    let in_clause = vec!["?"; user_ids.len()].join(", ");
+    let sql = super::q(&format!(
+        "SELECT user_id, role_id FROM member_roles WHERE space_id = ? AND user_id IN ({in_clause})"
+    ));
+    let mut query = sqlx::query_as::<_, (String, String)>(&sql).bind(space_id);
+    for id in user_ids {
+        query = query.bind(id);
+    }
+    for (user_id, role_id) in query.fetch_all(pool).await? {
+        map.entry(user_id).or_default().push(role_id);
+    }
+    Ok(map)
+}
+
+/// Distinct members of a space holding at least one of `role_ids`.
+pub async fn list_member_ids_with_roles(
+    pool: &DbPool,
+    space_id: &str,
+    role_ids: &[String],
+) -> Result<Vec<String>, AppError> {
+    if role_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let in_clause = vec!["?"; role_ids.len()].join(", ");
+    let sql = super::q(&format!(
+        "SELECT DISTINCT user_id FROM member_roles WHERE space_id = ? AND role_id IN ({in_clause})"
+    ));
+    let mut query = sqlx::query_as::<_, (String,)>(&sql).bind(space_id);
+    for id in role_ids {
+        query = query.bind(id);
+    }
+    Ok(query
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|(id,)| id)
+        .collect())
+}
+
+/// One page of user IDs that should be badged by an @everyone announcement:
+/// human members of the space, ordered by ID for keyset paging, minus anyone
+/// who has suppressed @everyone for the space.
+pub async fn list_everyone_recipient_ids(
+    pool: &DbPool,
+    space_id: &str,
+    after: Option<&str>,
+    limit: i64,
+) -> Result<Vec<String>, AppError> {
+    let rows: Vec<(String,)> = sqlx::query_as(&super::q(
+        "SELECT m.user_id FROM members m INNER JOIN users u ON m.user_id = u.id \
+         WHERE m.space_id = ? AND m.user_id > ? AND u.bot = FALSE AND u.system = FALSE \
+         AND NOT EXISTS (SELECT 1 FROM everyone_suppressions es \
+           WHERE es.space_id = m.space_id AND es.user_id = m.user_id) \
+         ORDER BY m.user_id ASC LIMIT ?",
+    ))
+    .bind(space_id)
+    .bind(after.unwrap_or(""))
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
 pub async fn search_members(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     query: &str,
     limit: i64,
 ) -> Result<Vec<MemberRow>, AppError> {
     let pattern = format!("%{query}%");
     let rows = sqlx::query(
-        &super::q("SELECT m.user_id, m.space_id, m.nickname, m.avatar, m.joined_at, m.premium_since, m.deaf, m.mute, m.pending, m.timed_out_until FROM members m INNER JOIN users u ON m.user_id = u.id WHERE m.space_id = ? AND u.system = FALSE AND (u.username LIKE ? OR m.nickname LIKE ?) LIMIT ?")
+        &super::q("SELECT m.user_id, m.space_id, m.nickname, m.avatar, m.banner, m.bio, m.pronouns, m.timezone, m.joined_at, m.premium_since, m.deaf, m.mute, m.pending, m.timed_out_until, m.source_invite FROM members m INNER JOIN users u ON m.user_id = u.id WHERE m.space_id = ? AND u.system = FALSE AND (u.username LIKE ? OR m.nickname LIKE ?) LIMIT ?")
     )
     .bind(space_id)
     .bind(&pattern)
@@ -92,7 +279,7 @@ pub async fn search_members(
 /// are dropped. Used to turn parsed message mentions into concrete recipients
 /// for the per-channel mention counter.
 pub async fn resolve_mention_user_ids(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     usernames: &[String],
 ) -> Result<Vec<String>, AppError> {
@@ -113,22 +300,52 @@ pub async fn resolve_mention_user_ids(
     Ok(rows.into_iter().map(|r| r.get::<String, _>("id")).collect())
 }
 
+/// The subset of `user_ids` that are members of the space, for `<@id>`
+/// mention tokens.
+pub async fn filter_member_ids(
+    pool: &DbPool,
+    space_id: &str,
+    user_ids: &[String],
+) -> Result<Vec<String>, AppError> {
+    if user_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let in_clause = vec!["?"; user_ids.len()].join(", ");
+    let sql = super::q(&format!(
+        "SELECT user_id FROM members WHERE space_id = ? AND user_id IN ({in_clause})"
+    ));
+    let mut q = sqlx::query(&sql).bind(space_id);
+    for id in user_ids {
+        q = q.bind(id);
+    }
+    let rows = q.fetch_all(pool).await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| r.get::<String, _>("user_id"))
+        .collect())
+}
+
 /// Adds a user as a member of a space. Returns `(MemberRow, newly_added)` where
 /// `newly_added` is `true` only if the user was not already a member.
 pub async fn add_member(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
     is_postgres: bool,
 ) -> Result<(MemberRow, bool), AppError> {
+    // New members of a space with screening on start out pending.
     let sql = if is_postgres {
-        "INSERT INTO members (user_id, space_id) VALUES (?, ?) ON CONFLICT DO NOTHING"
+        "INSERT INTO members (user_id, space_id, pending) VALUES (?, ?, \
+         COALESCE((SELECT enabled FROM member_screenings WHERE space_id = ?), ?)) ON CONFLICT DO NOTHING"
     } else {
-        "INSERT OR IGNORE INTO members (user_id, space_id) VALUES (?, ?)"
+        "INSERT OR IGNORE INTO members (user_id, space_id, pending) VALUES (?, ?, \
+         COALESCE((SELECT enabled FROM member_screenings WHERE space_id = ?), ?))"
     };
     let result = sqlx::query(&super::q(sql))
         .bind(user_id)
         .bind(space_id)
+        .bind(space_id)
+        .bind(false)
         .execute(pool)
         .await?;
 
@@ -137,7 +354,54 @@ pub async fn add_member(
     Ok((member, newly_added))
 }
 
-pub async fn remove_member(pool: &AnyPool, space_id: &str, user_id: &str) -> Result<(), AppError> {
+/// Whether the two users are members of at least one common space.
+pub async fn share_space(pool: &DbPool, user_a: &str, user_b: &str) -> Result<bool, AppError> {
+    let row = sqlx::query_as::<_, (i64,)>(&super::q(
+        "SELECT COUNT(*) FROM members a JOIN members b ON b.space_id = a.space_id \
+         WHERE a.user_id = ? AND b.user_id = ?",
+    ))
+    .bind(user_a)
+    .bind(user_b)
+    .fetch_one(pool)
+    .await?;
+    Ok(row.0 > 0)
+}
+
+/// Take a member's roles off their roles' member counts. Runs before
+/// `member_roles` rows go, whether deleted directly or by cascade.
+pub async fn release_member_roles(
+    pool: &DbPool,
+    space_id: &str,
+    user_id: &str,
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "UPDATE roles SET member_count = member_count - 1 WHERE id IN \
+         (SELECT role_id FROM member_roles WHERE space_id = ? AND user_id = ?)",
+    ))
+    .bind(space_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn adjust_role_member_count(
+    pool: &DbPool,
+    role_id: &str,
+    delta: i64,
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "UPDATE roles SET member_count = member_count + ? WHERE id = ?",
+    ))
+    .bind(delta)
+    .bind(role_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn remove_member(pool: &DbPool, space_id: &str, user_id: &str) -> Result<(), AppError> {
+    release_member_roles(pool, space_id, user_id).await?;
     sqlx::query(&super::q(
         "DELETE FROM members WHERE space_id = ? AND user_id = ?",
     ))
@@ -152,7 +416,7 @@ pub async fn remove_member(pool: &AnyPool, space_id: &str, user_id: &str) -> Res
 /// space: messages (in channels belonging to the space), reactions on those
 /// messages, read states, and channel mutes.
 pub async fn remove_member_and_data(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
 ) -> Result<(), AppError> {
@@ -207,7 +471,7 @@ pub async fn remove_member_and_data(
     .execute(pool)
     .await?;
 
-    // NULL out emojis created by this user in this space
+    // NULL out emojis and stickers created by this user in this space
     sqlx::query(&super::q(
         "UPDATE emojis SET creator_id = NULL WHERE creator_id = ? AND space_id = ?",
     ))
@@ -216,6 +480,14 @@ pub async fn remove_member_and_data(
     .execute(pool)
     .await?;
 
+    sqlx::query(&super::q(
+        "UPDATE stickers SET creator_id = NULL WHERE creator_id = ? AND space_id = ?",
+    ))
+    .bind(user_id)
+    .bind(space_id)
+    .execute(pool)
+    .await?;
+
     // Finally remove the membership (cascades member_roles via FK)
     remove_member(pool, space_id, user_id).await?;
 
@@ -223,7 +495,7 @@ pub async fn remove_member_and_data(
 }
 
 pub async fn update_member(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
     input: &UpdateMember,
@@ -260,6 +532,50 @@ pub async fn update_member(
         }
     }
 
+    // Per-space banner, bio, pronouns and timezone; an empty string falls
+    // back to the account's.
+    for (value, set_sql, clear_sql) in [
+        (
+            &input.banner,
+            "UPDATE members SET banner = ? WHERE space_id = ? AND user_id = ?",
+            "UPDATE members SET banner = NULL WHERE space_id = ? AND user_id = ?",
+        ),
+        (
+            &input.bio,
+            "UPDATE members SET bio = ? WHERE space_id = ? AND user_id = ?",
+            "UPDATE members SET bio = NULL WHERE space_id = ? AND user_id = ?",
+        ),
+        (
+            &input.pronouns,
+            "UPDATE members SET pronouns = ? WHERE space_id = ? AND user_id = ?",
+            "UPDATE members SET pronouns = NULL WHERE space_id = ? AND user_id = ?",
+        ),
+        (
+            &input.timezone,
+            "UPDATE members SET timezone = ? WHERE space_id = ? AND user_id = ?",
+            "UPDATE members SET timezone = NULL WHERE space_id = ? AND user_id = ?",
+        ),
+    ] {
+        match value.as_deref() {
+            Some("") => {
+                sqlx::query(&super::q(clear_sql))
+                    .bind(space_id)
+                    .bind(user_id)
+                    .execute(pool)
+                    .await?;
+            }
+            Some(value) => {
+                sqlx::query(&super::q(set_sql))
+                    .bind(value)
+                    .bind(space_id)
+                    .bind(user_id)
+                    .execute(pool)
+                    .await?;
+            }
+            None => {}
+        }
+    }
+
     if let Some(mute) = input.mute {
         sqlx::query(&super::q(
             "UPDATE members SET mute = ? WHERE space_id = ? AND user_id = ?",
@@ -309,6 +625,7 @@ pub async fn update_member(
 
     // Handle role updates
     if let Some(ref roles) = input.roles {
+        release_member_roles(pool, space_id, user_id).await?;
         sqlx::query(&super::q(
             "DELETE FROM member_roles WHERE user_id = ? AND space_id = ?",
         ))
@@ -325,6 +642,7 @@ pub async fn update_member(
             .bind(role_id)
             .execute(pool)
             .await?;
+            adjust_role_member_count(pool, role_id, 1).await?;
         }
     }
 
@@ -332,7 +650,7 @@ pub async fn update_member(
 }
 
 pub async fn get_member_role_ids(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
 ) -> Result<Vec<String>, AppError> {
@@ -347,7 +665,7 @@ pub async fn get_member_role_ids(
 }
 
 pub async fn add_role_to_member(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
     role_id: &str,
@@ -358,22 +676,25 @@ pub async fn add_role_to_member(
     } else {
         "INSERT OR IGNORE INTO member_roles (user_id, space_id, role_id) VALUES (?, ?, ?)"
     };
-    sqlx::query(&super::q(sql))
+    let result = sqlx::query(&super::q(sql))
         .bind(user_id)
         .bind(space_id)
         .bind(role_id)
         .execute(pool)
         .await?;
+    if result.rows_affected() > 0 {
+        adjust_role_member_count(pool, role_id, 1).await?;
+    }
     Ok(())
 }
 
 pub async fn remove_role_from_member(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
     role_id: &str,
 ) -> Result<(), AppError> {
-    sqlx::query(&super::q(
+    let result = sqlx::query(&super::q(
         "DELETE FROM member_roles WHERE user_id = ? AND space_id = ? AND role_id = ?",
     ))
     .bind(user_id)
@@ -381,5 +702,8 @@ pub async fn remove_role_from_member(
     .bind(role_id)
     .execute(pool)
     .await?;
+    if result.rows_affected() > 0 {
+        adjust_role_member_count(pool, role_id, -1).await?;
+    }
     Ok(())
 }
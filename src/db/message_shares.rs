@@ -0,0 +1,95 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::message::MessageShare;
+use crate::snowflake;
+
+fn row_to_share(row: sqlx::any::AnyRow) -> MessageShare {
+    MessageShare {
+        id: row.get("id"),
+        message_id: row.get("message_id"),
+        channel_id: row.get("channel_id"),
+        creator_id: row.get("creator_id"),
+        created_at: row.get("created_at"),
+        token: None,
+    }
+}
+
+const SELECT_SHARES: &str =
+    "SELECT id, message_id, channel_id, creator_id, created_at FROM message_shares";
+
+pub async fn get_share(pool: &DbPool, share_id: &str) -> Result<Option<MessageShare>, AppError> {
+    let row = sqlx::query(&super::q(&format!("{SELECT_SHARES} WHERE id = ?")))
+        .bind(share_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(row_to_share))
+}
+
+/// A message's live share links, oldest first.
+pub async fn list_shares(pool: &DbPool, message_id: &str) -> Result<Vec<MessageShare>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_SHARES} WHERE message_id = ? ORDER BY id"
+    )))
+    .bind(message_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(row_to_share).collect())
+}
+
+/// The link `creator_id` already made for a message, so asking again hands
+/// back the same one.
+pub async fn find_share(
+    pool: &DbPool,
+    message_id: &str,
+    creator_id: &str,
+) -> Result<Option<MessageShare>, AppError> {
+    let row = sqlx::query(&super::q(&format!(
+        "{SELECT_SHARES} WHERE message_id = ? AND creator_id = ?"
+    )))
+    .bind(message_id)
+    .bind(creator_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(row_to_share))
+}
+
+pub async fn create_share(
+    pool: &DbPool,
+    message_id: &str,
+    channel_id: &str,
+    creator_id: &str,
+) -> Result<MessageShare, AppError> {
+    let share = MessageShare {
+        id: snowflake::generate(),
+        message_id: message_id.to_string(),
+        channel_id: channel_id.to_string(),
+        creator_id: creator_id.to_string(),
+        created_at: chrono::Utc::now()
+            .format("%Y-%m-%dT%H:%M:%S+00:00")
+            .to_string(),
+        token: None,
+    };
+    sqlx::query(&super::q(
+        "INSERT INTO message_shares (id, message_id, channel_id, creator_id, created_at) \
+         VALUES (?, ?, ?, ?, ?)",
+    ))
+    .bind(&share.id)
+    .bind(&share.message_id)
+    .bind(&share.channel_id)
+    .bind(&share.creator_id)
+    .bind(&share.created_at)
+    .execute(pool)
+    .await?;
+    Ok(share)
+}
+
+pub async fn delete_share(pool: &DbPool, share_id: &str) -> Result<(), AppError> {
+    sqlx::query(&super::q("DELETE FROM message_shares WHERE id = ?"))
+        .bind(share_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
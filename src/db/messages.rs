@@ -1,9 +1,13 @@
 use std::collections::HashMap;
 
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
-use crate::models::message::{CreateMessage, MessageRow, UpdateMessage};
+use crate::models::message::{
+    CreateMessage, MessageRevision, MessageRow, UpdateMessage, FLAG_EPHEMERAL,
+};
 use crate::snowflake;
 
 fn row_to_message(row: sqlx::any::AnyRow) -> MessageRow {
@@ -25,15 +29,17 @@ fn row_to_message(row: sqlx::any::AnyRow) -> MessageRow {
         reply_to: row.get("reply_to"),
         flags: row.get("flags"),
         webhook_id: row.get("webhook_id"),
+        webhook_username: row.try_get("webhook_username").ok().flatten(),
+        webhook_avatar: row.try_get("webhook_avatar").ok().flatten(),
         thread_id: row.get("thread_id"),
         title: row.get("title"),
         origin: row.try_get("origin").ok().flatten(),
     }
 }
 
-const SELECT_MESSAGES: &str = "SELECT id, channel_id, space_id, author_id, content, type, created_at, edited_at, tts, pinned, mention_everyone, mentions, mention_roles, embeds, reply_to, flags, webhook_id, thread_id, title, origin FROM messages";
+const SELECT_MESSAGES: &str = "SELECT id, channel_id, space_id, author_id, content, type, created_at, edited_at, tts, pinned, mention_everyone, mentions, mention_roles, embeds, reply_to, flags, webhook_id, webhook_username, webhook_avatar, thread_id, title, origin FROM messages";
 
-pub async fn get_message_row(pool: &AnyPool, message_id: &str) -> Result<MessageRow, AppError> {
+pub async fn get_message_row(pool: &DbPool, message_id: &str) -> Result<MessageRow, AppError> {
     let row = sqlx::query(&super::q(&format!("{SELECT_MESSAGES} WHERE id = ?")))
         .bind(message_id)
         .fetch_optional(pool)
@@ -43,8 +49,63 @@ pub async fn get_message_row(pool: &AnyPool, message_id: &str) -> Result<Message
     Ok(row_to_message(row))
 }
 
+/// Set [flags] bits on a message, leaving the others alone.
+pub async fn add_message_flags(
+    pool: &DbPool,
+    message_id: &str,
+    flags: i64,
+) -> Result<MessageRow, AppError> {
+    sqlx::query(&super::q(
+        "UPDATE messages SET flags = flags | ? WHERE id = ?",
+    ))
+    .bind(flags)
+    .bind(message_id)
+    .execute(pool)
+    .await?;
+    get_message_row(pool, message_id).await
+}
+
+/// Clear [flags] bits on a message, leaving the others alone.
+pub async fn remove_message_flags(
+    pool: &DbPool,
+    message_id: &str,
+    flags: i64,
+) -> Result<MessageRow, AppError> {
+    sqlx::query(&super::q(
+        "UPDATE messages SET flags = flags & ~? WHERE id = ?",
+    ))
+    .bind(flags)
+    .bind(message_id)
+    .execute(pool)
+    .await?;
+    get_message_row(pool, message_id).await
+}
+
+/// The given messages, in no particular order. Unknown IDs are skipped.
+pub async fn get_message_rows(
+    pool: &DbPool,
+    message_ids: &[String],
+) -> Result<Vec<MessageRow>, AppError> {
+    if message_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; message_ids.len()].join(", ");
+    let sql = super::q(&format!("{SELECT_MESSAGES} WHERE id IN ({placeholders})"));
+    let mut query = sqlx::query(&sql);
+    for id in message_ids {
+        query = query.bind(id);
+    }
+    let rows = query.fetch_all(pool).await?;
+    Ok(rows.into_iter().map(row_to_message).collect())
+}
+
+/// Keeps ephemeral interaction responses and quarantined messages out of
+/// history and search (12 is [FLAG_EPHEMERAL] |
+/// [crate::models::message::FLAG_QUARANTINED]).
+const NOT_HIDDEN: &str = "(flags & 12) = 0";
+
 pub async fn list_messages(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     after: Option<&str>,
     limit: i64,
@@ -54,7 +115,7 @@ pub async fn list_messages(
         (Some(after_id), Some(tid)) => {
             // Thread replies after a cursor
             sqlx::query(&super::q(&format!(
-                "{SELECT_MESSAGES} WHERE channel_id = ? AND thread_id = ? AND id > ? ORDER BY id ASC LIMIT ?"
+                "{SELECT_MESSAGES} WHERE channel_id = ? AND thread_id = ? AND id > ? AND {NOT_HIDDEN} ORDER BY id ASC LIMIT ?"
             )))
             .bind(channel_id)
             .bind(tid)
@@ -66,7 +127,7 @@ pub async fn list_messages(
         (None, Some(tid)) => {
             // Thread replies (oldest first)
             sqlx::query(&super::q(&format!(
-                "{SELECT_MESSAGES} WHERE channel_id = ? AND thread_id = ? ORDER BY id ASC LIMIT ?"
+                "{SELECT_MESSAGES} WHERE channel_id = ? AND thread_id = ? AND {NOT_HIDDEN} ORDER BY id ASC LIMIT ?"
             )))
             .bind(channel_id)
             .bind(tid)
@@ -77,7 +138,7 @@ pub async fn list_messages(
         (Some(after_id), None) => {
             // Main channel feed after a cursor (exclude thread replies)
             sqlx::query(&super::q(&format!(
-                "{SELECT_MESSAGES} WHERE channel_id = ? AND thread_id IS NULL AND id > ? ORDER BY id ASC LIMIT ?"
+                "{SELECT_MESSAGES} WHERE channel_id = ? AND thread_id IS NULL AND id > ? AND {NOT_HIDDEN} ORDER BY id ASC LIMIT ?"
             )))
             .bind(channel_id)
             .bind(after_id)
@@ -88,7 +149,7 @@ pub async fn list_messages(
         (None, None) => {
             // Main channel feed (exclude thread replies)
             sqlx::query(&super::q(&format!(
-                "{SELECT_MESSAGES} WHERE channel_id = ? AND thread_id IS NULL ORDER BY id DESC LIMIT ?"
+                "{SELECT_MESSAGES} WHERE channel_id = ? AND thread_id IS NULL AND {NOT_HIDDEN} ORDER BY id DESC LIMIT ?"
             )))
             .bind(channel_id)
             .bind(limit + 1)
@@ -103,7 +164,7 @@ pub async fn list_messages(
 /// Lists top-level forum posts with optional sorting.
 /// Returns posts along with their last_reply_at timestamps.
 pub async fn list_forum_posts(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     after: Option<&str>,
     limit: i64,
@@ -124,7 +185,7 @@ pub async fn list_forum_posts(
     let rows = if let Some(after_id) = after {
         // For cursor-based pagination with sorting, use id as cursor
         let sql = format!(
-            "SELECT m.id, m.channel_id, m.space_id, m.author_id, m.content, m.type, m.created_at, m.edited_at, m.tts, m.pinned, m.mention_everyone, m.mentions, m.mention_roles, m.embeds, m.reply_to, m.flags, m.webhook_id, m.thread_id, m.title FROM messages m WHERE m.channel_id = ? AND m.thread_id IS NULL AND m.id > ? {order_clause} LIMIT ?"
+            "SELECT m.id, m.channel_id, m.space_id, m.author_id, m.content, m.type, m.created_at, m.edited_at, m.tts, m.pinned, m.mention_everyone, m.mentions, m.mention_roles, m.embeds, m.reply_to, m.flags, m.webhook_id, m.webhook_username, m.webhook_avatar, m.thread_id, m.title FROM messages m WHERE m.channel_id = ? AND m.thread_id IS NULL AND m.id > ? {order_clause} LIMIT ?"
         );
         sqlx::query(&super::q(&sql))
             .bind(channel_id)
@@ -134,7 +195,7 @@ pub async fn list_forum_posts(
             .await?
     } else {
         let sql = format!(
-            "SELECT m.id, m.channel_id, m.space_id, m.author_id, m.content, m.type, m.created_at, m.edited_at, m.tts, m.pinned, m.mention_everyone, m.mentions, m.mention_roles, m.embeds, m.reply_to, m.flags, m.webhook_id, m.thread_id, m.title FROM messages m WHERE m.channel_id = ? AND m.thread_id IS NULL {order_clause} LIMIT ?"
+            "SELECT m.id, m.channel_id, m.space_id, m.author_id, m.content, m.type, m.created_at, m.edited_at, m.tts, m.pinned, m.mention_everyone, m.mentions, m.mention_roles, m.embeds, m.reply_to, m.flags, m.webhook_id, m.webhook_username, m.webhook_avatar, m.thread_id, m.title FROM messages m WHERE m.channel_id = ? AND m.thread_id IS NULL {order_clause} LIMIT ?"
         );
         sqlx::query(&super::q(&sql))
             .bind(channel_id)
@@ -149,7 +210,7 @@ pub async fn list_forum_posts(
 /// Returns last_reply_at timestamps for multiple parent message IDs.
 /// Result maps parent_message_id -> last_reply_at ISO timestamp.
 pub async fn get_last_reply_timestamps(
-    pool: &AnyPool,
+    pool: &DbPool,
     message_ids: &[String],
 ) -> Result<HashMap<String, String>, AppError> {
     if message_ids.is_empty() {
@@ -176,7 +237,7 @@ pub async fn get_last_reply_timestamps(
 }
 
 pub async fn create_message(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     author_id: &str,
     space_id: Option<&str>,
@@ -187,21 +248,44 @@ pub async fn create_message(
 
     // Resolve inline @-mentions so the persisted message (and its gateway
     // broadcast) carries the concrete recipient IDs. `@everyone`/`@here` set the
-    // flag; `@username` resolves to member IDs within the space (DMs have no
-    // space, so usernames there are left as plain text).
+    // flag; `@username` and `<@id>` resolve to members of the space and
+    // `<@&id>` to its roles (DMs have no space, so mentions there are left as
+    // plain text).
     let parsed = crate::mentions::parse_mentions(&input.content);
-    let mention_user_ids = match space_id {
-        Some(sid) if !parsed.usernames.is_empty() => {
-            super::members::resolve_mention_user_ids(pool, sid, &parsed.usernames)
+    let (mention_user_ids, mention_role_ids) = match space_id {
+        Some(sid) => {
+            let mut user_ids =
+                super::members::resolve_mention_user_ids(pool, sid, &parsed.usernames)
+                    .await
+                    .unwrap_or_default();
+            for id in super::members::filter_member_ids(pool, sid, &parsed.user_ids)
                 .await
                 .unwrap_or_default()
+            {
+                if !user_ids.contains(&id) {
+                    user_ids.push(id);
+                }
+            }
+            let role_ids = if parsed.role_ids.is_empty() {
+                Vec::new()
+            } else {
+                super::roles::list_roles(pool, sid)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|r| parsed.role_ids.contains(&r.id))
+                    .map(|r| r.id)
+                    .collect()
+            };
+            (user_ids, role_ids)
         }
-        _ => Vec::new(),
+        None => (Vec::new(), Vec::new()),
     };
     let mentions_json = serde_json::to_string(&mention_user_ids).unwrap();
+    let mention_roles_json = serde_json::to_string(&mention_role_ids).unwrap();
 
     sqlx::query(&super::q(
-        "INSERT INTO messages (id, channel_id, space_id, author_id, content, tts, mention_everyone, mentions, embeds, reply_to, thread_id, title) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        "INSERT INTO messages (id, channel_id, space_id, author_id, content, tts, mention_everyone, mentions, mention_roles, embeds, reply_to, thread_id, title) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
     ))
     .bind(&id)
     .bind(channel_id)
@@ -211,6 +295,7 @@ pub async fn create_message(
     .bind(input.tts.unwrap_or(false))
     .bind(parsed.everyone)
     .bind(&mentions_json)
+    .bind(&mention_roles_json)
     .bind(&embeds_json)
     .bind(&input.reply_to)
     .bind(&input.thread_id)
@@ -218,6 +303,37 @@ pub async fn create_message(
     .execute(pool)
     .await?;
 
+    if let Some(poll) = &input.poll {
+        let answers: Vec<crate::models::poll::PollAnswer> = poll
+            .answers
+            .iter()
+            .zip(1..)
+            .map(|(a, id)| crate::models::poll::PollAnswer {
+                id,
+                text: a.text.trim().to_string(),
+            })
+            .collect();
+        let hours = poll
+            .duration_hours
+            .unwrap_or(crate::models::poll::DEFAULT_POLL_DURATION_HOURS);
+        let expires_at = (chrono::Utc::now() + chrono::Duration::hours(hours))
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string();
+        super::polls::create_poll(
+            pool,
+            &id,
+            poll.question.trim(),
+            &answers,
+            poll.allow_multiselect,
+            &expires_at,
+        )
+        .await?;
+    }
+
+    if let Some(sticker_ids) = &input.sticker_ids {
+        super::stickers::add_message_stickers(pool, &id, sticker_ids).await?;
+    }
+
     // Only top-level messages bump channels.last_message_id. Thread replies live
     // inside a thread; bumping the channel pointer would make get_unread_channels
     // report the channel as unread for everyone who isn't following that thread.
@@ -234,6 +350,82 @@ pub async fn create_message(
     get_message_row(pool, &id).await
 }
 
+/// Store an ephemeral interaction response for `user_id` alone, expiring at
+/// `expires_at`. Unlike [create_message] it doesn't resolve mentions or
+/// move the channel's `last_message_id`: nobody else will ever see it.
+pub async fn create_ephemeral_message(
+    pool: &DbPool,
+    channel_id: &str,
+    author_id: &str,
+    space_id: Option<&str>,
+    user_id: &str,
+    input: &CreateMessage,
+    expires_at: &str,
+) -> Result<MessageRow, AppError> {
+    let id = snowflake::generate();
+    let embeds_json = serde_json::to_string(&input.embeds.as_deref().unwrap_or(&[])).unwrap();
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(&super::q(
+        "INSERT INTO messages (id, channel_id, space_id, author_id, content, tts, embeds, flags) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(channel_id)
+    .bind(space_id)
+    .bind(author_id)
+    .bind(&input.content)
+    .bind(input.tts.unwrap_or(false))
+    .bind(&embeds_json)
+    .bind(FLAG_EPHEMERAL)
+    .execute(&mut tx)
+    .await?;
+    sqlx::query(&super::q(
+        "INSERT INTO ephemeral_messages (message_id, user_id, expires_at) VALUES (?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(user_id)
+    .bind(expires_at)
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    get_message_row(pool, &id).await
+}
+
+/// The only user who may see an ephemeral message.
+pub async fn ephemeral_recipient(
+    pool: &DbPool,
+    message_id: &str,
+) -> Result<Option<String>, AppError> {
+    let row: Option<(String,)> = sqlx::query_as(&super::q(
+        "SELECT user_id FROM ephemeral_messages WHERE message_id = ?",
+    ))
+    .bind(message_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|(user_id,)| user_id))
+}
+
+/// Delete ephemeral messages that expired at or before `now`. Returns how
+/// many went.
+pub async fn delete_expired_ephemeral_messages(pool: &DbPool, now: &str) -> Result<u64, AppError> {
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query(&super::q(
+        "DELETE FROM messages WHERE id IN (SELECT message_id FROM ephemeral_messages WHERE expires_at <= ?)",
+    ))
+    .bind(now)
+    .execute(&mut tx)
+    .await?;
+    sqlx::query(&super::q(
+        "DELETE FROM ephemeral_messages WHERE expires_at <= ?",
+    ))
+    .bind(now)
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
+
 /// Fields needed to mirror a remote message into the local replica.
 pub struct RemoteMessageInsert<'a> {
     /// Qualified message ID (`<snowflake>@<domain>`), assigned by the home server.
@@ -258,7 +450,7 @@ pub struct RemoteMessageInsert<'a> {
 /// (same qualified ID) is a no-op. Returns `None` when the message already
 /// existed (so callers can skip re-broadcasting), `Some(row)` when newly stored.
 pub async fn insert_remote_message(
-    pool: &AnyPool,
+    pool: &DbPool,
     msg: &RemoteMessageInsert<'_>,
 ) -> Result<Option<MessageRow>, AppError> {
     let res = sqlx::query(&super::q(
@@ -296,18 +488,21 @@ pub async fn insert_remote_message(
 
 /// Creates a system message with a custom type (e.g. "member_join", "member_leave").
 /// Unlike `create_message`, this sets the `type` column to the given value.
+/// `reply_to` points the message at the one it's about (a pin notice at
+/// the pinned message).
 pub async fn create_system_message(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     author_id: &str,
-    space_id: &str,
+    space_id: Option<&str>,
     content: &str,
     message_type: &str,
+    reply_to: Option<&str>,
 ) -> Result<MessageRow, AppError> {
     let id = snowflake::generate();
 
     sqlx::query(&super::q(
-        "INSERT INTO messages (id, channel_id, space_id, author_id, content, type, tts, embeds) VALUES (?, ?, ?, ?, ?, ?, FALSE, '[]')"
+        "INSERT INTO messages (id, channel_id, space_id, author_id, content, type, tts, embeds, reply_to) VALUES (?, ?, ?, ?, ?, ?, FALSE, '[]', ?)"
     ))
     .bind(&id)
     .bind(channel_id)
@@ -315,6 +510,7 @@ pub async fn create_system_message(
     .bind(author_id)
     .bind(content)
     .bind(message_type)
+    .bind(reply_to)
     .execute(pool)
     .await?;
 
@@ -331,7 +527,7 @@ pub async fn create_system_message(
 }
 
 pub async fn update_message(
-    pool: &AnyPool,
+    pool: &DbPool,
     message_id: &str,
     input: &UpdateMessage,
     is_postgres: bool,
@@ -374,7 +570,67 @@ pub async fn update_message(
     get_message_row(pool, message_id).await
 }
 
-pub async fn delete_message(pool: &AnyPool, message_id: &str) -> Result<(), AppError> {
+/// Most revisions kept per message; older ones are dropped.
+pub const MAX_REVISIONS_PER_MESSAGE: i64 = 50;
+
+/// Save [existing] as a revision before [editor_id]'s edit overwrites it.
+pub async fn record_revision(
+    pool: &DbPool,
+    existing: &MessageRow,
+    editor_id: &str,
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "INSERT INTO message_revisions (id, message_id, editor_id, content, title, embeds) VALUES (?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(snowflake::generate())
+    .bind(&existing.id)
+    .bind(editor_id)
+    .bind(&existing.content)
+    .bind(&existing.title)
+    .bind(&existing.embeds)
+    .execute(pool)
+    .await?;
+
+    sqlx::query(&super::q(
+        "DELETE FROM message_revisions WHERE message_id = ? AND id NOT IN \
+         (SELECT id FROM message_revisions WHERE message_id = ? ORDER BY id DESC LIMIT ?)",
+    ))
+    .bind(&existing.id)
+    .bind(&existing.id)
+    .bind(MAX_REVISIONS_PER_MESSAGE)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// A message's earlier versions, oldest first.
+pub async fn list_revisions(
+    pool: &DbPool,
+    message_id: &str,
+) -> Result<Vec<MessageRevision>, AppError> {
+    let rows = sqlx::query(&super::q(
+        "SELECT id, message_id, editor_id, content, title, embeds, revised_at \
+         FROM message_revisions WHERE message_id = ? ORDER BY id ASC",
+    ))
+    .bind(message_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| MessageRevision {
+            id: row.get("id"),
+            message_id: row.get("message_id"),
+            editor_id: row.get("editor_id"),
+            content: row.get("content"),
+            title: row.get("title"),
+            embeds: serde_json::from_str(&row.get::<String, _>("embeds"))
+                .unwrap_or_else(|_| serde_json::json!([])),
+            revised_at: row.get("revised_at"),
+        })
+        .collect())
+}
+
+pub async fn delete_message(pool: &DbPool, message_id: &str) -> Result<(), AppError> {
     sqlx::query(&super::q("DELETE FROM messages WHERE id = ?"))
         .bind(message_id)
         .execute(pool)
@@ -385,7 +641,7 @@ pub async fn delete_message(pool: &AnyPool, message_id: &str) -> Result<(), AppE
 /// Apply an authoritative edit to a mirrored (replica) message. `content` and
 /// `edited_at` are taken verbatim from the home server when present.
 pub async fn edit_remote_message(
-    pool: &AnyPool,
+    pool: &DbPool,
     message_id: &str,
     content: Option<&str>,
     edited_at: Option<&str>,
@@ -410,7 +666,7 @@ pub async fn edit_remote_message(
 /// Insert a reaction (idempotent). Used by the federation applier and the home
 /// server when a remote user reacts.
 pub async fn add_reaction(
-    pool: &AnyPool,
+    pool: &DbPool,
     message_id: &str,
     user_id: &str,
     emoji: &str,
@@ -427,7 +683,7 @@ pub async fn add_reaction(
 }
 
 pub async fn remove_reaction(
-    pool: &AnyPool,
+    pool: &DbPool,
     message_id: &str,
     user_id: &str,
     emoji: &str,
@@ -444,7 +700,7 @@ pub async fn remove_reaction(
 }
 
 pub async fn bulk_delete_messages(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     message_ids: &[String],
 ) -> Result<(), AppError> {
@@ -460,18 +716,60 @@ pub async fn bulk_delete_messages(
     Ok(())
 }
 
+/// IDs of the newest messages in [channel_id] posted at or after [since]
+/// (`YYYY-MM-DD HH:MM:SS`, UTC), optionally only [author_id]'s, at most
+/// [limit] of them.
+pub async fn list_recent_message_ids(
+    pool: &DbPool,
+    channel_id: &str,
+    author_id: Option<&str>,
+    since: &str,
+    limit: i64,
+) -> Result<Vec<String>, AppError> {
+    let mut sql = String::from("SELECT id FROM messages WHERE channel_id = ? AND created_at >= ?");
+    if author_id.is_some() {
+        sql.push_str(" AND author_id = ?");
+    }
+    sql.push_str(" ORDER BY id DESC LIMIT ?");
+    let sql = super::q(&sql);
+    let mut query = sqlx::query_as::<_, (String,)>(&sql)
+        .bind(channel_id)
+        .bind(since);
+    if let Some(author_id) = author_id {
+        query = query.bind(author_id);
+    }
+    let rows = query.bind(limit).fetch_all(pool).await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// Delete every message in [channel_id]; returns how many went.
+pub async fn delete_channel_messages(pool: &DbPool, channel_id: &str) -> Result<u64, AppError> {
+    let result = sqlx::query(&super::q("DELETE FROM messages WHERE channel_id = ?"))
+        .bind(channel_id)
+        .execute(pool)
+        .await?;
+    sqlx::query(&super::q(
+        "UPDATE channels SET last_message_id = NULL WHERE id = ?",
+    ))
+    .bind(channel_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Pin a message. Returns false if it was already pinned.
 pub async fn pin_message(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     message_id: &str,
     is_postgres: bool,
-) -> Result<(), AppError> {
+) -> Result<bool, AppError> {
     let sql = if is_postgres {
         "INSERT INTO pinned_messages (channel_id, message_id) VALUES (?, ?) ON CONFLICT DO NOTHING"
     } else {
         "INSERT OR IGNORE INTO pinned_messages (channel_id, message_id) VALUES (?, ?)"
     };
-    sqlx::query(&super::q(sql))
+    let result = sqlx::query(&super::q(sql))
         .bind(channel_id)
         .bind(message_id)
         .execute(pool)
@@ -480,15 +778,16 @@ pub async fn pin_message(
         .bind(message_id)
         .execute(pool)
         .await?;
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
+/// Unpin a message. Returns false if it wasn't pinned.
 pub async fn unpin_message(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     message_id: &str,
-) -> Result<(), AppError> {
-    sqlx::query(&super::q(
+) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
         "DELETE FROM pinned_messages WHERE channel_id = ? AND message_id = ?",
     ))
     .bind(channel_id)
@@ -499,7 +798,28 @@ pub async fn unpin_message(
         .bind(message_id)
         .execute(pool)
         .await?;
-    Ok(())
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn count_pins(pool: &DbPool, channel_id: &str) -> Result<i64, AppError> {
+    let row = sqlx::query(&super::q(
+        "SELECT COUNT(*) AS count FROM pinned_messages WHERE channel_id = ?",
+    ))
+    .bind(channel_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(row.get("count"))
+}
+
+/// When the channel's newest pin was made, if it has any.
+pub async fn last_pin_at(pool: &DbPool, channel_id: &str) -> Result<Option<String>, AppError> {
+    let row = sqlx::query(&super::q(
+        "SELECT MAX(pinned_at) AS pinned_at FROM pinned_messages WHERE channel_id = ?",
+    ))
+    .bind(channel_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(row.get("pinned_at"))
 }
 
 pub struct SearchMessagesParams<'a> {
@@ -514,7 +834,7 @@ pub struct SearchMessagesParams<'a> {
 }
 
 pub async fn search_messages(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     params: &SearchMessagesParams<'_>,
 ) -> Result<Vec<MessageRow>, AppError> {
@@ -525,12 +845,18 @@ pub async fn search_messages(
     let placeholders: Vec<&str> = params.channel_ids.iter().map(|_| "?").collect();
     let in_clause = placeholders.join(", ");
 
-    let mut sql = format!("{SELECT_MESSAGES} WHERE space_id = ? AND channel_id IN ({in_clause})");
+    let mut sql = format!(
+        "{SELECT_MESSAGES} WHERE space_id = ? AND channel_id IN ({in_clause}) AND {NOT_HIDDEN}"
+    );
     // We'll track bind values in order after space_id and channel_ids
     let mut bind_strings: Vec<String> = Vec::new();
 
     if let Some(q) = params.query {
-        sql.push_str(" AND content LIKE ?");
+        // Alt text counts too, so image-only messages can be found.
+        sql.push_str(
+            " AND (content LIKE ? OR id IN (SELECT message_id FROM attachments WHERE description LIKE ?))",
+        );
+        bind_strings.push(format!("%{q}%"));
         bind_strings.push(format!("%{q}%"));
     }
     if let Some(author) = params.author_id {
@@ -574,11 +900,11 @@ pub async fn search_messages(
 }
 
 pub async fn list_pinned_messages(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
 ) -> Result<Vec<MessageRow>, AppError> {
     let rows = sqlx::query(&super::q(
-        "SELECT m.id, m.channel_id, m.space_id, m.author_id, m.content, m.type, m.created_at, m.edited_at, m.tts, m.pinned, m.mention_everyone, m.mentions, m.mention_roles, m.embeds, m.reply_to, m.flags, m.webhook_id, m.thread_id FROM messages m INNER JOIN pinned_messages p ON m.id = p.message_id WHERE p.channel_id = ? ORDER BY p.pinned_at DESC"
+        "SELECT m.id, m.channel_id, m.space_id, m.author_id, m.content, m.type, m.created_at, m.edited_at, m.tts, m.pinned, m.mention_everyone, m.mentions, m.mention_roles, m.embeds, m.reply_to, m.flags, m.webhook_id, m.webhook_username, m.webhook_avatar, m.thread_id, m.title, m.origin FROM messages m INNER JOIN pinned_messages p ON m.id = p.message_id WHERE p.channel_id = ? ORDER BY p.pinned_at DESC"
     ))
     .bind(channel_id)
     .fetch_all(pool)
@@ -589,7 +915,7 @@ pub async fn list_pinned_messages(
 
 /// Returns the number of thread replies for a given parent message ID.
 pub async fn get_thread_reply_count(
-    pool: &AnyPool,
+    pool: &DbPool,
     parent_message_id: &str,
 ) -> Result<i64, AppError> {
     let row = sqlx::query(&super::q(
@@ -604,7 +930,7 @@ pub async fn get_thread_reply_count(
 /// Returns reply counts for multiple parent message IDs in a single query.
 /// Result maps parent_message_id -> reply_count.
 pub async fn get_thread_reply_counts(
-    pool: &AnyPool,
+    pool: &DbPool,
     message_ids: &[String],
 ) -> Result<HashMap<String, i64>, AppError> {
     if message_ids.is_empty() {
@@ -633,7 +959,7 @@ pub async fn get_thread_reply_counts(
 /// Returns thread metadata for a parent message: reply count, last reply timestamp,
 /// and participant user IDs.
 pub async fn get_thread_metadata(
-    pool: &AnyPool,
+    pool: &DbPool,
     parent_message_id: &str,
 ) -> Result<serde_json::Value, AppError> {
     let count_row = sqlx::query(&super::q(
@@ -672,7 +998,7 @@ pub async fn get_thread_metadata(
 
 /// Lists parent messages that have at least one thread reply in a channel.
 pub async fn list_active_threads(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
 ) -> Result<Vec<MessageRow>, AppError> {
     let sql = format!(
@@ -698,7 +1024,7 @@ pub struct ReactionAggregate {
 /// Fetches aggregated reaction data for a set of messages in one query.
 /// Returns a map from message_id to its list of reaction aggregates.
 pub async fn get_reactions_for_messages(
-    pool: &AnyPool,
+    pool: &DbPool,
     message_ids: &[String],
     current_user_id: Option<&str>,
 ) -> Result<HashMap<String, Vec<ReactionAggregate>>, AppError> {
@@ -2,32 +2,59 @@ pub mod admin;
 pub mod attachments;
 pub mod audit_log;
 pub mod auth;
+pub mod automod;
+pub mod ban_sync;
 pub mod bans;
+pub mod blocklist;
 pub mod channels;
+pub mod content_safety;
 pub mod dm_participants;
+pub mod drafts;
+pub mod email_domains;
+pub mod email_tokens;
 pub mod emojis;
+pub mod event_subscriptions;
 pub mod federation;
+pub mod forum_tags;
+pub mod instrument;
+pub mod integrations;
+pub mod interactions;
 pub mod invites;
 pub mod members;
+pub mod message_shares;
 pub mod messages;
 pub mod mutes;
+pub mod oauth2;
+pub mod passkeys;
 pub mod permission_overwrites;
 pub mod plugin_leaderboards;
 pub mod plugins;
+pub mod polls;
+mod pool;
+pub mod push;
+pub mod raid;
 pub mod read_states;
 pub mod relationships;
 pub mod reports;
 pub mod roles;
+pub mod screening;
+pub mod sessions;
 pub mod settings;
+pub mod signing_keys;
 pub mod soundboard;
 pub mod spaces;
+pub mod stickers;
+pub mod threads;
+pub mod user_settings;
 pub mod users;
+pub mod webhooks;
+
+pub use pool::{DbPool, DbTransaction};
 
 use std::str::FromStr;
 use std::sync::OnceLock;
 
 use sqlx::any::AnyConnectOptions;
-use sqlx::AnyPool;
 use sqlx::Connection;
 
 // ---------------------------------------------------------------------------
@@ -89,6 +116,19 @@ pub fn get_f64(row: &sqlx::any::AnyRow, col: &str) -> f64 {
         .unwrap_or_else(|_| row.get::<f32, _>(col) as f64)
 }
 
+/// Escape `%`, `_` and `\` so user input matches literally in a
+/// `LIKE ... ESCAPE '\'` pattern.
+pub fn escape_like(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for ch in input.chars() {
+        if matches!(ch, '%' | '_' | '\\') {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
 /// Returns the SQL expression for the current timestamp for the given backend.
 ///
 /// Both SQLite and PostgreSQL return timestamps as TEXT in `'YYYY-MM-DD HH24:MI:SS'`
@@ -282,7 +322,7 @@ async fn ensure_pg_database_exists(database_url: &str) -> Result<(), sqlx::Error
     Ok(())
 }
 
-pub async fn create_pool(database_url: &str) -> Result<AnyPool, sqlx::Error> {
+pub async fn create_pool(database_url: &str) -> Result<DbPool, sqlx::Error> {
     // Install both SQLite and Postgres drivers so AnyPool can pick at runtime.
     sqlx::any::install_default_drivers();
 
@@ -360,5 +400,5 @@ pub async fn create_pool(database_url: &str) -> Result<AnyPool, sqlx::Error> {
         sqlx::migrate!("./migrations").run(&pool).await?;
     }
 
-    Ok(pool)
+    Ok(DbPool::new(pool))
 }
@@ -1,10 +1,13 @@
-use sqlx::AnyPool;
+use crate::db::DbPool;
 
 use crate::error::AppError;
-use crate::models::mute::ChannelMute;
+use crate::models::mute::{
+    ChannelMute, ChannelOverride, ChannelStateUpdate, SpaceNotificationSettings,
+    UpdateSpaceNotificationSettings,
+};
 
 pub async fn get_mute(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
     channel_id: &str,
 ) -> Result<Option<ChannelMute>, AppError> {
@@ -24,7 +27,7 @@ pub async fn get_mute(
 }
 
 pub async fn create_mute(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
     channel_id: &str,
     is_postgres: bool,
@@ -40,13 +43,20 @@ pub async fn create_mute(
         .bind(channel_id)
         .execute(pool)
         .await?;
+    sqlx::query(&super::q(
+        "DELETE FROM channel_unmutes WHERE user_id = ? AND channel_id = ?",
+    ))
+    .bind(user_id)
+    .bind(channel_id)
+    .execute(pool)
+    .await?;
 
     get_mute(pool, user_id, channel_id)
         .await?
         .ok_or_else(|| AppError::NotFound("mute not found".into()))
 }
 
-pub async fn delete_mute(pool: &AnyPool, user_id: &str, channel_id: &str) -> Result<(), AppError> {
+pub async fn delete_mute(pool: &DbPool, user_id: &str, channel_id: &str) -> Result<(), AppError> {
     sqlx::query(&super::q(
         "DELETE FROM channel_mutes WHERE user_id = ? AND channel_id = ?",
     ))
@@ -58,7 +68,7 @@ pub async fn delete_mute(pool: &AnyPool, user_id: &str, channel_id: &str) -> Res
 }
 
 pub async fn list_mutes_for_user(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
 ) -> Result<Vec<ChannelMute>, AppError> {
     let rows = sqlx::query_as::<_, (String, String, String)>(
@@ -79,9 +89,10 @@ pub async fn list_mutes_for_user(
 }
 
 /// Returns all muted channel IDs for a user, including channels that inherit
-/// a mute from their parent category.
+/// a mute from their parent category or their space, less the ones the user
+/// keeps unmuted.
 pub async fn list_effective_muted_channel_ids(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
 ) -> Result<Vec<String>, AppError> {
     // Get directly muted channel IDs
@@ -106,10 +117,246 @@ pub async fn list_effective_muted_channel_ids(
     .fetch_all(pool)
     .await?;
 
+    let space_wide: Vec<(String,)> = sqlx::query_as(&super::q(
+        "SELECT c.id FROM channels c \
+         INNER JOIN space_mutes sm ON sm.space_id = c.space_id AND sm.user_id = ?",
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let unmuted: std::collections::HashSet<String> = sqlx::query_as::<_, (String,)>(&super::q(
+        "SELECT channel_id FROM channel_unmutes WHERE user_id = ?",
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|(id,)| id)
+    .collect();
+
     let mut all_ids = direct_ids;
-    for (id,) in inherited {
-        all_ids.insert(id);
+    for (id,) in inherited.into_iter().chain(space_wide) {
+        if !unmuted.contains(&id) {
+            all_ids.insert(id);
+        }
     }
 
     Ok(all_ids.into_iter().collect())
 }
+
+/// Channel IDs the user has collapsed in the sidebar.
+pub async fn list_collapsed_channel_ids(
+    pool: &DbPool,
+    user_id: &str,
+) -> Result<Vec<String>, AppError> {
+    let rows: Vec<(String,)> = sqlx::query_as(&super::q(
+        "SELECT channel_id FROM channel_collapses WHERE user_id = ? ORDER BY created_at",
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// Apply a batch of mute/collapse changes in one transaction.
+pub async fn apply_channel_states(
+    pool: &DbPool,
+    user_id: &str,
+    updates: &[ChannelStateUpdate],
+    is_postgres: bool,
+) -> Result<(), AppError> {
+    let insert = |table: &str| {
+        if is_postgres {
+            format!(
+                "INSERT INTO {table} (user_id, channel_id) VALUES (?, ?) ON CONFLICT DO NOTHING"
+            )
+        } else {
+            format!("INSERT OR IGNORE INTO {table} (user_id, channel_id) VALUES (?, ?)")
+        }
+    };
+    let mut tx = pool.begin().await?;
+    for update in updates {
+        for (table, value) in [
+            ("channel_mutes", update.muted),
+            ("channel_collapses", update.collapsed),
+        ] {
+            let sql = match value {
+                Some(true) => insert(table),
+                Some(false) => format!("DELETE FROM {table} WHERE user_id = ? AND channel_id = ?"),
+                None => continue,
+            };
+            sqlx::query(&super::q(&sql))
+                .bind(user_id)
+                .bind(&update.channel_id)
+                .execute(&mut tx)
+                .await?;
+        }
+        if update.muted == Some(true) {
+            sqlx::query(&super::q(
+                "DELETE FROM channel_unmutes WHERE user_id = ? AND channel_id = ?",
+            ))
+            .bind(user_id)
+            .bind(&update.channel_id)
+            .execute(&mut tx)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Opt the user out of @everyone mention badges in a space.
+pub async fn suppress_everyone(
+    pool: &DbPool,
+    user_id: &str,
+    space_id: &str,
+    is_postgres: bool,
+) -> Result<(), AppError> {
+    let sql = if is_postgres {
+        "INSERT INTO everyone_suppressions (user_id, space_id) VALUES (?, ?) ON CONFLICT DO NOTHING"
+    } else {
+        "INSERT OR IGNORE INTO everyone_suppressions (user_id, space_id) VALUES (?, ?)"
+    };
+    sqlx::query(&super::q(sql))
+        .bind(user_id)
+        .bind(space_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn unsuppress_everyone(
+    pool: &DbPool,
+    user_id: &str,
+    space_id: &str,
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "DELETE FROM everyone_suppressions WHERE user_id = ? AND space_id = ?",
+    ))
+    .bind(user_id)
+    .bind(space_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Space IDs where the user has suppressed @everyone.
+pub async fn list_everyone_suppressions(
+    pool: &DbPool,
+    user_id: &str,
+) -> Result<Vec<String>, AppError> {
+    let rows: Vec<(String,)> = sqlx::query_as(&super::q(
+        "SELECT space_id FROM everyone_suppressions WHERE user_id = ?",
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// The user's notification settings for one space. `channel_overrides` lists
+/// the space's channels the user has explicitly muted or kept unmuted.
+pub async fn get_space_settings(
+    pool: &DbPool,
+    user_id: &str,
+    space_id: &str,
+) -> Result<SpaceNotificationSettings, AppError> {
+    let flag = |table: &str| {
+        super::q(&format!(
+            "SELECT COUNT(*) FROM {table} WHERE user_id = ? AND space_id = ?"
+        ))
+    };
+    let (muted,): (i64,) = sqlx::query_as(&flag("space_mutes"))
+        .bind(user_id)
+        .bind(space_id)
+        .fetch_one(pool)
+        .await?;
+    let (suppressed,): (i64,) = sqlx::query_as(&flag("everyone_suppressions"))
+        .bind(user_id)
+        .bind(space_id)
+        .fetch_one(pool)
+        .await?;
+
+    let mut channel_overrides = Vec::new();
+    for (table, muted) in [("channel_mutes", true), ("channel_unmutes", false)] {
+        let rows: Vec<(String,)> = sqlx::query_as(&super::q(&format!(
+            "SELECT t.channel_id FROM {table} t INNER JOIN channels c ON c.id = t.channel_id \
+             WHERE t.user_id = ? AND c.space_id = ? ORDER BY t.created_at"
+        )))
+        .bind(user_id)
+        .bind(space_id)
+        .fetch_all(pool)
+        .await?;
+        channel_overrides.extend(rows.into_iter().map(|(channel_id,)| ChannelOverride {
+            channel_id,
+            muted: Some(muted),
+        }));
+    }
+
+    Ok(SpaceNotificationSettings {
+        space_id: space_id.to_string(),
+        muted: muted > 0,
+        suppress_everyone: suppressed > 0,
+        channel_overrides,
+    })
+}
+
+/// Apply a settings change in one transaction. An override's `muted` of
+/// `true` mutes the channel, `false` keeps it unmuted under a muted space or
+/// category, and `null` drops the override.
+pub async fn update_space_settings(
+    pool: &DbPool,
+    user_id: &str,
+    space_id: &str,
+    input: &UpdateSpaceNotificationSettings,
+    is_postgres: bool,
+) -> Result<(), AppError> {
+    let insert = |table: &str, column: &str| {
+        if is_postgres {
+            format!("INSERT INTO {table} (user_id, {column}) VALUES (?, ?) ON CONFLICT DO NOTHING")
+        } else {
+            format!("INSERT OR IGNORE INTO {table} (user_id, {column}) VALUES (?, ?)")
+        }
+    };
+    let delete = |table: &str, column: &str| {
+        format!("DELETE FROM {table} WHERE user_id = ? AND {column} = ?")
+    };
+
+    let mut tx = pool.begin().await?;
+    for (table, value) in [
+        ("space_mutes", input.muted),
+        ("everyone_suppressions", input.suppress_everyone),
+    ] {
+        let sql = match value {
+            Some(true) => insert(table, "space_id"),
+            Some(false) => delete(table, "space_id"),
+            None => continue,
+        };
+        sqlx::query(&super::q(&sql))
+            .bind(user_id)
+            .bind(space_id)
+            .execute(&mut tx)
+            .await?;
+    }
+    for o in input.channel_overrides.iter().flatten() {
+        let (set, clear) = match o.muted {
+            Some(true) => (Some("channel_mutes"), vec!["channel_unmutes"]),
+            Some(false) => (Some("channel_unmutes"), vec!["channel_mutes"]),
+            None => (None, vec!["channel_mutes", "channel_unmutes"]),
+        };
+        let statements = clear
+            .into_iter()
+            .map(|table| delete(table, "channel_id"))
+            .chain(set.map(|table| insert(table, "channel_id")));
+        for sql in statements {
+            sqlx::query(&super::q(&sql))
+                .bind(user_id)
+                .bind(&o.channel_id)
+                .execute(&mut tx)
+                .await?;
+        }
+    }
+    tx.commit().await?;
+    Ok(())
+}
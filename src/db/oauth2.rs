@@ -0,0 +1,238 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::middleware::auth::{create_token_hash, generate_token};
+use crate::models::oauth2::OAuthGrant;
+use crate::oauth2::{ACCESS_TOKEN_TTL_SECS, CODE_TTL_SECS};
+
+fn timestamp_in(secs: i64) -> String {
+    (chrono::Utc::now() + chrono::Duration::seconds(secs))
+        .format("%Y-%m-%dT%H:%M:%S+00:00")
+        .to_string()
+}
+
+/// Replace the application's client secret, returning the new one. Only its
+/// hash is kept.
+pub async fn reset_client_secret(pool: &DbPool, app_id: &str) -> Result<String, AppError> {
+    let secret = generate_token();
+    sqlx::query(&super::q(
+        "UPDATE applications SET client_secret_hash = ? WHERE id = ?",
+    ))
+    .bind(create_token_hash(&secret))
+    .bind(app_id)
+    .execute(pool)
+    .await?;
+    Ok(secret)
+}
+
+/// Whether `secret` is the application's current client secret.
+pub async fn check_client_secret(
+    pool: &DbPool,
+    app_id: &str,
+    secret: &str,
+) -> Result<bool, AppError> {
+    let stored: Option<Option<String>> = sqlx::query_scalar(&super::q(
+        "SELECT client_secret_hash FROM applications WHERE id = ?",
+    ))
+    .bind(app_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(stored.flatten().as_deref() == Some(create_token_hash(secret).as_str()))
+}
+
+pub async fn set_redirect_uris(
+    pool: &DbPool,
+    app_id: &str,
+    uris: &[String],
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "UPDATE applications SET redirect_uris = ? WHERE id = ?",
+    ))
+    .bind(serde_json::to_string(uris).unwrap())
+    .bind(app_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Issue an authorization code for `grant`, redeemable once within
+/// [CODE_TTL_SECS].
+pub async fn create_code(
+    pool: &DbPool,
+    grant: &OAuthGrant,
+    redirect_uri: &str,
+) -> Result<String, AppError> {
+    let code = generate_token();
+    sqlx::query(&super::q(
+        "INSERT INTO oauth2_codes (code_hash, application_id, user_id, redirect_uri, scopes, expires_at) \
+         VALUES (?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(create_token_hash(&code))
+    .bind(&grant.application_id)
+    .bind(&grant.user_id)
+    .bind(redirect_uri)
+    .bind(grant.scopes.join(" "))
+    .bind(timestamp_in(CODE_TTL_SECS))
+    .execute(pool)
+    .await?;
+    Ok(code)
+}
+
+/// Redeem a code issued to `app_id`. Returns the grant and the redirect URI
+/// it was issued for, or `None` if the code is unknown, expired or already
+/// used.
+pub async fn consume_code(
+    pool: &DbPool,
+    app_id: &str,
+    code: &str,
+) -> Result<Option<(OAuthGrant, String)>, AppError> {
+    let code_hash = create_token_hash(code);
+    let Some(row) = sqlx::query(&super::q(
+        "SELECT user_id, redirect_uri, scopes FROM oauth2_codes \
+         WHERE code_hash = ? AND application_id = ? AND expires_at > ?",
+    ))
+    .bind(&code_hash)
+    .bind(app_id)
+    .bind(timestamp_in(0))
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    // Only whoever deletes the row gets to use it.
+    let deleted = sqlx::query(&super::q("DELETE FROM oauth2_codes WHERE code_hash = ?"))
+        .bind(&code_hash)
+        .execute(pool)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    let scopes: String = row.get("scopes");
+    Ok(Some((
+        OAuthGrant {
+            application_id: app_id.to_string(),
+            user_id: row.get("user_id"),
+            scopes: scopes.split(' ').map(str::to_string).collect(),
+        },
+        row.get("redirect_uri"),
+    )))
+}
+
+/// Issue an access/refresh token pair for `grant`. Returns
+/// `(access_token, refresh_token)`.
+pub async fn create_token(pool: &DbPool, grant: &OAuthGrant) -> Result<(String, String), AppError> {
+    let access_token = generate_token();
+    let refresh_token = generate_token();
+    sqlx::query(&super::q(
+        "INSERT INTO oauth2_tokens (token_hash, refresh_token_hash, application_id, user_id, scopes, expires_at) \
+         VALUES (?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(create_token_hash(&access_token))
+    .bind(create_token_hash(&refresh_token))
+    .bind(&grant.application_id)
+    .bind(&grant.user_id)
+    .bind(grant.scopes.join(" "))
+    .bind(timestamp_in(ACCESS_TOKEN_TTL_SECS))
+    .execute(pool)
+    .await?;
+
+    // Drop this user's pairs for the app whose refresh window has passed.
+    sqlx::query(&super::q(
+        "DELETE FROM oauth2_tokens WHERE user_id = ? AND application_id = ? AND expires_at < ?",
+    ))
+    .bind(&grant.user_id)
+    .bind(&grant.application_id)
+    .bind(timestamp_in(-ACCESS_TOKEN_TTL_SECS))
+    .execute(pool)
+    .await?;
+    Ok((access_token, refresh_token))
+}
+
+/// Spend a refresh token issued to `app_id`, revoking its pair. Returns the
+/// grant to issue a fresh pair for.
+pub async fn consume_refresh_token(
+    pool: &DbPool,
+    app_id: &str,
+    refresh_token: &str,
+) -> Result<Option<OAuthGrant>, AppError> {
+    let refresh_hash = create_token_hash(refresh_token);
+    let Some(row) = sqlx::query(&super::q(
+        "SELECT user_id, scopes FROM oauth2_tokens \
+         WHERE refresh_token_hash = ? AND application_id = ? AND expires_at > ?",
+    ))
+    .bind(&refresh_hash)
+    .bind(app_id)
+    .bind(timestamp_in(-ACCESS_TOKEN_TTL_SECS))
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let deleted = sqlx::query(&super::q(
+        "DELETE FROM oauth2_tokens WHERE refresh_token_hash = ?",
+    ))
+    .bind(&refresh_hash)
+    .execute(pool)
+    .await?;
+    if deleted.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    let scopes: String = row.get("scopes");
+    Ok(Some(OAuthGrant {
+        application_id: app_id.to_string(),
+        user_id: row.get("user_id"),
+        scopes: scopes.split(' ').map(str::to_string).collect(),
+    }))
+}
+
+/// Revoke the pair `token` (access or refresh) belongs to.
+pub async fn revoke_token(pool: &DbPool, app_id: &str, token: &str) -> Result<(), AppError> {
+    let hash = create_token_hash(token);
+    sqlx::query(&super::q(
+        "DELETE FROM oauth2_tokens WHERE application_id = ? AND (token_hash = ? OR refresh_token_hash = ?)",
+    ))
+    .bind(app_id)
+    .bind(&hash)
+    .bind(&hash)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Look up an unexpired access token by hash. Returns the grant and whether
+/// the user is an admin, or `None` for unknown tokens and disabled users.
+pub async fn resolve_access_token(
+    pool: &DbPool,
+    token_hash: &str,
+) -> Result<Option<(OAuthGrant, bool)>, AppError> {
+    let row = sqlx::query(&super::q(
+        "SELECT t.application_id, t.user_id, t.scopes, u.is_admin, u.disabled \
+         FROM oauth2_tokens t JOIN users u ON t.user_id = u.id \
+         WHERE t.token_hash = ? AND t.expires_at > ?",
+    ))
+    .bind(token_hash)
+    .bind(timestamp_in(0))
+    .fetch_optional(pool)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    if crate::db::get_bool(&row, "disabled") {
+        return Ok(None);
+    }
+    let scopes: String = row.get("scopes");
+    Ok(Some((
+        OAuthGrant {
+            application_id: row.get("application_id"),
+            user_id: row.get("user_id"),
+            scopes: scopes.split(' ').map(str::to_string).collect(),
+        },
+        crate::db::get_bool(&row, "is_admin"),
+    )))
+}
@@ -0,0 +1,159 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::passkey::{Passkey, PasskeyCredential};
+use crate::snowflake;
+
+fn now() -> String {
+    chrono::Utc::now()
+        .format("%Y-%m-%dT%H:%M:%S+00:00")
+        .to_string()
+}
+
+fn row_to_passkey(row: sqlx::any::AnyRow) -> Passkey {
+    Passkey {
+        id: row.get("id"),
+        name: row.get("name"),
+        created_at: row.get("created_at"),
+        last_used_at: row.get("last_used_at"),
+    }
+}
+
+/// A user's passkeys, oldest first.
+pub async fn list_passkeys(pool: &DbPool, user_id: &str) -> Result<Vec<Passkey>, AppError> {
+    let rows = sqlx::query(&super::q(
+        "SELECT id, name, created_at, last_used_at FROM passkeys WHERE user_id = ? ORDER BY id",
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(row_to_passkey).collect())
+}
+
+pub async fn count_passkeys(pool: &DbPool, user_id: &str) -> Result<i64, AppError> {
+    let count: i64 =
+        sqlx::query_scalar(&super::q("SELECT COUNT(*) FROM passkeys WHERE user_id = ?"))
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+    Ok(count)
+}
+
+/// The credential IDs of a user's passkeys, for `allowCredentials` and
+/// `excludeCredentials`.
+pub async fn credential_ids(pool: &DbPool, user_id: &str) -> Result<Vec<String>, AppError> {
+    let ids: Vec<String> = sqlx::query_scalar(&super::q(
+        "SELECT credential_id FROM passkeys WHERE user_id = ? ORDER BY id",
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(ids)
+}
+
+pub async fn create_passkey(
+    pool: &DbPool,
+    user_id: &str,
+    name: &str,
+    credential_id: &str,
+    public_key: &str,
+    algorithm: i64,
+    sign_count: i64,
+) -> Result<Passkey, AppError> {
+    let id = snowflake::generate();
+    let created_at = now();
+    sqlx::query(&super::q(
+        "INSERT INTO passkeys (id, user_id, credential_id, public_key, algorithm, sign_count, name, created_at) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(user_id)
+    .bind(credential_id)
+    .bind(public_key)
+    .bind(algorithm)
+    .bind(sign_count)
+    .bind(name)
+    .bind(&created_at)
+    .execute(pool)
+    .await?;
+    Ok(Passkey {
+        id,
+        name: name.to_string(),
+        created_at,
+        last_used_at: None,
+    })
+}
+
+/// Look up a passkey by the ID its authenticator reports.
+pub async fn get_credential(
+    pool: &DbPool,
+    credential_id: &str,
+) -> Result<Option<PasskeyCredential>, AppError> {
+    let row = sqlx::query(&super::q(
+        "SELECT id, user_id, algorithm, public_key, sign_count FROM passkeys WHERE credential_id = ?",
+    ))
+    .bind(credential_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| PasskeyCredential {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        algorithm: row.get("algorithm"),
+        public_key: row.get("public_key"),
+        sign_count: row.get("sign_count"),
+    }))
+}
+
+/// Record a successful sign-in with a passkey.
+pub async fn record_use(pool: &DbPool, id: &str, sign_count: i64) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "UPDATE passkeys SET sign_count = ?, last_used_at = ? WHERE id = ?",
+    ))
+    .bind(sign_count)
+    .bind(now())
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn rename_passkey(
+    pool: &DbPool,
+    user_id: &str,
+    id: &str,
+    name: &str,
+) -> Result<Passkey, AppError> {
+    let result = sqlx::query(&super::q(
+        "UPDATE passkeys SET name = ? WHERE id = ? AND user_id = ?",
+    ))
+    .bind(name)
+    .bind(id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("unknown_passkey".to_string()));
+    }
+    let row = sqlx::query(&super::q(
+        "SELECT id, name, created_at, last_used_at FROM passkeys WHERE id = ?",
+    ))
+    .bind(id)
+    .fetch_one(pool)
+    .await?;
+    Ok(row_to_passkey(row))
+}
+
+/// Remove one of a user's passkeys. Returns false if they have no such
+/// passkey.
+pub async fn delete_passkey(pool: &DbPool, user_id: &str, id: &str) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "DELETE FROM passkeys WHERE id = ? AND user_id = ?",
+    ))
+    .bind(id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -1,14 +1,17 @@
-use sqlx::AnyPool;
+use std::collections::HashMap;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
-use crate::models::permission::PermissionOverwrite;
+use crate::models::channel::{ANNOUNCEMENT_LOCK_DENIES, CHANNEL_LOCK_DENIES};
+use crate::models::permission::{PermissionOverwrite, Permissions};
 
 pub async fn list_overwrites(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
 ) -> Result<Vec<PermissionOverwrite>, AppError> {
-    let rows = sqlx::query_as::<_, (String, String, String, String)>(&super::q(
-        "SELECT id, type, allow, deny FROM permission_overwrites WHERE channel_id = ?",
+    let rows = sqlx::query_as::<_, (String, String, i64, i64)>(&super::q(
+        "SELECT id, type, allow_bits, deny_bits FROM permission_overwrites WHERE channel_id = ?",
     ))
     .bind(channel_id)
     .fetch_all(pool)
@@ -19,29 +22,60 @@ pub async fn list_overwrites(
         .map(|(id, overwrite_type, allow, deny)| PermissionOverwrite {
             id,
             overwrite_type,
-            allow: serde_json::from_str(&allow).unwrap_or_default(),
-            deny: serde_json::from_str(&deny).unwrap_or_default(),
+            allow: Permissions::from_bits(allow),
+            deny: Permissions::from_bits(deny),
         })
         .collect())
 }
 
+/// Overwrites for several channels in one query, keyed by channel ID.
+pub async fn list_overwrites_for_channels(
+    pool: &DbPool,
+    channel_ids: &[String],
+) -> Result<HashMap<String, Vec<PermissionOverwrite>>, AppError> {
+    if channel_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let placeholders: Vec<&str> = channel_ids.iter().map(|_| "?").collect();
+    let sql = super::q(&format!(
+        "SELECT channel_id, id, type, allow_bits, deny_bits FROM permission_overwrites \
+         WHERE channel_id IN ({})",
+        placeholders.join(", ")
+    ));
+    let mut query = sqlx::query_as::<_, (String, String, String, i64, i64)>(&sql);
+    for id in channel_ids {
+        query = query.bind(id);
+    }
+
+    let mut result: HashMap<String, Vec<PermissionOverwrite>> = HashMap::new();
+    for (channel_id, id, overwrite_type, allow, deny) in query.fetch_all(pool).await? {
+        result
+            .entry(channel_id)
+            .or_default()
+            .push(PermissionOverwrite {
+                id,
+                overwrite_type,
+                allow: Permissions::from_bits(allow),
+                deny: Permissions::from_bits(deny),
+            });
+    }
+    Ok(result)
+}
+
 pub async fn upsert_overwrite(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     overwrite: &PermissionOverwrite,
 ) -> Result<(), AppError> {
-    let allow_json = serde_json::to_string(&overwrite.allow).unwrap();
-    let deny_json = serde_json::to_string(&overwrite.deny).unwrap();
-
     sqlx::query(
-        &super::q("INSERT INTO permission_overwrites (id, channel_id, type, allow, deny) VALUES (?, ?, ?, ?, ?) \
-         ON CONFLICT (id, channel_id) DO UPDATE SET type = excluded.type, allow = excluded.allow, deny = excluded.deny"),
+        &super::q("INSERT INTO permission_overwrites (id, channel_id, type, allow_bits, deny_bits) VALUES (?, ?, ?, ?, ?) \
+         ON CONFLICT (id, channel_id) DO UPDATE SET type = excluded.type, allow_bits = excluded.allow_bits, deny_bits = excluded.deny_bits"),
     )
     .bind(&overwrite.id)
     .bind(channel_id)
     .bind(&overwrite.overwrite_type)
-    .bind(&allow_json)
-    .bind(&deny_json)
+    .bind(overwrite.allow.bits())
+    .bind(overwrite.deny.bits())
     .execute(pool)
     .await?;
 
@@ -49,7 +83,7 @@ pub async fn upsert_overwrite(
 }
 
 pub async fn delete_overwrite(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     overwrite_id: &str,
 ) -> Result<(), AppError> {
@@ -62,3 +96,152 @@ pub async fn delete_overwrite(
     .await?;
     Ok(())
 }
+
+/// Add or strip the announcement-lock denies on the @everyone role overwrite,
+/// leaving any other allow/deny entries in it untouched. An overwrite left
+/// empty by unlocking is removed.
+pub async fn set_announcement_lock(
+    pool: &DbPool,
+    channel_id: &str,
+    everyone_role_id: &str,
+    locked: bool,
+) -> Result<(), AppError> {
+    let mut overwrite = everyone_overwrite(pool, channel_id, everyone_role_id).await?;
+    overwrite.allow = overwrite.allow & !ANNOUNCEMENT_LOCK_DENIES;
+    overwrite.deny = overwrite.deny & !ANNOUNCEMENT_LOCK_DENIES;
+    if locked {
+        overwrite.deny |= ANNOUNCEMENT_LOCK_DENIES;
+    }
+    save_everyone_overwrite(pool, channel_id, &overwrite).await
+}
+
+/// Recompute a channel's `announcement_locked` flag from its @everyone
+/// overwrite. Returns `true` if the stored flag changed.
+pub async fn sync_announcement_locked(
+    pool: &DbPool,
+    channel_id: &str,
+    everyone_role_id: &str,
+) -> Result<bool, AppError> {
+    let locked = list_overwrites(pool, channel_id)
+        .await?
+        .iter()
+        .find(|o| o.id == everyone_role_id)
+        .is_some_and(|o| o.deny.contains(ANNOUNCEMENT_LOCK_DENIES));
+
+    let result = sqlx::query(&super::q(
+        "UPDATE channels SET announcement_locked = ? WHERE id = ? AND announcement_locked <> ?",
+    ))
+    .bind(locked)
+    .bind(channel_id)
+    .bind(locked)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// The @everyone overwrite as stored, or an empty one to fill in.
+async fn everyone_overwrite(
+    pool: &DbPool,
+    channel_id: &str,
+    everyone_role_id: &str,
+) -> Result<PermissionOverwrite, AppError> {
+    Ok(list_overwrites(pool, channel_id)
+        .await?
+        .into_iter()
+        .find(|o| o.id == everyone_role_id)
+        .unwrap_or_else(|| PermissionOverwrite {
+            id: everyone_role_id.to_string(),
+            overwrite_type: "role".to_string(),
+            allow: Permissions::empty(),
+            deny: Permissions::empty(),
+        }))
+}
+
+async fn save_everyone_overwrite(
+    pool: &DbPool,
+    channel_id: &str,
+    overwrite: &PermissionOverwrite,
+) -> Result<(), AppError> {
+    if overwrite.allow.is_empty() && overwrite.deny.is_empty() {
+        delete_overwrite(pool, channel_id, &overwrite.id).await
+    } else {
+        upsert_overwrite(pool, channel_id, overwrite).await
+    }
+}
+
+pub async fn is_channel_locked(pool: &DbPool, channel_id: &str) -> Result<bool, AppError> {
+    let row = sqlx::query(&super::q(
+        "SELECT channel_id FROM channel_locks WHERE channel_id = ?",
+    ))
+    .bind(channel_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.is_some())
+}
+
+/// Deny `CHANNEL_LOCK_DENIES` to @everyone, remembering what the overwrite
+/// said about those permissions before. Returns `false` if the channel was
+/// already locked.
+pub async fn lock_channel(
+    pool: &DbPool,
+    channel_id: &str,
+    everyone_role_id: &str,
+    locked_by: &str,
+) -> Result<bool, AppError> {
+    let mut overwrite = everyone_overwrite(pool, channel_id, everyone_role_id).await?;
+    let result = sqlx::query(&super::q(
+        "INSERT INTO channel_locks (channel_id, prev_allow, prev_deny, locked_by, created_at) \
+         VALUES (?, ?, ?, ?, ?) ON CONFLICT (channel_id) DO NOTHING",
+    ))
+    .bind(channel_id)
+    .bind((overwrite.allow & CHANNEL_LOCK_DENIES).bits())
+    .bind((overwrite.deny & CHANNEL_LOCK_DENIES).bits())
+    .bind(locked_by)
+    .bind(
+        chrono::Utc::now()
+            .format("%Y-%m-%dT%H:%M:%S+00:00")
+            .to_string(),
+    )
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    overwrite.allow = overwrite.allow & !CHANNEL_LOCK_DENIES;
+    overwrite.deny |= CHANNEL_LOCK_DENIES;
+    save_everyone_overwrite(pool, channel_id, &overwrite).await?;
+    Ok(true)
+}
+
+/// Undo `lock_channel`: the locked permissions go back to what the @everyone
+/// overwrite said before, and anything else edited in the meantime stays.
+/// Returns `false` if the channel wasn't locked.
+pub async fn unlock_channel(
+    pool: &DbPool,
+    channel_id: &str,
+    everyone_role_id: &str,
+) -> Result<bool, AppError> {
+    let Some((prev_allow, prev_deny)) = sqlx::query_as::<_, (i64, i64)>(&super::q(
+        "SELECT prev_allow, prev_deny FROM channel_locks WHERE channel_id = ?",
+    ))
+    .bind(channel_id)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(false);
+    };
+
+    let mut overwrite = everyone_overwrite(pool, channel_id, everyone_role_id).await?;
+    overwrite.allow = (overwrite.allow & !CHANNEL_LOCK_DENIES)
+        | (Permissions::from_bits(prev_allow) & CHANNEL_LOCK_DENIES);
+    overwrite.deny = (overwrite.deny & !CHANNEL_LOCK_DENIES)
+        | (Permissions::from_bits(prev_deny) & CHANNEL_LOCK_DENIES);
+    save_everyone_overwrite(pool, channel_id, &overwrite).await?;
+
+    sqlx::query(&super::q("DELETE FROM channel_locks WHERE channel_id = ?"))
+        .bind(channel_id)
+        .execute(pool)
+        .await?;
+    Ok(true)
+}
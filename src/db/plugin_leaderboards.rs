@@ -1,4 +1,6 @@
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::models::plugin::LeaderboardRecord;
@@ -10,7 +12,7 @@ use crate::snowflake;
 /// `sort`: "ascending" | "descending"
 #[allow(clippy::too_many_arguments)]
 pub async fn upsert_record(
-    pool: &AnyPool,
+    pool: &DbPool,
     plugin_id: &str,
     space_id: &str,
     board_id: &str,
@@ -96,7 +98,7 @@ pub async fn upsert_record(
 
 /// Fetch the leaderboard sorted by score with rank.
 pub async fn get_leaderboard(
-    pool: &AnyPool,
+    pool: &DbPool,
     plugin_id: &str,
     space_id: &str,
     board_id: &str,
@@ -139,7 +141,7 @@ pub async fn get_leaderboard(
 
 /// Fetch records around a specific user's rank.
 pub async fn get_around(
-    pool: &AnyPool,
+    pool: &DbPool,
     plugin_id: &str,
     space_id: &str,
     board_id: &str,
@@ -223,7 +225,7 @@ pub async fn get_around(
 
 /// Fetch a single user's record with their rank.
 pub async fn get_user_record(
-    pool: &AnyPool,
+    pool: &DbPool,
     plugin_id: &str,
     space_id: &str,
     board_id: &str,
@@ -1,4 +1,6 @@
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::models::plugin::{Plugin, PluginManifest, PluginSession, PluginSessionParticipant};
@@ -49,7 +51,7 @@ fn row_to_plugin(row: sqlx::any::AnyRow) -> Plugin {
     }
 }
 
-pub async fn get_plugin(pool: &AnyPool, plugin_id: &str) -> Result<Plugin, AppError> {
+pub async fn get_plugin(pool: &DbPool, plugin_id: &str) -> Result<Plugin, AppError> {
     let row = sqlx::query(&super::q(&format!("{SELECT_PLUGINS} WHERE id = ?")))
         .bind(plugin_id)
         .fetch_optional(pool)
@@ -59,7 +61,7 @@ pub async fn get_plugin(pool: &AnyPool, plugin_id: &str) -> Result<Plugin, AppEr
 }
 
 pub async fn list_plugins(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     plugin_type: Option<&str>,
 ) -> Result<Vec<Plugin>, AppError> {
@@ -84,7 +86,7 @@ pub async fn list_plugins(
 }
 
 pub async fn require_plugin_in_space(
-    pool: &AnyPool,
+    pool: &DbPool,
     plugin_id: &str,
     space_id: &str,
 ) -> Result<(), AppError> {
@@ -105,7 +107,7 @@ pub async fn require_plugin_in_space(
 /// Insert a plugin with its manifest, bundle ZIP, and optional icon.
 #[allow(clippy::too_many_arguments)]
 pub async fn create_plugin(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     creator_id: &str,
     manifest: &PluginManifest,
@@ -139,7 +141,7 @@ pub async fn create_plugin(
     get_plugin(pool, &id).await
 }
 
-pub async fn delete_plugin(pool: &AnyPool, plugin_id: &str) -> Result<(), AppError> {
+pub async fn delete_plugin(pool: &DbPool, plugin_id: &str) -> Result<(), AppError> {
     sqlx::query(&super::q("DELETE FROM plugins WHERE id = ?"))
         .bind(plugin_id)
         .execute(pool)
@@ -148,7 +150,7 @@ pub async fn delete_plugin(pool: &AnyPool, plugin_id: &str) -> Result<(), AppErr
 }
 
 /// Fetch the bundle BLOB for a plugin.
-pub async fn get_bundle_blob(pool: &AnyPool, plugin_id: &str) -> Result<Vec<u8>, AppError> {
+pub async fn get_bundle_blob(pool: &DbPool, plugin_id: &str) -> Result<Vec<u8>, AppError> {
     let row: Option<(Vec<u8>,)> =
         sqlx::query_as(&super::q("SELECT bundle_blob FROM plugins WHERE id = ?"))
             .bind(plugin_id)
@@ -161,7 +163,7 @@ pub async fn get_bundle_blob(pool: &AnyPool, plugin_id: &str) -> Result<Vec<u8>,
 }
 
 /// Fetch the icon BLOB for a plugin.
-pub async fn get_icon_blob(pool: &AnyPool, plugin_id: &str) -> Result<Vec<u8>, AppError> {
+pub async fn get_icon_blob(pool: &DbPool, plugin_id: &str) -> Result<Vec<u8>, AppError> {
     let row: Option<(Vec<u8>,)> =
         sqlx::query_as(&super::q("SELECT icon_blob FROM plugins WHERE id = ?"))
             .bind(plugin_id)
@@ -174,7 +176,7 @@ pub async fn get_icon_blob(pool: &AnyPool, plugin_id: &str) -> Result<Vec<u8>, A
 }
 
 /// Get the space_id for a plugin.
-pub async fn get_plugin_space_id(pool: &AnyPool, plugin_id: &str) -> Result<String, AppError> {
+pub async fn get_plugin_space_id(pool: &DbPool, plugin_id: &str) -> Result<String, AppError> {
     let row: (String,) = sqlx::query_as(&super::q("SELECT space_id FROM plugins WHERE id = ?"))
         .bind(plugin_id)
         .fetch_optional(pool)
@@ -197,7 +199,7 @@ fn row_to_session(row: sqlx::any::AnyRow) -> PluginSession {
     }
 }
 
-pub async fn get_session(pool: &AnyPool, session_id: &str) -> Result<PluginSession, AppError> {
+pub async fn get_session(pool: &DbPool, session_id: &str) -> Result<PluginSession, AppError> {
     let row = sqlx::query(&super::q(
         "SELECT id, plugin_id, channel_id, host_user_id, state, created_at FROM plugin_sessions WHERE id = ?",
     ))
@@ -212,7 +214,7 @@ pub async fn get_session(pool: &AnyPool, session_id: &str) -> Result<PluginSessi
 }
 
 pub async fn create_session(
-    pool: &AnyPool,
+    pool: &DbPool,
     plugin_id: &str,
     channel_id: &str,
     host_user_id: &str,
@@ -239,7 +241,7 @@ pub async fn create_session(
 }
 
 pub async fn update_session_state(
-    pool: &AnyPool,
+    pool: &DbPool,
     session_id: &str,
     state: &str,
     is_postgres: bool,
@@ -255,7 +257,7 @@ pub async fn update_session_state(
     get_session(pool, session_id).await
 }
 
-pub async fn delete_session(pool: &AnyPool, session_id: &str) -> Result<(), AppError> {
+pub async fn delete_session(pool: &DbPool, session_id: &str) -> Result<(), AppError> {
     sqlx::query(&super::q("DELETE FROM plugin_sessions WHERE id = ?"))
         .bind(session_id)
         .execute(pool)
@@ -266,7 +268,7 @@ pub async fn delete_session(pool: &AnyPool, session_id: &str) -> Result<(), AppE
 // --- Participants ---
 
 pub async fn list_participants(
-    pool: &AnyPool,
+    pool: &DbPool,
     session_id: &str,
 ) -> Result<Vec<PluginSessionParticipant>, AppError> {
     let rows = sqlx::query(&super::q(
@@ -291,7 +293,7 @@ pub async fn list_participants(
 }
 
 pub async fn add_participant(
-    pool: &AnyPool,
+    pool: &DbPool,
     session_id: &str,
     user_id: &str,
     role: &str,
@@ -310,7 +312,7 @@ pub async fn add_participant(
 }
 
 pub async fn update_participant_role(
-    pool: &AnyPool,
+    pool: &DbPool,
     session_id: &str,
     user_id: &str,
     role: &str,
@@ -335,7 +337,7 @@ pub async fn update_participant_role(
 }
 
 pub async fn remove_participant(
-    pool: &AnyPool,
+    pool: &DbPool,
     session_id: &str,
     user_id: &str,
 ) -> Result<(), AppError> {
@@ -350,7 +352,7 @@ pub async fn remove_participant(
 }
 
 /// Count current players in a session.
-pub async fn count_players(pool: &AnyPool, session_id: &str) -> Result<i64, AppError> {
+pub async fn count_players(pool: &DbPool, session_id: &str) -> Result<i64, AppError> {
     let count: (i64,) = sqlx::query_as(&super::q(
         "SELECT COUNT(*) FROM plugin_session_participants WHERE session_id = ? AND role = 'player'",
     ))
@@ -361,7 +363,7 @@ pub async fn count_players(pool: &AnyPool, session_id: &str) -> Result<i64, AppE
 }
 
 /// Find the next available slot index for a session.
-pub async fn next_slot_index(pool: &AnyPool, session_id: &str) -> Result<i64, AppError> {
+pub async fn next_slot_index(pool: &DbPool, session_id: &str) -> Result<i64, AppError> {
     let max: Option<i64> = sqlx::query_scalar(&super::q(
         "SELECT MAX(slot_index) FROM plugin_session_participants WHERE session_id = ? AND role = 'player'",
     ))
@@ -374,7 +376,7 @@ pub async fn next_slot_index(pool: &AnyPool, session_id: &str) -> Result<i64, Ap
 
 /// Get all active (non-ended) sessions across all channels in a space.
 pub async fn get_active_sessions_for_space(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
 ) -> Result<Vec<PluginSession>, AppError> {
     let rows = sqlx::query(&super::q(
@@ -399,7 +401,7 @@ pub async fn get_active_sessions_for_space(
 
 /// Get active (non-ended) sessions for a channel.
 pub async fn get_active_sessions_for_channel(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
 ) -> Result<Vec<PluginSession>, AppError> {
     let rows = sqlx::query(&super::q(
@@ -420,7 +422,7 @@ pub async fn get_active_sessions_for_channel(
 
 /// Get participant user IDs for a session (for targeted gateway broadcasts).
 pub async fn get_session_user_ids(
-    pool: &AnyPool,
+    pool: &DbPool,
     session_id: &str,
 ) -> Result<Vec<String>, AppError> {
     let rows: Vec<(String,)> = sqlx::query_as(&super::q(
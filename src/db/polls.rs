@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::poll::{PollAnswer, PollRow};
+
+const SELECT_POLLS: &str =
+    "SELECT message_id, question, answers, allow_multiselect, expires_at, finalized_at FROM polls";
+
+fn row_to_poll(row: &sqlx::any::AnyRow) -> PollRow {
+    PollRow {
+        message_id: row.get("message_id"),
+        question: row.get("question"),
+        answers: row
+            .try_get::<String, _>("answers")
+            .ok()
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default(),
+        allow_multiselect: crate::db::get_bool(row, "allow_multiselect"),
+        expires_at: row.get("expires_at"),
+        finalized_at: row.get("finalized_at"),
+    }
+}
+
+pub async fn create_poll(
+    pool: &DbPool,
+    message_id: &str,
+    question: &str,
+    answers: &[PollAnswer],
+    allow_multiselect: bool,
+    expires_at: &str,
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "INSERT INTO polls (message_id, question, answers, allow_multiselect, expires_at) VALUES (?, ?, ?, ?, ?)",
+    ))
+    .bind(message_id)
+    .bind(question)
+    .bind(serde_json::to_string(answers).unwrap())
+    .bind(allow_multiselect)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_poll(pool: &DbPool, message_id: &str) -> Result<PollRow, AppError> {
+    let row = sqlx::query(&super::q(&format!("{SELECT_POLLS} WHERE message_id = ?")))
+        .bind(message_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("poll not found".into()))?;
+    Ok(row_to_poll(&row))
+}
+
+pub async fn get_polls_for_messages(
+    pool: &DbPool,
+    message_ids: &[String],
+) -> Result<HashMap<String, PollRow>, AppError> {
+    if message_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let in_clause = vec!["?"; message_ids.len()].join(", ");
+    let sql = super::q(&format!("{SELECT_POLLS} WHERE message_id IN ({in_clause})"));
+    let mut query = sqlx::query(&sql);
+    for id in message_ids {
+        query = query.bind(id);
+    }
+    Ok(query
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(row_to_poll)
+        .map(|p| (p.message_id.clone(), p))
+        .collect())
+}
+
+/// Vote counts per message and answer, plus the answers `user_id` picked.
+pub async fn get_vote_counts(
+    pool: &DbPool,
+    message_ids: &[String],
+    user_id: Option<&str>,
+) -> Result<HashMap<String, Vec<(i64, i64, bool)>>, AppError> {
+    if message_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let in_clause = vec!["?"; message_ids.len()].join(", ");
+    let sql = super::q(&format!(
+        "SELECT message_id, answer_id, COUNT(*) AS votes, \
+         SUM(CASE WHEN user_id = ? THEN 1 ELSE 0 END) AS mine \
+         FROM poll_votes WHERE message_id IN ({in_clause}) \
+         GROUP BY message_id, answer_id ORDER BY answer_id"
+    ));
+    let mut query = sqlx::query(&sql).bind(user_id.unwrap_or(""));
+    for id in message_ids {
+        query = query.bind(id);
+    }
+    let mut result: HashMap<String, Vec<(i64, i64, bool)>> = HashMap::new();
+    for row in query.fetch_all(pool).await? {
+        result.entry(row.get("message_id")).or_default().push((
+            row.get("answer_id"),
+            row.get("votes"),
+            row.get::<i64, _>("mine") > 0,
+        ));
+    }
+    Ok(result)
+}
+
+/// Returns false if the user had already voted for this answer.
+pub async fn add_vote(
+    pool: &DbPool,
+    message_id: &str,
+    user_id: &str,
+    answer_id: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "INSERT INTO poll_votes (message_id, user_id, answer_id) VALUES (?, ?, ?) ON CONFLICT DO NOTHING",
+    ))
+    .bind(message_id)
+    .bind(user_id)
+    .bind(answer_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Vote for `answer_id` on a single-choice poll, dropping any other vote the
+/// user had, in one transaction. Returns the answers whose votes were removed
+/// and whether the new vote was added (false if it was already there).
+pub async fn replace_vote(
+    pool: &DbPool,
+    message_id: &str,
+    user_id: &str,
+    answer_id: i64,
+) -> Result<(Vec<i64>, bool), AppError> {
+    let mut tx = pool.begin().await?;
+
+    // Touching the poll row first serializes concurrent votes on it, so two
+    // racing PUTs can't each see no earlier vote and both insert.
+    sqlx::query(&super::q(
+        "UPDATE polls SET message_id = message_id WHERE message_id = ?",
+    ))
+    .bind(message_id)
+    .execute(&mut tx)
+    .await?;
+
+    let previous: Vec<(i64,)> = sqlx::query_as(&super::q(
+        "SELECT answer_id FROM poll_votes WHERE message_id = ? AND user_id = ? AND answer_id <> ? \
+         ORDER BY answer_id",
+    ))
+    .bind(message_id)
+    .bind(user_id)
+    .bind(answer_id)
+    .fetch_all(&mut tx)
+    .await?;
+    sqlx::query(&super::q(
+        "DELETE FROM poll_votes WHERE message_id = ? AND user_id = ? AND answer_id <> ?",
+    ))
+    .bind(message_id)
+    .bind(user_id)
+    .bind(answer_id)
+    .execute(&mut tx)
+    .await?;
+    let added = sqlx::query(&super::q(
+        "INSERT INTO poll_votes (message_id, user_id, answer_id) VALUES (?, ?, ?) ON CONFLICT DO NOTHING",
+    ))
+    .bind(message_id)
+    .bind(user_id)
+    .bind(answer_id)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok((
+        previous.into_iter().map(|(id,)| id).collect(),
+        added.rows_affected() > 0,
+    ))
+}
+
+/// Returns false if the user had not voted for this answer.
+pub async fn remove_vote(
+    pool: &DbPool,
+    message_id: &str,
+    user_id: &str,
+    answer_id: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "DELETE FROM poll_votes WHERE message_id = ? AND user_id = ? AND answer_id = ?",
+    ))
+    .bind(message_id)
+    .bind(user_id)
+    .bind(answer_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// User IDs that voted for an answer, ordered by ID for `after` paging.
+pub async fn list_voters(
+    pool: &DbPool,
+    message_id: &str,
+    answer_id: i64,
+    after: Option<&str>,
+    limit: i64,
+) -> Result<Vec<String>, AppError> {
+    let rows: Vec<(String,)> = sqlx::query_as(&super::q(
+        "SELECT user_id FROM poll_votes WHERE message_id = ? AND answer_id = ? AND user_id > ? \
+         ORDER BY user_id LIMIT ?",
+    ))
+    .bind(message_id)
+    .bind(answer_id)
+    .bind(after.unwrap_or(""))
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// Open polls whose `expires_at` has passed.
+pub async fn list_expired(pool: &DbPool, now: &str) -> Result<Vec<PollRow>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_POLLS} WHERE finalized_at IS NULL AND expires_at <= ? ORDER BY expires_at"
+    )))
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(row_to_poll).collect())
+}
+
+/// Mark a poll finalized. Returns false if another sweep got there first.
+pub async fn claim_finalize(pool: &DbPool, message_id: &str, now: &str) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "UPDATE polls SET finalized_at = ? WHERE message_id = ? AND finalized_at IS NULL",
+    ))
+    .bind(now)
+    .bind(message_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,137 @@
+//! The connection pool every query runs on.
+//!
+//! [DbPool] and [DbTransaction] wrap sqlx's `Any` pool and transaction and
+//! hand each statement to [instrument](super::instrument) as it executes, so
+//! a statement run N times in a loop is counted N times, whether or not it
+//! was built with [q](super::q).
+
+use futures_util::future::BoxFuture;
+use futures_util::stream::BoxStream;
+use sqlx::any::{Any, AnyQueryResult, AnyRow, AnyStatement, AnyTypeInfo};
+use sqlx::Describe;
+use sqlx::{AnyPool, Either, Execute, Executor};
+
+use super::instrument;
+
+#[derive(Debug, Clone)]
+pub struct DbPool(AnyPool);
+
+impl DbPool {
+    pub fn new(pool: AnyPool) -> Self {
+        Self(pool)
+    }
+
+    pub async fn begin(&self) -> Result<DbTransaction, sqlx::Error> {
+        Ok(DbTransaction(self.0.begin().await?))
+    }
+
+    pub async fn close(&self) {
+        self.0.close().await
+    }
+}
+
+/// A transaction on a [DbPool]. Run statements on it with `&mut tx`.
+#[derive(Debug)]
+pub struct DbTransaction(sqlx::Transaction<'static, Any>);
+
+impl DbTransaction {
+    pub async fn commit(self) -> Result<(), sqlx::Error> {
+        self.0.commit().await
+    }
+
+    pub async fn rollback(self) -> Result<(), sqlx::Error> {
+        self.0.rollback().await
+    }
+}
+
+impl<'c> Executor<'c> for &'c DbPool {
+    type Database = Any;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<'e, Result<Either<AnyQueryResult, AnyRow>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + Execute<'q, Any>,
+    {
+        instrument::note(query.sql());
+        self.0.fetch_many(query)
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<Option<AnyRow>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + Execute<'q, Any>,
+    {
+        instrument::note(query.sql());
+        self.0.fetch_optional(query)
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [AnyTypeInfo],
+    ) -> BoxFuture<'e, Result<AnyStatement<'q>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        self.0.prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(self, sql: &'q str) -> BoxFuture<'e, Result<Describe<Any>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        self.0.describe(sql)
+    }
+}
+
+impl<'c> Executor<'c> for &'c mut DbTransaction {
+    type Database = Any;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<'e, Result<Either<AnyQueryResult, AnyRow>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + Execute<'q, Any>,
+    {
+        instrument::note(query.sql());
+        (&mut *self.0).fetch_many(query)
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<Option<AnyRow>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + Execute<'q, Any>,
+    {
+        instrument::note(query.sql());
+        (&mut *self.0).fetch_optional(query)
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [AnyTypeInfo],
+    ) -> BoxFuture<'e, Result<AnyStatement<'q>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        (&mut *self.0).prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(self, sql: &'q str) -> BoxFuture<'e, Result<Describe<Any>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        (&mut *self.0).describe(sql)
+    }
+}
@@ -0,0 +1,123 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::push::PushSubscription;
+use crate::snowflake;
+
+const SELECT_SUBSCRIPTIONS: &str =
+    "SELECT id, user_id, endpoint, p256dh, auth, created_at FROM push_subscriptions";
+
+fn row_to_subscription(row: &sqlx::any::AnyRow) -> PushSubscription {
+    PushSubscription {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        endpoint: row.get("endpoint"),
+        p256dh: row.get("p256dh"),
+        auth: row.get("auth"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Register `endpoint` for `user_id`, replacing any earlier registration of
+/// the same endpoint -- by this user or another one signed in on the same
+/// browser before.
+pub async fn create_subscription(
+    pool: &DbPool,
+    user_id: &str,
+    endpoint: &str,
+    p256dh: &str,
+    auth: &str,
+) -> Result<PushSubscription, AppError> {
+    let id = snowflake::generate();
+    let mut tx = pool.begin().await?;
+    sqlx::query(&super::q(
+        "DELETE FROM push_subscriptions WHERE endpoint = ?",
+    ))
+    .bind(endpoint)
+    .execute(&mut tx)
+    .await?;
+    sqlx::query(&super::q(
+        "INSERT INTO push_subscriptions (id, user_id, endpoint, p256dh, auth) VALUES (?, ?, ?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(user_id)
+    .bind(endpoint)
+    .bind(p256dh)
+    .bind(auth)
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    get_subscription(pool, &id).await
+}
+
+pub async fn get_subscription(pool: &DbPool, id: &str) -> Result<PushSubscription, AppError> {
+    let row = sqlx::query(&super::q(&format!("{SELECT_SUBSCRIPTIONS} WHERE id = ?")))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("push subscription not found".into()))?;
+    Ok(row_to_subscription(&row))
+}
+
+pub async fn list_subscriptions(
+    pool: &DbPool,
+    user_id: &str,
+) -> Result<Vec<PushSubscription>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_SUBSCRIPTIONS} WHERE user_id = ? ORDER BY id ASC"
+    )))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(row_to_subscription).collect())
+}
+
+/// Every subscription held by any of `user_ids`.
+pub async fn subscriptions_for_users(
+    pool: &DbPool,
+    user_ids: &[String],
+) -> Result<Vec<PushSubscription>, AppError> {
+    if user_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let in_clause = vec!["?"; user_ids.len()].join(", ");
+    let sql = super::q(&format!(
+        "{SELECT_SUBSCRIPTIONS} WHERE user_id IN ({in_clause}) ORDER BY id ASC"
+    ));
+    let mut query = sqlx::query(&sql);
+    for id in user_ids {
+        query = query.bind(id);
+    }
+    Ok(query
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(row_to_subscription)
+        .collect())
+}
+
+/// Delete one of the user's subscriptions. Returns false if they have no
+/// subscription with that ID.
+pub async fn delete_subscription(pool: &DbPool, user_id: &str, id: &str) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "DELETE FROM push_subscriptions WHERE id = ? AND user_id = ?",
+    ))
+    .bind(id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Forget a subscription the push service says no longer exists.
+pub async fn delete_by_endpoint(pool: &DbPool, endpoint: &str) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "DELETE FROM push_subscriptions WHERE endpoint = ?",
+    ))
+    .bind(endpoint)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
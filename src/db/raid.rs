@@ -0,0 +1,163 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::raid::{Lockdown, RaidProtection};
+
+/// Timestamps here share the `members.joined_at` format so they compare as
+/// strings on both backends.
+pub fn timestamp_in(secs: i64) -> String {
+    (chrono::Utc::now() + chrono::Duration::seconds(secs))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// A space's raid protection; spaces that never configured it get the
+/// defaults with automatic lockdowns off.
+pub async fn get_raid_protection(
+    pool: &DbPool,
+    space_id: &str,
+) -> Result<RaidProtection, AppError> {
+    let row = sqlx::query(&super::q(
+        "SELECT auto_lockdown, join_threshold, join_window_secs, lockdown_secs, mode, \
+         min_account_age_secs, lockdown_since, lockdown_until, lockdown_automatic \
+         FROM space_raid_protection WHERE space_id = ?",
+    ))
+    .bind(space_id)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(RaidProtection {
+            space_id: space_id.to_string(),
+            auto_lockdown: false,
+            join_threshold: 10,
+            join_window_secs: 60,
+            lockdown_secs: 3600,
+            mode: "account_age".to_string(),
+            min_account_age_secs: 7 * 24 * 60 * 60,
+            lockdown: None,
+        });
+    };
+    let since: Option<String> = row.try_get("lockdown_since").ok().flatten();
+    let until: Option<String> = row.try_get("lockdown_until").ok().flatten();
+    let now = timestamp_in(0);
+    // An automatic lockdown past its end has lifted, even though the row
+    // still says when it ran.
+    let lockdown = since
+        .filter(|_| until.as_ref().is_none_or(|u| *u > now))
+        .map(|since| Lockdown {
+            since,
+            until,
+            automatic: crate::db::get_bool(&row, "lockdown_automatic"),
+        });
+    Ok(RaidProtection {
+        space_id: space_id.to_string(),
+        auto_lockdown: crate::db::get_bool(&row, "auto_lockdown"),
+        join_threshold: row.get("join_threshold"),
+        join_window_secs: row.get("join_window_secs"),
+        lockdown_secs: row.get("lockdown_secs"),
+        mode: row.get("mode"),
+        min_account_age_secs: row.get("min_account_age_secs"),
+        lockdown,
+    })
+}
+
+/// Save the settings half of [RaidProtection]; the lockdown is left alone.
+pub async fn save_settings(pool: &DbPool, settings: &RaidProtection) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "INSERT INTO space_raid_protection \
+         (space_id, auto_lockdown, join_threshold, join_window_secs, lockdown_secs, mode, min_account_age_secs) \
+         VALUES (?, ?, ?, ?, ?, ?, ?) \
+         ON CONFLICT (space_id) DO UPDATE SET auto_lockdown = excluded.auto_lockdown, \
+         join_threshold = excluded.join_threshold, join_window_secs = excluded.join_window_secs, \
+         lockdown_secs = excluded.lockdown_secs, mode = excluded.mode, \
+         min_account_age_secs = excluded.min_account_age_secs",
+    ))
+    .bind(&settings.space_id)
+    .bind(settings.auto_lockdown)
+    .bind(settings.join_threshold)
+    .bind(settings.join_window_secs)
+    .bind(settings.lockdown_secs)
+    .bind(&settings.mode)
+    .bind(settings.min_account_age_secs)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Put a space in lockdown until `until` (or until lifted), replacing any
+/// lockdown already running.
+pub async fn start_lockdown(
+    pool: &DbPool,
+    space_id: &str,
+    until: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "INSERT INTO space_raid_protection (space_id, lockdown_since, lockdown_until, lockdown_automatic) \
+         VALUES (?, ?, ?, ?) \
+         ON CONFLICT (space_id) DO UPDATE SET lockdown_since = excluded.lockdown_since, \
+         lockdown_until = excluded.lockdown_until, lockdown_automatic = excluded.lockdown_automatic",
+    ))
+    .bind(space_id)
+    .bind(timestamp_in(0))
+    .bind(until)
+    .bind(false)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Start an automatic lockdown unless one is already running. Returns
+/// whether this call started it, so concurrent joins only report it once.
+pub async fn start_automatic_lockdown(
+    pool: &DbPool,
+    space_id: &str,
+    until: &str,
+) -> Result<bool, AppError> {
+    let now = timestamp_in(0);
+    let result = sqlx::query(&super::q(
+        "UPDATE space_raid_protection SET lockdown_since = ?, lockdown_until = ?, lockdown_automatic = ? \
+         WHERE space_id = ? AND (lockdown_since IS NULL OR lockdown_until <= ?)",
+    ))
+    .bind(&now)
+    .bind(until)
+    .bind(true)
+    .bind(space_id)
+    .bind(&now)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Lift a space's lockdown. Returns whether one was running.
+pub async fn end_lockdown(pool: &DbPool, space_id: &str) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "UPDATE space_raid_protection SET lockdown_since = NULL, lockdown_until = NULL, \
+         lockdown_automatic = ? WHERE space_id = ? AND lockdown_since IS NOT NULL \
+         AND (lockdown_until IS NULL OR lockdown_until > ?)",
+    ))
+    .bind(false)
+    .bind(space_id)
+    .bind(timestamp_in(0))
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Members who joined the space at or after `since`.
+pub async fn count_joins_since(
+    pool: &DbPool,
+    space_id: &str,
+    since: &str,
+) -> Result<i64, AppError> {
+    let row = sqlx::query_as::<_, (i64,)>(&super::q(
+        "SELECT COUNT(*) FROM members WHERE space_id = ? AND joined_at >= ?",
+    ))
+    .bind(space_id)
+    .bind(since)
+    .fetch_one(pool)
+    .await?;
+    Ok(row.0)
+}
@@ -1,5 +1,5 @@
+use crate::db::DbPool;
 use serde::Serialize;
-use sqlx::AnyPool;
 
 use crate::db::now_sql;
 use crate::error::AppError;
@@ -24,7 +24,7 @@ pub struct UnreadChannel {
 /// A channel is unread if its last_message_id is greater than the user's
 /// last_read_message_id (or if there is no read state but the channel has messages).
 pub async fn get_unread_channels(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
 ) -> Result<Vec<UnreadChannel>, AppError> {
     // Get channels the user is a member of (space channels + DMs) that have
@@ -74,7 +74,7 @@ pub async fn get_unread_channels(
 
 /// Mark a channel as read up to a given message ID.
 pub async fn ack_channel(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
     channel_id: &str,
     message_id: &str,
@@ -105,7 +105,7 @@ pub async fn ack_channel(
 
 /// Increment mention count for a user in a channel.
 pub async fn increment_mention_count(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
     channel_id: &str,
     is_postgres: bool,
@@ -127,3 +127,33 @@ pub async fn increment_mention_count(
 
     Ok(())
 }
+
+/// Increment the mention count in one channel for many users with a single
+/// multi-row upsert. Used by the @everyone fan-out, which works in batches.
+pub async fn increment_mention_counts(
+    pool: &DbPool,
+    user_ids: &[String],
+    channel_id: &str,
+    is_postgres: bool,
+) -> Result<(), AppError> {
+    if user_ids.is_empty() {
+        return Ok(());
+    }
+    let now = now_sql(is_postgres);
+    let values = vec![format!("(?, ?, 1, {now})"); user_ids.len()].join(", ");
+    let sql = format!(
+        "INSERT INTO read_states (user_id, channel_id, mention_count, updated_at)
+         VALUES {values}
+         ON CONFLICT(user_id, channel_id) DO UPDATE SET
+           mention_count = read_states.mention_count + 1,
+           updated_at = {now}"
+    );
+    let sql = super::q(&sql);
+    let mut query = sqlx::query(&sql);
+    for uid in user_ids {
+        query = query.bind(uid).bind(channel_id);
+    }
+    query.execute(pool).await?;
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use sqlx::AnyPool;
+use crate::db::DbPool;
 
 use crate::error::AppError;
 
@@ -45,7 +45,7 @@ fn row_to_rel(
 }
 
 pub async fn list_relationships(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
 ) -> Result<Vec<RelationshipRow>, AppError> {
     let rows = sqlx::query_as::<
@@ -70,7 +70,7 @@ pub async fn list_relationships(
 }
 
 pub async fn get_relationship(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
     target_id: &str,
 ) -> Result<Option<RelationshipRow>, AppError> {
@@ -98,7 +98,7 @@ pub async fn get_relationship(
 
 /// Insert or update a directed relationship row.
 pub async fn upsert_relationship(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
     target_id: &str,
     rel_type: i64,
@@ -119,7 +119,7 @@ pub async fn upsert_relationship(
 
 /// Delete a single directed relationship row. Returns true if a row was deleted.
 pub async fn delete_relationship(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
     target_id: &str,
 ) -> Result<bool, AppError> {
@@ -135,7 +135,7 @@ pub async fn delete_relationship(
 
 /// Delete both directed relationship rows (mutual remove for unfriend/decline).
 pub async fn delete_both_directions(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_a: &str,
     user_b: &str,
 ) -> Result<(), AppError> {
@@ -154,7 +154,7 @@ pub async fn delete_both_directions(
 }
 
 /// Return all friend user IDs for a user (type = 1).
-pub async fn get_friend_ids(pool: &AnyPool, user_id: &str) -> Result<Vec<String>, AppError> {
+pub async fn get_friend_ids(pool: &DbPool, user_id: &str) -> Result<Vec<String>, AppError> {
     let rows = sqlx::query_as::<_, (String,)>(&super::q(
         "SELECT target_user_id FROM relationships WHERE user_id = ? AND type = 1",
     ))
@@ -164,9 +164,32 @@ pub async fn get_friend_ids(pool: &AnyPool, user_id: &str) -> Result<Vec<String>
     Ok(rows.into_iter().map(|(id,)| id).collect())
 }
 
+/// Return the IDs of every user this user has blocked (type = 2).
+pub async fn get_blocked_ids(pool: &DbPool, user_id: &str) -> Result<Vec<String>, AppError> {
+    let rows = sqlx::query_as::<_, (String,)>(&super::q(
+        "SELECT target_user_id FROM relationships WHERE user_id = ? AND type = 2",
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// Whether the two users are friends (an accepted request).
+pub async fn are_friends(pool: &DbPool, user_a: &str, user_b: &str) -> Result<bool, AppError> {
+    let row = sqlx::query_as::<_, (i64,)>(&super::q(
+        "SELECT COUNT(*) FROM relationships WHERE user_id = ? AND target_user_id = ? AND type = 1",
+    ))
+    .bind(user_a)
+    .bind(user_b)
+    .fetch_one(pool)
+    .await?;
+    Ok(row.0 > 0)
+}
+
 /// Check whether user_b has blocked user_a.
 pub async fn is_blocked_by(
-    pool: &AnyPool,
+    pool: &DbPool,
     blocker_id: &str,
     blocked_id: &str,
 ) -> Result<bool, AppError> {
@@ -1,4 +1,4 @@
-use sqlx::AnyPool;
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::snowflake;
@@ -6,7 +6,7 @@ use crate::snowflake;
 #[derive(Debug, Clone)]
 pub struct ReportRow {
     pub id: String,
-    pub space_id: String,
+    pub space_id: Option<String>,
     pub reporter_id: String,
     pub target_type: String,
     pub target_id: String,
@@ -20,10 +20,46 @@ pub struct ReportRow {
     pub resolved_at: Option<String>,
 }
 
+type ReportTuple = (
+    String,
+    Option<String>,
+    String,
+    String,
+    String,
+    Option<String>,
+    String,
+    Option<String>,
+    String,
+    Option<String>,
+    Option<String>,
+    String,
+    Option<String>,
+);
+
+const REPORT_COLUMNS: &str = "id, space_id, reporter_id, target_type, target_id, channel_id, category, description, status, actioned_by, action_taken, created_at, resolved_at";
+
+fn report_from_tuple(row: ReportTuple) -> ReportRow {
+    ReportRow {
+        id: row.0,
+        space_id: row.1,
+        reporter_id: row.2,
+        target_type: row.3,
+        target_id: row.4,
+        channel_id: row.5,
+        category: row.6,
+        description: row.7,
+        status: row.8,
+        actioned_by: row.9,
+        action_taken: row.10,
+        created_at: row.11,
+        resolved_at: row.12,
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn create_report(
-    pool: &AnyPool,
-    space_id: &str,
+    pool: &DbPool,
+    space_id: Option<&str>,
     reporter_id: &str,
     target_type: &str,
     target_id: &str,
@@ -49,40 +85,30 @@ pub async fn create_report(
     get_report(pool, &id).await
 }
 
-pub async fn get_report(pool: &AnyPool, report_id: &str) -> Result<ReportRow, AppError> {
-    let row = sqlx::query_as::<_, (String, String, String, String, String, Option<String>, String, Option<String>, String, Option<String>, Option<String>, String, Option<String>)>(
-        &super::q("SELECT id, space_id, reporter_id, target_type, target_id, channel_id, category, description, status, actioned_by, action_taken, created_at, resolved_at FROM reports WHERE id = ?")
-    )
+pub async fn get_report(pool: &DbPool, report_id: &str) -> Result<ReportRow, AppError> {
+    let row = sqlx::query_as::<_, ReportTuple>(&super::q(&format!(
+        "SELECT {REPORT_COLUMNS} FROM reports WHERE id = ?"
+    )))
     .bind(report_id)
     .fetch_optional(pool)
     .await?
     .ok_or_else(|| AppError::NotFound("report not found".to_string()))?;
 
-    Ok(ReportRow {
-        id: row.0,
-        space_id: row.1,
-        reporter_id: row.2,
-        target_type: row.3,
-        target_id: row.4,
-        channel_id: row.5,
-        category: row.6,
-        description: row.7,
-        status: row.8,
-        actioned_by: row.9,
-        action_taken: row.10,
-        created_at: row.11,
-        resolved_at: row.12,
-    })
+    Ok(report_from_tuple(row))
 }
 
+/// A space's moderation queue. Reports about the space itself only go to
+/// instance admins, so they're left out.
 pub async fn list_reports(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     status_filter: Option<&str>,
     limit: i64,
     before: Option<&str>,
 ) -> Result<Vec<ReportRow>, AppError> {
-    let mut query = String::from("SELECT id, space_id, reporter_id, target_type, target_id, channel_id, category, description, status, actioned_by, action_taken, created_at, resolved_at FROM reports WHERE space_id = ?");
+    let mut query = format!(
+        "SELECT {REPORT_COLUMNS} FROM reports WHERE space_id = ? AND target_type <> 'space'"
+    );
 
     if status_filter.is_some() {
         query.push_str(" AND status = ?");
@@ -93,25 +119,7 @@ pub async fn list_reports(
     query.push_str(" ORDER BY created_at DESC LIMIT ?");
 
     let query = super::q(&query);
-    let mut q = sqlx::query_as::<
-        _,
-        (
-            String,
-            String,
-            String,
-            String,
-            String,
-            Option<String>,
-            String,
-            Option<String>,
-            String,
-            Option<String>,
-            Option<String>,
-            String,
-            Option<String>,
-        ),
-    >(&query)
-    .bind(space_id);
+    let mut q = sqlx::query_as::<_, ReportTuple>(&query).bind(space_id);
 
     if let Some(s) = status_filter {
         q = q.bind(s);
@@ -123,37 +131,68 @@ pub async fn list_reports(
 
     let rows = q.fetch_all(pool).await?;
 
-    Ok(rows
-        .into_iter()
-        .map(|row| ReportRow {
-            id: row.0,
-            space_id: row.1,
-            reporter_id: row.2,
-            target_type: row.3,
-            target_id: row.4,
-            channel_id: row.5,
-            category: row.6,
-            description: row.7,
-            status: row.8,
-            actioned_by: row.9,
-            action_taken: row.10,
-            created_at: row.11,
-            resolved_at: row.12,
-        })
-        .collect())
+    Ok(rows.into_iter().map(report_from_tuple).collect())
+}
+
+/// Filters for the instance-wide queue.
+#[derive(Debug, Default)]
+pub struct ReportFilter<'a> {
+    pub status: Option<&'a str>,
+    pub target_type: Option<&'a str>,
+    pub space_id: Option<&'a str>,
+    pub before: Option<&'a str>,
+}
+
+/// Every report on the instance, newest first.
+pub async fn list_all_reports(
+    pool: &DbPool,
+    filter: &ReportFilter<'_>,
+    limit: i64,
+) -> Result<Vec<ReportRow>, AppError> {
+    let mut query = format!("SELECT {REPORT_COLUMNS} FROM reports WHERE 1 = 1");
+    let mut binds = Vec::new();
+    for (column, value) in [
+        ("status = ?", filter.status),
+        ("target_type = ?", filter.target_type),
+        ("space_id = ?", filter.space_id),
+        ("id < ?", filter.before),
+    ] {
+        if let Some(value) = value {
+            query.push_str(" AND ");
+            query.push_str(column);
+            binds.push(value);
+        }
+    }
+    query.push_str(" ORDER BY id DESC LIMIT ?");
+
+    let query = super::q(&query);
+    let mut q = sqlx::query_as::<_, ReportTuple>(&query);
+    for value in binds {
+        q = q.bind(value);
+    }
+    let rows = q.bind(limit).fetch_all(pool).await?;
+
+    Ok(rows.into_iter().map(report_from_tuple).collect())
 }
 
+/// Move a report to `status`, recording who did it. `resolved_at` is set
+/// for `actioned` and `dismissed` and cleared otherwise, so a reopened
+/// report goes back in the queue.
 pub async fn resolve_report(
-    pool: &AnyPool,
+    pool: &DbPool,
     report_id: &str,
     actioned_by: &str,
     status: &str,
     action_taken: Option<&str>,
     is_postgres: bool,
 ) -> Result<ReportRow, AppError> {
-    let now_fn = crate::db::now_sql(is_postgres);
+    let resolved_at = if matches!(status, "actioned" | "dismissed") {
+        crate::db::now_sql(is_postgres)
+    } else {
+        "NULL"
+    };
     let sql = format!(
-        "UPDATE reports SET status = ?, actioned_by = ?, action_taken = ?, resolved_at = {now_fn} WHERE id = ?"
+        "UPDATE reports SET status = ?, actioned_by = ?, action_taken = ?, resolved_at = {resolved_at} WHERE id = ?"
     );
     let sql = super::q(&sql);
     sqlx::query(&sql)
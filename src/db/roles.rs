@@ -1,6 +1,9 @@
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
+use crate::models::permission::Permissions;
 use crate::models::role::{CreateRole, RoleRow, UpdateRole};
 use crate::snowflake;
 
@@ -13,15 +16,16 @@ fn row_to_role(row: sqlx::any::AnyRow) -> RoleRow {
         hoist: crate::db::get_bool(&row, "hoist"),
         icon: row.get("icon"),
         position: row.get("position"),
-        permissions: row.get("permissions"),
+        permissions: Permissions::from_bits(row.get("permission_bits")),
         managed: crate::db::get_bool(&row, "managed"),
         mentionable: crate::db::get_bool(&row, "mentionable"),
+        member_count: row.get("member_count"),
     }
 }
 
-const SELECT_ROLES: &str = "SELECT id, space_id, name, color, hoist, icon, position, permissions, managed, mentionable FROM roles";
+const SELECT_ROLES: &str = "SELECT id, space_id, name, color, hoist, icon, position, permission_bits, managed, mentionable, member_count FROM roles";
 
-pub async fn get_role_row(pool: &AnyPool, role_id: &str) -> Result<RoleRow, AppError> {
+pub async fn get_role_row(pool: &DbPool, role_id: &str) -> Result<RoleRow, AppError> {
     let row = sqlx::query(&super::q(&format!("{SELECT_ROLES} WHERE id = ?")))
         .bind(role_id)
         .fetch_optional(pool)
@@ -31,7 +35,19 @@ pub async fn get_role_row(pool: &AnyPool, role_id: &str) -> Result<RoleRow, AppE
     Ok(row_to_role(row))
 }
 
-pub async fn list_roles(pool: &AnyPool, space_id: &str) -> Result<Vec<RoleRow>, AppError> {
+/// ID of the space's @everyone role (the one at position 0).
+pub async fn get_everyone_role_id(pool: &DbPool, space_id: &str) -> Result<String, AppError> {
+    let row: Option<(String,)> = sqlx::query_as(&super::q(
+        "SELECT id FROM roles WHERE space_id = ? AND position = 0",
+    ))
+    .bind(space_id)
+    .fetch_optional(pool)
+    .await?;
+    row.map(|(id,)| id)
+        .ok_or_else(|| AppError::NotFound("unknown_role".to_string()))
+}
+
+pub async fn list_roles(pool: &DbPool, space_id: &str) -> Result<Vec<RoleRow>, AppError> {
     let rows = sqlx::query(&super::q(&format!(
         "{SELECT_ROLES} WHERE space_id = ? ORDER BY position"
     )))
@@ -43,12 +59,12 @@ pub async fn list_roles(pool: &AnyPool, space_id: &str) -> Result<Vec<RoleRow>,
 }
 
 pub async fn create_role(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     input: &CreateRole,
 ) -> Result<RoleRow, AppError> {
     let id = snowflake::generate();
-    let permissions = serde_json::to_string(&input.permissions.as_deref().unwrap_or(&[])).unwrap();
+    let permissions = Permissions::parse(input.permissions.as_deref().unwrap_or(&[]))?;
 
     // Get max position
     let max_pos: Option<i64> = sqlx::query_scalar(&super::q(
@@ -60,14 +76,14 @@ pub async fn create_role(
     let position = max_pos.unwrap_or(0) + 1;
 
     sqlx::query(
-        &super::q("INSERT INTO roles (id, space_id, name, color, hoist, permissions, mentionable, position) VALUES (?, ?, ?, ?, ?, ?, ?, ?)")
+        &super::q("INSERT INTO roles (id, space_id, name, color, hoist, permission_bits, mentionable, position) VALUES (?, ?, ?, ?, ?, ?, ?, ?)")
     )
     .bind(&id)
     .bind(space_id)
     .bind(&input.name)
     .bind(input.color.unwrap_or(0))
     .bind(input.hoist.unwrap_or(false))
-    .bind(&permissions)
+    .bind(permissions.bits())
     .bind(input.mentionable.unwrap_or(false))
     .bind(position)
     .execute(pool)
@@ -77,7 +93,7 @@ pub async fn create_role(
 }
 
 pub async fn update_role(
-    pool: &AnyPool,
+    pool: &DbPool,
     role_id: &str,
     input: &UpdateRole,
     is_postgres: bool,
@@ -96,15 +112,14 @@ pub async fn update_role(
         sets.push("icon = ?".to_string());
         str_values.push(icon.clone());
     }
-    if let Some(ref permissions) = input.permissions {
-        let json = serde_json::to_string(permissions).unwrap();
-        sets.push("permissions = ?".to_string());
-        str_values.push(json);
-    }
 
     if let Some(color) = input.color {
         int_vals.push(("color".to_string(), color));
     }
+    if let Some(ref permissions) = input.permissions {
+        let bits = Permissions::parse(permissions)?.bits();
+        int_vals.push(("permission_bits".to_string(), bits));
+    }
     if let Some(hoist) = input.hoist {
         bool_vals.push(("hoist".to_string(), hoist));
     }
@@ -146,7 +161,7 @@ pub async fn update_role(
     get_role_row(pool, role_id).await
 }
 
-pub async fn delete_role(pool: &AnyPool, role_id: &str) -> Result<(), AppError> {
+pub async fn delete_role(pool: &DbPool, role_id: &str) -> Result<(), AppError> {
     sqlx::query(&super::q("DELETE FROM roles WHERE id = ?"))
         .bind(role_id)
         .execute(pool)
@@ -155,7 +170,7 @@ pub async fn delete_role(pool: &AnyPool, role_id: &str) -> Result<(), AppError>
 }
 
 pub async fn reorder_roles(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     updates: &[(String, i64)],
 ) -> Result<(), AppError> {
@@ -0,0 +1,148 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::screening::{MemberScreening, WelcomeChannel, WelcomeScreen};
+
+/// A space's welcome screen; spaces that never configured one get a disabled,
+/// empty screen.
+pub async fn get_welcome_screen(pool: &DbPool, space_id: &str) -> Result<WelcomeScreen, AppError> {
+    let row = sqlx::query(&super::q(
+        "SELECT enabled, description, channels, updated_at FROM welcome_screens WHERE space_id = ?",
+    ))
+    .bind(space_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(match row {
+        Some(row) => WelcomeScreen {
+            space_id: space_id.to_string(),
+            enabled: crate::db::get_bool(&row, "enabled"),
+            description: row.try_get("description").ok().flatten(),
+            channels: row
+                .try_get::<String, _>("channels")
+                .ok()
+                .and_then(|c| serde_json::from_str(&c).ok())
+                .unwrap_or_default(),
+            updated_at: row.try_get("updated_at").ok(),
+        },
+        None => WelcomeScreen {
+            space_id: space_id.to_string(),
+            enabled: false,
+            description: None,
+            channels: Vec::new(),
+            updated_at: None,
+        },
+    })
+}
+
+pub async fn set_welcome_screen(
+    pool: &DbPool,
+    space_id: &str,
+    enabled: bool,
+    description: Option<&str>,
+    channels: &[WelcomeChannel],
+    is_postgres: bool,
+) -> Result<(), AppError> {
+    let now_fn = crate::db::now_sql(is_postgres);
+    sqlx::query(&super::q(&format!(
+        "INSERT INTO welcome_screens (space_id, enabled, description, channels) VALUES (?, ?, ?, ?)
+         ON CONFLICT (space_id) DO UPDATE SET enabled = excluded.enabled, \
+         description = excluded.description, channels = excluded.channels, updated_at = {now_fn}"
+    )))
+    .bind(space_id)
+    .bind(enabled)
+    .bind(description)
+    .bind(serde_json::to_string(channels).unwrap_or_else(|_| "[]".into()))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// A space's member screening form; spaces that never configured one get a
+/// disabled, empty form.
+pub async fn get_member_screening(
+    pool: &DbPool,
+    space_id: &str,
+) -> Result<MemberScreening, AppError> {
+    let row = sqlx::query(&super::q(
+        "SELECT enabled, description, rules, updated_at FROM member_screenings WHERE space_id = ?",
+    ))
+    .bind(space_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(match row {
+        Some(row) => MemberScreening {
+            space_id: space_id.to_string(),
+            enabled: crate::db::get_bool(&row, "enabled"),
+            description: row.try_get("description").ok().flatten(),
+            rules: row
+                .try_get::<String, _>("rules")
+                .ok()
+                .and_then(|r| serde_json::from_str(&r).ok())
+                .unwrap_or_default(),
+            updated_at: row.try_get("updated_at").ok(),
+        },
+        None => MemberScreening {
+            space_id: space_id.to_string(),
+            enabled: false,
+            description: None,
+            rules: Vec::new(),
+            updated_at: None,
+        },
+    })
+}
+
+/// Save a space's screening form. Turning screening off lets everyone still
+/// waiting on it in.
+pub async fn set_member_screening(
+    pool: &DbPool,
+    space_id: &str,
+    enabled: bool,
+    description: Option<&str>,
+    rules: &[String],
+    is_postgres: bool,
+) -> Result<(), AppError> {
+    let now_fn = crate::db::now_sql(is_postgres);
+    let mut tx = pool.begin().await?;
+    sqlx::query(&super::q(&format!(
+        "INSERT INTO member_screenings (space_id, enabled, description, rules) VALUES (?, ?, ?, ?)
+         ON CONFLICT (space_id) DO UPDATE SET enabled = excluded.enabled, \
+         description = excluded.description, rules = excluded.rules, updated_at = {now_fn}"
+    )))
+    .bind(space_id)
+    .bind(enabled)
+    .bind(description)
+    .bind(serde_json::to_string(rules).unwrap_or_else(|_| "[]".into()))
+    .execute(&mut tx)
+    .await?;
+    if !enabled {
+        sqlx::query(&super::q(
+            "UPDATE members SET pending = ? WHERE space_id = ? AND pending = ?",
+        ))
+        .bind(false)
+        .bind(space_id)
+        .bind(true)
+        .execute(&mut tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Mark a member as having accepted the space's rules. Returns whether they
+/// were pending.
+pub async fn clear_pending(pool: &DbPool, space_id: &str, user_id: &str) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "UPDATE members SET pending = ? WHERE space_id = ? AND user_id = ? AND pending = ?",
+    ))
+    .bind(false)
+    .bind(space_id)
+    .bind(user_id)
+    .bind(true)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
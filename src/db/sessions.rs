@@ -0,0 +1,161 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::session::Session;
+
+/// Length of the token-hash prefix that identifies a session.
+const SESSION_ID_LEN: usize = 16;
+
+/// How stale `last_used_at` may get before a request refreshes it.
+pub const TOUCH_INTERVAL_SECS: i64 = 300;
+
+/// The public ID of the session behind `token_hash`.
+pub fn session_id(token_hash: &str) -> &str {
+    &token_hash[..SESSION_ID_LEN.min(token_hash.len())]
+}
+
+fn now() -> String {
+    chrono::Utc::now()
+        .format("%Y-%m-%dT%H:%M:%S+00:00")
+        .to_string()
+}
+
+/// Store a freshly issued user token with where it was signed in from.
+pub async fn create_session(
+    pool: &DbPool,
+    user_id: &str,
+    token_hash: &str,
+    expires_at: &str,
+    device: Option<&str>,
+    ip: Option<&str>,
+) -> Result<(), AppError> {
+    let now = now();
+    sqlx::query(&super::q(
+        "INSERT INTO user_tokens (token_hash, user_id, expires_at, device, ip, last_used_at) \
+         VALUES (?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(token_hash)
+    .bind(user_id)
+    .bind(expires_at)
+    .bind(device)
+    .bind(ip)
+    .bind(&now)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// A user's unexpired sessions, most recently used first. `current_hash`
+/// marks the caller's own session.
+pub async fn list_sessions(
+    pool: &DbPool,
+    user_id: &str,
+    current_hash: &str,
+) -> Result<Vec<Session>, AppError> {
+    let rows = sqlx::query(&super::q(
+        "SELECT token_hash, device, ip, created_at, last_used_at, expires_at FROM user_tokens \
+         WHERE user_id = ? AND expires_at > ? \
+         ORDER BY COALESCE(last_used_at, created_at) DESC, token_hash ASC",
+    ))
+    .bind(user_id)
+    .bind(now())
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let token_hash: String = row.get("token_hash");
+            Session {
+                id: session_id(&token_hash).to_string(),
+                device: row.get("device"),
+                ip: row.get("ip"),
+                created_at: row.get("created_at"),
+                last_used_at: row.get("last_used_at"),
+                expires_at: row.get("expires_at"),
+                current: token_hash == current_hash,
+            }
+        })
+        .collect())
+}
+
+/// Delete one of `user_id`'s sessions. Returns false when there's no such
+/// session.
+pub async fn delete_session(pool: &DbPool, user_id: &str, id: &str) -> Result<bool, AppError> {
+    if id.len() != SESSION_ID_LEN {
+        return Ok(false);
+    }
+    let result = sqlx::query(&super::q(
+        "DELETE FROM user_tokens WHERE user_id = ? AND substr(token_hash, 1, 16) = ?",
+    ))
+    .bind(user_id)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Record that the session was used just now, from `ip`.
+pub async fn touch_session(
+    pool: &DbPool,
+    token_hash: &str,
+    ip: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "UPDATE user_tokens SET last_used_at = ?, ip = COALESCE(?, ip) WHERE token_hash = ?",
+    ))
+    .bind(now())
+    .bind(ip)
+    .bind(token_hash)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Note that `user_id` just proved possession of their second factor on the
+/// session `id`, for `require_recent_mfa`. Returns the verification time.
+pub async fn record_mfa_verification(
+    pool: &DbPool,
+    user_id: &str,
+    id: &str,
+) -> Result<String, AppError> {
+    let now = now();
+    sqlx::query(&super::q(
+        "UPDATE user_tokens SET mfa_verified_at = ? WHERE user_id = ? AND substr(token_hash, 1, 16) = ?",
+    ))
+    .bind(&now)
+    .bind(user_id)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(now)
+}
+
+/// When the session `id` last verified a second factor, if ever.
+pub async fn mfa_verified_at(
+    pool: &DbPool,
+    user_id: &str,
+    id: &str,
+) -> Result<Option<String>, AppError> {
+    let verified_at = sqlx::query_scalar::<_, Option<String>>(&super::q(
+        "SELECT mfa_verified_at FROM user_tokens WHERE user_id = ? AND substr(token_hash, 1, 16) = ?",
+    ))
+    .bind(user_id)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .flatten();
+    Ok(verified_at)
+}
+
+/// Forget every session's step-up verification, e.g. when 2FA is disabled.
+pub async fn clear_mfa_verifications(pool: &DbPool, user_id: &str) -> Result<(), AppError> {
+    sqlx::query(&super::q(
+        "UPDATE user_tokens SET mfa_verified_at = NULL WHERE user_id = ?",
+    ))
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
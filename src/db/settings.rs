@@ -1,14 +1,18 @@
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::models::settings::{ServerSettings, UpdateServerSettings};
 
-pub async fn get_settings(pool: &AnyPool) -> Result<ServerSettings, AppError> {
+pub async fn get_settings(pool: &DbPool) -> Result<ServerSettings, AppError> {
     let row = sqlx::query(
-        "SELECT max_emoji_size, max_avatar_size, max_sound_size, max_attachment_size, \
+        "SELECT max_emoji_size, max_avatar_size, max_sound_size, max_sticker_size, max_attachment_size, \
          max_attachments_per_message, server_name, registration_policy, max_spaces, \
-         max_members_per_space, motd, public_listing, tos_enabled, tos_text, \
-         tos_version, tos_url, updated_at \
+         max_members_per_space, max_emojis_per_space, max_sounds_per_space, max_stickers_per_space, motd, public_listing, tos_enabled, tos_text, \
+         tos_version, tos_url, cors_allowed_origins, cdn_content_security_policy, \
+         default_space_template, content_safety_providers, content_safety_endpoint, \
+         content_safety_action, updated_at \
          FROM server_settings WHERE id = 1",
     )
     .fetch_one(pool)
@@ -18,24 +22,36 @@ pub async fn get_settings(pool: &AnyPool) -> Result<ServerSettings, AppError> {
         max_emoji_size: row.get("max_emoji_size"),
         max_avatar_size: row.get("max_avatar_size"),
         max_sound_size: row.get("max_sound_size"),
+        max_sticker_size: row.get("max_sticker_size"),
         max_attachment_size: row.get("max_attachment_size"),
         max_attachments_per_message: row.get("max_attachments_per_message"),
         server_name: row.get("server_name"),
         registration_policy: row.get("registration_policy"),
         max_spaces: row.get("max_spaces"),
         max_members_per_space: row.get("max_members_per_space"),
+        max_emojis_per_space: row.get("max_emojis_per_space"),
+        max_sounds_per_space: row.get("max_sounds_per_space"),
+        max_stickers_per_space: row.get("max_stickers_per_space"),
         motd: row.get("motd"),
         public_listing: crate::db::get_bool(&row, "public_listing"),
         tos_enabled: crate::db::get_bool(&row, "tos_enabled"),
         tos_text: row.get("tos_text"),
         tos_version: row.get("tos_version"),
         tos_url: row.get("tos_url"),
+        cors_allowed_origins: row.get("cors_allowed_origins"),
+        cdn_content_security_policy: row.get("cdn_content_security_policy"),
+        default_space_template: row
+            .get::<Option<String>, _>("default_space_template")
+            .and_then(|json| serde_json::from_str(&json).ok()),
+        content_safety_providers: row.get("content_safety_providers"),
+        content_safety_endpoint: row.get("content_safety_endpoint"),
+        content_safety_action: row.get("content_safety_action"),
         updated_at: row.get("updated_at"),
     })
 }
 
 pub async fn update_settings(
-    pool: &AnyPool,
+    pool: &DbPool,
     input: &UpdateServerSettings,
     is_postgres: bool,
 ) -> Result<ServerSettings, AppError> {
@@ -50,6 +66,9 @@ pub async fn update_settings(
     if input.max_sound_size.is_some() {
         sets.push("max_sound_size = ?");
     }
+    if input.max_sticker_size.is_some() {
+        sets.push("max_sticker_size = ?");
+    }
     if input.max_attachment_size.is_some() {
         sets.push("max_attachment_size = ?");
     }
@@ -68,6 +87,15 @@ pub async fn update_settings(
     if input.max_members_per_space.is_some() {
         sets.push("max_members_per_space = ?");
     }
+    if input.max_emojis_per_space.is_some() {
+        sets.push("max_emojis_per_space = ?");
+    }
+    if input.max_sounds_per_space.is_some() {
+        sets.push("max_sounds_per_space = ?");
+    }
+    if input.max_stickers_per_space.is_some() {
+        sets.push("max_stickers_per_space = ?");
+    }
     if input.motd.is_some() {
         sets.push("motd = ?");
     }
@@ -90,6 +118,24 @@ pub async fn update_settings(
     if input.tos_url.is_some() {
         sets.push("tos_url = ?");
     }
+    if input.cors_allowed_origins.is_some() {
+        sets.push("cors_allowed_origins = ?");
+    }
+    if input.cdn_content_security_policy.is_some() {
+        sets.push("cdn_content_security_policy = ?");
+    }
+    if input.default_space_template.is_some() {
+        sets.push("default_space_template = ?");
+    }
+    if input.content_safety_providers.is_some() {
+        sets.push("content_safety_providers = ?");
+    }
+    if input.content_safety_endpoint.is_some() {
+        sets.push("content_safety_endpoint = ?");
+    }
+    if input.content_safety_action.is_some() {
+        sets.push("content_safety_action = ?");
+    }
 
     if sets.is_empty() {
         return get_settings(pool).await;
@@ -114,6 +160,9 @@ pub async fn update_settings(
     if let Some(v) = input.max_sound_size {
         query = query.bind(v);
     }
+    if let Some(v) = input.max_sticker_size {
+        query = query.bind(v);
+    }
     if let Some(v) = input.max_attachment_size {
         query = query.bind(v);
     }
@@ -132,6 +181,15 @@ pub async fn update_settings(
     if let Some(v) = input.max_members_per_space {
         query = query.bind(v);
     }
+    if let Some(v) = input.max_emojis_per_space {
+        query = query.bind(v);
+    }
+    if let Some(v) = input.max_sounds_per_space {
+        query = query.bind(v);
+    }
+    if let Some(v) = input.max_stickers_per_space {
+        query = query.bind(v);
+    }
     if let Some(ref v) = input.motd {
         query = query.bind(v);
     }
@@ -150,8 +208,47 @@ pub async fn update_settings(
     if let Some(ref v) = input.tos_url {
         query = query.bind(v);
     }
+    if let Some(ref v) = input.cors_allowed_origins {
+        query = query.bind(v);
+    }
+    if let Some(ref v) = input.cdn_content_security_policy {
+        query = query.bind(v);
+    }
+    if let Some(ref v) = input.default_space_template {
+        query = query.bind(v.as_ref().map(|t| serde_json::to_string(t).unwrap()));
+    }
+    // Empty strings clear the provider list and endpoint.
+    if let Some(ref v) = input.content_safety_providers {
+        query = query.bind((!v.is_empty()).then_some(v));
+    }
+    if let Some(ref v) = input.content_safety_endpoint {
+        query = query.bind((!v.is_empty()).then_some(v));
+    }
+    if let Some(ref v) = input.content_safety_action {
+        query = query.bind(v);
+    }
 
     query.execute(pool).await?;
 
     get_settings(pool).await
 }
+
+/// The key message share links are signed with, generated on first use.
+/// Kept in the database so every node signs and checks with the same key.
+pub async fn share_link_key(pool: &DbPool) -> Result<Vec<u8>, AppError> {
+    let mut key = [0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut key);
+    sqlx::query(&super::q(
+        "UPDATE server_settings SET share_link_key = ? WHERE id = 1 AND share_link_key IS NULL",
+    ))
+    .bind(data_encoding::HEXLOWER.encode(&key))
+    .execute(pool)
+    .await?;
+    let stored: String =
+        sqlx::query_scalar("SELECT share_link_key FROM server_settings WHERE id = 1")
+            .fetch_one(pool)
+            .await?;
+    data_encoding::HEXLOWER
+        .decode(stored.as_bytes())
+        .map_err(|_| AppError::Internal("stored share link key is invalid".to_string()))
+}
@@ -0,0 +1,124 @@
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::middleware::auth::generate_token;
+use crate::models::application::SigningKeyInfo;
+use crate::signing::SigningKey;
+use crate::snowflake;
+
+/// Timestamps here share the `created_at` column format so they compare as
+/// strings on both backends.
+fn timestamp_in(secs: i64) -> String {
+    (chrono::Utc::now() + chrono::Duration::seconds(secs))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Keys that still sign, oldest first, as [SigningKeyInfo] without secrets.
+pub async fn list_live_keys(
+    pool: &DbPool,
+    application_id: &str,
+) -> Result<Vec<SigningKeyInfo>, AppError> {
+    let rows: Vec<(String, String, Option<String>)> = sqlx::query_as(&super::q(
+        "SELECT id, created_at, expires_at FROM application_signing_keys \
+         WHERE application_id = ? AND (expires_at IS NULL OR expires_at > ?) \
+         ORDER BY created_at ASC, id ASC",
+    ))
+    .bind(application_id)
+    .bind(timestamp_in(0))
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, created_at, expires_at)| SigningKeyInfo {
+            id,
+            created_at,
+            expires_at,
+            secret: None,
+        })
+        .collect())
+}
+
+/// Keys that still sign, with secrets, for signing an outgoing request.
+pub async fn live_signing_keys(
+    pool: &DbPool,
+    application_id: &str,
+) -> Result<Vec<SigningKey>, AppError> {
+    let rows: Vec<(String, String)> = sqlx::query_as(&super::q(
+        "SELECT id, secret FROM application_signing_keys \
+         WHERE application_id = ? AND (expires_at IS NULL OR expires_at > ?) \
+         ORDER BY created_at ASC, id ASC",
+    ))
+    .bind(application_id)
+    .bind(timestamp_in(0))
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, secret)| SigningKey { id, secret })
+        .collect())
+}
+
+/// Add a new current key and give every other live key `overlap_secs` more
+/// to sign (never extending one that already expires sooner). Expired keys
+/// are dropped on the way. Returns the new key, secret included.
+pub async fn rotate(
+    pool: &DbPool,
+    application_id: &str,
+    overlap_secs: i64,
+) -> Result<SigningKeyInfo, AppError> {
+    let now = timestamp_in(0);
+    let retire_at = timestamp_in(overlap_secs);
+    let id = snowflake::generate();
+    let secret = generate_token();
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(&super::q(
+        "DELETE FROM application_signing_keys WHERE application_id = ? AND expires_at <= ?",
+    ))
+    .bind(application_id)
+    .bind(&now)
+    .execute(&mut tx)
+    .await?;
+    sqlx::query(&super::q(
+        "UPDATE application_signing_keys SET expires_at = ? \
+         WHERE application_id = ? AND (expires_at IS NULL OR expires_at > ?)",
+    ))
+    .bind(&retire_at)
+    .bind(application_id)
+    .bind(&retire_at)
+    .execute(&mut tx)
+    .await?;
+    sqlx::query(&super::q(
+        "INSERT INTO application_signing_keys (id, application_id, secret, created_at) VALUES (?, ?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(application_id)
+    .bind(&secret)
+    .bind(&now)
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(SigningKeyInfo {
+        id,
+        created_at: now,
+        expires_at: None,
+        secret: Some(secret),
+    })
+}
+
+/// Stop a key signing right away, e.g. after its secret leaked.
+pub async fn revoke(pool: &DbPool, application_id: &str, key_id: &str) -> Result<(), AppError> {
+    let result = sqlx::query(&super::q(
+        "DELETE FROM application_signing_keys WHERE application_id = ? AND id = ?",
+    ))
+    .bind(application_id)
+    .bind(key_id)
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("unknown_signing_key".into()));
+    }
+    Ok(())
+}
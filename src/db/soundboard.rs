@@ -1,4 +1,6 @@
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::models::soundboard::{CreateSound, SoundboardSound, UpdateSound};
@@ -16,7 +18,7 @@ fn row_to_sound(row: sqlx::any::AnyRow) -> SoundboardSound {
     }
 }
 
-pub async fn get_sound(pool: &AnyPool, sound_id: &str) -> Result<SoundboardSound, AppError> {
+pub async fn get_sound(pool: &DbPool, sound_id: &str) -> Result<SoundboardSound, AppError> {
     let row = sqlx::query(
         &super::q("SELECT id, name, audio_path, volume, creator_id, created_at, updated_at FROM soundboard_sounds WHERE id = ?")
     )
@@ -28,7 +30,7 @@ pub async fn get_sound(pool: &AnyPool, sound_id: &str) -> Result<SoundboardSound
     Ok(row_to_sound(row))
 }
 
-pub async fn list_sounds(pool: &AnyPool, space_id: &str) -> Result<Vec<SoundboardSound>, AppError> {
+pub async fn list_sounds(pool: &DbPool, space_id: &str) -> Result<Vec<SoundboardSound>, AppError> {
     let rows = sqlx::query(
         &super::q("SELECT id, name, audio_path, volume, creator_id, created_at, updated_at FROM soundboard_sounds WHERE space_id = ? ORDER BY created_at ASC")
     )
@@ -39,8 +41,18 @@ pub async fn list_sounds(pool: &AnyPool, space_id: &str) -> Result<Vec<Soundboar
     Ok(rows.into_iter().map(row_to_sound).collect())
 }
 
+pub async fn count_sounds(pool: &DbPool, space_id: &str) -> Result<i64, AppError> {
+    let count: i64 = sqlx::query_scalar(&super::q(
+        "SELECT COUNT(*) FROM soundboard_sounds WHERE space_id = ?",
+    ))
+    .bind(space_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(count)
+}
+
 pub async fn create_sound(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     creator_id: &str,
     input: &CreateSound,
@@ -69,7 +81,7 @@ pub async fn create_sound(
 }
 
 pub async fn update_sound(
-    pool: &AnyPool,
+    pool: &DbPool,
     sound_id: &str,
     input: &UpdateSound,
     is_postgres: bool,
@@ -100,7 +112,7 @@ pub async fn update_sound(
 }
 
 /// Delete a sound. Returns the audio_path for file cleanup.
-pub async fn delete_sound(pool: &AnyPool, sound_id: &str) -> Result<Option<String>, AppError> {
+pub async fn delete_sound(pool: &DbPool, sound_id: &str) -> Result<Option<String>, AppError> {
     let audio_path: Option<String> = sqlx::query_scalar(&super::q(
         "SELECT audio_path FROM soundboard_sounds WHERE id = ?",
     ))
@@ -1,7 +1,12 @@
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
-use crate::models::space::{CreateSpace, PublicSpaceRow, SpaceRow, UpdateSpace};
+use crate::models::permission::Permissions;
+use crate::models::space::{
+    split_tags, CreateSpace, DirectoryFilter, DirectorySort, PublicSpaceRow, SpaceRow, UpdateSpace,
+};
 use crate::slug;
 use crate::snowflake;
 
@@ -29,14 +34,34 @@ fn row_to_space(row: sqlx::any::AnyRow) -> SpaceRow {
         premium_subscription_count: row.get("premium_subscription_count"),
         public: crate::db::get_bool(&row, "public"),
         allow_guest_access: crate::db::get_bool(&row, "allow_guest_access"),
+        emoji_moderation: crate::db::get_bool(&row, "emoji_moderation"),
         max_members: row.get("max_members"),
+        max_emojis: row.get("max_emojis"),
+        max_sounds: row.get("max_sounds"),
+        max_stickers: row.get("max_stickers"),
+        channel_order_version: row.get("channel_order_version"),
+        mfa_level: row.get("mfa_level"),
+        widget_enabled: crate::db::get_bool(&row, "widget_enabled"),
+        widget_channel_id: row.get("widget_channel_id"),
+        message_sharing: crate::db::get_bool(&row, "message_sharing"),
+        invite_preview_required: crate::db::get_bool(&row, "invite_preview_required"),
+        stream_max_height: row.get("stream_max_height"),
+        stream_max_fps: row.get("stream_max_fps"),
+        category: row.get("category"),
+        tags: split_tags(&row.get::<String, _>("tags")),
+        featured: crate::db::get_bool(&row, "featured"),
+        archived_at: row.get("archived_at"),
         created_at: row.get("created_at"),
     }
 }
 
-const SELECT_SPACES: &str = "SELECT id, name, slug, description, icon, banner, splash, owner_id, verification_level, default_notifications, explicit_content_filter, vanity_url_code, preferred_locale, afk_channel_id, afk_timeout, system_channel_id, rules_channel_id, nsfw_level, premium_tier, premium_subscription_count, public, allow_guest_access, max_members, created_at FROM spaces";
+const SELECT_SPACES: &str = "SELECT id, name, slug, description, icon, banner, splash, owner_id, verification_level, default_notifications, explicit_content_filter, vanity_url_code, preferred_locale, afk_channel_id, afk_timeout, system_channel_id, rules_channel_id, nsfw_level, premium_tier, premium_subscription_count, public, allow_guest_access, emoji_moderation, max_members, \
+    COALESCE(max_emojis, (SELECT max_emojis_per_space FROM server_settings WHERE id = 1), 0) AS max_emojis, \
+    COALESCE(max_sounds, (SELECT max_sounds_per_space FROM server_settings WHERE id = 1), 0) AS max_sounds, \
+    COALESCE(max_stickers, (SELECT max_stickers_per_space FROM server_settings WHERE id = 1), 0) AS max_stickers, \
+    channel_order_version, mfa_level, widget_enabled, widget_channel_id, message_sharing, invite_preview_required, stream_max_height, stream_max_fps, category, tags, featured, archived_at, created_at FROM spaces";
 
-pub async fn get_space_row(pool: &AnyPool, space_id: &str) -> Result<SpaceRow, AppError> {
+pub async fn get_space_row(pool: &DbPool, space_id: &str) -> Result<SpaceRow, AppError> {
     let row = sqlx::query(&super::q(&format!("{SELECT_SPACES} WHERE id = ?")))
         .bind(space_id)
         .fetch_optional(pool)
@@ -47,7 +72,7 @@ pub async fn get_space_row(pool: &AnyPool, space_id: &str) -> Result<SpaceRow, A
 }
 
 pub async fn create_space(
-    pool: &AnyPool,
+    pool: &DbPool,
     owner_id: &str,
     input: &CreateSpace,
 ) -> Result<SpaceRow, AppError> {
@@ -63,8 +88,18 @@ pub async fn create_space(
     };
     let final_slug = ensure_unique_slug(pool, &base_slug, None).await?;
 
+    let builtin;
+    let template = match input.template {
+        Some(ref template) => template,
+        None => {
+            builtin = crate::space_template::SpaceTemplate::builtin();
+            &builtin
+        }
+    };
+
+    let mut tx = pool.begin().await?;
     sqlx::query(&super::q(
-        "INSERT INTO spaces (id, name, slug, description, owner_id, public, allow_guest_access) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        "INSERT INTO spaces (id, name, slug, description, owner_id, public, allow_guest_access, preferred_locale) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
     ))
     .bind(&id)
     .bind(&input.name)
@@ -73,60 +108,76 @@ pub async fn create_space(
     .bind(owner_id)
     .bind(input.public.unwrap_or(false))
     .bind(input.allow_guest_access.unwrap_or(true))
-    .execute(pool)
+    .bind(
+        input
+            .preferred_locale
+            .as_deref()
+            .unwrap_or(crate::locale::DEFAULT_LOCALE),
+    )
+    .execute(&mut tx)
     .await?;
 
     // Create @everyone role with default permissions
     let role_id = snowflake::generate();
     let default_perms =
-        serde_json::to_string(&crate::middleware::permissions::DEFAULT_EVERYONE_PERMISSIONS)
-            .unwrap();
+        Permissions::parse(crate::middleware::permissions::DEFAULT_EVERYONE_PERMISSIONS)?;
     sqlx::query(&super::q(
-        "INSERT INTO roles (id, space_id, name, position, permissions) VALUES (?, ?, '@everyone', 0, ?)"
+        "INSERT INTO roles (id, space_id, name, position, permission_bits) VALUES (?, ?, '@everyone', 0, ?)"
     ))
     .bind(&role_id)
     .bind(&id)
-    .bind(&default_perms)
-    .execute(pool)
-    .await?;
-
-    // Create Moderator role at position 1
-    let mod_role_id = snowflake::generate();
-    let mod_perms =
-        serde_json::to_string(&crate::middleware::permissions::MODERATOR_PERMISSIONS).unwrap();
-    sqlx::query(&super::q(
-        "INSERT INTO roles (id, space_id, name, color, hoist, position, permissions) VALUES (?, ?, 'Moderator', 3447003, ?, 1, ?)"
-    ))
-    .bind(&mod_role_id)
-    .bind(&id)
-    .bind(true)
-    .bind(&mod_perms)
-    .execute(pool)
+    .bind(default_perms.bits())
+    .execute(&mut tx)
     .await?;
 
-    // Create Admin role at position 2
-    let admin_role_id = snowflake::generate();
-    let admin_perms =
-        serde_json::to_string(&crate::middleware::permissions::ADMIN_PERMISSIONS).unwrap();
-    sqlx::query(&super::q(
-        "INSERT INTO roles (id, space_id, name, color, hoist, position, permissions) VALUES (?, ?, 'Admin', 15158332, ?, 2, ?)"
-    ))
-    .bind(&admin_role_id)
-    .bind(&id)
-    .bind(true)
-    .bind(&admin_perms)
-    .execute(pool)
-    .await?;
+    // Template roles stack above @everyone in the order given
+    let mut owner_role_ids = Vec::new();
+    for (i, role) in template.roles.iter().enumerate() {
+        let role_id = snowflake::generate();
+        sqlx::query(&super::q(
+            "INSERT INTO roles (id, space_id, name, color, hoist, position, permission_bits, member_count) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        ))
+        .bind(&role_id)
+        .bind(&id)
+        .bind(role.name.trim())
+        .bind(role.color.unwrap_or(0))
+        .bind(role.hoist)
+        .bind(i as i64 + 1)
+        .bind(Permissions::parse(&role.permissions)?.bits())
+        .bind(role.assign_to_owner as i64)
+        .execute(&mut tx)
+        .await?;
+        if role.assign_to_owner {
+            owner_role_ids.push(role_id);
+        }
+    }
 
-    // Create default #general text channel
-    let channel_id = snowflake::generate();
-    sqlx::query(&super::q(
-        "INSERT INTO channels (id, name, type, space_id, position) VALUES (?, 'general', 'text', ?, 0)"
-    ))
-    .bind(&channel_id)
-    .bind(&id)
-    .execute(pool)
-    .await?;
+    // Template channels; a parent always precedes its children
+    let mut category_ids: Vec<(&str, String)> = Vec::new();
+    for (i, channel) in template.channels.iter().enumerate() {
+        let channel_id = snowflake::generate();
+        let parent_id = channel.parent.as_deref().and_then(|parent| {
+            category_ids
+                .iter()
+                .find(|(name, _)| *name == parent)
+                .map(|(_, id)| id.clone())
+        });
+        sqlx::query(&super::q(
+            "INSERT INTO channels (id, name, type, space_id, topic, parent_id, position) VALUES (?, ?, ?, ?, ?, ?, ?)"
+        ))
+        .bind(&channel_id)
+        .bind(channel.name.trim())
+        .bind(&channel.channel_type)
+        .bind(&id)
+        .bind(&channel.topic)
+        .bind(parent_id)
+        .bind(i as i64)
+        .execute(&mut tx)
+        .await?;
+        if channel.channel_type == "category" {
+            category_ids.push((channel.name.as_str(), channel_id));
+        }
+    }
 
     // Add the owner as a member
     sqlx::query(&super::q(
@@ -134,24 +185,26 @@ pub async fn create_space(
     ))
     .bind(owner_id)
     .bind(&id)
-    .execute(pool)
+    .execute(&mut tx)
     .await?;
 
-    // Assign Admin role to owner
-    sqlx::query(&super::q(
-        "INSERT INTO member_roles (user_id, space_id, role_id) VALUES (?, ?, ?)",
-    ))
-    .bind(owner_id)
-    .bind(&id)
-    .bind(&admin_role_id)
-    .execute(pool)
-    .await?;
+    for role_id in &owner_role_ids {
+        sqlx::query(&super::q(
+            "INSERT INTO member_roles (user_id, space_id, role_id) VALUES (?, ?, ?)",
+        ))
+        .bind(owner_id)
+        .bind(&id)
+        .bind(role_id)
+        .execute(&mut tx)
+        .await?;
+    }
+    tx.commit().await?;
 
     get_space_row(pool, &id).await
 }
 
 pub async fn update_space(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     input: &UpdateSpace,
     is_postgres: bool,
@@ -214,6 +267,30 @@ pub async fn update_space(
         sets.push("preferred_locale = ?".to_string());
         values.push(preferred_locale.clone());
     }
+    if let Some(ref mfa_level) = input.mfa_level {
+        sets.push("mfa_level = ?".to_string());
+        values.push(mfa_level.clone());
+    }
+    if let Some(ref category) = input.category {
+        if category.is_empty() {
+            sets.push("category = NULL".to_string());
+        } else {
+            sets.push("category = ?".to_string());
+            values.push(category.clone());
+        }
+    }
+    if let Some(ref tags) = input.tags {
+        sets.push("tags = ?".to_string());
+        values.push(tags.join(","));
+    }
+    if let Some(ref widget_channel_id) = input.widget_channel_id {
+        if widget_channel_id.is_empty() {
+            sets.push("widget_channel_id = NULL".to_string());
+        } else {
+            sets.push("widget_channel_id = ?".to_string());
+            values.push(widget_channel_id.clone());
+        }
+    }
 
     // Collect integer fields that need separate binding
     let mut int_binds: Vec<i64> = Vec::new();
@@ -223,6 +300,14 @@ pub async fn update_space(
         sets.push("afk_timeout = ?".to_string());
         int_binds.push(timeout);
     }
+    if let Some(height) = input.stream_max_height {
+        sets.push("stream_max_height = ?".to_string());
+        int_binds.push(height);
+    }
+    if let Some(fps) = input.stream_max_fps {
+        sets.push("stream_max_fps = ?".to_string());
+        int_binds.push(fps);
+    }
     if let Some(public) = input.public {
         sets.push("public = ?".to_string());
         bool_binds.push(public);
@@ -231,6 +316,22 @@ pub async fn update_space(
         sets.push("allow_guest_access = ?".to_string());
         bool_binds.push(allow_guest_access);
     }
+    if let Some(emoji_moderation) = input.emoji_moderation {
+        sets.push("emoji_moderation = ?".to_string());
+        bool_binds.push(emoji_moderation);
+    }
+    if let Some(widget_enabled) = input.widget_enabled {
+        sets.push("widget_enabled = ?".to_string());
+        bool_binds.push(widget_enabled);
+    }
+    if let Some(message_sharing) = input.message_sharing {
+        sets.push("message_sharing = ?".to_string());
+        bool_binds.push(message_sharing);
+    }
+    if let Some(required) = input.invite_preview_required {
+        sets.push("invite_preview_required = ?".to_string());
+        bool_binds.push(required);
+    }
 
     if sets.is_empty() {
         return get_space_row(pool, space_id).await;
@@ -256,7 +357,7 @@ pub async fn update_space(
     get_space_row(pool, space_id).await
 }
 
-pub async fn delete_space(pool: &AnyPool, space_id: &str) -> Result<(), AppError> {
+pub async fn delete_space(pool: &DbPool, space_id: &str) -> Result<(), AppError> {
     sqlx::query(&super::q("DELETE FROM spaces WHERE id = ?"))
         .bind(space_id)
         .execute(pool)
@@ -264,36 +365,112 @@ pub async fn delete_space(pool: &AnyPool, space_id: &str) -> Result<(), AppError
     Ok(())
 }
 
-pub async fn list_public_spaces(pool: &AnyPool) -> Result<Vec<PublicSpaceRow>, AppError> {
-    let rows = sqlx::query(
-        "SELECT s.id, s.name, s.slug, s.description, s.icon, s.public, s.allow_guest_access,
-                COUNT(m.user_id) AS member_count
-         FROM spaces s
-         LEFT JOIN members m ON m.space_id = s.id
-         WHERE s.public = TRUE
-         GROUP BY s.id
-         ORDER BY s.name",
+/// Public spaces with their directory details, before filtering. Member
+/// counts and last activity are computed here so the outer query can sort
+/// and filter on them.
+const SELECT_DIRECTORY: &str = "SELECT * FROM (
+    SELECT s.id, s.name, s.slug, s.description, s.icon, s.public, s.allow_guest_access,
+           s.category, s.tags, s.featured, s.archived_at, s.created_at,
+           (SELECT COUNT(*) FROM members m WHERE m.space_id = s.id) AS member_count,
+           (SELECT MAX(msg.created_at) FROM messages msg WHERE msg.space_id = s.id) AS last_activity_at
+    FROM spaces s
+    WHERE s.public = TRUE
+) d";
+
+fn row_to_public_space(row: sqlx::any::AnyRow) -> PublicSpaceRow {
+    PublicSpaceRow {
+        id: row.get("id"),
+        name: row.get("name"),
+        slug: row.get("slug"),
+        description: row.get("description"),
+        icon: row.get("icon"),
+        member_count: row.get("member_count"),
+        public: crate::db::get_bool(&row, "public"),
+        allow_guest_access: crate::db::get_bool(&row, "allow_guest_access"),
+        category: row.get("category"),
+        tags: split_tags(&row.get::<String, _>("tags")),
+        featured: crate::db::get_bool(&row, "featured"),
+        last_activity_at: row.get("last_activity_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Every public space, by name.
+pub async fn list_public_spaces(pool: &DbPool) -> Result<Vec<PublicSpaceRow>, AppError> {
+    let rows = sqlx::query(&format!("{SELECT_DIRECTORY} ORDER BY d.name"))
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(row_to_public_space).collect())
+}
+
+/// A page of the public space directory. Fetches up to `limit + 1` rows so
+/// the caller can tell whether another page follows. Archived spaces can't
+/// be joined, so they're left out.
+pub async fn search_public_spaces(
+    pool: &DbPool,
+    filter: &DirectoryFilter,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<PublicSpaceRow>, AppError> {
+    let mut conditions: Vec<&str> = vec!["d.archived_at IS NULL"];
+    let mut binds: Vec<String> = Vec::new();
+
+    if let Some(ref search) = filter.search {
+        for word in search.split_whitespace() {
+            conditions.push(
+                "(LOWER(d.name) LIKE ? ESCAPE '\\' OR LOWER(COALESCE(d.description, '')) LIKE ? ESCAPE '\\')",
+            );
+            let pattern = format!("%{}%", super::escape_like(&word.to_lowercase()));
+            binds.push(pattern.clone());
+            binds.push(pattern);
+        }
+    }
+    if let Some(ref category) = filter.category {
+        conditions.push("d.category = ?");
+        binds.push(category.clone());
+    }
+    if let Some(ref tag) = filter.tag {
+        conditions.push("(',' || d.tags || ',') LIKE ? ESCAPE '\\'");
+        binds.push(format!("%,{},%", super::escape_like(tag)));
+    }
+    if filter.featured_only {
+        conditions.push("d.featured = TRUE");
+    }
+
+    let where_clause = format!(" WHERE {}", conditions.join(" AND "));
+    let order = match filter.sort {
+        DirectorySort::Members => "d.member_count DESC, d.id",
+        DirectorySort::Activity => "COALESCE(d.last_activity_at, '') DESC, d.id",
+        DirectorySort::Newest => "d.id DESC",
+        DirectorySort::Name => "LOWER(d.name), d.id",
+    };
+    let sql = super::q(&format!(
+        "{SELECT_DIRECTORY}{where_clause} ORDER BY {order} LIMIT ? OFFSET ?"
+    ));
+    let mut query = sqlx::query(&sql);
+    for v in &binds {
+        query = query.bind(v);
+    }
+    let rows = query.bind(limit + 1).bind(offset).fetch_all(pool).await?;
+    Ok(rows.into_iter().map(row_to_public_space).collect())
+}
+
+/// How many public spaces list themselves under each category.
+pub async fn count_public_spaces_by_category(
+    pool: &DbPool,
+) -> Result<Vec<(String, i64)>, AppError> {
+    let rows = sqlx::query_as::<_, (String, i64)>(
+        "SELECT category, COUNT(*) FROM spaces
+         WHERE public = TRUE AND archived_at IS NULL AND category IS NOT NULL
+         GROUP BY category",
     )
     .fetch_all(pool)
     .await?;
-
-    Ok(rows
-        .into_iter()
-        .map(|row| PublicSpaceRow {
-            id: row.get("id"),
-            name: row.get("name"),
-            slug: row.get("slug"),
-            description: row.get("description"),
-            icon: row.get("icon"),
-            member_count: row.get("member_count"),
-            public: crate::db::get_bool(&row, "public"),
-            allow_guest_access: crate::db::get_bool(&row, "allow_guest_access"),
-        })
-        .collect())
+    Ok(rows)
 }
 
 pub async fn list_space_ids_for_user(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
 ) -> Result<Vec<String>, AppError> {
     let rows =
@@ -305,7 +482,7 @@ pub async fn list_space_ids_for_user(
 }
 
 pub async fn list_member_ids_for_space(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
 ) -> Result<Vec<String>, AppError> {
     let rows =
@@ -316,7 +493,28 @@ pub async fn list_member_ids_for_space(
     Ok(rows.into_iter().map(|r| r.0).collect())
 }
 
-pub async fn get_space_by_slug(pool: &AnyPool, slug: &str) -> Result<SpaceRow, AppError> {
+/// Archive a space, or with `false` unarchive it.
+pub async fn set_archived(pool: &DbPool, space_id: &str, archived: bool) -> Result<(), AppError> {
+    let archived_at = archived.then(|| chrono::Utc::now().to_rfc3339());
+    sqlx::query(&super::q("UPDATE spaces SET archived_at = ? WHERE id = ?"))
+        .bind(archived_at)
+        .bind(space_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Whether a space is archived. Unknown spaces aren't.
+pub async fn is_archived(pool: &DbPool, space_id: &str) -> Result<bool, AppError> {
+    let archived_at: Option<Option<String>> =
+        sqlx::query_scalar(&super::q("SELECT archived_at FROM spaces WHERE id = ?"))
+            .bind(space_id)
+            .fetch_optional(pool)
+            .await?;
+    Ok(archived_at.flatten().is_some())
+}
+
+pub async fn get_space_by_slug(pool: &DbPool, slug: &str) -> Result<SpaceRow, AppError> {
     let row = sqlx::query(&super::q(&format!("{SELECT_SPACES} WHERE slug = ?")))
         .bind(slug)
         .fetch_optional(pool)
@@ -329,7 +527,7 @@ pub async fn get_space_by_slug(pool: &AnyPool, slug: &str) -> Result<SpaceRow, A
 /// Ensure a slug is unique in the database. If taken, appends `-2`, `-3`, etc.
 /// When `exclude_id` is `Some`, the space with that ID is ignored (for updates).
 async fn ensure_unique_slug(
-    pool: &AnyPool,
+    pool: &DbPool,
     base_slug: &str,
     exclude_id: Option<&str>,
 ) -> Result<String, AppError> {
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::sticker::{Sticker, UpdateSticker};
+
+const SELECT_STICKERS: &str = "SELECT id, space_id, name, description, tags, format, image_path, creator_id, created_at, updated_at FROM stickers";
+
+fn row_to_sticker(row: &sqlx::any::AnyRow) -> Sticker {
+    Sticker {
+        id: row.get("id"),
+        space_id: row.get("space_id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        tags: row.get("tags"),
+        format: row.get("format"),
+        image_url: row.get("image_path"),
+        creator_id: row.get("creator_id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+pub async fn get_sticker(pool: &DbPool, sticker_id: &str) -> Result<Sticker, AppError> {
+    let row = sqlx::query(&super::q(&format!("{SELECT_STICKERS} WHERE id = ?")))
+        .bind(sticker_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("unknown_sticker".to_string()))?;
+    Ok(row_to_sticker(&row))
+}
+
+/// A sticker of `space_id`; stickers of other spaces are not found.
+pub async fn get_sticker_in_space(
+    pool: &DbPool,
+    space_id: &str,
+    sticker_id: &str,
+) -> Result<Sticker, AppError> {
+    let sticker = get_sticker(pool, sticker_id).await?;
+    if sticker.space_id != space_id {
+        return Err(AppError::NotFound("unknown_sticker".to_string()));
+    }
+    Ok(sticker)
+}
+
+pub async fn list_stickers(pool: &DbPool, space_id: &str) -> Result<Vec<Sticker>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_STICKERS} WHERE space_id = ? ORDER BY created_at ASC, id ASC"
+    )))
+    .bind(space_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(row_to_sticker).collect())
+}
+
+pub async fn count_stickers(pool: &DbPool, space_id: &str) -> Result<i64, AppError> {
+    let count: i64 = sqlx::query_scalar(&super::q(
+        "SELECT COUNT(*) FROM stickers WHERE space_id = ?",
+    ))
+    .bind(space_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(count)
+}
+
+/// Of `sticker_ids`, the ones that belong to `space_id`.
+pub async fn filter_space_sticker_ids(
+    pool: &DbPool,
+    space_id: &str,
+    sticker_ids: &[String],
+) -> Result<Vec<String>, AppError> {
+    if sticker_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let in_clause = vec!["?"; sticker_ids.len()].join(", ");
+    let sql = super::q(&format!(
+        "SELECT id FROM stickers WHERE space_id = ? AND id IN ({in_clause})"
+    ));
+    let mut query = sqlx::query_as::<_, (String,)>(&sql).bind(space_id);
+    for id in sticker_ids {
+        query = query.bind(id);
+    }
+    Ok(query
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|(id,)| id)
+        .collect())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn create_sticker(
+    pool: &DbPool,
+    id: &str,
+    space_id: &str,
+    creator_id: &str,
+    name: &str,
+    description: Option<&str>,
+    tags: &str,
+    format: &str,
+    image_path: &str,
+    image_content_type: &str,
+    image_size: usize,
+) -> Result<Sticker, AppError> {
+    sqlx::query(&super::q(
+        "INSERT INTO stickers (id, space_id, name, description, tags, format, image_path, image_content_type, image_size, creator_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(id)
+    .bind(space_id)
+    .bind(name)
+    .bind(description)
+    .bind(tags)
+    .bind(format)
+    .bind(image_path)
+    .bind(image_content_type)
+    .bind(image_size as i64)
+    .bind(creator_id)
+    .execute(pool)
+    .await?;
+    get_sticker(pool, id).await
+}
+
+pub async fn update_sticker(
+    pool: &DbPool,
+    sticker_id: &str,
+    input: &UpdateSticker,
+    is_postgres: bool,
+) -> Result<Sticker, AppError> {
+    let now_fn = crate::db::now_sql(is_postgres);
+    let mut sets = Vec::new();
+    let mut binds: Vec<Option<String>> = Vec::new();
+    if let Some(ref name) = input.name {
+        sets.push("name = ?");
+        binds.push(Some(name.trim().to_string()));
+    }
+    if let Some(ref description) = input.description {
+        sets.push("description = ?");
+        let description = description.trim();
+        binds.push((!description.is_empty()).then(|| description.to_string()));
+    }
+    if let Some(ref tags) = input.tags {
+        sets.push("tags = ?");
+        binds.push(Some(tags.trim().to_string()));
+    }
+    if !sets.is_empty() {
+        let sql = format!(
+            "UPDATE stickers SET {}, updated_at = {now_fn} WHERE id = ?",
+            sets.join(", ")
+        );
+        let sql = super::q(&sql);
+        let mut query = sqlx::query(&sql);
+        for value in binds {
+            query = query.bind(value);
+        }
+        query.bind(sticker_id).execute(pool).await?;
+    }
+    get_sticker(pool, sticker_id).await
+}
+
+/// Delete a sticker. Returns its image path for file cleanup.
+pub async fn delete_sticker(pool: &DbPool, sticker_id: &str) -> Result<String, AppError> {
+    let sticker = get_sticker(pool, sticker_id).await?;
+    sqlx::query(&super::q("DELETE FROM stickers WHERE id = ?"))
+        .bind(sticker_id)
+        .execute(pool)
+        .await?;
+    Ok(sticker.image_url)
+}
+
+/// Attach stickers to a new message, keeping their order.
+pub async fn add_message_stickers(
+    pool: &DbPool,
+    message_id: &str,
+    sticker_ids: &[String],
+) -> Result<(), AppError> {
+    for (position, sticker_id) in sticker_ids.iter().enumerate() {
+        sqlx::query(&super::q(
+            "INSERT INTO message_stickers (message_id, sticker_id, position) VALUES (?, ?, ?)",
+        ))
+        .bind(message_id)
+        .bind(sticker_id)
+        .bind(position as i64)
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// The stickers on each of `message_ids`, in the order they were sent.
+pub async fn get_stickers_for_messages(
+    pool: &DbPool,
+    message_ids: &[String],
+) -> Result<HashMap<String, Vec<Sticker>>, AppError> {
+    if message_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let in_clause = vec!["?"; message_ids.len()].join(", ");
+    let sql = super::q(&format!(
+        "SELECT ms.message_id, s.id, s.space_id, s.name, s.description, s.tags, s.format, \
+         s.image_path, s.creator_id, s.created_at, s.updated_at \
+         FROM message_stickers ms INNER JOIN stickers s ON s.id = ms.sticker_id \
+         WHERE ms.message_id IN ({in_clause}) ORDER BY ms.message_id, ms.position"
+    ));
+    let mut query = sqlx::query(&sql);
+    for id in message_ids {
+        query = query.bind(id);
+    }
+    let mut result: HashMap<String, Vec<Sticker>> = HashMap::new();
+    for row in query.fetch_all(pool).await? {
+        result
+            .entry(row.get("message_id"))
+            .or_default()
+            .push(row_to_sticker(&row));
+    }
+    Ok(result)
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::db::now_sql;
+use crate::error::AppError;
+use crate::models::forum::ForumSort;
+use crate::models::thread::{ThreadRow, UpdateThread};
+
+fn row_to_thread(row: sqlx::any::AnyRow) -> ThreadRow {
+    ThreadRow {
+        id: row.get("id"),
+        channel_id: row.get("channel_id"),
+        space_id: row.get("space_id"),
+        owner_id: row.get("owner_id"),
+        name: row.get("name"),
+        auto_archive_after: row.get("auto_archive_after"),
+        archived: crate::db::get_bool(&row, "archived"),
+        archived_at: row.get("archived_at"),
+        locked: crate::db::get_bool(&row, "locked"),
+        last_activity_at: row.get("last_activity_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+const SELECT_THREADS: &str = "SELECT id, channel_id, space_id, owner_id, name, auto_archive_after, archived, archived_at, locked, last_activity_at, created_at FROM threads";
+
+/// Create a thread anchored on `message_id` and add the owner as its first
+/// member. Fails with `Conflict` if the message already has a thread.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_thread(
+    pool: &DbPool,
+    message_id: &str,
+    channel_id: &str,
+    space_id: Option<&str>,
+    owner_id: &str,
+    name: &str,
+    auto_archive_after: i64,
+    is_postgres: bool,
+) -> Result<ThreadRow, AppError> {
+    if find_thread(pool, message_id).await?.is_some() {
+        return Err(AppError::Conflict("thread_already_exists".into()));
+    }
+    sqlx::query(&super::q(
+        "INSERT INTO threads (id, channel_id, space_id, owner_id, name, auto_archive_after) VALUES (?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(message_id)
+    .bind(channel_id)
+    .bind(space_id)
+    .bind(owner_id)
+    .bind(name)
+    .bind(auto_archive_after)
+    .execute(pool)
+    .await?;
+
+    add_thread_member(pool, message_id, owner_id, is_postgres).await?;
+    get_thread(pool, message_id).await
+}
+
+pub async fn get_thread(pool: &DbPool, thread_id: &str) -> Result<ThreadRow, AppError> {
+    let row = sqlx::query(&super::q(&format!("{SELECT_THREADS} WHERE id = ?")))
+        .bind(thread_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("unknown_thread".to_string()))?;
+    Ok(row_to_thread(row))
+}
+
+/// Like [get_thread] but returns `None` for legacy reply chains that have no
+/// thread object.
+pub async fn find_thread(pool: &DbPool, thread_id: &str) -> Result<Option<ThreadRow>, AppError> {
+    let row = sqlx::query(&super::q(&format!("{SELECT_THREADS} WHERE id = ?")))
+        .bind(thread_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(row_to_thread))
+}
+
+/// Threads in a channel, most recently active first.
+pub async fn list_threads(
+    pool: &DbPool,
+    channel_id: &str,
+    archived: bool,
+) -> Result<Vec<ThreadRow>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_THREADS} WHERE channel_id = ? AND archived = ? ORDER BY last_activity_at DESC, id DESC"
+    )))
+    .bind(channel_id)
+    .bind(archived)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(row_to_thread).collect())
+}
+
+/// A page of a forum's posts. `after` is the last post of the previous page;
+/// `tag_id` keeps only posts carrying that tag.
+pub async fn list_forum_threads(
+    pool: &DbPool,
+    channel_id: &str,
+    sort: ForumSort,
+    archived: bool,
+    tag_id: Option<&str>,
+    after: Option<&ThreadRow>,
+    limit: i64,
+) -> Result<Vec<ThreadRow>, AppError> {
+    let mut sql = format!("{} WHERE channel_id = ? AND archived = ?", SELECT_THREADS);
+    if tag_id.is_some() {
+        sql.push_str(" AND id IN (SELECT thread_id FROM thread_tags WHERE tag_id = ?)");
+    }
+    if after.is_some() {
+        sql.push_str(match sort {
+            ForumSort::LatestActivity => {
+                " AND (last_activity_at < ? OR (last_activity_at = ? AND id < ?))"
+            }
+            ForumSort::Creation => " AND id < ?",
+        });
+    }
+    sql.push_str(match sort {
+        ForumSort::LatestActivity => " ORDER BY last_activity_at DESC, id DESC LIMIT ?",
+        ForumSort::Creation => " ORDER BY id DESC LIMIT ?",
+    });
+
+    let sql = super::q(&sql);
+    let mut query = sqlx::query(&sql).bind(channel_id).bind(archived);
+    if let Some(tag_id) = tag_id {
+        query = query.bind(tag_id);
+    }
+    if let Some(after) = after {
+        if sort == ForumSort::LatestActivity {
+            query = query
+                .bind(&after.last_activity_at)
+                .bind(&after.last_activity_at);
+        }
+        query = query.bind(&after.id);
+    }
+    let rows = query.bind(limit).fetch_all(pool).await?;
+    Ok(rows.into_iter().map(row_to_thread).collect())
+}
+
+pub async fn update_thread(
+    pool: &DbPool,
+    thread_id: &str,
+    input: &UpdateThread,
+    is_postgres: bool,
+) -> Result<ThreadRow, AppError> {
+    let mut sets: Vec<String> = Vec::new();
+    if input.name.is_some() {
+        sets.push("name = ?".to_string());
+    }
+    if input.auto_archive_after.is_some() {
+        sets.push("auto_archive_after = ?".to_string());
+    }
+    if input.locked.is_some() {
+        sets.push("locked = ?".to_string());
+    }
+    if let Some(archived) = input.archived {
+        sets.push("archived = ?".to_string());
+        if archived {
+            sets.push(format!("archived_at = {}", now_sql(is_postgres)));
+        } else {
+            // Unarchiving restarts the inactivity clock.
+            sets.push("archived_at = NULL".to_string());
+            sets.push(format!("last_activity_at = {}", now_sql(is_postgres)));
+        }
+    }
+    if sets.is_empty() {
+        return get_thread(pool, thread_id).await;
+    }
+
+    let sql = super::q(&format!(
+        "UPDATE threads SET {} WHERE id = ?",
+        sets.join(", ")
+    ));
+    let mut query = sqlx::query(&sql);
+    if let Some(ref name) = input.name {
+        query = query.bind(name);
+    }
+    if let Some(after) = input.auto_archive_after {
+        query = query.bind(after);
+    }
+    if let Some(locked) = input.locked {
+        query = query.bind(locked);
+    }
+    if let Some(archived) = input.archived {
+        query = query.bind(archived);
+    }
+    query.bind(thread_id).execute(pool).await?;
+
+    get_thread(pool, thread_id).await
+}
+
+/// Record a reply: bumps `last_activity_at` and reopens an archived thread.
+pub async fn touch_thread(
+    pool: &DbPool,
+    thread_id: &str,
+    is_postgres: bool,
+) -> Result<(), AppError> {
+    let now = now_sql(is_postgres);
+    sqlx::query(&super::q(&format!(
+        "UPDATE threads SET last_activity_at = {now}, archived = ?, archived_at = NULL WHERE id = ?"
+    )))
+    .bind(false)
+    .bind(thread_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Delete a thread object along with its replies. The starter message is
+/// left in place.
+pub async fn delete_thread(pool: &DbPool, thread_id: &str) -> Result<(), AppError> {
+    sqlx::query(&super::q("DELETE FROM messages WHERE thread_id = ?"))
+        .bind(thread_id)
+        .execute(pool)
+        .await?;
+    sqlx::query(&super::q("DELETE FROM threads WHERE id = ?"))
+        .bind(thread_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// IDs of open threads with `auto_archive_after` of `minutes` whose last
+/// activity was before `cutoff` (`YYYY-MM-DD HH:MM:SS`, UTC).
+pub async fn list_stale_thread_ids(
+    pool: &DbPool,
+    minutes: i64,
+    cutoff: &str,
+) -> Result<Vec<String>, AppError> {
+    let rows: Vec<(String,)> = sqlx::query_as(&super::q(
+        "SELECT id FROM threads WHERE archived = ? AND auto_archive_after = ? AND last_activity_at < ?",
+    ))
+    .bind(false)
+    .bind(minutes)
+    .bind(cutoff)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+pub async fn add_thread_member(
+    pool: &DbPool,
+    thread_id: &str,
+    user_id: &str,
+    is_postgres: bool,
+) -> Result<(), AppError> {
+    let sql = if is_postgres {
+        "INSERT INTO thread_members (thread_id, user_id) VALUES (?, ?) ON CONFLICT DO NOTHING"
+    } else {
+        "INSERT OR IGNORE INTO thread_members (thread_id, user_id) VALUES (?, ?)"
+    };
+    sqlx::query(&super::q(sql))
+        .bind(thread_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Returns `false` when the user was not a member of the thread.
+pub async fn remove_thread_member(
+    pool: &DbPool,
+    thread_id: &str,
+    user_id: &str,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(&super::q(
+        "DELETE FROM thread_members WHERE thread_id = ? AND user_id = ?",
+    ))
+    .bind(thread_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// `(user_id, joined_at)` for every member of a thread, oldest first.
+pub async fn list_thread_members(
+    pool: &DbPool,
+    thread_id: &str,
+) -> Result<Vec<(String, String)>, AppError> {
+    let rows = sqlx::query_as::<_, (String, String)>(&super::q(
+        "SELECT user_id, joined_at FROM thread_members WHERE thread_id = ? ORDER BY joined_at ASC, user_id ASC",
+    ))
+    .bind(thread_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Member counts for each of the given threads in a single query. Threads
+/// with no members are absent from the map.
+pub async fn count_thread_members_by_thread(
+    pool: &DbPool,
+    thread_ids: &[String],
+) -> Result<HashMap<String, i64>, AppError> {
+    let mut result = HashMap::new();
+    if thread_ids.is_empty() {
+        return Ok(result);
+    }
+    let placeholders = vec!["?"; thread_ids.len()].join(", ");
+    let sql = super::q(&format!(
+        "SELECT thread_id, COUNT(*) AS cnt FROM thread_members \
+         WHERE thread_id IN ({placeholders}) GROUP BY thread_id"
+    ));
+    let mut query = sqlx::query(&sql);
+    for id in thread_ids {
+        query = query.bind(id);
+    }
+    for row in query.fetch_all(pool).await? {
+        result.insert(row.get("thread_id"), row.get::<i64, _>("cnt"));
+    }
+    Ok(result)
+}
+
+pub async fn count_thread_members(pool: &DbPool, thread_id: &str) -> Result<i64, AppError> {
+    let (count,): (i64,) = sqlx::query_as(&super::q(
+        "SELECT COUNT(*) FROM thread_members WHERE thread_id = ?",
+    ))
+    .bind(thread_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(count)
+}
@@ -0,0 +1,94 @@
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::models::user_settings::{SpaceFolder, UserSettings};
+
+/// Load a user's settings; users who never saved any get an empty object at
+/// version 0.
+pub async fn get_user_settings(pool: &DbPool, user_id: &str) -> Result<UserSettings, AppError> {
+    let row = sqlx::query_as::<_, (String, i64, String)>(&super::q(
+        "SELECT settings, version, updated_at FROM user_settings WHERE user_id = ?",
+    ))
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(match row {
+        Some((settings, version, updated_at)) => UserSettings {
+            settings: serde_json::from_str(&settings).unwrap_or_default(),
+            version,
+            updated_at: Some(updated_at),
+        },
+        None => UserSettings {
+            settings: serde_json::Map::new(),
+            version: 0,
+            updated_at: None,
+        },
+    })
+}
+
+/// Replace a user's settings if they are still at [expected_version]. Returns
+/// false (and writes nothing) when another write got there first.
+pub async fn replace_user_settings(
+    pool: &DbPool,
+    user_id: &str,
+    expected_version: i64,
+    settings: &serde_json::Map<String, serde_json::Value>,
+    is_postgres: bool,
+) -> Result<bool, AppError> {
+    let json = serde_json::Value::Object(settings.clone()).to_string();
+    if expected_version == 0 {
+        let result = sqlx::query(&super::q(
+            "INSERT INTO user_settings (user_id, settings, version) VALUES (?, ?, 1)
+             ON CONFLICT (user_id) DO NOTHING",
+        ))
+        .bind(user_id)
+        .bind(&json)
+        .execute(pool)
+        .await?;
+        return Ok(result.rows_affected() > 0);
+    }
+
+    let now_fn = crate::db::now_sql(is_postgres);
+    let result = sqlx::query(&super::q(&format!(
+        "UPDATE user_settings SET settings = ?, version = version + 1, updated_at = {now_fn}
+         WHERE user_id = ? AND version = ?"
+    )))
+    .bind(&json)
+    .bind(user_id)
+    .bind(expected_version)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// A user's sidebar entries, top to bottom (empty if never saved).
+pub async fn get_space_layout(pool: &DbPool, user_id: &str) -> Result<Vec<SpaceFolder>, AppError> {
+    let row = sqlx::query_as::<_, (String,)>(&super::q(
+        "SELECT entries FROM user_space_layouts WHERE user_id = ?",
+    ))
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row
+        .and_then(|(entries,)| serde_json::from_str(&entries).ok())
+        .unwrap_or_default())
+}
+
+pub async fn set_space_layout(
+    pool: &DbPool,
+    user_id: &str,
+    folders: &[SpaceFolder],
+    is_postgres: bool,
+) -> Result<(), AppError> {
+    let now_fn = crate::db::now_sql(is_postgres);
+    sqlx::query(&super::q(&format!(
+        "INSERT INTO user_space_layouts (user_id, entries) VALUES (?, ?)
+         ON CONFLICT (user_id) DO UPDATE SET entries = excluded.entries, updated_at = {now_fn}"
+    )))
+    .bind(user_id)
+    .bind(serde_json::to_string(folders).unwrap_or_else(|_| "[]".into()))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use sqlx::{AnyPool, Row};
+use sqlx::Row;
+
+use crate::db::DbPool;
 
 use crate::error::AppError;
 use crate::models::user::{CreateUser, UpdateUser, User};
@@ -13,10 +15,16 @@ fn row_to_user(row: sqlx::any::AnyRow) -> User {
         banner: row.get("banner"),
         accent_color: row.get("accent_color"),
         bio: row.get("bio"),
+        utc_offset_minutes: crate::profile::utc_offset_minutes(
+            row.get::<Option<String>, _>("timezone").as_deref(),
+        ),
+        pronouns: row.get("pronouns"),
+        timezone: row.get("timezone"),
         bot: crate::db::get_bool(&row, "bot"),
         system: crate::db::get_bool(&row, "system"),
         is_admin: crate::db::get_bool(&row, "is_admin"),
         mfa_enabled: crate::db::get_bool(&row, "totp_enabled"),
+        email_verified: crate::db::get_bool(&row, "email_verified"),
         disabled: crate::db::get_bool(&row, "disabled"),
         flags: row.get("flags"),
         public_flags: row.get("public_flags"),
@@ -25,9 +33,9 @@ fn row_to_user(row: sqlx::any::AnyRow) -> User {
     }
 }
 
-const SELECT_USERS: &str = "SELECT id, username, display_name, avatar, banner, accent_color, bio, bot, system, is_admin, totp_enabled, disabled, flags, public_flags, created_at, origin FROM users";
+const SELECT_USERS: &str = "SELECT id, username, display_name, avatar, banner, accent_color, bio, pronouns, timezone, bot, system, is_admin, totp_enabled, email_verified, disabled, flags, public_flags, created_at, origin FROM users";
 
-pub async fn get_user(pool: &AnyPool, user_id: &str) -> Result<User, AppError> {
+pub async fn get_user(pool: &DbPool, user_id: &str) -> Result<User, AppError> {
     let row = sqlx::query(&super::q(&format!("{SELECT_USERS} WHERE id = ?")))
         .bind(user_id)
         .fetch_optional(pool)
@@ -41,7 +49,7 @@ pub async fn get_user(pool: &AnyPool, user_id: &str) -> Result<User, AppError> {
 /// omitted, and the result order is unspecified — callers index by `id`. Used
 /// to embed member user objects in the member-list response without an N+1
 /// round-trip per member.
-pub async fn get_users_by_ids(pool: &AnyPool, ids: &[String]) -> Result<Vec<User>, AppError> {
+pub async fn get_users_by_ids(pool: &DbPool, ids: &[String]) -> Result<Vec<User>, AppError> {
     if ids.is_empty() {
         return Ok(Vec::new());
     }
@@ -56,7 +64,7 @@ pub async fn get_users_by_ids(pool: &AnyPool, ids: &[String]) -> Result<Vec<User
     Ok(rows.into_iter().map(row_to_user).collect())
 }
 
-pub async fn create_user(pool: &AnyPool, input: &CreateUser) -> Result<User, AppError> {
+pub async fn create_user(pool: &DbPool, input: &CreateUser) -> Result<User, AppError> {
     let id = snowflake::generate();
     let display_name = input.display_name.as_deref().unwrap_or(&input.username);
 
@@ -83,7 +91,7 @@ pub async fn create_user(pool: &AnyPool, input: &CreateUser) -> Result<User, App
 /// mutable fields.
 #[allow(clippy::too_many_arguments)]
 pub async fn upsert_remote_user(
-    pool: &AnyPool,
+    pool: &DbPool,
     id: &str,
     origin: &str,
     handle: &str,
@@ -117,7 +125,7 @@ pub async fn upsert_remote_user(
 /// home may refresh its profile.
 #[allow(clippy::too_many_arguments)]
 pub async fn ensure_remote_user(
-    pool: &AnyPool,
+    pool: &DbPool,
     id: &str,
     origin: &str,
     handle: &str,
@@ -141,7 +149,7 @@ pub async fn ensure_remote_user(
 /// Returns the id of the singleton System user, creating it if absent.
 /// Used as the author/actor id for server-originated writes (MCP, automated
 /// moderation) so they satisfy the `users(id)` foreign keys.
-pub async fn get_or_create_system_user(pool: &AnyPool) -> Result<String, AppError> {
+pub async fn get_or_create_system_user(pool: &DbPool) -> Result<String, AppError> {
     if let Some(row) = sqlx::query(&super::q(
         "SELECT id FROM users WHERE system = TRUE LIMIT 1",
     ))
@@ -169,7 +177,7 @@ pub async fn get_or_create_system_user(pool: &AnyPool) -> Result<String, AppErro
 }
 
 pub async fn update_user(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
     input: &UpdateUser,
     is_postgres: bool,
@@ -206,6 +214,22 @@ pub async fn update_user(
         sets.push("bio = ?");
         values.push(bio.clone());
     }
+    if let Some(ref pronouns) = input.pronouns {
+        if pronouns.is_empty() {
+            sets.push("pronouns = NULL");
+        } else {
+            sets.push("pronouns = ?");
+            values.push(pronouns.clone());
+        }
+    }
+    if let Some(ref timezone) = input.timezone {
+        if timezone.is_empty() {
+            sets.push("timezone = NULL");
+        } else {
+            sets.push("timezone = ?");
+            values.push(timezone.clone());
+        }
+    }
 
     if sets.is_empty() && input.accent_color.is_none() {
         return get_user(pool, user_id).await;
@@ -252,13 +276,13 @@ pub async fn update_user(
 }
 
 pub async fn get_user_dm_channels(
-    pool: &AnyPool,
+    pool: &DbPool,
     user_id: &str,
 ) -> Result<Vec<crate::models::channel::ChannelRow>, AppError> {
     let rows = sqlx::query(&super::q(
         "SELECT id, type, space_id, name, description, topic, position, parent_id, \
          nsfw, rate_limit, bitrate, user_limit, owner_id, last_message_id, \
-         archived, auto_archive_after, created_at \
+         archived, auto_archive_after, icon, created_at \
          FROM channels WHERE id IN \
          (SELECT channel_id FROM dm_participants WHERE user_id = ?) \
          ORDER BY last_message_id DESC",
@@ -287,12 +311,18 @@ pub async fn get_user_dm_channels(
             archived: crate::db::get_bool(&row, "archived"),
             auto_archive_after: row.get("auto_archive_after"),
             allow_anonymous_read: false,
+            announcement_locked: false,
+            voice_chat_retention: "keep".to_string(),
+            icon: row.get("icon"),
+            auto_thread: false,
+            auto_thread_name: None,
+            max_pins: None,
             created_at: row.get("created_at"),
         })
         .collect())
 }
 
-pub async fn get_user_spaces(pool: &AnyPool, user_id: &str) -> Result<Vec<String>, AppError> {
+pub async fn get_user_spaces(pool: &DbPool, user_id: &str) -> Result<Vec<String>, AppError> {
     let rows =
         sqlx::query_as::<_, (String,)>(&super::q("SELECT space_id FROM members WHERE user_id = ?"))
             .bind(user_id)
@@ -0,0 +1,222 @@
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::middleware::auth::create_token_hash;
+use crate::models::message::{CreateMessage, MessageRow};
+use crate::models::webhook::{UpdateWebhook, WebhookRow};
+use crate::snowflake;
+
+fn row_to_webhook(row: sqlx::any::AnyRow) -> WebhookRow {
+    WebhookRow {
+        id: row.get("id"),
+        channel_id: row.get("channel_id"),
+        space_id: row.get("space_id"),
+        creator_id: row.get("creator_id"),
+        name: row.get("name"),
+        avatar: row.get("avatar"),
+        source_channel_id: row.get("source_channel_id"),
+        created_at: row.get("created_at"),
+    }
+}
+
+const SELECT_WEBHOOKS: &str = "SELECT id, channel_id, space_id, creator_id, name, avatar, source_channel_id, created_at FROM webhooks";
+
+/// Create a webhook. Only the hash of [token] is stored.
+pub async fn create_webhook(
+    pool: &DbPool,
+    channel_id: &str,
+    space_id: &str,
+    creator_id: &str,
+    name: &str,
+    avatar: Option<&str>,
+    token: &str,
+) -> Result<WebhookRow, AppError> {
+    let id = snowflake::generate();
+    sqlx::query(&super::q(
+        "INSERT INTO webhooks (id, channel_id, space_id, creator_id, name, avatar, token_hash) VALUES (?, ?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(channel_id)
+    .bind(space_id)
+    .bind(creator_id)
+    .bind(name)
+    .bind(avatar)
+    .bind(create_token_hash(token))
+    .execute(pool)
+    .await?;
+    get_webhook(pool, &id).await
+}
+
+/// Create the webhook that delivers [source_channel_id]'s crossposts into
+/// [channel_id]. Its token is never handed out.
+pub async fn create_follower_webhook(
+    pool: &DbPool,
+    channel_id: &str,
+    space_id: &str,
+    creator_id: &str,
+    name: &str,
+    source_channel_id: &str,
+) -> Result<WebhookRow, AppError> {
+    let id = snowflake::generate();
+    sqlx::query(&super::q(
+        "INSERT INTO webhooks (id, channel_id, space_id, creator_id, name, token_hash, source_channel_id) VALUES (?, ?, ?, ?, ?, ?, ?)",
+    ))
+    .bind(&id)
+    .bind(channel_id)
+    .bind(space_id)
+    .bind(creator_id)
+    .bind(name)
+    .bind(create_token_hash(&crate::middleware::auth::generate_token()))
+    .bind(source_channel_id)
+    .execute(pool)
+    .await?;
+    get_webhook(pool, &id).await
+}
+
+/// Webhooks following [source_channel_id].
+pub async fn list_followers(
+    pool: &DbPool,
+    source_channel_id: &str,
+) -> Result<Vec<WebhookRow>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_WEBHOOKS} WHERE source_channel_id = ? ORDER BY id ASC"
+    )))
+    .bind(source_channel_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(row_to_webhook).collect())
+}
+
+pub async fn get_webhook(pool: &DbPool, webhook_id: &str) -> Result<WebhookRow, AppError> {
+    let row = sqlx::query(&super::q(&format!("{SELECT_WEBHOOKS} WHERE id = ?")))
+        .bind(webhook_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("unknown_webhook".to_string()))?;
+    Ok(row_to_webhook(row))
+}
+
+/// Look up a webhook by ID and token. A wrong token is reported the same as
+/// an unknown webhook.
+pub async fn get_webhook_with_token(
+    pool: &DbPool,
+    webhook_id: &str,
+    token: &str,
+) -> Result<WebhookRow, AppError> {
+    let row = sqlx::query(&super::q(&format!(
+        "{SELECT_WEBHOOKS} WHERE id = ? AND token_hash = ?"
+    )))
+    .bind(webhook_id)
+    .bind(create_token_hash(token))
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("unknown_webhook".to_string()))?;
+    Ok(row_to_webhook(row))
+}
+
+pub async fn list_channel_webhooks(
+    pool: &DbPool,
+    channel_id: &str,
+) -> Result<Vec<WebhookRow>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_WEBHOOKS} WHERE channel_id = ? ORDER BY id ASC"
+    )))
+    .bind(channel_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(row_to_webhook).collect())
+}
+
+pub async fn list_space_webhooks(
+    pool: &DbPool,
+    space_id: &str,
+) -> Result<Vec<WebhookRow>, AppError> {
+    let rows = sqlx::query(&super::q(&format!(
+        "{SELECT_WEBHOOKS} WHERE space_id = ? ORDER BY id ASC"
+    )))
+    .bind(space_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(row_to_webhook).collect())
+}
+
+pub async fn update_webhook(
+    pool: &DbPool,
+    webhook_id: &str,
+    input: &UpdateWebhook,
+) -> Result<WebhookRow, AppError> {
+    let mut sets: Vec<&str> = Vec::new();
+    if input.name.is_some() {
+        sets.push("name = ?");
+    }
+    if input.avatar.is_some() {
+        sets.push("avatar = ?");
+    }
+    if input.channel_id.is_some() {
+        sets.push("channel_id = ?");
+    }
+    if sets.is_empty() {
+        return get_webhook(pool, webhook_id).await;
+    }
+
+    let sql = super::q(&format!(
+        "UPDATE webhooks SET {} WHERE id = ?",
+        sets.join(", ")
+    ));
+    let mut query = sqlx::query(&sql);
+    if let Some(ref name) = input.name {
+        query = query.bind(name);
+    }
+    if let Some(ref avatar) = input.avatar {
+        query = query.bind(if avatar.is_empty() {
+            None
+        } else {
+            Some(avatar.as_str())
+        });
+    }
+    if let Some(ref channel_id) = input.channel_id {
+        query = query.bind(channel_id);
+    }
+    query.bind(webhook_id).execute(pool).await?;
+
+    get_webhook(pool, webhook_id).await
+}
+
+pub async fn delete_webhook(pool: &DbPool, webhook_id: &str) -> Result<(), AppError> {
+    sqlx::query(&super::q("DELETE FROM webhooks WHERE id = ?"))
+        .bind(webhook_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Post a message through [webhook], attributed to its creator and stamped
+/// with the display name and avatar it was sent under.
+pub async fn create_webhook_message(
+    pool: &DbPool,
+    webhook: &WebhookRow,
+    username: &str,
+    avatar: Option<&str>,
+    input: &CreateMessage,
+) -> Result<MessageRow, AppError> {
+    let msg = super::messages::create_message(
+        pool,
+        &webhook.channel_id,
+        &webhook.creator_id,
+        Some(&webhook.space_id),
+        input,
+    )
+    .await?;
+    sqlx::query(&super::q(
+        "UPDATE messages SET webhook_id = ?, webhook_username = ?, webhook_avatar = ? WHERE id = ?",
+    ))
+    .bind(&webhook.id)
+    .bind(username)
+    .bind(avatar)
+    .bind(&msg.id)
+    .execute(pool)
+    .await?;
+    super::messages::get_message_row(pool, &msg.id).await
+}
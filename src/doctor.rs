@@ -0,0 +1,230 @@
+//! Consistency checks (`accord-doctor`). Each check looks for one kind of
+//! record left pointing at something that no longer exists — rows that
+//! foreign keys should have cleaned up but didn't (databases from before
+//! `foreign_keys` was enabled, hand edits, interrupted imports), voice
+//! states outliving their channel, and attachments whose file is gone — and
+//! can optionally delete what it finds.
+//!
+//! Used by `GET /admin/doctor`, `POST /admin/doctor/repair` and the
+//! `accord-doctor` binary. Voice states only exist in a running server, so
+//! the binary skips that check.
+
+use serde::Serialize;
+use sqlx::Row;
+
+use crate::db::DbPool;
+
+use crate::error::AppError;
+use crate::state::AppState;
+use crate::storage::Storage;
+
+/// How many offending records each check lists in its report.
+pub const MAX_EXAMPLES: usize = 10;
+
+/// Attachments are checked against storage in pages this size.
+const ATTACHMENT_PAGE: i64 = 500;
+
+/// What one check found, and what it fixed.
+#[derive(Debug, Serialize)]
+pub struct CheckReport {
+    pub check: &'static str,
+    pub description: &'static str,
+    pub found: u64,
+    pub repaired: u64,
+    /// Up to [MAX_EXAMPLES] of the records found.
+    pub examples: Vec<String>,
+    /// Set when the check couldn't run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<String>,
+}
+
+impl CheckReport {
+    fn new(check: &'static str, description: &'static str) -> Self {
+        Self {
+            check,
+            description,
+            found: 0,
+            repaired: 0,
+            examples: Vec::new(),
+            skipped: None,
+        }
+    }
+
+    fn note(&mut self, example: String) {
+        self.found += 1;
+        if self.examples.len() < MAX_EXAMPLES {
+            self.examples.push(example);
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DoctorReport {
+    pub repair: bool,
+    pub found: u64,
+    pub repaired: u64,
+    pub checks: Vec<CheckReport>,
+}
+
+/// Run every check. With [repair], what's found is deleted. [live] is the
+/// running server, for the checks on in-memory state.
+pub async fn run(
+    pool: &DbPool,
+    storage: &dyn Storage,
+    live: Option<&AppState>,
+    repair: bool,
+) -> Result<DoctorReport, AppError> {
+    let checks = vec![
+        orphaned_members(pool, repair).await?,
+        stale_voice_states(pool, live, repair).await?,
+        dangling_role_overwrites(pool, repair).await?,
+        missing_attachment_files(pool, storage, repair).await?,
+    ];
+    Ok(DoctorReport {
+        repair,
+        found: checks.iter().map(|c| c.found).sum(),
+        repaired: checks.iter().map(|c| c.repaired).sum(),
+        checks,
+    })
+}
+
+async fn orphaned_members(pool: &DbPool, repair: bool) -> Result<CheckReport, AppError> {
+    let mut report = CheckReport::new("orphaned_members", "members of spaces that no longer exist");
+    let rows = sqlx::query(
+        "SELECT user_id, space_id FROM members WHERE space_id NOT IN (SELECT id FROM spaces)",
+    )
+    .fetch_all(pool)
+    .await?;
+    for row in &rows {
+        let user_id: String = row.get("user_id");
+        let space_id: String = row.get("space_id");
+        report.note(format!("user {user_id} in space {space_id}"));
+    }
+    if repair && report.found > 0 {
+        sqlx::query("DELETE FROM member_roles WHERE space_id NOT IN (SELECT id FROM spaces)")
+            .execute(pool)
+            .await?;
+        report.repaired =
+            sqlx::query("DELETE FROM members WHERE space_id NOT IN (SELECT id FROM spaces)")
+                .execute(pool)
+                .await?
+                .rows_affected();
+    }
+    Ok(report)
+}
+
+async fn stale_voice_states(
+    pool: &DbPool,
+    live: Option<&AppState>,
+    repair: bool,
+) -> Result<CheckReport, AppError> {
+    let mut report = CheckReport::new(
+        "stale_voice_states",
+        "voice states in channels that no longer exist",
+    );
+    let Some(state) = live else {
+        report.skipped = Some("voice states are only held by a running server".into());
+        return Ok(report);
+    };
+    let connected: Vec<(String, String)> = state
+        .voice_states
+        .iter()
+        .filter_map(|vs| Some((vs.user_id.clone(), vs.channel_id.clone()?)))
+        .collect();
+    for (user_id, channel_id) in connected {
+        let exists = sqlx::query(&crate::db::q("SELECT id FROM channels WHERE id = ?"))
+            .bind(&channel_id)
+            .fetch_optional(pool)
+            .await?
+            .is_some();
+        if exists {
+            continue;
+        }
+        report.note(format!("user {user_id} in channel {channel_id}"));
+        if repair && crate::voice::state::leave_voice_channel(state, &user_id).is_some() {
+            report.repaired += 1;
+        }
+    }
+    Ok(report)
+}
+
+async fn dangling_role_overwrites(pool: &DbPool, repair: bool) -> Result<CheckReport, AppError> {
+    let mut report = CheckReport::new(
+        "dangling_role_overwrites",
+        "channel permission overwrites for roles that no longer exist",
+    );
+    let rows = sqlx::query(
+        "SELECT id, channel_id FROM permission_overwrites
+         WHERE type = 'role' AND id NOT IN (SELECT id FROM roles)",
+    )
+    .fetch_all(pool)
+    .await?;
+    for row in &rows {
+        let role_id: String = row.get("id");
+        let channel_id: String = row.get("channel_id");
+        report.note(format!("role {role_id} on channel {channel_id}"));
+    }
+    if repair && report.found > 0 {
+        report.repaired = sqlx::query(
+            "DELETE FROM permission_overwrites
+             WHERE type = 'role' AND id NOT IN (SELECT id FROM roles)",
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+    }
+    Ok(report)
+}
+
+async fn missing_attachment_files(
+    pool: &DbPool,
+    storage: &dyn Storage,
+    repair: bool,
+) -> Result<CheckReport, AppError> {
+    let mut report = CheckReport::new(
+        "missing_attachment_files",
+        "attachments whose file is gone from storage",
+    );
+    let mut missing: Vec<String> = Vec::new();
+    let mut after = String::new();
+    loop {
+        let rows = sqlx::query(&crate::db::q(
+            "SELECT id, message_id, url FROM attachments WHERE id > ? ORDER BY id LIMIT ?",
+        ))
+        .bind(&after)
+        .bind(ATTACHMENT_PAGE)
+        .fetch_all(pool)
+        .await?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        after = last.get("id");
+        for row in &rows {
+            let url: String = row.get("url");
+            // Attachments hosted elsewhere (federated copies) aren't ours to check.
+            let Some(key) = url.strip_prefix("/cdn/") else {
+                continue;
+            };
+            if storage.exists(key).await? {
+                continue;
+            }
+            let id: String = row.get("id");
+            let message_id: String = row.get("message_id");
+            report.note(format!("attachment {id} on message {message_id} ({url})"));
+            missing.push(id);
+        }
+        if (rows.len() as i64) < ATTACHMENT_PAGE {
+            break;
+        }
+    }
+    if repair {
+        for id in &missing {
+            report.repaired += sqlx::query(&crate::db::q("DELETE FROM attachments WHERE id = ?"))
+                .bind(id)
+                .execute(pool)
+                .await?
+                .rows_affected();
+        }
+    }
+    Ok(report)
+}
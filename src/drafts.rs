@@ -0,0 +1,77 @@
+//! Server-side message drafts: one per user per channel, synced to the
+//! user's other sessions and dropped after [DRAFT_TTL] without an edit.
+
+use std::time::Duration;
+
+use crate::db;
+use crate::gateway::events::GatewayBroadcast;
+use crate::state::AppState;
+
+/// Longest draft, matching the message content limit.
+pub const MAX_DRAFT_LENGTH: usize = 4000;
+/// Most drafts a user may keep at once.
+pub const MAX_DRAFTS_PER_USER: i64 = 500;
+/// Drafts not edited for this long expire.
+pub const DRAFT_TTL: chrono::Duration = chrono::Duration::days(30);
+/// How often expired drafts are swept.
+pub const DRAFT_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Oldest `updated_at` a draft may have and still be served.
+pub fn expiry_cutoff() -> String {
+    (chrono::Utc::now() - DRAFT_TTL)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Sweep forever, deleting expired drafts.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(DRAFT_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        match db::drafts::delete_expired_drafts(&state.db, &expiry_cutoff()).await {
+            Ok(0) => {}
+            Ok(n) => tracing::debug!("swept {n} expired drafts"),
+            Err(e) => tracing::warn!("draft sweep failed: {e:?}"),
+        }
+    }
+}
+
+/// Tell the user's sessions that a draft changed (`draft` is `None` when it
+/// was cleared).
+pub async fn broadcast_draft(
+    state: &AppState,
+    user_id: &str,
+    channel_id: &str,
+    draft: Option<&crate::models::draft::Draft>,
+) {
+    if let Some(ref gtx) = *state.gateway_tx.read().await {
+        let event = match draft {
+            Some(draft) => serde_json::json!({
+                "op": 0,
+                "type": "draft.update",
+                "data": draft
+            }),
+            None => serde_json::json!({
+                "op": 0,
+                "type": "draft.delete",
+                "data": { "channel_id": channel_id }
+            }),
+        };
+        let _ = gtx.send(GatewayBroadcast {
+            space_id: None,
+            target_user_ids: Some(vec![user_id.to_string()]),
+            event,
+            intent: "messages".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
+
+/// Clear the author's draft once the message is sent.
+pub async fn clear_on_send(state: &AppState, user_id: &str, channel_id: &str) {
+    match db::drafts::delete_draft(&state.db, user_id, channel_id).await {
+        Ok(true) => broadcast_draft(state, user_id, channel_id, None).await,
+        Ok(false) => {}
+        Err(e) => tracing::warn!("failed to clear draft in {channel_id}: {e:?}"),
+    }
+}
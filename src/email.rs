@@ -0,0 +1,116 @@
+//! Outgoing mail: password reset codes and email verification. Only
+//! available when SMTP is configured (`SMTP_HOST`); mail is sent in the
+//! background so response times don't reveal whether an address is known.
+
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::{SmtpConfig, SmtpTls};
+use crate::error::AppError;
+
+/// How long a password reset code can be used for.
+pub const PASSWORD_RESET_TTL_SECS: i64 = 3600;
+
+/// How long an email verification code can be used for.
+pub const EMAIL_VERIFY_TTL_SECS: i64 = 24 * 3600;
+
+/// Minimum gap between two codes of the same kind for one user.
+pub const RESEND_INTERVAL_SECS: i64 = 60;
+
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Mailer {
+    pub fn new(config: &SmtpConfig) -> Result<Self, String> {
+        let from = config
+            .from
+            .parse::<Mailbox>()
+            .map_err(|e| format!("invalid SMTP_FROM: {e}"))?;
+        let builder = match config.tls {
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+                .map_err(|e| e.to_string())?,
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                .map_err(|e| e.to_string())?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        };
+        let mut builder = builder.port(config.port);
+        if let Some(ref username) = config.username {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                config.password.clone().unwrap_or_default(),
+            ));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> Result<(), AppError> {
+        let to = to
+            .parse::<Mailbox>()
+            .map_err(|_| AppError::BadRequest("invalid email address".into()))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject)
+            .body(body)
+            .map_err(|e| AppError::Internal(format!("failed to build email: {e}")))?;
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| AppError::Internal(format!("failed to send email: {e}")))?;
+        Ok(())
+    }
+}
+
+/// Send without waiting, logging failures.
+pub fn send_in_background(
+    mailer: std::sync::Arc<Mailer>,
+    to: String,
+    subject: &'static str,
+    body: String,
+) {
+    tokio::spawn(async move {
+        if let Err(e) = mailer.send(&to, subject, body).await {
+            tracing::warn!("failed to send \"{subject}\" email: {e:?}");
+        }
+    });
+}
+
+pub fn password_reset_body(username: &str, token: &str) -> String {
+    format!(
+        "Hi {username},\n\n\
+         Someone asked to reset the password for your Accord account. If it was \
+         you, enter this code to choose a new password:\n\n{token}\n\n\
+         The code expires in {} minutes. If you didn't ask for this, you can \
+         ignore this email.\n",
+        PASSWORD_RESET_TTL_SECS / 60
+    )
+}
+
+pub fn verification_body(username: &str, token: &str) -> String {
+    format!(
+        "Hi {username},\n\n\
+         Enter this code to verify the email address on your Accord account:\n\n\
+         {token}\n\n\
+         The code expires in {} hours.\n",
+        EMAIL_VERIFY_TTL_SECS / 3600
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bodies_carry_the_code_and_its_lifetime() {
+        let body = password_reset_body("alice", "abc123");
+        assert!(body.contains("abc123"));
+        assert!(body.contains("60 minutes"));
+        assert!(verification_body("alice", "def456").contains("24 hours"));
+    }
+}
@@ -0,0 +1,98 @@
+//! Spaces that only admit users with a verified email on certain domains,
+//! e.g. a company space limited to `company.com`.
+//!
+//! The domains live in `space_email_domains` (see
+//! `migrations/077_space_email_domains.sql`). [check_join] runs before invite
+//! accepts and public joins, next to the raid protection check.
+
+use crate::db;
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+
+pub const MAX_DOMAINS: usize = 10;
+
+/// Lowercase `domain` and drop a leading `@`, refusing anything that isn't a
+/// plausible hostname.
+pub fn normalize_domain(domain: &str) -> Result<String, AppError> {
+    let domain = domain
+        .trim()
+        .trim_start_matches('@')
+        .trim_end_matches('.')
+        .to_ascii_lowercase();
+    let valid = domain.len() <= 253
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if !valid {
+        return Err(AppError::BadRequest(format!(
+            "{domain:?} is not a valid domain"
+        )));
+    }
+    Ok(domain)
+}
+
+/// Fail with 403 unless `auth` may join a space that restricts its members'
+/// email domains: `email_verification_required` without a verified email,
+/// `email_domain_not_allowed` when it's on another domain. Bots are exempt.
+pub async fn check_join(
+    pool: &crate::db::DbPool,
+    space_id: &str,
+    auth: &AuthUser,
+) -> Result<(), AppError> {
+    if auth.is_bot {
+        return Ok(());
+    }
+    let domains = db::email_domains::list_domains(pool, space_id).await?;
+    if domains.is_empty() {
+        return Ok(());
+    }
+    let Some(email) = db::email_domains::verified_email(pool, &auth.user_id).await? else {
+        return Err(AppError::ForbiddenCode(
+            "email_verification_required",
+            format!(
+                "this space only admits members with a verified email address on {}",
+                domains.join(", ")
+            ),
+        ));
+    };
+    let candidates = crate::blocklist::email_domain_candidates(&email);
+    if !domains.iter().any(|d| candidates.contains(d)) {
+        return Err(AppError::ForbiddenCode(
+            "email_domain_not_allowed",
+            format!(
+                "this space only admits members with an email address on {}",
+                domains.join(", ")
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn domains_are_normalized() {
+        assert_eq!(normalize_domain(" @Company.COM. ").unwrap(), "company.com");
+        assert_eq!(
+            normalize_domain("eu.company.com").unwrap(),
+            "eu.company.com"
+        );
+        for bad in [
+            "",
+            "localhost",
+            "company..com",
+            "-company.com",
+            "comp any.com",
+            "a@b.com",
+        ] {
+            assert!(normalize_domain(bad).is_err(), "{bad} should be rejected");
+        }
+    }
+}
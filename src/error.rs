@@ -11,12 +11,45 @@ pub enum AppError {
     NotFound(String),
     Unauthorized(String),
     Forbidden(String),
+    /// A 403 with a specific machine-readable code (e.g. `email_domain_blocked`)
+    /// so clients can tell the user exactly why they were refused.
+    ForbiddenCode(&'static str, String),
     Conflict(String),
+    /// A 409 with a specific machine-readable code, for conflicts other than
+    /// a duplicate resource (e.g. `settings_version_conflict`).
+    ConflictCode(&'static str, String),
+    /// A 410 for something that existed but no longer can be used (e.g.
+    /// `invite_expired`).
+    GoneCode(&'static str, String),
     PayloadTooLarge(String),
-    RateLimited { retry_after: u64 },
+    /// A 400 naming every invalid part of a request by its path (e.g.
+    /// `embeds[0].title`) alongside what's wrong with it.
+    InvalidFields(Vec<(String, String)>),
+    /// A 400 for a space that has used all of its slots for something (e.g.
+    /// `emoji_limit_reached`), with how many it has and may have.
+    LimitReached {
+        code: &'static str,
+        current: i64,
+        max: i64,
+    },
+    RateLimited {
+        retry_after: u64,
+    },
+    GlobalRateLimited {
+        retry_after_ms: u64,
+    },
 }
 
 impl AppError {
+    /// Err with [AppError::LimitReached] when `current` has reached `max`.
+    /// A `max` of 0 means unlimited.
+    pub fn check_limit(code: &'static str, current: i64, max: i64) -> Result<(), AppError> {
+        if max > 0 && current >= max {
+            return Err(AppError::LimitReached { code, current, max });
+        }
+        Ok(())
+    }
+
     fn code(&self) -> &'static str {
         match self {
             AppError::Database(_) => "internal_error",
@@ -25,9 +58,15 @@ impl AppError {
             AppError::NotFound(_) => "not_found",
             AppError::Unauthorized(_) => "unauthorized",
             AppError::Forbidden(_) => "forbidden",
+            AppError::ForbiddenCode(code, _) => code,
             AppError::Conflict(_) => "already_exists",
+            AppError::ConflictCode(code, _) => code,
+            AppError::GoneCode(code, _) => code,
             AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::InvalidFields(_) => "invalid_fields",
+            AppError::LimitReached { code, .. } => code,
             AppError::RateLimited { .. } => "rate_limited",
+            AppError::GlobalRateLimited { .. } => "global_rate_limited",
         }
     }
 
@@ -35,13 +74,17 @@ impl AppError {
         match self {
             AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::BadRequest(_)
+            | AppError::InvalidFields(_)
+            | AppError::LimitReached { .. } => StatusCode::BAD_REQUEST,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
             AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
-            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
-            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Forbidden(_) | AppError::ForbiddenCode(..) => StatusCode::FORBIDDEN,
+            AppError::Conflict(_) | AppError::ConflictCode(..) => StatusCode::CONFLICT,
+            AppError::GoneCode(..) => StatusCode::GONE,
             AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
             AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::GlobalRateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
         }
     }
 
@@ -59,11 +102,24 @@ impl AppError {
             AppError::NotFound(msg) => msg.clone(),
             AppError::Unauthorized(msg) => msg.clone(),
             AppError::Forbidden(msg) => msg.clone(),
+            AppError::ForbiddenCode(_, msg) => msg.clone(),
             AppError::Conflict(msg) => msg.clone(),
+            AppError::ConflictCode(_, msg) => msg.clone(),
+            AppError::GoneCode(_, msg) => msg.clone(),
             AppError::PayloadTooLarge(msg) => msg.clone(),
+            AppError::InvalidFields(errors) => match errors.as_slice() {
+                [(field, msg)] => format!("{field} {msg}"),
+                _ => format!("{} invalid fields", errors.len()),
+            },
+            AppError::LimitReached { current, max, .. } => {
+                format!("limit reached: {current} of {max} slots used")
+            }
             AppError::RateLimited { retry_after } => {
                 format!("rate limited, retry after {retry_after}s")
             }
+            AppError::GlobalRateLimited { retry_after_ms } => {
+                format!("global rate limit exceeded, retry after {retry_after_ms}ms")
+            }
         }
     }
 }
@@ -71,18 +127,51 @@ impl AppError {
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let status = self.status();
-        let body = json!({
+        let mut body = json!({
             "error": {
                 "code": self.code(),
                 "message": self.message()
             }
         });
+        if let AppError::GlobalRateLimited { retry_after_ms } = &self {
+            body["error"]["global"] = json!(true);
+            body["error"]["retry_after"] = json!(*retry_after_ms as f64 / 1000.0);
+        }
+        if let AppError::LimitReached { current, max, .. } = &self {
+            body["error"]["current"] = json!(current);
+            body["error"]["max"] = json!(max);
+        }
+        if let AppError::InvalidFields(errors) = &self {
+            body["error"]["errors"] = errors
+                .iter()
+                .map(|(field, message)| json!({ "field": field, "message": message }))
+                .collect();
+        }
 
         let mut response = (status, Json(body)).into_response();
-        if let AppError::RateLimited { retry_after } = &self {
-            response
-                .headers_mut()
-                .insert("Retry-After", retry_after.to_string().parse().unwrap());
+        // The global bot quota gets distinct headers so bot libraries pause
+        // every route rather than just the one that tripped.
+        match &self {
+            AppError::RateLimited { retry_after } => {
+                response
+                    .headers_mut()
+                    .insert("Retry-After", retry_after.to_string().parse().unwrap());
+            }
+            AppError::GlobalRateLimited { retry_after_ms } => {
+                let headers = response.headers_mut();
+                // Retry-After is whole seconds; round up so clients never retry early.
+                let secs = retry_after_ms.div_ceil(1000).max(1);
+                headers.insert("Retry-After", secs.to_string().parse().unwrap());
+                headers.insert("X-RateLimit-Global", "true".parse().unwrap());
+                headers.insert("X-RateLimit-Scope", "global".parse().unwrap());
+                headers.insert(
+                    "X-RateLimit-Reset-After",
+                    format!("{:.3}", *retry_after_ms as f64 / 1000.0)
+                        .parse()
+                        .unwrap(),
+                );
+            }
+            _ => {}
         }
         response
     }
@@ -97,11 +186,33 @@ impl std::fmt::Display for AppError {
             AppError::NotFound(msg) => write!(f, "not found: {msg}"),
             AppError::Unauthorized(msg) => write!(f, "unauthorized: {msg}"),
             AppError::Forbidden(msg) => write!(f, "forbidden: {msg}"),
+            AppError::ForbiddenCode(code, msg) => write!(f, "forbidden ({code}): {msg}"),
             AppError::Conflict(msg) => write!(f, "conflict: {msg}"),
+            AppError::ConflictCode(code, msg) => write!(f, "conflict ({code}): {msg}"),
+            AppError::GoneCode(code, msg) => write!(f, "gone ({code}): {msg}"),
             AppError::PayloadTooLarge(msg) => write!(f, "payload too large: {msg}"),
+            AppError::InvalidFields(errors) => {
+                write!(f, "invalid fields: ")?;
+                for (i, (field, msg)) in errors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{field} {msg}")?;
+                }
+                Ok(())
+            }
+            AppError::LimitReached { code, current, max } => {
+                write!(f, "limit reached ({code}): {current}/{max}")
+            }
             AppError::RateLimited { retry_after } => {
                 write!(f, "rate limited, retry after {retry_after}s")
             }
+            AppError::GlobalRateLimited { retry_after_ms } => {
+                write!(
+                    f,
+                    "global rate limit exceeded, retry after {retry_after_ms}ms"
+                )
+            }
         }
     }
 }
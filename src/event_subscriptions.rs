@@ -0,0 +1,246 @@
+//! Outgoing webhooks: applications subscribe a URL to gateway event types
+//! and receive each matching event as a signed JSON POST.
+//!
+//! [run] listens to the events this node broadcasts and queues a delivery
+//! per matching subscription in `event_deliveries` (see
+//! `migrations/079_event_subscriptions.sql`), then works the queue. A
+//! delivery succeeds on any 2xx response; anything else is retried with
+//! exponential backoff until [MAX_ATTEMPTS] have failed. Requests are signed
+//! like everything else the server sends an application (see [crate::signing]).
+
+use std::time::Duration;
+
+use reqwest::Method;
+
+use crate::db;
+use crate::error::AppError;
+use crate::gateway::events::GatewayBroadcast;
+use crate::models::event_subscription::{EventDelivery, EVENT_TYPES};
+use crate::safe_fetch::{self, FetchOptions};
+use crate::signing::{signature_header, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use crate::state::AppState;
+
+pub const EVENT_HEADER: &str = "X-Accord-Event";
+pub const DELIVERY_HEADER: &str = "X-Accord-Delivery";
+
+/// Attempts before a delivery is marked `failed`.
+pub const MAX_ATTEMPTS: i64 = 6;
+/// Backoff after the first failed attempt; doubles after each one after.
+pub const RETRY_BASE_SECS: i64 = 30;
+/// How often the queue is checked for due deliveries.
+pub const DELIVERY_INTERVAL: Duration = Duration::from_secs(5);
+/// How long a claimed delivery is hidden from other nodes while it's sent.
+const CLAIM_LEASE_SECS: i64 = 60;
+/// Finished deliveries are kept in the log this long.
+const DELIVERY_LOG_SECS: i64 = 7 * 24 * 60 * 60;
+const BATCH_SIZE: i64 = 50;
+
+/// Seconds to wait before retrying a delivery that has failed `attempts`
+/// times, or `None` once it's out of attempts.
+pub fn retry_delay(attempts: i64) -> Option<i64> {
+    (attempts < MAX_ATTEMPTS).then(|| RETRY_BASE_SECS << (attempts - 1).clamp(0, 20))
+}
+
+/// Queue and deliver forever.
+///
+/// Only events that originated on this node are queued -- the outbound
+/// channel never carries events relayed from other nodes -- so each event
+/// is delivered once however many nodes are running.
+pub async fn run(state: AppState) {
+    let receiver = state
+        .gateway_tx
+        .read()
+        .await
+        .as_ref()
+        .map(|tx| tx.subscribe());
+    if let Some(mut receiver) = receiver {
+        let listen_state = state.clone();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(broadcast) => {
+                        if let Err(e) = enqueue(&listen_state, &broadcast).await {
+                            tracing::warn!("queueing event deliveries failed: {e:?}");
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("event subscriptions dropped {n} events");
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    let mut interval = tokio::time::interval(DELIVERY_INTERVAL);
+    let mut ticks: u64 = 0;
+    loop {
+        interval.tick().await;
+        if let Err(e) = deliver_due(&state).await {
+            tracing::warn!("event delivery failed: {e:?}");
+        }
+        // Prune the log about once an hour.
+        ticks += 1;
+        if ticks.is_multiple_of(720) {
+            let cutoff = db::event_subscriptions::timestamp_in(-DELIVERY_LOG_SECS);
+            if let Err(e) = db::event_subscriptions::prune_deliveries(&state.db, &cutoff).await {
+                tracing::warn!("pruning event deliveries failed: {e:?}");
+            }
+        }
+    }
+}
+
+/// Queue a delivery of `broadcast` for every subscription that wants it.
+/// Only space events are delivered, and only from spaces the application's
+/// bot is in; channel events also need the bot to see the channel. Returns
+/// how many deliveries were queued.
+pub async fn enqueue(state: &AppState, broadcast: &GatewayBroadcast) -> Result<usize, AppError> {
+    if broadcast.target_user_ids.is_some() {
+        return Ok(0);
+    }
+    let Some(space_id) = broadcast.space_id.as_deref() else {
+        return Ok(0);
+    };
+    let Some(event_type) = broadcast.event.get("type").and_then(|t| t.as_str()) else {
+        return Ok(0);
+    };
+    if !EVENT_TYPES.contains(&event_type) {
+        return Ok(0);
+    }
+
+    let data = broadcast
+        .event
+        .get("data")
+        .cloned()
+        .unwrap_or(serde_json::Value::Null);
+    let channel_id = data.get("channel_id").and_then(|c| c.as_str());
+    let mut queued = 0;
+    for (sub, bot_user_id) in db::event_subscriptions::subscribers(&state.db, event_type).await? {
+        if !sub.space_ids.is_empty() && !sub.space_ids.iter().any(|s| s == space_id) {
+            continue;
+        }
+        let Some(bot_user_id) = bot_user_id else {
+            continue;
+        };
+        if db::members::get_member_row(&state.db, space_id, &bot_user_id)
+            .await
+            .is_err()
+        {
+            continue;
+        }
+        if let Some(channel_id) = channel_id {
+            if crate::middleware::permissions::require_channel_membership(
+                &state.db,
+                channel_id,
+                &bot_user_id,
+            )
+            .await
+            .is_err()
+            {
+                continue;
+            }
+        }
+
+        let id = crate::snowflake::generate();
+        let payload = serde_json::json!({
+            "id": id,
+            "type": event_type,
+            "space_id": space_id,
+            "created_at": chrono::Utc::now().to_rfc3339(),
+            "data": data,
+        });
+        db::event_subscriptions::enqueue_delivery(&state.db, &id, &sub.id, event_type, &payload)
+            .await?;
+        queued += 1;
+    }
+    Ok(queued)
+}
+
+/// Attempt every delivery that's due. Returns how many were attempted.
+pub async fn deliver_due(state: &AppState) -> Result<usize, AppError> {
+    let due = db::event_subscriptions::due_deliveries(&state.db, BATCH_SIZE).await?;
+    let lease = db::event_subscriptions::timestamp_in(CLAIM_LEASE_SECS);
+    let mut attempted = 0;
+    for delivery in due {
+        if !db::event_subscriptions::claim_delivery(&state.db, &delivery, &lease).await? {
+            continue;
+        }
+        attempt(state, &delivery).await?;
+        attempted += 1;
+    }
+    Ok(attempted)
+}
+
+async fn attempt(state: &AppState, delivery: &EventDelivery) -> Result<(), AppError> {
+    let sub = match db::event_subscriptions::get_subscription(&state.db, &delivery.subscription_id)
+        .await
+    {
+        Ok(sub) => sub,
+        // Deleted mid-flight; the cascade takes the delivery with it.
+        Err(AppError::NotFound(_)) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    let body = delivery.payload.to_string();
+    let timestamp = chrono::Utc::now().timestamp();
+    let keys = db::signing_keys::live_signing_keys(&state.db, &sub.application_id).await?;
+    let headers = [
+        ("Content-Type", "application/json".to_string()),
+        (TIMESTAMP_HEADER, timestamp.to_string()),
+        (
+            SIGNATURE_HEADER,
+            signature_header(&keys, timestamp, body.as_bytes()),
+        ),
+        (EVENT_HEADER, delivery.event_type.clone()),
+        (DELIVERY_HEADER, delivery.id.clone()),
+    ];
+    let (last_status, last_error) = match safe_fetch::request(
+        Method::POST,
+        &sub.url,
+        &headers,
+        Some(body.as_bytes()),
+        &FetchOptions::default(),
+    )
+    .await
+    {
+        Ok(resp) if (200..300).contains(&resp.status) => (Some(resp.status as i64), None),
+        Ok(resp) => (
+            Some(resp.status as i64),
+            Some(format!("unexpected status {}", resp.status)),
+        ),
+        Err(e) => (None, Some(e.to_string())),
+    };
+
+    let attempts = delivery.attempts + 1;
+    let (status, next_attempt_at) = match (&last_error, retry_delay(attempts)) {
+        (None, _) => ("succeeded", None),
+        (Some(_), Some(delay)) => (
+            "pending",
+            Some(db::event_subscriptions::timestamp_in(delay)),
+        ),
+        (Some(_), None) => ("failed", None),
+    };
+    db::event_subscriptions::record_attempt(
+        &state.db,
+        &delivery.id,
+        status,
+        attempts,
+        next_attempt_at.as_deref(),
+        last_status,
+        last_error.as_deref(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_until_attempts_run_out() {
+        assert_eq!(retry_delay(1), Some(30));
+        assert_eq!(retry_delay(2), Some(60));
+        assert_eq!(retry_delay(5), Some(480));
+        assert_eq!(retry_delay(MAX_ATTEMPTS), None);
+    }
+}
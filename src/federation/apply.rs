@@ -252,6 +252,8 @@ async fn apply_message_create(
     // Inject into the local gateway at the same seam local writes use. This is
     // delivery-only: it MUST NOT trigger outbound fanout (S7).
     let json = crate::routes::messages::message_row_to_json_with_attachments(&row, &[], None);
+    let role_mention_user_ids =
+        crate::gateway::events::resolve_role_mentions(&state.db, &json).await;
     if let Some(dispatcher) = state.gateway_tx.read().await.as_ref() {
         let event = serde_json::json!({
             "op": 0,
@@ -263,6 +265,7 @@ async fn apply_message_create(
             target_user_ids: None,
             event,
             intent: "messages".to_string(),
+            role_mention_user_ids,
         });
     }
 
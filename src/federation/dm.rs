@@ -465,6 +465,8 @@ async fn serve_send(
             reply_to: req.reply_to.clone(),
             thread_id: None,
             title: None,
+            poll: None,
+            sticker_ids: None,
         },
     )
     .await?;
@@ -676,6 +678,7 @@ async fn broadcast_to_participants(
             target_user_ids: Some(participant_ids),
             event,
             intent: intent.to_string(),
+            role_mention_user_ids: None,
         });
     }
 }
@@ -65,6 +65,7 @@ fn remote_actor_auth(user_id: &str) -> AuthUser {
         is_admin: false,
         is_guest: false,
         guest_space_id: None,
+        login_session_id: None,
     }
 }
 
@@ -141,13 +142,9 @@ async fn serve_send(
     // Authoritative permission check: re-derived from OUR DB, never trusting the
     // request (S1). Also fails if the actor is not a member of the channel.
     let auth = remote_actor_auth(&req.actor.id);
-    let space_id = crate::middleware::permissions::require_channel_permission(
-        &state.db,
-        &req.channel_id,
-        &auth,
-        "send_messages",
-    )
-    .await?;
+    let space_id =
+        crate::middleware::permissions::require_send_permission(&state.db, &req.channel_id, &auth)
+            .await?;
 
     // Persist as a normal local message (this server homes the space).
     let msg = crate::db::messages::create_message(
@@ -162,6 +159,8 @@ async fn serve_send(
             reply_to: req.reply_to.clone(),
             thread_id: None,
             title: None,
+            poll: None,
+            sticker_ids: None,
         },
     )
     .await?;
@@ -465,6 +464,7 @@ async fn serve_edit(
     )
     .await?;
 
+    crate::db::messages::record_revision(&state.db, &existing, &req.actor.id).await?;
     let msg = crate::db::messages::update_message(
         &state.db,
         &req.message_id,
@@ -472,6 +472,7 @@ async fn serve_edit(
             content: Some(req.content.clone()),
             embeds: None,
             title: None,
+            attachments: None,
         },
         state.db_is_postgres,
     )
@@ -22,6 +22,7 @@ use crate::error::AppError;
 use crate::federation::err_response as err;
 use crate::federation::mapping::RemoteUserRef;
 use crate::federation::{authority, mapping};
+use crate::models::permission::Permissions;
 use crate::state::AppState;
 
 /// Path of the join endpoint, also the signed `(request-target)`.
@@ -141,7 +142,8 @@ async fn build_snapshot(
                 "space_id": q(&r.space_id),
                 "name": r.name,
                 "position": r.position,
-                "permissions": r.permissions,
+                // A JSON-encoded name list, as before bitfields.
+                "permissions": serde_json::to_string(&r.permissions).unwrap(),
             })
         })
         .collect();
@@ -404,7 +406,10 @@ pub async fn apply_snapshot(
             &snap.space.id,
             &r.name,
             r.position,
-            &r.permissions,
+            // A newer peer may know permissions this server doesn't.
+            Permissions::from_names(
+                &serde_json::from_str::<Vec<String>>(&r.permissions).unwrap_or_default(),
+            ),
         )
         .await?;
         let _ = &r.space_id;
@@ -2,10 +2,11 @@
 
 use reqwest::dns::{Addrs, Name, Resolve, Resolving};
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, SocketAddr};
+use std::net::IpAddr;
 use std::time::Duration;
 
 use crate::error::AppError;
+use crate::safe_fetch::{is_blocked_ip, PublicOnlyResolver};
 
 /// Path where every server publishes its federation metadata.
 pub const WELL_KNOWN_PATH: &str = "/.well-known/accord-federation";
@@ -35,8 +36,8 @@ pub fn allow_insecure() -> bool {
 /// `localhost`. This is the synchronous half (scheme + IP-literal checks);
 /// [`validate_peer_url_resolved`] additionally resolves hostnames and rejects
 /// those that map to internal addresses. A determined attacker controlling DNS
-/// could still rebind between validation and connect (TOCTOU); fully closing
-/// that requires pinning the resolved IP into the HTTP client.
+/// could still rebind between validation and connect (TOCTOU); the client's
+/// [`SsrfGuardResolver`] closes that by re-checking at connect time.
 pub fn validate_peer_url(url: &str) -> Result<(), AppError> {
     if allow_insecure() {
         return Ok(());
@@ -55,7 +56,7 @@ pub fn validate_peer_url(url: &str) -> Result<(), AppError> {
         return Err(AppError::BadRequest("peer host not allowed".to_string()));
     }
     if let Ok(ip) = host.parse::<IpAddr>() {
-        if is_private(&ip) {
+        if is_blocked_ip(&ip) {
             return Err(AppError::BadRequest(
                 "peer host resolves to a private address".to_string(),
             ));
@@ -92,7 +93,7 @@ pub async fn validate_peer_url_resolved(url: &str) -> Result<(), AppError> {
         )));
     }
     for addr in resolved {
-        if is_private(&addr.ip()) {
+        if is_blocked_ip(&addr.ip()) {
             return Err(AppError::BadRequest(format!(
                 "peer host {host} resolves to a private address"
             )));
@@ -101,84 +102,21 @@ pub async fn validate_peer_url_resolved(url: &str) -> Result<(), AppError> {
     Ok(())
 }
 
-/// True when an address must never be the target of an outbound federation
-/// request: loopback, private/RFC1918, link-local, CGNAT, IPv6 ULA, etc. IPv6
-/// addresses that embed an IPv4 address (mapped/compatible) are folded back to
-/// their V4 form so an attacker cannot smuggle `::ffff:127.0.0.1` past the V4
-/// checks.
-fn is_private(ip: &IpAddr) -> bool {
-    match ip {
-        IpAddr::V4(v4) => is_private_v4(v4),
-        IpAddr::V6(v6) => {
-            if let Some(mapped) = v6.to_ipv4_mapped() {
-                return is_private_v4(&mapped);
-            }
-            // `to_ipv4()` also covers the deprecated IPv4-compatible range
-            // (::a.b.c.d), which would otherwise slip through as a global V6.
-            if let Some(compat) = v6.to_ipv4() {
-                return is_private_v4(&compat);
-            }
-            let seg = v6.segments();
-            v6.is_loopback()
-                || v6.is_unspecified()
-                // Unique local addresses (fc00::/7).
-                || (seg[0] & 0xfe00) == 0xfc00
-                // Link-local unicast (fe80::/10).
-                || (seg[0] & 0xffc0) == 0xfe80
-                // Documentation prefix (2001:db8::/32).
-                || (seg[0] == 0x2001 && seg[1] == 0x0db8)
-        }
-    }
-}
-
-fn is_private_v4(v4: &std::net::Ipv4Addr) -> bool {
-    let o = v4.octets();
-    v4.is_private()
-        || v4.is_loopback()
-        || v4.is_link_local()
-        || v4.is_unspecified()
-        || v4.is_broadcast()
-        || v4.is_documentation()
-        // Carrier-grade NAT (100.64.0.0/10).
-        || (o[0] == 100 && (o[1] & 0xc0) == 64)
-        // "This host on this network" (0.0.0.0/8).
-        || o[0] == 0
-        // Reserved/benchmarking and multicast ranges have no business being a
-        // unicast federation peer.
-        || v4.is_multicast()
-}
-
-/// Custom DNS resolver that re-applies [`is_private`] at connect time, closing
-/// the TOCTOU/DNS-rebinding gap between [`validate_peer_url_resolved`] and the
-/// actual TCP connection. Wired into the federation HTTP client via
-/// `ClientBuilder::dns_resolver`.
+/// DNS resolver for the federation client: [`PublicOnlyResolver`], except
+/// that insecure testing mode (see [`allow_insecure`]) defers to the system
+/// resolver unfiltered so local two-node setups can reach loopback peers.
 #[derive(Debug, Clone, Default)]
 pub struct SsrfGuardResolver;
 
 impl Resolve for SsrfGuardResolver {
     fn resolve(&self, name: Name) -> Resolving {
+        if !allow_insecure() {
+            return PublicOnlyResolver.resolve(name);
+        }
         Box::pin(async move {
-            if allow_insecure() {
-                // Defer to the system resolver without filtering.
-                let host = name.as_str().to_string();
-                let addrs = tokio::net::lookup_host((host, 0)).await?;
-                let iter: Addrs = Box::new(addrs);
-                return Ok(iter);
-            }
             let host = name.as_str().to_string();
-            let resolved: Vec<SocketAddr> =
-                tokio::net::lookup_host((host.clone(), 0)).await?.collect();
-            if resolved.is_empty() {
-                return Err(format!("host {host} did not resolve").into());
-            }
-            for addr in &resolved {
-                if is_private(&addr.ip()) {
-                    return Err(
-                        format!("host {host} resolves to a private/internal address").into(),
-                    );
-                }
-            }
-            let iter: Addrs = Box::new(resolved.into_iter());
+            let addrs = tokio::net::lookup_host((host, 0)).await?;
+            let iter: Addrs = Box::new(addrs);
             Ok(iter)
         })
     }
@@ -0,0 +1,256 @@
+//! Cross-node gateway fan-out over Redis pub/sub.
+//!
+//! Without a transport, `AppState::gateway_tx` and the sessions share one
+//! in-process broadcast channel, so only one main server can run. With
+//! `REDIS_URL` set, `gateway_tx` feeds a relay instead: each event is
+//! delivered to this node's sessions straight away and published to Redis
+//! for everyone else.
+//!
+//! Channels are keyed the way sessions filter events: `{prefix}:space:{id}`
+//! for space events, `{prefix}:user:{id}` for targeted ones (one copy per
+//! recipient, each narrowed to that recipient) and `{prefix}:global` for the
+//! rest. A node only subscribes to the spaces and users of the sessions it
+//! holds, and every message carries its origin node so the sender skips its
+//! own echo.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, Notify};
+
+use super::dispatcher::Dispatcher;
+use super::events::GatewayBroadcast;
+use super::session::GatewaySession;
+use crate::config::RedisConfig;
+
+/// Longest wait between attempts to re-establish the subscription.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    node: String,
+    broadcast: GatewayBroadcast,
+}
+
+fn space_channel(prefix: &str, space_id: &str) -> String {
+    format!("{prefix}:space:{space_id}")
+}
+
+fn user_channel(prefix: &str, user_id: &str) -> String {
+    format!("{prefix}:user:{user_id}")
+}
+
+fn global_channel(prefix: &str) -> String {
+    format!("{prefix}:global")
+}
+
+/// Where `broadcast` is published, and the copy each channel gets.
+fn route(prefix: &str, broadcast: &GatewayBroadcast) -> Vec<(String, GatewayBroadcast)> {
+    match (&broadcast.target_user_ids, &broadcast.space_id) {
+        (Some(targets), _) => targets
+            .iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|user_id| {
+                let mut copy = broadcast.clone();
+                copy.target_user_ids = Some(vec![user_id.clone()]);
+                (user_channel(prefix, user_id), copy)
+            })
+            .collect(),
+        (None, Some(space_id)) => vec![(space_channel(prefix, space_id), broadcast.clone())],
+        (None, None) => vec![(global_channel(prefix), broadcast.clone())],
+    }
+}
+
+/// Channels this node needs for the sessions it currently holds.
+fn wanted_channels(prefix: &str, sessions: &DashMap<String, GatewaySession>) -> HashSet<String> {
+    let mut wanted = HashSet::from([global_channel(prefix)]);
+    for session in sessions.iter() {
+        wanted.insert(user_channel(prefix, &session.user_id));
+        for space_id in &session.space_ids {
+            wanted.insert(space_channel(prefix, space_id));
+        }
+    }
+    wanted
+}
+
+/// Connect to Redis and start relaying. Returns the sender to use as
+/// `AppState::gateway_tx`.
+pub async fn start(
+    config: &RedisConfig,
+    dispatcher: &Dispatcher,
+) -> redis::RedisResult<broadcast::Sender<GatewayBroadcast>> {
+    let client = redis::Client::open(config.url.as_str())?;
+    let publisher = client.get_connection_manager().await?;
+    let node = uuid::Uuid::new_v4().to_string();
+    let (outbound_tx, outbound_rx) = broadcast::channel(1024);
+
+    tokio::spawn(relay_outbound(
+        outbound_rx,
+        dispatcher.local_sender(),
+        publisher,
+        config.channel_prefix.clone(),
+        node.clone(),
+    ));
+    tokio::spawn(subscribe_loop(
+        client,
+        config.channel_prefix.clone(),
+        node,
+        dispatcher.sessions().clone(),
+        dispatcher.interest_changed(),
+        dispatcher.local_sender(),
+    ));
+    Ok(outbound_tx)
+}
+
+/// Deliver locally first so a Redis outage never delays this node's own
+/// sessions, then publish for the rest of the cluster.
+async fn relay_outbound(
+    mut outbound: broadcast::Receiver<GatewayBroadcast>,
+    local: broadcast::Sender<GatewayBroadcast>,
+    mut publisher: redis::aio::ConnectionManager,
+    prefix: String,
+    node: String,
+) {
+    loop {
+        let broadcast = match outbound.recv().await {
+            Ok(broadcast) => broadcast,
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                tracing::warn!("cluster relay lagged, {n} events not published");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let _ = local.send(broadcast.clone());
+
+        for (channel, copy) in route(&prefix, &broadcast) {
+            let payload = serde_json::to_string(&Envelope {
+                node: node.clone(),
+                broadcast: copy,
+            })
+            .unwrap();
+            if let Err(e) = publisher.publish::<_, _, ()>(&channel, payload).await {
+                tracing::warn!("failed to publish gateway event to {channel}: {e}");
+            }
+        }
+    }
+}
+
+/// Keep a subscription matching this node's sessions and hand everything
+/// other nodes publish to the local sessions, reconnecting with backoff.
+async fn subscribe_loop(
+    client: redis::Client,
+    prefix: String,
+    node: String,
+    sessions: Arc<DashMap<String, GatewaySession>>,
+    interest: Arc<Notify>,
+    local: broadcast::Sender<GatewayBroadcast>,
+) {
+    let mut delay = Duration::from_secs(1);
+    loop {
+        match run_subscription(&client, &prefix, &node, &sessions, &interest, &local).await {
+            Ok(()) => {
+                tracing::warn!("redis subscription closed, reconnecting");
+                delay = Duration::from_secs(1);
+            }
+            Err(e) => {
+                tracing::warn!("redis subscription failed: {e}, retrying in {delay:?}");
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+/// Returns `Ok` when an established subscription is dropped by the server.
+async fn run_subscription(
+    client: &redis::Client,
+    prefix: &str,
+    node: &str,
+    sessions: &DashMap<String, GatewaySession>,
+    interest: &Notify,
+    local: &broadcast::Sender<GatewayBroadcast>,
+) -> redis::RedisResult<()> {
+    let mut pubsub = client.get_async_pubsub().await?;
+    let mut subscribed: HashSet<String> = HashSet::new();
+    loop {
+        // Follow the sessions on this node. Events published between a
+        // session registering and this catching up are missed, just as ones
+        // sent before READY would be.
+        let wanted = wanted_channels(prefix, sessions);
+        for channel in wanted.difference(&subscribed) {
+            pubsub.subscribe(channel).await?;
+        }
+        for channel in subscribed.difference(&wanted) {
+            pubsub.unsubscribe(channel).await?;
+        }
+        subscribed = wanted;
+
+        let mut messages = pubsub.on_message();
+        loop {
+            tokio::select! {
+                msg = messages.next() => {
+                    let Some(msg) = msg else {
+                        return Ok(());
+                    };
+                    let envelope = msg
+                        .get_payload::<String>()
+                        .ok()
+                        .and_then(|payload| serde_json::from_str::<Envelope>(&payload).ok());
+                    match envelope {
+                        Some(envelope) if envelope.node != node => {
+                            let _ = local.send(envelope.broadcast);
+                        }
+                        Some(_) => {}
+                        None => tracing::warn!(
+                            "ignoring malformed gateway event on {}",
+                            msg.get_channel_name()
+                        ),
+                    }
+                }
+                _ = interest.notified() => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn broadcast(space_id: Option<&str>, targets: Option<&[&str]>) -> GatewayBroadcast {
+        GatewayBroadcast {
+            space_id: space_id.map(str::to_string),
+            target_user_ids: targets.map(|t| t.iter().map(|s| s.to_string()).collect()),
+            event: serde_json::json!({ "type": "message.create" }),
+            intent: "messages".to_string(),
+            role_mention_user_ids: None,
+        }
+    }
+
+    #[test]
+    fn targeted_events_get_one_narrowed_copy_per_user() {
+        let mut routed = route("accord", &broadcast(None, Some(&["1", "2", "1"])));
+        routed.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(routed.len(), 2);
+        assert_eq!(routed[0].0, "accord:user:1");
+        assert_eq!(routed[0].1.target_user_ids, Some(vec!["1".to_string()]));
+        assert_eq!(routed[1].0, "accord:user:2");
+    }
+
+    #[test]
+    fn space_and_global_events_use_one_channel() {
+        let routed = route("accord", &broadcast(Some("9"), None));
+        assert_eq!(routed.len(), 1);
+        assert_eq!(routed[0].0, "accord:space:9");
+        assert_eq!(
+            route("accord", &broadcast(None, None))[0].0,
+            "accord:global"
+        );
+    }
+}
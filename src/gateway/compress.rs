@@ -0,0 +1,79 @@
+use std::io::Write;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+/// Value of `compress` in IDENTIFY/RESUME that turns on transport compression.
+pub const ZLIB_STREAM: &str = "zlib-stream";
+
+/// Every flushed chunk ends with this marker; clients buffer binary frames
+/// until they see it, then inflate the buffer with their one long-lived
+/// zlib context.
+pub const ZLIB_SUFFIX: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// One zlib stream shared by all frames on a connection. Keeping the deflate
+/// context alive between messages lets repeated keys and IDs in later events
+/// back-reference earlier ones, which is where most of the savings come from.
+pub struct ZlibStream {
+    encoder: ZlibEncoder<Vec<u8>>,
+}
+
+impl ZlibStream {
+    pub fn new() -> Self {
+        Self {
+            encoder: ZlibEncoder::new(Vec::new(), Compression::default()),
+        }
+    }
+
+    /// Compress one payload and sync-flush, returning the bytes to send as a
+    /// single binary frame.
+    pub fn compress(&mut self, payload: &str) -> std::io::Result<Vec<u8>> {
+        self.compress_bytes(payload.as_bytes())
+    }
+
+    /// [compress] for binary payloads (MessagePack sessions).
+    pub fn compress_bytes(&mut self, payload: &[u8]) -> std::io::Result<Vec<u8>> {
+        self.encoder.write_all(payload)?;
+        self.encoder.flush()?;
+        Ok(std::mem::take(self.encoder.get_mut()))
+    }
+}
+
+impl Default for ZlibStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Decompress, FlushDecompress};
+
+    fn inflate(ctx: &mut Decompress, chunk: &[u8]) -> String {
+        let mut out = Vec::with_capacity(4096);
+        ctx.decompress_vec(chunk, &mut out, FlushDecompress::Sync)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_chunks_end_with_sync_marker() {
+        let mut z = ZlibStream::new();
+        let chunk = z.compress(r#"{"op":10}"#).unwrap();
+        assert!(chunk.ends_with(&ZLIB_SUFFIX));
+    }
+
+    #[test]
+    fn test_frames_share_one_stream() {
+        let mut z = ZlibStream::new();
+        let mut ctx = Decompress::new(true);
+        let payload = r#"{"op":0,"type":"message.create","data":{"channel_id":"1"}}"#;
+        let first = z.compress(payload).unwrap();
+        let second = z.compress(payload).unwrap();
+        // The repeat back-references the first frame, so it's much smaller.
+        assert!(second.len() < first.len());
+        assert_eq!(inflate(&mut ctx, &first), payload);
+        assert_eq!(inflate(&mut ctx, &second), payload);
+    }
+}
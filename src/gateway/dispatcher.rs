@@ -2,13 +2,20 @@ use dashmap::DashMap;
 use std::sync::Arc;
 use tokio::sync::broadcast;
 
+use tokio::sync::{oneshot, watch, Notify};
+
 use super::events::GatewayBroadcast;
-use super::session::GatewaySession;
+use super::session::{GatewaySession, ResumeHandoff, ResumeRequest};
 
 /// Manages all active gateway sessions and broadcasts events.
 pub struct Dispatcher {
     sessions: Arc<DashMap<String, GatewaySession>>,
     tx: broadcast::Sender<GatewayBroadcast>,
+    /// Flipped to `true` once the server starts shutting down.
+    shutdown: watch::Sender<bool>,
+    /// Notified whenever a session is added or removed, so a cluster
+    /// transport can follow which spaces and users this node serves.
+    interest: Arc<Notify>,
 }
 
 impl Dispatcher {
@@ -19,6 +26,8 @@ impl Dispatcher {
             Self {
                 sessions: Arc::new(DashMap::new()),
                 tx,
+                shutdown: watch::channel(false).0,
+                interest: Arc::new(Notify::new()),
             },
             sender,
         )
@@ -30,10 +39,35 @@ impl Dispatcher {
 
     pub fn register_session(&self, session: GatewaySession) {
         self.sessions.insert(session.session_id.clone(), session);
+        self.interest.notify_one();
     }
 
     pub fn remove_session(&self, session_id: &str) {
-        self.sessions.remove(session_id);
+        if self.sessions.remove(session_id).is_some() {
+            self.interest.notify_one();
+        }
+    }
+
+    pub fn interest_changed(&self) -> Arc<Notify> {
+        self.interest.clone()
+    }
+
+    /// Ask the connection task that owns `session_id` to hand the session over
+    /// to a resuming connection, replaying events after `seq`. Returns `None`
+    /// if the session is unknown or belongs to a different user.
+    pub fn request_resume(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        seq: u64,
+    ) -> Option<oneshot::Receiver<Option<ResumeHandoff>>> {
+        let session = self.sessions.get(session_id)?;
+        if session.user_id != user_id {
+            return None;
+        }
+        let (reply, reply_rx) = oneshot::channel();
+        session.resume_tx.send(ResumeRequest { seq, reply }).ok()?;
+        Some(reply_rx)
     }
 
     pub fn subscribe(&self) -> broadcast::Receiver<GatewayBroadcast> {
@@ -43,4 +77,20 @@ impl Dispatcher {
     pub fn broadcast(&self, msg: GatewayBroadcast) {
         let _ = self.tx.send(msg);
     }
+
+    /// The channel sessions on this node receive from. Without a cluster
+    /// transport this is also what `AppState::gateway_tx` sends on.
+    pub fn local_sender(&self) -> broadcast::Sender<GatewayBroadcast> {
+        self.tx.clone()
+    }
+
+    /// Close every gateway connection with `SERVER_SHUTDOWN`. Connections
+    /// opened afterwards are closed as soon as they identify.
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    pub fn shutdown_signal(&self) -> watch::Receiver<bool> {
+        self.shutdown.subscribe()
+    }
 }
@@ -1,7 +1,12 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
 use serde::{Deserialize, Serialize};
 
+use crate::db::{self, DbPool};
+
 /// Broadcast message sent through the gateway channel.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GatewayBroadcast {
     pub space_id: Option<String>,
     /// When set, only sessions belonging to these user IDs receive the event.
@@ -9,6 +14,53 @@ pub struct GatewayBroadcast {
     pub target_user_ids: Option<Vec<String>>,
     pub event: serde_json::Value,
     pub intent: String,
+    /// For `message.create`: members mentioned through one of the message's
+    /// `mention_roles`, resolved once before fan-out (see
+    /// [resolve_role_mentions]) so sessions don't each look up their roles.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_mention_user_ids: Option<Arc<HashSet<String>>>,
+}
+
+impl GatewayBroadcast {
+    /// An instance-level event for admin dashboards. Sent everywhere, but the
+    /// dispatcher only delivers the `admin` intent to instance admins.
+    pub fn admin(event_type: &str, data: serde_json::Value) -> Self {
+        GatewayBroadcast {
+            space_id: None,
+            target_user_ids: None,
+            event: serde_json::json!({
+                "op": 0,
+                "type": event_type,
+                "data": data
+            }),
+            intent: crate::gateway::intents::ADMIN_INTENT.to_string(),
+            role_mention_user_ids: None,
+        }
+    }
+}
+
+/// The members holding any of the `mention_roles` in a message payload.
+/// `None` when it mentions no roles or isn't in a space.
+pub async fn resolve_role_mentions(
+    pool: &DbPool,
+    message: &serde_json::Value,
+) -> Option<Arc<HashSet<String>>> {
+    let space_id = message["space_id"].as_str()?;
+    let roles: Vec<String> = message["mention_roles"]
+        .as_array()?
+        .iter()
+        .filter_map(|v| v.as_str().map(str::to_string))
+        .collect();
+    if roles.is_empty() {
+        return None;
+    }
+    match db::members::list_member_ids_with_roles(pool, space_id, &roles).await {
+        Ok(ids) => Some(Arc::new(ids.into_iter().collect())),
+        Err(e) => {
+            tracing::warn!("failed to resolve role mentions: {e:?}");
+            None
+        }
+    }
 }
 
 /// Opcodes for gateway messages.
@@ -24,22 +76,50 @@ pub mod opcode {
     pub const PRESENCE_UPDATE: u8 = 8;
     pub const VOICE_STATE_UPDATE: u8 = 9;
     pub const REQUEST_MEMBERS: u8 = 10;
+    pub const SUBSCRIBE_SPACES: u8 = 11;
+    /// Sent instead of IDENTIFY by a signed-out device waiting on a QR login.
+    pub const LOGIN_TICKET: u8 = 12;
 }
 
-/// Close codes.
+/// Close codes. Clients should not reconnect after [close_code::AUTH_FAILED],
+/// [close_code::INVALID_INTENT], [close_code::DISALLOWED_INTENT] or
+/// [close_code::IP_NOT_ALLOWED]; every other code is safe to reconnect (and
+/// RESUME where the session is still valid) after.
 pub mod close_code {
     pub const UNKNOWN_ERROR: u16 = 4000;
+    /// An opcode the server doesn't accept from clients.
     pub const UNKNOWN_OPCODE: u16 = 4001;
+    /// A payload that isn't valid JSON or doesn't match its opcode's shape.
     pub const DECODE_ERROR: u16 = 4002;
+    /// No IDENTIFY or RESUME within the identify timeout.
     pub const NOT_AUTHENTICATED: u16 = 4003;
+    /// The IDENTIFY token was invalid, expired, or belongs to a disabled user,
+    /// or its login session was revoked while connected.
     pub const AUTH_FAILED: u16 = 4004;
+    /// A second IDENTIFY or RESUME on an authenticated connection.
     pub const ALREADY_AUTHENTICATED: u16 = 4005;
+    /// RESUME failed: unknown or expired session, or another user's.
+    /// Reconnect and IDENTIFY.
+    pub const SESSION_NO_LONGER_VALID: u16 = 4006;
+    /// RESUME with a `seq` the replay buffer no longer covers.
     pub const INVALID_SEQ: u16 = 4007;
+    /// More than the per-connection message quota.
     pub const RATE_LIMITED: u16 = 4008;
+    /// No heartbeat within the heartbeat timeout. The session stays
+    /// resumable for the resume window.
     pub const SESSION_TIMED_OUT: u16 = 4009;
     pub const INVALID_VERSION: u16 = 4012;
+    /// IDENTIFY named an intent that doesn't exist.
     pub const INVALID_INTENT: u16 = 4013;
+    /// IDENTIFY asked for an intent the session may not have.
     pub const DISALLOWED_INTENT: u16 = 4014;
+    /// A bot token used from outside its application's IP allowlist.
+    pub const IP_NOT_ALLOWED: u16 = 4015;
+    /// The server is shutting down. Sessions are not kept across restarts:
+    /// reconnect with backoff and IDENTIFY.
+    pub const SERVER_SHUTDOWN: u16 = 4016;
+    /// A LOGIN_TICKET expired before anyone approved it. Request a new one.
+    pub const LOGIN_TICKET_EXPIRED: u16 = 4017;
 }
 
 /// Gateway message envelope.
@@ -61,6 +141,69 @@ pub struct IdentifyData {
     pub intents: Vec<String>,
     pub properties: Option<serde_json::Value>,
     pub presence: Option<serde_json::Value>,
+    /// Transport compression, e.g. `"zlib-stream"`.
+    pub compress: Option<String>,
+    #[serde(default)]
+    pub capabilities: Capabilities,
+}
+
+/// Optional behaviours a client opts into at IDENTIFY. They last for the
+/// session (including RESUMEs) and are echoed back in READY. Unknown flags
+/// are ignored so newer clients can talk to older servers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Capabilities {
+    /// zlib-stream transport compression; same as `"compress": "zlib-stream"`.
+    pub supports_compression: bool,
+    /// Frames are MessagePack maps in binary frames instead of JSON text.
+    pub supports_msgpack: bool,
+    /// `presence.update` events are coalesced into `presence.update_batch`.
+    pub wants_presence_batching: bool,
+    /// `space.members_chunk` lists users and presences alongside members
+    /// instead of nesting a user object in each member.
+    pub member_list_v2: bool,
+    /// READY carries only space objects, DMs, and friends' presences; each
+    /// space's channels, roles, members, and presences arrive as a
+    /// `space.sync` once the client sends SUBSCRIBE_SPACES for it.
+    pub lazy_spaces: bool,
+    /// Drop `message.create`/`message.update` events from users this user has
+    /// blocked instead of delivering them with `blocked: true`.
+    pub omit_blocked_messages: bool,
+}
+
+/// RESUME (opcode 3) payload data.
+#[derive(Debug, Deserialize)]
+pub struct ResumeData {
+    pub token: String,
+    pub session_id: String,
+    pub seq: u64,
+    pub compress: Option<String>,
+}
+
+/// REQUEST_MEMBERS (opcode 10) payload data.
+#[derive(Debug, Deserialize)]
+pub struct RequestMembersData {
+    pub space_id: String,
+    /// Only members whose username or nickname starts with this prefix.
+    pub query: Option<String>,
+    /// Maximum number of members to return; all matching members when unset.
+    pub limit: Option<i64>,
+    /// Echoed back in every chunk so clients can match responses to requests.
+    pub nonce: Option<String>,
+}
+
+/// LOGIN_TICKET (opcode 12) payload data, as returned by
+/// `POST /auth/login-tickets`.
+#[derive(Debug, Deserialize)]
+pub struct LoginTicketData {
+    pub ticket: String,
+    pub secret: String,
+}
+
+/// SUBSCRIBE_SPACES (opcode 11) payload data.
+#[derive(Debug, Deserialize)]
+pub struct SubscribeSpacesData {
+    pub space_ids: Vec<String>,
 }
 
 /// PRESENCE_UPDATE (opcode 8) payload data.
@@ -78,5 +221,6 @@ pub struct VoiceStateUpdateData {
     pub self_mute: Option<bool>,
     pub self_deaf: Option<bool>,
     pub self_video: Option<bool>,
-    pub self_stream: Option<bool>,
+    // No `self_stream`: streaming starts and stops with
+    // `POST`/`DELETE /channels/{id}/voice/stream`, and a sent value is ignored.
 }
@@ -18,54 +18,189 @@ pub const ALL_INTENTS: &[&str] = &[
     "members",
     "presences",
     "message_content",
+    // Instance admins only
+    "admin",
 ];
 
 pub const PRIVILEGED_INTENTS: &[&str] = &["members", "presences", "message_content"];
 
-/// Map an event type to its required intent.
-pub fn intent_for_event(event_type: &str) -> Option<&'static str> {
-    match event_type {
-        "message.create" | "message.update" | "message.delete" | "message.delete_bulk" => {
-            Some("messages")
-        }
-        "member.join" | "member.leave" | "member.update" | "member.chunk" => Some("members"),
-        "space.create" | "space.update" | "space.delete" => Some("spaces"),
-        "channel.create"
-        | "channel.update"
-        | "channel.delete"
-        | "channel.reorder"
-        | "channel.pins_update" => Some("spaces"),
-        "role.create" | "role.update" | "role.delete" => Some("spaces"),
-        "reaction.add" | "reaction.remove" | "reaction.clear" | "reaction.clear_emoji" => {
-            Some("message_reactions")
-        }
-        "typing.start" => Some("message_typing"),
-        "presence.update" => Some("presences"),
-        "voice.state_update" | "voice.server_update" | "voice.signal" => Some("voice_states"),
-        "call.ring" | "call.accept" | "call.decline" | "call.cancel" | "call.end" => {
-            Some("voice_states")
-        }
-        "ban.create" | "ban.delete" | "audit_log.create" => Some("moderation"),
-        "invite.create" | "invite.delete" => Some("spaces"),
-        "emoji.create" | "emoji.update" | "emoji.delete" => Some("emojis"),
-        "soundboard.create" | "soundboard.update" | "soundboard.delete" | "soundboard.play" => {
-            Some("soundboard")
+/// Instance events (registrations, new spaces, reports, the voice backend
+/// going down). Only instance admins may IDENTIFY with it or receive it.
+pub const ADMIN_INTENT: &str = "admin";
+
+/// Classifies the events every session receives, whatever intents it
+/// asked for. Not an intent a client can request.
+pub const UNGATED: &str = "ungated";
+
+macro_rules! event_catalog {
+    ($($event:literal => $intent:expr,)*) => {
+        /// The intent a session needs to receive `event_type` ([UNGATED] for
+        /// none), or `None` for a type missing from the catalog below, which
+        /// is never delivered.
+        pub fn intent_for_event(event_type: &str) -> Option<&'static str> {
+            match event_type {
+                $($event => Some($intent),)*
+                _ => None,
+            }
         }
-        "relationship.add" | "relationship.update" | "relationship.remove" => Some("relationships"),
-        "plugin.installed"
-        | "plugin.uninstalled"
-        | "plugin.event"
-        | "plugin.session_state"
-        | "plugin.role_changed" => Some("plugins"),
-        "interaction.create" => None, // always delivered
-        _ => None,
-    }
+
+        /// Every event type the gateway dispatches. `schema/catalog.json`
+        /// must describe each of them; `tests/contract.rs` checks that it
+        /// does.
+        pub const EVENT_TYPES: &[&str] = &[$($event,)*];
+    };
+}
+
+// A new event type isn't delivered to anyone until it's classified here.
+event_catalog! {
+    // Connection lifecycle
+    "ready" => UNGATED,
+    "resumed" => UNGATED,
+    "space.sync" => UNGATED,
+    "login_ticket.approved" => UNGATED,
+    "auth_session.revoke" => UNGATED,
+    // Messages
+    "message.create" => "messages",
+    "message.update" => "messages",
+    "message.delete" => "messages",
+    "message.delete_bulk" => "messages",
+    "message.ack" => UNGATED,
+    "voice_chat.clear" => "messages",
+    "mention.create" => "messages",
+    "thread.create" => "messages",
+    "thread.update" => "messages",
+    "thread.delete" => "messages",
+    "forum_post.create" => UNGATED,
+    "forum_tag.create" => UNGATED,
+    "forum_tag.update" => UNGATED,
+    "forum_tag.delete" => UNGATED,
+    "draft.update" => UNGATED,
+    "draft.delete" => UNGATED,
+    "read_state.update" => UNGATED,
+    // Members
+    "member.join" => "members",
+    "member.leave" => "members",
+    "member.update" => "members",
+    "space.members_chunk" => "members",
+    "anonymous_count_updated" => UNGATED,
+    // Spaces and channels
+    "space.update" => "spaces",
+    "space.delete" => "spaces",
+    "space.lockdown" => UNGATED,
+    "channel.create" => "spaces",
+    "channel.update" => "spaces",
+    "channel.delete" => "spaces",
+    "channel.position_update" => "spaces",
+    "channel.pins_update" => "spaces",
+    // Reactions and polls
+    "reaction.add" => "message_reactions",
+    "reaction.remove" => "message_reactions",
+    "reaction.clear" => "message_reactions",
+    "reaction.clear_emoji" => "message_reactions",
+    "poll.vote_add" => "message_reactions",
+    "poll.vote_remove" => "message_reactions",
+    // Typing and presence
+    "typing.start" => "message_typing",
+    "presence.update" => "presences",
+    "presence.update_batch" => "presences",
+    // Voice and calls
+    "voice.state_update" => "voice_states",
+    "voice.server_update" => "voice_states",
+    "voice.stream_start" => "voice_states",
+    "voice.stream_stop" => "voice_states",
+    "voice.token_refresh" => "voice_states",
+    "call.ring" => "voice_states",
+    "call.decline" => "voice_states",
+    "call.cancel" => "voice_states",
+    "call.end" => "voice_states",
+    // Moderation
+    "ban.create" => "moderation",
+    "ban.delete" => "moderation",
+    "audit_log.create" => "moderation",
+    "automod.action" => "moderation",
+    "content_safety.flag" => "moderation",
+    "report.create" => "moderation",
+    // Emoji, stickers and soundboard
+    "emoji.create" => "emojis",
+    "emoji.update" => "emojis",
+    "emoji.delete" => "emojis",
+    "emoji.queue_update" => "emojis",
+    "sticker.create" => "emojis",
+    "sticker.update" => "emojis",
+    "sticker.delete" => "emojis",
+    "soundboard.create" => "soundboard",
+    "soundboard.update" => "soundboard",
+    "soundboard.delete" => "soundboard",
+    "soundboard.play" => "soundboard",
+    // Relationships and per-user settings
+    "relationship.add" => "relationships",
+    "relationship.update" => "relationships",
+    "relationship.remove" => "relationships",
+    "user_settings.update" => UNGATED,
+    "user_settings.spaces_update" => UNGATED,
+    "notification_settings.update" => UNGATED,
+    "everyone_suppression.update" => UNGATED,
+    "channel_mute.create" => UNGATED,
+    "channel_mute.delete" => UNGATED,
+    "channel_states.update" => UNGATED,
+    // Plugins and interactions
+    "plugin.installed" => "plugins",
+    "plugin.uninstalled" => "plugins",
+    "plugin.event" => "plugins",
+    "plugin.session_state" => "plugins",
+    "plugin.role_changed" => "plugins",
+    "plugin.leaderboard_updated" => UNGATED,
+    "interaction.create" => UNGATED,
+    // Instance admin
+    "admin.user_create" => ADMIN_INTENT,
+    "admin.space_create" => ADMIN_INTENT,
+    "admin.report_create" => ADMIN_INTENT,
+    "admin.report_update" => ADMIN_INTENT,
+    "admin.content_safety_flag" => ADMIN_INTENT,
+    "admin.voice_backend_update" => ADMIN_INTENT,
 }
 
 /// Check if a set of intents includes the required intent for an event.
 pub fn has_intent(intents: &[String], event_type: &str) -> bool {
     match intent_for_event(event_type) {
+        Some(UNGATED) => true,
         Some(required) => intents.iter().any(|i| i == required),
-        None => true, // No intent required = always delivered
+        None => false,
+    }
+}
+
+/// Whether a session may receive an event: it asked for the event's intent,
+/// and admin events only ever reach instance admins.
+pub fn may_receive(intents: &[String], is_admin: bool, event_type: &str) -> bool {
+    if intent_for_event(event_type) == Some(ADMIN_INTENT) && !is_admin {
+        return false;
+    }
+    has_intent(intents, event_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_event_names_a_real_intent() {
+        for event_type in EVENT_TYPES {
+            let intent = intent_for_event(event_type).unwrap();
+            assert!(
+                intent == UNGATED || ALL_INTENTS.contains(&intent),
+                "{event_type}: {intent}"
+            );
+        }
+        assert!(!ALL_INTENTS.contains(&UNGATED));
+    }
+
+    #[test]
+    fn unregistered_events_are_never_delivered() {
+        let everything: Vec<String> = ALL_INTENTS.iter().map(|i| i.to_string()).collect();
+        assert_eq!(intent_for_event("space.unheard_of"), None);
+        assert!(!may_receive(&everything, true, "space.unheard_of"));
+        assert!(may_receive(&[], false, "ready"));
+        assert!(!may_receive(&everything, false, "admin.report_update"));
+        assert!(may_receive(&everything, true, "admin.report_update"));
     }
 }
@@ -1,214 +1,242 @@
+#[cfg(feature = "redis")]
+pub mod cluster;
+pub mod compress;
 pub mod dispatcher;
 pub mod events;
 pub mod heartbeat;
 pub mod intents;
+pub mod msgpack;
 pub mod session;
 
-use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade};
 use axum::extract::State;
+use axum::http::{Extensions, HeaderMap};
 use axum::response::Response;
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
 use std::collections::HashSet;
-use tokio::sync::mpsc;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc};
 
 use crate::db;
 use crate::middleware::auth as auth_resolve;
+use crate::middleware::ip_allowlist::{self, client_ip};
 use crate::routes;
 use crate::state::AppState;
+use compress::ZlibStream;
 use events::{
-    GatewayBroadcast, GatewayMessage, IdentifyData, PresenceUpdateData, VoiceStateUpdateData,
+    Capabilities, GatewayBroadcast, GatewayMessage, IdentifyData, LoginTicketData,
+    PresenceUpdateData, RequestMembersData, ResumeData, SubscribeSpacesData, VoiceStateUpdateData,
 };
 use heartbeat::{HEARTBEAT_INTERVAL, HEARTBEAT_TIMEOUT};
-use session::GatewaySession;
+use session::{
+    GatewaySession, ReplayBuffer, ResumeHandoff, ResumeRequest, REPLAY_BUFFER_SIZE, RESUME_WINDOW,
+};
 
-pub async fn ws_upgrade(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+pub async fn ws_upgrade(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    extensions: Extensions,
+) -> Response {
+    let ip = client_ip(&headers, &extensions);
+    ws.on_upgrade(move |socket| handle_socket(socket, state, ip))
 }
 
-async fn handle_socket(socket: WebSocket, state: AppState) {
-    let (mut ws_sink, mut ws_stream) = socket.split();
+/// Returns a fresh compression context if the client asked for a supported
+/// transport compression, either per connection or as a session capability.
+/// Unknown values fall back to plain JSON text frames.
+fn negotiate_compression(
+    requested: Option<&str>,
+    capabilities: &Capabilities,
+) -> Option<ZlibStream> {
+    (capabilities.supports_compression || requested == Some(compress::ZLIB_STREAM))
+        .then(ZlibStream::new)
+}
 
-    // Send HELLO
-    let hello = serde_json::json!({
-        "op": events::opcode::HELLO,
-        "data": {
-            "heartbeat_interval": HEARTBEAT_INTERVAL.as_millis() as u64
-        }
-    });
-    if ws_sink
-        .send(Message::Text(hello.to_string().into()))
-        .await
-        .is_err()
-    {
-        return;
+/// Frames an outgoing payload: JSON text, or one zlib-stream chunk in a binary
+/// frame when compression was negotiated. MessagePack sessions always get
+/// binary frames.
+fn frame(zlib: &mut Option<ZlibStream>, use_msgpack: bool, payload: String) -> Message {
+    if !use_msgpack {
+        return match zlib {
+            // Writing into a Vec can't fail; the text fallback is unreachable.
+            Some(z) => match z.compress(&payload) {
+                Ok(bytes) => Message::Binary(bytes.into()),
+                Err(_) => Message::Text(payload.into()),
+            },
+            None => Message::Text(payload.into()),
+        };
     }
-
-    // Wait for IDENTIFY
-    let session_id;
-    let user_id;
-    let is_bot;
-    let is_admin;
-    let user_intents: Vec<String>;
-    let space_ids: HashSet<String>;
-    let mut muted_channel_ids: HashSet<String>;
-
-    // Channel for sending messages to this client
-    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
-
-    // Give client 30 seconds to identify
-    let identify_timeout = tokio::time::sleep(std::time::Duration::from_secs(30));
-    tokio::pin!(identify_timeout);
-
-    loop {
-        tokio::select! {
-            _ = &mut identify_timeout => {
-                let close = serde_json::json!({
-                    "op": events::opcode::INVALID_SESSION,
-                    "data": { "resumable": false }
-                });
-                let _ = ws_sink.send(Message::Text(close.to_string().into())).await;
-                return;
-            }
-            msg = ws_stream.next() => {
-                match msg {
-                    Some(Ok(Message::Text(text))) => {
-                        if let Ok(gw_msg) = serde_json::from_str::<GatewayMessage>(&text) {
-                            if gw_msg.op == events::opcode::IDENTIFY {
-                                if let Some(data) = gw_msg.data {
-                                    if let Ok(identify) = serde_json::from_value::<IdentifyData>(data) {
-                                        // Resolve token
-                                        let resolved = resolve_token(&state, &identify.token).await;
-                                        match resolved {
-                                            Some(auth) => {
-                                                user_id = auth.user_id;
-                                                is_bot = auth.is_bot;
-                                                is_admin = auth.is_admin;
-                                                user_intents = identify.intents;
-                                                session_id = crate::snowflake::generate();
-
-                                                if auth.is_guest {
-                                                    // Guest: use scoped space only, no mutes
-                                                    space_ids = auth.guest_space_id
-                                                        .into_iter()
-                                                        .collect();
-                                                    muted_channel_ids = HashSet::new();
-                                                } else {
-                                                    // Load user's space memberships
-                                                    space_ids = db::spaces::list_space_ids_for_user(&state.db, &user_id).await
-                                                        .map(|sids| sids.into_iter().collect())
-                                                        .unwrap_or_default();
-
-                                                    muted_channel_ids = db::mutes::list_effective_muted_channel_ids(&state.db, &user_id).await
-                                                        .map(|ids| ids.into_iter().collect())
-                                                        .unwrap_or_default();
-                                                }
-
-                                                break;
-                                            }
-                                            None => {
-                                                let close = serde_json::json!({
-                                                    "op": events::opcode::INVALID_SESSION,
-                                                    "data": { "resumable": false }
-                                                });
-                                                let _ = ws_sink.send(Message::Text(close.to_string().into())).await;
-                                                return;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Some(Ok(Message::Close(_))) | None => return,
-                    _ => {}
-                }
-            }
-        }
+    let bytes = match serde_json::from_str(&payload) {
+        Ok(value) => msgpack::encode(&value),
+        Err(_) => payload.into_bytes(),
+    };
+    match zlib {
+        Some(z) => match z.compress_bytes(&bytes) {
+            Ok(compressed) => Message::Binary(compressed.into()),
+            Err(_) => Message::Binary(bytes.into()),
+        },
+        None => Message::Binary(bytes.into()),
     }
+}
 
-    // Guest sessions: track in-memory, skip presence/relationships
-    let is_guest_session = user_id.starts_with("guest:");
-
-    let presences_json: Vec<serde_json::Value>;
-    let friend_ids: HashSet<String>;
-    let relationships_json: Vec<serde_json::Value>;
-
-    if is_guest_session {
-        presences_json = vec![];
-        friend_ids = HashSet::new();
-        relationships_json = vec![];
-    } else {
-        // Set user presence to online
-        crate::presence::set_presence(&state, &user_id, "online", vec![]);
+/// Wrap coalesced presence updates in one `presence.update_batch` event.
+fn presence_batch_event(pending: &mut Vec<serde_json::Value>) -> serde_json::Value {
+    serde_json::json!({
+        "op": events::opcode::EVENT,
+        "type": "presence.update_batch",
+        "data": { "presences": std::mem::take(pending) }
+    })
+}
 
-        // Collect presences of online members in the user's spaces
-        let mut all_member_ids = std::collections::HashSet::new();
-        for sid in &space_ids {
-            if let Ok(members) = db::spaces::list_member_ids_for_space(&state.db, sid).await {
-                for mid in members {
-                    all_member_ids.insert(mid);
-                }
+/// How long presence updates are held for sessions with
+/// `wants_presence_batching` before they are flushed as one batch.
+pub const PRESENCE_BATCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Members per `space.members_chunk` event.
+pub const MEMBER_CHUNK_SIZE: i64 = 1000;
+
+/// REQUEST_MEMBERS a session may send per [MEMBER_REQUEST_WINDOW]. Each one
+/// scans the space's member list, so requests over the limit are dropped.
+pub const MEMBER_REQUEST_LIMIT: u32 = 5;
+pub const MEMBER_REQUEST_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Answers a REQUEST_MEMBERS by streaming the matching members of a space to
+/// this session as `space.members_chunk` events. Runs off the connection task
+/// so a large space doesn't stall heartbeats; chunks are queued on the
+/// session's direct channel like other per-session replies.
+///
+/// With [member_list_v2], each chunk carries `users` and the online
+/// `presences` of its members as flat lists instead of a `user` per member.
+async fn send_member_chunks(
+    state: AppState,
+    tx: mpsc::UnboundedSender<String>,
+    req: RequestMembersData,
+    member_list_v2: bool,
+) {
+    let prefix = req.query.as_deref().filter(|q| !q.is_empty());
+    let total = match db::members::count_members_matching(&state.db, &req.space_id, prefix).await {
+        Ok(n) => n,
+        Err(_) => return,
+    };
+    let wanted = match req.limit {
+        Some(limit) if limit > 0 => total.min(limit),
+        _ => total,
+    };
+    // Always answer with at least one (possibly empty) chunk so the client
+    // knows the request finished.
+    let chunk_count = ((wanted + MEMBER_CHUNK_SIZE - 1) / MEMBER_CHUNK_SIZE).max(1);
+
+    let mut after: Option<String> = None;
+    let mut sent = 0i64;
+    for chunk_index in 0..chunk_count {
+        let page_size = (wanted - sent).clamp(0, MEMBER_CHUNK_SIZE);
+        let rows = if page_size > 0 {
+            match db::members::list_members_matching(
+                &state.db,
+                &req.space_id,
+                prefix,
+                after.as_deref(),
+                page_size,
+            )
+            .await
+            {
+                Ok(rows) => rows,
+                Err(_) => return,
             }
+        } else {
+            Vec::new()
+        };
+        sent += rows.len() as i64;
+        // If members left since the count, later pages come back empty; keep
+        // the cursor so they stay empty rather than restarting from the top.
+        if let Some(last) = rows.last() {
+            after = Some(last.user_id.clone());
         }
-        let presences = crate::presence::get_space_presences(&state, &all_member_ids);
-        presences_json = presences
-            .iter()
-            .map(|p| serde_json::to_value(p).unwrap_or_default())
-            .collect();
-
-        // Load this user's relationships for READY payload and friend set for presence routing
-        friend_ids = db::relationships::get_friend_ids(&state.db, &user_id)
-            .await
-            .unwrap_or_default()
-            .into_iter()
-            .collect();
 
-        relationships_json = db::relationships::list_relationships(&state.db, &user_id)
+        let ids: Vec<String> = rows.iter().map(|r| r.user_id.clone()).collect();
+        let mut role_ids = db::members::get_role_ids_for_members(&state.db, &req.space_id, &ids)
             .await
-            .unwrap_or_default()
-            .iter()
-            .map(|r| {
-                let display = r
-                    .target_display_name
-                    .clone()
-                    .unwrap_or_else(|| r.target_username.clone());
-                serde_json::json!({
-                    "id": r.target_user_id,
-                    "user": {
-                        "id": r.target_user_id,
-                        "username": r.target_username,
-                        "display_name": display,
-                        "avatar": r.target_avatar
-                    },
-                    "type": r.rel_type,
-                    "since": r.created_at
+            .unwrap_or_default();
+        let users: std::collections::HashMap<String, serde_json::Value> =
+            db::users::get_users_by_ids(&state.db, &ids)
+                .await
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|u| {
+                    let id = u.id.clone();
+                    serde_json::to_value(crate::models::user::PublicUser::from(u))
+                        .ok()
+                        .map(|json| (id, json))
                 })
+                .collect();
+        let members: Vec<serde_json::Value> = rows
+            .iter()
+            .map(|row| {
+                let roles = role_ids.remove(&row.user_id).unwrap_or_default();
+                let mut member = routes::members::member_row_to_json(row, &roles);
+                if !member_list_v2 {
+                    if let Some(user) = users.get(&row.user_id) {
+                        member["user"] = user.clone();
+                    }
+                }
+                member
             })
             .collect();
-    }
 
-    // Fetch full initial state for the READY payload
-    let current_user_json = if !is_guest_session {
-        db::users::get_user(&state.db, &user_id)
-            .await
-            .ok()
-            .map(|u| serde_json::to_value(&u).unwrap_or_default())
-    } else {
-        None
-    };
+        let mut data = serde_json::json!({
+            "space_id": req.space_id,
+            "members": members,
+            "chunk_index": chunk_index,
+            "chunk_count": chunk_count,
+            "nonce": req.nonce,
+        });
+        if member_list_v2 {
+            let id_set: HashSet<String> = ids.iter().cloned().collect();
+            let presences: Vec<serde_json::Value> =
+                crate::presence::get_space_presences(&state, &id_set)
+                    .await
+                    .iter()
+                    .map(|p| serde_json::to_value(p).unwrap_or_default())
+                    .collect();
+            data["users"] = serde_json::json!(ids
+                .iter()
+                .filter_map(|id| users.get(id))
+                .collect::<Vec<_>>());
+            data["presences"] = serde_json::json!(presences);
+        }
+        let event = serde_json::json!({
+            "op": events::opcode::EVENT,
+            "type": "space.members_chunk",
+            "data": data
+        });
+        if tx.send(event.to_string()).is_err() {
+            return;
+        }
+    }
+}
 
-    let mut spaces_json: Vec<serde_json::Value> = Vec::new();
-    let mut all_channels_json: Vec<serde_json::Value> = Vec::new();
-    let mut all_members_json: Vec<serde_json::Value> = Vec::new();
-    let mut all_roles_json: Vec<serde_json::Value> = Vec::new();
-    let mut all_voice_states_json: Vec<serde_json::Value> = Vec::new();
-    let mut all_users_json: Vec<serde_json::Value> = Vec::new();
-    let mut seen_user_ids: HashSet<String> = HashSet::new();
+/// Space data for READY (every space) or a `space.sync` (one space), with
+/// member users deduplicated across spaces.
+#[derive(Default)]
+struct SpaceSnapshot {
+    spaces: Vec<serde_json::Value>,
+    channels: Vec<serde_json::Value>,
+    members: Vec<serde_json::Value>,
+    roles: Vec<serde_json::Value>,
+    users: Vec<serde_json::Value>,
+    voice_states: Vec<serde_json::Value>,
+    seen_user_ids: HashSet<String>,
+}
 
-    for sid in &space_ids {
+impl SpaceSnapshot {
+    async fn add_space(&mut self, state: &AppState, sid: &str) {
         // Space
         if let Ok(space_row) = db::spaces::get_space_row(&state.db, sid).await {
-            spaces_json.push(serde_json::to_value(&space_row).unwrap_or_default());
+            self.spaces
+                .push(serde_json::to_value(&space_row).unwrap_or_default());
         }
 
         // Channels (with permission overwrites)
@@ -216,7 +244,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
             if let Ok(channels) =
                 routes::spaces::channels_to_json_async(&state.db, &channel_rows).await
             {
-                all_channels_json.extend(channels);
+                self.channels.extend(channels);
             }
         }
 
@@ -226,7 +254,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                 .iter()
                 .map(routes::roles::role_row_to_json)
                 .collect();
-            all_roles_json.extend(roles);
+            self.roles.extend(roles);
         }
 
         // Members (all pages, with embedded user objects)
@@ -244,20 +272,31 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                 rows.clone()
             };
 
+            let user_ids: Vec<String> = page.iter().map(|m| m.user_id.clone()).collect();
+            let role_ids = db::members::get_role_ids_for_members(&state.db, sid, &user_ids)
+                .await
+                .unwrap_or_default();
             for member_row in &page {
-                let role_ids =
-                    db::members::get_member_role_ids(&state.db, sid, &member_row.user_id)
-                        .await
-                        .unwrap_or_default();
-                let member_json = routes::members::member_row_to_json(member_row, &role_ids);
-                all_members_json.push(member_json);
-
-                // Collect unique user objects
-                if !seen_user_ids.contains(&member_row.user_id) {
-                    if let Ok(user) = db::users::get_user(&state.db, &member_row.user_id).await {
-                        all_users_json.push(serde_json::to_value(&user).unwrap_or_default());
-                        seen_user_ids.insert(member_row.user_id.clone());
-                    }
+                let member_json = routes::members::member_row_to_json(
+                    member_row,
+                    role_ids
+                        .get(&member_row.user_id)
+                        .map(Vec::as_slice)
+                        .unwrap_or(&[]),
+                );
+                self.members.push(member_json);
+            }
+
+            // Collect unique user objects
+            let unseen: Vec<String> = user_ids
+                .into_iter()
+                .filter(|id| !self.seen_user_ids.contains(id))
+                .collect();
+            if let Ok(users) = db::users::get_users_by_ids(&state.db, &unseen).await {
+                for user in users {
+                    self.seen_user_ids.insert(user.id.clone());
+                    self.users
+                        .push(serde_json::to_value(&user).unwrap_or_default());
                 }
             }
 
@@ -269,163 +308,351 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
         }
 
         // Voice states for this space
-        let voice_states = crate::voice::state::get_space_voice_states(&state, sid);
+        let voice_states = crate::voice::state::get_space_voice_states(state, sid);
         for vs in &voice_states {
-            all_voice_states_json.push(serde_json::to_value(vs).unwrap_or_default());
+            self.voice_states
+                .push(serde_json::to_value(vs).unwrap_or_default());
         }
     }
+}
 
-    // DM channels (with recipients)
-    let dm_channels_json: Vec<serde_json::Value> = if !is_guest_session {
-        match db::users::get_user_dm_channels(&state.db, &user_id).await {
-            Ok(dm_rows) => {
-                let mut dms = Vec::new();
-                for row in &dm_rows {
-                    dms.push(routes::spaces::channel_row_to_json_pub(&state.db, row).await);
-                }
-                dms
+/// Answers a SUBSCRIBE_SPACES from a `lazy_spaces` session with one
+/// `space.sync` per requested space: its channels, roles, members, users,
+/// voice states and the presences of its online members. Runs off the
+/// connection task like member chunks.
+async fn send_space_syncs(state: AppState, tx: mpsc::UnboundedSender<String>, ids: Vec<String>) {
+    for sid in ids {
+        let mut snapshot = SpaceSnapshot::default();
+        snapshot.add_space(&state, &sid).await;
+        let Some(space) = snapshot.spaces.pop() else {
+            continue;
+        };
+        let member_ids: HashSet<String> = db::spaces::list_member_ids_for_space(&state.db, &sid)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        let presences: Vec<serde_json::Value> =
+            crate::presence::get_space_presences(&state, &member_ids)
+                .await
+                .iter()
+                .map(|p| serde_json::to_value(p).unwrap_or_default())
+                .collect();
+        let event = serde_json::json!({
+            "op": events::opcode::EVENT,
+            "type": "space.sync",
+            "data": {
+                "space_id": sid,
+                "space": space,
+                "channels": snapshot.channels,
+                "roles": snapshot.roles,
+                "members": snapshot.members,
+                "users": snapshot.users,
+                "voice_states": snapshot.voice_states,
+                "presences": presences,
             }
-            Err(_) => vec![],
+        });
+        if tx.send(event.to_string()).is_err() {
+            return;
         }
-    } else {
-        vec![]
-    };
-
-    // Muted channel IDs (already loaded as muted_channel_ids HashSet)
-    let mutes_json: Vec<serde_json::Value> = muted_channel_ids
-        .iter()
-        .map(|cid| serde_json::json!({ "channel_id": cid }))
-        .collect();
+    }
+}
 
-    // Unread states
-    let unread_json: Vec<serde_json::Value> = if !is_guest_session {
-        db::read_states::get_unread_channels(&state.db, &user_id)
-            .await
-            .map(|entries| {
-                entries
-                    .iter()
-                    .map(|e| serde_json::to_value(e).unwrap_or_default())
-                    .collect()
-            })
-            .unwrap_or_default()
-    } else {
-        vec![]
-    };
+async fn handle_socket(socket: WebSocket, state: AppState, ip: Option<IpAddr>) {
+    let (mut ws_sink, mut ws_stream) = socket.split();
 
-    // Send READY event
-    let motd = state.settings.load().motd.clone();
-    let ready = serde_json::json!({
-        "op": events::opcode::EVENT,
-        "seq": 1,
-        "type": "ready",
+    // Send HELLO
+    let hello = serde_json::json!({
+        "op": events::opcode::HELLO,
         "data": {
-            "session_id": session_id,
-            "user_id": user_id,
-            "user": current_user_json,
-            "spaces": spaces_json,
-            "channels": all_channels_json,
-            "members": all_members_json,
-            "roles": all_roles_json,
-            "users": all_users_json,
-            "voice_states": all_voice_states_json,
-            "dm_channels": dm_channels_json,
-            "mutes": mutes_json,
-            "unread": unread_json,
-            "presences": presences_json,
-            "relationships": relationships_json,
-            "is_guest": is_guest_session,
-            "api_version": "v1",
-            "server_version": env!("CARGO_PKG_VERSION"),
-            "motd": motd
+            "heartbeat_interval": HEARTBEAT_INTERVAL.as_millis() as u64
         }
     });
     if ws_sink
-        .send(Message::Text(ready.to_string().into()))
+        .send(Message::Text(hello.to_string().into()))
         .await
         .is_err()
     {
         return;
     }
 
-    // Register session with dispatcher
-    let session = GatewaySession {
-        session_id: session_id.clone(),
-        user_id: user_id.clone(),
-        intents: user_intents.clone(),
-        space_ids: space_ids.clone(),
-        sequence: 1,
-        tx: tx.clone(),
-    };
+    // Wait for IDENTIFY
+    let session_id;
+    let user_id;
+    let is_bot;
+    let is_admin;
+    let login_session_id: Option<String>;
+    let user_intents: Vec<String>;
+    let space_ids: HashSet<String>;
+    let mut muted_channel_ids: HashSet<String>;
+    let mut everyone_suppressed_space_ids: HashSet<String>;
+    let mut blocked_user_ids: HashSet<String>;
+    // Spaces a `lazy_spaces` session has asked for with SUBSCRIBE_SPACES;
+    // typing in the others is not sent to it.
+    let mut synced_space_ids: HashSet<String> = HashSet::new();
+    // Set when the client RESUMEs an existing session instead of identifying.
+    let mut resumed: Option<ResumeHandoff> = None;
+    // Transport compression negotiated in IDENTIFY/RESUME; applies to every
+    // frame the server sends after the handshake.
+    let mut zlib: Option<ZlibStream>;
+    // Client capabilities from IDENTIFY, carried over on RESUME.
+    let capabilities: Capabilities;
 
-    if let Some(ref dispatcher) = *state.dispatcher.read().await {
-        dispatcher.register_session(session);
-    }
+    // Channel for sending messages to this client
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
 
-    // Guest connect: broadcast anonymous_count_updated
-    if is_guest_session {
-        if let Some(ref gtx) = *state.gateway_tx.read().await {
-            for sid in &space_ids {
-                let count = state.guest_counts.get(sid).map(|c| *c).unwrap_or(0);
-                let event = serde_json::json!({
-                    "op": events::opcode::EVENT,
-                    "type": "anonymous_count_updated",
-                    "data": { "count": count, "space_id": sid }
-                });
-                let _ = gtx.send(GatewayBroadcast {
-                    space_id: Some(sid.clone()),
-                    target_user_ids: None,
-                    event,
-                    intent: "members".to_string(),
-                });
+    // Give client 30 seconds to identify
+    let identify_timeout = tokio::time::sleep(std::time::Duration::from_secs(30));
+    tokio::pin!(identify_timeout);
+
+    loop {
+        tokio::select! {
+            _ = &mut identify_timeout => {
+                send_invalid_session(&mut ws_sink).await;
+                send_close(&mut ws_sink, events::close_code::NOT_AUTHENTICATED, "identify_timeout").await;
+                return;
+            }
+            msg = ws_stream.next() => {
+                match msg {
+                    Some(Ok(Message::Text(text))) => {
+                        let Ok(gw_msg) = serde_json::from_str::<GatewayMessage>(&text) else {
+                            send_close(&mut ws_sink, events::close_code::DECODE_ERROR, "decode_error").await;
+                            return;
+                        };
+                        if gw_msg.op == events::opcode::IDENTIFY {
+                            let Some(identify) = gw_msg.data.and_then(|d| serde_json::from_value::<IdentifyData>(d).ok()) else {
+                                send_close(&mut ws_sink, events::close_code::DECODE_ERROR, "decode_error").await;
+                                return;
+                            };
+                            if let Some(bad) = identify.intents.iter().find(|i| !intents::ALL_INTENTS.contains(&i.as_str())) {
+                                tracing::debug!("gateway IDENTIFY with unknown intent {bad}");
+                                send_close(&mut ws_sink, events::close_code::INVALID_INTENT, "invalid_intent").await;
+                                return;
+                            }
+                            // Resolve token
+                            let resolved = resolve_token(&state, &identify.token).await;
+                            match resolved {
+                                Some(auth) if auth.is_bot && !bot_ip_allowed(&state, &identify.token, ip).await => {
+                                    send_close(&mut ws_sink, events::close_code::IP_NOT_ALLOWED, "ip_not_allowed").await;
+                                    return;
+                                }
+                                Some(auth) if !auth.is_admin && identify.intents.iter().any(|i| i == intents::ADMIN_INTENT) => {
+                                    send_close(&mut ws_sink, events::close_code::DISALLOWED_INTENT, "disallowed_intent").await;
+                                    return;
+                                }
+                                Some(auth) => {
+                                    user_id = auth.user_id;
+                                    is_bot = auth.is_bot;
+                                    is_admin = auth.is_admin;
+                                    login_session_id = auth.login_session_id;
+                                    user_intents = identify.intents;
+                                    session_id = crate::snowflake::generate();
+                                    capabilities = identify.capabilities;
+                                    zlib = negotiate_compression(identify.compress.as_deref(), &capabilities);
+
+                                    if auth.is_guest {
+                                        // Guest: use scoped space only, no mutes
+                                        space_ids = auth.guest_space_id
+                                            .into_iter()
+                                            .collect();
+                                        muted_channel_ids = HashSet::new();
+                                        everyone_suppressed_space_ids = HashSet::new();
+                                        blocked_user_ids = HashSet::new();
+                                    } else {
+                                        // Load user's space memberships
+                                        space_ids = db::spaces::list_space_ids_for_user(&state.db, &user_id).await
+                                            .map(|sids| sids.into_iter().collect())
+                                            .unwrap_or_default();
+
+                                        muted_channel_ids = db::mutes::list_effective_muted_channel_ids(&state.db, &user_id).await
+                                            .map(|ids| ids.into_iter().collect())
+                                            .unwrap_or_default();
+
+                                        everyone_suppressed_space_ids = db::mutes::list_everyone_suppressions(&state.db, &user_id).await
+                                            .map(|ids| ids.into_iter().collect())
+                                            .unwrap_or_default();
+
+                                        blocked_user_ids = db::relationships::get_blocked_ids(&state.db, &user_id).await
+                                            .map(|ids| ids.into_iter().collect())
+                                            .unwrap_or_default();
+                                    }
+
+                                    break;
+                                }
+                                None => {
+                                    send_invalid_session(&mut ws_sink).await;
+                                    send_close(&mut ws_sink, events::close_code::AUTH_FAILED, "authentication_failed").await;
+                                    return;
+                                }
+                            }
+                        } else if gw_msg.op == events::opcode::RESUME {
+                            let Some(resume) = gw_msg.data.and_then(|d| serde_json::from_value::<ResumeData>(d).ok()) else {
+                                send_close(&mut ws_sink, events::close_code::DECODE_ERROR, "decode_error").await;
+                                return;
+                            };
+                            match resume_session(&state, &resume, ip).await {
+                                Ok((auth, handoff)) => {
+                                    user_id = auth.user_id;
+                                    is_bot = auth.is_bot;
+                                    is_admin = auth.is_admin;
+                                    login_session_id = auth.login_session_id;
+                                    user_intents = handoff.intents.clone();
+                                    session_id = resume.session_id;
+                                    capabilities = handoff.capabilities;
+                                    zlib = negotiate_compression(resume.compress.as_deref(), &capabilities);
+                                    space_ids = handoff.space_ids.clone();
+                                    muted_channel_ids = handoff.muted_channel_ids.clone();
+                                    everyone_suppressed_space_ids = handoff.everyone_suppressed_space_ids.clone();
+                                    blocked_user_ids = handoff.blocked_user_ids.clone();
+                                    synced_space_ids = handoff.synced_space_ids.clone();
+                                    resumed = Some(handoff);
+                                    break;
+                                }
+                                Err((code, reason)) => {
+                                    send_invalid_session(&mut ws_sink).await;
+                                    send_close(&mut ws_sink, code, reason).await;
+                                    return;
+                                }
+                            }
+                        } else if gw_msg.op == events::opcode::LOGIN_TICKET {
+                            let Some(login) = gw_msg.data.and_then(|d| serde_json::from_value::<LoginTicketData>(d).ok()) else {
+                                send_close(&mut ws_sink, events::close_code::DECODE_ERROR, "decode_error").await;
+                                return;
+                            };
+                            wait_for_login_ticket(&state, &mut ws_sink, &mut ws_stream, login).await;
+                            return;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None => return,
+                    _ => {}
+                }
             }
         }
     }
 
-    // Broadcast presence.update (online) to all spaces (skip for guests)
-    if !is_guest_session {
-        if let Some(ref gtx) = *state.gateway_tx.read().await {
-            let presence_data = serde_json::json!({
-                "user_id": user_id,
-                "status": "online",
-                "client_status": { "desktop": "online" },
-                "activities": []
-            });
-            for sid in &space_ids {
-                let event = serde_json::json!({
-                    "op": events::opcode::EVENT,
-                    "type": "presence.update",
-                    "data": presence_data
-                });
-                let _ = gtx.send(GatewayBroadcast {
-                    space_id: Some(sid.clone()),
-                    target_user_ids: None,
-                    event,
-                    intent: "presences".to_string(),
-                });
-            }
-            // Also broadcast to friends who may not share any space
-            if !friend_ids.is_empty() {
-                let event = serde_json::json!({
-                    "op": events::opcode::EVENT,
-                    "type": "presence.update",
-                    "data": presence_data
-                });
-                let _ = gtx.send(GatewayBroadcast {
-                    space_id: None,
-                    target_user_ids: Some(friend_ids.iter().cloned().collect()),
-                    event,
-                    intent: "presences".to_string(),
-                });
+    // Guest sessions: track in-memory, skip presence/relationships
+    let is_guest_session = user_id.starts_with("guest:");
+
+    let (resume_tx, mut resume_rx) = mpsc::unbounded_channel::<ResumeRequest>();
+    let friend_ids: HashSet<String>;
+    let replay: Arc<Mutex<ReplayBuffer>>;
+    let mut broadcast_rx: Option<broadcast::Receiver<GatewayBroadcast>>;
+    let mut seq: u64;
+    // Set when the socket drops uncleanly: the session keeps routing events
+    // into its replay buffer until this deadline so the client can RESUME.
+    let mut detached_until: Option<tokio::time::Instant> = None;
+
+    if let Some(handoff) = resumed {
+        friend_ids = handoff.friend_ids;
+        replay = handoff.replay;
+        broadcast_rx = handoff.broadcast_rx;
+        seq = handoff.sequence;
+
+        // Re-register under the same session_id, now owned by this connection.
+        let session = GatewaySession {
+            session_id: session_id.clone(),
+            user_id: user_id.clone(),
+            intents: user_intents.clone(),
+            space_ids: space_ids.clone(),
+            sequence: seq,
+            tx: tx.clone(),
+            replay: replay.clone(),
+            resume_tx: resume_tx.clone(),
+        };
+        if let Some(ref dispatcher) = *state.dispatcher.read().await {
+            dispatcher.register_session(session);
+        }
+
+        // Replay what the client missed, then confirm the resume.
+        let replayed = handoff.missed.len();
+        let resumed_event = serde_json::json!({
+            "op": events::opcode::EVENT,
+            "type": "resumed",
+            "data": { "session_id": session_id, "replayed": replayed }
+        });
+        for msg in handoff
+            .missed
+            .into_iter()
+            .chain(std::iter::once(resumed_event.to_string()))
+        {
+            if ws_sink
+                .send(frame(&mut zlib, capabilities.supports_msgpack, msg))
+                .await
+                .is_err()
+            {
+                detached_until = Some(tokio::time::Instant::now() + RESUME_WINDOW);
+                break;
             }
         }
-    }
+        for msg in handoff.pending {
+            let _ = tx.send(msg);
+        }
+    } else {
+        let ready_fut = build_ready(
+            &state,
+            &session_id,
+            &user_id,
+            &space_ids,
+            &muted_channel_ids,
+            is_guest_session,
+            capabilities.lazy_spaces,
+        );
+        // In test mode READY reports its query count, like X-Query-Count
+        // does for HTTP requests.
+        let (mut ready, friends) = if state.test_mode {
+            let ((mut ready, friends), queries) = crate::db::instrument::record(ready_fut).await;
+            ready["data"]["_trace"] = serde_json::json!({ "queries": queries.count() });
+            (ready, friends)
+        } else {
+            ready_fut.await
+        };
+        friend_ids = friends;
+        let negotiated = Capabilities {
+            supports_compression: zlib.is_some(),
+            ..capabilities
+        };
+        ready["data"]["capabilities"] = serde_json::json!(negotiated);
+        if ws_sink
+            .send(frame(
+                &mut zlib,
+                capabilities.supports_msgpack,
+                ready.to_string(),
+            ))
+            .await
+            .is_err()
+        {
+            return;
+        }
 
-    // Subscribe to broadcasts
-    let mut broadcast_rx = (*state.dispatcher.read().await)
-        .as_ref()
-        .map(|dispatcher| dispatcher.subscribe());
+        // Register session with dispatcher
+        replay = Arc::new(Mutex::new(ReplayBuffer::new(REPLAY_BUFFER_SIZE, 1)));
+        let session = GatewaySession {
+            session_id: session_id.clone(),
+            user_id: user_id.clone(),
+            intents: user_intents.clone(),
+            space_ids: space_ids.clone(),
+            sequence: 1,
+            tx: tx.clone(),
+            replay: replay.clone(),
+            resume_tx: resume_tx.clone(),
+        };
+
+        if let Some(ref dispatcher) = *state.dispatcher.read().await {
+            dispatcher.register_session(session);
+        }
+        if !is_guest_session {
+            crate::presence::open_session(&state, &user_id, &session_id).await;
+        }
+
+        announce_connect(&state, &user_id, &space_ids, &friend_ids, is_guest_session).await;
+
+        // Subscribe to broadcasts
+        broadcast_rx = (*state.dispatcher.read().await)
+            .as_ref()
+            .map(|dispatcher| dispatcher.subscribe());
+        seq = 1;
+    }
 
-    let mut seq: u64 = 1;
     let mut last_heartbeat = tokio::time::Instant::now();
     let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
 
@@ -434,15 +661,131 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     const WS_RATE_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
     let mut ws_msg_count: u32 = 0;
     let mut ws_rate_window_start = tokio::time::Instant::now();
+    let mut member_request_count: u32 = 0;
+    let mut member_request_window_start = tokio::time::Instant::now();
+
+    // True once the session has been handed to a resuming connection; this
+    // task then exits without disconnect cleanup.
+    let mut handed_over = false;
+
+    // Presence updates held for `wants_presence_batching`, latest per user,
+    // and when they are due to be flushed.
+    let mut pending_presences: Vec<serde_json::Value> = Vec::new();
+    let mut presence_flush_at: Option<tokio::time::Instant> = None;
+
+    let mut shutdown_rx = (*state.dispatcher.read().await)
+        .as_ref()
+        .map(|dispatcher| dispatcher.shutdown_signal());
 
     loop {
         tokio::select! {
-            // Outgoing messages from the session channel
-            Some(msg) = rx.recv() => {
-                if ws_sink.send(Message::Text(msg.into())).await.is_err() {
-                    break;
+            // Outgoing messages from the session channel (held while detached)
+            Some(msg) = rx.recv(), if detached_until.is_none() => {
+                if ws_sink.send(frame(&mut zlib, capabilities.supports_msgpack, msg)).await.is_err() {
+                    detached_until = Some(tokio::time::Instant::now() + RESUME_WINDOW);
+                }
+            }
+            // Another connection RESUMEd this session
+            Some(req) = resume_rx.recv() => {
+                let missed = replay.lock().ok().and_then(|r| r.since(req.seq));
+                let Some(mut missed) = missed else {
+                    let _ = req.reply.send(None);
+                    continue;
+                };
+                // Held presences go out with the replay.
+                if !pending_presences.is_empty() {
+                    presence_flush_at = None;
+                    seq += 1;
+                    let mut event = presence_batch_event(&mut pending_presences);
+                    if let Some(obj) = event.as_object_mut() {
+                        obj.insert("seq".to_string(), serde_json::json!(seq));
+                    }
+                    let payload = event.to_string();
+                    if let Ok(mut buf) = replay.lock() {
+                        buf.push(seq, payload.clone());
+                    }
+                    missed.push(payload);
+                }
+                let mut pending = Vec::new();
+                while let Ok(msg) = rx.try_recv() {
+                    pending.push(msg);
+                }
+                let handoff = ResumeHandoff {
+                    broadcast_rx: broadcast_rx.take(),
+                    sequence: seq,
+                    intents: user_intents.clone(),
+                    space_ids: space_ids.clone(),
+                    muted_channel_ids: muted_channel_ids.clone(),
+                    everyone_suppressed_space_ids: everyone_suppressed_space_ids.clone(),
+                    blocked_user_ids: blocked_user_ids.clone(),
+                    friend_ids: friend_ids.clone(),
+                    synced_space_ids: synced_space_ids.clone(),
+                    replay: replay.clone(),
+                    capabilities,
+                    missed,
+                    pending,
+                };
+                match req.reply.send(Some(handoff)) {
+                    Ok(()) => {
+                        if detached_until.is_none() {
+                            let _ = ws_sink.send(Message::Close(None)).await;
+                        }
+                        handed_over = true;
+                        break;
+                    }
+                    // The resuming connection gave up; keep the session.
+                    Err(Some(handoff)) => broadcast_rx = handoff.broadcast_rx,
+                    Err(None) => {}
                 }
             }
+            // Resume window expired
+            _ = async {
+                match detached_until {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending::<()>().await,
+                }
+            } => {
+                break;
+            }
+            // Batched presence updates are due
+            _ = async {
+                match presence_flush_at {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending::<()>().await,
+                }
+            } => {
+                presence_flush_at = None;
+                seq += 1;
+                let mut event = presence_batch_event(&mut pending_presences);
+                if let Some(obj) = event.as_object_mut() {
+                    obj.insert("seq".to_string(), serde_json::json!(seq));
+                }
+                let payload = event.to_string();
+                if let Ok(mut buf) = replay.lock() {
+                    buf.push(seq, payload.clone());
+                }
+                if detached_until.is_none()
+                    && ws_sink.send(frame(&mut zlib, capabilities.supports_msgpack, payload)).await.is_err()
+                {
+                    detached_until = Some(tokio::time::Instant::now() + RESUME_WINDOW);
+                }
+            }
+            // Server shutting down
+            _ = async {
+                // A dropped sender means no shutdown is coming.
+                let shut_down = match shutdown_rx {
+                    Some(ref mut rx) => rx.wait_for(|down| *down).await.is_ok(),
+                    None => false,
+                };
+                if !shut_down {
+                    std::future::pending::<()>().await;
+                }
+            } => {
+                if detached_until.is_none() {
+                    send_close(&mut ws_sink, events::close_code::SERVER_SHUTDOWN, "server_shutdown").await;
+                }
+                break;
+            }
             // Broadcast events
             broadcast = async {
                 if let Some(ref mut rx) = broadcast_rx {
@@ -464,6 +807,24 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                             .and_then(|t| t.as_str())
                             .unwrap_or("");
 
+                        // The login session this connection identified with
+                        // was revoked: sign the connection out for good.
+                        if event_type == "auth_session.revoke" {
+                            let revoked = broadcast.event["data"]["session_ids"]
+                                .as_array()
+                                .is_some_and(|ids| {
+                                    ids.iter().any(|id| id.as_str() == login_session_id.as_deref())
+                                });
+                            if revoked {
+                                if detached_until.is_none() {
+                                    send_invalid_session(&mut ws_sink).await;
+                                    send_close(&mut ws_sink, events::close_code::AUTH_FAILED, "session_revoked").await;
+                                }
+                                break;
+                            }
+                            continue;
+                        }
+
                         // Handle mute list updates from REST API
                         if event_type == "channel_mute.create" || event_type == "channel_mute.delete" {
                             muted_channel_ids = db::mutes::list_effective_muted_channel_ids(&state.db, &user_id).await
@@ -472,40 +833,113 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                             continue;
                         }
 
-                        // Suppress message/typing events for muted channels
-                        if event_type.starts_with("message.") || event_type.starts_with("typing.") {
+                        // Batched mute/collapse sync: refresh the filter, then deliver
+                        if event_type == "channel_states.update" || event_type == "notification_settings.update" {
+                            muted_channel_ids = db::mutes::list_effective_muted_channel_ids(&state.db, &user_id).await
+                                .map(|ids| ids.into_iter().collect())
+                                .unwrap_or_default();
+                        }
+
+                        if event_type == "everyone_suppression.update" || event_type == "notification_settings.update" {
+                            everyone_suppressed_space_ids = db::mutes::list_everyone_suppressions(&state.db, &user_id).await
+                                .map(|ids| ids.into_iter().collect())
+                                .unwrap_or_default();
+                        }
+
+                        // Keep the block list current; the event itself still goes out
+                        if event_type == "relationship.add" || event_type == "relationship.remove" {
+                            blocked_user_ids = db::relationships::get_blocked_ids(&state.db, &user_id).await
+                                .map(|ids| ids.into_iter().collect())
+                                .unwrap_or_default();
+                        }
+
+                        let mentioned = event_type == "message.create"
+                            && mentions_user(&user_id, &broadcast, &everyone_suppressed_space_ids);
+
+                        // Suppress message/typing events for muted channels,
+                        // except new messages that mention this user
+                        if (event_type.starts_with("message.") || event_type.starts_with("typing.")) && !mentioned {
                             let channel_id = broadcast.event.get("data")
                                 .and_then(|d| d.get("channel_id"))
                                 .and_then(|c| c.as_str())
                                 .unwrap_or("");
                             if !channel_id.is_empty() && muted_channel_ids.contains(channel_id) {
+                                if event_type == "typing.start" {
+                                    state.typing.stats.record_dropped();
+                                }
+                                continue;
+                            }
+                        }
+
+                        // Nobody needs their own typing echoed back, and lazy
+                        // sessions only want it for spaces they have open.
+                        if event_type == "typing.start" {
+                            let own = broadcast.event["data"]["user_id"].as_str() == Some(user_id.as_str());
+                            let unsynced = capabilities.lazy_spaces
+                                && broadcast.space_id.as_ref().is_some_and(|sid| !synced_space_ids.contains(sid));
+                            if own || unsynced {
+                                state.typing.stats.record_dropped();
                                 continue;
                             }
                         }
 
                         // Check intent
-                        if intents::has_intent(&user_intents, event_type) {
-                            seq += 1;
+                        if intents::may_receive(&user_intents, is_admin, event_type) {
+                            if capabilities.wants_presence_batching && event_type == "presence.update" {
+                                if let Some(data) = broadcast.event.get("data") {
+                                    pending_presences.retain(|p| p.get("user_id") != data.get("user_id"));
+                                    pending_presences.push(data.clone());
+                                    presence_flush_at.get_or_insert_with(|| {
+                                        tokio::time::Instant::now() + PRESENCE_BATCH_INTERVAL
+                                    });
+                                }
+                                continue;
+                            }
                             let mut event = broadcast.event.clone();
+                            // Messages from blocked users are flagged, or
+                            // dropped for `omit_blocked_messages` sessions
+                            if event_type == "message.create" || event_type == "message.update" {
+                                let author_id = event.get("data")
+                                    .and_then(|d| d.get("author_id"))
+                                    .and_then(|a| a.as_str())
+                                    .unwrap_or("");
+                                if blocked_user_ids.contains(author_id) {
+                                    if capabilities.omit_blocked_messages {
+                                        continue;
+                                    }
+                                    event["data"]["blocked"] = serde_json::json!(true);
+                                }
+                            }
+                            if mentioned {
+                                event["data"]["mentioned"] = serde_json::json!(true);
+                            }
+                            seq += 1;
                             if let Some(obj) = event.as_object_mut() {
                                 obj.insert("seq".to_string(), serde_json::json!(seq));
                             }
-                            if ws_sink.send(Message::Text(event.to_string().into())).await.is_err() {
-                                break;
+                            let payload = event.to_string();
+                            if let Ok(mut buf) = replay.lock() {
+                                buf.push(seq, payload.clone());
+                            }
+                            if detached_until.is_none()
+                                && ws_sink.send(frame(&mut zlib, capabilities.supports_msgpack, payload)).await.is_err()
+                            {
+                                detached_until = Some(tokio::time::Instant::now() + RESUME_WINDOW);
                             }
                         }
                     }
                 }
             }
             // Heartbeat check
-            _ = heartbeat_interval.tick() => {
+            _ = heartbeat_interval.tick(), if detached_until.is_none() => {
                 if last_heartbeat.elapsed() > HEARTBEAT_TIMEOUT {
-                    // Session timed out
-                    break;
+                    // Session timed out; keep it resumable
+                    send_close(&mut ws_sink, events::close_code::SESSION_TIMED_OUT, "session_timed_out").await;
+                    detached_until = Some(tokio::time::Instant::now() + RESUME_WINDOW);
                 }
             }
             // Incoming messages
-            msg = ws_stream.next() => {
+            msg = ws_stream.next(), if detached_until.is_none() => {
                 match msg {
                     Some(Ok(Message::Text(text))) => {
                         // Per-connection rate limiting
@@ -515,7 +949,10 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                         }
                         ws_msg_count += 1;
                         if ws_msg_count > WS_RATE_LIMIT {
-                            // Drop excess messages silently to prevent flooding
+                            // Flooding clients are disconnected; the session
+                            // stays resumable.
+                            send_close(&mut ws_sink, events::close_code::RATE_LIMITED, "rate_limited").await;
+                            detached_until = Some(tokio::time::Instant::now() + RESUME_WINDOW);
                             continue;
                         }
 
@@ -526,8 +963,8 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                     let ack = serde_json::json!({
                                         "op": events::opcode::HEARTBEAT_ACK
                                     });
-                                    if ws_sink.send(Message::Text(ack.to_string().into())).await.is_err() {
-                                        break;
+                                    if ws_sink.send(frame(&mut zlib, capabilities.supports_msgpack, ack.to_string())).await.is_err() {
+                                        detached_until = Some(tokio::time::Instant::now() + RESUME_WINDOW);
                                     }
                                 }
                                 op if op == events::opcode::PRESENCE_UPDATE => {
@@ -543,7 +980,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                 Some(a) => vec![a],
                                                 None => vec![],
                                             };
-                                            crate::presence::set_presence(&state, &user_id, status, activities.clone());
+                                            crate::presence::set_presence(&state, &user_id, status, activities.clone()).await;
 
                                             // Broadcast to all spaces and to friends
                                             if let Some(ref gtx) = *state.gateway_tx.read().await {
@@ -565,6 +1002,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                         target_user_ids: None,
                                                         event,
                                                         intent: "presences".to_string(),
+                                                        role_mention_user_ids: None,
                                                     });
                                                 }
                                                 // Also broadcast to friends not sharing any space
@@ -579,6 +1017,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                         target_user_ids: Some(friend_ids.iter().cloned().collect()),
                                                         event,
                                                         intent: "presences".to_string(),
+                                                        role_mention_user_ids: None,
                                                     });
                                                 }
                                             }
@@ -592,7 +1031,6 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                 let self_mute = vsu.self_mute.unwrap_or(false);
                                                 let self_deaf = vsu.self_deaf.unwrap_or(false);
                                                 let self_video = vsu.self_video.unwrap_or(false);
-                                                let self_stream = vsu.self_stream.unwrap_or(false);
 
                                                 if let Some(channel_id) = vsu.channel_id {
                                                     // Check if user is already in this exact channel (flag-only update)
@@ -608,6 +1046,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                             is_admin,
                                                             is_guest: is_guest_session,
                                                             guest_space_id: None,
+                                                            login_session_id: login_session_id.clone(),
                                                         };
                                                         if crate::middleware::permissions::require_channel_permission(
                                                             &state.db, &channel_id, &auth_user, "connect",
@@ -617,7 +1056,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
 
                                                         // Update flags in-place — no LiveKit teardown/rejoin
                                                         if let Some(voice_state) = crate::voice::state::update_voice_state(
-                                                            &state, &user_id, self_mute, self_deaf, self_video, self_stream,
+                                                            &state, &user_id, self_mute, self_deaf, self_video,
                                                         ) {
                                                             let event = serde_json::json!({
                                                                 "op": events::opcode::EVENT,
@@ -630,6 +1069,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                                     target_user_ids: None,
                                                                     event,
                                                                     intent: "voice_states".to_string(),
+                                                                    role_mention_user_ids: None,
                                                                 });
                                                             }
                                                         }
@@ -641,12 +1081,13 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                             is_admin,
                                                             is_guest: is_guest_session,
                                                             guest_space_id: None,
+                                                            login_session_id: login_session_id.clone(),
                                                         };
                                                         let channel = match crate::db::channels::get_channel_row(&state.db, &channel_id).await {
                                                             Ok(ch) => ch,
                                                             Err(_) => continue,
                                                         };
-                                                        if channel.channel_type != "voice" {
+                                                        if !crate::models::channel::is_voice_type(&channel.channel_type) {
                                                             continue;
                                                         }
                                                         if crate::middleware::permissions::require_channel_permission(
@@ -663,11 +1104,17 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
 
                                                         let (voice_state, prev) = crate::voice::state::join_voice_channel(
                                                             &state, &user_id, Some(&vsu.space_id), &channel_id,
-                                                            &session_id, self_mute, self_deaf, self_video, self_stream,
+                                                            &session_id, self_mute, self_deaf, self_video,
+                                                            channel.channel_type == "stage",
                                                         );
+                                                        let voice_state = crate::voice::state::apply_server_flags(&state, voice_state).await;
+                                                        if let Some(ref prev) = prev {
+                                                            crate::voice::stream::end(&state, prev).await;
+                                                        }
 
                                                         // Clean up old LiveKit room if the user moved channels
-                                                        if let Some(ref prev_ch) = prev {
+                                                        if let Some(ref prev_ch) = prev.and_then(|vs| vs.channel_id) {
+                                                            crate::voice::chat::clear_if_empty(&state, prev_ch).await;
                                                             if !state.test_mode {
                                                                 if let Some(ref lk) = state.livekit_client {
                                                                     lk.remove_participant(prev_ch, &user_id).await;
@@ -688,6 +1135,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                                 target_user_ids: None,
                                                                 event,
                                                                 intent: "voice_states".to_string(),
+                                                                role_mention_user_ids: None,
                                                             });
                                                         }
 
@@ -701,8 +1149,8 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                                 .ok()
                                                                 .and_then(|u| u.display_name.or(Some(u.username)))
                                                                 .unwrap_or_else(|| user_id.clone());
-                                                            let server_update = match lk.generate_token(&user_id, &display_name, &channel_id) {
-                                                                Ok(token) => serde_json::json!({
+                                                            let server_update = match crate::voice::token::issue(&state, lk, &display_name, &channel_id, &voice_state) {
+                                                                Ok((token, expires_at)) => serde_json::json!({
                                                                     "op": events::opcode::EVENT,
                                                                     "type": "voice.server_update",
                                                                     "data": {
@@ -710,7 +1158,8 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                                         "channel_id": channel_id,
                                                                         "backend": "livekit",
                                                                         "url": lk.external_url(),
-                                                                        "token": token
+                                                                        "token": token,
+                                                                        "expires_at": expires_at.to_rfc3339()
                                                                     }
                                                                 }),
                                                                 Err(_) => serde_json::json!({
@@ -730,6 +1179,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                 } else {
                                                     // Leave voice
                                                     if let Some(old_vs) = crate::voice::state::leave_voice_channel(&state, &user_id) {
+                                                        crate::voice::stream::end(&state, &old_vs).await;
                                                         let left_state = crate::models::voice::VoiceState {
                                                             user_id: user_id.clone(),
                                                             space_id: old_vs.space_id.clone(),
@@ -742,6 +1192,8 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                             self_stream: false,
                                                             self_video: false,
                                                             suppress: false,
+                                                            request_to_speak_timestamp: None,
+                                                            invited_to_speak: false,
                                                         };
                                                         let event = serde_json::json!({
                                                             "op": events::opcode::EVENT,
@@ -754,11 +1206,13 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                                                 target_user_ids: None,
                                                                 event,
                                                                 intent: "voice_states".to_string(),
+                                                                role_mention_user_ids: None,
                                                             });
                                                         }
 
                                                         // LiveKit cleanup
                                                         if let Some(ref ch_id) = old_vs.channel_id {
+                                                            crate::voice::chat::clear_if_empty(&state, ch_id).await;
                                                             if !state.test_mode {
                                                                 if let Some(ref lk) = state.livekit_client {
                                                                     lk.remove_participant(ch_id, &user_id).await;
@@ -772,19 +1226,80 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                                         }
                                     }
                                 }
-                                _ => {}
+                                op if op == events::opcode::REQUEST_MEMBERS => {
+                                    if let Some(data) = gw_msg.data {
+                                        if let Ok(req) = serde_json::from_value::<RequestMembersData>(data) {
+                                            if member_request_window_start.elapsed() >= MEMBER_REQUEST_WINDOW {
+                                                member_request_count = 0;
+                                                member_request_window_start = tokio::time::Instant::now();
+                                            }
+                                            // Same gate as dispatch: the chunks are `members` events.
+                                            if space_ids.contains(&req.space_id)
+                                                && intents::may_receive(&user_intents, is_admin, "space.members_chunk")
+                                                && member_request_count < MEMBER_REQUEST_LIMIT
+                                            {
+                                                member_request_count += 1;
+                                                tokio::spawn(send_member_chunks(state.clone(), tx.clone(), req, capabilities.member_list_v2));
+                                            }
+                                        }
+                                    }
+                                }
+                                op if op == events::opcode::SUBSCRIBE_SPACES => {
+                                    if let Some(data) = gw_msg.data {
+                                        if let Ok(req) = serde_json::from_value::<SubscribeSpacesData>(data) {
+                                            let mut wanted: Vec<String> = Vec::new();
+                                            for sid in req.space_ids {
+                                                if space_ids.contains(&sid) && !wanted.contains(&sid) {
+                                                    wanted.push(sid);
+                                                }
+                                            }
+                                            if !wanted.is_empty() {
+                                                synced_space_ids.extend(wanted.iter().cloned());
+                                                tokio::spawn(send_space_syncs(state.clone(), tx.clone(), wanted));
+                                            }
+                                        }
+                                    }
+                                }
+                                op if op == events::opcode::IDENTIFY || op == events::opcode::RESUME => {
+                                    send_close(&mut ws_sink, events::close_code::ALREADY_AUTHENTICATED, "already_authenticated").await;
+                                    detached_until = Some(tokio::time::Instant::now() + RESUME_WINDOW);
+                                }
+                                _ => {
+                                    send_close(&mut ws_sink, events::close_code::UNKNOWN_OPCODE, "unknown_opcode").await;
+                                    detached_until = Some(tokio::time::Instant::now() + RESUME_WINDOW);
+                                }
                             }
+                        } else {
+                            send_close(&mut ws_sink, events::close_code::DECODE_ERROR, "decode_error").await;
+                            detached_until = Some(tokio::time::Instant::now() + RESUME_WINDOW);
+                        }
+                    }
+                    // A normal close (1000/1001 or no code) ends the session;
+                    // anything else, or a dropped connection, leaves it
+                    // resumable for RESUME_WINDOW.
+                    Some(Ok(Message::Close(frame))) => {
+                        let code = frame.map(|f| f.code).unwrap_or(1000);
+                        if code == 1000 || code == 1001 {
+                            break;
                         }
+                        detached_until = Some(tokio::time::Instant::now() + RESUME_WINDOW);
+                    }
+                    Some(Err(_)) | None => {
+                        detached_until = Some(tokio::time::Instant::now() + RESUME_WINDOW);
                     }
-                    Some(Ok(Message::Close(_))) | None => break,
                     _ => {}
                 }
             }
         }
     }
 
+    if handed_over {
+        return;
+    }
+
     // Cleanup: remove from voice if connected
     if let Some(old_vs) = crate::voice::state::leave_voice_channel(&state, &user_id) {
+        crate::voice::stream::end(&state, &old_vs).await;
         if let Some(ref sid) = old_vs.space_id {
             let left_state = crate::models::voice::VoiceState {
                 user_id: user_id.clone(),
@@ -798,6 +1313,8 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                 self_stream: false,
                 self_video: false,
                 suppress: false,
+                request_to_speak_timestamp: None,
+                invited_to_speak: false,
             };
             let event = serde_json::json!({
                 "op": events::opcode::EVENT,
@@ -810,12 +1327,14 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                     target_user_ids: None,
                     event,
                     intent: "voice_states".to_string(),
+                    role_mention_user_ids: None,
                 });
             }
         }
 
         // LiveKit cleanup on disconnect
         if let Some(ref ch_id) = old_vs.channel_id {
+            crate::voice::chat::clear_if_empty(&state, ch_id).await;
             if !state.test_mode {
                 if let Some(ref lk) = state.livekit_client {
                     lk.remove_participant(ch_id, &user_id).await;
@@ -829,6 +1348,9 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     if let Some(ref dispatcher) = *state.dispatcher.read().await {
         dispatcher.remove_session(&session_id);
     }
+    if !is_guest_session {
+        crate::presence::close_session(&state, &user_id, &session_id).await;
+    }
 
     // Guest cleanup: decrement guest count and broadcast updated count
     if is_guest_session {
@@ -852,6 +1374,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                     target_user_ids: None,
                     event,
                     intent: "members".to_string(),
+                    role_mention_user_ids: None,
                 });
             }
         }
@@ -861,7 +1384,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     if !is_guest_session
         && !crate::presence::user_has_other_sessions(&state, &user_id, &session_id).await
     {
-        crate::presence::remove_presence(&state, &user_id);
+        crate::presence::remove_presence(&state, &user_id).await;
 
         // Broadcast presence.update (offline) to all spaces
         if let Some(ref gtx) = *state.gateway_tx.read().await {
@@ -882,6 +1405,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                     target_user_ids: None,
                     event,
                     intent: "presences".to_string(),
+                    role_mention_user_ids: None,
                 });
             }
             // Also broadcast offline to friends who may not share any space
@@ -896,22 +1420,495 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                     target_user_ids: Some(friend_ids.iter().cloned().collect()),
                     event,
                     intent: "presences".to_string(),
+                    role_mention_user_ids: None,
+                });
+            }
+        }
+    }
+}
+
+/// Build the READY payload for a freshly identified session. Also marks the
+/// user online. Returns the payload and the user's friend IDs (used for
+/// presence routing).
+async fn build_ready(
+    state: &AppState,
+    session_id: &str,
+    user_id: &str,
+    space_ids: &HashSet<String>,
+    muted_channel_ids: &HashSet<String>,
+    is_guest_session: bool,
+    lazy_spaces: bool,
+) -> (serde_json::Value, HashSet<String>) {
+    let presences_json: Vec<serde_json::Value>;
+    let friend_ids: HashSet<String>;
+    let relationships_json: Vec<serde_json::Value>;
+
+    if is_guest_session {
+        presences_json = vec![];
+        friend_ids = HashSet::new();
+        relationships_json = vec![];
+    } else {
+        // Set user presence to online
+        crate::presence::set_presence(state, user_id, "online", vec![]).await;
+
+        // Load this user's relationships for READY payload and friend set for presence routing
+        friend_ids = db::relationships::get_friend_ids(&state.db, user_id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+
+        // Collect presences of online members in the user's spaces (just
+        // friends for lazy sessions; space presences come with space.sync)
+        let mut all_member_ids = HashSet::new();
+        if lazy_spaces {
+            all_member_ids.clone_from(&friend_ids);
+        } else {
+            for sid in space_ids {
+                if let Ok(members) = db::spaces::list_member_ids_for_space(&state.db, sid).await {
+                    for mid in members {
+                        all_member_ids.insert(mid);
+                    }
+                }
+            }
+        }
+        let presences = crate::presence::get_space_presences(state, &all_member_ids).await;
+        presences_json = presences
+            .iter()
+            .map(|p| serde_json::to_value(p).unwrap_or_default())
+            .collect();
+
+        relationships_json = db::relationships::list_relationships(&state.db, user_id)
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(|r| {
+                let display = r
+                    .target_display_name
+                    .clone()
+                    .unwrap_or_else(|| r.target_username.clone());
+                serde_json::json!({
+                    "id": r.target_user_id,
+                    "user": {
+                        "id": r.target_user_id,
+                        "username": r.target_username,
+                        "display_name": display,
+                        "avatar": r.target_avatar
+                    },
+                    "type": r.rel_type,
+                    "since": r.created_at
+                })
+            })
+            .collect();
+    }
+
+    // Fetch full initial state for the READY payload
+    let current_user_json = if !is_guest_session {
+        db::users::get_user(&state.db, user_id)
+            .await
+            .ok()
+            .map(|u| serde_json::to_value(&u).unwrap_or_default())
+    } else {
+        None
+    };
+
+    let mut snapshot = SpaceSnapshot::default();
+    for sid in space_ids {
+        if lazy_spaces {
+            // Space objects only; the rest comes with space.sync.
+            if let Ok(space_row) = db::spaces::get_space_row(&state.db, sid).await {
+                snapshot
+                    .spaces
+                    .push(serde_json::to_value(&space_row).unwrap_or_default());
+            }
+        } else {
+            snapshot.add_space(state, sid).await;
+        }
+    }
+
+    // DM channels (with recipients)
+    let dm_channels_json: Vec<serde_json::Value> = if !is_guest_session {
+        match db::users::get_user_dm_channels(&state.db, user_id).await {
+            Ok(dm_rows) => {
+                let mut dms = Vec::new();
+                for row in &dm_rows {
+                    dms.push(routes::spaces::channel_row_to_json_pub(&state.db, row).await);
+                }
+                dms
+            }
+            Err(_) => vec![],
+        }
+    } else {
+        vec![]
+    };
+
+    // Muted channel IDs (already loaded as muted_channel_ids HashSet)
+    let mutes_json: Vec<serde_json::Value> = muted_channel_ids
+        .iter()
+        .map(|cid| serde_json::json!({ "channel_id": cid }))
+        .collect();
+
+    // Collapsed sidebar entries
+    let collapsed_json: Vec<String> = if !is_guest_session {
+        db::mutes::list_collapsed_channel_ids(&state.db, user_id)
+            .await
+            .unwrap_or_default()
+    } else {
+        vec![]
+    };
+
+    // Drafts in progress on other devices
+    let drafts = if !is_guest_session {
+        db::drafts::list_drafts(&state.db, user_id, &crate::drafts::expiry_cutoff())
+            .await
+            .unwrap_or_default()
+    } else {
+        vec![]
+    };
+
+    // Sidebar order and folders
+    let space_layout = if !is_guest_session {
+        let mut folders = db::user_settings::get_space_layout(&state.db, user_id)
+            .await
+            .unwrap_or_default();
+        routes::user_settings::prune_space_layout(&mut folders, space_ids);
+        folders
+    } else {
+        vec![]
+    };
+
+    // Unread states
+    let unread_json: Vec<serde_json::Value> = if !is_guest_session {
+        db::read_states::get_unread_channels(&state.db, user_id)
+            .await
+            .map(|entries| {
+                entries
+                    .iter()
+                    .map(|e| serde_json::to_value(e).unwrap_or_default())
+                    .collect()
+            })
+            .unwrap_or_default()
+    } else {
+        vec![]
+    };
+
+    // Send READY event
+    let motd = state.settings.load().motd.clone();
+    let ready = serde_json::json!({
+        "op": events::opcode::EVENT,
+        "seq": 1,
+        "type": "ready",
+        "data": {
+            "session_id": session_id,
+            "user_id": user_id,
+            "user": current_user_json,
+            "spaces": snapshot.spaces,
+            "channels": snapshot.channels,
+            "members": snapshot.members,
+            "roles": snapshot.roles,
+            "users": snapshot.users,
+            "voice_states": snapshot.voice_states,
+            "dm_channels": dm_channels_json,
+            "mutes": mutes_json,
+            "collapsed_channels": collapsed_json,
+            "drafts": drafts,
+            "unread": unread_json,
+            "presences": presences_json,
+            "relationships": relationships_json,
+            "space_folders": space_layout,
+            "is_guest": is_guest_session,
+            "api_version": "v1",
+            "server_version": env!("CARGO_PKG_VERSION"),
+            "motd": motd
+        }
+    });
+
+    (ready, friend_ids)
+}
+
+/// Tell other sessions that this user connected: guest counts for guest
+/// sessions, otherwise an online presence to shared spaces and friends.
+async fn announce_connect(
+    state: &AppState,
+    user_id: &str,
+    space_ids: &HashSet<String>,
+    friend_ids: &HashSet<String>,
+    is_guest_session: bool,
+) {
+    // Guest connect: broadcast anonymous_count_updated
+    if is_guest_session {
+        if let Some(ref gtx) = *state.gateway_tx.read().await {
+            for sid in space_ids {
+                let count = state.guest_counts.get(sid).map(|c| *c).unwrap_or(0);
+                let event = serde_json::json!({
+                    "op": events::opcode::EVENT,
+                    "type": "anonymous_count_updated",
+                    "data": { "count": count, "space_id": sid }
+                });
+                let _ = gtx.send(GatewayBroadcast {
+                    space_id: Some(sid.clone()),
+                    target_user_ids: None,
+                    event,
+                    intent: "members".to_string(),
+                    role_mention_user_ids: None,
+                });
+            }
+        }
+    }
+
+    // Broadcast presence.update (online) to all spaces (skip for guests)
+    if !is_guest_session {
+        if let Some(ref gtx) = *state.gateway_tx.read().await {
+            let presence_data = serde_json::json!({
+                "user_id": user_id,
+                "status": "online",
+                "client_status": { "desktop": "online" },
+                "activities": []
+            });
+            for sid in space_ids {
+                let event = serde_json::json!({
+                    "op": events::opcode::EVENT,
+                    "type": "presence.update",
+                    "data": presence_data
+                });
+                let _ = gtx.send(GatewayBroadcast {
+                    space_id: Some(sid.clone()),
+                    target_user_ids: None,
+                    event,
+                    intent: "presences".to_string(),
+                    role_mention_user_ids: None,
+                });
+            }
+            // Also broadcast to friends who may not share any space
+            if !friend_ids.is_empty() {
+                let event = serde_json::json!({
+                    "op": events::opcode::EVENT,
+                    "type": "presence.update",
+                    "data": presence_data
+                });
+                let _ = gtx.send(GatewayBroadcast {
+                    space_id: None,
+                    target_user_ids: Some(friend_ids.iter().cloned().collect()),
+                    event,
+                    intent: "presences".to_string(),
+                    role_mention_user_ids: None,
                 });
             }
         }
     }
 }
 
+/// Validate a RESUME and take the session over from the connection task that
+/// currently owns it (live or detached). On failure returns the close code
+/// and reason to send: bad token, unknown/expired session, another user's
+/// session, or a `seq` the replay buffer can no longer cover.
+async fn resume_session(
+    state: &AppState,
+    resume: &ResumeData,
+    ip: Option<IpAddr>,
+) -> Result<(ResolvedAuth, ResumeHandoff), (u16, &'static str)> {
+    use events::close_code;
+    let no_session = (
+        close_code::SESSION_NO_LONGER_VALID,
+        "session_no_longer_valid",
+    );
+
+    let auth = resolve_token(state, &resume.token)
+        .await
+        .ok_or((close_code::AUTH_FAILED, "authentication_failed"))?;
+    if auth.is_bot && !bot_ip_allowed(state, &resume.token, ip).await {
+        return Err((close_code::IP_NOT_ALLOWED, "ip_not_allowed"));
+    }
+    let reply = (*state.dispatcher.read().await)
+        .as_ref()
+        .and_then(|d| d.request_resume(&resume.session_id, &auth.user_id, resume.seq))
+        .ok_or(no_session)?;
+    let handoff = tokio::time::timeout(std::time::Duration::from_secs(5), reply)
+        .await
+        .map_err(|_| no_session)?
+        .map_err(|_| no_session)?
+        .ok_or((close_code::INVALID_SEQ, "invalid_seq"))?;
+    Ok((auth, handoff))
+}
+
+/// Send INVALID_SESSION (not resumable) ahead of closing, for clients that
+/// only look at opcodes.
+async fn send_invalid_session(ws_sink: &mut SplitSink<WebSocket, Message>) {
+    let invalid = serde_json::json!({
+        "op": events::opcode::INVALID_SESSION,
+        "data": { "resumable": false }
+    });
+    let _ = ws_sink
+        .send(Message::Text(invalid.to_string().into()))
+        .await;
+}
+
+/// Close the connection with one of the [events::close_code] codes and a
+/// short machine-readable reason.
+/// Serves a LOGIN_TICKET connection: answers heartbeats until the ticket is
+/// approved, then sends the new session's token in a `login_ticket.approved`
+/// event and closes. The device reconnects and IDENTIFYs with that token.
+async fn wait_for_login_ticket(
+    state: &AppState,
+    ws_sink: &mut SplitSink<WebSocket, Message>,
+    ws_stream: &mut SplitStream<WebSocket>,
+    login: LoginTicketData,
+) {
+    let ticket_hash = auth_resolve::create_token_hash(&login.ticket);
+    let secret_hash = auth_resolve::create_token_hash(&login.secret);
+    let pending = state
+        .login_tickets
+        .get(&ticket_hash)
+        .filter(|t| t.secret_hash == secret_hash && t.expires_at > chrono::Utc::now())
+        .map(|t| (t.approved_by.subscribe(), t.expires_at));
+    let Some((mut approved_by, expires_at)) = pending else {
+        send_close(
+            ws_sink,
+            events::close_code::AUTH_FAILED,
+            "invalid_login_ticket",
+        )
+        .await;
+        return;
+    };
+
+    let expiry = tokio::time::sleep(
+        (expires_at - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    );
+    tokio::pin!(expiry);
+    loop {
+        tokio::select! {
+            _ = &mut expiry => {
+                state.login_tickets.remove(&ticket_hash);
+                send_close(ws_sink, events::close_code::LOGIN_TICKET_EXPIRED, "login_ticket_expired").await;
+                return;
+            }
+            approved = async { approved_by.wait_for(Option::is_some).await.is_ok() } => {
+                // The sender is gone once the ticket is swept as expired.
+                if !approved {
+                    send_close(ws_sink, events::close_code::LOGIN_TICKET_EXPIRED, "login_ticket_expired").await;
+                    return;
+                }
+                break;
+            }
+            msg = ws_stream.next() => match msg {
+                Some(Ok(Message::Text(text)))
+                    if serde_json::from_str::<GatewayMessage>(&text)
+                        .is_ok_and(|m| m.op == events::opcode::HEARTBEAT) =>
+                {
+                    let ack = serde_json::json!({ "op": events::opcode::HEARTBEAT_ACK });
+                    let _ = ws_sink.send(Message::Text(ack.to_string().into())).await;
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                _ => {}
+            }
+        }
+    }
+
+    // Removing the ticket makes sure only one connection redeems it.
+    let Some((_, ticket)) = state.login_tickets.remove(&ticket_hash) else {
+        send_close(
+            ws_sink,
+            events::close_code::LOGIN_TICKET_EXPIRED,
+            "login_ticket_expired",
+        )
+        .await;
+        return;
+    };
+    let Some(user_id) = ticket.approved_by.borrow().clone() else {
+        return;
+    };
+    match routes::auth::redeem_login_ticket(state, &user_id, &ticket).await {
+        Ok(data) => {
+            let event = serde_json::json!({
+                "op": events::opcode::EVENT,
+                "type": "login_ticket.approved",
+                "data": data
+            });
+            let _ = ws_sink.send(Message::Text(event.to_string().into())).await;
+            send_close(ws_sink, 1000, "login_ticket_redeemed").await;
+        }
+        Err(e) => {
+            tracing::warn!("failed to redeem login ticket: {e}");
+            send_close(ws_sink, events::close_code::UNKNOWN_ERROR, "unknown_error").await;
+        }
+    }
+}
+
+async fn send_close(ws_sink: &mut SplitSink<WebSocket, Message>, code: u16, reason: &'static str) {
+    let close = CloseFrame {
+        code,
+        reason: reason.into(),
+    };
+    let _ = ws_sink.send(Message::Close(Some(close))).await;
+}
+
+/// Whether a `message.create` broadcast mentions [user_id]: by ID, through
+/// one of their roles, or with @everyone/@here in a space where they haven't
+/// suppressed it. Nobody is mentioned by their own message.
+fn mentions_user(
+    user_id: &str,
+    broadcast: &GatewayBroadcast,
+    everyone_suppressed: &HashSet<String>,
+) -> bool {
+    let data = &broadcast.event["data"];
+    if data["author_id"].as_str() == Some(user_id) {
+        return false;
+    }
+    let ids = |key: &str| -> Vec<&str> {
+        data[key]
+            .as_array()
+            .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+            .unwrap_or_default()
+    };
+    if ids("mentions").contains(&user_id) {
+        return true;
+    }
+    let Some(space_id) = data["space_id"].as_str() else {
+        return false;
+    };
+    if data["mention_everyone"].as_bool() == Some(true) && !everyone_suppressed.contains(space_id) {
+        return true;
+    }
+    broadcast
+        .role_mention_user_ids
+        .as_ref()
+        .is_some_and(|ids| ids.contains(user_id))
+}
+
+/// Whether a `Bot ...` gateway token may connect from [ip] under its
+/// application's IP allowlist.
+async fn bot_ip_allowed(state: &AppState, token: &str, ip: Option<IpAddr>) -> bool {
+    let Some(tok) = token.strip_prefix("Bot ") else {
+        return true;
+    };
+    let token_hash = auth_resolve::create_token_hash(tok);
+    let allowlist = sqlx::query_as::<_, (Option<String>,)>(&crate::db::q(
+        "SELECT a.ip_allowlist FROM bot_tokens bt LEFT JOIN applications a ON bt.application_id = a.id WHERE bt.token_hash = ?",
+    ))
+    .bind(&token_hash)
+    .fetch_optional(&state.db)
+    .await
+    .ok()
+    .flatten();
+    // Tokens without an application have no allowlist.
+    allowlist.is_some_and(|(list,)| ip_allowlist::is_allowed(list.as_deref().unwrap_or("[]"), ip))
+}
+
 struct ResolvedAuth {
     user_id: String,
     is_bot: bool,
     is_admin: bool,
     is_guest: bool,
     guest_space_id: Option<String>,
+    /// The login session behind a user token; see [db::sessions::session_id].
+    login_session_id: Option<String>,
 }
 
 async fn resolve_token(state: &AppState, token: &str) -> Option<ResolvedAuth> {
     // Token format: "Bot xxx" or "Bearer xxx"
+    let mut login_session_id = None;
     let (user_id, is_bot) = if let Some(tok) = token.strip_prefix("Bot ") {
         let token_hash = auth_resolve::create_token_hash(tok);
         let row = sqlx::query_as::<_, (String,)>(&crate::db::q(
@@ -935,6 +1932,7 @@ async fn resolve_token(state: &AppState, token: &str) -> Option<ResolvedAuth> {
             .ok()?;
 
         if let Some(row) = row {
+            login_session_id = Some(db::sessions::session_id(&token_hash).to_string());
             (row.0, false)
         } else {
             // Try guest token lookup
@@ -955,6 +1953,7 @@ async fn resolve_token(state: &AppState, token: &str) -> Option<ResolvedAuth> {
                 is_admin: false,
                 is_guest: true,
                 guest_space_id: Some(guest_row.0),
+                login_session_id: None,
             });
         }
     } else {
@@ -974,5 +1973,6 @@ async fn resolve_token(state: &AppState, token: &str) -> Option<ResolvedAuth> {
         is_admin: user.is_admin,
         is_guest: false,
         guest_space_id: None,
+        login_session_id,
     })
 }
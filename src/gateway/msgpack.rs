@@ -0,0 +1,133 @@
+use serde_json::Value;
+
+/// Encode a JSON value as MessagePack. Integers use the smallest format that
+/// holds them; other numbers are float64.
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut out = Vec::with_capacity(128);
+    write_value(&mut out, value);
+    out
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(0xc0),
+        Value::Bool(false) => out.push(0xc2),
+        Value::Bool(true) => out.push(0xc3),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                write_uint(out, u);
+            } else if let Some(i) = n.as_i64() {
+                write_int(out, i);
+            } else {
+                out.push(0xcb);
+                out.extend_from_slice(&n.as_f64().unwrap_or(0.0).to_be_bytes());
+            }
+        }
+        Value::String(s) => {
+            let len = s.len();
+            if len < 32 {
+                out.push(0xa0 | len as u8);
+            } else if len <= u8::MAX as usize {
+                out.extend_from_slice(&[0xd9, len as u8]);
+            } else if len <= u16::MAX as usize {
+                out.push(0xda);
+                out.extend_from_slice(&(len as u16).to_be_bytes());
+            } else {
+                out.push(0xdb);
+                out.extend_from_slice(&(len as u32).to_be_bytes());
+            }
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(items) => {
+            write_len(out, items.len(), 0x90, 0xdc);
+            for item in items {
+                write_value(out, item);
+            }
+        }
+        Value::Object(map) => {
+            write_len(out, map.len(), 0x80, 0xde);
+            for (k, v) in map {
+                write_value(out, &Value::String(k.clone()));
+                write_value(out, v);
+            }
+        }
+    }
+}
+
+/// Array/map header: fix format under 16 entries, else the 16/32-bit form
+/// (whose marker follows [marker16]).
+fn write_len(out: &mut Vec<u8>, len: usize, fix: u8, marker16: u8) {
+    if len < 16 {
+        out.push(fix | len as u8);
+    } else if len <= u16::MAX as usize {
+        out.push(marker16);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(marker16 + 1);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+fn write_uint(out: &mut Vec<u8>, u: u64) {
+    if u < 128 {
+        out.push(u as u8);
+    } else if u <= u8::MAX as u64 {
+        out.extend_from_slice(&[0xcc, u as u8]);
+    } else if u <= u16::MAX as u64 {
+        out.push(0xcd);
+        out.extend_from_slice(&(u as u16).to_be_bytes());
+    } else if u <= u32::MAX as u64 {
+        out.push(0xce);
+        out.extend_from_slice(&(u as u32).to_be_bytes());
+    } else {
+        out.push(0xcf);
+        out.extend_from_slice(&u.to_be_bytes());
+    }
+}
+
+fn write_int(out: &mut Vec<u8>, i: i64) {
+    if i >= -32 {
+        out.push(i as u8);
+    } else if i >= i8::MIN as i64 {
+        out.extend_from_slice(&[0xd0, i as u8]);
+    } else if i >= i16::MIN as i64 {
+        out.push(0xd1);
+        out.extend_from_slice(&(i as i16).to_be_bytes());
+    } else if i >= i32::MIN as i64 {
+        out.push(0xd2);
+        out.extend_from_slice(&(i as i32).to_be_bytes());
+    } else {
+        out.push(0xd3);
+        out.extend_from_slice(&i.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_scalars() {
+        assert_eq!(encode(&Value::Null), [0xc0]);
+        assert_eq!(encode(&serde_json::json!(true)), [0xc3]);
+        assert_eq!(encode(&serde_json::json!(5)), [0x05]);
+        assert_eq!(encode(&serde_json::json!(-1)), [0xff]);
+        assert_eq!(encode(&serde_json::json!(300)), [0xcd, 0x01, 0x2c]);
+        assert_eq!(encode(&serde_json::json!(-200)), [0xd1, 0xff, 0x38]);
+        assert_eq!(
+            encode(&serde_json::json!(1.5)),
+            [0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(encode(&serde_json::json!("op")), [0xa2, b'o', b'p']);
+    }
+
+    #[test]
+    fn encodes_containers() {
+        assert_eq!(
+            encode(&serde_json::json!({ "d": [1, null] })),
+            [0x81, 0xa1, b'd', 0x92, 0x01, 0xc0]
+        );
+        let long = Value::Array(vec![Value::Null; 20]);
+        assert_eq!(&encode(&long)[..3], [0xdc, 0x00, 0x14]);
+    }
+}
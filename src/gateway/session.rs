@@ -1,5 +1,15 @@
-use std::collections::HashSet;
-use tokio::sync::mpsc;
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+use super::events::{Capabilities, GatewayBroadcast};
+
+/// Number of dispatched events kept per session for RESUME replay.
+pub const REPLAY_BUFFER_SIZE: usize = 500;
+
+/// How long a dropped session stays resumable before it is torn down.
+pub const RESUME_WINDOW: Duration = Duration::from_secs(60);
 
 /// Represents an authenticated gateway session.
 #[derive(Debug)]
@@ -10,4 +20,93 @@ pub struct GatewaySession {
     pub space_ids: HashSet<String>,
     pub sequence: u64,
     pub tx: mpsc::UnboundedSender<String>,
+    /// Recently dispatched events, shared with the connection task.
+    pub replay: Arc<Mutex<ReplayBuffer>>,
+    /// Handle for a new connection to take this session over via RESUME.
+    pub resume_tx: mpsc::UnboundedSender<ResumeRequest>,
+}
+
+/// A RESUME takeover request. The owning connection task answers with its
+/// live state and stops without running disconnect cleanup, or with `None`
+/// (and keeps running) if it can't replay everything after `seq`.
+#[derive(Debug)]
+pub struct ResumeRequest {
+    pub seq: u64,
+    pub reply: oneshot::Sender<Option<ResumeHandoff>>,
+}
+
+/// State handed from the old connection task to the resuming one. The
+/// broadcast receiver moves across as-is so no event is dropped or delivered
+/// twice during the switch.
+#[derive(Debug)]
+pub struct ResumeHandoff {
+    pub broadcast_rx: Option<broadcast::Receiver<GatewayBroadcast>>,
+    pub sequence: u64,
+    pub intents: Vec<String>,
+    pub space_ids: HashSet<String>,
+    pub muted_channel_ids: HashSet<String>,
+    /// Spaces where @everyone doesn't count as a mention of this user.
+    pub everyone_suppressed_space_ids: HashSet<String>,
+    pub blocked_user_ids: HashSet<String>,
+    pub friend_ids: HashSet<String>,
+    /// Spaces a `lazy_spaces` session has sent SUBSCRIBE_SPACES for.
+    pub synced_space_ids: HashSet<String>,
+    pub replay: Arc<Mutex<ReplayBuffer>>,
+    pub capabilities: Capabilities,
+    /// Buffered events after the client's `seq`, to send before anything new.
+    pub missed: Vec<String>,
+    /// Direct (non-sequenced) messages queued but not yet written.
+    pub pending: Vec<String>,
+}
+
+/// Fixed-size ring of `(seq, payload)` for events sent on a session.
+#[derive(Debug)]
+pub struct ReplayBuffer {
+    events: VecDeque<(u64, String)>,
+    capacity: usize,
+    last_seq: u64,
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize, last_seq: u64) -> Self {
+        Self {
+            events: VecDeque::with_capacity(capacity.min(64)),
+            capacity,
+            last_seq,
+        }
+    }
+
+    pub fn push(&mut self, seq: u64, payload: String) {
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back((seq, payload));
+        self.last_seq = seq;
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// Events with a sequence number greater than `seq`, oldest first.
+    /// Returns `None` when the client is ahead of the session or when events
+    /// after `seq` have already been evicted — the client must re-IDENTIFY.
+    pub fn since(&self, seq: u64) -> Option<Vec<String>> {
+        if seq > self.last_seq {
+            return None;
+        }
+        if seq == self.last_seq {
+            return Some(Vec::new());
+        }
+        match self.events.front() {
+            Some((oldest, _)) if *oldest <= seq + 1 => Some(
+                self.events
+                    .iter()
+                    .filter(|(s, _)| *s > seq)
+                    .map(|(_, p)| p.clone())
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
 }
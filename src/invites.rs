@@ -0,0 +1,27 @@
+//! Background cleanup of invites that expired or ran out of uses, and of
+//! unused join tokens. Lists and acceptance already ignore them; the sweep
+//! just keeps the tables small.
+
+use std::time::Duration;
+
+use crate::db;
+use crate::state::AppState;
+
+/// How often dead invites are deleted.
+pub const INVITE_SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Sweep forever, deleting dead invites.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(INVITE_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        match db::invites::delete_expired_invites(&state.db).await {
+            Ok(0) => {}
+            Ok(n) => tracing::debug!("swept {n} expired invites"),
+            Err(e) => tracing::warn!("invite sweep failed: {e:?}"),
+        }
+        if let Err(e) = db::invites::delete_expired_join_tokens(&state.db).await {
+            tracing::warn!("join token sweep failed: {e:?}");
+        }
+    }
+}
@@ -1,18 +1,44 @@
+pub mod announcements;
+pub mod automod;
+pub mod blocklist;
 pub mod config;
+pub mod content_safety;
 pub mod db;
+pub mod doctor;
+pub mod drafts;
+pub mod email;
+pub mod email_domains;
 pub mod error;
+pub mod event_subscriptions;
 pub mod federation;
 pub mod gateway;
+pub mod invites;
+pub mod locale;
 pub mod master;
 pub mod mcp;
 pub mod mentions;
 pub mod middleware;
 pub mod models;
+pub mod oauth2;
+pub mod polls;
 pub mod presence;
+pub mod profile;
+pub mod push;
+pub mod raid;
+pub mod retention;
 pub mod routes;
+pub mod safe_fetch;
+pub mod schema;
+pub mod share_links;
+pub mod shutdown;
+pub mod signing;
 pub mod slug;
 pub mod snowflake;
+pub mod space_template;
 pub mod state;
 pub mod storage;
+pub mod threads;
+pub mod typing;
 pub mod unfurl;
 pub mod voice;
+pub mod webauthn;
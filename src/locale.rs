@@ -0,0 +1,157 @@
+//! Text the server writes into a space on its own behalf (join
+//! announcements, pin notices, poll results, AutoMod reports) follows the
+//! space's `preferred_locale`, so a multilingual community sees one language
+//! regardless of who triggered it.
+
+use crate::error::AppError;
+
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+/// Locales a space can choose; each has translations below.
+pub const SUPPORTED_LOCALES: &[&str] = &[
+    "en-US", "en-GB", "de", "es-ES", "fr", "it", "nl", "pl", "pt-BR", "ja",
+];
+
+/// Accept a locale case-insensitively and return its canonical spelling.
+pub fn normalize(locale: &str) -> Result<&'static str, AppError> {
+    SUPPORTED_LOCALES
+        .iter()
+        .find(|l| l.eq_ignore_ascii_case(locale.trim()))
+        .copied()
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "preferred_locale must be one of: {}",
+                SUPPORTED_LOCALES.join(", ")
+            ))
+        })
+}
+
+/// Body of the `member_join` system message.
+pub fn member_joined(locale: &str, name: &str) -> String {
+    match locale {
+        "de" => format!("{name} ist dem Server beigetreten."),
+        "es-ES" => format!("{name} se ha unido al servidor."),
+        "fr" => format!("{name} a rejoint le serveur."),
+        "it" => format!("{name} è entrato nel server."),
+        "nl" => format!("{name} is lid geworden van de server."),
+        "pl" => format!("{name} dołącza do serwera."),
+        "pt-BR" => format!("{name} entrou no servidor."),
+        "ja" => format!("{name} がサーバーに参加しました。"),
+        _ => format!("{name} joined the server."),
+    }
+}
+
+/// Body of the `channel_pinned_message` system message.
+pub fn message_pinned(locale: &str, name: &str) -> String {
+    match locale {
+        "de" => format!("{name} hat eine Nachricht an diesen Kanal angeheftet."),
+        "es-ES" => format!("{name} ha fijado un mensaje en este canal."),
+        "fr" => format!("{name} a épinglé un message dans ce salon."),
+        "it" => format!("{name} ha fissato un messaggio in questo canale."),
+        "nl" => format!("{name} heeft een bericht vastgezet in dit kanaal."),
+        "pl" => format!("{name} przypina wiadomość na tym kanale."),
+        "pt-BR" => format!("{name} fixou uma mensagem neste canal."),
+        "ja" => format!("{name} がこのチャンネルにメッセージをピン留めしました。"),
+        _ => format!("{name} pinned a message to this channel."),
+    }
+}
+
+/// Body of the `poll_result` system message. `winner` is the answer with
+/// the most votes, or `None` when nobody voted or the top answers tied.
+pub fn poll_ended(locale: &str, question: &str, winner: Option<&str>) -> String {
+    match (locale, winner) {
+        ("de", Some(w)) => format!("Die Umfrage „{question}“ ist beendet. Gewonnen hat: {w}"),
+        ("de", None) => format!("Die Umfrage „{question}“ ist beendet, ohne klaren Sieger."),
+        ("es-ES", Some(w)) => format!("La encuesta \"{question}\" ha terminado. Ganadora: {w}"),
+        ("es-ES", None) => {
+            format!("La encuesta \"{question}\" ha terminado sin una respuesta ganadora.")
+        }
+        ("fr", Some(w)) => format!("Le sondage « {question} » est terminé. Gagnant : {w}"),
+        ("fr", None) => format!("Le sondage « {question} » est terminé sans réponse gagnante."),
+        ("it", Some(w)) => format!("Il sondaggio \"{question}\" è terminato. Vince: {w}"),
+        ("it", None) => format!("Il sondaggio \"{question}\" è terminato senza un vincitore."),
+        ("nl", Some(w)) => format!("De peiling \"{question}\" is afgelopen. Winnaar: {w}"),
+        ("nl", None) => format!("De peiling \"{question}\" is afgelopen zonder winnaar."),
+        ("pl", Some(w)) => format!("Ankieta \"{question}\" dobiegła końca. Wygrywa: {w}"),
+        ("pl", None) => format!("Ankieta \"{question}\" dobiegła końca bez zwycięzcy."),
+        ("pt-BR", Some(w)) => format!("A enquete \"{question}\" terminou. Vencedora: {w}"),
+        ("pt-BR", None) => format!("A enquete \"{question}\" terminou sem uma vencedora."),
+        ("ja", Some(w)) => format!("投票「{question}」が終了しました。最多得票: {w}"),
+        ("ja", None) => format!("投票「{question}」が終了しました。勝者はいません。"),
+        (_, Some(w)) => format!("The poll \"{question}\" has ended. Winning answer: {w}"),
+        (_, None) => format!("The poll \"{question}\" has ended with no winning answer."),
+    }
+}
+
+/// First line of an `automod_flag` report; the quoted message follows it.
+pub fn automod_flagged(
+    locale: &str,
+    rule: &str,
+    user_id: &str,
+    channel_id: &str,
+    matched: &str,
+) -> String {
+    let (user, channel) = (format!("<@{user_id}>"), format!("<#{channel_id}>"));
+    match locale {
+        "de" => format!(
+            "AutoMod-Regel „{rule}“ hat eine Nachricht von {user} in {channel} markiert (Treffer: „{matched}“):"
+        ),
+        "es-ES" => format!(
+            "La regla de AutoMod \"{rule}\" ha marcado un mensaje de {user} en {channel} (coincidencia: \"{matched}\"):"
+        ),
+        "fr" => format!(
+            "La règle AutoMod « {rule} » a signalé un message de {user} dans {channel} (correspondance : « {matched} ») :"
+        ),
+        "it" => format!(
+            "La regola AutoMod \"{rule}\" ha segnalato un messaggio di {user} in {channel} (corrispondenza: \"{matched}\"):"
+        ),
+        "nl" => format!(
+            "AutoMod-regel \"{rule}\" heeft een bericht van {user} in {channel} gemarkeerd (overeenkomst: \"{matched}\"):"
+        ),
+        "pl" => format!(
+            "Reguła AutoMod \"{rule}\" oznaczyła wiadomość od {user} na {channel} (dopasowanie: \"{matched}\"):"
+        ),
+        "pt-BR" => format!(
+            "A regra do AutoMod \"{rule}\" sinalizou uma mensagem de {user} em {channel} (correspondência: \"{matched}\"):"
+        ),
+        "ja" => format!(
+            "AutoModルール「{rule}」が {channel} での {user} のメッセージにフラグを付けました（一致:「{matched}」）:"
+        ),
+        _ => format!(
+            "AutoMod rule \"{rule}\" flagged a message from {user} in {channel} (matched \"{matched}\"):"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_is_case_insensitive() {
+        assert_eq!(normalize("pt-br").unwrap(), "pt-BR");
+        assert_eq!(normalize(" DE ").unwrap(), "de");
+        assert!(normalize("xx-YY").is_err());
+    }
+
+    #[test]
+    fn every_supported_locale_is_translated() {
+        let english = member_joined(DEFAULT_LOCALE, "ana");
+        for locale in SUPPORTED_LOCALES.iter().filter(|l| !l.starts_with("en")) {
+            assert_ne!(member_joined(locale, "ana"), english, "{locale}");
+            assert_ne!(
+                message_pinned(locale, "ana"),
+                message_pinned(DEFAULT_LOCALE, "ana"),
+                "{locale}"
+            );
+            for winner in [Some("a"), None] {
+                assert_ne!(
+                    poll_ended(locale, "q", winner),
+                    poll_ended(DEFAULT_LOCALE, "q", winner),
+                    "{locale}"
+                );
+            }
+            assert!(automod_flagged(locale, "r", "1", "2", "m").contains("<@1>"));
+        }
+    }
+}
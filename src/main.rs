@@ -126,6 +126,39 @@ async fn run_main_server(config: Config) {
         .expect("failed to create database pool");
 
     let (dispatcher, gateway_tx) = Dispatcher::new();
+    let gateway_tx = match config.redis.as_ref() {
+        #[cfg(feature = "redis")]
+        Some(redis) => {
+            let tx = accordserver::gateway::cluster::start(redis, &dispatcher)
+                .await
+                .expect("failed to connect to REDIS_URL");
+            status_line(format!(
+                "  \x1b[2mcluster\x1b[0m      redis pub/sub ({})",
+                redis.channel_prefix
+            ));
+            tx
+        }
+        #[cfg(not(feature = "redis"))]
+        Some(_) => {
+            tracing::warn!(
+                "REDIS_URL is set but this build lacks the `redis` feature; running as a single node"
+            );
+            gateway_tx
+        }
+        None => gateway_tx,
+    };
+    #[cfg(feature = "redis")]
+    let shared_presence: Option<Arc<dyn accordserver::presence::SharedPresence>> =
+        match config.redis.as_ref() {
+            Some(redis) => Some(Arc::new(
+                accordserver::presence::redis::RedisPresence::connect(redis)
+                    .await
+                    .expect("failed to connect to REDIS_URL"),
+            )),
+            None => None,
+        };
+    #[cfg(not(feature = "redis"))]
+    let shared_presence: Option<Arc<dyn accordserver::presence::SharedPresence>> = None;
 
     let livekit_client = match config.livekit.as_ref() {
         Some(lk) => {
@@ -134,6 +167,7 @@ async fn run_main_server(config: Config) {
                 &lk.external_url,
                 &lk.api_key,
                 &lk.api_secret,
+                lk.media_url.as_deref(),
             );
             match client.check_connectivity().await {
                 Ok(()) => {
@@ -164,6 +198,18 @@ async fn run_main_server(config: Config) {
             tracing::error!("failed to create storage directory {:?}: {:?}", dir, e);
         }
     }
+    let storage: Arc<dyn accordserver::storage::Storage> = match config.s3 {
+        Some(s3) => {
+            status_line(format!(
+                "  \x1b[2mstorage\x1b[0m      s3 bucket {} at {}",
+                s3.bucket, s3.endpoint
+            ));
+            Arc::new(accordserver::storage::S3Storage::new(s3))
+        }
+        None => Arc::new(accordserver::storage::LocalStorage::new(
+            storage_path.clone(),
+        )),
+    };
 
     let gateway_tx_arc = Arc::new(RwLock::new(Some(gateway_tx)));
 
@@ -197,23 +243,66 @@ async fn run_main_server(config: Config) {
         _ => None,
     };
 
+    let mailer = match config.smtp.as_ref() {
+        Some(smtp) => match accordserver::email::Mailer::new(smtp) {
+            Ok(mailer) => {
+                status_line(format!(
+                    "  \x1b[2msmtp\x1b[0m         {}:{}",
+                    smtp.host, smtp.port
+                ));
+                Some(Arc::new(mailer))
+            }
+            Err(e) => {
+                tracing::error!("failed to configure SMTP: {e}");
+                None
+            }
+        },
+        None => None,
+    };
+
+    let push = match config.push.as_ref() {
+        Some(push) => match accordserver::push::Notifier::new(push) {
+            Ok(notifier) => {
+                status_line(format!(
+                    "  \x1b[2mpush\x1b[0m         {}",
+                    push.subject.as_deref().unwrap_or("enabled")
+                ));
+                Some(Arc::new(notifier))
+            }
+            Err(e) => {
+                tracing::error!("failed to configure Web Push: {e}");
+                None
+            }
+        },
+        None => None,
+    };
+
     let state = AppState {
         db,
         db_is_postgres: accordserver::db::url_is_postgres(&config.database_url),
         voice_states: Arc::new(DashMap::new()),
+        voice_stats: Arc::new(DashMap::new()),
+        voice_tokens: Arc::new(DashMap::new()),
         presences: Arc::new(DashMap::new()),
+        shared_presence,
         dispatcher: Arc::new(RwLock::new(Some(dispatcher))),
         gateway_tx: gateway_tx_arc,
         test_mode: config.test_mode,
         livekit_client,
         rate_limits: Arc::new(DashMap::new()),
+        bot_rate_limits: Arc::new(DashMap::new()),
+        typing: Arc::new(Default::default()),
         update_status_path: storage_path.parent().map(|p| p.join("update_status.json")),
         storage_path,
+        storage,
         settings: Arc::new(ArcSwap::from_pointee(settings.clone())),
         master_config: master_config.clone(),
         master_task: Arc::new(Mutex::new(None)),
         federation,
         mfa_tickets: Arc::new(DashMap::new()),
+        login_tickets: Arc::new(DashMap::new()),
+        passkey_challenges: Arc::new(DashMap::new()),
+        webauthn: config.webauthn.clone().map(Arc::new),
         totp_attempts: Arc::new(DashMap::new()),
         totp_key,
         mcp_api_key,
@@ -221,6 +310,10 @@ async fn run_main_server(config: Config) {
         register_attempts: Arc::new(DashMap::new()),
         guest_attempts: Arc::new(DashMap::new()),
         guest_counts: Arc::new(DashMap::new()),
+        cors: Arc::new(config.cors),
+        mailer,
+        push,
+        require_verified_email: config.require_verified_email,
     };
 
     // Ensure a default invite exists and display it
@@ -248,6 +341,34 @@ async fn run_main_server(config: Config) {
         tokio::spawn(accordserver::federation::run(state.clone()));
     }
 
+    // Archive threads that have gone quiet past their auto-archive window.
+    tokio::spawn(accordserver::threads::run(state.clone()));
+
+    // Drop message drafts nobody has touched in a while.
+    tokio::spawn(accordserver::drafts::run(state.clone()));
+
+    // Delete invites that expired or ran out of uses.
+    tokio::spawn(accordserver::invites::run(state.clone()));
+
+    // Purge ephemeral interaction responses past their TTL.
+    tokio::spawn(accordserver::retention::run(state.clone()));
+
+    // Deliver subscribed events to application webhooks.
+    tokio::spawn(accordserver::event_subscriptions::run(state.clone()));
+
+    // Close expired polls and announce their results.
+    tokio::spawn(accordserver::polls::run(state.clone()));
+
+    // Push DMs and mentions to users who aren't connected.
+    tokio::spawn(accordserver::push::run(state.clone()));
+
+    // Tell instance admins when LiveKit stops (or starts) answering.
+    tokio::spawn(accordserver::voice::health::run(state.clone()));
+
+    // Replace voice tokens before they expire mid-call.
+    tokio::spawn(accordserver::voice::token::run(state.clone()));
+
+    let shutdown_state = state.clone();
     let app = accordserver::routes::router(state);
 
     let listener = TcpListener::bind((config.bind.as_str(), config.port))
@@ -258,5 +379,43 @@ async fn run_main_server(config: Config) {
     status_line(format!("  \x1b[32m→ listening on {actual_addr}\x1b[0m"));
     eprintln!();
 
-    axum::serve(listener, app).await.expect("server error");
+    // On SIGTERM/Ctrl-C the listener stops accepting while the gateway is
+    // drained alongside; upgraded WebSockets aren't waited on by axum.
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    tokio::spawn(async move {
+        accordserver::shutdown::signal().await;
+        tracing::info!("shutting down");
+        let _ = shutdown_tx.send(true);
+    });
+
+    let mut gateway_rx = shutdown_rx.clone();
+    let gateway_drain = tokio::spawn(async move {
+        if gateway_rx.wait_for(|down| *down).await.is_ok() {
+            accordserver::shutdown::drain_gateway(&shutdown_state).await;
+        }
+    });
+
+    let mut serve_rx = shutdown_rx.clone();
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        let _ = serve_rx.wait_for(|down| *down).await;
+    });
+
+    let mut deadline_rx = shutdown_rx;
+    tokio::select! {
+        result = server => result.expect("server error"),
+        _ = async {
+            let _ = deadline_rx.wait_for(|down| *down).await;
+            tokio::time::sleep(accordserver::shutdown::HTTP_DRAIN_TIMEOUT).await;
+        } => {
+            tracing::warn!(
+                "in-flight requests still running after {:?}; exiting anyway",
+                accordserver::shutdown::HTTP_DRAIN_TIMEOUT
+            );
+        }
+    }
+    let _ = gateway_drain.await;
 }
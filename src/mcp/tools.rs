@@ -415,6 +415,8 @@ async fn tool_send_message(state: &AppState, args: &Value) -> Result<String, Str
         reply_to,
         thread_id: None,
         title: None,
+        poll: None,
+        sticker_ids: None,
     };
 
     let msg = db::messages::create_message(
@@ -447,6 +449,7 @@ async fn tool_send_message(state: &AppState, args: &Value) -> Result<String, Str
             target_user_ids: None,
             event,
             intent: "messages".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -496,6 +499,7 @@ async fn tool_create_channel(state: &AppState, args: &Value) -> Result<String, S
             target_user_ids: None,
             event,
             intent: "channels".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -529,6 +533,7 @@ async fn tool_delete_channel(state: &AppState, args: &Value) -> Result<String, S
                 target_user_ids: None,
                 event,
                 intent: "channels".to_string(),
+                role_mention_user_ids: None,
             });
         }
     }
@@ -595,6 +600,7 @@ async fn tool_ban_user(state: &AppState, args: &Value) -> Result<String, String>
             target_user_ids: None,
             event,
             intent: "moderation".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -628,6 +634,7 @@ async fn tool_unban_user(state: &AppState, args: &Value) -> Result<String, Strin
             target_user_ids: None,
             event,
             intent: "moderation".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -661,6 +668,7 @@ async fn tool_delete_message(state: &AppState, args: &Value) -> Result<String, S
             target_user_ids: None,
             event,
             intent: "messages".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -682,6 +690,7 @@ async fn broadcast_member_leave(state: &AppState, space_id: &str, user_id: &str)
             target_user_ids: None,
             event,
             intent: "members".to_string(),
+            role_mention_user_ids: None,
         });
     }
 }
@@ -691,7 +700,7 @@ async fn tool_server_info(state: &AppState) -> Result<String, String> {
         .await
         .map_err(map_err)?;
 
-    let online_users = state.presences.len();
+    let online_users = crate::presence::online_count(state).await;
     let voice_connections = state.voice_states.len();
     let voice_backend = if state.livekit_client.is_some() {
         "livekit"
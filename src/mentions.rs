@@ -1,10 +1,11 @@
 //! Parsing of `@`-mentions out of raw message content.
 //!
-//! Accord messages carry mentions inline as plain text (`@everyone`, `@here`,
-//! `@username`) rather than as resolved `<@id>` tokens, mirroring the client's
-//! own `RegExp(r'@(everyone|here)\b|@(\w+)')`. We parse them server-side so the
-//! per-user mention counter (the red badge) is authoritative and survives
-//! reconnects.
+//! Accord messages mostly carry mentions inline as plain text (`@everyone`,
+//! `@here`, `@username`), mirroring the client's own
+//! `RegExp(r'@(everyone|here)\b|@(\w+)')`. Bots and newer clients also send
+//! ID tokens: `<@user_id>`, `<@&role_id>`, and `<#channel_id>`. We parse both
+//! server-side so the per-user mention counter (the red badge) is
+//! authoritative and survives reconnects.
 
 /// The mentions found in a message's content.
 #[derive(Debug, Default, Clone)]
@@ -14,9 +15,36 @@ pub struct ParsedMentions {
     /// Candidate `@username` handles, in order of appearance, de-duplicated
     /// case-insensitively. These still need resolving to member user IDs.
     pub usernames: Vec<String>,
+    /// IDs from `<@user_id>` tokens, de-duplicated.
+    pub user_ids: Vec<String>,
+    /// IDs from `<@&role_id>` tokens, de-duplicated.
+    pub role_ids: Vec<String>,
+    /// IDs from `<#channel_id>` tokens, de-duplicated.
+    pub channel_ids: Vec<String>,
 }
 
-/// Extracts `@everyone`/`@here` and `@username` tokens from [content].
+/// Matches an ID token starting at `bytes[i] == b'<'`, returning the token's
+/// target list and ID along with the index just past its closing `>`.
+fn parse_token<'a>(
+    content: &'a str,
+    out: &'a mut ParsedMentions,
+    i: usize,
+) -> Option<(&'a mut Vec<String>, &'a str, usize)> {
+    let bytes = content.as_bytes();
+    let (list, start) = match bytes.get(i + 1..i + 3) {
+        Some([b'@', b'&']) => (&mut out.role_ids, i + 3),
+        Some([b'@', _]) => (&mut out.user_ids, i + 2),
+        Some([b'#', _]) => (&mut out.channel_ids, i + 2),
+        _ => return None,
+    };
+    let mut j = start;
+    while j < bytes.len() && bytes[j].is_ascii_digit() {
+        j += 1;
+    }
+    (j > start && bytes.get(j) == Some(&b'>')).then(|| (list, &content[start..j], j + 1))
+}
+
+/// Extracts `@everyone`/`@here`, `@username`, and ID tokens from [content].
 ///
 /// A `@` only starts a mention at the beginning of the string or when preceded
 /// by a non-alphanumeric byte, so the domain part of an email (`foo@bar`) is not
@@ -27,6 +55,15 @@ pub fn parse_mentions(content: &str) -> ParsedMentions {
     let mut out = ParsedMentions::default();
     let mut i = 0;
     while i < bytes.len() {
+        if bytes[i] == b'<' {
+            if let Some((list, id, end)) = parse_token(content, &mut out, i) {
+                if !list.iter().any(|existing| existing == id) {
+                    list.push(id.to_string());
+                }
+                i = end;
+                continue;
+            }
+        }
         if bytes[i] != b'@' || (i > 0 && bytes[i - 1].is_ascii_alphanumeric()) {
             i += 1;
             continue;
@@ -77,6 +114,16 @@ mod tests {
         assert!(!m.everyone);
     }
 
+    #[test]
+    fn parses_id_tokens() {
+        let m = parse_mentions("<@123> see <#456>, ask <@&789> or <@123> @bob <@abc>");
+        assert_eq!(m.user_ids, vec!["123".to_string()]);
+        assert_eq!(m.channel_ids, vec!["456".to_string()]);
+        assert_eq!(m.role_ids, vec!["789".to_string()]);
+        // Malformed tokens fall back to plain handles.
+        assert_eq!(m.usernames, vec!["bob".to_string(), "abc".to_string()]);
+    }
+
     #[test]
     fn handles_unicode_after_at() {
         // A `@` followed by a non-ASCII letter yields no ASCII handle.
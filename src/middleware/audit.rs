@@ -0,0 +1,78 @@
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+
+/// Longest reason stored in the audit log, in characters.
+pub const MAX_AUDIT_REASON_LEN: usize = 512;
+
+/// The optional `X-Audit-Log-Reason` header attached to moderation requests.
+///
+/// Clients percent-encode the value so non-ASCII reasons survive as a header;
+/// it is decoded, trimmed, and capped at [MAX_AUDIT_REASON_LEN] characters.
+/// Never rejects: a missing or empty header is `AuditReason(None)`.
+#[derive(Debug, Clone, Default)]
+pub struct AuditReason(pub Option<String>);
+
+impl AuditReason {
+    pub fn as_deref(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuditReason {
+    type Rejection = std::convert::Infallible;
+
+    fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> impl std::future::Future<Output = Result<Self, Self::Rejection>> + Send {
+        let reason = parts
+            .headers
+            .get("X-Audit-Log-Reason")
+            .map(|v| percent_decode(v.as_bytes()))
+            .map(|s| {
+                s.trim()
+                    .chars()
+                    .take(MAX_AUDIT_REASON_LEN)
+                    .collect::<String>()
+            })
+            .filter(|s| !s.is_empty());
+        async move { Ok(AuditReason(reason)) }
+    }
+}
+
+/// Decode `%XX` escapes; anything malformed is kept as-is.
+fn percent_decode(input: &[u8]) -> String {
+    let hex = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] == b'%' && i + 2 < input.len() {
+            if let (Some(hi), Some(lo)) = (hex(input[i + 1]), hex(input[i + 2])) {
+                out.push((hi << 4) | lo);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(input[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(percent_decode(b"spam%20bot"), "spam bot");
+        assert_eq!(percent_decode(b"%C3%A9t%C3%A9"), "été");
+    }
+
+    #[test]
+    fn keeps_malformed_escapes() {
+        assert_eq!(percent_decode(b"100%"), "100%");
+        assert_eq!(percent_decode(b"%zz"), "%zz");
+        assert_eq!(percent_decode(b"%4"), "%4");
+    }
+}
@@ -1,12 +1,14 @@
-use axum::extract::FromRequestParts;
+use crate::db::DbPool;
+use axum::extract::{FromRequestParts, MatchedPath};
 use axum::http::request::Parts;
-use axum::http::StatusCode;
+use axum::http::{Method, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde_json::json;
 use sha2::{Digest, Sha256};
-use sqlx::AnyPool;
+use std::net::IpAddr;
 
+use super::ip_allowlist::client_ip;
 use crate::state::AppState;
 
 #[derive(Debug, Clone)]
@@ -18,6 +20,9 @@ pub struct AuthUser {
     pub is_guest: bool,
     /// Space ID the guest token is scoped to (only set for guest tokens).
     pub guest_space_id: Option<String>,
+    /// The login session behind a user token; see [crate::db::sessions::session_id].
+    /// `None` for bot, OAuth2 and guest tokens.
+    pub login_session_id: Option<String>,
 }
 
 fn hash_token(token: &str) -> String {
@@ -26,39 +31,55 @@ fn hash_token(token: &str) -> String {
     format!("{:x}", hasher.finalize())
 }
 
-async fn resolve_bot_token(pool: &AnyPool, token: &str) -> Option<AuthUser> {
+/// Resolve a bot token. A valid token used from outside its application's IP
+/// allowlist is rejected with [AuthRejection::IpNotAllowed].
+async fn resolve_bot_token(
+    pool: &DbPool,
+    token: &str,
+    ip: Option<IpAddr>,
+) -> Result<AuthUser, AuthRejection> {
     let token_hash = hash_token(token);
     let row = sqlx::query(
-        &crate::db::q("SELECT bt.user_id, u.is_admin, u.disabled FROM bot_tokens bt JOIN users u ON bt.user_id = u.id WHERE bt.token_hash = ?"),
+        &crate::db::q("SELECT bt.user_id, u.is_admin, u.disabled, a.ip_allowlist FROM bot_tokens bt JOIN users u ON bt.user_id = u.id LEFT JOIN applications a ON bt.application_id = a.id WHERE bt.token_hash = ?"),
     )
     .bind(&token_hash)
     .fetch_optional(pool)
     .await
-    .ok()??;
+    .ok()
+    .flatten()
+    .ok_or(AuthRejection::Unauthorized)?;
 
     use sqlx::Row;
     let user_id: String = row.get("user_id");
     let is_admin = crate::db::get_bool(&row, "is_admin");
     let disabled = crate::db::get_bool(&row, "disabled");
+    // Tokens without an application have no allowlist.
+    let ip_allowlist: String = row
+        .get::<Option<String>, _>("ip_allowlist")
+        .unwrap_or_else(|| "[]".to_string());
 
     // Disabled users cannot authenticate
     if disabled {
-        return None;
+        return Err(AuthRejection::Unauthorized);
+    }
+    if !super::ip_allowlist::is_allowed(&ip_allowlist, ip) {
+        return Err(AuthRejection::IpNotAllowed);
     }
 
-    Some(AuthUser {
+    Ok(AuthUser {
         user_id,
         is_bot: true,
         is_admin,
         is_guest: false,
         guest_space_id: None,
+        login_session_id: None,
     })
 }
 
-async fn resolve_bearer_token(pool: &AnyPool, token: &str) -> Option<AuthUser> {
+async fn resolve_bearer_token(pool: &DbPool, token: &str, ip: Option<IpAddr>) -> Option<AuthUser> {
     let token_hash = hash_token(token);
     let row = sqlx::query(
-        &crate::db::q("SELECT ut.user_id, ut.expires_at, u.is_admin, u.disabled FROM user_tokens ut JOIN users u ON ut.user_id = u.id WHERE ut.token_hash = ?"),
+        &crate::db::q("SELECT ut.user_id, ut.expires_at, ut.last_used_at, u.is_admin, u.disabled FROM user_tokens ut JOIN users u ON ut.user_id = u.id WHERE ut.token_hash = ?"),
     )
     .bind(&token_hash)
     .fetch_optional(pool)
@@ -66,6 +87,7 @@ async fn resolve_bearer_token(pool: &AnyPool, token: &str) -> Option<AuthUser> {
     .ok()??;
 
     use sqlx::Row;
+    let login_session_id = crate::db::sessions::session_id(&token_hash).to_string();
     let user_id: String = row.get("user_id");
     let expires_at: String = row.get("expires_at");
     let is_admin = crate::db::get_bool(&row, "is_admin");
@@ -93,16 +115,66 @@ async fn resolve_bearer_token(pool: &AnyPool, token: &str) -> Option<AuthUser> {
         return None;
     }
 
+    // Refresh the session's last use off the request path, at most every
+    // few minutes.
+    let last_used_at: Option<String> = row.get("last_used_at");
+    let stale = last_used_at.is_none_or(|at| {
+        chrono::DateTime::parse_from_str(&at, "%Y-%m-%dT%H:%M:%S%z").is_ok_and(|at| {
+            chrono::Utc::now() - at.to_utc()
+                > chrono::Duration::seconds(crate::db::sessions::TOUCH_INTERVAL_SECS)
+        })
+    });
+    if stale {
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            let ip = ip.map(|ip| ip.to_string());
+            if let Err(e) =
+                crate::db::sessions::touch_session(&pool, &token_hash, ip.as_deref()).await
+            {
+                tracing::warn!("failed to record session use: {e:?}");
+            }
+        });
+    }
+
     Some(AuthUser {
         user_id,
         is_bot: false,
         is_admin,
         is_guest: false,
         guest_space_id: None,
+        login_session_id: Some(login_session_id),
     })
 }
 
-async fn resolve_guest_token(pool: &AnyPool, token: &str) -> Option<AuthUser> {
+/// Resolve an OAuth2 access token. The token only works on routes its scopes
+/// cover; anywhere else it's rejected with [AuthRejection::MissingScope].
+async fn resolve_oauth_token(
+    pool: &DbPool,
+    token: &str,
+    method: &Method,
+    path: Option<&str>,
+) -> Option<Result<AuthUser, AuthRejection>> {
+    let (grant, is_admin) = crate::db::oauth2::resolve_access_token(pool, &hash_token(token))
+        .await
+        .ok()??;
+    let path = path.map(|p| p.strip_prefix("/api/v1").unwrap_or(p));
+    let allowed = path
+        .and_then(|path| crate::oauth2::route_scope(method, path))
+        .is_some_and(|scope| grant.scopes.iter().any(|s| s == scope));
+    if !allowed {
+        return Some(Err(AuthRejection::MissingScope));
+    }
+    Some(Ok(AuthUser {
+        user_id: grant.user_id,
+        is_bot: false,
+        is_admin,
+        is_guest: false,
+        guest_space_id: None,
+        login_session_id: None,
+    }))
+}
+
+async fn resolve_guest_token(pool: &DbPool, token: &str) -> Option<AuthUser> {
     let token_hash = hash_token(token);
     let row = sqlx::query(&crate::db::q(
         "SELECT space_id, expires_at FROM guest_tokens WHERE token_hash = ?",
@@ -142,21 +214,47 @@ async fn resolve_guest_token(pool: &AnyPool, token: &str) -> Option<AuthUser> {
         is_admin: false,
         is_guest: true,
         guest_space_id: Some(space_id),
+        login_session_id: None,
     })
 }
 
 /// Rejection type for when auth fails.
-pub struct AuthRejection;
+pub enum AuthRejection {
+    /// Missing, invalid, or expired credentials.
+    Unauthorized,
+    /// A valid bot token used from an address outside its application's
+    /// IP allowlist.
+    IpNotAllowed,
+    /// An OAuth2 access token used on a route its scopes don't cover.
+    MissingScope,
+}
 
 impl IntoResponse for AuthRejection {
     fn into_response(self) -> Response {
+        let (status, code, message) = match self {
+            AuthRejection::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                "invalid or missing authentication",
+            ),
+            AuthRejection::IpNotAllowed => (
+                StatusCode::FORBIDDEN,
+                "ip_not_allowed",
+                "this bot token cannot be used from this IP address",
+            ),
+            AuthRejection::MissingScope => (
+                StatusCode::FORBIDDEN,
+                "missing_scope",
+                "this OAuth2 token's scopes don't cover this endpoint",
+            ),
+        };
         let body = json!({
             "error": {
-                "code": "unauthorized",
-                "message": "invalid or missing authentication"
+                "code": code,
+                "message": message
             }
         });
-        (StatusCode::UNAUTHORIZED, Json(body)).into_response()
+        (status, Json(body)).into_response()
     }
 }
 
@@ -173,18 +271,28 @@ impl FromRequestParts<AppState> for AuthUser {
             .get("Authorization")
             .and_then(|v| v.to_str().ok())
             .map(|s| s.to_string());
+        let ip = client_ip(&parts.headers, &parts.extensions);
+        let method = parts.method.clone();
+        let path = parts
+            .extensions
+            .get::<MatchedPath>()
+            .map(|p| p.as_str().to_string());
 
         async move {
             let auth_user = match auth_header {
                 Some(header) if header.starts_with("Bot ") => {
-                    resolve_bot_token(&pool, &header[4..]).await
+                    return resolve_bot_token(&pool, &header[4..], ip).await;
                 }
                 Some(header) if header.starts_with("Bearer ") => {
                     let token = &header[7..];
-                    // Try regular bearer token first, fall back to guest token
-                    let user = resolve_bearer_token(&pool, token).await;
+                    // Try regular bearer token first, then OAuth2, then guest
+                    let user = resolve_bearer_token(&pool, token, ip).await;
                     if user.is_some() {
                         user
+                    } else if let Some(oauth) =
+                        resolve_oauth_token(&pool, token, &method, path.as_deref()).await
+                    {
+                        return oauth;
                     } else {
                         resolve_guest_token(&pool, token).await
                     }
@@ -192,7 +300,7 @@ impl FromRequestParts<AppState> for AuthUser {
                 _ => None,
             };
 
-            auth_user.ok_or(AuthRejection)
+            auth_user.ok_or(AuthRejection::Unauthorized)
         }
     }
 }
@@ -214,17 +322,27 @@ impl FromRequestParts<AppState> for OptionalAuthUser {
             .get("Authorization")
             .and_then(|v| v.to_str().ok())
             .map(|s| s.to_string());
+        let ip = client_ip(&parts.headers, &parts.extensions);
+        let method = parts.method.clone();
+        let path = parts
+            .extensions
+            .get::<MatchedPath>()
+            .map(|p| p.as_str().to_string());
 
         async move {
             let auth_user = match auth_header {
                 Some(header) if header.starts_with("Bot ") => {
-                    resolve_bot_token(&pool, &header[4..]).await
+                    resolve_bot_token(&pool, &header[4..], ip).await.ok()
                 }
                 Some(header) if header.starts_with("Bearer ") => {
                     let token = &header[7..];
-                    let user = resolve_bearer_token(&pool, token).await;
+                    let user = resolve_bearer_token(&pool, token, ip).await;
                     if user.is_some() {
                         user
+                    } else if let Some(oauth) =
+                        resolve_oauth_token(&pool, token, &method, path.as_deref()).await
+                    {
+                        oauth.ok()
                     } else {
                         resolve_guest_token(&pool, token).await
                     }
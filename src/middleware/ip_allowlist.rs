@@ -0,0 +1,159 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::extract::ConnectInfo;
+use axum::http::{Extensions, HeaderMap};
+
+/// Most CIDR ranges a single application may allowlist.
+pub const MAX_ALLOWLIST_ENTRIES: usize = 32;
+
+/// An IP network in CIDR notation. A bare address is a single-host range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (
+                addr.parse::<IpAddr>().ok()?,
+                Some(prefix.parse::<u8>().ok()?),
+            ),
+            None => (s.trim().parse::<IpAddr>().ok()?, None),
+        };
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        if prefix > max {
+            return None;
+        }
+        Some(Cidr { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // Compare IPv4-mapped IPv6 peers against IPv4 ranges.
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Cidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Whether [ip] is allowed by a stored allowlist (a JSON array of CIDR
+/// strings). An empty list allows everything; an unknown IP is only allowed
+/// when the list is empty.
+pub fn is_allowed(allowlist_json: &str, ip: Option<IpAddr>) -> bool {
+    let ranges: Vec<String> = serde_json::from_str(allowlist_json).unwrap_or_default();
+    if ranges.is_empty() {
+        return true;
+    }
+    let Some(ip) = ip else {
+        return false;
+    };
+    ranges
+        .iter()
+        .filter_map(|r| Cidr::parse(r))
+        .any(|c| c.contains(ip))
+}
+
+fn is_proxy_peer(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_loopback() || v4.is_private(),
+        IpAddr::V6(v6) => {
+            v6.is_loopback()
+                || v6
+                    .to_ipv4_mapped()
+                    .is_some_and(|v4| v4.is_loopback() || v4.is_private())
+        }
+    }
+}
+
+/// The client's IP address. Forwarding headers are only trusted when the
+/// peer is a loopback or private address (a reverse proxy) or the peer is
+/// unknown; `X-Real-IP` wins, otherwise the last `X-Forwarded-For` hop, since
+/// that is the one the proxy appended.
+pub fn client_ip(headers: &HeaderMap, extensions: &Extensions) -> Option<IpAddr> {
+    let peer = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    if peer.is_some_and(|ip| !is_proxy_peer(ip)) {
+        return peer;
+    }
+    let forwarded = headers
+        .get("X-Real-IP")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.trim().parse().ok())
+        .or_else(|| {
+            headers
+                .get("X-Forwarded-For")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|s| s.rsplit(',').next())
+                .and_then(|s| s.trim().parse().ok())
+        });
+    forwarded.or(peer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_matches_ranges() {
+        let net = Cidr::parse("10.1.0.0/16").unwrap();
+        assert!(net.contains("10.1.200.3".parse().unwrap()));
+        assert!(!net.contains("10.2.0.1".parse().unwrap()));
+        assert!(net.contains("::ffff:10.1.0.9".parse().unwrap()));
+
+        let host = Cidr::parse("2001:db8::1").unwrap();
+        assert_eq!(host.to_string(), "2001:db8::1/128");
+        assert!(host.contains("2001:db8::1".parse().unwrap()));
+        assert!(!host.contains("2001:db8::2".parse().unwrap()));
+
+        assert!(Cidr::parse("0.0.0.0/0")
+            .unwrap()
+            .contains("8.8.8.8".parse().unwrap()));
+        assert!(Cidr::parse("10.0.0.0/33").is_none());
+        assert!(Cidr::parse("not-an-ip").is_none());
+    }
+
+    #[test]
+    fn empty_allowlist_allows_everything() {
+        assert!(is_allowed("[]", None));
+        assert!(!is_allowed(r#"["10.0.0.0/8"]"#, None));
+        assert!(is_allowed(
+            r#"["10.0.0.0/8"]"#,
+            Some("10.9.9.9".parse().unwrap())
+        ));
+    }
+
+    #[test]
+    fn forwarding_headers_ignored_from_public_peers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Forwarded-For", "1.1.1.1, 10.0.0.5".parse().unwrap());
+        let mut ext = Extensions::new();
+        assert_eq!(client_ip(&headers, &ext), Some("10.0.0.5".parse().unwrap()));
+
+        ext.insert(ConnectInfo(SocketAddr::from(([203, 0, 113, 7], 443))));
+        assert_eq!(
+            client_ip(&headers, &ext),
+            Some("203.0.113.7".parse().unwrap())
+        );
+    }
+}
@@ -1,3 +1,7 @@
+pub mod audit;
 pub mod auth;
+pub mod ip_allowlist;
 pub mod permissions;
+pub mod query_count;
 pub mod rate_limit;
+pub mod security_headers;
@@ -1,14 +1,15 @@
-use sqlx::AnyPool;
+use crate::db::DbPool;
 
 use crate::db;
 use crate::error::AppError;
 use crate::middleware::auth::AuthUser;
-use crate::models::permission::has_permission;
+use crate::models::permission::{has_permission, PermissionOverwrite, Permissions};
 
 /// Default permissions granted to the @everyone role when a space is created.
 pub const DEFAULT_EVERYONE_PERMISSIONS: &[&str] = &[
     "view_channel",
     "send_messages",
+    "send_messages_in_voice",
     "read_history",
     "add_reactions",
     "create_invites",
@@ -30,6 +31,7 @@ pub const MODERATOR_PERMISSIONS: &[&str] = &[
     // @everyone base
     "view_channel",
     "send_messages",
+    "send_messages_in_voice",
     "read_history",
     "add_reactions",
     "create_invites",
@@ -61,6 +63,7 @@ pub const ADMIN_PERMISSIONS: &[&str] = &[
     // @everyone base
     "view_channel",
     "send_messages",
+    "send_messages_in_voice",
     "read_history",
     "add_reactions",
     "create_invites",
@@ -107,43 +110,43 @@ pub fn require_server_admin(auth: &AuthUser) -> Result<(), AppError> {
 
 /// Compute effective permissions for a user in a space.
 ///
-/// - If `is_server_admin` is true, returns `["administrator"]` (instance-level bypass).
-/// - If the user is the space owner, returns `["administrator"]`.
+/// - If `is_server_admin` is true, returns `administrator` (instance-level bypass).
+/// - If the user is the space owner, returns `administrator`.
 /// - If the user is not a member, returns `Forbidden`.
 /// - Otherwise, merges @everyone permissions with all assigned role permissions.
 pub async fn resolve_member_permissions(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
-) -> Result<Vec<String>, AppError> {
+) -> Result<Permissions, AppError> {
     resolve_member_permissions_inner(pool, space_id, user_id, false).await
 }
 
 /// Like `resolve_member_permissions` but allows an instance-admin bypass.
 pub async fn resolve_member_permissions_with_admin(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
     is_server_admin: bool,
-) -> Result<Vec<String>, AppError> {
+) -> Result<Permissions, AppError> {
     resolve_member_permissions_inner(pool, space_id, user_id, is_server_admin).await
 }
 
 async fn resolve_member_permissions_inner(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
     is_server_admin: bool,
-) -> Result<Vec<String>, AppError> {
+) -> Result<Permissions, AppError> {
     // Instance-level admin bypass
     if is_server_admin {
-        return Ok(vec!["administrator".to_string()]);
+        return Ok(Permissions::ADMINISTRATOR);
     }
 
     // Check ownership first
     let space = db::spaces::get_space_row(pool, space_id).await?;
     if space.owner_id == user_id {
-        return Ok(vec!["administrator".to_string()]);
+        return Ok(Permissions::ADMINISTRATOR);
     }
 
     // Verify membership (will return NotFound → we convert to Forbidden)
@@ -158,38 +161,57 @@ async fn resolve_member_permissions_inner(
 
     // Start with @everyone role permissions
     let roles = db::roles::list_roles(pool, space_id).await?;
-    let mut perms: Vec<String> = Vec::new();
+    let mut perms = Permissions::empty();
 
     // Find @everyone role (position 0)
     if let Some(everyone) = roles.iter().find(|r| r.position == 0) {
-        let everyone_perms: Vec<String> =
-            serde_json::from_str(&everyone.permissions).unwrap_or_default();
-        perms.extend(everyone_perms);
+        perms |= everyone.permissions;
     }
 
     // Get member's assigned roles and merge their permissions
     let member_role_ids = db::members::get_member_role_ids(pool, space_id, user_id).await?;
     for role in &roles {
         if member_role_ids.contains(&role.id) {
-            let role_perms: Vec<String> =
-                serde_json::from_str(&role.permissions).unwrap_or_default();
-            for p in role_perms {
-                if !perms.contains(&p) {
-                    perms.push(p);
-                }
-            }
+            perms |= role.permissions;
         }
     }
 
     Ok(perms)
 }
 
+/// IDs of every space member whose space-level permissions include `perm`
+/// (directly or via `administrator`), plus the owner. Channel overwrites are
+/// not considered. Used to target moderator-only gateway events.
+pub async fn list_member_ids_with_permission(
+    pool: &DbPool,
+    space_id: &str,
+    perm: &str,
+) -> Result<Vec<String>, AppError> {
+    let space = db::spaces::get_space_row(pool, space_id).await?;
+    let roles = db::roles::list_roles(pool, space_id).await?;
+    let granting: Vec<_> = roles
+        .iter()
+        .filter(|r| has_permission(r.permissions, perm))
+        .collect();
+
+    let mut ids = if granting.iter().any(|r| r.position == 0) {
+        db::spaces::list_member_ids_for_space(pool, space_id).await?
+    } else {
+        let role_ids: Vec<String> = granting.iter().map(|r| r.id.clone()).collect();
+        db::members::list_member_ids_with_roles(pool, space_id, &role_ids).await?
+    };
+    if !ids.contains(&space.owner_id) {
+        ids.push(space.owner_id);
+    }
+    Ok(ids)
+}
+
 /// Check that a user has a specific permission in a space.
 /// Instance admins (`auth.is_admin`) bypass all permission checks.
 /// Guest tokens are scoped to read-only access on their assigned space.
 /// Returns `Forbidden` if the user lacks the permission or is not a member.
 pub async fn require_permission(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     auth: &AuthUser,
     perm: &str,
@@ -200,7 +222,7 @@ pub async fn require_permission(
     }
     let perms =
         resolve_member_permissions_with_admin(pool, space_id, &auth.user_id, auth.is_admin).await?;
-    if !has_permission(&perms, perm) {
+    if !has_permission(perms, perm) {
         return Err(AppError::Forbidden(format!("missing permission: {perm}")));
     }
     Ok(())
@@ -233,12 +255,12 @@ fn require_guest_space_permission(
 /// Shorthand: require that a user is a member of the space (has view_channel).
 /// Note: does not handle guest tokens — use `require_permission` with an `AuthUser` for guests.
 pub async fn require_membership(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
 ) -> Result<(), AppError> {
     let perms = resolve_member_permissions(pool, space_id, user_id).await?;
-    if !has_permission(&perms, "view_channel") {
+    if !perms.has(Permissions::VIEW_CHANNEL) {
         return Err(AppError::Forbidden(
             "missing permission: view_channel".into(),
         ));
@@ -253,18 +275,18 @@ pub async fn require_membership(
 /// 1. Start with base space permissions from `resolve_member_permissions`.
 /// 2. If base includes `administrator`, return immediately (bypass).
 /// 3. Apply @everyone role overwrite: deny removes, allow adds.
-/// 4. Union of user's role overwrites: collect all allow/deny, allow wins, then apply.
+/// 4. Union of user's role overwrites, applied together (so allow wins).
 /// 5. Apply member-specific overwrite: deny removes, allow adds.
 pub async fn resolve_channel_permissions(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     space_id: &str,
     user_id: &str,
-) -> Result<Vec<String>, AppError> {
-    let mut perms = resolve_member_permissions(pool, space_id, user_id).await?;
+) -> Result<Permissions, AppError> {
+    let perms = resolve_member_permissions(pool, space_id, user_id).await?;
 
     // Administrator bypasses all overwrites
-    if perms.iter().any(|p| p == "administrator") {
+    if perms.is_admin() {
         return Ok(perms);
     }
 
@@ -275,80 +297,63 @@ pub async fn resolve_channel_permissions(
 
     // Find the @everyone role (its ID is the role at position 0)
     let roles = db::roles::list_roles(pool, space_id).await?;
-    let everyone_role_id = roles.iter().find(|r| r.position == 0).map(|r| r.id.clone());
+    let everyone_role_id = roles
+        .iter()
+        .find(|r| r.position == 0)
+        .map(|r| r.id.as_str());
 
+    let member_role_ids = db::members::get_member_role_ids(pool, space_id, user_id).await?;
+    Ok(apply_channel_overwrites(
+        perms,
+        &overwrites,
+        everyone_role_id,
+        &member_role_ids,
+        user_id,
+    ))
+}
+
+/// Apply a channel's overwrites to a member's space-level `perms`. Callers
+/// handle the `administrator` bypass first.
+pub fn apply_channel_overwrites(
+    mut perms: Permissions,
+    overwrites: &[PermissionOverwrite],
+    everyone_role_id: Option<&str>,
+    member_role_ids: &[String],
+    user_id: &str,
+) -> Permissions {
     // Step 1: Apply @everyone role overwrite
-    if let Some(ref eid) = everyone_role_id {
+    if let Some(eid) = everyone_role_id {
         if let Some(ow) = overwrites
             .iter()
-            .find(|o| o.overwrite_type == "role" && o.id == *eid)
+            .find(|o| o.overwrite_type == "role" && o.id == eid)
         {
-            for d in &ow.deny {
-                perms.retain(|p| p != d);
-            }
-            for a in &ow.allow {
-                if !perms.contains(a) {
-                    perms.push(a.clone());
-                }
-            }
+            perms = perms.overwrite(ow.allow, ow.deny);
         }
     }
 
     // Step 2: Union of user's assigned role overwrites
-    let member_role_ids = db::members::get_member_role_ids(pool, space_id, user_id).await?;
-    let role_overwrites: Vec<&crate::models::permission::PermissionOverwrite> = overwrites
-        .iter()
-        .filter(|o| {
-            o.overwrite_type == "role"
-                && member_role_ids.contains(&o.id)
-                && everyone_role_id.as_deref() != Some(&o.id)
-        })
-        .collect();
-
-    if !role_overwrites.is_empty() {
-        let mut role_allow: Vec<String> = Vec::new();
-        let mut role_deny: Vec<String> = Vec::new();
-        for ow in &role_overwrites {
-            for a in &ow.allow {
-                if !role_allow.contains(a) {
-                    role_allow.push(a.clone());
-                }
-            }
-            for d in &ow.deny {
-                if !role_deny.contains(d) {
-                    role_deny.push(d.clone());
-                }
-            }
-        }
-        // Allow wins over deny across roles
-        role_deny.retain(|d| !role_allow.contains(d));
-
-        for d in &role_deny {
-            perms.retain(|p| p != d);
-        }
-        for a in &role_allow {
-            if !perms.contains(a) {
-                perms.push(a.clone());
-            }
-        }
+    let mut role_allow = Permissions::empty();
+    let mut role_deny = Permissions::empty();
+    for ow in overwrites.iter().filter(|o| {
+        o.overwrite_type == "role"
+            && member_role_ids.contains(&o.id)
+            && everyone_role_id != Some(o.id.as_str())
+    }) {
+        role_allow |= ow.allow;
+        role_deny |= ow.deny;
     }
+    // Allow wins over deny across roles: it is applied after
+    perms = perms.overwrite(role_allow, role_deny);
 
     // Step 3: Apply member-specific overwrite (highest precedence)
     if let Some(ow) = overwrites
         .iter()
         .find(|o| o.overwrite_type == "member" && o.id == user_id)
     {
-        for d in &ow.deny {
-            perms.retain(|p| p != d);
-        }
-        for a in &ow.allow {
-            if !perms.contains(a) {
-                perms.push(a.clone());
-            }
-        }
+        perms = perms.overwrite(ow.allow, ow.deny);
     }
 
-    Ok(perms)
+    perms
 }
 
 /// Returns `true` if the given timeout timestamp is in the future, i.e. the
@@ -367,7 +372,7 @@ pub fn is_timed_out(timed_out_until: Option<&str>) -> bool {
 /// the given space. Used to gate message sending, reactions, typing, and voice
 /// connect for timed-out members. Instance admins are exempt.
 pub async fn require_not_timed_out(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     auth: &AuthUser,
 ) -> Result<(), AppError> {
@@ -388,9 +393,102 @@ pub async fn require_not_timed_out(
     Ok(())
 }
 
+/// Rejects with 403 `space_archived` if the space is archived. Archived
+/// spaces stay readable, but nothing is posted, no channel changes, and
+/// nobody joins until the owner unarchives them.
+pub async fn require_not_archived(pool: &DbPool, space_id: &str) -> Result<(), AppError> {
+    if db::spaces::is_archived(pool, space_id).await? {
+        return Err(AppError::ForbiddenCode(
+            "space_archived",
+            "this space is archived and read-only".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects with 403 `screening_pending` while the member hasn't accepted the
+/// space's rules yet. Pending members can read but not post, react or start
+/// threads. Bots and instance admins are exempt.
+pub async fn require_not_pending(
+    pool: &DbPool,
+    space_id: &str,
+    auth: &AuthUser,
+) -> Result<(), AppError> {
+    if auth.is_bot || auth.is_admin {
+        return Ok(());
+    }
+    let member = match db::members::get_member_row(pool, space_id, &auth.user_id).await {
+        Ok(m) => m,
+        Err(AppError::NotFound(_)) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if member.pending {
+        return Err(AppError::ForbiddenCode(
+            "screening_pending",
+            "accept this space's rules before taking part".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// How long a TOTP or backup-code verification counts as recent.
+pub const MFA_RECENT_SECS: i64 = 600;
+
+/// In spaces whose `mfa_level` is `elevated`, destructive actions (deleting
+/// the space, channels or roles, kicks and bans) need the calling session to
+/// have verified a code within [MFA_RECENT_SECS] -- at login or through
+/// `POST /auth/mfa/verify`. Callers without TOTP get `mfa_not_enrolled`
+/// instead, since no code would help. Bots can't hold a TOTP secret and are
+/// exempt.
+pub async fn require_recent_mfa(
+    pool: &DbPool,
+    space_id: &str,
+    auth: &AuthUser,
+) -> Result<(), AppError> {
+    if auth.is_bot {
+        return Ok(());
+    }
+    let mfa_level: Option<String> =
+        sqlx::query_scalar(&db::q("SELECT mfa_level FROM spaces WHERE id = ?"))
+            .bind(space_id)
+            .fetch_optional(pool)
+            .await?;
+    if mfa_level.as_deref() != Some("elevated") {
+        return Ok(());
+    }
+    let enrolled = sqlx::query(&db::q("SELECT totp_enabled FROM users WHERE id = ?"))
+        .bind(&auth.user_id)
+        .fetch_optional(pool)
+        .await?
+        .is_some_and(|row| db::get_bool(&row, "totp_enabled"));
+    if !enrolled {
+        return Err(AppError::ForbiddenCode(
+            "mfa_not_enrolled",
+            "this space requires two-factor authentication for moderation; enable it first".into(),
+        ));
+    }
+    let verified_at = match auth.login_session_id {
+        Some(ref session_id) => {
+            db::sessions::mfa_verified_at(pool, &auth.user_id, session_id).await?
+        }
+        None => None,
+    };
+    let cutoff = (chrono::Utc::now() - chrono::Duration::seconds(MFA_RECENT_SECS))
+        .format("%Y-%m-%dT%H:%M:%S+00:00")
+        .to_string();
+    if verified_at.is_some_and(|t| t >= cutoff) {
+        return Ok(());
+    }
+    Err(AppError::ForbiddenCode(
+        "mfa_required",
+        "this space requires a recent MFA verification; verify a code with POST /auth/mfa/verify"
+            .into(),
+    ))
+}
+
 /// Check that a user is a participant in a DM channel.
 pub async fn require_dm_access(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     user_id: &str,
 ) -> Result<(), AppError> {
@@ -402,6 +500,28 @@ pub async fn require_dm_access(
     Ok(())
 }
 
+/// Rejects with 403 if [channel_id] is a 1:1 DM whose other participant has
+/// blocked the user. Space channels and group DMs pass.
+pub async fn require_not_blocked_in_dm(
+    pool: &DbPool,
+    channel_id: &str,
+    user_id: &str,
+) -> Result<(), AppError> {
+    let channel = db::channels::get_channel_row(pool, channel_id).await?;
+    if channel.channel_type != "dm" {
+        return Ok(());
+    }
+    for other in db::dm_participants::list_participant_ids(pool, channel_id).await? {
+        if other != user_id && db::relationships::is_blocked_by(pool, &other, user_id).await? {
+            return Err(AppError::ForbiddenCode(
+                "blocked_by_recipient",
+                "you cannot send a DM to this user".into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
 /// Check that a user has a specific permission for a channel.
 /// Uses `resolve_channel_permissions` which accounts for overwrites.
 /// Instance admins (`auth.is_admin`) bypass all permission checks.
@@ -409,7 +529,7 @@ pub async fn require_dm_access(
 /// For DM/group_dm channels, checks participant access instead.
 /// Returns the space_id on success (empty string for DMs).
 pub async fn require_channel_permission(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     auth: &AuthUser,
     perm: &str,
@@ -458,16 +578,49 @@ pub async fn require_channel_permission(
         return Ok(space_id);
     }
     let perms = resolve_channel_permissions(pool, channel_id, &space_id, &auth.user_id).await?;
-    if !has_permission(&perms, perm) {
+    if !has_permission(perms, perm) {
         return Err(AppError::Forbidden(format!("missing permission: {perm}")));
     }
     Ok(space_id)
 }
 
+/// The permission needed to post in a channel of [channel_type]. Text chat
+/// in voice and stage channels is granted separately from text channels.
+pub fn send_permission_for(channel_type: &str) -> &'static str {
+    if crate::models::channel::is_voice_type(channel_type) {
+        "send_messages_in_voice"
+    } else {
+        "send_messages"
+    }
+}
+
+/// [require_channel_permission] with the send permission for the channel's
+/// type, in a space that isn't archived, by a member who isn't pending
+/// screening. Returns the space ID (empty for DMs).
+pub async fn require_send_permission(
+    pool: &DbPool,
+    channel_id: &str,
+    auth: &AuthUser,
+) -> Result<String, AppError> {
+    let channel = db::channels::get_channel_row(pool, channel_id).await?;
+    let space_id = require_channel_permission(
+        pool,
+        channel_id,
+        auth,
+        send_permission_for(&channel.channel_type),
+    )
+    .await?;
+    if !space_id.is_empty() {
+        require_not_archived(pool, &space_id).await?;
+        require_not_pending(pool, &space_id, auth).await?;
+    }
+    Ok(space_id)
+}
+
 /// Shorthand: require that a user is a member of the channel's space.
 /// Returns the space_id on success.
 pub async fn require_channel_membership(
-    pool: &AnyPool,
+    pool: &DbPool,
     channel_id: &str,
     user_id: &str,
 ) -> Result<String, AppError> {
@@ -479,6 +632,7 @@ pub async fn require_channel_membership(
         is_admin: false,
         is_guest: false,
         guest_space_id: None,
+        login_session_id: None,
     };
     require_channel_permission(pool, channel_id, &auth, "view_channel").await
 }
@@ -486,7 +640,7 @@ pub async fn require_channel_membership(
 /// Returns a user's highest role position in a space.
 /// Space owner returns `i64::MAX`. A member with only @everyone returns 0.
 pub async fn get_highest_role_position(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     user_id: &str,
 ) -> Result<i64, AppError> {
@@ -516,7 +670,7 @@ pub async fn get_highest_role_position(
 /// the target user's highest role position. Prevents lateral or upward actions.
 /// Instance admins (`auth.is_admin`) bypass this check.
 pub async fn require_hierarchy(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     auth: &AuthUser,
     target_id: &str,
@@ -537,7 +691,7 @@ pub async fn require_hierarchy(
 /// Requires that the actor's highest role position is strictly greater than
 /// the given role's position. Used for role management operations.
 pub async fn require_role_hierarchy(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     actor_id: &str,
     role_position: i64,
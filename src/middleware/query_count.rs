@@ -0,0 +1,26 @@
+use axum::extract::{Request, State};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::db::instrument;
+use crate::state::AppState;
+
+/// Response header carrying the number of queries a request issued.
+pub const QUERY_COUNT_HEADER: &str = "x-query-count";
+
+/// In test mode, report each request's query count in [QUERY_COUNT_HEADER].
+pub async fn query_count_middleware(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !state.test_mode {
+        return next.run(req).await;
+    }
+    let (mut response, log) = instrument::record(next.run(req)).await;
+    if let Ok(value) = HeaderValue::from_str(&log.count().to_string()) {
+        response.headers_mut().insert(QUERY_COUNT_HEADER, value);
+    }
+    response
+}
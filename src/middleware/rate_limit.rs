@@ -1,22 +1,231 @@
-use axum::extract::{Request, State};
+use std::time::Duration;
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::HeaderMap;
 use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
 use sha2::{Digest, Sha256};
 use tokio::time::Instant;
 
 use crate::error::AppError;
+use crate::middleware::ip_allowlist::client_ip;
 use crate::state::{AppState, RateLimitBucket};
 
-/// Maximum requests per window.
-const RATE_LIMIT: u32 = 60;
-/// Burst allowance on top of the base rate.
-const BURST: u32 = 10;
-/// Total bucket capacity (base + burst).
-const CAPACITY: u32 = RATE_LIMIT + BURST;
-/// Window duration in seconds — tokens refill fully after this period.
-const WINDOW_SECS: u64 = 60;
+/// A class of requests with its own per-user token bucket: `limit` requests
+/// per `window`, refilled one token at a time.
+pub struct RouteLimit {
+    /// Reported in `X-RateLimit-Bucket` so clients can pace each bucket.
+    pub bucket: &'static str,
+    pub limit: u32,
+    pub window: Duration,
+}
+
+impl RouteLimit {
+    /// Time for one token to come back.
+    fn refill_interval(&self) -> Duration {
+        self.window / self.limit
+    }
+}
+
+/// Applies to every request under `/api/v1`, on top of any route bucket.
+/// 60 requests per minute plus a burst of 10.
+pub const DEFAULT_LIMIT: RouteLimit = RouteLimit {
+    bucket: "default",
+    limit: 70,
+    window: Duration::from_secs(60),
+};
+
+/// Routes with a tighter bucket of their own, keyed by method and matched
+/// path (relative to `/api/v1`).
+const ROUTE_LIMITS: &[(&str, &str, RouteLimit)] = &[
+    (
+        "POST",
+        "/channels/{channel_id}/messages",
+        MESSAGE_SEND_LIMIT,
+    ),
+    (
+        "PUT",
+        "/channels/{channel_id}/messages/{message_id}/reactions/{emoji}/@me",
+        REACTION_LIMIT,
+    ),
+    (
+        "DELETE",
+        "/channels/{channel_id}/messages/{message_id}/reactions/{emoji}/@me",
+        REACTION_LIMIT,
+    ),
+    ("POST", "/spaces/{space_id}/invites", INVITE_LIMIT),
+    ("POST", "/channels/{channel_id}/invites", INVITE_LIMIT),
+    ("POST", "/invites/{code}/accept", INVITE_LIMIT),
+];
+
+pub const MESSAGE_SEND_LIMIT: RouteLimit = RouteLimit {
+    bucket: "message_send",
+    limit: 10,
+    window: Duration::from_secs(10),
+};
+
+pub const REACTION_LIMIT: RouteLimit = RouteLimit {
+    bucket: "reactions",
+    limit: 10,
+    window: Duration::from_secs(5),
+};
+
+pub const INVITE_LIMIT: RouteLimit = RouteLimit {
+    bucket: "invites",
+    limit: 10,
+    window: Duration::from_secs(60),
+};
+
+/// Global per-application request quota for bot tokens, applied on top of the
+/// regular bucket. Counts every bot-authenticated request under `/api/v1`,
+/// including interaction callbacks.
+const GLOBAL_BOT_LIMIT: u32 = 50;
+/// Window for the global bot quota in milliseconds.
+const GLOBAL_BOT_WINDOW_MS: u64 = 1000;
+
+/// The route bucket for a request, if it has one.
+fn route_limit(method: &str, matched_path: &str) -> Option<&'static RouteLimit> {
+    let path = matched_path.strip_prefix("/api/v1").unwrap_or(matched_path);
+    ROUTE_LIMITS
+        .iter()
+        .find(|(m, p, _)| *m == method && *p == path)
+        .map(|(_, _, limit)| limit)
+}
+
+/// State of a bucket after a request was let through.
+struct Taken {
+    remaining: u32,
+    /// Time until the bucket is full again.
+    reset_after: Duration,
+}
+
+/// Consume one token from `limit`'s bucket for `key`. Returns
+/// `Err(retry_after)` — the time until the next token — when it is empty.
+fn take_token(
+    state: &AppState,
+    limit: &RouteLimit,
+    key: &str,
+    now: Instant,
+) -> Result<Taken, Duration> {
+    let mut entry = state
+        .rate_limits
+        .entry((limit.bucket, key.to_string()))
+        .or_insert_with(|| RateLimitBucket {
+            remaining: limit.limit,
+            last_refill: now,
+        });
+    let bucket = entry.value_mut();
+    let interval = limit.refill_interval();
+
+    let refill = (now.duration_since(bucket.last_refill).as_nanos() / interval.as_nanos())
+        .min(limit.limit as u128) as u32;
+    if refill > 0 {
+        bucket.remaining = (bucket.remaining + refill).min(limit.limit);
+        bucket.last_refill += interval * refill;
+    }
+    // A full bucket doesn't bank time towards the next token.
+    if bucket.remaining == limit.limit {
+        bucket.last_refill = now;
+    }
 
-/// Token-bucket rate limiter keyed by auth header hash or remote IP.
+    let since_refill = now.duration_since(bucket.last_refill);
+    if bucket.remaining == 0 {
+        return Err(interval.saturating_sub(since_refill));
+    }
+    bucket.remaining -= 1;
+    Ok(Taken {
+        remaining: bucket.remaining,
+        reset_after: (interval * (limit.limit - bucket.remaining)).saturating_sub(since_refill),
+    })
+}
+
+/// Consume one token from the global bot bucket for `key` (see
+/// [global_bot_key]). Returns the remaining tokens, or `Err(retry_after_ms)`
+/// when the quota is exhausted.
+fn take_global_bot_token(state: &AppState, key: &str, now: Instant) -> Result<u32, u64> {
+    let mut entry = state
+        .bot_rate_limits
+        .entry(key.to_string())
+        .or_insert_with(|| RateLimitBucket {
+            remaining: GLOBAL_BOT_LIMIT,
+            last_refill: now,
+        });
+    let bucket = entry.value_mut();
+
+    let elapsed = now.duration_since(bucket.last_refill).as_millis() as u64;
+    if elapsed >= GLOBAL_BOT_WINDOW_MS {
+        bucket.remaining = GLOBAL_BOT_LIMIT;
+        bucket.last_refill = now;
+    }
+
+    if bucket.remaining == 0 {
+        return Err(GLOBAL_BOT_WINDOW_MS.saturating_sub(elapsed).max(1));
+    }
+    bucket.remaining -= 1;
+    Ok(bucket.remaining)
+}
+
+/// Key for the global bot quota: the token's application, so every token
+/// an application holds shares one quota. Unknown tokens fall back to the
+/// request key; they are rejected by the auth extractor anyway.
+async fn global_bot_key(state: &AppState, token: &str, request_key: &str) -> String {
+    match crate::db::auth::bot_token_application_id(&state.db, token).await {
+        Ok(Some(app_id)) => format!("app:{app_id}"),
+        _ => request_key.to_string(),
+    }
+}
+
+/// `X-RateLimit-*` headers describing `limit`'s bucket.
+fn insert_limit_headers(
+    headers: &mut HeaderMap,
+    limit: &RouteLimit,
+    remaining: u32,
+    reset_after: Duration,
+) {
+    headers.insert(
+        "X-RateLimit-Limit",
+        limit.limit.to_string().parse().unwrap(),
+    );
+    headers.insert(
+        "X-RateLimit-Remaining",
+        remaining.to_string().parse().unwrap(),
+    );
+    // Reset is the Unix time (whole seconds, rounded up) at which the bucket
+    // is full again; Reset-After is the same moment relative to now.
+    let reset_ms = chrono::Utc::now().timestamp_millis() + reset_after.as_millis() as i64;
+    headers.insert(
+        "X-RateLimit-Reset",
+        (reset_ms + 999)
+            .div_euclid(1000)
+            .to_string()
+            .parse()
+            .unwrap(),
+    );
+    headers.insert(
+        "X-RateLimit-Reset-After",
+        format!("{:.3}", reset_after.as_secs_f64()).parse().unwrap(),
+    );
+    headers.insert("X-RateLimit-Bucket", limit.bucket.parse().unwrap());
+}
+
+/// 429 for an exhausted bucket, with the headers a client needs to back off.
+fn rate_limited(limit: &RouteLimit, retry_after: Duration) -> Response {
+    // Retry-After is whole seconds; round up so clients never retry early.
+    let retry_after_secs = retry_after.as_millis().div_ceil(1000).max(1) as u64;
+    let mut response = AppError::RateLimited {
+        retry_after: retry_after_secs,
+    }
+    .into_response();
+    let headers = response.headers_mut();
+    insert_limit_headers(headers, limit, 0, retry_after);
+    headers.insert("X-RateLimit-Scope", "user".parse().unwrap());
+    response
+}
+
+/// Token-bucket rate limiter keyed by auth header hash or remote IP. Every
+/// request draws from the default bucket; message sends, reactions and
+/// invites also draw from a bucket of their own, which is the one reported
+/// in the response headers.
 pub async fn rate_limit_middleware(
     State(state): State<AppState>,
     req: Request,
@@ -35,18 +244,8 @@ pub async fn rate_limit_middleware(
         .unwrap_or_else(|| {
             // Use IP-based keying for unauthenticated requests to prevent
             // one attacker from exhausting the bucket for all anonymous users.
-            let ip = req
-                .headers()
-                .get("X-Forwarded-For")
-                .and_then(|v| v.to_str().ok())
-                .and_then(|s| s.split(',').next())
-                .map(|s| s.trim().to_string())
-                .or_else(|| {
-                    req.headers()
-                        .get("X-Real-IP")
-                        .and_then(|v| v.to_str().ok())
-                        .map(|s| s.to_string())
-                })
+            let ip = client_ip(req.headers(), req.extensions())
+                .map(|ip| ip.to_string())
                 .unwrap_or_else(|| "unknown".to_string());
             let mut hasher = Sha256::new();
             hasher.update(ip.as_bytes());
@@ -55,51 +254,82 @@ pub async fn rate_limit_middleware(
 
     let now = Instant::now();
 
-    let (remaining, retry_after) = {
-        let mut entry = state
-            .rate_limits
-            .entry(key)
-            .or_insert_with(|| RateLimitBucket {
-                remaining: CAPACITY,
-                last_refill: now,
-            });
-
-        let bucket = entry.value_mut();
-
-        // Refill tokens based on elapsed time
-        let elapsed = now.duration_since(bucket.last_refill).as_secs();
-        if elapsed >= WINDOW_SECS {
-            bucket.remaining = CAPACITY;
-            bucket.last_refill = now;
-        } else if elapsed > 0 {
-            let refill = ((elapsed as f64 / WINDOW_SECS as f64) * CAPACITY as f64) as u32;
-            bucket.remaining = (bucket.remaining + refill).min(CAPACITY);
-            bucket.last_refill = now;
-        }
-
-        if bucket.remaining == 0 {
-            let secs_until_refill =
-                WINDOW_SECS.saturating_sub(now.duration_since(bucket.last_refill).as_secs());
-            (0u32, Some(secs_until_refill.max(1)))
-        } else {
-            bucket.remaining -= 1;
-            (bucket.remaining, None)
+    // Bot tokens are additionally subject to a global per-application quota so
+    // a single bot cannot saturate the server by spreading load across routes.
+    let bot_token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|auth| auth.strip_prefix("Bot "))
+        .map(str::to_string);
+    let global_remaining = if let Some(token) = bot_token {
+        let global_key = global_bot_key(&state, &token, &key).await;
+        match take_global_bot_token(&state, &global_key, now) {
+            Ok(remaining) => Some(remaining),
+            Err(retry_after_ms) => {
+                return AppError::GlobalRateLimited { retry_after_ms }.into_response();
+            }
         }
+    } else {
+        None
     };
 
-    if let Some(retry_after) = retry_after {
-        return AppError::RateLimited { retry_after }.into_response();
-    }
+    // The route bucket goes first so a refused send doesn't also cost a
+    // token from the default bucket.
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|path| route_limit(req.method().as_str(), path.as_str()));
+    let route_taken = match route {
+        Some(limit) => match take_token(&state, limit, &key, now) {
+            Ok(taken) => Some((limit, taken)),
+            Err(retry_after) => return rate_limited(limit, retry_after),
+        },
+        None => None,
+    };
+    let default_taken = match take_token(&state, &DEFAULT_LIMIT, &key, now) {
+        Ok(taken) => taken,
+        Err(retry_after) => return rate_limited(&DEFAULT_LIMIT, retry_after),
+    };
+    let (limit, taken) = route_taken.unwrap_or((&DEFAULT_LIMIT, default_taken));
 
     let mut response = next.run(req).await;
     let headers = response.headers_mut();
-    headers.insert("X-RateLimit-Limit", CAPACITY.to_string().parse().unwrap());
-    headers.insert(
-        "X-RateLimit-Remaining",
-        remaining.to_string().parse().unwrap(),
-    );
-    // Reset timestamp: seconds until next full refill
-    let reset = chrono::Utc::now().timestamp() + WINDOW_SECS as i64;
-    headers.insert("X-RateLimit-Reset", reset.to_string().parse().unwrap());
+    insert_limit_headers(headers, limit, taken.remaining, taken.reset_after);
+    if let Some(global_remaining) = global_remaining {
+        headers.insert(
+            "X-RateLimit-Global-Limit",
+            GLOBAL_BOT_LIMIT.to_string().parse().unwrap(),
+        );
+        headers.insert(
+            "X-RateLimit-Global-Remaining",
+            global_remaining.to_string().parse().unwrap(),
+        );
+    }
     response
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_resolve_to_their_bucket() {
+        let send = route_limit("POST", "/api/v1/channels/{channel_id}/messages").unwrap();
+        assert_eq!(send.bucket, "message_send");
+        assert!(route_limit("GET", "/api/v1/channels/{channel_id}/messages").is_none());
+        let react = route_limit(
+            "PUT",
+            "/api/v1/channels/{channel_id}/messages/{message_id}/reactions/{emoji}/@me",
+        )
+        .unwrap();
+        assert_eq!(react.bucket, "reactions");
+        assert_eq!(
+            route_limit("POST", "/api/v1/invites/{code}/accept")
+                .unwrap()
+                .bucket,
+            "invites"
+        );
+        assert!(route_limit("GET", "/api/v1/users/@me").is_none());
+    }
+}
@@ -0,0 +1,50 @@
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::state::AppState;
+
+/// CSP applied to `/cdn` responses when the `cdn_content_security_policy`
+/// server setting is unset. Uploaded files are user content: they may render
+/// images and media from this origin but must never run script, load
+/// third-party resources, or be navigated to as an app page.
+pub const DEFAULT_CDN_CSP: &str =
+    "default-src 'none'; img-src 'self'; media-src 'self'; style-src 'unsafe-inline'; sandbox";
+
+/// Adds the standard security headers to every response, plus a
+/// Content-Security-Policy on CDN responses. Headers a handler already set are
+/// left alone so individual routes (e.g. the embeddable invite page) can opt
+/// out.
+pub async fn security_headers_middleware(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let is_cdn = req.uri().path().starts_with("/cdn/");
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+
+    headers
+        .entry(header::X_CONTENT_TYPE_OPTIONS)
+        .or_insert(HeaderValue::from_static("nosniff"));
+    headers
+        .entry(header::X_FRAME_OPTIONS)
+        .or_insert(HeaderValue::from_static("DENY"));
+    headers
+        .entry(header::REFERRER_POLICY)
+        .or_insert(HeaderValue::from_static("strict-origin-when-cross-origin"));
+
+    if is_cdn && !headers.contains_key(header::CONTENT_SECURITY_POLICY) {
+        let settings = state.settings.load();
+        let csp = settings
+            .cdn_content_security_policy
+            .as_deref()
+            .filter(|v| !v.trim().is_empty())
+            .and_then(|v| HeaderValue::from_str(v).ok())
+            .unwrap_or(HeaderValue::from_static(DEFAULT_CDN_CSP));
+        headers.insert(header::CONTENT_SECURITY_POLICY, csp);
+    }
+
+    response
+}
@@ -9,6 +9,16 @@ pub struct Application {
     pub bot_public: bool,
     pub owner_id: String,
     pub flags: i64,
+    pub bot_user_id: Option<String>,
+    /// CIDR ranges the bot token may be used from; empty allows any address.
+    pub ip_allowlist: Vec<String>,
+    /// Where the OAuth2 authorize flow may send users back to.
+    pub redirect_uris: Vec<String>,
+    /// When set, interactions are POSTed here instead of sent over the
+    /// gateway.
+    pub interactions_endpoint_url: Option<String>,
+    /// Hex Ed25519 public key that signs HTTP interactions.
+    pub verify_key: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -16,3 +26,44 @@ pub struct CreateApplication {
     pub name: String,
     pub description: Option<String>,
 }
+
+/// Body for `PATCH /applications/@me`.
+#[derive(Debug, Deserialize)]
+pub struct UpdateApplication {
+    pub redirect_uris: Option<Vec<String>>,
+    /// Must answer a signed `ping` with a `pong` to be saved; empty to go
+    /// back to gateway delivery.
+    pub interactions_endpoint_url: Option<String>,
+}
+
+/// Body for `PUT /applications/@me/ip-allowlist`.
+#[derive(Debug, Deserialize)]
+pub struct UpdateIpAllowlist {
+    pub ranges: Vec<String>,
+}
+
+/// Overlap window a rotation gives the keys it replaces, unless the request
+/// names one.
+pub const DEFAULT_SIGNING_KEY_OVERLAP_SECS: i64 = 24 * 60 * 60;
+pub const MAX_SIGNING_KEY_OVERLAP_SECS: i64 = 7 * 24 * 60 * 60;
+/// Most signing keys an application can have live at once.
+pub const MAX_SIGNING_KEYS: i64 = 3;
+
+/// One of an application's request signing keys. The secret is only shown
+/// when the key is created.
+#[derive(Debug, Clone, Serialize)]
+pub struct SigningKeyInfo {
+    pub id: String,
+    pub created_at: String,
+    /// When the key stops signing; `null` for the current key.
+    pub expires_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+/// Body for `POST /applications/@me/signing-keys/rotate`.
+#[derive(Debug, Default, Deserialize)]
+pub struct RotateSigningKeys {
+    /// How long the keys being replaced keep signing; 0 retires them now.
+    pub overlap_secs: Option<i64>,
+}
@@ -8,6 +8,20 @@ pub struct Attachment {
     pub content_type: Option<String>,
     pub size: i64,
     pub url: String,
+    /// Preview-sized version of an image attachment.
+    pub proxy_url: Option<String>,
     pub width: Option<i64>,
     pub height: Option<i64>,
 }
+
+/// Longest alt text an attachment may carry, in characters.
+pub const MAX_DESCRIPTION_LENGTH: usize = 1024;
+
+/// Alt text for one attachment. On upload [id] is the `N` of the
+/// `files[N]` field; on edit it is the attachment's ID. A null
+/// [description] clears it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AttachmentDescription {
+    pub id: String,
+    pub description: Option<String>,
+}
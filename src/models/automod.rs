@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+/// What an AutoMod rule matches in a message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AutomodTrigger {
+    /// Any of the words or phrases, case-insensitively.
+    Keyword { keywords: Vec<String> },
+    /// Any of the regular expressions.
+    Regex { patterns: Vec<String> },
+    /// More than `mention_limit` mentions (`@everyone`/`@here` count as one).
+    MentionSpam { mention_limit: usize },
+    /// Any link whose host isn't one of `allowed_domains` (or a subdomain of
+    /// one). An empty list matches every link.
+    Link {
+        #[serde(default)]
+        allowed_domains: Vec<String>,
+    },
+}
+
+/// What happens when a rule matches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AutomodAction {
+    /// Reject the message.
+    Block,
+    /// Post a report of the message in a log channel.
+    Flag { channel_id: String },
+    /// Time the author out for `duration_seconds`.
+    Timeout { duration_seconds: i64 },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AutomodRule {
+    pub id: String,
+    pub space_id: String,
+    pub creator_id: String,
+    pub name: String,
+    pub trigger: AutomodTrigger,
+    pub actions: Vec<AutomodAction>,
+    pub enabled: bool,
+    pub created_at: String,
+}
+
+/// Body for `POST /spaces/{id}/automod/rules`.
+#[derive(Debug, Deserialize)]
+pub struct CreateAutomodRule {
+    pub name: String,
+    pub trigger: AutomodTrigger,
+    pub actions: Vec<AutomodAction>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAutomodRule {
+    pub name: Option<String>,
+    pub trigger: Option<AutomodTrigger>,
+    pub actions: Option<Vec<AutomodAction>>,
+    pub enabled: Option<bool>,
+}
+
+/// One rule's line in `GET /spaces/{id}/automod/stats`.
+#[derive(Debug, Serialize)]
+pub struct AutomodRuleStats {
+    pub rule_id: String,
+    pub name: String,
+    pub trigger_type: String,
+    pub enabled: bool,
+    pub hits: i64,
+    /// Hits a moderator marked as false positives.
+    pub overrides: i64,
+    /// `overrides / hits`, or 0 with no hits.
+    pub override_rate: f64,
+    /// Redacted excerpts of the most recent matches.
+    pub samples: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AutomodStatsQuery {
+    /// How far back to look, in days (default 30).
+    pub days: Option<i64>,
+}
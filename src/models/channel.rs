@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use super::permission::PermissionOverwrite;
+use super::permission::{PermissionOverwrite, Permissions};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Channel {
@@ -44,6 +44,19 @@ pub struct ChannelRow {
     pub archived: bool,
     pub auto_archive_after: Option<i64>,
     pub allow_anonymous_read: bool,
+    pub announcement_locked: bool,
+    /// For voice channels: `keep`, `clear_on_empty` or `off` (see
+    /// `VOICE_CHAT_RETENTIONS`).
+    pub voice_chat_retention: String,
+    /// Group DMs only: the icon's `/cdn/` path.
+    pub icon: Option<String>,
+    /// Start a thread on every top-level message.
+    pub auto_thread: bool,
+    /// Name template for auto-created threads (see
+    /// `crate::models::thread::auto_thread_name`).
+    pub auto_thread_name: Option<String>,
+    /// Pins the channel may hold; `None` for [DEFAULT_MAX_PINS].
+    pub max_pins: Option<i64>,
     pub created_at: String,
 }
 
@@ -83,6 +96,53 @@ pub struct UpdateChannel {
     pub user_limit: Option<i64>,
     pub archived: Option<bool>,
     pub allow_anonymous_read: Option<bool>,
+    /// Make the channel view-only for @everyone. Applied through the
+    /// @everyone permission overwrite rather than written directly (see
+    /// `ANNOUNCEMENT_LOCK_DENIES`).
+    pub announcement_locked: Option<bool>,
+    /// Voice and stage channels only.
+    pub voice_chat_retention: Option<String>,
+    /// Group DMs only: a data URI, or empty to remove the icon. The route
+    /// layer stores the image and swaps in its `/cdn/` path.
+    pub icon: Option<String>,
+    /// Group DMs only: hand the group to another participant.
+    pub owner_id: Option<String>,
+    /// Text and announcement channels only.
+    pub auto_thread: Option<bool>,
+    /// Empty to go back to the default template.
+    pub auto_thread_name: Option<String>,
+    /// Archive window (minutes) for auto-created threads; one of
+    /// `AUTO_ARCHIVE_DURATIONS`.
+    pub auto_archive_after: Option<i64>,
+    /// Pin limit, up to [MAX_PINS_LIMIT].
+    pub max_pins: Option<i64>,
+}
+
+/// Pins a channel holds unless it sets its own limit.
+pub const DEFAULT_MAX_PINS: i64 = 50;
+
+/// Highest pin limit a channel may set.
+pub const MAX_PINS_LIMIT: i64 = 250;
+
+/// Permissions an announcement-locked channel denies to @everyone.
+pub const ANNOUNCEMENT_LOCK_DENIES: Permissions =
+    Permissions::SEND_MESSAGES.union(Permissions::ADD_REACTIONS);
+
+/// Permissions `POST /channels/{id}/lock` denies to @everyone.
+pub const CHANNEL_LOCK_DENIES: Permissions =
+    Permissions::SEND_MESSAGES.union(Permissions::SEND_IN_THREADS);
+
+/// How long a voice channel's text chat is kept: `keep` (like a text
+/// channel), `clear_on_empty` (deleted when the last person leaves the call),
+/// or `off` (never listed as history, and cleared like `clear_on_empty`).
+pub const VOICE_CHAT_RETENTIONS: &[&str] = &["keep", "clear_on_empty", "off"];
+
+/// Channel types members connect to over voice. A `stage` is a voice channel
+/// where joiners listen as the audience until a moderator lets them speak.
+pub const VOICE_CHANNEL_TYPES: &[&str] = &["voice", "stage"];
+
+pub fn is_voice_type(channel_type: &str) -> bool {
+    VOICE_CHANNEL_TYPES.contains(&channel_type)
 }
 
 #[derive(Debug, Deserialize)]
@@ -90,3 +150,15 @@ pub struct ChannelPositionUpdate {
     pub id: String,
     pub position: i64,
 }
+
+/// Body for `PATCH /spaces/{id}/channels`: either a bare list of moves, or
+/// the list with the `channel_order_version` it was computed against.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ReorderChannels {
+    Moves(Vec<ChannelPositionUpdate>),
+    Versioned {
+        channels: Vec<ChannelPositionUpdate>,
+        expected_version: Option<i64>,
+    },
+}
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+/// Scanning providers an instance can enable (see `crate::content_safety`).
+pub const PROVIDERS: &[&str] = &["hash_list", "http"];
+
+/// What happens to a flagged message: `quarantine` hides it until reviewed,
+/// `alert` only tells moderators.
+pub const ACTIONS: &[&str] = &["quarantine", "alert"];
+
+/// `pending` until an admin either `released` the message or `removed` it.
+pub const FLAG_STATUSES: &[&str] = &["pending", "released", "removed"];
+
+/// A message a provider matched.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentSafetyFlag {
+    pub id: String,
+    pub message_id: String,
+    pub channel_id: String,
+    pub space_id: Option<String>,
+    pub author_id: String,
+    pub provider: String,
+    /// What the provider said it matched, e.g. the hash list entry's label.
+    pub label: String,
+    pub action: String,
+    pub status: String,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<String>,
+    pub created_at: String,
+}
+
+/// An entry on the `hash_list` provider's list.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentSafetyHash {
+    pub hash: String,
+    pub label: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ContentSafetyHashInput {
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContentSafetyFlagQuery {
+    pub status: Option<String>,
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Body for `PATCH /admin/content-safety/flags/{id}`.
+#[derive(Debug, Deserialize)]
+pub struct ReviewContentSafetyFlag {
+    /// `released` or `removed`.
+    pub status: String,
+}
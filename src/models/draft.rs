@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Draft {
+    pub channel_id: String,
+    pub content: String,
+    pub reply_to: Option<String>,
+    pub updated_at: String,
+}
+
+/// Body for `PUT /channels/{id}/draft`.
+#[derive(Debug, Deserialize)]
+pub struct PutDraft {
+    pub content: String,
+    pub reply_to: Option<String>,
+}
@@ -50,3 +50,229 @@ pub struct EmbedField {
     #[serde(default)]
     pub inline: bool,
 }
+
+/// Most embeds one message may carry.
+pub const MAX_EMBEDS: usize = 10;
+pub const MAX_TITLE_LEN: usize = 256;
+pub const MAX_DESCRIPTION_LEN: usize = 4096;
+pub const MAX_FIELDS: usize = 25;
+pub const MAX_FIELD_NAME_LEN: usize = 256;
+pub const MAX_FIELD_VALUE_LEN: usize = 1024;
+pub const MAX_FOOTER_TEXT_LEN: usize = 2048;
+pub const MAX_AUTHOR_NAME_LEN: usize = 256;
+/// Characters across the titles, descriptions, field names and values,
+/// footers and author names of all of a message's embeds.
+pub const MAX_TOTAL_CHARS: usize = 6000;
+/// Schemes an embed URL may use. `attachment://` refers to a file uploaded
+/// with the same message.
+pub const URL_SCHEMES: &[&str] = &["http", "https", "attachment"];
+
+/// One problem with a submitted embed, keyed by its path in the request
+/// (e.g. `embeds[0].fields[2].value`).
+pub type FieldError = (String, String);
+
+/// Check sender-provided embeds against the limits above, trimming their
+/// URLs on the way. Every problem is reported, not just the first, so bot
+/// developers can fix a payload in one go.
+pub fn validate_embeds(embeds: &mut [Embed]) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+    if embeds.len() > MAX_EMBEDS {
+        errors.push((
+            "embeds".to_string(),
+            format!("at most {MAX_EMBEDS} embeds per message"),
+        ));
+    }
+    let mut total = 0;
+    for (i, embed) in embeds.iter_mut().enumerate() {
+        let path = format!("embeds[{i}]");
+        let mut texts: Vec<(String, &str, usize)> = Vec::new();
+        if let Some(ref title) = embed.title {
+            texts.push((format!("{path}.title"), title, MAX_TITLE_LEN));
+        }
+        if let Some(ref description) = embed.description {
+            texts.push((
+                format!("{path}.description"),
+                description,
+                MAX_DESCRIPTION_LEN,
+            ));
+        }
+        if let Some(ref footer) = embed.footer {
+            texts.push((
+                format!("{path}.footer.text"),
+                &footer.text,
+                MAX_FOOTER_TEXT_LEN,
+            ));
+        }
+        if let Some(ref author) = embed.author {
+            texts.push((
+                format!("{path}.author.name"),
+                &author.name,
+                MAX_AUTHOR_NAME_LEN,
+            ));
+        }
+        let fields = embed.fields.as_deref().unwrap_or_default();
+        for (j, field) in fields.iter().enumerate() {
+            let field_path = format!("{path}.fields[{j}]");
+            for (key, value, max) in [
+                ("name", &field.name, MAX_FIELD_NAME_LEN),
+                ("value", &field.value, MAX_FIELD_VALUE_LEN),
+            ] {
+                if value.trim().is_empty() {
+                    errors.push((format!("{field_path}.{key}"), "must not be empty".into()));
+                }
+                texts.push((format!("{field_path}.{key}"), value, max));
+            }
+        }
+        if fields.len() > MAX_FIELDS {
+            errors.push((
+                format!("{path}.fields"),
+                format!("at most {MAX_FIELDS} fields per embed"),
+            ));
+        }
+        for (field, value, max) in texts {
+            let len = value.chars().count();
+            total += len;
+            if len > max {
+                errors.push((field, format!("must be at most {max} characters")));
+            }
+        }
+
+        if let Some(color) = embed.color {
+            if !(0..=0xFF_FFFF).contains(&color) {
+                errors.push((
+                    format!("{path}.color"),
+                    "must be an RGB value from 0 to 16777215".into(),
+                ));
+            }
+        }
+        if let Some(ref timestamp) = embed.timestamp {
+            if chrono::DateTime::parse_from_rfc3339(timestamp).is_err() {
+                errors.push((
+                    format!("{path}.timestamp"),
+                    "must be an RFC 3339 timestamp".into(),
+                ));
+            }
+        }
+
+        let urls = [
+            ("url", embed.url.as_mut()),
+            ("image.url", embed.image.as_mut().map(|i| &mut i.url)),
+            (
+                "thumbnail.url",
+                embed.thumbnail.as_mut().map(|t| &mut t.url),
+            ),
+            (
+                "footer.icon_url",
+                embed.footer.as_mut().and_then(|f| f.icon_url.as_mut()),
+            ),
+        ];
+        for (field, url) in urls.into_iter() {
+            if let Some(url) = url {
+                sanitize_url(&format!("{path}.{field}"), url, &mut errors);
+            }
+        }
+        if let Some(ref mut author) = embed.author {
+            if let Some(ref mut url) = author.url {
+                sanitize_url(&format!("{path}.author.url"), url, &mut errors);
+            }
+            if let Some(ref mut url) = author.icon_url {
+                sanitize_url(&format!("{path}.author.icon_url"), url, &mut errors);
+            }
+        }
+    }
+    if total > MAX_TOTAL_CHARS {
+        errors.push((
+            "embeds".to_string(),
+            format!("embeds may total at most {MAX_TOTAL_CHARS} characters"),
+        ));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Trim [url] and check its scheme is in [URL_SCHEMES].
+fn sanitize_url(field: &str, url: &mut String, errors: &mut Vec<FieldError>) {
+    let trimmed = url.trim();
+    if trimmed.len() != url.len() {
+        *url = trimmed.to_string();
+    }
+    let allowed = url.split_once("://").is_some_and(|(scheme, rest)| {
+        !rest.is_empty() && URL_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str())
+    });
+    if !allowed {
+        errors.push((
+            field.to_string(),
+            format!("must be a URL with scheme {}", URL_SCHEMES.join(", ")),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embed() -> Embed {
+        Embed {
+            title: Some("Release notes".into()),
+            embed_type: None,
+            description: Some("What's new".into()),
+            url: Some(" https://example.com/notes ".into()),
+            timestamp: Some("2026-10-17T12:00:00Z".into()),
+            color: Some(0x5865F2),
+            footer: None,
+            image: None,
+            thumbnail: None,
+            author: None,
+            fields: Some(vec![EmbedField {
+                name: "Version".into(),
+                value: "1.2.0".into(),
+                inline: true,
+            }]),
+        }
+    }
+
+    #[test]
+    fn valid_embeds_pass_with_urls_trimmed() {
+        let mut embeds = vec![embed()];
+        assert!(validate_embeds(&mut embeds).is_ok());
+        assert_eq!(embeds[0].url.as_deref(), Some("https://example.com/notes"));
+    }
+
+    #[test]
+    fn every_problem_is_reported_by_path() {
+        let mut bad = embed();
+        bad.title = Some("t".repeat(MAX_TITLE_LEN + 1));
+        bad.url = Some("javascript:alert(1)".into());
+        bad.color = Some(-1);
+        bad.image = Some(EmbedImage {
+            url: "data:image/png;base64,AAAA".into(),
+            width: None,
+            height: None,
+        });
+        let mut embeds = vec![embed(), bad];
+        let errors = validate_embeds(&mut embeds).unwrap_err();
+        let paths: Vec<&str> = errors.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "embeds[1].title",
+                "embeds[1].color",
+                "embeds[1].url",
+                "embeds[1].image.url"
+            ]
+        );
+    }
+
+    #[test]
+    fn total_character_budget_spans_embeds() {
+        let mut big = embed();
+        big.description = Some("d".repeat(MAX_DESCRIPTION_LEN));
+        let mut embeds = vec![big.clone(), big];
+        let errors = validate_embeds(&mut embeds).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, "embeds");
+    }
+}
@@ -12,6 +12,9 @@ pub struct Emoji {
     pub creator_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image_url: Option<String>,
+    /// Awaiting moderator approval; hidden from the space's emoji list.
+    #[serde(default)]
+    pub pending: bool,
 }
 
 #[derive(Debug, Deserialize)]
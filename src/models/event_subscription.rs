@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+
+/// Gateway events an application can subscribe to. All of them happen in a
+/// space; DMs and events aimed at single users are never delivered.
+pub const EVENT_TYPES: &[&str] = &[
+    "message.create",
+    "message.update",
+    "message.delete",
+    "reaction.add",
+    "reaction.remove",
+    "member.join",
+    "member.update",
+    "member.leave",
+    "channel.create",
+    "channel.update",
+    "channel.delete",
+    "space.update",
+    "ban.create",
+    "ban.delete",
+];
+
+pub const MAX_EVENT_SUBSCRIPTIONS: i64 = 10;
+pub const MAX_SUBSCRIPTION_SPACES: usize = 100;
+
+/// An outgoing webhook: where an application wants events delivered.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventSubscription {
+    pub id: String,
+    pub application_id: String,
+    pub url: String,
+    pub event_types: Vec<String>,
+    /// Spaces to deliver events from; empty means every space the
+    /// application's bot is in.
+    pub space_ids: Vec<String>,
+    pub enabled: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateEventSubscription {
+    pub url: String,
+    pub event_types: Vec<String>,
+    #[serde(default)]
+    pub space_ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateEventSubscription {
+    pub url: Option<String>,
+    pub event_types: Option<Vec<String>>,
+    pub space_ids: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+}
+
+/// One event sent (or being sent) to a subscription.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventDelivery {
+    pub id: String,
+    pub subscription_id: String,
+    pub event_type: String,
+    /// `pending` while attempts remain, then `succeeded` or `failed`.
+    pub status: String,
+    pub attempts: i64,
+    pub next_attempt_at: Option<String>,
+    /// HTTP status of the last attempt, if it got a response.
+    pub last_status: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventDeliveryQuery {
+    pub status: Option<String>,
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+}
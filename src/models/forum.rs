@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+/// Tags a single forum channel may offer.
+pub const MAX_FORUM_TAGS: i64 = 20;
+
+/// Tags a single post may carry.
+pub const MAX_APPLIED_TAGS: usize = 5;
+
+/// A tag a forum channel offers for categorising its posts.
+#[derive(Debug, Clone, Serialize)]
+pub struct ForumTag {
+    pub id: String,
+    pub channel_id: String,
+    pub name: String,
+    /// A unicode emoji or custom emoji ID shown next to the name.
+    pub emoji: Option<String>,
+    /// Only members with `manage_threads` may apply or remove it.
+    pub moderated: bool,
+    pub position: i64,
+    pub created_at: String,
+}
+
+/// Body for `POST /channels/{id}/tags`.
+#[derive(Debug, Deserialize)]
+pub struct CreateForumTag {
+    pub name: String,
+    pub emoji: Option<String>,
+    pub moderated: Option<bool>,
+    pub position: Option<i64>,
+}
+
+/// Body for `PATCH /channels/{id}/tags/{tag_id}`. `emoji: ""` removes it.
+#[derive(Debug, Deserialize)]
+pub struct UpdateForumTag {
+    pub name: Option<String>,
+    pub emoji: Option<String>,
+    pub moderated: Option<bool>,
+    pub position: Option<i64>,
+}
+
+/// How `GET /channels/{id}/posts` orders a forum's posts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForumSort {
+    /// Most recent reply (or the post itself) first.
+    #[default]
+    LatestActivity,
+    /// Newest post first.
+    Creation,
+}
@@ -0,0 +1,28 @@
+use serde::Serialize;
+
+/// A bot user in a space, with the application behind it (if any).
+#[derive(Debug, Serialize)]
+pub struct SpaceBot {
+    pub user_id: String,
+    pub username: String,
+    pub application_id: Option<String>,
+    pub application_name: Option<String>,
+    /// The application's owner.
+    pub owner_id: Option<String>,
+    pub joined_at: String,
+    /// Commands the application registered for this space only.
+    pub space_command_count: i64,
+    /// Latest message the bot posted or command it was sent in this space.
+    pub last_activity: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpaceWebhook {
+    pub id: String,
+    pub channel_id: String,
+    pub name: String,
+    pub creator_id: String,
+    pub created_at: String,
+    /// Latest message posted through the webhook.
+    pub last_activity: Option<String>,
+}
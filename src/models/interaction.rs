@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+use super::embed::Embed;
 use super::message::Message;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,3 +60,58 @@ pub struct CommandChoice {
     pub name: String,
     pub value: serde_json::Value,
 }
+
+/// Body for `POST /applications/{id}/commands`. Registering a name that
+/// already exists in the same scope overwrites that command.
+#[derive(Debug, Deserialize)]
+pub struct CreateCommand {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub options: Option<Vec<CommandOption>>,
+    #[serde(rename = "type")]
+    pub command_type: Option<String>,
+}
+
+/// Body for `POST /interactions`: a user invoking a command in a channel.
+#[derive(Debug, Deserialize)]
+pub struct InvokeCommand {
+    pub command_id: String,
+    pub channel_id: String,
+    pub options: Option<Vec<CommandOptionValue>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InteractionRow {
+    pub id: String,
+    pub application_id: String,
+    pub command_id: String,
+    pub space_id: Option<String>,
+    pub channel_id: String,
+    pub user_id: String,
+    /// `None` until the bot answers, then the callback type it used.
+    pub response_type: Option<String>,
+    pub created_at: String,
+}
+
+/// Body for `POST /interactions/{id}/{token}/callback`.
+#[derive(Debug, Deserialize)]
+pub struct InteractionCallback {
+    /// `channel_message` posts `data` to the channel; `deferred` acknowledges
+    /// now and leaves room for a `channel_message` later.
+    #[serde(rename = "type")]
+    pub callback_type: String,
+    pub data: Option<InteractionCallbackData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InteractionCallbackData {
+    #[serde(default)]
+    pub content: String,
+    pub tts: Option<bool>,
+    pub embeds: Option<Vec<Embed>>,
+    /// Show the response only to the user who invoked the command. It stays
+    /// out of channel history and is deleted after a while.
+    #[serde(default)]
+    pub ephemeral: bool,
+}
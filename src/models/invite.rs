@@ -12,6 +12,65 @@ pub struct Invite {
     pub temporary: bool,
     pub created_at: String,
     pub expires_at: Option<String>,
+    /// The space's vanity code rather than a created invite.
+    #[serde(default)]
+    pub vanity: bool,
+}
+
+/// Joins through an invite on one day (UTC, `YYYY-MM-DD`).
+#[derive(Debug, Serialize)]
+pub struct InviteUsesDay {
+    pub date: String,
+    pub uses: i64,
+}
+
+/// One member joining through an invite.
+#[derive(Debug, Serialize)]
+pub struct InviteJoin {
+    pub user_id: String,
+    pub used_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InviteStatsQuery {
+    /// How many days back `uses_over_time` goes; default 30, at most 90.
+    pub days: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateVanityUrl {
+    /// `null` or empty to release the space's code.
+    pub code: Option<String>,
+}
+
+/// Shortest and longest a vanity code may be.
+pub const VANITY_CODE_MIN_LEN: usize = 3;
+pub const VANITY_CODE_MAX_LEN: usize = 32;
+
+/// Vanity codes follow the slug rules (`[a-z0-9-]`, no leading, trailing or
+/// doubled hyphens) within the length limits above.
+pub fn validate_vanity_code(code: &str) -> Result<(), String> {
+    if !(VANITY_CODE_MIN_LEN..=VANITY_CODE_MAX_LEN).contains(&code.len()) {
+        return Err(format!(
+            "vanity code must be {VANITY_CODE_MIN_LEN} to {VANITY_CODE_MAX_LEN} characters"
+        ));
+    }
+    crate::slug::validate_slug(code).map_err(|e| e.replace("slug", "vanity code"))
+}
+
+/// How long a join token from an invite preview can be used.
+pub const JOIN_TOKEN_TTL_SECS: i64 = 10 * 60;
+
+/// Optional body of `POST /invites/{code}/accept`.
+#[derive(Debug, Default, Deserialize)]
+pub struct AcceptInvite {
+    /// From `GET /invites/{code}/preview`; required when the space has
+    /// `invite_preview_required` set.
+    pub join_token: Option<String>,
+    /// Accept the space's member screening rules, shown in the preview, so
+    /// the member doesn't join pending. Needs `join_token`.
+    #[serde(default)]
+    pub accept_screening: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -6,6 +6,10 @@ pub struct Member {
     pub space_id: String,
     pub nickname: Option<String>,
     pub avatar: Option<String>,
+    pub banner: Option<String>,
+    pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    pub timezone: Option<String>,
     pub roles: Vec<String>,
     pub joined_at: String,
     pub premium_since: Option<String>,
@@ -22,18 +26,33 @@ pub struct MemberRow {
     pub space_id: String,
     pub nickname: Option<String>,
     pub avatar: Option<String>,
+    pub banner: Option<String>,
+    pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    pub timezone: Option<String>,
     pub joined_at: String,
     pub premium_since: Option<String>,
     pub deaf: bool,
     pub mute: bool,
     pub pending: bool,
     pub timed_out_until: Option<String>,
+    /// The invite code (or vanity code) the member joined through.
+    pub source_invite: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateMember {
     pub nickname: Option<String>,
     pub avatar: Option<String>,
+    /// Overrides the account's banner in this space: a data URI to upload,
+    /// empty string clears.
+    pub banner: Option<String>,
+    /// Overrides the account's bio in this space; empty string clears.
+    pub bio: Option<String>,
+    /// Overrides the account's pronouns in this space; empty string clears.
+    pub pronouns: Option<String>,
+    /// Overrides the account's timezone in this space; empty string clears.
+    pub timezone: Option<String>,
     pub roles: Option<Vec<String>>,
     pub mute: Option<bool>,
     pub deaf: Option<bool>,
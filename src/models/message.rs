@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use super::attachment::Attachment;
+use super::attachment::{Attachment, AttachmentDescription};
 use super::embed::Embed;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,6 +43,22 @@ pub struct ReactionEmoji {
     pub name: String,
 }
 
+/// `flags` bit: the message was crossposted to the channels following its
+/// announcement channel.
+pub const FLAG_CROSSPOSTED: i64 = 1 << 0;
+
+/// `flags` bit: the message is a crossposted copy from a followed channel.
+pub const FLAG_IS_CROSSPOST: i64 = 1 << 1;
+
+/// `flags` bit: an ephemeral interaction response. Only the invoking user
+/// sees it; it's left out of history and deleted after
+/// [crate::retention::EPHEMERAL_MESSAGE_TTL].
+pub const FLAG_EPHEMERAL: i64 = 1 << 2;
+
+/// `flags` bit: hidden by content-safety scanning until an instance admin
+/// reviews it (see [crate::content_safety]).
+pub const FLAG_QUARANTINED: i64 = 1 << 3;
+
 /// Row from the DB before loading relations.
 #[derive(Debug, Clone)]
 pub struct MessageRow {
@@ -63,6 +79,9 @@ pub struct MessageRow {
     pub reply_to: Option<String>,
     pub flags: i64,
     pub webhook_id: Option<String>,
+    /// Display name a webhook message was posted under.
+    pub webhook_username: Option<String>,
+    pub webhook_avatar: Option<String>,
     pub thread_id: Option<String>,
     pub title: Option<String>,
     /// Home domain for a federated (replica) message, or `None` when local.
@@ -77,6 +96,11 @@ pub struct CreateMessage {
     pub reply_to: Option<String>,
     pub thread_id: Option<String>,
     pub title: Option<String>,
+    #[serde(default)]
+    pub poll: Option<crate::models::poll::CreatePoll>,
+    /// Stickers from the channel's space, sent with the message.
+    #[serde(default)]
+    pub sticker_ids: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -84,9 +108,36 @@ pub struct UpdateMessage {
     pub content: Option<String>,
     pub embeds: Option<Vec<Embed>>,
     pub title: Option<String>,
+    /// Alt text changes for attachments already on the message.
+    pub attachments: Option<Vec<AttachmentDescription>>,
+}
+
+/// What a message said before one of its edits.
+#[derive(Debug, Serialize)]
+pub struct MessageRevision {
+    pub id: String,
+    pub message_id: String,
+    pub editor_id: String,
+    pub content: String,
+    pub title: Option<String>,
+    pub embeds: serde_json::Value,
+    pub revised_at: String,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct BulkDeleteMessages {
     pub messages: Vec<String>,
 }
+
+/// A public link to one message. `token` is filled in by the route, which
+/// signs it on the way out; only the row is stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageShare {
+    pub id: String,
+    pub message_id: String,
+    pub channel_id: String,
+    pub creator_id: String,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
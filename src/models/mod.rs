@@ -1,22 +1,39 @@
 pub mod application;
 pub mod attachment;
+pub mod automod;
 pub mod channel;
+pub mod content_safety;
+pub mod draft;
 pub mod embed;
 pub mod emoji;
+pub mod event_subscription;
+pub mod forum;
+pub mod integration;
 pub mod interaction;
 pub mod invite;
 pub mod member;
 pub mod message;
 pub mod mute;
+pub mod oauth2;
+pub mod passkey;
 pub mod permission;
 pub mod plugin;
+pub mod poll;
 pub mod presence;
+pub mod push;
+pub mod raid;
 pub mod role;
+pub mod screening;
+pub mod session;
 pub mod settings;
 pub mod soundboard;
 pub mod space;
+pub mod sticker;
+pub mod thread;
 pub mod user;
+pub mod user_settings;
 pub mod voice;
+pub mod webhook;
 
 use serde::Serialize;
 
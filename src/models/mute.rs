@@ -1,4 +1,4 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize)]
 pub struct ChannelMute {
@@ -6,3 +6,46 @@ pub struct ChannelMute {
     pub channel_id: String,
     pub created_at: String,
 }
+
+/// One channel's entry in `PATCH /users/@me/channel-states`. Omitted fields
+/// are left as they are.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelStateUpdate {
+    pub channel_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub muted: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collapsed: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateChannelStates {
+    pub channels: Vec<ChannelStateUpdate>,
+}
+
+/// A user's notification settings for a space, from
+/// `GET /users/@me/spaces/{space_id}/settings`.
+#[derive(Debug, Serialize)]
+pub struct SpaceNotificationSettings {
+    pub space_id: String,
+    /// Every channel in the space is muted unless overridden.
+    pub muted: bool,
+    /// @everyone and @here don't count as mentions of this user.
+    pub suppress_everyone: bool,
+    pub channel_overrides: Vec<ChannelOverride>,
+}
+
+/// A channel muted (`true`) or kept unmuted (`false`) regardless of its
+/// space and category. `null` in a PATCH removes the override.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelOverride {
+    pub channel_id: String,
+    pub muted: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSpaceNotificationSettings {
+    pub muted: Option<bool>,
+    pub suppress_everyone: Option<bool>,
+    pub channel_overrides: Option<Vec<ChannelOverride>>,
+}
@@ -0,0 +1,52 @@
+use serde::Deserialize;
+
+/// Query for `GET /oauth2/authorize`: what the consent screen should show.
+#[derive(Debug, Deserialize)]
+pub struct AuthorizeQuery {
+    pub client_id: String,
+    pub scope: String,
+    pub redirect_uri: Option<String>,
+}
+
+/// Body for `POST /oauth2/authorize`, sent once the user approves.
+#[derive(Debug, Deserialize)]
+pub struct AuthorizeRequest {
+    pub client_id: String,
+    pub scope: String,
+    pub redirect_uri: Option<String>,
+    /// Only `code` is supported.
+    pub response_type: Option<String>,
+    /// Echoed back on the redirect.
+    pub state: Option<String>,
+    /// Space to add the bot to; required with the `bot` scope.
+    pub space_id: Option<String>,
+}
+
+/// Form body for `POST /oauth2/token`. Client credentials may come here or
+/// as HTTP Basic auth.
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub refresh_token: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+/// Form body for `POST /oauth2/token/revoke`.
+#[derive(Debug, Deserialize)]
+pub struct RevokeRequest {
+    /// An access or refresh token; the whole pair is revoked.
+    pub token: String,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+/// What an authorization code or token was granted for.
+#[derive(Debug, Clone)]
+pub struct OAuthGrant {
+    pub application_id: String,
+    pub user_id: String,
+    pub scopes: Vec<String>,
+}
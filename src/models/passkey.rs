@@ -0,0 +1,26 @@
+use serde::Serialize;
+
+/// Passkeys a single account may register.
+pub const MAX_PASSKEYS_PER_USER: i64 = 10;
+
+pub const MAX_PASSKEY_NAME_LEN: usize = 64;
+
+/// A registered passkey, as listed by `GET /auth/passkeys`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Passkey {
+    pub id: String,
+    /// User-chosen label, e.g. "YubiKey" or "Phone".
+    pub name: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+/// What login needs to verify an assertion from a stored passkey.
+#[derive(Debug, Clone)]
+pub struct PasskeyCredential {
+    pub id: String,
+    pub user_id: String,
+    pub algorithm: i64,
+    pub public_key: String,
+    pub sign_count: i64,
+}
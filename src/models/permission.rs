@@ -1,56 +1,305 @@
-use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::AppError;
+
+/// A set of permissions, stored as bits in the database and checked with
+/// bit operations. Serializes as a list of permission names, so the API and
+/// federation keep speaking strings.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Permissions(u64);
+
+macro_rules! permission_catalog {
+    ($($bit:literal $konst:ident $name:literal,)*) => {
+        impl Permissions {
+            $(pub const $konst: Permissions = Permissions(1 << $bit);)*
+
+            /// The permission called `name`, if there is one.
+            pub fn from_name(name: &str) -> Option<Permissions> {
+                match name {
+                    $($name => Some(Permissions::$konst),)*
+                    _ => None,
+                }
+            }
+        }
+
+        /// Every permission and its API name, in bit order. This is the one
+        /// list of permissions; since the bits are what the database stores,
+        /// entries are only ever appended, never renumbered.
+        pub const PERMISSION_CATALOG: &[(&str, Permissions)] =
+            &[$(($name, Permissions::$konst),)*];
+
+        pub const ALL_PERMISSIONS: &[&str] = &[$($name,)*];
+    };
+}
+
+permission_catalog! {
+    0 CREATE_INVITES "create_invites",
+    1 KICK_MEMBERS "kick_members",
+    2 BAN_MEMBERS "ban_members",
+    3 ADMINISTRATOR "administrator",
+    4 MANAGE_CHANNELS "manage_channels",
+    5 MANAGE_SPACE "manage_space",
+    6 ADD_REACTIONS "add_reactions",
+    7 VIEW_AUDIT_LOG "view_audit_log",
+    8 PRIORITY_SPEAKER "priority_speaker",
+    9 STREAM "stream",
+    10 VIEW_CHANNEL "view_channel",
+    11 SEND_MESSAGES "send_messages",
+    12 SEND_MESSAGES_IN_VOICE "send_messages_in_voice",
+    13 SEND_TTS "send_tts",
+    14 MANAGE_MESSAGES "manage_messages",
+    15 EMBED_LINKS "embed_links",
+    16 ATTACH_FILES "attach_files",
+    17 READ_HISTORY "read_history",
+    18 MENTION_EVERYONE "mention_everyone",
+    19 USE_EXTERNAL_EMOJIS "use_external_emojis",
+    20 CONNECT "connect",
+    21 SPEAK "speak",
+    22 MUTE_MEMBERS "mute_members",
+    23 DEAFEN_MEMBERS "deafen_members",
+    24 MOVE_MEMBERS "move_members",
+    25 USE_VAD "use_vad",
+    26 CHANGE_NICKNAME "change_nickname",
+    27 MANAGE_NICKNAMES "manage_nicknames",
+    28 MANAGE_ROLES "manage_roles",
+    29 MANAGE_WEBHOOKS "manage_webhooks",
+    30 MANAGE_EMOJIS "manage_emojis",
+    31 MANAGE_SOUNDBOARD "manage_soundboard",
+    32 USE_SOUNDBOARD "use_soundboard",
+    33 USE_COMMANDS "use_commands",
+    34 MANAGE_EVENTS "manage_events",
+    35 MANAGE_THREADS "manage_threads",
+    36 CREATE_THREADS "create_threads",
+    37 USE_EXTERNAL_STICKERS "use_external_stickers",
+    38 SEND_IN_THREADS "send_in_threads",
+    39 MODERATE_MEMBERS "moderate_members",
+}
+
+impl Permissions {
+    pub const fn empty() -> Self {
+        Permissions(0)
+    }
+
+    /// The value stored in the database.
+    pub const fn bits(self) -> i64 {
+        self.0 as i64
+    }
+
+    /// From a stored value, dropping bits no permission uses.
+    pub fn from_bits(bits: i64) -> Self {
+        let known = PERMISSION_CATALOG.iter().fold(0, |acc, (_, p)| acc | p.0);
+        Permissions(bits as u64 & known)
+    }
+
+    /// Permissions named in `names`, ignoring names this server doesn't know.
+    /// Only for names it didn't take from an API client, such as a newer
+    /// federated peer's; client input goes through [Permissions::parse].
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> Self {
+        names
+            .iter()
+            .filter_map(|n| Permissions::from_name(n.as_ref()))
+            .fold(Permissions::empty(), |acc, p| acc | p)
+    }
+
+    /// Like [Permissions::from_names] but rejects unknown names, for input
+    /// from API clients. The error lists every name it didn't know.
+    pub fn parse<S: AsRef<str>>(names: &[S]) -> Result<Self, AppError> {
+        Permissions::try_from_names(names).map_err(|unknown| {
+            AppError::BadRequest(format!("unknown permissions: {}", unknown.join(", ")))
+        })
+    }
+
+    fn try_from_names<S: AsRef<str>>(names: &[S]) -> Result<Self, Vec<&str>> {
+        let mut perms = Permissions::empty();
+        let mut unknown = Vec::new();
+        for name in names {
+            match Permissions::from_name(name.as_ref()) {
+                Some(p) => perms |= p,
+                None => unknown.push(name.as_ref()),
+            }
+        }
+        if unknown.is_empty() {
+            Ok(perms)
+        } else {
+            Err(unknown)
+        }
+    }
+
+    /// Names of the permissions in the set, in catalog order.
+    pub fn names(self) -> Vec<&'static str> {
+        PERMISSION_CATALOG
+            .iter()
+            .filter(|(_, p)| self.contains(*p))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    /// `self | other`, usable in constants.
+    pub const fn union(self, other: Permissions) -> Self {
+        Permissions(self.0 | other.0)
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether every permission in `other` is in the set, literally (see
+    /// [Permissions::has] for the `administrator` bypass).
+    pub const fn contains(self, other: Permissions) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_admin(self) -> bool {
+        self.contains(Permissions::ADMINISTRATOR)
+    }
+
+    /// Whether the set grants `perm`: it contains it, or `administrator`.
+    pub const fn has(self, perm: Permissions) -> bool {
+        self.is_admin() || self.contains(perm)
+    }
+
+    /// Apply a channel overwrite: `deny` removes, then `allow` adds.
+    pub fn overwrite(self, allow: Permissions, deny: Permissions) -> Self {
+        (self & !deny) | allow
+    }
+}
+
+impl BitOr for Permissions {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Permissions(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Permissions {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for Permissions {
+    type Output = Self;
+    fn bitand(self, rhs: Self) -> Self {
+        Permissions(self.0 & rhs.0)
+    }
+}
+
+impl Not for Permissions {
+    type Output = Self;
+    fn not(self) -> Self {
+        Permissions(!self.0)
+    }
+}
+
+impl fmt::Debug for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+impl Serialize for Permissions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.names())
+    }
+}
+
+/// Rejects unknown names, like [Permissions::parse].
+impl<'de> Deserialize<'de> for Permissions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        Permissions::try_from_names(&names).map_err(|unknown| {
+            serde::de::Error::custom(format!("unknown permissions: {}", unknown.join(", ")))
+        })
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PermissionOverwrite {
     pub id: String,
     #[serde(rename = "type")]
     pub overwrite_type: String,
-    pub allow: Vec<String>,
-    pub deny: Vec<String>,
-}
-
-pub const ALL_PERMISSIONS: &[&str] = &[
-    "create_invites",
-    "kick_members",
-    "ban_members",
-    "administrator",
-    "manage_channels",
-    "manage_space",
-    "add_reactions",
-    "view_audit_log",
-    "priority_speaker",
-    "stream",
-    "view_channel",
-    "send_messages",
-    "send_tts",
-    "manage_messages",
-    "embed_links",
-    "attach_files",
-    "read_history",
-    "mention_everyone",
-    "use_external_emojis",
-    "connect",
-    "speak",
-    "mute_members",
-    "deafen_members",
-    "move_members",
-    "use_vad",
-    "change_nickname",
-    "manage_nicknames",
-    "manage_roles",
-    "manage_webhooks",
-    "manage_emojis",
-    "manage_soundboard",
-    "use_soundboard",
-    "use_commands",
-    "manage_events",
-    "manage_threads",
-    "create_threads",
-    "use_external_stickers",
-    "send_in_threads",
-    "moderate_members",
-];
-
-pub fn has_permission(perms: &[String], perm: &str) -> bool {
-    perms.iter().any(|p| p == "administrator" || p == perm)
+    pub allow: Permissions,
+    pub deny: Permissions,
+}
+
+/// Whether `perms` grants the permission called `perm`. Unknown names are
+/// only granted by `administrator`.
+pub fn has_permission(perms: Permissions, perm: &str) -> bool {
+    match Permissions::from_name(perm) {
+        Some(p) => perms.has(p),
+        None => perms.is_admin(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_are_stable() {
+        // Stored in the database: changing these corrupts every role.
+        assert_eq!(Permissions::CREATE_INVITES.bits(), 1);
+        assert_eq!(Permissions::ADMINISTRATOR.bits(), 1 << 3);
+        assert_eq!(Permissions::VIEW_CHANNEL.bits(), 1 << 10);
+        assert_eq!(Permissions::MODERATE_MEMBERS.bits(), 1 << 39);
+        for (i, (name, perm)) in PERMISSION_CATALOG.iter().enumerate() {
+            assert_eq!(perm.bits(), 1 << i, "{name}");
+            assert_eq!(ALL_PERMISSIONS[i], *name);
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        let perms = Permissions::parse(&["view_channel", "ban_members"]).unwrap();
+        assert_eq!(perms, Permissions::VIEW_CHANNEL | Permissions::BAN_MEMBERS);
+        assert_eq!(perms.names(), vec!["ban_members", "view_channel"]);
+        assert_eq!(
+            serde_json::to_value(perms).unwrap(),
+            serde_json::json!(["ban_members", "view_channel"])
+        );
+        let parsed: Permissions =
+            serde_json::from_value(serde_json::json!(["ban_members"])).unwrap();
+        assert_eq!(parsed, Permissions::BAN_MEMBERS);
+        let err = serde_json::from_value::<Permissions>(serde_json::json!([
+            "ban_members",
+            "from_the_future",
+            "manage_channel"
+        ]))
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unknown permissions: from_the_future, manage_channel"));
+        assert!(matches!(
+            Permissions::parse(&["from_the_future", "view_channel", "manage_channel"]),
+            Err(AppError::BadRequest(msg)) if msg == "unknown permissions: from_the_future, manage_channel"
+        ));
+        assert_eq!(
+            Permissions::from_names(&["ban_members", "from_the_future"]),
+            Permissions::BAN_MEMBERS
+        );
+        assert_eq!(Permissions::from_bits(perms.bits() | 1 << 62), perms);
+    }
+
+    #[test]
+    fn administrator_grants_everything() {
+        let admin = Permissions::ADMINISTRATOR;
+        assert!(admin.has(Permissions::BAN_MEMBERS));
+        assert!(!admin.contains(Permissions::BAN_MEMBERS));
+        assert!(has_permission(admin, "anything"));
+        assert!(!has_permission(Permissions::VIEW_CHANNEL, "anything"));
+        assert!(has_permission(Permissions::VIEW_CHANNEL, "view_channel"));
+    }
+
+    #[test]
+    fn overwrites_deny_then_allow() {
+        let base = Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES;
+        let applied = base.overwrite(Permissions::ADD_REACTIONS, Permissions::SEND_MESSAGES);
+        assert_eq!(
+            applied,
+            Permissions::VIEW_CHANNEL | Permissions::ADD_REACTIONS
+        );
+    }
 }
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+pub const MIN_POLL_ANSWERS: usize = 2;
+pub const MAX_POLL_ANSWERS: usize = 10;
+pub const MAX_POLL_QUESTION_LEN: usize = 300;
+pub const MAX_POLL_ANSWER_LEN: usize = 55;
+pub const DEFAULT_POLL_DURATION_HOURS: i64 = 24;
+/// 32 days.
+pub const MAX_POLL_DURATION_HOURS: i64 = 768;
+
+/// One answer of a poll. IDs run from 1 in the order the answers were given.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollAnswer {
+    pub id: i64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PollRow {
+    pub message_id: String,
+    pub question: String,
+    pub answers: Vec<PollAnswer>,
+    pub allow_multiselect: bool,
+    pub expires_at: String,
+    /// Set when the poll expired and its results were announced.
+    pub finalized_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePollAnswer {
+    pub text: String,
+}
+
+/// The `poll` of a new message.
+#[derive(Debug, Deserialize)]
+pub struct CreatePoll {
+    pub question: String,
+    pub answers: Vec<CreatePollAnswer>,
+    #[serde(default)]
+    pub allow_multiselect: bool,
+    /// Hours until voting closes, 1 to [MAX_POLL_DURATION_HOURS]; defaults
+    /// to [DEFAULT_POLL_DURATION_HOURS].
+    pub duration_hours: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PollVotersQuery {
+    pub after: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Check a new poll, collecting every problem as a field error. Lengths
+/// count characters of the trimmed text.
+pub fn validate_poll(poll: &CreatePoll) -> Result<(), Vec<crate::models::embed::FieldError>> {
+    let mut errors = Vec::new();
+    let question = poll.question.trim().chars().count();
+    if question == 0 || question > MAX_POLL_QUESTION_LEN {
+        errors.push((
+            "poll.question".to_string(),
+            format!("must be between 1 and {MAX_POLL_QUESTION_LEN} characters"),
+        ));
+    }
+    if !(MIN_POLL_ANSWERS..=MAX_POLL_ANSWERS).contains(&poll.answers.len()) {
+        errors.push((
+            "poll.answers".to_string(),
+            format!("must have between {MIN_POLL_ANSWERS} and {MAX_POLL_ANSWERS} answers"),
+        ));
+    }
+    for (i, answer) in poll.answers.iter().enumerate() {
+        let len = answer.text.trim().chars().count();
+        if len == 0 || len > MAX_POLL_ANSWER_LEN {
+            errors.push((
+                format!("poll.answers[{i}].text"),
+                format!("must be between 1 and {MAX_POLL_ANSWER_LEN} characters"),
+            ));
+        }
+    }
+    if let Some(hours) = poll.duration_hours {
+        if !(1..=MAX_POLL_DURATION_HOURS).contains(&hours) {
+            errors.push((
+                "poll.duration_hours".to_string(),
+                format!("must be between 1 and {MAX_POLL_DURATION_HOURS}"),
+            ));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll(question: &str, answers: &[&str], duration_hours: Option<i64>) -> CreatePoll {
+        CreatePoll {
+            question: question.to_string(),
+            answers: answers
+                .iter()
+                .map(|text| CreatePollAnswer {
+                    text: text.to_string(),
+                })
+                .collect(),
+            allow_multiselect: false,
+            duration_hours,
+        }
+    }
+
+    #[test]
+    fn accepts_a_valid_poll() {
+        assert!(validate_poll(&poll("Lunch?", &["Pizza", "Tacos"], Some(48))).is_ok());
+    }
+
+    #[test]
+    fn reports_each_bad_field() {
+        let long = "x".repeat(MAX_POLL_ANSWER_LEN + 1);
+        let errors = validate_poll(&poll("  ", &["ok", &long], Some(0))).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|(f, _)| f.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "poll.question",
+                "poll.answers[1].text",
+                "poll.duration_hours"
+            ]
+        );
+
+        let errors = validate_poll(&poll("Only one?", &["yes"], None)).unwrap_err();
+        assert_eq!(errors[0].0, "poll.answers");
+    }
+}
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+pub const MAX_PUSH_SUBSCRIPTIONS: i64 = 10;
+
+/// A browser's Web Push subscription. The encryption keys stay server-side.
+#[derive(Debug, Clone, Serialize)]
+pub struct PushSubscription {
+    pub id: String,
+    pub endpoint: String,
+    #[serde(skip)]
+    pub user_id: String,
+    /// The browser's P-256 public key, base64url.
+    #[serde(skip)]
+    pub p256dh: String,
+    /// The browser's 16-byte authentication secret, base64url.
+    #[serde(skip)]
+    pub auth: String,
+    pub created_at: String,
+}
+
+/// `PushSubscriptionKeys`, as `PushSubscription.toJSON()` puts them.
+#[derive(Debug, Deserialize)]
+pub struct PushSubscriptionKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+/// `POST /users/@me/push/subscriptions`: the browser's
+/// `PushSubscription.toJSON()`, sent as is. `expirationTime` is ignored;
+/// a subscription the push service has dropped is removed on first use.
+#[derive(Debug, Deserialize)]
+pub struct CreatePushSubscription {
+    pub endpoint: String,
+    pub keys: PushSubscriptionKeys,
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+/// Lockdown modes: `account_age` turns away accounts younger than the
+/// space's minimum age, `block_joins` turns away everyone.
+pub const LOCKDOWN_MODES: &[&str] = &["account_age", "block_joins"];
+pub const MAX_JOIN_WINDOW_SECS: i64 = 60 * 60;
+pub const MAX_LOCKDOWN_SECS: i64 = 7 * 24 * 60 * 60;
+pub const MAX_MIN_ACCOUNT_AGE_SECS: i64 = 90 * 24 * 60 * 60;
+
+/// A space's raid protection settings and current lockdown.
+#[derive(Debug, Clone, Serialize)]
+pub struct RaidProtection {
+    pub space_id: String,
+    pub auto_lockdown: bool,
+    pub join_threshold: i64,
+    pub join_window_secs: i64,
+    pub lockdown_secs: i64,
+    pub mode: String,
+    pub min_account_age_secs: i64,
+    pub lockdown: Option<Lockdown>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Lockdown {
+    pub since: String,
+    /// When the lockdown lifts by itself; `null` until lifted by hand.
+    pub until: Option<String>,
+    /// Started by the join-rate heuristics rather than a moderator.
+    pub automatic: bool,
+}
+
+/// `PATCH /spaces/{space_id}/raid-protection`. Omitted fields are left as
+/// they are.
+#[derive(Debug, Deserialize)]
+pub struct UpdateRaidProtection {
+    pub auto_lockdown: Option<bool>,
+    pub join_threshold: Option<i64>,
+    pub join_window_secs: Option<i64>,
+    pub lockdown_secs: Option<i64>,
+    pub mode: Option<String>,
+    pub min_account_age_secs: Option<i64>,
+}
+
+/// `PUT /spaces/{space_id}/lockdown`. Without `duration_secs` the lockdown
+/// lasts until lifted.
+#[derive(Debug, Default, Deserialize)]
+pub struct StartLockdown {
+    pub duration_secs: Option<i64>,
+}
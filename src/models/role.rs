@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::models::permission::Permissions;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Role {
     pub id: String,
@@ -8,7 +10,7 @@ pub struct Role {
     pub hoist: bool,
     pub icon: Option<String>,
     pub position: i64,
-    pub permissions: Vec<String>,
+    pub permissions: Permissions,
     pub managed: bool,
     pub mentionable: bool,
 }
@@ -22,9 +24,11 @@ pub struct RoleRow {
     pub hoist: bool,
     pub icon: Option<String>,
     pub position: i64,
-    pub permissions: String, // JSON array string
+    pub permissions: Permissions,
     pub managed: bool,
     pub mentionable: bool,
+    /// Members holding the role. Not tracked for @everyone.
+    pub member_count: i64,
 }
 
 #[derive(Debug, Deserialize)]
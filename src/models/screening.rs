@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// Most channels a welcome screen can feature.
+pub const MAX_WELCOME_CHANNELS: usize = 5;
+/// Most rules a member screening form can list.
+pub const MAX_SCREENING_RULES: usize = 16;
+pub const MAX_WELCOME_DESCRIPTION_LEN: usize = 140;
+pub const MAX_WELCOME_CHANNEL_DESCRIPTION_LEN: usize = 50;
+pub const MAX_SCREENING_DESCRIPTION_LEN: usize = 300;
+pub const MAX_RULE_LEN: usize = 300;
+
+/// What new members see when they open a space: a short description and a
+/// few channels to start in.
+#[derive(Debug, Clone, Serialize)]
+pub struct WelcomeScreen {
+    pub space_id: String,
+    pub enabled: bool,
+    pub description: Option<String>,
+    pub channels: Vec<WelcomeChannel>,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WelcomeChannel {
+    pub channel_id: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+}
+
+/// `PATCH /spaces/{space_id}/welcome-screen`. Omitted fields are left as
+/// they are; an empty description clears it.
+#[derive(Debug, Deserialize)]
+pub struct UpdateWelcomeScreen {
+    pub enabled: Option<bool>,
+    pub description: Option<String>,
+    pub channels: Option<Vec<WelcomeChannel>>,
+}
+
+/// Rules members accept before they can talk. While screening is enabled,
+/// new members join `pending` and stay that way until they accept.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberScreening {
+    pub space_id: String,
+    pub enabled: bool,
+    pub description: Option<String>,
+    pub rules: Vec<String>,
+    pub updated_at: Option<String>,
+}
+
+/// `PATCH /spaces/{space_id}/member-screening`. Omitted fields are left as
+/// they are; an empty description clears it.
+#[derive(Debug, Deserialize)]
+pub struct UpdateMemberScreening {
+    pub enabled: Option<bool>,
+    pub description: Option<String>,
+    pub rules: Option<Vec<String>>,
+}
@@ -0,0 +1,16 @@
+use serde::Serialize;
+
+/// A login session: one user token, as listed by `GET /users/@me/sessions`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Session {
+    pub id: String,
+    /// Client-supplied device name, or the User-Agent it signed in with.
+    pub device: Option<String>,
+    /// Address the session was last used from.
+    pub ip: Option<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub expires_at: String,
+    /// Whether this is the session making the request.
+    pub current: bool,
+}
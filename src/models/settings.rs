@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+use crate::space_template::SpaceTemplate;
 use crate::storage;
 
 #[derive(Debug, Clone, Serialize)]
@@ -7,18 +8,42 @@ pub struct ServerSettings {
     pub max_emoji_size: i64,
     pub max_avatar_size: i64,
     pub max_sound_size: i64,
+    pub max_sticker_size: i64,
     pub max_attachment_size: i64,
     pub max_attachments_per_message: i64,
     pub server_name: String,
     pub registration_policy: String,
     pub max_spaces: i64,
     pub max_members_per_space: i64,
+    /// Custom emoji a space may have unless an admin overrides it for that
+    /// space. 0 means unlimited.
+    pub max_emojis_per_space: i64,
+    /// Soundboard sounds a space may have, likewise.
+    pub max_sounds_per_space: i64,
+    /// Stickers a space may have, likewise.
+    pub max_stickers_per_space: i64,
     pub motd: Option<String>,
     pub public_listing: bool,
     pub tos_enabled: bool,
     pub tos_text: Option<String>,
     pub tos_version: i64,
     pub tos_url: Option<String>,
+    /// Comma-separated browser origins allowed by CORS. Empty/unset falls back
+    /// to `CORS_ALLOWED_ORIGINS`, then to allowing any origin.
+    pub cors_allowed_origins: Option<String>,
+    /// Content-Security-Policy sent with `/cdn` responses. Unset uses
+    /// `middleware::security_headers::DEFAULT_CDN_CSP`.
+    pub cdn_content_security_policy: Option<String>,
+    /// Roles and channels new spaces start with. Unset uses
+    /// `SpaceTemplate::builtin`.
+    pub default_space_template: Option<SpaceTemplate>,
+    /// Comma-separated content-safety providers new messages are scanned
+    /// with (see `models::content_safety::PROVIDERS`). Unset turns scanning off.
+    pub content_safety_providers: Option<String>,
+    /// Where the `http` provider sends messages to be scanned.
+    pub content_safety_endpoint: Option<String>,
+    /// `quarantine` or `alert`.
+    pub content_safety_action: String,
     pub updated_at: Option<String>,
 }
 
@@ -28,18 +53,28 @@ impl Default for ServerSettings {
             max_emoji_size: storage::MAX_EMOJI_SIZE as i64,
             max_avatar_size: storage::MAX_AVATAR_SIZE as i64,
             max_sound_size: storage::MAX_SOUND_SIZE as i64,
+            max_sticker_size: storage::MAX_STICKER_SIZE as i64,
             max_attachment_size: storage::MAX_ATTACHMENT_SIZE as i64,
             max_attachments_per_message: 10,
             server_name: "Accord Server".to_string(),
             registration_policy: "open".to_string(),
             max_spaces: 0,
             max_members_per_space: 0,
+            max_emojis_per_space: 50,
+            max_sounds_per_space: 8,
+            max_stickers_per_space: 20,
             motd: None,
             public_listing: false,
             tos_enabled: true,
             tos_text: None,
             tos_version: 1,
             tos_url: None,
+            cors_allowed_origins: None,
+            cdn_content_security_policy: None,
+            default_space_template: None,
+            content_safety_providers: None,
+            content_safety_endpoint: None,
+            content_safety_action: "quarantine".to_string(),
             updated_at: None,
         }
     }
@@ -50,16 +85,40 @@ pub struct UpdateServerSettings {
     pub max_emoji_size: Option<i64>,
     pub max_avatar_size: Option<i64>,
     pub max_sound_size: Option<i64>,
+    pub max_sticker_size: Option<i64>,
     pub max_attachment_size: Option<i64>,
     pub max_attachments_per_message: Option<i64>,
     pub server_name: Option<String>,
     pub registration_policy: Option<String>,
     pub max_spaces: Option<i64>,
     pub max_members_per_space: Option<i64>,
+    pub max_emojis_per_space: Option<i64>,
+    pub max_sounds_per_space: Option<i64>,
+    pub max_stickers_per_space: Option<i64>,
     pub motd: Option<String>,
     pub public_listing: Option<bool>,
     pub tos_enabled: Option<bool>,
     pub tos_text: Option<String>,
     pub tos_version: Option<i64>,
     pub tos_url: Option<String>,
+    pub cors_allowed_origins: Option<String>,
+    pub cdn_content_security_policy: Option<String>,
+    /// `null` goes back to the built-in template.
+    #[serde(default, deserialize_with = "deserialize_double_option")]
+    pub default_space_template: Option<Option<SpaceTemplate>>,
+    /// Empty turns scanning off.
+    pub content_safety_providers: Option<String>,
+    pub content_safety_endpoint: Option<String>,
+    pub content_safety_action: Option<String>,
+}
+
+/// Tells an explicit `null` (`Some(None)`) apart from an omitted field (`None`).
+pub(crate) fn deserialize_double_option<'de, D, T>(
+    deserializer: D,
+) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(Some(Option::deserialize(deserializer)?))
 }
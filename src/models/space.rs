@@ -47,8 +47,67 @@ pub struct PublicSpaceRow {
     pub member_count: i64,
     pub public: bool,
     pub allow_guest_access: bool,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub featured: bool,
+    /// When the last message in the space was sent, if ever.
+    pub last_activity_at: Option<String>,
+    pub created_at: String,
+}
+
+/// Filters for the public space directory.
+#[derive(Debug, Default)]
+pub struct DirectoryFilter {
+    /// Words that must each appear in the name or description.
+    pub search: Option<String>,
+    pub category: Option<String>,
+    pub tag: Option<String>,
+    pub featured_only: bool,
+    /// `members` (default), `activity`, `newest` or `name`.
+    pub sort: DirectorySort,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DirectorySort {
+    #[default]
+    Members,
+    Activity,
+    Newest,
+    Name,
 }
 
+impl DirectorySort {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "members" => Some(Self::Members),
+            "activity" => Some(Self::Activity),
+            "newest" => Some(Self::Newest),
+            "name" => Some(Self::Name),
+            _ => None,
+        }
+    }
+}
+
+/// Categories a space can list itself under in the directory.
+pub const SPACE_CATEGORIES: &[&str] = &[
+    "gaming",
+    "music",
+    "art",
+    "education",
+    "science",
+    "technology",
+    "entertainment",
+    "sports",
+    "community",
+    "other",
+];
+
+/// How many directory tags a space may have.
+pub const MAX_SPACE_TAGS: usize = 5;
+
+/// Longest a directory tag may be.
+pub const MAX_SPACE_TAG_LEN: usize = 24;
+
 /// Lightweight version from the DB row before loading relations.
 #[derive(Debug, Clone, Serialize)]
 pub struct SpaceRow {
@@ -73,8 +132,40 @@ pub struct SpaceRow {
     pub premium_tier: String,
     pub public: bool,
     pub allow_guest_access: bool,
+    pub emoji_moderation: bool,
     pub premium_subscription_count: i64,
     pub max_members: i64,
+    /// Custom emoji slots: the space's override, else the instance's
+    /// `max_emojis_per_space`. 0 means unlimited.
+    pub max_emojis: i64,
+    /// Soundboard slots, resolved the same way from `max_sounds_per_space`.
+    pub max_sounds: i64,
+    /// Sticker slots, resolved the same way from `max_stickers_per_space`.
+    pub max_stickers: i64,
+    /// Pass back as `expected_version` when reordering channels.
+    pub channel_order_version: i64,
+    /// `"elevated"` makes destructive moderation require a recent MFA
+    /// verification; `"none"` otherwise.
+    pub mfa_level: String,
+    /// Whether `GET /spaces/{id}/widget.json` is served.
+    pub widget_enabled: bool,
+    /// The channel the widget's instant invite leads to, if any.
+    pub widget_channel_id: Option<String>,
+    /// Whether members may create public share links to messages.
+    pub message_sharing: bool,
+    /// Whether accepting an invite needs a join token from its preview.
+    pub invite_preview_required: bool,
+    /// Screen share caps: frame height in pixels, and frames per second.
+    pub stream_max_height: i64,
+    pub stream_max_fps: i64,
+    /// Directory category, one of `SPACE_CATEGORIES`.
+    pub category: Option<String>,
+    /// Directory tags.
+    pub tags: Vec<String>,
+    /// Set by server admins to highlight the space in the directory.
+    pub featured: bool,
+    /// When the owner archived the space; archived spaces are read-only.
+    pub archived_at: Option<String>,
     pub created_at: String,
 }
 
@@ -85,6 +176,13 @@ pub struct CreateSpace {
     pub description: Option<String>,
     pub public: Option<bool>,
     pub allow_guest_access: Option<bool>,
+    /// One of `locale::SUPPORTED_LOCALES`; defaults to `en-US`.
+    pub preferred_locale: Option<String>,
+    /// Roles and channels to start with. The route fills this from the
+    /// `default_space_template` server setting when omitted; the db layer
+    /// falls back to `SpaceTemplate::builtin`.
+    #[serde(default)]
+    pub template: Option<crate::space_template::SpaceTemplate>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -108,6 +206,27 @@ pub struct AdminUpdateSpace {
     pub owner_id: Option<String>,
     pub public: Option<bool>,
     pub allow_guest_access: Option<bool>,
+    /// Override the instance's emoji slot count for this space; `null` goes
+    /// back to the instance default.
+    #[serde(
+        default,
+        deserialize_with = "super::settings::deserialize_double_option"
+    )]
+    pub max_emojis: Option<Option<i64>>,
+    /// Same, for soundboard sounds.
+    #[serde(
+        default,
+        deserialize_with = "super::settings::deserialize_double_option"
+    )]
+    pub max_sounds: Option<Option<i64>>,
+    /// Same, for stickers.
+    #[serde(
+        default,
+        deserialize_with = "super::settings::deserialize_double_option"
+    )]
+    pub max_stickers: Option<Option<i64>>,
+    /// Highlight the space in the public directory.
+    pub featured: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -126,4 +245,62 @@ pub struct UpdateSpace {
     pub preferred_locale: Option<String>,
     pub public: Option<bool>,
     pub allow_guest_access: Option<bool>,
+    pub emoji_moderation: Option<bool>,
+    /// Only the owner can change this.
+    pub mfa_level: Option<String>,
+    pub widget_enabled: Option<bool>,
+    /// Empty to stop offering an invite.
+    pub widget_channel_id: Option<String>,
+    /// Turning this off makes existing share links stop rendering.
+    pub message_sharing: Option<bool>,
+    /// Require `GET /invites/{code}/preview` before an invite is accepted.
+    pub invite_preview_required: Option<bool>,
+    /// One of `STREAM_HEIGHTS`.
+    pub stream_max_height: Option<i64>,
+    /// One of `STREAM_FRAME_RATES`.
+    pub stream_max_fps: Option<i64>,
+    /// One of `SPACE_CATEGORIES`; empty to clear.
+    pub category: Option<String>,
+    /// Replaces the directory tags; see `normalize_tags`.
+    pub tags: Option<Vec<String>>,
+}
+
+/// Screen share heights a space may cap streams at.
+pub const STREAM_HEIGHTS: &[i64] = &[480, 720, 1080, 1440];
+
+/// Screen share frame rates a space may cap streams at.
+pub const STREAM_FRAME_RATES: &[i64] = &[15, 30, 60];
+
+/// Lowercases and dedupes directory tags, rejecting more than
+/// `MAX_SPACE_TAGS` or any that isn't 1 to `MAX_SPACE_TAG_LEN` letters,
+/// digits or dashes.
+pub fn normalize_tags(tags: &[String]) -> Result<Vec<String>, String> {
+    let mut out: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty()
+            || tag.chars().count() > MAX_SPACE_TAG_LEN
+            || !tag.chars().all(|c| c.is_alphanumeric() || c == '-')
+        {
+            return Err(format!(
+                "tags must be 1 to {MAX_SPACE_TAG_LEN} letters, digits or dashes"
+            ));
+        }
+        if !out.contains(&tag) {
+            out.push(tag);
+        }
+    }
+    if out.len() > MAX_SPACE_TAGS {
+        return Err(format!("a space can have at most {MAX_SPACE_TAGS} tags"));
+    }
+    Ok(out)
+}
+
+/// Splits the comma-separated `spaces.tags` column.
+pub fn split_tags(column: &str) -> Vec<String> {
+    column
+        .split(',')
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
 }
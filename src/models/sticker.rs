@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+/// Stickers one message may carry.
+pub const MAX_STICKERS_PER_MESSAGE: usize = 3;
+pub const MIN_STICKER_NAME_LEN: usize = 2;
+pub const MAX_STICKER_NAME_LEN: usize = 30;
+pub const MAX_STICKER_DESCRIPTION_LEN: usize = 100;
+pub const MAX_STICKER_TAGS_LEN: usize = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sticker {
+    pub id: String,
+    pub space_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// Comma-separated words clients suggest the sticker for.
+    pub tags: String,
+    /// `png`, `apng`, or `lottie`.
+    pub format: String,
+    pub image_url: String,
+    pub creator_id: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSticker {
+    pub name: String,
+    pub description: Option<String>,
+    pub tags: Option<String>,
+    pub image: String, // base64 data URI
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSticker {
+    pub name: Option<String>,
+    /// An empty string clears the description.
+    pub description: Option<String>,
+    pub tags: Option<String>,
+}
+
+/// Check sticker text fields, collecting every problem as a field error.
+pub fn validate_sticker_fields(
+    name: Option<&str>,
+    description: Option<&str>,
+    tags: Option<&str>,
+) -> Result<(), Vec<crate::models::embed::FieldError>> {
+    let mut errors = Vec::new();
+    if let Some(name) = name {
+        let len = name.trim().chars().count();
+        if !(MIN_STICKER_NAME_LEN..=MAX_STICKER_NAME_LEN).contains(&len) {
+            errors.push((
+                "name".to_string(),
+                format!(
+                    "must be between {MIN_STICKER_NAME_LEN} and {MAX_STICKER_NAME_LEN} characters"
+                ),
+            ));
+        }
+    }
+    if description.is_some_and(|d| d.trim().chars().count() > MAX_STICKER_DESCRIPTION_LEN) {
+        errors.push((
+            "description".to_string(),
+            format!("must be at most {MAX_STICKER_DESCRIPTION_LEN} characters"),
+        ));
+    }
+    if tags.is_some_and(|t| t.trim().chars().count() > MAX_STICKER_TAGS_LEN) {
+        errors.push((
+            "tags".to_string(),
+            format!("must be at most {MAX_STICKER_TAGS_LEN} characters"),
+        ));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
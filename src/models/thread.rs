@@ -0,0 +1,105 @@
+use serde::Deserialize;
+
+/// Inactivity windows (minutes) a thread may auto-archive after.
+pub const AUTO_ARCHIVE_DURATIONS: &[i64] = &[60, 1440, 4320, 10080];
+
+/// Default auto-archive window: one day.
+pub const DEFAULT_AUTO_ARCHIVE_AFTER: i64 = 1440;
+
+/// Name template for auto-created threads when the channel doesn't set one.
+pub const DEFAULT_AUTO_THREAD_NAME: &str = "{content}";
+
+/// Longest name template a channel may set.
+pub const MAX_AUTO_THREAD_NAME_LEN: usize = 100;
+
+/// Name a thread started by a channel's auto-thread mode. `{author}`,
+/// `{date}` and `{content}` (the message's first non-blank line) are filled
+/// in; a name that comes out blank falls back to the author's, and anything
+/// past the 100-character limit is cut off.
+pub fn auto_thread_name(template: Option<&str>, author: &str, content: &str, date: &str) -> String {
+    let first_line = content
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or("");
+    let name = template
+        .unwrap_or(DEFAULT_AUTO_THREAD_NAME)
+        .replace("{author}", author)
+        .replace("{date}", date)
+        .replace("{content}", first_line);
+    let name = match name.trim() {
+        "" => author.trim(),
+        trimmed => trimmed,
+    };
+    name.chars().take(100).collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct ThreadRow {
+    /// Same as the ID of the message that starts the thread.
+    pub id: String,
+    /// The channel the thread lives in.
+    pub channel_id: String,
+    pub space_id: Option<String>,
+    pub owner_id: String,
+    pub name: String,
+    pub auto_archive_after: i64,
+    pub archived: bool,
+    pub archived_at: Option<String>,
+    pub locked: bool,
+    pub last_activity_at: String,
+    pub created_at: String,
+}
+
+/// Body for `POST /channels/{id}/messages/{id}/threads`.
+#[derive(Debug, Deserialize)]
+pub struct CreateThread {
+    pub name: String,
+    pub auto_archive_after: Option<i64>,
+}
+
+/// Body for `POST /channels/{id}/threads` (a thread with no existing parent
+/// message; the server posts a titled starter message for it).
+#[derive(Debug, Deserialize)]
+pub struct CreateStandaloneThread {
+    pub name: String,
+    pub content: Option<String>,
+    pub auto_archive_after: Option<i64>,
+    /// Forum channels only: IDs of the channel's tags to put on the post.
+    pub applied_tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateThread {
+    pub name: Option<String>,
+    pub archived: Option<bool>,
+    pub locked: Option<bool>,
+    pub auto_archive_after: Option<i64>,
+    /// Forum posts only: replaces the post's tags.
+    pub applied_tags: Option<Vec<String>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_thread_names_fill_the_template() {
+        assert_eq!(
+            auto_thread_name(None, "ada", "\n  release notes  \nmore", "2026-01-02"),
+            "release notes"
+        );
+        assert_eq!(
+            auto_thread_name(Some("{author} on {date}"), "ada", "hi", "2026-01-02"),
+            "ada on 2026-01-02"
+        );
+        // Attachment-only messages have no text to name the thread after.
+        assert_eq!(auto_thread_name(None, "ada", "", "2026-01-02"), "ada");
+        assert_eq!(
+            auto_thread_name(None, "ada", &"x".repeat(300), "2026-01-02")
+                .chars()
+                .count(),
+            100
+        );
+    }
+}
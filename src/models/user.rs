@@ -9,10 +9,16 @@ pub struct User {
     pub banner: Option<String>,
     pub accent_color: Option<i64>,
     pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    /// IANA timezone name.
+    pub timezone: Option<String>,
+    /// Current offset of `timezone`, computed when the user is loaded.
+    pub utc_offset_minutes: Option<i32>,
     pub bot: bool,
     pub system: bool,
     pub is_admin: bool,
     pub mfa_enabled: bool,
+    pub email_verified: bool,
     pub disabled: bool,
     pub flags: i64,
     pub public_flags: i64,
@@ -35,6 +41,9 @@ pub struct PublicUser {
     pub banner: Option<String>,
     pub accent_color: Option<i64>,
     pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    pub timezone: Option<String>,
+    pub utc_offset_minutes: Option<i32>,
     pub bot: bool,
     pub system: bool,
     pub public_flags: i64,
@@ -53,6 +62,9 @@ impl From<User> for PublicUser {
             banner: u.banner,
             accent_color: u.accent_color,
             bio: u.bio,
+            pronouns: u.pronouns,
+            timezone: u.timezone,
+            utc_offset_minutes: u.utc_offset_minutes,
             bot: u.bot,
             system: u.system,
             public_flags: u.public_flags,
@@ -76,6 +88,10 @@ pub struct UpdateUser {
     pub banner: Option<String>,
     pub accent_color: Option<i64>,
     pub bio: Option<String>,
+    /// Empty string clears.
+    pub pronouns: Option<String>,
+    /// IANA timezone name; empty string clears.
+    pub timezone: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+/// A user's synced client settings.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserSettings {
+    pub settings: serde_json::Map<String, serde_json::Value>,
+    /// Increments on every write; 0 means nothing has been saved yet.
+    pub version: i64,
+    pub updated_at: Option<String>,
+}
+
+/// PATCH /users/@me/settings body. Keys in `settings` are merged into the
+/// stored object; a `null` value removes the key.
+#[derive(Debug, Deserialize)]
+pub struct UpdateUserSettings {
+    pub settings: serde_json::Map<String, serde_json::Value>,
+    /// The version the client last saw. When set, the write is rejected if
+    /// another device has written since.
+    pub version: Option<i64>,
+}
+
+/// One sidebar entry: a folder of spaces, or (with no `id`) a single
+/// ungrouped space.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpaceFolder {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub color: Option<i64>,
+    pub space_ids: Vec<String>,
+}
+
+/// PUT /users/@me/settings/spaces body: the whole sidebar, top to bottom.
+#[derive(Debug, Deserialize)]
+pub struct UpdateSpaceLayout {
+    pub folders: Vec<SpaceFolder>,
+}
@@ -12,5 +12,80 @@ pub struct VoiceState {
     pub self_mute: bool,
     pub self_stream: bool,
     pub self_video: bool,
+    /// In a stage channel: listening in the audience rather than speaking.
     pub suppress: bool,
+    /// When this audience member raised their hand to speak, if they have.
+    #[serde(default)]
+    pub request_to_speak_timestamp: Option<String>,
+    /// A stage moderator has invited this audience member up to speak.
+    #[serde(default)]
+    pub invited_to_speak: bool,
+}
+
+/// PATCH /channels/{channel_id}/voice-states/@me
+#[derive(Debug, Deserialize)]
+pub struct UpdateOwnStageState {
+    /// `true` raises a hand, `false` lowers it.
+    pub request_to_speak: Option<bool>,
+    /// `false` to start speaking (needs an invite or `mute_members`),
+    /// `true` to step back into the audience.
+    pub suppress: Option<bool>,
+}
+
+/// PATCH /channels/{channel_id}/voice-states/{user_id}
+#[derive(Debug, Deserialize)]
+pub struct UpdateStageState {
+    /// `false` brings a raised hand up to speak, or invites someone who
+    /// hasn't asked; `true` moves a speaker back to the audience.
+    pub suppress: bool,
+}
+
+/// POST /channels/{channel_id}/voice/stats — what a participant's WebRTC
+/// stack measured for its connection to the media server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceConnectionStats {
+    /// Round-trip time in milliseconds.
+    pub rtt_ms: Option<f64>,
+    pub jitter_ms: Option<f64>,
+    /// Fraction of packets lost, from 0 to 1.
+    pub packet_loss: Option<f64>,
+    /// Combined send and receive bitrate, in bits per second.
+    pub bitrate: Option<i64>,
+}
+
+impl VoiceConnectionStats {
+    /// A coarse rating for a connection-quality indicator: `good`, `fair`,
+    /// `poor`, or `unknown` without RTT or loss figures.
+    pub fn quality(&self) -> &'static str {
+        let (rtt, loss) = (self.rtt_ms, self.packet_loss);
+        if rtt.is_none() && loss.is_none() {
+            return "unknown";
+        }
+        let (rtt, loss) = (rtt.unwrap_or(0.0), loss.unwrap_or(0.0));
+        if rtt > 400.0 || loss > 0.1 {
+            "poor"
+        } else if rtt > 150.0 || loss > 0.03 {
+            "fair"
+        } else {
+            "good"
+        }
+    }
+}
+
+/// A participant's last stats report, tied to the voice session it was sent
+/// from so a rejoin starts clean.
+#[derive(Debug, Clone)]
+pub struct VoiceStatsReport {
+    pub session_id: String,
+    pub stats: VoiceConnectionStats,
+    pub reported_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The LiveKit token last handed to a participant for their voice session,
+/// so it can be replaced before it runs out.
+#[derive(Debug, Clone)]
+pub struct VoiceTokenGrant {
+    pub session_id: String,
+    pub channel_id: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
 }
@@ -0,0 +1,52 @@
+use serde::Deserialize;
+
+use super::embed::Embed;
+
+#[derive(Debug, Clone)]
+pub struct WebhookRow {
+    pub id: String,
+    pub channel_id: String,
+    pub space_id: String,
+    pub creator_id: String,
+    pub name: String,
+    pub avatar: Option<String>,
+    /// Set on webhooks created by following an announcement channel; they
+    /// receive that channel's crossposts.
+    pub source_channel_id: Option<String>,
+    pub created_at: String,
+}
+
+/// Body for `POST /channels/{id}/followers`.
+#[derive(Debug, Deserialize)]
+pub struct FollowChannel {
+    /// Where crossposts should be delivered.
+    pub webhook_channel_id: String,
+}
+
+/// Body for `POST /channels/{id}/webhooks`.
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhook {
+    pub name: String,
+    pub avatar: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhook {
+    pub name: Option<String>,
+    /// An empty string clears the avatar.
+    pub avatar: Option<String>,
+    /// Move the webhook to another channel in the same space.
+    pub channel_id: Option<String>,
+}
+
+/// Body for `POST /webhooks/{id}/{token}`. `username` and `avatar_url`
+/// override the webhook's defaults for this message only.
+#[derive(Debug, Deserialize)]
+pub struct ExecuteWebhook {
+    #[serde(default)]
+    pub content: String,
+    pub username: Option<String>,
+    pub avatar_url: Option<String>,
+    pub tts: Option<bool>,
+    pub embeds: Option<Vec<Embed>>,
+}
@@ -0,0 +1,141 @@
+//! OAuth2 authorization server for third-party applications.
+//!
+//! A user approves an application's requested scopes at `/oauth2/authorize`;
+//! the application trades the resulting code for an access token at
+//! `/oauth2/token`. Access tokens are accepted as `Bearer` credentials but
+//! only on the routes their scopes cover (see [route_scope]). The `bot` scope
+//! issues no token: approving it adds the application's bot to a space the
+//! user manages.
+
+use axum::http::Method;
+
+use crate::error::AppError;
+
+pub const SCOPE_IDENTIFY: &str = "identify";
+pub const SCOPE_SPACES_READ: &str = "spaces.read";
+pub const SCOPE_BOT: &str = "bot";
+
+pub const SCOPES: &[&str] = &[SCOPE_IDENTIFY, SCOPE_SPACES_READ, SCOPE_BOT];
+
+/// How long an authorization code can be redeemed for.
+pub const CODE_TTL_SECS: i64 = 600;
+
+/// Lifetime of an access token. Its refresh token is single-use and stays
+/// valid for as long again after the access token expires.
+pub const ACCESS_TOKEN_TTL_SECS: i64 = 7 * 24 * 3600;
+
+pub const MAX_REDIRECT_URIS: usize = 10;
+
+/// Parse a space-separated scope list, dropping duplicates.
+pub fn parse_scopes(scope: &str) -> Result<Vec<String>, AppError> {
+    let mut scopes: Vec<String> = Vec::new();
+    for s in scope.split_whitespace() {
+        if !SCOPES.contains(&s) {
+            return Err(AppError::BadRequest(format!("unknown scope: {s}")));
+        }
+        if !scopes.iter().any(|existing| existing == s) {
+            scopes.push(s.to_string());
+        }
+    }
+    if scopes.is_empty() {
+        return Err(AppError::BadRequest("scope is required".into()));
+    }
+    Ok(scopes)
+}
+
+/// The scope an OAuth2 access token needs for a route, or `None` if such
+/// tokens can't use it at all. `path` is the matched route pattern without
+/// the `/api/v1` prefix.
+pub fn route_scope(method: &Method, path: &str) -> Option<&'static str> {
+    match (method, path) {
+        (&Method::GET, "/users/@me") => Some(SCOPE_IDENTIFY),
+        (&Method::GET, "/users/@me/spaces") => Some(SCOPE_SPACES_READ),
+        _ => None,
+    }
+}
+
+/// Redirect URIs must be absolute, fragment-free and HTTPS, except on
+/// loopback where native apps listen.
+pub fn validate_redirect_uri(uri: &str) -> Result<(), AppError> {
+    let loopback = ["http://localhost", "http://127.0.0.1", "http://[::1]"]
+        .iter()
+        .any(|prefix| {
+            uri.strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with([':', '/']))
+        });
+    if uri.len() > 512
+        || uri.contains('#')
+        || uri.chars().any(char::is_whitespace)
+        || !((uri.starts_with("https://") && uri.len() > "https://".len()) || loopback)
+    {
+        return Err(AppError::BadRequest(format!(
+            "invalid redirect URI: {uri} (must be https://, or http:// on loopback)"
+        )));
+    }
+    Ok(())
+}
+
+/// Append query parameters to a registered redirect URI.
+pub fn redirect_with(uri: &str, params: &[(&str, &str)]) -> String {
+    let mut out = uri.to_string();
+    let mut sep = if uri.contains('?') { '&' } else { '?' };
+    for (key, value) in params {
+        out.push(sep);
+        out.push_str(key);
+        out.push('=');
+        out.push_str(&urlencoded(value));
+        sep = '&';
+    }
+    out
+}
+
+fn urlencoded(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char);
+            }
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_are_checked_and_deduplicated() {
+        assert_eq!(
+            parse_scopes("identify  spaces.read identify").unwrap(),
+            vec!["identify", "spaces.read"]
+        );
+        assert!(parse_scopes("identify admin").is_err());
+        assert!(parse_scopes(" ").is_err());
+    }
+
+    #[test]
+    fn redirect_uris_must_be_https_or_loopback() {
+        assert!(validate_redirect_uri("https://app.example/callback").is_ok());
+        assert!(validate_redirect_uri("http://localhost:8080/cb").is_ok());
+        assert!(validate_redirect_uri("http://127.0.0.1/cb").is_ok());
+        assert!(validate_redirect_uri("http://app.example/cb").is_err());
+        assert!(validate_redirect_uri("http://localhost.evil.example/cb").is_err());
+        assert!(validate_redirect_uri("https://app.example/cb#frag").is_err());
+        assert!(validate_redirect_uri("https://").is_err());
+    }
+
+    #[test]
+    fn redirect_params_are_encoded() {
+        assert_eq!(
+            redirect_with("https://a.example/cb", &[("code", "x"), ("state", "a b&c")]),
+            "https://a.example/cb?code=x&state=a%20b%26c"
+        );
+        assert_eq!(
+            redirect_with("https://a.example/cb?v=1", &[("code", "x")]),
+            "https://a.example/cb?v=1&code=x"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! Background finalization for polls.
+//!
+//! Voting closes at a poll's `expires_at`. The sweep then marks the poll
+//! finalized, posts a `poll_result` system message replying to it, and
+//! sends a `message.update` so clients show the final tallies.
+
+use std::time::Duration;
+
+use crate::db;
+use crate::error::AppError;
+use crate::models::poll::PollRow;
+use crate::routes::messages::{
+    broadcast_channel_event, message_row_to_json, message_to_json_with_context,
+};
+use crate::state::AppState;
+
+/// How often expired polls are swept.
+pub const FINALIZE_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Sweep forever, finalizing expired polls.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(FINALIZE_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = finalize_expired(&state).await {
+            tracing::warn!("poll finalization sweep failed: {e:?}");
+        }
+    }
+}
+
+/// Finalize every open poll past its `expires_at`. Returns how many this
+/// sweep finalized.
+pub async fn finalize_expired(state: &AppState) -> Result<usize, AppError> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut finalized = 0;
+    for poll in db::polls::list_expired(&state.db, &now).await? {
+        // Claim the poll first so a concurrent sweep can't announce it twice.
+        if !db::polls::claim_finalize(&state.db, &poll.message_id, &now).await? {
+            continue;
+        }
+        if let Err(e) = announce(state, &poll).await {
+            tracing::warn!("failed to announce poll {} results: {e:?}", poll.message_id);
+        }
+        finalized += 1;
+    }
+    Ok(finalized)
+}
+
+/// The answer with strictly the most votes.
+fn winner<'a>(poll: &'a PollRow, votes: &[(i64, i64, bool)]) -> Option<&'a str> {
+    let mut counts: Vec<(i64, i64)> = votes.iter().map(|(id, n, _)| (*id, *n)).collect();
+    counts.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+    match counts.as_slice() {
+        [(id, n), rest @ ..] if *n > 0 && rest.first().is_none_or(|(_, m)| m < n) => poll
+            .answers
+            .iter()
+            .find(|a| a.id == *id)
+            .map(|a| a.text.as_str()),
+        _ => None,
+    }
+}
+
+async fn announce(state: &AppState, poll: &PollRow) -> Result<(), AppError> {
+    let msg = db::messages::get_message_row(&state.db, &poll.message_id).await?;
+    let channel = db::channels::get_channel_row(&state.db, &msg.channel_id).await?;
+    let votes = db::polls::get_vote_counts(&state.db, std::slice::from_ref(&poll.message_id), None)
+        .await?
+        .remove(&poll.message_id)
+        .unwrap_or_default();
+    let locale = match channel.space_id {
+        Some(ref sid) => db::spaces::get_space_row(&state.db, sid)
+            .await
+            .map(|s| s.preferred_locale)
+            .unwrap_or_else(|_| crate::locale::DEFAULT_LOCALE.to_string()),
+        None => crate::locale::DEFAULT_LOCALE.to_string(),
+    };
+
+    let notice = db::messages::create_system_message(
+        &state.db,
+        &channel.id,
+        &msg.author_id,
+        channel.space_id.as_deref(),
+        &crate::locale::poll_ended(&locale, &poll.question, winner(poll, &votes)),
+        "poll_result",
+        Some(&msg.id),
+    )
+    .await?;
+    broadcast_channel_event(
+        state,
+        &channel,
+        "message.create",
+        message_row_to_json(&notice),
+        "messages",
+    )
+    .await;
+
+    let attachments = db::attachments::get_attachments_for_message(&state.db, &msg.id).await?;
+    let json = message_to_json_with_context(&state.db, &msg, &attachments).await?;
+    broadcast_channel_event(state, &channel, "message.update", json, "messages").await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::poll::PollAnswer;
+
+    fn poll() -> PollRow {
+        PollRow {
+            message_id: "1".into(),
+            question: "q".into(),
+            answers: ["a", "b", "c"]
+                .iter()
+                .zip(1..)
+                .map(|(text, id)| PollAnswer {
+                    id,
+                    text: text.to_string(),
+                })
+                .collect(),
+            allow_multiselect: false,
+            expires_at: String::new(),
+            finalized_at: None,
+        }
+    }
+
+    #[test]
+    fn winner_needs_a_clear_lead() {
+        let poll = poll();
+        assert_eq!(winner(&poll, &[(1, 2, false), (3, 5, false)]), Some("c"));
+        assert_eq!(winner(&poll, &[(1, 2, false), (2, 2, false)]), None);
+        assert_eq!(winner(&poll, &[]), None);
+    }
+}
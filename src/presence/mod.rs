@@ -0,0 +1,148 @@
+//! Who is online. A single node keeps presence in `AppState::presences` and
+//! counts sessions from the dispatcher; with a [SharedPresence] store (Redis,
+//! when built with the `redis` feature) both live outside the process, so
+//! presence holds across replicas and restarts.
+
+#[cfg(feature = "redis")]
+pub mod redis;
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::models::presence::{ClientStatus, Presence};
+use crate::state::AppState;
+
+pub type PresenceFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Presence and gateway-session bookkeeping shared between nodes.
+/// Implementations log and swallow backend errors: presence is best-effort
+/// and must never fail a gateway connection.
+pub trait SharedPresence: Send + Sync {
+    /// Store `presence`, returning the previous one.
+    fn set<'a>(&'a self, presence: &'a Presence) -> PresenceFuture<'a, Option<Presence>>;
+
+    fn remove<'a>(&'a self, user_id: &'a str) -> PresenceFuture<'a, Option<Presence>>;
+
+    /// Presences of whichever of `user_ids` are online.
+    fn get_many<'a>(&'a self, user_ids: &'a [String]) -> PresenceFuture<'a, Vec<Presence>>;
+
+    fn online_count(&self) -> PresenceFuture<'_, usize>;
+
+    /// Record a gateway session held by this node.
+    fn open_session<'a>(&'a self, user_id: &'a str, session_id: &'a str) -> PresenceFuture<'a, ()>;
+
+    fn close_session<'a>(&'a self, user_id: &'a str, session_id: &'a str)
+        -> PresenceFuture<'a, ()>;
+
+    /// Live sessions of `user_id` on any node, other than `exclude_session_id`.
+    fn other_sessions<'a>(
+        &'a self,
+        user_id: &'a str,
+        exclude_session_id: &'a str,
+    ) -> PresenceFuture<'a, usize>;
+}
+
+/// Set a user's presence. Returns the previous presence if any.
+pub async fn set_presence(
+    state: &AppState,
+    user_id: &str,
+    status: &str,
+    activities: Vec<serde_json::Value>,
+) -> Option<Presence> {
+    let presence = Presence {
+        user_id: user_id.to_string(),
+        status: status.to_string(),
+        client_status: ClientStatus {
+            desktop: Some(status.to_string()),
+            mobile: None,
+            web: None,
+        },
+        activities,
+        space_id: None,
+    };
+    match state.shared_presence {
+        Some(ref shared) => shared.set(&presence).await,
+        None => state.presences.insert(user_id.to_string(), presence),
+    }
+}
+
+/// Remove a user's presence. Returns the old presence if any.
+pub async fn remove_presence(state: &AppState, user_id: &str) -> Option<Presence> {
+    match state.shared_presence {
+        Some(ref shared) => shared.remove(user_id).await,
+        None => state.presences.remove(user_id).map(|(_, p)| p),
+    }
+}
+
+/// Get a single user's current presence.
+pub async fn get_user_presence(state: &AppState, user_id: &str) -> Option<Presence> {
+    match state.shared_presence {
+        Some(ref shared) => shared
+            .get_many(&[user_id.to_string()])
+            .await
+            .into_iter()
+            .next(),
+        None => state.presences.get(user_id).map(|p| p.clone()),
+    }
+}
+
+/// Get presences for all online members of a space.
+/// Requires the set of member user IDs for that space.
+pub async fn get_space_presences(state: &AppState, member_ids: &HashSet<String>) -> Vec<Presence> {
+    match state.shared_presence {
+        Some(ref shared) => {
+            let ids: Vec<String> = member_ids.iter().cloned().collect();
+            shared.get_many(&ids).await
+        }
+        None => state
+            .presences
+            .iter()
+            .filter(|entry| member_ids.contains(entry.key()))
+            .map(|entry| entry.value().clone())
+            .collect(),
+    }
+}
+
+/// Number of users currently online.
+pub async fn online_count(state: &AppState) -> usize {
+    match state.shared_presence {
+        Some(ref shared) => shared.online_count().await,
+        None => state.presences.len(),
+    }
+}
+
+/// Note a newly identified gateway session. Only the shared store needs
+/// this; locally the dispatcher already knows its sessions.
+pub async fn open_session(state: &AppState, user_id: &str, session_id: &str) {
+    if let Some(ref shared) = state.shared_presence {
+        shared.open_session(user_id, session_id).await;
+    }
+}
+
+/// Counterpart to [open_session], run when a session is torn down.
+pub async fn close_session(state: &AppState, user_id: &str, session_id: &str) {
+    if let Some(ref shared) = state.shared_presence {
+        shared.close_session(user_id, session_id).await;
+    }
+}
+
+/// Check if a user has any other active gateway sessions.
+pub async fn user_has_other_sessions(
+    state: &AppState,
+    user_id: &str,
+    exclude_session_id: &str,
+) -> bool {
+    if let Some(ref shared) = state.shared_presence {
+        return shared.other_sessions(user_id, exclude_session_id).await > 0;
+    }
+    if let Some(ref dispatcher) = *state.dispatcher.read().await {
+        for entry in dispatcher.sessions().iter() {
+            let session = entry.value();
+            if session.user_id == user_id && session.session_id != exclude_session_id {
+                return true;
+            }
+        }
+    }
+    false
+}
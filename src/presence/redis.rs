@@ -0,0 +1,259 @@
+//! [SharedPresence] backed by Redis.
+//!
+//! Each online user has `{prefix}:presence:{user_id}` (the presence JSON) and
+//! `{prefix}:sessions:{user_id}`, a sorted set of session ids scored by when
+//! they expire. Nodes refresh both for the sessions they hold every
+//! [REFRESH_INTERVAL], so if a node dies its users drop offline within
+//! [SESSION_TTL] instead of staying online forever.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+
+use super::{PresenceFuture, SharedPresence};
+use crate::config::RedisConfig;
+use crate::models::presence::Presence;
+
+/// How long a session (and its user's presence) outlives its last refresh.
+pub const SESSION_TTL: Duration = Duration::from_secs(90);
+
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Keys per MGET when loading a space's presences.
+const MGET_CHUNK: usize = 500;
+
+pub struct RedisPresence {
+    conn: ConnectionManager,
+    prefix: String,
+    /// session_id -> user_id for the sessions this node refreshes.
+    local_sessions: Arc<DashMap<String, String>>,
+}
+
+fn presence_key(prefix: &str, user_id: &str) -> String {
+    format!("{prefix}:presence:{user_id}")
+}
+
+fn sessions_key(prefix: &str, user_id: &str) -> String {
+    format!("{prefix}:sessions:{user_id}")
+}
+
+fn now_secs() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// Log a failed call and carry on with `T::default()`.
+fn or_default<T: Default>(what: &str, result: redis::RedisResult<T>) -> T {
+    result.unwrap_or_else(|e| {
+        tracing::warn!("redis presence {what} failed: {e}");
+        T::default()
+    })
+}
+
+fn parse(json: Option<String>) -> Option<Presence> {
+    json.and_then(|json| serde_json::from_str(&json).ok())
+}
+
+impl RedisPresence {
+    /// Connect and start refreshing this node's sessions.
+    pub async fn connect(config: &RedisConfig) -> redis::RedisResult<Self> {
+        let client = redis::Client::open(config.url.as_str())?;
+        let presence = Self {
+            conn: client.get_connection_manager().await?,
+            prefix: config.channel_prefix.clone(),
+            local_sessions: Arc::new(DashMap::new()),
+        };
+        tokio::spawn(refresh_loop(
+            presence.conn.clone(),
+            presence.prefix.clone(),
+            presence.local_sessions.clone(),
+        ));
+        Ok(presence)
+    }
+}
+
+/// Push the expiry of every local session, and its user's presence, out by
+/// another [SESSION_TTL].
+async fn refresh_loop(
+    mut conn: ConnectionManager,
+    prefix: String,
+    local_sessions: Arc<DashMap<String, String>>,
+) {
+    let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+    loop {
+        interval.tick().await;
+        let sessions: Vec<(String, String)> = local_sessions
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        if sessions.is_empty() {
+            continue;
+        }
+        let expires = now_secs() + SESSION_TTL.as_secs() as i64;
+        let mut pipe = redis::pipe();
+        for (session_id, user_id) in &sessions {
+            let key = sessions_key(&prefix, user_id);
+            pipe.zadd(&key, session_id, expires)
+                .ignore()
+                .expire(&key, SESSION_TTL.as_secs() as i64)
+                .ignore()
+                .expire(presence_key(&prefix, user_id), SESSION_TTL.as_secs() as i64)
+                .ignore();
+        }
+        or_default::<()>("refresh", pipe.query_async(&mut conn).await);
+    }
+}
+
+impl SharedPresence for RedisPresence {
+    fn set<'a>(&'a self, presence: &'a Presence) -> PresenceFuture<'a, Option<Presence>> {
+        Box::pin(async move {
+            let key = presence_key(&self.prefix, &presence.user_id);
+            let json = serde_json::to_string(presence).unwrap();
+            let (prev,): (Option<String>,) = or_default(
+                "set",
+                redis::pipe()
+                    .get(&key)
+                    .set_ex(&key, json, SESSION_TTL.as_secs())
+                    .ignore()
+                    .query_async(&mut self.conn.clone())
+                    .await,
+            );
+            parse(prev)
+        })
+    }
+
+    fn remove<'a>(&'a self, user_id: &'a str) -> PresenceFuture<'a, Option<Presence>> {
+        Box::pin(async move {
+            let key = presence_key(&self.prefix, user_id);
+            let (prev,): (Option<String>,) = or_default(
+                "remove",
+                redis::pipe()
+                    .get(&key)
+                    .del(&key)
+                    .ignore()
+                    .query_async(&mut self.conn.clone())
+                    .await,
+            );
+            parse(prev)
+        })
+    }
+
+    fn get_many<'a>(&'a self, user_ids: &'a [String]) -> PresenceFuture<'a, Vec<Presence>> {
+        Box::pin(async move {
+            let mut conn = self.conn.clone();
+            let mut presences = Vec::new();
+            for chunk in user_ids.chunks(MGET_CHUNK) {
+                let keys: Vec<String> = chunk
+                    .iter()
+                    .map(|id| presence_key(&self.prefix, id))
+                    .collect();
+                let values: Vec<Option<String>> = or_default(
+                    "get",
+                    redis::cmd("MGET").arg(&keys).query_async(&mut conn).await,
+                );
+                presences.extend(values.into_iter().filter_map(parse));
+            }
+            presences
+        })
+    }
+
+    fn online_count(&self) -> PresenceFuture<'_, usize> {
+        Box::pin(async move {
+            let mut conn = self.conn.clone();
+            let pattern = presence_key(&self.prefix, "*");
+            let mut count = 0;
+            let mut cursor = 0u64;
+            loop {
+                let (next, keys): (u64, Vec<String>) = match redis::cmd("SCAN")
+                    .arg(cursor)
+                    .arg("MATCH")
+                    .arg(&pattern)
+                    .arg("COUNT")
+                    .arg(1000)
+                    .query_async(&mut conn)
+                    .await
+                {
+                    Ok(page) => page,
+                    Err(e) => {
+                        tracing::warn!("redis presence count failed: {e}");
+                        return count;
+                    }
+                };
+                count += keys.len();
+                if next == 0 {
+                    return count;
+                }
+                cursor = next;
+            }
+        })
+    }
+
+    fn open_session<'a>(&'a self, user_id: &'a str, session_id: &'a str) -> PresenceFuture<'a, ()> {
+        Box::pin(async move {
+            self.local_sessions
+                .insert(session_id.to_string(), user_id.to_string());
+            let key = sessions_key(&self.prefix, user_id);
+            let expires = now_secs() + SESSION_TTL.as_secs() as i64;
+            or_default::<()>(
+                "open session",
+                redis::pipe()
+                    .zadd(&key, session_id, expires)
+                    .ignore()
+                    .expire(&key, SESSION_TTL.as_secs() as i64)
+                    .ignore()
+                    .query_async(&mut self.conn.clone())
+                    .await,
+            );
+        })
+    }
+
+    fn close_session<'a>(
+        &'a self,
+        user_id: &'a str,
+        session_id: &'a str,
+    ) -> PresenceFuture<'a, ()> {
+        Box::pin(async move {
+            self.local_sessions.remove(session_id);
+            let key = sessions_key(&self.prefix, user_id);
+            or_default(
+                "close session",
+                self.conn.clone().zrem::<_, _, ()>(&key, session_id).await,
+            );
+        })
+    }
+
+    fn other_sessions<'a>(
+        &'a self,
+        user_id: &'a str,
+        exclude_session_id: &'a str,
+    ) -> PresenceFuture<'a, usize> {
+        Box::pin(async move {
+            let key = sessions_key(&self.prefix, user_id);
+            let now = now_secs();
+            let (live,): (Vec<String>,) = or_default(
+                "count sessions",
+                redis::pipe()
+                    .zrembyscore(&key, "-inf", now)
+                    .ignore()
+                    .zrangebyscore(&key, now, "+inf")
+                    .query_async(&mut self.conn.clone())
+                    .await,
+            );
+            live.iter().filter(|id| *id != exclude_session_id).count()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_share_the_cluster_prefix() {
+        assert_eq!(presence_key("accord", "42"), "accord:presence:42");
+        assert_eq!(sessions_key("accord", "42"), "accord:sessions:42");
+        assert_eq!(presence_key("accord", "*"), "accord:presence:*");
+    }
+}
@@ -0,0 +1,58 @@
+//! Pronouns and timezone, set on the account and optionally overridden per
+//! space. Payloads carry `utc_offset_minutes` alongside a timezone so
+//! clients can show a collaborator's local time without a tz database.
+
+use chrono::{Offset, TimeZone};
+
+use crate::error::AppError;
+
+pub const MAX_PRONOUNS_LEN: usize = 40;
+
+pub fn validate_pronouns(pronouns: &str) -> Result<(), AppError> {
+    if pronouns.chars().count() > MAX_PRONOUNS_LEN {
+        return Err(AppError::BadRequest(format!(
+            "pronouns must be at most {MAX_PRONOUNS_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+/// Check an IANA timezone name (`Europe/Berlin`). An empty string clears the
+/// field and is passed through.
+pub fn validate_timezone(timezone: &str) -> Result<(), AppError> {
+    if timezone.is_empty() || timezone.parse::<chrono_tz::Tz>().is_ok() {
+        return Ok(());
+    }
+    Err(AppError::BadRequest(
+        "timezone must be an IANA name such as Europe/Berlin".into(),
+    ))
+}
+
+/// The zone's current offset from UTC, or `None` for an unset or unknown
+/// zone.
+pub fn utc_offset_minutes(timezone: Option<&str>) -> Option<i32> {
+    let tz: chrono_tz::Tz = timezone?.parse().ok()?;
+    let offset = tz.offset_from_utc_datetime(&chrono::Utc::now().naive_utc());
+    Some(offset.fix().local_minus_utc() / 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timezones_are_iana_names() {
+        assert!(validate_timezone("Europe/Berlin").is_ok());
+        assert!(validate_timezone("").is_ok());
+        assert!(validate_timezone("Mars/Olympus_Mons").is_err());
+        assert_eq!(utc_offset_minutes(Some("UTC")), Some(0));
+        assert_eq!(utc_offset_minutes(Some("Asia/Kolkata")), Some(330));
+        assert_eq!(utc_offset_minutes(None), None);
+    }
+
+    #[test]
+    fn pronouns_are_length_limited() {
+        assert!(validate_pronouns("they/them").is_ok());
+        assert!(validate_pronouns(&"x".repeat(MAX_PRONOUNS_LEN + 1)).is_err());
+    }
+}
@@ -0,0 +1,452 @@
+//! Web Push: DMs and mentions delivered to the browsers of users who have
+//! no gateway session open.
+//!
+//! Browsers register with `POST /users/@me/push/subscriptions`, passing the
+//! `PushSubscription` they got by subscribing with this server's VAPID
+//! public key (`GET /users/@me/push/vapid-key`). [run] watches the
+//! `message.create` events this node broadcasts and, for each [recipients]
+//! of one, POSTs a payload encrypted for each of their subscriptions
+//! (RFC 8291, `aes128gcm`) to the push service, signed with a VAPID JWT
+//! (RFC 8292). Push is best-effort: failures are logged, not retried, and a
+//! subscription the push service reports gone is deleted.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes128Gcm, Nonce};
+use hmac::{Hmac, Mac};
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{PublicKey, SecretKey};
+use rand::RngCore;
+use reqwest::{Method, Url};
+use sha2::Sha256;
+
+use crate::config::PushConfig;
+use crate::db;
+use crate::error::AppError;
+use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::permissions::resolve_channel_permissions;
+use crate::models::permission::has_permission;
+use crate::models::push::PushSubscription;
+use crate::safe_fetch::{self, FetchOptions};
+use crate::state::AppState;
+use crate::webauthn::{decode, encode};
+
+/// How long the push service holds a notification for an offline browser.
+pub const PUSH_TTL_SECS: u64 = 24 * 60 * 60;
+/// Lifetime of a VAPID JWT; RFC 8292 caps it at a day.
+const JWT_TTL_SECS: i64 = 12 * 60 * 60;
+/// Characters of the message kept in the notification.
+pub const PUSH_PREVIEW_LEN: usize = 200;
+/// The payload goes out as a single record of at most this many bytes.
+const RECORD_SIZE: u32 = 4096;
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// HKDF-SHA256 for outputs of one block (at most 32 bytes), which is all
+/// RFC 8291 needs.
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+    let prk = hmac_sha256(salt, &[ikm]);
+    hmac_sha256(&prk, &[info, &[1]])[..len].to_vec()
+}
+
+/// Encrypt `plaintext` for a browser holding the private half of
+/// `ua_public` and the secret `auth_secret`, producing an `aes128gcm` body.
+pub fn encrypt(ua_public: &[u8], auth_secret: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let as_secret = SecretKey::random(&mut rand::thread_rng());
+    encrypt_with(&as_secret, &salt, ua_public, auth_secret, plaintext)
+}
+
+fn encrypt_with(
+    as_secret: &SecretKey,
+    salt: &[u8; 16],
+    ua_public: &[u8],
+    auth_secret: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, String> {
+    let ua_key = PublicKey::from_sec1_bytes(ua_public).map_err(|_| "invalid p256dh key")?;
+    let as_public = as_secret.public_key().to_encoded_point(false);
+    let shared = p256::ecdh::diffie_hellman(as_secret.to_nonzero_scalar(), ua_key.as_affine());
+
+    let key_info = [
+        b"WebPush: info\0".as_slice(),
+        ua_public,
+        as_public.as_bytes(),
+    ]
+    .concat();
+    let ikm = hkdf(auth_secret, shared.raw_secret_bytes(), &key_info, 32);
+    let cek = hkdf(salt, &ikm, b"Content-Encoding: aes128gcm\0", 16);
+    let nonce = hkdf(salt, &ikm, b"Content-Encoding: nonce\0", 12);
+
+    // A single record: the payload, then the final-record delimiter.
+    let mut record = plaintext.to_vec();
+    record.push(2);
+    if record.len() + 16 > RECORD_SIZE as usize {
+        return Err("payload too large".into());
+    }
+    let cipher = Aes128Gcm::new_from_slice(&cek).map_err(|e| e.to_string())?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), record.as_slice())
+        .map_err(|e| e.to_string())?;
+
+    let mut body = Vec::with_capacity(86 + ciphertext.len());
+    body.extend_from_slice(salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(as_public.as_bytes().len() as u8);
+    body.extend_from_slice(as_public.as_bytes());
+    body.extend_from_slice(&ciphertext);
+    Ok(body)
+}
+
+/// This server's VAPID identity, and the sending half of Web Push.
+pub struct Notifier {
+    key: SigningKey,
+    public_key: String,
+    subject: Option<String>,
+}
+
+impl Notifier {
+    pub fn new(config: &PushConfig) -> Result<Self, String> {
+        let bytes = decode("VAPID_PRIVATE_KEY", &config.vapid_private_key)
+            .map_err(|_| "VAPID_PRIVATE_KEY is not valid base64url".to_string())?;
+        let key = SigningKey::from_slice(&bytes)
+            .map_err(|_| "VAPID_PRIVATE_KEY is not a P-256 private key".to_string())?;
+        let public_key = encode(key.verifying_key().to_encoded_point(false).as_bytes());
+        Ok(Self {
+            key,
+            public_key,
+            subject: config.subject.clone(),
+        })
+    }
+
+    /// The uncompressed public key, base64url: the `applicationServerKey`
+    /// browsers subscribe with.
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    /// The `Authorization` header for a request to `endpoint`.
+    fn authorization(&self, endpoint: &Url) -> String {
+        let header = encode(br#"{"typ":"JWT","alg":"ES256"}"#);
+        let mut claims = serde_json::json!({
+            "aud": endpoint.origin().ascii_serialization(),
+            "exp": chrono::Utc::now().timestamp() + JWT_TTL_SECS,
+        });
+        if let Some(ref subject) = self.subject {
+            claims["sub"] = subject.clone().into();
+        }
+        let signing_input = format!("{header}.{}", encode(claims.to_string().as_bytes()));
+        let signature: Signature = self.key.sign(signing_input.as_bytes());
+        format!(
+            "vapid t={signing_input}.{}, k={}",
+            encode(&signature.to_bytes()),
+            self.public_key
+        )
+    }
+
+    /// Deliver `payload` to one subscription, deleting it if the push
+    /// service says it has expired or been unsubscribed.
+    pub async fn send(
+        &self,
+        pool: &crate::db::DbPool,
+        sub: &PushSubscription,
+        payload: &[u8],
+    ) -> Result<(), String> {
+        let endpoint = safe_fetch::validate_url(&sub.endpoint).map_err(|e| e.to_string())?;
+        let ua_public = decode("p256dh", &sub.p256dh).map_err(|_| "invalid p256dh")?;
+        let auth_secret = decode("auth", &sub.auth).map_err(|_| "invalid auth")?;
+        let body = encrypt(&ua_public, &auth_secret, payload)?;
+        let headers = [
+            ("Authorization", self.authorization(&endpoint)),
+            ("Content-Encoding", "aes128gcm".to_string()),
+            ("Content-Type", "application/octet-stream".to_string()),
+            ("TTL", PUSH_TTL_SECS.to_string()),
+            ("Urgency", "high".to_string()),
+        ];
+        let resp = safe_fetch::request(
+            Method::POST,
+            &sub.endpoint,
+            &headers,
+            Some(&body),
+            &FetchOptions::default(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        match resp.status {
+            200..=299 => Ok(()),
+            404 | 410 => {
+                db::push::delete_by_endpoint(pool, &sub.endpoint)
+                    .await
+                    .map_err(|e| format!("{e:?}"))?;
+                Ok(())
+            }
+            status => Err(format!("push service returned {status}")),
+        }
+    }
+}
+
+/// Send pushes for the messages this node creates, forever. Returns at once
+/// when push isn't configured.
+pub async fn run(state: AppState) {
+    if state.push.is_none() {
+        return;
+    }
+    let receiver = state
+        .gateway_tx
+        .read()
+        .await
+        .as_ref()
+        .map(|tx| tx.subscribe());
+    let Some(mut receiver) = receiver else {
+        return;
+    };
+    loop {
+        match receiver.recv().await {
+            Ok(broadcast) if broadcast.event["type"] == "message.create" => {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = notify(&state, &broadcast).await {
+                        tracing::warn!("sending push notifications failed: {e:?}");
+                    }
+                });
+            }
+            Ok(_) => {}
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                tracing::warn!("push notifier dropped {n} events");
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Users who should get a push for `broadcast`: for a new DM, the other
+/// participants; for a new space message, the members it mentions by ID or
+/// role who can see the channel (@everyone and @here never push). Anyone
+/// with a gateway session open, anyone without a push subscription, anyone
+/// who has the channel muted (directly or through its category or space),
+/// and anyone who has blocked the author is left out.
+pub async fn recipients(
+    state: &AppState,
+    broadcast: &GatewayBroadcast,
+) -> Result<Vec<String>, AppError> {
+    if broadcast.event["type"] != "message.create" {
+        return Ok(Vec::new());
+    }
+    let data = &broadcast.event["data"];
+    let (Some(channel_id), Some(author_id)) =
+        (data["channel_id"].as_str(), data["author_id"].as_str())
+    else {
+        return Ok(Vec::new());
+    };
+    let ids = |key: &str| -> Vec<String> {
+        data[key]
+            .as_array()
+            .map(|a| {
+                a.iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let mut candidates = match (&broadcast.space_id, &broadcast.target_user_ids) {
+        (None, Some(participants)) => participants.clone(),
+        (Some(space_id), _) => {
+            let mut mentioned = ids("mentions");
+            match &broadcast.role_mention_user_ids {
+                Some(role_members) => mentioned.extend(role_members.iter().cloned()),
+                None => mentioned.extend(
+                    db::members::list_member_ids_with_roles(
+                        &state.db,
+                        space_id,
+                        &ids("mention_roles"),
+                    )
+                    .await?,
+                ),
+            }
+            mentioned
+        }
+        (None, None) => return Ok(Vec::new()),
+    };
+    candidates.sort();
+    candidates.dedup();
+    candidates.retain(|id| id != author_id);
+
+    let subscribed: std::collections::HashSet<String> =
+        db::push::subscriptions_for_users(&state.db, &candidates)
+            .await?
+            .into_iter()
+            .map(|sub| sub.user_id)
+            .collect();
+
+    let mut recipients = Vec::new();
+    for user_id in candidates {
+        if !subscribed.contains(&user_id)
+            || crate::presence::user_has_other_sessions(state, &user_id, "").await
+            || db::relationships::is_blocked_by(&state.db, &user_id, author_id).await?
+            || db::mutes::list_effective_muted_channel_ids(&state.db, &user_id)
+                .await?
+                .iter()
+                .any(|id| id == channel_id)
+        {
+            continue;
+        }
+        if let Some(ref space_id) = broadcast.space_id {
+            let can_view = resolve_channel_permissions(&state.db, channel_id, space_id, &user_id)
+                .await
+                .is_ok_and(|perms| has_permission(perms, "view_channel"));
+            if !can_view {
+                continue;
+            }
+        }
+        recipients.push(user_id);
+    }
+    Ok(recipients)
+}
+
+/// The notification a service worker receives for a message.
+pub async fn payload(state: &AppState, data: &serde_json::Value) -> serde_json::Value {
+    let author_id = data["author_id"].as_str().unwrap_or_default();
+    let author = match data["webhook_id"].as_str() {
+        Some(_) => serde_json::json!({
+            "id": author_id,
+            "username": data["webhook_username"],
+            "display_name": null,
+            "avatar": null,
+        }),
+        None => match db::users::get_user(&state.db, author_id).await {
+            Ok(user) => serde_json::json!({
+                "id": user.id,
+                "username": user.username,
+                "display_name": user.display_name,
+                "avatar": user.avatar,
+            }),
+            Err(_) => serde_json::json!({ "id": author_id }),
+        },
+    };
+    serde_json::json!({
+        "type": "message.create",
+        "message_id": data["id"],
+        "channel_id": data["channel_id"],
+        "space_id": data["space_id"],
+        "author": author,
+        "content": data["content"]
+            .as_str()
+            .unwrap_or_default()
+            .chars()
+            .take(PUSH_PREVIEW_LEN)
+            .collect::<String>(),
+    })
+}
+
+/// Push `broadcast` to each of its [recipients]' subscriptions.
+pub async fn notify(state: &AppState, broadcast: &GatewayBroadcast) -> Result<(), AppError> {
+    let Some(ref notifier) = state.push else {
+        return Ok(());
+    };
+    let users = recipients(state, broadcast).await?;
+    if users.is_empty() {
+        return Ok(());
+    }
+    let payload = payload(state, &broadcast.event["data"]).await.to_string();
+    for sub in db::push::subscriptions_for_users(&state.db, &users).await? {
+        if let Err(e) = notifier.send(&state.db, &sub, payload.as_bytes()).await {
+            tracing::debug!("push to subscription {} failed: {e}", sub.id);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::signature::Verifier;
+    use p256::ecdsa::VerifyingKey;
+
+    /// The browser's side of RFC 8291, to check [encrypt_with] against.
+    fn decrypt(ua_secret: &SecretKey, auth_secret: &[u8], body: &[u8]) -> Vec<u8> {
+        let salt = &body[..16];
+        assert_eq!(
+            u32::from_be_bytes(body[16..20].try_into().unwrap()),
+            RECORD_SIZE
+        );
+        let id_len = body[20] as usize;
+        let as_public = &body[21..21 + id_len];
+        let as_key = PublicKey::from_sec1_bytes(as_public).unwrap();
+        let ua_public = ua_secret.public_key().to_encoded_point(false);
+        let shared = p256::ecdh::diffie_hellman(ua_secret.to_nonzero_scalar(), as_key.as_affine());
+        let key_info = [
+            b"WebPush: info\0".as_slice(),
+            ua_public.as_bytes(),
+            as_public,
+        ]
+        .concat();
+        let ikm = hkdf(auth_secret, shared.raw_secret_bytes(), &key_info, 32);
+        let cek = hkdf(salt, &ikm, b"Content-Encoding: aes128gcm\0", 16);
+        let nonce = hkdf(salt, &ikm, b"Content-Encoding: nonce\0", 12);
+        let mut record = Aes128Gcm::new_from_slice(&cek)
+            .unwrap()
+            .decrypt(Nonce::from_slice(&nonce), &body[21 + id_len..])
+            .unwrap();
+        assert_eq!(record.pop(), Some(2));
+        record
+    }
+
+    #[test]
+    fn payloads_decrypt_with_the_browser_keys() {
+        let ua_secret = SecretKey::random(&mut rand::thread_rng());
+        let ua_public = ua_secret.public_key().to_encoded_point(false);
+        let auth_secret = [7u8; 16];
+        let body = encrypt(ua_public.as_bytes(), &auth_secret, b"{\"hello\":1}").unwrap();
+        assert_eq!(decrypt(&ua_secret, &auth_secret, &body), b"{\"hello\":1}");
+
+        assert!(encrypt(b"not a key", &auth_secret, b"hi").is_err());
+        assert!(encrypt(ua_public.as_bytes(), &auth_secret, &[b'x'; 4096]).is_err());
+    }
+
+    #[test]
+    fn vapid_header_is_a_jwt_signed_by_the_public_key() {
+        let key = SigningKey::random(&mut rand::thread_rng());
+        let notifier = Notifier::new(&PushConfig {
+            vapid_private_key: encode(&key.to_bytes()),
+            subject: Some("mailto:admin@example.com".into()),
+        })
+        .unwrap();
+        let endpoint = Url::parse("https://push.example.com/send/abc?x=1").unwrap();
+        let header = notifier.authorization(&endpoint);
+
+        let (token, k) = header
+            .strip_prefix("vapid t=")
+            .and_then(|rest| rest.split_once(", k="))
+            .unwrap();
+        assert_eq!(k, notifier.public_key());
+        let (signing_input, signature) = token.rsplit_once('.').unwrap();
+        let claims: serde_json::Value = serde_json::from_slice(
+            &decode("claims", signing_input.split_once('.').unwrap().1).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(claims["aud"], "https://push.example.com");
+        assert_eq!(claims["sub"], "mailto:admin@example.com");
+
+        let verifying = VerifyingKey::from_sec1_bytes(&decode("k", k).unwrap()).unwrap();
+        let signature = Signature::from_slice(&decode("sig", signature).unwrap()).unwrap();
+        assert!(verifying
+            .verify(signing_input.as_bytes(), &signature)
+            .is_ok());
+
+        assert!(Notifier::new(&PushConfig {
+            vapid_private_key: "not-a-key".into(),
+            subject: None,
+        })
+        .is_err());
+    }
+}
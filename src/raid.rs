@@ -0,0 +1,104 @@
+//! Raid protection: lockdowns that keep new members out of a space.
+//!
+//! The settings and current lockdown live in `space_raid_protection` (see
+//! `migrations/074_raid_protection.sql`). [check_join] runs before invite
+//! accepts and public joins; [record_join] runs after a member is added and
+//! starts an automatic lockdown when joins spike.
+
+use crate::db;
+use crate::error::AppError;
+use crate::gateway::events::GatewayBroadcast;
+use crate::models::raid::RaidProtection;
+use crate::state::AppState;
+
+/// Fail with 403 if the space's lockdown keeps `user_id` out: `space_lockdown`
+/// when it blocks all joins, `account_too_new` when the account is younger
+/// than the space's minimum age.
+pub async fn check_join(state: &AppState, space_id: &str, user_id: &str) -> Result<(), AppError> {
+    let raid = db::raid::get_raid_protection(&state.db, space_id).await?;
+    if raid.lockdown.is_none() {
+        return Ok(());
+    }
+    if raid.mode == "block_joins" {
+        return Err(AppError::ForbiddenCode(
+            "space_lockdown",
+            "this space isn't accepting new members right now".into(),
+        ));
+    }
+    let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+    let old_enough = crate::snowflake::timestamp_of(user_id).is_some_and(|created_ms| {
+        now_ms.saturating_sub(created_ms) >= raid.min_account_age_secs as u64 * 1000
+    });
+    if !old_enough {
+        return Err(AppError::ForbiddenCode(
+            "account_too_new",
+            "this space isn't accepting new accounts right now".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Called after a member joins. With automatic lockdowns on, a join that
+/// brings the space to `join_threshold` joins within `join_window_secs`
+/// locks it down for `lockdown_secs`.
+pub async fn record_join(state: &AppState, space_id: &str) -> Result<(), AppError> {
+    let raid = db::raid::get_raid_protection(&state.db, space_id).await?;
+    if !raid.auto_lockdown || raid.lockdown.is_some() {
+        return Ok(());
+    }
+    let window_start = db::raid::timestamp_in(-raid.join_window_secs);
+    let joins = db::raid::count_joins_since(&state.db, space_id, &window_start).await?;
+    if joins < raid.join_threshold {
+        return Ok(());
+    }
+    let until = db::raid::timestamp_in(raid.lockdown_secs);
+    if !db::raid::start_automatic_lockdown(&state.db, space_id, &until).await? {
+        return Ok(());
+    }
+    tracing::warn!(
+        "space {space_id} locked down: {joins} joins in {}s",
+        raid.join_window_secs
+    );
+
+    let raid = db::raid::get_raid_protection(&state.db, space_id).await?;
+    let system_user_id = db::users::get_or_create_system_user(&state.db).await?;
+    crate::routes::audit_log::record(
+        state,
+        space_id,
+        &system_user_id,
+        "space_lockdown_start",
+        Some((space_id, "space")),
+        Some(&format!(
+            "{joins} joins in {} seconds",
+            raid.join_window_secs
+        )),
+        None,
+    )
+    .await;
+    broadcast_lockdown(state, &raid).await;
+    Ok(())
+}
+
+/// Send `space.lockdown` with the space's current lockdown (`null` once
+/// lifted) to its members.
+pub async fn broadcast_lockdown(state: &AppState, raid: &RaidProtection) {
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "space.lockdown",
+            "data": {
+                "space_id": raid.space_id,
+                "mode": raid.mode,
+                "min_account_age_secs": raid.min_account_age_secs,
+                "lockdown": raid.lockdown,
+            }
+        });
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: Some(raid.space_id.clone()),
+            target_user_ids: None,
+            event,
+            intent: "spaces".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
@@ -0,0 +1,40 @@
+//! The retention reaper: deletes messages whose time is up. For now that's
+//! ephemeral interaction responses, which live for [EPHEMERAL_MESSAGE_TTL].
+
+use std::time::Duration;
+
+use crate::db;
+use crate::state::AppState;
+
+/// How long an ephemeral interaction response is kept.
+pub const EPHEMERAL_MESSAGE_TTL: chrono::Duration = chrono::Duration::minutes(15);
+/// How often expired messages are swept.
+pub const RETENTION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// When an ephemeral message created now expires.
+pub fn ephemeral_expiry() -> String {
+    (chrono::Utc::now() + EPHEMERAL_MESSAGE_TTL)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Sweep forever, deleting expired messages.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(RETENTION_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = sweep(&state).await {
+            tracing::warn!("retention sweep failed: {e:?}");
+        }
+    }
+}
+
+/// One pass of the reaper. Returns how many messages it deleted.
+pub async fn sweep(state: &AppState) -> Result<u64, crate::error::AppError> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let deleted = db::messages::delete_expired_ephemeral_messages(&state.db, &now).await?;
+    if deleted > 0 {
+        tracing::debug!("swept {deleted} expired ephemeral messages");
+    }
+    Ok(deleted)
+}
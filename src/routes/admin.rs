@@ -7,8 +7,12 @@ use serde::Deserialize;
 
 use crate::db;
 use crate::error::AppError;
+use crate::middleware::audit::AuditReason;
 use crate::middleware::auth::AuthUser;
 use crate::middleware::permissions::require_server_admin;
+use crate::models::content_safety::{
+    ContentSafetyFlagQuery, ContentSafetyHashInput, ReviewContentSafetyFlag, FLAG_STATUSES,
+};
 use crate::models::space::AdminUpdateSpace;
 use crate::models::user::AdminUpdateUser;
 use crate::state::AppState;
@@ -20,6 +24,54 @@ pub struct AdminListQuery {
     pub search: Option<String>,
 }
 
+// =========================================================================
+// Metrics
+// =========================================================================
+
+/// Counters kept in memory since the process started.
+pub async fn get_metrics(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    Ok(Json(serde_json::json!({
+        "data": { "typing": state.typing.stats.to_json() }
+    })))
+}
+
+// =========================================================================
+// Doctor
+// =========================================================================
+
+/// GET /admin/doctor — scan for inconsistent records without changing
+/// anything (see `crate::doctor`).
+pub async fn run_doctor(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    let report = crate::doctor::run(&state.db, state.storage.as_ref(), Some(&state), false).await?;
+    Ok(Json(serde_json::json!({ "data": report })))
+}
+
+/// POST /admin/doctor/repair — scan, and delete the inconsistent records
+/// found.
+pub async fn repair_doctor(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    let report = crate::doctor::run(&state.db, state.storage.as_ref(), Some(&state), true).await?;
+    if report.repaired > 0 {
+        tracing::warn!(
+            "admin {} repaired {} inconsistent records",
+            auth.user_id,
+            report.repaired
+        );
+    }
+    Ok(Json(serde_json::json!({ "data": report })))
+}
+
 // =========================================================================
 // Spaces
 // =========================================================================
@@ -71,6 +123,14 @@ pub async fn update_space(
     if let Some(ref owner_id) = input.owner_id {
         db::users::get_user(&state.db, owner_id).await?;
     }
+    if matches!(input.max_emojis, Some(Some(v)) if v < 0)
+        || matches!(input.max_sounds, Some(Some(v)) if v < 0)
+        || matches!(input.max_stickers, Some(Some(v)) if v < 0)
+    {
+        return Err(AppError::BadRequest(
+            "max_emojis, max_sounds, and max_stickers must be 0 (unlimited) or more".into(),
+        ));
+    }
 
     db::admin::admin_update_space(&state.db, &space_id, &input, state.db_is_postgres).await?;
 
@@ -78,6 +138,60 @@ pub async fn update_space(
     Ok(Json(serde_json::json!({ "data": space })))
 }
 
+/// DELETE /admin/spaces/{space_id} — delete any space, without the owner's
+/// MFA requirement.
+pub async fn delete_space(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    let space = db::spaces::get_space_row(&state.db, &space_id).await?;
+    super::spaces::remove_space(&state, &space_id).await?;
+    tracing::warn!(
+        "admin {} deleted space {} ({})",
+        auth.user_id,
+        space_id,
+        space.name
+    );
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// DELETE /admin/vanity-urls/{code}
+///
+/// Takes a vanity code away from whichever space holds it and keeps anyone
+/// from claiming it again. The space's audit log records it, with the
+/// `X-Audit-Log-Reason` if given.
+pub async fn revoke_vanity_url(
+    state: State<AppState>,
+    Path(code): Path<String>,
+    auth: AuthUser,
+    audit_reason: AuditReason,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    let code = code.to_lowercase();
+    let space_id =
+        db::invites::revoke_vanity_code(&state.db, &code, &auth.user_id, audit_reason.as_deref())
+            .await?;
+    if let Some(ref space_id) = space_id {
+        super::audit_log::record(
+            &state,
+            space_id,
+            &auth.user_id,
+            "vanity_url_revoke",
+            None,
+            audit_reason.as_deref(),
+            Some(serde_json::json!({
+                "vanity_url_code": { "old": code, "new": null }
+            })),
+        )
+        .await;
+    }
+    Ok(Json(serde_json::json!({
+        "data": { "code": code, "space_id": space_id }
+    })))
+}
+
 // =========================================================================
 // Users
 // =========================================================================
@@ -152,9 +266,27 @@ pub async fn update_user(
 
     let user =
         db::admin::admin_update_user(&state.db, &user_id, &input, state.db_is_postgres).await?;
+    // Disabled accounts can't authenticate; also drop the sessions they
+    // already have open.
+    if input.disabled == Some(true) {
+        super::auth::revoke_user_sessions(&state, &user_id).await?;
+    }
     Ok(Json(serde_json::json!({ "data": user })))
 }
 
+/// POST /admin/users/{user_id}/logout — revoke every session the user has
+/// and disconnect their gateway connections.
+pub async fn logout_user(
+    state: State<AppState>,
+    Path(user_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    db::users::get_user(&state.db, &user_id).await?;
+    let revoked = super::auth::revoke_user_sessions(&state, &user_id).await?;
+    Ok(Json(serde_json::json!({ "data": { "revoked": revoked } })))
+}
+
 pub async fn delete_user(
     state: State<AppState>,
     Path(user_id): Path<String>,
@@ -269,6 +401,110 @@ pub async fn reset_user_password(
     })))
 }
 
+// =========================================================================
+// Reports
+// =========================================================================
+
+const REPORT_STATUSES: &[&str] = &["pending", "reviewing", "actioned", "dismissed"];
+
+#[derive(Deserialize)]
+pub struct AdminReportsQuery {
+    pub status: Option<String>,
+    pub target_type: Option<String>,
+    pub space_id: Option<String>,
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// GET /admin/reports — every report on the instance, newest first.
+pub async fn list_reports(
+    state: State<AppState>,
+    auth: AuthUser,
+    Query(params): Query<AdminReportsQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+
+    let limit = params.limit.unwrap_or(50).clamp(1, 100);
+    let filter = db::reports::ReportFilter {
+        status: params.status.as_deref(),
+        target_type: params.target_type.as_deref(),
+        space_id: params.space_id.as_deref(),
+        before: params.before.as_deref(),
+    };
+    let mut rows = db::reports::list_all_reports(&state.db, &filter, limit + 1).await?;
+
+    let has_more = rows.len() as i64 > limit;
+    if has_more {
+        rows.truncate(limit as usize);
+    }
+
+    let data: Vec<_> = rows.iter().map(super::reports::report_to_json).collect();
+    let mut response = serde_json::json!({ "data": data });
+    if has_more {
+        response["cursor"] = serde_json::json!({
+            "before": rows.last().map(|r| r.id.clone()).unwrap_or_default(),
+            "has_more": has_more
+        });
+    }
+    Ok(Json(response))
+}
+
+pub async fn get_report(
+    state: State<AppState>,
+    Path(report_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    let report = db::reports::get_report(&state.db, &report_id).await?;
+    Ok(Json(
+        serde_json::json!({ "data": super::reports::report_to_json(&report) }),
+    ))
+}
+
+/// PATCH /admin/reports/{report_id}
+///
+/// Moves a report through `pending` → `reviewing` → `actioned`/`dismissed`;
+/// a resolved report can be reopened to `reviewing`. `action_taken` is kept
+/// unless a new one is given.
+pub async fn update_report(
+    state: State<AppState>,
+    Path(report_id): Path<String>,
+    auth: AuthUser,
+    Json(body): Json<super::reports::ResolveReportBody>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    if !REPORT_STATUSES.contains(&body.status.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "status must be one of: {}",
+            REPORT_STATUSES.join(", ")
+        )));
+    }
+
+    let existing = db::reports::get_report(&state.db, &report_id).await?;
+    super::reports::check_transition(&existing.status, &body.status)?;
+    let action_taken = body.action_taken.or(existing.action_taken);
+
+    let report = db::reports::resolve_report(
+        &state.db,
+        &report_id,
+        &auth.user_id,
+        &body.status,
+        action_taken.as_deref(),
+        state.db_is_postgres,
+    )
+    .await?;
+    let json = super::reports::report_to_json(&report);
+
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast::admin(
+            "admin.report_update",
+            json.clone(),
+        ));
+    }
+
+    Ok(Json(serde_json::json!({ "data": json })))
+}
+
 // =========================================================================
 // Federation peers
 // =========================================================================
@@ -405,3 +641,229 @@ pub async fn delete_federation_peer(
     db::federation::delete_peer(&state.db, &domain).await?;
     Ok(Json(serde_json::json!({ "data": { "deleted": true } })))
 }
+
+// =========================================================================
+// Instance blocklists
+// =========================================================================
+
+#[derive(Deserialize, Default)]
+pub struct BlocklistEntryInput {
+    pub reason: Option<String>,
+}
+
+fn require_blocklist_kind(kind: &str) -> Result<(), AppError> {
+    if !crate::blocklist::KINDS.contains(&kind) {
+        return Err(AppError::NotFound(format!(
+            "unknown blocklist: {kind} (expected one of {})",
+            crate::blocklist::KINDS.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+pub async fn list_blocklist(
+    state: State<AppState>,
+    Path(kind): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    require_blocklist_kind(&kind)?;
+    let entries = db::blocklist::list_entries(&state.db, &kind).await?;
+    Ok(Json(serde_json::json!({ "data": entries })))
+}
+
+pub async fn add_blocklist_entry(
+    state: State<AppState>,
+    Path((kind, value)): Path<(String, String)>,
+    auth: AuthUser,
+    body: Option<Json<BlocklistEntryInput>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    require_blocklist_kind(&kind)?;
+    let value = crate::blocklist::normalize_value(&kind, &value)?;
+    if kind == crate::blocklist::KIND_USER {
+        db::users::get_user(&state.db, &value).await?;
+        if value == auth.user_id {
+            return Err(AppError::BadRequest("you cannot ban yourself".into()));
+        }
+    }
+    let reason = body.and_then(|Json(b)| b.reason);
+    if reason.as_ref().is_some_and(|r| r.len() > 512) {
+        return Err(AppError::BadRequest(
+            "reason must be at most 512 characters".into(),
+        ));
+    }
+    db::blocklist::upsert_entry(
+        &state.db,
+        &kind,
+        &value,
+        reason.as_deref(),
+        &auth.user_id,
+        state.db_is_postgres,
+    )
+    .await?;
+    Ok(Json(serde_json::json!({
+        "data": { "kind": kind, "value": value, "reason": reason }
+    })))
+}
+
+pub async fn delete_blocklist_entry(
+    state: State<AppState>,
+    Path((kind, value)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    require_blocklist_kind(&kind)?;
+    let value = crate::blocklist::normalize_value(&kind, &value)?;
+    if !db::blocklist::delete_entry(&state.db, &kind, &value).await? {
+        return Err(AppError::NotFound("blocklist entry not found".into()));
+    }
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+// =========================================================================
+// Content safety
+// =========================================================================
+
+/// GET /admin/content-safety/flags — newest first, optionally by `status`.
+pub async fn list_content_safety_flags(
+    state: State<AppState>,
+    auth: AuthUser,
+    Query(params): Query<ContentSafetyFlagQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    if let Some(ref status) = params.status {
+        if !FLAG_STATUSES.contains(&status.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "status must be one of: {}",
+                FLAG_STATUSES.join(", ")
+            )));
+        }
+    }
+
+    let limit = params.limit.unwrap_or(50).clamp(1, 100);
+    let mut flags = db::content_safety::list_flags(
+        &state.db,
+        params.status.as_deref(),
+        params.before.as_deref(),
+        limit + 1,
+    )
+    .await?;
+    let has_more = flags.len() as i64 > limit;
+    if has_more {
+        flags.truncate(limit as usize);
+    }
+
+    let mut response = serde_json::json!({ "data": flags });
+    if has_more {
+        response["cursor"] = serde_json::json!({
+            "before": flags.last().map(|f| f.id.clone()).unwrap_or_default(),
+            "has_more": has_more
+        });
+    }
+    Ok(Json(response))
+}
+
+pub async fn get_content_safety_flag(
+    state: State<AppState>,
+    Path(flag_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    let flag = db::content_safety::get_flag(&state.db, &flag_id).await?;
+    Ok(Json(serde_json::json!({ "data": flag })))
+}
+
+/// PATCH /admin/content-safety/flags/{flag_id}
+///
+/// Review a pending flag: `released` puts a quarantined message back,
+/// `removed` deletes the message and its attachments. A flag is reviewed
+/// once (409 `flag_already_reviewed`).
+pub async fn review_content_safety_flag(
+    state: State<AppState>,
+    Path(flag_id): Path<String>,
+    auth: AuthUser,
+    Json(body): Json<ReviewContentSafetyFlag>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    if !matches!(body.status.as_str(), "released" | "removed") {
+        return Err(AppError::BadRequest(
+            "status must be released or removed".into(),
+        ));
+    }
+    let flag = db::content_safety::get_flag(&state.db, &flag_id).await?;
+    if !db::content_safety::review_flag(
+        &state.db,
+        &flag_id,
+        &body.status,
+        &auth.user_id,
+        state.db_is_postgres,
+    )
+    .await?
+    {
+        return Err(AppError::ConflictCode(
+            "flag_already_reviewed",
+            "this flag has already been reviewed".into(),
+        ));
+    }
+    if body.status == "released" {
+        crate::content_safety::release(&state, &flag).await?;
+    } else {
+        crate::content_safety::remove(&state, &flag).await?;
+    }
+    let flag = db::content_safety::get_flag(&state.db, &flag_id).await?;
+    Ok(Json(serde_json::json!({ "data": flag })))
+}
+
+/// GET /admin/content-safety/hashes — the `hash_list` provider's list, in
+/// hash order (`?after=<hash>` pages).
+pub async fn list_content_safety_hashes(
+    state: State<AppState>,
+    auth: AuthUser,
+    Query(params): Query<AdminListQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+    let hashes = db::content_safety::list_hashes(&state.db, params.after.as_deref(), limit).await?;
+    Ok(Json(serde_json::json!({ "data": hashes })))
+}
+
+/// PUT /admin/content-safety/hashes/{hash} — add a SHA-256 (hex) to the
+/// list, with an optional `label` shown on the flags it raises.
+pub async fn add_content_safety_hash(
+    state: State<AppState>,
+    Path(hash): Path<String>,
+    auth: AuthUser,
+    body: Option<Json<ContentSafetyHashInput>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    let hash = crate::blocklist::normalize_value(crate::blocklist::KIND_FILE_HASH, &hash)?;
+    let label = body.and_then(|Json(b)| b.label).filter(|l| !l.is_empty());
+    if label.as_ref().is_some_and(|l| l.len() > 256) {
+        return Err(AppError::BadRequest(
+            "label must be at most 256 characters".into(),
+        ));
+    }
+    let entry = db::content_safety::upsert_hash(
+        &state.db,
+        &hash,
+        label.as_deref(),
+        &auth.user_id,
+        state.db_is_postgres,
+    )
+    .await?;
+    Ok(Json(serde_json::json!({ "data": entry })))
+}
+
+pub async fn delete_content_safety_hash(
+    state: State<AppState>,
+    Path(hash): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_server_admin(&auth)?;
+    let hash = crate::blocklist::normalize_value(crate::blocklist::KIND_FILE_HASH, &hash)?;
+    if !db::content_safety::delete_hash(&state.db, &hash).await? {
+        return Err(AppError::NotFound("hash not listed".into()));
+    }
+    Ok(Json(serde_json::json!({ "data": null })))
+}
@@ -1,10 +1,18 @@
-use axum::extract::State;
+use axum::extract::{Path, Query, State};
 use axum::Json;
 
 use crate::db;
 use crate::error::AppError;
 use crate::middleware::auth::AuthUser;
-use crate::models::application::CreateApplication;
+use crate::middleware::ip_allowlist::{Cidr, MAX_ALLOWLIST_ENTRIES};
+use crate::models::application::{
+    CreateApplication, RotateSigningKeys, UpdateApplication, UpdateIpAllowlist,
+    DEFAULT_SIGNING_KEY_OVERLAP_SECS, MAX_SIGNING_KEYS, MAX_SIGNING_KEY_OVERLAP_SECS,
+};
+use crate::models::event_subscription::{
+    CreateEventSubscription, EventDeliveryQuery, EventSubscription, UpdateEventSubscription,
+    EVENT_TYPES, MAX_EVENT_SUBSCRIPTIONS, MAX_SUBSCRIPTION_SPACES,
+};
 use crate::state::AppState;
 
 pub async fn create_application(
@@ -34,12 +42,69 @@ pub async fn get_current_application(
 pub async fn update_current_application(
     state: State<AppState>,
     auth: AuthUser,
-    Json(_body): Json<serde_json::Value>,
+    Json(input): Json<UpdateApplication>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let app = db::auth::get_application_by_owner(&state.db, &auth.user_id).await?;
+    if let Some(ref uris) = input.redirect_uris {
+        if auth.is_bot {
+            return Err(AppError::Forbidden(
+                "bots cannot change their own redirect URIs".into(),
+            ));
+        }
+        if uris.len() > crate::oauth2::MAX_REDIRECT_URIS {
+            return Err(AppError::BadRequest(format!(
+                "at most {} redirect URIs",
+                crate::oauth2::MAX_REDIRECT_URIS
+            )));
+        }
+        for uri in uris {
+            crate::oauth2::validate_redirect_uri(uri)?;
+        }
+        db::oauth2::set_redirect_uris(&state.db, &app.id, uris).await?;
+    }
+    if let Some(ref url) = input.interactions_endpoint_url {
+        if auth.is_bot {
+            return Err(AppError::Forbidden(
+                "bots cannot change their own interactions endpoint".into(),
+            ));
+        }
+        if url.is_empty() {
+            db::auth::set_interactions_endpoint(&state.db, &app.id, None).await?;
+        } else {
+            check_interactions_endpoint(&state, &app.id, url).await?;
+            db::auth::set_interactions_endpoint(&state.db, &app.id, Some(url)).await?;
+        }
+    }
+    let app = db::auth::get_application(&state.db, &app.id).await?;
     Ok(Json(serde_json::json!({ "data": app })))
 }
 
+/// Send `url` a signed `ping`; it must answer 2xx with `{"type": "pong"}`
+/// to become the application's interactions endpoint.
+async fn check_interactions_endpoint(
+    state: &AppState,
+    application_id: &str,
+    url: &str,
+) -> Result<(), AppError> {
+    crate::safe_fetch::validate_url(url)
+        .map_err(|e| AppError::BadRequest(format!("interactions_endpoint_url: {e}")))?;
+    let ping = serde_json::json!({
+        "id": crate::snowflake::generate(),
+        "application_id": application_id,
+        "type": "ping",
+    });
+    let resp = super::interactions::post_to_endpoint(state, application_id, url, &ping).await?;
+    let pong = (200..300).contains(&resp.status)
+        && serde_json::from_slice::<serde_json::Value>(&resp.body)
+            .is_ok_and(|body| body["type"] == "pong");
+    if !pong {
+        return Err(AppError::BadRequest(
+            "interactions endpoint didn't answer the ping with a pong".into(),
+        ));
+    }
+    Ok(())
+}
+
 pub async fn reset_token(
     state: State<AppState>,
     auth: AuthUser,
@@ -48,3 +113,357 @@ pub async fn reset_token(
     let token = db::auth::reset_bot_token(&state.db, &app.id).await?;
     Ok(Json(serde_json::json!({ "data": { "token": token } })))
 }
+
+/// `POST /applications/@me/oauth2/reset-secret` -- issue a new OAuth2 client
+/// secret. It is only shown once; the previous one stops working.
+pub async fn reset_client_secret(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if auth.is_bot {
+        return Err(AppError::Forbidden(
+            "bots cannot reset their own client secret".into(),
+        ));
+    }
+    let app = db::auth::get_application_by_owner(&state.db, &auth.user_id).await?;
+    let secret = db::oauth2::reset_client_secret(&state.db, &app.id).await?;
+    Ok(Json(serde_json::json!({
+        "data": { "client_id": app.id, "client_secret": secret }
+    })))
+}
+
+pub async fn get_ip_allowlist(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let app = db::auth::get_application_by_owner(&state.db, &auth.user_id).await?;
+    Ok(Json(
+        serde_json::json!({ "data": { "ranges": app.ip_allowlist } }),
+    ))
+}
+
+/// `PUT /applications/@me/ip-allowlist` -- replace the CIDR ranges the bot
+/// token may be used from. An empty list lifts the restriction.
+pub async fn update_ip_allowlist(
+    state: State<AppState>,
+    auth: AuthUser,
+    Json(input): Json<UpdateIpAllowlist>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    // Otherwise a leaked bot token could lift its own restriction.
+    if auth.is_bot {
+        return Err(AppError::Forbidden(
+            "bots cannot change their own IP allowlist".into(),
+        ));
+    }
+    let app = db::auth::get_application_by_owner(&state.db, &auth.user_id).await?;
+    if input.ranges.len() > MAX_ALLOWLIST_ENTRIES {
+        return Err(AppError::BadRequest(format!(
+            "at most {MAX_ALLOWLIST_ENTRIES} ranges"
+        )));
+    }
+    let mut ranges: Vec<String> = Vec::new();
+    for range in &input.ranges {
+        let cidr = Cidr::parse(range)
+            .ok_or_else(|| AppError::BadRequest(format!("invalid CIDR range: {range}")))?
+            .to_string();
+        if !ranges.contains(&cidr) {
+            ranges.push(cidr);
+        }
+    }
+    let app = db::auth::set_ip_allowlist(&state.db, &app.id, &ranges).await?;
+    Ok(Json(
+        serde_json::json!({ "data": { "ranges": app.ip_allowlist } }),
+    ))
+}
+
+/// `GET /applications/@me/signing-keys` -- the keys requests to this
+/// application are currently signed with, oldest first. Secrets are not
+/// included.
+pub async fn list_signing_keys(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let app = db::auth::get_application_by_owner(&state.db, &auth.user_id).await?;
+    let keys = db::signing_keys::list_live_keys(&state.db, &app.id).await?;
+    Ok(Json(serde_json::json!({ "data": keys })))
+}
+
+/// `POST /applications/@me/signing-keys/rotate` -- add a new signing key.
+/// The keys it replaces keep signing alongside it for `overlap_secs`, so
+/// receivers can switch secrets without dropping requests. The new secret
+/// is only shown once.
+pub async fn rotate_signing_key(
+    state: State<AppState>,
+    auth: AuthUser,
+    body: Option<Json<RotateSigningKeys>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if auth.is_bot {
+        return Err(AppError::Forbidden(
+            "bots cannot rotate their own signing keys".into(),
+        ));
+    }
+    let app = db::auth::get_application_by_owner(&state.db, &auth.user_id).await?;
+    let input = body.map(|Json(b)| b).unwrap_or_default();
+    let overlap_secs = input
+        .overlap_secs
+        .unwrap_or(DEFAULT_SIGNING_KEY_OVERLAP_SECS);
+    if !(0..=MAX_SIGNING_KEY_OVERLAP_SECS).contains(&overlap_secs) {
+        return Err(AppError::BadRequest(format!(
+            "overlap_secs must be between 0 and {MAX_SIGNING_KEY_OVERLAP_SECS}"
+        )));
+    }
+    if overlap_secs > 0 {
+        let live = db::signing_keys::list_live_keys(&state.db, &app.id).await?;
+        AppError::check_limit(
+            "signing_key_limit_reached",
+            live.len() as i64,
+            MAX_SIGNING_KEYS,
+        )?;
+    }
+    let key = db::signing_keys::rotate(&state.db, &app.id, overlap_secs).await?;
+    Ok(Json(serde_json::json!({ "data": key })))
+}
+
+/// `DELETE /applications/@me/signing-keys/{key_id}` -- stop a key signing
+/// immediately, without waiting out its overlap.
+pub async fn revoke_signing_key(
+    state: State<AppState>,
+    Path(key_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if auth.is_bot {
+        return Err(AppError::Forbidden(
+            "bots cannot revoke their own signing keys".into(),
+        ));
+    }
+    let app = db::auth::get_application_by_owner(&state.db, &auth.user_id).await?;
+    db::signing_keys::revoke(&state.db, &app.id, &key_id).await?;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+// =========================================================================
+// Event subscriptions
+// =========================================================================
+
+const DELIVERY_STATUSES: &[&str] = &["pending", "succeeded", "failed"];
+
+fn validate_subscription_url(url: &str) -> Result<(), AppError> {
+    crate::safe_fetch::validate_url(url)
+        .map(|_| ())
+        .map_err(|e| AppError::BadRequest(format!("url: {e}")))
+}
+
+fn validate_event_types(event_types: &[String]) -> Result<Vec<String>, AppError> {
+    if event_types.is_empty() {
+        return Err(AppError::BadRequest("event_types must not be empty".into()));
+    }
+    let mut types: Vec<String> = Vec::new();
+    for event_type in event_types {
+        if !EVENT_TYPES.contains(&event_type.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "unknown event type: {event_type}"
+            )));
+        }
+        if !types.contains(event_type) {
+            types.push(event_type.clone());
+        }
+    }
+    Ok(types)
+}
+
+fn validate_space_ids(space_ids: &[String]) -> Result<Vec<String>, AppError> {
+    let mut ids: Vec<String> = Vec::new();
+    for id in space_ids {
+        if !ids.contains(id) {
+            ids.push(id.clone());
+        }
+    }
+    if ids.len() > MAX_SUBSCRIPTION_SPACES {
+        return Err(AppError::BadRequest(format!(
+            "at most {MAX_SUBSCRIPTION_SPACES} space_ids"
+        )));
+    }
+    Ok(ids)
+}
+
+/// The @me application and one of its subscriptions, refusing bots: a
+/// bot token shouldn't be able to point its own events somewhere else.
+async fn owned_subscription(
+    state: &AppState,
+    auth: &AuthUser,
+    subscription_id: &str,
+) -> Result<EventSubscription, AppError> {
+    if auth.is_bot {
+        return Err(AppError::Forbidden(
+            "bots cannot manage event subscriptions".into(),
+        ));
+    }
+    let app = db::auth::get_application_by_owner(&state.db, &auth.user_id).await?;
+    let sub = db::event_subscriptions::get_subscription(&state.db, subscription_id).await?;
+    if sub.application_id != app.id {
+        return Err(AppError::NotFound("unknown_event_subscription".into()));
+    }
+    Ok(sub)
+}
+
+/// `GET /applications/@me/event-subscriptions`
+pub async fn list_event_subscriptions(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if auth.is_bot {
+        return Err(AppError::Forbidden(
+            "bots cannot manage event subscriptions".into(),
+        ));
+    }
+    let app = db::auth::get_application_by_owner(&state.db, &auth.user_id).await?;
+    let subs = db::event_subscriptions::list_subscriptions(&state.db, &app.id).await?;
+    Ok(Json(serde_json::json!({ "data": subs })))
+}
+
+/// `POST /applications/@me/event-subscriptions` -- deliver events of
+/// `event_types` to `url`, from `space_ids` or every space the bot is in.
+/// Deliveries are signed, so the application needs a signing key first.
+pub async fn create_event_subscription(
+    state: State<AppState>,
+    auth: AuthUser,
+    Json(input): Json<CreateEventSubscription>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if auth.is_bot {
+        return Err(AppError::Forbidden(
+            "bots cannot manage event subscriptions".into(),
+        ));
+    }
+    let app = db::auth::get_application_by_owner(&state.db, &auth.user_id).await?;
+    validate_subscription_url(&input.url)?;
+    let event_types = validate_event_types(&input.event_types)?;
+    let space_ids = validate_space_ids(&input.space_ids)?;
+
+    let existing = db::event_subscriptions::list_subscriptions(&state.db, &app.id).await?;
+    AppError::check_limit(
+        "event_subscription_limit_reached",
+        existing.len() as i64,
+        MAX_EVENT_SUBSCRIPTIONS,
+    )?;
+    if db::signing_keys::live_signing_keys(&state.db, &app.id)
+        .await?
+        .is_empty()
+    {
+        return Err(AppError::BadRequest(
+            "rotate in a signing key before subscribing to events".into(),
+        ));
+    }
+
+    let sub = db::event_subscriptions::create_subscription(
+        &state.db,
+        &app.id,
+        &input.url,
+        &event_types,
+        &space_ids,
+    )
+    .await?;
+    Ok(Json(serde_json::json!({ "data": sub })))
+}
+
+/// `PATCH /applications/@me/event-subscriptions/{subscription_id}`
+pub async fn update_event_subscription(
+    state: State<AppState>,
+    Path(subscription_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<UpdateEventSubscription>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let mut sub = owned_subscription(&state, &auth, &subscription_id).await?;
+    if let Some(url) = input.url {
+        validate_subscription_url(&url)?;
+        sub.url = url;
+    }
+    if let Some(event_types) = input.event_types {
+        sub.event_types = validate_event_types(&event_types)?;
+    }
+    if let Some(space_ids) = input.space_ids {
+        sub.space_ids = validate_space_ids(&space_ids)?;
+    }
+    if let Some(enabled) = input.enabled {
+        sub.enabled = enabled;
+    }
+    db::event_subscriptions::update_subscription(&state.db, &sub).await?;
+    Ok(Json(serde_json::json!({ "data": sub })))
+}
+
+/// `DELETE /applications/@me/event-subscriptions/{subscription_id}` --
+/// also drops its delivery log and anything still queued.
+pub async fn delete_event_subscription(
+    state: State<AppState>,
+    Path(subscription_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let sub = owned_subscription(&state, &auth, &subscription_id).await?;
+    db::event_subscriptions::delete_subscription(&state.db, &sub.id).await?;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// `GET /applications/@me/event-subscriptions/{subscription_id}/deliveries`
+/// -- the delivery log, newest first, optionally filtered by `status`.
+pub async fn list_event_deliveries(
+    state: State<AppState>,
+    Path(subscription_id): Path<String>,
+    auth: AuthUser,
+    Query(params): Query<EventDeliveryQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let sub = owned_subscription(&state, &auth, &subscription_id).await?;
+    if let Some(status) = params.status.as_deref() {
+        if !DELIVERY_STATUSES.contains(&status) {
+            return Err(AppError::BadRequest(format!(
+                "status must be one of: {}",
+                DELIVERY_STATUSES.join(", ")
+            )));
+        }
+    }
+
+    let limit = params.limit.unwrap_or(50).clamp(1, 100);
+    let mut rows = db::event_subscriptions::list_deliveries(
+        &state.db,
+        &sub.id,
+        params.status.as_deref(),
+        params.before.as_deref(),
+        limit + 1,
+    )
+    .await?;
+
+    let has_more = rows.len() as i64 > limit;
+    if has_more {
+        rows.truncate(limit as usize);
+    }
+
+    let mut response = serde_json::json!({ "data": rows });
+    if has_more {
+        response["cursor"] = serde_json::json!({
+            "before": rows.last().map(|d| d.id.clone()).unwrap_or_default(),
+            "has_more": has_more
+        });
+    }
+    Ok(Json(response))
+}
+
+/// `POST /applications/@me/event-subscriptions/{subscription_id}/deliveries/{delivery_id}/retry`
+/// -- queue a delivery again with a fresh set of attempts.
+pub async fn retry_event_delivery(
+    state: State<AppState>,
+    Path((subscription_id, delivery_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let sub = owned_subscription(&state, &auth, &subscription_id).await?;
+    let delivery = db::event_subscriptions::get_delivery(&state.db, &delivery_id).await?;
+    if delivery.subscription_id != sub.id {
+        return Err(AppError::NotFound("unknown_event_delivery".into()));
+    }
+    if delivery.status == "pending" {
+        return Err(AppError::ConflictCode(
+            "delivery_pending",
+            "this delivery is still being attempted".into(),
+        ));
+    }
+    db::event_subscriptions::redeliver(&state.db, &delivery.id).await?;
+    let delivery = db::event_subscriptions::get_delivery(&state.db, &delivery.id).await?;
+    Ok(Json(serde_json::json!({ "data": delivery })))
+}
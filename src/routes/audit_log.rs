@@ -12,8 +12,11 @@ use crate::state::AppState;
 #[derive(Deserialize)]
 pub struct ListAuditLogQuery {
     pub action_type: Option<String>,
+    /// The acting user. `actor_id` is accepted as an alias.
+    #[serde(alias = "actor_id")]
     pub user_id: Option<String>,
     pub before: Option<String>,
+    pub after: Option<String>,
     pub limit: Option<i64>,
 }
 
@@ -31,6 +34,7 @@ pub async fn list_audit_log(
         query.action_type.as_deref(),
         query.user_id.as_deref(),
         query.before.as_deref(),
+        query.after.as_deref(),
         limit,
     )
     .await?;
@@ -38,6 +42,52 @@ pub async fn list_audit_log(
     Ok(Json(serde_json::json!({ "data": data })))
 }
 
+/// Record a moderation action and broadcast it. Failures are logged and
+/// swallowed: by the time this runs the action itself has already happened.
+pub async fn record(
+    state: &AppState,
+    space_id: &str,
+    actor_id: &str,
+    action_type: &str,
+    target: Option<(&str, &str)>,
+    reason: Option<&str>,
+    changes: Option<serde_json::Value>,
+) {
+    let changes = changes.map(|c| c.to_string());
+    match db::audit_log::create_entry(
+        &state.db,
+        space_id,
+        actor_id,
+        action_type,
+        target.map(|(id, _)| id),
+        target.map(|(_, kind)| kind),
+        reason,
+        changes.as_deref(),
+    )
+    .await
+    {
+        Ok(entry) => broadcast_entry(state, &entry).await,
+        Err(e) => tracing::warn!("failed to record audit log entry {action_type}: {e:?}"),
+    }
+}
+
+/// Field-level diff of two JSON objects as `{field: {"old": .., "new": ..}}`,
+/// or `None` when nothing changed. Pass `{}` as `before` for a creation or as
+/// `after` for a deletion to record the whole object.
+pub fn diff(before: &serde_json::Value, after: &serde_json::Value) -> Option<serde_json::Value> {
+    let (Some(before), Some(after)) = (before.as_object(), after.as_object()) else {
+        return None;
+    };
+    let mut changes = serde_json::Map::new();
+    for key in before.keys().chain(after.keys()) {
+        let (old, new) = (before.get(key), after.get(key));
+        if old != new && !changes.contains_key(key) {
+            changes.insert(key.clone(), serde_json::json!({ "old": old, "new": new }));
+        }
+    }
+    (!changes.is_empty()).then_some(serde_json::Value::Object(changes))
+}
+
 /// Broadcast an audit_log.create gateway event for the given entry.
 pub async fn broadcast_entry(state: &AppState, entry: &db::audit_log::AuditLogRow) {
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
@@ -52,6 +102,7 @@ pub async fn broadcast_entry(state: &AppState, entry: &db::audit_log::AuditLogRo
             target_user_ids: None,
             event,
             intent: "moderation".to_string(),
+            role_mention_user_ids: None,
         });
     }
 }
@@ -3,8 +3,8 @@ use aes_gcm::{Aes256Gcm, Nonce};
 use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use axum::extract::State;
-use axum::http::HeaderMap;
+use axum::extract::{Path, State};
+use axum::http::{Extensions, HeaderMap};
 use axum::Json;
 use rand::RngCore;
 use serde::Deserialize;
@@ -12,15 +12,17 @@ use sha2::{Digest, Sha256};
 use tokio::time::Instant;
 
 use sqlx::Row;
+use std::sync::Arc;
 
 use crate::db;
 use crate::error::AppError;
 use crate::gateway::events::GatewayBroadcast;
 use crate::middleware::auth::{create_token_hash, generate_token, AuthUser};
+use crate::middleware::ip_allowlist::client_ip;
 use crate::snowflake;
 use crate::state::{
-    AppState, GuestAttemptTracker, LoginFailureTracker, MfaTicket, RegisterAttemptTracker,
-    TotpAttemptTracker,
+    AppState, GuestAttemptTracker, LoginFailureTracker, LoginTicket, MfaTicket,
+    RegisterAttemptTracker, TotpAttemptTracker,
 };
 
 // ---------------------------------------------------------------------------
@@ -53,6 +55,11 @@ const GUEST_MAX_ATTEMPTS: u32 = 10;
 const GUEST_WINDOW_SECS: u64 = 3600; // 1 hour
 const GUEST_TOKEN_LIFETIME_SECS: i64 = 3600; // 1 hour
 
+// ---------------------------------------------------------------------------
+// QR login constants
+// ---------------------------------------------------------------------------
+const LOGIN_TICKET_LIFETIME_SECS: i64 = 120; // 2 minutes
+
 // ---------------------------------------------------------------------------
 // Request structs
 // ---------------------------------------------------------------------------
@@ -62,12 +69,16 @@ pub struct RegisterRequest {
     pub username: String,
     pub password: String,
     pub display_name: Option<String>,
+    pub email: Option<String>,
+    /// Name for the session this creates; defaults to the User-Agent.
+    pub device_name: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
+    pub device_name: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -76,10 +87,38 @@ pub struct ChangePasswordRequest {
     pub new_password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct MfaLoginRequest {
     pub ticket: String,
+    /// A TOTP or backup code. Not needed when `passkey` is given.
+    #[serde(default)]
     pub code: String,
+    /// An assertion answering the `passkey` options from the login response,
+    /// used instead of a code.
+    pub passkey: Option<crate::webauthn::AssertionCredential>,
+    pub device_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateLoginTicketRequest {
+    /// Name for the session the device receives; defaults to the User-Agent.
+    pub device_name: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -92,6 +131,11 @@ pub struct Verify2faRequest {
     pub code: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MfaVerifyRequest {
+    pub code: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Disable2faRequest {
     pub password: String,
@@ -268,18 +312,10 @@ fn hash_ip(ip: &str) -> String {
     format!("{:x}", hasher.finalize())
 }
 
-fn extract_request_ip(headers: &HeaderMap) -> String {
-    headers
-        .get("X-Forwarded-For")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.split(',').next())
-        .map(|s| s.trim().to_string())
-        .or_else(|| {
-            headers
-                .get("X-Real-IP")
-                .and_then(|v| v.to_str().ok())
-                .map(|s| s.to_string())
-        })
+/// Rate-limit key for the client address; see [client_ip].
+fn extract_request_ip(headers: &HeaderMap, extensions: &Extensions) -> String {
+    client_ip(headers, extensions)
+        .map(|ip| ip.to_string())
         .unwrap_or_else(|| "unknown".to_string())
 }
 
@@ -346,7 +382,20 @@ fn generate_backup_codes() -> Vec<String> {
 // Token helpers
 // ---------------------------------------------------------------------------
 
-fn issue_bearer_token() -> (String, String, String) {
+/// Longest device name kept for a session.
+const MAX_DEVICE_NAME_LEN: usize = 128;
+
+/// The name a new session is listed under: the client's `device_name`, else
+/// its User-Agent.
+pub(crate) fn session_device(headers: &HeaderMap, device_name: Option<&str>) -> Option<String> {
+    device_name
+        .or_else(|| headers.get("User-Agent").and_then(|v| v.to_str().ok()))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| name.chars().take(MAX_DEVICE_NAME_LEN).collect())
+}
+
+pub(crate) fn issue_bearer_token() -> (String, String, String) {
     let token = generate_token();
     let token_hash = create_token_hash(&token);
     let expires_at = (chrono::Utc::now() + chrono::Duration::days(30))
@@ -386,6 +435,22 @@ pub(crate) async fn verify_user_password(
     Ok(())
 }
 
+/// Hash a password with Argon2id (OWASP-recommended params: 19 MiB memory,
+/// 3 iterations).
+fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    let argon2 = Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        argon2::Params::new(19456, 3, 1, None)
+            .map_err(|e| AppError::Internal(format!("argon2 params failed: {e}")))?,
+    );
+    Ok(argon2
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| AppError::Internal(format!("password hashing failed: {e}")))?
+        .to_string())
+}
+
 /// Validate a username's character set and format.
 ///
 /// Usernames are public identifiers that login looks users up by, so they must
@@ -420,10 +485,11 @@ pub(crate) fn validate_username(username: &str) -> Result<(), AppError> {
 pub async fn register(
     State(state): State<AppState>,
     headers: HeaderMap,
+    extensions: Extensions,
     Json(input): Json<RegisterRequest>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     // Per-IP rate limit: max 5 registration attempts per 15 minutes
-    let ip = extract_request_ip(&headers);
+    let ip = extract_request_ip(&headers, &extensions);
     check_register_rate_limit(&state, &ip)?;
     record_register_attempt(&state, &ip);
 
@@ -468,6 +534,23 @@ pub async fn register(
         ));
     }
 
+    // Optional email, checked against the instance's blocked domains
+    let email = input
+        .email
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty());
+    if let Some(email) = email {
+        let valid = email.len() <= 254
+            && email
+                .split_once('@')
+                .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'));
+        if !valid {
+            return Err(AppError::BadRequest("invalid email address".to_string()));
+        }
+        crate::blocklist::check_email(&state.db, email).await?;
+    }
+
     // Check for username conflict
     let existing = sqlx::query_scalar::<_, String>(&crate::db::q(
         "SELECT id FROM users WHERE username = ? AND bot = false",
@@ -481,18 +564,7 @@ pub async fn register(
         return Err(AppError::Conflict("registration failed".to_string()));
     }
 
-    // Hash password with Argon2id (OWASP-recommended params: 19 MiB memory, 3 iterations)
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::new(
-        argon2::Algorithm::Argon2id,
-        argon2::Version::V0x13,
-        argon2::Params::new(19456, 3, 1, None)
-            .map_err(|e| AppError::Internal(format!("argon2 params failed: {e}")))?,
-    );
-    let password_hash = argon2
-        .hash_password(input.password.as_bytes(), &salt)
-        .map_err(|e| AppError::Internal(format!("password hashing failed: {e}")))?
-        .to_string();
+    let password_hash = hash_password(&input.password)?;
 
     // Create user
     let id = snowflake::generate();
@@ -503,18 +575,29 @@ pub async fn register(
     let is_admin = admin_count == 0;
 
     sqlx::query(
-        &crate::db::q("INSERT INTO users (id, username, display_name, password_hash, is_admin) VALUES (?, ?, ?, ?, ?)"),
+        &crate::db::q("INSERT INTO users (id, username, display_name, password_hash, is_admin, email) VALUES (?, ?, ?, ?, ?, ?)"),
     )
     .bind(&id)
     .bind(username)
     .bind(display_name)
     .bind(&password_hash)
     .bind(is_admin)
+    .bind(email)
     .execute(&state.db)
     .await
     .map_err(AppError::from)?;
 
     let user = db::users::get_user(&state.db, &id).await?;
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast::admin(
+            "admin.user_create",
+            serde_json::json!(user),
+        ));
+    }
+
+    if let (Some(email), Some(mailer)) = (email, state.mailer.clone()) {
+        send_verification_email(&state, mailer, &id, username, email).await?;
+    }
 
     // Auto-join the default space (first space created on the server)
     let default_space: Option<(String,)> =
@@ -619,6 +702,7 @@ pub async fn register(
                     target_user_ids: None,
                     event,
                     intent: "members".to_string(),
+                    role_mention_user_ids: None,
                 });
             }
         }
@@ -627,15 +711,16 @@ pub async fn register(
     // Generate bearer token with 30-day expiry
     let (token, token_hash, expires_at) = issue_bearer_token();
 
-    sqlx::query(&crate::db::q(
-        "INSERT INTO user_tokens (token_hash, user_id, expires_at) VALUES (?, ?, ?)",
-    ))
-    .bind(&token_hash)
-    .bind(&id)
-    .bind(&expires_at)
-    .execute(&state.db)
-    .await
-    .map_err(AppError::from)?;
+    let ip = client_ip(&headers, &extensions).map(|ip| ip.to_string());
+    db::sessions::create_session(
+        &state.db,
+        &id,
+        &token_hash,
+        &expires_at,
+        session_device(&headers, input.device_name.as_deref()).as_deref(),
+        ip.as_deref(),
+    )
+    .await?;
 
     // Clean up expired tokens and enforce session limit
     cleanup_expired_tokens(&state.db, &id).await;
@@ -655,6 +740,8 @@ pub async fn register(
 
 pub async fn login(
     State(state): State<AppState>,
+    headers: HeaderMap,
+    extensions: Extensions,
     Json(input): Json<LoginRequest>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     // Per-username brute-force protection: max 5 failed attempts per 15 minutes
@@ -726,27 +813,32 @@ pub async fn login(
             },
         );
 
-        return Ok(Json(serde_json::json!({
-            "data": {
-                "mfa_required": true,
-                "ticket": ticket
-            }
-        })));
+        let mut data = serde_json::json!({
+            "mfa_required": true,
+            "ticket": ticket
+        });
+        if let Some(options) =
+            crate::routes::passkeys::second_factor_options(&state, &user_id).await?
+        {
+            data["passkey"] = serde_json::json!({ "publicKey": options });
+        }
+        return Ok(Json(serde_json::json!({ "data": data })));
     }
 
     // No 2FA — issue token directly
     let user = db::users::get_user(&state.db, &user_id).await?;
     let (token, token_hash, expires_at) = issue_bearer_token();
 
-    sqlx::query(&crate::db::q(
-        "INSERT INTO user_tokens (token_hash, user_id, expires_at) VALUES (?, ?, ?)",
-    ))
-    .bind(&token_hash)
-    .bind(&user_id)
-    .bind(&expires_at)
-    .execute(&state.db)
-    .await
-    .map_err(AppError::from)?;
+    let ip = client_ip(&headers, &extensions).map(|ip| ip.to_string());
+    db::sessions::create_session(
+        &state.db,
+        &user_id,
+        &token_hash,
+        &expires_at,
+        session_device(&headers, input.device_name.as_deref()).as_deref(),
+        ip.as_deref(),
+    )
+    .await?;
 
     cleanup_expired_tokens(&state.db, &user_id).await;
     enforce_session_limit(&state.db, &user_id).await;
@@ -768,6 +860,8 @@ pub async fn login(
 
 pub async fn login_mfa(
     State(state): State<AppState>,
+    headers: HeaderMap,
+    extensions: Extensions,
     Json(input): Json<MfaLoginRequest>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     // Resolve the MFA ticket
@@ -795,7 +889,10 @@ pub async fn login_mfa(
     // Determine if this is a TOTP code (6 digits) or a backup code (8 alphanumeric chars)
     let is_totp_code = code.len() == 6 && code.chars().all(|c| c.is_ascii_digit());
 
-    if is_totp_code {
+    if let Some(ref passkey) = input.passkey {
+        // A passkey stands in for the code
+        crate::routes::passkeys::verify_login(&state, passkey, Some(user_id)).await?;
+    } else if is_totp_code {
         // Verify TOTP code
         verify_totp_code(&state, user_id, code).await?;
     } else {
@@ -809,15 +906,23 @@ pub async fn login_mfa(
     let user = db::users::get_user(&state.db, user_id).await?;
     let (token, token_hash, expires_at) = issue_bearer_token();
 
-    sqlx::query(&crate::db::q(
-        "INSERT INTO user_tokens (token_hash, user_id, expires_at) VALUES (?, ?, ?)",
-    ))
-    .bind(&token_hash)
-    .bind(user_id)
-    .bind(&expires_at)
-    .execute(&state.db)
-    .await
-    .map_err(AppError::from)?;
+    let ip = client_ip(&headers, &extensions).map(|ip| ip.to_string());
+    db::sessions::create_session(
+        &state.db,
+        user_id,
+        &token_hash,
+        &expires_at,
+        session_device(&headers, input.device_name.as_deref()).as_deref(),
+        ip.as_deref(),
+    )
+    .await?;
+    // The code was just checked, so the new session starts stepped up.
+    db::sessions::record_mfa_verification(
+        &state.db,
+        user_id,
+        db::sessions::session_id(&token_hash),
+    )
+    .await?;
 
     cleanup_expired_tokens(&state.db, user_id).await;
     enforce_session_limit(&state.db, user_id).await;
@@ -845,6 +950,139 @@ pub async fn login_mfa(
     Ok(Json(serde_json::json!({ "data": data })))
 }
 
+// =========================================================================
+// QR login (device handoff)
+// =========================================================================
+
+/// POST /auth/login-tickets
+///
+/// Starts a QR login for a device that can't easily type a password. The
+/// device shows `ticket` as a QR code, keeps `secret` to itself, and sends
+/// both in a gateway LOGIN_TICKET to wait for a signed-in user to approve it.
+pub async fn create_login_ticket(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    extensions: Extensions,
+    Json(input): Json<CreateLoginTicketRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let now = chrono::Utc::now();
+    state.login_tickets.retain(|_, t| t.expires_at > now);
+
+    let ticket = generate_token();
+    let secret = generate_token();
+    let expires_at = now + chrono::Duration::seconds(LOGIN_TICKET_LIFETIME_SECS);
+    let (approved_by, _) = tokio::sync::watch::channel(None);
+    state.login_tickets.insert(
+        create_token_hash(&ticket),
+        LoginTicket {
+            secret_hash: create_token_hash(&secret),
+            device_name: session_device(&headers, input.device_name.as_deref()),
+            ip: client_ip(&headers, &extensions).map(|ip| ip.to_string()),
+            expires_at,
+            approved_by,
+        },
+    );
+
+    Ok(Json(serde_json::json!({
+        "data": {
+            "ticket": ticket,
+            "secret": secret,
+            "expires_at": expires_at.format("%Y-%m-%dT%H:%M:%S+00:00").to_string()
+        }
+    })))
+}
+
+/// Looks up a login ticket that is still waiting for approval.
+fn pending_login_ticket<'a>(
+    state: &'a AppState,
+    ticket: &str,
+) -> Result<dashmap::mapref::one::Ref<'a, String, LoginTicket>, AppError> {
+    let entry = state
+        .login_tickets
+        .get(&create_token_hash(ticket))
+        .filter(|t| t.expires_at > chrono::Utc::now())
+        .ok_or_else(|| AppError::NotFound("login ticket not found or expired".to_string()))?;
+    if entry.approved_by.borrow().is_some() {
+        return Err(AppError::ConflictCode(
+            "login_ticket_used",
+            "login ticket was already approved".to_string(),
+        ));
+    }
+    Ok(entry)
+}
+
+fn require_login_approver(auth: &AuthUser) -> Result<(), AppError> {
+    if auth.is_bot || auth.is_guest {
+        return Err(AppError::Forbidden(
+            "only users can approve logins".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// GET /auth/login-tickets/{ticket}
+///
+/// What a scanned ticket would sign in, so the approving user can check the
+/// device before confirming.
+pub async fn get_login_ticket(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(ticket): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_login_approver(&auth)?;
+    let entry = pending_login_ticket(&state, &ticket)?;
+    Ok(Json(serde_json::json!({
+        "data": {
+            "device_name": entry.device_name,
+            "ip": entry.ip,
+            "expires_at": entry.expires_at.format("%Y-%m-%dT%H:%M:%S+00:00").to_string()
+        }
+    })))
+}
+
+/// POST /auth/login-tickets/{ticket}/approve
+///
+/// Signs the waiting device in as the caller. The session is created when
+/// the device picks it up over the gateway.
+pub async fn approve_login_ticket(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(ticket): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_login_approver(&auth)?;
+    let entry = pending_login_ticket(&state, &ticket)?;
+    entry.approved_by.send_replace(Some(auth.user_id));
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// Creates the session for an approved login ticket and returns the
+/// `{ user, token }` the device signs in with.
+pub(crate) async fn redeem_login_ticket(
+    state: &AppState,
+    user_id: &str,
+    ticket: &LoginTicket,
+) -> Result<serde_json::Value, AppError> {
+    let user = db::users::get_user(&state.db, user_id).await?;
+    let (token, token_hash, expires_at) = issue_bearer_token();
+    db::sessions::create_session(
+        &state.db,
+        user_id,
+        &token_hash,
+        &expires_at,
+        ticket.device_name.as_deref(),
+        ticket.ip.as_deref(),
+    )
+    .await?;
+
+    cleanup_expired_tokens(&state.db, user_id).await;
+    enforce_session_limit(&state.db, user_id).await;
+
+    Ok(serde_json::json!({
+        "user": user,
+        "token": token
+    }))
+}
+
 // =========================================================================
 // Logout
 // =========================================================================
@@ -883,12 +1121,82 @@ pub async fn revoke_all_sessions(
     State(state): State<AppState>,
     auth: AuthUser,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    revoke_user_sessions(&state, &auth.user_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "data": { "ok": true }
+    })))
+}
+
+/// Sign a user out everywhere: delete every login token and close the
+/// gateway connections opened with them. Returns how many were revoked.
+pub(super) async fn revoke_user_sessions(
+    state: &AppState,
+    user_id: &str,
+) -> Result<usize, AppError> {
+    let sessions = db::sessions::list_sessions(&state.db, user_id, "").await?;
     sqlx::query(&crate::db::q("DELETE FROM user_tokens WHERE user_id = ?"))
-        .bind(&auth.user_id)
+        .bind(user_id)
         .execute(&state.db)
         .await
         .map_err(AppError::from)?;
+    let count = sessions.len();
+    disconnect_sessions(state, user_id, sessions.into_iter().map(|s| s.id).collect()).await;
+    Ok(count)
+}
+
+// =========================================================================
+// Sessions
+// =========================================================================
+
+/// Close gateway connections that identified with any of the given login
+/// sessions. They get [close_code::AUTH_FAILED] and must sign in again.
+///
+/// [close_code::AUTH_FAILED]: crate::gateway::events::close_code::AUTH_FAILED
+async fn disconnect_sessions(state: &AppState, user_id: &str, session_ids: Vec<String>) {
+    if session_ids.is_empty() {
+        return;
+    }
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: None,
+            target_user_ids: Some(vec![user_id.to_string()]),
+            event: serde_json::json!({
+                "op": 0,
+                "type": "auth_session.revoke",
+                "data": { "session_ids": session_ids }
+            }),
+            intent: "user_settings".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
+
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let raw_token = headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .unwrap_or("");
+    let sessions =
+        db::sessions::list_sessions(&state.db, &auth.user_id, &create_token_hash(raw_token))
+            .await?;
+    Ok(Json(serde_json::json!({ "data": sessions })))
+}
 
+pub async fn delete_session(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(session_id): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if !db::sessions::delete_session(&state.db, &auth.user_id, &session_id).await? {
+        return Err(AppError::NotFound("session not found".into()));
+    }
+    disconnect_sessions(&state, &auth.user_id, vec![session_id]).await;
     Ok(Json(serde_json::json!({
         "data": { "ok": true }
     })))
@@ -914,18 +1222,7 @@ pub async fn change_password(
     // Verify old password
     verify_user_password(&state, &auth.user_id, &input.old_password).await?;
 
-    // Hash the new password with Argon2id
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::new(
-        argon2::Algorithm::Argon2id,
-        argon2::Version::V0x13,
-        argon2::Params::new(19456, 3, 1, None)
-            .map_err(|e| AppError::Internal(format!("argon2 params failed: {e}")))?,
-    );
-    let password_hash = argon2
-        .hash_password(input.new_password.as_bytes(), &salt)
-        .map_err(|e| AppError::Internal(format!("password hashing failed: {e}")))?
-        .to_string();
+    let password_hash = hash_password(&input.new_password)?;
 
     // Update password and clear force_password_reset flag
     let now_fn = crate::db::now_sql(state.db_is_postgres);
@@ -960,6 +1257,195 @@ pub async fn change_password(
     })))
 }
 
+// =========================================================================
+// Password reset (requires SMTP)
+// =========================================================================
+
+fn require_mailer(state: &AppState) -> Result<Arc<crate::email::Mailer>, AppError> {
+    state
+        .mailer
+        .clone()
+        .ok_or_else(|| AppError::BadRequest("email is not configured on this server".to_string()))
+}
+
+/// Email a reset code to the account with this address. Always succeeds, so
+/// it can't be used to find out which addresses have accounts.
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(input): Json<PasswordResetRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let mailer = require_mailer(&state)?;
+    let email = input.email.trim();
+
+    let row = sqlx::query(&crate::db::q(
+        "SELECT id, username, email FROM users \
+         WHERE LOWER(email) = LOWER(?) AND bot = false AND disabled = false AND password_hash IS NOT NULL",
+    ))
+    .bind(email)
+    .fetch_optional(&state.db)
+    .await?;
+
+    if let Some(row) = row {
+        let user_id: String = row.get("id");
+        let kind = db::email_tokens::KIND_PASSWORD_RESET;
+        if !db::email_tokens::issued_within(
+            &state.db,
+            &user_id,
+            kind,
+            crate::email::RESEND_INTERVAL_SECS,
+        )
+        .await?
+        {
+            let address: String = row.get("email");
+            let token = db::email_tokens::create_token(
+                &state.db,
+                &user_id,
+                kind,
+                &address,
+                crate::email::PASSWORD_RESET_TTL_SECS,
+            )
+            .await?;
+            let username: String = row.get("username");
+            crate::email::send_in_background(
+                mailer,
+                address,
+                "Reset your Accord password",
+                crate::email::password_reset_body(&username, &token),
+            );
+        }
+    }
+
+    Ok(Json(serde_json::json!({ "data": { "ok": true } })))
+}
+
+/// Set a new password with a reset code. Signs out every session, since
+/// whoever asked for the reset may not trust them.
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(input): Json<PasswordResetConfirmRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if input.new_password.len() < 8 || input.new_password.len() > 128 {
+        return Err(AppError::BadRequest(
+            "password must be between 8 and 128 characters".to_string(),
+        ));
+    }
+    let (user_id, email) = db::email_tokens::consume_token(
+        &state.db,
+        input.token.trim(),
+        db::email_tokens::KIND_PASSWORD_RESET,
+    )
+    .await?
+    .ok_or_else(|| AppError::BadRequest("invalid or expired reset code".to_string()))?;
+
+    let password_hash = hash_password(&input.new_password)?;
+    let now_fn = crate::db::now_sql(state.db_is_postgres);
+    sqlx::query(&crate::db::q(&format!(
+        "UPDATE users SET password_hash = ?, force_password_reset = FALSE, updated_at = {now_fn} WHERE id = ?",
+    )))
+    .bind(&password_hash)
+    .bind(&user_id)
+    .execute(&state.db)
+    .await?;
+
+    sqlx::query(&crate::db::q("DELETE FROM user_tokens WHERE user_id = ?"))
+        .bind(&user_id)
+        .execute(&state.db)
+        .await?;
+
+    // The code arrived at the address, which proves the user reads it.
+    db::email_tokens::mark_email_verified(&state.db, &user_id, &email).await?;
+
+    Ok(Json(serde_json::json!({ "data": { "ok": true } })))
+}
+
+// =========================================================================
+// Email verification (requires SMTP)
+// =========================================================================
+
+/// Email a verification code to `user`'s address.
+async fn send_verification_email(
+    state: &AppState,
+    mailer: Arc<crate::email::Mailer>,
+    user_id: &str,
+    username: &str,
+    email: &str,
+) -> Result<(), AppError> {
+    let token = db::email_tokens::create_token(
+        &state.db,
+        user_id,
+        db::email_tokens::KIND_VERIFY_EMAIL,
+        email,
+        crate::email::EMAIL_VERIFY_TTL_SECS,
+    )
+    .await?;
+    crate::email::send_in_background(
+        mailer,
+        email.to_string(),
+        "Verify your email address",
+        crate::email::verification_body(username, &token),
+    );
+    Ok(())
+}
+
+/// Send (or resend) a verification code to the current user's email.
+pub async fn resend_email_verification(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let mailer = require_mailer(&state)?;
+    let row = sqlx::query(&crate::db::q(
+        "SELECT username, email, email_verified FROM users WHERE id = ?",
+    ))
+    .bind(&auth.user_id)
+    .fetch_one(&state.db)
+    .await?;
+    let email: Option<String> = row.get("email");
+    let email =
+        email.ok_or_else(|| AppError::BadRequest("account has no email address".to_string()))?;
+    if crate::db::get_bool(&row, "email_verified") {
+        return Err(AppError::BadRequest(
+            "email is already verified".to_string(),
+        ));
+    }
+    if db::email_tokens::issued_within(
+        &state.db,
+        &auth.user_id,
+        db::email_tokens::KIND_VERIFY_EMAIL,
+        crate::email::RESEND_INTERVAL_SECS,
+    )
+    .await?
+    {
+        return Err(AppError::RateLimited {
+            retry_after: crate::email::RESEND_INTERVAL_SECS as u64,
+        });
+    }
+
+    let username: String = row.get("username");
+    send_verification_email(&state, mailer, &auth.user_id, &username, &email).await?;
+    Ok(Json(serde_json::json!({ "data": { "ok": true } })))
+}
+
+/// Redeem a verification code. Needs no session, so the code can be entered
+/// on any device.
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(input): Json<VerifyEmailRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let (user_id, email) = db::email_tokens::consume_token(
+        &state.db,
+        input.token.trim(),
+        db::email_tokens::KIND_VERIFY_EMAIL,
+    )
+    .await?
+    .ok_or_else(|| AppError::BadRequest("invalid or expired verification code".to_string()))?;
+    if !db::email_tokens::mark_email_verified(&state.db, &user_id, &email).await? {
+        return Err(AppError::BadRequest(
+            "the account's email address has changed since this code was sent".to_string(),
+        ));
+    }
+    Ok(Json(serde_json::json!({ "data": { "ok": true } })))
+}
+
 // =========================================================================
 // Two-Factor Authentication — Enable (step 1)
 // =========================================================================
@@ -1091,6 +1577,9 @@ pub async fn verify_2fa(
     .execute(&state.db)
     .await
     .map_err(AppError::from)?;
+    if let Some(ref session_id) = auth.login_session_id {
+        db::sessions::record_mfa_verification(&state.db, &auth.user_id, session_id).await?;
+    }
 
     // Generate and store hashed backup codes
     let codes = generate_backup_codes();
@@ -1120,6 +1609,55 @@ pub async fn verify_2fa(
     })))
 }
 
+// =========================================================================
+// MFA step-up — re-verify a code for actions that need a recent one
+// =========================================================================
+
+pub async fn verify_mfa(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(input): Json<MfaVerifyRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let enabled = {
+        let row = sqlx::query(&crate::db::q("SELECT totp_enabled FROM users WHERE id = ?"))
+            .bind(&auth.user_id)
+            .fetch_one(&state.db)
+            .await
+            .map_err(AppError::from)?;
+        crate::db::get_bool(&row, "totp_enabled")
+    };
+    if !enabled {
+        return Err(AppError::BadRequest("2FA is not enabled".to_string()));
+    }
+    // Step-up belongs to a login session; app tokens have none to mark.
+    let Some(session_id) = auth.login_session_id.as_deref() else {
+        return Err(AppError::BadRequest(
+            "MFA verification needs a signed-in session".to_string(),
+        ));
+    };
+
+    check_totp_rate_limit(&state, &auth.user_id)?;
+
+    let code = input.code.trim();
+    let is_totp_code = code.len() == 6 && code.chars().all(|c| c.is_ascii_digit());
+    if is_totp_code {
+        verify_totp_code(&state, &auth.user_id, code).await?;
+    } else {
+        verify_and_consume_backup_code(&state, &auth.user_id, code).await?;
+    }
+
+    clear_totp_failures(&state, &auth.user_id);
+    let verified_at =
+        db::sessions::record_mfa_verification(&state.db, &auth.user_id, session_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "data": {
+            "verified_at": verified_at,
+            "valid_for": crate::middleware::permissions::MFA_RECENT_SECS
+        }
+    })))
+}
+
 // =========================================================================
 // Two-Factor Authentication — Disable
 // =========================================================================
@@ -1141,6 +1679,7 @@ pub async fn disable_2fa(
     .execute(&state.db)
     .await
     .map_err(AppError::from)?;
+    db::sessions::clear_mfa_verifications(&state.db, &auth.user_id).await?;
 
     sqlx::query(&crate::db::q("DELETE FROM backup_codes WHERE user_id = ?"))
         .bind(&auth.user_id)
@@ -1289,7 +1828,7 @@ async fn verify_and_consume_backup_code(
 
 /// Enforce maximum concurrent session limit per user.
 /// Deletes the oldest tokens when the user exceeds MAX_SESSIONS_PER_USER active tokens.
-async fn enforce_session_limit(pool: &sqlx::AnyPool, user_id: &str) {
+pub(crate) async fn enforce_session_limit(pool: &crate::db::DbPool, user_id: &str) {
     let count: i64 = sqlx::query_scalar(&crate::db::q(
         "SELECT COUNT(*) FROM user_tokens WHERE user_id = ?",
     ))
@@ -1314,7 +1853,7 @@ async fn enforce_session_limit(pool: &sqlx::AnyPool, user_id: &str) {
 }
 
 /// Delete expired tokens for a user (background cleanup).
-async fn cleanup_expired_tokens(pool: &sqlx::AnyPool, user_id: &str) {
+pub(crate) async fn cleanup_expired_tokens(pool: &crate::db::DbPool, user_id: &str) {
     let now = chrono::Utc::now()
         .format("%Y-%m-%dT%H:%M:%S+00:00")
         .to_string();
@@ -1334,9 +1873,10 @@ async fn cleanup_expired_tokens(pool: &sqlx::AnyPool, user_id: &str) {
 pub async fn guest(
     State(state): State<AppState>,
     headers: HeaderMap,
+    extensions: Extensions,
 ) -> Result<Json<serde_json::Value>, AppError> {
     // Per-IP rate limit: max 10 guest tokens per hour
-    let ip = extract_request_ip(&headers);
+    let ip = extract_request_ip(&headers, &extensions);
     check_guest_rate_limit(&state, &ip)?;
     record_guest_attempt(&state, &ip);
 
@@ -1385,7 +1925,7 @@ pub async fn guest(
 /// Find the space that guest tokens should be scoped to.
 /// Prefers the first public space; falls back to the first space on the server.
 async fn find_guest_space(
-    pool: &sqlx::AnyPool,
+    pool: &crate::db::DbPool,
 ) -> Result<crate::models::space::SpaceRow, AppError> {
     // Try public spaces with guest access enabled first
     let public = sqlx::query_as::<_, (String,)>(&crate::db::q(
@@ -0,0 +1,193 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+
+use crate::automod::{validate_rule, HIT_RETENTION_DAYS, MAX_RULES_PER_SPACE};
+use crate::db;
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::require_permission;
+use crate::models::automod::{
+    AutomodAction, AutomodRuleStats, AutomodStatsQuery, CreateAutomodRule, UpdateAutomodRule,
+};
+use crate::state::AppState;
+
+/// Recent samples returned per rule in the stats.
+const STATS_SAMPLES_PER_RULE: i64 = 5;
+
+fn validate_name(name: &str) -> Result<(), AppError> {
+    if name.trim().is_empty() || name.len() > 100 {
+        return Err(AppError::BadRequest(
+            "name must be between 1 and 100 characters".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Flag actions must report into a channel of the rule's own space.
+async fn validate_log_channels(
+    state: &AppState,
+    space_id: &str,
+    actions: &[AutomodAction],
+) -> Result<(), AppError> {
+    for action in actions {
+        if let AutomodAction::Flag { channel_id } = action {
+            let channel = db::channels::get_channel_row(&state.db, channel_id)
+                .await
+                .map_err(|_| AppError::BadRequest("unknown log channel".into()))?;
+            if channel.space_id.as_deref() != Some(space_id) {
+                return Err(AppError::BadRequest(
+                    "log channel must belong to this space".into(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn list_rules(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let rules = db::automod::list_rules(&state.db, &space_id).await?;
+    Ok(Json(serde_json::json!({ "data": rules })))
+}
+
+pub async fn create_rule(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<CreateAutomodRule>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    validate_name(&input.name)?;
+    validate_rule(&input.trigger, &input.actions)?;
+    validate_log_channels(&state, &space_id, &input.actions).await?;
+    let existing = db::automod::list_rules(&state.db, &space_id).await?;
+    if existing.len() >= MAX_RULES_PER_SPACE {
+        return Err(AppError::BadRequest(format!(
+            "a space can have at most {MAX_RULES_PER_SPACE} automod rules"
+        )));
+    }
+
+    let rule = db::automod::create_rule(&state.db, &space_id, &auth.user_id, &input).await?;
+    Ok(Json(serde_json::json!({ "data": rule })))
+}
+
+pub async fn get_rule(
+    state: State<AppState>,
+    Path((space_id, rule_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let rule = db::automod::get_rule(&state.db, &space_id, &rule_id).await?;
+    Ok(Json(serde_json::json!({ "data": rule })))
+}
+
+pub async fn update_rule(
+    state: State<AppState>,
+    Path((space_id, rule_id)): Path<(String, String)>,
+    auth: AuthUser,
+    Json(input): Json<UpdateAutomodRule>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let existing = db::automod::get_rule(&state.db, &space_id, &rule_id).await?;
+    if let Some(ref name) = input.name {
+        validate_name(name)?;
+    }
+    let trigger = input.trigger.as_ref().unwrap_or(&existing.trigger);
+    let actions = input.actions.as_deref().unwrap_or(&existing.actions);
+    validate_rule(trigger, actions)?;
+    if let Some(ref actions) = input.actions {
+        validate_log_channels(&state, &space_id, actions).await?;
+    }
+
+    let rule = db::automod::update_rule(&state.db, &space_id, &rule_id, &input).await?;
+    Ok(Json(serde_json::json!({ "data": rule })))
+}
+
+pub async fn delete_rule(
+    state: State<AppState>,
+    Path((space_id, rule_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    db::automod::delete_rule(&state.db, &space_id, &rule_id).await?;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// GET /spaces/{space_id}/automod/stats?days=
+/// Per-rule hit counts, false-positive overrides and recent samples, most
+/// triggered first.
+pub async fn get_stats(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    Query(query): Query<AutomodStatsQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let days = query.days.unwrap_or(30);
+    if !(1..=HIT_RETENTION_DAYS).contains(&days) {
+        return Err(AppError::BadRequest(format!(
+            "days must be between 1 and {HIT_RETENTION_DAYS}"
+        )));
+    }
+    let since = (chrono::Utc::now() - chrono::Duration::days(days))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+
+    let counts = db::automod::hit_counts(&state.db, &space_id, &since).await?;
+    let mut stats = Vec::new();
+    for rule in db::automod::list_rules(&state.db, &space_id).await? {
+        let (hits, overrides) = counts
+            .iter()
+            .find(|(id, _, _)| *id == rule.id)
+            .map(|(_, h, o)| (*h, *o))
+            .unwrap_or((0, 0));
+        let samples = if hits > 0 {
+            db::automod::recent_samples(&state.db, &rule.id, &since, STATS_SAMPLES_PER_RULE).await?
+        } else {
+            Vec::new()
+        };
+        stats.push(AutomodRuleStats {
+            trigger_type: serde_json::to_value(&rule.trigger).unwrap_or_default()["type"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            rule_id: rule.id,
+            name: rule.name,
+            enabled: rule.enabled,
+            hits,
+            overrides,
+            override_rate: if hits > 0 {
+                overrides as f64 / hits as f64
+            } else {
+                0.0
+            },
+            samples,
+        });
+    }
+    stats.sort_by_key(|s| std::cmp::Reverse(s.hits));
+
+    let total_hits: i64 = stats.iter().map(|s| s.hits).sum();
+    Ok(Json(serde_json::json!({
+        "data": {
+            "since": since,
+            "total_hits": total_hits,
+            "rules": stats,
+        }
+    })))
+}
+
+/// POST /spaces/{space_id}/automod/hits/{hit_id}/override
+/// Mark a hit as a false positive.
+pub async fn override_hit(
+    state: State<AppState>,
+    Path((space_id, hit_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_messages").await?;
+    db::automod::override_hit(&state.db, &space_id, &hit_id, &auth.user_id).await?;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
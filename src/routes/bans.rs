@@ -4,15 +4,56 @@ use serde::Deserialize;
 
 use crate::db;
 use crate::error::AppError;
+use crate::middleware::audit::AuditReason;
 use crate::middleware::auth::AuthUser;
-use crate::middleware::permissions::{require_hierarchy, require_permission};
+use crate::middleware::permissions::{
+    require_hierarchy, require_permission, require_recent_mfa, resolve_member_permissions,
+};
+use crate::models::permission::Permissions;
 use crate::state::AppState;
 
+/// Most entries accepted by a single ban list import.
+pub const MAX_BAN_IMPORT: usize = 1000;
+
 #[derive(Deserialize)]
 pub struct CreateBanBody {
     pub reason: Option<String>,
 }
 
+#[derive(Deserialize)]
+pub struct ImportBanEntry {
+    pub user_id: String,
+    pub reason: Option<String>,
+}
+
+/// Accepts the `bans` array from [export_bans] as-is; other fields are ignored.
+#[derive(Deserialize)]
+pub struct ImportBansBody {
+    pub bans: Vec<ImportBanEntry>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CreateBanSyncGroupBody {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct JoinBanSyncGroupBody {
+    pub group_id: String,
+}
+
+fn ban_to_json(b: &db::bans::BanRow) -> serde_json::Value {
+    serde_json::json!({
+        "user_id": b.user_id,
+        "space_id": b.space_id,
+        "reason": b.reason,
+        "banned_by": b.banned_by,
+        "created_at": b.created_at
+    })
+}
+
 pub async fn list_bans(
     state: State<AppState>,
     Path(space_id): Path<String>,
@@ -20,18 +61,7 @@ pub async fn list_bans(
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "ban_members").await?;
     let bans = db::bans::list_bans(&state.db, &space_id).await?;
-    let data: Vec<serde_json::Value> = bans
-        .iter()
-        .map(|b| {
-            serde_json::json!({
-                "user_id": b.user_id,
-                "space_id": b.space_id,
-                "reason": b.reason,
-                "banned_by": b.banned_by,
-                "created_at": b.created_at
-            })
-        })
-        .collect();
+    let data: Vec<serde_json::Value> = bans.iter().map(ban_to_json).collect();
     Ok(Json(serde_json::json!({ "data": data })))
 }
 
@@ -42,26 +72,23 @@ pub async fn get_ban(
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "ban_members").await?;
     let ban = db::bans::get_ban(&state.db, &space_id, &user_id).await?;
-    Ok(Json(serde_json::json!({
-        "data": {
-            "user_id": ban.user_id,
-            "space_id": ban.space_id,
-            "reason": ban.reason,
-            "banned_by": ban.banned_by,
-            "created_at": ban.created_at
-        }
-    })))
+    Ok(Json(serde_json::json!({ "data": ban_to_json(&ban) })))
 }
 
 pub async fn create_ban(
     state: State<AppState>,
     Path((space_id, user_id)): Path<(String, String)>,
     auth: AuthUser,
+    audit_reason: AuditReason,
     body: Option<Json<CreateBanBody>>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "ban_members").await?;
     require_hierarchy(&state.db, &space_id, &auth, &user_id).await?;
-    let reason = body.and_then(|b| b.reason.clone());
+    require_recent_mfa(&state.db, &space_id, &auth).await?;
+    // A reason in the body wins; otherwise fall back to the audit header.
+    let reason = body
+        .and_then(|b| b.reason.clone())
+        .or_else(|| audit_reason.0.clone());
     let ban = db::bans::create_ban(
         &state.db,
         &space_id,
@@ -71,24 +98,335 @@ pub async fn create_ban(
         state.db_is_postgres,
     )
     .await?;
-    Ok(Json(serde_json::json!({
-        "data": {
-            "user_id": ban.user_id,
-            "space_id": ban.space_id,
-            "reason": ban.reason,
-            "banned_by": ban.banned_by,
-            "created_at": ban.created_at
-        }
-    })))
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "member_ban_add",
+        Some((&user_id, "user")),
+        reason.as_deref(),
+        None,
+    )
+    .await;
+    propagate_ban(
+        &state,
+        &space_id,
+        &user_id,
+        reason.as_deref(),
+        &auth.user_id,
+    )
+    .await;
+    Ok(Json(serde_json::json!({ "data": ban_to_json(&ban) })))
 }
 
 pub async fn delete_ban(
     state: State<AppState>,
     Path((space_id, user_id)): Path<(String, String)>,
     auth: AuthUser,
+    audit_reason: AuditReason,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "ban_members").await?;
     require_hierarchy(&state.db, &space_id, &auth, &user_id).await?;
     db::bans::delete_ban(&state.db, &space_id, &user_id).await?;
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "member_ban_remove",
+        Some((&user_id, "user")),
+        audit_reason.as_deref(),
+        None,
+    )
+    .await;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// Copy a new ban in [source_space_id] to the other spaces in its sync group.
+///
+/// Spaces where the user is already banned, or where they hold `ban_members`
+/// themselves, are skipped. Each copied ban gets an audit entry in the
+/// receiving space naming the group and the originating space. Removing a
+/// ban is never propagated.
+async fn propagate_ban(
+    state: &AppState,
+    source_space_id: &str,
+    user_id: &str,
+    reason: Option<&str>,
+    actor_id: &str,
+) {
+    let result: Result<(), AppError> = async {
+        let Some(group_id) =
+            db::ban_sync::get_group_id_for_space(&state.db, source_space_id).await?
+        else {
+            return Ok(());
+        };
+        for space_id in db::ban_sync::list_group_space_ids(&state.db, &group_id).await? {
+            if space_id == source_space_id
+                || db::bans::get_ban(&state.db, &space_id, user_id)
+                    .await
+                    .is_ok()
+            {
+                continue;
+            }
+            let is_moderator = resolve_member_permissions(&state.db, &space_id, user_id)
+                .await
+                .map(|perms| perms.has(Permissions::BAN_MEMBERS))
+                .unwrap_or(false);
+            if is_moderator {
+                continue;
+            }
+            db::bans::create_ban(
+                &state.db,
+                &space_id,
+                user_id,
+                reason,
+                actor_id,
+                state.db_is_postgres,
+            )
+            .await?;
+            super::audit_log::record(
+                state,
+                &space_id,
+                actor_id,
+                "member_ban_add",
+                Some((user_id, "user")),
+                reason,
+                Some(serde_json::json!({
+                    "synced_from": { "group_id": group_id, "space_id": source_space_id }
+                })),
+            )
+            .await;
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("ban sync from space {source_space_id} failed: {e:?}");
+    }
+}
+
+pub async fn export_bans(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "ban_members").await?;
+    let bans = db::bans::list_bans(&state.db, &space_id).await?;
+    let entries: Vec<serde_json::Value> = bans
+        .iter()
+        .map(|b| {
+            serde_json::json!({
+                "user_id": b.user_id,
+                "reason": b.reason,
+                "created_at": b.created_at
+            })
+        })
+        .collect();
+    Ok(Json(serde_json::json!({
+        "data": { "space_id": space_id, "bans": entries }
+    })))
+}
+
+/// Ban every user in an exported list. With `dry_run` nothing is written and
+/// the response previews which entries would be added or skipped.
+pub async fn import_bans(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    audit_reason: AuditReason,
+    Json(input): Json<ImportBansBody>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "ban_members").await?;
+    if input.bans.len() > MAX_BAN_IMPORT {
+        return Err(AppError::BadRequest(format!(
+            "at most {MAX_BAN_IMPORT} bans can be imported at once"
+        )));
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    let mut added: Vec<String> = Vec::new();
+    let mut skipped: Vec<serde_json::Value> = Vec::new();
+    for entry in &input.bans {
+        if !seen.insert(entry.user_id.clone()) {
+            continue;
+        }
+        let skip_reason = if entry.user_id == auth.user_id {
+            Some("self")
+        } else if db::users::get_user(&state.db, &entry.user_id)
+            .await
+            .is_err()
+        {
+            Some("unknown_user")
+        } else if db::bans::get_ban(&state.db, &space_id, &entry.user_id)
+            .await
+            .is_ok()
+        {
+            Some("already_banned")
+        } else if require_hierarchy(&state.db, &space_id, &auth, &entry.user_id)
+            .await
+            .is_err()
+        {
+            Some("hierarchy")
+        } else {
+            None
+        };
+        if let Some(why) = skip_reason {
+            skipped.push(serde_json::json!({ "user_id": entry.user_id, "reason": why }));
+            continue;
+        }
+
+        if !input.dry_run {
+            let reason = entry.reason.as_deref().or(audit_reason.as_deref());
+            db::bans::create_ban(
+                &state.db,
+                &space_id,
+                &entry.user_id,
+                reason,
+                &auth.user_id,
+                state.db_is_postgres,
+            )
+            .await?;
+        }
+        added.push(entry.user_id.clone());
+    }
+
+    if !input.dry_run && !added.is_empty() {
+        super::audit_log::record(
+            &state,
+            &space_id,
+            &auth.user_id,
+            "member_ban_import",
+            None,
+            audit_reason.as_deref(),
+            Some(serde_json::json!({ "added": added })),
+        )
+        .await;
+    }
+
+    Ok(Json(serde_json::json!({
+        "data": { "dry_run": input.dry_run, "added": added, "skipped": skipped }
+    })))
+}
+
+async fn ban_sync_group_json(
+    state: &AppState,
+    group_id: &str,
+) -> Result<serde_json::Value, AppError> {
+    let group = db::ban_sync::get_group(&state.db, group_id).await?;
+    let space_ids = db::ban_sync::list_group_space_ids(&state.db, group_id).await?;
+    Ok(serde_json::json!({
+        "id": group.id,
+        "name": group.name,
+        "owner_id": group.owner_id,
+        "created_at": group.created_at,
+        "space_ids": space_ids
+    }))
+}
+
+pub async fn get_ban_sync(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "ban_members").await?;
+    let data = match db::ban_sync::get_group_id_for_space(&state.db, &space_id).await? {
+        Some(group_id) => ban_sync_group_json(&state, &group_id).await?,
+        None => serde_json::Value::Null,
+    };
+    Ok(Json(serde_json::json!({ "data": data })))
+}
+
+/// Start a new sync group with this space as its only member.
+pub async fn create_ban_sync_group(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<CreateBanSyncGroupBody>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let name = input.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(AppError::BadRequest(
+            "name must be between 1 and 100 characters".into(),
+        ));
+    }
+    let group = db::ban_sync::create_group(&state.db, name, &auth.user_id, &space_id).await?;
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "ban_sync_join",
+        Some((&group.id, "ban_sync_group")),
+        None,
+        None,
+    )
+    .await;
+    let data = ban_sync_group_json(&state, &group.id).await?;
+    Ok(Json(serde_json::json!({ "data": data })))
+}
+
+/// Opt this space into an existing group. The caller must manage both this
+/// space and at least one space already in the group, so a stranger can't
+/// attach a space and start pushing bans into others.
+pub async fn join_ban_sync_group(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<JoinBanSyncGroupBody>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    db::ban_sync::get_group(&state.db, &input.group_id).await?;
+    let mut manages_member = false;
+    for member_space_id in db::ban_sync::list_group_space_ids(&state.db, &input.group_id).await? {
+        if require_permission(&state.db, &member_space_id, &auth, "manage_space")
+            .await
+            .is_ok()
+        {
+            manages_member = true;
+            break;
+        }
+    }
+    if !manages_member {
+        return Err(AppError::Forbidden(
+            "you must manage a space already in this ban sync group".into(),
+        ));
+    }
+    db::ban_sync::add_space(&state.db, &input.group_id, &space_id, &auth.user_id).await?;
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "ban_sync_join",
+        Some((&input.group_id, "ban_sync_group")),
+        None,
+        None,
+    )
+    .await;
+    let data = ban_sync_group_json(&state, &input.group_id).await?;
+    Ok(Json(serde_json::json!({ "data": data })))
+}
+
+pub async fn leave_ban_sync_group(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let Some(group_id) = db::ban_sync::get_group_id_for_space(&state.db, &space_id).await? else {
+        return Err(AppError::NotFound(
+            "space is not in a ban sync group".into(),
+        ));
+    };
+    db::ban_sync::remove_space(&state.db, &space_id).await?;
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "ban_sync_leave",
+        Some((&group_id, "ban_sync_group")),
+        None,
+        None,
+    )
+    .await;
     Ok(Json(serde_json::json!({ "data": null })))
 }
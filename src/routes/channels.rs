@@ -4,13 +4,17 @@ use axum::Json;
 use crate::db;
 use crate::error::AppError;
 use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::audit::AuditReason;
 use crate::middleware::auth::AuthUser;
 use crate::middleware::permissions::{
     require_channel_membership, require_channel_permission, require_dm_access,
+    require_not_archived, require_recent_mfa,
 };
-use crate::models::channel::UpdateChannel;
-use crate::models::permission::{PermissionOverwrite, ALL_PERMISSIONS};
+use crate::models::channel::{is_voice_type, UpdateChannel, MAX_PINS_LIMIT, VOICE_CHAT_RETENTIONS};
+use crate::models::permission::{PermissionOverwrite, Permissions};
+use crate::models::thread::{AUTO_ARCHIVE_DURATIONS, MAX_AUTO_THREAD_NAME_LEN};
 use crate::state::AppState;
+use crate::storage;
 
 #[derive(serde::Deserialize)]
 pub struct UpsertOverwriteRequest {
@@ -45,20 +49,60 @@ pub async fn update_channel(
     state: State<AppState>,
     Path(channel_id): Path<String>,
     auth: AuthUser,
-    Json(input): Json<UpdateChannel>,
+    audit_reason: AuditReason,
+    Json(mut input): Json<UpdateChannel>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let existing = db::channels::get_channel_row(&state.db, &channel_id).await?;
     if existing.channel_type == "group_dm" {
         require_dm_access(&state.db, &channel_id, &auth.user_id).await?;
         if existing.owner_id.as_deref() != Some(&auth.user_id) {
             return Err(AppError::Forbidden(
-                "only the group owner can rename".into(),
+                "only the group owner can edit the group".into(),
             ));
         }
     } else if existing.channel_type == "dm" {
         return Err(AppError::BadRequest("cannot rename a 1:1 DM".into()));
     } else {
-        require_channel_permission(&state.db, &channel_id, &auth, "manage_channels").await?;
+        let space_id =
+            require_channel_permission(&state.db, &channel_id, &auth, "manage_channels").await?;
+        require_not_archived(&state.db, &space_id).await?;
+    }
+
+    if existing.channel_type != "group_dm" && (input.icon.is_some() || input.owner_id.is_some()) {
+        return Err(AppError::BadRequest(
+            "icon and owner_id only apply to group DMs".into(),
+        ));
+    }
+    if let Some(ref owner_id) = input.owner_id {
+        if !db::dm_participants::is_participant(&state.db, &channel_id, owner_id).await? {
+            return Err(AppError::BadRequest(
+                "the new owner must be in the group".into(),
+            ));
+        }
+    }
+    if let Some(ref icon) = input.icon {
+        if icon.starts_with("data:") {
+            crate::blocklist::check_data_uri_upload(&state.db, &auth.user_id, icon).await?;
+            if let Some(ref old_icon) = existing.icon {
+                let _ = storage::delete_file(state.storage.as_ref(), old_icon).await;
+            }
+            let (url, _, _, _) = storage::save_avatar_image(
+                state.storage.as_ref(),
+                "icons",
+                &channel_id,
+                icon,
+                state.settings.load().max_avatar_size as usize,
+            )
+            .await?;
+            input.icon = Some(url);
+        } else if icon.is_empty() {
+            // Kept as Some("") — the DB layer clears the column
+            storage::delete_avatar(state.storage.as_ref(), "icons", &channel_id).await?;
+        } else {
+            return Err(AppError::BadRequest(
+                "icon must be an image data URI, or empty to remove it".into(),
+            ));
+        }
     }
 
     // A channel's type may only be changed retroactively when the conversion is
@@ -76,10 +120,99 @@ pub async fn update_channel(
         }
     }
 
+    if let Some(ref retention) = input.voice_chat_retention {
+        if !is_voice_type(&existing.channel_type) {
+            return Err(AppError::BadRequest(
+                "voice_chat_retention only applies to voice and stage channels".into(),
+            ));
+        }
+        if !VOICE_CHAT_RETENTIONS.contains(&retention.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "voice_chat_retention must be one of: {}",
+                VOICE_CHAT_RETENTIONS.join(", ")
+            )));
+        }
+    }
+
+    if input.auto_thread.is_some()
+        || input.auto_thread_name.is_some()
+        || input.auto_archive_after.is_some()
+    {
+        let channel_type = input
+            .channel_type
+            .as_deref()
+            .unwrap_or(&existing.channel_type);
+        if existing.space_id.is_none() || !matches!(channel_type, "text" | "announcement") {
+            return Err(AppError::BadRequest(
+                "auto-thread settings only apply to text and announcement channels".into(),
+            ));
+        }
+    }
+    if let Some(ref template) = input.auto_thread_name {
+        if template.chars().count() > MAX_AUTO_THREAD_NAME_LEN {
+            return Err(AppError::BadRequest(format!(
+                "auto_thread_name must be at most {MAX_AUTO_THREAD_NAME_LEN} characters"
+            )));
+        }
+    }
+    if let Some(minutes) = input.auto_archive_after {
+        if !AUTO_ARCHIVE_DURATIONS.contains(&minutes) {
+            return Err(AppError::BadRequest(format!(
+                "auto_archive_after must be one of {AUTO_ARCHIVE_DURATIONS:?}"
+            )));
+        }
+    }
+
+    if let Some(max_pins) = input.max_pins {
+        if !(1..=MAX_PINS_LIMIT).contains(&max_pins) {
+            return Err(AppError::BadRequest(format!(
+                "max_pins must be between 1 and {MAX_PINS_LIMIT}"
+            )));
+        }
+    }
+
+    let before_json = super::spaces::channel_row_to_json_pub(&state.db, &existing).await;
+
+    // The announcement lock is stored as @everyone overwrites, so it takes the
+    // same permission as editing overwrites by hand.
+    if let Some(locked) = input.announcement_locked {
+        let Some(ref space_id) = existing.space_id else {
+            return Err(AppError::BadRequest(
+                "only space channels can be announcement-locked".into(),
+            ));
+        };
+        require_channel_permission(&state.db, &channel_id, &auth, "manage_roles").await?;
+        let everyone_id = db::roles::get_everyone_role_id(&state.db, space_id).await?;
+        db::permission_overwrites::set_announcement_lock(
+            &state.db,
+            &channel_id,
+            &everyone_id,
+            locked,
+        )
+        .await?;
+        db::permission_overwrites::sync_announcement_locked(&state.db, &channel_id, &everyone_id)
+            .await?;
+    }
+
     let channel =
         db::channels::update_channel(&state.db, &channel_id, &input, state.db_is_postgres).await?;
     let json = super::spaces::channel_row_to_json_pub(&state.db, &channel).await;
 
+    if let Some(ref space_id) = existing.space_id {
+        if let Some(changes) = super::audit_log::diff(&before_json, &json) {
+            super::audit_log::record(
+                &state,
+                space_id,
+                &auth.user_id,
+                "channel_update",
+                Some((&channel_id, "channel")),
+                audit_reason.as_deref(),
+                Some(changes),
+            )
+            .await;
+        }
+    }
+
     // Broadcast channel.update
     if existing.channel_type == "dm" || existing.channel_type == "group_dm" {
         let participant_ids =
@@ -95,6 +228,7 @@ pub async fn update_channel(
                 target_user_ids: Some(participant_ids),
                 event,
                 intent: "channels".to_string(),
+                role_mention_user_ids: None,
             });
         }
     } else if let Some(ref space_id) = existing.space_id {
@@ -109,6 +243,7 @@ pub async fn update_channel(
                 target_user_ids: None,
                 event,
                 intent: "channels".to_string(),
+                role_mention_user_ids: None,
             });
         }
     }
@@ -120,45 +255,36 @@ pub async fn delete_channel(
     state: State<AppState>,
     Path(channel_id): Path<String>,
     auth: AuthUser,
+    audit_reason: AuditReason,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let existing = db::channels::get_channel_row(&state.db, &channel_id).await?;
     if existing.channel_type == "dm" || existing.channel_type == "group_dm" {
         // For DM channels, "delete" means remove the caller from participants
         require_dm_access(&state.db, &channel_id, &auth.user_id).await?;
         db::dm_participants::remove_participant(&state.db, &channel_id, &auth.user_id).await?;
+        // The caller's other sessions drop the channel too
+        dispatch_to_users(
+            &state,
+            vec![auth.user_id.clone()],
+            "channel.delete",
+            serde_json::json!({ "id": channel_id }),
+        )
+        .await;
 
         let remaining = db::dm_participants::count_participants(&state.db, &channel_id).await?;
         if remaining <= 0 {
             // No participants left — actually delete the channel
             db::channels::delete_channel(&state.db, &channel_id).await?;
+            if existing.icon.is_some() {
+                let _ = storage::delete_avatar(state.storage.as_ref(), "icons", &channel_id).await;
+            }
         } else if existing.channel_type == "group_dm"
             && existing.owner_id.as_deref() == Some(&auth.user_id)
         {
             // Owner left — transfer ownership to first remaining participant
             let ids = db::dm_participants::list_participant_ids(&state.db, &channel_id).await?;
             if let Some(new_owner) = ids.first() {
-                let update = UpdateChannel {
-                    name: None,
-                    channel_type: None,
-                    topic: None,
-                    position: None,
-                    parent_id: None,
-                    nsfw: None,
-                    rate_limit: None,
-                    bitrate: None,
-                    user_limit: None,
-                    archived: None,
-                    allow_anonymous_read: None,
-                };
-                // We need to update owner_id directly since UpdateChannel doesn't have it
-                sqlx::query(&crate::db::q(
-                    "UPDATE channels SET owner_id = ? WHERE id = ?",
-                ))
-                .bind(new_owner)
-                .bind(&channel_id)
-                .execute(&state.db)
-                .await?;
-                let _ = update; // unused, just for clarity
+                db::dm_participants::set_owner(&state.db, &channel_id, new_owner).await?;
             }
         }
 
@@ -179,6 +305,7 @@ pub async fn delete_channel(
                     target_user_ids: Some(participant_ids),
                     event,
                     intent: "channels".to_string(),
+                    role_mention_user_ids: None,
                 });
             }
         }
@@ -187,6 +314,10 @@ pub async fn delete_channel(
     }
 
     require_channel_permission(&state.db, &channel_id, &auth, "manage_channels").await?;
+    if let Some(ref space_id) = existing.space_id {
+        require_not_archived(&state.db, space_id).await?;
+        require_recent_mfa(&state.db, space_id, &auth).await?;
+    }
 
     // Broadcast channel.delete to space members before deleting
     if let Some(ref space_id) = existing.space_id {
@@ -201,11 +332,25 @@ pub async fn delete_channel(
                 target_user_ids: None,
                 event,
                 intent: "channels".to_string(),
+                role_mention_user_ids: None,
             });
         }
     }
 
+    let before_json = super::spaces::channel_row_to_json_pub(&state.db, &existing).await;
     db::channels::delete_channel(&state.db, &channel_id).await?;
+    if let Some(ref space_id) = existing.space_id {
+        super::audit_log::record(
+            &state,
+            space_id,
+            &auth.user_id,
+            "channel_delete",
+            Some((&channel_id, "channel")),
+            audit_reason.as_deref(),
+            super::audit_log::diff(&before_json, &serde_json::json!({})),
+        )
+        .await;
+    }
     Ok(Json(serde_json::json!({ "data": null })))
 }
 
@@ -223,9 +368,14 @@ pub async fn upsert_overwrite(
     state: State<AppState>,
     Path((channel_id, overwrite_id)): Path<(String, String)>,
     auth: AuthUser,
+    audit_reason: AuditReason,
     Json(input): Json<UpsertOverwriteRequest>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    require_channel_permission(&state.db, &channel_id, &auth, "manage_roles").await?;
+    let space_id =
+        require_channel_permission(&state.db, &channel_id, &auth, "manage_roles").await?;
+    if !space_id.is_empty() {
+        require_not_archived(&state.db, &space_id).await?;
+    }
 
     // Validate overwrite_type
     if input.overwrite_type != "role" && input.overwrite_type != "member" {
@@ -235,11 +385,8 @@ pub async fn upsert_overwrite(
     }
 
     // Validate permission strings
-    for perm in input.allow.iter().chain(input.deny.iter()) {
-        if !ALL_PERMISSIONS.contains(&perm.as_str()) {
-            return Err(AppError::BadRequest(format!("unknown permission: {perm}")));
-        }
-    }
+    let allow = Permissions::parse(&input.allow)?;
+    let deny = Permissions::parse(&input.deny)?;
 
     // Validate that role/member belongs to the same space as the channel
     if input.overwrite_type == "role" {
@@ -257,10 +404,37 @@ pub async fn upsert_overwrite(
     let overwrite = PermissionOverwrite {
         id: overwrite_id,
         overwrite_type: input.overwrite_type,
-        allow: input.allow,
-        deny: input.deny,
+        allow,
+        deny,
     };
+    let before = db::permission_overwrites::list_overwrites(&state.db, &channel_id)
+        .await?
+        .into_iter()
+        .find(|o| o.id == overwrite.id);
     db::permission_overwrites::upsert_overwrite(&state.db, &channel_id, &overwrite).await?;
+    resync_announcement_lock(&state, &channel_id).await?;
+    if !space_id.is_empty() {
+        let before_json = before
+            .map(|o| serde_json::json!({ "type": o.overwrite_type, "allow": o.allow, "deny": o.deny }))
+            .unwrap_or_else(|| serde_json::json!({}));
+        let after_json = serde_json::json!({
+            "type": overwrite.overwrite_type,
+            "allow": overwrite.allow,
+            "deny": overwrite.deny,
+        });
+        if let Some(changes) = super::audit_log::diff(&before_json, &after_json) {
+            super::audit_log::record(
+                &state,
+                &space_id,
+                &auth.user_id,
+                "channel_overwrite_update",
+                Some((&channel_id, "channel")),
+                audit_reason.as_deref(),
+                Some(serde_json::json!({ "overwrite_id": overwrite.id, "changes": changes })),
+            )
+            .await;
+        }
+    }
 
     Ok(Json(serde_json::json!({ "data": overwrite })))
 }
@@ -269,12 +443,191 @@ pub async fn delete_overwrite(
     state: State<AppState>,
     Path((channel_id, overwrite_id)): Path<(String, String)>,
     auth: AuthUser,
+    audit_reason: AuditReason,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    require_channel_permission(&state.db, &channel_id, &auth, "manage_roles").await?;
+    let space_id =
+        require_channel_permission(&state.db, &channel_id, &auth, "manage_roles").await?;
+    if !space_id.is_empty() {
+        require_not_archived(&state.db, &space_id).await?;
+    }
     db::permission_overwrites::delete_overwrite(&state.db, &channel_id, &overwrite_id).await?;
+    resync_announcement_lock(&state, &channel_id).await?;
+    if !space_id.is_empty() {
+        super::audit_log::record(
+            &state,
+            &space_id,
+            &auth.user_id,
+            "channel_overwrite_delete",
+            Some((&channel_id, "channel")),
+            audit_reason.as_deref(),
+            Some(serde_json::json!({ "overwrite_id": overwrite_id })),
+        )
+        .await;
+    }
     Ok(Json(serde_json::json!({ "data": null })))
 }
 
+/// POST /channels/{id}/lock — deny sending to @everyone in one step. The
+/// @everyone overwrite's previous say on those permissions is kept so
+/// `unlock` can restore it. 409 `channel_locked` if already locked.
+pub async fn lock_channel(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+    audit_reason: AuditReason,
+) -> Result<Json<serde_json::Value>, AppError> {
+    set_channel_lock(&state, &channel_id, &auth, audit_reason.as_deref(), true).await
+}
+
+/// POST /channels/{id}/unlock — undo `lock`. 409 `channel_not_locked` if
+/// it isn't locked.
+pub async fn unlock_channel(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+    audit_reason: AuditReason,
+) -> Result<Json<serde_json::Value>, AppError> {
+    set_channel_lock(&state, &channel_id, &auth, audit_reason.as_deref(), false).await
+}
+
+async fn set_channel_lock(
+    state: &AppState,
+    channel_id: &str,
+    auth: &AuthUser,
+    reason: Option<&str>,
+    locked: bool,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let channel = db::channels::get_channel_row(&state.db, channel_id).await?;
+    let Some(space_id) = channel.space_id else {
+        return Err(AppError::BadRequest(
+            "only space channels can be locked".into(),
+        ));
+    };
+    // Locking edits the @everyone overwrite, so it takes the same
+    // permission as editing overwrites by hand.
+    require_channel_permission(&state.db, channel_id, auth, "manage_roles").await?;
+    require_not_archived(&state.db, &space_id).await?;
+
+    let everyone_id = db::roles::get_everyone_role_id(&state.db, &space_id).await?;
+    if locked {
+        if !db::permission_overwrites::lock_channel(
+            &state.db,
+            channel_id,
+            &everyone_id,
+            &auth.user_id,
+        )
+        .await?
+        {
+            return Err(AppError::ConflictCode(
+                "channel_locked",
+                "channel is already locked".into(),
+            ));
+        }
+    } else if !db::permission_overwrites::unlock_channel(&state.db, channel_id, &everyone_id)
+        .await?
+    {
+        return Err(AppError::ConflictCode(
+            "channel_not_locked",
+            "channel is not locked".into(),
+        ));
+    }
+    db::permission_overwrites::sync_announcement_locked(&state.db, channel_id, &everyone_id)
+        .await?;
+
+    let channel = db::channels::get_channel_row(&state.db, channel_id).await?;
+    let json = super::spaces::channel_row_to_json_pub(&state.db, &channel).await;
+    super::audit_log::record(
+        state,
+        &space_id,
+        &auth.user_id,
+        if locked {
+            "channel_lock"
+        } else {
+            "channel_unlock"
+        },
+        Some((channel_id, "channel")),
+        reason,
+        None,
+    )
+    .await;
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "channel.update",
+            "data": json
+        });
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: Some(space_id),
+            target_user_ids: None,
+            event,
+            intent: "channels".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+    Ok(Json(serde_json::json!({ "data": json })))
+}
+
+/// Keep `announcement_locked` in step with hand-edited overwrites, and tell
+/// the space when it flips.
+async fn resync_announcement_lock(state: &AppState, channel_id: &str) -> Result<(), AppError> {
+    let channel = db::channels::get_channel_row(&state.db, channel_id).await?;
+    let Some(ref space_id) = channel.space_id else {
+        return Ok(());
+    };
+    let everyone_id = db::roles::get_everyone_role_id(&state.db, space_id).await?;
+    if !db::permission_overwrites::sync_announcement_locked(&state.db, channel_id, &everyone_id)
+        .await?
+    {
+        return Ok(());
+    }
+
+    let channel = db::channels::get_channel_row(&state.db, channel_id).await?;
+    let json = super::spaces::channel_row_to_json_pub(&state.db, &channel).await;
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "channel.update",
+            "data": json
+        });
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: Some(space_id.clone()),
+            target_user_ids: None,
+            event,
+            intent: "channels".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+    Ok(())
+}
+
+/// Send a DM channel event to the given users' sessions.
+async fn dispatch_to_users(
+    state: &AppState,
+    user_ids: Vec<String>,
+    event_type: &str,
+    data: serde_json::Value,
+) {
+    if user_ids.is_empty() {
+        return;
+    }
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: None,
+            target_user_ids: Some(user_ids),
+            event: serde_json::json!({
+                "op": 0,
+                "type": event_type,
+                "data": data
+            }),
+            intent: "channels".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
+
+/// PUT /channels/{id}/recipients/{user_id} — the group owner adds someone.
+/// The newcomer gets `channel.create`; everyone already in gets
+/// `channel.update`.
 pub async fn add_recipient(
     state: State<AppState>,
     Path((channel_id, user_id)): Path<(String, String)>,
@@ -296,39 +649,58 @@ pub async fn add_recipient(
     // Validate target user exists
     db::users::get_user(&state.db, &user_id).await?;
 
-    // Check participant count
-    let count = db::dm_participants::count_participants(&state.db, &channel_id).await?;
-    if count >= 10 {
-        return Err(AppError::BadRequest(
-            "group DMs cannot have more than 10 participants".into(),
-        ));
-    }
+    let already_present =
+        db::dm_participants::is_participant(&state.db, &channel_id, &user_id).await?;
+    if !already_present {
+        // Same rules as opening a DM with them
+        if db::relationships::is_blocked_by(&state.db, &user_id, &auth.user_id).await? {
+            return Err(AppError::Forbidden(
+                "you cannot add this user to a group".into(),
+            ));
+        }
+        if !db::relationships::are_friends(&state.db, &auth.user_id, &user_id).await?
+            && !db::members::share_space(&state.db, &auth.user_id, &user_id).await?
+        {
+            return Err(AppError::ForbiddenCode(
+                "dm_requires_relationship",
+                "you can only add friends or members of a space you share".into(),
+            ));
+        }
+
+        let count = db::dm_participants::count_participants(&state.db, &channel_id).await?;
+        if count >= 10 {
+            return Err(AppError::BadRequest(
+                "group DMs cannot have more than 10 participants".into(),
+            ));
+        }
 
-    db::dm_participants::add_participant(&state.db, &channel_id, &user_id, state.db_is_postgres)
+        db::dm_participants::add_participant(
+            &state.db,
+            &channel_id,
+            &user_id,
+            state.db_is_postgres,
+        )
         .await?;
+    }
 
     let updated = db::channels::get_channel_row(&state.db, &channel_id).await?;
     let json = super::spaces::channel_row_to_json_pub(&state.db, &updated).await;
 
-    // Broadcast channel.update to all participants (including the new one)
-    let participant_ids = db::dm_participants::list_participant_ids(&state.db, &channel_id).await?;
-    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
-        let event = serde_json::json!({
-            "op": 0,
-            "type": "channel.update",
-            "data": json
-        });
-        let _ = dispatcher.send(GatewayBroadcast {
-            space_id: None,
-            target_user_ids: Some(participant_ids),
-            event,
-            intent: "channels".to_string(),
-        });
+    let mut participant_ids =
+        db::dm_participants::list_participant_ids(&state.db, &channel_id).await?;
+    if !already_present {
+        participant_ids.retain(|id| id != &user_id);
+        dispatch_to_users(&state, vec![user_id], "channel.create", json.clone()).await;
     }
+    dispatch_to_users(&state, participant_ids, "channel.update", json.clone()).await;
 
     Ok(Json(serde_json::json!({ "data": json })))
 }
 
+/// DELETE /channels/{id}/recipients/{user_id} — leave the group, or (owner
+/// only) remove someone else. The removed user gets `channel.delete`; the
+/// rest get `channel.update`, with ownership passed on if the owner left. A
+/// group left with one person is deleted.
 pub async fn remove_recipient(
     state: State<AppState>,
     Path((channel_id, user_id)): Path<(String, String)>,
@@ -348,66 +720,41 @@ pub async fn remove_recipient(
             "only the group owner can remove members".into(),
         ));
     }
+    if !db::dm_participants::is_participant(&state.db, &channel_id, &user_id).await? {
+        return Err(AppError::NotFound("unknown_recipient".into()));
+    }
 
     db::dm_participants::remove_participant(&state.db, &channel_id, &user_id).await?;
-
-    let remaining = db::dm_participants::count_participants(&state.db, &channel_id).await?;
-    if remaining <= 1 {
+    let deleted = serde_json::json!({ "id": channel_id });
+    dispatch_to_users(
+        &state,
+        vec![user_id.clone()],
+        "channel.delete",
+        deleted.clone(),
+    )
+    .await;
+
+    let remaining_ids = db::dm_participants::list_participant_ids(&state.db, &channel_id).await?;
+    if remaining_ids.len() <= 1 {
         // Not enough participants — delete the channel
         db::channels::delete_channel(&state.db, &channel_id).await?;
-        // Broadcast channel.delete to remaining participant if any
-        let remaining_ids =
-            db::dm_participants::list_participant_ids(&state.db, &channel_id).await?;
-        if !remaining_ids.is_empty() {
-            if let Some(ref dispatcher) = *state.gateway_tx.read().await {
-                let event = serde_json::json!({
-                    "op": 0,
-                    "type": "channel.delete",
-                    "data": { "id": channel_id }
-                });
-                let _ = dispatcher.send(GatewayBroadcast {
-                    space_id: None,
-                    target_user_ids: Some(remaining_ids),
-                    event,
-                    intent: "channels".to_string(),
-                });
-            }
+        if channel.icon.is_some() {
+            let _ = storage::delete_avatar(state.storage.as_ref(), "icons", &channel_id).await;
         }
+        dispatch_to_users(&state, remaining_ids, "channel.delete", deleted).await;
         return Ok(Json(serde_json::json!({ "data": null })));
     }
 
     // Transfer ownership if the owner left
     if channel.owner_id.as_deref() == Some(&user_id) {
-        let ids = db::dm_participants::list_participant_ids(&state.db, &channel_id).await?;
-        if let Some(new_owner) = ids.first() {
-            sqlx::query(&crate::db::q(
-                "UPDATE channels SET owner_id = ? WHERE id = ?",
-            ))
-            .bind(new_owner)
-            .bind(&channel_id)
-            .execute(&state.db)
-            .await?;
+        if let Some(new_owner) = remaining_ids.first() {
+            db::dm_participants::set_owner(&state.db, &channel_id, new_owner).await?;
         }
     }
 
     let updated = db::channels::get_channel_row(&state.db, &channel_id).await?;
     let json = super::spaces::channel_row_to_json_pub(&state.db, &updated).await;
-
-    // Broadcast channel.update to remaining participants
-    let participant_ids = db::dm_participants::list_participant_ids(&state.db, &channel_id).await?;
-    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
-        let event = serde_json::json!({
-            "op": 0,
-            "type": "channel.update",
-            "data": json
-        });
-        let _ = dispatcher.send(GatewayBroadcast {
-            space_id: None,
-            target_user_ids: Some(participant_ids),
-            event,
-            intent: "channels".to_string(),
-        });
-    }
+    dispatch_to_users(&state, remaining_ids, "channel.update", json.clone()).await;
 
     Ok(Json(serde_json::json!({ "data": json })))
 }
@@ -0,0 +1,113 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::db;
+use crate::drafts::{broadcast_draft, expiry_cutoff, MAX_DRAFTS_PER_USER, MAX_DRAFT_LENGTH};
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::require_channel_membership;
+use crate::models::draft::PutDraft;
+use crate::state::AppState;
+
+fn require_account(auth: &AuthUser) -> Result<(), AppError> {
+    if auth.is_guest {
+        return Err(AppError::Forbidden(
+            "guest accounts cannot store drafts".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// GET /users/@me/drafts
+pub async fn list_drafts(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_account(&auth)?;
+    let drafts = db::drafts::list_drafts(&state.db, &auth.user_id, &expiry_cutoff()).await?;
+    Ok(Json(serde_json::json!({ "data": drafts })))
+}
+
+/// GET /channels/{channel_id}/draft
+/// `data` is null when there is no draft.
+pub async fn get_draft(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_account(&auth)?;
+    require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    let draft = db::drafts::get_draft(
+        &state.db,
+        &auth.user_id,
+        &channel_id,
+        Some(&expiry_cutoff()),
+    )
+    .await?;
+    Ok(Json(serde_json::json!({ "data": draft })))
+}
+
+/// PUT /channels/{channel_id}/draft
+/// Saving blank content clears the draft.
+pub async fn put_draft(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<PutDraft>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_account(&auth)?;
+    require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+
+    if input.content.len() > MAX_DRAFT_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "draft content must be at most {MAX_DRAFT_LENGTH} characters"
+        )));
+    }
+    if input.content.trim().is_empty() {
+        if db::drafts::delete_draft(&state.db, &auth.user_id, &channel_id).await? {
+            broadcast_draft(&state, &auth.user_id, &channel_id, None).await;
+        }
+        return Ok(Json(serde_json::json!({ "data": null })));
+    }
+    if let Some(ref reply_to) = input.reply_to {
+        let target = db::messages::get_message_row(&state.db, reply_to).await?;
+        if target.channel_id != channel_id {
+            return Err(AppError::BadRequest(
+                "reply_to must be a message in this channel".into(),
+            ));
+        }
+    }
+    let existing = db::drafts::get_draft(&state.db, &auth.user_id, &channel_id, None).await?;
+    if existing.is_none()
+        && db::drafts::count_drafts(&state.db, &auth.user_id).await? >= MAX_DRAFTS_PER_USER
+    {
+        return Err(AppError::BadRequest(format!(
+            "at most {MAX_DRAFTS_PER_USER} drafts; send or clear some first"
+        )));
+    }
+
+    let draft = db::drafts::upsert_draft(
+        &state.db,
+        &auth.user_id,
+        &channel_id,
+        &input.content,
+        input.reply_to.as_deref(),
+        state.db_is_postgres,
+    )
+    .await?;
+    broadcast_draft(&state, &auth.user_id, &channel_id, Some(&draft)).await;
+    Ok(Json(serde_json::json!({ "data": draft })))
+}
+
+/// DELETE /channels/{channel_id}/draft
+pub async fn delete_draft(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_account(&auth)?;
+    if db::drafts::delete_draft(&state.db, &auth.user_id, &channel_id).await? {
+        broadcast_draft(&state, &auth.user_id, &channel_id, None).await;
+    }
+    Ok(Json(serde_json::json!({ "data": null })))
+}
@@ -0,0 +1,78 @@
+use std::collections::BTreeSet;
+
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::Deserialize;
+
+use crate::db;
+use crate::email_domains::{normalize_domain, MAX_DOMAINS};
+use crate::error::AppError;
+use crate::middleware::audit::AuditReason;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::require_permission;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct SetEmailDomains {
+    pub domains: Vec<String>,
+}
+
+/// GET /spaces/{space_id}/email-domains
+///
+/// Anyone signed in can see the list, so people can tell why they can't
+/// join before trying.
+pub async fn get_email_domains(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    _auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    db::spaces::get_space_row(&state.db, &space_id).await?;
+    let domains = db::email_domains::list_domains(&state.db, &space_id).await?;
+    Ok(Json(serde_json::json!({ "data": { "domains": domains } })))
+}
+
+/// PUT /spaces/{space_id}/email-domains
+///
+/// Replaces the list; an empty list lets anyone join again. Members who
+/// already joined stay.
+pub async fn set_email_domains(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    AuditReason(reason): AuditReason,
+    Json(input): Json<SetEmailDomains>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let domains = input
+        .domains
+        .iter()
+        .map(|d| normalize_domain(d))
+        .collect::<Result<BTreeSet<_>, _>>()?;
+    if domains.len() > MAX_DOMAINS {
+        return Err(AppError::BadRequest(format!(
+            "a space can allow at most {MAX_DOMAINS} email domains"
+        )));
+    }
+    let domains: Vec<String> = domains.into_iter().collect();
+
+    let before = db::email_domains::list_domains(&state.db, &space_id).await?;
+    db::email_domains::set_domains(&state.db, &space_id, &domains).await?;
+
+    if before != domains {
+        super::audit_log::record(
+            &state,
+            &space_id,
+            &auth.user_id,
+            "email_domains_update",
+            Some((&space_id, "space")),
+            reason.as_deref(),
+            super::audit_log::diff(
+                &serde_json::json!({ "domains": before }),
+                &serde_json::json!({ "domains": domains }),
+            ),
+        )
+        .await;
+    }
+
+    Ok(Json(serde_json::json!({ "data": { "domains": domains } })))
+}
@@ -4,8 +4,12 @@ use axum::Json;
 use crate::db;
 use crate::error::AppError;
 use crate::middleware::auth::AuthUser;
-use crate::middleware::permissions::{require_membership, require_permission};
-use crate::models::emoji::{CreateEmoji, UpdateEmoji};
+use crate::middleware::permissions::{
+    list_member_ids_with_permission, require_membership, require_not_timed_out, require_permission,
+    resolve_member_permissions_with_admin,
+};
+use crate::models::emoji::{CreateEmoji, Emoji, UpdateEmoji};
+use crate::models::permission::Permissions;
 use crate::state::AppState;
 use crate::storage;
 
@@ -27,6 +31,13 @@ pub async fn get_emoji(
     require_membership(&state.db, &space_id, &auth.user_id).await?;
     db::emojis::require_emoji_in_space(&state.db, &emoji_id, &space_id).await?;
     let emoji = db::emojis::get_emoji(&state.db, &emoji_id).await?;
+    // Pending emoji are only visible to their uploader and the reviewers.
+    if emoji.pending
+        && emoji.creator_id.as_deref() != Some(&auth.user_id)
+        && !can_manage_emojis(&state, &space_id, &auth).await
+    {
+        return Err(AppError::NotFound("unknown_emoji".to_string()));
+    }
     Ok(Json(serde_json::json!({ "data": emoji })))
 }
 
@@ -36,14 +47,30 @@ pub async fn create_emoji(
     auth: AuthUser,
     Json(input): Json<CreateEmoji>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    require_permission(&state.db, &space_id, &auth, "manage_emojis").await?;
+    // With emoji moderation on, any member may upload; uploads from members
+    // without manage_emojis wait in the review queue.
+    let pending = match require_permission(&state.db, &space_id, &auth, "manage_emojis").await {
+        Ok(()) => false,
+        Err(AppError::Forbidden(msg)) => {
+            let space = db::spaces::get_space_row(&state.db, &space_id).await?;
+            if !space.emoji_moderation || auth.is_guest {
+                return Err(AppError::Forbidden(msg));
+            }
+            require_membership(&state.db, &space_id, &auth.user_id).await?;
+            require_not_timed_out(&state.db, &space_id, &auth).await?;
+            true
+        }
+        Err(e) => return Err(e),
+    };
     require_local_space(&state, &space_id).await?;
+    require_emoji_slot(&state, &space_id).await?;
 
     let max_emoji_size = state.settings.load().max_emoji_size as usize;
+    crate::blocklist::check_data_uri_upload(&state.db, &auth.user_id, &input.image).await?;
 
     // Save the image file
     let (image_path, content_type, size, animated) = storage::save_base64_image(
-        &state.storage_path,
+        state.storage.as_ref(),
         &space_id,
         &input.name,
         &input.image,
@@ -60,6 +87,7 @@ pub async fn create_emoji(
         Some(&content_type),
         Some(size),
         animated,
+        pending,
     )
     .await?;
 
@@ -67,9 +95,9 @@ pub async fn create_emoji(
     if let (Some(emoji_id), Some(_)) = (emoji.id.clone(), &emoji.image_url) {
         // The file was saved with input.name, but we want it named by ID
         // Re-save with the correct ID-based path
-        let _ = storage::delete_file(&state.storage_path, &image_path).await;
+        let _ = storage::delete_file(state.storage.as_ref(), &image_path).await;
         let (real_path, _, _, _) = storage::save_base64_image(
-            &state.storage_path,
+            state.storage.as_ref(),
             &space_id,
             &emoji_id,
             &input.image,
@@ -90,6 +118,11 @@ pub async fn create_emoji(
         emoji = db::emojis::get_emoji(&state.db, &emoji_id).await?;
     }
 
+    if pending {
+        broadcast_queue_update(&state, &space_id, &emoji, "pending").await;
+        return Ok(Json(serde_json::json!({ "data": emoji })));
+    }
+
     // Broadcast to gateway
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
         let event = serde_json::json!({
@@ -105,6 +138,7 @@ pub async fn create_emoji(
             target_user_ids: None,
             event,
             intent: "emojis".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -125,6 +159,11 @@ pub async fn update_emoji(
     let emoji =
         db::emojis::update_emoji(&state.db, &emoji_id, &input, state.db_is_postgres).await?;
 
+    if emoji.pending {
+        broadcast_queue_update(&state, &space_id, &emoji, "pending").await;
+        return Ok(Json(serde_json::json!({ "data": emoji })));
+    }
+
     // Broadcast to gateway
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
         let event = serde_json::json!({
@@ -140,6 +179,7 @@ pub async fn update_emoji(
             target_user_ids: None,
             event,
             intent: "emojis".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -161,7 +201,7 @@ pub async fn delete_emoji(
 
     // Delete the file from disk
     if let Some(ref path) = image_path {
-        let _ = storage::delete_file(&state.storage_path, path).await;
+        let _ = storage::delete_file(state.storage.as_ref(), path).await;
     }
 
     // Broadcast to gateway
@@ -179,6 +219,7 @@ pub async fn delete_emoji(
             target_user_ids: None,
             event,
             intent: "emojis".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -197,6 +238,133 @@ pub async fn delete_emoji(
     Ok(Json(serde_json::json!({ "data": null })))
 }
 
+/// GET /spaces/{space_id}/emojis/pending
+/// The review queue: uploads waiting for a moderator.
+pub async fn list_pending_emojis(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_emojis").await?;
+    let emojis = db::emojis::list_pending_emojis(&state.db, &space_id).await?;
+    Ok(Json(serde_json::json!({ "data": emojis })))
+}
+
+/// Refuse with `emoji_limit_reached` when the space has no free emoji slot.
+async fn require_emoji_slot(state: &AppState, space_id: &str) -> Result<(), AppError> {
+    let space = db::spaces::get_space_row(&state.db, space_id).await?;
+    let current = db::emojis::count_emojis(&state.db, space_id).await?;
+    AppError::check_limit("emoji_limit_reached", current, space.max_emojis)
+}
+
+/// POST /spaces/{space_id}/emojis/{emoji_id}/approve
+pub async fn approve_emoji(
+    state: State<AppState>,
+    Path((space_id, emoji_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_emojis").await?;
+    let emoji = get_pending_emoji(&state, &space_id, &emoji_id).await?;
+    require_emoji_slot(&state, &space_id).await?;
+    let emoji = db::emojis::approve_emoji(
+        &state.db,
+        emoji.id.as_deref().unwrap_or_default(),
+        state.db_is_postgres,
+    )
+    .await?;
+
+    broadcast_queue_update(&state, &space_id, &emoji, "approved").await;
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "emoji.create",
+            "data": {
+                "space_id": space_id,
+                "emoji": emoji
+            }
+        });
+        let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast {
+            space_id: Some(space_id.clone()),
+            target_user_ids: None,
+            event,
+            intent: "emojis".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+    fanout_emoji_upsert(&state, &space_id, "m.emoji.create", &emoji).await;
+
+    Ok(Json(serde_json::json!({ "data": emoji })))
+}
+
+/// POST /spaces/{space_id}/emojis/{emoji_id}/reject
+/// Drops the upload and its image.
+pub async fn reject_emoji(
+    state: State<AppState>,
+    Path((space_id, emoji_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_emojis").await?;
+    let emoji = get_pending_emoji(&state, &space_id, &emoji_id).await?;
+    let image_path = db::emojis::delete_emoji(&state.db, &emoji_id).await?;
+    if let Some(ref path) = image_path {
+        let _ = storage::delete_file(state.storage.as_ref(), path).await;
+    }
+
+    broadcast_queue_update(&state, &space_id, &emoji, "rejected").await;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+async fn get_pending_emoji(
+    state: &AppState,
+    space_id: &str,
+    emoji_id: &str,
+) -> Result<Emoji, AppError> {
+    db::emojis::require_emoji_in_space(&state.db, emoji_id, space_id).await?;
+    let emoji = db::emojis::get_emoji(&state.db, emoji_id).await?;
+    if !emoji.pending {
+        return Err(AppError::BadRequest("emoji is not pending review".into()));
+    }
+    Ok(emoji)
+}
+
+async fn can_manage_emojis(state: &AppState, space_id: &str, auth: &AuthUser) -> bool {
+    resolve_member_permissions_with_admin(&state.db, space_id, &auth.user_id, auth.is_admin)
+        .await
+        .map(|perms| perms.has(Permissions::MANAGE_EMOJIS))
+        .unwrap_or(false)
+}
+
+/// Send `emoji.queue_update` to everyone who can review the queue, plus the
+/// uploader, so moderators see submissions and decisions live.
+async fn broadcast_queue_update(state: &AppState, space_id: &str, emoji: &Emoji, status: &str) {
+    let mut targets = list_member_ids_with_permission(&state.db, space_id, "manage_emojis")
+        .await
+        .unwrap_or_default();
+    if let Some(ref creator_id) = emoji.creator_id {
+        if !targets.contains(creator_id) {
+            targets.push(creator_id.clone());
+        }
+    }
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "emoji.queue_update",
+            "data": {
+                "space_id": space_id,
+                "status": status,
+                "emoji": emoji
+            }
+        });
+        let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast {
+            space_id: None,
+            target_user_ids: Some(targets),
+            event,
+            intent: "emojis".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
+
 /// Reject emoji mutations on a remote-homed (mirrored) space. Emoji are
 /// authoritative on the space's home server; a replica must not create local
 /// bare-ID emoji rows on a space it only mirrors. The home server propagates
@@ -0,0 +1,195 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use serde::Deserialize;
+
+use crate::db;
+use crate::error::AppError;
+use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::{require_channel_membership, require_channel_permission};
+use crate::models::forum::{CreateForumTag, ForumSort, UpdateForumTag, MAX_FORUM_TAGS};
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct ListPostsQuery {
+    #[serde(default)]
+    pub sort: ForumSort,
+    /// Only posts carrying this tag.
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub archived: bool,
+    pub after: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Checks the channel is a forum.
+async fn require_forum(state: &AppState, channel_id: &str) -> Result<(), AppError> {
+    let channel = db::channels::get_channel_row(&state.db, channel_id).await?;
+    if channel.channel_type != "forum" {
+        return Err(AppError::BadRequest("channel is not a forum".into()));
+    }
+    Ok(())
+}
+
+fn validate_tag_name(name: &str) -> Result<(), AppError> {
+    let len = name.trim().chars().count();
+    if !(1..=50).contains(&len) {
+        return Err(AppError::BadRequest(
+            "tag name must be 1-50 characters".into(),
+        ));
+    }
+    Ok(())
+}
+
+async fn broadcast_tag_event(
+    state: &AppState,
+    space_id: String,
+    event_type: &str,
+    data: serde_json::Value,
+) {
+    if let Some(ref gtx) = *state.gateway_tx.read().await {
+        let _ = gtx.send(GatewayBroadcast {
+            space_id: Some(space_id),
+            target_user_ids: None,
+            event: serde_json::json!({ "op": 0, "type": event_type, "data": data }),
+            intent: "channels".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
+
+/// GET /channels/{channel_id}/tags
+pub async fn list_tags(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    require_forum(&state, &channel_id).await?;
+    let tags = db::forum_tags::list_tags(&state.db, &channel_id).await?;
+    Ok(Json(serde_json::json!({ "data": tags })))
+}
+
+/// POST /channels/{channel_id}/tags
+pub async fn create_tag(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<CreateForumTag>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_id =
+        require_channel_permission(&state.db, &channel_id, &auth, "manage_channels").await?;
+    require_forum(&state, &channel_id).await?;
+    validate_tag_name(&input.name)?;
+    let current = db::forum_tags::count_tags(&state.db, &channel_id).await?;
+    AppError::check_limit("tag_limit_reached", current, MAX_FORUM_TAGS)?;
+
+    let tag = db::forum_tags::create_tag(&state.db, &channel_id, &input).await?;
+    let data = serde_json::to_value(&tag).unwrap_or_default();
+    broadcast_tag_event(&state, space_id, "forum_tag.create", data.clone()).await;
+    Ok(Json(serde_json::json!({ "data": data })))
+}
+
+/// PATCH /channels/{channel_id}/tags/{tag_id}
+pub async fn update_tag(
+    state: State<AppState>,
+    Path((channel_id, tag_id)): Path<(String, String)>,
+    auth: AuthUser,
+    Json(input): Json<UpdateForumTag>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_id =
+        require_channel_permission(&state.db, &channel_id, &auth, "manage_channels").await?;
+    db::forum_tags::get_tag(&state.db, &channel_id, &tag_id).await?;
+    if let Some(ref name) = input.name {
+        validate_tag_name(name)?;
+    }
+
+    let tag = db::forum_tags::update_tag(&state.db, &channel_id, &tag_id, &input).await?;
+    let data = serde_json::to_value(&tag).unwrap_or_default();
+    broadcast_tag_event(&state, space_id, "forum_tag.update", data.clone()).await;
+    Ok(Json(serde_json::json!({ "data": data })))
+}
+
+/// DELETE /channels/{channel_id}/tags/{tag_id}
+pub async fn delete_tag(
+    state: State<AppState>,
+    Path((channel_id, tag_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_id =
+        require_channel_permission(&state.db, &channel_id, &auth, "manage_channels").await?;
+    db::forum_tags::get_tag(&state.db, &channel_id, &tag_id).await?;
+    db::forum_tags::delete_tag(&state.db, &tag_id).await?;
+    broadcast_tag_event(
+        &state,
+        space_id,
+        "forum_tag.delete",
+        serde_json::json!({ "id": tag_id, "channel_id": channel_id }),
+    )
+    .await;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// GET /channels/{channel_id}/posts
+/// A forum's posts as thread objects, each with its opening `message`.
+pub async fn list_posts(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+    Query(params): Query<ListPostsQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    require_forum(&state, &channel_id).await?;
+
+    let after = match params.after {
+        Some(ref id) => {
+            let row = db::threads::get_thread(&state.db, id).await?;
+            if row.channel_id != channel_id {
+                return Err(AppError::BadRequest(
+                    "after is not a post in this forum".into(),
+                ));
+            }
+            Some(row)
+        }
+        None => None,
+    };
+    let limit = params.limit.unwrap_or(25).clamp(1, 100);
+    let mut rows = db::threads::list_forum_threads(
+        &state.db,
+        &channel_id,
+        params.sort,
+        params.archived,
+        params.tag.as_deref(),
+        after.as_ref(),
+        limit + 1,
+    )
+    .await?;
+    let has_more = rows.len() as i64 > limit;
+    if has_more {
+        rows.truncate(limit as usize);
+    }
+
+    // Starter messages share their thread's ID.
+    let ids: Vec<String> = rows.iter().map(|r| r.id.clone()).collect();
+    let starters = db::messages::get_message_rows(&state.db, &ids).await?;
+    let starters =
+        super::messages::messages_to_json(&state.db, &starters, Some(&auth.user_id)).await?;
+
+    let mut posts = super::threads::threads_to_json(&state, &rows).await?;
+    for (post, row) in posts.iter_mut().zip(&rows) {
+        post["message"] = starters
+            .iter()
+            .find(|m| m["id"] == row.id.as_str())
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+    }
+
+    let mut response = serde_json::json!({ "data": posts });
+    if has_more {
+        response["cursor"] = serde_json::json!({
+            "after": rows.last().map(|r| r.id.clone()).unwrap_or_default(),
+            "has_more": has_more
+        });
+    }
+    Ok(Json(response))
+}
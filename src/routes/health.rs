@@ -10,3 +10,8 @@ pub async fn version() -> Json<serde_json::Value> {
         "git_sha": env!("GIT_SHA"),
     }))
 }
+
+/// The payload catalog (see [crate::schema]).
+pub async fn schema() -> Json<serde_json::Value> {
+    Json(crate::schema::catalog().clone())
+}
@@ -0,0 +1,27 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::db;
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::require_permission;
+use crate::state::AppState;
+
+/// GET /spaces/{space_id}/integrations
+/// Everything with automated access to the space -- bots and webhooks --
+/// with who set it up and when it last did anything, for auditing.
+pub async fn list_integrations(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let bots = db::integrations::list_space_bots(&state.db, &space_id).await?;
+    let webhooks = db::integrations::list_space_webhooks(&state.db, &space_id).await?;
+    Ok(Json(serde_json::json!({
+        "data": {
+            "bots": bots,
+            "webhooks": webhooks,
+        }
+    })))
+}
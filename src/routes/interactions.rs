@@ -1,31 +1,610 @@
 use axum::extract::{Path, State};
 use axum::Json;
 
+use crate::db;
 use crate::error::AppError;
-use crate::middleware::auth::AuthUser;
+use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::auth::{generate_token, AuthUser};
+use crate::middleware::permissions::{
+    require_channel_permission, require_not_archived, require_permission,
+};
+use crate::models::application::Application;
+use crate::models::embed::validate_embeds;
+use crate::models::interaction::{
+    Command, CommandOption, CommandOptionValue, CreateCommand, Interaction, InteractionCallback,
+    InteractionData, InteractionRow, InvokeCommand,
+};
+use crate::models::message::CreateMessage;
 use crate::state::AppState;
 
+/// Option types a command may declare.
+const OPTION_TYPES: &[&str] = &[
+    "string", "integer", "number", "boolean", "user", "channel", "role",
+];
+
+/// Most commands an application may register per scope (global or one space).
+pub const MAX_COMMANDS_PER_SCOPE: usize = 100;
+
+/// Most options a single command may declare.
+const MAX_OPTIONS: usize = 25;
+
+/// How long a bot has to answer an interaction.
+pub const INTERACTION_TTL_MINUTES: i64 = 15;
+
+/// How long an `interactions_endpoint_url` has to answer a request.
+const HTTP_INTERACTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Check that [auth] may manage [app_id]'s commands: its owner or its bot.
+async fn require_app_manager(
+    state: &AppState,
+    app_id: &str,
+    auth: &AuthUser,
+) -> Result<Application, AppError> {
+    let app = db::auth::get_application(&state.db, app_id).await?;
+    if app.owner_id != auth.user_id && app.bot_user_id.as_deref() != Some(auth.user_id.as_str()) {
+        return Err(AppError::Forbidden(
+            "only the application's owner or bot can manage its commands".into(),
+        ));
+    }
+    Ok(app)
+}
+
+async fn is_bot_in_space(state: &AppState, app: &Application, space_id: &str) -> bool {
+    match app.bot_user_id {
+        Some(ref bot_id) => db::members::get_member_row(&state.db, space_id, bot_id)
+            .await
+            .is_ok(),
+        None => false,
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    (1..=32).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+fn validate_command(input: &CreateCommand) -> Result<(), AppError> {
+    if !is_valid_name(&input.name) {
+        return Err(AppError::BadRequest(
+            "name must be 1-32 lowercase letters, digits, '-' or '_'".into(),
+        ));
+    }
+    if input.description.trim().is_empty() || input.description.len() > 100 {
+        return Err(AppError::BadRequest(
+            "description must be between 1 and 100 characters".into(),
+        ));
+    }
+    if input
+        .command_type
+        .as_deref()
+        .is_some_and(|t| t != "chat_input")
+    {
+        return Err(AppError::BadRequest(
+            "only chat_input commands are supported".into(),
+        ));
+    }
+    let options = input.options.as_deref().unwrap_or(&[]);
+    if options.len() > MAX_OPTIONS {
+        return Err(AppError::BadRequest(format!(
+            "a command can have at most {MAX_OPTIONS} options"
+        )));
+    }
+    let mut seen_optional = false;
+    for (i, option) in options.iter().enumerate() {
+        if !is_valid_name(&option.name) || options[..i].iter().any(|o| o.name == option.name) {
+            return Err(AppError::BadRequest(format!(
+                "invalid or duplicate option name: {}",
+                option.name
+            )));
+        }
+        if !OPTION_TYPES.contains(&option.option_type.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "invalid option type: {}",
+                option.option_type
+            )));
+        }
+        if option.description.len() > 100 {
+            return Err(AppError::BadRequest(
+                "option descriptions must be at most 100 characters".into(),
+            ));
+        }
+        if option.required.unwrap_or(false) {
+            if seen_optional {
+                return Err(AppError::BadRequest(
+                    "required options must come before optional ones".into(),
+                ));
+            }
+        } else {
+            seen_optional = true;
+        }
+        if option.choices.as_ref().is_some_and(|c| c.len() > 25) {
+            return Err(AppError::BadRequest(
+                "an option can have at most 25 choices".into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn value_matches_type(option_type: &str, value: &serde_json::Value) -> bool {
+    match option_type {
+        "integer" => value.is_i64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        // string, user, channel and role values are all strings (IDs for
+        // the latter three).
+        _ => value.is_string(),
+    }
+}
+
+/// Check the option values a user supplied against the command's schema.
+fn validate_invocation(command: &Command, values: &[CommandOptionValue]) -> Result<(), AppError> {
+    let declared: &[CommandOption] = command.options.as_deref().unwrap_or(&[]);
+    for (i, value) in values.iter().enumerate() {
+        let Some(option) = declared.iter().find(|o| o.name == value.name) else {
+            return Err(AppError::BadRequest(format!(
+                "unknown option: {}",
+                value.name
+            )));
+        };
+        if values[..i].iter().any(|v| v.name == value.name) {
+            return Err(AppError::BadRequest(format!(
+                "duplicate option: {}",
+                value.name
+            )));
+        }
+        let Some(ref v) = value.value else {
+            continue;
+        };
+        if !value_matches_type(&option.option_type, v) {
+            return Err(AppError::BadRequest(format!(
+                "option {} must be a {}",
+                option.name, option.option_type
+            )));
+        }
+        if let Some(ref choices) = option.choices {
+            if !choices.is_empty() && !choices.iter().any(|c| &c.value == v) {
+                return Err(AppError::BadRequest(format!(
+                    "option {} must be one of its choices",
+                    option.name
+                )));
+            }
+        }
+    }
+    for option in declared.iter().filter(|o| o.required.unwrap_or(false)) {
+        let supplied = values
+            .iter()
+            .any(|v| v.name == option.name && v.value.is_some());
+        if !supplied {
+            return Err(AppError::BadRequest(format!(
+                "missing required option: {}",
+                option.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+async fn register_command(
+    state: &AppState,
+    app: &Application,
+    space_id: Option<&str>,
+    input: &CreateCommand,
+) -> Result<Command, AppError> {
+    validate_command(input)?;
+    let existing = db::interactions::list_commands(&state.db, &app.id, space_id).await?;
+    if existing.len() >= MAX_COMMANDS_PER_SCOPE && !existing.iter().any(|c| c.name == input.name) {
+        return Err(AppError::BadRequest(format!(
+            "at most {MAX_COMMANDS_PER_SCOPE} commands per scope"
+        )));
+    }
+    db::interactions::upsert_command(&state.db, &app.id, space_id, input).await
+}
+
 pub async fn list_global_commands(
-    _state: State<AppState>,
-    Path(_app_id): Path<String>,
-    _auth: AuthUser,
+    state: State<AppState>,
+    Path(app_id): Path<String>,
+    auth: AuthUser,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    Ok(Json(serde_json::json!({ "data": [] })))
+    require_app_manager(&state, &app_id, &auth).await?;
+    let commands = db::interactions::list_commands(&state.db, &app_id, None).await?;
+    Ok(Json(serde_json::json!({ "data": commands })))
 }
 
 pub async fn create_global_command(
-    _state: State<AppState>,
-    Path(_app_id): Path<String>,
-    _auth: AuthUser,
-    Json(body): Json<serde_json::Value>,
+    state: State<AppState>,
+    Path(app_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<CreateCommand>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    Ok(Json(serde_json::json!({ "data": body })))
+    let app = require_app_manager(&state, &app_id, &auth).await?;
+    let command = register_command(&state, &app, None, &input).await?;
+    Ok(Json(serde_json::json!({ "data": command })))
 }
 
-pub async fn interaction_callback(
-    _state: State<AppState>,
-    Path((_interaction_id, _token)): Path<(String, String)>,
-    Json(_body): Json<serde_json::Value>,
+pub async fn list_app_space_commands(
+    state: State<AppState>,
+    Path((app_id, space_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_app_manager(&state, &app_id, &auth).await?;
+    let commands = db::interactions::list_commands(&state.db, &app_id, Some(&space_id)).await?;
+    Ok(Json(serde_json::json!({ "data": commands })))
+}
+
+pub async fn create_space_command(
+    state: State<AppState>,
+    Path((app_id, space_id)): Path<(String, String)>,
+    auth: AuthUser,
+    Json(input): Json<CreateCommand>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let app = require_app_manager(&state, &app_id, &auth).await?;
+    if !is_bot_in_space(&state, &app, &space_id).await {
+        return Err(AppError::BadRequest(
+            "the application's bot is not a member of this space".into(),
+        ));
+    }
+    let command = register_command(&state, &app, Some(&space_id), &input).await?;
+    Ok(Json(serde_json::json!({ "data": command })))
+}
+
+async fn get_app_command(
+    state: &AppState,
+    app_id: &str,
+    command_id: &str,
+) -> Result<Command, AppError> {
+    let command = db::interactions::get_command(&state.db, command_id).await?;
+    if command.application_id != app_id {
+        return Err(AppError::NotFound("unknown_command".to_string()));
+    }
+    Ok(command)
+}
+
+pub async fn get_command(
+    state: State<AppState>,
+    Path((app_id, command_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_app_manager(&state, &app_id, &auth).await?;
+    let command = get_app_command(&state, &app_id, &command_id).await?;
+    Ok(Json(serde_json::json!({ "data": command })))
+}
+
+pub async fn delete_command(
+    state: State<AppState>,
+    Path((app_id, command_id)): Path<(String, String)>,
+    auth: AuthUser,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    require_app_manager(&state, &app_id, &auth).await?;
+    get_app_command(&state, &app_id, &command_id).await?;
+    db::interactions::delete_command(&state.db, &command_id).await?;
     Ok(Json(serde_json::json!({ "data": null })))
 }
+
+/// `GET /spaces/{id}/commands` -- every command members can invoke in a
+/// space, for client autocomplete.
+pub async fn list_space_commands(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "view_channel").await?;
+    let commands = db::interactions::list_space_commands(&state.db, &space_id).await?;
+    Ok(Json(serde_json::json!({ "data": commands })))
+}
+
+/// `POST /interactions` -- invoke a command. The owning bot receives an
+/// `interaction.create` event carrying a token it answers with through the
+/// callback endpoint.
+pub async fn create_interaction(
+    state: State<AppState>,
+    auth: AuthUser,
+    Json(input): Json<InvokeCommand>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_id =
+        require_channel_permission(&state.db, &input.channel_id, &auth, "use_commands").await?;
+    if space_id.is_empty() {
+        return Err(AppError::BadRequest(
+            "commands can only be used in space channels".into(),
+        ));
+    }
+    let command = db::interactions::get_command(&state.db, &input.command_id).await?;
+    if command
+        .space_id
+        .as_deref()
+        .is_some_and(|sid| sid != space_id)
+    {
+        return Err(AppError::NotFound("unknown_command".to_string()));
+    }
+    let app = db::auth::get_application(&state.db, &command.application_id).await?;
+    let Some(bot_user_id) = app.bot_user_id.clone() else {
+        return Err(AppError::BadRequest("application has no bot user".into()));
+    };
+    if !is_bot_in_space(&state, &app, &space_id).await {
+        return Err(AppError::BadRequest(
+            "the application's bot is not a member of this space".into(),
+        ));
+    }
+    let options = input.options.unwrap_or_default();
+    validate_invocation(&command, &options)?;
+
+    let token = generate_token();
+    let row = db::interactions::create_interaction(
+        &state.db,
+        &command,
+        Some(&space_id),
+        &input.channel_id,
+        &auth.user_id,
+        &token,
+    )
+    .await?;
+
+    let interaction = Interaction {
+        id: row.id.clone(),
+        application_id: row.application_id.clone(),
+        interaction_type: "command".to_string(),
+        data: Some(InteractionData {
+            id: command.id.clone(),
+            name: command.name.clone(),
+            options: Some(options),
+        }),
+        space_id: Some(space_id),
+        channel_id: Some(row.channel_id.clone()),
+        member_id: Some(auth.user_id.clone()),
+        user_id: Some(auth.user_id.clone()),
+        token,
+        message: None,
+        locale: None,
+    };
+    if let Some(url) = app.interactions_endpoint_url.clone() {
+        // The endpoint answers in its response; nobody waits on it here.
+        let state = state.0.clone();
+        let row = row.clone();
+        tokio::spawn(async move {
+            if let Err(e) = deliver_over_http(&state, &url, &row, &interaction).await {
+                tracing::warn!("HTTP interaction {} failed: {e:?}", row.id);
+            }
+        });
+    } else if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: None,
+            target_user_ids: Some(vec![bot_user_id]),
+            event: serde_json::json!({
+                "op": 0,
+                "type": "interaction.create",
+                "data": interaction,
+            }),
+            intent: "interactions".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+
+    // The invoker gets the interaction without its token.
+    Ok(Json(serde_json::json!({
+        "data": {
+            "id": row.id,
+            "application_id": row.application_id,
+            "type": "command",
+            "command_id": row.command_id,
+            "space_id": row.space_id,
+            "channel_id": row.channel_id,
+            "user_id": row.user_id,
+            "created_at": row.created_at,
+        }
+    })))
+}
+
+/// POST `body` to an application's interactions endpoint, signed with its
+/// Ed25519 key and any live signing keys.
+pub(super) async fn post_to_endpoint(
+    state: &AppState,
+    application_id: &str,
+    url: &str,
+    body: &serde_json::Value,
+) -> Result<crate::safe_fetch::FetchedResponse, AppError> {
+    use crate::signing;
+
+    let body = body.to_string();
+    let timestamp = chrono::Utc::now().timestamp();
+    let private_key = db::auth::interaction_private_key(&state.db, application_id).await?;
+    let ed25519 = signing::ed25519_signature(&private_key, timestamp, body.as_bytes())
+        .ok_or_else(|| AppError::Internal("unreadable interaction key".into()))?;
+    let keys = db::signing_keys::live_signing_keys(&state.db, application_id).await?;
+    let mut headers = vec![
+        ("Content-Type", "application/json".to_string()),
+        (signing::ED25519_SIGNATURE_HEADER, ed25519),
+        (signing::ED25519_TIMESTAMP_HEADER, timestamp.to_string()),
+    ];
+    if !keys.is_empty() {
+        headers.push((signing::TIMESTAMP_HEADER, timestamp.to_string()));
+        headers.push((
+            signing::SIGNATURE_HEADER,
+            signing::signature_header(&keys, timestamp, body.as_bytes()),
+        ));
+    }
+    let opts = crate::safe_fetch::FetchOptions {
+        timeout: HTTP_INTERACTION_TIMEOUT,
+        ..Default::default()
+    };
+    crate::safe_fetch::request(
+        reqwest::Method::POST,
+        url,
+        &headers,
+        Some(body.as_bytes()),
+        &opts,
+    )
+    .await
+    .map_err(|e| AppError::BadRequest(format!("interactions endpoint: {e}")))
+}
+
+/// Send an interaction to the application's endpoint and apply the callback
+/// it answers with, as if it had been POSTed to the callback route.
+async fn deliver_over_http(
+    state: &AppState,
+    url: &str,
+    row: &InteractionRow,
+    interaction: &Interaction,
+) -> Result<(), AppError> {
+    let body = serde_json::to_value(interaction).unwrap_or_default();
+    let resp = post_to_endpoint(state, &row.application_id, url, &body).await?;
+    if !(200..300).contains(&resp.status) {
+        return Err(AppError::BadRequest(format!(
+            "interactions endpoint answered {}",
+            resp.status
+        )));
+    }
+    let callback: InteractionCallback = serde_json::from_slice(&resp.body)
+        .map_err(|e| AppError::BadRequest(format!("unreadable interaction response: {e}")))?;
+    apply_callback(state, row, callback).await?;
+    Ok(())
+}
+
+/// `POST /interactions/{id}/{token}/callback` -- the bot's answer. The token
+/// from `interaction.create` is the only credential.
+pub async fn interaction_callback(
+    state: State<AppState>,
+    Path((interaction_id, token)): Path<(String, String)>,
+    Json(input): Json<InteractionCallback>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let interaction =
+        db::interactions::get_interaction_with_token(&state.db, &interaction_id, &token).await?;
+    let cutoff = (chrono::Utc::now() - chrono::Duration::minutes(INTERACTION_TTL_MINUTES))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    if interaction.created_at < cutoff {
+        return Err(AppError::BadRequest("interaction_expired".into()));
+    }
+    Ok(Json(
+        serde_json::json!({ "data": apply_callback(&state, &interaction, input).await? }),
+    ))
+}
+
+/// Answer an interaction: acknowledge it (`deferred`, returning `null`) or
+/// post the response message and return it.
+async fn apply_callback(
+    state: &AppState,
+    interaction: &InteractionRow,
+    input: InteractionCallback,
+) -> Result<serde_json::Value, AppError> {
+    match input.callback_type.as_str() {
+        "deferred" => {
+            if !db::interactions::claim_response(&state.db, &interaction.id, "deferred").await? {
+                return Err(AppError::Conflict(
+                    "interaction_already_acknowledged".into(),
+                ));
+            }
+            Ok(serde_json::Value::Null)
+        }
+        "channel_message" => {
+            let mut data = input
+                .data
+                .ok_or_else(|| AppError::BadRequest("channel_message needs data".into()))?;
+            let has_embeds = data.embeds.as_ref().is_some_and(|e| !e.is_empty());
+            if data.content.trim().is_empty() && !has_embeds {
+                return Err(AppError::BadRequest(
+                    "responses need content or embeds".into(),
+                ));
+            }
+            if data.content.len() > 4000 {
+                return Err(AppError::BadRequest(
+                    "message content must be at most 4000 characters".into(),
+                ));
+            }
+            if let Some(ref mut embeds) = data.embeds {
+                validate_embeds(embeds).map_err(AppError::InvalidFields)?;
+            }
+            if let Some(ref space_id) = interaction.space_id {
+                require_not_archived(&state.db, space_id).await?;
+            }
+            let app = db::auth::get_application(&state.db, &interaction.application_id).await?;
+            let bot_user_id = app
+                .bot_user_id
+                .ok_or_else(|| AppError::BadRequest("application has no bot user".into()))?;
+            if !db::interactions::claim_response(&state.db, &interaction.id, "channel_message")
+                .await?
+            {
+                return Err(AppError::Conflict(
+                    "interaction_already_acknowledged".into(),
+                ));
+            }
+
+            let create = CreateMessage {
+                content: data.content,
+                tts: data.tts,
+                embeds: data.embeds,
+                reply_to: None,
+                thread_id: None,
+                title: None,
+                poll: None,
+                sticker_ids: None,
+            };
+
+            if data.ephemeral {
+                let msg = db::messages::create_ephemeral_message(
+                    &state.db,
+                    &interaction.channel_id,
+                    &bot_user_id,
+                    interaction.space_id.as_deref(),
+                    &interaction.user_id,
+                    &create,
+                    &crate::retention::ephemeral_expiry(),
+                )
+                .await?;
+                let json = super::messages::message_row_to_json(&msg);
+                if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+                    let _ = dispatcher.send(GatewayBroadcast {
+                        space_id: interaction.space_id.clone(),
+                        target_user_ids: Some(vec![interaction.user_id.clone()]),
+                        event: serde_json::json!({
+                            "op": 0,
+                            "type": "message.create",
+                            "data": json
+                        }),
+                        intent: "messages".to_string(),
+                        role_mention_user_ids: None,
+                    });
+                }
+                return Ok(json);
+            }
+
+            let msg = db::messages::create_message(
+                &state.db,
+                &interaction.channel_id,
+                &bot_user_id,
+                interaction.space_id.as_deref(),
+                &create,
+            )
+            .await?;
+
+            super::messages::apply_mention_counts(state, &msg).await;
+
+            let json = super::messages::message_row_to_json(&msg);
+            let role_mention_user_ids =
+                crate::gateway::events::resolve_role_mentions(&state.db, &json).await;
+            if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+                let _ = dispatcher.send(GatewayBroadcast {
+                    space_id: interaction.space_id.clone(),
+                    target_user_ids: None,
+                    event: serde_json::json!({
+                        "op": 0,
+                        "type": "message.create",
+                        "data": json
+                    }),
+                    intent: "messages".to_string(),
+                    role_mention_user_ids,
+                });
+            }
+
+            if let Err(e) = crate::federation::outbound::fanout_message_create(state, &msg).await {
+                tracing::warn!("federation fanout failed for message {}: {e}", msg.id);
+            }
+
+            Ok(json)
+        }
+        other => Err(AppError::BadRequest(format!(
+            "invalid callback type: {other}"
+        ))),
+    }
+}
@@ -34,8 +34,8 @@ pub async fn invite_page(
         return Err(AppError::NotFound("invalid invite code".to_string()));
     }
 
-    // Look up the invite — 404 if expired/missing
-    let invite = db::invites::get_invite(&state.db, &code).await?;
+    // Look up the invite — 404 if missing, 410 if expired
+    let invite = db::invites::get_live_invite(&state.db, &code).await?;
     let space = db::spaces::get_space_row(&state.db, &invite.space_id).await?;
 
     // Strip port from host for the daccord:// URI if it's the default
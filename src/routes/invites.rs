@@ -1,12 +1,18 @@
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::Json;
 
 use crate::db;
 use crate::error::AppError;
 use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::audit::AuditReason;
 use crate::middleware::auth::AuthUser;
-use crate::middleware::permissions::{require_channel_permission, require_permission};
-use crate::models::invite::CreateInvite;
+use crate::middleware::permissions::{
+    require_channel_permission, require_not_archived, require_permission,
+};
+use crate::models::invite::{
+    validate_vanity_code, AcceptInvite, CreateInvite, InviteStatsQuery, UpdateVanityUrl,
+    JOIN_TOKEN_TTL_SECS,
+};
 use crate::state::AppState;
 
 pub async fn get_invite(
@@ -15,10 +21,59 @@ pub async fn get_invite(
     _auth: AuthUser,
 ) -> Result<Json<serde_json::Value>, AppError> {
     // get_invite is accessible to any authenticated user (they need the code to look it up)
-    let invite = db::invites::get_invite(&state.db, &code).await?;
+    let invite = db::invites::get_live_invite(&state.db, &code).await?;
     Ok(Json(serde_json::json!({ "data": invite })))
 }
 
+/// GET /invites/{code}/preview
+///
+/// What accepting would join: the space, its welcome screen and the rules
+/// member screening will ask for, plus a `join_token` the caller can accept
+/// with for the next [JOIN_TOKEN_TTL_SECS]. Previewing doesn't use the
+/// invite.
+pub async fn preview_invite(
+    state: State<AppState>,
+    Path(code): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let invite = db::invites::get_live_invite(&state.db, &code).await?;
+    let space = db::spaces::get_space_row(&state.db, &invite.space_id).await?;
+    let member_count = db::members::count_members_matching(&state.db, &space.id, None).await?;
+    let welcome_screen = db::screening::get_welcome_screen(&state.db, &space.id).await?;
+    let screening = db::screening::get_member_screening(&state.db, &space.id).await?;
+    let already_member = db::members::get_member_row(&state.db, &space.id, &auth.user_id)
+        .await
+        .is_ok();
+    let (join_token, expires_at) = db::invites::create_join_token(
+        &state.db,
+        &invite.code,
+        &space.id,
+        &auth.user_id,
+        JOIN_TOKEN_TTL_SECS,
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "data": {
+            "invite": invite,
+            "space": {
+                "id": space.id,
+                "name": space.name,
+                "description": space.description,
+                "icon": space.icon,
+                "banner": space.banner,
+                "splash": space.splash,
+                "member_count": member_count,
+            },
+            "welcome_screen": welcome_screen.enabled.then_some(welcome_screen),
+            "member_screening": screening.enabled.then_some(screening),
+            "already_member": already_member,
+            "join_token": join_token,
+            "join_token_expires_at": expires_at,
+        }
+    })))
+}
+
 pub async fn delete_invite(
     state: State<AppState>,
     Path(code): Path<String>,
@@ -30,11 +85,50 @@ pub async fn delete_invite(
     Ok(Json(serde_json::json!({ "data": null })))
 }
 
+/// POST /invites/{code}/accept
+///
+/// Joins straight away unless the space has `invite_preview_required`, in
+/// which case the body needs a `join_token` from [preview_invite]. With a
+/// token, `accept_screening` also accepts the space's member screening.
 pub async fn accept_invite(
     state: State<AppState>,
     Path(code): Path<String>,
     auth: AuthUser,
+    body: Option<Json<AcceptInvite>>,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    let input = body.map(|Json(b)| b).unwrap_or_default();
+    if input.accept_screening && input.join_token.is_none() {
+        return Err(AppError::BadRequest(
+            "accept_screening needs a join_token from the invite preview".into(),
+        ));
+    }
+
+    // Checked before the invite is used so a refused join doesn't spend it.
+    crate::blocklist::check_user(&state.db, &auth.user_id).await?;
+    let live = db::invites::get_live_invite(&state.db, &code).await?;
+    require_not_archived(&state.db, &live.space_id).await?;
+    crate::raid::check_join(&state, &live.space_id, &auth.user_id).await?;
+    crate::email_domains::check_join(&state.db, &live.space_id, &auth).await?;
+    match input.join_token.as_deref() {
+        Some(token) => {
+            if !db::invites::consume_join_token(&state.db, token, &live.code, &auth.user_id).await?
+            {
+                return Err(AppError::ForbiddenCode(
+                    "invalid_join_token",
+                    "join token is invalid or has expired; preview the invite again".into(),
+                ));
+            }
+        }
+        None => {
+            let space = db::spaces::get_space_row(&state.db, &live.space_id).await?;
+            if space.invite_preview_required {
+                return Err(AppError::ForbiddenCode(
+                    "join_token_required",
+                    "preview this invite before accepting it".into(),
+                ));
+            }
+        }
+    }
     let invite = db::invites::use_invite(&state.db, &code).await?;
 
     // Check if the user is banned from this space
@@ -55,7 +149,17 @@ pub async fn accept_invite(
     )
     .await?;
 
+    if newly_added && input.accept_screening {
+        db::screening::clear_pending(&state.db, &invite.space_id, &auth.user_id).await?;
+    }
+
     if newly_added {
+        db::invites::record_invite_use(&state.db, &invite.space_id, &invite.code, &auth.user_id)
+            .await?;
+        if let Err(e) = crate::raid::record_join(&state, &invite.space_id).await {
+            tracing::warn!("raid check failed for space {}: {e:?}", invite.space_id);
+        }
+
         // Broadcast member.join to the space
         let user = db::users::get_user(&state.db, &auth.user_id).await?;
         if let Some(ref dispatcher) = *state.gateway_tx.read().await {
@@ -73,6 +177,7 @@ pub async fn accept_invite(
                 target_user_ids: None,
                 event,
                 intent: "members".to_string(),
+                role_mention_user_ids: None,
             });
         }
 
@@ -173,3 +278,130 @@ pub async fn create_space_invite(
         db::invites::create_invite(&state.db, &space_id, None, &auth.user_id, &input).await?;
     Ok(Json(serde_json::json!({ "data": invite })))
 }
+
+/// How many recent joins [get_invite_stats] lists.
+const INVITE_STATS_RECENT_JOINS: i64 = 50;
+
+/// GET /spaces/{space_id}/invites/{code}/stats
+///
+/// Who joined through an invite and when, for tracing where a wave of
+/// joins came from. Stats outlive the invite itself, so deleted and
+/// expired codes can still be looked up.
+pub async fn get_invite_stats(
+    state: State<AppState>,
+    Path((space_id, code)): Path<(String, String)>,
+    auth: AuthUser,
+    Query(query): Query<InviteStatsQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_channels").await?;
+    let (uses, members_remaining) =
+        db::invites::count_invite_uses(&state.db, &space_id, &code).await?;
+    let invite = match db::invites::get_invite(&state.db, &code).await {
+        Ok(invite) => Some(invite).filter(|i| i.space_id == space_id),
+        Err(AppError::NotFound(_)) => None,
+        Err(e) => return Err(e),
+    };
+    if invite.is_none() && uses == 0 {
+        let space = db::spaces::get_space_row(&state.db, &space_id).await?;
+        if space.vanity_url_code.as_deref() != Some(code.as_str()) {
+            return Err(AppError::NotFound("invite not found".to_string()));
+        }
+    }
+
+    let days = query.days.unwrap_or(30).clamp(1, 90);
+    let since = (chrono::Utc::now() - chrono::Duration::days(days - 1))
+        .format("%Y-%m-%d")
+        .to_string();
+    let uses_over_time =
+        db::invites::invite_uses_by_day(&state.db, &space_id, &code, &since).await?;
+    let recent_joins =
+        db::invites::list_invite_joins(&state.db, &space_id, &code, INVITE_STATS_RECENT_JOINS)
+            .await?;
+
+    Ok(Json(serde_json::json!({
+        "data": {
+            "code": code,
+            "inviter_id": invite.as_ref().and_then(|i| i.inviter_id.clone()),
+            "uses": uses,
+            "members_remaining": members_remaining,
+            "uses_over_time": uses_over_time,
+            "recent_joins": recent_joins
+        }
+    })))
+}
+
+/// GET /spaces/{space_id}/vanity-url
+pub async fn get_vanity_url(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let space = db::spaces::get_space_row(&state.db, &space_id).await?;
+    let uses = match space.vanity_url_code {
+        Some(ref code) => db::invites::get_vanity_invite(&state.db, code)
+            .await?
+            .map_or(0, |i| i.uses),
+        None => 0,
+    };
+    Ok(Json(serde_json::json!({
+        "data": { "code": space.vanity_url_code, "uses": uses }
+    })))
+}
+
+/// PATCH /spaces/{space_id}/vanity-url
+///
+/// Claims `code` as the space's vanity invite, usable anywhere an invite
+/// code is (`/invites/{code}`), or releases it with `null`. Codes already
+/// used by an invite or another space are 409 `vanity_code_taken`; codes
+/// revoked by an instance admin are 403 `vanity_code_revoked`.
+pub async fn update_vanity_url(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    audit_reason: AuditReason,
+    Json(input): Json<UpdateVanityUrl>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let space = db::spaces::get_space_row(&state.db, &space_id).await?;
+    let code = input
+        .code
+        .map(|c| c.trim().to_lowercase())
+        .filter(|c| !c.is_empty());
+    if code == space.vanity_url_code {
+        return get_vanity_url(state, Path(space_id), auth).await;
+    }
+    if let Some(ref code) = code {
+        validate_vanity_code(code).map_err(AppError::BadRequest)?;
+        if db::invites::is_vanity_code_revoked(&state.db, code).await? {
+            return Err(AppError::ForbiddenCode(
+                "vanity_code_revoked",
+                "that vanity code has been revoked by the instance".to_string(),
+            ));
+        }
+        if db::invites::vanity_code_taken(&state.db, code, &space_id).await? {
+            return Err(AppError::ConflictCode(
+                "vanity_code_taken",
+                "that vanity code is taken".to_string(),
+            ));
+        }
+    }
+    db::invites::set_vanity_code(&state.db, &space_id, code.as_deref()).await?;
+
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "vanity_url_update",
+        None,
+        audit_reason.as_deref(),
+        Some(serde_json::json!({
+            "vanity_url_code": { "old": space.vanity_url_code, "new": code }
+        })),
+    )
+    .await;
+
+    Ok(Json(serde_json::json!({
+        "data": { "code": code, "uses": 0 }
+    })))
+}
@@ -7,9 +7,11 @@ use serde::Deserialize;
 use crate::db;
 use crate::error::AppError;
 use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::audit::AuditReason;
 use crate::middleware::auth::AuthUser;
 use crate::middleware::permissions::{
-    require_hierarchy, require_membership, require_permission, require_role_hierarchy,
+    require_hierarchy, require_membership, require_permission, require_recent_mfa,
+    require_role_hierarchy,
 };
 use crate::models::member::{MemberRow, UpdateMember};
 use crate::models::user::PublicUser;
@@ -86,10 +88,82 @@ pub async fn list_members(
 
     let user_json = resolve_member_users(&state, &rows, params.with_user).await?;
 
+    let user_ids: Vec<String> = rows.iter().map(|r| r.user_id.clone()).collect();
+    let role_ids = db::members::get_role_ids_for_members(&state.db, &space_id, &user_ids).await?;
+
+    let show_source_invite = can_see_source_invite(&state, &space_id, &auth).await;
+    let mut members = Vec::new();
+    for row in &rows {
+        let mut member = member_row_to_json(
+            row,
+            role_ids.get(&row.user_id).map(Vec::as_slice).unwrap_or(&[]),
+        );
+        member["source_invite"] =
+            serde_json::json!(row.source_invite.as_deref().filter(|_| show_source_invite));
+        if let Some(user) = user_json.get(&row.user_id) {
+            member["user"] = user.clone();
+        }
+        members.push(member);
+    }
+
+    let last_id = rows.last().map(|m| m.user_id.clone());
+    let mut response = serde_json::json!({ "data": members });
+    if has_more {
+        response["cursor"] = serde_json::json!({
+            "after": last_id.unwrap_or_default(),
+            "has_more": has_more
+        });
+    }
+    Ok(Json(response))
+}
+
+/// Members holding a role, paginated like [list_members]. @everyone is
+/// everyone, so it's rejected in favour of the member list itself.
+pub async fn list_role_members(
+    state: State<AppState>,
+    Path((space_id, role_id)): Path<(String, String)>,
+    auth: AuthUser,
+    Query(params): Query<ListMembersQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_membership(&state.db, &space_id, &auth.user_id).await?;
+    let role = db::roles::get_role_row(&state.db, &role_id).await?;
+    if role.space_id != space_id {
+        return Err(AppError::NotFound("role not found in this space".into()));
+    }
+    if role.position == 0 {
+        return Err(AppError::BadRequest(
+            "every member holds @everyone; list /spaces/{space_id}/members instead".into(),
+        ));
+    }
+    let limit = params.limit.unwrap_or(50).min(1000);
+    let mut rows = db::members::list_members_with_role(
+        &state.db,
+        &space_id,
+        &role_id,
+        params.after.as_deref(),
+        limit,
+    )
+    .await?;
+
+    let has_more = rows.len() as i64 > limit;
+    if has_more {
+        rows.truncate(limit as usize);
+    }
+
+    let user_json = resolve_member_users(&state, &rows, params.with_user).await?;
+
+    let user_ids: Vec<String> = rows.iter().map(|r| r.user_id.clone()).collect();
+    let role_ids = db::members::get_role_ids_for_members(&state.db, &space_id, &user_ids).await?;
+
+    let show_source_invite = can_see_source_invite(&state, &space_id, &auth).await;
     let mut members = Vec::new();
     for row in &rows {
-        let role_ids = db::members::get_member_role_ids(&state.db, &space_id, &row.user_id).await?;
-        let mut member = member_row_to_json(row, &role_ids);
+        let mut member = member_row_to_json(
+            row,
+            role_ids.get(&row.user_id).map(Vec::as_slice).unwrap_or(&[]),
+        );
+        member["source_invite"] =
+            serde_json::json!(row.source_invite.as_deref().filter(|_| show_source_invite));
         if let Some(user) = user_json.get(&row.user_id) {
             member["user"] = user.clone();
         }
@@ -119,10 +193,18 @@ pub async fn search_members(
 
     let user_json = resolve_member_users(&state, &rows, params.with_user).await?;
 
+    let user_ids: Vec<String> = rows.iter().map(|r| r.user_id.clone()).collect();
+    let role_ids = db::members::get_role_ids_for_members(&state.db, &space_id, &user_ids).await?;
+
+    let show_source_invite = can_see_source_invite(&state, &space_id, &auth).await;
     let mut members = Vec::new();
     for row in &rows {
-        let role_ids = db::members::get_member_role_ids(&state.db, &space_id, &row.user_id).await?;
-        let mut member = member_row_to_json(row, &role_ids);
+        let mut member = member_row_to_json(
+            row,
+            role_ids.get(&row.user_id).map(Vec::as_slice).unwrap_or(&[]),
+        );
+        member["source_invite"] =
+            serde_json::json!(row.source_invite.as_deref().filter(|_| show_source_invite));
         if let Some(user) = user_json.get(&row.user_id) {
             member["user"] = user.clone();
         }
@@ -140,15 +222,26 @@ pub async fn get_member(
     require_membership(&state.db, &space_id, &auth.user_id).await?;
     let row = db::members::get_member_row(&state.db, &space_id, &user_id).await?;
     let role_ids = db::members::get_member_role_ids(&state.db, &space_id, &user_id).await?;
-    Ok(Json(
-        serde_json::json!({ "data": member_row_to_json(&row, &role_ids) }),
-    ))
+    let show_source_invite = can_see_source_invite(&state, &space_id, &auth).await;
+    let mut member = member_row_to_json(&row, &role_ids);
+    member["source_invite"] =
+        serde_json::json!(row.source_invite.as_deref().filter(|_| show_source_invite));
+    Ok(Json(serde_json::json!({ "data": member })))
+}
+
+/// Which invite a member joined through is shown to those who can see the
+/// space's invites (`manage_channels`); it's null for everyone else.
+async fn can_see_source_invite(state: &AppState, space_id: &str, auth: &AuthUser) -> bool {
+    require_permission(&state.db, space_id, auth, "manage_channels")
+        .await
+        .is_ok()
 }
 
 pub async fn update_member(
     state: State<AppState>,
     Path((space_id, user_id)): Path<(String, String)>,
     auth: AuthUser,
+    audit_reason: AuditReason,
     Json(mut input): Json<UpdateMember>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     // Nickname changes require manage_nicknames
@@ -161,6 +254,16 @@ pub async fn update_member(
         require_permission(&state.db, &space_id, &auth, "manage_nicknames").await?;
     }
 
+    // So do profile fields, e.g. to clear abusive pronouns
+    if input.banner.is_some()
+        || input.bio.is_some()
+        || input.pronouns.is_some()
+        || input.timezone.is_some()
+    {
+        require_permission(&state.db, &space_id, &auth, "manage_nicknames").await?;
+        validate_profile_fields(&input)?;
+    }
+
     // Role changes require manage_roles + hierarchy checks
     if let Some(ref roles) = input.roles {
         require_permission(&state.db, &space_id, &auth, "manage_roles").await?;
@@ -203,39 +306,29 @@ pub async fn update_member(
         }
     }
 
-    let max_avatar_size = state.settings.load().max_avatar_size as usize;
+    let before = db::members::get_member_row(&state.db, &space_id, &user_id).await?;
+    let before_roles = db::members::get_member_role_ids(&state.db, &space_id, &user_id).await?;
+    let before_json = member_row_to_json(&before, &before_roles);
 
-    // Process avatar data URI
-    let entity_id = format!("{}_{}", space_id, user_id);
-    if let Some(ref avatar) = input.avatar {
-        if avatar.starts_with("data:") {
-            let old_member = db::members::get_member_row(&state.db, &space_id, &user_id).await?;
-            if let Some(ref old_avatar) = old_member.avatar {
-                let _ = storage::delete_file(&state.storage_path, old_avatar).await;
-            }
-            let (url, _, _, _) = storage::save_avatar_image(
-                &state.storage_path,
-                "avatars",
-                &entity_id,
-                avatar,
-                max_avatar_size,
-            )
-            .await?;
-            input.avatar = Some(url);
-        } else if avatar.is_empty() {
-            let old_member = db::members::get_member_row(&state.db, &space_id, &user_id).await?;
-            if let Some(ref old_avatar) = old_member.avatar {
-                let _ = storage::delete_file(&state.storage_path, old_avatar).await;
-            }
-            storage::delete_avatar(&state.storage_path, "avatars", &entity_id).await?;
-            // Keep as Some("") — DB layer will treat empty string as NULL
-        }
-    }
+    store_member_images(&state, &auth.user_id, &space_id, &user_id, &mut input).await?;
 
     let row = db::members::update_member(&state.db, &space_id, &user_id, &input).await?;
     let role_ids = db::members::get_member_role_ids(&state.db, &space_id, &user_id).await?;
     let member_json = member_row_to_json(&row, &role_ids);
 
+    if let Some(changes) = super::audit_log::diff(&before_json, &member_json) {
+        super::audit_log::record(
+            &state,
+            &space_id,
+            &auth.user_id,
+            "member_update",
+            Some((&user_id, "user")),
+            audit_reason.as_deref(),
+            Some(changes),
+        )
+        .await;
+    }
+
     // Broadcast member.update to the space
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
         let event = serde_json::json!({
@@ -244,13 +337,38 @@ pub async fn update_member(
             "data": member_json
         });
         let _ = dispatcher.send(GatewayBroadcast {
-            space_id: Some(space_id),
+            space_id: Some(space_id.clone()),
             target_user_ids: None,
             event,
             intent: "members".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
+    // A server mute or deafen takes effect on the call they're in right away.
+    if row.mute != before.mute || row.deaf != before.deaf {
+        let in_space = crate::voice::state::get_user_voice_state(&state, &user_id)
+            .filter(|vs| vs.space_id.as_deref() == Some(space_id.as_str()))
+            .and_then(|vs| vs.channel_id);
+        if let Some(channel_id) = in_space {
+            if let Some(voice_state) =
+                crate::voice::state::modify_in_channel(&state, &user_id, &channel_id, |vs| {
+                    vs.mute = row.mute;
+                    vs.deaf = row.deaf;
+                })
+            {
+                super::voice::apply_voice_change(
+                    &state,
+                    &channel_id,
+                    &space_id,
+                    true,
+                    &voice_state,
+                )
+                .await;
+            }
+        }
+    }
+
     Ok(Json(serde_json::json!({ "data": member_json })))
 }
 
@@ -258,9 +376,11 @@ pub async fn kick_member(
     state: State<AppState>,
     Path((space_id, user_id)): Path<(String, String)>,
     auth: AuthUser,
+    audit_reason: AuditReason,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "kick_members").await?;
     require_hierarchy(&state.db, &space_id, &auth, &user_id).await?;
+    require_recent_mfa(&state.db, &space_id, &auth).await?;
 
     // Capture interested peers BEFORE removal: once the kicked member's row is
     // gone, their home server may no longer appear in the interested set and
@@ -270,6 +390,16 @@ pub async fn kick_member(
         .unwrap_or_default();
 
     db::members::remove_member(&state.db, &space_id, &user_id).await?;
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "member_kick",
+        Some((&user_id, "user")),
+        audit_reason.as_deref(),
+        None,
+    )
+    .await;
 
     // Broadcast member.leave to the space
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
@@ -286,6 +416,7 @@ pub async fn kick_member(
             target_user_ids: None,
             event,
             intent: "members".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -347,6 +478,7 @@ pub async fn leave_space(
                 target_user_ids: None,
                 event,
                 intent: "members".to_string(),
+                role_mention_user_ids: None,
             });
         }
         return Ok(Json(serde_json::json!({ "data": null })));
@@ -379,6 +511,7 @@ pub async fn leave_space(
             target_user_ids: None,
             event,
             intent: "members".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -405,41 +538,17 @@ pub async fn update_own_member(
     Json(mut input): Json<UpdateMember>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "change_nickname").await?;
+    validate_profile_fields(&input)?;
 
-    let max_avatar_size = state.settings.load().max_avatar_size as usize;
-
-    // Process avatar data URI for self
-    let entity_id = format!("{}_{}", space_id, auth.user_id);
-    if let Some(ref avatar) = input.avatar {
-        if avatar.starts_with("data:") {
-            let old_member =
-                db::members::get_member_row(&state.db, &space_id, &auth.user_id).await?;
-            if let Some(ref old_avatar) = old_member.avatar {
-                let _ = storage::delete_file(&state.storage_path, old_avatar).await;
-            }
-            let (url, _, _, _) = storage::save_avatar_image(
-                &state.storage_path,
-                "avatars",
-                &entity_id,
-                avatar,
-                max_avatar_size,
-            )
-            .await?;
-            input.avatar = Some(url);
-        } else if avatar.is_empty() {
-            let old_member =
-                db::members::get_member_row(&state.db, &space_id, &auth.user_id).await?;
-            if let Some(ref old_avatar) = old_member.avatar {
-                let _ = storage::delete_file(&state.storage_path, old_avatar).await;
-            }
-            storage::delete_avatar(&state.storage_path, "avatars", &entity_id).await?;
-            // Keep as Some("") — DB layer will treat empty string as NULL
-        }
-    }
+    store_member_images(&state, &auth.user_id, &space_id, &auth.user_id, &mut input).await?;
 
     let limited = UpdateMember {
         nickname: input.nickname,
         avatar: input.avatar,
+        banner: input.banner,
+        bio: input.bio,
+        pronouns: input.pronouns,
+        timezone: input.timezone,
         roles: None,
         mute: None,
         deaf: None,
@@ -462,6 +571,7 @@ pub async fn update_own_member(
             target_user_ids: None,
             event,
             intent: "members".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -472,6 +582,7 @@ pub async fn add_role(
     state: State<AppState>,
     Path((space_id, user_id, role_id)): Path<(String, String, String)>,
     auth: AuthUser,
+    audit_reason: AuditReason,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "manage_roles").await?;
     let role = db::roles::get_role_row(&state.db, &role_id).await?;
@@ -487,6 +598,16 @@ pub async fn add_role(
         state.db_is_postgres,
     )
     .await?;
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "member_role_update",
+        Some((&user_id, "user")),
+        audit_reason.as_deref(),
+        Some(serde_json::json!({ "$add": [&role_id] })),
+    )
+    .await;
 
     // Broadcast member.update to the space
     let row = db::members::get_member_row(&state.db, &space_id, &user_id).await?;
@@ -502,6 +623,7 @@ pub async fn add_role(
             target_user_ids: None,
             event,
             intent: "members".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -512,6 +634,7 @@ pub async fn remove_role(
     state: State<AppState>,
     Path((space_id, user_id, role_id)): Path<(String, String, String)>,
     auth: AuthUser,
+    audit_reason: AuditReason,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "manage_roles").await?;
     let role = db::roles::get_role_row(&state.db, &role_id).await?;
@@ -520,6 +643,16 @@ pub async fn remove_role(
     }
     require_role_hierarchy(&state.db, &space_id, &auth.user_id, role.position).await?;
     db::members::remove_role_from_member(&state.db, &space_id, &user_id, &role_id).await?;
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "member_role_update",
+        Some((&user_id, "user")),
+        audit_reason.as_deref(),
+        Some(serde_json::json!({ "$remove": [&role_id] })),
+    )
+    .await;
 
     // Broadcast member.update to the space
     let row = db::members::get_member_row(&state.db, &space_id, &user_id).await?;
@@ -535,18 +668,91 @@ pub async fn remove_role(
             target_user_ids: None,
             event,
             intent: "members".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
     Ok(Json(serde_json::json!({ "data": null })))
 }
 
+/// Upload or remove the member's per-space avatar and banner, the same way
+/// (and with the same size limit) as the account's own. Data URIs in
+/// `input` are replaced by the stored image's URL; an empty string deletes
+/// the current one and stays empty so the DB layer clears the column.
+async fn store_member_images(
+    state: &AppState,
+    uploader_id: &str,
+    space_id: &str,
+    user_id: &str,
+    input: &mut UpdateMember,
+) -> Result<(), AppError> {
+    if input.avatar.is_none() && input.banner.is_none() {
+        return Ok(());
+    }
+    let member = db::members::get_member_row(&state.db, space_id, user_id).await?;
+    let max_avatar_size = state.settings.load().max_avatar_size as usize;
+    let entity_id = format!("{space_id}_{user_id}");
+    for (folder, value, old) in [
+        ("avatars", &mut input.avatar, &member.avatar),
+        ("banners", &mut input.banner, &member.banner),
+    ] {
+        let Some(image) = value.as_deref() else {
+            continue;
+        };
+        if image.starts_with("data:") {
+            crate::blocklist::check_data_uri_upload(&state.db, uploader_id, image).await?;
+            if let Some(old) = old {
+                let _ = storage::delete_file(state.storage.as_ref(), old).await;
+            }
+            let (url, _, _, _) = storage::save_avatar_image(
+                state.storage.as_ref(),
+                folder,
+                &entity_id,
+                image,
+                max_avatar_size,
+            )
+            .await?;
+            *value = Some(url);
+        } else if image.is_empty() {
+            if let Some(old) = old {
+                let _ = storage::delete_file(state.storage.as_ref(), old).await;
+            }
+            storage::delete_avatar(state.storage.as_ref(), folder, &entity_id).await?;
+        }
+    }
+    Ok(())
+}
+
+fn validate_profile_fields(input: &UpdateMember) -> Result<(), AppError> {
+    if let Some(ref bio) = input.bio {
+        if bio.len() > 2000 {
+            return Err(AppError::BadRequest(
+                "bio must be at most 2000 characters".into(),
+            ));
+        }
+    }
+    if let Some(ref pronouns) = input.pronouns {
+        crate::profile::validate_pronouns(pronouns)?;
+    }
+    if let Some(ref timezone) = input.timezone {
+        crate::profile::validate_timezone(timezone)?;
+    }
+    Ok(())
+}
+
+/// `avatar`, `banner`, `bio`, `pronouns` and `timezone` are this space's
+/// overrides; clients fall back to the user's own when they're null.
 pub fn member_row_to_json(row: &MemberRow, role_ids: &[String]) -> serde_json::Value {
     serde_json::json!({
         "user_id": row.user_id,
         "space_id": row.space_id,
         "nickname": row.nickname,
         "avatar": row.avatar,
+        "banner": row.banner,
+        "bio": row.bio,
+        "pronouns": row.pronouns,
+        "timezone": row.timezone,
+        "utc_offset_minutes": crate::profile::utc_offset_minutes(row.timezone.as_deref()),
         "roles": role_ids,
         "joined_at": row.joined_at,
         "premium_since": row.premium_since,
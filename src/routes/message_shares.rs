@@ -0,0 +1,215 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::db;
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::{require_channel_membership, require_channel_permission};
+use crate::models::message::{MessageRow, MessageShare};
+use crate::share_links;
+use crate::state::AppState;
+
+fn sharing_disabled() -> AppError {
+    AppError::ForbiddenCode(
+        "sharing_disabled",
+        "this space doesn't allow sharing messages".into(),
+    )
+}
+
+fn unknown_share() -> AppError {
+    AppError::NotFound("unknown_share".to_string())
+}
+
+/// Loads a message, checking it belongs to `channel_id`.
+async fn channel_message(
+    state: &AppState,
+    channel_id: &str,
+    message_id: &str,
+) -> Result<MessageRow, AppError> {
+    let msg = db::messages::get_message_row(&state.db, message_id).await?;
+    if msg.channel_id != channel_id {
+        return Err(AppError::NotFound("unknown_message".to_string()));
+    }
+    Ok(msg)
+}
+
+fn with_token(key: &[u8], mut share: MessageShare) -> MessageShare {
+    share.token = Some(share_links::sign(
+        key,
+        &share.id,
+        &share.channel_id,
+        &share.message_id,
+    ));
+    share
+}
+
+/// POST /channels/{channel_id}/messages/{message_id}/share
+///
+/// Makes a public link to a message anyone who can read it may hand out,
+/// if its space allows sharing. Asking again returns the caller's existing
+/// link.
+pub async fn create_share(
+    state: State<AppState>,
+    Path((channel_id, message_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let msg = channel_message(&state, &channel_id, &message_id).await?;
+    let space_id = msg
+        .space_id
+        .clone()
+        .ok_or_else(|| AppError::BadRequest("only messages in spaces can be shared".to_string()))?;
+    require_channel_permission(&state.db, &channel_id, &auth, "read_history").await?;
+    if auth.is_guest {
+        return Err(AppError::Forbidden(
+            "guest accounts cannot share messages".into(),
+        ));
+    }
+    if !db::spaces::get_space_row(&state.db, &space_id)
+        .await?
+        .message_sharing
+    {
+        return Err(sharing_disabled());
+    }
+
+    let share = match db::message_shares::find_share(&state.db, &message_id, &auth.user_id).await? {
+        Some(share) => share,
+        None => {
+            db::message_shares::create_share(&state.db, &message_id, &channel_id, &auth.user_id)
+                .await?
+        }
+    };
+    let key = db::settings::share_link_key(&state.db).await?;
+    Ok(Json(serde_json::json!({ "data": with_token(&key, share) })))
+}
+
+/// Whether `auth` may see and revoke every link to `msg`, rather than only
+/// their own: its author and message managers can.
+async fn manages_shares(
+    state: &AppState,
+    msg: &MessageRow,
+    auth: &AuthUser,
+) -> Result<bool, AppError> {
+    if msg.author_id == auth.user_id {
+        return Ok(true);
+    }
+    Ok(
+        require_channel_permission(&state.db, &msg.channel_id, auth, "manage_messages")
+            .await
+            .is_ok(),
+    )
+}
+
+/// GET /channels/{channel_id}/messages/{message_id}/shares
+///
+/// The message's live links: all of them for its author and message
+/// managers, otherwise the caller's own.
+pub async fn list_shares(
+    state: State<AppState>,
+    Path((channel_id, message_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let msg = channel_message(&state, &channel_id, &message_id).await?;
+    require_channel_permission(&state.db, &channel_id, &auth, "read_history").await?;
+    let all = manages_shares(&state, &msg, &auth).await?;
+    let key = db::settings::share_link_key(&state.db).await?;
+    let shares: Vec<MessageShare> = db::message_shares::list_shares(&state.db, &message_id)
+        .await?
+        .into_iter()
+        .filter(|s| all || s.creator_id == auth.user_id)
+        .map(|s| with_token(&key, s))
+        .collect();
+    Ok(Json(serde_json::json!({ "data": shares })))
+}
+
+/// DELETE /channels/{channel_id}/messages/{message_id}/shares/{share_id}
+///
+/// Revokes a link. Its creator, the message's author and message managers
+/// may do this.
+pub async fn revoke_share(
+    state: State<AppState>,
+    Path((channel_id, message_id, share_id)): Path<(String, String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let msg = channel_message(&state, &channel_id, &message_id).await?;
+    let share = db::message_shares::get_share(&state.db, &share_id)
+        .await?
+        .filter(|s| s.message_id == message_id)
+        .ok_or_else(unknown_share)?;
+    if share.creator_id != auth.user_id && !manages_shares(&state, &msg, &auth).await? {
+        return Err(AppError::Forbidden(
+            "only the link's creator, the message's author or moderators can revoke it".into(),
+        ));
+    }
+    db::message_shares::delete_share(&state.db, &share_id).await?;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// GET /shared-messages/{token}
+///
+/// Renders a shared message for anyone holding the link, without
+/// authentication: sanitized content, attachments, and how the author and
+/// the space appear. The link stops working once revoked, once the space
+/// turns sharing off, or once its creator can no longer read the channel.
+pub async fn get_shared_message(
+    state: State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let key = db::settings::share_link_key(&state.db).await?;
+    let shared = share_links::verify(&key, &token).ok_or_else(unknown_share)?;
+    let share = db::message_shares::get_share(&state.db, &shared.share_id)
+        .await?
+        .filter(|s| s.message_id == shared.message_id && s.channel_id == shared.channel_id)
+        .ok_or_else(unknown_share)?;
+
+    let msg = channel_message(&state, &share.channel_id, &share.message_id).await?;
+    let space_id = msg.space_id.clone().ok_or_else(unknown_share)?;
+    let space = db::spaces::get_space_row(&state.db, &space_id).await?;
+    if !space.message_sharing {
+        return Err(sharing_disabled());
+    }
+    if require_channel_membership(&state.db, &share.channel_id, &share.creator_id)
+        .await
+        .is_err()
+    {
+        return Err(unknown_share());
+    }
+    let channel = db::channels::get_channel_row(&state.db, &share.channel_id).await?;
+
+    let author = match msg.webhook_id {
+        Some(_) => serde_json::json!({
+            "id": null,
+            "display_name": msg.webhook_username,
+            "avatar": msg.webhook_avatar,
+            "bot": true
+        }),
+        None => {
+            let user = db::users::get_user(&state.db, &msg.author_id).await?;
+            let member = db::members::get_member_row(&state.db, &space_id, &msg.author_id)
+                .await
+                .ok();
+            let nickname = member.as_ref().and_then(|m| m.nickname.clone());
+            serde_json::json!({
+                "id": user.id,
+                "username": user.username,
+                "display_name": nickname.or(user.display_name).unwrap_or_else(|| user.username.clone()),
+                "avatar": member.and_then(|m| m.avatar).or(user.avatar),
+                "bot": user.bot
+            })
+        }
+    };
+    let attachments = db::attachments::get_attachments_for_message(&state.db, &msg.id).await?;
+
+    Ok(Json(serde_json::json!({
+        "data": {
+            "id": msg.id,
+            "content": share_links::sanitize_content(&msg.content),
+            "title": msg.title.as_deref().map(share_links::sanitize_content),
+            "timestamp": msg.created_at,
+            "edited_at": msg.edited_at,
+            "attachments": attachments,
+            "author": author,
+            "channel": { "id": channel.id, "name": channel.name },
+            "space": { "id": space.id, "name": space.name, "icon": space.icon }
+        }
+    })))
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use axum::extract::{Multipart, Path, Query, State};
 use axum::Json;
 use serde::Deserialize;
@@ -5,13 +7,21 @@ use serde::Deserialize;
 use crate::db;
 use crate::db::messages::ReactionAggregate;
 use crate::error::AppError;
+use crate::middleware::audit::AuditReason;
 use crate::middleware::auth::{AuthUser, OptionalAuthUser};
 use crate::middleware::permissions::{
-    require_channel_membership, require_channel_permission, require_not_timed_out,
-    resolve_channel_permissions,
+    require_channel_membership, require_channel_permission, require_not_blocked_in_dm,
+    require_not_timed_out, require_send_permission, resolve_channel_permissions,
+};
+use crate::models::attachment::{Attachment, AttachmentDescription, MAX_DESCRIPTION_LENGTH};
+use crate::models::channel::{is_voice_type, ChannelRow, DEFAULT_MAX_PINS};
+use crate::models::embed::validate_embeds;
+use crate::models::message::{
+    BulkDeleteMessages, CreateMessage, MessageRow, UpdateMessage, FLAG_EPHEMERAL, FLAG_QUARANTINED,
 };
-use crate::models::attachment::Attachment;
-use crate::models::message::{BulkDeleteMessages, CreateMessage, MessageRow, UpdateMessage};
+use crate::models::permission::Permissions;
+use crate::models::poll::validate_poll;
+use crate::models::thread::ThreadRow;
 use crate::state::AppState;
 use crate::storage;
 
@@ -24,6 +34,22 @@ pub struct ListMessagesQuery {
     pub sort: Option<String>,
 }
 
+/// The part of a multipart `payload_json` that isn't a [CreateMessage].
+#[derive(Deserialize)]
+struct UploadAttachments {
+    #[serde(default)]
+    attachments: Vec<AttachmentDescription>,
+}
+
+fn validate_description(description: Option<&str>) -> Result<(), AppError> {
+    if description.is_some_and(|d| d.chars().count() > MAX_DESCRIPTION_LENGTH) {
+        return Err(AppError::BadRequest(format!(
+            "attachment description must be at most {MAX_DESCRIPTION_LENGTH} characters"
+        )));
+    }
+    Ok(())
+}
+
 pub async fn list_messages(
     state: State<AppState>,
     Path(channel_id): Path<String>,
@@ -47,6 +73,10 @@ pub async fn list_messages(
             .ok_or_else(|| AppError::Unauthorized("authentication required".into()))?;
         require_channel_membership(&state.db, &channel_id, uid).await?;
     }
+    // Voice chat with history off is only seen live.
+    if is_voice_type(&channel.channel_type) && channel.voice_chat_retention == "off" {
+        return Ok(Json(serde_json::json!({ "data": [] })));
+    }
     let limit = params.limit.unwrap_or(50).min(100);
 
     let is_forum = params.top_level.unwrap_or(false);
@@ -113,6 +143,15 @@ pub async fn get_message(
     if msg.channel_id != channel_id {
         return Err(AppError::NotFound("unknown_message".to_string()));
     }
+    if msg.flags & FLAG_QUARANTINED != 0 {
+        return Err(AppError::NotFound("unknown_message".to_string()));
+    }
+    // Ephemeral responses exist only for the user who invoked the command.
+    if msg.flags & FLAG_EPHEMERAL != 0
+        && db::messages::ephemeral_recipient(&state.db, &msg.id).await? != current_user_id
+    {
+        return Err(AppError::NotFound("unknown_message".to_string()));
+    }
     let msgs = messages_to_json(&state.db, &[msg], current_user_id.as_deref()).await?;
     Ok(Json(
         serde_json::json!({ "data": msgs.into_iter().next().unwrap() }),
@@ -123,7 +162,7 @@ pub async fn get_message(
 /// (already resolved into `msg.mentions` at insert time), skipping the author so
 /// self-mentions never badge yourself. This is what makes the red mention badge
 /// survive a reconnect — the live gateway event only updates open clients.
-async fn apply_mention_counts(state: &AppState, msg: &MessageRow) {
+pub(super) async fn apply_mention_counts(state: &AppState, msg: &MessageRow) {
     let mentions: Vec<String> = serde_json::from_str(&msg.mentions).unwrap_or_default();
     for uid in &mentions {
         if uid == &msg.author_id {
@@ -139,17 +178,86 @@ async fn apply_mention_counts(state: &AppState, msg: &MessageRow) {
     }
 }
 
+/// Load the thread object a reply targets, if any. Replies must stay in the
+/// thread's channel, and locked threads only accept replies from thread
+/// managers. Legacy reply chains without a thread object return `None`.
+async fn check_thread_reply(
+    state: &AppState,
+    channel_id: &str,
+    auth: &AuthUser,
+    thread_id: Option<&str>,
+) -> Result<Option<ThreadRow>, AppError> {
+    let Some(thread_id) = thread_id else {
+        return Ok(None);
+    };
+    let Some(thread) = db::threads::find_thread(&state.db, thread_id).await? else {
+        return Ok(None);
+    };
+    if thread.channel_id != channel_id {
+        return Err(AppError::BadRequest(
+            "thread does not belong to this channel".into(),
+        ));
+    }
+    if thread.locked {
+        require_channel_permission(&state.db, channel_id, auth, "manage_threads").await?;
+    }
+    Ok(Some(thread))
+}
+
+/// A reply keeps its thread open: bump its activity, reopen it if it was
+/// archived, and make the author a member.
+async fn record_thread_reply(state: &AppState, thread: &ThreadRow, author_id: &str) {
+    let _ = db::threads::touch_thread(&state.db, &thread.id, state.db_is_postgres).await;
+    let _ = db::threads::add_thread_member(&state.db, &thread.id, author_id, state.db_is_postgres)
+        .await;
+    if thread.archived {
+        if let Ok(row) = db::threads::get_thread(&state.db, &thread.id).await {
+            let json = super::threads::thread_to_json(state, &row).await;
+            super::threads::broadcast_thread_event(
+                state,
+                "thread.update",
+                row.space_id.clone(),
+                json,
+            )
+            .await;
+        }
+    }
+}
+
+/// Polls and stickers live on the server that homes the channel, and
+/// federation only carries plain text, so replicas can't forward either.
+fn require_forwardable(input: &CreateMessage) -> Result<(), AppError> {
+    if input.poll.is_some() {
+        return Err(AppError::InvalidFields(vec![(
+            "poll".to_string(),
+            "polls are not supported in federated channels".to_string(),
+        )]));
+    }
+    if input
+        .sticker_ids
+        .as_ref()
+        .is_some_and(|ids| !ids.is_empty())
+    {
+        return Err(AppError::InvalidFields(vec![(
+            "sticker_ids".to_string(),
+            "stickers are not supported in federated channels".to_string(),
+        )]));
+    }
+    Ok(())
+}
+
 pub async fn create_message(
     state: State<AppState>,
     Path(channel_id): Path<String>,
     auth: AuthUser,
-    Json(input): Json<CreateMessage>,
+    Json(mut input): Json<CreateMessage>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let space_id =
-        require_channel_permission(&state.db, &channel_id, &auth, "send_messages").await?;
+    let space_id = require_send_permission(&state.db, &channel_id, &auth).await?;
     // Block timed-out members from sending in a space (DMs have no timeout).
     if !space_id.is_empty() {
         require_not_timed_out(&state.db, &space_id, &auth).await?;
+    } else {
+        require_not_blocked_in_dm(&state.db, &channel_id, &auth.user_id).await?;
     }
 
     // Thread permission enforcement
@@ -173,20 +281,32 @@ pub async fn create_message(
             ));
         }
     }
-    if let Some(ref embeds) = input.embeds {
-        if embeds.len() > 10 {
-            return Err(AppError::BadRequest("at most 10 embeds per message".into()));
-        }
+    if let Some(ref mut embeds) = input.embeds {
+        validate_embeds(embeds).map_err(AppError::InvalidFields)?;
+    }
+    if let Some(ref poll) = input.poll {
+        validate_poll(poll).map_err(AppError::InvalidFields)?;
     }
 
     let channel = db::channels::get_channel_row(&state.db, &channel_id).await?;
 
+    let thread = check_thread_reply(&state, &channel_id, &auth, input.thread_id.as_deref()).await?;
+    if let Some(ref sticker_ids) = input.sticker_ids {
+        super::stickers::validate_message_stickers(
+            &state,
+            channel.space_id.as_deref(),
+            sticker_ids,
+        )
+        .await?;
+    }
+
     // Remote-homed space: this server is only a replica. Forward the message to
     // the authoritative home server and return its canonical result; the home
     // server fans it back to us (and other peers) via our inbox. We deliberately
     // do NOT persist locally here (the inbox does, with the canonical ID).
     if let Some(ref sid) = channel.space_id {
         if let Some(home) = crate::db::federation::space_origin(&state.db, sid).await? {
+            require_forwardable(&input)?;
             let author = db::users::get_user(&state.db, &auth.user_id).await?;
             let payload = crate::federation::forward::forward_message(
                 &state,
@@ -206,6 +326,7 @@ pub async fn create_message(
     let is_dm = channel.space_id.is_none() && crate::federation::dm::is_dm(&channel.channel_type);
     if is_dm {
         if let Some(home) = crate::db::federation::channel_origin(&state.db, &channel_id).await? {
+            require_forwardable(&input)?;
             let author = db::users::get_user(&state.db, &auth.user_id).await?;
             let payload = crate::federation::dm::forward_dm_message(
                 &state,
@@ -220,6 +341,12 @@ pub async fn create_message(
         }
     }
 
+    // AutoMod runs on the home server only; replicas forwarded above.
+    if !space_id.is_empty() {
+        crate::automod::check_message(&state, &space_id, &channel_id, &auth, &input.content)
+            .await?;
+    }
+
     let msg = db::messages::create_message(
         &state.db,
         &channel_id,
@@ -230,8 +357,29 @@ pub async fn create_message(
     .await?;
 
     apply_mention_counts(&state, &msg).await;
+    crate::drafts::clear_on_send(&state, &auth.user_id, &channel_id).await;
+
+    if let Some(ref thread) = thread {
+        record_thread_reply(&state, thread, &auth.user_id).await;
+    }
+
+    // @everyone from a space manager in an announcement channel is badged for
+    // every member server-side, in batches off the request path.
+    if let Some(ref sid) = channel.space_id {
+        if crate::announcements::is_everyone_announcement(
+            &state,
+            &msg,
+            &channel.channel_type,
+            sid,
+            auth.is_admin,
+        )
+        .await
+        {
+            crate::announcements::spawn_everyone_fanout(state.0.clone(), sid.clone(), msg.clone());
+        }
+    }
 
-    let json = message_row_to_json_with_attachments(&msg, &[], None);
+    let json = message_to_json_with_context(&state.db, &msg, &[]).await?;
 
     // DMs have no space, so gateway delivery targets the participant user IDs
     // directly rather than space membership.
@@ -246,6 +394,8 @@ pub async fn create_message(
     };
 
     // Broadcast to gateway
+    let role_mention_user_ids =
+        crate::gateway::events::resolve_role_mentions(&state.db, &json).await;
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
         let event = serde_json::json!({
             "op": 0,
@@ -257,6 +407,7 @@ pub async fn create_message(
             target_user_ids: dm_targets.clone(),
             event,
             intent: "messages".to_string(),
+            role_mention_user_ids,
         });
 
         // When a thread reply is created, broadcast an update for the parent
@@ -286,11 +437,14 @@ pub async fn create_message(
                     target_user_ids: None,
                     event: update_event,
                     intent: "messages".to_string(),
+                    role_mention_user_ids: None,
                 });
             }
         }
     }
 
+    super::threads::start_auto_thread(&state, &channel, &msg).await;
+
     // Fan the message out to federated peers that have a member in this space.
     // No-op unless federation is enabled and the space is locally homed.
     if let Err(e) = crate::federation::outbound::fanout_message_create(&state, &msg).await {
@@ -312,53 +466,65 @@ pub async fn create_message(
         }
     }
 
-    // Spawn URL unfurling in the background -- if the message has no embeds
-    // already and its content contains URLs, fetch OpenGraph metadata and
-    // update the message with generated embeds.
+    // Unfurl links in the background unless the sender supplied embeds.
     if input.embeds.as_ref().is_none_or(|e| e.is_empty()) {
-        let content = input.content.clone();
-        let msg_id = msg.id.clone();
-        let space_id = channel.space_id.clone();
-        let db = state.db.clone();
-        let is_postgres = state.db_is_postgres;
-        let gateway_tx = state.gateway_tx.clone();
-        tokio::spawn(async move {
-            let embeds = crate::unfurl::unfurl_message_urls(&content).await;
-            if embeds.is_empty() {
-                return;
-            }
-            let update = UpdateMessage {
-                content: None,
-                embeds: Some(embeds),
-                title: None,
-            };
-            if let Ok(updated_msg) =
-                db::messages::update_message(&db, &msg_id, &update, is_postgres).await
-            {
-                let attachments = db::attachments::get_attachments_for_message(&db, &msg_id)
-                    .await
-                    .unwrap_or_default();
-                let json = message_row_to_json_with_attachments(&updated_msg, &attachments, None);
-                if let Some(ref dispatcher) = *gateway_tx.read().await {
-                    let event = serde_json::json!({
-                        "op": 0,
-                        "type": "message.update",
-                        "data": json
-                    });
-                    let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast {
-                        space_id,
-                        target_user_ids: None,
-                        event,
-                        intent: "messages".to_string(),
-                    });
-                }
-            }
-        });
+        spawn_unfurl(&state, &msg);
     }
+    crate::content_safety::spawn_scan(&state, &msg, Vec::new());
 
     Ok(Json(serde_json::json!({ "data": json })))
 }
 
+/// Fetch OpenGraph/Twitter card previews for the links in [msg] off the
+/// request path, then store them as the message's embeds and broadcast a
+/// `message.update`. Fetches go through `safe_fetch`, so private and
+/// loopback addresses are refused.
+fn spawn_unfurl(state: &AppState, msg: &MessageRow) {
+    let content = msg.content.clone();
+    let msg_id = msg.id.clone();
+    let space_id = msg.space_id.clone();
+    let db = state.db.clone();
+    let is_postgres = state.db_is_postgres;
+    let gateway_tx = state.gateway_tx.clone();
+    tokio::spawn(async move {
+        let embeds = crate::unfurl::unfurl_message_urls(&content).await;
+        if embeds.is_empty() {
+            return;
+        }
+        let update = UpdateMessage {
+            content: None,
+            embeds: Some(embeds),
+            title: None,
+            attachments: None,
+        };
+        if let Ok(updated_msg) =
+            db::messages::update_message(&db, &msg_id, &update, is_postgres).await
+        {
+            let attachments = db::attachments::get_attachments_for_message(&db, &msg_id)
+                .await
+                .unwrap_or_default();
+            let json = match message_to_json_with_context(&db, &updated_msg, &attachments).await {
+                Ok(json) => json,
+                Err(_) => message_row_to_json_with_attachments(&updated_msg, &attachments, None),
+            };
+            if let Some(ref dispatcher) = *gateway_tx.read().await {
+                let event = serde_json::json!({
+                    "op": 0,
+                    "type": "message.update",
+                    "data": json
+                });
+                let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast {
+                    space_id,
+                    target_user_ids: None,
+                    event,
+                    intent: "messages".to_string(),
+                    role_mention_user_ids: None,
+                });
+            }
+        }
+    });
+}
+
 /// Handles multipart/form-data message creation with file attachments.
 /// Expects a `payload_json` field with the message metadata and zero or more
 /// file fields named `files[0]`, `files[1]`, etc.
@@ -368,10 +534,11 @@ pub async fn create_message_multipart(
     auth: AuthUser,
     mut multipart: Multipart,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let space_id =
-        require_channel_permission(&state.db, &channel_id, &auth, "send_messages").await?;
+    let space_id = require_send_permission(&state.db, &channel_id, &auth).await?;
     if !space_id.is_empty() {
         require_not_timed_out(&state.db, &space_id, &auth).await?;
+    } else {
+        require_not_blocked_in_dm(&state.db, &channel_id, &auth.user_id).await?;
     }
 
     let settings = state.settings.load();
@@ -379,7 +546,8 @@ pub async fn create_message_multipart(
     let max_attachment_size = settings.max_attachment_size as usize;
 
     let mut payload_json: Option<CreateMessage> = None;
-    let mut files: Vec<(String, String, Vec<u8>)> = Vec::new(); // (filename, content_type, bytes)
+    let mut descriptions: Vec<AttachmentDescription> = Vec::new();
+    let mut files: Vec<(String, String, String, Vec<u8>)> = Vec::new(); // (index, filename, content_type, bytes)
 
     while let Some(field) = multipart
         .next_field()
@@ -397,12 +565,19 @@ pub async fn create_message_multipart(
                 serde_json::from_str(&text)
                     .map_err(|e| AppError::BadRequest(format!("invalid payload_json: {e}")))?,
             );
+            descriptions = serde_json::from_str::<UploadAttachments>(&text)
+                .map_err(|e| AppError::BadRequest(format!("invalid payload_json: {e}")))?
+                .attachments;
         } else if name.starts_with("files[") {
             if files.len() >= max_attachments {
                 return Err(AppError::BadRequest(format!(
                     "maximum {max_attachments} attachments per message"
                 )));
             }
+            let index = name
+                .trim_start_matches("files[")
+                .trim_end_matches(']')
+                .to_string();
             let filename = field.file_name().unwrap_or("attachment").to_string();
             let content_type = field
                 .content_type()
@@ -412,13 +587,32 @@ pub async fn create_message_multipart(
                 .bytes()
                 .await
                 .map_err(|e| AppError::BadRequest(format!("failed to read file: {e}")))?;
-            files.push((filename, content_type, bytes.to_vec()));
+            files.push((index, filename, content_type, bytes.to_vec()));
         }
     }
 
-    let input = payload_json.ok_or_else(|| {
+    let mut input = payload_json.ok_or_else(|| {
         AppError::BadRequest("missing payload_json field in multipart request".to_string())
     })?;
+    if let Some(ref mut embeds) = input.embeds {
+        validate_embeds(embeds).map_err(AppError::InvalidFields)?;
+    }
+    if let Some(ref poll) = input.poll {
+        validate_poll(poll).map_err(AppError::InvalidFields)?;
+    }
+
+    for (_, _, _, bytes) in &files {
+        crate::blocklist::check_upload(&state.db, &auth.user_id, bytes).await?;
+    }
+    for d in &descriptions {
+        validate_description(d.description.as_deref())?;
+        if !files.iter().any(|(index, ..)| *index == d.id) {
+            return Err(AppError::BadRequest(format!(
+                "attachment description for unknown file {}",
+                d.id
+            )));
+        }
+    }
 
     // Thread permission enforcement
     if input.thread_id.is_some() {
@@ -426,6 +620,19 @@ pub async fn create_message_multipart(
     }
 
     let channel = db::channels::get_channel_row(&state.db, &channel_id).await?;
+    let thread = check_thread_reply(&state, &channel_id, &auth, input.thread_id.as_deref()).await?;
+    if let Some(ref sticker_ids) = input.sticker_ids {
+        super::stickers::validate_message_stickers(
+            &state,
+            channel.space_id.as_deref(),
+            sticker_ids,
+        )
+        .await?;
+    }
+    if !space_id.is_empty() {
+        crate::automod::check_message(&state, &space_id, &channel_id, &auth, &input.content)
+            .await?;
+    }
     let msg = db::messages::create_message(
         &state.db,
         &channel_id,
@@ -436,32 +643,73 @@ pub async fn create_message_multipart(
     .await?;
 
     apply_mention_counts(&state, &msg).await;
+    crate::drafts::clear_on_send(&state, &auth.user_id, &channel_id).await;
+    if let Some(ref thread) = thread {
+        record_thread_reply(&state, thread, &auth.user_id).await;
+    }
 
     // Save files and create attachment records.
     //
-    // We generate the attachment ID up front and use it as the on-disk
-    // directory name (instead of the message ID). This way the URL returned
-    // to the client, the URL stored in the database, and the file path on
-    // disk are all derived from the same stable identifier and cannot drift.
+    // We generate the attachment ID up front and use it in the storage key
+    // (instead of the message ID). This way the URL returned to the client,
+    // the URL stored in the database, and the stored object are all derived
+    // from the same stable identifier and cannot drift.
     let mut attachments: Vec<Attachment> = Vec::new();
-    for (filename, content_type, bytes) in &files {
+    let scanning = crate::content_safety::is_enabled(&state);
+    let mut scanned = Vec::new();
+    for (index, filename, content_type, bytes) in &files {
         let attachment_id = crate::snowflake::generate();
+        let description = descriptions
+            .iter()
+            .find(|d| d.id == *index)
+            .and_then(|d| d.description.as_deref());
 
         let (url, size) = storage::save_attachment(
-            &state.storage_path,
+            state.storage.as_ref(),
             &channel_id,
             &attachment_id,
             filename,
+            content_type,
             bytes,
             max_attachment_size,
         )
         .await?;
 
-        // Detect image dimensions for image content types
-        let (width, height) = if content_type.starts_with("image/") {
-            detect_image_dimensions(bytes)
+        // Detect image dimensions and build a preview for image content types
+        let (width, height, proxy_url) = if content_type.starts_with("image/") {
+            let source = bytes.clone();
+            let thumbnail =
+                tokio::task::spawn_blocking(move || storage::thumbnail::generate(&source))
+                    .await
+                    .ok()
+                    .flatten();
+            match thumbnail {
+                Some(thumb) => {
+                    let proxy_url = match thumb.webp {
+                        Some(webp) => Some(
+                            storage::save_attachment_thumbnail(
+                                state.storage.as_ref(),
+                                &channel_id,
+                                &attachment_id,
+                                &webp,
+                            )
+                            .await?,
+                        ),
+                        None => Some(url.clone()),
+                    };
+                    (
+                        Some(thumb.width as i64),
+                        Some(thumb.height as i64),
+                        proxy_url,
+                    )
+                }
+                None => {
+                    let (width, height) = detect_image_dimensions(bytes);
+                    (width, height, None)
+                }
+            }
         } else {
-            (None, None)
+            (None, None, None)
         };
 
         let attachment = db::attachments::insert_attachment(
@@ -469,19 +717,31 @@ pub async fn create_message_multipart(
             &attachment_id,
             &msg.id,
             filename,
+            description,
             Some(content_type.as_str()),
             size as i64,
             &url,
+            proxy_url.as_deref(),
             width,
             height,
         )
         .await?;
+        if scanning {
+            scanned.push(crate::content_safety::ScannedAttachment {
+                id: attachment.id.clone(),
+                url: attachment.url.clone(),
+                content_type: attachment.content_type.clone(),
+                sha256: crate::blocklist::hash_bytes(bytes),
+            });
+        }
         attachments.push(attachment);
     }
 
-    let json = message_row_to_json_with_attachments(&msg, &attachments, None);
+    let json = message_to_json_with_context(&state.db, &msg, &attachments).await?;
 
     // Broadcast to gateway
+    let role_mention_user_ids =
+        crate::gateway::events::resolve_role_mentions(&state.db, &json).await;
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
         let event = serde_json::json!({
             "op": 0,
@@ -489,12 +749,19 @@ pub async fn create_message_multipart(
             "data": json
         });
         let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast {
-            space_id: channel.space_id,
+            space_id: channel.space_id.clone(),
             target_user_ids: None,
             event,
             intent: "messages".to_string(),
+            role_mention_user_ids,
         });
     }
+    super::threads::start_auto_thread(&state, &channel, &msg).await;
+
+    if input.embeds.as_ref().is_none_or(|e| e.is_empty()) {
+        spawn_unfurl(&state, &msg);
+    }
+    crate::content_safety::spawn_scan(&state, &msg, scanned);
 
     Ok(Json(serde_json::json!({ "data": json })))
 }
@@ -503,13 +770,20 @@ pub async fn update_message(
     state: State<AppState>,
     Path((channel_id, message_id)): Path<(String, String)>,
     auth: AuthUser,
-    Json(input): Json<UpdateMessage>,
+    Json(mut input): Json<UpdateMessage>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
     let existing = db::messages::get_message_row(&state.db, &message_id).await?;
     if existing.channel_id != channel_id {
         return Err(AppError::NotFound("unknown_message".to_string()));
     }
+    // Webhook messages are attributed to the webhook's creator, but they
+    // didn't write them.
+    if existing.webhook_id.is_some() {
+        return Err(AppError::Forbidden(
+            "webhook messages cannot be edited".into(),
+        ));
+    }
 
     // Remote-homed space: forward the edit to the authoritative home server.
     if let Some(ref sid) = existing.space_id {
@@ -541,12 +815,31 @@ pub async fn update_message(
             ));
         }
     }
+    if let Some(ref mut embeds) = input.embeds {
+        validate_embeds(embeds).map_err(AppError::InvalidFields)?;
+    }
+    if let Some(ref edits) = input.attachments {
+        let current = db::attachments::get_attachments_for_message(&state.db, &message_id).await?;
+        for d in edits {
+            validate_description(d.description.as_deref())?;
+            if !current.iter().any(|a| a.id == d.id) {
+                return Err(AppError::BadRequest(format!("unknown attachment {}", d.id)));
+            }
+        }
+    }
+    if input.content.is_some() || input.title.is_some() || input.embeds.is_some() {
+        db::messages::record_revision(&state.db, &existing, &auth.user_id).await?;
+    }
+    for d in input.attachments.iter().flatten() {
+        db::attachments::set_description(&state.db, &message_id, &d.id, d.description.as_deref())
+            .await?;
+    }
     let msg =
         db::messages::update_message(&state.db, &message_id, &input, state.db_is_postgres).await?;
 
     // Load existing attachments for the response
     let attachments = db::attachments::get_attachments_for_message(&state.db, &message_id).await?;
-    let json = message_row_to_json_with_attachments(&msg, &attachments, None);
+    let json = message_to_json_with_context(&state.db, &msg, &attachments).await?;
 
     // Broadcast to gateway
     let channel = db::channels::get_channel_row(&state.db, &channel_id).await?;
@@ -561,6 +854,7 @@ pub async fn update_message(
             target_user_ids: None,
             event,
             intent: "messages".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -585,6 +879,22 @@ pub async fn update_message(
     Ok(Json(serde_json::json!({ "data": json })))
 }
 
+/// GET /channels/{channel_id}/messages/{message_id}/history
+/// Earlier versions of an edited message, oldest first. Moderators only.
+pub async fn get_message_history(
+    state: State<AppState>,
+    Path((channel_id, message_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_permission(&state.db, &channel_id, &auth, "manage_messages").await?;
+    let existing = db::messages::get_message_row(&state.db, &message_id).await?;
+    if existing.channel_id != channel_id {
+        return Err(AppError::NotFound("unknown_message".to_string()));
+    }
+    let revisions = db::messages::list_revisions(&state.db, &message_id).await?;
+    Ok(Json(serde_json::json!({ "data": revisions })))
+}
+
 pub async fn delete_message(
     state: State<AppState>,
     Path((channel_id, message_id)): Path<(String, String)>,
@@ -609,10 +919,10 @@ pub async fn delete_message(
         require_channel_permission(&state.db, &channel_id, &auth, "manage_messages").await?;
     }
 
-    // Delete attachment files from disk before deleting the message
+    // Delete attachment files from storage before deleting the message
     let attachments = db::attachments::get_attachments_for_message(&state.db, &message_id).await?;
     for att in &attachments {
-        let _ = storage::delete_file(&state.storage_path, &att.url).await;
+        let _ = storage::delete_file(state.storage.as_ref(), &att.url).await;
     }
 
     db::messages::delete_message(&state.db, &message_id).await?;
@@ -634,6 +944,7 @@ pub async fn delete_message(
             target_user_ids: None,
             event,
             intent: "messages".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -673,6 +984,95 @@ pub async fn bulk_delete_messages(
     Ok(Json(serde_json::json!({ "data": null })))
 }
 
+/// Most messages one purge removes.
+const PURGE_MAX_MESSAGES: i64 = 500;
+/// Furthest back a purge reaches: 14 days.
+const PURGE_MAX_SECONDS: i64 = 14 * 24 * 60 * 60;
+
+#[derive(Deserialize)]
+pub struct PurgeQuery {
+    pub user_id: Option<String>,
+    pub seconds: i64,
+}
+
+/// POST /channels/{id}/purge?user_id=&seconds= — delete the last `seconds`
+/// of a channel's messages (up to 14 days), optionally only one member's,
+/// newest first and at most 500 per call. Sends `message.delete_bulk`.
+pub async fn purge_messages(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+    audit_reason: AuditReason,
+    Query(query): Query<PurgeQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_id =
+        require_channel_permission(&state.db, &channel_id, &auth, "manage_messages").await?;
+    if !(1..=PURGE_MAX_SECONDS).contains(&query.seconds) {
+        return Err(AppError::BadRequest(format!(
+            "seconds must be between 1 and {PURGE_MAX_SECONDS}"
+        )));
+    }
+    let since = (chrono::Utc::now() - chrono::Duration::seconds(query.seconds))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    let ids = db::messages::list_recent_message_ids(
+        &state.db,
+        &channel_id,
+        query.user_id.as_deref(),
+        &since,
+        PURGE_MAX_MESSAGES,
+    )
+    .await?;
+
+    let attachments = db::attachments::get_attachments_for_messages(&state.db, &ids).await?;
+    for att in attachments.values().flatten() {
+        let _ = storage::delete_file(state.storage.as_ref(), &att.url).await;
+    }
+    db::messages::bulk_delete_messages(&state.db, &channel_id, &ids).await?;
+
+    if !ids.is_empty() {
+        let space_id = (!space_id.is_empty()).then_some(space_id);
+        if let Some(ref sid) = space_id {
+            super::audit_log::record(
+                &state,
+                sid,
+                &auth.user_id,
+                "message_purge",
+                Some((&channel_id, "channel")),
+                audit_reason.as_deref(),
+                Some(serde_json::json!({
+                    "user_id": query.user_id,
+                    "seconds": query.seconds,
+                    "count": ids.len(),
+                })),
+            )
+            .await;
+        }
+        if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+            let event = serde_json::json!({
+                "op": 0,
+                "type": "message.delete_bulk",
+                "data": {
+                    "ids": ids,
+                    "channel_id": channel_id,
+                    "space_id": space_id,
+                }
+            });
+            let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast {
+                space_id: space_id.clone(),
+                target_user_ids: None,
+                event,
+                intent: "messages".to_string(),
+                role_mention_user_ids: None,
+            });
+        }
+    }
+
+    Ok(Json(
+        serde_json::json!({ "data": { "deleted": ids.len() } }),
+    ))
+}
+
 pub async fn list_pins(
     state: State<AppState>,
     Path(channel_id): Path<String>,
@@ -684,26 +1084,126 @@ pub async fn list_pins(
     Ok(Json(serde_json::json!({ "data": messages })))
 }
 
+/// PUT /channels/{channel_id}/pins/{message_id}
+/// Pin a message, up to the channel's pin limit. A new pin posts a
+/// `channel_pinned_message` notice pointing at the message and sends
+/// `channel.pins_update`.
 pub async fn pin_message(
     state: State<AppState>,
     Path((channel_id, message_id)): Path<(String, String)>,
     auth: AuthUser,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_channel_permission(&state.db, &channel_id, &auth, "manage_messages").await?;
-    db::messages::pin_message(&state.db, &channel_id, &message_id, state.db_is_postgres).await?;
+    let msg = db::messages::get_message_row(&state.db, &message_id).await?;
+    if msg.channel_id != channel_id {
+        return Err(AppError::NotFound("unknown_message".to_string()));
+    }
+    if msg.pinned {
+        return Ok(Json(serde_json::json!({ "data": null })));
+    }
+    let channel = db::channels::get_channel_row(&state.db, &channel_id).await?;
+    let pins = db::messages::count_pins(&state.db, &channel_id).await?;
+    AppError::check_limit(
+        "pin_limit_reached",
+        pins,
+        channel.max_pins.unwrap_or(DEFAULT_MAX_PINS),
+    )?;
+    if !db::messages::pin_message(&state.db, &channel_id, &message_id, state.db_is_postgres).await?
+    {
+        return Ok(Json(serde_json::json!({ "data": null })));
+    }
+
+    let user = db::users::get_user(&state.db, &auth.user_id).await?;
+    let name = user.display_name.as_deref().unwrap_or(&user.username);
+    let locale = match channel.space_id {
+        Some(ref sid) => db::spaces::get_space_row(&state.db, sid)
+            .await
+            .map(|s| s.preferred_locale)
+            .unwrap_or_else(|_| crate::locale::DEFAULT_LOCALE.to_string()),
+        None => crate::locale::DEFAULT_LOCALE.to_string(),
+    };
+    let notice = db::messages::create_system_message(
+        &state.db,
+        &channel_id,
+        &auth.user_id,
+        channel.space_id.as_deref(),
+        &crate::locale::message_pinned(&locale, name),
+        "channel_pinned_message",
+        Some(&message_id),
+    )
+    .await?;
+    let notice_json = message_row_to_json(&notice);
+    broadcast_channel_event(&state, &channel, "message.create", notice_json, "messages").await;
+    broadcast_pins_update(&state, &channel).await;
+
     Ok(Json(serde_json::json!({ "data": null })))
 }
 
+/// DELETE /channels/{channel_id}/pins/{message_id}
 pub async fn unpin_message(
     state: State<AppState>,
     Path((channel_id, message_id)): Path<(String, String)>,
     auth: AuthUser,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_channel_permission(&state.db, &channel_id, &auth, "manage_messages").await?;
-    db::messages::unpin_message(&state.db, &channel_id, &message_id).await?;
+    if db::messages::unpin_message(&state.db, &channel_id, &message_id).await? {
+        let channel = db::channels::get_channel_row(&state.db, &channel_id).await?;
+        broadcast_pins_update(&state, &channel).await;
+    }
     Ok(Json(serde_json::json!({ "data": null })))
 }
 
+/// Send `channel.pins_update` with the time of the channel's newest pin.
+async fn broadcast_pins_update(state: &AppState, channel: &ChannelRow) {
+    let last_pin_timestamp = db::messages::last_pin_at(&state.db, &channel.id)
+        .await
+        .unwrap_or_default();
+    let data = serde_json::json!({
+        "channel_id": channel.id,
+        "space_id": channel.space_id,
+        "last_pin_timestamp": last_pin_timestamp,
+    });
+    broadcast_channel_event(state, channel, "channel.pins_update", data, "spaces").await;
+}
+
+/// Broadcast an event to everyone in `channel`'s space, or to its
+/// participants when it's a DM.
+pub(crate) async fn broadcast_channel_event(
+    state: &AppState,
+    channel: &ChannelRow,
+    event_type: &str,
+    data: serde_json::Value,
+    intent: &str,
+) {
+    let target_user_ids = if channel.space_id.is_none() {
+        Some(
+            db::dm_participants::list_participant_ids(&state.db, &channel.id)
+                .await
+                .unwrap_or_default(),
+        )
+    } else {
+        None
+    };
+    let role_mention_user_ids = match event_type {
+        "message.create" => crate::gateway::events::resolve_role_mentions(&state.db, &data).await,
+        _ => None,
+    };
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": event_type,
+            "data": data
+        });
+        let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast {
+            space_id: channel.space_id.clone(),
+            target_user_ids,
+            event,
+            intent: intent.to_string(),
+            role_mention_user_ids,
+        });
+    }
+}
+
 #[derive(Deserialize, Default)]
 pub struct TypingIndicatorBody {
     pub thread_id: Option<String>,
@@ -715,8 +1215,7 @@ pub async fn typing_indicator(
     auth: AuthUser,
     body: Option<Json<TypingIndicatorBody>>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let space_id =
-        require_channel_permission(&state.db, &channel_id, &auth, "send_messages").await?;
+    let space_id = require_send_permission(&state.db, &channel_id, &auth).await?;
     if !space_id.is_empty() {
         require_not_timed_out(&state.db, &space_id, &auth).await?;
     }
@@ -734,6 +1233,13 @@ pub async fn typing_indicator(
     }
 
     let thread_id = body.and_then(|b| b.thread_id.clone());
+    // Still typing from a recent request: that event hasn't run out yet.
+    if !state
+        .typing
+        .should_send(&auth.user_id, &channel_id, thread_id.as_deref())
+    {
+        return Ok(Json(serde_json::json!({ "data": null })));
+    }
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
         let channel = db::channels::get_channel_row(&state.db, &channel_id).await?;
         let mut data = serde_json::json!({
@@ -754,6 +1260,7 @@ pub async fn typing_indicator(
             target_user_ids: None,
             event,
             intent: "message_typing".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -819,10 +1326,7 @@ pub async fn search_messages(
             let perms =
                 resolve_channel_permissions(&state.db, &ch.id, &space_id, &user.user_id).await;
             if let Ok(perms) = perms {
-                if perms
-                    .iter()
-                    .any(|p| p == "administrator" || p == "view_channel")
-                {
+                if perms.has(Permissions::VIEW_CHANNEL) {
                     ids.push(ch.id.clone());
                 }
             }
@@ -980,18 +1484,222 @@ pub fn message_row_to_json_full(
         "embeds": embeds,
         "reactions": reactions_json,
         "reply_to": row.reply_to,
+        "referenced_message": null,
+        "resolved_mentions": [],
+        "poll": null,
+        "sticker_items": [],
         "flags": row.flags,
         "webhook_id": row.webhook_id,
+        "webhook_author": row.webhook_id.as_ref().map(|_| serde_json::json!({
+            "username": row.webhook_username,
+            "avatar_url": row.webhook_avatar,
+        })),
         "thread_id": row.thread_id,
         "reply_count": reply_count.unwrap_or(0),
         "title": row.title
     })
 }
 
+/// Characters of the replied-to message kept in `referenced_message`.
+pub const REPLY_PREVIEW_LEN: usize = 100;
+
+fn mentioned_user_json(user: &crate::models::user::User) -> serde_json::Value {
+    serde_json::json!({
+        "id": user.id,
+        "username": user.username,
+        "display_name": user.display_name,
+        "avatar": user.avatar,
+    })
+}
+
+/// Fills in `referenced_message` (the replied-to message's author and the
+/// start of its content), `resolved_mentions` (the users, roles, and
+/// channels the message mentions), `poll` with its tallies, and
+/// `sticker_items` for each of `rows`, whose JSON is `out` in the same order.
+/// Everything is loaded in a handful of queries for the whole batch so
+/// clients don't have to look each one up.
+pub async fn add_message_context(
+    pool: &crate::db::DbPool,
+    rows: &[MessageRow],
+    out: &mut [serde_json::Value],
+    current_user_id: Option<&str>,
+) -> Result<(), AppError> {
+    let mut reply_ids: Vec<String> = rows.iter().filter_map(|r| r.reply_to.clone()).collect();
+    reply_ids.sort();
+    reply_ids.dedup();
+    let referenced: HashMap<String, MessageRow> = db::messages::get_message_rows(pool, &reply_ids)
+        .await?
+        .into_iter()
+        .filter(|m| m.flags & (FLAG_EPHEMERAL | FLAG_QUARANTINED) == 0)
+        .map(|m| (m.id.clone(), m))
+        .collect();
+
+    let mentions: Vec<Vec<String>> = rows
+        .iter()
+        .map(|r| serde_json::from_str(&r.mentions).unwrap_or_default())
+        .collect();
+    let mention_roles: Vec<Vec<String>> = rows
+        .iter()
+        .map(|r| serde_json::from_str(&r.mention_roles).unwrap_or_default())
+        .collect();
+    let mention_channels: Vec<Vec<String>> = rows
+        .iter()
+        .map(|r| match r.space_id {
+            Some(_) => crate::mentions::parse_mentions(&r.content).channel_ids,
+            None => Vec::new(),
+        })
+        .collect();
+
+    let ids: Vec<String> = rows.iter().map(|r| r.id.clone()).collect();
+    let polls = db::polls::get_polls_for_messages(pool, &ids).await?;
+    let poll_ids: Vec<String> = polls.keys().cloned().collect();
+    let votes = db::polls::get_vote_counts(pool, &poll_ids, current_user_id).await?;
+    let stickers = db::stickers::get_stickers_for_messages(pool, &ids).await?;
+
+    let mut user_ids: Vec<String> = mentions.iter().flatten().cloned().collect();
+    user_ids.extend(referenced.values().map(|m| m.author_id.clone()));
+    user_ids.sort();
+    user_ids.dedup();
+    let users: HashMap<String, crate::models::user::User> =
+        db::users::get_users_by_ids(pool, &user_ids)
+            .await?
+            .into_iter()
+            .map(|u| (u.id.clone(), u))
+            .collect();
+
+    // Roles and channels are looked up per space; a page of history is
+    // almost always from one.
+    let mut roles = HashMap::new();
+    let mut channels = HashMap::new();
+    let mut loaded_spaces: Vec<(&str, bool)> = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        let Some(space_id) = row.space_id.as_deref() else {
+            continue;
+        };
+        if !mention_roles[i].is_empty() && !loaded_spaces.contains(&(space_id, true)) {
+            loaded_spaces.push((space_id, true));
+            for role in db::roles::list_roles(pool, space_id).await? {
+                roles.insert(role.id.clone(), role);
+            }
+        }
+        if !mention_channels[i].is_empty() && !loaded_spaces.contains(&(space_id, false)) {
+            loaded_spaces.push((space_id, false));
+            for channel in db::channels::list_channels_in_space(pool, space_id).await? {
+                channels.insert(channel.id.clone(), channel);
+            }
+        }
+    }
+
+    for (i, (row, json)) in rows.iter().zip(out.iter_mut()).enumerate() {
+        if let Some(reply) = row.reply_to.as_ref().and_then(|id| referenced.get(id)) {
+            json["referenced_message"] = serde_json::json!({
+                "id": reply.id,
+                "channel_id": reply.channel_id,
+                "author_id": reply.author_id,
+                "author": users.get(&reply.author_id).map(mentioned_user_json),
+                "webhook_author": reply.webhook_id.as_ref().map(|_| serde_json::json!({
+                    "username": reply.webhook_username,
+                    "avatar_url": reply.webhook_avatar,
+                })),
+                "content": reply.content.chars().take(REPLY_PREVIEW_LEN).collect::<String>(),
+                "timestamp": reply.created_at,
+            });
+        }
+
+        let mut resolved = Vec::new();
+        for user in mentions[i].iter().filter_map(|id| users.get(id)) {
+            let mut entry = mentioned_user_json(user);
+            entry["type"] = "user".into();
+            resolved.push(entry);
+        }
+        for role in mention_roles[i].iter().filter_map(|id| roles.get(id)) {
+            resolved.push(serde_json::json!({
+                "type": "role",
+                "id": role.id,
+                "name": role.name,
+                "color": role.color,
+            }));
+        }
+        for channel in mention_channels[i]
+            .iter()
+            .filter_map(|id| channels.get(id))
+            .filter(|c| c.space_id == row.space_id)
+        {
+            resolved.push(serde_json::json!({
+                "type": "channel",
+                "id": channel.id,
+                "name": channel.name,
+                "channel_type": channel.channel_type,
+            }));
+        }
+        json["resolved_mentions"] = serde_json::Value::Array(resolved);
+        if let Some(poll) = polls.get(&row.id) {
+            json["poll"] = poll_to_json(poll, votes.get(&row.id).map(Vec::as_slice));
+        }
+        if let Some(items) = stickers.get(&row.id) {
+            json["sticker_items"] = items
+                .iter()
+                .map(|s| {
+                    serde_json::json!({
+                        "id": s.id,
+                        "name": s.name,
+                        "format": s.format,
+                        "image_url": s.image_url,
+                    })
+                })
+                .collect();
+        }
+    }
+    Ok(())
+}
+
+/// A poll with its per-answer tallies. `me` marks the answers the viewer
+/// picked; events broadcast to everyone leave it false.
+pub fn poll_to_json(
+    poll: &crate::models::poll::PollRow,
+    votes: Option<&[(i64, i64, bool)]>,
+) -> serde_json::Value {
+    let votes = votes.unwrap_or_default();
+    let answers: Vec<serde_json::Value> = poll
+        .answers
+        .iter()
+        .map(|a| {
+            let (count, me) = votes
+                .iter()
+                .find(|(id, ..)| *id == a.id)
+                .map(|(_, count, me)| (*count, *me))
+                .unwrap_or((0, false));
+            serde_json::json!({ "id": a.id, "text": a.text, "votes": count, "me": me })
+        })
+        .collect();
+    serde_json::json!({
+        "question": poll.question,
+        "answers": answers,
+        "allow_multiselect": poll.allow_multiselect,
+        "expires_at": poll.expires_at,
+        "finalized": poll.finalized_at.is_some(),
+        "total_votes": votes.iter().map(|(_, count, _)| count).sum::<i64>(),
+    })
+}
+
+/// A single message's JSON with its attachments, reply preview, resolved
+/// mentions, and poll, as sent in `message.create` and `message.update`.
+pub async fn message_to_json_with_context(
+    pool: &crate::db::DbPool,
+    row: &MessageRow,
+    attachments: &[Attachment],
+) -> Result<serde_json::Value, AppError> {
+    let mut json = [message_row_to_json_with_attachments(row, attachments, None)];
+    add_message_context(pool, std::slice::from_ref(row), &mut json, None).await?;
+    let [json] = json;
+    Ok(json)
+}
+
 /// Converts a batch of message rows to JSON, enriching each with its
-/// reactions, attachments, and thread reply counts.
+/// reactions, attachments, thread reply counts, reply preview, resolved
+/// mentions, and poll.
 pub async fn messages_to_json(
-    pool: &sqlx::AnyPool,
+    pool: &crate::db::DbPool,
     rows: &[MessageRow],
     current_user_id: Option<&str>,
 ) -> Result<Vec<serde_json::Value>, crate::error::AppError> {
@@ -1000,7 +1708,7 @@ pub async fn messages_to_json(
         db::messages::get_reactions_for_messages(pool, &ids, current_user_id).await?;
     let attachments_map = db::attachments::get_attachments_for_messages(pool, &ids).await?;
     let reply_counts = db::messages::get_thread_reply_counts(pool, &ids).await?;
-    Ok(rows
+    let mut out = rows
         .iter()
         .map(|row| {
             let atts = attachments_map
@@ -1010,13 +1718,15 @@ pub async fn messages_to_json(
             let count = reply_counts.get(&row.id).copied();
             message_row_to_json_full(row, atts, reactions_map.get(&row.id), count)
         })
-        .collect())
+        .collect::<Vec<_>>();
+    add_message_context(pool, rows, &mut out, current_user_id).await?;
+    Ok(out)
 }
 
 /// Converts a batch of forum post rows to JSON, enriching each with
 /// reactions, attachments, thread reply counts, and last_reply_at.
 pub async fn messages_to_forum_json(
-    pool: &sqlx::AnyPool,
+    pool: &crate::db::DbPool,
     rows: &[MessageRow],
     current_user_id: Option<&str>,
 ) -> Result<Vec<serde_json::Value>, crate::error::AppError> {
@@ -1026,7 +1736,7 @@ pub async fn messages_to_forum_json(
     let attachments_map = db::attachments::get_attachments_for_messages(pool, &ids).await?;
     let reply_counts = db::messages::get_thread_reply_counts(pool, &ids).await?;
     let last_reply_timestamps = db::messages::get_last_reply_timestamps(pool, &ids).await?;
-    Ok(rows
+    let mut out = rows
         .iter()
         .map(|row| {
             let atts = attachments_map
@@ -1040,7 +1750,9 @@ pub async fn messages_to_forum_json(
             }
             json
         })
-        .collect())
+        .collect::<Vec<_>>();
+    add_message_context(pool, rows, &mut out, current_user_id).await?;
+    Ok(out)
 }
 
 /// Try to detect image dimensions from raw bytes (PNG and JPEG).
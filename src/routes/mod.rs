@@ -1,50 +1,79 @@
 mod admin;
 mod applications;
-mod audit_log;
-mod auth;
+pub mod audit_log;
+pub mod auth;
+mod automod;
 mod bans;
 pub mod channels;
+mod drafts;
+mod email_domains;
 mod emojis;
+mod forum;
 mod gateway;
 mod health;
+mod integrations;
 mod interactions;
 mod invite_page;
 mod invites;
 mod landing;
 pub mod members;
+mod message_shares;
 pub mod messages;
 mod mutes;
+mod oauth2;
+mod passkeys;
 mod plugins;
+mod polls;
+mod push;
+mod raid;
 mod reactions;
 mod read_states;
 mod relationships;
 mod reports;
 pub mod roles;
+mod screening;
 pub mod seo;
 mod settings;
 mod soundboard;
 pub mod spaces;
+mod stickers;
 pub mod system_messages;
 #[cfg(feature = "test-seed")]
 mod test_seed;
+pub mod threads;
+pub mod user_settings;
 mod users;
 mod voice;
+mod webhooks;
+mod widget;
 
+use axum::extract::State;
+use axum::http::{StatusCode, Uri};
 use axum::middleware as axum_mw;
+use axum::response::{IntoResponse, Redirect, Response};
 use axum::routing::{delete, get, patch, post, put};
 use axum::Router;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::CorsLayer;
 use tower_http::services::ServeDir;
 use tower_http::trace::TraceLayer;
 
+use crate::config::parse_origin_list;
+use crate::middleware::query_count::query_count_middleware;
 use crate::middleware::rate_limit::rate_limit_middleware;
+use crate::middleware::security_headers::security_headers_middleware;
 use crate::state::AppState;
 
 /// Build the full application router. Consumes the state so middleware
 /// layers that need `State<AppState>` (e.g. rate limiter) can be wired up.
 pub fn router(state: AppState) -> Router {
     let api = api_routes(&state);
-    let cdn_service = ServeDir::new(&state.storage_path);
+    let cdn_service: Router = match state.storage.local_root() {
+        Some(root) => Router::new().fallback_service(ServeDir::new(root)),
+        None => Router::new()
+            .fallback(cdn_redirect)
+            .with_state(state.clone()),
+    }
+    .layer(build_cdn_cors_layer(&state));
 
     let seo = Router::new()
         .route("/{space_slug}", get(seo::space_snapshot))
@@ -114,7 +143,6 @@ pub fn router(state: AppState) -> Router {
             crate::federation::dm::DM_SEND_PATH,
             post(crate::federation::dm::handle_send),
         )
-        .nest_service("/cdn", cdn_service)
         .nest("/s", seo)
         .nest("/api/v1", api);
 
@@ -123,8 +151,19 @@ pub fn router(state: AppState) -> Router {
     #[cfg(feature = "test-seed")]
     let base = base.route("/test/seed", post(test_seed::seed));
 
-    base.layer(TraceLayer::new_for_http())
-        .layer(build_cors_layer())
+    // App-route CORS wraps only the routes above; /cdn is added afterwards
+    // with its own policy so the two layers never both answer a request.
+    base.layer(build_cors_layer(&state))
+        .nest_service("/cdn", cdn_service)
+        .layer(axum_mw::from_fn_with_state(
+            state.clone(),
+            security_headers_middleware,
+        ))
+        .layer(axum_mw::from_fn_with_state(
+            state.clone(),
+            query_count_middleware,
+        ))
+        .layer(TraceLayer::new_for_http())
         .with_state(state)
 }
 
@@ -135,9 +174,25 @@ fn api_routes(state: &AppState) -> Router<AppState> {
         .route("/auth/login", post(auth::login))
         .route("/auth/login/mfa", post(auth::login_mfa))
         .route("/auth/guest", post(auth::guest))
+        .route("/auth/login-tickets", post(auth::create_login_ticket))
+        .route("/auth/login-tickets/{ticket}", get(auth::get_login_ticket))
+        .route(
+            "/auth/login-tickets/{ticket}/approve",
+            post(auth::approve_login_ticket),
+        )
         .route("/auth/logout", post(auth::logout))
         .route("/auth/sessions/revoke-all", post(auth::revoke_all_sessions))
         .route("/auth/change-password", post(auth::change_password))
+        .route("/auth/password-reset", post(auth::request_password_reset))
+        .route(
+            "/auth/password-reset/confirm",
+            post(auth::confirm_password_reset),
+        )
+        .route("/auth/verify-email", post(auth::verify_email))
+        .route(
+            "/auth/verify-email/resend",
+            post(auth::resend_email_verification),
+        )
         // 2FA (all require auth)
         .route("/auth/2fa/enable", post(auth::enable_2fa))
         .route("/auth/2fa/verify", post(auth::verify_2fa))
@@ -146,6 +201,30 @@ fn api_routes(state: &AppState) -> Router<AppState> {
             "/auth/2fa/backup-codes",
             post(auth::regenerate_backup_codes),
         )
+        .route("/auth/mfa/totp/enable", post(auth::enable_2fa))
+        .route("/auth/mfa/totp/verify", post(auth::verify_2fa))
+        .route("/auth/mfa/totp/disable", post(auth::disable_2fa))
+        .route(
+            "/auth/mfa/backup-codes",
+            post(auth::regenerate_backup_codes),
+        )
+        .route("/auth/mfa/verify", post(auth::verify_mfa))
+        // Passkeys (login is public, the rest require auth)
+        .route("/auth/passkeys", get(passkeys::list_passkeys))
+        .route(
+            "/auth/passkeys/register/start",
+            post(passkeys::start_registration),
+        )
+        .route(
+            "/auth/passkeys/register/finish",
+            post(passkeys::finish_registration),
+        )
+        .route("/auth/passkeys/login/start", post(passkeys::start_login))
+        .route("/auth/passkeys/login/finish", post(passkeys::finish_login))
+        .route(
+            "/auth/passkeys/{passkey_id}",
+            patch(passkeys::rename_passkey).delete(passkeys::delete_passkey),
+        )
         // Gateway (public, no auth needed)
         .route("/gateway", get(gateway::get_gateway))
         // Users
@@ -160,6 +239,11 @@ fn api_routes(state: &AppState) -> Router<AppState> {
             get(users::export_current_user_data),
         )
         .route("/users/@me/spaces", get(users::get_current_user_spaces))
+        .route("/users/@me/sessions", get(auth::list_sessions))
+        .route(
+            "/users/@me/sessions/{session_id}",
+            delete(auth::delete_session),
+        )
         .route(
             "/users/@me/channels",
             get(users::get_current_user_channels).post(users::create_dm_channel),
@@ -168,7 +252,37 @@ fn api_routes(state: &AppState) -> Router<AppState> {
             "/users/@me/read-states",
             get(read_states::get_unread_channels),
         )
+        .route(
+            "/users/@me/settings",
+            get(user_settings::get_settings).patch(user_settings::update_settings),
+        )
+        .route(
+            "/users/@me/settings/spaces",
+            get(user_settings::get_space_layout).put(user_settings::update_space_layout),
+        )
+        .route("/users/@me/drafts", get(drafts::list_drafts))
         .route("/users/@me/mutes", get(mutes::list_mutes))
+        .route(
+            "/users/@me/channel-states",
+            get(mutes::get_channel_states).patch(mutes::update_channel_states),
+        )
+        .route(
+            "/users/@me/everyone-suppressions",
+            get(mutes::list_everyone_suppressions),
+        )
+        .route(
+            "/users/@me/spaces/{space_id}/settings",
+            get(mutes::get_space_settings).patch(mutes::update_space_settings),
+        )
+        .route("/users/@me/push/vapid-key", get(push::get_vapid_key))
+        .route(
+            "/users/@me/push/subscriptions",
+            get(push::list_subscriptions).post(push::create_subscription),
+        )
+        .route(
+            "/users/@me/push/subscriptions/{subscription_id}",
+            delete(push::delete_subscription),
+        )
         .route(
             "/users/@me/relationships",
             get(relationships::list_relationships),
@@ -180,6 +294,10 @@ fn api_routes(state: &AppState) -> Router<AppState> {
         .route("/users/{user_id}", get(users::get_user))
         // Spaces
         .route("/spaces/public", get(spaces::list_public_spaces))
+        .route(
+            "/spaces/public/categories",
+            get(spaces::list_directory_categories),
+        )
         .route("/spaces", post(spaces::create_space))
         .route(
             "/spaces/{space_id}",
@@ -187,6 +305,19 @@ fn api_routes(state: &AppState) -> Router<AppState> {
                 .patch(spaces::update_space)
                 .delete(spaces::delete_space),
         )
+        .route("/spaces/{space_id}/widget.json", get(widget::get_widget))
+        .route(
+            "/spaces/{space_id}/welcome-screen",
+            get(screening::get_welcome_screen).patch(screening::update_welcome_screen),
+        )
+        .route(
+            "/spaces/{space_id}/member-screening",
+            get(screening::get_member_screening).patch(screening::update_member_screening),
+        )
+        .route(
+            "/spaces/{space_id}/member-screening/accept",
+            post(screening::accept_member_screening),
+        )
         .route(
             "/spaces/{space_id}/channels",
             get(spaces::list_channels)
@@ -220,13 +351,42 @@ fn api_routes(state: &AppState) -> Router<AppState> {
         )
         // Bans
         .route("/spaces/{space_id}/bans", get(bans::list_bans))
+        .route("/spaces/{space_id}/bans/export", get(bans::export_bans))
+        .route("/spaces/{space_id}/bans/import", post(bans::import_bans))
+        .route(
+            "/spaces/{space_id}/ban-sync",
+            get(bans::get_ban_sync)
+                .post(bans::create_ban_sync_group)
+                .put(bans::join_ban_sync_group)
+                .delete(bans::leave_ban_sync_group),
+        )
         .route(
             "/spaces/{space_id}/bans/{user_id}",
             get(bans::get_ban)
                 .put(bans::create_ban)
                 .delete(bans::delete_ban),
         )
+        // AutoMod
+        .route(
+            "/spaces/{space_id}/automod/rules",
+            get(automod::list_rules).post(automod::create_rule),
+        )
+        .route(
+            "/spaces/{space_id}/automod/rules/{rule_id}",
+            get(automod::get_rule)
+                .patch(automod::update_rule)
+                .delete(automod::delete_rule),
+        )
+        .route("/spaces/{space_id}/automod/stats", get(automod::get_stats))
+        .route(
+            "/spaces/{space_id}/automod/hits/{hit_id}/override",
+            post(automod::override_hit),
+        )
         // Audit log
+        .route(
+            "/spaces/{space_id}/audit-logs",
+            get(audit_log::list_audit_log),
+        )
         .route(
             "/spaces/{space_id}/audit-log",
             get(audit_log::list_audit_log),
@@ -240,6 +400,7 @@ fn api_routes(state: &AppState) -> Router<AppState> {
             "/spaces/{space_id}/reports/{report_id}",
             get(reports::get_report).patch(reports::resolve_report),
         )
+        .route("/reports", post(reports::file_report))
         // Roles
         .route(
             "/spaces/{space_id}/roles",
@@ -251,6 +412,10 @@ fn api_routes(state: &AppState) -> Router<AppState> {
             "/spaces/{space_id}/roles/{role_id}",
             patch(roles::update_role).delete(roles::delete_role),
         )
+        .route(
+            "/spaces/{space_id}/roles/{role_id}/members",
+            get(members::list_role_members),
+        )
         // Channels
         .route(
             "/channels/{channel_id}",
@@ -264,11 +429,42 @@ fn api_routes(state: &AppState) -> Router<AppState> {
         )
         // Read states
         .route("/channels/{channel_id}/ack", post(read_states::ack_channel))
+        .route(
+            "/channels/{channel_id}/messages/{message_id}/ack",
+            post(read_states::ack_message),
+        )
+        // Drafts
+        .route(
+            "/channels/{channel_id}/draft",
+            get(drafts::get_draft)
+                .put(drafts::put_draft)
+                .delete(drafts::delete_draft),
+        )
         // Channel mutes
         .route(
             "/channels/{channel_id}/mute",
             put(mutes::mute_channel).delete(mutes::unmute_channel),
         )
+        .route(
+            "/spaces/{space_id}/email-domains",
+            get(email_domains::get_email_domains).put(email_domains::set_email_domains),
+        )
+        .route(
+            "/spaces/{space_id}/raid-protection",
+            get(raid::get_raid_protection).patch(raid::update_raid_protection),
+        )
+        .route(
+            "/spaces/{space_id}/lockdown",
+            put(raid::start_lockdown).delete(raid::end_lockdown),
+        )
+        .route(
+            "/spaces/{space_id}/archive",
+            put(spaces::archive_space).delete(spaces::unarchive_space),
+        )
+        .route(
+            "/spaces/{space_id}/suppress-everyone",
+            put(mutes::suppress_everyone).delete(mutes::unsuppress_everyone),
+        )
         .route(
             "/channels/{channel_id}/permissions",
             get(channels::list_overwrites),
@@ -277,6 +473,15 @@ fn api_routes(state: &AppState) -> Router<AppState> {
             "/channels/{channel_id}/permissions/{overwrite_id}",
             put(channels::upsert_overwrite).delete(channels::delete_overwrite),
         )
+        .route("/channels/{channel_id}/lock", post(channels::lock_channel))
+        .route(
+            "/channels/{channel_id}/unlock",
+            post(channels::unlock_channel),
+        )
+        .route(
+            "/channels/{channel_id}/purge",
+            post(messages::purge_messages),
+        )
         // Messages
         .route(
             "/channels/{channel_id}/messages",
@@ -292,17 +497,99 @@ fn api_routes(state: &AppState) -> Router<AppState> {
                 .patch(messages::update_message)
                 .delete(messages::delete_message),
         )
+        .route(
+            "/channels/{channel_id}/messages/{message_id}/history",
+            get(messages::get_message_history),
+        )
         .route(
             "/channels/{channel_id}/messages/bulk-delete",
             post(messages::bulk_delete_messages),
         )
         .route(
             "/channels/{channel_id}/messages/{message_id}/threads",
-            get(messages::get_thread_info),
+            get(messages::get_thread_info).post(threads::create_thread_from_message),
+        )
+        // Webhooks
+        .route(
+            "/channels/{channel_id}/webhooks",
+            get(webhooks::list_channel_webhooks).post(webhooks::create_webhook),
+        )
+        .route(
+            "/spaces/{space_id}/webhooks",
+            get(webhooks::list_space_webhooks),
+        )
+        .route(
+            "/spaces/{space_id}/integrations",
+            get(integrations::list_integrations),
+        )
+        .route(
+            "/webhooks/{webhook_id}",
+            get(webhooks::get_webhook)
+                .patch(webhooks::update_webhook)
+                .delete(webhooks::delete_webhook),
+        )
+        .route(
+            "/channels/{channel_id}/followers",
+            post(webhooks::follow_channel),
+        )
+        .route(
+            "/channels/{channel_id}/messages/{message_id}/crosspost",
+            post(webhooks::crosspost_message),
+        )
+        .route(
+            "/channels/{channel_id}/messages/{message_id}/share",
+            post(message_shares::create_share),
+        )
+        .route(
+            "/channels/{channel_id}/messages/{message_id}/shares",
+            get(message_shares::list_shares),
+        )
+        .route(
+            "/channels/{channel_id}/messages/{message_id}/shares/{share_id}",
+            delete(message_shares::revoke_share),
+        )
+        .route(
+            "/shared-messages/{token}",
+            get(message_shares::get_shared_message),
+        )
+        .route(
+            "/webhooks/{webhook_id}/{token}",
+            post(webhooks::execute_webhook),
         )
         .route(
             "/channels/{channel_id}/threads",
-            get(messages::list_active_threads),
+            get(messages::list_active_threads).post(threads::create_standalone_thread),
+        )
+        .route(
+            "/channels/{channel_id}/threads/active",
+            get(threads::list_active_threads),
+        )
+        .route(
+            "/channels/{channel_id}/threads/archived",
+            get(threads::list_archived_threads),
+        )
+        .route(
+            "/channels/{channel_id}/threads/{thread_id}",
+            get(threads::get_thread)
+                .patch(threads::update_thread)
+                .delete(threads::delete_thread),
+        )
+        .route(
+            "/channels/{channel_id}/threads/{thread_id}/members",
+            get(threads::list_thread_members),
+        )
+        .route(
+            "/channels/{channel_id}/threads/{thread_id}/members/@me",
+            put(threads::join_thread).delete(threads::leave_thread),
+        )
+        .route("/channels/{channel_id}/posts", get(forum::list_posts))
+        .route(
+            "/channels/{channel_id}/tags",
+            get(forum::list_tags).post(forum::create_tag),
+        )
+        .route(
+            "/channels/{channel_id}/tags/{tag_id}",
+            patch(forum::update_tag).delete(forum::delete_tag),
         )
         .route("/channels/{channel_id}/pins", get(messages::list_pins))
         .route(
@@ -330,12 +617,30 @@ fn api_routes(state: &AppState) -> Router<AppState> {
             "/channels/{channel_id}/messages/{message_id}/reactions",
             delete(reactions::remove_all_reactions),
         )
+        // Polls
+        .route(
+            "/channels/{channel_id}/polls/{message_id}/answers/{answer_id}/@me",
+            put(polls::add_vote).delete(polls::remove_vote),
+        )
+        .route(
+            "/channels/{channel_id}/polls/{message_id}/answers/{answer_id}",
+            get(polls::list_voters),
+        )
         // Invites
         .route(
             "/invites/{code}",
             get(invites::get_invite).delete(invites::delete_invite),
         )
+        .route("/invites/{code}/preview", get(invites::preview_invite))
         .route("/invites/{code}/accept", post(invites::accept_invite))
+        .route(
+            "/spaces/{space_id}/invites/{code}/stats",
+            get(invites::get_invite_stats),
+        )
+        .route(
+            "/spaces/{space_id}/vanity-url",
+            get(invites::get_vanity_url).patch(invites::update_vanity_url),
+        )
         .route(
             "/spaces/{space_id}/invites",
             get(invites::list_space_invites).post(invites::create_space_invite),
@@ -364,6 +669,29 @@ fn api_routes(state: &AppState) -> Router<AppState> {
                 .patch(emojis::update_emoji)
                 .delete(emojis::delete_emoji),
         )
+        .route(
+            "/spaces/{space_id}/emojis/pending",
+            get(emojis::list_pending_emojis),
+        )
+        .route(
+            "/spaces/{space_id}/emojis/{emoji_id}/approve",
+            post(emojis::approve_emoji),
+        )
+        .route(
+            "/spaces/{space_id}/emojis/{emoji_id}/reject",
+            post(emojis::reject_emoji),
+        )
+        // Stickers
+        .route(
+            "/spaces/{space_id}/stickers",
+            get(stickers::list_stickers).post(stickers::create_sticker),
+        )
+        .route(
+            "/spaces/{space_id}/stickers/{sticker_id}",
+            get(stickers::get_sticker)
+                .patch(stickers::update_sticker)
+                .delete(stickers::delete_sticker),
+        )
         // Plugins
         .route(
             "/spaces/{space_id}/plugins",
@@ -453,10 +781,31 @@ fn api_routes(state: &AppState) -> Router<AppState> {
             get(voice::get_voice_status),
         )
         .route("/channels/{channel_id}/voice/join", post(voice::join_voice))
+        .route(
+            "/channels/{channel_id}/voice/refresh-token",
+            post(voice::refresh_voice_token),
+        )
+        .route(
+            "/channels/{channel_id}/voice/stream",
+            post(voice::start_stream).delete(voice::stop_stream),
+        )
+        .route(
+            "/channels/{channel_id}/voice/stats",
+            get(voice::get_voice_stats).post(voice::report_voice_stats),
+        )
         .route(
             "/channels/{channel_id}/voice/leave",
             delete(voice::leave_voice),
         )
+        // Stage speakers and audience
+        .route(
+            "/channels/{channel_id}/voice-states/@me",
+            patch(voice::update_own_stage_state),
+        )
+        .route(
+            "/channels/{channel_id}/voice-states/{user_id}",
+            patch(voice::update_stage_state),
+        )
         // DM call signaling
         .route("/channels/{channel_id}/call/ring", post(voice::ring_call))
         .route(
@@ -478,18 +827,91 @@ fn api_routes(state: &AppState) -> Router<AppState> {
             "/applications/@me/reset-token",
             post(applications::reset_token),
         )
-        // Interactions (stubs)
+        .route(
+            "/applications/@me/ip-allowlist",
+            get(applications::get_ip_allowlist).put(applications::update_ip_allowlist),
+        )
+        .route(
+            "/applications/@me/oauth2/reset-secret",
+            post(applications::reset_client_secret),
+        )
+        .route(
+            "/applications/@me/signing-keys",
+            get(applications::list_signing_keys),
+        )
+        .route(
+            "/applications/@me/signing-keys/rotate",
+            post(applications::rotate_signing_key),
+        )
+        .route(
+            "/applications/@me/signing-keys/{key_id}",
+            delete(applications::revoke_signing_key),
+        )
+        .route(
+            "/applications/@me/event-subscriptions",
+            get(applications::list_event_subscriptions)
+                .post(applications::create_event_subscription),
+        )
+        .route(
+            "/applications/@me/event-subscriptions/{subscription_id}",
+            patch(applications::update_event_subscription)
+                .delete(applications::delete_event_subscription),
+        )
+        .route(
+            "/applications/@me/event-subscriptions/{subscription_id}/deliveries",
+            get(applications::list_event_deliveries),
+        )
+        .route(
+            "/applications/@me/event-subscriptions/{subscription_id}/deliveries/{delivery_id}/retry",
+            post(applications::retry_event_delivery),
+        )
+        // OAuth2
+        .route(
+            "/oauth2/authorize",
+            get(oauth2::get_authorize).post(oauth2::authorize),
+        )
+        .route("/oauth2/token", post(oauth2::token))
+        .route("/oauth2/token/revoke", post(oauth2::revoke))
+        // Interactions
         .route(
             "/applications/{app_id}/commands",
             get(interactions::list_global_commands).post(interactions::create_global_command),
         )
+        .route(
+            "/applications/{app_id}/commands/{command_id}",
+            get(interactions::get_command).delete(interactions::delete_command),
+        )
+        .route(
+            "/applications/{app_id}/spaces/{space_id}/commands",
+            get(interactions::list_app_space_commands).post(interactions::create_space_command),
+        )
+        .route(
+            "/spaces/{space_id}/commands",
+            get(interactions::list_space_commands),
+        )
+        .route("/interactions", post(interactions::create_interaction))
         .route(
             "/interactions/{interaction_id}/{token}/callback",
             post(interactions::interaction_callback),
         )
         // Admin
+        .route("/admin/metrics", get(admin::get_metrics))
+        .route("/admin/doctor", get(admin::run_doctor))
+        .route("/admin/doctor/repair", post(admin::repair_doctor))
         .route("/admin/spaces", get(admin::list_spaces))
-        .route("/admin/spaces/{space_id}", patch(admin::update_space))
+        .route(
+            "/admin/spaces/{space_id}",
+            patch(admin::update_space).delete(admin::delete_space),
+        )
+        .route(
+            "/admin/vanity-urls/{code}",
+            delete(admin::revoke_vanity_url),
+        )
+        .route("/admin/reports", get(admin::list_reports))
+        .route(
+            "/admin/reports/{report_id}",
+            get(admin::get_report).patch(admin::update_report),
+        )
         .route("/admin/users", get(admin::list_users))
         .route(
             "/admin/users/{user_id}",
@@ -499,6 +921,30 @@ fn api_routes(state: &AppState) -> Router<AppState> {
             "/admin/users/{user_id}/reset-password",
             post(admin::reset_user_password),
         )
+        .route("/admin/users/{user_id}/logout", post(admin::logout_user))
+        // Admin: trust & safety blocklists
+        .route("/admin/blocklists/{kind}", get(admin::list_blocklist))
+        .route(
+            "/admin/blocklists/{kind}/{value}",
+            put(admin::add_blocklist_entry).delete(admin::delete_blocklist_entry),
+        )
+        // Admin: content-safety review and the hash list
+        .route(
+            "/admin/content-safety/flags",
+            get(admin::list_content_safety_flags),
+        )
+        .route(
+            "/admin/content-safety/flags/{flag_id}",
+            get(admin::get_content_safety_flag).patch(admin::review_content_safety_flag),
+        )
+        .route(
+            "/admin/content-safety/hashes",
+            get(admin::list_content_safety_hashes),
+        )
+        .route(
+            "/admin/content-safety/hashes/{hash}",
+            put(admin::add_content_safety_hash).delete(admin::delete_content_safety_hash),
+        )
         // Admin: federation peer management
         .route(
             "/admin/federation/peers",
@@ -517,6 +963,7 @@ fn api_routes(state: &AppState) -> Router<AppState> {
         .route("/settings", get(settings::get_public_settings))
         // Version
         .route("/version", get(health::version))
+        .route("/schema", get(health::schema))
         // Gateway info (authenticated)
         .route("/gateway/bot", get(gateway::get_gateway_bot))
         // Rate limit on all API routes
@@ -526,41 +973,104 @@ fn api_routes(state: &AppState) -> Router<AppState> {
         ))
 }
 
-/// Build the CORS layer. If `CORS_ALLOWED_ORIGINS` is set, restrict to those
-/// origins (comma-separated). Otherwise, allow any origin for backward
-/// compatibility with game clients that don't send an Origin header.
-fn build_cors_layer() -> CorsLayer {
-    use axum::http::{HeaderName, Method};
+/// True when `origin` is in `allowed`. `*` matches anything, except on a
+/// `credentialed` policy, where reflecting every origin would let any site
+/// act as the user.
+fn origin_allowed(allowed: &[String], origin: &str, credentialed: bool) -> bool {
+    allowed
+        .iter()
+        .any(|o| (o == "*" && !credentialed) || o == origin)
+}
 
-    let methods = [
-        Method::GET,
-        Method::POST,
-        Method::PUT,
-        Method::PATCH,
-        Method::DELETE,
-        Method::OPTIONS,
-    ];
-    let headers = [
-        HeaderName::from_static("authorization"),
-        HeaderName::from_static("content-type"),
-        HeaderName::from_static("accept"),
-        HeaderName::from_static("user-agent"),
-    ];
+/// Build the CORS layer for app routes (`/api`, `/ws`, pages). Allowed origins
+/// come from the `cors_allowed_origins` server setting (read per request, so
+/// admins can change it without a restart), falling back to
+/// `CorsConfig::allowed_origins`. If neither lists anything, any origin is
+/// allowed for backward compatibility with game clients — except when
+/// credentials are enabled, where an open policy would let any site act as
+/// the user, so cross-origin requests are refused instead and `*` entries
+/// match nothing.
+fn build_cors_layer(state: &AppState) -> CorsLayer {
+    use axum::http::HeaderValue;
+    use tower_http::cors::AllowOrigin;
 
-    match std::env::var("CORS_ALLOWED_ORIGINS") {
-        Ok(origins) if !origins.is_empty() => {
-            let origins: Vec<_> = origins
-                .split(',')
-                .filter_map(|o| o.trim().parse().ok())
-                .collect();
-            CorsLayer::new()
-                .allow_origin(origins)
-                .allow_methods(methods)
-                .allow_headers(headers)
+    let cors = state.cors.clone();
+    let settings = state.settings.clone();
+    let allow_credentials = cors.allow_credentials;
+    let allow_origin = AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+        let Ok(origin) = origin.to_str() else {
+            return false;
+        };
+        let configured = settings.load();
+        let from_settings = configured
+            .cors_allowed_origins
+            .as_deref()
+            .map(parse_origin_list)
+            .unwrap_or_default();
+        let allowed = if !from_settings.is_empty() {
+            &from_settings
+        } else {
+            &cors.allowed_origins
+        };
+        if allowed.is_empty() {
+            return !cors.allow_credentials;
         }
-        _ => CorsLayer::new()
-            .allow_origin(Any)
-            .allow_methods(methods)
-            .allow_headers(headers),
+        origin_allowed(allowed, origin, cors.allow_credentials)
+    });
+
+    base_cors_layer(state.cors.max_age_secs)
+        .allow_origin(allow_origin)
+        .allow_credentials(allow_credentials)
+}
+
+/// `/cdn/<key>` for object-store backends: redirect to a presigned URL.
+async fn cdn_redirect(state: State<AppState>, uri: Uri) -> Response {
+    let key = uri.path().trim_start_matches('/');
+    if crate::storage::validate_key(key).is_err() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    match state.storage.presigned_url(key) {
+        Some(url) => Redirect::temporary(&url).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Build the CORS layer for `/cdn`. Uploaded media is public and meant to be
+/// embeddable, so this is open by default and never credentialed.
+fn build_cdn_cors_layer(state: &AppState) -> CorsLayer {
+    use axum::http::HeaderValue;
+    use tower_http::cors::{AllowOrigin, Any};
+
+    let allowed = state.cors.cdn_allowed_origins.clone();
+    let layer = base_cors_layer(state.cors.max_age_secs);
+    if allowed.is_empty() || allowed.iter().any(|o| o == "*") {
+        return layer.allow_origin(Any);
     }
+    layer.allow_origin(AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+        origin
+            .to_str()
+            .map(|o| origin_allowed(&allowed, o, false))
+            .unwrap_or(false)
+    }))
+}
+
+fn base_cors_layer(max_age_secs: u64) -> CorsLayer {
+    use axum::http::{HeaderName, Method};
+
+    CorsLayer::new()
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers([
+            HeaderName::from_static("authorization"),
+            HeaderName::from_static("content-type"),
+            HeaderName::from_static("accept"),
+            HeaderName::from_static("user-agent"),
+        ])
+        .max_age(std::time::Duration::from_secs(max_age_secs))
 }
@@ -5,7 +5,8 @@ use crate::db;
 use crate::error::AppError;
 use crate::gateway::events::GatewayBroadcast;
 use crate::middleware::auth::AuthUser;
-use crate::middleware::permissions::require_channel_membership;
+use crate::middleware::permissions::{require_channel_membership, require_membership};
+use crate::models::mute::{UpdateChannelStates, UpdateSpaceNotificationSettings};
 use crate::state::AppState;
 
 /// PUT /channels/{channel_id}/mute
@@ -35,6 +36,7 @@ pub async fn mute_channel(
             target_user_ids: Some(vec![auth.user_id.clone()]),
             event,
             intent: "channels".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -60,6 +62,7 @@ pub async fn unmute_channel(
             target_user_ids: Some(vec![auth.user_id.clone()]),
             event,
             intent: "channels".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -74,3 +77,209 @@ pub async fn list_mutes(
     let mutes = db::mutes::list_mutes_for_user(&state.db, &auth.user_id).await?;
     Ok(Json(serde_json::json!({ "data": mutes })))
 }
+
+/// Most channels one `PATCH /users/@me/channel-states` may touch.
+pub const MAX_CHANNEL_STATE_UPDATES: usize = 200;
+
+/// GET /users/@me/channel-states
+/// The user's directly muted and collapsed channels, for restoring the
+/// sidebar on another device.
+pub async fn get_channel_states(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let muted: Vec<String> = db::mutes::list_mutes_for_user(&state.db, &auth.user_id)
+        .await?
+        .into_iter()
+        .map(|m| m.channel_id)
+        .collect();
+    let collapsed = db::mutes::list_collapsed_channel_ids(&state.db, &auth.user_id).await?;
+    Ok(Json(
+        serde_json::json!({ "data": { "muted": muted, "collapsed": collapsed } }),
+    ))
+}
+
+/// PATCH /users/@me/channel-states
+/// Set `muted` and/or `collapsed` for many channels in one write.
+pub async fn update_channel_states(
+    state: State<AppState>,
+    auth: AuthUser,
+    Json(input): Json<UpdateChannelStates>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if input.channels.is_empty() || input.channels.len() > MAX_CHANNEL_STATE_UPDATES {
+        return Err(AppError::BadRequest(format!(
+            "channels must have between 1 and {MAX_CHANNEL_STATE_UPDATES} entries"
+        )));
+    }
+    let mut seen = std::collections::HashSet::new();
+    for update in &input.channels {
+        if !seen.insert(update.channel_id.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "channel {} is listed more than once",
+                update.channel_id
+            )));
+        }
+        let channel = db::channels::get_channel_row(&state.db, &update.channel_id).await?;
+        if channel.space_id.is_some() {
+            require_channel_membership(&state.db, &update.channel_id, &auth.user_id).await?;
+        }
+    }
+
+    db::mutes::apply_channel_states(
+        &state.db,
+        &auth.user_id,
+        &input.channels,
+        state.db_is_postgres,
+    )
+    .await?;
+
+    // Sync the other sessions; the gateway also refreshes its mute filter.
+    if let Some(ref gtx) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "channel_states.update",
+            "data": { "channels": input.channels }
+        });
+        let _ = gtx.send(GatewayBroadcast {
+            space_id: None,
+            target_user_ids: Some(vec![auth.user_id.clone()]),
+            event,
+            intent: "channels".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+
+    get_channel_states(state, auth).await
+}
+
+/// PUT /spaces/{space_id}/suppress-everyone
+/// Opt out of mention badges from @everyone announcements in this space.
+pub async fn suppress_everyone(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_membership(&state.db, &space_id, &auth.user_id).await?;
+    db::mutes::suppress_everyone(&state.db, &auth.user_id, &space_id, state.db_is_postgres).await?;
+    broadcast_everyone_suppression(&state, &auth.user_id, &space_id, true).await;
+    Ok(Json(
+        serde_json::json!({ "data": { "space_id": space_id, "suppress_everyone": true } }),
+    ))
+}
+
+/// DELETE /spaces/{space_id}/suppress-everyone
+pub async fn unsuppress_everyone(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    db::mutes::unsuppress_everyone(&state.db, &auth.user_id, &space_id).await?;
+    broadcast_everyone_suppression(&state, &auth.user_id, &space_id, false).await;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// GET /users/@me/everyone-suppressions
+pub async fn list_everyone_suppressions(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_ids = db::mutes::list_everyone_suppressions(&state.db, &auth.user_id).await?;
+    Ok(Json(serde_json::json!({ "data": space_ids })))
+}
+
+/// Sync the setting to the user's other sessions.
+async fn broadcast_everyone_suppression(
+    state: &AppState,
+    user_id: &str,
+    space_id: &str,
+    suppressed: bool,
+) {
+    if let Some(ref gtx) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "everyone_suppression.update",
+            "data": { "space_id": space_id, "suppress_everyone": suppressed }
+        });
+        let _ = gtx.send(GatewayBroadcast {
+            space_id: None,
+            target_user_ids: Some(vec![user_id.to_string()]),
+            event,
+            intent: "spaces".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
+
+/// GET /users/@me/spaces/{space_id}/settings
+pub async fn get_space_settings(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_membership(&state.db, &space_id, &auth.user_id).await?;
+    let settings = db::mutes::get_space_settings(&state.db, &auth.user_id, &space_id).await?;
+    Ok(Json(serde_json::json!({ "data": settings })))
+}
+
+/// PATCH /users/@me/spaces/{space_id}/settings
+/// Mute the space, suppress @everyone in it, and mute or keep unmuted
+/// individual channels. Omitted fields are left as they are.
+pub async fn update_space_settings(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<UpdateSpaceNotificationSettings>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_membership(&state.db, &space_id, &auth.user_id).await?;
+    let overrides = input.channel_overrides.as_deref().unwrap_or_default();
+    if overrides.len() > MAX_CHANNEL_STATE_UPDATES {
+        return Err(AppError::BadRequest(format!(
+            "channel_overrides may have at most {MAX_CHANNEL_STATE_UPDATES} entries"
+        )));
+    }
+    let mut seen = std::collections::HashSet::new();
+    for o in overrides {
+        if !seen.insert(o.channel_id.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "channel {} is listed more than once",
+                o.channel_id
+            )));
+        }
+        let channel = db::channels::get_channel_row(&state.db, &o.channel_id).await?;
+        if channel.space_id.as_deref() != Some(space_id.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "channel {} is not in this space",
+                o.channel_id
+            )));
+        }
+        require_channel_membership(&state.db, &o.channel_id, &auth.user_id).await?;
+    }
+
+    db::mutes::update_space_settings(
+        &state.db,
+        &auth.user_id,
+        &space_id,
+        &input,
+        state.db_is_postgres,
+    )
+    .await?;
+    let settings = db::mutes::get_space_settings(&state.db, &auth.user_id, &space_id).await?;
+
+    // Sync the other sessions; the gateway also refreshes its mute filter.
+    if let Some(ref gtx) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "notification_settings.update",
+            "data": settings
+        });
+        let _ = gtx.send(GatewayBroadcast {
+            space_id: None,
+            target_user_ids: Some(vec![auth.user_id.clone()]),
+            event,
+            intent: "spaces".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+
+    Ok(Json(serde_json::json!({ "data": settings })))
+}
@@ -0,0 +1,329 @@
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Form, Json};
+use data_encoding::BASE64;
+
+use crate::db;
+use crate::error::AppError;
+use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::require_permission;
+use crate::models::application::Application;
+use crate::models::oauth2::{
+    AuthorizeQuery, AuthorizeRequest, OAuthGrant, RevokeRequest, TokenRequest,
+};
+use crate::oauth2::{parse_scopes, redirect_with, ACCESS_TOKEN_TTL_SECS, SCOPE_BOT};
+use crate::state::AppState;
+
+/// Errors from the token endpoint, in the RFC 6749 shape
+/// (`{"error": "invalid_grant", "error_description": ...}`) that OAuth2
+/// client libraries expect.
+pub enum OAuthError {
+    InvalidClient,
+    InvalidGrant(&'static str),
+    InvalidRequest(String),
+    UnsupportedGrantType,
+    App(AppError),
+}
+
+impl From<AppError> for OAuthError {
+    fn from(e: AppError) -> Self {
+        OAuthError::App(e)
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let (status, error, description) = match self {
+            OAuthError::App(e) => return e.into_response(),
+            OAuthError::InvalidClient => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_client",
+                "client authentication failed".to_string(),
+            ),
+            OAuthError::InvalidGrant(msg) => {
+                (StatusCode::BAD_REQUEST, "invalid_grant", msg.to_string())
+            }
+            OAuthError::InvalidRequest(msg) => (StatusCode::BAD_REQUEST, "invalid_request", msg),
+            OAuthError::UnsupportedGrantType => (
+                StatusCode::BAD_REQUEST,
+                "unsupported_grant_type",
+                "grant_type must be authorization_code or refresh_token".to_string(),
+            ),
+        };
+        (
+            status,
+            Json(serde_json::json!({ "error": error, "error_description": description })),
+        )
+            .into_response()
+    }
+}
+
+/// Application behind `client_id`, with the redirect URI to use: the one
+/// given, which must be registered, or the only registered one.
+async fn client_and_redirect(
+    state: &AppState,
+    client_id: &str,
+    redirect_uri: Option<&str>,
+) -> Result<(Application, Option<String>), AppError> {
+    let app = db::auth::get_application(&state.db, client_id).await?;
+    let redirect_uri = match redirect_uri {
+        Some(uri) if app.redirect_uris.iter().any(|r| r == uri) => Some(uri.to_string()),
+        Some(_) => {
+            return Err(AppError::BadRequest(
+                "redirect_uri is not registered for this application".into(),
+            ))
+        }
+        None if app.redirect_uris.len() == 1 => Some(app.redirect_uris[0].clone()),
+        None => None,
+    };
+    Ok((app, redirect_uri))
+}
+
+/// `GET /oauth2/authorize` -- what a consent screen needs: the application
+/// and the scopes it asks for. Nothing is granted until the POST.
+pub async fn get_authorize(
+    state: State<AppState>,
+    _auth: AuthUser,
+    Query(query): Query<AuthorizeQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let scopes = parse_scopes(&query.scope)?;
+    let (app, redirect_uri) =
+        client_and_redirect(&state, &query.client_id, query.redirect_uri.as_deref()).await?;
+    Ok(Json(serde_json::json!({
+        "data": {
+            "application": {
+                "id": app.id,
+                "name": app.name,
+                "icon": app.icon,
+                "description": app.description,
+                "bot_user_id": app.bot_user_id,
+            },
+            "scopes": scopes,
+            "redirect_uri": redirect_uri,
+        }
+    })))
+}
+
+/// `POST /oauth2/authorize` -- the user approves. With `bot` the
+/// application's bot joins `space_id`; any other scopes produce an
+/// authorization code. Returns the `location` to send the user to.
+pub async fn authorize(
+    state: State<AppState>,
+    auth: AuthUser,
+    Json(input): Json<AuthorizeRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if auth.is_bot || auth.is_guest {
+        return Err(AppError::Forbidden(
+            "only users can authorize applications".into(),
+        ));
+    }
+    if input.response_type.as_deref().is_some_and(|t| t != "code") {
+        return Err(AppError::BadRequest("response_type must be code".into()));
+    }
+    let mut scopes = parse_scopes(&input.scope)?;
+    let (app, redirect_uri) =
+        client_and_redirect(&state, &input.client_id, input.redirect_uri.as_deref()).await?;
+
+    let add_bot = scopes.iter().any(|s| s == SCOPE_BOT);
+    scopes.retain(|s| s != SCOPE_BOT);
+    if !scopes.is_empty() && redirect_uri.is_none() {
+        return Err(AppError::BadRequest("redirect_uri is required".into()));
+    }
+
+    let mut params: Vec<(&str, String)> = Vec::new();
+    if add_bot {
+        let space_id = input.space_id.as_deref().ok_or_else(|| {
+            AppError::BadRequest("space_id is required with the bot scope".into())
+        })?;
+        add_bot_to_space(&state, &auth, &app, space_id).await?;
+        params.push(("space_id", space_id.to_string()));
+    }
+    if let Some(ref redirect_uri) = redirect_uri {
+        if !scopes.is_empty() {
+            let grant = OAuthGrant {
+                application_id: app.id.clone(),
+                user_id: auth.user_id.clone(),
+                scopes,
+            };
+            let code = db::oauth2::create_code(&state.db, &grant, redirect_uri).await?;
+            params.push(("code", code));
+        }
+    }
+    if let Some(st) = input.state {
+        params.push(("state", st));
+    }
+
+    let location = redirect_uri.map(|uri| {
+        let params: Vec<(&str, &str)> = params.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        redirect_with(&uri, &params)
+    });
+    Ok(Json(
+        serde_json::json!({ "data": { "location": location } }),
+    ))
+}
+
+async fn add_bot_to_space(
+    state: &AppState,
+    auth: &AuthUser,
+    app: &Application,
+    space_id: &str,
+) -> Result<(), AppError> {
+    require_permission(&state.db, space_id, auth, "manage_space").await?;
+    let bot_user_id = app
+        .bot_user_id
+        .as_deref()
+        .ok_or_else(|| AppError::BadRequest("this application has no bot".into()))?;
+    if db::bans::get_ban(&state.db, space_id, bot_user_id)
+        .await
+        .is_ok()
+    {
+        return Err(AppError::Forbidden(
+            "this bot is banned from the space".into(),
+        ));
+    }
+
+    let (member, newly_added) =
+        db::members::add_member(&state.db, space_id, bot_user_id, state.db_is_postgres).await?;
+    if !newly_added {
+        return Ok(());
+    }
+    // Bots don't go through member screening.
+    db::screening::clear_pending(&state.db, space_id, bot_user_id).await?;
+
+    let bot = db::users::get_user(&state.db, bot_user_id).await?;
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "member.join",
+            "data": {
+                "space_id": space_id,
+                "user": bot,
+                "joined_at": member.joined_at
+            }
+        });
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: Some(space_id.to_string()),
+            target_user_ids: None,
+            event,
+            intent: "members".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+
+    if let Ok(entry) = db::audit_log::create_entry(
+        &state.db,
+        space_id,
+        &auth.user_id,
+        "bot_add",
+        Some(bot_user_id),
+        Some("user"),
+        None,
+        Some(&serde_json::json!({ "application_id": app.id }).to_string()),
+    )
+    .await
+    {
+        super::audit_log::broadcast_entry(state, &entry).await;
+    }
+    Ok(())
+}
+
+/// Client credentials from HTTP Basic auth, else from the form body.
+fn client_credentials(
+    headers: &HeaderMap,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+) -> Option<(String, String)> {
+    let basic = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Basic "))
+        .and_then(|b| BASE64.decode(b.trim().as_bytes()).ok())
+        .and_then(|b| String::from_utf8(b).ok())
+        .and_then(|s| {
+            s.split_once(':')
+                .map(|(id, secret)| (id.to_string(), secret.to_string()))
+        });
+    basic.or(client_id.zip(client_secret))
+}
+
+async fn authenticate_client(
+    state: &AppState,
+    headers: &HeaderMap,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+) -> Result<String, OAuthError> {
+    let (client_id, secret) =
+        client_credentials(headers, client_id, client_secret).ok_or(OAuthError::InvalidClient)?;
+    if !db::oauth2::check_client_secret(&state.db, &client_id, &secret).await? {
+        return Err(OAuthError::InvalidClient);
+    }
+    Ok(client_id)
+}
+
+/// `POST /oauth2/token` -- redeem an authorization code or refresh token.
+pub async fn token(
+    state: State<AppState>,
+    headers: HeaderMap,
+    Form(input): Form<TokenRequest>,
+) -> Result<Response, OAuthError> {
+    let app_id =
+        authenticate_client(&state, &headers, input.client_id, input.client_secret).await?;
+
+    let grant = match input.grant_type.as_str() {
+        "authorization_code" => {
+            let code = input
+                .code
+                .ok_or_else(|| OAuthError::InvalidRequest("code is required".into()))?;
+            let (grant, redirect_uri) = db::oauth2::consume_code(&state.db, &app_id, &code)
+                .await?
+                .ok_or(OAuthError::InvalidGrant(
+                    "authorization code is invalid or expired",
+                ))?;
+            if input.redirect_uri.as_deref() != Some(redirect_uri.as_str()) {
+                return Err(OAuthError::InvalidGrant(
+                    "redirect_uri does not match the authorization request",
+                ));
+            }
+            grant
+        }
+        "refresh_token" => {
+            let refresh_token = input
+                .refresh_token
+                .ok_or_else(|| OAuthError::InvalidRequest("refresh_token is required".into()))?;
+            db::oauth2::consume_refresh_token(&state.db, &app_id, &refresh_token)
+                .await?
+                .ok_or(OAuthError::InvalidGrant(
+                    "refresh token is invalid or expired",
+                ))?
+        }
+        _ => return Err(OAuthError::UnsupportedGrantType),
+    };
+
+    let (access_token, refresh_token) = db::oauth2::create_token(&state.db, &grant).await?;
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(serde_json::json!({
+            "access_token": access_token,
+            "token_type": "Bearer",
+            "expires_in": ACCESS_TOKEN_TTL_SECS,
+            "refresh_token": refresh_token,
+            "scope": grant.scopes.join(" "),
+        })),
+    )
+        .into_response())
+}
+
+/// `POST /oauth2/token/revoke` -- revoke a token pair (RFC 7009). Unknown
+/// tokens succeed too, so this can't be used to probe for valid ones.
+pub async fn revoke(
+    state: State<AppState>,
+    headers: HeaderMap,
+    Form(input): Form<RevokeRequest>,
+) -> Result<StatusCode, OAuthError> {
+    let app_id =
+        authenticate_client(&state, &headers, input.client_id, input.client_secret).await?;
+    db::oauth2::revoke_token(&state.db, &app_id, &input.token).await?;
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,406 @@
+use axum::extract::{Path, State};
+use axum::http::{Extensions, HeaderMap};
+use axum::Json;
+use serde::Deserialize;
+use sqlx::Row;
+use std::sync::Arc;
+
+use crate::config::WebAuthnConfig;
+use crate::db;
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::ip_allowlist::client_ip;
+use crate::models::passkey::{MAX_PASSKEYS_PER_USER, MAX_PASSKEY_NAME_LEN};
+use crate::routes::auth::{
+    cleanup_expired_tokens, enforce_session_limit, issue_bearer_token, session_device,
+    verify_user_password,
+};
+use crate::state::{AppState, PasskeyCeremony, PasskeyChallenge};
+use crate::webauthn::{self, AssertionCredential, RegistrationCredential, VerifiedAssertion};
+
+#[derive(Debug, Deserialize)]
+pub struct StartRegistrationRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FinishRegistrationRequest {
+    pub name: Option<String>,
+    pub credential: RegistrationCredential,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenamePasskeyRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartLoginRequest {
+    /// Limits the login to this user's passkeys. Without it, any
+    /// discoverable passkey for this server may answer.
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FinishLoginRequest {
+    pub credential: AssertionCredential,
+    pub device_name: Option<String>,
+}
+
+fn require_webauthn(state: &AppState) -> Result<Arc<WebAuthnConfig>, AppError> {
+    state.webauthn.clone().ok_or_else(|| {
+        AppError::BadRequest("passkeys are not configured on this server".to_string())
+    })
+}
+
+fn require_user(auth: &AuthUser) -> Result<(), AppError> {
+    if auth.is_bot || auth.is_guest {
+        return Err(AppError::Forbidden(
+            "only users can have passkeys".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Hands out a challenge for `ceremony`, dropping any that have expired.
+fn issue_challenge(state: &AppState, ceremony: PasskeyCeremony, user_id: Option<String>) -> String {
+    let now = chrono::Utc::now();
+    state.passkey_challenges.retain(|_, c| c.expires_at > now);
+    let challenge = webauthn::generate_challenge();
+    state.passkey_challenges.insert(
+        challenge.clone(),
+        PasskeyChallenge {
+            ceremony,
+            user_id,
+            expires_at: now + chrono::Duration::seconds(webauthn::CHALLENGE_TTL_SECS),
+        },
+    );
+    challenge
+}
+
+/// Consumes the challenge `client_data_json` answers. Each challenge can be
+/// tried once, whether or not the response checks out.
+fn take_challenge(
+    state: &AppState,
+    client_data_json: &str,
+    ceremony: PasskeyCeremony,
+) -> Result<(String, PasskeyChallenge), AppError> {
+    let challenge = webauthn::client_data_challenge(client_data_json)?;
+    state
+        .passkey_challenges
+        .remove(&challenge)
+        .filter(|(_, c)| c.ceremony == ceremony && c.expires_at > chrono::Utc::now())
+        .ok_or_else(|| AppError::Unauthorized("unknown or expired passkey challenge".to_string()))
+}
+
+/// Request options for a login as `user_id`, or `None` if WebAuthn is off or
+/// they have no passkeys. Offered alongside the MFA ticket of a password
+/// login so the second step can use a passkey instead of a code.
+pub(crate) async fn second_factor_options(
+    state: &AppState,
+    user_id: &str,
+) -> Result<Option<serde_json::Value>, AppError> {
+    let Some(config) = state.webauthn.clone() else {
+        return Ok(None);
+    };
+    let allow = db::passkeys::credential_ids(&state.db, user_id).await?;
+    if allow.is_empty() {
+        return Ok(None);
+    }
+    let challenge = issue_challenge(state, PasskeyCeremony::Login, Some(user_id.to_string()));
+    Ok(Some(webauthn::request_options(
+        &config,
+        &challenge,
+        &allow,
+        "discouraged",
+    )))
+}
+
+/// Checks a login assertion and records the passkey's use. `expected_user`
+/// is the account the caller is already signing in as, if any. Returns the
+/// passkey's owner.
+pub(crate) async fn verify_login(
+    state: &AppState,
+    credential: &AssertionCredential,
+    expected_user: Option<&str>,
+) -> Result<(String, VerifiedAssertion), AppError> {
+    let config = require_webauthn(state)?;
+    let (challenge, pending) = take_challenge(
+        state,
+        &credential.response.client_data_json,
+        PasskeyCeremony::Login,
+    )?;
+
+    let credential_id = webauthn::encode(&webauthn::decode("id", &credential.id)?);
+    let stored = db::passkeys::get_credential(&state.db, &credential_id)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("unknown passkey".to_string()))?;
+    let owner_matches = |user_id: Option<&str>| user_id.is_none_or(|id| id == stored.user_id);
+    if !owner_matches(pending.user_id.as_deref()) || !owner_matches(expected_user) {
+        return Err(AppError::Unauthorized(
+            "passkey belongs to a different account".to_string(),
+        ));
+    }
+    if let Some(ref handle) = credential.response.user_handle {
+        if webauthn::decode("userHandle", handle)? != stored.user_id.as_bytes() {
+            return Err(AppError::Unauthorized(
+                "passkey belongs to a different account".to_string(),
+            ));
+        }
+    }
+
+    let public_key = webauthn::decode("public_key", &stored.public_key)
+        .map_err(|_| AppError::Internal("stored passkey key is invalid".to_string()))?;
+    let verified = webauthn::verify_assertion(
+        &config,
+        &challenge,
+        stored.algorithm,
+        &public_key,
+        credential,
+    )?;
+
+    // A counter that fails to advance means two authenticators hold this key.
+    let sign_count = i64::from(verified.sign_count);
+    if (sign_count != 0 || stored.sign_count != 0) && sign_count <= stored.sign_count {
+        tracing::warn!(
+            "passkey {} for user {} reused sign count {}; refusing login",
+            stored.id,
+            stored.user_id,
+            sign_count
+        );
+        return Err(AppError::Unauthorized(
+            "passkey sign count did not advance; it may have been cloned".to_string(),
+        ));
+    }
+    db::passkeys::record_use(&state.db, &stored.id, sign_count).await?;
+
+    Ok((stored.user_id, verified))
+}
+
+// =========================================================================
+// Managing passkeys
+// =========================================================================
+
+/// GET /auth/passkeys
+pub async fn list_passkeys(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_user(&auth)?;
+    let passkeys = db::passkeys::list_passkeys(&state.db, &auth.user_id).await?;
+    Ok(Json(serde_json::json!({ "data": passkeys })))
+}
+
+/// POST /auth/passkeys/register/start
+///
+/// Returns `publicKey` creation options for `navigator.credentials.create()`.
+/// Needs the account password, as enabling TOTP does.
+pub async fn start_registration(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(input): Json<StartRegistrationRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_user(&auth)?;
+    let config = require_webauthn(&state)?;
+    verify_user_password(&state, &auth.user_id, &input.password).await?;
+
+    let count = db::passkeys::count_passkeys(&state.db, &auth.user_id).await?;
+    AppError::check_limit("passkey_limit_reached", count, MAX_PASSKEYS_PER_USER)?;
+
+    let user = db::users::get_user(&state.db, &auth.user_id).await?;
+    let exclude = db::passkeys::credential_ids(&state.db, &auth.user_id).await?;
+    let challenge = issue_challenge(
+        &state,
+        PasskeyCeremony::Register,
+        Some(auth.user_id.clone()),
+    );
+    let options = webauthn::creation_options(
+        &config,
+        &challenge,
+        &user.id,
+        &user.username,
+        user.display_name.as_deref().unwrap_or(&user.username),
+        &exclude,
+    );
+
+    Ok(Json(
+        serde_json::json!({ "data": { "publicKey": options } }),
+    ))
+}
+
+/// POST /auth/passkeys/register/finish
+///
+/// Verifies the authenticator's response to a registration challenge and
+/// stores the passkey.
+pub async fn finish_registration(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(input): Json<FinishRegistrationRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_user(&auth)?;
+    let config = require_webauthn(&state)?;
+    let name = validate_name(input.name.as_deref().unwrap_or("Passkey"))?;
+
+    let (challenge, pending) = take_challenge(
+        &state,
+        &input.credential.response.client_data_json,
+        PasskeyCeremony::Register,
+    )?;
+    if pending.user_id.as_deref() != Some(auth.user_id.as_str()) {
+        return Err(AppError::Unauthorized(
+            "registration was started by a different account".to_string(),
+        ));
+    }
+    let created = webauthn::verify_registration(&config, &challenge, &input.credential)?;
+
+    let count = db::passkeys::count_passkeys(&state.db, &auth.user_id).await?;
+    AppError::check_limit("passkey_limit_reached", count, MAX_PASSKEYS_PER_USER)?;
+
+    let passkey = db::passkeys::create_passkey(
+        &state.db,
+        &auth.user_id,
+        &name,
+        &created.credential_id,
+        &webauthn::encode(&created.public_key),
+        created.algorithm,
+        i64::from(created.sign_count),
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({ "data": passkey })))
+}
+
+/// PATCH /auth/passkeys/{passkey_id}
+pub async fn rename_passkey(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(passkey_id): Path<String>,
+    Json(input): Json<RenamePasskeyRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_user(&auth)?;
+    let name = validate_name(&input.name)?;
+    let passkey =
+        db::passkeys::rename_passkey(&state.db, &auth.user_id, &passkey_id, &name).await?;
+    Ok(Json(serde_json::json!({ "data": passkey })))
+}
+
+/// DELETE /auth/passkeys/{passkey_id}
+pub async fn delete_passkey(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(passkey_id): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_user(&auth)?;
+    if !db::passkeys::delete_passkey(&state.db, &auth.user_id, &passkey_id).await? {
+        return Err(AppError::NotFound("unknown_passkey".to_string()));
+    }
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+fn validate_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_PASSKEY_NAME_LEN {
+        return Err(AppError::BadRequest(format!(
+            "name must be 1-{MAX_PASSKEY_NAME_LEN} characters"
+        )));
+    }
+    Ok(name.to_string())
+}
+
+// =========================================================================
+// Passwordless login
+// =========================================================================
+
+/// POST /auth/passkeys/login/start
+///
+/// Returns `publicKey` request options for `navigator.credentials.get()`.
+/// An unknown username gets options any passkey may answer, so the response
+/// doesn't reveal which accounts exist.
+pub async fn start_login(
+    State(state): State<AppState>,
+    Json(input): Json<StartLoginRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let config = require_webauthn(&state)?;
+
+    let user_id = match input.username.as_deref() {
+        Some(username) => sqlx::query(&crate::db::q(
+            "SELECT id FROM users WHERE username = ? AND bot = false",
+        ))
+        .bind(username)
+        .fetch_optional(&state.db)
+        .await?
+        .map(|row| row.get::<String, _>("id")),
+        None => None,
+    };
+    let allow = match user_id {
+        Some(ref id) => db::passkeys::credential_ids(&state.db, id).await?,
+        None => Vec::new(),
+    };
+    let challenge = issue_challenge(&state, PasskeyCeremony::Login, user_id);
+    let options = webauthn::request_options(&config, &challenge, &allow, "required");
+
+    Ok(Json(
+        serde_json::json!({ "data": { "publicKey": options } }),
+    ))
+}
+
+/// POST /auth/passkeys/login/finish
+///
+/// Signs in with a passkey alone. The authenticator must have verified the
+/// user (PIN or biometric), which also counts as a fresh MFA verification.
+pub async fn finish_login(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    extensions: Extensions,
+    Json(input): Json<FinishLoginRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let (user_id, verified) = verify_login(&state, &input.credential, None).await?;
+    if !verified.user_verified {
+        return Err(AppError::Unauthorized(
+            "passkey login requires user verification".to_string(),
+        ));
+    }
+
+    let row = sqlx::query(&crate::db::q(
+        "SELECT disabled, force_password_reset FROM users WHERE id = ?",
+    ))
+    .bind(&user_id)
+    .fetch_one(&state.db)
+    .await?;
+    if crate::db::get_bool(&row, "disabled") {
+        return Err(AppError::Forbidden("account is disabled".to_string()));
+    }
+    let force_password_reset = crate::db::get_bool(&row, "force_password_reset");
+
+    let user = db::users::get_user(&state.db, &user_id).await?;
+    let (token, token_hash, expires_at) = issue_bearer_token();
+    let ip = client_ip(&headers, &extensions).map(|ip| ip.to_string());
+    db::sessions::create_session(
+        &state.db,
+        &user_id,
+        &token_hash,
+        &expires_at,
+        session_device(&headers, input.device_name.as_deref()).as_deref(),
+        ip.as_deref(),
+    )
+    .await?;
+    db::sessions::record_mfa_verification(
+        &state.db,
+        &user_id,
+        db::sessions::session_id(&token_hash),
+    )
+    .await?;
+
+    cleanup_expired_tokens(&state.db, &user_id).await;
+    enforce_session_limit(&state.db, &user_id).await;
+
+    let mut data = serde_json::json!({
+        "user": user,
+        "token": token
+    });
+    if force_password_reset {
+        data["force_password_reset"] = serde_json::json!(true);
+    }
+
+    Ok(Json(serde_json::json!({ "data": data })))
+}
@@ -879,6 +879,7 @@ async fn broadcast_plugin_event(
             target_user_ids,
             event,
             intent: "plugins".to_string(),
+            role_mention_user_ids: None,
         });
     }
 }
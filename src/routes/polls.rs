@@ -0,0 +1,167 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+
+use crate::db;
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::{
+    require_channel_membership, require_channel_permission, require_not_pending,
+    require_not_timed_out,
+};
+use crate::models::channel::ChannelRow;
+use crate::models::poll::{PollRow, PollVotersQuery};
+use crate::routes::messages::broadcast_channel_event;
+use crate::state::AppState;
+
+/// Load the poll on `message_id`, which must be a message in `channel_id`
+/// offering `answer_id`.
+async fn load_poll(
+    state: &AppState,
+    channel_id: &str,
+    message_id: &str,
+    answer_id: i64,
+) -> Result<PollRow, AppError> {
+    let msg = db::messages::get_message_row(&state.db, message_id).await?;
+    if msg.channel_id != channel_id {
+        return Err(AppError::NotFound("unknown_message".to_string()));
+    }
+    let poll = db::polls::get_poll(&state.db, message_id).await?;
+    if !poll.answers.iter().any(|a| a.id == answer_id) {
+        return Err(AppError::NotFound("poll answer not found".into()));
+    }
+    Ok(poll)
+}
+
+fn require_open(poll: &PollRow) -> Result<(), AppError> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    if poll.finalized_at.is_some() || poll.expires_at <= now {
+        return Err(AppError::BadRequest("this poll has ended".into()));
+    }
+    Ok(())
+}
+
+async fn broadcast_vote(
+    state: &AppState,
+    channel: &ChannelRow,
+    event_type: &str,
+    message_id: &str,
+    user_id: &str,
+    answer_id: i64,
+) {
+    let data = serde_json::json!({
+        "channel_id": channel.id,
+        "space_id": channel.space_id,
+        "message_id": message_id,
+        "user_id": user_id,
+        "answer_id": answer_id,
+    });
+    broadcast_channel_event(state, channel, event_type, data, "message_reactions").await;
+}
+
+/// `PUT /channels/{channel_id}/polls/{message_id}/answers/{answer_id}/@me` --
+/// vote for an answer. On a single-choice poll this replaces any earlier
+/// vote.
+pub async fn add_vote(
+    state: State<AppState>,
+    Path((channel_id, message_id, answer_id)): Path<(String, String, i64)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_id =
+        require_channel_permission(&state.db, &channel_id, &auth, "view_channel").await?;
+    if !space_id.is_empty() {
+        require_not_timed_out(&state.db, &space_id, &auth).await?;
+        require_not_pending(&state.db, &space_id, &auth).await?;
+    }
+    let poll = load_poll(&state, &channel_id, &message_id, answer_id).await?;
+    require_open(&poll)?;
+    let channel = db::channels::get_channel_row(&state.db, &channel_id).await?;
+
+    let added = if poll.allow_multiselect {
+        db::polls::add_vote(&state.db, &message_id, &auth.user_id, answer_id).await?
+    } else {
+        let (removed, added) =
+            db::polls::replace_vote(&state.db, &message_id, &auth.user_id, answer_id).await?;
+        for previous in removed {
+            broadcast_vote(
+                &state,
+                &channel,
+                "poll.vote_remove",
+                &message_id,
+                &auth.user_id,
+                previous,
+            )
+            .await;
+        }
+        added
+    };
+    if added {
+        broadcast_vote(
+            &state,
+            &channel,
+            "poll.vote_add",
+            &message_id,
+            &auth.user_id,
+            answer_id,
+        )
+        .await;
+    }
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// `DELETE /channels/{channel_id}/polls/{message_id}/answers/{answer_id}/@me`
+pub async fn remove_vote(
+    state: State<AppState>,
+    Path((channel_id, message_id, answer_id)): Path<(String, String, i64)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    let poll = load_poll(&state, &channel_id, &message_id, answer_id).await?;
+    require_open(&poll)?;
+    if db::polls::remove_vote(&state.db, &message_id, &auth.user_id, answer_id).await? {
+        let channel = db::channels::get_channel_row(&state.db, &channel_id).await?;
+        broadcast_vote(
+            &state,
+            &channel,
+            "poll.vote_remove",
+            &message_id,
+            &auth.user_id,
+            answer_id,
+        )
+        .await;
+    }
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// `GET /channels/{channel_id}/polls/{message_id}/answers/{answer_id}` --
+/// the IDs of users who voted for an answer, paged like reaction users.
+pub async fn list_voters(
+    state: State<AppState>,
+    Path((channel_id, message_id, answer_id)): Path<(String, String, i64)>,
+    auth: AuthUser,
+    Query(params): Query<PollVotersQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    load_poll(&state, &channel_id, &message_id, answer_id).await?;
+    let limit = params.limit.unwrap_or(25).clamp(1, 100);
+    let mut user_ids = db::polls::list_voters(
+        &state.db,
+        &message_id,
+        answer_id,
+        params.after.as_deref(),
+        limit + 1,
+    )
+    .await?;
+    let has_more = user_ids.len() as i64 > limit;
+    if has_more {
+        user_ids.truncate(limit as usize);
+    }
+
+    let mut response = serde_json::json!({ "data": user_ids });
+    if has_more {
+        response["cursor"] = serde_json::json!({
+            "after": user_ids.last().cloned().unwrap_or_default(),
+            "has_more": has_more
+        });
+    }
+    Ok(Json(response))
+}
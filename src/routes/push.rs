@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::db;
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::models::push::{CreatePushSubscription, MAX_PUSH_SUBSCRIPTIONS};
+use crate::state::AppState;
+use crate::webauthn::decode;
+
+fn require_push(state: &AppState) -> Result<Arc<crate::push::Notifier>, AppError> {
+    state.push.clone().ok_or_else(|| {
+        AppError::BadRequest("push notifications are not configured on this server".to_string())
+    })
+}
+
+fn require_person(auth: &AuthUser) -> Result<(), AppError> {
+    if auth.is_bot || auth.is_guest {
+        return Err(AppError::Forbidden(
+            "only user accounts can receive push notifications".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// `GET /users/@me/push/vapid-key` -- the `applicationServerKey` to pass
+/// to `PushManager.subscribe()`.
+pub async fn get_vapid_key(
+    state: State<AppState>,
+    _auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let notifier = require_push(&state)?;
+    Ok(Json(serde_json::json!({
+        "data": { "public_key": notifier.public_key() }
+    })))
+}
+
+/// `GET /users/@me/push/subscriptions`
+pub async fn list_subscriptions(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let subs = db::push::list_subscriptions(&state.db, &auth.user_id).await?;
+    Ok(Json(serde_json::json!({ "data": subs })))
+}
+
+/// `POST /users/@me/push/subscriptions` -- register a browser's
+/// `PushSubscription`. Registering an endpoint again replaces it.
+pub async fn create_subscription(
+    state: State<AppState>,
+    auth: AuthUser,
+    Json(input): Json<CreatePushSubscription>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_person(&auth)?;
+    require_push(&state)?;
+
+    let mut errors = Vec::new();
+    match crate::safe_fetch::validate_url(&input.endpoint) {
+        Ok(url) if url.scheme() == "https" => {}
+        Ok(_) => errors.push(("endpoint".to_string(), "must be an https URL".to_string())),
+        Err(e) => errors.push(("endpoint".to_string(), e.to_string())),
+    }
+    let p256dh_valid = decode("keys.p256dh", &input.keys.p256dh)
+        .is_ok_and(|key| key.len() == 65 && p256::PublicKey::from_sec1_bytes(&key).is_ok());
+    if !p256dh_valid {
+        errors.push((
+            "keys.p256dh".to_string(),
+            "must be an uncompressed P-256 public key".to_string(),
+        ));
+    }
+    if !decode("keys.auth", &input.keys.auth).is_ok_and(|secret| secret.len() == 16) {
+        errors.push((
+            "keys.auth".to_string(),
+            "must be a 16-byte secret".to_string(),
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(AppError::InvalidFields(errors));
+    }
+
+    let existing = db::push::list_subscriptions(&state.db, &auth.user_id).await?;
+    if !existing.iter().any(|sub| sub.endpoint == input.endpoint) {
+        AppError::check_limit(
+            "push_subscription_limit_reached",
+            existing.len() as i64,
+            MAX_PUSH_SUBSCRIPTIONS,
+        )?;
+    }
+
+    let sub = db::push::create_subscription(
+        &state.db,
+        &auth.user_id,
+        &input.endpoint,
+        &input.keys.p256dh,
+        &input.keys.auth,
+    )
+    .await?;
+    Ok(Json(serde_json::json!({ "data": sub })))
+}
+
+/// `DELETE /users/@me/push/subscriptions/{subscription_id}`
+pub async fn delete_subscription(
+    state: State<AppState>,
+    Path(subscription_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if !db::push::delete_subscription(&state.db, &auth.user_id, &subscription_id).await? {
+        return Err(AppError::NotFound("push subscription not found".into()));
+    }
+    Ok(Json(serde_json::json!({ "data": null })))
+}
@@ -0,0 +1,164 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::db;
+use crate::error::AppError;
+use crate::middleware::audit::AuditReason;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::require_permission;
+use crate::models::raid::{
+    StartLockdown, UpdateRaidProtection, LOCKDOWN_MODES, MAX_JOIN_WINDOW_SECS, MAX_LOCKDOWN_SECS,
+    MAX_MIN_ACCOUNT_AGE_SECS,
+};
+use crate::state::AppState;
+
+fn check_range(field: &str, value: i64, min: i64, max: i64) -> Result<(), AppError> {
+    if !(min..=max).contains(&value) {
+        return Err(AppError::BadRequest(format!(
+            "{field} must be between {min} and {max}"
+        )));
+    }
+    Ok(())
+}
+
+/// GET /spaces/{space_id}/raid-protection
+pub async fn get_raid_protection(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let raid = db::raid::get_raid_protection(&state.db, &space_id).await?;
+    Ok(Json(serde_json::json!({ "data": raid })))
+}
+
+/// PATCH /spaces/{space_id}/raid-protection
+///
+/// Settings apply to the next lockdown; a running one keeps its end time
+/// but checks joins against the new mode straight away.
+pub async fn update_raid_protection(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    AuditReason(reason): AuditReason,
+    Json(input): Json<UpdateRaidProtection>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let before = db::raid::get_raid_protection(&state.db, &space_id).await?;
+    let mut raid = before.clone();
+
+    if let Some(auto_lockdown) = input.auto_lockdown {
+        raid.auto_lockdown = auto_lockdown;
+    }
+    if let Some(threshold) = input.join_threshold {
+        check_range("join_threshold", threshold, 2, 1000)?;
+        raid.join_threshold = threshold;
+    }
+    if let Some(window) = input.join_window_secs {
+        check_range("join_window_secs", window, 1, MAX_JOIN_WINDOW_SECS)?;
+        raid.join_window_secs = window;
+    }
+    if let Some(secs) = input.lockdown_secs {
+        check_range("lockdown_secs", secs, 60, MAX_LOCKDOWN_SECS)?;
+        raid.lockdown_secs = secs;
+    }
+    if let Some(mode) = input.mode {
+        if !LOCKDOWN_MODES.contains(&mode.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "mode must be one of: {}",
+                LOCKDOWN_MODES.join(", ")
+            )));
+        }
+        raid.mode = mode;
+    }
+    if let Some(age) = input.min_account_age_secs {
+        check_range("min_account_age_secs", age, 0, MAX_MIN_ACCOUNT_AGE_SECS)?;
+        raid.min_account_age_secs = age;
+    }
+
+    db::raid::save_settings(&state.db, &raid).await?;
+    let raid = db::raid::get_raid_protection(&state.db, &space_id).await?;
+
+    let before_json = serde_json::json!(before);
+    let after_json = serde_json::json!(raid);
+    if let Some(changes) = super::audit_log::diff(&before_json, &after_json) {
+        super::audit_log::record(
+            &state,
+            &space_id,
+            &auth.user_id,
+            "raid_protection_update",
+            Some((&space_id, "space")),
+            reason.as_deref(),
+            Some(changes),
+        )
+        .await;
+    }
+
+    Ok(Json(serde_json::json!({ "data": raid })))
+}
+
+/// PUT /spaces/{space_id}/lockdown
+///
+/// Lock the space down now, in its configured mode, for `duration_secs` or
+/// until lifted. Replaces a lockdown that's already running.
+pub async fn start_lockdown(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    AuditReason(reason): AuditReason,
+    body: Option<Json<StartLockdown>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let input = body.map(|Json(b)| b).unwrap_or_default();
+    let until = match input.duration_secs {
+        Some(secs) => {
+            check_range("duration_secs", secs, 60, MAX_LOCKDOWN_SECS)?;
+            Some(db::raid::timestamp_in(secs))
+        }
+        None => None,
+    };
+    db::raid::start_lockdown(&state.db, &space_id, until.as_deref()).await?;
+    let raid = db::raid::get_raid_protection(&state.db, &space_id).await?;
+
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "space_lockdown_start",
+        Some((&space_id, "space")),
+        reason.as_deref(),
+        None,
+    )
+    .await;
+    crate::raid::broadcast_lockdown(&state, &raid).await;
+
+    Ok(Json(serde_json::json!({ "data": raid })))
+}
+
+/// DELETE /spaces/{space_id}/lockdown
+pub async fn end_lockdown(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    AuditReason(reason): AuditReason,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let ended = db::raid::end_lockdown(&state.db, &space_id).await?;
+    let raid = db::raid::get_raid_protection(&state.db, &space_id).await?;
+
+    if ended {
+        super::audit_log::record(
+            &state,
+            &space_id,
+            &auth.user_id,
+            "space_lockdown_end",
+            Some((&space_id, "space")),
+            reason.as_deref(),
+            None,
+        )
+        .await;
+        crate::raid::broadcast_lockdown(&state, &raid).await;
+    }
+
+    Ok(Json(serde_json::json!({ "data": raid })))
+}
@@ -1,12 +1,15 @@
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::Json;
+use serde::Deserialize;
 
 use crate::error::AppError;
 use crate::gateway::events::GatewayBroadcast;
 use crate::middleware::auth::AuthUser;
 use crate::middleware::permissions::{
-    require_channel_membership, require_channel_permission, require_not_timed_out,
+    require_channel_membership, require_channel_permission, require_not_pending,
+    require_not_timed_out,
 };
+use crate::routes::members::member_row_to_json;
 use crate::state::AppState;
 
 /// Convert the space_id string returned by permission helpers into the
@@ -27,9 +30,23 @@ pub async fn add_reaction(
 ) -> Result<Json<serde_json::Value>, AppError> {
     let space_id =
         require_channel_permission(&state.db, &channel_id, &auth, "add_reactions").await?;
-    // Block timed-out members from reacting in a space (DMs have no timeout).
+    // Block timed-out and still-screening members from reacting in a space
+    // (DMs have neither).
     if !space_id.is_empty() {
         require_not_timed_out(&state.db, &space_id, &auth).await?;
+        require_not_pending(&state.db, &space_id, &auth).await?;
+    }
+
+    // Users can't react to messages from someone who has blocked them.
+    if let Ok(message) = crate::db::messages::get_message_row(&state.db, &message_id).await {
+        if crate::db::relationships::is_blocked_by(&state.db, &message.author_id, &auth.user_id)
+            .await?
+        {
+            return Err(AppError::ForbiddenCode(
+                "blocked_by_author",
+                "you cannot react to this user's messages".into(),
+            ));
+        }
     }
 
     // Remote-homed space: forward to the authoritative home server. The reaction
@@ -80,6 +97,7 @@ pub async fn add_reaction(
             target_user_ids: None,
             event,
             intent: "message_reactions".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -156,6 +174,7 @@ pub async fn remove_own_reaction(
             target_user_ids: None,
             event,
             intent: "message_reactions".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -213,28 +232,71 @@ pub async fn remove_user_reaction(
             target_user_ids: None,
             event,
             intent: "message_reactions".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
     Ok(Json(serde_json::json!({ "data": null })))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ListReactionsQuery {
+    pub after: Option<String>,
+    pub limit: Option<i64>,
+    /// When `true`, also return the reactors' member objects (space
+    /// channels only).
+    #[serde(default)]
+    pub with_member: bool,
+}
+
+/// GET /channels/{channel_id}/messages/{message_id}/reactions/{emoji}
+/// User IDs that reacted with `emoji`, a page at a time in user ID order.
 pub async fn list_reactions(
     state: State<AppState>,
     Path((channel_id, message_id, emoji)): Path<(String, String, String)>,
     auth: AuthUser,
+    Query(params): Query<ListReactionsQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    let space_id = require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    let limit = params.limit.unwrap_or(25).clamp(1, 100);
     let users = sqlx::query_as::<_, (String,)>(&crate::db::q(
-        "SELECT user_id FROM reactions WHERE message_id = ? AND emoji_name = ?",
+        "SELECT user_id FROM reactions WHERE message_id = ? AND emoji_name = ? AND user_id > ? \
+         ORDER BY user_id ASC LIMIT ?",
     ))
     .bind(&message_id)
     .bind(&emoji)
+    .bind(params.after.as_deref().unwrap_or(""))
+    .bind(limit + 1)
     .fetch_all(&state.db)
     .await?;
 
-    let user_ids: Vec<String> = users.into_iter().map(|r| r.0).collect();
-    Ok(Json(serde_json::json!({ "data": user_ids })))
+    let mut user_ids: Vec<String> = users.into_iter().map(|r| r.0).collect();
+    let has_more = user_ids.len() as i64 > limit;
+    if has_more {
+        user_ids.truncate(limit as usize);
+    }
+
+    let mut response = serde_json::json!({ "data": user_ids });
+    if params.with_member && !space_id.is_empty() {
+        let rows = crate::db::members::get_members_by_ids(&state.db, &space_id, &user_ids).await?;
+        let role_ids =
+            crate::db::members::get_role_ids_for_members(&state.db, &space_id, &user_ids).await?;
+        let members: serde_json::Map<String, serde_json::Value> = rows
+            .iter()
+            .map(|row| {
+                let roles = role_ids.get(&row.user_id).map(Vec::as_slice).unwrap_or(&[]);
+                (row.user_id.clone(), member_row_to_json(row, roles))
+            })
+            .collect();
+        response["members"] = serde_json::Value::Object(members);
+    }
+    if has_more {
+        response["cursor"] = serde_json::json!({
+            "after": user_ids.last().cloned().unwrap_or_default(),
+            "has_more": has_more
+        });
+    }
+    Ok(Json(response))
 }
 
 pub async fn remove_all_reactions(
@@ -263,6 +325,7 @@ pub async fn remove_all_reactions(
             target_user_ids: None,
             event,
             intent: "message_reactions".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -299,6 +362,7 @@ pub async fn remove_all_reactions_emoji(
             target_user_ids: None,
             event,
             intent: "message_reactions".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -44,36 +44,69 @@ pub async fn ack_channel(
     Json(input): Json<AckChannelBody>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    apply_ack(&state, &auth.user_id, &channel_id, &input.message_id).await?;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// POST /channels/{channel_id}/messages/{message_id}/ack
+/// Mark a channel as read up to a message named in the path.
+pub async fn ack_message(
+    state: State<AppState>,
+    Path((channel_id, message_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    let msg = db::messages::get_message_row(&state.db, &message_id).await?;
+    if msg.channel_id != channel_id {
+        return Err(AppError::NotFound("unknown_message".to_string()));
+    }
+    apply_ack(&state, &auth.user_id, &channel_id, &message_id).await?;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
 
+/// Store the new read position and sync it to the user's *other* sessions
+/// (multi-device: reading on your phone clears the badge on desktop). Events
+/// are targeted at the acking user only, so they carry no space_id and aren't
+/// muted-channel-suppressed.
+async fn apply_ack(
+    state: &AppState,
+    user_id: &str,
+    channel_id: &str,
+    message_id: &str,
+) -> Result<(), AppError> {
     db::read_states::ack_channel(
         &state.db,
-        &auth.user_id,
-        &channel_id,
-        &input.message_id,
+        user_id,
+        channel_id,
+        message_id,
         state.db_is_postgres,
     )
     .await?;
 
-    // Sync the new read position to the user's *other* sessions (multi-device:
-    // reading on your phone clears the badge on desktop). Targeted at the acking
-    // user only, so it carries no space_id and isn't muted-channel-suppressed.
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
-        let event = serde_json::json!({
-            "op": 0,
-            "type": "read_state.update",
-            "data": {
-                "channel_id": channel_id,
-                "last_read_message_id": input.message_id,
-                "mention_count": 0,
-            }
-        });
-        let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast {
-            space_id: None,
-            target_user_ids: Some(vec![auth.user_id.clone()]),
-            event,
-            intent: "messages".to_string(),
+        let data = serde_json::json!({
+            "channel_id": channel_id,
+            "message_id": message_id,
+            "last_read_message_id": message_id,
+            "mention_count": 0,
         });
+        // `read_state.update` predates `message.ack`; both are sent until
+        // clients have moved over.
+        for event_type in ["message.ack", "read_state.update"] {
+            let event = serde_json::json!({
+                "op": 0,
+                "type": event_type,
+                "data": data
+            });
+            let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast {
+                space_id: None,
+                target_user_ids: Some(vec![user_id.to_string()]),
+                event,
+                intent: "messages".to_string(),
+                role_mention_user_ids: None,
+            });
+        }
     }
 
-    Ok(Json(serde_json::json!({ "data": null })))
+    Ok(())
 }
@@ -5,7 +5,10 @@ use serde::Deserialize;
 use crate::db;
 use crate::error::AppError;
 use crate::middleware::auth::AuthUser;
-use crate::middleware::permissions::require_permission;
+use crate::middleware::permissions::{
+    list_member_ids_with_permission, require_channel_membership, require_membership,
+    require_permission,
+};
 use crate::state::AppState;
 
 #[derive(Deserialize)]
@@ -13,6 +16,8 @@ pub struct CreateReportBody {
     pub target_type: String,
     pub target_id: String,
     pub channel_id: Option<String>,
+    /// Only read by `POST /reports`, for user reports.
+    pub space_id: Option<String>,
     pub category: String,
     pub description: Option<String>,
 }
@@ -40,22 +45,36 @@ const VALID_CATEGORIES: &[&str] = &[
     "other",
 ];
 
-pub async fn create_report(
-    state: State<AppState>,
-    Path(space_id): Path<String>,
-    auth: AuthUser,
-    Json(body): Json<CreateReportBody>,
-) -> Result<Json<serde_json::Value>, AppError> {
+/// Where a report can go from each status. Either moderation outcome can be
+/// reopened for another look, but never straight back to `pending`.
+pub(super) fn check_transition(from: &str, to: &str) -> Result<(), AppError> {
+    let allowed: &[&str] = match from {
+        "pending" => &["reviewing", "actioned", "dismissed"],
+        "reviewing" => &["pending", "actioned", "dismissed"],
+        "actioned" | "dismissed" => &["reviewing"],
+        _ => &[],
+    };
+    if !allowed.contains(&to) {
+        return Err(AppError::ConflictCode(
+            "invalid_report_transition",
+            format!("a {from} report can't be moved to {to}"),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_report(body: &CreateReportBody, target_types: &[&str]) -> Result<(), AppError> {
     if !VALID_CATEGORIES.contains(&body.category.as_str()) {
         return Err(AppError::BadRequest(format!(
             "invalid category: {}",
             body.category
         )));
     }
-    if body.target_type != "message" && body.target_type != "user" {
-        return Err(AppError::BadRequest(
-            "target_type must be 'message' or 'user'".into(),
-        ));
+    if !target_types.contains(&body.target_type.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "target_type must be one of: {}",
+            target_types.join(", ")
+        )));
     }
 
     if let Some(ref desc) = body.description {
@@ -65,6 +84,48 @@ pub async fn create_report(
             ));
         }
     }
+    Ok(())
+}
+
+/// Tell the space's moderators (for reports they can see) and instance
+/// admins about a new report.
+async fn broadcast_report_create(state: &AppState, json: &serde_json::Value) {
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        if let Some(space_id) = json["space_id"]
+            .as_str()
+            .filter(|_| json["target_type"] != "space")
+        {
+            let moderators =
+                list_member_ids_with_permission(&state.db, space_id, "moderate_members")
+                    .await
+                    .unwrap_or_default();
+            let event = serde_json::json!({
+                "op": 0,
+                "type": "report.create",
+                "data": json
+            });
+            let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast {
+                space_id: None,
+                target_user_ids: Some(moderators),
+                event,
+                intent: "moderation".to_string(),
+                role_mention_user_ids: None,
+            });
+        }
+        let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast::admin(
+            "admin.report_create",
+            json.clone(),
+        ));
+    }
+}
+
+pub async fn create_report(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    Json(body): Json<CreateReportBody>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    validate_report(&body, &["message", "user"])?;
 
     // Verify user is a member of the space
     db::members::get_member_row(&state.db, &space_id, &auth.user_id)
@@ -73,7 +134,7 @@ pub async fn create_report(
 
     let report = db::reports::create_report(
         &state.db,
-        &space_id,
+        Some(&space_id),
         &auth.user_id,
         &body.target_type,
         &body.target_id,
@@ -84,21 +145,67 @@ pub async fn create_report(
     .await?;
 
     let json = report_to_json(&report);
+    broadcast_report_create(&state, &json).await;
 
-    // Broadcast to gateway (moderation intent)
-    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
-        let event = serde_json::json!({
-            "op": 0,
-            "type": "report.create",
-            "data": json
-        });
-        let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast {
-            space_id: Some(space_id),
-            target_user_ids: None,
-            event,
-            intent: "moderation".to_string(),
-        });
-    }
+    Ok(Json(serde_json::json!({ "data": json })))
+}
+
+/// POST /reports
+///
+/// Report a message, user or space to the instance admins. The reporter has
+/// to be able to see what they report; messages and spaces are filed under
+/// their space (so its moderators see message reports too), users under
+/// `space_id` when given and the reporter is a member there.
+pub async fn file_report(
+    state: State<AppState>,
+    auth: AuthUser,
+    Json(body): Json<CreateReportBody>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    validate_report(&body, &["message", "user", "space"])?;
+
+    let (space_id, channel_id) = match body.target_type.as_str() {
+        "message" => {
+            let msg = db::messages::get_message_row(&state.db, &body.target_id).await?;
+            require_channel_membership(&state.db, &msg.channel_id, &auth.user_id)
+                .await
+                .map_err(|_| AppError::NotFound("unknown_message".to_string()))?;
+            (msg.space_id, Some(msg.channel_id))
+        }
+        "user" => {
+            if body.target_id == auth.user_id {
+                return Err(AppError::BadRequest("you can't report yourself".into()));
+            }
+            db::users::get_user(&state.db, &body.target_id).await?;
+            if let Some(ref space_id) = body.space_id {
+                require_membership(&state.db, space_id, &auth.user_id).await?;
+            }
+            (body.space_id.clone(), None)
+        }
+        _ => {
+            let space = db::spaces::get_space_row(&state.db, &body.target_id).await?;
+            if !space.public {
+                require_membership(&state.db, &space.id, &auth.user_id)
+                    .await
+                    .map_err(|_| AppError::NotFound("unknown_space".to_string()))?;
+            }
+            (Some(space.id), None)
+        }
+    };
+
+    let report = db::reports::create_report(
+        &state.db,
+        space_id.as_deref(),
+        &auth.user_id,
+        &body.target_type,
+        &body.target_id,
+        channel_id.as_deref(),
+        &body.category,
+        body.description.as_deref(),
+    )
+    .await?;
+
+    let json = report_to_json(&report);
+    broadcast_report_create(&state, &json).await;
 
     Ok(Json(serde_json::json!({ "data": json })))
 }
@@ -130,7 +237,7 @@ pub async fn get_report(
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "moderate_members").await?;
     let report = db::reports::get_report(&state.db, &report_id).await?;
-    if report.space_id != space_id {
+    if report.space_id.as_deref() != Some(space_id.as_str()) || report.target_type == "space" {
         return Err(AppError::NotFound("report not found".to_string()));
     }
     Ok(Json(serde_json::json!({ "data": report_to_json(&report) })))
@@ -152,7 +259,7 @@ pub async fn resolve_report(
 
     // Verify report belongs to this space
     let existing = db::reports::get_report(&state.db, &report_id).await?;
-    if existing.space_id != space_id {
+    if existing.space_id.as_deref() != Some(space_id.as_str()) || existing.target_type == "space" {
         return Err(AppError::NotFound("report not found".to_string()));
     }
 
@@ -169,7 +276,7 @@ pub async fn resolve_report(
     Ok(Json(serde_json::json!({ "data": report_to_json(&report) })))
 }
 
-fn report_to_json(r: &db::reports::ReportRow) -> serde_json::Value {
+pub(super) fn report_to_json(r: &db::reports::ReportRow) -> serde_json::Value {
     serde_json::json!({
         "id": r.id,
         "space_id": r.space_id,
@@ -3,42 +3,37 @@ use axum::Json;
 
 use crate::db;
 use crate::error::AppError;
+use crate::middleware::audit::AuditReason;
 use crate::middleware::auth::AuthUser;
 use crate::middleware::permissions::{
-    require_membership, require_permission, require_role_hierarchy,
+    require_membership, require_permission, require_recent_mfa, require_role_hierarchy,
     resolve_member_permissions_with_admin,
 };
-use crate::models::permission::ALL_PERMISSIONS;
+use crate::models::permission::Permissions;
 use crate::models::role::{CreateRole, RolePositionUpdate, RoleRow, UpdateRole};
 use crate::state::AppState;
 
 /// Validate that every permission in the list is known and that the actor holds
 /// all of them. This prevents privilege escalation via role creation/editing.
 async fn validate_role_permissions(
-    pool: &sqlx::AnyPool,
+    pool: &crate::db::DbPool,
     space_id: &str,
     auth: &AuthUser,
     permissions: &[String],
 ) -> Result<(), AppError> {
     // Reject unknown permission strings
-    for p in permissions {
-        if !ALL_PERMISSIONS.contains(&p.as_str()) {
-            return Err(AppError::BadRequest(format!("unknown permission: {p}")));
-        }
-    }
+    let requested = Permissions::parse(permissions)?;
     // Actor can only grant permissions they themselves hold
     let actor_perms =
         resolve_member_permissions_with_admin(pool, space_id, &auth.user_id, auth.is_admin).await?;
     // Administrators can grant anything
-    if actor_perms.iter().any(|p| p == "administrator") {
+    if actor_perms.is_admin() {
         return Ok(());
     }
-    for p in permissions {
-        if !actor_perms.contains(p) {
-            return Err(AppError::Forbidden(format!(
-                "you cannot grant a permission you do not have: {p}"
-            )));
-        }
+    if let Some(p) = (requested & !actor_perms).names().first() {
+        return Err(AppError::Forbidden(format!(
+            "you cannot grant a permission you do not have: {p}"
+        )));
     }
     Ok(())
 }
@@ -58,6 +53,7 @@ pub async fn create_role(
     state: State<AppState>,
     Path(space_id): Path<String>,
     auth: AuthUser,
+    audit_reason: AuditReason,
     Json(input): Json<CreateRole>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "manage_roles").await?;
@@ -72,13 +68,25 @@ pub async fn create_role(
         validate_role_permissions(&state.db, &space_id, &auth, perms).await?;
     }
     let row = db::roles::create_role(&state.db, &space_id, &input).await?;
-    Ok(Json(serde_json::json!({ "data": role_row_to_json(&row) })))
+    let json = role_row_to_json(&row);
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "role_create",
+        Some((&row.id, "role")),
+        audit_reason.as_deref(),
+        super::audit_log::diff(&serde_json::json!({}), &json),
+    )
+    .await;
+    Ok(Json(serde_json::json!({ "data": json })))
 }
 
 pub async fn update_role(
     state: State<AppState>,
     Path((space_id, role_id)): Path<(String, String)>,
     auth: AuthUser,
+    audit_reason: AuditReason,
     Json(mut input): Json<UpdateRole>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "manage_roles").await?;
@@ -93,15 +101,30 @@ pub async fn update_role(
     // Strip position — must use the dedicated reorder_roles endpoint
     input.position = None;
     let row = db::roles::update_role(&state.db, &role_id, &input, state.db_is_postgres).await?;
-    Ok(Json(serde_json::json!({ "data": role_row_to_json(&row) })))
+    let json = role_row_to_json(&row);
+    if let Some(changes) = super::audit_log::diff(&role_row_to_json(&target_role), &json) {
+        super::audit_log::record(
+            &state,
+            &space_id,
+            &auth.user_id,
+            "role_update",
+            Some((&role_id, "role")),
+            audit_reason.as_deref(),
+            Some(changes),
+        )
+        .await;
+    }
+    Ok(Json(serde_json::json!({ "data": json })))
 }
 
 pub async fn delete_role(
     state: State<AppState>,
     Path((space_id, role_id)): Path<(String, String)>,
     auth: AuthUser,
+    audit_reason: AuditReason,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "manage_roles").await?;
+    require_recent_mfa(&state.db, &space_id, &auth).await?;
     let target_role = db::roles::get_role_row(&state.db, &role_id).await?;
     if target_role.space_id != space_id {
         return Err(AppError::NotFound("role not found in this space".into()));
@@ -113,6 +136,16 @@ pub async fn delete_role(
     }
     require_role_hierarchy(&state.db, &space_id, &auth.user_id, target_role.position).await?;
     db::roles::delete_role(&state.db, &role_id).await?;
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "role_delete",
+        Some((&role_id, "role")),
+        audit_reason.as_deref(),
+        super::audit_log::diff(&role_row_to_json(&target_role), &serde_json::json!({})),
+    )
+    .await;
     Ok(Json(serde_json::json!({ "data": null })))
 }
 
@@ -143,7 +176,6 @@ pub async fn reorder_roles(
 }
 
 pub fn role_row_to_json(row: &RoleRow) -> serde_json::Value {
-    let permissions: Vec<String> = serde_json::from_str(&row.permissions).unwrap_or_default();
     serde_json::json!({
         "id": row.id,
         "name": row.name,
@@ -151,8 +183,9 @@ pub fn role_row_to_json(row: &RoleRow) -> serde_json::Value {
         "hoist": row.hoist,
         "icon": row.icon,
         "position": row.position,
-        "permissions": permissions,
+        "permissions": row.permissions,
         "managed": row.managed,
-        "mentionable": row.mentionable
+        "mentionable": row.mentionable,
+        "member_count": (row.position != 0).then_some(row.member_count),
     })
 }
@@ -0,0 +1,273 @@
+use std::collections::HashSet;
+
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::db;
+use crate::error::AppError;
+use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::audit::AuditReason;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::{require_membership, require_permission};
+use crate::models::screening::{
+    UpdateMemberScreening, UpdateWelcomeScreen, MAX_RULE_LEN, MAX_SCREENING_DESCRIPTION_LEN,
+    MAX_SCREENING_RULES, MAX_WELCOME_CHANNELS, MAX_WELCOME_CHANNEL_DESCRIPTION_LEN,
+    MAX_WELCOME_DESCRIPTION_LEN,
+};
+use crate::state::AppState;
+
+use super::members::member_row_to_json;
+
+/// Members see the welcome screen and screening form of their own spaces;
+/// anyone signed in sees them for public spaces, so they can read the rules
+/// before joining.
+async fn require_can_view(
+    state: &AppState,
+    space_id: &str,
+    auth: &AuthUser,
+) -> Result<(), AppError> {
+    let space = db::spaces::get_space_row(&state.db, space_id).await?;
+    if space.public {
+        return Ok(());
+    }
+    require_membership(&state.db, space_id, &auth.user_id).await
+}
+
+/// `None` leaves the description alone, an empty string clears it.
+fn resolve_description(
+    input: Option<String>,
+    current: Option<String>,
+    max_len: usize,
+) -> Result<Option<String>, AppError> {
+    let Some(description) = input else {
+        return Ok(current);
+    };
+    let description = description.trim();
+    if description.chars().count() > max_len {
+        return Err(AppError::BadRequest(format!(
+            "description must be at most {max_len} characters"
+        )));
+    }
+    Ok((!description.is_empty()).then(|| description.to_string()))
+}
+
+/// GET /spaces/{space_id}/welcome-screen
+///
+/// Featured channels that have since been deleted are left out.
+pub async fn get_welcome_screen(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_can_view(&state, &space_id, &auth).await?;
+    let mut screen = db::screening::get_welcome_screen(&state.db, &space_id).await?;
+    let channel_ids: HashSet<String> = db::channels::list_channels_in_space(&state.db, &space_id)
+        .await?
+        .into_iter()
+        .map(|c| c.id)
+        .collect();
+    screen
+        .channels
+        .retain(|c| channel_ids.contains(&c.channel_id));
+    Ok(Json(serde_json::json!({ "data": screen })))
+}
+
+/// PATCH /spaces/{space_id}/welcome-screen
+pub async fn update_welcome_screen(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    AuditReason(reason): AuditReason,
+    Json(input): Json<UpdateWelcomeScreen>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let before = db::screening::get_welcome_screen(&state.db, &space_id).await?;
+
+    let description = resolve_description(
+        input.description,
+        before.description.clone(),
+        MAX_WELCOME_DESCRIPTION_LEN,
+    )?;
+    let channels = match input.channels {
+        Some(channels) => {
+            if channels.len() > MAX_WELCOME_CHANNELS {
+                return Err(AppError::BadRequest(format!(
+                    "a welcome screen can feature at most {MAX_WELCOME_CHANNELS} channels"
+                )));
+            }
+            let mut seen = HashSet::new();
+            for channel in &channels {
+                if !seen.insert(channel.channel_id.as_str()) {
+                    return Err(AppError::BadRequest(
+                        "each channel can only be featured once".into(),
+                    ));
+                }
+                if channel.description.trim().is_empty()
+                    || channel.description.chars().count() > MAX_WELCOME_CHANNEL_DESCRIPTION_LEN
+                {
+                    return Err(AppError::BadRequest(format!(
+                        "channel descriptions must be 1-{MAX_WELCOME_CHANNEL_DESCRIPTION_LEN} characters"
+                    )));
+                }
+                let row = db::channels::get_channel_row(&state.db, &channel.channel_id).await?;
+                if row.space_id.as_deref() != Some(space_id.as_str()) {
+                    return Err(AppError::BadRequest(
+                        "featured channels must belong to this space".into(),
+                    ));
+                }
+            }
+            channels
+        }
+        None => before.channels.clone(),
+    };
+    let enabled = input.enabled.unwrap_or(before.enabled);
+
+    db::screening::set_welcome_screen(
+        &state.db,
+        &space_id,
+        enabled,
+        description.as_deref(),
+        &channels,
+        state.db_is_postgres,
+    )
+    .await?;
+    let screen = db::screening::get_welcome_screen(&state.db, &space_id).await?;
+
+    let before_json = serde_json::json!(before);
+    let after_json = serde_json::json!(screen);
+    if let Some(changes) = super::audit_log::diff(&before_json, &after_json) {
+        super::audit_log::record(
+            &state,
+            &space_id,
+            &auth.user_id,
+            "welcome_screen_update",
+            Some((&space_id, "space")),
+            reason.as_deref(),
+            Some(changes),
+        )
+        .await;
+    }
+
+    Ok(Json(serde_json::json!({ "data": screen })))
+}
+
+/// GET /spaces/{space_id}/member-screening
+pub async fn get_member_screening(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_can_view(&state, &space_id, &auth).await?;
+    let screening = db::screening::get_member_screening(&state.db, &space_id).await?;
+    Ok(Json(serde_json::json!({ "data": screening })))
+}
+
+/// PATCH /spaces/{space_id}/member-screening
+///
+/// Turning screening on only affects members who join afterwards; turning
+/// it off lets in everyone still pending.
+pub async fn update_member_screening(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    AuditReason(reason): AuditReason,
+    Json(input): Json<UpdateMemberScreening>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let before = db::screening::get_member_screening(&state.db, &space_id).await?;
+
+    let description = resolve_description(
+        input.description,
+        before.description.clone(),
+        MAX_SCREENING_DESCRIPTION_LEN,
+    )?;
+    let rules = match input.rules {
+        Some(rules) => {
+            if rules.len() > MAX_SCREENING_RULES {
+                return Err(AppError::BadRequest(format!(
+                    "at most {MAX_SCREENING_RULES} rules"
+                )));
+            }
+            let rules: Vec<String> = rules.iter().map(|r| r.trim().to_string()).collect();
+            if rules
+                .iter()
+                .any(|r| r.is_empty() || r.chars().count() > MAX_RULE_LEN)
+            {
+                return Err(AppError::BadRequest(format!(
+                    "rules must be 1-{MAX_RULE_LEN} characters"
+                )));
+            }
+            rules
+        }
+        None => before.rules.clone(),
+    };
+    let enabled = input.enabled.unwrap_or(before.enabled);
+    if enabled && rules.is_empty() {
+        return Err(AppError::BadRequest(
+            "member screening needs at least one rule".into(),
+        ));
+    }
+
+    db::screening::set_member_screening(
+        &state.db,
+        &space_id,
+        enabled,
+        description.as_deref(),
+        &rules,
+        state.db_is_postgres,
+    )
+    .await?;
+    let screening = db::screening::get_member_screening(&state.db, &space_id).await?;
+
+    let before_json = serde_json::json!(before);
+    let after_json = serde_json::json!(screening);
+    if let Some(changes) = super::audit_log::diff(&before_json, &after_json) {
+        super::audit_log::record(
+            &state,
+            &space_id,
+            &auth.user_id,
+            "member_screening_update",
+            Some((&space_id, "space")),
+            reason.as_deref(),
+            Some(changes),
+        )
+        .await;
+    }
+
+    Ok(Json(serde_json::json!({ "data": screening })))
+}
+
+/// POST /spaces/{space_id}/member-screening/accept
+///
+/// The caller accepts the space's rules and stops being pending. Accepting
+/// again, or when nothing is pending, is a no-op.
+pub async fn accept_member_screening(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_membership(&state.db, &space_id, &auth.user_id).await?;
+    let cleared = db::screening::clear_pending(&state.db, &space_id, &auth.user_id).await?;
+    let row = db::members::get_member_row(&state.db, &space_id, &auth.user_id).await?;
+    let role_ids = db::members::get_member_role_ids(&state.db, &space_id, &auth.user_id).await?;
+    let member_json = member_row_to_json(&row, &role_ids);
+
+    if cleared {
+        if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+            let event = serde_json::json!({
+                "op": 0,
+                "type": "member.update",
+                "data": member_json
+            });
+            let _ = dispatcher.send(GatewayBroadcast {
+                space_id: Some(space_id.clone()),
+                target_user_ids: None,
+                event,
+                intent: "members".to_string(),
+                role_mention_user_ids: None,
+            });
+        }
+    }
+
+    Ok(Json(serde_json::json!({ "data": member_json })))
+}
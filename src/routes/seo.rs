@@ -76,7 +76,7 @@ fn oembed_link(base: &str, page_url: &str) -> String {
 
 /// Channel types that are never exposed as public crawlable pages.
 fn is_hidden_channel_type(t: &str) -> bool {
-    matches!(t, "category" | "dm" | "group_dm" | "voice")
+    matches!(t, "category" | "dm" | "group_dm" | "voice" | "stage")
 }
 
 /// Percent-encode a single URL path segment (RFC 3986 unreserved set kept).
@@ -1265,6 +1265,8 @@ mod tests {
             reply_to: None,
             flags: 0,
             webhook_id: None,
+            webhook_username: None,
+            webhook_avatar: None,
             thread_id: None,
             title: None,
             origin: None,
@@ -1294,8 +1296,24 @@ mod tests {
             premium_tier: "none".into(),
             public: true,
             allow_guest_access: true,
+            emoji_moderation: false,
             premium_subscription_count: 0,
             max_members: 0,
+            max_emojis: 50,
+            max_sounds: 8,
+            max_stickers: 20,
+            channel_order_version: 0,
+            mfa_level: "none".into(),
+            widget_enabled: false,
+            widget_channel_id: None,
+            message_sharing: false,
+            invite_preview_required: false,
+            stream_max_height: 720,
+            stream_max_fps: 30,
+            category: None,
+            tags: Vec::new(),
+            featured: false,
+            archived_at: None,
             created_at: "2026-06-13 11:00:00".into(),
         }
     }
@@ -1402,7 +1420,7 @@ mod tests {
 
     #[test]
     fn hidden_channel_types_are_excluded() {
-        for t in ["category", "dm", "group_dm", "voice"] {
+        for t in ["category", "dm", "group_dm", "voice", "stage"] {
             assert!(is_hidden_channel_type(t), "{t} should be hidden");
         }
         for t in ["text", "forum"] {
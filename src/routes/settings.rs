@@ -7,6 +7,7 @@ use crate::db;
 use crate::error::AppError;
 use crate::middleware::auth::AuthUser;
 use crate::middleware::permissions::require_server_admin;
+use crate::models::content_safety::ACTIONS;
 use crate::models::settings::UpdateServerSettings;
 use crate::state::AppState;
 
@@ -31,8 +32,12 @@ pub async fn get_public_settings(
             "max_emoji_size": settings.max_emoji_size,
             "max_avatar_size": settings.max_avatar_size,
             "max_sound_size": settings.max_sound_size,
+            "max_sticker_size": settings.max_sticker_size,
             "max_attachment_size": settings.max_attachment_size,
             "max_attachments_per_message": settings.max_attachments_per_message,
+            "max_emojis_per_space": settings.max_emojis_per_space,
+            "max_sounds_per_space": settings.max_sounds_per_space,
+            "max_stickers_per_space": settings.max_stickers_per_space,
             "server_name": settings.server_name,
             "registration_policy": settings.registration_policy,
             "motd": settings.motd,
@@ -47,9 +52,68 @@ pub async fn get_public_settings(
 pub async fn update_settings(
     state: State<AppState>,
     auth: AuthUser,
-    Json(input): Json<UpdateServerSettings>,
+    Json(mut input): Json<UpdateServerSettings>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_server_admin(&auth)?;
+    if let Some(Some(ref template)) = input.default_space_template {
+        template.validate()?;
+    }
+    if input.max_emojis_per_space.is_some_and(|v| v < 0)
+        || input.max_sounds_per_space.is_some_and(|v| v < 0)
+        || input.max_stickers_per_space.is_some_and(|v| v < 0)
+    {
+        return Err(AppError::BadRequest(
+            "per-space emoji, sound, and sticker limits must be 0 (unlimited) or more".into(),
+        ));
+    }
+
+    if state.cors.allow_credentials
+        && input.cors_allowed_origins.as_deref().is_some_and(|list| {
+            crate::config::parse_origin_list(list)
+                .iter()
+                .any(|o| o == "*")
+        })
+    {
+        return Err(AppError::BadRequest(
+            "cors_allowed_origins can't contain * while CORS credentials are enabled".into(),
+        ));
+    }
+
+    if let Some(ref providers) = input.content_safety_providers {
+        input.content_safety_providers =
+            Some(crate::content_safety::normalize_providers(providers)?);
+    }
+    if let Some(ref endpoint) = input.content_safety_endpoint {
+        if !endpoint.is_empty() {
+            crate::safe_fetch::validate_url(endpoint)
+                .map_err(|e| AppError::BadRequest(format!("content_safety_endpoint: {e}")))?;
+        }
+    }
+    if let Some(ref action) = input.content_safety_action {
+        if !ACTIONS.contains(&action.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "content_safety_action must be one of: {}",
+                ACTIONS.join(", ")
+            )));
+        }
+    }
+    // The http provider is no use without somewhere to send messages.
+    {
+        let current = state.settings.load();
+        let providers = input
+            .content_safety_providers
+            .as_ref()
+            .or(current.content_safety_providers.as_ref());
+        let endpoint = match input.content_safety_endpoint {
+            Some(ref e) => (!e.is_empty()).then_some(e),
+            None => current.content_safety_endpoint.as_ref(),
+        };
+        if providers.is_some_and(|p| p.split(',').any(|n| n == "http")) && endpoint.is_none() {
+            return Err(AppError::BadRequest(
+                "the http content-safety provider needs a content_safety_endpoint".into(),
+            ));
+        }
+    }
 
     let old_public_listing = state.settings.load().public_listing;
 
@@ -36,13 +36,17 @@ pub async fn create_sound(
     Json(input): Json<CreateSound>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "manage_soundboard").await?;
+    let space = db::spaces::get_space_row(&state.db, &space_id).await?;
+    let current = db::soundboard::count_sounds(&state.db, &space_id).await?;
+    AppError::check_limit("sound_limit_reached", current, space.max_sounds)?;
 
     let max_sound_size = state.settings.load().max_sound_size as usize;
+    crate::blocklist::check_data_uri_upload(&state.db, &auth.user_id, &input.audio).await?;
 
     // Save audio file
     let id = crate::snowflake::generate();
     let (audio_path, content_type, size) = storage::save_base64_audio(
-        &state.storage_path,
+        state.storage.as_ref(),
         &space_id,
         &id,
         &input.audio,
@@ -76,6 +80,7 @@ pub async fn create_sound(
             target_user_ids: None,
             event,
             intent: "soundboard".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -107,6 +112,7 @@ pub async fn update_sound(
             target_user_ids: None,
             event,
             intent: "soundboard".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -124,7 +130,7 @@ pub async fn delete_sound(
 
     // Delete the file from disk
     if let Some(ref path) = audio_path {
-        let _ = storage::delete_file(&state.storage_path, path).await;
+        let _ = storage::delete_file(state.storage.as_ref(), path).await;
     }
 
     // Broadcast to gateway
@@ -142,12 +148,28 @@ pub async fn delete_sound(
             target_user_ids: None,
             event,
             intent: "soundboard".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
     Ok(Json(serde_json::json!({ "data": null })))
 }
 
+/// Where the voice backend can fetch a sound's file: a presigned URL for
+/// object storage, otherwise `/cdn` under the configured media URL.
+fn sound_source_url(state: &AppState, audio_url: &str, media_url: Option<&str>) -> Option<String> {
+    let key = audio_url.strip_prefix("/cdn/")?;
+    state
+        .storage
+        .presigned_url(key)
+        .or_else(|| media_url.map(|base| format!("{base}{audio_url}")))
+}
+
+/// POST /spaces/{space_id}/soundboard/{sound_id}/play
+///
+/// Plays a sound into the voice channel the caller is connected to in this
+/// space. Voice clients hear it through the room; everyone in the space gets
+/// `soundboard.play` with the channel, the sound and the player.
 pub async fn play_sound(
     state: State<AppState>,
     Path((space_id, sound_id)): Path<(String, String)>,
@@ -158,6 +180,36 @@ pub async fn play_sound(
     // Verify the sound exists
     let sound = db::soundboard::get_sound(&state.db, &sound_id).await?;
 
+    let voice_state = crate::voice::state::get_user_voice_state(&state, &auth.user_id)
+        .filter(|vs| vs.space_id.as_deref() == Some(space_id.as_str()))
+        .ok_or_else(|| {
+            AppError::BadRequest("join a voice channel in this space to play sounds".to_string())
+        })?;
+    if voice_state.mute || voice_state.suppress {
+        return Err(AppError::Forbidden(
+            "you can't play sounds while you can't speak".to_string(),
+        ));
+    }
+    let channel_id = voice_state.channel_id.unwrap_or_default();
+
+    if !state.test_mode {
+        if let Some(ref lk) = state.livekit_client {
+            match sound
+                .audio_url
+                .as_deref()
+                .and_then(|url| sound_source_url(&state, url, lk.media_url()))
+            {
+                Some(source_url) => {
+                    lk.play_sound(&channel_id, &sound_id, &auth.user_id, &source_url)
+                        .await?
+                }
+                None => tracing::warn!(
+                    "soundboard sound {sound_id} has no URL LiveKit can fetch; set LIVEKIT_MEDIA_URL"
+                ),
+            }
+        }
+    }
+
     // Broadcast to gateway
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
         let event = serde_json::json!({
@@ -165,6 +217,8 @@ pub async fn play_sound(
             "type": "soundboard.play",
             "data": {
                 "space_id": space_id,
+                "channel_id": channel_id,
+                "sound_id": sound_id,
                 "sound": sound,
                 "user_id": auth.user_id
             }
@@ -174,6 +228,7 @@ pub async fn play_sound(
             target_user_ids: None,
             event,
             intent: "soundboard".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
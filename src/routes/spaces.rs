@@ -1,21 +1,28 @@
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::Json;
+use serde::Deserialize;
 
 use crate::db;
 use crate::error::AppError;
 use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::audit::AuditReason;
 use crate::middleware::auth::{AuthUser, OptionalAuthUser};
-use crate::middleware::permissions::{require_membership, require_permission};
-use crate::models::channel::{ChannelPositionUpdate, ChannelRow, CreateChannel};
+use crate::middleware::permissions::{
+    require_membership, require_not_archived, require_permission, require_recent_mfa,
+};
+use crate::models::channel::{ChannelRow, CreateChannel, ReorderChannels, DEFAULT_MAX_PINS};
 use crate::models::permission::PermissionOverwrite;
-use crate::models::space::{CreateSpace, UpdateSpace};
+use crate::models::space::{
+    normalize_tags, CreateSpace, DirectoryFilter, DirectorySort, UpdateSpace, SPACE_CATEGORIES,
+    STREAM_FRAME_RATES, STREAM_HEIGHTS,
+};
 use crate::state::AppState;
 use crate::storage;
 
 pub async fn create_space(
     state: State<AppState>,
     auth: AuthUser,
-    Json(input): Json<CreateSpace>,
+    Json(mut input): Json<CreateSpace>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     // Input validation
     let name = input.name.trim();
@@ -38,8 +45,21 @@ pub async fn create_space(
             ));
         }
     }
+    if let Some(ref locale) = input.preferred_locale {
+        input.preferred_locale = Some(crate::locale::normalize(locale)?.to_string());
+    }
+    match input.template {
+        Some(ref template) => template.validate()?,
+        None => input.template = state.settings.load().default_space_template.clone(),
+    }
 
     let space = db::spaces::create_space(&state.db, &auth.user_id, &input).await?;
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let _ = dispatcher.send(GatewayBroadcast::admin(
+            "admin.space_create",
+            serde_json::json!(space),
+        ));
+    }
     Ok(Json(serde_json::json!({ "data": space })))
 }
 
@@ -77,21 +97,78 @@ pub async fn update_space(
     state: State<AppState>,
     Path(space_id): Path<String>,
     auth: AuthUser,
+    audit_reason: AuditReason,
     Json(mut input): Json<UpdateSpace>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "manage_space").await?;
+    let before = db::spaces::get_space_row(&state.db, &space_id).await?;
+
+    if let Some(ref locale) = input.preferred_locale {
+        input.preferred_locale = Some(crate::locale::normalize(locale)?.to_string());
+    }
+    if let Some(ref mfa_level) = input.mfa_level {
+        if mfa_level != "none" && mfa_level != "elevated" {
+            return Err(AppError::BadRequest(
+                "mfa_level must be none or elevated".to_string(),
+            ));
+        }
+        if before.owner_id != auth.user_id {
+            return Err(AppError::Forbidden(
+                "only the owner can change mfa_level".to_string(),
+            ));
+        }
+        require_recent_mfa(&state.db, &space_id, &auth).await?;
+    }
+    if let Some(ref channel_id) = input.widget_channel_id {
+        if !channel_id.is_empty() {
+            let channel = db::channels::get_channel_row(&state.db, channel_id).await?;
+            if channel.space_id.as_deref() != Some(space_id.as_str()) {
+                return Err(AppError::BadRequest(
+                    "widget_channel_id must be a channel in this space".to_string(),
+                ));
+            }
+        }
+    }
+    if input
+        .stream_max_height
+        .is_some_and(|h| !STREAM_HEIGHTS.contains(&h))
+    {
+        return Err(AppError::BadRequest(format!(
+            "stream_max_height must be one of {STREAM_HEIGHTS:?}"
+        )));
+    }
+    if input
+        .stream_max_fps
+        .is_some_and(|fps| !STREAM_FRAME_RATES.contains(&fps))
+    {
+        return Err(AppError::BadRequest(format!(
+            "stream_max_fps must be one of {STREAM_FRAME_RATES:?}"
+        )));
+    }
+    if let Some(ref category) = input.category {
+        if !category.is_empty() && !SPACE_CATEGORIES.contains(&category.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "category must be one of {SPACE_CATEGORIES:?}"
+            )));
+        }
+    }
+    if let Some(ref tags) = input.tags {
+        input.tags = Some(normalize_tags(tags).map_err(AppError::BadRequest)?);
+    }
+    let before_json = serde_json::to_value(&before).unwrap_or_default();
 
     let max_avatar_size = state.settings.load().max_avatar_size as usize;
 
     // Process icon data URI
     if let Some(ref icon) = input.icon {
         if icon.starts_with("data:") {
+            crate::blocklist::check_data_uri_upload(&state.db, &auth.user_id, icon).await?;
             let old_space = db::spaces::get_space_row(&state.db, &space_id).await?;
             if let Some(ref old_icon) = old_space.icon {
-                let _ = storage::delete_file(&state.storage_path, old_icon).await;
+                let _ = storage::delete_file(state.storage.as_ref(), old_icon).await;
             }
             let (url, _, _, _) = storage::save_avatar_image(
-                &state.storage_path,
+                state.storage.as_ref(),
                 "icons",
                 &space_id,
                 icon,
@@ -102,9 +179,9 @@ pub async fn update_space(
         } else if icon.is_empty() {
             let old_space = db::spaces::get_space_row(&state.db, &space_id).await?;
             if let Some(ref old_icon) = old_space.icon {
-                let _ = storage::delete_file(&state.storage_path, old_icon).await;
+                let _ = storage::delete_file(state.storage.as_ref(), old_icon).await;
             }
-            storage::delete_avatar(&state.storage_path, "icons", &space_id).await?;
+            storage::delete_avatar(state.storage.as_ref(), "icons", &space_id).await?;
             // Keep as Some("") — DB layer will treat empty string as NULL
         }
     }
@@ -112,12 +189,13 @@ pub async fn update_space(
     // Process banner data URI
     if let Some(ref banner) = input.banner {
         if banner.starts_with("data:") {
+            crate::blocklist::check_data_uri_upload(&state.db, &auth.user_id, banner).await?;
             let old_space = db::spaces::get_space_row(&state.db, &space_id).await?;
             if let Some(ref old_banner) = old_space.banner {
-                let _ = storage::delete_file(&state.storage_path, old_banner).await;
+                let _ = storage::delete_file(state.storage.as_ref(), old_banner).await;
             }
             let (url, _, _, _) = storage::save_avatar_image(
-                &state.storage_path,
+                state.storage.as_ref(),
                 "banners",
                 &space_id,
                 banner,
@@ -128,9 +206,9 @@ pub async fn update_space(
         } else if banner.is_empty() {
             let old_space = db::spaces::get_space_row(&state.db, &space_id).await?;
             if let Some(ref old_banner) = old_space.banner {
-                let _ = storage::delete_file(&state.storage_path, old_banner).await;
+                let _ = storage::delete_file(state.storage.as_ref(), old_banner).await;
             }
-            storage::delete_avatar(&state.storage_path, "banners", &space_id).await?;
+            storage::delete_avatar(state.storage.as_ref(), "banners", &space_id).await?;
             // Keep as Some("") — DB layer will treat empty string as NULL
         }
     }
@@ -138,6 +216,20 @@ pub async fn update_space(
     let space =
         db::spaces::update_space(&state.db, &space_id, &input, state.db_is_postgres).await?;
 
+    let after_json = serde_json::to_value(&space).unwrap_or_default();
+    if let Some(changes) = super::audit_log::diff(&before_json, &after_json) {
+        super::audit_log::record(
+            &state,
+            &space_id,
+            &auth.user_id,
+            "space_update",
+            Some((&space_id, "space")),
+            audit_reason.as_deref(),
+            Some(changes),
+        )
+        .await;
+    }
+
     // Broadcast space.update to space members
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
         let event = serde_json::json!({
@@ -150,6 +242,7 @@ pub async fn update_space(
             target_user_ids: None,
             event,
             intent: "spaces".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -165,6 +258,13 @@ pub async fn delete_space(
     if space.owner_id != auth.user_id && !auth.is_admin {
         return Err(AppError::Forbidden("you do not own this space".to_string()));
     }
+    require_recent_mfa(&state.db, &space_id, &auth).await?;
+    remove_space(&state, &space_id).await?;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// Delete a space and tell its members.
+pub(super) async fn remove_space(state: &AppState, space_id: &str) -> Result<(), AppError> {
     // Broadcast space.delete before deleting so members still exist
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
         let event = serde_json::json!({
@@ -173,15 +273,89 @@ pub async fn delete_space(
             "data": { "id": space_id }
         });
         let _ = dispatcher.send(GatewayBroadcast {
-            space_id: Some(space_id.clone()),
+            space_id: Some(space_id.to_string()),
             target_user_ids: None,
             event,
             intent: "spaces".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
-    db::spaces::delete_space(&state.db, &space_id).await?;
-    Ok(Json(serde_json::json!({ "data": null })))
+    db::spaces::delete_space(&state.db, space_id).await
+}
+
+/// PUT /spaces/{space_id}/archive — freeze the space. Its history stays
+/// readable, but sends, channel changes and joins are rejected with 403
+/// `space_archived` until the owner unarchives it.
+pub async fn archive_space(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    audit_reason: AuditReason,
+) -> Result<Json<serde_json::Value>, AppError> {
+    set_space_archived(&state, &space_id, &auth, audit_reason.as_deref(), true).await
+}
+
+/// DELETE /spaces/{space_id}/archive
+pub async fn unarchive_space(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    audit_reason: AuditReason,
+) -> Result<Json<serde_json::Value>, AppError> {
+    set_space_archived(&state, &space_id, &auth, audit_reason.as_deref(), false).await
+}
+
+async fn set_space_archived(
+    state: &AppState,
+    space_id: &str,
+    auth: &AuthUser,
+    reason: Option<&str>,
+    archived: bool,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let before = db::spaces::get_space_row(&state.db, space_id).await?;
+    if before.owner_id != auth.user_id && !auth.is_admin {
+        return Err(AppError::Forbidden(
+            "only the owner can archive or unarchive a space".to_string(),
+        ));
+    }
+    if before.archived_at.is_some() == archived {
+        return Ok(Json(serde_json::json!({ "data": before })));
+    }
+    db::spaces::set_archived(&state.db, space_id, archived).await?;
+    let space = db::spaces::get_space_row(&state.db, space_id).await?;
+
+    super::audit_log::record(
+        state,
+        space_id,
+        &auth.user_id,
+        if archived {
+            "space_archive"
+        } else {
+            "space_unarchive"
+        },
+        Some((space_id, "space")),
+        reason,
+        None,
+    )
+    .await;
+
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "space.update",
+            "data": space
+        });
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: Some(space_id.to_string()),
+            target_user_ids: None,
+            event,
+            intent: "spaces".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+
+    Ok(Json(serde_json::json!({ "data": space })))
 }
 
 pub async fn list_channels(
@@ -220,9 +394,11 @@ pub async fn create_channel(
     state: State<AppState>,
     Path(space_id): Path<String>,
     auth: AuthUser,
+    audit_reason: AuditReason,
     Json(input): Json<CreateChannel>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "manage_channels").await?;
+    require_not_archived(&state.db, &space_id).await?;
 
     // Input validation
     let name = input.name.trim();
@@ -257,6 +433,17 @@ pub async fn create_channel(
     // Newly created channel has no overwrites
     let json = channel_row_to_json_with_overwrites(&channel, &[]);
 
+    super::audit_log::record(
+        &state,
+        &space_id,
+        &auth.user_id,
+        "channel_create",
+        Some((&channel.id, "channel")),
+        audit_reason.as_deref(),
+        super::audit_log::diff(&serde_json::json!({}), &json),
+    )
+    .await;
+
     // Broadcast channel.create to space members
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
         let event = serde_json::json!({
@@ -269,36 +456,57 @@ pub async fn create_channel(
             target_user_ids: None,
             event,
             intent: "channels".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
     Ok(Json(serde_json::json!({ "data": json })))
 }
 
+/// `PATCH /spaces/{id}/channels` -- move channels, renumbering the whole
+/// space to contiguous positions. Members get one `channel.position_update`
+/// with every position that changed and the new `channel_order_version`.
 pub async fn reorder_channels(
     state: State<AppState>,
     Path(space_id): Path<String>,
     auth: AuthUser,
-    Json(input): Json<Vec<ChannelPositionUpdate>>,
+    Json(input): Json<ReorderChannels>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     require_permission(&state.db, &space_id, &auth, "manage_channels").await?;
-    let updates: Vec<(String, i64)> = input.into_iter().map(|u| (u.id, u.position)).collect();
-    db::channels::reorder_channels(&state.db, &space_id, &updates).await?;
+    require_not_archived(&state.db, &space_id).await?;
+    let (moves, expected_version) = match input {
+        ReorderChannels::Moves(moves) => (moves, None),
+        ReorderChannels::Versioned {
+            channels,
+            expected_version,
+        } => (channels, expected_version),
+    };
+    let updates: Vec<(String, i64)> = moves.into_iter().map(|u| (u.id, u.position)).collect();
+    let (version, changed) =
+        db::channels::reorder_channels(&state.db, &space_id, &updates, expected_version).await?;
     let channels = db::channels::list_channels_in_space(&state.db, &space_id).await?;
     let data = channels_to_json_async(&state.db, &channels).await?;
 
-    // Broadcast channel.reorder to space members
     if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let positions: Vec<serde_json::Value> = changed
+            .iter()
+            .map(|(id, position)| serde_json::json!({ "id": id, "position": position }))
+            .collect();
         let event = serde_json::json!({
             "op": 0,
-            "type": "channel.reorder",
-            "data": { "space_id": space_id, "channels": data }
+            "type": "channel.position_update",
+            "data": {
+                "space_id": space_id,
+                "channel_order_version": version,
+                "positions": positions
+            }
         });
         let _ = dispatcher.send(GatewayBroadcast {
             space_id: Some(space_id),
             target_user_ids: None,
             event,
             intent: "channels".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -307,7 +515,10 @@ pub async fn reorder_channels(
 
 /// Build channel JSON for external callers (e.g. channels.rs).
 /// Loads overwrites from the DB. For DM/group_dm channels, includes recipients.
-pub async fn channel_row_to_json_pub(pool: &sqlx::AnyPool, row: &ChannelRow) -> serde_json::Value {
+pub async fn channel_row_to_json_pub(
+    pool: &crate::db::DbPool,
+    row: &ChannelRow,
+) -> serde_json::Value {
     let overwrites = db::permission_overwrites::list_overwrites(pool, &row.id)
         .await
         .unwrap_or_default();
@@ -316,10 +527,12 @@ pub async fn channel_row_to_json_pub(pool: &sqlx::AnyPool, row: &ChannelRow) ->
         let users = db::dm_participants::get_participant_users(pool, &row.id)
             .await
             .unwrap_or_default();
-        json.as_object_mut().unwrap().insert(
+        let obj = json.as_object_mut().unwrap();
+        obj.insert(
             "recipients".to_string(),
             serde_json::to_value(&users).unwrap_or_default(),
         );
+        obj.insert("icon".to_string(), serde_json::json!(row.icon));
     }
     json
 }
@@ -346,27 +559,106 @@ fn channel_row_to_json_with_overwrites(
         "archived": row.archived,
         "auto_archive_after": row.auto_archive_after,
         "allow_anonymous_read": row.allow_anonymous_read,
+        "announcement_locked": row.announcement_locked,
+        "voice_chat_retention": row.voice_chat_retention,
+        "auto_thread": row.auto_thread,
+        "auto_thread_name": row.auto_thread_name,
+        "max_pins": row.max_pins.unwrap_or(DEFAULT_MAX_PINS),
         "created_at": row.created_at
     })
 }
 
 pub async fn channels_to_json_async(
-    pool: &sqlx::AnyPool,
+    pool: &crate::db::DbPool,
     rows: &[ChannelRow],
 ) -> Result<Vec<serde_json::Value>, AppError> {
-    let mut result = Vec::with_capacity(rows.len());
-    for row in rows {
-        let overwrites = db::permission_overwrites::list_overwrites(pool, &row.id).await?;
-        result.push(channel_row_to_json_with_overwrites(row, &overwrites));
-    }
-    Ok(result)
+    let ids: Vec<String> = rows.iter().map(|r| r.id.clone()).collect();
+    let overwrites = db::permission_overwrites::list_overwrites_for_channels(pool, &ids).await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            channel_row_to_json_with_overwrites(
+                row,
+                overwrites.get(&row.id).map(Vec::as_slice).unwrap_or(&[]),
+            )
+        })
+        .collect())
 }
 
+#[derive(Deserialize)]
+pub struct DirectoryQuery {
+    /// Words to look for in the name and description.
+    pub q: Option<String>,
+    pub category: Option<String>,
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub featured: bool,
+    pub sort: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// GET /spaces/public
+///
+/// The public space directory, most members first unless `sort` says
+/// `activity`, `newest` or `name`. Pages with `limit` (up to 100) and
+/// `offset`; `cursor.offset` gives the next page while there is one.
 pub async fn list_public_spaces(
     state: State<AppState>,
+    Query(params): Query<DirectoryQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let sort = match params.sort.as_deref() {
+        None => DirectorySort::default(),
+        Some(s) => DirectorySort::parse(s).ok_or_else(|| {
+            AppError::BadRequest("sort must be members, activity, newest or name".into())
+        })?,
+    };
+    let filter = DirectoryFilter {
+        search: params.q.filter(|q| !q.trim().is_empty()),
+        category: params.category.filter(|c| !c.is_empty()),
+        tag: params
+            .tag
+            .map(|t| t.trim().to_lowercase())
+            .filter(|t| !t.is_empty()),
+        featured_only: params.featured,
+        sort,
+    };
+    let limit = params.limit.unwrap_or(50).clamp(1, 100);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    let mut spaces = db::spaces::search_public_spaces(&state.db, &filter, limit, offset).await?;
+    let has_more = spaces.len() as i64 > limit;
+    if has_more {
+        spaces.truncate(limit as usize);
+    }
+    let mut response = serde_json::json!({ "data": spaces });
+    if has_more {
+        response["cursor"] = serde_json::json!({
+            "offset": offset + limit,
+            "has_more": has_more
+        });
+    }
+    Ok(Json(response))
+}
+
+/// GET /spaces/public/categories
+///
+/// Every directory category with how many public spaces list under it.
+pub async fn list_directory_categories(
+    state: State<AppState>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let spaces = db::spaces::list_public_spaces(&state.db).await?;
-    Ok(Json(serde_json::json!({ "data": spaces })))
+    let counts = db::spaces::count_public_spaces_by_category(&state.db).await?;
+    let categories: Vec<serde_json::Value> = SPACE_CATEGORIES
+        .iter()
+        .map(|name| {
+            let count = counts
+                .iter()
+                .find(|(c, _)| c == name)
+                .map_or(0, |(_, n)| *n);
+            serde_json::json!({ "name": name, "space_count": count })
+        })
+        .collect();
+    Ok(Json(serde_json::json!({ "data": categories })))
 }
 
 pub async fn join_public_space(
@@ -383,6 +675,21 @@ pub async fn join_public_space(
     if !space.public {
         return Err(AppError::Forbidden("this space is not public".to_string()));
     }
+    require_not_archived(&state.db, &space.id).await?;
+    crate::blocklist::check_user(&state.db, &auth.user_id).await?;
+    crate::raid::check_join(&state, &space.id, &auth.user_id).await?;
+    crate::email_domains::check_join(&state.db, &space.id, &auth).await?;
+    if state.require_verified_email
+        && !auth.is_bot
+        && !db::users::get_user(&state.db, &auth.user_id)
+            .await?
+            .email_verified
+    {
+        return Err(AppError::ForbiddenCode(
+            "email_unverified",
+            "verify your email address before joining public spaces".to_string(),
+        ));
+    }
 
     // Check if the user is banned
     if db::bans::get_ban(&state.db, &space.id, &auth.user_id)
@@ -398,6 +705,10 @@ pub async fn join_public_space(
         db::members::add_member(&state.db, &space.id, &auth.user_id, state.db_is_postgres).await?;
 
     if newly_added {
+        if let Err(e) = crate::raid::record_join(&state, &space.id).await {
+            tracing::warn!("raid check failed for space {}: {e:?}", space.id);
+        }
+
         // Broadcast member.join to the space
         let user = db::users::get_user(&state.db, &auth.user_id).await?;
         if let Some(ref dispatcher) = *state.gateway_tx.read().await {
@@ -415,6 +726,7 @@ pub async fn join_public_space(
                 target_user_ids: None,
                 event,
                 intent: "members".to_string(),
+                role_mention_user_ids: None,
             });
         }
 
@@ -0,0 +1,216 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::db;
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::{require_membership, require_permission};
+use crate::models::sticker::{validate_sticker_fields, CreateSticker, UpdateSticker};
+use crate::state::AppState;
+use crate::storage;
+
+pub async fn list_stickers(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_membership(&state.db, &space_id, &auth.user_id).await?;
+    let stickers = db::stickers::list_stickers(&state.db, &space_id).await?;
+    Ok(Json(serde_json::json!({ "data": stickers })))
+}
+
+pub async fn get_sticker(
+    state: State<AppState>,
+    Path((space_id, sticker_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_membership(&state.db, &space_id, &auth.user_id).await?;
+    let sticker = db::stickers::get_sticker_in_space(&state.db, &space_id, &sticker_id).await?;
+    Ok(Json(serde_json::json!({ "data": sticker })))
+}
+
+/// POST /spaces/{space_id}/stickers
+/// Stickers are managed with the same permission as emoji.
+pub async fn create_sticker(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<CreateSticker>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_emojis").await?;
+    require_local_space(&state, &space_id).await?;
+    validate_sticker_fields(
+        Some(&input.name),
+        input.description.as_deref(),
+        input.tags.as_deref(),
+    )
+    .map_err(AppError::InvalidFields)?;
+
+    let space = db::spaces::get_space_row(&state.db, &space_id).await?;
+    let current = db::stickers::count_stickers(&state.db, &space_id).await?;
+    AppError::check_limit("sticker_limit_reached", current, space.max_stickers)?;
+
+    let max_sticker_size = state.settings.load().max_sticker_size as usize;
+    crate::blocklist::check_data_uri_upload(&state.db, &auth.user_id, &input.image).await?;
+
+    let sticker_id = crate::snowflake::generate();
+    let (image_path, content_type, size, format) = storage::save_sticker(
+        state.storage.as_ref(),
+        &space_id,
+        &sticker_id,
+        &input.image,
+        max_sticker_size,
+    )
+    .await?;
+
+    let description = input
+        .description
+        .as_deref()
+        .map(str::trim)
+        .filter(|d| !d.is_empty());
+    let sticker = db::stickers::create_sticker(
+        &state.db,
+        &sticker_id,
+        &space_id,
+        &auth.user_id,
+        input.name.trim(),
+        description,
+        input.tags.as_deref().unwrap_or_default().trim(),
+        format,
+        &image_path,
+        &content_type,
+        size,
+    )
+    .await?;
+
+    broadcast_sticker_event(
+        &state,
+        &space_id,
+        "sticker.create",
+        serde_json::json!({ "space_id": space_id, "sticker": sticker }),
+    )
+    .await;
+
+    Ok(Json(serde_json::json!({ "data": sticker })))
+}
+
+pub async fn update_sticker(
+    state: State<AppState>,
+    Path((space_id, sticker_id)): Path<(String, String)>,
+    auth: AuthUser,
+    Json(input): Json<UpdateSticker>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_emojis").await?;
+    require_local_space(&state, &space_id).await?;
+    db::stickers::get_sticker_in_space(&state.db, &space_id, &sticker_id).await?;
+    validate_sticker_fields(
+        input.name.as_deref(),
+        input.description.as_deref(),
+        input.tags.as_deref(),
+    )
+    .map_err(AppError::InvalidFields)?;
+
+    let sticker =
+        db::stickers::update_sticker(&state.db, &sticker_id, &input, state.db_is_postgres).await?;
+
+    broadcast_sticker_event(
+        &state,
+        &space_id,
+        "sticker.update",
+        serde_json::json!({ "space_id": space_id, "sticker": sticker }),
+    )
+    .await;
+
+    Ok(Json(serde_json::json!({ "data": sticker })))
+}
+
+/// DELETE /spaces/{space_id}/stickers/{sticker_id}
+/// Messages that carried the sticker drop it from their `sticker_items`.
+pub async fn delete_sticker(
+    state: State<AppState>,
+    Path((space_id, sticker_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_emojis").await?;
+    require_local_space(&state, &space_id).await?;
+    db::stickers::get_sticker_in_space(&state.db, &space_id, &sticker_id).await?;
+
+    let image_path = db::stickers::delete_sticker(&state.db, &sticker_id).await?;
+    let _ = storage::delete_file(state.storage.as_ref(), &image_path).await;
+
+    broadcast_sticker_event(
+        &state,
+        &space_id,
+        "sticker.delete",
+        serde_json::json!({ "space_id": space_id, "sticker_id": sticker_id }),
+    )
+    .await;
+
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// Check the `sticker_ids` of a new message: at most
+/// [MAX_STICKERS_PER_MESSAGE](crate::models::sticker::MAX_STICKERS_PER_MESSAGE),
+/// no repeats, and all from the space the message is sent in. DMs have no
+/// stickers to send.
+pub(crate) async fn validate_message_stickers(
+    state: &AppState,
+    space_id: Option<&str>,
+    sticker_ids: &[String],
+) -> Result<(), AppError> {
+    use crate::models::sticker::MAX_STICKERS_PER_MESSAGE;
+
+    let invalid = |message: String| AppError::InvalidFields(vec![("sticker_ids".into(), message)]);
+    if sticker_ids.is_empty() {
+        return Ok(());
+    }
+    let Some(space_id) = space_id else {
+        return Err(invalid("stickers can only be sent in a space".into()));
+    };
+    if sticker_ids.len() > MAX_STICKERS_PER_MESSAGE {
+        return Err(invalid(format!(
+            "at most {MAX_STICKERS_PER_MESSAGE} stickers per message"
+        )));
+    }
+    let found = db::stickers::filter_space_sticker_ids(&state.db, space_id, sticker_ids).await?;
+    if found.len() != sticker_ids.len() {
+        return Err(invalid("must be distinct stickers from this space".into()));
+    }
+    Ok(())
+}
+
+async fn broadcast_sticker_event(
+    state: &AppState,
+    space_id: &str,
+    event_type: &str,
+    data: serde_json::Value,
+) {
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": event_type,
+            "data": data
+        });
+        let _ = dispatcher.send(crate::gateway::events::GatewayBroadcast {
+            space_id: Some(space_id.to_string()),
+            target_user_ids: None,
+            event,
+            intent: "emojis".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
+
+/// Stickers are kept on the space's home server and aren't federated, so a
+/// replica can't manage them.
+async fn require_local_space(state: &AppState, space_id: &str) -> Result<(), AppError> {
+    if db::federation::space_origin(&state.db, space_id)
+        .await?
+        .is_some()
+    {
+        return Err(AppError::Forbidden(
+            "sticker management must be performed on the space's home server".to_string(),
+        ));
+    }
+    Ok(())
+}
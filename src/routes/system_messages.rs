@@ -79,15 +79,16 @@ pub async fn broadcast_member_join_message(state: &AppState, space_id: &str, use
 
     let username = user.display_name.as_deref().unwrap_or(&user.username);
 
-    let content = format!("{} joined the server.", username);
+    let content = crate::locale::member_joined(&space.preferred_locale, username);
 
     let msg = match db::messages::create_system_message(
         &state.db,
         &system_channel_id,
         user_id,
-        space_id,
+        Some(space_id),
         &content,
         "member_join",
+        None,
     )
     .await
     {
@@ -125,6 +126,7 @@ pub async fn broadcast_member_join_message(state: &AppState, space_id: &str, use
             target_user_ids: None,
             event,
             intent: "messages".to_string(),
+            role_mention_user_ids: None,
         });
     }
 }
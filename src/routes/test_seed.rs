@@ -1,24 +1,50 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::body::Bytes;
 use axum::extract::State;
 use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::response::{IntoResponse, Response};
 use axum::Json;
+use serde::Deserialize;
 use serde_json::json;
 
-use sqlx::AnyPool;
+use crate::db::DbPool;
 
 use crate::db;
 use crate::error::AppError;
 use crate::middleware::auth::{create_token_hash, generate_token};
 use crate::models::channel::CreateChannel;
+use crate::models::message::CreateMessage;
+use crate::models::permission::ALL_PERMISSIONS;
 use crate::models::plugin::PluginManifest;
+use crate::models::role::CreateRole;
 use crate::models::space::{CreateSpace, SpaceRow};
 use crate::models::user::{CreateUser, User};
 use crate::snowflake;
 use crate::state::AppState;
 
-pub async fn seed(State(state): State<AppState>) -> impl IntoResponse {
-    match do_seed(&state).await {
-        Ok(data) => (StatusCode::OK, Json(json!({ "data": data }))),
+/// POST /test/seed
+///
+/// With no body, ensures the fixed fixture (test user, bot, space, #testing,
+/// plugin) exists. With a JSON [Scenario] body, builds that scenario
+/// instead and returns every created ID and token.
+pub async fn seed(State(state): State<AppState>, body: Bytes) -> Response {
+    let result = if body.iter().all(u8::is_ascii_whitespace) {
+        do_seed(&state).await
+    } else {
+        let scenario: Scenario = match serde_json::from_slice(&body) {
+            Ok(s) => s,
+            Err(e) => {
+                return AppError::BadRequest(format!("invalid scenario: {e}")).into_response()
+            }
+        };
+        if let Err(e) = scenario.validate() {
+            return e.into_response();
+        }
+        seed_scenario(&state, &scenario).await
+    };
+    match result {
+        Ok(data) => (StatusCode::OK, Json(json!({ "data": data }))).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({
@@ -27,7 +53,8 @@ pub async fn seed(State(state): State<AppState>) -> impl IntoResponse {
                     "message": format!("{e:?}")
                 }
             })),
-        ),
+        )
+            .into_response(),
     }
 }
 
@@ -36,22 +63,7 @@ async fn do_seed(state: &AppState) -> Result<serde_json::Value, AppError> {
 
     // 1. Find or create the bearer user, then rotate its token
     let user = find_or_create_user(pool, "test_user", "Test User").await?;
-
-    sqlx::query(&crate::db::q("DELETE FROM user_tokens WHERE user_id = ?"))
-        .bind(&user.id)
-        .execute(pool)
-        .await?;
-
-    let user_token = generate_token();
-    let user_token_hash = create_token_hash(&user_token);
-
-    sqlx::query(&crate::db::q(
-        "INSERT INTO user_tokens (token_hash, user_id, expires_at) VALUES (?, ?, '2099-12-31T23:59:59')",
-    ))
-    .bind(&user_token_hash)
-    .bind(&user.id)
-    .execute(pool)
-    .await?;
+    let user_token = rotate_user_token(pool, &user.id).await?;
 
     // 2. Find or create the bot application, then rotate its token
     let (app, bot_user_id, bot_token) =
@@ -153,12 +165,373 @@ async fn do_seed(state: &AppState) -> Result<serde_json::Value, AppError> {
     }))
 }
 
+// ---------------------------------------------------------------------------
+// Scenarios
+// ---------------------------------------------------------------------------
+
+/// A fixture described declaratively. Users are referred to by username
+/// everywhere else in the document; existing users with that name are
+/// reused (with a fresh token), while spaces are always created anew.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    users: Vec<ScenarioUser>,
+    #[serde(default)]
+    spaces: Vec<ScenarioSpace>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioUser {
+    username: String,
+    display_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioSpace {
+    /// Key for this space in the response; defaults to `name`.
+    key: Option<String>,
+    name: String,
+    owner: String,
+    #[serde(default)]
+    public: bool,
+    /// Members besides the owner.
+    #[serde(default)]
+    members: Vec<String>,
+    #[serde(default)]
+    roles: Vec<ScenarioRole>,
+    #[serde(default)]
+    channels: Vec<ScenarioChannel>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioRole {
+    name: String,
+    #[serde(default)]
+    permissions: Vec<String>,
+    #[serde(default)]
+    members: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioChannel {
+    name: String,
+    #[serde(rename = "type", default = "default_channel_type")]
+    channel_type: String,
+    topic: Option<String>,
+    /// Posted in order, oldest first.
+    #[serde(default)]
+    messages: Vec<ScenarioMessage>,
+    /// Users connected to this (voice) channel.
+    #[serde(default)]
+    voice_states: Vec<ScenarioVoiceState>,
+}
+
+fn default_channel_type() -> String {
+    "text".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioMessage {
+    author: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioVoiceState {
+    user: String,
+    #[serde(default)]
+    self_mute: bool,
+    #[serde(default)]
+    self_deaf: bool,
+}
+
+impl Scenario {
+    /// Check every cross-reference up front so a bad document writes nothing.
+    fn validate(&self) -> Result<(), AppError> {
+        let bad = |msg: String| Err(AppError::BadRequest(msg));
+        let mut usernames = HashSet::new();
+        for user in &self.users {
+            if user.username.trim().is_empty() {
+                return bad("usernames must not be empty".into());
+            }
+            if !usernames.insert(user.username.as_str()) {
+                return bad(format!("user {} is listed twice", user.username));
+            }
+        }
+
+        let mut space_keys = HashSet::new();
+        for space in &self.spaces {
+            let key = space.key.as_deref().unwrap_or(&space.name);
+            if !space_keys.insert(key) {
+                return bad(format!("space key {key} is used twice"));
+            }
+            if !usernames.contains(space.owner.as_str()) {
+                return bad(format!("space {key}: unknown owner {}", space.owner));
+            }
+            let mut members: HashSet<&str> = HashSet::from([space.owner.as_str()]);
+            for member in &space.members {
+                if !usernames.contains(member.as_str()) {
+                    return bad(format!("space {key}: unknown member {member}"));
+                }
+                members.insert(member.as_str());
+            }
+            let is_member = |username: &str, what: &str| {
+                if members.contains(username) {
+                    Ok(())
+                } else {
+                    bad(format!("space {key}: {what} {username} is not a member"))
+                }
+            };
+
+            let mut role_names = HashSet::new();
+            for role in &space.roles {
+                if !role_names.insert(role.name.as_str()) {
+                    return bad(format!("space {key}: role {} is listed twice", role.name));
+                }
+                if let Some(p) = role
+                    .permissions
+                    .iter()
+                    .find(|p| !ALL_PERMISSIONS.contains(&p.as_str()))
+                {
+                    return bad(format!("space {key}: unknown permission {p}"));
+                }
+                for member in &role.members {
+                    is_member(member, "role member")?;
+                }
+            }
+
+            let mut channel_names = HashSet::new();
+            let mut in_voice = HashSet::new();
+            for channel in &space.channels {
+                if !channel_names.insert(channel.name.as_str()) {
+                    return bad(format!(
+                        "space {key}: channel {} is listed twice",
+                        channel.name
+                    ));
+                }
+                for message in &channel.messages {
+                    is_member(&message.author, "message author")?;
+                }
+                if !channel.voice_states.is_empty()
+                    && !crate::models::channel::is_voice_type(&channel.channel_type)
+                {
+                    return bad(format!(
+                        "space {key}: voice states need a voice channel, {} is {}",
+                        channel.name, channel.channel_type
+                    ));
+                }
+                for vs in &channel.voice_states {
+                    is_member(&vs.user, "voice user")?;
+                    if !in_voice.insert(vs.user.as_str()) {
+                        return bad(format!("space {key}: {} is in two voice channels", vs.user));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn seed_scenario(
+    state: &AppState,
+    scenario: &Scenario,
+) -> Result<serde_json::Value, AppError> {
+    let pool = &state.db;
+
+    let mut user_ids: HashMap<&str, String> = HashMap::new();
+    let mut users_json = serde_json::Map::new();
+    for u in &scenario.users {
+        let user = find_or_create_user(
+            pool,
+            &u.username,
+            u.display_name.as_deref().unwrap_or(&u.username),
+        )
+        .await?;
+        let token = rotate_user_token(pool, &user.id).await?;
+        users_json.insert(
+            u.username.clone(),
+            json!({
+                "id": user.id,
+                "username": user.username,
+                "token": token,
+                "token_type": "Bearer"
+            }),
+        );
+        user_ids.insert(u.username.as_str(), user.id);
+    }
+
+    let mut spaces_json = serde_json::Map::new();
+    for s in &scenario.spaces {
+        let space = db::spaces::create_space(
+            pool,
+            &user_ids[s.owner.as_str()],
+            &CreateSpace {
+                name: s.name.clone(),
+                slug: None,
+                description: None,
+                public: Some(s.public),
+                allow_guest_access: None,
+                preferred_locale: None,
+                template: None,
+            },
+        )
+        .await?;
+        for member in &s.members {
+            let _ = db::members::add_member(
+                pool,
+                &space.id,
+                &user_ids[member.as_str()],
+                state.db_is_postgres,
+            )
+            .await;
+        }
+
+        let mut roles_json = serde_json::Map::new();
+        for r in &s.roles {
+            let role = db::roles::create_role(
+                pool,
+                &space.id,
+                &CreateRole {
+                    name: r.name.clone(),
+                    color: None,
+                    hoist: None,
+                    permissions: Some(r.permissions.clone()),
+                    mentionable: None,
+                },
+            )
+            .await?;
+            for member in &r.members {
+                db::members::add_role_to_member(
+                    pool,
+                    &space.id,
+                    &user_ids[member.as_str()],
+                    &role.id,
+                    state.db_is_postgres,
+                )
+                .await?;
+            }
+            roles_json.insert(r.name.clone(), json!(role.id));
+        }
+
+        let mut channels_json = serde_json::Map::new();
+        for c in &s.channels {
+            let channel = db::channels::create_channel(
+                pool,
+                &space.id,
+                &CreateChannel {
+                    name: c.name.clone(),
+                    channel_type: c.channel_type.clone(),
+                    topic: c.topic.clone(),
+                    parent_id: None,
+                    nsfw: None,
+                    bitrate: None,
+                    user_limit: None,
+                    rate_limit: None,
+                    allow_anonymous_read: None,
+                    position: None,
+                },
+            )
+            .await?;
+
+            let mut message_ids = Vec::new();
+            for m in &c.messages {
+                let msg = db::messages::create_message(
+                    pool,
+                    &channel.id,
+                    &user_ids[m.author.as_str()],
+                    Some(&space.id),
+                    &CreateMessage {
+                        content: m.content.clone(),
+                        tts: None,
+                        embeds: None,
+                        reply_to: None,
+                        thread_id: None,
+                        title: None,
+                        poll: None,
+                        sticker_ids: None,
+                    },
+                )
+                .await?;
+                message_ids.push(msg.id);
+            }
+
+            // Voice states live in memory, like a real connection's.
+            for vs in &c.voice_states {
+                crate::voice::state::join_voice_channel(
+                    state,
+                    &user_ids[vs.user.as_str()],
+                    Some(&space.id),
+                    &channel.id,
+                    &snowflake::generate(),
+                    vs.self_mute,
+                    vs.self_deaf,
+                    false,
+                    c.channel_type == "stage",
+                );
+            }
+
+            channels_json.insert(
+                c.name.clone(),
+                json!({
+                    "id": channel.id,
+                    "type": channel.channel_type,
+                    "message_ids": message_ids,
+                }),
+            );
+        }
+
+        spaces_json.insert(
+            s.key.clone().unwrap_or_else(|| s.name.clone()),
+            json!({
+                "id": space.id,
+                "name": space.name,
+                "slug": space.slug,
+                "owner_id": space.owner_id,
+                "roles": roles_json,
+                "channels": channels_json,
+            }),
+        );
+    }
+
+    Ok(json!({
+        "users": users_json,
+        "spaces": spaces_json,
+    }))
+}
+
 // ---------------------------------------------------------------------------
 // Find-or-create helpers
 // ---------------------------------------------------------------------------
 
+/// Replace all of [user_id]'s bearer tokens with a fresh long-lived one.
+async fn rotate_user_token(pool: &DbPool, user_id: &str) -> Result<String, AppError> {
+    sqlx::query(&crate::db::q("DELETE FROM user_tokens WHERE user_id = ?"))
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    let token = generate_token();
+    let token_hash = create_token_hash(&token);
+
+    sqlx::query(&crate::db::q(
+        "INSERT INTO user_tokens (token_hash, user_id, expires_at) VALUES (?, ?, '2099-12-31T23:59:59')",
+    ))
+    .bind(&token_hash)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(token)
+}
+
 async fn find_or_create_user(
-    pool: &AnyPool,
+    pool: &DbPool,
     username: &str,
     display_name: &str,
 ) -> Result<User, AppError> {
@@ -190,7 +563,7 @@ async fn find_or_create_user(
 /// deleted (e.g. by a test) but the bot *user* still exists, the bot user
 /// is reused instead of hitting a UNIQUE-constraint violation on `username`.
 async fn find_or_create_application(
-    pool: &AnyPool,
+    pool: &DbPool,
     owner_id: &str,
     name: &str,
     description: &str,
@@ -282,7 +655,7 @@ async fn find_or_create_application(
 }
 
 async fn find_or_create_test_plugin(
-    pool: &AnyPool,
+    pool: &DbPool,
     space_id: &str,
     creator_id: &str,
 ) -> Result<crate::models::plugin::Plugin, AppError> {
@@ -318,7 +691,7 @@ async fn find_or_create_test_plugin(
 }
 
 async fn find_or_create_space(
-    pool: &AnyPool,
+    pool: &DbPool,
     owner_id: &str,
     name: &str,
 ) -> Result<SpaceRow, AppError> {
@@ -340,6 +713,8 @@ async fn find_or_create_space(
                     description: None,
                     public: None,
                     allow_guest_access: None,
+                    preferred_locale: None,
+                    template: None,
                 },
             )
             .await
@@ -0,0 +1,607 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::db;
+use crate::error::AppError;
+use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::{
+    require_channel_membership, require_channel_permission, require_not_archived,
+    require_not_pending, require_not_timed_out, resolve_channel_permissions,
+};
+use crate::models::channel::ChannelRow;
+use crate::models::forum::MAX_APPLIED_TAGS;
+use crate::models::message::{CreateMessage, MessageRow};
+use crate::models::permission::Permissions;
+use crate::models::thread::{
+    auto_thread_name, CreateStandaloneThread, CreateThread, ThreadRow, UpdateThread,
+    AUTO_ARCHIVE_DURATIONS, DEFAULT_AUTO_ARCHIVE_AFTER,
+};
+use crate::state::AppState;
+
+/// Channel types that can hold threads.
+const THREADABLE_TYPES: &[&str] = &["text", "announcement", "forum"];
+
+/// Serialize a thread in the shape of a channel object (`type: "thread"`,
+/// `parent_id` = the channel it lives in) so clients can render it with the
+/// same code paths. `applied_tags` is only ever non-empty for forum posts.
+pub fn thread_row_to_json(
+    row: &ThreadRow,
+    member_count: i64,
+    message_count: i64,
+    applied_tags: &[String],
+) -> serde_json::Value {
+    serde_json::json!({
+        "id": row.id,
+        "type": "thread",
+        "space_id": row.space_id,
+        "parent_id": row.channel_id,
+        "name": row.name,
+        "owner_id": row.owner_id,
+        "auto_archive_after": row.auto_archive_after,
+        "archived": row.archived,
+        "archived_at": row.archived_at,
+        "locked": row.locked,
+        "member_count": member_count,
+        "message_count": message_count,
+        "last_activity_at": row.last_activity_at,
+        "applied_tags": applied_tags,
+        "created_at": row.created_at,
+    })
+}
+
+/// [thread_row_to_json] with counts and tags loaded from the DB.
+pub async fn thread_to_json(state: &AppState, row: &ThreadRow) -> serde_json::Value {
+    let member_count = db::threads::count_thread_members(&state.db, &row.id)
+        .await
+        .unwrap_or(0);
+    let message_count = db::messages::get_thread_reply_count(&state.db, &row.id)
+        .await
+        .unwrap_or(0);
+    let applied_tags = db::forum_tags::applied_tag_ids(&state.db, std::slice::from_ref(&row.id))
+        .await
+        .unwrap_or_default()
+        .remove(&row.id)
+        .unwrap_or_default();
+    thread_row_to_json(row, member_count, message_count, &applied_tags)
+}
+
+/// Serialize many threads with one query per count instead of one per row.
+pub async fn threads_to_json(
+    state: &AppState,
+    rows: &[ThreadRow],
+) -> Result<Vec<serde_json::Value>, AppError> {
+    let ids: Vec<String> = rows.iter().map(|r| r.id.clone()).collect();
+    let member_counts = db::threads::count_thread_members_by_thread(&state.db, &ids).await?;
+    let message_counts = db::messages::get_thread_reply_counts(&state.db, &ids).await?;
+    let mut applied_tags = db::forum_tags::applied_tag_ids(&state.db, &ids).await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            thread_row_to_json(
+                row,
+                member_counts.get(&row.id).copied().unwrap_or(0),
+                message_counts.get(&row.id).copied().unwrap_or(0),
+                &applied_tags.remove(&row.id).unwrap_or_default(),
+            )
+        })
+        .collect())
+}
+
+/// Broadcast a `thread.*` event to the thread's space.
+pub async fn broadcast_thread_event(
+    state: &AppState,
+    event_type: &str,
+    space_id: Option<String>,
+    data: serde_json::Value,
+) {
+    if let Some(ref gtx) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": event_type,
+            "data": data
+        });
+        let _ = gtx.send(GatewayBroadcast {
+            space_id,
+            target_user_ids: None,
+            event,
+            intent: "messages".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
+
+/// Start a thread on `msg` if it's a top-level message in a channel with
+/// auto-thread mode on. The author owns the thread, which archives after the
+/// channel's `auto_archive_after` window. Failures are logged rather than
+/// returned -- the message itself has already been sent.
+pub async fn start_auto_thread(state: &AppState, channel: &ChannelRow, msg: &MessageRow) {
+    if !channel.auto_thread || msg.thread_id.is_some() {
+        return;
+    }
+    let Some(ref space_id) = channel.space_id else {
+        return;
+    };
+    let author = match db::users::get_user(&state.db, &msg.author_id).await {
+        Ok(user) => user.display_name.unwrap_or(user.username),
+        Err(e) => {
+            tracing::warn!("auto-thread for message {} failed: {e:?}", msg.id);
+            return;
+        }
+    };
+    let date = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    let name = auto_thread_name(
+        channel.auto_thread_name.as_deref(),
+        &author,
+        &msg.content,
+        &date,
+    );
+    let thread = match db::threads::create_thread(
+        &state.db,
+        &msg.id,
+        &channel.id,
+        Some(space_id),
+        &msg.author_id,
+        &name,
+        channel
+            .auto_archive_after
+            .unwrap_or(DEFAULT_AUTO_ARCHIVE_AFTER),
+        state.db_is_postgres,
+    )
+    .await
+    {
+        Ok(thread) => thread,
+        Err(e) => {
+            tracing::warn!("auto-thread for message {} failed: {e:?}", msg.id);
+            return;
+        }
+    };
+    let json = thread_to_json(state, &thread).await;
+    broadcast_thread_event(state, "thread.create", Some(space_id.clone()), json).await;
+}
+
+fn validate_name(name: &str) -> Result<(), AppError> {
+    let len = name.trim().chars().count();
+    if len == 0 || len > 100 {
+        return Err(AppError::BadRequest(
+            "thread name must be 1-100 characters".into(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_auto_archive(value: Option<i64>) -> Result<i64, AppError> {
+    let minutes = value.unwrap_or(DEFAULT_AUTO_ARCHIVE_AFTER);
+    if !AUTO_ARCHIVE_DURATIONS.contains(&minutes) {
+        return Err(AppError::BadRequest(format!(
+            "auto_archive_after must be one of {AUTO_ARCHIVE_DURATIONS:?}"
+        )));
+    }
+    Ok(minutes)
+}
+
+/// Check the tags requested for a forum post and return them deduplicated.
+/// Moderated tags can only be added or removed with `manage_threads`;
+/// `current` is what the post carries now.
+async fn validate_applied_tags(
+    state: &AppState,
+    channel_id: &str,
+    space_id: &str,
+    auth: &AuthUser,
+    requested: &[String],
+    current: &[String],
+) -> Result<Vec<String>, AppError> {
+    let channel = db::channels::get_channel_row(&state.db, channel_id).await?;
+    if channel.channel_type != "forum" {
+        return Err(AppError::BadRequest(
+            "applied_tags only apply to forum posts".into(),
+        ));
+    }
+    let mut tags: Vec<String> = Vec::with_capacity(requested.len());
+    for id in requested {
+        if !tags.contains(id) {
+            tags.push(id.clone());
+        }
+    }
+    if tags.len() > MAX_APPLIED_TAGS {
+        return Err(AppError::BadRequest(format!(
+            "a post can have at most {MAX_APPLIED_TAGS} tags"
+        )));
+    }
+
+    let available = db::forum_tags::list_tags(&state.db, channel_id).await?;
+    let mut touches_moderated = false;
+    for id in &tags {
+        let tag = available
+            .iter()
+            .find(|t| &t.id == id)
+            .ok_or_else(|| AppError::BadRequest(format!("unknown tag {id}")))?;
+        touches_moderated |= tag.moderated && !current.contains(id);
+    }
+    touches_moderated |= available
+        .iter()
+        .any(|t| t.moderated && current.contains(&t.id) && !tags.contains(&t.id));
+    if touches_moderated && !can_manage_threads(state, channel_id, space_id, auth).await {
+        return Err(AppError::Forbidden(
+            "missing permission: manage_threads (moderated tag)".into(),
+        ));
+    }
+    Ok(tags)
+}
+
+/// Loads a thread and checks it belongs to `channel_id`.
+async fn get_channel_thread(
+    state: &AppState,
+    channel_id: &str,
+    thread_id: &str,
+) -> Result<ThreadRow, AppError> {
+    let thread = db::threads::get_thread(&state.db, thread_id).await?;
+    if thread.channel_id != channel_id {
+        return Err(AppError::NotFound("unknown_thread".to_string()));
+    }
+    Ok(thread)
+}
+
+/// Whether the user holds `manage_threads` in the channel.
+async fn can_manage_threads(
+    state: &AppState,
+    channel_id: &str,
+    space_id: &str,
+    auth: &AuthUser,
+) -> bool {
+    if auth.is_admin {
+        return true;
+    }
+    resolve_channel_permissions(&state.db, channel_id, space_id, &auth.user_id)
+        .await
+        .map(|perms| perms.has(Permissions::MANAGE_THREADS))
+        .unwrap_or(false)
+}
+
+/// POST /channels/{channel_id}/messages/{message_id}/threads
+/// Start a thread on an existing message.
+pub async fn create_thread_from_message(
+    state: State<AppState>,
+    Path((channel_id, message_id)): Path<(String, String)>,
+    auth: AuthUser,
+    Json(input): Json<CreateThread>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_id =
+        require_channel_permission(&state.db, &channel_id, &auth, "create_threads").await?;
+    if space_id.is_empty() {
+        return Err(AppError::BadRequest(
+            "threads are not supported in DMs".into(),
+        ));
+    }
+    require_not_timed_out(&state.db, &space_id, &auth).await?;
+    require_not_archived(&state.db, &space_id).await?;
+    require_not_pending(&state.db, &space_id, &auth).await?;
+    validate_name(&input.name)?;
+    let auto_archive_after = validate_auto_archive(input.auto_archive_after)?;
+
+    let channel = db::channels::get_channel_row(&state.db, &channel_id).await?;
+    if !THREADABLE_TYPES.contains(&channel.channel_type.as_str()) {
+        return Err(AppError::BadRequest(
+            "this channel type does not support threads".into(),
+        ));
+    }
+
+    let msg = db::messages::get_message_row(&state.db, &message_id).await?;
+    if msg.channel_id != channel_id {
+        return Err(AppError::NotFound("unknown_message".to_string()));
+    }
+    if msg.thread_id.is_some() {
+        return Err(AppError::BadRequest(
+            "cannot start a thread from a thread reply".into(),
+        ));
+    }
+
+    let thread = db::threads::create_thread(
+        &state.db,
+        &message_id,
+        &channel_id,
+        Some(&space_id),
+        &auth.user_id,
+        input.name.trim(),
+        auto_archive_after,
+        state.db_is_postgres,
+    )
+    .await?;
+    let json = thread_to_json(&state, &thread).await;
+    broadcast_thread_event(&state, "thread.create", Some(space_id), json.clone()).await;
+
+    Ok(Json(serde_json::json!({ "data": json })))
+}
+
+/// POST /channels/{channel_id}/threads
+/// Start a thread that isn't attached to an existing message. The server
+/// posts a titled starter message that anchors it.
+pub async fn create_standalone_thread(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<CreateStandaloneThread>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_id =
+        require_channel_permission(&state.db, &channel_id, &auth, "create_threads").await?;
+    if space_id.is_empty() {
+        return Err(AppError::BadRequest(
+            "threads are not supported in DMs".into(),
+        ));
+    }
+    require_channel_permission(&state.db, &channel_id, &auth, "send_messages").await?;
+    require_not_timed_out(&state.db, &space_id, &auth).await?;
+    require_not_archived(&state.db, &space_id).await?;
+    require_not_pending(&state.db, &space_id, &auth).await?;
+    validate_name(&input.name)?;
+    let auto_archive_after = validate_auto_archive(input.auto_archive_after)?;
+    let content = input.content.unwrap_or_default();
+    if content.len() > 4000 {
+        return Err(AppError::BadRequest(
+            "message content must be at most 4000 characters".into(),
+        ));
+    }
+
+    let channel = db::channels::get_channel_row(&state.db, &channel_id).await?;
+    if !THREADABLE_TYPES.contains(&channel.channel_type.as_str()) {
+        return Err(AppError::BadRequest(
+            "this channel type does not support threads".into(),
+        ));
+    }
+    let applied_tags = match input.applied_tags {
+        Some(ref tags) => {
+            validate_applied_tags(&state, &channel_id, &space_id, &auth, tags, &[]).await?
+        }
+        None => Vec::new(),
+    };
+
+    let starter = db::messages::create_message(
+        &state.db,
+        &channel_id,
+        &auth.user_id,
+        Some(&space_id),
+        &CreateMessage {
+            content,
+            tts: None,
+            embeds: None,
+            reply_to: None,
+            thread_id: None,
+            title: Some(input.name.trim().to_string()),
+            poll: None,
+            sticker_ids: None,
+        },
+    )
+    .await?;
+
+    let thread = db::threads::create_thread(
+        &state.db,
+        &starter.id,
+        &channel_id,
+        Some(&space_id),
+        &auth.user_id,
+        input.name.trim(),
+        auto_archive_after,
+        state.db_is_postgres,
+    )
+    .await?;
+    if !applied_tags.is_empty() {
+        db::forum_tags::set_thread_tags(&state.db, &thread.id, &applied_tags).await?;
+    }
+
+    let starter_json = super::messages::message_row_to_json(&starter);
+    let role_mention_user_ids =
+        crate::gateway::events::resolve_role_mentions(&state.db, &starter_json).await;
+    if let Some(ref gtx) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "message.create",
+            "data": starter_json
+        });
+        let _ = gtx.send(GatewayBroadcast {
+            space_id: Some(space_id.clone()),
+            target_user_ids: None,
+            event,
+            intent: "messages".to_string(),
+            role_mention_user_ids,
+        });
+    }
+    let json = thread_to_json(&state, &thread).await;
+    broadcast_thread_event(
+        &state,
+        "thread.create",
+        Some(space_id.clone()),
+        json.clone(),
+    )
+    .await;
+    // Forum views get the post and its opening message in one event.
+    if channel.channel_type == "forum" {
+        let mut post = json.clone();
+        post["message"] = starter_json;
+        broadcast_thread_event(&state, "forum_post.create", Some(space_id), post).await;
+    }
+
+    Ok(Json(serde_json::json!({ "data": json })))
+}
+
+/// GET /channels/{channel_id}/threads/active
+pub async fn list_active_threads(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    list_threads(&state, &channel_id, &auth, false).await
+}
+
+/// GET /channels/{channel_id}/threads/archived
+pub async fn list_archived_threads(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    list_threads(&state, &channel_id, &auth, true).await
+}
+
+async fn list_threads(
+    state: &AppState,
+    channel_id: &str,
+    auth: &AuthUser,
+    archived: bool,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_membership(&state.db, channel_id, &auth.user_id).await?;
+    let rows = db::threads::list_threads(&state.db, channel_id, archived).await?;
+    let data = threads_to_json(state, &rows).await?;
+    Ok(Json(serde_json::json!({ "data": data })))
+}
+
+/// GET /channels/{channel_id}/threads/{thread_id}
+pub async fn get_thread(
+    state: State<AppState>,
+    Path((channel_id, thread_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    let thread = get_channel_thread(&state, &channel_id, &thread_id).await?;
+    Ok(Json(
+        serde_json::json!({ "data": thread_to_json(&state, &thread).await }),
+    ))
+}
+
+/// PATCH /channels/{channel_id}/threads/{thread_id}
+/// The thread owner may rename, archive/unarchive, and change the archive
+/// window; `manage_threads` is needed for everything else, including locking
+/// and reopening a locked thread.
+pub async fn update_thread(
+    state: State<AppState>,
+    Path((channel_id, thread_id)): Path<(String, String)>,
+    auth: AuthUser,
+    Json(input): Json<UpdateThread>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_id = require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    let thread = get_channel_thread(&state, &channel_id, &thread_id).await?;
+
+    if let Some(ref name) = input.name {
+        validate_name(name)?;
+    }
+    if input.auto_archive_after.is_some() {
+        validate_auto_archive(input.auto_archive_after)?;
+    }
+
+    let is_owner = thread.owner_id == auth.user_id;
+    let needs_manage =
+        !is_owner || input.locked.is_some() || (thread.locked && input.archived == Some(false));
+    if needs_manage && !can_manage_threads(&state, &channel_id, &space_id, &auth).await {
+        return Err(AppError::Forbidden(
+            "missing permission: manage_threads".into(),
+        ));
+    }
+
+    if let Some(ref tags) = input.applied_tags {
+        let current = db::forum_tags::applied_tag_ids(&state.db, std::slice::from_ref(&thread_id))
+            .await?
+            .remove(&thread_id)
+            .unwrap_or_default();
+        let tags =
+            validate_applied_tags(&state, &channel_id, &space_id, &auth, tags, &current).await?;
+        db::forum_tags::set_thread_tags(&state.db, &thread_id, &tags).await?;
+    }
+
+    let input = UpdateThread {
+        name: input.name.map(|n| n.trim().to_string()),
+        ..input
+    };
+    let updated =
+        db::threads::update_thread(&state.db, &thread_id, &input, state.db_is_postgres).await?;
+    let json = thread_to_json(&state, &updated).await;
+    broadcast_thread_event(
+        &state,
+        "thread.update",
+        updated.space_id.clone(),
+        json.clone(),
+    )
+    .await;
+
+    Ok(Json(serde_json::json!({ "data": json })))
+}
+
+/// DELETE /channels/{channel_id}/threads/{thread_id}
+/// Removes the thread and its replies; the starter message stays.
+pub async fn delete_thread(
+    state: State<AppState>,
+    Path((channel_id, thread_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_id =
+        require_channel_permission(&state.db, &channel_id, &auth, "manage_threads").await?;
+    let thread = get_channel_thread(&state, &channel_id, &thread_id).await?;
+    db::threads::delete_thread(&state.db, &thread_id).await?;
+
+    broadcast_thread_event(
+        &state,
+        "thread.delete",
+        Some(space_id),
+        serde_json::json!({
+            "id": thread.id,
+            "parent_id": thread.channel_id,
+            "space_id": thread.space_id,
+        }),
+    )
+    .await;
+
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// GET /channels/{channel_id}/threads/{thread_id}/members
+pub async fn list_thread_members(
+    state: State<AppState>,
+    Path((channel_id, thread_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    get_channel_thread(&state, &channel_id, &thread_id).await?;
+    let members = db::threads::list_thread_members(&state.db, &thread_id).await?;
+    let data: Vec<serde_json::Value> = members
+        .into_iter()
+        .map(|(user_id, joined_at)| {
+            serde_json::json!({
+                "thread_id": thread_id,
+                "user_id": user_id,
+                "joined_at": joined_at,
+            })
+        })
+        .collect();
+    Ok(Json(serde_json::json!({ "data": data })))
+}
+
+/// PUT /channels/{channel_id}/threads/{thread_id}/members/@me
+pub async fn join_thread(
+    state: State<AppState>,
+    Path((channel_id, thread_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    let thread = get_channel_thread(&state, &channel_id, &thread_id).await?;
+    if thread.archived {
+        return Err(AppError::BadRequest("thread is archived".into()));
+    }
+    db::threads::add_thread_member(&state.db, &thread_id, &auth.user_id, state.db_is_postgres)
+        .await?;
+    let json = thread_to_json(&state, &thread).await;
+    broadcast_thread_event(&state, "thread.update", thread.space_id.clone(), json).await;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// DELETE /channels/{channel_id}/threads/{thread_id}/members/@me
+pub async fn leave_thread(
+    state: State<AppState>,
+    Path((channel_id, thread_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    let thread = get_channel_thread(&state, &channel_id, &thread_id).await?;
+    if !db::threads::remove_thread_member(&state.db, &thread_id, &auth.user_id).await? {
+        return Err(AppError::NotFound("unknown_thread_member".to_string()));
+    }
+    let json = thread_to_json(&state, &thread).await;
+    broadcast_thread_event(&state, "thread.update", thread.space_id.clone(), json).await;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
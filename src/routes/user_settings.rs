@@ -0,0 +1,228 @@
+use std::collections::HashSet;
+
+use axum::extract::State;
+use axum::Json;
+
+use crate::db;
+use crate::error::AppError;
+use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::auth::AuthUser;
+use crate::models::user_settings::{SpaceFolder, UpdateSpaceLayout, UpdateUserSettings};
+use crate::state::AppState;
+
+/// Largest settings object a user may store, serialized.
+pub const MAX_SETTINGS_BYTES: usize = 64 * 1024;
+
+/// Most sidebar entries (folders plus ungrouped spaces) a layout may have.
+pub const MAX_LAYOUT_ENTRIES: usize = 200;
+
+/// Times an unversioned PATCH re-reads and retries after losing a race.
+const MERGE_ATTEMPTS: usize = 3;
+
+fn require_account(auth: &AuthUser) -> Result<(), AppError> {
+    if auth.is_guest {
+        return Err(AppError::Forbidden(
+            "guest accounts cannot store settings".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// GET /users/@me/settings
+pub async fn get_settings(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_account(&auth)?;
+    let settings = db::user_settings::get_user_settings(&state.db, &auth.user_id).await?;
+    Ok(Json(serde_json::json!({ "data": settings })))
+}
+
+/// PATCH /users/@me/settings
+/// Merges `settings` into the stored object. With `version`, the write only
+/// applies if nothing has been saved since that version (409
+/// `settings_version_conflict` otherwise); without it, the merge is applied
+/// to whatever is current.
+pub async fn update_settings(
+    state: State<AppState>,
+    auth: AuthUser,
+    Json(input): Json<UpdateUserSettings>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_account(&auth)?;
+
+    let mut attempts = 0;
+    let updated = loop {
+        attempts += 1;
+        let mut current = db::user_settings::get_user_settings(&state.db, &auth.user_id).await?;
+        if input.version.is_some_and(|v| v != current.version) {
+            return Err(AppError::ConflictCode(
+                "settings_version_conflict",
+                format!(
+                    "settings changed since version {}; current version is {}",
+                    input.version.unwrap_or_default(),
+                    current.version
+                ),
+            ));
+        }
+
+        for (key, value) in &input.settings {
+            if value.is_null() {
+                current.settings.remove(key);
+            } else {
+                current.settings.insert(key.clone(), value.clone());
+            }
+        }
+        if serde_json::Value::Object(current.settings.clone())
+            .to_string()
+            .len()
+            > MAX_SETTINGS_BYTES
+        {
+            return Err(AppError::PayloadTooLarge(format!(
+                "settings must be at most {MAX_SETTINGS_BYTES} bytes"
+            )));
+        }
+
+        if db::user_settings::replace_user_settings(
+            &state.db,
+            &auth.user_id,
+            current.version,
+            &current.settings,
+            state.db_is_postgres,
+        )
+        .await?
+        {
+            break db::user_settings::get_user_settings(&state.db, &auth.user_id).await?;
+        }
+        // Another device wrote between our read and write.
+        if input.version.is_some() || attempts >= MERGE_ATTEMPTS {
+            return Err(AppError::ConflictCode(
+                "settings_version_conflict",
+                "settings were changed by another device; retry".into(),
+            ));
+        }
+    };
+
+    // Every session of this user (including other devices) picks up the change.
+    if let Some(ref gtx) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "user_settings.update",
+            "data": updated
+        });
+        let _ = gtx.send(GatewayBroadcast {
+            space_id: None,
+            target_user_ids: Some(vec![auth.user_id.clone()]),
+            event,
+            intent: "user_settings".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+
+    Ok(Json(serde_json::json!({ "data": updated })))
+}
+
+/// Drop spaces the user has since left, and entries left empty by that.
+pub fn prune_space_layout(folders: &mut Vec<SpaceFolder>, space_ids: &HashSet<String>) {
+    for folder in folders.iter_mut() {
+        folder.space_ids.retain(|sid| space_ids.contains(sid));
+    }
+    folders.retain(|f| !f.space_ids.is_empty());
+}
+
+/// GET /users/@me/settings/spaces
+pub async fn get_space_layout(
+    state: State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_account(&auth)?;
+    let mut folders = db::user_settings::get_space_layout(&state.db, &auth.user_id).await?;
+    let space_ids: HashSet<String> = db::spaces::list_space_ids_for_user(&state.db, &auth.user_id)
+        .await?
+        .into_iter()
+        .collect();
+    prune_space_layout(&mut folders, &space_ids);
+    Ok(Json(serde_json::json!({ "data": { "folders": folders } })))
+}
+
+/// PUT /users/@me/settings/spaces
+/// Replaces the sidebar layout. Each space may appear once, and only spaces
+/// the user is a member of; entries without an `id` are single ungrouped
+/// spaces, and folders sent without one get a new ID.
+pub async fn update_space_layout(
+    state: State<AppState>,
+    auth: AuthUser,
+    Json(input): Json<UpdateSpaceLayout>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_account(&auth)?;
+    let mut folders = input.folders;
+    if folders.len() > MAX_LAYOUT_ENTRIES {
+        return Err(AppError::BadRequest(format!(
+            "a layout can have at most {MAX_LAYOUT_ENTRIES} entries"
+        )));
+    }
+
+    let member_of: HashSet<String> = db::spaces::list_space_ids_for_user(&state.db, &auth.user_id)
+        .await?
+        .into_iter()
+        .collect();
+    let mut seen: HashSet<&str> = HashSet::new();
+    for folder in &folders {
+        if folder.space_ids.is_empty() {
+            return Err(AppError::BadRequest(
+                "every entry needs at least one space".into(),
+            ));
+        }
+        for sid in &folder.space_ids {
+            if !member_of.contains(sid) {
+                return Err(AppError::BadRequest(format!(
+                    "you are not a member of space {sid}"
+                )));
+            }
+            if !seen.insert(sid.as_str()) {
+                return Err(AppError::BadRequest(format!(
+                    "space {sid} appears more than once"
+                )));
+            }
+        }
+        if folder.name.as_ref().is_some_and(|n| n.len() > 100) {
+            return Err(AppError::BadRequest(
+                "folder names must be at most 100 characters".into(),
+            ));
+        }
+        if folder.color.is_some_and(|c| !(0..=0xFF_FF_FF).contains(&c)) {
+            return Err(AppError::BadRequest(
+                "folder color must be an RGB integer".into(),
+            ));
+        }
+    }
+
+    for folder in &mut folders {
+        let is_folder =
+            folder.name.is_some() || folder.color.is_some() || folder.space_ids.len() > 1;
+        if folder.id.is_none() && is_folder {
+            folder.id = Some(crate::snowflake::generate());
+        }
+    }
+
+    db::user_settings::set_space_layout(&state.db, &auth.user_id, &folders, state.db_is_postgres)
+        .await?;
+
+    let data = serde_json::json!({ "folders": folders });
+    // Other sessions re-render the sidebar from this.
+    if let Some(ref gtx) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "user_settings.spaces_update",
+            "data": data
+        });
+        let _ = gtx.send(GatewayBroadcast {
+            space_id: None,
+            target_user_ids: Some(vec![auth.user_id.clone()]),
+            event,
+            intent: "user_settings".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+
+    Ok(Json(serde_json::json!({ "data": data })))
+}
@@ -74,19 +74,26 @@ pub async fn update_current_user(
             ));
         }
     }
+    if let Some(ref pronouns) = input.pronouns {
+        crate::profile::validate_pronouns(pronouns)?;
+    }
+    if let Some(ref timezone) = input.timezone {
+        crate::profile::validate_timezone(timezone)?;
+    }
 
     let max_avatar_size = state.settings.load().max_avatar_size as usize;
 
     // Process avatar data URI
     if let Some(ref avatar) = input.avatar {
         if avatar.starts_with("data:") {
+            crate::blocklist::check_data_uri_upload(&state.db, &auth.user_id, avatar).await?;
             // Fetch old avatar to clean up
             let old_user = db::users::get_user(&state.db, &auth.user_id).await?;
             if let Some(ref old_avatar) = old_user.avatar {
-                let _ = storage::delete_file(&state.storage_path, old_avatar).await;
+                let _ = storage::delete_file(state.storage.as_ref(), old_avatar).await;
             }
             let (url, _, _, _) = storage::save_avatar_image(
-                &state.storage_path,
+                state.storage.as_ref(),
                 "avatars",
                 &auth.user_id,
                 avatar,
@@ -98,9 +105,9 @@ pub async fn update_current_user(
             // Empty string means remove avatar
             let old_user = db::users::get_user(&state.db, &auth.user_id).await?;
             if let Some(ref old_avatar) = old_user.avatar {
-                let _ = storage::delete_file(&state.storage_path, old_avatar).await;
+                let _ = storage::delete_file(state.storage.as_ref(), old_avatar).await;
             }
-            storage::delete_avatar(&state.storage_path, "avatars", &auth.user_id).await?;
+            storage::delete_avatar(state.storage.as_ref(), "avatars", &auth.user_id).await?;
             // Keep as Some("") — DB layer will treat empty string as NULL
         }
     }
@@ -108,12 +115,13 @@ pub async fn update_current_user(
     // Process banner data URI
     if let Some(ref banner) = input.banner {
         if banner.starts_with("data:") {
+            crate::blocklist::check_data_uri_upload(&state.db, &auth.user_id, banner).await?;
             let old_user = db::users::get_user(&state.db, &auth.user_id).await?;
             if let Some(ref old_banner) = old_user.banner {
-                let _ = storage::delete_file(&state.storage_path, old_banner).await;
+                let _ = storage::delete_file(state.storage.as_ref(), old_banner).await;
             }
             let (url, _, _, _) = storage::save_avatar_image(
-                &state.storage_path,
+                state.storage.as_ref(),
                 "banners",
                 &auth.user_id,
                 banner,
@@ -124,9 +132,9 @@ pub async fn update_current_user(
         } else if banner.is_empty() {
             let old_user = db::users::get_user(&state.db, &auth.user_id).await?;
             if let Some(ref old_banner) = old_user.banner {
-                let _ = storage::delete_file(&state.storage_path, old_banner).await;
+                let _ = storage::delete_file(state.storage.as_ref(), old_banner).await;
             }
-            storage::delete_avatar(&state.storage_path, "banners", &auth.user_id).await?;
+            storage::delete_avatar(state.storage.as_ref(), "banners", &auth.user_id).await?;
             // Keep as Some("") — DB layer will treat empty string as NULL
         }
     }
@@ -247,6 +255,17 @@ pub async fn create_dm_channel(
                 "you cannot send a DM to this user".into(),
             ));
         }
+        // Strangers must be friends first: a DM needs an accepted friend
+        // request or a space in common.
+        if rid != &auth.user_id
+            && !db::relationships::are_friends(&state.db, &auth.user_id, rid).await?
+            && !db::members::share_space(&state.db, &auth.user_id, rid).await?
+        {
+            return Err(AppError::ForbiddenCode(
+                "dm_requires_relationship",
+                "you can only message friends or members of a space you share".into(),
+            ));
+        }
     }
 
     let channel = db::dm_participants::create_dm_channel(
@@ -272,6 +291,7 @@ pub async fn create_dm_channel(
             target_user_ids: Some(participant_ids),
             event,
             intent: "channels".to_string(),
+            role_mention_user_ids: None,
         });
     }
 
@@ -349,7 +369,7 @@ pub async fn export_current_user_data(
     // Relationships
     let relationships = sqlx::query(
         &crate::db::q(
-            "SELECT user_id, target_id, type, created_at FROM relationships WHERE user_id = ? OR target_id = ?"
+            "SELECT user_id, target_user_id, type, created_at FROM relationships WHERE user_id = ? OR target_user_id = ?"
         ),
     )
     .bind(&auth.user_id)
@@ -361,13 +381,15 @@ pub async fn export_current_user_data(
         .map(|r| {
             serde_json::json!({
                 "user_id": r.get::<String, _>("user_id"),
-                "target_id": r.get::<String, _>("target_id"),
-                "type": r.get::<i32, _>("type"),
+                "target_id": r.get::<String, _>("target_user_id"),
+                "type": r.get::<i64, _>("type"),
                 "created_at": r.get::<String, _>("created_at"),
             })
         })
         .collect();
 
+    let settings = db::user_settings::get_user_settings(&state.db, &auth.user_id).await?;
+
     Ok(Json(serde_json::json!({
         "data": {
             "export_date": chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S+00:00").to_string(),
@@ -376,6 +398,7 @@ pub async fn export_current_user_data(
             "messages": messages_json,
             "message_count": messages_json.len(),
             "relationships": relationships_json,
+            "settings": settings.settings,
         }
     })))
 }
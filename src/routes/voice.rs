@@ -8,9 +8,13 @@ use crate::middleware::auth::AuthUser;
 use crate::middleware::permissions::{
     require_channel_permission, require_dm_access, require_membership, require_not_timed_out,
 };
-use crate::models::voice::VoiceState;
+use crate::models::channel::is_voice_type;
+use crate::models::voice::{
+    UpdateOwnStageState, UpdateStageState, VoiceConnectionStats, VoiceState, VoiceStatsReport,
+};
 use crate::state::AppState;
 use crate::voice;
+use crate::voice::livekit::LiveKitClient;
 
 /// Whether a channel type is a DM or group DM (no parent space).
 fn is_dm_channel(channel_type: &str) -> bool {
@@ -42,6 +46,206 @@ pub async fn get_voice_status(
     Ok(Json(serde_json::json!({ "data": states })))
 }
 
+/// POST /channels/{id}/voice/stream — start screen sharing in the call
+/// you're in. Needs `stream`, and stage audiences can't. Returns a publish-
+/// only LiveKit token for screen share sources, under its own identity, with
+/// the space's `max_height`/`max_fps` caps; the call gets `voice.stream_start`
+/// and the caller's `self_stream` turns on.
+pub async fn start_stream(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let current = voice::state::get_user_voice_state(&state, &auth.user_id)
+        .filter(|vs| vs.channel_id.as_deref() == Some(channel_id.as_str()))
+        .ok_or_else(|| AppError::BadRequest("not_in_voice_channel".to_string()))?;
+    require_channel_permission(&state.db, &channel_id, &auth, "stream").await?;
+    if current.suppress {
+        return Err(AppError::Forbidden(
+            "stage audience members cannot stream".into(),
+        ));
+    }
+    let lk = state
+        .livekit_client
+        .as_ref()
+        .ok_or_else(|| AppError::BadRequest("voice_not_configured".to_string()))?;
+
+    let (max_height, max_fps) = match current.space_id {
+        Some(ref sid) => {
+            let space = db::spaces::get_space_row(&state.db, sid).await?;
+            (space.stream_max_height, space.stream_max_fps)
+        }
+        None => (720, 30),
+    };
+    let user = db::users::get_user(&state.db, &auth.user_id).await?;
+    let display_name = user.display_name.as_deref().unwrap_or(&user.username);
+    let token = lk.generate_stream_token(
+        display_name,
+        &channel_id,
+        &auth.user_id,
+        max_height,
+        max_fps,
+    )?;
+
+    let voice_state = voice::state::modify_in_channel(&state, &auth.user_id, &channel_id, |vs| {
+        vs.self_stream = true
+    })
+    .ok_or_else(|| AppError::BadRequest("not_in_voice_channel".to_string()))?;
+    if !current.self_stream {
+        broadcast_voice_state_update(
+            &state,
+            &channel_id,
+            voice_state.space_id.as_deref(),
+            &voice_state,
+        )
+        .await;
+        voice::stream::broadcast(
+            &state,
+            &channel_id,
+            voice_state.space_id.as_deref(),
+            "voice.stream_start",
+            serde_json::json!({
+                "user_id": auth.user_id,
+                "channel_id": channel_id,
+                "space_id": voice_state.space_id,
+                "max_height": max_height,
+                "max_fps": max_fps,
+            }),
+        )
+        .await;
+    }
+
+    Ok(Json(serde_json::json!({
+        "data": {
+            "voice_state": voice_state,
+            "livekit_url": lk.external_url(),
+            "token": token,
+            "identity": LiveKitClient::stream_identity(&auth.user_id),
+            "max_height": max_height,
+            "max_fps": max_fps
+        }
+    })))
+}
+
+/// DELETE /channels/{id}/voice/stream — stop screen sharing. The stream
+/// identity is disconnected and the call gets `voice.stream_stop`.
+pub async fn stop_stream(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let current = voice::state::get_user_voice_state(&state, &auth.user_id)
+        .filter(|vs| vs.channel_id.as_deref() == Some(channel_id.as_str()))
+        .ok_or_else(|| AppError::BadRequest("not_in_voice_channel".to_string()))?;
+    if !current.self_stream {
+        return Ok(Json(serde_json::json!({ "data": current })));
+    }
+    let voice_state = voice::state::modify_in_channel(&state, &auth.user_id, &channel_id, |vs| {
+        vs.self_stream = false
+    })
+    .ok_or_else(|| AppError::BadRequest("not_in_voice_channel".to_string()))?;
+    voice::stream::end(&state, &current).await;
+    broadcast_voice_state_update(
+        &state,
+        &channel_id,
+        voice_state.space_id.as_deref(),
+        &voice_state,
+    )
+    .await;
+    Ok(Json(serde_json::json!({ "data": voice_state })))
+}
+
+/// Stats reports older than this are treated as missing: the client has
+/// stopped sending them.
+const VOICE_STATS_STALE_SECS: i64 = 60;
+
+/// POST /channels/{id}/voice/stats — a participant reports what their WebRTC
+/// stack measures (RTT, jitter, loss, bitrate), every few seconds while
+/// connected. LiveKit doesn't expose these per participant, so clients are
+/// the source. 400 `not_in_voice_channel` unless the caller is in this call.
+pub async fn report_voice_stats(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+    Json(stats): Json<VoiceConnectionStats>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let Some(voice_state) = voice::state::get_user_voice_state(&state, &auth.user_id)
+        .filter(|vs| vs.channel_id.as_deref() == Some(channel_id.as_str()))
+    else {
+        return Err(AppError::BadRequest("not_in_voice_channel".to_string()));
+    };
+    let finite_non_negative = |v: Option<f64>| v.is_none_or(|v| v.is_finite() && v >= 0.0);
+    if !finite_non_negative(stats.rtt_ms)
+        || !finite_non_negative(stats.jitter_ms)
+        || !stats
+            .packet_loss
+            .is_none_or(|l| l.is_finite() && (0.0..=1.0).contains(&l))
+        || stats.bitrate.is_some_and(|b| b < 0)
+    {
+        return Err(AppError::BadRequest(
+            "stats must be non-negative, with packet_loss between 0 and 1".to_string(),
+        ));
+    }
+    state.voice_stats.insert(
+        auth.user_id.clone(),
+        VoiceStatsReport {
+            session_id: voice_state.session_id,
+            stats,
+            reported_at: chrono::Utc::now(),
+        },
+    );
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// GET /channels/{id}/voice/stats — connection stats for everyone in the
+/// call, for a connection-quality indicator. Participants with no recent
+/// report from their current session get nulls and quality `unknown`.
+pub async fn get_voice_stats(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_permission(&state.db, &channel_id, &auth, "view_channel").await?;
+    let cutoff = chrono::Utc::now() - chrono::Duration::seconds(VOICE_STATS_STALE_SECS);
+    let data: Vec<serde_json::Value> = voice::state::get_channel_voice_states(&state, &channel_id)
+        .into_iter()
+        .map(|vs| {
+            let report = state
+                .voice_stats
+                .get(&vs.user_id)
+                .filter(|r| r.session_id == vs.session_id && r.reported_at >= cutoff)
+                .map(|r| r.clone());
+            let stats = report.as_ref().map(|r| &r.stats);
+            serde_json::json!({
+                "user_id": vs.user_id,
+                "rtt_ms": stats.and_then(|s| s.rtt_ms),
+                "jitter_ms": stats.and_then(|s| s.jitter_ms),
+                "packet_loss": stats.and_then(|s| s.packet_loss),
+                "bitrate": stats.and_then(|s| s.bitrate),
+                "quality": stats.map_or("unknown", |s| s.quality()),
+                "updated_at": report.map(|r| r.reported_at.to_rfc3339()),
+            })
+        })
+        .collect();
+    Ok(Json(serde_json::json!({ "data": data })))
+}
+
+/// POST /channels/{id}/voice/refresh-token — a new LiveKit token for the
+/// caller's current session in this call, for when the one from joining is
+/// about to run out. 400 `not_in_voice_channel` unless they're in it.
+pub async fn refresh_voice_token(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let voice_state = voice::state::get_user_voice_state(&state, &auth.user_id)
+        .filter(|vs| vs.channel_id.as_deref() == Some(channel_id.as_str()))
+        .ok_or_else(|| AppError::BadRequest("not_in_voice_channel".to_string()))?;
+    require_channel_permission(&state.db, &channel_id, &auth, "connect").await?;
+    let data = voice::token::refresh(&state, &voice_state).await?;
+    Ok(Json(serde_json::json!({ "data": data })))
+}
+
 #[derive(serde::Deserialize)]
 pub struct JoinVoiceRequest {
     pub self_mute: Option<bool>,
@@ -61,12 +265,13 @@ pub async fn join_voice(
     // Look up channel to confirm it exists and get space_id
     let channel = db::channels::get_channel_row(&state.db, &channel_id).await?;
 
-    // DM/group DM calls have no parent space and aren't a "voice" channel type;
-    // space channels must be voice and gate on the member's timeout status.
+    // DM/group DM calls have no parent space and aren't a voice channel type;
+    // space channels must be voice or stage and gate on the member's timeout
+    // status.
     let space_id: Option<String> = if is_dm_channel(&channel.channel_type) {
         None
     } else {
-        if channel.channel_type != "voice" {
+        if !is_voice_type(&channel.channel_type) {
             return Err(AppError::BadRequest("channel_not_voice".to_string()));
         }
         let sid = channel
@@ -81,7 +286,7 @@ pub async fn join_voice(
     let self_mute = input.self_mute.unwrap_or(false);
     let self_deaf = input.self_deaf.unwrap_or(false);
 
-    let (voice_state, previous_channel) = voice::state::join_voice_channel(
+    let (mut voice_state, previous) = voice::state::join_voice_channel(
         &state,
         &auth.user_id,
         space_id.as_deref(),
@@ -90,8 +295,16 @@ pub async fn join_voice(
         self_mute,
         self_deaf,
         false,
-        false,
+        channel.channel_type == "stage",
     );
+    voice_state = voice::state::apply_server_flags(&state, voice_state).await;
+    if let Some(ref prev) = previous {
+        voice::stream::end(&state, prev).await;
+    }
+    let previous_channel = previous.and_then(|vs| vs.channel_id);
+    if let Some(ref prev_ch) = previous_channel {
+        voice::chat::clear_if_empty(&state, prev_ch).await;
+    }
 
     let lk = state
         .livekit_client
@@ -115,13 +328,15 @@ pub async fn join_voice(
     }
     let user = db::users::get_user(&state.db, &auth.user_id).await?;
     let display_name = user.display_name.as_deref().unwrap_or(&user.username);
-    let token = lk.generate_token(&auth.user_id, display_name, &channel_id)?;
+    let (token, expires_at) =
+        voice::token::issue(&state, lk, display_name, &channel_id, &voice_state)?;
     Ok(Json(serde_json::json!({
         "data": {
             "voice_state": voice_state,
             "backend": "livekit",
             "livekit_url": lk.external_url(),
-            "token": token
+            "token": token,
+            "expires_at": expires_at.to_rfc3339()
         }
     })))
 }
@@ -135,6 +350,7 @@ pub async fn leave_voice(
     let old_state = voice::state::leave_voice_channel(&state, &auth.user_id);
 
     if let Some(ref vs) = old_state {
+        voice::stream::end(&state, vs).await;
         if let Some(ref left_channel) = vs.channel_id {
             let left_state = VoiceState {
                 user_id: auth.user_id.clone(),
@@ -148,6 +364,8 @@ pub async fn leave_voice(
                 self_stream: false,
                 self_video: false,
                 suppress: false,
+                request_to_speak_timestamp: None,
+                invited_to_speak: false,
             };
             // Notify the space, or the DM participants when there's no space.
             broadcast_voice_state_update(&state, left_channel, vs.space_id.as_deref(), &left_state)
@@ -161,6 +379,8 @@ pub async fn leave_voice(
                 }
             }
 
+            voice::chat::clear_if_empty(&state, left_channel).await;
+
             // For DM calls, if no participants remain in voice the call is over;
             // emit a `call.end` so ringing/active-call UI can clear.
             if vs.space_id.is_none()
@@ -183,6 +403,127 @@ pub async fn leave_voice(
     Ok(Json(serde_json::json!({ "data": { "ok": true } })))
 }
 
+/// The stage channel [channel_id] and its space, or 400 for anything else.
+async fn require_stage(state: &AppState, channel_id: &str) -> Result<String, AppError> {
+    let channel = db::channels::get_channel_row(&state.db, channel_id).await?;
+    if channel.channel_type != "stage" {
+        return Err(AppError::BadRequest("channel_not_stage".to_string()));
+    }
+    channel
+        .space_id
+        .ok_or_else(|| AppError::BadRequest("channel_has_no_space".to_string()))
+}
+
+/// Broadcast a changed voice state, and bring the LiveKit permissions of a
+/// connected participant in line when [rights_changed].
+pub(super) async fn apply_voice_change(
+    state: &AppState,
+    channel_id: &str,
+    space_id: &str,
+    rights_changed: bool,
+    voice_state: &VoiceState,
+) {
+    let mut voice_state = voice_state.clone();
+    // Moved into a stage's audience: the stream goes too.
+    if voice_state.suppress && voice_state.self_stream {
+        if let Some(updated) =
+            voice::state::modify_in_channel(state, &voice_state.user_id, channel_id, |vs| {
+                vs.self_stream = false
+            })
+        {
+            voice::stream::end(state, &voice_state).await;
+            voice_state = updated;
+        }
+    }
+    if rights_changed && !state.test_mode {
+        if let Some(ref lk) = state.livekit_client {
+            lk.sync_permissions(channel_id, &voice_state).await;
+        }
+    }
+    broadcast_voice_state_update(state, channel_id, Some(space_id), &voice_state).await;
+}
+
+/// PATCH /channels/{channel_id}/voice-states/@me
+/// Raise or lower a hand, accept an invite to speak, or step back into the
+/// audience.
+pub async fn update_own_stage_state(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<UpdateOwnStageState>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_id = require_stage(&state, &channel_id).await?;
+    require_channel_permission(&state.db, &channel_id, &auth, "connect").await?;
+    let current = voice::state::get_user_voice_state(&state, &auth.user_id)
+        .filter(|vs| vs.channel_id.as_deref() == Some(channel_id.as_str()))
+        .ok_or_else(|| AppError::BadRequest("not_in_stage".to_string()))?;
+
+    // Moderators can bring themselves up without asking.
+    if input.suppress == Some(false) && current.suppress && !current.invited_to_speak {
+        require_channel_permission(&state.db, &channel_id, &auth, "mute_members").await?;
+    }
+    // Members who may not speak at all have nothing to raise a hand for.
+    if input.request_to_speak == Some(true) {
+        require_channel_permission(&state.db, &channel_id, &auth, "speak").await?;
+    }
+
+    let now = chrono::Utc::now()
+        .format("%Y-%m-%dT%H:%M:%S+00:00")
+        .to_string();
+    let updated = voice::state::modify_in_channel(&state, &auth.user_id, &channel_id, |vs| {
+        match input.request_to_speak {
+            Some(true) if vs.suppress => vs.request_to_speak_timestamp = Some(now),
+            Some(false) => vs.request_to_speak_timestamp = None,
+            _ => {}
+        }
+        if let Some(suppress) = input.suppress {
+            // Either way an outstanding invite or raised hand is settled.
+            vs.suppress = suppress;
+            vs.invited_to_speak = false;
+            vs.request_to_speak_timestamp = None;
+        }
+    })
+    .ok_or_else(|| AppError::BadRequest("not_in_stage".to_string()))?;
+
+    let rights_changed = current.suppress != updated.suppress;
+    apply_voice_change(&state, &channel_id, &space_id, rights_changed, &updated).await;
+    Ok(Json(serde_json::json!({ "data": updated })))
+}
+
+/// PATCH /channels/{channel_id}/voice-states/{user_id}
+/// Moderators bring a raised hand up to speak, invite someone who hasn't
+/// asked (they accept through `@me`), or move a speaker to the audience.
+pub async fn update_stage_state(
+    state: State<AppState>,
+    Path((channel_id, user_id)): Path<(String, String)>,
+    auth: AuthUser,
+    Json(input): Json<UpdateStageState>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space_id = require_stage(&state, &channel_id).await?;
+    require_channel_permission(&state.db, &channel_id, &auth, "mute_members").await?;
+    let current = voice::state::get_user_voice_state(&state, &user_id)
+        .filter(|vs| vs.channel_id.as_deref() == Some(channel_id.as_str()))
+        .ok_or_else(|| AppError::NotFound("unknown_voice_state".to_string()))?;
+
+    let updated = voice::state::modify_in_channel(&state, &user_id, &channel_id, |vs| {
+        if input.suppress {
+            vs.suppress = true;
+            vs.invited_to_speak = false;
+        } else if vs.request_to_speak_timestamp.is_some() {
+            vs.suppress = false;
+            vs.request_to_speak_timestamp = None;
+            vs.invited_to_speak = false;
+        } else if vs.suppress {
+            vs.invited_to_speak = true;
+        }
+    })
+    .ok_or_else(|| AppError::NotFound("unknown_voice_state".to_string()))?;
+
+    let rights_changed = current.suppress != updated.suppress;
+    apply_voice_change(&state, &channel_id, &space_id, rights_changed, &updated).await;
+    Ok(Json(serde_json::json!({ "data": updated })))
+}
+
 #[derive(serde::Deserialize, Default)]
 pub struct CallSignalBody {
     /// Optional free-form payload echoed to recipients (e.g. ringtone hints).
@@ -294,6 +635,7 @@ async fn broadcast_call_event(
             target_user_ids: Some(participant_ids),
             event,
             intent: "voice_states".to_string(),
+            role_mention_user_ids: None,
         });
     }
 }
@@ -316,28 +658,12 @@ async fn broadcast_voice_state_update(
     space_id: Option<&str>,
     voice_state: &VoiceState,
 ) {
-    let event = serde_json::json!({
-        "op": 0,
-        "type": "voice.state_update",
-        "data": voice_state
-    });
-
-    let (space, targets) = match space_id {
-        Some(sid) => (Some(sid.to_string()), None),
-        None => {
-            let ids = db::dm_participants::list_participant_ids(&state.db, channel_id)
-                .await
-                .unwrap_or_default();
-            (None, Some(ids))
-        }
-    };
-
-    if let Some(ref tx) = *state.gateway_tx.read().await {
-        let _ = tx.send(GatewayBroadcast {
-            space_id: space,
-            target_user_ids: targets,
-            event,
-            intent: "voice_states".to_string(),
-        });
-    }
+    voice::stream::broadcast(
+        state,
+        channel_id,
+        space_id,
+        "voice.state_update",
+        serde_json::json!(voice_state),
+    )
+    .await;
 }
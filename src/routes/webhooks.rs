@@ -0,0 +1,440 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::db;
+use crate::error::AppError;
+use crate::gateway::events::GatewayBroadcast;
+use crate::middleware::auth::{generate_token, AuthUser};
+use crate::middleware::permissions::{
+    require_channel_membership, require_channel_permission, require_not_archived,
+    require_permission,
+};
+use crate::models::embed::validate_embeds;
+use crate::models::message::{CreateMessage, FLAG_CROSSPOSTED, FLAG_IS_CROSSPOST};
+use crate::models::webhook::{
+    CreateWebhook, ExecuteWebhook, FollowChannel, UpdateWebhook, WebhookRow,
+};
+use crate::state::AppState;
+
+/// Channel types webhooks can post into.
+const WEBHOOK_CHANNEL_TYPES: &[&str] = &["text", "announcement"];
+
+/// Most webhooks a single channel may have.
+pub const MAX_WEBHOOKS_PER_CHANNEL: usize = 15;
+
+fn webhook_to_json(w: &WebhookRow) -> serde_json::Value {
+    serde_json::json!({
+        "id": w.id,
+        "channel_id": w.channel_id,
+        "space_id": w.space_id,
+        "creator_id": w.creator_id,
+        "name": w.name,
+        "avatar": w.avatar,
+        "source_channel_id": w.source_channel_id,
+        "created_at": w.created_at,
+    })
+}
+
+fn validate_name(name: &str) -> Result<(), AppError> {
+    if name.trim().is_empty() || name.len() > 80 {
+        return Err(AppError::BadRequest(
+            "name must be between 1 and 80 characters".into(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_avatar_url(url: &str) -> Result<(), AppError> {
+    let valid = url.len() <= 2048
+        && reqwest::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https"));
+    if !valid {
+        return Err(AppError::BadRequest(
+            "avatar must be an http(s) URL of at most 2048 characters".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that [channel_id] is a local space channel webhooks can post into
+/// and return its space ID.
+async fn require_webhook_channel(state: &AppState, channel_id: &str) -> Result<String, AppError> {
+    let channel = db::channels::get_channel_row(&state.db, channel_id).await?;
+    let Some(space_id) = channel.space_id else {
+        return Err(AppError::BadRequest(
+            "webhooks can only be created in space channels".into(),
+        ));
+    };
+    if !WEBHOOK_CHANNEL_TYPES.contains(&channel.channel_type.as_str()) {
+        return Err(AppError::BadRequest(
+            "webhooks can only post to text or announcement channels".into(),
+        ));
+    }
+    if db::federation::space_origin(&state.db, &space_id)
+        .await?
+        .is_some()
+    {
+        return Err(AppError::BadRequest(
+            "webhooks are not supported in federated spaces".into(),
+        ));
+    }
+    Ok(space_id)
+}
+
+pub async fn create_webhook(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<CreateWebhook>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_permission(&state.db, &channel_id, &auth, "manage_webhooks").await?;
+    let space_id = require_webhook_channel(&state, &channel_id).await?;
+    validate_name(&input.name)?;
+    if let Some(ref avatar) = input.avatar {
+        validate_avatar_url(avatar)?;
+    }
+    let existing = db::webhooks::list_channel_webhooks(&state.db, &channel_id).await?;
+    if existing.len() >= MAX_WEBHOOKS_PER_CHANNEL {
+        return Err(AppError::BadRequest(format!(
+            "a channel can have at most {MAX_WEBHOOKS_PER_CHANNEL} webhooks"
+        )));
+    }
+
+    let token = generate_token();
+    let webhook = db::webhooks::create_webhook(
+        &state.db,
+        &channel_id,
+        &space_id,
+        &auth.user_id,
+        input.name.trim(),
+        input.avatar.as_deref(),
+        &token,
+    )
+    .await?;
+
+    // The token is only ever returned here.
+    let mut json = webhook_to_json(&webhook);
+    json["token"] = serde_json::json!(token);
+    json["url"] = serde_json::json!(format!("/api/v1/webhooks/{}/{token}", webhook.id));
+    Ok(Json(serde_json::json!({ "data": json })))
+}
+
+pub async fn list_channel_webhooks(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_channel_permission(&state.db, &channel_id, &auth, "manage_webhooks").await?;
+    let webhooks = db::webhooks::list_channel_webhooks(&state.db, &channel_id).await?;
+    let data: Vec<serde_json::Value> = webhooks.iter().map(webhook_to_json).collect();
+    Ok(Json(serde_json::json!({ "data": data })))
+}
+
+pub async fn list_space_webhooks(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_permission(&state.db, &space_id, &auth, "manage_webhooks").await?;
+    let webhooks = db::webhooks::list_space_webhooks(&state.db, &space_id).await?;
+    let data: Vec<serde_json::Value> = webhooks.iter().map(webhook_to_json).collect();
+    Ok(Json(serde_json::json!({ "data": data })))
+}
+
+pub async fn get_webhook(
+    state: State<AppState>,
+    Path(webhook_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let webhook = db::webhooks::get_webhook(&state.db, &webhook_id).await?;
+    require_channel_permission(&state.db, &webhook.channel_id, &auth, "manage_webhooks").await?;
+    Ok(Json(
+        serde_json::json!({ "data": webhook_to_json(&webhook) }),
+    ))
+}
+
+pub async fn update_webhook(
+    state: State<AppState>,
+    Path(webhook_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<UpdateWebhook>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let webhook = db::webhooks::get_webhook(&state.db, &webhook_id).await?;
+    require_channel_permission(&state.db, &webhook.channel_id, &auth, "manage_webhooks").await?;
+    if let Some(ref name) = input.name {
+        validate_name(name)?;
+    }
+    if let Some(ref avatar) = input.avatar {
+        if !avatar.is_empty() {
+            validate_avatar_url(avatar)?;
+        }
+    }
+    if let Some(ref channel_id) = input.channel_id {
+        // Moving needs manage_webhooks on the destination too.
+        require_channel_permission(&state.db, channel_id, &auth, "manage_webhooks").await?;
+        if require_webhook_channel(&state, channel_id).await? != webhook.space_id {
+            return Err(AppError::BadRequest(
+                "webhooks can only move within the same space".into(),
+            ));
+        }
+    }
+    let webhook = db::webhooks::update_webhook(&state.db, &webhook_id, &input).await?;
+    Ok(Json(
+        serde_json::json!({ "data": webhook_to_json(&webhook) }),
+    ))
+}
+
+pub async fn delete_webhook(
+    state: State<AppState>,
+    Path(webhook_id): Path<String>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let webhook = db::webhooks::get_webhook(&state.db, &webhook_id).await?;
+    require_channel_permission(&state.db, &webhook.channel_id, &auth, "manage_webhooks").await?;
+    db::webhooks::delete_webhook(&state.db, &webhook_id).await?;
+    Ok(Json(serde_json::json!({ "data": null })))
+}
+
+/// `POST /webhooks/{id}/{token}` -- post a message as the webhook. The token
+/// in the path is the only credential; no `Authorization` header is needed.
+pub async fn execute_webhook(
+    state: State<AppState>,
+    Path((webhook_id, token)): Path<(String, String)>,
+    Json(mut input): Json<ExecuteWebhook>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let webhook = db::webhooks::get_webhook_with_token(&state.db, &webhook_id, &token).await?;
+    require_not_archived(&state.db, &webhook.space_id).await?;
+
+    let has_embeds = input.embeds.as_ref().is_some_and(|e| !e.is_empty());
+    if input.content.trim().is_empty() && !has_embeds {
+        return Err(AppError::BadRequest(
+            "webhook messages need content or embeds".into(),
+        ));
+    }
+    if input.content.len() > 4000 {
+        return Err(AppError::BadRequest(
+            "message content must be at most 4000 characters".into(),
+        ));
+    }
+    if let Some(ref mut embeds) = input.embeds {
+        validate_embeds(embeds).map_err(AppError::InvalidFields)?;
+    }
+    if let Some(ref username) = input.username {
+        validate_name(username)?;
+    }
+    if let Some(ref avatar_url) = input.avatar_url {
+        validate_avatar_url(avatar_url)?;
+    }
+
+    let username = input
+        .username
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or(&webhook.name);
+    let avatar = input.avatar_url.as_deref().or(webhook.avatar.as_deref());
+    let create = CreateMessage {
+        content: input.content,
+        tts: input.tts,
+        embeds: input.embeds,
+        reply_to: None,
+        thread_id: None,
+        title: None,
+        poll: None,
+        sticker_ids: None,
+    };
+    let msg = db::webhooks::create_webhook_message(&state.db, &webhook, username, avatar, &create)
+        .await?;
+
+    super::messages::apply_mention_counts(&state, &msg).await;
+
+    let json = super::messages::message_to_json_with_context(&state.db, &msg, &[]).await?;
+    let role_mention_user_ids =
+        crate::gateway::events::resolve_role_mentions(&state.db, &json).await;
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "message.create",
+            "data": json
+        });
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: Some(webhook.space_id.clone()),
+            target_user_ids: None,
+            event,
+            intent: "messages".to_string(),
+            role_mention_user_ids,
+        });
+    }
+
+    if let Err(e) = crate::federation::outbound::fanout_message_create(&state, &msg).await {
+        tracing::warn!("federation fanout failed for message {}: {e}", msg.id);
+    }
+
+    Ok(Json(serde_json::json!({ "data": json })))
+}
+
+/// `POST /channels/{id}/followers` -- follow an announcement channel from
+/// `webhook_channel_id`. Crossposts arrive there through a webhook named after
+/// the source; deleting that webhook unfollows.
+pub async fn follow_channel(
+    state: State<AppState>,
+    Path(channel_id): Path<String>,
+    auth: AuthUser,
+    Json(input): Json<FollowChannel>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let source_space_id = require_channel_membership(&state.db, &channel_id, &auth.user_id).await?;
+    let source = db::channels::get_channel_row(&state.db, &channel_id).await?;
+    if source.channel_type != "announcement" {
+        return Err(AppError::BadRequest(
+            "only announcement channels can be followed".into(),
+        ));
+    }
+    let target_id = input.webhook_channel_id;
+    if target_id == channel_id {
+        return Err(AppError::BadRequest(
+            "a channel cannot follow itself".into(),
+        ));
+    }
+    require_channel_permission(&state.db, &target_id, &auth, "manage_webhooks").await?;
+    let space_id = require_webhook_channel(&state, &target_id).await?;
+
+    let existing = db::webhooks::list_channel_webhooks(&state.db, &target_id).await?;
+    if existing
+        .iter()
+        .any(|w| w.source_channel_id.as_deref() == Some(channel_id.as_str()))
+    {
+        return Err(AppError::ConflictCode(
+            "already_following",
+            "that channel already follows this one".into(),
+        ));
+    }
+    if existing.len() >= MAX_WEBHOOKS_PER_CHANNEL {
+        return Err(AppError::BadRequest(format!(
+            "a channel can have at most {MAX_WEBHOOKS_PER_CHANNEL} webhooks"
+        )));
+    }
+
+    let source_space = db::spaces::get_space_row(&state.db, &source_space_id).await?;
+    let name: String = format!(
+        "{} #{}",
+        source_space.name,
+        source.name.as_deref().unwrap_or_default()
+    )
+    .chars()
+    .take(80)
+    .collect();
+    let webhook = db::webhooks::create_follower_webhook(
+        &state.db,
+        &target_id,
+        &space_id,
+        &auth.user_id,
+        &name,
+        &channel_id,
+    )
+    .await?;
+    Ok(Json(serde_json::json!({
+        "data": { "channel_id": channel_id, "webhook_id": webhook.id }
+    })))
+}
+
+/// `POST /channels/{id}/messages/{id}/crosspost` -- republish an
+/// announcement to every following channel. Authors need `send_messages`;
+/// anyone else needs `manage_messages`. A message can only go out once.
+pub async fn crosspost_message(
+    state: State<AppState>,
+    Path((channel_id, message_id)): Path<(String, String)>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let msg = db::messages::get_message_row(&state.db, &message_id).await?;
+    if msg.channel_id != channel_id {
+        return Err(AppError::NotFound("unknown_message".to_string()));
+    }
+    let permission = if msg.author_id == auth.user_id {
+        "send_messages"
+    } else {
+        "manage_messages"
+    };
+    let space_id = require_channel_permission(&state.db, &channel_id, &auth, permission).await?;
+    let channel = db::channels::get_channel_row(&state.db, &channel_id).await?;
+    if channel.channel_type != "announcement" {
+        return Err(AppError::BadRequest(
+            "only messages in announcement channels can be crossposted".into(),
+        ));
+    }
+    if msg.flags & FLAG_CROSSPOSTED != 0 {
+        return Err(AppError::ConflictCode(
+            "already_crossposted",
+            "this message has already been crossposted".into(),
+        ));
+    }
+
+    let embeds = serde_json::from_str(&msg.embeds).ok();
+    for webhook in db::webhooks::list_followers(&state.db, &channel_id).await? {
+        let create = CreateMessage {
+            content: msg.content.clone(),
+            tts: None,
+            embeds: Option::clone(&embeds),
+            reply_to: None,
+            thread_id: None,
+            title: msg.title.clone(),
+            poll: None,
+            sticker_ids: None,
+        };
+        let delivered = async {
+            let copy = db::webhooks::create_webhook_message(
+                &state.db,
+                &webhook,
+                &webhook.name,
+                webhook.avatar.as_deref(),
+                &create,
+            )
+            .await?;
+            db::messages::add_message_flags(&state.db, &copy.id, FLAG_IS_CROSSPOST).await
+        }
+        .await;
+        // One broken follower shouldn't stop the others.
+        let copy = match delivered {
+            Ok(copy) => copy,
+            Err(e) => {
+                tracing::warn!(
+                    "crosspost of {message_id} to webhook {} failed: {e}",
+                    webhook.id
+                );
+                continue;
+            }
+        };
+        let json = super::messages::message_row_to_json(&copy);
+        let role_mention_user_ids =
+            crate::gateway::events::resolve_role_mentions(&state.db, &json).await;
+        if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+            let _ = dispatcher.send(GatewayBroadcast {
+                space_id: Some(webhook.space_id.clone()),
+                target_user_ids: None,
+                event: serde_json::json!({
+                    "op": 0,
+                    "type": "message.create",
+                    "data": json
+                }),
+                intent: "messages".to_string(),
+                role_mention_user_ids,
+            });
+        }
+    }
+
+    let updated = db::messages::add_message_flags(&state.db, &message_id, FLAG_CROSSPOSTED).await?;
+    let json = super::messages::messages_to_json(&state.db, &[updated], Some(&auth.user_id))
+        .await?
+        .remove(0);
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let _ = dispatcher.send(GatewayBroadcast {
+            space_id: Some(space_id),
+            target_user_ids: None,
+            event: serde_json::json!({
+                "op": 0,
+                "type": "message.update",
+                "data": json
+            }),
+            intent: "messages".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+    Ok(Json(serde_json::json!({ "data": json })))
+}
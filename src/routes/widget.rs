@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap};
+use axum::Json;
+
+use crate::db;
+use crate::error::AppError;
+use crate::models::channel::is_voice_type;
+use crate::models::permission::Permissions;
+use crate::state::AppState;
+
+/// Most online members a widget lists; `presence_count` still counts all.
+const MAX_WIDGET_MEMBERS: usize = 100;
+
+/// GET /spaces/{space_id}/widget.json
+///
+/// A public summary for embedding on other sites: the voice channels
+/// anyone may see, who is online, and an invite link. Served without
+/// authentication, and only for spaces that turned `widget_enabled` on.
+pub async fn get_widget(
+    state: State<AppState>,
+    Path(space_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let space = db::spaces::get_space_row(&state.db, &space_id).await?;
+    if !space.widget_enabled {
+        return Err(AppError::ForbiddenCode(
+            "widget_disabled",
+            "this space's widget is disabled".into(),
+        ));
+    }
+
+    // Only what @everyone could see is listed.
+    let roles = db::roles::list_roles(&state.db, &space_id).await?;
+    let everyone = roles
+        .iter()
+        .find(|r| r.position == 0)
+        .map(|r| (r.id.clone(), r.permissions));
+    let mut channels = Vec::new();
+    if let Some((everyone_id, base)) = everyone {
+        let mut rows = db::channels::list_channels_in_space(&state.db, &space_id).await?;
+        rows.sort_by_key(|c| c.position);
+        for channel in rows.into_iter().filter(|c| is_voice_type(&c.channel_type)) {
+            let mut perms = base;
+            let overwrites =
+                db::permission_overwrites::list_overwrites(&state.db, &channel.id).await?;
+            if let Some(ow) = overwrites
+                .iter()
+                .find(|o| o.overwrite_type == "role" && o.id == everyone_id)
+            {
+                perms = perms.overwrite(ow.allow, ow.deny);
+            }
+            if perms.has(Permissions::VIEW_CHANNEL) {
+                channels.push(serde_json::json!({
+                    "id": channel.id,
+                    "name": channel.name,
+                    "position": channel.position,
+                }));
+            }
+        }
+    }
+
+    let member_ids: HashSet<String> = db::spaces::list_member_ids_for_space(&state.db, &space_id)
+        .await?
+        .into_iter()
+        .collect();
+    let mut online: Vec<_> = crate::presence::get_space_presences(&state, &member_ids)
+        .await
+        .into_iter()
+        .filter(|p| p.status != "offline" && p.status != "invisible")
+        .collect();
+    let presence_count = online.len();
+    online.sort_by(|a, b| a.user_id.cmp(&b.user_id));
+    online.truncate(MAX_WIDGET_MEMBERS);
+    let ids: Vec<String> = online.iter().map(|p| p.user_id.clone()).collect();
+    let users = db::users::get_users_by_ids(&state.db, &ids).await?;
+    let members: Vec<serde_json::Value> = online
+        .iter()
+        .filter_map(|p| {
+            let user = users.iter().find(|u| u.id == p.user_id)?;
+            let channel_id = crate::voice::state::get_user_voice_state(&state, &user.id)
+                .and_then(|vs| vs.channel_id)
+                .filter(|id| channels.iter().any(|c| c["id"] == id.as_str()));
+            Some(serde_json::json!({
+                "id": user.id,
+                "username": user.username,
+                "display_name": user.display_name,
+                "avatar": user.avatar,
+                "status": p.status,
+                "channel_id": channel_id,
+            }))
+        })
+        .collect();
+
+    let instant_invite = match space.widget_channel_id {
+        Some(ref channel_id) => {
+            let invite = db::invites::get_or_create_widget_invite(
+                &state.db,
+                &space_id,
+                channel_id,
+                &space.owner_id,
+            )
+            .await?;
+            let host = headers
+                .get(header::HOST)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("localhost");
+            Some(format!("https://{host}/invite/{}", invite.code))
+        }
+        None => None,
+    };
+
+    Ok(Json(serde_json::json!({
+        "data": {
+            "id": space.id,
+            "name": space.name,
+            "instant_invite": instant_invite,
+            "channels": channels,
+            "members": members,
+            "presence_count": presence_count,
+        }
+    })))
+}
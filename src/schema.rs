@@ -0,0 +1,215 @@
+//! The payload catalog served at `GET /api/v1/schema`: JSON Schemas for REST
+//! responses and gateway event data, inferred from sample payloads.
+//!
+//! Most payloads are built by hand from the typed rows (see
+//! [crate::routes::messages::message_row_to_json] and friends), so there's no
+//! one type to derive a schema from. Instead `tests/contract.rs` drives the
+//! real handlers through a scripted session, feeds each response and event
+//! into a [CatalogBuilder], and compares the result with
+//! `schema/catalog.json`, which is what the endpoint serves. A payload that
+//! changes shape fails that test until the catalog is regenerated with
+//! `UPDATE_SCHEMA=1 cargo test --test contract`, so the change shows up in
+//! review -- and third-party clients can diff it.
+//!
+//! The shapes are only as complete as the samples: a field the session never
+//! saw set is missing, and one it only saw as `null` is typed `null`. The
+//! catalog has an entry for each route `/api/v1` registers and each type in
+//! [EVENT_TYPES](crate::gateway::intents::EVENT_TYPES), because the test
+//! also fails when one has none.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::OnceLock;
+
+use serde_json::{json, Map, Value};
+
+/// The checked-in catalog.
+pub const CATALOG: &str = include_str!("../schema/catalog.json");
+
+pub const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The catalog's own `description`, so clients reading it know what it is.
+const SAMPLE_DERIVED: &str = "Inferred from the payloads a scripted test session received. \
+    A field the session never received is absent, and one it only received as null is typed null.";
+
+/// [CATALOG], parsed once.
+pub fn catalog() -> &'static Value {
+    static PARSED: OnceLock<Value> = OnceLock::new();
+    PARSED.get_or_init(|| serde_json::from_str(CATALOG).expect("schema/catalog.json is valid JSON"))
+}
+
+/// The schema of one observed value. Objects list every key as required;
+/// [merge] relaxes that as more observations come in.
+pub fn infer(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({ "type": "null" }),
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Number(n) if n.is_f64() => json!({ "type": "number" }),
+        Value::Number(_) => json!({ "type": "integer" }),
+        Value::String(_) => json!({ "type": "string" }),
+        Value::Array(items) => {
+            let mut schema = json!({ "type": "array" });
+            if let Some(item_schema) = items.iter().map(infer).reduce(|a, b| merge(&a, &b)) {
+                schema["items"] = item_schema;
+            }
+            schema
+        }
+        Value::Object(fields) => {
+            let properties: Map<String, Value> =
+                fields.iter().map(|(k, v)| (k.clone(), infer(v))).collect();
+            let required: Vec<&String> = fields.keys().collect();
+            json!({ "type": "object", "properties": properties, "required": required })
+        }
+    }
+}
+
+fn types(schema: &Value) -> BTreeSet<String> {
+    match &schema["type"] {
+        Value::String(t) => BTreeSet::from([t.clone()]),
+        Value::Array(ts) => ts
+            .iter()
+            .filter_map(|t| t.as_str().map(str::to_string))
+            .collect(),
+        _ => BTreeSet::new(),
+    }
+}
+
+fn required(schema: &Value) -> BTreeSet<String> {
+    schema["required"]
+        .as_array()
+        .map(|r| {
+            r.iter()
+                .filter_map(|k| k.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A schema that accepts everything either `a` or `b` does: types are
+/// unioned (`integer` widens to `number`), properties merged, and a key
+/// stays required only if every object seen had it.
+pub fn merge(a: &Value, b: &Value) -> Value {
+    let mut all_types: BTreeSet<String> = types(a).union(&types(b)).cloned().collect();
+    if all_types.contains("number") {
+        all_types.remove("integer");
+    }
+    let mut schema = Map::new();
+    schema.insert(
+        "type".into(),
+        match all_types.len() {
+            1 => json!(all_types.first()),
+            _ => json!(all_types),
+        },
+    );
+
+    if all_types.contains("object") {
+        let (a_props, b_props) = (&a["properties"], &b["properties"]);
+        let keys: BTreeSet<&String> = a_props
+            .as_object()
+            .into_iter()
+            .chain(b_props.as_object())
+            .flat_map(|p| p.keys())
+            .collect();
+        let properties: Map<String, Value> = keys
+            .into_iter()
+            .map(|k| {
+                let merged = match (a_props.get(k), b_props.get(k)) {
+                    (Some(x), Some(y)) => merge(x, y),
+                    (Some(x), None) | (None, Some(x)) => x.clone(),
+                    (None, None) => unreachable!(),
+                };
+                (k.clone(), merged)
+            })
+            .collect();
+        let required: BTreeSet<String> =
+            match (types(a).contains("object"), types(b).contains("object")) {
+                (true, true) => required(a).intersection(&required(b)).cloned().collect(),
+                (true, false) => required(a),
+                _ => required(b),
+            };
+        schema.insert("properties".into(), Value::Object(properties));
+        schema.insert("required".into(), json!(required));
+    }
+
+    if all_types.contains("array") {
+        match (a.get("items"), b.get("items")) {
+            (Some(x), Some(y)) => {
+                schema.insert("items".into(), merge(x, y));
+            }
+            (Some(x), None) | (None, Some(x)) => {
+                schema.insert("items".into(), x.clone());
+            }
+            (None, None) => {}
+        }
+    }
+    Value::Object(schema)
+}
+
+/// Collects observed payloads into a catalog.
+#[derive(Debug, Default)]
+pub struct CatalogBuilder {
+    rest: BTreeMap<String, Value>,
+    events: BTreeMap<String, Value>,
+}
+
+impl CatalogBuilder {
+    /// Record a response body, keyed by method and route template (e.g.
+    /// `GET /channels/{channel_id}/messages`).
+    pub fn observe_rest(&mut self, route: &str, body: &Value) {
+        observe(&mut self.rest, route, body);
+    }
+
+    /// Set the schema of a response that can't be inferred from a JSON
+    /// body, such as a file download, replacing anything observed for it.
+    pub fn describe_rest(&mut self, route: &str, schema: Value) {
+        self.rest.insert(route.to_string(), schema);
+    }
+
+    /// Record the `data` of a gateway event.
+    pub fn observe_event(&mut self, event_type: &str, data: &Value) {
+        observe(&mut self.events, event_type, data);
+    }
+
+    pub fn build(self) -> Value {
+        json!({
+            "$schema": SCHEMA_DIALECT,
+            "title": "Accord API payloads",
+            "description": SAMPLE_DERIVED,
+            "rest": self.rest,
+            "events": self.events,
+        })
+    }
+}
+
+fn observe(into: &mut BTreeMap<String, Value>, key: &str, value: &Value) {
+    let schema = infer(value);
+    let merged = match into.get(key) {
+        Some(existing) => merge(existing, &schema),
+        None => schema,
+    };
+    into.insert(key.to_string(), merged);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merging_observations_relaxes_the_schema() {
+        let a = infer(&json!({ "id": "1", "topic": null, "count": 1, "tags": [] }));
+        let b =
+            infer(&json!({ "id": "2", "topic": "hi", "count": 1.5, "tags": ["x"], "new": true }));
+        let merged = merge(&a, &b);
+        assert_eq!(
+            merged["properties"]["topic"]["type"],
+            json!(["null", "string"])
+        );
+        assert_eq!(merged["properties"]["count"]["type"], "number");
+        assert_eq!(merged["properties"]["tags"]["items"]["type"], "string");
+        assert_eq!(merged["required"], json!(["count", "id", "tags", "topic"]));
+    }
+
+    #[test]
+    fn checked_in_catalog_parses() {
+        assert_eq!(catalog()["$schema"], SCHEMA_DIALECT);
+    }
+}
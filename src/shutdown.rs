@@ -0,0 +1,93 @@
+//! Orderly shutdown on SIGTERM or Ctrl-C: gateway sessions are closed with
+//! `SERVER_SHUTDOWN` and allowed to run their own cleanup, anyone still in
+//! voice is disconnected, and in-flight HTTP requests get a bounded window
+//! to finish.
+
+use std::time::Duration;
+
+use crate::state::AppState;
+use crate::voice;
+
+/// How long gateway sessions get to close and clean up after themselves.
+pub const GATEWAY_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long in-flight HTTP requests get once the listener stops accepting.
+pub const HTTP_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Resolves on Ctrl-C, or SIGTERM on Unix.
+pub async fn signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Close every gateway session and wait (up to [GATEWAY_DRAIN_TIMEOUT]) for
+/// their disconnect cleanup, then clear any voice states left behind.
+pub async fn drain_gateway(state: &AppState) {
+    let sessions = match *state.dispatcher.read().await {
+        Some(ref dispatcher) => {
+            dispatcher.shutdown();
+            dispatcher.sessions().clone()
+        }
+        None => return,
+    };
+
+    let deadline = tokio::time::Instant::now() + GATEWAY_DRAIN_TIMEOUT;
+    while !sessions.is_empty() && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    if !sessions.is_empty() {
+        tracing::warn!(
+            "{} gateway sessions still open after {:?}",
+            sessions.len(),
+            GATEWAY_DRAIN_TIMEOUT
+        );
+    }
+
+    flush_voice_states(state).await;
+}
+
+/// Disconnect everyone still in voice (HTTP joins have no gateway session
+/// to do it) so LiveKit rooms and voice chats don't outlive the server.
+async fn flush_voice_states(state: &AppState) {
+    let user_ids: Vec<String> = state
+        .voice_states
+        .iter()
+        .map(|entry| entry.key().clone())
+        .collect();
+    for user_id in user_ids {
+        let Some(old_vs) = voice::state::leave_voice_channel(state, &user_id) else {
+            continue;
+        };
+        voice::stream::end(state, &old_vs).await;
+        let Some(ref channel_id) = old_vs.channel_id else {
+            continue;
+        };
+        voice::chat::clear_if_empty(state, channel_id).await;
+        if !state.test_mode {
+            if let Some(ref lk) = state.livekit_client {
+                lk.remove_participant(channel_id, &user_id).await;
+                lk.delete_room_if_empty(channel_id).await;
+            }
+        }
+    }
+}
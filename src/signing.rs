@@ -0,0 +1,157 @@
+//! Signatures on requests the server sends to applications.
+//!
+//! Each request carries `X-Accord-Timestamp` (unix seconds) and
+//! `X-Accord-Signature`, a comma-separated list of `<key id>=<hex>` entries:
+//! one HMAC-SHA256 of `<timestamp>.<body>` for every signing key the
+//! application has live. While a rotation's overlap window is open there are
+//! two or more entries, so a receiver holding either the old or the new
+//! secret can verify the request and switch over in its own time.
+//!
+//! Interactions sent to an `interactions_endpoint_url` are also signed with
+//! the application's Ed25519 key, the way serverless bot libraries expect:
+//! `X-Signature-Ed25519` is the hex signature of `<timestamp><body>` and
+//! `X-Signature-Timestamp` the timestamp, checked against the hex
+//! `verify_key` the application shows.
+
+use data_encoding::{BASE64, HEXLOWER};
+use ed25519_dalek::{Signer, Verifier};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+pub const SIGNATURE_HEADER: &str = "X-Accord-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Accord-Timestamp";
+pub const ED25519_SIGNATURE_HEADER: &str = "X-Signature-Ed25519";
+pub const ED25519_TIMESTAMP_HEADER: &str = "X-Signature-Timestamp";
+
+/// A live key: its public ID and the shared secret.
+#[derive(Debug, Clone)]
+pub struct SigningKey {
+    pub id: String,
+    pub secret: String,
+}
+
+fn mac(secret: &str, timestamp: i64, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// The `X-Accord-Signature` value for `body` sent at `timestamp`.
+pub fn signature_header(keys: &[SigningKey], timestamp: i64, body: &[u8]) -> String {
+    keys.iter()
+        .map(|key| {
+            let digest = mac(&key.secret, timestamp, body).finalize().into_bytes();
+            format!("{}={}", key.id, HEXLOWER.encode(&digest))
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Whether `header` holds a valid signature of `body` by `key` -- what a
+/// receiver does with the one secret it has.
+pub fn verify(key: &SigningKey, timestamp: i64, body: &[u8], header: &str) -> bool {
+    header
+        .split(',')
+        .filter_map(|entry| entry.trim().split_once('='))
+        .filter(|(id, _)| *id == key.id)
+        .filter_map(|(_, sig)| HEXLOWER.decode(sig.as_bytes()).ok())
+        .any(|sig| mac(&key.secret, timestamp, body).verify_slice(&sig).is_ok())
+}
+
+/// A new interaction keypair: the private key as base64, for storage, and
+/// the public `verify_key` as hex.
+pub fn generate_interaction_key() -> (String, String) {
+    let key = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+    (
+        BASE64.encode(&key.to_bytes()),
+        HEXLOWER.encode(key.verifying_key().as_bytes()),
+    )
+}
+
+/// The `X-Signature-Ed25519` value for `body` sent at `timestamp`, or
+/// `None` if `private_key` isn't one [generate_interaction_key] made.
+pub fn ed25519_signature(private_key: &str, timestamp: i64, body: &[u8]) -> Option<String> {
+    let bytes: [u8; 32] = BASE64
+        .decode(private_key.as_bytes())
+        .ok()?
+        .try_into()
+        .ok()?;
+    let key = ed25519_dalek::SigningKey::from_bytes(&bytes);
+    let mut message = timestamp.to_string().into_bytes();
+    message.extend_from_slice(body);
+    Some(HEXLOWER.encode(&key.sign(&message).to_bytes()))
+}
+
+/// Whether `signature` is `verify_key`'s signature of `body` sent at
+/// `timestamp` -- what a receiver does with the application's verify key.
+pub fn verify_ed25519(verify_key: &str, timestamp: &str, body: &[u8], signature: &str) -> bool {
+    let Some(key) = HEXLOWER
+        .decode(verify_key.as_bytes())
+        .ok()
+        .and_then(|b| <[u8; 32]>::try_from(b).ok())
+        .and_then(|b| ed25519_dalek::VerifyingKey::from_bytes(&b).ok())
+    else {
+        return false;
+    };
+    let Some(signature) = HEXLOWER
+        .decode(signature.as_bytes())
+        .ok()
+        .and_then(|b| <[u8; 64]>::try_from(b).ok())
+    else {
+        return false;
+    };
+    let mut message = timestamp.as_bytes().to_vec();
+    message.extend_from_slice(body);
+    key.verify(&message, &ed25519_dalek::Signature::from_bytes(&signature))
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, secret: &str) -> SigningKey {
+        SigningKey {
+            id: id.into(),
+            secret: secret.into(),
+        }
+    }
+
+    #[test]
+    fn every_live_key_signs_during_an_overlap() {
+        let old = key("k1", "old secret");
+        let new = key("k2", "new secret");
+        let header = signature_header(&[old.clone(), new.clone()], 1_700_000_000, b"{}");
+        assert!(header.starts_with("k1=") && header.contains(",k2="));
+        assert!(verify(&old, 1_700_000_000, b"{}", &header));
+        assert!(verify(&new, 1_700_000_000, b"{}", &header));
+
+        // A replay with another timestamp or body doesn't verify.
+        assert!(!verify(&new, 1_700_000_001, b"{}", &header));
+        assert!(!verify(&new, 1_700_000_000, b"{\"a\":1}", &header));
+    }
+
+    #[test]
+    fn signatures_are_matched_by_key_id() {
+        let header = signature_header(&[key("k1", "secret")], 1, b"body");
+        assert!(!verify(&key("k2", "secret"), 1, b"body", &header));
+        assert!(!verify(&key("k1", "other"), 1, b"body", &header));
+        assert!(!verify(&key("k1", "secret"), 1, b"body", "k1=zz"));
+    }
+
+    #[test]
+    fn interaction_signatures_verify_with_the_public_key() {
+        let (private_key, verify_key) = generate_interaction_key();
+        let sig = ed25519_signature(&private_key, 1_700_000_000, b"{}").unwrap();
+        assert!(verify_ed25519(&verify_key, "1700000000", b"{}", &sig));
+        assert!(!verify_ed25519(&verify_key, "1700000001", b"{}", &sig));
+        assert!(!verify_ed25519(&verify_key, "1700000000", b"{ }", &sig));
+
+        let (_, other_key) = generate_interaction_key();
+        assert!(!verify_ed25519(&other_key, "1700000000", b"{}", &sig));
+        assert!(ed25519_signature("not a key", 1, b"{}").is_none());
+    }
+}
@@ -0,0 +1,182 @@
+//! The roles and channels a new space starts with. Instances can replace the
+//! built-in layout with the `default_space_template` server setting, and a
+//! create request can bring its own `template`; either way the whole
+//! structure is written in the same transaction as the space.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::middleware::permissions::{ADMIN_PERMISSIONS, MODERATOR_PERMISSIONS};
+use crate::models::permission::ALL_PERMISSIONS;
+
+pub const MAX_TEMPLATE_ROLES: usize = 25;
+pub const MAX_TEMPLATE_CHANNELS: usize = 50;
+
+/// Channel types a template may create.
+pub const TEMPLATE_CHANNEL_TYPES: &[&str] = &[
+    "text",
+    "voice",
+    "stage",
+    "category",
+    "announcement",
+    "forum",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpaceTemplate {
+    /// Created above @everyone, lowest first.
+    #[serde(default)]
+    pub roles: Vec<TemplateRole>,
+    /// Created in order; a channel's `parent` names a category earlier in the
+    /// list.
+    #[serde(default)]
+    pub channels: Vec<TemplateChannel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateRole {
+    pub name: String,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    pub color: Option<i64>,
+    #[serde(default)]
+    pub hoist: bool,
+    /// Give this role to the space's owner.
+    #[serde(default)]
+    pub assign_to_owner: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateChannel {
+    pub name: String,
+    #[serde(rename = "type", default = "default_channel_type")]
+    pub channel_type: String,
+    pub topic: Option<String>,
+    pub parent: Option<String>,
+}
+
+fn default_channel_type() -> String {
+    "text".to_string()
+}
+
+impl SpaceTemplate {
+    /// Moderator and Admin roles (the owner gets Admin) and a #general
+    /// channel.
+    pub fn builtin() -> Self {
+        let role = |name: &str, color, permissions: &[&str], assign_to_owner| TemplateRole {
+            name: name.to_string(),
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+            color: Some(color),
+            hoist: true,
+            assign_to_owner,
+        };
+        Self {
+            roles: vec![
+                role("Moderator", 3447003, MODERATOR_PERMISSIONS, false),
+                role("Admin", 15158332, ADMIN_PERMISSIONS, true),
+            ],
+            channels: vec![TemplateChannel {
+                name: "general".to_string(),
+                channel_type: default_channel_type(),
+                topic: None,
+                parent: None,
+            }],
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        let bad = |msg: String| Err(AppError::BadRequest(format!("template: {msg}")));
+        if self.roles.len() > MAX_TEMPLATE_ROLES {
+            return bad(format!("at most {MAX_TEMPLATE_ROLES} roles"));
+        }
+        if self.channels.len() > MAX_TEMPLATE_CHANNELS {
+            return bad(format!("at most {MAX_TEMPLATE_CHANNELS} channels"));
+        }
+
+        for (i, role) in self.roles.iter().enumerate() {
+            let name = role.name.trim();
+            if name.is_empty() || name.len() > 100 {
+                return bad("role names must be between 1 and 100 characters".into());
+            }
+            if name == "@everyone" {
+                return bad("@everyone is always created and can't be templated".into());
+            }
+            if self.roles[..i].iter().any(|r| r.name.trim() == name) {
+                return bad(format!("duplicate role {name}"));
+            }
+            if let Some(p) = role
+                .permissions
+                .iter()
+                .find(|p| !ALL_PERMISSIONS.contains(&p.as_str()))
+            {
+                return bad(format!("role {name}: unknown permission {p}"));
+            }
+        }
+
+        for (i, channel) in self.channels.iter().enumerate() {
+            let name = channel.name.trim();
+            if name.is_empty() || name.len() > 100 {
+                return bad("channel names must be between 1 and 100 characters".into());
+            }
+            if !TEMPLATE_CHANNEL_TYPES.contains(&channel.channel_type.as_str()) {
+                return bad(format!(
+                    "channel {name}: type must be one of {}",
+                    TEMPLATE_CHANNEL_TYPES.join(", ")
+                ));
+            }
+            if channel.topic.as_ref().is_some_and(|t| t.len() > 1024) {
+                return bad(format!(
+                    "channel {name}: topic must be at most 1024 characters"
+                ));
+            }
+            if let Some(ref parent) = channel.parent {
+                if channel.channel_type == "category" {
+                    return bad(format!("category {name} can't have a parent"));
+                }
+                if !self.channels[..i]
+                    .iter()
+                    .any(|c| c.channel_type == "category" && c.name == *parent)
+                {
+                    return bad(format!(
+                        "channel {name}: parent {parent} must be a category listed before it"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_template_is_valid() {
+        SpaceTemplate::builtin().validate().unwrap();
+    }
+
+    #[test]
+    fn parent_must_be_an_earlier_category() {
+        let template: SpaceTemplate = serde_json::from_value(serde_json::json!({
+            "channels": [
+                { "name": "chat", "parent": "Text" },
+                { "name": "Text", "type": "category" },
+            ]
+        }))
+        .unwrap();
+        assert!(template.validate().is_err());
+
+        let template: SpaceTemplate = serde_json::from_value(serde_json::json!({
+            "channels": [
+                { "name": "Text", "type": "category" },
+                { "name": "chat", "parent": "Text" },
+            ]
+        }))
+        .unwrap();
+        template.validate().unwrap();
+    }
+}
@@ -1,17 +1,18 @@
+use crate::db::DbPool;
 use arc_swap::ArcSwap;
 use dashmap::DashMap;
-use sqlx::AnyPool;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::{broadcast, Mutex, RwLock};
 use tokio::time::Instant;
 
-use crate::config::MasterServerConfig;
+use crate::config::{CorsConfig, MasterServerConfig, WebAuthnConfig};
 use crate::gateway::dispatcher::Dispatcher;
 use crate::gateway::events::GatewayBroadcast;
 use crate::models::presence::Presence;
 use crate::models::settings::ServerSettings;
-use crate::models::voice::VoiceState;
+use crate::models::voice::{VoiceState, VoiceStatsReport, VoiceTokenGrant};
+use crate::storage::Storage;
 use crate::voice::livekit::LiveKitClient;
 
 /// Per-key token bucket for rate limiting.
@@ -56,19 +57,69 @@ pub struct MfaTicket {
     pub expires_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// Pending QR login. The signed-out device that requested it waits on the
+/// gateway until a signed-in user approves it; `approved_by` then carries
+/// that user's ID.
+pub struct LoginTicket {
+    /// Hash of the secret only the requesting device knows; the ticket
+    /// itself is shown in the QR code.
+    pub secret_hash: String,
+    /// Name and IP of the requesting device, for the session it receives.
+    pub device_name: Option<String>,
+    pub ip: Option<String>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub approved_by: tokio::sync::watch::Sender<Option<String>>,
+}
+
+/// What an outstanding passkey challenge may be answered for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PasskeyCeremony {
+    /// Adding a passkey to `user_id`'s account.
+    Register,
+    /// Signing in; `user_id` is set when the passkey must be theirs (a
+    /// username was given, or it's the second step of a password login).
+    Login,
+}
+
+/// A challenge handed out by a `/auth/passkeys` start route, keyed by the
+/// challenge itself, which comes back inside `clientDataJSON`.
+#[derive(Clone)]
+pub struct PasskeyChallenge {
+    pub ceremony: PasskeyCeremony,
+    pub user_id: Option<String>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
 #[derive(Clone)]
 pub struct AppState {
-    pub db: AnyPool,
+    pub db: DbPool,
     /// True when the runtime database is PostgreSQL; false for SQLite.
     pub db_is_postgres: bool,
     pub voice_states: Arc<DashMap<String, VoiceState>>,
+    /// user_id -> their last `POST /channels/{id}/voice/stats` report
+    pub voice_stats: Arc<DashMap<String, VoiceStatsReport>>,
+    /// user_id -> expiry of the voice token they were last given
+    pub voice_tokens: Arc<DashMap<String, VoiceTokenGrant>>,
     pub presences: Arc<DashMap<String, Presence>>,
+    /// Replaces `presences` and local session counting when presence is
+    /// shared between nodes.
+    pub shared_presence: Option<Arc<dyn crate::presence::SharedPresence>>,
     pub dispatcher: Arc<RwLock<Option<Dispatcher>>>,
     pub gateway_tx: Arc<RwLock<Option<broadcast::Sender<GatewayBroadcast>>>>,
     pub test_mode: bool,
     pub livekit_client: Option<LiveKitClient>,
-    pub rate_limits: Arc<DashMap<String, RateLimitBucket>>,
+    /// (bucket, auth key) -> RateLimitBucket; see `middleware::rate_limit`
+    pub rate_limits: Arc<DashMap<(&'static str, String), RateLimitBucket>>,
+    /// application ID -> RateLimitBucket; global per-application quota for bot tokens
+    pub bot_rate_limits: Arc<DashMap<String, RateLimitBucket>>,
+    /// Coalesces `typing.start` and counts what it suppressed
+    pub typing: Arc<crate::typing::TypingThrottle>,
+    /// Root of the local data directory. Uploads live here unless `storage`
+    /// is an object store.
     pub storage_path: PathBuf,
+    /// Backend uploaded files (emoji, avatars, sounds, attachments) are
+    /// written to and served from.
+    pub storage: Arc<dyn Storage>,
     /// Path to `update_status.json` written by the desktop tray app (when the
     /// server runs as a bundled sidecar). `None` for standalone deployments.
     pub update_status_path: Option<PathBuf>,
@@ -80,6 +131,12 @@ pub struct AppState {
     pub federation: Option<Arc<crate::federation::FederationContext>>,
     /// ticket_hash -> MfaTicket; short-lived tickets for 2FA login flow
     pub mfa_tickets: Arc<DashMap<String, MfaTicket>>,
+    /// ticket_hash -> LoginTicket; pending QR logins
+    pub login_tickets: Arc<DashMap<String, LoginTicket>>,
+    /// challenge -> PasskeyChallenge; pending passkey ceremonies
+    pub passkey_challenges: Arc<DashMap<String, PasskeyChallenge>>,
+    /// Passkey relying party, when WebAuthn is configured
+    pub webauthn: Option<Arc<WebAuthnConfig>>,
     /// user_id -> TotpAttemptTracker; brute-force protection for TOTP verification
     pub totp_attempts: Arc<DashMap<String, TotpAttemptTracker>>,
     /// Optional AES-256-GCM key for encrypting TOTP secrets at rest
@@ -94,4 +151,12 @@ pub struct AppState {
     pub guest_attempts: Arc<DashMap<String, GuestAttemptTracker>>,
     /// Tracks the number of active anonymous guests per space for member list display
     pub guest_counts: Arc<DashMap<String, u32>>,
+    /// Browser CORS policy (app routes vs. CDN), from `Config`
+    pub cors: Arc<CorsConfig>,
+    /// Outgoing mail, when SMTP is configured
+    pub mailer: Option<Arc<crate::email::Mailer>>,
+    /// Web Push sender, when VAPID keys are configured
+    pub push: Option<Arc<crate::push::Notifier>>,
+    /// Users must verify their email before joining public spaces
+    pub require_verified_email: bool,
 }
@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+
+use super::{Storage, StorageFuture};
+use crate::error::AppError;
+
+/// Files on local disk under `storage_path`, served by `/cdn` directly.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        bytes: &'a [u8],
+        _content_type: &'a str,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let file_path = self.root.join(key);
+            if let Some(dir) = file_path.parent() {
+                tokio::fs::create_dir_all(dir)
+                    .await
+                    .map_err(|e| AppError::Internal(format!("failed to create directory: {e}")))?;
+            }
+            tokio::fs::write(&file_path, bytes)
+                .await
+                .map_err(|e| AppError::Internal(format!("failed to write file: {e}")))
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let file_path = self.root.join(key);
+
+            // Canonicalize both paths to prevent directory traversal
+            let canonical_storage = self
+                .root
+                .canonicalize()
+                .unwrap_or_else(|_| self.root.clone());
+            if let Ok(canonical_file) = file_path.canonicalize() {
+                if !canonical_file.starts_with(&canonical_storage) {
+                    return Err(AppError::BadRequest("invalid file path".to_string()));
+                }
+                tokio::fs::remove_file(&canonical_file)
+                    .await
+                    .map_err(|e| AppError::Internal(format!("failed to delete file: {e}")))?;
+            }
+            Ok(())
+        })
+    }
+
+    fn exists<'a>(&'a self, key: &'a str) -> StorageFuture<'a, bool> {
+        Box::pin(async move {
+            tokio::fs::try_exists(self.root.join(key))
+                .await
+                .map_err(|e| AppError::Internal(format!("failed to check file: {e}")))
+        })
+    }
+
+    fn local_root(&self) -> Option<&Path> {
+        Some(&self.root)
+    }
+}
@@ -1,15 +1,70 @@
+mod local;
+mod s3;
+pub mod thumbnail;
+
+use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+pub use local::LocalStorage;
+pub use s3::S3Storage;
 
 use crate::error::AppError;
 
 pub const MAX_EMOJI_SIZE: usize = 256 * 1024; // 256 KB
 pub const MAX_AVATAR_SIZE: usize = 2 * 1024 * 1024; // 2 MB
 pub const MAX_SOUND_SIZE: usize = 2 * 1024 * 1024; // 2 MB
+pub const MAX_STICKER_SIZE: usize = 512 * 1024; // 512 KB
 pub const MAX_ATTACHMENT_SIZE: usize = 25 * 1024 * 1024; // 25 MB
 
 pub const ALLOWED_IMAGE_TYPES: &[&str] = &["image/png", "image/gif", "image/webp"];
 pub const ALLOWED_AUDIO_TYPES: &[&str] = &["audio/ogg", "audio/mpeg", "audio/wav"];
 
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, AppError>> + Send + 'a>>;
+
+/// Where uploaded files live. Objects are addressed by key, the path under
+/// the CDN root (e.g. `emojis/<space_id>/<emoji_id>.png`); the URL clients
+/// see is always `/cdn/<key>`.
+pub trait Storage: Send + Sync {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        bytes: &'a [u8],
+        content_type: &'a str,
+    ) -> StorageFuture<'a, ()>;
+
+    /// Delete the object at [key]. Deleting a missing object is not an error.
+    fn delete<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ()>;
+
+    /// Whether an object is stored at [key].
+    fn exists<'a>(&'a self, key: &'a str) -> StorageFuture<'a, bool>;
+
+    /// The directory `/cdn` is served from, when files are on local disk.
+    fn local_root(&self) -> Option<&Path> {
+        None
+    }
+
+    /// A short-lived URL `/cdn/<key>` redirects to, for backends the server
+    /// does not serve itself.
+    fn presigned_url(&self, _key: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Reject keys that could escape the CDN root or that are not CDN paths at
+/// all (e.g. an external avatar URL).
+pub fn validate_key(key: &str) -> Result<(), AppError> {
+    let valid = !key.is_empty()
+        && !key.contains('\\')
+        && key
+            .split('/')
+            .all(|seg| !seg.is_empty() && seg != "." && seg != "..");
+    if !valid {
+        return Err(AppError::BadRequest("invalid file path".to_string()));
+    }
+    Ok(())
+}
+
 /// Parse a `data:<mime>;base64,<data>` URI for images with a custom size limit.
 /// Returns `(decoded_bytes, content_type, is_animated)`.
 pub fn validate_image_data_uri_with_limit(
@@ -80,10 +135,10 @@ pub fn validate_audio_data_uri(data: &str, max_size: usize) -> Result<(Vec<u8>,
     Ok((bytes, mime.to_string()))
 }
 
-/// Save a base64-encoded image to disk.
+/// Save a base64-encoded image.
 /// Returns `(relative_url, content_type, file_size)`.
 pub async fn save_base64_image(
-    storage_path: &Path,
+    storage: &dyn Storage,
     space_id: &str,
     file_id: &str,
     data: &str,
@@ -93,25 +148,109 @@ pub async fn save_base64_image(
     let ext = mime_to_ext(&content_type);
     let size = bytes.len();
 
-    let dir = storage_path.join("emojis").join(space_id);
-    tokio::fs::create_dir_all(&dir)
-        .await
-        .map_err(|e| AppError::Internal(format!("failed to create emoji directory: {e}")))?;
+    let key = format!("emojis/{space_id}/{file_id}.{ext}");
+    storage.put(&key, &bytes, &content_type).await?;
 
-    let filename = format!("{file_id}.{ext}");
-    let file_path = dir.join(&filename);
-    tokio::fs::write(&file_path, &bytes)
-        .await
-        .map_err(|e| AppError::Internal(format!("failed to write emoji file: {e}")))?;
-
-    let relative_url = format!("/cdn/emojis/{space_id}/{filename}");
+    let relative_url = format!("/cdn/{key}");
     Ok((relative_url, content_type, size, is_animated))
 }
 
-/// Save a base64-encoded audio file to disk.
+/// Parse a `data:<mime>;base64,<data>` URI for a sticker: a PNG, an APNG
+/// (`image/png` or `image/apng`), or a Lottie animation
+/// (`application/json`). The format comes from the content, not the declared
+/// type. Returns `(decoded_bytes, content_type, format)`.
+pub fn validate_sticker_data_uri(
+    data: &str,
+    max_size: usize,
+) -> Result<(Vec<u8>, &'static str, &'static str), AppError> {
+    let rest = data
+        .strip_prefix("data:")
+        .ok_or_else(|| AppError::BadRequest("image must be a data URI".to_string()))?;
+    let (mime, b64) = rest
+        .split_once(";base64,")
+        .ok_or_else(|| AppError::BadRequest("image must be a base64 data URI".to_string()))?;
+    if !["image/png", "image/apng", "application/json"].contains(&mime) {
+        return Err(AppError::BadRequest(format!(
+            "unsupported sticker type: {mime}. allowed: png, apng, lottie json"
+        )));
+    }
+
+    let bytes = base64_decode(b64)?;
+    if bytes.len() > max_size {
+        return Err(AppError::PayloadTooLarge(format!(
+            "sticker exceeds maximum size of {} KB",
+            max_size / 1024
+        )));
+    }
+
+    let (content_type, format) = if mime == "application/json" {
+        if !is_lottie(&bytes) {
+            return Err(AppError::BadRequest(
+                "sticker is not a Lottie animation".to_string(),
+            ));
+        }
+        ("application/json", "lottie")
+    } else if !bytes.starts_with(PNG_SIGNATURE) {
+        return Err(AppError::BadRequest("sticker is not a PNG".to_string()));
+    } else if is_animated_png(&bytes) {
+        ("image/apng", "apng")
+    } else {
+        ("image/png", "png")
+    };
+    Ok((bytes, content_type, format))
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// An APNG is a PNG with an `acTL` chunk ahead of its image data.
+fn is_animated_png(bytes: &[u8]) -> bool {
+    let mut pos = PNG_SIGNATURE.len();
+    while let Some(header) = bytes.get(pos..pos + 8) {
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        match &header[4..8] {
+            b"acTL" => return true,
+            b"IDAT" | b"IEND" => return false,
+            _ => pos = pos.saturating_add(12).saturating_add(len),
+        }
+    }
+    false
+}
+
+/// Lottie files are JSON objects with a format version and a layer list.
+fn is_lottie(bytes: &[u8]) -> bool {
+    serde_json::from_slice::<serde_json::Value>(bytes).is_ok_and(|json| {
+        json.get("v").is_some_and(|v| v.is_string())
+            && json.get("layers").is_some_and(|l| l.is_array())
+    })
+}
+
+/// Save a base64-encoded sticker under its ID. The extension follows the
+/// format so `/cdn` serves PNGs as `image/png`, APNGs as `image/apng`, and
+/// Lottie files as `application/json`.
+/// Returns `(relative_url, content_type, file_size, format)`.
+pub async fn save_sticker(
+    storage: &dyn Storage,
+    space_id: &str,
+    sticker_id: &str,
+    data: &str,
+    max_size: usize,
+) -> Result<(String, String, usize, &'static str), AppError> {
+    let (bytes, content_type, format) = validate_sticker_data_uri(data, max_size)?;
+    let key = format!(
+        "stickers/{space_id}/{sticker_id}.{}",
+        mime_to_ext(content_type)
+    );
+    validate_key(&key)?;
+    storage.put(&key, &bytes, content_type).await?;
+
+    let relative_url = format!("/cdn/{key}");
+    Ok((relative_url, content_type.to_string(), bytes.len(), format))
+}
+
+/// Save a base64-encoded audio file.
 /// Returns `(relative_url, content_type, file_size)`.
 pub async fn save_base64_audio(
-    storage_path: &Path,
+    storage: &dyn Storage,
     space_id: &str,
     file_id: &str,
     data: &str,
@@ -121,26 +260,18 @@ pub async fn save_base64_audio(
     let ext = mime_to_ext(&content_type);
     let size = bytes.len();
 
-    let dir = storage_path.join("sounds").join(space_id);
-    tokio::fs::create_dir_all(&dir)
-        .await
-        .map_err(|e| AppError::Internal(format!("failed to create sounds directory: {e}")))?;
-
-    let filename = format!("{file_id}.{ext}");
-    let file_path = dir.join(&filename);
-    tokio::fs::write(&file_path, &bytes)
-        .await
-        .map_err(|e| AppError::Internal(format!("failed to write sound file: {e}")))?;
+    let key = format!("sounds/{space_id}/{file_id}.{ext}");
+    storage.put(&key, &bytes, &content_type).await?;
 
-    let relative_url = format!("/cdn/sounds/{space_id}/{filename}");
+    let relative_url = format!("/cdn/{key}");
     Ok((relative_url, content_type, size))
 }
 
-/// Save a base64-encoded avatar/icon/banner image to disk.
+/// Save a base64-encoded avatar/icon/banner image.
 /// `category` should be `"avatars"`, `"icons"`, or `"banners"`.
 /// Returns `(relative_url, content_type, file_size, is_animated)`.
 pub async fn save_avatar_image(
-    storage_path: &Path,
+    storage: &dyn Storage,
     category: &str,
     entity_id: &str,
     data: &str,
@@ -150,54 +281,32 @@ pub async fn save_avatar_image(
     let ext = mime_to_ext(&content_type);
     let size = bytes.len();
 
-    let dir = storage_path.join(category);
-    tokio::fs::create_dir_all(&dir)
-        .await
-        .map_err(|e| AppError::Internal(format!("failed to create {category} directory: {e}")))?;
-
     // Delete any existing files for this entity (handles extension changes on re-upload)
-    delete_avatar(storage_path, category, entity_id).await?;
+    delete_avatar(storage, category, entity_id).await?;
 
-    let filename = format!("{entity_id}.{ext}");
-    let file_path = dir.join(&filename);
-    tokio::fs::write(&file_path, &bytes)
-        .await
-        .map_err(|e| AppError::Internal(format!("failed to write {category} file: {e}")))?;
+    let key = format!("{category}/{entity_id}.{ext}");
+    storage.put(&key, &bytes, &content_type).await?;
 
-    let relative_url = format!("/cdn/{category}/{filename}");
+    let relative_url = format!("/cdn/{key}");
     Ok((relative_url, content_type, size, is_animated))
 }
 
-/// Delete all files matching `entity_id.*` in the category directory.
-/// Handles extension changes on re-upload.
+/// Delete every image stored for `entity_id` in the category, whatever its
+/// extension. Handles extension changes on re-upload.
 pub async fn delete_avatar(
-    storage_path: &Path,
+    storage: &dyn Storage,
     category: &str,
     entity_id: &str,
 ) -> Result<(), AppError> {
-    let dir = storage_path.join(category);
-    if !dir.exists() {
-        return Ok(());
-    }
-    let mut entries = tokio::fs::read_dir(&dir)
-        .await
-        .map_err(|e| AppError::Internal(format!("failed to read {category} directory: {e}")))?;
-    let prefix = format!("{entity_id}.");
-    while let Some(entry) = entries
-        .next_entry()
-        .await
-        .map_err(|e| AppError::Internal(format!("failed to read directory entry: {e}")))?
-    {
-        if let Some(name) = entry.file_name().to_str() {
-            if name.starts_with(&prefix) {
-                let _ = tokio::fs::remove_file(entry.path()).await;
-            }
-        }
+    for content_type in ALLOWED_IMAGE_TYPES.iter().chain(&["image/jpeg"]) {
+        let key = format!("{category}/{entity_id}.{}", mime_to_ext(content_type));
+        validate_key(&key)?;
+        storage.delete(&key).await?;
     }
     Ok(())
 }
 
-/// Save an uploaded attachment file to disk.
+/// Save an uploaded attachment file.
 ///
 /// Files are organized by `attachment_id` (a stable, unique ID generated for
 /// each upload) rather than `message_id`, so the on-disk location does not
@@ -207,10 +316,11 @@ pub async fn delete_avatar(
 ///
 /// Returns `(relative_url, file_size)`.
 pub async fn save_attachment(
-    storage_path: &Path,
+    storage: &dyn Storage,
     channel_id: &str,
     attachment_id: &str,
     filename: &str,
+    content_type: &str,
     bytes: &[u8],
     max_size: usize,
 ) -> Result<(String, usize), AppError> {
@@ -221,25 +331,45 @@ pub async fn save_attachment(
         )));
     }
 
-    let dir = storage_path
-        .join("attachments")
-        .join(channel_id)
-        .join(attachment_id);
-    tokio::fs::create_dir_all(&dir)
-        .await
-        .map_err(|e| AppError::Internal(format!("failed to create attachment directory: {e}")))?;
-
     let safe_filename = sanitize_filename(filename);
-    let file_path = dir.join(&safe_filename);
+    let key = format!("attachments/{channel_id}/{attachment_id}/{safe_filename}");
+    validate_key(&key)?;
     let size = bytes.len();
-    tokio::fs::write(&file_path, bytes)
-        .await
-        .map_err(|e| AppError::Internal(format!("failed to write attachment file: {e}")))?;
+    // Object stores serve the stored type as-is, so anything a browser would
+    // render as a document is stored as a download.
+    let inline = ["image/", "audio/", "video/"]
+        .iter()
+        .any(|p| content_type.starts_with(p))
+        && content_type != "image/svg+xml";
+    let content_type = if inline || content_type == "text/plain" {
+        content_type
+    } else {
+        "application/octet-stream"
+    };
+    storage.put(&key, bytes, content_type).await?;
 
-    let relative_url = format!("/cdn/attachments/{channel_id}/{attachment_id}/{safe_filename}");
+    let relative_url = format!("/cdn/{key}");
     Ok((relative_url, size))
 }
 
+/// Store the WebP preview for attachment [attachment_id]. It sits in a
+/// subdirectory next to the original so it can't collide with an uploaded
+/// filename.
+pub async fn save_attachment_thumbnail(
+    storage: &dyn Storage,
+    channel_id: &str,
+    attachment_id: &str,
+    webp: &[u8],
+) -> Result<String, AppError> {
+    let key = format!(
+        "attachments/{channel_id}/{attachment_id}/thumbnails/{}.webp",
+        thumbnail::THUMBNAIL_SIZE
+    );
+    validate_key(&key)?;
+    storage.put(&key, webp, "image/webp").await?;
+    Ok(format!("/cdn/{key}"))
+}
+
 /// Sanitize a filename to prevent directory traversal and other issues.
 /// Only allows alphanumeric characters, hyphens, underscores, and a single dot for extension.
 fn sanitize_filename(name: &str) -> String {
@@ -277,40 +407,29 @@ fn sanitize_filename(name: &str) -> String {
 }
 
 /// Delete a file given its relative path (e.g. `/cdn/emojis/123/456.png`).
-pub async fn delete_file(storage_path: &Path, relative_path: &str) -> Result<(), AppError> {
-    // Strip the leading `/cdn/` to get the path relative to storage_path
-    let rel = relative_path.strip_prefix("/cdn/").unwrap_or(relative_path);
-    let file_path = storage_path.join(rel);
-
-    // Canonicalize both paths to prevent directory traversal
-    let canonical_storage = storage_path
-        .canonicalize()
-        .unwrap_or_else(|_| storage_path.to_path_buf());
-    if let Ok(canonical_file) = file_path.canonicalize() {
-        if !canonical_file.starts_with(&canonical_storage) {
-            return Err(AppError::BadRequest("invalid file path".to_string()));
-        }
-        tokio::fs::remove_file(&canonical_file)
-            .await
-            .map_err(|e| AppError::Internal(format!("failed to delete file: {e}")))?;
-    }
-    Ok(())
+pub async fn delete_file(storage: &dyn Storage, relative_path: &str) -> Result<(), AppError> {
+    // Strip the leading `/cdn/` to get the storage key
+    let key = relative_path.strip_prefix("/cdn/").unwrap_or(relative_path);
+    validate_key(key)?;
+    storage.delete(key).await
 }
 
 fn mime_to_ext(content_type: &str) -> &'static str {
     match content_type {
         "image/png" => "png",
+        "image/apng" => "apng",
         "image/gif" => "gif",
         "image/webp" => "webp",
         "image/jpeg" => "jpg",
         "audio/ogg" => "ogg",
         "audio/mpeg" => "mp3",
         "audio/wav" => "wav",
+        "application/json" => "json",
         _ => "bin",
     }
 }
 
-fn base64_decode(input: &str) -> Result<Vec<u8>, AppError> {
+pub fn base64_decode(input: &str) -> Result<Vec<u8>, AppError> {
     // Simple base64 decoder using a lookup table
     const DECODE_TABLE: [u8; 256] = {
         let mut table = [255u8; 256];
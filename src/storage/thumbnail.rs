@@ -0,0 +1,99 @@
+//! Preview thumbnails for image attachments.
+
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+
+/// Longest edge of a generated thumbnail, in pixels.
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// Images larger than this on either edge aren't decoded.
+const MAX_SOURCE_DIMENSION: u32 = 16_384;
+
+/// Decoding budget, so a small file can't claim to be a huge canvas.
+const MAX_DECODE_ALLOC: u64 = 256 * 1024 * 1024;
+
+pub struct Thumbnail {
+    /// Dimensions of the source image.
+    pub width: u32,
+    pub height: u32,
+    /// WebP-encoded preview, or `None` when the source already fits within
+    /// [THUMBNAIL_SIZE] and can serve as its own preview.
+    pub webp: Option<Vec<u8>>,
+}
+
+/// Decode [bytes] and produce a WebP thumbnail. Returns `None` for anything
+/// that isn't a decodable PNG, JPEG, GIF or WebP within the size limits.
+/// This is CPU-bound; call it from `spawn_blocking`.
+pub fn generate(bytes: &[u8]) -> Option<Thumbnail> {
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?;
+    if !matches!(
+        reader.format(),
+        Some(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP)
+    ) {
+        return None;
+    }
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+    reader.limits(limits);
+
+    let image = reader.decode().ok()?;
+    let (width, height) = (image.width(), image.height());
+    if width <= THUMBNAIL_SIZE && height <= THUMBNAIL_SIZE {
+        return Some(Thumbnail {
+            width,
+            height,
+            webp: None,
+        });
+    }
+
+    // The WebP encoder only takes 8-bit buffers.
+    let preview =
+        DynamicImage::ImageRgba8(image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgba8());
+    let mut webp = Vec::new();
+    preview
+        .write_to(&mut Cursor::new(&mut webp), ImageFormat::WebP)
+        .ok()?;
+    Some(Thumbnail {
+        width,
+        height,
+        webp: Some(webp),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut out = Vec::new();
+        DynamicImage::new_rgb8(width, height)
+            .write_to(&mut Cursor::new(&mut out), ImageFormat::Png)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn large_images_are_scaled_to_fit() {
+        let thumb = generate(&png(1024, 512)).unwrap();
+        assert_eq!((thumb.width, thumb.height), (1024, 512));
+        let preview = image::load_from_memory(&thumb.webp.unwrap()).unwrap();
+        assert_eq!((preview.width(), preview.height()), (256, 128));
+    }
+
+    #[test]
+    fn small_images_are_their_own_preview() {
+        let thumb = generate(&png(64, 32)).unwrap();
+        assert_eq!((thumb.width, thumb.height), (64, 32));
+        assert!(thumb.webp.is_none());
+    }
+
+    #[test]
+    fn non_images_are_skipped() {
+        assert!(generate(b"definitely not a picture").is_none());
+    }
+}
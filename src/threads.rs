@@ -0,0 +1,58 @@
+//! Background auto-archiving for threads.
+//!
+//! Each thread archives itself after `auto_archive_after` minutes without a
+//! reply. A reply (or an explicit unarchive) reopens it; see
+//! `db::threads::touch_thread`.
+
+use std::time::Duration;
+
+use crate::db;
+use crate::models::thread::AUTO_ARCHIVE_DURATIONS;
+use crate::state::AppState;
+
+/// How often stale threads are swept.
+pub const ARCHIVE_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sweep forever, archiving inactive threads.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(ARCHIVE_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = archive_stale_threads(&state).await {
+            tracing::warn!("thread auto-archive sweep failed: {e:?}");
+        }
+    }
+}
+
+/// Archive every open thread whose last activity is older than its window,
+/// broadcasting `thread.update` for each. Returns how many were archived.
+pub async fn archive_stale_threads(state: &AppState) -> Result<usize, crate::error::AppError> {
+    let mut archived = 0;
+    for &minutes in AUTO_ARCHIVE_DURATIONS {
+        let cutoff = (chrono::Utc::now() - chrono::Duration::minutes(minutes))
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string();
+        for thread_id in db::threads::list_stale_thread_ids(&state.db, minutes, &cutoff).await? {
+            let update = crate::models::thread::UpdateThread {
+                name: None,
+                archived: Some(true),
+                locked: None,
+                auto_archive_after: None,
+                applied_tags: None,
+            };
+            let row =
+                db::threads::update_thread(&state.db, &thread_id, &update, state.db_is_postgres)
+                    .await?;
+            let json = crate::routes::threads::thread_to_json(state, &row).await;
+            crate::routes::threads::broadcast_thread_event(
+                state,
+                "thread.update",
+                row.space_id.clone(),
+                json,
+            )
+            .await;
+            archived += 1;
+        }
+    }
+    Ok(archived)
+}
@@ -0,0 +1,96 @@
+//! Server-side throttling for `typing.start`.
+//!
+//! Clients re-send typing every few seconds while the user types, and every
+//! send fans out to the whole space. The REST endpoint lets one event per
+//! user per channel (or thread) through each [TYPING_COALESCE_WINDOW] and
+//! swallows the rest; gateway sessions then skip typing events they have no
+//! use for. Both are counted in [TypingStats] for `GET /admin/metrics`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+/// How long a `typing.start` covers. Clients show the indicator for about
+/// ten seconds, so repeats inside this window add nothing.
+pub const TYPING_COALESCE_WINDOW: Duration = Duration::from_secs(8);
+
+/// Entries kept before expired ones are swept out.
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// Running totals since the process started.
+#[derive(Debug, Default)]
+pub struct TypingStats {
+    /// Typing events broadcast to the gateway.
+    pub sent: AtomicU64,
+    /// Typing requests absorbed by an earlier event still in its window.
+    pub coalesced: AtomicU64,
+    /// Per-session deliveries skipped because the session didn't need them.
+    pub dropped: AtomicU64,
+}
+
+impl TypingStats {
+    pub fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "sent": self.sent.load(Ordering::Relaxed),
+            "coalesced": self.coalesced.load(Ordering::Relaxed),
+            "dropped": self.dropped.load(Ordering::Relaxed),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TypingThrottle {
+    /// "user_id:channel_id[:thread_id]" -> when its last event went out
+    last_sent: DashMap<String, Instant>,
+    pub stats: TypingStats,
+}
+
+impl TypingThrottle {
+    /// Whether a typing event from [user_id] in [channel_id] (or one of its
+    /// threads) should be broadcast now. Records it as sent if so.
+    pub fn should_send(&self, user_id: &str, channel_id: &str, thread_id: Option<&str>) -> bool {
+        let key = match thread_id {
+            Some(tid) => format!("{user_id}:{channel_id}:{tid}"),
+            None => format!("{user_id}:{channel_id}"),
+        };
+        let now = Instant::now();
+        if self
+            .last_sent
+            .get(&key)
+            .is_some_and(|at| now.duration_since(*at) < TYPING_COALESCE_WINDOW)
+        {
+            self.stats.coalesced.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        if self.last_sent.len() >= SWEEP_THRESHOLD {
+            self.last_sent
+                .retain(|_, at| now.duration_since(*at) < TYPING_COALESCE_WINDOW);
+        }
+        self.last_sent.insert(key, now);
+        self.stats.sent.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_inside_the_window_are_coalesced() {
+        let throttle = TypingThrottle::default();
+        assert!(throttle.should_send("u1", "c1", None));
+        assert!(!throttle.should_send("u1", "c1", None));
+        // Other users, channels and threads have their own windows.
+        assert!(throttle.should_send("u2", "c1", None));
+        assert!(throttle.should_send("u1", "c2", None));
+        assert!(throttle.should_send("u1", "c1", Some("t1")));
+        assert_eq!(throttle.stats.to_json()["sent"], 4);
+        assert_eq!(throttle.stats.to_json()["coalesced"], 1);
+    }
+}
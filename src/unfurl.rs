@@ -1,9 +1,16 @@
-use crate::models::embed::{Embed, EmbedAuthor, EmbedImage};
-use reqwest::Client;
-use tracing::warn;
+use crate::models::embed::{Embed, EmbedAuthor, EmbedImage, EmbedThumbnail};
+use crate::safe_fetch;
+use tracing::debug;
 
 const MAX_URLS: usize = 5;
 const FETCH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// OpenGraph tags live in <head>; no need to pull more than this.
+const MAX_HTML_BYTES: usize = 512 * 1024;
+/// Longest title and description kept from a page.
+const MAX_TITLE_CHARS: usize = 256;
+const MAX_DESCRIPTION_CHARS: usize = 1024;
+/// Longest URL kept for a preview image.
+const MAX_IMAGE_URL_LEN: usize = 2048;
 
 /// Extract URLs from message text content.
 pub fn extract_urls(content: &str) -> Vec<String> {
@@ -13,6 +20,7 @@ pub fn extract_urls(content: &str) -> Vec<String> {
         let trimmed = word.trim_matches(|c: char| "<>()[]\"'".contains(c));
         if (trimmed.starts_with("http://") || trimmed.starts_with("https://"))
             && trimmed.contains('.')
+            && !urls.iter().any(|u| u == trimmed)
         {
             urls.push(trimmed.to_string());
             if urls.len() >= MAX_URLS {
@@ -24,41 +32,60 @@ pub fn extract_urls(content: &str) -> Vec<String> {
 }
 
 /// Fetch OpenGraph metadata from a URL and build an Embed.
-pub async fn unfurl_url(url: &str, client: &Client) -> Option<Embed> {
-    let response = client
-        .get(url)
-        .header("User-Agent", "AccordBot/1.0 (link preview)")
-        .timeout(FETCH_TIMEOUT)
-        .send()
-        .await
-        .ok()?;
+pub async fn unfurl_url(url: &str) -> Option<Embed> {
+    let opts = safe_fetch::FetchOptions {
+        timeout: FETCH_TIMEOUT,
+        max_bytes: MAX_HTML_BYTES,
+        ..Default::default()
+    };
+    let response = match safe_fetch::get(url, &opts).await {
+        Ok(r) => r,
+        Err(e) => {
+            debug!("Skipping unfurl of {url}: {e}");
+            return None;
+        }
+    };
 
-    let status = response.status();
-    if !status.is_success() {
+    if !(200..300).contains(&response.status) {
         return None;
     }
 
     // Only parse HTML responses
-    let content_type = response
-        .headers()
-        .get("content-type")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("");
-    if !content_type.contains("text/html") {
+    if !response
+        .content_type
+        .as_deref()
+        .unwrap_or("")
+        .contains("text/html")
+    {
         return None;
     }
 
-    let body = response.text().await.ok()?;
-    parse_opengraph(&body, url)
+    let body = String::from_utf8_lossy(&response.body);
+    parse_opengraph(&body, &response.url)
 }
 
-/// Parse OpenGraph meta tags from HTML body.
+/// Cut [s] to at most [max] characters, marking the cut with an ellipsis.
+fn truncate_chars(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut out: String = s.chars().take(max - 1).collect();
+    out.push('…');
+    out
+}
+
+/// Parse OpenGraph meta tags from HTML body, falling back to Twitter card
+/// tags and then `<title>`.
 fn parse_opengraph(html: &str, source_url: &str) -> Option<Embed> {
     let mut og_title: Option<String> = None;
     let mut og_description: Option<String> = None;
     let mut og_image: Option<String> = None;
     let mut og_site_name: Option<String> = None;
     let mut og_type: Option<String> = None;
+    let mut twitter_title: Option<String> = None;
+    let mut twitter_description: Option<String> = None;
+    let mut twitter_image: Option<String> = None;
+    let mut twitter_card: Option<String> = None;
     let mut html_title: Option<String> = None;
 
     // Simple meta tag extraction without a full HTML parser.
@@ -74,10 +101,18 @@ fn parse_opengraph(html: &str, source_url: &str) -> Option<Embed> {
             "og:image" => og_image = Some(content),
             "og:site_name" => og_site_name = Some(content),
             "og:type" => og_type = Some(content),
+            "twitter:title" => twitter_title = Some(content),
+            "twitter:description" => twitter_description = Some(content),
+            "twitter:image" | "twitter:image:src" => twitter_image = Some(content),
+            "twitter:card" => twitter_card = Some(content.to_lowercase()),
             _ => {}
         }
     }
 
+    let og_title = og_title.or(twitter_title);
+    let og_description = og_description.or(twitter_description);
+    let og_image = og_image.or(twitter_image);
+
     // Fallback: try to extract <title> if no og:title
     if og_title.is_none() {
         if let Some(start) = lower.find("<title") {
@@ -93,7 +128,10 @@ fn parse_opengraph(html: &str, source_url: &str) -> Option<Embed> {
         }
     }
 
-    let title = og_title.or(html_title);
+    let title = og_title
+        .or(html_title)
+        .map(|t| truncate_chars(t.trim(), MAX_TITLE_CHARS));
+    let og_description = og_description.map(|d| truncate_chars(d.trim(), MAX_DESCRIPTION_CHARS));
 
     // Need at least a title or description to produce an embed
     if title.is_none() && og_description.is_none() {
@@ -105,14 +143,29 @@ fn parse_opengraph(html: &str, source_url: &str) -> Option<Embed> {
         _ => Some("link".to_string()),
     };
 
-    let image = og_image.map(|img_url| {
-        let resolved = resolve_url(&img_url, source_url);
-        EmbedImage {
-            url: resolved,
-            width: None,
-            height: None,
-        }
-    });
+    // A "summary" card shows a small thumbnail rather than a large image.
+    let image_url = og_image
+        .map(|img_url| resolve_url(&img_url, source_url))
+        .filter(|u| u.len() <= MAX_IMAGE_URL_LEN);
+    let (image, thumbnail) = match (image_url, twitter_card.as_deref()) {
+        (Some(url), Some("summary")) => (
+            None,
+            Some(EmbedThumbnail {
+                url,
+                width: None,
+                height: None,
+            }),
+        ),
+        (Some(url), _) => (
+            Some(EmbedImage {
+                url,
+                width: None,
+                height: None,
+            }),
+            None,
+        ),
+        (None, _) => (None, None),
+    };
 
     let author = og_site_name.map(|name| EmbedAuthor {
         name,
@@ -129,7 +182,7 @@ fn parse_opengraph(html: &str, source_url: &str) -> Option<Embed> {
         color: None,
         footer: None,
         image,
-        thumbnail: None,
+        thumbnail,
         author,
         fields: None,
     })
@@ -231,25 +284,12 @@ pub async fn unfurl_message_urls(content: &str) -> Vec<Embed> {
         return Vec::new();
     }
 
-    let client = match Client::builder()
-        .timeout(FETCH_TIMEOUT)
-        .redirect(reqwest::redirect::Policy::limited(3))
-        .build()
-    {
-        Ok(c) => c,
-        Err(e) => {
-            warn!("Failed to build HTTP client for unfurling: {e}");
-            return Vec::new();
-        }
-    };
-
-    let mut embeds = Vec::new();
-    for url in &urls {
-        if let Some(embed) = unfurl_url(url, &client).await {
-            embeds.push(embed);
-        }
-    }
-    embeds
+    // Fetch in parallel; results keep the order the links appear in.
+    futures_util::future::join_all(urls.iter().map(|url| unfurl_url(url)))
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
 }
 
 #[cfg(test)]
@@ -332,6 +372,47 @@ mod tests {
         assert_eq!(embed.embed_type.as_deref(), Some("video"));
     }
 
+    #[test]
+    fn test_extract_urls_dedupes() {
+        let urls = extract_urls("https://example.com and again https://example.com");
+        assert_eq!(urls, vec!["https://example.com"]);
+    }
+
+    #[test]
+    fn test_parse_twitter_card_fallback() {
+        let html = r#"
+            <html><head>
+                <meta name="twitter:card" content="summary">
+                <meta name="twitter:title" content="Card Title">
+                <meta name="twitter:description" content="Card description">
+                <meta name="twitter:image" content="/thumb.png">
+            </head></html>
+        "#;
+        let embed = parse_opengraph(html, "https://example.com/post").unwrap();
+        assert_eq!(embed.title.as_deref(), Some("Card Title"));
+        assert_eq!(embed.description.as_deref(), Some("Card description"));
+        assert!(embed.image.is_none());
+        assert_eq!(
+            embed.thumbnail.as_ref().unwrap().url,
+            "https://example.com/thumb.png"
+        );
+    }
+
+    #[test]
+    fn test_parse_opengraph_truncates_long_fields() {
+        let html = format!(
+            r#"<meta property="og:title" content="{}"><meta property="og:description" content="{}">"#,
+            "t".repeat(1000),
+            "d".repeat(5000)
+        );
+        let embed = parse_opengraph(&html, "https://example.com").unwrap();
+        assert_eq!(embed.title.unwrap().chars().count(), MAX_TITLE_CHARS);
+        assert_eq!(
+            embed.description.unwrap().chars().count(),
+            MAX_DESCRIPTION_CHARS
+        );
+    }
+
     #[test]
     fn test_resolve_url_absolute() {
         assert_eq!(
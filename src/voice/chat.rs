@@ -0,0 +1,53 @@
+//! Text chat in voice channels, bound to the call: with
+//! `voice_chat_retention` set to `clear_on_empty` or `off`, the chat is
+//! deleted once the last participant leaves.
+
+use crate::db;
+use crate::gateway::events::GatewayBroadcast;
+use crate::models::channel::is_voice_type;
+use crate::state::AppState;
+
+/// Called whenever someone leaves [channel_id]'s call. If nobody is left
+/// and the channel doesn't keep its chat, delete the messages and send
+/// `voice_chat.clear` to the space.
+pub async fn clear_if_empty(state: &AppState, channel_id: &str) {
+    if !super::state::get_channel_voice_states(state, channel_id).is_empty() {
+        return;
+    }
+    let Ok(channel) = db::channels::get_channel_row(&state.db, channel_id).await else {
+        return;
+    };
+    if !is_voice_type(&channel.channel_type) || channel.voice_chat_retention == "keep" {
+        return;
+    }
+
+    let urls = db::attachments::list_channel_attachment_urls(&state.db, channel_id)
+        .await
+        .unwrap_or_default();
+    match db::messages::delete_channel_messages(&state.db, channel_id).await {
+        Ok(0) => return,
+        Ok(_) => {}
+        Err(e) => {
+            tracing::warn!("failed to clear voice chat in {channel_id}: {e:?}");
+            return;
+        }
+    }
+    for url in &urls {
+        let _ = crate::storage::delete_file(state.storage.as_ref(), url).await;
+    }
+
+    if let Some(ref gtx) = *state.gateway_tx.read().await {
+        let event = serde_json::json!({
+            "op": 0,
+            "type": "voice_chat.clear",
+            "data": { "channel_id": channel_id, "space_id": channel.space_id }
+        });
+        let _ = gtx.send(GatewayBroadcast {
+            space_id: channel.space_id.clone(),
+            target_user_ids: None,
+            event,
+            intent: "messages".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
@@ -0,0 +1,52 @@
+//! Background watch on the voice backend, so instance admins hear about
+//! LiveKit going away (and coming back) as `admin.voice_backend_update`
+//! instead of from users whose calls won't connect.
+
+use std::time::Duration;
+
+use crate::gateway::events::GatewayBroadcast;
+use crate::state::AppState;
+use crate::voice::livekit::LiveKitClient;
+
+/// How often LiveKit is probed.
+pub const VOICE_HEALTH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Probe forever, announcing each change in reachability. The first probe
+/// only announces an outage: a healthy start isn't news.
+pub async fn run(state: AppState) {
+    let Some(client) = state.livekit_client.clone() else {
+        return;
+    };
+    let mut online = true;
+    let mut interval = tokio::time::interval(VOICE_HEALTH_INTERVAL);
+    loop {
+        interval.tick().await;
+        online = probe(&state, &client, online).await;
+    }
+}
+
+/// Probe once, announcing the change if reachability differs from
+/// `was_online`. Returns whether the backend is reachable now.
+pub async fn probe(state: &AppState, client: &LiveKitClient, was_online: bool) -> bool {
+    let result = client.check_connectivity().await;
+    let online = result.is_ok();
+    if online == was_online {
+        return online;
+    }
+    match result {
+        Ok(()) => tracing::info!("voice backend reachable again"),
+        Err(ref e) => tracing::warn!("voice backend unreachable: {e}"),
+    }
+    if let Some(ref dispatcher) = *state.gateway_tx.read().await {
+        let _ = dispatcher.send(GatewayBroadcast::admin(
+            "admin.voice_backend_update",
+            serde_json::json!({
+                "backend": "livekit",
+                "url": client.internal_url(),
+                "online": online,
+                "error": result.err(),
+            }),
+        ));
+    }
+    online
+}
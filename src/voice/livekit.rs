@@ -1,7 +1,44 @@
 use crate::error::AppError;
+use crate::models::voice::VoiceState;
 use livekit_api::access_token::{AccessToken, VideoGrants};
-use livekit_api::services::room::{CreateRoomOptions, RoomClient};
+use livekit_api::services::ingress::{CreateIngressOptions, IngressClient};
+use livekit_api::services::room::{CreateRoomOptions, RoomClient, UpdateParticipantOptions};
+use livekit_protocol::{IngressInput, ParticipantPermission, TrackSource};
 use std::sync::Arc;
+use std::time::Duration;
+
+/// How long a voice join token is valid for.
+pub const VOICE_TOKEN_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// What a participant may send and receive. A stage's audience publishes
+/// nothing, a server-muted member publishes no microphone, and a
+/// server-deafened member receives nothing.
+struct MediaRights {
+    publish: bool,
+    microphone: bool,
+    subscribe: bool,
+}
+
+impl MediaRights {
+    fn of(voice_state: &VoiceState) -> Self {
+        Self {
+            publish: !voice_state.suppress,
+            microphone: !voice_state.mute,
+            subscribe: !voice_state.deaf,
+        }
+    }
+
+    /// Sources allowed when publishing. Screen shares never go out under
+    /// the voice identity: they take a separate stream grant
+    /// ([LiveKitClient::generate_stream_token]).
+    fn sources(&self) -> Vec<TrackSource> {
+        if self.microphone {
+            vec![TrackSource::Microphone, TrackSource::Camera]
+        } else {
+            vec![TrackSource::Camera]
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct LiveKitClient {
@@ -9,17 +46,32 @@ pub struct LiveKitClient {
     external_url: String,
     api_key: String,
     api_secret: String,
+    /// Base URL LiveKit fetches this server's `/cdn` files from.
+    media_url: Option<String>,
     room_client: Arc<RoomClient>,
+    ingress_client: Arc<IngressClient>,
 }
 
 impl LiveKitClient {
-    pub fn new(internal_url: &str, external_url: &str, api_key: &str, api_secret: &str) -> Self {
+    pub fn new(
+        internal_url: &str,
+        external_url: &str,
+        api_key: &str,
+        api_secret: &str,
+        media_url: Option<&str>,
+    ) -> Self {
         Self {
             internal_url: internal_url.to_string(),
             external_url: external_url.to_string(),
             api_key: api_key.to_string(),
             api_secret: api_secret.to_string(),
+            media_url: media_url.map(|url| url.trim_end_matches('/').to_string()),
             room_client: Arc::new(RoomClient::with_api_key(internal_url, api_key, api_secret)),
+            ingress_client: Arc::new(IngressClient::with_api_key(
+                internal_url,
+                api_key,
+                api_secret,
+            )),
         }
     }
 
@@ -31,26 +83,83 @@ impl LiveKitClient {
         &self.external_url
     }
 
+    pub fn media_url(&self) -> Option<&str> {
+        self.media_url.as_deref()
+    }
+
     pub fn room_name(channel_id: &str) -> String {
         format!("channel_{channel_id}")
     }
 
+    /// A join token for [channel_id], granting what [voice_state] allows
+    /// (see [MediaRights]), valid for [VOICE_TOKEN_TTL].
     pub fn generate_token(
         &self,
-        user_id: &str,
         display_name: &str,
         channel_id: &str,
+        voice_state: &VoiceState,
     ) -> Result<String, AppError> {
         let room_name = Self::room_name(channel_id);
+        let rights = MediaRights::of(voice_state);
         AccessToken::with_api_key(&self.api_key, &self.api_secret)
-            .with_identity(user_id)
+            .with_identity(&voice_state.user_id)
             .with_name(display_name)
+            .with_ttl(VOICE_TOKEN_TTL)
             .with_grants(VideoGrants {
                 room_join: true,
                 room: room_name,
-                can_publish: true,
-                can_subscribe: true,
+                can_publish: rights.publish,
+                can_subscribe: rights.subscribe,
                 can_publish_data: true,
+                can_publish_sources: rights
+                    .sources()
+                    .iter()
+                    .map(|s| s.as_str_name().to_lowercase())
+                    .collect(),
+                ..Default::default()
+            })
+            .to_jwt()
+            .map_err(|e| AppError::Internal(format!("failed to generate livekit token: {}", e)))
+    }
+
+    /// LiveKit identity a user's screen share publishes under, alongside
+    /// their voice identity.
+    pub fn stream_identity(user_id: &str) -> String {
+        format!("stream_{user_id}")
+    }
+
+    /// A publish-only token for [user_id]'s screen share in [channel_id].
+    /// The space's caps ride along in the participant metadata for the
+    /// client to encode at, and for viewers to see.
+    pub fn generate_stream_token(
+        &self,
+        display_name: &str,
+        channel_id: &str,
+        user_id: &str,
+        max_height: i64,
+        max_fps: i64,
+    ) -> Result<String, AppError> {
+        AccessToken::with_api_key(&self.api_key, &self.api_secret)
+            .with_identity(&Self::stream_identity(user_id))
+            .with_name(display_name)
+            .with_metadata(
+                &serde_json::json!({
+                    "user_id": user_id,
+                    "max_height": max_height,
+                    "max_fps": max_fps
+                })
+                .to_string(),
+            )
+            .with_grants(VideoGrants {
+                room_join: true,
+                room: Self::room_name(channel_id),
+                can_publish: true,
+                can_subscribe: false,
+                can_publish_data: false,
+                can_publish_sources: [TrackSource::ScreenShare, TrackSource::ScreenShareAudio]
+                    .iter()
+                    .map(|s| s.as_str_name().to_lowercase())
+                    .collect(),
                 ..Default::default()
             })
             .to_jwt()
@@ -92,6 +201,67 @@ impl LiveKitClient {
         Ok(())
     }
 
+    /// Bring a connected participant's permissions in line with
+    /// [voice_state], e.g. after a stage promotion or a server mute. LiveKit
+    /// unpublishes tracks and drops subscriptions the new rights don't cover.
+    pub async fn sync_permissions(&self, channel_id: &str, voice_state: &VoiceState) {
+        let room_name = Self::room_name(channel_id);
+        let rights = MediaRights::of(voice_state);
+        let options = UpdateParticipantOptions {
+            permission: Some(ParticipantPermission {
+                can_subscribe: rights.subscribe,
+                can_publish: rights.publish,
+                can_publish_data: true,
+                can_publish_sources: rights.sources().iter().map(|s| *s as i32).collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        if let Err(e) = self
+            .room_client
+            .update_participant(&room_name, &voice_state.user_id, options)
+            .await
+        {
+            tracing::warn!(
+                "Failed to update permissions of {} in {}: {}",
+                voice_state.user_id,
+                room_name,
+                e
+            );
+        }
+    }
+
+    /// Play the audio file at [source_url] once into [channel_id]'s room.
+    /// A URL ingress joins as its own participant, publishes the file and
+    /// is removed by LiveKit when the file ends; `metadata` tells clients
+    /// which sound it is and who played it.
+    pub async fn play_sound(
+        &self,
+        channel_id: &str,
+        sound_id: &str,
+        user_id: &str,
+        source_url: &str,
+    ) -> Result<(), AppError> {
+        let options = CreateIngressOptions {
+            name: format!("soundboard {sound_id}"),
+            room_name: Self::room_name(channel_id),
+            participant_identity: format!("soundboard_{}", crate::snowflake::generate()),
+            participant_name: "Soundboard".to_string(),
+            participant_metadata: serde_json::json!({
+                "sound_id": sound_id,
+                "user_id": user_id
+            })
+            .to_string(),
+            url: source_url.to_string(),
+            ..Default::default()
+        };
+        self.ingress_client
+            .create_ingress(IngressInput::UrlInput, options)
+            .await
+            .map_err(|e| AppError::Internal(format!("failed to play sound in livekit: {}", e)))?;
+        Ok(())
+    }
+
     pub async fn remove_participant(&self, channel_id: &str, user_id: &str) {
         let room_name = Self::room_name(channel_id);
         if let Err(e) = self
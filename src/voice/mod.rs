@@ -1,2 +1,6 @@
+pub mod chat;
+pub mod health;
 pub mod livekit;
 pub mod state;
+pub mod stream;
+pub mod token;
@@ -1,8 +1,10 @@
 use crate::models::voice::VoiceState;
 use crate::state::AppState;
 
-/// Join a voice channel. Returns the new VoiceState and the previous channel_id if the user moved.
-/// `space_id` is `None` for DM/group DM calls, which have no parent space.
+/// Join a voice channel. Returns the new VoiceState and the previous one if
+/// the user moved. `space_id` is `None` for DM/group DM calls, which have no
+/// parent space. `suppress` puts the user in a stage channel's audience.
+/// Nobody joins streaming: that takes a stream grant (see `voice::stream`).
 #[allow(clippy::too_many_arguments)]
 pub fn join_voice_channel(
     state: &AppState,
@@ -13,12 +15,13 @@ pub fn join_voice_channel(
     self_mute: bool,
     self_deaf: bool,
     self_video: bool,
-    self_stream: bool,
-) -> (VoiceState, Option<String>) {
-    let previous_channel = state
+    suppress: bool,
+) -> (VoiceState, Option<VoiceState>) {
+    let previous = state
         .voice_states
         .get(user_id)
-        .and_then(|vs| vs.channel_id.clone());
+        .filter(|vs| vs.channel_id.is_some())
+        .map(|vs| vs.clone());
 
     let voice_state = VoiceState {
         user_id: user_id.to_string(),
@@ -29,39 +32,81 @@ pub fn join_voice_channel(
         mute: false,
         self_deaf,
         self_mute,
-        self_stream,
+        self_stream: false,
         self_video,
-        suppress: false,
+        suppress,
+        request_to_speak_timestamp: None,
+        invited_to_speak: false,
     };
 
     state
         .voice_states
         .insert(user_id.to_string(), voice_state.clone());
 
-    (voice_state, previous_channel)
+    (voice_state, previous)
 }
 
 /// Update an existing voice state's flags in-place without changing channel or session.
 /// Returns the updated VoiceState, or None if the user is not in voice.
+/// `self_stream` isn't one of them: it follows the stream grant.
 pub fn update_voice_state(
     state: &AppState,
     user_id: &str,
     self_mute: bool,
     self_deaf: bool,
     self_video: bool,
-    self_stream: bool,
 ) -> Option<VoiceState> {
     let mut entry = state.voice_states.get_mut(user_id)?;
     let vs = entry.value_mut();
     vs.self_mute = self_mute;
     vs.self_deaf = self_deaf;
     vs.self_video = self_video;
-    vs.self_stream = self_stream;
     Some(vs.clone())
 }
 
+/// Apply [f] to [user_id]'s voice state if they are connected to
+/// [channel_id]. Returns the updated VoiceState.
+pub fn modify_in_channel(
+    state: &AppState,
+    user_id: &str,
+    channel_id: &str,
+    f: impl FnOnce(&mut VoiceState),
+) -> Option<VoiceState> {
+    let mut entry = state.voice_states.get_mut(user_id)?;
+    let vs = entry.value_mut();
+    if vs.channel_id.as_deref() != Some(channel_id) {
+        return None;
+    }
+    f(vs);
+    Some(vs.clone())
+}
+
+/// Carry the member's server mute and deafen over to a voice state they
+/// just joined with, so leaving and rejoining doesn't shed them.
+pub async fn apply_server_flags(state: &AppState, voice_state: VoiceState) -> VoiceState {
+    let (Some(space_id), Some(channel_id)) = (&voice_state.space_id, &voice_state.channel_id)
+    else {
+        return voice_state;
+    };
+    let Ok(member) =
+        crate::db::members::get_member_row(&state.db, space_id, &voice_state.user_id).await
+    else {
+        return voice_state;
+    };
+    if !member.mute && !member.deaf {
+        return voice_state;
+    }
+    modify_in_channel(state, &voice_state.user_id, channel_id, |vs| {
+        vs.mute = member.mute;
+        vs.deaf = member.deaf;
+    })
+    .unwrap_or(voice_state)
+}
+
 /// Leave voice. Returns the old VoiceState if the user was in voice.
 pub fn leave_voice_channel(state: &AppState, user_id: &str) -> Option<VoiceState> {
+    state.voice_stats.remove(user_id);
+    state.voice_tokens.remove(user_id);
     state.voice_states.remove(user_id).map(|(_, vs)| vs)
 }
 
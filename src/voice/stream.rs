@@ -0,0 +1,73 @@
+//! Screen sharing. A stream is published under its own LiveKit identity
+//! with a grant for screen share sources only (the voice grant no longer
+//! covers them), so `self_stream` is set by the server when it hands out
+//! that grant rather than by the client.
+
+use crate::db;
+use crate::gateway::events::GatewayBroadcast;
+use crate::models::voice::VoiceState;
+use crate::state::AppState;
+use crate::voice::livekit::LiveKitClient;
+
+/// Send a voice event to whoever sees [channel_id]'s call: the space, or
+/// for DM calls (`space_id` is `None`) the channel's participants.
+pub async fn broadcast(
+    state: &AppState,
+    channel_id: &str,
+    space_id: Option<&str>,
+    event_type: &str,
+    data: serde_json::Value,
+) {
+    let (space, targets) = match space_id {
+        Some(sid) => (Some(sid.to_string()), None),
+        None => {
+            let ids = db::dm_participants::list_participant_ids(&state.db, channel_id)
+                .await
+                .unwrap_or_default();
+            (None, Some(ids))
+        }
+    };
+    if let Some(ref tx) = *state.gateway_tx.read().await {
+        let _ = tx.send(GatewayBroadcast {
+            space_id: space,
+            target_user_ids: targets,
+            event: serde_json::json!({
+                "op": 0,
+                "type": event_type,
+                "data": data
+            }),
+            intent: "voice_states".to_string(),
+            role_mention_user_ids: None,
+        });
+    }
+}
+
+/// Wind down the stream of a voice state that just ended, if it had one:
+/// disconnect the stream identity and announce `voice.stream_stop`. Call
+/// before deleting the room, which the stream would otherwise keep alive.
+pub async fn end(state: &AppState, old: &VoiceState) {
+    let Some(ref channel_id) = old.channel_id else {
+        return;
+    };
+    if !old.self_stream {
+        return;
+    }
+    if !state.test_mode {
+        if let Some(ref lk) = state.livekit_client {
+            lk.remove_participant(channel_id, &LiveKitClient::stream_identity(&old.user_id))
+                .await;
+        }
+    }
+    broadcast(
+        state,
+        channel_id,
+        old.space_id.as_deref(),
+        "voice.stream_stop",
+        serde_json::json!({
+            "user_id": old.user_id,
+            "channel_id": channel_id,
+            "space_id": old.space_id,
+        }),
+    )
+    .await;
+}
@@ -0,0 +1,127 @@
+//! Voice token lifetimes. LiveKit tokens run out after
+//! [VOICE_TOKEN_TTL](crate::voice::livekit::VOICE_TOKEN_TTL) even while the
+//! call goes on, so every token handed out is tracked, and participants get
+//! a fresh one as `voice.token_refresh` shortly before theirs expires, or on
+//! request from `POST /channels/{id}/voice/refresh-token`.
+
+use std::time::Duration;
+
+use crate::db;
+use crate::error::AppError;
+use crate::gateway::events::GatewayBroadcast;
+use crate::models::voice::{VoiceState, VoiceTokenGrant};
+use crate::state::AppState;
+use crate::voice::livekit::{LiveKitClient, VOICE_TOKEN_TTL};
+
+/// How long before expiry a replacement token is pushed.
+pub const REFRESH_LEAD_SECS: i64 = 5 * 60;
+
+/// How often grants are checked for upcoming expiry.
+pub const REFRESH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A join token for [voice_state]'s session, remembering when it expires.
+pub fn issue(
+    state: &AppState,
+    lk: &LiveKitClient,
+    display_name: &str,
+    channel_id: &str,
+    voice_state: &VoiceState,
+) -> Result<(String, chrono::DateTime<chrono::Utc>), AppError> {
+    let token = lk.generate_token(display_name, channel_id, voice_state)?;
+    let expires_at = chrono::Utc::now()
+        + chrono::Duration::from_std(VOICE_TOKEN_TTL).unwrap_or(chrono::Duration::zero());
+    state.voice_tokens.insert(
+        voice_state.user_id.clone(),
+        VoiceTokenGrant {
+            session_id: voice_state.session_id.clone(),
+            channel_id: channel_id.to_string(),
+            expires_at,
+        },
+    );
+    Ok((token, expires_at))
+}
+
+/// A new token for the session [voice_state] is in, as the payload of
+/// `voice.token_refresh` and the refresh endpoint.
+pub async fn refresh(
+    state: &AppState,
+    voice_state: &VoiceState,
+) -> Result<serde_json::Value, AppError> {
+    let channel_id = voice_state
+        .channel_id
+        .as_deref()
+        .ok_or_else(|| AppError::BadRequest("not_in_voice_channel".to_string()))?;
+    let lk = state
+        .livekit_client
+        .as_ref()
+        .ok_or_else(|| AppError::BadRequest("voice_not_configured".to_string()))?;
+    let user = db::users::get_user(&state.db, &voice_state.user_id).await?;
+    let display_name = user.display_name.as_deref().unwrap_or(&user.username);
+    let (token, expires_at) = issue(state, lk, display_name, channel_id, voice_state)?;
+    Ok(serde_json::json!({
+        "space_id": voice_state.space_id,
+        "channel_id": channel_id,
+        "session_id": voice_state.session_id,
+        "backend": "livekit",
+        "livekit_url": lk.external_url(),
+        "token": token,
+        "expires_at": expires_at.to_rfc3339(),
+    }))
+}
+
+/// Push a replacement token to everyone whose token is about to run out,
+/// forever.
+pub async fn run(state: AppState) {
+    if state.livekit_client.is_none() {
+        return;
+    }
+    let mut interval = tokio::time::interval(REFRESH_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        refresh_expiring(&state).await;
+    }
+}
+
+/// Push a replacement token to everyone whose token runs out within
+/// [REFRESH_LEAD_SECS]. Grants for sessions that have ended are dropped.
+pub async fn refresh_expiring(state: &AppState) {
+    let due = chrono::Utc::now() + chrono::Duration::seconds(REFRESH_LEAD_SECS);
+    let expiring: Vec<(String, VoiceTokenGrant)> = state
+        .voice_tokens
+        .iter()
+        .filter(|g| g.expires_at <= due)
+        .map(|g| (g.key().clone(), g.value().clone()))
+        .collect();
+    for (user_id, grant) in expiring {
+        let current = crate::voice::state::get_user_voice_state(state, &user_id).filter(|vs| {
+            vs.session_id == grant.session_id
+                && vs.channel_id.as_deref() == Some(grant.channel_id.as_str())
+        });
+        let Some(voice_state) = current else {
+            state
+                .voice_tokens
+                .remove_if(&user_id, |_, g| g.session_id == grant.session_id);
+            continue;
+        };
+        let data = match refresh(state, &voice_state).await {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::warn!("failed to refresh voice token for {user_id}: {e:?}");
+                continue;
+            }
+        };
+        if let Some(ref tx) = *state.gateway_tx.read().await {
+            let _ = tx.send(GatewayBroadcast {
+                space_id: None,
+                target_user_ids: Some(vec![user_id]),
+                event: serde_json::json!({
+                    "op": 0,
+                    "type": "voice.token_refresh",
+                    "data": data
+                }),
+                intent: "voice_states".to_string(),
+                role_mention_user_ids: None,
+            });
+        }
+    }
+}
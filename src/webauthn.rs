@@ -0,0 +1,524 @@
+//! Passkeys: the relying-party half of WebAuthn registration and login.
+//!
+//! Clients run the ceremonies with `navigator.credentials` (or a platform
+//! equivalent) using the options the `/auth/passkeys` routes hand out, and
+//! send back the authenticator's response with binary fields base64url
+//! encoded. Only `none` attestation is requested, so attestation statements
+//! are not checked; what matters is that later assertions are signed by the
+//! key registered here. ES256 and EdDSA keys are supported.
+
+use data_encoding::BASE64URL_NOPAD;
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::WebAuthnConfig;
+use crate::error::AppError;
+
+/// COSE algorithm identifiers offered to authenticators, in preference order.
+pub const ALG_ES256: i64 = -7;
+pub const ALG_EDDSA: i64 = -8;
+
+/// How long a ceremony's challenge can be answered for.
+pub const CHALLENGE_TTL_SECS: i64 = 300;
+
+/// Passed to the client as the ceremony timeout, in milliseconds.
+const CEREMONY_TIMEOUT_MS: i64 = CHALLENGE_TTL_SECS * 1000;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+/// A fresh random challenge, base64url encoded as it appears in
+/// `clientDataJSON`.
+pub fn generate_challenge() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE64URL_NOPAD.encode(&bytes)
+}
+
+pub fn encode(bytes: &[u8]) -> String {
+    BASE64URL_NOPAD.encode(bytes)
+}
+
+pub fn decode(field: &str, value: &str) -> Result<Vec<u8>, AppError> {
+    // Some clients keep the padding; the spec's JSON encoding omits it.
+    BASE64URL_NOPAD
+        .decode(value.trim_end_matches('=').as_bytes())
+        .map_err(|_| AppError::BadRequest(format!("{field} is not valid base64url")))
+}
+
+/// `PublicKeyCredentialCreationOptions` for registering a passkey for
+/// `user_id`. `exclude` holds the user's existing credential IDs so the same
+/// authenticator isn't registered twice.
+pub fn creation_options(
+    config: &WebAuthnConfig,
+    challenge: &str,
+    user_id: &str,
+    username: &str,
+    display_name: &str,
+    exclude: &[String],
+) -> serde_json::Value {
+    serde_json::json!({
+        "challenge": challenge,
+        "rp": { "id": config.rp_id, "name": config.rp_name },
+        "user": {
+            "id": encode(user_id.as_bytes()),
+            "name": username,
+            "displayName": display_name
+        },
+        "pubKeyCredParams": [
+            { "type": "public-key", "alg": ALG_ES256 },
+            { "type": "public-key", "alg": ALG_EDDSA }
+        ],
+        "excludeCredentials": exclude
+            .iter()
+            .map(|id| serde_json::json!({ "type": "public-key", "id": id }))
+            .collect::<Vec<_>>(),
+        "authenticatorSelection": {
+            "residentKey": "preferred",
+            "userVerification": "preferred"
+        },
+        "attestation": "none",
+        "timeout": CEREMONY_TIMEOUT_MS
+    })
+}
+
+/// `PublicKeyCredentialRequestOptions` for signing in. An empty `allow`
+/// lets the authenticator offer any discoverable passkey for this site.
+pub fn request_options(
+    config: &WebAuthnConfig,
+    challenge: &str,
+    allow: &[String],
+    user_verification: &str,
+) -> serde_json::Value {
+    serde_json::json!({
+        "challenge": challenge,
+        "rpId": config.rp_id,
+        "allowCredentials": allow
+            .iter()
+            .map(|id| serde_json::json!({ "type": "public-key", "id": id }))
+            .collect::<Vec<_>>(),
+        "userVerification": user_verification,
+        "timeout": CEREMONY_TIMEOUT_MS
+    })
+}
+
+/// The `response` of a `PublicKeyCredential` from `create()`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub attestation_object: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegistrationCredential {
+    pub id: String,
+    pub response: AttestationResponse,
+}
+
+/// The `response` of a `PublicKeyCredential` from `get()`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub authenticator_data: String,
+    pub signature: String,
+    pub user_handle: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssertionCredential {
+    pub id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+}
+
+/// Parses `clientDataJSON` far enough to find which challenge it answers.
+/// The rest is checked by [verify_registration] and [verify_assertion].
+pub fn client_data_challenge(client_data_json: &str) -> Result<String, AppError> {
+    let raw = decode("clientDataJSON", client_data_json)?;
+    let data: ClientData = serde_json::from_slice(&raw)
+        .map_err(|_| AppError::BadRequest("clientDataJSON is malformed".to_string()))?;
+    Ok(data.challenge)
+}
+
+fn check_client_data(
+    config: &WebAuthnConfig,
+    raw: &[u8],
+    kind: &str,
+    challenge: &str,
+) -> Result<(), AppError> {
+    let data: ClientData = serde_json::from_slice(raw)
+        .map_err(|_| AppError::BadRequest("clientDataJSON is malformed".to_string()))?;
+    if data.kind != kind {
+        return Err(AppError::BadRequest(format!(
+            "clientDataJSON type must be {kind}"
+        )));
+    }
+    if data.challenge != challenge {
+        return Err(AppError::Unauthorized(
+            "passkey response is for a different challenge".to_string(),
+        ));
+    }
+    if !config.origins.iter().any(|o| o == &data.origin) {
+        return Err(AppError::Unauthorized(format!(
+            "passkey origin {} is not allowed",
+            data.origin
+        )));
+    }
+    Ok(())
+}
+
+/// The fixed-size head of `authenticatorData`, after checking it was made
+/// for this relying party with the user present.
+struct AuthenticatorData<'a> {
+    flags: u8,
+    sign_count: u32,
+    rest: &'a [u8],
+}
+
+fn parse_authenticator_data<'a>(
+    config: &WebAuthnConfig,
+    data: &'a [u8],
+) -> Result<AuthenticatorData<'a>, AppError> {
+    if data.len() < 37 {
+        return Err(AppError::BadRequest(
+            "authenticator data is too short".to_string(),
+        ));
+    }
+    if data[..32] != Sha256::digest(config.rp_id.as_bytes())[..] {
+        return Err(AppError::Unauthorized(
+            "passkey was created for a different site".to_string(),
+        ));
+    }
+    let flags = data[32];
+    if flags & FLAG_USER_PRESENT == 0 {
+        return Err(AppError::Unauthorized(
+            "authenticator did not confirm user presence".to_string(),
+        ));
+    }
+    Ok(AuthenticatorData {
+        flags,
+        sign_count: u32::from_be_bytes([data[33], data[34], data[35], data[36]]),
+        rest: &data[37..],
+    })
+}
+
+/// A credential that passed registration, ready to store.
+#[derive(Debug)]
+pub struct NewCredential {
+    pub credential_id: String,
+    pub algorithm: i64,
+    /// SEC1 point for ES256, the raw 32-byte key for EdDSA.
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+/// Checks a `create()` response against `challenge` and extracts the new
+/// credential's public key.
+pub fn verify_registration(
+    config: &WebAuthnConfig,
+    challenge: &str,
+    credential: &RegistrationCredential,
+) -> Result<NewCredential, AppError> {
+    let client_data = decode("clientDataJSON", &credential.response.client_data_json)?;
+    check_client_data(config, &client_data, "webauthn.create", challenge)?;
+
+    let attestation = decode("attestationObject", &credential.response.attestation_object)?;
+    let attestation: ciborium::value::Value = ciborium::de::from_reader(&attestation[..])
+        .map_err(|_| AppError::BadRequest("attestationObject is not valid CBOR".to_string()))?;
+    let auth_data = cbor_entry(&attestation, "authData")
+        .and_then(|v| v.as_bytes())
+        .ok_or_else(|| AppError::BadRequest("attestationObject has no authData".to_string()))?;
+
+    let parsed = parse_authenticator_data(config, auth_data)?;
+    if parsed.flags & FLAG_ATTESTED_CREDENTIAL == 0 || parsed.rest.len() < 18 {
+        return Err(AppError::BadRequest(
+            "authenticator data has no credential".to_string(),
+        ));
+    }
+    // 16-byte AAGUID, 2-byte length, credential ID, then the COSE key.
+    let id_len = u16::from_be_bytes([parsed.rest[16], parsed.rest[17]]) as usize;
+    let after_len = &parsed.rest[18..];
+    if after_len.len() < id_len {
+        return Err(AppError::BadRequest(
+            "authenticator data is truncated".to_string(),
+        ));
+    }
+    let (credential_id, cose_key) = after_len.split_at(id_len);
+    let credential_id = encode(credential_id);
+    if decode("id", &credential.id)? != decode("id", &credential_id)? {
+        return Err(AppError::BadRequest(
+            "credential id does not match authenticator data".to_string(),
+        ));
+    }
+
+    let cose_key: ciborium::value::Value = ciborium::de::from_reader(cose_key)
+        .map_err(|_| AppError::BadRequest("credential public key is not valid CBOR".to_string()))?;
+    let (algorithm, public_key) = parse_cose_key(&cose_key)?;
+
+    Ok(NewCredential {
+        credential_id,
+        algorithm,
+        public_key,
+        sign_count: parsed.sign_count,
+    })
+}
+
+/// What a verified assertion tells us about the authenticator.
+#[derive(Debug)]
+pub struct VerifiedAssertion {
+    pub sign_count: u32,
+    pub user_verified: bool,
+}
+
+/// Checks a `get()` response against `challenge` and the stored key.
+pub fn verify_assertion(
+    config: &WebAuthnConfig,
+    challenge: &str,
+    algorithm: i64,
+    public_key: &[u8],
+    credential: &AssertionCredential,
+) -> Result<VerifiedAssertion, AppError> {
+    let client_data = decode("clientDataJSON", &credential.response.client_data_json)?;
+    check_client_data(config, &client_data, "webauthn.get", challenge)?;
+
+    let auth_data = decode("authenticatorData", &credential.response.authenticator_data)?;
+    let parsed = parse_authenticator_data(config, &auth_data)?;
+    let signature = decode("signature", &credential.response.signature)?;
+
+    let mut signed = auth_data.clone();
+    signed.extend_from_slice(&Sha256::digest(&client_data));
+    verify_signature(algorithm, public_key, &signed, &signature)?;
+
+    Ok(VerifiedAssertion {
+        sign_count: parsed.sign_count,
+        user_verified: parsed.flags & FLAG_USER_VERIFIED != 0,
+    })
+}
+
+fn verify_signature(
+    algorithm: i64,
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), AppError> {
+    let invalid = || AppError::Unauthorized("invalid passkey signature".to_string());
+    match algorithm {
+        ALG_ES256 => {
+            use p256::ecdsa::signature::Verifier;
+            let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key)
+                .map_err(|_| AppError::Internal("stored passkey key is invalid".to_string()))?;
+            let sig = p256::ecdsa::Signature::from_der(signature).map_err(|_| invalid())?;
+            key.verify(message, &sig).map_err(|_| invalid())
+        }
+        ALG_EDDSA => {
+            use ed25519_dalek::Verifier;
+            let bytes: [u8; 32] = public_key
+                .try_into()
+                .map_err(|_| AppError::Internal("stored passkey key is invalid".to_string()))?;
+            let key = ed25519_dalek::VerifyingKey::from_bytes(&bytes)
+                .map_err(|_| AppError::Internal("stored passkey key is invalid".to_string()))?;
+            let sig = ed25519_dalek::Signature::from_slice(signature).map_err(|_| invalid())?;
+            key.verify(message, &sig).map_err(|_| invalid())
+        }
+        other => Err(AppError::Internal(format!(
+            "unsupported passkey algorithm {other}"
+        ))),
+    }
+}
+
+fn cbor_entry<'a>(
+    map: &'a ciborium::value::Value,
+    key: &str,
+) -> Option<&'a ciborium::value::Value> {
+    map.as_map()?
+        .iter()
+        .find(|(k, _)| k.as_text() == Some(key))
+        .map(|(_, v)| v)
+}
+
+fn cose_entry(map: &ciborium::value::Value, label: i128) -> Option<&ciborium::value::Value> {
+    map.as_map()?
+        .iter()
+        .find(|(k, _)| k.as_integer().map(i128::from) == Some(label))
+        .map(|(_, v)| v)
+}
+
+/// Reads a COSE_Key into its algorithm and the key bytes we verify with.
+fn parse_cose_key(key: &ciborium::value::Value) -> Result<(i64, Vec<u8>), AppError> {
+    let unsupported =
+        || AppError::BadRequest("passkey must use ES256 (P-256) or EdDSA (Ed25519)".to_string());
+    let int = |label| {
+        cose_entry(key, label)
+            .and_then(|v| v.as_integer())
+            .map(i128::from)
+    };
+    let bytes = |label| cose_entry(key, label).and_then(|v| v.as_bytes());
+
+    // Labels: 1 kty, 3 alg, -1 crv, -2 x, -3 y.
+    match (int(1), int(3).map(|a| a as i64), int(-1)) {
+        (Some(2), Some(ALG_ES256), Some(1)) => {
+            let (x, y) = bytes(-2).zip(bytes(-3)).ok_or_else(unsupported)?;
+            if x.len() != 32 || y.len() != 32 {
+                return Err(unsupported());
+            }
+            let mut point = Vec::with_capacity(65);
+            point.push(0x04);
+            point.extend_from_slice(x);
+            point.extend_from_slice(y);
+            p256::ecdsa::VerifyingKey::from_sec1_bytes(&point).map_err(|_| unsupported())?;
+            Ok((ALG_ES256, point))
+        }
+        (Some(1), Some(ALG_EDDSA), Some(6)) => {
+            let x = bytes(-2)
+                .filter(|x| x.len() == 32)
+                .ok_or_else(unsupported)?;
+            Ok((ALG_EDDSA, x.clone()))
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ciborium::value::Value;
+    use p256::ecdsa::signature::Signer;
+
+    fn config() -> WebAuthnConfig {
+        WebAuthnConfig {
+            rp_id: "accord.test".to_string(),
+            rp_name: "Accord".to_string(),
+            origins: vec!["https://accord.test".to_string()],
+        }
+    }
+
+    fn client_data(kind: &str, challenge: &str, origin: &str) -> String {
+        encode(
+            serde_json::json!({ "type": kind, "challenge": challenge, "origin": origin })
+                .to_string()
+                .as_bytes(),
+        )
+    }
+
+    fn auth_data(rp_id: &str, flags: u8, sign_count: u32) -> Vec<u8> {
+        let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        data
+    }
+
+    fn register(key: &p256::ecdsa::SigningKey, challenge: &str) -> RegistrationCredential {
+        let point = key.verifying_key().to_encoded_point(false);
+        let cose = Value::Map(vec![
+            (Value::from(1), Value::from(2)),
+            (Value::from(3), Value::from(ALG_ES256)),
+            (Value::from(-1), Value::from(1)),
+            (Value::from(-2), Value::Bytes(point.x().unwrap().to_vec())),
+            (Value::from(-3), Value::Bytes(point.y().unwrap().to_vec())),
+        ]);
+        let credential_id = b"credential-1";
+        let mut data = auth_data("accord.test", 0x45, 0);
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+        data.extend_from_slice(credential_id);
+        ciborium::ser::into_writer(&cose, &mut data).unwrap();
+        let mut attestation = Vec::new();
+        ciborium::ser::into_writer(
+            &Value::Map(vec![
+                (Value::from("fmt"), Value::from("none")),
+                (Value::from("attStmt"), Value::Map(vec![])),
+                (Value::from("authData"), Value::Bytes(data)),
+            ]),
+            &mut attestation,
+        )
+        .unwrap();
+        RegistrationCredential {
+            id: encode(credential_id),
+            response: AttestationResponse {
+                client_data_json: client_data("webauthn.create", challenge, "https://accord.test"),
+                attestation_object: encode(&attestation),
+            },
+        }
+    }
+
+    fn assert_with(
+        key: &p256::ecdsa::SigningKey,
+        challenge: &str,
+        origin: &str,
+    ) -> AssertionCredential {
+        let client_data_json = client_data("webauthn.get", challenge, origin);
+        let data = auth_data("accord.test", 0x05, 1);
+        let mut signed = data.clone();
+        signed.extend_from_slice(&Sha256::digest(decode("", &client_data_json).unwrap()));
+        let signature: p256::ecdsa::Signature = key.sign(&signed);
+        AssertionCredential {
+            id: encode(b"credential-1"),
+            response: AssertionResponse {
+                client_data_json,
+                authenticator_data: encode(&data),
+                signature: encode(signature.to_der().as_bytes()),
+                user_handle: None,
+            },
+        }
+    }
+
+    #[test]
+    fn registered_es256_key_verifies_assertions() {
+        let key = p256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        let created = verify_registration(&config(), "reg", &register(&key, "reg")).unwrap();
+        assert_eq!(created.algorithm, ALG_ES256);
+        assert_eq!(created.credential_id, encode(b"credential-1"));
+
+        let verified = verify_assertion(
+            &config(),
+            "login",
+            created.algorithm,
+            &created.public_key,
+            &assert_with(&key, "login", "https://accord.test"),
+        )
+        .unwrap();
+        assert_eq!(verified.sign_count, 1);
+        assert!(verified.user_verified);
+    }
+
+    #[test]
+    fn assertions_are_bound_to_challenge_origin_and_key() {
+        let key = p256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        let created = verify_registration(&config(), "reg", &register(&key, "reg")).unwrap();
+        let check = |credential: &AssertionCredential| {
+            verify_assertion(
+                &config(),
+                "login",
+                created.algorithm,
+                &created.public_key,
+                credential,
+            )
+        };
+
+        assert!(check(&assert_with(&key, "other", "https://accord.test")).is_err());
+        assert!(check(&assert_with(&key, "login", "https://evil.test")).is_err());
+        let other_key = p256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        assert!(check(&assert_with(&other_key, "login", "https://accord.test")).is_err());
+    }
+
+    #[test]
+    fn registration_for_another_rp_is_rejected() {
+        let key = p256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        let mut other = config();
+        other.rp_id = "elsewhere.test".to_string();
+        assert!(verify_registration(&other, "reg", &register(&key, "reg")).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
 use accordserver::db;
+use accordserver::db::DbPool;
 use accordserver::gateway::dispatcher::Dispatcher;
 use accordserver::middleware::auth::{create_token_hash, generate_token};
 use accordserver::models::user::{CreateUser, User};
@@ -12,7 +13,6 @@ use arc_swap::ArcSwap;
 use axum::body::Body;
 use dashmap::DashMap;
 use http::{Method, Request};
-use sqlx::AnyPool;
 use std::sync::Arc;
 use tokio::sync::{Mutex, RwLock};
 
@@ -63,30 +63,72 @@ impl TestServer {
             // Truncate all application tables (order doesn't matter with CASCADE).
             // server_settings is re-created by get_settings() below.
             for table in &[
+                "thread_members",
+                "threads",
                 "read_states",
                 "reactions",
+                "poll_votes",
+                "polls",
                 "pinned_messages",
+                "message_shares",
                 "attachments",
+                "ephemeral_messages",
                 "messages",
+                "webhooks",
                 "permission_overwrites",
+                "channel_locks",
                 "channel_mutes",
+                "everyone_suppressions",
+                "space_mutes",
+                "channel_unmutes",
                 "dm_participants",
                 "member_roles",
                 "space_introductions",
                 "members",
                 "bans",
+                "ban_sync_group_spaces",
+                "ban_sync_groups",
+                "invite_join_tokens",
                 "invites",
+                "revoked_vanity_codes",
+                "invite_uses",
+                "welcome_screens",
+                "member_screenings",
+                "space_raid_protection",
+                "space_email_domains",
+                "message_stickers",
+                "stickers",
                 "emoji_roles",
                 "emojis",
                 "soundboard_sounds",
+                "interactions",
+                "application_commands",
+                "oauth2_codes",
+                "oauth2_tokens",
                 "bot_tokens",
+                "event_deliveries",
+                "event_subscriptions",
+                "application_signing_keys",
                 "applications",
+                "email_tokens",
                 "user_tokens",
                 "backup_codes",
+                "passkeys",
+                "push_subscriptions",
                 "channels",
                 "roles",
                 "reports",
                 "relationships",
+                "user_settings",
+                "user_space_layouts",
+                "automod_rules",
+                "automod_hits",
+                "channel_collapses",
+                "message_drafts",
+                "message_revisions",
+                "instance_blocklist",
+                "content_safety_flags",
+                "content_safety_hashes",
                 "federation_peers",
                 "federation_inbox_dedup",
                 "federation_outbox",
@@ -120,6 +162,7 @@ impl TestServer {
             "ws://localhost:7880",
             "devkey",
             "secret",
+            None,
         ));
 
         let settings = db::settings::get_settings(&pool).await.unwrap_or_default();
@@ -128,12 +171,18 @@ impl TestServer {
             db: pool,
             db_is_postgres: is_postgres,
             voice_states: Arc::new(DashMap::new()),
+            voice_stats: Arc::new(DashMap::new()),
+            voice_tokens: Arc::new(DashMap::new()),
             presences: Arc::new(DashMap::new()),
+            shared_presence: None,
             dispatcher: Arc::new(RwLock::new(Some(dispatcher))),
             gateway_tx: Arc::new(RwLock::new(Some(gateway_tx))),
             test_mode: true,
             livekit_client,
             rate_limits: Arc::new(DashMap::new()),
+            bot_rate_limits: Arc::new(DashMap::new()),
+            typing: Arc::new(Default::default()),
+            storage: Arc::new(storage::LocalStorage::new(storage_path.clone())),
             storage_path,
             update_status_path: None,
             settings: Arc::new(ArcSwap::from_pointee(settings)),
@@ -141,6 +190,13 @@ impl TestServer {
             master_task: Arc::new(Mutex::new(None)),
             federation: None,
             mfa_tickets: Arc::new(DashMap::new()),
+            login_tickets: Arc::new(DashMap::new()),
+            passkey_challenges: Arc::new(DashMap::new()),
+            webauthn: Some(Arc::new(accordserver::config::WebAuthnConfig {
+                rp_id: "localhost".to_string(),
+                rp_name: "Accord".to_string(),
+                origins: vec!["http://localhost".to_string()],
+            })),
             totp_attempts: Arc::new(DashMap::new()),
             totp_key: None,
             mcp_api_key: None,
@@ -148,6 +204,16 @@ impl TestServer {
             register_attempts: Arc::new(DashMap::new()),
             guest_attempts: Arc::new(DashMap::new()),
             guest_counts: Arc::new(DashMap::new()),
+            cors: Arc::new(accordserver::config::CorsConfig::default()),
+            mailer: None,
+            push: Some(Arc::new(
+                accordserver::push::Notifier::new(&accordserver::config::PushConfig {
+                    vapid_private_key: "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE".to_string(),
+                    subject: Some("mailto:admin@localhost".to_string()),
+                })
+                .unwrap(),
+            )),
+            require_verified_email: false,
         };
 
         Self { state }
@@ -173,7 +239,7 @@ impl TestServer {
     }
 
     /// Returns a reference to the underlying database pool.
-    pub fn pool(&self) -> &AnyPool {
+    pub fn pool(&self) -> &DbPool {
         &self.state.db
     }
 
@@ -308,6 +374,8 @@ impl TestServer {
                 description: None,
                 public: None,
                 allow_guest_access: None,
+                preferred_locale: None,
+                template: None,
             },
         )
         .await
@@ -326,6 +394,8 @@ impl TestServer {
                 description: None,
                 public: Some(true),
                 allow_guest_access: None,
+                preferred_locale: None,
+                template: None,
             },
         )
         .await
@@ -455,6 +525,104 @@ impl TestServer {
     }
 }
 
+/// A software authenticator holding one ES256 passkey for `localhost`, the
+/// relying party test servers are configured with.
+pub struct SoftPasskey {
+    key: p256::ecdsa::SigningKey,
+    pub credential_id: Vec<u8>,
+    pub sign_count: u32,
+}
+
+impl SoftPasskey {
+    pub fn new() -> Self {
+        let mut credential_id = vec![0u8; 16];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut credential_id);
+        Self {
+            key: p256::ecdsa::SigningKey::random(&mut rand::thread_rng()),
+            credential_id,
+            sign_count: 0,
+        }
+    }
+
+    pub fn b64(bytes: &[u8]) -> String {
+        data_encoding::BASE64URL_NOPAD.encode(bytes)
+    }
+
+    fn client_data(kind: &str, challenge: &str) -> Vec<u8> {
+        serde_json::json!({ "type": kind, "challenge": challenge, "origin": "http://localhost" })
+            .to_string()
+            .into_bytes()
+    }
+
+    fn auth_data(&self, flags: u8) -> Vec<u8> {
+        use sha2::Digest;
+        let mut data = sha2::Sha256::digest(b"localhost").to_vec();
+        data.push(flags);
+        data.extend_from_slice(&self.sign_count.to_be_bytes());
+        data
+    }
+
+    /// Answers creation options like `navigator.credentials.create()`.
+    pub fn create(&self, options: &serde_json::Value) -> serde_json::Value {
+        use ciborium::value::Value;
+        let challenge = options["publicKey"]["challenge"].as_str().unwrap();
+        let point = self.key.verifying_key().to_encoded_point(false);
+        let cose = Value::Map(vec![
+            (Value::from(1), Value::from(2)),
+            (Value::from(3), Value::from(-7)),
+            (Value::from(-1), Value::from(1)),
+            (Value::from(-2), Value::Bytes(point.x().unwrap().to_vec())),
+            (Value::from(-3), Value::Bytes(point.y().unwrap().to_vec())),
+        ]);
+        let mut data = self.auth_data(0x45);
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+        data.extend_from_slice(&self.credential_id);
+        ciborium::ser::into_writer(&cose, &mut data).unwrap();
+        let mut attestation = Vec::new();
+        ciborium::ser::into_writer(
+            &Value::Map(vec![
+                (Value::from("fmt"), Value::from("none")),
+                (Value::from("attStmt"), Value::Map(vec![])),
+                (Value::from("authData"), Value::Bytes(data)),
+            ]),
+            &mut attestation,
+        )
+        .unwrap();
+        serde_json::json!({
+            "id": Self::b64(&self.credential_id),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": Self::b64(&Self::client_data("webauthn.create", challenge)),
+                "attestationObject": Self::b64(&attestation)
+            }
+        })
+    }
+
+    /// Answers request options like `navigator.credentials.get()`, with the
+    /// user verified.
+    pub fn get(&mut self, options: &serde_json::Value) -> serde_json::Value {
+        use p256::ecdsa::signature::Signer;
+        use sha2::Digest;
+        let challenge = options["publicKey"]["challenge"].as_str().unwrap();
+        self.sign_count += 1;
+        let client_data = Self::client_data("webauthn.get", challenge);
+        let data = self.auth_data(0x05);
+        let mut signed = data.clone();
+        signed.extend_from_slice(&sha2::Sha256::digest(&client_data));
+        let signature: p256::ecdsa::Signature = self.key.sign(&signed);
+        serde_json::json!({
+            "id": Self::b64(&self.credential_id),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": Self::b64(&client_data),
+                "authenticatorData": Self::b64(&data),
+                "signature": Self::b64(signature.to_der().as_bytes())
+            }
+        })
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Request builder helpers
 // ---------------------------------------------------------------------------
@@ -495,6 +663,18 @@ pub fn json_request(method: Method, uri: &str, body: &serde_json::Value) -> Requ
         .unwrap()
 }
 
+/// Build an unauthenticated `application/x-www-form-urlencoded` POST. Values
+/// must already be URL-safe.
+pub fn form_request(uri: &str, fields: &[(&str, &str)]) -> Request<Body> {
+    let body: Vec<String> = fields.iter().map(|(k, v)| format!("{k}={v}")).collect();
+    Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(Body::from(body.join("&")))
+        .unwrap()
+}
+
 /// Parse a response body into a `serde_json::Value`.
 pub async fn parse_body(response: axum::response::Response) -> serde_json::Value {
     let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
@@ -503,6 +683,32 @@ pub async fn parse_body(response: axum::response::Response) -> serde_json::Value
     serde_json::from_slice(&bytes).unwrap()
 }
 
+/// Queries a request issued, from the `X-Query-Count` header the server adds
+/// in test mode.
+pub fn query_count(response: &axum::response::Response) -> usize {
+    response
+        .headers()
+        .get(accordserver::middleware::query_count::QUERY_COUNT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .expect("test-mode responses carry a query count")
+}
+
+/// Queries it took to build a READY payload (test mode adds `_trace`).
+pub fn ready_query_count(ready: &serde_json::Value) -> usize {
+    ready["data"]["_trace"]["queries"]
+        .as_u64()
+        .expect("test-mode READY carries a query count") as usize
+}
+
+/// Fail when [what] issued more than [budget] queries.
+pub fn assert_query_budget(what: &str, queries: usize, budget: usize) {
+    assert!(
+        queries <= budget,
+        "{what} issued {queries} queries, over its budget of {budget}"
+    );
+}
+
 // ---------------------------------------------------------------------------
 // Backward-compatible shim for existing tests
 // ---------------------------------------------------------------------------
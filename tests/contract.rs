@@ -0,0 +1,2619 @@
+//! Payload contract tests: a scripted session whose REST responses and
+//! gateway events must match `schema/catalog.json` in shape, and a check
+//! that each registered route and event type has a catalog entry. The
+//! catalog is inferred from this session's payloads, so its shapes are only
+//! as complete as the session.
+//!
+//! After an intended change, regenerate the catalog with
+//! `UPDATE_SCHEMA=1 cargo test --test contract` and commit the diff. A new
+//! route or event type needs a step in the session before it will pass.
+
+mod common;
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use accordserver::config::{SmtpConfig, SmtpTls};
+use accordserver::db::email_tokens::{self, KIND_PASSWORD_RESET, KIND_VERIFY_EMAIL};
+use accordserver::email::Mailer;
+use accordserver::gateway::events::GatewayBroadcast;
+use accordserver::schema::CatalogBuilder;
+use axum::body::Body;
+use axum::extract::MatchedPath;
+use axum::middleware::Next;
+use axum::response::Response;
+use common::{SoftPasskey, TestServer, TestUser};
+use futures_util::{SinkExt, StreamExt};
+use http::{header, Method, Request};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::TryRecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio_tungstenite::tungstenite::Message;
+use tower::ServiceExt;
+
+const CATALOG_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/schema/catalog.json");
+
+type Ws =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Copy the route a request matched onto its response, so the session can
+/// record it under the route template rather than the concrete URI.
+async fn expose_matched_path(req: Request<Body>, next: Next) -> Response {
+    let matched = req.extensions().get::<MatchedPath>().cloned();
+    let mut response = next.run(req).await;
+    if let Some(matched) = matched {
+        response.extensions_mut().insert(matched);
+    }
+    response
+}
+
+struct Session {
+    server: TestServer,
+    app: axum::Router,
+    ws_url: Option<String>,
+    events: Receiver<GatewayBroadcast>,
+    catalog: CatalogBuilder,
+}
+
+impl Session {
+    async fn new(server: TestServer) -> Self {
+        let events = server
+            .state
+            .gateway_tx
+            .read()
+            .await
+            .as_ref()
+            .unwrap()
+            .subscribe();
+        let app = server
+            .router()
+            .layer(axum::middleware::from_fn(expose_matched_path));
+        Self {
+            server,
+            app,
+            ws_url: None,
+            events,
+            catalog: CatalogBuilder::default(),
+        }
+    }
+
+    /// Send a request, record its response under the `METHOD /route` it
+    /// matched and the events it broadcast, and return the JSON body (null
+    /// when there is none).
+    async fn send(&mut self, req: Request<Body>) -> Value {
+        // The session sends far more than a client would in a minute.
+        self.server.state.rate_limits.clear();
+        self.server.state.bot_rate_limits.clear();
+
+        let (method, uri) = (req.method().clone(), req.uri().clone());
+        let response = self.app.clone().oneshot(req).await.unwrap();
+        let status = response.status();
+        let route = response
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|p| p.as_str().to_string());
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(
+            status.is_success() || status.is_redirection(),
+            "{method} {uri} returned {status}: {}",
+            String::from_utf8_lossy(&bytes)
+        );
+        let route = route.unwrap_or_else(|| panic!("{method} {uri} matched no route"));
+        let route = route
+            .strip_prefix("/api/v1")
+            .unwrap_or_else(|| panic!("{route} is outside /api/v1"));
+        let key = format!("{method} {route}");
+
+        let body = match content_type.as_deref() {
+            _ if bytes.is_empty() => Value::Null,
+            Some(ct) if ct.starts_with("application/json") => {
+                serde_json::from_slice(&bytes).unwrap()
+            }
+            Some(ct) => {
+                let media_type = ct.split(';').next().unwrap_or(ct).trim();
+                self.catalog
+                    .describe_rest(&key, json!({ "contentMediaType": media_type }));
+                self.drain_events();
+                return Value::Null;
+            }
+            None => panic!("{method} {uri} returned a body without a content type"),
+        };
+        self.catalog.observe_rest(&key, &body);
+        self.drain_events();
+        body
+    }
+
+    /// `method` on `/api/v1{path}` as `user`, with an optional JSON body.
+    async fn call(
+        &mut self,
+        user: &TestUser,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Value {
+        let uri = format!("/api/v1{path}");
+        let req = match body {
+            Some(body) => {
+                common::authenticated_json_request(method, &uri, &user.auth_header(), &body)
+            }
+            None => common::authenticated_request(method, &uri, &user.auth_header()),
+        };
+        self.send(req).await
+    }
+
+    async fn get(&mut self, user: &TestUser, path: &str) -> Value {
+        self.call(user, Method::GET, path, None).await
+    }
+
+    /// An unauthenticated JSON request.
+    async fn anon(&mut self, method: Method, path: &str, body: Option<Value>) -> Value {
+        let uri = format!("/api/v1{path}");
+        let req = match body {
+            Some(body) => common::json_request(method, &uri, &body),
+            None => Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap(),
+        };
+        self.send(req).await
+    }
+
+    /// Record the events broadcast so far. Events from background tasks land
+    /// whenever they finish; [Session::settle] waits for them.
+    fn drain_events(&mut self) {
+        loop {
+            match self.events.try_recv() {
+                Ok(broadcast) => {
+                    if let Some(event_type) = broadcast.event["type"].as_str() {
+                        self.catalog
+                            .observe_event(event_type, &broadcast.event["data"]);
+                    }
+                }
+                Err(TryRecvError::Lagged(n)) => panic!("missed {n} events"),
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Wait for spawned work (unfurls, fan-outs, scans) to broadcast.
+    async fn settle(&mut self) {
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        self.drain_events();
+    }
+
+    /// Open a gateway connection for `token` and IDENTIFY with every intent
+    /// the user may hold, recording the READY.
+    async fn connect(&mut self, token: &str, extra: Value) -> Ws {
+        let mut ws = self.open_ws().await;
+        let mut data = json!({ "token": token, "intents": ["spaces", "messages"] });
+        if let (Some(data), Some(extra)) = (data.as_object_mut(), extra.as_object()) {
+            data.extend(extra.clone());
+        }
+        send_op(&mut ws, 2, data).await;
+        self.recv(&mut ws, "ready").await;
+        ws
+    }
+
+    /// A gateway connection that has had its HELLO and nothing else.
+    async fn open_ws(&mut self) -> Ws {
+        if self.ws_url.is_none() {
+            let url = self.server.spawn().await;
+            self.ws_url = Some(url.replace("http://", "ws://"));
+        }
+        let url = format!("{}/ws", self.ws_url.as_ref().unwrap());
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let hello = next_frame(&mut ws).await;
+        assert_eq!(hello["op"], 5);
+        ws
+    }
+
+    /// Read frames until an event of `event_type` arrives, recording every
+    /// event read on the way, and return its `data`.
+    async fn recv(&mut self, ws: &mut Ws, event_type: &str) -> Value {
+        loop {
+            let frame = next_frame(ws).await;
+            let Some(received) = frame["type"].as_str() else {
+                continue;
+            };
+            let mut data = frame["data"].clone();
+            // Test-mode query tracing, not part of the payload.
+            if let Some(data) = data.as_object_mut() {
+                data.remove("_trace");
+            }
+            self.catalog.observe_event(received, &data);
+            if received == event_type {
+                return data;
+            }
+        }
+    }
+}
+
+async fn send_op(ws: &mut Ws, op: u8, data: Value) {
+    let frame = json!({ "op": op, "data": data });
+    ws.send(Message::Text(frame.to_string().into()))
+        .await
+        .unwrap();
+}
+
+async fn next_frame(ws: &mut Ws) -> Value {
+    let msg = tokio::time::timeout(std::time::Duration::from_secs(5), ws.next())
+        .await
+        .expect("timed out waiting for a gateway frame")
+        .expect("gateway connection closed")
+        .unwrap();
+    serde_json::from_str(&msg.into_text().unwrap()).unwrap()
+}
+
+fn id(body: &Value) -> String {
+    body["data"]["id"].as_str().unwrap().to_string()
+}
+
+/// The cast and the space most sections share.
+struct Ctx {
+    /// Owns the space.
+    alice: TestUser,
+    /// A plain member.
+    bob: TestUser,
+    /// A server admin, in no spaces.
+    admin: TestUser,
+    space_id: String,
+    channel_id: String,
+    /// Public and open to guests, with its widget on.
+    lobby_id: String,
+}
+
+/// A 1x1 PNG.
+const PNG: &[u8] = &[
+    0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x00, 0x00, 0x00, 0x90, 0x77, 0x53,
+    0xDE, 0x00, 0x00, 0x00, 0x0C, 0x49, 0x44, 0x41, 0x54, 0x08, 0xD7, 0x63, 0xF8, 0xCF, 0xC0, 0x00,
+    0x00, 0x00, 0x02, 0x00, 0x01, 0xE2, 0x21, 0xBC, 0x33, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E,
+    0x44, 0xAE, 0x42, 0x60, 0x82,
+];
+
+/// [PNG] as the data URI image uploads take.
+fn png_data_uri() -> String {
+    format!(
+        "data:image/png;base64,{}",
+        data_encoding::BASE64.encode(PNG)
+    )
+}
+
+const PASSWORD: &str = "correct-horse-battery";
+const MCP_API_KEY: &str = "contract-mcp-key";
+
+/// The user a register or login response signed in.
+fn signed_in(body: &Value) -> TestUser {
+    TestUser {
+        user: serde_json::from_value(body["data"]["user"].clone()).unwrap(),
+        token: body["data"]["token"].as_str().unwrap().to_string(),
+        is_bot: false,
+    }
+}
+
+fn totp(secret: &Value) -> totp_rs::TOTP {
+    let secret = data_encoding::BASE32_NOPAD
+        .decode(secret.as_str().unwrap().as_bytes())
+        .unwrap();
+    totp_rs::TOTP::new(totp_rs::Algorithm::SHA1, 6, 1, 30, secret).unwrap()
+}
+
+/// `METHOD /route` for every route `api_routes` registers, read from its
+/// source so a new route can't go without a catalog entry.
+fn registered_routes() -> BTreeSet<String> {
+    let source = include_str!("../src/routes/mod.rs");
+    let start = source.find("fn api_routes").unwrap();
+    let body = &source[start..];
+    let body = &body[..body.find("\n}\n").unwrap()];
+    let route = regex::Regex::new(r#"\.route\(\s*"([^"]+)",\s*((?:[^()]|\([^()]*\))*)\)"#).unwrap();
+    let method = regex::Regex::new(r"\b(get|post|put|patch|delete)\(").unwrap();
+    let mut routes = BTreeSet::new();
+    for captures in route.captures_iter(body) {
+        for m in method.captures_iter(&captures[2]) {
+            routes.insert(format!("{} {}", m[1].to_uppercase(), &captures[1]));
+        }
+    }
+    assert_eq!(
+        body.matches(".route(").count(),
+        route.captures_iter(body).count(),
+        "couldn't parse every .route() in api_routes"
+    );
+    routes
+}
+
+#[test]
+fn test_catalog_has_an_entry_per_route_and_event_type() {
+    let catalog = accordserver::schema::catalog();
+    let keys = |section: &str| -> BTreeSet<String> {
+        catalog[section]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect()
+    };
+    let events: BTreeSet<String> = accordserver::gateway::intents::EVENT_TYPES
+        .iter()
+        .map(|event_type| event_type.to_string())
+        .collect();
+
+    let mut problems = Vec::new();
+    for (what, registered, cataloged) in [
+        ("route", registered_routes(), keys("rest")),
+        ("event", events, keys("events")),
+    ] {
+        for missing in registered.difference(&cataloged) {
+            problems.push(format!("{what} missing from the catalog: {missing}"));
+        }
+        for stale in cataloged.difference(&registered) {
+            problems.push(format!("{what} in the catalog isn't registered: {stale}"));
+        }
+    }
+    assert!(problems.is_empty(), "{}", problems.join("\n"));
+}
+
+#[tokio::test]
+async fn test_payloads_match_the_schema_catalog() {
+    let mut server = TestServer::new().await;
+    // Nothing listens on the discard port; the sends fail in the background.
+    server.state.mailer = Some(Arc::new(
+        Mailer::new(&SmtpConfig {
+            host: "127.0.0.1".into(),
+            port: 9,
+            username: None,
+            password: None,
+            from: "Accord <noreply@localhost>".into(),
+            tls: SmtpTls::None,
+        })
+        .unwrap(),
+    ));
+    server.state.mcp_api_key = Some(MCP_API_KEY.into());
+    // For the admin section's peer to trust; set before the router is built.
+    server.enable_federation("a.test");
+    let mut s = Session::new(server).await;
+
+    auth(&mut s).await;
+    let cx = spaces(&mut s).await;
+    members_and_roles(&mut s, &cx).await;
+    moderation(&mut s, &cx).await;
+    channels(&mut s, &cx).await;
+    messages(&mut s, &cx).await;
+    threads_and_forums(&mut s, &cx).await;
+    invites(&mut s, &cx).await;
+    account(&mut s, &cx).await;
+    expressions(&mut s, &cx).await;
+    webhooks(&mut s, &cx).await;
+    voice(&mut s, &cx).await;
+    plugins(&mut s, &cx).await;
+    applications(&mut s, &cx).await;
+    admin(&mut s, &cx).await;
+    mcp(&mut s, &cx).await;
+    login_tickets(&mut s, &cx).await;
+    gateway(&mut s, &cx).await;
+    s.call(
+        &cx.alice,
+        Method::DELETE,
+        &format!("/spaces/{}", cx.space_id),
+        None,
+    )
+    .await;
+
+    // Fetched directly: recording the catalog's own shape would make it
+    // depend on its previous contents.
+    let viewer = s.server.create_user_with_token("viewer").await;
+    let req = common::authenticated_request(Method::GET, "/api/v1/schema", &viewer.auth_header());
+    let served = common::parse_body(s.server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(served, *accordserver::schema::catalog());
+    s.catalog.describe_rest(
+        "GET /schema",
+        json!({
+            "type": "object",
+            "properties": {
+                "$schema": { "type": "string" },
+                "title": { "type": "string" },
+                "rest": { "type": "object", "additionalProperties": { "$ref": accordserver::schema::SCHEMA_DIALECT } },
+                "events": { "type": "object", "additionalProperties": { "$ref": accordserver::schema::SCHEMA_DIALECT } },
+            },
+            "required": ["$schema", "events", "rest", "title"],
+        }),
+    );
+
+    s.settle().await;
+    let generated = s.catalog.build();
+    let checked_in: Value =
+        serde_json::from_str(&std::fs::read_to_string(CATALOG_PATH).unwrap()).unwrap();
+
+    if std::env::var_os("UPDATE_SCHEMA").is_some() {
+        let mut pretty = serde_json::to_string_pretty(&generated).unwrap();
+        pretty.push('\n');
+        std::fs::write(CATALOG_PATH, pretty).unwrap();
+        return;
+    }
+    let mut changed = Vec::new();
+    for section in ["rest", "events"] {
+        let (old, new) = (&checked_in[section], &generated[section]);
+        let keys: BTreeSet<&String> = old
+            .as_object()
+            .into_iter()
+            .chain(new.as_object())
+            .flat_map(|m| m.keys())
+            .collect();
+        for key in keys {
+            match (old.get(key), new.get(key)) {
+                (Some(a), Some(b)) if a == b => {}
+                (Some(_), Some(_)) => changed.push(format!("changed: {key}")),
+                (Some(_), None) => changed.push(format!("removed: {key}")),
+                _ => changed.push(format!("added: {key}")),
+            }
+        }
+    }
+    assert!(
+        changed.is_empty(),
+        "payload shapes differ from schema/catalog.json (rerun with UPDATE_SCHEMA=1 if intended):\n{}",
+        changed.join("\n")
+    );
+}
+
+/// Accounts end to end on a fresh user: registration, passwords, email,
+/// TOTP under both route prefixes, passkeys and sessions.
+async fn auth(s: &mut Session) {
+    let body = s
+        .anon(
+            Method::POST,
+            "/auth/register",
+            Some(
+                json!({ "username": "carol", "password": PASSWORD, "email": "carol@example.com" }),
+            ),
+        )
+        .await;
+    let carol = signed_in(&body);
+    let login = json!({ "username": "carol", "password": PASSWORD });
+    s.anon(Method::POST, "/auth/login", Some(login.clone()))
+        .await;
+    s.call(
+        &carol,
+        Method::POST,
+        "/auth/change-password",
+        Some(json!({ "old_password": PASSWORD, "new_password": PASSWORD })),
+    )
+    .await;
+
+    // Email: the code registration sent is spent before asking for another.
+    sqlx::query(&accordserver::db::q(
+        "DELETE FROM email_tokens WHERE user_id = ?",
+    ))
+    .bind(&carol.user.id)
+    .execute(s.server.pool())
+    .await
+    .unwrap();
+    s.call(&carol, Method::POST, "/auth/verify-email/resend", None)
+        .await;
+    let token = email_tokens::create_token(
+        s.server.pool(),
+        &carol.user.id,
+        KIND_VERIFY_EMAIL,
+        "carol@example.com",
+        3600,
+    )
+    .await
+    .unwrap();
+    s.anon(
+        Method::POST,
+        "/auth/verify-email",
+        Some(json!({ "token": token })),
+    )
+    .await;
+    s.anon(
+        Method::POST,
+        "/auth/password-reset",
+        Some(json!({ "email": "carol@example.com" })),
+    )
+    .await;
+
+    // TOTP, set up and torn down once under each prefix.
+    for prefix in ["/auth/2fa", "/auth/mfa/totp"] {
+        let setup = s
+            .call(
+                &carol,
+                Method::POST,
+                &format!("{prefix}/enable"),
+                Some(json!({ "password": PASSWORD })),
+            )
+            .await;
+        let code = totp(&setup["data"]["secret"]).generate_current().unwrap();
+        s.call(
+            &carol,
+            Method::POST,
+            &format!("{prefix}/verify"),
+            Some(json!({ "code": code })),
+        )
+        .await;
+        let backup_codes = if prefix == "/auth/2fa" {
+            "/auth/2fa/backup-codes"
+        } else {
+            "/auth/mfa/backup-codes"
+        };
+        let codes = s
+            .call(
+                &carol,
+                Method::POST,
+                backup_codes,
+                Some(json!({ "password": PASSWORD })),
+            )
+            .await;
+        let codes = &codes["data"]["backup_codes"];
+        if prefix == "/auth/2fa" {
+            s.call(
+                &carol,
+                Method::POST,
+                "/auth/mfa/verify",
+                Some(json!({ "code": codes[0] })),
+            )
+            .await;
+            let ticket = s
+                .anon(Method::POST, "/auth/login", Some(login.clone()))
+                .await;
+            s.anon(
+                Method::POST,
+                "/auth/login/mfa",
+                Some(json!({ "ticket": ticket["data"]["ticket"], "code": codes[1] })),
+            )
+            .await;
+        }
+        s.call(
+            &carol,
+            Method::POST,
+            &format!("{prefix}/disable"),
+            Some(json!({ "password": PASSWORD })),
+        )
+        .await;
+    }
+
+    // Passkeys
+    let options = s
+        .call(
+            &carol,
+            Method::POST,
+            "/auth/passkeys/register/start",
+            Some(json!({ "password": PASSWORD })),
+        )
+        .await;
+    let mut passkey = SoftPasskey::new();
+    s.call(
+        &carol,
+        Method::POST,
+        "/auth/passkeys/register/finish",
+        Some(json!({ "name": "Laptop", "credential": passkey.create(&options["data"]) })),
+    )
+    .await;
+    let passkeys = s.get(&carol, "/auth/passkeys").await;
+    let passkey_id = passkeys["data"][0]["id"].as_str().unwrap().to_string();
+    s.call(
+        &carol,
+        Method::PATCH,
+        &format!("/auth/passkeys/{passkey_id}"),
+        Some(json!({ "name": "Work laptop" })),
+    )
+    .await;
+    let options = s
+        .anon(
+            Method::POST,
+            "/auth/passkeys/login/start",
+            Some(json!({ "username": "carol" })),
+        )
+        .await;
+    s.anon(
+        Method::POST,
+        "/auth/passkeys/login/finish",
+        Some(json!({ "credential": passkey.get(&options["data"]) })),
+    )
+    .await;
+    s.call(
+        &carol,
+        Method::DELETE,
+        &format!("/auth/passkeys/{passkey_id}"),
+        None,
+    )
+    .await;
+
+    // Sessions, ending with a reset that signs carol out everywhere.
+    let sessions = s.get(&carol, "/users/@me/sessions").await;
+    let other = sessions["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|session| session["current"] != true)
+        .unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    s.call(
+        &carol,
+        Method::DELETE,
+        &format!("/users/@me/sessions/{other}"),
+        None,
+    )
+    .await;
+    let phone = signed_in(
+        &s.anon(Method::POST, "/auth/login", Some(login.clone()))
+            .await,
+    );
+    s.call(&phone, Method::POST, "/auth/logout", None).await;
+    s.call(&carol, Method::POST, "/auth/sessions/revoke-all", None)
+        .await;
+    let token = email_tokens::create_token(
+        s.server.pool(),
+        &carol.user.id,
+        KIND_PASSWORD_RESET,
+        "carol@example.com",
+        3600,
+    )
+    .await
+    .unwrap();
+    s.anon(
+        Method::POST,
+        "/auth/password-reset/confirm",
+        Some(json!({ "token": token, "new_password": PASSWORD })),
+    )
+    .await;
+}
+
+/// Creates the shared space and covers the routes about spaces as a whole.
+async fn spaces(s: &mut Session) -> Ctx {
+    let alice = s.server.create_user_with_token("alice").await;
+    let bob = s.server.create_user_with_token("bob").await;
+    let admin = s.server.create_admin_with_token("admin").await;
+
+    s.get(&alice, "/users/@me").await;
+    s.call(
+        &alice,
+        Method::PATCH,
+        "/users/@me",
+        Some(json!({ "display_name": "Alice", "pronouns": "she/her" })),
+    )
+    .await;
+    s.get(&alice, &format!("/users/{}", bob.user.id)).await;
+
+    let space = s
+        .call(
+            &alice,
+            Method::POST,
+            "/spaces",
+            Some(json!({ "name": "Contract" })),
+        )
+        .await;
+    let space_id = id(&space);
+    s.get(&alice, &format!("/spaces/{space_id}")).await;
+    s.call(
+        &alice,
+        Method::PATCH,
+        &format!("/spaces/{space_id}"),
+        Some(json!({ "description": "Where the contract lives" })),
+    )
+    .await;
+    let channel = s
+        .call(
+            &alice,
+            Method::POST,
+            &format!("/spaces/{space_id}/channels"),
+            Some(json!({ "name": "general", "type": "text" })),
+        )
+        .await;
+    let channel_id = id(&channel);
+    let channels = s.get(&alice, &format!("/spaces/{space_id}/channels")).await;
+    let moves: Vec<Value> = channels["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(position, channel)| json!({ "id": channel["id"], "position": position }))
+        .collect();
+    s.call(
+        &alice,
+        Method::PATCH,
+        &format!("/spaces/{space_id}/channels"),
+        Some(json!(moves)),
+    )
+    .await;
+    s.server.add_member(&space_id, &bob.user.id).await;
+
+    // The lobby: public, open to guests, with a widget and a directory entry.
+    let lobby_id = s.server.create_public_space(&alice.user.id, "Lobby").await;
+    s.call(
+        &alice,
+        Method::PATCH,
+        &format!("/spaces/{lobby_id}"),
+        Some(json!({ "allow_guest_access": true, "widget_enabled": true, "category": "gaming" })),
+    )
+    .await;
+    s.get(&bob, "/spaces/public").await;
+    s.get(&bob, "/spaces/public/categories").await;
+    s.anon(
+        Method::GET,
+        &format!("/spaces/{lobby_id}/widget.json"),
+        None,
+    )
+    .await;
+    s.anon(Method::POST, "/auth/guest", None).await;
+    s.get(&alice, &format!("/spaces/{lobby_id}/anonymous-count"))
+        .await;
+
+    // Screening: bob joins the lobby pending and accepts the rules.
+    s.get(&alice, &format!("/spaces/{lobby_id}/welcome-screen"))
+        .await;
+    let lobby_channel = s.server.create_channel(&lobby_id, "welcome").await;
+    s.call(
+        &alice,
+        Method::PATCH,
+        &format!("/spaces/{lobby_id}/welcome-screen"),
+        Some(json!({
+            "enabled": true,
+            "description": "Hello",
+            "channels": [{ "channel_id": lobby_channel, "description": "Start here" }],
+        })),
+    )
+    .await;
+    s.get(&alice, &format!("/spaces/{lobby_id}/member-screening"))
+        .await;
+    s.call(
+        &alice,
+        Method::PATCH,
+        &format!("/spaces/{lobby_id}/member-screening"),
+        Some(json!({ "enabled": true, "rules": ["Be kind"] })),
+    )
+    .await;
+    s.call(
+        &bob,
+        Method::POST,
+        &format!("/spaces/{lobby_id}/join"),
+        None,
+    )
+    .await;
+    s.call(
+        &bob,
+        Method::POST,
+        &format!("/spaces/{lobby_id}/member-screening/accept"),
+        None,
+    )
+    .await;
+
+    // Space-wide switches, each flipped on and back off.
+    for (route, on) in [
+        ("lockdown", json!({ "duration_secs": 60 })),
+        ("archive", Value::Null),
+    ] {
+        let path = format!("/spaces/{lobby_id}/{route}");
+        let body = (!on.is_null()).then_some(on);
+        s.call(&alice, Method::PUT, &path, body).await;
+        s.call(&alice, Method::DELETE, &path, None).await;
+    }
+    s.get(&alice, &format!("/spaces/{space_id}/raid-protection"))
+        .await;
+    s.call(
+        &alice,
+        Method::PATCH,
+        &format!("/spaces/{space_id}/raid-protection"),
+        Some(json!({ "auto_lockdown": true, "join_threshold": 20 })),
+    )
+    .await;
+    s.get(&bob, &format!("/spaces/{space_id}/email-domains"))
+        .await;
+    s.call(
+        &alice,
+        Method::PUT,
+        &format!("/spaces/{space_id}/email-domains"),
+        Some(json!({ "domains": ["example.com"] })),
+    )
+    .await;
+    s.call(
+        &alice,
+        Method::PUT,
+        &format!("/spaces/{space_id}/email-domains"),
+        Some(json!({ "domains": [] })),
+    )
+    .await;
+    s.get(&alice, &format!("/spaces/{space_id}/vanity-url"))
+        .await;
+    s.call(
+        &alice,
+        Method::PATCH,
+        &format!("/spaces/{space_id}/vanity-url"),
+        Some(json!({ "code": "contract" })),
+    )
+    .await;
+    s.get(&alice, &format!("/spaces/{space_id}/integrations"))
+        .await;
+    s.get(&alice, &format!("/spaces/{space_id}/voice-regions"))
+        .await;
+
+    Ctx {
+        alice,
+        bob,
+        admin,
+        space_id,
+        channel_id,
+        lobby_id,
+    }
+}
+
+/// Members, their roles and the space's roles.
+async fn members_and_roles(s: &mut Session, cx: &Ctx) {
+    let (alice, bob, space_id) = (&cx.alice, &cx.bob, &cx.space_id);
+    let role = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("/spaces/{space_id}/roles"),
+            Some(json!({ "name": "Helpers", "mentionable": true })),
+        )
+        .await;
+    let role_id = id(&role);
+    s.call(
+        alice,
+        Method::PATCH,
+        &format!("/spaces/{space_id}/roles/{role_id}"),
+        Some(json!({ "color": 3447003 })),
+    )
+    .await;
+    let roles = s.get(alice, &format!("/spaces/{space_id}/roles")).await;
+    let moves: Vec<Value> = roles["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|role| role["position"] != 0)
+        .map(|role| json!({ "id": role["id"], "position": role["position"] }))
+        .collect();
+    s.call(
+        alice,
+        Method::PATCH,
+        &format!("/spaces/{space_id}/roles"),
+        Some(json!(moves)),
+    )
+    .await;
+
+    let member = format!("/spaces/{space_id}/members/{}", bob.user.id);
+    s.call(
+        alice,
+        Method::PUT,
+        &format!("{member}/roles/{role_id}"),
+        None,
+    )
+    .await;
+    s.get(
+        alice,
+        &format!("/spaces/{space_id}/roles/{role_id}/members"),
+    )
+    .await;
+    s.call(
+        alice,
+        Method::DELETE,
+        &format!("{member}/roles/{role_id}"),
+        None,
+    )
+    .await;
+    s.call(
+        alice,
+        Method::PATCH,
+        &member,
+        Some(json!({ "nickname": "Bobby" })),
+    )
+    .await;
+    s.get(alice, &member).await;
+    s.get(alice, &format!("/spaces/{space_id}/members")).await;
+    s.get(
+        alice,
+        &format!("/spaces/{space_id}/members/search?query=bo&with_user=true"),
+    )
+    .await;
+    s.call(
+        bob,
+        Method::PATCH,
+        &format!("/spaces/{space_id}/members/@me"),
+        Some(json!({ "nickname": "Bob" })),
+    )
+    .await;
+    s.call(
+        alice,
+        Method::DELETE,
+        &format!("/spaces/{space_id}/roles/{role_id}"),
+        None,
+    )
+    .await;
+
+    // Leaving and being kicked, in the lobby.
+    let lobby_id = &cx.lobby_id;
+    s.call(
+        bob,
+        Method::DELETE,
+        &format!("/spaces/{lobby_id}/members/@me"),
+        None,
+    )
+    .await;
+    s.server.add_member(lobby_id, &bob.user.id).await;
+    s.call(
+        alice,
+        Method::DELETE,
+        &format!("/spaces/{lobby_id}/members/{}", bob.user.id),
+        None,
+    )
+    .await;
+}
+
+/// Bans and ban sync, AutoMod, reports and the audit log.
+async fn moderation(s: &mut Session, cx: &Ctx) {
+    let (alice, bob, space_id) = (&cx.alice, &cx.bob, &cx.space_id);
+    let spammer = s.server.create_user_with_token("spammer").await;
+    s.server.add_member(space_id, &spammer.user.id).await;
+
+    // AutoMod flags rather than blocks, so the message that trips it posts.
+    let rule = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("/spaces/{space_id}/automod/rules"),
+            Some(json!({
+                "name": "Scams",
+                "trigger": { "type": "keyword", "keywords": ["free nitro"] },
+                "actions": [{ "type": "flag", "channel_id": cx.channel_id }],
+            })),
+        )
+        .await;
+    let rule_id = id(&rule);
+    let rule_path = format!("/spaces/{space_id}/automod/rules/{rule_id}");
+    s.get(alice, &format!("/spaces/{space_id}/automod/rules"))
+        .await;
+    s.get(alice, &rule_path).await;
+    s.call(
+        alice,
+        Method::PATCH,
+        &rule_path,
+        Some(json!({ "name": "Nitro scams" })),
+    )
+    .await;
+    let scam = s
+        .call(
+            &spammer,
+            Method::POST,
+            &format!("/channels/{}/messages", cx.channel_id),
+            Some(json!({ "content": "FREE NITRO here" })),
+        )
+        .await;
+    s.get(alice, &format!("/spaces/{space_id}/automod/stats"))
+        .await;
+    let hit_id: String = sqlx::query_scalar(&accordserver::db::q(
+        "SELECT id FROM automod_hits WHERE rule_id = ?",
+    ))
+    .bind(&rule_id)
+    .fetch_one(s.server.pool())
+    .await
+    .unwrap();
+    s.call(
+        alice,
+        Method::POST,
+        &format!("/spaces/{space_id}/automod/hits/{hit_id}/override"),
+        None,
+    )
+    .await;
+    s.call(alice, Method::DELETE, &rule_path, None).await;
+
+    // Reports, in the space and to the server's admins.
+    let report = s
+        .call(
+            bob,
+            Method::POST,
+            &format!("/spaces/{space_id}/reports"),
+            Some(json!({
+                "target_type": "message",
+                "target_id": id(&scam),
+                "channel_id": cx.channel_id,
+                "category": "fraud",
+            })),
+        )
+        .await;
+    let report_id = id(&report);
+    s.get(alice, &format!("/spaces/{space_id}/reports")).await;
+    s.get(alice, &format!("/spaces/{space_id}/reports/{report_id}"))
+        .await;
+    s.call(
+        alice,
+        Method::PATCH,
+        &format!("/spaces/{space_id}/reports/{report_id}"),
+        Some(json!({ "status": "actioned", "action_taken": "banned" })),
+    )
+    .await;
+    s.call(
+        bob,
+        Method::POST,
+        "/reports",
+        Some(json!({
+            "target_type": "user",
+            "target_id": spammer.user.id,
+            "space_id": space_id,
+            "category": "fraud",
+        })),
+    )
+    .await;
+
+    // Bans
+    let ban = format!("/spaces/{space_id}/bans/{}", spammer.user.id);
+    s.call(alice, Method::PUT, &ban, Some(json!({ "reason": "scams" })))
+        .await;
+    s.get(alice, &ban).await;
+    s.get(alice, &format!("/spaces/{space_id}/bans")).await;
+    let export = s
+        .get(alice, &format!("/spaces/{space_id}/bans/export"))
+        .await;
+    s.call(alice, Method::DELETE, &ban, None).await;
+    s.call(
+        alice,
+        Method::POST,
+        &format!("/spaces/{space_id}/bans/import"),
+        Some(json!({ "bans": export["data"]["bans"] })),
+    )
+    .await;
+    let sync = format!("/spaces/{space_id}/ban-sync");
+    let group = s
+        .call(
+            alice,
+            Method::POST,
+            &sync,
+            Some(json!({ "name": "Friends" })),
+        )
+        .await;
+    let lobby_sync = format!("/spaces/{}/ban-sync", cx.lobby_id);
+    s.call(
+        alice,
+        Method::PUT,
+        &lobby_sync,
+        Some(json!({ "group_id": group["data"]["id"] })),
+    )
+    .await;
+    s.get(alice, &sync).await;
+    s.call(alice, Method::DELETE, &lobby_sync, None).await;
+
+    s.get(alice, &format!("/spaces/{space_id}/audit-logs"))
+        .await;
+    s.get(alice, &format!("/spaces/{space_id}/audit-log?limit=5"))
+        .await;
+}
+
+/// Channel settings, permission overwrites, per-user channel state and DMs.
+async fn channels(s: &mut Session, cx: &Ctx) {
+    let (alice, bob) = (&cx.alice, &cx.bob);
+    let channel = format!("/channels/{}", cx.channel_id);
+    s.call(
+        alice,
+        Method::PATCH,
+        &channel,
+        Some(json!({ "topic": "Anything goes" })),
+    )
+    .await;
+    s.get(alice, &channel).await;
+
+    let overwrite = format!("{channel}/permissions/{}", bob.user.id);
+    s.call(
+        alice,
+        Method::PUT,
+        &overwrite,
+        Some(json!({ "type": "member", "allow": ["attach_files"], "deny": [] })),
+    )
+    .await;
+    s.get(alice, &format!("{channel}/permissions")).await;
+    s.call(alice, Method::DELETE, &overwrite, None).await;
+    s.call(alice, Method::POST, &format!("{channel}/lock"), None)
+        .await;
+    s.call(alice, Method::POST, &format!("{channel}/unlock"), None)
+        .await;
+
+    // Bob's own view of the channel.
+    s.call(bob, Method::PUT, &format!("{channel}/mute"), None)
+        .await;
+    s.get(bob, "/users/@me/mutes").await;
+    s.call(bob, Method::DELETE, &format!("{channel}/mute"), None)
+        .await;
+    s.call(
+        bob,
+        Method::PATCH,
+        "/users/@me/channel-states",
+        Some(json!({ "channels": [{ "channel_id": cx.channel_id, "collapsed": true }] })),
+    )
+    .await;
+    s.get(bob, "/users/@me/channel-states").await;
+    let draft = format!("{channel}/draft");
+    s.call(
+        bob,
+        Method::PUT,
+        &draft,
+        Some(json!({ "content": "half a thought" })),
+    )
+    .await;
+    s.get(bob, &draft).await;
+    s.get(bob, "/users/@me/drafts").await;
+    s.call(bob, Method::DELETE, &draft, None).await;
+    s.call(bob, Method::POST, &format!("{channel}/typing"), None)
+        .await;
+
+    // DMs, one to one and in a group.
+    let carol = s.server.create_user_with_token("dm_carol").await;
+    let dave = s.server.create_user_with_token("dm_dave").await;
+    for user in [&carol, &dave] {
+        s.server.add_member(&cx.space_id, &user.user.id).await;
+    }
+    s.call(
+        alice,
+        Method::POST,
+        "/users/@me/channels",
+        Some(json!({ "recipient_id": bob.user.id })),
+    )
+    .await;
+    let group = s
+        .call(
+            alice,
+            Method::POST,
+            "/users/@me/channels",
+            Some(json!({ "recipients": [bob.user.id, carol.user.id] })),
+        )
+        .await;
+    let recipient = format!("/channels/{}/recipients/{}", id(&group), dave.user.id);
+    s.call(alice, Method::PUT, &recipient, None).await;
+    s.call(alice, Method::DELETE, &recipient, None).await;
+    s.get(alice, "/users/@me/channels").await;
+
+    let scratch = s.server.create_channel(&cx.space_id, "scratch").await;
+    s.call(alice, Method::DELETE, &format!("/channels/{scratch}"), None)
+        .await;
+}
+
+/// Messages and everything hung off them: reactions, pins, polls, shares,
+/// read state, search and bulk removal.
+async fn messages(s: &mut Session, cx: &Ctx) {
+    let (alice, bob, channel_id) = (&cx.alice, &cx.bob, &cx.channel_id);
+    let messages = format!("/channels/{channel_id}/messages");
+    let role_id = s
+        .server
+        .create_role(&cx.space_id, "Pingable", &["send_messages"])
+        .await;
+    sqlx::query(&accordserver::db::q(
+        "UPDATE roles SET mentionable = TRUE WHERE id = ?",
+    ))
+    .bind(&role_id)
+    .execute(s.server.pool())
+    .await
+    .unwrap();
+    s.server
+        .assign_role(&cx.space_id, &alice.user.id, &role_id)
+        .await;
+
+    let message = s
+        .call(
+            alice,
+            Method::POST,
+            &messages,
+            Some(json!({ "content": "hello" })),
+        )
+        .await;
+    let message_id = id(&message);
+    let message = format!("{messages}/{message_id}");
+    s.call(
+        bob,
+        Method::POST,
+        &messages,
+        Some(json!({
+            "content": format!("<@{}> see <#{channel_id}> <@&{role_id}>", alice.user.id),
+            "reply_to": message_id,
+        })),
+    )
+    .await;
+    s.call(
+        alice,
+        Method::PATCH,
+        &message,
+        Some(json!({ "content": "hello, world" })),
+    )
+    .await;
+    s.get(alice, &message).await;
+    s.get(alice, &format!("{message}/history")).await;
+    s.get(alice, &messages).await;
+    s.get(
+        alice,
+        &format!("/spaces/{}/messages/search?query=hello", cx.space_id),
+    )
+    .await;
+    s.call(
+        bob,
+        Method::POST,
+        &format!("/channels/{channel_id}/ack"),
+        Some(json!({ "message_id": message_id })),
+    )
+    .await;
+    s.call(bob, Method::POST, &format!("{message}/ack"), None)
+        .await;
+    s.get(bob, "/users/@me/read-states").await;
+
+    // Reactions
+    let thumbs = format!("{message}/reactions/%F0%9F%91%8D");
+    s.call(bob, Method::PUT, &format!("{thumbs}/@me"), None)
+        .await;
+    s.call(alice, Method::PUT, &format!("{thumbs}/@me"), None)
+        .await;
+    s.get(alice, &thumbs).await;
+    s.call(bob, Method::DELETE, &format!("{thumbs}/@me"), None)
+        .await;
+    s.call(
+        alice,
+        Method::DELETE,
+        &format!("{thumbs}/{}", alice.user.id),
+        None,
+    )
+    .await;
+    s.call(bob, Method::PUT, &format!("{thumbs}/@me"), None)
+        .await;
+    s.call(alice, Method::DELETE, &thumbs, None).await;
+    s.call(bob, Method::PUT, &format!("{thumbs}/@me"), None)
+        .await;
+    s.call(alice, Method::DELETE, &format!("{message}/reactions"), None)
+        .await;
+
+    // Pins
+    let pin = format!("/channels/{channel_id}/pins/{message_id}");
+    s.call(alice, Method::PUT, &pin, None).await;
+    s.get(alice, &format!("/channels/{channel_id}/pins")).await;
+    s.call(alice, Method::DELETE, &pin, None).await;
+
+    // Polls
+    let poll = s
+        .call(
+            alice,
+            Method::POST,
+            &messages,
+            Some(json!({
+                "content": "",
+                "poll": { "question": "Tea?", "answers": [{ "text": "Yes" }, { "text": "No" }] },
+            })),
+        )
+        .await;
+    let answer = format!("/channels/{channel_id}/polls/{}/answers/1", id(&poll));
+    s.call(bob, Method::PUT, &format!("{answer}/@me"), None)
+        .await;
+    s.get(alice, &answer).await;
+    s.call(bob, Method::DELETE, &format!("{answer}/@me"), None)
+        .await;
+
+    // Share links
+    s.call(
+        alice,
+        Method::PATCH,
+        &format!("/spaces/{}", cx.space_id),
+        Some(json!({ "message_sharing": true })),
+    )
+    .await;
+    let share = s
+        .call(bob, Method::POST, &format!("{message}/share"), None)
+        .await;
+    s.anon(
+        Method::GET,
+        &format!(
+            "/shared-messages/{}",
+            share["data"]["token"].as_str().unwrap()
+        ),
+        None,
+    )
+    .await;
+    s.get(alice, &format!("{message}/shares")).await;
+    s.call(
+        alice,
+        Method::DELETE,
+        &format!("{message}/shares/{}", id(&share)),
+        None,
+    )
+    .await;
+
+    // Attachments
+    let boundary = "contract-boundary";
+    let mut body = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"payload_json\"\r\n\
+         Content-Type: application/json\r\n\r\n{}\r\n--{boundary}\r\n\
+         Content-Disposition: form-data; name=\"files[0]\"; filename=\"notes.txt\"\r\n\
+         Content-Type: text/plain\r\n\r\n",
+        json!({ "content": "notes attached" })
+    )
+    .into_bytes();
+    body.extend_from_slice(b"remember the milk\r\n");
+    body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("/api/v1{messages}/upload"))
+        .header(header::AUTHORIZATION, alice.auth_header())
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .body(Body::from(body))
+        .unwrap();
+    s.send(req).await;
+
+    // Removal: one, a batch, then everything recent.
+    let mut batch = Vec::new();
+    for content in ["one", "two", "three"] {
+        let sent = s
+            .call(
+                bob,
+                Method::POST,
+                &messages,
+                Some(json!({ "content": content })),
+            )
+            .await;
+        batch.push(id(&sent));
+    }
+    s.call(
+        bob,
+        Method::DELETE,
+        &format!("{messages}/{}", batch.pop().unwrap()),
+        None,
+    )
+    .await;
+    s.call(
+        alice,
+        Method::POST,
+        &format!("{messages}/bulk-delete"),
+        Some(json!({ "messages": batch })),
+    )
+    .await;
+    s.call(
+        alice,
+        Method::POST,
+        &format!(
+            "/channels/{channel_id}/purge?seconds=3600&user_id={}",
+            bob.user.id
+        ),
+        None,
+    )
+    .await;
+}
+
+/// Threads off a message and standalone, and forum channels with tags.
+async fn threads_and_forums(s: &mut Session, cx: &Ctx) {
+    let (alice, bob, channel_id) = (&cx.alice, &cx.bob, &cx.channel_id);
+    let channel = format!("/channels/{channel_id}");
+    let message = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("{channel}/messages"),
+            Some(json!({ "content": "thread starter" })),
+        )
+        .await;
+    let message = format!("{channel}/messages/{}", id(&message));
+    s.call(
+        alice,
+        Method::POST,
+        &format!("{message}/threads"),
+        Some(json!({ "name": "Greetings" })),
+    )
+    .await;
+    s.get(alice, &format!("{message}/threads")).await;
+
+    let thread = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("{channel}/threads"),
+            Some(json!({ "name": "Standalone", "content": "first post" })),
+        )
+        .await;
+    let thread = format!("{channel}/threads/{}", id(&thread));
+    s.get(alice, &thread).await;
+    s.call(bob, Method::PUT, &format!("{thread}/members/@me"), None)
+        .await;
+    s.get(alice, &format!("{thread}/members")).await;
+    s.call(bob, Method::DELETE, &format!("{thread}/members/@me"), None)
+        .await;
+    s.call(
+        alice,
+        Method::PATCH,
+        &thread,
+        Some(json!({ "archived": true })),
+    )
+    .await;
+    s.get(alice, &format!("{channel}/threads")).await;
+    s.get(alice, &format!("{channel}/threads/active")).await;
+    s.get(alice, &format!("{channel}/threads/archived")).await;
+    s.call(alice, Method::DELETE, &thread, None).await;
+
+    // Forums
+    let forum = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("/spaces/{}/channels", cx.space_id),
+            Some(json!({ "name": "ideas", "type": "forum" })),
+        )
+        .await;
+    let forum = format!("/channels/{}", id(&forum));
+    let tag = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("{forum}/tags"),
+            Some(json!({ "name": "Feature" })),
+        )
+        .await;
+    let tag_id = id(&tag);
+    s.call(
+        alice,
+        Method::PATCH,
+        &format!("{forum}/tags/{tag_id}"),
+        Some(json!({ "emoji": "✨" })),
+    )
+    .await;
+    s.get(bob, &format!("{forum}/tags")).await;
+    s.call(
+        bob,
+        Method::POST,
+        &format!("{forum}/threads"),
+        Some(json!({ "name": "Dark mode", "content": "Please", "applied_tags": [tag_id] })),
+    )
+    .await;
+    s.get(bob, &format!("{forum}/posts")).await;
+    s.call(
+        alice,
+        Method::DELETE,
+        &format!("{forum}/tags/{tag_id}"),
+        None,
+    )
+    .await;
+}
+
+/// Space and channel invites, the vanity URL aside.
+async fn invites(s: &mut Session, cx: &Ctx) {
+    let (alice, space_id) = (&cx.alice, &cx.space_id);
+    let invite = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("/spaces/{space_id}/invites"),
+            Some(json!({ "max_uses": 5 })),
+        )
+        .await;
+    let code = invite["data"]["code"].as_str().unwrap().to_string();
+    let newcomer = s.server.create_user_with_token("newcomer").await;
+    s.get(&newcomer, &format!("/invites/{code}")).await;
+    s.get(&newcomer, &format!("/invites/{code}/preview")).await;
+    s.call(
+        &newcomer,
+        Method::POST,
+        &format!("/invites/{code}/accept"),
+        None,
+    )
+    .await;
+    s.get(alice, &format!("/spaces/{space_id}/invites")).await;
+    s.get(alice, &format!("/spaces/{space_id}/invites/{code}/stats"))
+        .await;
+    s.call(alice, Method::DELETE, &format!("/invites/{code}"), None)
+        .await;
+
+    let channel_invites = format!("/channels/{}/invites", cx.channel_id);
+    s.call(alice, Method::POST, &channel_invites, Some(json!({})))
+        .await;
+    s.get(alice, &channel_invites).await;
+}
+
+/// What a user keeps for themselves: settings, notification preferences,
+/// push subscriptions, relationships, their data, and finally the account.
+async fn account(s: &mut Session, cx: &Ctx) {
+    let (alice, bob, space_id) = (&cx.alice, &cx.bob, &cx.space_id);
+    s.get(alice, "/users/@me/spaces").await;
+    s.get(alice, "/users/@me/settings").await;
+    s.call(
+        alice,
+        Method::PATCH,
+        "/users/@me/settings",
+        Some(json!({ "settings": { "theme": "dark" } })),
+    )
+    .await;
+    s.get(alice, "/users/@me/settings/spaces").await;
+    s.call(
+        alice,
+        Method::PUT,
+        "/users/@me/settings/spaces",
+        Some(json!({ "folders": [
+            { "id": "favourites", "name": "Favourites", "space_ids": [space_id] },
+            { "space_ids": [cx.lobby_id] },
+        ] })),
+    )
+    .await;
+
+    // Notifications
+    let suppress = format!("/spaces/{space_id}/suppress-everyone");
+    s.call(bob, Method::PUT, &suppress, None).await;
+    s.get(bob, "/users/@me/everyone-suppressions").await;
+    s.call(bob, Method::DELETE, &suppress, None).await;
+    let settings = format!("/users/@me/spaces/{space_id}/settings");
+    s.get(bob, &settings).await;
+    s.call(
+        bob,
+        Method::PATCH,
+        &settings,
+        Some(json!({
+            "muted": false,
+            "channel_overrides": [{ "channel_id": cx.channel_id, "muted": true }],
+        })),
+    )
+    .await;
+
+    // Push
+    s.get(bob, "/users/@me/push/vapid-key").await;
+    let browser_key = p256::SecretKey::random(&mut rand::thread_rng());
+    let subscription = s
+        .call(
+            bob,
+            Method::POST,
+            "/users/@me/push/subscriptions",
+            Some(json!({
+                "endpoint": "https://push.invalid/send/1",
+                "expirationTime": null,
+                "keys": {
+                    "p256dh": accordserver::webauthn::encode(
+                        browser_key.public_key().to_encoded_point(false).as_bytes(),
+                    ),
+                    "auth": accordserver::webauthn::encode(&[9u8; 16]),
+                },
+            })),
+        )
+        .await;
+    s.get(bob, "/users/@me/push/subscriptions").await;
+    s.call(
+        bob,
+        Method::DELETE,
+        &format!("/users/@me/push/subscriptions/{}", id(&subscription)),
+        None,
+    )
+    .await;
+
+    // Relationships: a friendship, then a block.
+    let to_bob = format!("/users/@me/relationships/{}", bob.user.id);
+    let to_alice = format!("/users/@me/relationships/{}", alice.user.id);
+    s.call(alice, Method::PUT, &to_bob, Some(json!({ "type": 1 })))
+        .await;
+    s.call(bob, Method::PUT, &to_alice, Some(json!({ "type": 1 })))
+        .await;
+    s.get(alice, "/users/@me/relationships").await;
+    s.call(alice, Method::DELETE, &to_bob, None).await;
+    s.call(bob, Method::PUT, &to_alice, Some(json!({ "type": 2 })))
+        .await;
+    s.call(bob, Method::DELETE, &to_alice, None).await;
+
+    s.get(bob, "/users/@me/data-export").await;
+    let leaving = s
+        .anon(
+            Method::POST,
+            "/auth/register",
+            Some(json!({ "username": "leaving", "password": PASSWORD })),
+        )
+        .await;
+    s.call(
+        &signed_in(&leaving),
+        Method::DELETE,
+        "/users/@me",
+        Some(json!({ "password": PASSWORD })),
+    )
+    .await;
+}
+
+/// Custom emojis with their moderation queue, and stickers.
+async fn expressions(s: &mut Session, cx: &Ctx) {
+    let (alice, bob, space_id) = (&cx.alice, &cx.bob, &cx.space_id);
+    let emojis = format!("/spaces/{space_id}/emojis");
+    let emoji = s
+        .call(
+            alice,
+            Method::POST,
+            &emojis,
+            Some(json!({ "name": "wave", "image": png_data_uri() })),
+        )
+        .await;
+    let emoji = format!("{emojis}/{}", id(&emoji));
+    s.get(bob, &emojis).await;
+    s.get(bob, &emoji).await;
+    s.call(
+        alice,
+        Method::PATCH,
+        &emoji,
+        Some(json!({ "name": "hello" })),
+    )
+    .await;
+    s.call(alice, Method::DELETE, &emoji, None).await;
+
+    s.call(
+        alice,
+        Method::PATCH,
+        &format!("/spaces/{space_id}"),
+        Some(json!({ "emoji_moderation": true })),
+    )
+    .await;
+    for (name, verdict) in [("party", "approve"), ("nope", "reject")] {
+        let pending = s
+            .call(
+                bob,
+                Method::POST,
+                &emojis,
+                Some(json!({ "name": name, "image": png_data_uri() })),
+            )
+            .await;
+        s.get(alice, &format!("{emojis}/pending")).await;
+        s.call(
+            alice,
+            Method::POST,
+            &format!("{emojis}/{}/{verdict}", id(&pending)),
+            None,
+        )
+        .await;
+    }
+
+    let stickers = format!("/spaces/{space_id}/stickers");
+    let sticker = s
+        .call(
+            alice,
+            Method::POST,
+            &stickers,
+            Some(json!({ "name": "Thumbs", "tags": "thumbs", "image": png_data_uri() })),
+        )
+        .await;
+    let sticker = format!("{stickers}/{}", id(&sticker));
+    s.get(bob, &stickers).await;
+    s.get(bob, &sticker).await;
+    s.call(
+        alice,
+        Method::PATCH,
+        &sticker,
+        Some(json!({ "description": "A thumbs up" })),
+    )
+    .await;
+    s.call(alice, Method::DELETE, &sticker, None).await;
+}
+
+/// Incoming webhooks, and announcement channels that others follow.
+async fn webhooks(s: &mut Session, cx: &Ctx) {
+    let (alice, space_id, channel_id) = (&cx.alice, &cx.space_id, &cx.channel_id);
+    let hooks = format!("/channels/{channel_id}/webhooks");
+    let webhook = s
+        .call(
+            alice,
+            Method::POST,
+            &hooks,
+            Some(json!({ "name": "Deploys" })),
+        )
+        .await;
+    let token = webhook["data"]["token"].as_str().unwrap().to_string();
+    let webhook = format!("/webhooks/{}", id(&webhook));
+    s.get(alice, &hooks).await;
+    s.get(alice, &format!("/spaces/{space_id}/webhooks")).await;
+    s.get(alice, &webhook).await;
+    s.call(
+        alice,
+        Method::PATCH,
+        &webhook,
+        Some(json!({ "name": "Releases" })),
+    )
+    .await;
+    s.anon(
+        Method::POST,
+        &format!("{webhook}/{token}"),
+        Some(json!({ "content": "v1.2.3 is out", "username": "CI" })),
+    )
+    .await;
+    s.call(alice, Method::DELETE, &webhook, None).await;
+
+    // @everyone from the owner in an announcement channel badges every
+    // member, then goes out to the followers.
+    let news = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("/spaces/{space_id}/channels"),
+            Some(json!({ "name": "news", "type": "announcement" })),
+        )
+        .await;
+    let news = format!("/channels/{}", id(&news));
+    s.call(
+        alice,
+        Method::POST,
+        &format!("{news}/followers"),
+        Some(json!({ "webhook_channel_id": channel_id })),
+    )
+    .await;
+    let post = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("{news}/messages"),
+            Some(json!({ "content": "@everyone we shipped" })),
+        )
+        .await;
+    s.call(
+        alice,
+        Method::POST,
+        &format!("{news}/messages/{}/crosspost", id(&post)),
+        None,
+    )
+    .await;
+    s.settle().await;
+}
+
+/// Voice channels with their chat and soundboard, a stage, and a DM call.
+async fn voice(s: &mut Session, cx: &Ctx) {
+    let (alice, bob, space_id) = (&cx.alice, &cx.bob, &cx.space_id);
+    s.get(alice, "/voice/info").await;
+    let lounge = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("/spaces/{space_id}/channels"),
+            Some(json!({ "name": "lounge", "type": "voice" })),
+        )
+        .await;
+    let lounge = format!("/channels/{}", id(&lounge));
+    s.call(
+        alice,
+        Method::PATCH,
+        &lounge,
+        Some(json!({ "voice_chat_retention": "clear_on_empty" })),
+    )
+    .await;
+    for user in [alice, bob] {
+        s.call(
+            user,
+            Method::POST,
+            &format!("{lounge}/voice/join"),
+            Some(json!({ "self_mute": false, "self_deaf": false })),
+        )
+        .await;
+    }
+    s.get(bob, &format!("{lounge}/voice-status")).await;
+    s.call(bob, Method::POST, &format!("{lounge}/voice/stream"), None)
+        .await;
+    s.call(bob, Method::DELETE, &format!("{lounge}/voice/stream"), None)
+        .await;
+    s.call(
+        bob,
+        Method::POST,
+        &format!("{lounge}/voice/stats"),
+        Some(json!({ "rtt_ms": 42.0, "jitter_ms": 3.5, "packet_loss": 0.01, "bitrate": 64000 })),
+    )
+    .await;
+    s.get(alice, &format!("{lounge}/voice/stats")).await;
+    s.call(
+        bob,
+        Method::POST,
+        &format!("{lounge}/voice/refresh-token"),
+        None,
+    )
+    .await;
+
+    // The background jobs: a token about to run out, and the backend
+    // (nothing listens on its port) going away.
+    s.server
+        .state
+        .voice_tokens
+        .get_mut(&bob.user.id)
+        .unwrap()
+        .expires_at = chrono::Utc::now();
+    accordserver::voice::token::refresh_expiring(&s.server.state).await;
+    let livekit = s.server.state.livekit_client.as_ref().unwrap();
+    accordserver::voice::health::probe(&s.server.state, livekit, true).await;
+    s.drain_events();
+
+    let soundboard = format!("/spaces/{space_id}/soundboard");
+    let ogg = data_encoding::BASE64.encode(&[0x4F, 0x67, 0x67, 0x53, 0, 2, 0, 0]);
+    let sound = s
+        .call(
+            alice,
+            Method::POST,
+            &soundboard,
+            Some(json!({ "name": "airhorn", "audio": format!("data:audio/ogg;base64,{ogg}") })),
+        )
+        .await;
+    let sound = format!("{soundboard}/{}", id(&sound));
+    s.get(bob, &soundboard).await;
+    s.get(bob, &sound).await;
+    s.call(alice, Method::PATCH, &sound, Some(json!({ "volume": 0.5 })))
+        .await;
+    s.call(bob, Method::POST, &format!("{sound}/play"), None)
+        .await;
+    s.call(alice, Method::DELETE, &sound, None).await;
+
+    // The last one out clears the channel's chat.
+    s.call(
+        alice,
+        Method::POST,
+        &format!("{lounge}/messages"),
+        Some(json!({ "content": "brb" })),
+    )
+    .await;
+    for user in [alice, bob] {
+        s.call(user, Method::DELETE, &format!("{lounge}/voice/leave"), None)
+            .await;
+    }
+
+    let stage = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("/spaces/{space_id}/channels"),
+            Some(json!({ "name": "town-hall", "type": "stage" })),
+        )
+        .await;
+    let stage = format!("/channels/{}", id(&stage));
+    s.call(
+        bob,
+        Method::POST,
+        &format!("{stage}/voice/join"),
+        Some(json!({})),
+    )
+    .await;
+    s.call(
+        bob,
+        Method::PATCH,
+        &format!("{stage}/voice-states/@me"),
+        Some(json!({ "request_to_speak": true })),
+    )
+    .await;
+    s.call(
+        alice,
+        Method::PATCH,
+        &format!("{stage}/voice-states/{}", bob.user.id),
+        Some(json!({ "suppress": false })),
+    )
+    .await;
+    s.call(bob, Method::DELETE, &format!("{stage}/voice/leave"), None)
+        .await;
+
+    let dm = s
+        .call(
+            alice,
+            Method::POST,
+            "/users/@me/channels",
+            Some(json!({ "recipient_id": bob.user.id })),
+        )
+        .await;
+    let dm = format!("/channels/{}", id(&dm));
+    s.call(
+        alice,
+        Method::POST,
+        &format!("{dm}/voice/join"),
+        Some(json!({})),
+    )
+    .await;
+    s.call(alice, Method::POST, &format!("{dm}/call/ring"), None)
+        .await;
+    s.call(bob, Method::POST, &format!("{dm}/call/decline"), None)
+        .await;
+    s.call(
+        alice,
+        Method::POST,
+        &format!("{dm}/call/ring"),
+        Some(json!({ "metadata": { "video": false } })),
+    )
+    .await;
+    s.call(alice, Method::POST, &format!("{dm}/call/cancel"), None)
+        .await;
+    s.call(alice, Method::DELETE, &format!("{dm}/voice/leave"), None)
+        .await;
+}
+
+/// A scripted activity bundle with an icon and a leaderboard.
+fn plugin_bundle() -> Vec<u8> {
+    use std::io::Write;
+    let manifest = json!({
+        "name": "Tic-tac-toe",
+        "type": "activity",
+        "runtime": "scripted",
+        "version": "1.0.0",
+        "entry_point": "src/main.lua",
+        "max_participants": 2,
+        "lobby": true,
+        "canvas_size": [480, 360],
+        "services": { "leaderboards": [{ "id": "wins", "sort": "descending", "operator": "increment" }] },
+    });
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, contents) in [
+        ("plugin.json", manifest.to_string().into_bytes()),
+        ("src/main.lua", b"function _draw() end\n".to_vec()),
+        ("assets/icon.png", PNG.to_vec()),
+    ] {
+        zip.start_file(name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(&contents).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+/// An activity plugin: installed, downloaded, played and scored, removed.
+async fn plugins(s: &mut Session, cx: &Ctx) {
+    let (alice, bob, space_id, channel_id) = (&cx.alice, &cx.bob, &cx.space_id, &cx.channel_id);
+    let boundary = "contract-boundary";
+    let mut body = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"bundle\"; \
+         filename=\"tic-tac-toe.daccord-plugin\"\r\n\
+         Content-Type: application/zip\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(&plugin_bundle());
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("/api/v1/spaces/{space_id}/plugins"))
+        .header(header::AUTHORIZATION, alice.auth_header())
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .body(Body::from(body))
+        .unwrap();
+    let plugin_id = id(&s.send(req).await);
+    let plugin = format!("/plugins/{plugin_id}");
+    s.get(bob, &format!("/spaces/{space_id}/plugins")).await;
+    s.get(bob, &format!("{plugin}/source")).await;
+    s.get(bob, &format!("{plugin}/bundle")).await;
+    s.get(bob, &format!("{plugin}/icon")).await;
+
+    let session = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("{plugin}/sessions"),
+            Some(json!({ "channel_id": channel_id })),
+        )
+        .await;
+    let session = format!("{plugin}/sessions/{}", id(&session));
+    s.get(bob, &format!("/channels/{channel_id}/sessions/active"))
+        .await;
+    s.get(bob, &format!("/spaces/{space_id}/sessions/active"))
+        .await;
+    s.call(
+        bob,
+        Method::POST,
+        &format!("{session}/roles"),
+        Some(json!({ "user_id": bob.user.id, "role": "player" })),
+    )
+    .await;
+    s.call(
+        alice,
+        Method::PATCH,
+        &session,
+        Some(json!({ "state": "running" })),
+    )
+    .await;
+    s.call(
+        bob,
+        Method::POST,
+        &format!("{session}/actions"),
+        Some(json!({ "move": [1, 1] })),
+    )
+    .await;
+    s.call(bob, Method::POST, &format!("{session}/leave"), None)
+        .await;
+    s.call(alice, Method::DELETE, &session, None).await;
+
+    let board = format!("{plugin}/leaderboards/wins");
+    for user in [alice, bob] {
+        s.call(
+            user,
+            Method::POST,
+            &format!("{board}/submit"),
+            Some(json!({ "score": 1.0, "metadata": { "streak": 1 } })),
+        )
+        .await;
+    }
+    s.get(bob, &format!("{board}?limit=10")).await;
+    s.get(bob, &format!("{board}/around?limit=5")).await;
+    s.get(bob, &format!("{board}/user/{}", alice.user.id)).await;
+
+    s.call(
+        alice,
+        Method::DELETE,
+        &format!("/spaces/{space_id}/plugins/{plugin_id}"),
+        None,
+    )
+    .await;
+}
+
+/// A developer's application: its credentials, its bot added over OAuth2,
+/// event subscriptions, and a command a member invokes.
+async fn applications(s: &mut Session, cx: &Ctx) {
+    let (alice, bob, space_id, channel_id) = (&cx.alice, &cx.bob, &cx.space_id, &cx.channel_id);
+    let created = s
+        .call(
+            alice,
+            Method::POST,
+            "/applications",
+            Some(json!({ "name": "Dice", "description": "Rolls dice" })),
+        )
+        .await;
+    let app = &created["data"]["application"];
+    let app_id = app["id"].as_str().unwrap().to_string();
+    s.get(alice, "/applications/@me").await;
+    let redirect = "https://dice.example/callback";
+    s.call(
+        alice,
+        Method::PATCH,
+        "/applications/@me",
+        Some(json!({ "redirect_uris": [redirect] })),
+    )
+    .await;
+    let reset = s
+        .call(alice, Method::POST, "/applications/@me/reset-token", None)
+        .await;
+    let token = reset["data"]["token"].as_str().unwrap().to_string();
+    let me = s
+        .send(common::authenticated_request(
+            Method::GET,
+            "/api/v1/users/@me",
+            &format!("Bot {token}"),
+        ))
+        .await;
+    let bot = TestUser {
+        user: serde_json::from_value(me["data"].clone()).unwrap(),
+        token,
+        is_bot: true,
+    };
+    s.get(alice, "/applications/@me/ip-allowlist").await;
+    for ranges in [json!(["10.0.0.0/8"]), json!([])] {
+        s.call(
+            alice,
+            Method::PUT,
+            "/applications/@me/ip-allowlist",
+            Some(json!({ "ranges": ranges })),
+        )
+        .await;
+    }
+    let secret = s
+        .call(
+            alice,
+            Method::POST,
+            "/applications/@me/oauth2/reset-secret",
+            None,
+        )
+        .await;
+    let secret = secret["data"]["client_secret"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let first_key = s
+        .call(
+            alice,
+            Method::POST,
+            "/applications/@me/signing-keys/rotate",
+            Some(json!({})),
+        )
+        .await;
+    s.call(
+        alice,
+        Method::POST,
+        "/applications/@me/signing-keys/rotate",
+        Some(json!({ "overlap_secs": 3600 })),
+    )
+    .await;
+    s.get(alice, "/applications/@me/signing-keys").await;
+    s.call(
+        alice,
+        Method::DELETE,
+        &format!("/applications/@me/signing-keys/{}", id(&first_key)),
+        None,
+    )
+    .await;
+
+    // Alice adds the bot to her space and signs in to the application.
+    s.get(
+        alice,
+        &format!(
+            "/oauth2/authorize?client_id={app_id}&scope=identify%20bot&redirect_uri={redirect}"
+        ),
+    )
+    .await;
+    let authorized = s
+        .call(
+            alice,
+            Method::POST,
+            "/oauth2/authorize",
+            Some(json!({
+                "client_id": app_id,
+                "scope": "identify bot",
+                "redirect_uri": redirect,
+                "space_id": space_id,
+                "state": "xyz",
+            })),
+        )
+        .await;
+    let location = authorized["data"]["location"].as_str().unwrap();
+    let code = location
+        .split("code=")
+        .nth(1)
+        .unwrap()
+        .split('&')
+        .next()
+        .unwrap();
+    let tokens = s
+        .send(common::form_request(
+            "/api/v1/oauth2/token",
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", "https%3A%2F%2Fdice.example%2Fcallback"),
+                ("client_id", &app_id),
+                ("client_secret", &secret),
+            ],
+        ))
+        .await;
+    s.send(common::form_request(
+        "/api/v1/oauth2/token/revoke",
+        &[
+            ("token", tokens["access_token"].as_str().unwrap()),
+            ("client_id", &app_id),
+            ("client_secret", &secret),
+        ],
+    ))
+    .await;
+
+    // Event subscriptions. Nothing resolves the URL; the delivery is marked
+    // failed by hand so it can be retried.
+    let subscriptions = "/applications/@me/event-subscriptions";
+    let subscription = s
+        .call(
+            alice,
+            Method::POST,
+            subscriptions,
+            Some(json!({
+                "url": "https://invalid.invalid/hook",
+                "event_types": ["message.create"],
+                "space_ids": [space_id],
+            })),
+        )
+        .await;
+    let subscription = format!("{subscriptions}/{}", id(&subscription));
+    s.get(alice, subscriptions).await;
+    s.call(
+        alice,
+        Method::PATCH,
+        &subscription,
+        Some(json!({ "event_types": ["message.create", "member.join"] })),
+    )
+    .await;
+    let broadcast = GatewayBroadcast {
+        space_id: Some(space_id.clone()),
+        target_user_ids: None,
+        event: json!({
+            "op": 0,
+            "type": "message.create",
+            "data": { "channel_id": channel_id, "content": "hi" },
+        }),
+        intent: "messages".to_string(),
+        role_mention_user_ids: None,
+    };
+    accordserver::event_subscriptions::enqueue(&s.server.state, &broadcast)
+        .await
+        .unwrap();
+    sqlx::query(&accordserver::db::q(
+        "UPDATE event_deliveries SET status = 'failed', next_attempt_at = NULL",
+    ))
+    .execute(s.server.pool())
+    .await
+    .unwrap();
+    let deliveries = s
+        .get(alice, &format!("{subscription}/deliveries?status=failed"))
+        .await;
+    s.call(
+        alice,
+        Method::POST,
+        &format!(
+            "{subscription}/deliveries/{}/retry",
+            deliveries["data"][0]["id"].as_str().unwrap()
+        ),
+        None,
+    )
+    .await;
+    s.call(alice, Method::DELETE, &subscription, None).await;
+
+    // Commands, answered by the bot over the gateway.
+    let mut bot_ws = s.connect(&bot.gateway_token(), json!({})).await;
+    s.get(&bot, "/gateway/bot").await;
+    let global = s
+        .call(
+            &bot,
+            Method::POST,
+            &format!("/applications/{app_id}/commands"),
+            Some(json!({
+                "name": "roll",
+                "description": "Roll a die",
+                "options": [{ "name": "sides", "description": "How many sides", "type": "integer", "required": false }],
+            })),
+        )
+        .await;
+    let global = format!("/applications/{app_id}/commands/{}", id(&global));
+    s.get(alice, &format!("/applications/{app_id}/commands"))
+        .await;
+    s.get(alice, &global).await;
+    let space_commands = format!("/applications/{app_id}/spaces/{space_id}/commands");
+    s.call(
+        &bot,
+        Method::POST,
+        &space_commands,
+        Some(json!({ "name": "flip", "description": "Flip a coin" })),
+    )
+    .await;
+    s.get(&bot, &space_commands).await;
+    s.get(bob, &format!("/spaces/{space_id}/commands")).await;
+    let command_id = global.rsplit('/').next().unwrap().to_string();
+    s.call(
+        alice,
+        Method::POST,
+        "/interactions",
+        Some(json!({
+            "command_id": command_id,
+            "channel_id": channel_id,
+            "options": [{ "name": "sides", "value": 6 }],
+        })),
+    )
+    .await;
+    let interaction = s.recv(&mut bot_ws, "interaction.create").await;
+    s.anon(
+        Method::POST,
+        &format!(
+            "/interactions/{}/{}/callback",
+            interaction["id"].as_str().unwrap(),
+            interaction["token"].as_str().unwrap()
+        ),
+        Some(json!({ "type": "channel_message", "data": { "content": "You rolled a 4" } })),
+    )
+    .await;
+    s.call(&bot, Method::DELETE, &global, None).await;
+}
+
+/// A second federated server, serving plain http on a port it knows before
+/// it starts, that trusts `origin` and homes a federated space. Returns its
+/// domain and the space.
+async fn federation_peer(origin: &accordserver::federation::FederationContext) -> (String, String) {
+    // Loopback and plain http are only allowed with this set.
+    std::env::set_var("ACCORD_FEDERATION_ALLOW_INSECURE", "1");
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let domain = listener.local_addr().unwrap().to_string();
+    let mut peer = TestServer::new().await;
+    let config = accordserver::config::FederationConfig {
+        domain: domain.clone(),
+        public_url: format!("http://{domain}"),
+        enabled: true,
+    };
+    peer.state.federation = Some(Arc::new(
+        accordserver::federation::FederationContext::build(&config, &peer.state.storage_path)
+            .unwrap(),
+    ));
+    accordserver::db::federation::upsert_peer(
+        peer.pool(),
+        &origin.domain,
+        &origin.identity.public_key_b64(),
+        &origin.inbox_url(),
+        "trusted",
+    )
+    .await
+    .unwrap();
+    let owner = peer.create_user_with_token("faraway").await;
+    let space_id = peer.create_space(&owner.user.id, "Faraway").await;
+    peer.create_channel(&space_id, "general").await;
+    accordserver::db::federation::set_space_federation_enabled(peer.pool(), &space_id, true)
+        .await
+        .unwrap();
+    let app = peer.router();
+    tokio::spawn(async move {
+        // Keeps the peer's state alive as long as it serves.
+        let _peer = peer;
+        axum::serve(listener, app).await.unwrap();
+    });
+    (domain, space_id)
+}
+
+/// The instance admin's tools, the public instance endpoints, and joining a
+/// space on another server.
+async fn admin(s: &mut Session, cx: &Ctx) {
+    let (admin, alice) = (&cx.admin, &cx.alice);
+    s.get(alice, "/settings").await;
+    s.get(alice, "/version").await;
+    s.get(alice, "/gateway").await;
+    s.get(admin, "/admin/settings").await;
+    s.get(admin, "/admin/metrics").await;
+    s.get(admin, "/admin/doctor").await;
+    s.call(admin, Method::POST, "/admin/doctor/repair", None)
+        .await;
+
+    // Spaces
+    s.get(admin, "/admin/spaces?limit=5").await;
+    s.call(
+        admin,
+        Method::PATCH,
+        &format!("/admin/spaces/{}", cx.lobby_id),
+        Some(json!({ "max_emojis": 100 })),
+    )
+    .await;
+    let doomed = s.server.create_space(&alice.user.id, "Doomed").await;
+    s.call(
+        admin,
+        Method::DELETE,
+        &format!("/admin/spaces/{doomed}"),
+        None,
+    )
+    .await;
+    s.call(admin, Method::DELETE, "/admin/vanity-urls/contract", None)
+        .await;
+
+    // Reports: the one filed against a user is still open.
+    let reports = s.get(admin, "/admin/reports?status=pending").await;
+    let report = format!(
+        "/admin/reports/{}",
+        id(&json!({ "data": reports["data"][0] }))
+    );
+    s.get(admin, &report).await;
+    s.call(
+        admin,
+        Method::PATCH,
+        &report,
+        Some(json!({ "status": "dismissed" })),
+    )
+    .await;
+
+    // Users
+    let user = s.server.create_user_with_token("troublemaker").await;
+    let user_path = format!("/admin/users/{}", user.user.id);
+    s.get(admin, "/admin/users?limit=5").await;
+    s.call(
+        admin,
+        Method::PATCH,
+        &user_path,
+        Some(json!({ "display_name": "Reformed" })),
+    )
+    .await;
+    s.call(admin, Method::POST, &format!("{user_path}/logout"), None)
+        .await;
+    s.call(
+        admin,
+        Method::POST,
+        &format!("{user_path}/reset-password"),
+        Some(json!({ "new_password": PASSWORD })),
+    )
+    .await;
+    s.call(admin, Method::DELETE, &user_path, None).await;
+
+    let blocked = "/admin/blocklists/email_domain/spam.example";
+    s.call(
+        admin,
+        Method::PUT,
+        blocked,
+        Some(json!({ "reason": "spam" })),
+    )
+    .await;
+    s.get(admin, "/admin/blocklists/email_domain").await;
+    s.call(admin, Method::DELETE, blocked, None).await;
+
+    // Content safety: a listed hash quarantines the message it's attached to.
+    s.call(
+        admin,
+        Method::PATCH,
+        "/admin/settings",
+        Some(json!({ "content_safety_providers": "hash_list" })),
+    )
+    .await;
+    let bad_hash = accordserver::blocklist::hash_bytes(b"known bad file");
+    let hash = format!("/admin/content-safety/hashes/{bad_hash}");
+    s.call(
+        admin,
+        Method::PUT,
+        &hash,
+        Some(json!({ "label": "test set" })),
+    )
+    .await;
+    s.get(admin, "/admin/content-safety/hashes").await;
+    let message = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("/channels/{}/messages", cx.channel_id),
+            Some(json!({ "content": "look at this" })),
+        )
+        .await;
+    let item = accordserver::content_safety::ScanItem {
+        message: accordserver::db::messages::get_message_row(s.server.pool(), &id(&message))
+            .await
+            .unwrap(),
+        attachments: vec![accordserver::content_safety::ScannedAttachment {
+            id: "1".into(),
+            url: "/cdn/attachments/x.png".into(),
+            content_type: Some("image/png".into()),
+            sha256: bad_hash.clone(),
+        }],
+    };
+    let flag = accordserver::content_safety::scan(&s.server.state, &item)
+        .await
+        .unwrap()
+        .unwrap();
+    s.drain_events();
+    s.get(admin, "/admin/content-safety/flags?status=pending")
+        .await;
+    let flag = format!("/admin/content-safety/flags/{}", flag.id);
+    s.get(admin, &flag).await;
+    s.call(
+        admin,
+        Method::PATCH,
+        &flag,
+        Some(json!({ "status": "released" })),
+    )
+    .await;
+    s.call(admin, Method::DELETE, &hash, None).await;
+
+    // Federation
+    let (peer_domain, remote_space) =
+        federation_peer(s.server.state.federation.as_ref().unwrap()).await;
+    s.call(
+        admin,
+        Method::POST,
+        "/admin/federation/peers",
+        Some(json!({ "domain": peer_domain, "trusted": true })),
+    )
+    .await;
+    s.get(admin, "/admin/federation/peers").await;
+    let peer = format!("/admin/federation/peers/{peer_domain}");
+    s.call(
+        admin,
+        Method::PATCH,
+        &peer,
+        Some(json!({ "refresh": true, "trusted": true })),
+    )
+    .await;
+    s.call(
+        alice,
+        Method::POST,
+        "/federation/spaces/join",
+        Some(json!({ "domain": peer_domain, "space_id": remote_space })),
+    )
+    .await;
+    s.call(admin, Method::DELETE, &peer, None).await;
+}
+
+/// The `/mcp` endpoint's moderation tools, which broadcast the `ban.*`
+/// events. `/mcp` sits outside `/api/v1`, so only its events are recorded.
+async fn mcp(s: &mut Session, cx: &Ctx) {
+    let target = s.server.create_user_with_token("raider").await;
+    s.server.add_member(&cx.space_id, &target.user.id).await;
+    for tool in ["ban_user", "unban_user"] {
+        let req = common::authenticated_json_request(
+            Method::POST,
+            "/mcp",
+            &format!("Bearer {MCP_API_KEY}"),
+            &json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": {
+                    "name": tool,
+                    "arguments": { "space_id": cx.space_id, "user_id": target.user.id },
+                },
+            }),
+        );
+        let response = s.app.clone().oneshot(req).await.unwrap();
+        assert!(response.status().is_success(), "{tool}");
+        s.drain_events();
+    }
+}
+
+/// Signing a device in by approving the ticket it shows.
+async fn login_tickets(s: &mut Session, cx: &Ctx) {
+    let ticket = s
+        .anon(
+            Method::POST,
+            "/auth/login-tickets",
+            Some(json!({ "device_name": "Living room TV" })),
+        )
+        .await;
+    let mut device = s.open_ws().await;
+    send_op(
+        &mut device,
+        12,
+        json!({ "ticket": ticket["data"]["ticket"], "secret": ticket["data"]["secret"] }),
+    )
+    .await;
+    let path = format!(
+        "/auth/login-tickets/{}",
+        ticket["data"]["ticket"].as_str().unwrap()
+    );
+    s.get(&cx.alice, &path).await;
+    s.call(&cx.alice, Method::POST, &format!("{path}/approve"), None)
+        .await;
+    s.recv(&mut device, "login_ticket.approved").await;
+}
+
+/// Gateway-only traffic: resuming, presences, member lists, lazily synced
+/// spaces, voice joins, and guests.
+async fn gateway(s: &mut Session, cx: &Ctx) {
+    let (alice, bob) = (&cx.alice, &cx.bob);
+
+    // A dropped connection resumes where it left off.
+    let mut ws = s.open_ws().await;
+    send_op(
+        &mut ws,
+        2,
+        json!({ "token": bob.gateway_token(), "intents": ["spaces"] }),
+    )
+    .await;
+    let ready = s.recv(&mut ws, "ready").await;
+    drop(ws);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let mut ws = s.open_ws().await;
+    send_op(
+        &mut ws,
+        3,
+        json!({ "token": bob.gateway_token(), "session_id": ready["session_id"], "seq": 1 }),
+    )
+    .await;
+    s.recv(&mut ws, "resumed").await;
+    drop(ws);
+
+    let mut bob_ws = s
+        .connect(
+            &bob.gateway_token(),
+            json!({
+                "intents": ["spaces", "members", "presences", "voice_states"],
+                "capabilities": { "wants_presence_batching": true, "lazy_spaces": true },
+            }),
+        )
+        .await;
+    let mut alice_ws = s
+        .connect(
+            &alice.gateway_token(),
+            json!({ "intents": ["spaces", "voice_states"] }),
+        )
+        .await;
+    send_op(&mut alice_ws, 8, json!({ "status": "idle" })).await;
+    s.recv(&mut bob_ws, "presence.update_batch").await;
+    s.drain_events();
+
+    send_op(
+        &mut bob_ws,
+        10,
+        json!({ "space_id": cx.space_id, "limit": 10 }),
+    )
+    .await;
+    s.recv(&mut bob_ws, "space.members_chunk").await;
+    send_op(&mut bob_ws, 11, json!({ "space_ids": [cx.space_id] })).await;
+    s.recv(&mut bob_ws, "space.sync").await;
+
+    let hangout = s
+        .call(
+            alice,
+            Method::POST,
+            &format!("/spaces/{}/channels", cx.space_id),
+            Some(json!({ "name": "hangout", "type": "voice" })),
+        )
+        .await;
+    let voice = |channel_id: Value| json!({ "space_id": cx.space_id, "channel_id": channel_id, "self_mute": false, "self_deaf": false });
+    send_op(&mut alice_ws, 9, voice(hangout["data"]["id"].clone())).await;
+    s.recv(&mut alice_ws, "voice.server_update").await;
+    send_op(&mut alice_ws, 9, voice(Value::Null)).await;
+    s.settle().await;
+
+    // Guests are counted, not announced.
+    let guest = s.anon(Method::POST, "/auth/guest", None).await;
+    let _guest_ws = s
+        .connect(
+            &format!("Bearer {}", guest["token"].as_str().unwrap()),
+            json!({}),
+        )
+        .await;
+    s.settle().await;
+}
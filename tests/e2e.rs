@@ -1,7 +1,8 @@
 mod common;
 
 use common::{
-    authenticated_json_request, authenticated_request, json_request, parse_body, TestServer,
+    assert_query_budget, authenticated_json_request, authenticated_request, form_request,
+    json_request, parse_body, query_count, ready_query_count, TestServer,
 };
 use futures_util::{SinkExt, StreamExt};
 use http::{Method, StatusCode};
@@ -39,6 +40,131 @@ async fn test_invalid_token_returns_401() {
     assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
 
+#[tokio::test]
+async fn test_password_reset_and_email_verification() {
+    use accordserver::db::email_tokens::{self, KIND_PASSWORD_RESET, KIND_VERIFY_EMAIL};
+
+    let mut server = TestServer::new().await;
+    server.state.require_verified_email = true;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    sqlx::query(&accordserver::db::q(
+        "UPDATE users SET email = ? WHERE id = ?",
+    ))
+    .bind("alice@example.com")
+    .bind(&alice.user.id)
+    .execute(server.pool())
+    .await
+    .unwrap();
+
+    // Without SMTP there is nothing to send codes with.
+    let response = server
+        .router()
+        .oneshot(json_request(
+            Method::POST,
+            "/api/v1/auth/password-reset",
+            &serde_json::json!({ "email": "alice@example.com" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Redeem a reset code as if it had been emailed.
+    let code = email_tokens::create_token(
+        server.pool(),
+        &alice.user.id,
+        KIND_PASSWORD_RESET,
+        "alice@example.com",
+        3600,
+    )
+    .await
+    .unwrap();
+    let confirm = |code: String| {
+        server.router().oneshot(json_request(
+            Method::POST,
+            "/api/v1/auth/password-reset/confirm",
+            &serde_json::json!({ "token": code, "new_password": "a-brand-new-password" }),
+        ))
+    };
+    let response = confirm(code.clone()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = confirm(code).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Old sessions are gone; the new password works and the address counts
+    // as verified.
+    let response = server
+        .router()
+        .oneshot(authenticated_request(
+            Method::GET,
+            "/api/v1/users/@me",
+            &alice.auth_header(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = server
+        .router()
+        .oneshot(json_request(
+            Method::POST,
+            "/api/v1/auth/login",
+            &serde_json::json!({ "username": alice.user.username, "password": "a-brand-new-password" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        parse_body(response).await["data"]["user"]["email_verified"],
+        true
+    );
+
+    // Unverified users can't join public spaces until they verify.
+    let space_id = server.create_public_space(&alice.user.id, "Open").await;
+    let join = || {
+        server.router().oneshot(authenticated_request(
+            Method::POST,
+            &format!("/api/v1/spaces/{space_id}/join"),
+            &bob.auth_header(),
+        ))
+    };
+    let response = join().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "email_unverified"
+    );
+
+    sqlx::query(&accordserver::db::q(
+        "UPDATE users SET email = ? WHERE id = ?",
+    ))
+    .bind("bob@example.com")
+    .bind(&bob.user.id)
+    .execute(server.pool())
+    .await
+    .unwrap();
+    let code = email_tokens::create_token(
+        server.pool(),
+        &bob.user.id,
+        KIND_VERIFY_EMAIL,
+        "bob@example.com",
+        3600,
+    )
+    .await
+    .unwrap();
+    let response = server
+        .router()
+        .oneshot(json_request(
+            Method::POST,
+            "/api/v1/auth/verify-email",
+            &serde_json::json!({ "token": code }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = join().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
 // =========================================================================
 // Users
 // =========================================================================
@@ -92,6 +218,64 @@ async fn test_update_current_user() {
     assert_eq!(body["data"]["display_name"], "Alice Wonderland");
 }
 
+#[tokio::test]
+async fn test_pronouns_and_timezone() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "ProfileSpace").await;
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/users/@me",
+        &alice.auth_header(),
+        &serde_json::json!({ "pronouns": "she/her", "timezone": "Asia/Kolkata" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["pronouns"], "she/her");
+    assert_eq!(body["data"]["timezone"], "Asia/Kolkata");
+    assert_eq!(body["data"]["utc_offset_minutes"], 330);
+
+    for bad in [
+        serde_json::json!({ "timezone": "Nowhere/Special" }),
+        serde_json::json!({ "pronouns": "x".repeat(41) }),
+    ] {
+        let req = authenticated_json_request(
+            Method::PATCH,
+            "/api/v1/users/@me",
+            &alice.auth_header(),
+            &bad,
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // Per-space override, then cleared back to the account's.
+    let uri = format!("/api/v1/spaces/{space_id}/members/@me");
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "timezone": "UTC" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["timezone"], "UTC");
+    assert_eq!(body["data"]["utc_offset_minutes"], 0);
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "timezone": "" }),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert!(body["data"]["timezone"].is_null());
+    assert!(body["data"]["utc_offset_minutes"].is_null());
+}
+
 #[tokio::test]
 async fn test_get_user_by_id() {
     let server = TestServer::new().await;
@@ -312,6 +496,59 @@ async fn test_list_channels_in_space() {
     assert_eq!(channels.len(), 2); // #general + extra
 }
 
+#[tokio::test]
+async fn test_reorder_channels_renumbers_and_checks_version() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Ordered").await;
+    // All at position 0 alongside #general.
+    let a = server.create_channel(&space_id, "a").await;
+    let b = server.create_channel(&space_id, "b").await;
+    let uri = format!("/api/v1/spaces/{space_id}/channels");
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &uri,
+        &alice.auth_header(),
+        &serde_json::json!({
+            "channels": [{ "id": b, "position": 0 }, { "id": a, "position": 1 }],
+            "expected_version": 0
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let order: Vec<(&str, i64)> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| (c["id"].as_str().unwrap(), c["position"].as_i64().unwrap()))
+        .collect();
+    assert_eq!(order.len(), 3);
+    assert_eq!(order[0], (b.as_str(), 0));
+    assert_eq!(order[1], (a.as_str(), 1));
+    assert_eq!(order[2].1, 2);
+
+    // A client still on version 0 has lost the race.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "channels": [{ "id": a, "position": 0 }], "expected_version": 0 }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "channel_order_conflict"
+    );
+
+    let space = accordserver::db::spaces::get_space_row(server.pool(), &space_id)
+        .await
+        .unwrap();
+    assert_eq!(space.channel_order_version, 1);
+}
+
 // =========================================================================
 // Messages
 // =========================================================================
@@ -414,6 +651,64 @@ async fn test_list_messages_with_cursor() {
     assert_eq!(body["cursor"]["has_more"], true);
 }
 
+#[tokio::test]
+async fn test_list_reactions_pages_reactors() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Reactions").await;
+    let channel_id = server.create_channel(&space_id, "chat").await;
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+        &serde_json::json!({ "content": "vote" }),
+    );
+    let message = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let message_id = message["data"]["id"].as_str().unwrap().to_string();
+    let reactions_uri =
+        format!("/api/v1/channels/{channel_id}/messages/{message_id}/reactions/%F0%9F%91%8D");
+
+    let bob = server.create_user_with_token("bob").await;
+    let carol = server.create_user_with_token("carol").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    server.add_member(&space_id, &carol.user.id).await;
+    let reactors = [&alice, &bob, &carol];
+    for user in reactors {
+        let req = authenticated_request(
+            Method::PUT,
+            &format!("{reactions_uri}/@me"),
+            &user.auth_header(),
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let mut expected: Vec<String> = reactors.iter().map(|u| u.user.id.clone()).collect();
+    expected.sort();
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("{reactions_uri}?limit=2&with_member=true"),
+        &alice.auth_header(),
+    );
+    let page = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(page["data"], serde_json::json!(expected[..2]));
+    assert_eq!(page["cursor"]["after"], expected[1].as_str());
+    assert_eq!(
+        page["members"][&expected[0]]["user_id"],
+        expected[0].as_str()
+    );
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("{reactions_uri}?limit=2&after={}", expected[1]),
+        &alice.auth_header(),
+    );
+    let page = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(page["data"], serde_json::json!(expected[2..]));
+    assert!(page.get("cursor").is_none());
+    assert!(page.get("members").is_none());
+}
+
 #[tokio::test]
 async fn test_other_user_cannot_edit_message() {
     let server = TestServer::new().await;
@@ -447,6 +742,57 @@ async fn test_other_user_cannot_edit_message() {
     assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
+#[tokio::test]
+async fn test_message_edit_history() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "HistorySpace").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let channel_id = server.create_channel(&space_id, "chat").await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &bob.auth_header(),
+        &serde_json::json!({ "content": "first" }),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let msg_id = body["data"]["id"].as_str().unwrap().to_string();
+
+    for content in ["second", "third"] {
+        let req = authenticated_json_request(
+            Method::PATCH,
+            &format!("/api/v1/channels/{channel_id}/messages/{msg_id}"),
+            &bob.auth_header(),
+            &serde_json::json!({ "content": content }),
+        );
+        assert_eq!(
+            server.router().oneshot(req).await.unwrap().status(),
+            StatusCode::OK
+        );
+    }
+
+    let history_url = format!("/api/v1/channels/{channel_id}/messages/{msg_id}/history");
+
+    // The author alone can't read it; it's for moderators.
+    let req = authenticated_request(Method::GET, &history_url, &bob.auth_header());
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let req = authenticated_request(Method::GET, &history_url, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let revisions = parse_body(response).await["data"].clone();
+    let revisions = revisions.as_array().unwrap();
+    assert_eq!(revisions.len(), 2);
+    assert_eq!(revisions[0]["content"], "first");
+    assert_eq!(revisions[1]["content"], "second");
+    assert_eq!(revisions[1]["editor_id"], bob.user.id.as_str());
+}
+
 // =========================================================================
 // Public Spaces & Space-Level Invites
 // =========================================================================
@@ -490,6 +836,137 @@ async fn test_space_defaults_to_private() {
     assert_eq!(body["data"]["public"], false);
 }
 
+#[tokio::test]
+async fn test_space_template_sets_initial_layout() {
+    let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
+    let alice = server.create_user_with_token("alice").await;
+
+    let create = |auth: String, body: serde_json::Value| {
+        let req = authenticated_json_request(Method::POST, "/api/v1/spaces", &auth, &body);
+        server.router().oneshot(req)
+    };
+    async fn layout(
+        server: &TestServer,
+        auth: &str,
+        space_id: &str,
+    ) -> (serde_json::Value, serde_json::Value) {
+        let mut data = Vec::new();
+        for what in ["channels", "roles"] {
+            let req = authenticated_request(
+                Method::GET,
+                &format!("/api/v1/spaces/{space_id}/{what}"),
+                auth,
+            );
+            data.push(
+                parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone(),
+            );
+        }
+        (data[0].clone(), data[1].clone())
+    }
+
+    // Built-in layout: #general plus Moderator and Admin.
+    let response = create(alice.auth_header(), serde_json::json!({ "name": "Plain" }))
+        .await
+        .unwrap();
+    let space_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let (channels, roles) = layout(&server, &alice.auth_header(), &space_id).await;
+    let names: Vec<_> = channels
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| &c["name"])
+        .collect();
+    assert_eq!(names, ["general"]);
+    assert_eq!(roles.as_array().unwrap().len(), 3);
+
+    // The instance default replaces it.
+    let template = serde_json::json!({
+        "roles": [{ "name": "Regular", "permissions": ["send_messages"] }],
+        "channels": [
+            { "name": "Text", "type": "category" },
+            { "name": "lobby", "parent": "Text", "topic": "Say hi" },
+            { "name": "Voice", "type": "category" },
+            { "name": "Hangout", "type": "voice", "parent": "Voice" },
+        ]
+    });
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/admin/settings",
+        &admin.auth_header(),
+        &serde_json::json!({ "default_space_template": template }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = create(alice.auth_header(), serde_json::json!({ "name": "Custom" }))
+        .await
+        .unwrap();
+    let space_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let (channels, roles) = layout(&server, &alice.auth_header(), &space_id).await;
+    let channels = channels.as_array().unwrap();
+    let by_name = |name: &str| channels.iter().find(|c| c["name"] == name).unwrap();
+    assert_eq!(channels.len(), 4);
+    assert_eq!(by_name("lobby")["parent_id"], by_name("Text")["id"]);
+    assert_eq!(by_name("lobby")["topic"], "Say hi");
+    assert_eq!(by_name("Hangout")["type"], "voice");
+    assert_eq!(by_name("Hangout")["parent_id"], by_name("Voice")["id"]);
+    let role_names: Vec<_> = roles
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| &r["name"])
+        .collect();
+    assert_eq!(role_names, ["@everyone", "Regular"]);
+
+    // A template in the request wins over the instance default.
+    let response = create(
+        alice.auth_header(),
+        serde_json::json!({
+            "name": "Own",
+            "template": { "channels": [{ "name": "announcements", "type": "announcement" }] }
+        }),
+    )
+    .await
+    .unwrap();
+    let space_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let (channels, _) = layout(&server, &alice.auth_header(), &space_id).await;
+    assert_eq!(channels[0]["name"], "announcements");
+    assert_eq!(channels.as_array().unwrap().len(), 1);
+
+    // Templates are validated up front.
+    let response = create(
+        alice.auth_header(),
+        serde_json::json!({
+            "name": "Broken",
+            "template": { "channels": [{ "name": "orphan", "parent": "Nowhere" }] }
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Clearing the setting restores the built-in layout.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/admin/settings",
+        &admin.auth_header(),
+        &serde_json::json!({ "default_space_template": null }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(parse_body(response).await["data"]["default_space_template"].is_null());
+}
+
 #[tokio::test]
 async fn test_update_space_public_flag() {
     let server = TestServer::new().await;
@@ -654,52 +1131,287 @@ async fn test_accept_space_level_invite() {
 }
 
 #[tokio::test]
-async fn test_join_public_space() {
+async fn test_used_up_invites_are_expired() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
     let bob = server.create_user_with_token("bob").await;
-    let space_id = server
-        .create_public_space(&alice.user.id, "Open Space")
-        .await;
+    let carol = server.create_user_with_token("carol").await;
+    let space_id = server.create_space(&alice.user.id, "OneShot").await;
 
-    // Bob joins the public space without an invite
-    let app = server.router();
-    let req = authenticated_request(
+    let req = authenticated_json_request(
         Method::POST,
-        &format!("/api/v1/spaces/{space_id}/join"),
-        &bob.auth_header(),
+        &format!("/api/v1/spaces/{space_id}/invites"),
+        &alice.auth_header(),
+        &serde_json::json!({ "max_uses": 1 }),
     );
-    let response = app.oneshot(req).await.unwrap();
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let code = body["data"]["code"].as_str().unwrap().to_string();
+    let accept = format!("/api/v1/invites/{code}/accept");
+
+    let req = authenticated_request(Method::POST, &accept, &bob.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    assert_eq!(body["data"]["space_id"], space_id);
 
-    // Verify Bob is now a member
-    let app = server.router();
+    let req = authenticated_request(Method::POST, &accept, &carol.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "invite_expired"
+    );
+
+    // Gone from the list, and from the table once swept.
     let req = authenticated_request(
         Method::GET,
-        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
-        &bob.auth_header(),
+        &format!("/api/v1/spaces/{space_id}/invites"),
+        &alice.auth_header(),
     );
-    let response = app.oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert!(body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|invite| invite["code"] != code.as_str()));
+    let swept = accordserver::db::invites::delete_expired_invites(server.pool())
+        .await
+        .unwrap();
+    assert_eq!(swept, 1);
 }
 
 #[tokio::test]
-async fn test_join_private_space_forbidden() {
+async fn test_invite_stats_and_join_attribution() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
     let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "Private Space").await;
+    let carol = server.create_user_with_token("carol").await;
+    let dave = server.create_user_with_token("dave").await;
+    let space_id = server.create_space(&alice.user.id, "Tracked").await;
+    server.add_member(&space_id, &dave.user.id).await;
 
-    // Bob tries to join a private space
-    let app = server.router();
-    let req = authenticated_request(
+    let req = authenticated_json_request(
         Method::POST,
-        &format!("/api/v1/spaces/{space_id}/join"),
-        &bob.auth_header(),
+        &format!("/api/v1/spaces/{space_id}/invites"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
     );
-    let response = app.oneshot(req).await.unwrap();
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let code = body["data"]["code"].as_str().unwrap().to_string();
+    for user in [&bob, &carol] {
+        let req = authenticated_request(
+            Method::POST,
+            &format!("/api/v1/invites/{code}/accept"),
+            &user.auth_header(),
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/spaces/{space_id}/members/@me"),
+        &carol.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Moderators see where a member came from; others see null.
+    let member_uri = format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id);
+    let req = authenticated_request(Method::GET, &member_uri, &alice.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["source_invite"], code.as_str());
+    let req = authenticated_request(Method::GET, &member_uri, &dave.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert!(body["data"]["source_invite"].is_null());
+
+    let stats_uri = format!("/api/v1/spaces/{space_id}/invites/{code}/stats");
+    let req = authenticated_request(Method::GET, &stats_uri, &dave.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let req = authenticated_request(Method::GET, &stats_uri, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let stats = parse_body(response).await["data"].clone();
+    assert_eq!(stats["inviter_id"], alice.user.id.as_str());
+    assert_eq!(stats["uses"], 2);
+    assert_eq!(stats["members_remaining"], 1);
+    let days = stats["uses_over_time"].as_array().unwrap();
+    assert_eq!(days.len(), 1);
+    assert_eq!(days[0]["uses"], 2);
+    assert_eq!(stats["recent_joins"].as_array().unwrap().len(), 2);
+
+    // Still there once the invite is deleted; unknown codes are 404s.
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/invites/{code}"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let req = authenticated_request(Method::GET, &stats_uri, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(parse_body(response).await["data"]["uses"], 2);
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/invites/nope/stats"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_vanity_invite_urls() {
+    let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Vanity").await;
+    let other_space = server.create_space(&bob.user.id, "Copycat").await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    let claim = |space: &str, auth: String, code: serde_json::Value| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::PATCH,
+            &format!("/api/v1/spaces/{space}/vanity-url"),
+            &auth,
+            &serde_json::json!({ "code": code }),
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+
+    // Needs manage_space, and a valid slug.
+    let response = claim(&space_id, bob.auth_header(), "cool-club".into()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = claim(&space_id, alice.auth_header(), "no spaces!".into()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = claim(&space_id, alice.auth_header(), "Cool-Club".into()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(parse_body(response).await["data"]["code"], "cool-club");
+
+    let response = claim(&other_space, bob.auth_header(), "cool-club".into()).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "vanity_code_taken"
+    );
+
+    // Works like any invite code.
+    let carol = server.create_user_with_token("carol").await;
+    let req = authenticated_request(
+        Method::GET,
+        "/api/v1/invites/cool-club",
+        &carol.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["space_id"], space_id);
+    assert_eq!(body["data"]["vanity"], true);
+    let req = authenticated_request(
+        Method::POST,
+        "/api/v1/invites/cool-club/accept",
+        &carol.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/vanity-url"),
+        &alice.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["code"], "cool-club");
+    assert_eq!(body["data"]["uses"], 1);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/audit-logs?action_type=vanity_url_update"),
+        &alice.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+
+    // Revoked by the instance: the space loses it and nobody gets it back.
+    let req = authenticated_request(
+        Method::DELETE,
+        "/api/v1/admin/vanity-urls/cool-club",
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let req = authenticated_request(
+        Method::DELETE,
+        "/api/v1/admin/vanity-urls/cool-club",
+        &admin.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(parse_body(response).await["data"]["space_id"], space_id);
+
+    let req = authenticated_request(
+        Method::GET,
+        "/api/v1/invites/cool-club",
+        &carol.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = claim(&other_space, bob.auth_header(), "cool-club".into()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "vanity_code_revoked"
+    );
+}
+
+#[tokio::test]
+async fn test_join_public_space() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server
+        .create_public_space(&alice.user.id, "Open Space")
+        .await;
+
+    // Bob joins the public space without an invite
+    let app = server.router();
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/join"),
+        &bob.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["space_id"], space_id);
+
+    // Verify Bob is now a member
+    let app = server.router();
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
+        &bob.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_join_private_space_forbidden() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Private Space").await;
+
+    // Bob tries to join a private space
+    let app = server.router();
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/join"),
+        &bob.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
@@ -782,6 +1494,16 @@ async fn test_introduction_message_only_posted_once_per_account() {
             preferred_locale: None,
             public: None,
             allow_guest_access: None,
+            emoji_moderation: None,
+            mfa_level: None,
+            widget_enabled: None,
+            widget_channel_id: None,
+            message_sharing: None,
+            invite_preview_required: None,
+            stream_max_height: None,
+            stream_max_fps: None,
+            category: None,
+            tags: None,
         },
         server.state.db_is_postgres,
     )
@@ -1175,6 +1897,114 @@ async fn test_non_member_cannot_create_ban() {
     assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
+#[tokio::test]
+async fn test_role_member_count_and_listing() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let bob = server.create_user_with_token("bob").await;
+    let carol = server.create_user_with_token("carol").await;
+    let space_id = server.create_space(&owner.user.id, "TestSpace").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    server.add_member(&space_id, &carol.user.id).await;
+    let role_id = server.create_role(&space_id, "Helpers", &[]).await;
+
+    let role_count = |body: &serde_json::Value| {
+        body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|r| r["id"] == role_id.as_str())
+            .unwrap()["member_count"]
+            .clone()
+    };
+    let list_roles = || async {
+        let req = authenticated_request(
+            Method::GET,
+            &format!("/api/v1/spaces/{space_id}/roles"),
+            &owner.auth_header(),
+        );
+        parse_body(server.router().oneshot(req).await.unwrap()).await
+    };
+
+    for user in [&bob, &carol] {
+        let req = authenticated_request(
+            Method::PUT,
+            &format!(
+                "/api/v1/spaces/{space_id}/members/{}/roles/{role_id}",
+                user.user.id
+            ),
+            &owner.auth_header(),
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    // Assigning again doesn't count twice.
+    server.assign_role(&space_id, &bob.user.id, &role_id).await;
+    let roles = list_roles().await;
+    assert_eq!(role_count(&roles), 2);
+    let everyone = roles["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|r| r["position"] == 0)
+        .unwrap();
+    assert!(everyone["member_count"].is_null());
+
+    // Paginated listing of holders.
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/roles/{role_id}/members?limit=1"),
+        &bob.auth_header(),
+    );
+    let page = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(page["data"].as_array().unwrap().len(), 1);
+    assert_eq!(page["cursor"]["has_more"], true);
+    let after = page["cursor"]["after"].as_str().unwrap();
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/roles/{role_id}/members?after={after}"),
+        &bob.auth_header(),
+    );
+    let page = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(page["data"].as_array().unwrap().len(), 1);
+    assert!(page.get("cursor").is_none());
+
+    // Unassigning and leaving both drop the count.
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!(
+            "/api/v1/spaces/{space_id}/members/{}/roles/{role_id}",
+            bob.user.id
+        ),
+        &owner.auth_header(),
+    );
+    server.router().oneshot(req).await.unwrap();
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/spaces/{space_id}/members/{}", carol.user.id),
+        &owner.auth_header(),
+    );
+    server.router().oneshot(req).await.unwrap();
+    assert_eq!(role_count(&list_roles().await), 0);
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/roles/{role_id}/members"),
+        &owner.auth_header(),
+    );
+    let page = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(page["data"], serde_json::json!([]));
+
+    // @everyone is the member list itself.
+    let everyone_id = everyone["id"].as_str().unwrap();
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/roles/{everyone_id}/members"),
+        &owner.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
 // =========================================================================
 // Rate limiting
 // =========================================================================
@@ -1220,6 +2050,133 @@ async fn test_rate_limit_returns_429() {
     assert!(response.headers().contains_key("retry-after"));
 }
 
+#[tokio::test]
+async fn test_message_send_has_its_own_bucket() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Buckets").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let send = |i: usize| {
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/messages"),
+            &alice.auth_header(),
+            &serde_json::json!({ "content": format!("msg {i}") }),
+        );
+        server.router().oneshot(req)
+    };
+
+    // message_send allows 10 per 10s; the headers track that bucket.
+    for i in 0..10 {
+        let response = send(i).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-ratelimit-bucket"], "message_send");
+        assert_eq!(response.headers()["x-ratelimit-limit"], "10");
+        assert_eq!(
+            response.headers()["x-ratelimit-remaining"],
+            (9 - i).to_string().as_str()
+        );
+    }
+
+    let response = send(10).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let headers = response.headers();
+    assert_eq!(headers["x-ratelimit-bucket"], "message_send");
+    assert_eq!(headers["x-ratelimit-remaining"], "0");
+    assert_eq!(headers["x-ratelimit-scope"], "user");
+    assert!(headers.contains_key("x-ratelimit-reset"));
+    assert!(headers.contains_key("x-ratelimit-reset-after"));
+    let retry_after: u64 = headers["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!((1..=10).contains(&retry_after));
+    let body = parse_body(response).await;
+    assert_eq!(body["error"]["code"], "rate_limited");
+
+    // Other routes draw from the default bucket and are unaffected.
+    let req = authenticated_request(Method::GET, "/api/v1/users/@me", &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-ratelimit-bucket"], "default");
+
+    // ...as is another user's send bucket.
+    let bob = server.create_user_with_token("bob").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &bob.auth_header(),
+        &serde_json::json!({ "content": "hi" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_global_bot_rate_limit() {
+    let server = TestServer::new().await;
+    let (_owner, bot) = server.create_bot_with_token("owner", "QuotaBot").await;
+
+    // The global bot quota (50 req/s) trips well before the per-key bucket
+    // (70). Requests go out together so they land inside one window.
+    let responses = futures_util::future::join_all((0..69).map(|_| {
+        let req = authenticated_request(Method::GET, "/api/v1/gateway/bot", &bot.auth_header());
+        server.router().oneshot(req)
+    }))
+    .await;
+    for response in responses {
+        let response = response.unwrap();
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            assert_eq!(response.headers()["x-ratelimit-global"], "true");
+            assert!(response.headers().contains_key("retry-after"));
+            let body = parse_body(response).await;
+            assert_eq!(body["error"]["code"], "global_rate_limited");
+            assert_eq!(body["error"]["global"], true);
+            return;
+        }
+        assert!(response
+            .headers()
+            .contains_key("x-ratelimit-global-remaining"));
+    }
+    panic!("global bot rate limit was never hit");
+}
+
+#[tokio::test]
+async fn test_global_bot_rate_limit_is_per_application() {
+    let server = TestServer::new().await;
+    let (_owner, bot) = server.create_bot_with_token("owner", "QuotaBot").await;
+
+    // A second token for the same application draws from the same quota.
+    let second_token = "second-quota-bot-token";
+    sqlx::query(&accordserver::db::q(
+        "INSERT INTO bot_tokens (token_hash, application_id, user_id) \
+         SELECT ?, application_id, user_id FROM bot_tokens WHERE user_id = ?",
+    ))
+    .bind(accordserver::middleware::auth::create_token_hash(
+        second_token,
+    ))
+    .bind(&bot.user.id)
+    .execute(server.pool())
+    .await
+    .unwrap();
+    let second_header = format!("Bot {second_token}");
+
+    // 35 requests per token stay under each token's own bucket (70) but
+    // exceed the application's global quota (50) together.
+    let responses = futures_util::future::join_all((0..70).map(|n| {
+        let header = if n % 2 == 0 {
+            bot.auth_header()
+        } else {
+            second_header.clone()
+        };
+        let req = authenticated_request(Method::GET, "/api/v1/gateway/bot", &header);
+        server.router().oneshot(req)
+    }))
+    .await;
+    assert!(responses
+        .into_iter()
+        .any(|r| r.unwrap().status() == StatusCode::TOO_MANY_REQUESTS));
+}
+
 // =========================================================================
 // Auth (register / login / logout)
 // =========================================================================
@@ -1875,6 +2832,120 @@ async fn test_admin_last_admin_protection() {
     assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[tokio::test]
+async fn test_admin_doctor_finds_and_repairs_inconsistencies() {
+    let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
+    let auth = admin.auth_header();
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&admin.user.id, "Doctored").await;
+    let channel_id = server.create_channel(&space_id, "general-2").await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &auth,
+        &serde_json::json!({ "content": "files" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let message_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // One attachment whose file is on disk, one whose file is gone.
+    let kept = format!("attachments/{channel_id}/kept/a.png");
+    let kept_path = server.state.storage_path.join(&kept);
+    std::fs::create_dir_all(kept_path.parent().unwrap()).unwrap();
+    std::fs::write(&kept_path, b"png").unwrap();
+    for (id, url) in [
+        ("kept", format!("/cdn/{kept}")),
+        ("lost", format!("/cdn/attachments/{channel_id}/lost/b.png")),
+    ] {
+        sqlx::query(&accordserver::db::q(
+            "INSERT INTO attachments (id, message_id, filename, url) VALUES (?, ?, ?, ?)",
+        ))
+        .bind(id)
+        .bind(&message_id)
+        .bind("file.png")
+        .bind(url)
+        .execute(server.pool())
+        .await
+        .unwrap();
+    }
+    sqlx::query(&accordserver::db::q(
+        "INSERT INTO permission_overwrites (id, channel_id, type, allow_bits, deny_bits) VALUES (?, ?, 'role', 0, 0)",
+    ))
+    .bind("deleted-role")
+    .bind(&channel_id)
+    .execute(server.pool())
+    .await
+    .unwrap();
+    accordserver::voice::state::join_voice_channel(
+        &server.state,
+        &bob.user.id,
+        Some(&space_id),
+        "deleted-channel",
+        "session",
+        false,
+        false,
+        false,
+        false,
+    );
+
+    let req = authenticated_request(Method::GET, "/api/v1/admin/doctor", &bob.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let check = |body: &serde_json::Value, name: &str| {
+        body["data"]["checks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["check"] == name)
+            .cloned()
+            .unwrap()
+    };
+
+    let req = authenticated_request(Method::GET, "/api/v1/admin/doctor", &auth);
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["found"], 3);
+    assert_eq!(body["data"]["repaired"], 0);
+    assert_eq!(check(&body, "orphaned_members")["found"], 0);
+    assert_eq!(check(&body, "stale_voice_states")["found"], 1);
+    assert_eq!(check(&body, "dangling_role_overwrites")["found"], 1);
+    let attachments = check(&body, "missing_attachment_files");
+    assert_eq!(attachments["found"], 1);
+    assert!(attachments["examples"][0]
+        .as_str()
+        .unwrap()
+        .contains("attachment lost"));
+    // Scanning changes nothing.
+    assert!(server.state.voice_states.contains_key(&bob.user.id));
+
+    let req = authenticated_request(Method::POST, "/api/v1/admin/doctor/repair", &auth);
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["repaired"], 3);
+    assert!(!server.state.voice_states.contains_key(&bob.user.id));
+
+    let req = authenticated_request(Method::GET, "/api/v1/admin/doctor", &auth);
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(parse_body(response).await["data"]["found"], 0);
+    let remaining: i64 = sqlx::query_scalar(&accordserver::db::q(
+        "SELECT COUNT(*) FROM attachments WHERE message_id = ?",
+    ))
+    .bind(&message_id)
+    .fetch_one(server.pool())
+    .await
+    .unwrap();
+    assert_eq!(remaining, 1);
+}
+
 #[tokio::test]
 async fn test_admin_disable_user() {
     let server = TestServer::new().await;
@@ -2042,15 +3113,101 @@ async fn test_admin_delete_admin_user_prevented() {
 }
 
 #[tokio::test]
-async fn test_registration_policy_closed() {
+async fn test_admin_force_logout() {
     let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
+    let bob = server.create_user_with_token("bob").await;
+    let carol = server.create_user_with_token("carol").await;
 
-    // Set policy to closed
-    sqlx::query("UPDATE server_settings SET registration_policy = 'closed' WHERE id = 1")
-        .execute(server.pool())
-        .await
-        .unwrap();
-    // Reload settings into state
+    let app = server.router();
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/admin/users/{}/logout", bob.user.id),
+        &bob.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let app = server.router();
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/admin/users/{}/logout", bob.user.id),
+        &admin.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["revoked"], 1);
+
+    let app = server.router();
+    let req = authenticated_request(Method::GET, "/api/v1/users/@me", &bob.auth_header());
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Disabling an account signs it out too, so re-enabling it doesn't bring
+    // old sessions back.
+    for disabled in [true, false] {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::PATCH,
+            &format!("/api/v1/admin/users/{}", carol.user.id),
+            &admin.auth_header(),
+            &serde_json::json!({ "disabled": disabled }),
+        );
+        let response = app.oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let app = server.router();
+    let req = authenticated_request(Method::GET, "/api/v1/users/@me", &carol.auth_header());
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_admin_delete_space() {
+    let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&bob.user.id, "Bob's Space").await;
+
+    let app = server.router();
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/admin/spaces/{space_id}"),
+        &bob.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let app = server.router();
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/admin/spaces/{space_id}"),
+        &admin.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let app = server.router();
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}"),
+        &bob.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_registration_policy_closed() {
+    let server = TestServer::new().await;
+
+    // Set policy to closed
+    sqlx::query("UPDATE server_settings SET registration_policy = 'closed' WHERE id = 1")
+        .execute(server.pool())
+        .await
+        .unwrap();
+    // Reload settings into state
     let settings = accordserver::db::settings::get_settings(server.pool())
         .await
         .unwrap();
@@ -2400,3 +3557,2666 @@ async fn test_admin_list_spaces_with_search() {
     assert_eq!(spaces.len(), 1);
     assert_eq!(spaces[0]["name"], "Alpha Space");
 }
+
+// =========================================================================
+// @everyone announcements
+// =========================================================================
+
+async fn mention_count(server: &TestServer, auth: &str, channel_id: &str) -> i64 {
+    let req = authenticated_request(Method::GET, "/api/v1/users/@me/read-states", auth);
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|u| u["channel_id"] == channel_id)
+        .and_then(|u| u["mention_count"].as_i64())
+        .unwrap_or(0)
+}
+
+#[tokio::test]
+async fn test_ack_message_clears_mentions() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Acks").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    let other_channel_id = server.create_channel(&space_id, "other").await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &bob.auth_header(),
+        &serde_json::json!({ "content": "hey @alice" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let message_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert_eq!(
+        mention_count(&server, &alice.auth_header(), &channel_id).await,
+        1
+    );
+
+    // The message must belong to the channel in the path.
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/channels/{other_channel_id}/messages/{message_id}/ack"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages/{message_id}/ack"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(
+        Method::GET,
+        "/api/v1/users/@me/read-states",
+        &alice.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert!(body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|u| u["channel_id"] != channel_id.as_str()));
+}
+
+#[tokio::test]
+async fn test_everyone_announcement_fans_out_mention_counts() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&owner.user.id, "News").await;
+    server.add_member(&space_id, &alice.user.id).await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/channels"),
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "announcements", "type": "announcement" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let channel_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Bob opts out of @everyone for this space.
+    let req = authenticated_request(
+        Method::PUT,
+        &format!("/api/v1/spaces/{space_id}/suppress-everyone"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // A regular member's @everyone is not fanned out by the server.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+        &serde_json::json!({ "content": "@everyone hello" }),
+    );
+    server.router().oneshot(req).await.unwrap();
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &owner.auth_header(),
+        &serde_json::json!({ "content": "@everyone release day" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The fan-out runs in the background; poll until it lands.
+    let mut alice_count = 0;
+    for _ in 0..50 {
+        alice_count = mention_count(&server, &alice.auth_header(), &channel_id).await;
+        if alice_count > 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(alice_count, 1);
+    assert_eq!(
+        mention_count(&server, &bob.auth_header(), &channel_id).await,
+        0
+    );
+    assert_eq!(
+        mention_count(&server, &owner.auth_header(), &channel_id).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_everyone_announcement_skips_members_who_cannot_see_the_channel() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let staff = server.create_user_with_token("staff").await;
+    let member = server.create_user_with_token("member").await;
+    let space_id = server.create_space(&owner.user.id, "News").await;
+    server.add_member(&space_id, &staff.user.id).await;
+    server.add_member(&space_id, &member.user.id).await;
+    let staff_role = server.create_role(&space_id, "Staff", &[]).await;
+    server
+        .assign_role(&space_id, &staff.user.id, &staff_role)
+        .await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/channels"),
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "staff-news", "type": "announcement" }),
+    );
+    let channel_id = parse_body(server.router().oneshot(req).await.unwrap()).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Hidden from @everyone, visible to Staff.
+    let everyone_role = accordserver::db::roles::list_roles(server.pool(), &space_id)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|r| r.position == 0)
+        .unwrap()
+        .id;
+    for (role_id, body) in [
+        (
+            everyone_role,
+            serde_json::json!({ "type": "role", "allow": [], "deny": ["view_channel"] }),
+        ),
+        (
+            staff_role,
+            serde_json::json!({ "type": "role", "allow": ["view_channel"], "deny": [] }),
+        ),
+    ] {
+        let req = authenticated_json_request(
+            Method::PUT,
+            &format!("/api/v1/channels/{channel_id}/permissions/{role_id}"),
+            &owner.auth_header(),
+            &body,
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &owner.auth_header(),
+        &serde_json::json!({ "content": "@everyone staff meeting" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let mut staff_count = 0;
+    for _ in 0..50 {
+        staff_count = mention_count(&server, &staff.auth_header(), &channel_id).await;
+        if staff_count > 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(staff_count, 1);
+    assert_eq!(
+        mention_count(&server, &member.auth_header(), &channel_id).await,
+        0
+    );
+}
+
+// =========================================================================
+// Threads
+// =========================================================================
+
+async fn post_message(
+    server: &TestServer,
+    auth: &str,
+    channel_id: &str,
+    body: serde_json::Value,
+) -> http::Response<axum::body::Body> {
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        auth,
+        &body,
+    );
+    server.router().oneshot(req).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_thread_from_message_tracks_members() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&owner.user.id, "Threads").await;
+    server.add_member(&space_id, &alice.user.id).await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let response = post_message(
+        &server,
+        &owner.auth_header(),
+        &channel_id,
+        serde_json::json!({ "content": "release plan" }),
+    )
+    .await;
+    let message_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages/{message_id}/threads"),
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "Release discussion", "auto_archive_after": 60 }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let thread = parse_body(response).await["data"].clone();
+    assert_eq!(thread["id"], message_id.as_str());
+    assert_eq!(thread["type"], "thread");
+    assert_eq!(thread["parent_id"], channel_id.as_str());
+    assert_eq!(thread["auto_archive_after"], 60);
+    assert_eq!(thread["member_count"], 1);
+
+    // A second thread on the same message is rejected.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages/{message_id}/threads"),
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "Again" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // Replying joins the thread.
+    let response = post_message(
+        &server,
+        &alice.auth_header(),
+        &channel_id,
+        serde_json::json!({ "content": "ship it", "thread_id": message_id }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/threads/{message_id}"),
+        &owner.auth_header(),
+    );
+    let thread = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(thread["member_count"], 2);
+    assert_eq!(thread["message_count"], 1);
+
+    // The channel listing reports the same counts.
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/threads/active"),
+        &owner.auth_header(),
+    );
+    let threads = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let threads = threads["data"].as_array().unwrap();
+    assert_eq!(threads.len(), 1);
+    assert_eq!(threads[0]["member_count"], 2);
+    assert_eq!(threads[0]["message_count"], 1);
+
+    // Leaving removes the member.
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/channels/{channel_id}/threads/{message_id}/members/@me"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/threads/{message_id}/members"),
+        &owner.auth_header(),
+    );
+    let members = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let members = members["data"].as_array().unwrap();
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0]["user_id"], owner.user.id.as_str());
+
+    // Leaving a thread you are not in is a 404.
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/channels/{channel_id}/threads/{message_id}/members/@me"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Outsiders cannot touch threads in channels they cannot see.
+    let outsider = server.create_user_with_token("outsider").await;
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/channels/{channel_id}/threads/{message_id}/members/@me"),
+        &outsider.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_thread_from_message_rejects_unthreadable_channel() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let space_id = server.create_space(&owner.user.id, "Threads").await;
+    let voice_id = server.create_voice_channel(&space_id, "Hangout").await;
+
+    let response = post_message(
+        &server,
+        &owner.auth_header(),
+        &voice_id,
+        serde_json::json!({ "content": "voice chat text" }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let message_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{voice_id}/messages/{message_id}/threads"),
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "Side chat" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_auto_thread_channel_threads_top_level_messages() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&owner.user.id, "Threads").await;
+    server.add_member(&space_id, &alice.user.id).await;
+    let channel_id = server.create_channel(&space_id, "questions").await;
+
+    // Archive windows are limited to the thread durations.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{channel_id}"),
+        &owner.auth_header(),
+        &serde_json::json!({ "auto_thread": true, "auto_archive_after": 30 }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{channel_id}"),
+        &owner.auth_header(),
+        &serde_json::json!({
+            "auto_thread": true,
+            "auto_thread_name": "Q: {content}",
+            "auto_archive_after": 60
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let channel = parse_body(response).await["data"].clone();
+    assert_eq!(channel["auto_thread"], true);
+    assert_eq!(channel["auto_thread_name"], "Q: {content}");
+    assert_eq!(channel["auto_archive_after"], 60);
+
+    let response = post_message(
+        &server,
+        &alice.auth_header(),
+        &channel_id,
+        serde_json::json!({ "content": "how do I deploy?\nsteps I tried: ..." }),
+    )
+    .await;
+    let message_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/threads/{message_id}"),
+        &owner.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let thread = parse_body(response).await["data"].clone();
+    assert_eq!(thread["name"], "Q: how do I deploy?");
+    assert_eq!(thread["owner_id"], alice.user.id.as_str());
+    assert_eq!(thread["auto_archive_after"], 60);
+
+    // Replies in the thread don't start threads of their own.
+    let response = post_message(
+        &server,
+        &owner.auth_header(),
+        &channel_id,
+        serde_json::json!({ "content": "see the docs", "thread_id": message_id }),
+    )
+    .await;
+    let reply_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/threads/{reply_id}"),
+        &owner.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_standalone_thread_archive_and_lock() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&owner.user.id, "Threads").await;
+    server.add_member(&space_id, &alice.user.id).await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/threads"),
+        &alice.auth_header(),
+        &serde_json::json!({ "name": "Off topic", "content": "anything goes" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let thread = parse_body(response).await["data"].clone();
+    let thread_id = thread["id"].as_str().unwrap().to_string();
+    assert_eq!(thread["owner_id"], alice.user.id.as_str());
+    assert_eq!(thread["auto_archive_after"], 1440);
+
+    // The owner can archive their own thread.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{channel_id}/threads/{thread_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "archived": true }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/threads/archived"),
+        &owner.auth_header(),
+    );
+    let archived = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(archived["data"].as_array().unwrap().len(), 1);
+
+    // A reply reopens an archived thread.
+    let response = post_message(
+        &server,
+        &alice.auth_header(),
+        &channel_id,
+        serde_json::json!({ "content": "back again", "thread_id": thread_id }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/threads/active"),
+        &owner.auth_header(),
+    );
+    let active = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let active = active["data"].as_array().unwrap();
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0]["archived"], false);
+
+    // Only thread managers may lock.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{channel_id}/threads/{thread_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "locked": true }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{channel_id}/threads/{thread_id}"),
+        &owner.auth_header(),
+        &serde_json::json!({ "locked": true }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Locked threads reject replies from non-managers.
+    let response = post_message(
+        &server,
+        &alice.auth_header(),
+        &channel_id,
+        serde_json::json!({ "content": "one more", "thread_id": thread_id }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = post_message(
+        &server,
+        &owner.auth_header(),
+        &channel_id,
+        serde_json::json!({ "content": "closing this", "thread_id": thread_id }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_forum_posts_and_tags() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&owner.user.id, "Forum").await;
+    server.add_member(&space_id, &alice.user.id).await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/channels"),
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "help", "type": "forum" }),
+    );
+    let forum_id = parse_body(server.router().oneshot(req).await.unwrap()).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Only channel managers define tags.
+    let tags_uri = format!("/api/v1/channels/{forum_id}/tags");
+    let req = authenticated_json_request(
+        Method::POST,
+        &tags_uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "name": "question" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let mut tag_ids = Vec::new();
+    for body in [
+        serde_json::json!({ "name": "question", "emoji": "❓" }),
+        serde_json::json!({ "name": "solved", "moderated": true }),
+    ] {
+        let req = authenticated_json_request(Method::POST, &tags_uri, &owner.auth_header(), &body);
+        let tag = parse_body(server.router().oneshot(req).await.unwrap()).await;
+        tag_ids.push(tag["data"]["id"].as_str().unwrap().to_string());
+    }
+    let (question, solved) = (tag_ids[0].clone(), tag_ids[1].clone());
+
+    // Members can use open tags but not moderated ones.
+    let threads_uri = format!("/api/v1/channels/{forum_id}/threads");
+    let req = authenticated_json_request(
+        Method::POST,
+        &threads_uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "name": "Stuck", "content": "help", "applied_tags": [solved] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let req = authenticated_json_request(
+        Method::POST,
+        &threads_uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "name": "Stuck", "content": "help", "applied_tags": [question] }),
+    );
+    let post = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    let post_id = post["id"].as_str().unwrap().to_string();
+    assert_eq!(post["applied_tags"], serde_json::json!([question]));
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &threads_uri,
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "Rules", "content": "read me" }),
+    );
+    let rules = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    let rules_id = rules["id"].as_str().unwrap().to_string();
+
+    // A moderator marks alice's post solved.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{forum_id}/threads/{post_id}"),
+        &owner.auth_header(),
+        &serde_json::json!({ "applied_tags": [question, solved] }),
+    );
+    let updated = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(
+        updated["data"]["applied_tags"],
+        serde_json::json!([question, solved])
+    );
+
+    // Posts list newest-first by creation, filter by tag, and page.
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{forum_id}/posts?sort=creation&limit=1"),
+        &alice.auth_header(),
+    );
+    let page = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(page["data"][0]["id"], rules_id.as_str());
+    assert_eq!(page["data"][0]["message"]["content"], "read me");
+    assert_eq!(page["cursor"]["after"], rules_id.as_str());
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{forum_id}/posts?sort=creation&limit=1&after={rules_id}"),
+        &alice.auth_header(),
+    );
+    let page = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(page["data"][0]["id"], post_id.as_str());
+    assert!(page.get("cursor").is_none());
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{forum_id}/posts?tag={solved}"),
+        &alice.auth_header(),
+    );
+    let tagged = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(tagged["data"].as_array().unwrap().len(), 1);
+    assert_eq!(tagged["data"][0]["id"], post_id.as_str());
+
+    // Deleting a tag takes it off posts.
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/channels/{forum_id}/tags/{solved}"),
+        &owner.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{forum_id}/threads/{post_id}"),
+        &alice.auth_header(),
+    );
+    let post = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(post["data"]["applied_tags"], serde_json::json!([question]));
+}
+
+#[tokio::test]
+async fn test_stale_threads_auto_archive() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let space_id = server.create_space(&owner.user.id, "Threads").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/threads"),
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "Quiet", "auto_archive_after": 60 }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let thread_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Nothing is stale yet.
+    let archived = accordserver::threads::archive_stale_threads(&server.state)
+        .await
+        .unwrap();
+    assert_eq!(archived, 0);
+
+    sqlx::query(&accordserver::db::q(
+        "UPDATE threads SET last_activity_at = '2000-01-01 00:00:00' WHERE id = ?",
+    ))
+    .bind(&thread_id)
+    .execute(&server.state.db)
+    .await
+    .unwrap();
+
+    let archived = accordserver::threads::archive_stale_threads(&server.state)
+        .await
+        .unwrap();
+    assert_eq!(archived, 1);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/threads/{thread_id}"),
+        &owner.auth_header(),
+    );
+    let thread = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(thread["archived"], true);
+}
+
+// =========================================================================
+// Audit log
+// =========================================================================
+
+async fn list_audit_logs(
+    server: &TestServer,
+    auth: &str,
+    space_id: &str,
+    query: &str,
+) -> Vec<serde_json::Value> {
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/audit-logs?{query}"),
+        auth,
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    parse_body(response).await["data"]
+        .as_array()
+        .unwrap()
+        .clone()
+}
+
+#[tokio::test]
+async fn test_moderation_actions_recorded_in_audit_log() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let bob = server.create_user_with_token("bob").await;
+    let charlie = server.create_user_with_token("charlie").await;
+    let space_id = server.create_space(&owner.user.id, "Audited").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    server.add_member(&space_id, &charlie.user.id).await;
+
+    // Kick with a percent-encoded reason header.
+    let mut req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
+        &owner.auth_header(),
+    );
+    req.headers_mut().insert(
+        "X-Audit-Log-Reason",
+        http::HeaderValue::from_static("spam%20bot"),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // A ban body reason wins over the header.
+    let req = authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/spaces/{space_id}/bans/{}", charlie.user.id),
+        &owner.auth_header(),
+        &serde_json::json!({ "reason": "raiding" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Create then rename a role.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/roles"),
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "Helpers" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let role_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}/roles/{role_id}"),
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "Mods" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let kicks = list_audit_logs(
+        &server,
+        &owner.auth_header(),
+        &space_id,
+        "action_type=member_kick",
+    )
+    .await;
+    assert_eq!(kicks.len(), 1);
+    assert_eq!(kicks[0]["user_id"], owner.user.id.as_str());
+    assert_eq!(kicks[0]["target_id"], bob.user.id.as_str());
+    assert_eq!(kicks[0]["reason"], "spam bot");
+
+    let bans = list_audit_logs(
+        &server,
+        &owner.auth_header(),
+        &space_id,
+        "action_type=member_ban_add",
+    )
+    .await;
+    assert_eq!(bans.len(), 1);
+    assert_eq!(bans[0]["reason"], "raiding");
+
+    let updates = list_audit_logs(
+        &server,
+        &owner.auth_header(),
+        &space_id,
+        "action_type=role_update",
+    )
+    .await;
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0]["target_id"], role_id.as_str());
+    assert_eq!(updates[0]["changes"]["name"]["old"], "Helpers");
+    assert_eq!(updates[0]["changes"]["name"]["new"], "Mods");
+
+    // Actor filter and cursors: newest first, `after` pages towards newer.
+    let mine = list_audit_logs(
+        &server,
+        &owner.auth_header(),
+        &space_id,
+        &format!("actor_id={}", owner.user.id),
+    )
+    .await;
+    let actions: Vec<&str> = mine
+        .iter()
+        .map(|e| e["action_type"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        actions,
+        vec![
+            "role_update",
+            "role_create",
+            "member_ban_add",
+            "member_kick"
+        ]
+    );
+    let kick_id = mine[3]["id"].as_str().unwrap();
+    let role_update_id = mine[0]["id"].as_str().unwrap();
+    let newer = list_audit_logs(
+        &server,
+        &owner.auth_header(),
+        &space_id,
+        &format!("actor_id={}&after={kick_id}&limit=2", owner.user.id),
+    )
+    .await;
+    let actions: Vec<&str> = newer
+        .iter()
+        .map(|e| e["action_type"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions, vec!["role_create", "member_ban_add"]);
+    let older = list_audit_logs(
+        &server,
+        &owner.auth_header(),
+        &space_id,
+        &format!("actor_id={}&before={role_update_id}&limit=1", owner.user.id),
+    )
+    .await;
+    assert_eq!(older[0]["action_type"], "role_create");
+
+    // Regular members can't read the log.
+    server.add_member(&space_id, &bob.user.id).await;
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/audit-logs"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_ban_export_import_and_sync_group() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let stranger = server.create_user_with_token("stranger").await;
+    let troll = server.create_user_with_token("troll").await;
+    let spammer = server.create_user_with_token("spammer").await;
+    let space_a = server.create_space(&owner.user.id, "Alpha").await;
+    let space_b = server.create_space(&owner.user.id, "Beta").await;
+    let space_c = server.create_space(&stranger.user.id, "Gamma").await;
+    server.add_member(&space_b, &troll.user.id).await;
+
+    // Export from A and preview the import into B.
+    let req = authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/spaces/{space_a}/bans/{}", troll.user.id),
+        &owner.auth_header(),
+        &serde_json::json!({ "reason": "trolling" }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_a}/bans/export"),
+        &owner.auth_header(),
+    );
+    let export = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(export["bans"].as_array().unwrap().len(), 1);
+
+    let mut import = export.clone();
+    import["bans"]
+        .as_array_mut()
+        .unwrap()
+        .push(serde_json::json!({ "user_id": "nobody" }));
+    import["dry_run"] = serde_json::json!(true);
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_b}/bans/import"),
+        &owner.auth_header(),
+        &import,
+    );
+    let preview = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(preview["added"], serde_json::json!([troll.user.id]));
+    assert_eq!(preview["skipped"][0]["reason"], "unknown_user");
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_b}/bans/{}", troll.user.id),
+        &owner.auth_header(),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::NOT_FOUND
+    );
+
+    import["dry_run"] = serde_json::json!(false);
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_b}/bans/import"),
+        &owner.auth_header(),
+        &import,
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_b}/bans/{}", troll.user.id),
+        &owner.auth_header(),
+    );
+    let ban = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(ban["reason"], "trolling");
+
+    // A and B share a sync group; a stranger can't attach their space.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_a}/ban-sync"),
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "Network" }),
+    );
+    let group_id = parse_body(server.router().oneshot(req).await.unwrap()).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let req = authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/spaces/{space_b}/ban-sync"),
+        &owner.auth_header(),
+        &serde_json::json!({ "group_id": group_id }),
+    );
+    let group = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(group["space_ids"], serde_json::json!([space_a, space_b]));
+    let req = authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/spaces/{space_c}/ban-sync"),
+        &stranger.auth_header(),
+        &serde_json::json!({ "group_id": group_id }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::FORBIDDEN
+    );
+
+    // A ban in B propagates to A with provenance in A's audit log.
+    let req = authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/spaces/{space_b}/bans/{}", spammer.user.id),
+        &owner.auth_header(),
+        &serde_json::json!({ "reason": "spam" }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_a}/bans/{}", spammer.user.id),
+        &owner.auth_header(),
+    );
+    let ban = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(ban["reason"], "spam");
+    let entries = list_audit_logs(
+        &server,
+        &owner.auth_header(),
+        &space_a,
+        "action_type=member_ban_add",
+    )
+    .await;
+    assert_eq!(entries[0]["target_id"], spammer.user.id.as_str());
+    assert_eq!(
+        entries[0]["changes"]["synced_from"]["space_id"],
+        space_b.as_str()
+    );
+    assert_eq!(
+        entries[0]["changes"]["synced_from"]["group_id"],
+        group_id.as_str()
+    );
+}
+
+// =========================================================================
+// Webhooks
+// =========================================================================
+
+#[tokio::test]
+async fn test_incoming_webhook_posts_message() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let member = server.create_user_with_token("member").await;
+    let space_id = server.create_space(&owner.user.id, "Hooks").await;
+    let channel_id = server.create_channel(&space_id, "alerts").await;
+    server.add_member(&space_id, &member.user.id).await;
+
+    // Plain members can't create webhooks.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/webhooks"),
+        &member.auth_header(),
+        &serde_json::json!({ "name": "CI" }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/webhooks"),
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "CI", "avatar": "https://ci.example.com/logo.png" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let webhook = parse_body(response).await["data"].clone();
+    let webhook_id = webhook["id"].as_str().unwrap().to_string();
+    let url = webhook["url"].as_str().unwrap().to_string();
+
+    // The token is not shown again.
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/webhooks"),
+        &owner.auth_header(),
+    );
+    let list = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert!(list[0].get("token").is_none());
+
+    // A wrong token looks like an unknown webhook.
+    let req = json_request(
+        Method::POST,
+        &format!("/api/v1/webhooks/{webhook_id}/not-the-token"),
+        &serde_json::json!({ "content": "hi" }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::NOT_FOUND
+    );
+
+    // No Authorization header; the username is overridden per message.
+    let req = json_request(
+        Method::POST,
+        &url,
+        &serde_json::json!({ "content": "build passed", "username": "Nightly" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let msg = parse_body(response).await["data"].clone();
+    assert_eq!(msg["webhook_id"], webhook_id.as_str());
+    assert_eq!(msg["webhook_author"]["username"], "Nightly");
+    assert_eq!(
+        msg["webhook_author"]["avatar_url"],
+        "https://ci.example.com/logo.png"
+    );
+    let message_id = msg["id"].as_str().unwrap().to_string();
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &member.auth_header(),
+    );
+    let messages = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(messages[0]["id"], message_id.as_str());
+    assert_eq!(messages[0]["webhook_author"]["username"], "Nightly");
+
+    // Its creator can't edit what the webhook posted.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{channel_id}/messages/{message_id}"),
+        &owner.auth_header(),
+        &serde_json::json!({ "content": "build failed" }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::FORBIDDEN
+    );
+
+    // Deleting the webhook revokes the URL.
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/webhooks/{webhook_id}"),
+        &owner.auth_header(),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = json_request(Method::POST, &url, &serde_json::json!({ "content": "hi" }));
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn test_follow_announcement_channel_and_crosspost() {
+    let server = TestServer::new().await;
+    let news = server.create_user_with_token("news").await;
+    let fan = server.create_user_with_token("fan").await;
+    let source_space = server.create_space(&news.user.id, "Newsroom").await;
+    let target_space = server.create_space(&fan.user.id, "Fans").await;
+    server.add_member(&source_space, &fan.user.id).await;
+    let target_channel = server.create_channel(&target_space, "feed").await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{source_space}/channels"),
+        &news.auth_header(),
+        &serde_json::json!({ "name": "releases", "type": "announcement" }),
+    );
+    let source_channel = parse_body(server.router().oneshot(req).await.unwrap()).await["data"]
+        ["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // The fan manages webhooks in their own space, so they can follow.
+    let follow = |token: String| {
+        authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{source_channel}/followers"),
+            &token,
+            &serde_json::json!({ "webhook_channel_id": target_channel }),
+        )
+    };
+    let response = server
+        .router()
+        .oneshot(follow(news.auth_header()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let followed = parse_body(
+        server
+            .router()
+            .oneshot(follow(fan.auth_header()))
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(followed["data"]["channel_id"], source_channel.as_str());
+    let response = server
+        .router()
+        .oneshot(follow(fan.auth_header()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{source_channel}/messages"),
+        &news.auth_header(),
+        &serde_json::json!({ "content": "v2.0 is out" }),
+    );
+    let message = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let message_id = message["data"]["id"].as_str().unwrap().to_string();
+    let crosspost = format!("/api/v1/channels/{source_channel}/messages/{message_id}/crosspost");
+
+    // Only the author (or a moderator) may publish it.
+    let req = authenticated_request(Method::POST, &crosspost, &fan.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let req = authenticated_request(Method::POST, &crosspost, &news.auth_header());
+    let published = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(published["data"]["flags"], 1);
+    let req = authenticated_request(Method::POST, &crosspost, &news.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{target_channel}/messages"),
+        &fan.auth_header(),
+    );
+    let feed = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(feed["data"][0]["content"], "v2.0 is out");
+    assert_eq!(feed["data"][0]["flags"], 2);
+    assert_eq!(
+        feed["data"][0]["webhook_id"],
+        followed["data"]["webhook_id"]
+    );
+    assert_eq!(
+        feed["data"][0]["webhook_author"]["username"],
+        "Newsroom #releases"
+    );
+}
+
+#[tokio::test]
+async fn test_space_integrations_overview() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let member = server.create_user_with_token("member").await;
+    let (dev, bot) = server.create_bot_with_token("dev", "Helper").await;
+    let space_id = server.create_space(&owner.user.id, "Audited").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &member.user.id).await;
+    server.add_member(&space_id, &bot.user.id).await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/webhooks"),
+        &owner.auth_header(),
+        &serde_json::json!({ "name": "CI" }),
+    );
+    let webhook = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    let req = json_request(
+        Method::POST,
+        webhook["url"].as_str().unwrap(),
+        &serde_json::json!({ "content": "deployed" }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+
+    let url = format!("/api/v1/spaces/{space_id}/integrations");
+    let req = authenticated_request(Method::GET, &url, &member.auth_header());
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let req = authenticated_request(Method::GET, &url, &owner.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let data = parse_body(response).await["data"].clone();
+
+    let bots = data["bots"].as_array().unwrap();
+    assert_eq!(bots.len(), 1);
+    assert_eq!(bots[0]["user_id"], bot.user.id.as_str());
+    assert_eq!(bots[0]["application_name"], "Helper");
+    assert_eq!(bots[0]["owner_id"], dev.user.id.as_str());
+    assert!(bots[0]["last_activity"].is_null());
+
+    let webhooks = data["webhooks"].as_array().unwrap();
+    assert_eq!(webhooks.len(), 1);
+    assert_eq!(webhooks[0]["id"], webhook["id"]);
+    assert_eq!(webhooks[0]["creator_id"], owner.user.id.as_str());
+    assert!(webhooks[0]["last_activity"].is_string());
+}
+
+// =========================================================================
+// OAuth2
+// =========================================================================
+
+#[tokio::test]
+async fn test_oauth2_authorization_code_flow() {
+    let server = TestServer::new().await;
+    let dev = server.create_user_with_token("dev").await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "OAuthSpace").await;
+    let redirect = "https://app.example/callback";
+
+    let req = authenticated_json_request(
+        Method::POST,
+        "/api/v1/applications",
+        &dev.auth_header(),
+        &serde_json::json!({ "name": "Helper" }),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let app = body["data"]["application"].clone();
+    let client_id = app["id"].as_str().unwrap().to_string();
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/applications/@me",
+        &dev.auth_header(),
+        &serde_json::json!({ "redirect_uris": [redirect] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let req = authenticated_request(
+        Method::POST,
+        "/api/v1/applications/@me/oauth2/reset-secret",
+        &dev.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let secret = body["data"]["client_secret"].as_str().unwrap().to_string();
+
+    // Alice approves: the bot joins her space and she gets a code.
+    let req = authenticated_json_request(
+        Method::POST,
+        "/api/v1/oauth2/authorize",
+        &alice.auth_header(),
+        &serde_json::json!({
+            "client_id": client_id,
+            "scope": "identify bot",
+            "redirect_uri": redirect,
+            "space_id": space_id,
+            "state": "xyz",
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let location = body["data"]["location"].as_str().unwrap().to_string();
+    assert!(location.starts_with(&format!("{redirect}?space_id={space_id}&code=")));
+    assert!(location.ends_with("&state=xyz"));
+    let code = location
+        .split("code=")
+        .nth(1)
+        .unwrap()
+        .split('&')
+        .next()
+        .unwrap();
+
+    let bot_id = app["bot_user_id"].as_str().unwrap();
+    let uri = format!("/api/v1/spaces/{space_id}/members/{bot_id}");
+    let req = authenticated_request(Method::GET, &uri, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let exchange = [
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", "https%3A%2F%2Fapp.example%2Fcallback"),
+        ("client_id", client_id.as_str()),
+        ("client_secret", secret.as_str()),
+    ];
+    let req = form_request("/api/v1/oauth2/token", &exchange);
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let tokens = parse_body(response).await;
+    assert_eq!(tokens["scope"], "identify");
+    let access = format!("Bearer {}", tokens["access_token"].as_str().unwrap());
+
+    // Codes are single-use.
+    let req = form_request("/api/v1/oauth2/token", &exchange);
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(parse_body(response).await["error"], "invalid_grant");
+
+    // The token works where its scopes reach and nowhere else.
+    let req = authenticated_request(Method::GET, "/api/v1/users/@me", &access);
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        parse_body(response).await["data"]["id"],
+        alice.user.id.as_str()
+    );
+    let req = authenticated_request(Method::GET, "/api/v1/users/@me/spaces", &access);
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(parse_body(response).await["error"]["code"], "missing_scope");
+
+    let req = form_request(
+        "/api/v1/oauth2/token",
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", tokens["refresh_token"].as_str().unwrap()),
+            ("client_id", client_id.as_str()),
+            ("client_secret", "wrong"),
+        ],
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(parse_body(response).await["error"], "invalid_client");
+}
+
+#[tokio::test]
+async fn test_application_signing_key_rotation() {
+    let server = TestServer::new().await;
+    let dev = server.create_user_with_token("dev").await;
+    let req = authenticated_json_request(
+        Method::POST,
+        "/api/v1/applications",
+        &dev.auth_header(),
+        &serde_json::json!({ "name": "Signer" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let rotate = |body: serde_json::Value| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::POST,
+            "/api/v1/applications/@me/signing-keys/rotate",
+            &dev.auth_header(),
+            &body,
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+    let list = || {
+        let app = server.router();
+        let req = authenticated_request(
+            Method::GET,
+            "/api/v1/applications/@me/signing-keys",
+            &dev.auth_header(),
+        );
+        async move { parse_body(app.oneshot(req).await.unwrap()).await["data"].clone() }
+    };
+
+    let response = rotate(serde_json::json!({})).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let first = parse_body(response).await["data"].clone();
+    assert!(first["secret"].as_str().is_some_and(|s| !s.is_empty()));
+    assert!(first["expires_at"].is_null());
+
+    // The old key keeps signing through the overlap; secrets aren't listed.
+    let response = rotate(serde_json::json!({ "overlap_secs": 3600 })).await;
+    let second = parse_body(response).await["data"].clone();
+    let keys = list().await;
+    assert_eq!(keys.as_array().unwrap().len(), 2);
+    assert_eq!(keys[0]["id"], first["id"]);
+    assert!(keys[0]["expires_at"].is_string());
+    assert_eq!(keys[1]["id"], second["id"]);
+    assert!(keys[1]["expires_at"].is_null());
+    assert!(keys[0].get("secret").is_none());
+
+    let response = rotate(serde_json::json!({ "overlap_secs": -1 })).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Revoking drops a key at once; a zero overlap retires the rest.
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!(
+            "/api/v1/applications/@me/signing-keys/{}",
+            first["id"].as_str().unwrap()
+        ),
+        &dev.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = rotate(serde_json::json!({ "overlap_secs": 0 })).await;
+    let third = parse_body(response).await["data"].clone();
+    let keys = list().await;
+    assert_eq!(keys.as_array().unwrap().len(), 1);
+    assert_eq!(keys[0]["id"], third["id"]);
+}
+
+#[tokio::test]
+async fn test_event_subscription_deliveries() {
+    use accordserver::gateway::events::GatewayBroadcast;
+
+    let server = TestServer::new().await;
+    let dev = server.create_user_with_token("dev").await;
+    let req = authenticated_json_request(
+        Method::POST,
+        "/api/v1/applications",
+        &dev.auth_header(),
+        &serde_json::json!({ "name": "Hooked" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let bot_user_id = parse_body(response).await["data"]["application"]["bot_user_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let subscribe = |body: serde_json::Value| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::POST,
+            "/api/v1/applications/@me/event-subscriptions",
+            &dev.auth_header(),
+            &body,
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+    let body = serde_json::json!({
+        "url": "https://invalid.invalid/hook",
+        "event_types": ["message.create", "message.create"],
+    });
+
+    // Deliveries are signed, so a signing key comes first.
+    let response = subscribe(body.clone()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let req = authenticated_json_request(
+        Method::POST,
+        "/api/v1/applications/@me/signing-keys/rotate",
+        &dev.auth_header(),
+        &serde_json::json!({}),
+    );
+    server.router().oneshot(req).await.unwrap();
+
+    let response = subscribe(serde_json::json!({
+        "url": "https://invalid.invalid/hook",
+        "event_types": ["message.explode"],
+    }))
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = subscribe(serde_json::json!({
+        "url": "ftp://invalid.invalid/hook",
+        "event_types": ["message.create"],
+    }))
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = subscribe(body).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let sub = parse_body(response).await["data"].clone();
+    assert_eq!(sub["event_types"], serde_json::json!(["message.create"]));
+    assert_eq!(sub["enabled"], true);
+    let sub_id = sub["id"].as_str().unwrap().to_string();
+
+    let owner = server.create_user_with_token("owner").await;
+    let space_id = server.create_space(&owner.user.id, "Watched").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    let event = |event_type: &str, targets: Option<Vec<String>>| GatewayBroadcast {
+        space_id: Some(space_id.clone()),
+        target_user_ids: targets,
+        event: serde_json::json!({
+            "op": 0,
+            "type": event_type,
+            "data": { "channel_id": channel_id, "content": "hi" },
+        }),
+        intent: "messages".to_string(),
+        role_mention_user_ids: None,
+    };
+
+    // Nothing is queued from spaces the bot isn't in, for unsubscribed
+    // types, or for events aimed at particular users.
+    let queued =
+        accordserver::event_subscriptions::enqueue(&server.state, &event("message.create", None))
+            .await
+            .unwrap();
+    assert_eq!(queued, 0);
+    server.add_member(&space_id, &bot_user_id).await;
+    for broadcast in [
+        event("message.delete", None),
+        event("message.create", Some(vec![owner.user.id.clone()])),
+    ] {
+        let queued = accordserver::event_subscriptions::enqueue(&server.state, &broadcast)
+            .await
+            .unwrap();
+        assert_eq!(queued, 0);
+    }
+    let queued =
+        accordserver::event_subscriptions::enqueue(&server.state, &event("message.create", None))
+            .await
+            .unwrap();
+    assert_eq!(queued, 1);
+
+    // The URL doesn't resolve, so the first attempt fails and is retried
+    // later rather than straight away.
+    let attempted = accordserver::event_subscriptions::deliver_due(&server.state)
+        .await
+        .unwrap();
+    assert_eq!(attempted, 1);
+    let attempted = accordserver::event_subscriptions::deliver_due(&server.state)
+        .await
+        .unwrap();
+    assert_eq!(attempted, 0);
+
+    let deliveries_uri =
+        format!("/api/v1/applications/@me/event-subscriptions/{sub_id}/deliveries");
+    let req = authenticated_request(Method::GET, &deliveries_uri, &dev.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let deliveries = parse_body(response).await["data"].clone();
+    assert_eq!(deliveries.as_array().unwrap().len(), 1);
+    let delivery = &deliveries[0];
+    assert_eq!(delivery["status"], "pending");
+    assert_eq!(delivery["attempts"], 1);
+    assert!(delivery["last_error"].is_string());
+    assert!(delivery["next_attempt_at"].is_string());
+    assert_eq!(delivery["payload"]["type"], "message.create");
+    assert_eq!(delivery["payload"]["space_id"], space_id.as_str());
+    assert_eq!(delivery["payload"]["data"]["content"], "hi");
+
+    // A delivery that's still being attempted can't be retried by hand.
+    let req = authenticated_request(
+        Method::POST,
+        &format!(
+            "{deliveries_uri}/{}/retry",
+            delivery["id"].as_str().unwrap()
+        ),
+        &dev.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // Other users can't see the log.
+    let req = authenticated_request(Method::GET, &deliveries_uri, &owner.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Disabled subscriptions stop queueing.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/applications/@me/event-subscriptions/{sub_id}"),
+        &dev.auth_header(),
+        &serde_json::json!({ "enabled": false }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let queued =
+        accordserver::event_subscriptions::enqueue(&server.state, &event("message.create", None))
+            .await
+            .unwrap();
+    assert_eq!(queued, 0);
+
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/applications/@me/event-subscriptions/{sub_id}"),
+        &dev.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let req = authenticated_request(Method::GET, &deliveries_uri, &dev.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_interactions_endpoint_needs_a_pong() {
+    let server = TestServer::new().await;
+    let dev = server.create_user_with_token("dev").await;
+    let req = authenticated_json_request(
+        Method::POST,
+        "/api/v1/applications",
+        &dev.auth_header(),
+        &serde_json::json!({ "name": "Serverless" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let app = parse_body(response).await["data"]["application"].clone();
+    let verify_key = app["verify_key"].as_str().unwrap();
+    assert_eq!(verify_key.len(), 64);
+    assert!(app.get("interactions_private_key").is_none());
+    assert!(app["interactions_endpoint_url"].is_null());
+
+    let update = |body: serde_json::Value| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::PATCH,
+            "/api/v1/applications/@me",
+            &dev.auth_header(),
+            &body,
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+
+    // An endpoint is only saved once it answers the signed ping.
+    for url in [
+        "ftp://invalid.invalid/interactions",
+        "https://invalid.invalid/interactions",
+    ] {
+        let response = update(serde_json::json!({ "interactions_endpoint_url": url })).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    let response = update(serde_json::json!({ "interactions_endpoint_url": "" })).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let app = parse_body(response).await["data"].clone();
+    assert!(app["interactions_endpoint_url"].is_null());
+    assert_eq!(app["verify_key"], verify_key);
+}
+
+#[tokio::test]
+async fn test_push_subscriptions_and_recipients() {
+    use accordserver::gateway::events::GatewayBroadcast;
+    use p256::elliptic_curve::sec1::ToEncodedPoint;
+
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let carol = server.create_user_with_token("carol").await;
+
+    let req = authenticated_request(
+        Method::GET,
+        "/api/v1/users/@me/push/vapid-key",
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        parse_body(response).await["data"]["public_key"]
+            .as_str()
+            .unwrap()
+            .len(),
+        87
+    );
+
+    let browser_key = p256::SecretKey::random(&mut rand::thread_rng());
+    let p256dh =
+        accordserver::webauthn::encode(browser_key.public_key().to_encoded_point(false).as_bytes());
+    let auth = accordserver::webauthn::encode(&[9u8; 16]);
+    let subscribe = |user: &common::TestUser, body: serde_json::Value| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::POST,
+            "/api/v1/users/@me/push/subscriptions",
+            &user.auth_header(),
+            &body,
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+    let response = subscribe(
+        &bob,
+        serde_json::json!({
+            "endpoint": "http://push.invalid/send/1",
+            "keys": { "p256dh": "AAAA", "auth": auth },
+        }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let errors = parse_body(response).await["error"]["errors"].clone();
+    let fields: Vec<&str> = errors
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["endpoint", "keys.p256dh"]);
+
+    let body = serde_json::json!({
+        "endpoint": "https://push.invalid/send/1",
+        "expirationTime": null,
+        "keys": { "p256dh": p256dh, "auth": auth },
+    });
+    let response = subscribe(&bob, body.clone()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let sub = parse_body(response).await["data"].clone();
+    assert!(sub.get("auth").is_none());
+    // Registering the endpoint again replaces it rather than adding one.
+    let response = subscribe(&bob, body).await;
+    let sub_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let req = authenticated_request(
+        Method::GET,
+        "/api/v1/users/@me/push/subscriptions",
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let subs = parse_body(response).await["data"].clone();
+    assert_eq!(subs.as_array().unwrap().len(), 1);
+    assert_eq!(subs[0]["id"], sub_id.as_str());
+
+    // Carol is subscribed too, but only mentioned through a role.
+    let response = subscribe(
+        &carol,
+        serde_json::json!({
+            "endpoint": "https://push.invalid/send/2",
+            "keys": { "p256dh": p256dh, "auth": auth },
+        }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let space_id = server.create_space(&alice.user.id, "Pushy").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    server.add_member(&space_id, &carol.user.id).await;
+    let role_id = server.create_role(&space_id, "Helpers", &[]).await;
+    server
+        .assign_role(&space_id, &carol.user.id, &role_id)
+        .await;
+    let dm_id = server.create_dm(&alice.user.id, &bob.user.id).await;
+
+    let message = |space_id: Option<&str>, channel_id: &str, data: serde_json::Value| {
+        let mut data = data;
+        data["channel_id"] = channel_id.into();
+        data["author_id"] = alice.user.id.clone().into();
+        GatewayBroadcast {
+            space_id: space_id.map(str::to_string),
+            target_user_ids: space_id
+                .is_none()
+                .then(|| vec![alice.user.id.clone(), bob.user.id.clone()]),
+            event: serde_json::json!({ "op": 0, "type": "message.create", "data": data }),
+            intent: "messages".to_string(),
+            role_mention_user_ids: None,
+        }
+    };
+    let recipients = |broadcast: GatewayBroadcast| {
+        let state = server.state.clone();
+        async move {
+            let mut ids = accordserver::push::recipients(&state, &broadcast)
+                .await
+                .unwrap();
+            ids.sort();
+            ids
+        }
+    };
+
+    let dm = message(None, &dm_id, serde_json::json!({ "content": "hi" }));
+    assert_eq!(recipients(dm.clone()).await, vec![bob.user.id.clone()]);
+    let plain = message(
+        Some(&space_id),
+        &channel_id,
+        serde_json::json!({ "content": "hi", "mentions": [], "mention_roles": [] }),
+    );
+    assert!(recipients(plain).await.is_empty());
+    let mentions = message(
+        Some(&space_id),
+        &channel_id,
+        serde_json::json!({
+            "mentions": [alice.user.id, bob.user.id],
+            "mention_roles": [role_id],
+            "mention_everyone": true,
+        }),
+    );
+    let mut both = vec![bob.user.id.clone(), carol.user.id.clone()];
+    both.sort();
+    assert_eq!(recipients(mentions.clone()).await, both);
+
+    // A muted space and a muted DM are both quiet.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/users/@me/spaces/{space_id}/settings"),
+        &carol.auth_header(),
+        &serde_json::json!({ "muted": true }),
+    );
+    server.router().oneshot(req).await.unwrap();
+    assert_eq!(
+        recipients(mentions.clone()).await,
+        vec![bob.user.id.clone()]
+    );
+    let req = authenticated_request(
+        Method::PUT,
+        &format!("/api/v1/channels/{dm_id}/mute"),
+        &bob.auth_header(),
+    );
+    server.router().oneshot(req).await.unwrap();
+    assert!(recipients(dm).await.is_empty());
+
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/users/@me/push/subscriptions/{sub_id}"),
+        &carol.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/users/@me/push/subscriptions/{sub_id}"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(recipients(mentions).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_poll_voting_and_finalization() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let carol = server.create_user_with_token("carol").await;
+    let space_id = server.create_space(&alice.user.id, "Polls").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    server.add_member(&space_id, &carol.user.id).await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let post = |body: serde_json::Value| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/messages"),
+            &alice.auth_header(),
+            &body,
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+    let response = post(serde_json::json!({
+        "content": "",
+        "poll": {
+            "question": " ",
+            "answers": [{ "text": "only" }],
+            "duration_hours": 1000,
+        },
+    }))
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let errors = parse_body(response).await["error"]["errors"].clone();
+    let fields: Vec<&str> = errors
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        fields,
+        ["poll.question", "poll.answers", "poll.duration_hours"]
+    );
+
+    let response = post(serde_json::json!({
+        "content": "Team lunch",
+        "poll": {
+            "question": "Where to?",
+            "answers": [{ "text": "Pizza" }, { "text": "Tacos" }, { "text": "Sushi" }],
+        },
+    }))
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let msg = parse_body(response).await["data"].clone();
+    let message_id = msg["id"].as_str().unwrap().to_string();
+    assert_eq!(msg["poll"]["question"], "Where to?");
+    assert_eq!(msg["poll"]["answers"][2]["id"], 3);
+    assert_eq!(msg["poll"]["allow_multiselect"], false);
+    assert_eq!(msg["poll"]["finalized"], false);
+
+    let vote = |user: &common::TestUser, method: Method, answer: i64| {
+        let app = server.router();
+        let req = authenticated_request(
+            method,
+            &format!("/api/v1/channels/{channel_id}/polls/{message_id}/answers/{answer}/@me"),
+            &user.auth_header(),
+        );
+        async move { app.oneshot(req).await.unwrap().status() }
+    };
+    assert_eq!(vote(&bob, Method::PUT, 4).await, StatusCode::NOT_FOUND);
+    assert_eq!(vote(&bob, Method::PUT, 1).await, StatusCode::OK);
+    // A single-choice poll moves the vote rather than adding another.
+    assert_eq!(vote(&bob, Method::PUT, 2).await, StatusCode::OK);
+    assert_eq!(vote(&carol, Method::PUT, 2).await, StatusCode::OK);
+    assert_eq!(vote(&alice, Method::PUT, 3).await, StatusCode::OK);
+    assert_eq!(vote(&alice, Method::DELETE, 3).await, StatusCode::OK);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/messages/{message_id}"),
+        &bob.auth_header(),
+    );
+    let poll =
+        parse_body(server.router().oneshot(req).await.unwrap()).await["data"]["poll"].clone();
+    let tallies: Vec<(i64, bool)> = poll["answers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| (a["votes"].as_i64().unwrap(), a["me"].as_bool().unwrap()))
+        .collect();
+    assert_eq!(tallies, [(0, false), (2, true), (0, false)]);
+    assert_eq!(poll["total_votes"], 2);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/polls/{message_id}/answers/2"),
+        &alice.auth_header(),
+    );
+    let voters = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(voters.as_array().unwrap().len(), 2);
+
+    // Nothing has expired yet.
+    let finalized = accordserver::polls::finalize_expired(&server.state)
+        .await
+        .unwrap();
+    assert_eq!(finalized, 0);
+
+    sqlx::query(&accordserver::db::q(
+        "UPDATE polls SET expires_at = '2000-01-01 00:00:00' WHERE message_id = ?",
+    ))
+    .bind(&message_id)
+    .execute(&server.state.db)
+    .await
+    .unwrap();
+    assert_eq!(vote(&alice, Method::PUT, 1).await, StatusCode::BAD_REQUEST);
+
+    let finalized = accordserver::polls::finalize_expired(&server.state)
+        .await
+        .unwrap();
+    assert_eq!(finalized, 1);
+    let finalized = accordserver::polls::finalize_expired(&server.state)
+        .await
+        .unwrap();
+    assert_eq!(finalized, 0);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+    );
+    let messages = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    let notice = messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["type"] == "poll_result")
+        .expect("expected a poll_result notice");
+    assert_eq!(notice["reply_to"], message_id.as_str());
+    assert!(notice["content"].as_str().unwrap().contains("Tacos"));
+    let poll = messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["id"] == message_id.as_str())
+        .unwrap()["poll"]
+        .clone();
+    assert_eq!(poll["finalized"], true);
+}
+
+#[tokio::test]
+async fn test_poll_concurrent_single_choice_votes() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Polls").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+        &serde_json::json!({
+            "content": "",
+            "poll": {
+                "question": "Pick one",
+                "answers": [{ "text": "A" }, { "text": "B" }, { "text": "C" }],
+            },
+        }),
+    );
+    let msg = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    let message_id = msg["id"].as_str().unwrap().to_string();
+
+    // Votes for different answers go out together; a single-choice poll
+    // must still end up with exactly one of them.
+    let responses = futures_util::future::join_all((0..12).map(|i| {
+        let req = authenticated_request(
+            Method::PUT,
+            &format!(
+                "/api/v1/channels/{channel_id}/polls/{message_id}/answers/{}/@me",
+                i % 3 + 1
+            ),
+            &bob.auth_header(),
+        );
+        server.router().oneshot(req)
+    }))
+    .await;
+    for response in responses {
+        assert_eq!(response.unwrap().status(), StatusCode::OK);
+    }
+
+    let votes: Vec<(i64,)> = sqlx::query_as(&accordserver::db::q(
+        "SELECT answer_id FROM poll_votes WHERE message_id = ? AND user_id = ?",
+    ))
+    .bind(&message_id)
+    .bind(&bob.user.id)
+    .fetch_all(&server.state.db)
+    .await
+    .unwrap();
+    assert_eq!(votes.len(), 1);
+}
+
+// =========================================================================
+// AutoMod
+// =========================================================================
+
+#[tokio::test]
+async fn test_content_safety_quarantines_listed_hashes() {
+    use accordserver::content_safety::{ScanItem, ScannedAttachment};
+
+    let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Scanned").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let patch_settings = |body: serde_json::Value| {
+        authenticated_json_request(
+            Method::PATCH,
+            "/api/v1/admin/settings",
+            &admin.auth_header(),
+            &body,
+        )
+    };
+    let response = server
+        .router()
+        .oneshot(patch_settings(
+            serde_json::json!({ "content_safety_providers": "antivirus" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    // The http provider needs an endpoint.
+    let response = server
+        .router()
+        .oneshot(patch_settings(
+            serde_json::json!({ "content_safety_providers": "http" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = server
+        .router()
+        .oneshot(patch_settings(
+            serde_json::json!({ "content_safety_providers": "hash_list" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let bad_hash = accordserver::blocklist::hash_bytes(b"known bad file");
+    let req = authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/admin/content-safety/hashes/{bad_hash}"),
+        &admin.auth_header(),
+        &serde_json::json!({ "label": "test set" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = post_message(
+        &server,
+        &alice.auth_header(),
+        &channel_id,
+        serde_json::json!({ "content": "look at this" }),
+    )
+    .await;
+    let message_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let message = accordserver::db::messages::get_message_row(server.pool(), &message_id)
+        .await
+        .unwrap();
+    let item = |sha256: &str| ScanItem {
+        message: message.clone(),
+        attachments: vec![ScannedAttachment {
+            id: "1".into(),
+            url: "/cdn/attachments/x.png".into(),
+            content_type: Some("image/png".into()),
+            sha256: sha256.to_string(),
+        }],
+    };
+
+    let clean = accordserver::blocklist::hash_bytes(b"holiday photo");
+    assert!(
+        accordserver::content_safety::scan(&server.state, &item(&clean))
+            .await
+            .unwrap()
+            .is_none()
+    );
+    let flag = accordserver::content_safety::scan(&server.state, &item(&bad_hash))
+        .await
+        .unwrap()
+        .expect("listed hash should be flagged");
+    assert_eq!(flag.provider, "hash_list");
+    assert_eq!(flag.label, "test set");
+    assert_eq!(flag.action, "quarantine");
+
+    // Quarantined messages drop out of history.
+    let list_messages = || {
+        authenticated_request(
+            Method::GET,
+            &format!("/api/v1/channels/{channel_id}/messages"),
+            &alice.auth_header(),
+        )
+    };
+    let body = parse_body(server.router().oneshot(list_messages()).await.unwrap()).await;
+    assert!(body["data"].as_array().unwrap().is_empty());
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/messages/{message_id}"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let req = authenticated_request(
+        Method::GET,
+        "/api/v1/admin/content-safety/flags?status=pending",
+        &admin.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert_eq!(body["data"][0]["message_id"], message_id.as_str());
+
+    let review = |status: &str| {
+        authenticated_json_request(
+            Method::PATCH,
+            &format!("/api/v1/admin/content-safety/flags/{}", flag.id),
+            &admin.auth_header(),
+            &serde_json::json!({ "status": status }),
+        )
+    };
+    let response = server.router().oneshot(review("released")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(parse_body(response).await["data"]["status"], "released");
+    let response = server.router().oneshot(review("removed")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let body = parse_body(server.router().oneshot(list_messages()).await.unwrap()).await;
+    assert_eq!(body["data"][0]["id"], message_id.as_str());
+}
+
+#[tokio::test]
+async fn test_automod_rules_block_and_flag() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let member = server.create_user_with_token("member").await;
+    let space_id = server.create_space(&owner.user.id, "Modded").await;
+    let general = server.create_channel(&space_id, "general").await;
+    let log = server.create_channel(&space_id, "mod-log").await;
+    server.add_member(&space_id, &member.user.id).await;
+
+    // Plain members can't manage rules.
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/automod/rules"),
+        &member.auth_header(),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::FORBIDDEN
+    );
+
+    // Invalid regexes are rejected up front.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/automod/rules"),
+        &owner.auth_header(),
+        &serde_json::json!({
+            "name": "Broken",
+            "trigger": { "type": "regex", "patterns": ["(unclosed"] },
+            "actions": [{ "type": "block" }],
+        }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/automod/rules"),
+        &owner.auth_header(),
+        &serde_json::json!({
+            "name": "No spoilers",
+            "trigger": { "type": "keyword", "keywords": ["spoiler"] },
+            "actions": [{ "type": "block" }, { "type": "flag", "channel_id": log }],
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let rule = parse_body(response).await["data"].clone();
+    let rule_id = rule["id"].as_str().unwrap().to_string();
+    assert_eq!(rule["enabled"], true);
+    assert_eq!(rule["trigger"]["type"], "keyword");
+
+    // A matching message is blocked and reported in the log channel.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{general}/messages"),
+        &member.auth_header(),
+        &serde_json::json!({ "content": "big SPOILER ahead" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "automod_blocked"
+    );
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{log}/messages"),
+        &owner.auth_header(),
+    );
+    let logged = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(logged.as_array().unwrap().len(), 1);
+    assert_eq!(logged[0]["type"], "automod_flag");
+    assert!(logged[0]["content"]
+        .as_str()
+        .unwrap()
+        .contains("big SPOILER ahead"));
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{general}/messages"),
+        &owner.auth_header(),
+    );
+    let messages = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert!(messages.as_array().unwrap().is_empty());
+
+    // Managers are exempt.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{general}/messages"),
+        &owner.auth_header(),
+        &serde_json::json!({ "content": "spoiler policy: don't" }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+
+    // Disabled rules don't run.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}/automod/rules/{rule_id}"),
+        &owner.auth_header(),
+        &serde_json::json!({ "enabled": false }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(parse_body(response).await["data"]["enabled"], false);
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{general}/messages"),
+        &member.auth_header(),
+        &serde_json::json!({ "content": "another spoiler" }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_automod_stats_and_overrides() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let member = server.create_user_with_token("member").await;
+    let space_id = server.create_space(&owner.user.id, "Tuned").await;
+    let general = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &member.user.id).await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/automod/rules"),
+        &owner.auth_header(),
+        &serde_json::json!({
+            "name": "Scams",
+            "trigger": { "type": "keyword", "keywords": ["free nitro"] },
+            "actions": [{ "type": "block" }],
+        }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+
+    for content in ["@owner FREE NITRO here", "is free nitro a scam?"] {
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{general}/messages"),
+            &member.auth_header(),
+            &serde_json::json!({ "content": content }),
+        );
+        assert_eq!(
+            server.router().oneshot(req).await.unwrap().status(),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    let stats_url = format!("/api/v1/spaces/{space_id}/automod/stats");
+    let req = authenticated_request(Method::GET, &stats_url, &member.auth_header());
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let req = authenticated_request(Method::GET, &stats_url, &owner.auth_header());
+    let data = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(data["total_hits"], 2);
+    let rule = &data["rules"][0];
+    assert_eq!(rule["hits"], 2);
+    assert_eq!(rule["overrides"], 0);
+    assert_eq!(rule["trigger_type"], "keyword");
+    let samples: Vec<&str> = rule["samples"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s.as_str().unwrap())
+        .collect();
+    assert!(samples.contains(&"@user FREE NITRO here"));
+
+    // Mark one hit as a false positive.
+    let hit_id: String = sqlx::query_scalar(&accordserver::db::q(
+        "SELECT id FROM automod_hits WHERE sample LIKE '%scam%'",
+    ))
+    .fetch_one(server.pool())
+    .await
+    .unwrap();
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/automod/hits/{hit_id}/override"),
+        &owner.auth_header(),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+
+    let req = authenticated_request(Method::GET, &stats_url, &owner.auth_header());
+    let data = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(data["rules"][0]["overrides"], 1);
+    assert_eq!(data["rules"][0]["override_rate"], 0.5);
+}
+
+#[tokio::test]
+async fn test_space_locale_drives_system_messages() {
+    use accordserver::db;
+
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_public_space(&alice.user.id, "Café").await;
+    let intro_channel_id = server.create_channel(&space_id, "accueil").await;
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "preferred_locale": "tlh" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Locales are matched case-insensitively and stored canonically.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "preferred_locale": "FR", "system_channel_id": intro_channel_id }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(parse_body(response).await["data"]["preferred_locale"], "fr");
+
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/join"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let msgs = db::messages::list_messages(server.pool(), &intro_channel_id, None, 50, None)
+        .await
+        .unwrap();
+    let intro = msgs
+        .iter()
+        .find(|m| m.message_type == "member_join")
+        .expect("join should post an introduction");
+    assert!(
+        intro.content.ends_with("a rejoint le serveur."),
+        "unexpected intro: {}",
+        intro.content
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Query budgets: hot endpoints must not grow a query per row.
+// ---------------------------------------------------------------------------
+
+const MESSAGE_LIST_QUERY_BUDGET: usize = 25;
+const MEMBER_LIST_QUERY_BUDGET: usize = 15;
+
+#[tokio::test]
+async fn test_query_recorder_counts_every_execution() {
+    let server = TestServer::new().await;
+    let lookup = accordserver::db::q("SELECT COUNT(*) FROM users WHERE id = ?");
+
+    let ((), log) = accordserver::db::instrument::record(async {
+        // One statement built once and run in a loop: the N+1 shape.
+        for id in ["a", "b", "c"] {
+            sqlx::query_scalar::<_, i64>(&lookup)
+                .bind(id)
+                .fetch_one(server.pool())
+                .await
+                .unwrap();
+        }
+        // Raw statements that never go through db::q() count too.
+        sqlx::query("SELECT 1")
+            .execute(server.pool())
+            .await
+            .unwrap();
+        let mut tx = server.pool().begin().await.unwrap();
+        sqlx::query("SELECT 1").execute(&mut tx).await.unwrap();
+        tx.commit().await.unwrap();
+    })
+    .await;
+
+    assert_eq!(log.count(), 5);
+    assert_eq!(log.repeated(), vec![(lookup.as_str(), 3), ("SELECT 1", 2)]);
+}
+
+#[tokio::test]
+async fn test_message_list_query_budget() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Budget").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    let messages_url = format!("/api/v1/channels/{channel_id}/messages");
+
+    let post = |content: String| {
+        let req = authenticated_json_request(
+            Method::POST,
+            &messages_url,
+            &alice.auth_header(),
+            &serde_json::json!({ "content": content }),
+        );
+        server.router().oneshot(req)
+    };
+    let list_queries = || async {
+        let req = authenticated_request(Method::GET, &messages_url, &alice.auth_header());
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        query_count(&response)
+    };
+
+    post("first".into()).await.unwrap();
+    let one = list_queries().await;
+    // Straight into the db: twenty sends would trip the message_send bucket.
+    for i in 0..20 {
+        accordserver::db::messages::create_message(
+            server.pool(),
+            &channel_id,
+            &alice.user.id,
+            Some(&space_id),
+            &accordserver::models::message::CreateMessage {
+                content: format!("message {i}"),
+                tts: None,
+                embeds: None,
+                reply_to: None,
+                thread_id: None,
+                title: None,
+                poll: None,
+                sticker_ids: None,
+            },
+        )
+        .await
+        .unwrap();
+    }
+    let many = list_queries().await;
+
+    assert_eq!(one, many, "message list issues a query per message");
+    assert_query_budget(
+        "GET /channels/{id}/messages",
+        many,
+        MESSAGE_LIST_QUERY_BUDGET,
+    );
+}
+
+#[tokio::test]
+async fn test_member_list_query_budget() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Budget").await;
+    let members_url = format!("/api/v1/spaces/{space_id}/members?with_user=true");
+
+    let list_queries = || async {
+        let req = authenticated_request(Method::GET, &members_url, &alice.auth_header());
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        query_count(&response)
+    };
+
+    let one = list_queries().await;
+    for i in 0..20 {
+        let member = server.create_user_with_token(&format!("member{i}")).await;
+        server.add_member(&space_id, &member.user.id).await;
+    }
+    let many = list_queries().await;
+
+    assert_eq!(one, many, "member list issues a query per member");
+    assert_query_budget("GET /spaces/{id}/members", many, MEMBER_LIST_QUERY_BUDGET);
+}
+
+const READY_QUERY_BUDGET: usize = 35;
+
+async fn ready_payload(ws_url: &str, token: &str) -> serde_json::Value {
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    ws.next().await.unwrap().unwrap(); // HELLO
+    let identify = serde_json::json!({
+        "op": 2,
+        "data": { "token": token, "intents": ["messages"] }
+    });
+    ws.send(Message::Text(identify.to_string().into()))
+        .await
+        .unwrap();
+    let msg = ws.next().await.unwrap().unwrap();
+    let ready: serde_json::Value = serde_json::from_str(&msg.into_text().unwrap()).unwrap();
+    assert_eq!(ready["type"], "ready");
+    let _ = ws.close(None).await;
+    ready
+}
+
+#[tokio::test]
+async fn test_ready_query_budget() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Budget").await;
+    server.create_channel(&space_id, "general").await;
+    let ws_url = server.spawn().await.replace("http://", "ws://");
+
+    let small = ready_query_count(&ready_payload(&ws_url, &alice.gateway_token()).await);
+
+    for i in 0..10 {
+        let member = server.create_user_with_token(&format!("member{i}")).await;
+        server.add_member(&space_id, &member.user.id).await;
+        let channel_id = server.create_channel(&space_id, &format!("room-{i}")).await;
+        let req = authenticated_json_request(
+            Method::PUT,
+            &format!(
+                "/api/v1/channels/{channel_id}/permissions/{}",
+                member.user.id
+            ),
+            &alice.auth_header(),
+            &serde_json::json!({ "type": "member", "allow": ["view_channel"], "deny": [] }),
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let large = ready_query_count(&ready_payload(&ws_url, &alice.gateway_token()).await);
+
+    assert_eq!(small, large, "READY issues a query per member or channel");
+    assert_query_budget("READY", large, READY_QUERY_BUDGET);
+}
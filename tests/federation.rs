@@ -352,6 +352,8 @@ async fn local_message_fans_out_to_interested_peers() {
             reply_to: None,
             thread_id: None,
             title: None,
+            poll: None,
+            sticker_ids: None,
         },
     )
     .await
@@ -398,6 +400,8 @@ async fn local_message_without_remote_members_does_not_fan_out() {
             reply_to: None,
             thread_id: None,
             title: None,
+            poll: None,
+            sticker_ids: None,
         },
     )
     .await
@@ -1152,6 +1156,8 @@ async fn home_react_rejects_message_from_a_different_channel() {
             reply_to: None,
             thread_id: None,
             title: None,
+            poll: None,
+            sticker_ids: None,
         },
     )
     .await
@@ -1364,6 +1370,7 @@ async fn local_emoji_create_fans_out_to_interested_peers() {
         Some("image/png"),
         Some(10),
         false,
+        false,
     )
     .await
     .unwrap();
@@ -1,7 +1,7 @@
 mod common;
 
 use axum::body::Body;
-use common::{authenticated_json_request, authenticated_request, parse_body, TestServer};
+use common::{authenticated_json_request, authenticated_request, parse_body, TestServer, TestUser};
 use http::{Method, Request, StatusCode};
 use tower::ServiceExt;
 
@@ -104,6 +104,229 @@ async fn test_cors_preflight() {
         .contains_key("access-control-allow-methods"));
 }
 
+#[tokio::test]
+async fn test_cors_respects_allowed_origins_setting() {
+    let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/admin/settings",
+        &admin.auth_header(),
+        &serde_json::json!({ "cors_allowed_origins": "https://app.example.com" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let cors_origin = |origin: &'static str| {
+        Request::builder()
+            .uri("/health")
+            .header("Origin", origin)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = server
+        .router()
+        .oneshot(cors_origin("https://app.example.com"))
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+
+    let response = server
+        .router()
+        .oneshot(cors_origin("https://evil.example.com"))
+        .await
+        .unwrap();
+    assert!(!response
+        .headers()
+        .contains_key("access-control-allow-origin"));
+}
+
+#[tokio::test]
+async fn test_cors_per_route_policy_and_credentials() {
+    let mut server = TestServer::new().await;
+    server.state.cors = std::sync::Arc::new(accordserver::config::CorsConfig {
+        allowed_origins: vec!["https://app.example.com".to_string()],
+        allow_credentials: true,
+        ..Default::default()
+    });
+    std::fs::write(server.state.storage_path.join("avatars/cors.png"), b"png").unwrap();
+
+    let with_origin = |uri: &str, origin: &str| {
+        Request::builder()
+            .uri(uri)
+            .header("Origin", origin)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // App routes: only the configured origin, with credentials.
+    let response = server
+        .router()
+        .oneshot(with_origin("/health", "https://app.example.com"))
+        .await
+        .unwrap();
+    let headers = response.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+    assert_eq!(headers["access-control-allow-credentials"], "true");
+
+    let response = server
+        .router()
+        .oneshot(with_origin("/health", "https://other.example.com"))
+        .await
+        .unwrap();
+    assert!(!response
+        .headers()
+        .contains_key("access-control-allow-origin"));
+
+    // CDN: open to any origin and never credentialed.
+    let response = server
+        .router()
+        .oneshot(with_origin(
+            "/cdn/avatars/cors.png",
+            "https://other.example.com",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(headers["access-control-allow-origin"], "*");
+    assert!(!headers.contains_key("access-control-allow-credentials"));
+}
+
+#[tokio::test]
+async fn test_cors_credentials_without_origins_refuses_cross_origin() {
+    let mut server = TestServer::new().await;
+    server.state.cors = std::sync::Arc::new(accordserver::config::CorsConfig {
+        allow_credentials: true,
+        ..Default::default()
+    });
+
+    let response = server
+        .router()
+        .oneshot(
+            Request::builder()
+                .uri("/health")
+                .header("Origin", "https://app.example.com")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(!response
+        .headers()
+        .contains_key("access-control-allow-origin"));
+}
+
+#[tokio::test]
+async fn test_cors_credentials_never_match_wildcard() {
+    let mut server = TestServer::new().await;
+    server.state.cors = std::sync::Arc::new(accordserver::config::CorsConfig {
+        allowed_origins: vec!["*".to_string()],
+        allow_credentials: true,
+        ..Default::default()
+    });
+    let admin = server.create_admin_with_token("admin").await;
+
+    let from_evil = || {
+        Request::builder()
+            .uri("/health")
+            .header("Origin", "https://evil.example.com")
+            .body(Body::empty())
+            .unwrap()
+    };
+    let response = server.router().oneshot(from_evil()).await.unwrap();
+    assert!(!response
+        .headers()
+        .contains_key("access-control-allow-origin"));
+
+    // The setting can't open it up either.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/admin/settings",
+        &admin.auth_header(),
+        &serde_json::json!({ "cors_allowed_origins": "https://app.example.com, *" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // A wildcard stored before credentials were turned on matches nothing.
+    let mut settings = (**server.state.settings.load()).clone();
+    settings.cors_allowed_origins = Some("*".to_string());
+    server.state.settings.store(std::sync::Arc::new(settings));
+    let response = server.router().oneshot(from_evil()).await.unwrap();
+    assert!(!response
+        .headers()
+        .contains_key("access-control-allow-origin"));
+}
+
+#[tokio::test]
+async fn test_security_headers_present() {
+    let app = common::test_app().await;
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/health")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let headers = response.headers();
+    assert_eq!(headers["x-content-type-options"], "nosniff");
+    assert_eq!(headers["x-frame-options"], "DENY");
+    assert_eq!(
+        headers["referrer-policy"],
+        "strict-origin-when-cross-origin"
+    );
+    // CSP is only applied to CDN responses.
+    assert!(!headers.contains_key("content-security-policy"));
+}
+
+#[tokio::test]
+async fn test_cdn_content_security_policy() {
+    let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
+    std::fs::write(server.state.storage_path.join("avatars/csp.png"), b"png").unwrap();
+
+    let cdn_request = || {
+        Request::builder()
+            .uri("/cdn/avatars/csp.png")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = server.router().oneshot(cdn_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-security-policy"],
+        accordserver::middleware::security_headers::DEFAULT_CDN_CSP
+    );
+    assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/admin/settings",
+        &admin.auth_header(),
+        &serde_json::json!({ "cdn_content_security_policy": "default-src 'none'" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = server.router().oneshot(cdn_request()).await.unwrap();
+    assert_eq!(
+        response.headers()["content-security-policy"],
+        "default-src 'none'"
+    );
+}
+
 #[tokio::test]
 async fn test_ws_rejects_non_upgrade() {
     let app = common::test_app().await;
@@ -124,1936 +347,4846 @@ async fn test_ws_rejects_non_upgrade() {
 // ---------------------------------------------------------------------------
 
 #[tokio::test]
-async fn test_message_search_content() {
+async fn test_public_space_directory() {
     let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "SearchSpace").await;
-    let channel_id = server.create_channel(&space_id, "general").await;
+    let bob = server.create_user_with_token("bob").await;
 
-    // Create messages via DB
-    let msg_input = accordserver::models::message::CreateMessage {
-        content: "hello world".to_string(),
-        tts: None,
-        embeds: None,
-        reply_to: None,
-        thread_id: None,
-        title: None,
+    let games = server
+        .create_public_space(&alice.user.id, "Retro Games")
+        .await;
+    let tunes = server.create_public_space(&alice.user.id, "Tune Lab").await;
+    let hidden = server.create_space(&alice.user.id, "Hidden Games").await;
+    server.add_member(&tunes, &bob.user.id).await;
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{games}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "category": "knitting" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{games}"),
+        &alice.auth_header(),
+        &serde_json::json!({
+            "description": "Speedruns and 8-bit classics",
+            "category": "gaming",
+            "tags": ["Retro", "speedrun", "retro"]
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(
+        body["data"]["tags"],
+        serde_json::json!(["retro", "speedrun"])
+    );
+
+    let list = |query: &str| {
+        let app = server.router();
+        let req = authenticated_request(
+            Method::GET,
+            &format!("/api/v1/spaces/public{query}"),
+            &bob.auth_header(),
+        );
+        async move {
+            let response = app.oneshot(req).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            parse_body(response).await
+        }
+    };
+    let ids = |body: &serde_json::Value| -> Vec<String> {
+        body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["id"].as_str().unwrap().to_string())
+            .collect()
     };
-    accordserver::db::messages::create_message(
-        server.pool(),
-        &channel_id,
-        &alice.user.id,
-        Some(&space_id),
-        &msg_input,
-    )
-    .await
-    .unwrap();
 
-    let msg_input2 = accordserver::models::message::CreateMessage {
-        content: "goodbye world".to_string(),
-        tts: None,
-        embeds: None,
-        reply_to: None,
-        thread_id: None,
-        title: None,
+    // Most members first by default; private spaces never show.
+    let body = list("").await;
+    assert_eq!(ids(&body), vec![tunes.clone(), games.clone()]);
+    assert_eq!(body["data"][0]["member_count"], 2);
+    assert!(!ids(&body).contains(&hidden));
+
+    assert_eq!(ids(&list("?q=8-BIT%20classics").await), vec![games.clone()]);
+    assert_eq!(ids(&list("?category=gaming").await), vec![games.clone()]);
+    assert_eq!(ids(&list("?tag=Speedrun").await), vec![games.clone()]);
+    assert!(ids(&list("?tag=speed").await).is_empty());
+    assert_eq!(
+        ids(&list("?sort=name").await),
+        vec![games.clone(), tunes.clone()]
+    );
+
+    let page = list("?limit=1").await;
+    assert_eq!(ids(&page), vec![tunes.clone()]);
+    assert_eq!(page["cursor"]["offset"], 1);
+    let page = list("?limit=1&offset=1").await;
+    assert_eq!(ids(&page), vec![games.clone()]);
+    assert!(page.get("cursor").is_none());
+
+    // Activity follows the latest message.
+    let channel_id = server.create_channel(&games, "chat").await;
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+        &serde_json::json!({ "content": "gg" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = list("?sort=activity").await;
+    assert_eq!(ids(&body), vec![games.clone(), tunes.clone()]);
+    assert!(body["data"][0]["last_activity_at"].is_string());
+
+    // Only server admins feature spaces.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/admin/spaces/{tunes}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "featured": true }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/admin/spaces/{tunes}"),
+        &admin.auth_header(),
+        &serde_json::json!({ "featured": true }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = list("?featured=true").await;
+    assert_eq!(ids(&body), vec![tunes.clone()]);
+    assert_eq!(body["data"][0]["featured"], true);
+
+    let req = authenticated_request(
+        Method::GET,
+        "/api/v1/spaces/public/categories",
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let gaming = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["name"] == "gaming")
+        .unwrap();
+    assert_eq!(gaming["space_count"], 1);
+}
+
+#[tokio::test]
+async fn test_archived_space_is_read_only() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let carol = server.create_user_with_token("carol").await;
+    let space_id = server.create_space(&alice.user.id, "Old Times").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/invites"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let code = body["data"]["code"].as_str().unwrap().to_string();
+
+    let archive_uri = format!("/api/v1/spaces/{space_id}/archive");
+    let req = authenticated_request(Method::PUT, &archive_uri, &bob.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let req = authenticated_request(Method::PUT, &archive_uri, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(parse_body(response).await["data"]["archived_at"].is_string());
+
+    let send = |auth: String| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/messages"),
+            &auth,
+            &serde_json::json!({ "content": "anyone here?" }),
+        );
+        async move { app.oneshot(req).await.unwrap() }
     };
-    accordserver::db::messages::create_message(
-        server.pool(),
-        &channel_id,
-        &alice.user.id,
-        Some(&space_id),
-        &msg_input2,
+    let response = send(bob.auth_header()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "space_archived"
+    );
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{channel_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "name": "renamed" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "space_archived"
+    );
+
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/invites/{code}/accept"),
+        &carol.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "space_archived"
+    );
+
+    // Reads still work.
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(Method::DELETE, &archive_uri, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(parse_body(response).await["data"]["archived_at"].is_null());
+    let response = send(bob.auth_header()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_welcome_screen_and_member_screening() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Rules First").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}/welcome-screen"),
+        &alice.auth_header(),
+        &serde_json::json!({
+            "enabled": true,
+            "description": "  Say hi!  ",
+            "channels": [{ "channel_id": channel_id, "description": "Chat here" }]
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["description"], "Say hi!");
+    assert_eq!(
+        body["data"]["channels"][0]["channel_id"],
+        channel_id.as_str()
+    );
+
+    let screening_uri = format!("/api/v1/spaces/{space_id}/member-screening");
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &screening_uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "enabled": true }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &screening_uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "enabled": true, "rules": ["Be kind", "No spam"] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/invites"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let code = body["data"]["code"].as_str().unwrap().to_string();
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/invites/{code}/accept"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
+        &alice.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["pending"], true);
+
+    // Pending members can read the welcome screen and rules but not post.
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/welcome-screen"),
+        &bob.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["enabled"], true);
+    let req = authenticated_request(Method::GET, &screening_uri, &bob.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(
+        body["data"]["rules"],
+        serde_json::json!(["Be kind", "No spam"])
+    );
+
+    let send = || {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/messages"),
+            &bob.auth_header(),
+            &serde_json::json!({ "content": "hello!" }),
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+    let response = send().await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "screening_pending"
+    );
+
+    let req = authenticated_request(
+        Method::POST,
+        &format!("{screening_uri}/accept"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(parse_body(response).await["data"]["pending"], false);
+    let response = send().await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Only members who can manage the space change the configuration.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &screening_uri,
+        &bob.auth_header(),
+        &serde_json::json!({ "enabled": false }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_invite_preview_and_join_token() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let carol = server.create_user_with_token("carol").await;
+    let space_id = server.create_space(&alice.user.id, "Look First").await;
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "invite_preview_required": true }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        parse_body(response).await["data"]["invite_preview_required"],
+        true
+    );
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}/member-screening"),
+        &alice.auth_header(),
+        &serde_json::json!({ "enabled": true, "rules": ["Be kind"] }),
+    );
+    server.router().oneshot(req).await.unwrap();
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/invites"),
+        &alice.auth_header(),
+        &serde_json::json!({ "max_uses": 5 }),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let code = body["data"]["code"].as_str().unwrap().to_string();
+    let accept = |auth: String, body: serde_json::Value| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/invites/{code}/accept"),
+            &auth,
+            &body,
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+    let preview = |auth: String| {
+        let app = server.router();
+        let req = authenticated_request(
+            Method::GET,
+            &format!("/api/v1/invites/{code}/preview"),
+            &auth,
+        );
+        async move { parse_body(app.oneshot(req).await.unwrap()).await["data"].clone() }
+    };
+
+    // Without a token the space turns the join away, and the invite isn't spent.
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/invites/{code}/accept"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "join_token_required"
+    );
+
+    let data = preview(bob.auth_header()).await;
+    assert_eq!(data["space"]["name"], "Look First");
+    assert_eq!(data["space"]["member_count"], 1);
+    assert_eq!(
+        data["member_screening"]["rules"],
+        serde_json::json!(["Be kind"])
+    );
+    assert!(data["welcome_screen"].is_null());
+    assert_eq!(data["already_member"], false);
+    assert_eq!(data["invite"]["uses"], 0);
+    let token = data["join_token"].as_str().unwrap().to_string();
+
+    // Tokens are bound to whoever previewed.
+    let response = accept(
+        carol.auth_header(),
+        serde_json::json!({ "join_token": token }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "invalid_join_token"
+    );
+    let response = accept(
+        carol.auth_header(),
+        serde_json::json!({ "accept_screening": true }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Accepting screening in the same call joins without being pending.
+    let response = accept(
+        bob.auth_header(),
+        serde_json::json!({ "join_token": token, "accept_screening": true }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(parse_body(response).await["data"]["uses"], 1);
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
+        &alice.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["pending"], false);
+
+    // A token only works once.
+    let response = accept(
+        bob.auth_header(),
+        serde_json::json!({ "join_token": token }),
+    )
+    .await;
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "invalid_join_token"
+    );
+
+    // Without accept_screening the member still joins pending.
+    let token = preview(carol.auth_header()).await["join_token"].clone();
+    let response = accept(
+        carol.auth_header(),
+        serde_json::json!({ "join_token": token }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/members/{}", carol.user.id),
+        &alice.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["pending"], true);
+}
+
+#[tokio::test]
+async fn test_raid_lockdown() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Busy").await;
+    let mut joiners = Vec::new();
+    for name in ["bob", "carol", "dave", "eve"] {
+        joiners.push(server.create_user_with_token(name).await);
+    }
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/invites"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let code = body["data"]["code"].as_str().unwrap().to_string();
+    let accept = |auth: String| {
+        let app = server.router();
+        let req = authenticated_request(
+            Method::POST,
+            &format!("/api/v1/invites/{code}/accept"),
+            &auth,
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+
+    let raid_uri = format!("/api/v1/spaces/{space_id}/raid-protection");
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &raid_uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "auto_lockdown": true, "join_threshold": 3, "mode": "block_joins" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Alice's own join counts, so carol's is the third in the window.
+    for joiner in &joiners[..2] {
+        let response = accept(joiner.auth_header()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = accept(joiners[2].auth_header()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "space_lockdown"
+    );
+
+    let req = authenticated_request(Method::GET, &raid_uri, &alice.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["lockdown"]["automatic"], true);
+    assert!(body["data"]["lockdown"]["until"].is_string());
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/audit-logs?action_type=space_lockdown_start"),
+        &alice.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+
+    let lockdown_uri = format!("/api/v1/spaces/{space_id}/lockdown");
+    let req = authenticated_request(Method::DELETE, &lockdown_uri, &alice.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert!(body["data"]["lockdown"].is_null());
+
+    // A manual lockdown in account_age mode only turns away new accounts.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &raid_uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "auto_lockdown": false, "mode": "account_age", "min_account_age_secs": 86400 }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let req = authenticated_request(Method::PUT, &lockdown_uri, &joiners[0].auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let req = authenticated_request(Method::PUT, &lockdown_uri, &alice.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["lockdown"]["automatic"], false);
+    assert!(body["data"]["lockdown"]["until"].is_null());
+    let response = accept(joiners[3].auth_header()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "account_too_new"
+    );
+}
+
+#[tokio::test]
+async fn test_space_email_domains() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_public_space(&alice.user.id, "Company").await;
+    let users = [
+        ("bob", "bob@eu.company.com", true),
+        ("carol", "carol@company.com", false),
+        ("dave", "dave@elsewhere.org", true),
+    ];
+    let mut joiners = Vec::new();
+    for (name, email, verified) in users {
+        let user = server.create_user_with_token(name).await;
+        sqlx::query(&accordserver::db::q(
+            "UPDATE users SET email = ?, email_verified = ? WHERE id = ?",
+        ))
+        .bind(email)
+        .bind(verified)
+        .bind(&user.user.id)
+        .execute(server.pool())
+        .await
+        .unwrap();
+        joiners.push(user);
+    }
+
+    let uri = format!("/api/v1/spaces/{space_id}/email-domains");
+    let req = authenticated_json_request(
+        Method::PUT,
+        &uri,
+        &joiners[0].auth_header(),
+        &serde_json::json!({ "domains": ["company.com"] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let req = authenticated_json_request(
+        Method::PUT,
+        &uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "domains": ["not a domain"] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let req = authenticated_json_request(
+        Method::PUT,
+        &uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "domains": ["@Company.com", "company.com"] }),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["domains"], serde_json::json!(["company.com"]));
+
+    let req = authenticated_request(Method::GET, &uri, &joiners[2].auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["domains"], serde_json::json!(["company.com"]));
+
+    let join = |user: &TestUser| {
+        let app = server.router();
+        let req = authenticated_request(
+            Method::POST,
+            &format!("/api/v1/spaces/{space_id}/join"),
+            &user.auth_header(),
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+    // Subdomains count; an unverified address on the right domain doesn't.
+    assert_eq!(join(&joiners[0]).await.status(), StatusCode::OK);
+    let response = join(&joiners[1]).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "email_verification_required"
+    );
+    let response = join(&joiners[2]).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "email_domain_not_allowed"
+    );
+
+    // Invites are held to the same rule.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/invites"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let code = body["data"]["code"].as_str().unwrap().to_string();
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/invites/{code}/accept"),
+        &joiners[2].auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Clearing the list opens the space up again.
+    let req = authenticated_json_request(
+        Method::PUT,
+        &uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "domains": [] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(join(&joiners[2]).await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_instance_report_queue() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let carol = server.create_user_with_token("carol").await;
+    let admin = server.create_admin_with_token("admin").await;
+    let dm_id = server.create_dm(&alice.user.id, &bob.user.id).await;
+    let space_id = server.create_space(&bob.user.id, "Bob's").await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{dm_id}/messages"),
+        &bob.auth_header(),
+        &serde_json::json!({ "content": "buy my coins" }),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let message_id = body["data"]["id"].as_str().unwrap().to_string();
+
+    let report = |user: &TestUser, body: serde_json::Value| {
+        let app = server.router();
+        let req =
+            authenticated_json_request(Method::POST, "/api/v1/reports", &user.auth_header(), &body);
+        async move { app.oneshot(req).await.unwrap() }
+    };
+
+    // A DM message is reported to the instance, with no space.
+    let response = report(
+        &alice,
+        serde_json::json!({ "target_type": "message", "target_id": message_id, "category": "fraud" }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert!(body["data"]["space_id"].is_null());
+    assert_eq!(body["data"]["channel_id"], dm_id.as_str());
+    assert_eq!(body["data"]["status"], "pending");
+    let report_id = body["data"]["id"].as_str().unwrap().to_string();
+
+    // Only what the reporter can see can be reported.
+    let response = report(
+        &carol,
+        serde_json::json!({ "target_type": "message", "target_id": message_id, "category": "fraud" }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = report(
+        &carol,
+        serde_json::json!({ "target_type": "space", "target_id": space_id, "category": "other" }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = report(
+        &carol,
+        serde_json::json!({ "target_type": "user", "target_id": carol.user.id, "category": "other" }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // A report about a space goes to the admins, not the space's moderators.
+    let response = report(
+        &bob,
+        serde_json::json!({ "target_type": "space", "target_id": space_id, "category": "hate" }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/reports"),
+        &bob.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 0);
+
+    let req = authenticated_request(Method::GET, "/api/v1/admin/reports", &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let req = authenticated_request(
+        Method::GET,
+        "/api/v1/admin/reports?status=pending&target_type=message",
+        &admin.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let queue = body["data"].as_array().unwrap();
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue[0]["id"], report_id.as_str());
+
+    let update = |status: &str, action: Option<&str>| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::PATCH,
+            &format!("/api/v1/admin/reports/{report_id}"),
+            &admin.auth_header(),
+            &serde_json::json!({ "status": status, "action_taken": action }),
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+    let response = update("reviewing", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["actioned_by"], admin.user.id.as_str());
+    assert!(body["data"]["resolved_at"].is_null());
+
+    let body = parse_body(update("actioned", Some("user suspended")).await).await;
+    assert_eq!(body["data"]["status"], "actioned");
+    assert!(body["data"]["resolved_at"].is_string());
+
+    // Resolved reports can be reopened for review, not sent back to pending.
+    let response = update("pending", None).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "invalid_report_transition"
+    );
+    let body = parse_body(update("reviewing", None).await).await;
+    assert_eq!(body["data"]["status"], "reviewing");
+    assert_eq!(body["data"]["action_taken"], "user suspended");
+    assert!(body["data"]["resolved_at"].is_null());
+}
+
+#[tokio::test]
+async fn test_message_embeds_are_validated() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Embeds").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let send = |embeds: serde_json::Value| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/messages"),
+            &alice.auth_header(),
+            &serde_json::json!({ "content": "see below", "embeds": embeds }),
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+
+    let response = send(serde_json::json!([{
+        "title": "t".repeat(300),
+        "url": "javascript:alert(1)",
+        "fields": [{ "name": "ok", "value": "" }]
+    }]))
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let error = parse_body(response).await["error"].clone();
+    assert_eq!(error["code"], "invalid_fields");
+    let fields: Vec<&str> = error["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        fields,
+        vec![
+            "embeds[0].fields[0].value",
+            "embeds[0].title",
+            "embeds[0].url"
+        ]
+    );
+
+    let response = send(serde_json::json!([{
+        "title": "Release",
+        "url": "  https://example.com/release  ",
+        "color": 3447003
+    }]))
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(
+        body["data"]["embeds"][0]["url"],
+        "https://example.com/release"
+    );
+}
+
+#[tokio::test]
+async fn test_message_search_content() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "SearchSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    // Create messages via DB
+    let msg_input = accordserver::models::message::CreateMessage {
+        content: "hello world".to_string(),
+        tts: None,
+        embeds: None,
+        reply_to: None,
+        thread_id: None,
+        title: None,
+        poll: None,
+        sticker_ids: None,
+    };
+    accordserver::db::messages::create_message(
+        server.pool(),
+        &channel_id,
+        &alice.user.id,
+        Some(&space_id),
+        &msg_input,
+    )
+    .await
+    .unwrap();
+
+    let msg_input2 = accordserver::models::message::CreateMessage {
+        content: "goodbye world".to_string(),
+        tts: None,
+        embeds: None,
+        reply_to: None,
+        thread_id: None,
+        title: None,
+        poll: None,
+        sticker_ids: None,
+    };
+    accordserver::db::messages::create_message(
+        server.pool(),
+        &channel_id,
+        &alice.user.id,
+        Some(&space_id),
+        &msg_input2,
+    )
+    .await
+    .unwrap();
+
+    // Search for "hello"
+    let app = server.router();
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/messages/search?query=hello"),
+        &alice.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0]["content"], "hello world");
+}
+
+#[tokio::test]
+async fn test_message_search_author_filter() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "SearchSpace").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    // Alice sends a message
+    let msg = accordserver::models::message::CreateMessage {
+        content: "from alice".to_string(),
+        tts: None,
+        embeds: None,
+        reply_to: None,
+        thread_id: None,
+        title: None,
+        poll: None,
+        sticker_ids: None,
+    };
+    accordserver::db::messages::create_message(
+        server.pool(),
+        &channel_id,
+        &alice.user.id,
+        Some(&space_id),
+        &msg,
+    )
+    .await
+    .unwrap();
+
+    // Bob sends a message
+    let msg2 = accordserver::models::message::CreateMessage {
+        content: "from bob".to_string(),
+        tts: None,
+        embeds: None,
+        reply_to: None,
+        thread_id: None,
+        title: None,
+        poll: None,
+        sticker_ids: None,
+    };
+    accordserver::db::messages::create_message(
+        server.pool(),
+        &channel_id,
+        &bob.user.id,
+        Some(&space_id),
+        &msg2,
+    )
+    .await
+    .unwrap();
+
+    // Search by author_id (bob)
+    let app = server.router();
+    let req = authenticated_request(
+        Method::GET,
+        &format!(
+            "/api/v1/spaces/{space_id}/messages/search?author_id={}",
+            bob.user.id
+        ),
+        &alice.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0]["content"], "from bob");
+}
+
+#[tokio::test]
+async fn test_message_search_pinned_filter() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "SearchSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let msg = accordserver::models::message::CreateMessage {
+        content: "pinned message".to_string(),
+        tts: None,
+        embeds: None,
+        reply_to: None,
+        thread_id: None,
+        title: None,
+        poll: None,
+        sticker_ids: None,
+    };
+    let created = accordserver::db::messages::create_message(
+        server.pool(),
+        &channel_id,
+        &alice.user.id,
+        Some(&space_id),
+        &msg,
+    )
+    .await
+    .unwrap();
+
+    let msg2 = accordserver::models::message::CreateMessage {
+        content: "unpinned message".to_string(),
+        tts: None,
+        embeds: None,
+        reply_to: None,
+        thread_id: None,
+        title: None,
+        poll: None,
+        sticker_ids: None,
+    };
+    accordserver::db::messages::create_message(
+        server.pool(),
+        &channel_id,
+        &alice.user.id,
+        Some(&space_id),
+        &msg2,
+    )
+    .await
+    .unwrap();
+
+    // Pin the first message
+    accordserver::db::messages::pin_message(
+        server.pool(),
+        &channel_id,
+        &created.id,
+        server.state.db_is_postgres,
+    )
+    .await
+    .unwrap();
+
+    // Search for pinned messages
+    let app = server.router();
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/messages/search?pinned=true"),
+        &alice.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0]["content"], "pinned message");
+}
+
+#[tokio::test]
+async fn test_pins_post_notices_and_respect_the_channel_limit() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "PinSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{channel_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "max_pins": 1 }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(parse_body(response).await["data"]["max_pins"], 1);
+
+    let mut ids = Vec::new();
+    for content in ["first", "second"] {
+        let msg = accordserver::models::message::CreateMessage {
+            content: content.to_string(),
+            tts: None,
+            embeds: None,
+            reply_to: None,
+            thread_id: None,
+            title: None,
+            poll: None,
+            sticker_ids: None,
+        };
+        let created = accordserver::db::messages::create_message(
+            server.pool(),
+            &channel_id,
+            &alice.user.id,
+            Some(&space_id),
+            &msg,
+        )
+        .await
+        .unwrap();
+        ids.push(created.id);
+    }
+    let pin = |id: &str| {
+        authenticated_request(
+            Method::PUT,
+            &format!("/api/v1/channels/{channel_id}/pins/{id}"),
+            &alice.auth_header(),
+        )
+    };
+
+    let mut events = server
+        .state
+        .gateway_tx
+        .read()
+        .await
+        .as_ref()
+        .unwrap()
+        .subscribe();
+    let response = server.router().oneshot(pin(&ids[0])).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let notice = events.try_recv().unwrap();
+    assert_eq!(notice.event["type"], "message.create");
+    assert_eq!(notice.event["data"]["type"], "channel_pinned_message");
+    assert_eq!(notice.event["data"]["reply_to"], ids[0].as_str());
+    let update = events.try_recv().unwrap();
+    assert_eq!(update.event["type"], "channel.pins_update");
+    assert_eq!(update.event["data"]["channel_id"], channel_id.as_str());
+    assert!(update.event["data"]["last_pin_timestamp"].is_string());
+
+    // Pinning it again changes nothing.
+    let response = server.router().oneshot(pin(&ids[0])).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(events.try_recv().is_err());
+
+    let response = server.router().oneshot(pin(&ids[1])).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = parse_body(response).await;
+    assert_eq!(body["error"]["code"], "pin_limit_reached");
+    assert_eq!(body["error"]["current"], 1);
+    assert_eq!(body["error"]["max"], 1);
+
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/channels/{channel_id}/pins/{}", ids[0]),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let update = events.try_recv().unwrap();
+    assert_eq!(update.event["type"], "channel.pins_update");
+    assert!(update.event["data"]["last_pin_timestamp"].is_null());
+}
+
+#[tokio::test]
+async fn test_replies_carry_a_preview_and_resolved_mentions() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "ReplySpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let role_id = server.create_role(&space_id, "Helpers", &[]).await;
+
+    let post = |token: String, body: serde_json::Value| {
+        authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/messages"),
+            &token,
+            &body,
+        )
+    };
+    let original = "x".repeat(150);
+    let response = server
+        .router()
+        .oneshot(post(
+            alice.auth_header(),
+            serde_json::json!({ "content": original }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let original_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let mut events = server
+        .state
+        .gateway_tx
+        .read()
+        .await
+        .as_ref()
+        .unwrap()
+        .subscribe();
+    let content = format!(
+        "<@{}> see <#{channel_id}>, or ask <@&{role_id}> <@999>",
+        alice.user.id
+    );
+    let response = server
+        .router()
+        .oneshot(post(
+            bob.auth_header(),
+            serde_json::json!({ "content": content, "reply_to": original_id }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let reply = parse_body(response).await["data"].clone();
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.event["type"], "message.create");
+    assert_eq!(
+        event.event["data"]["referenced_message"],
+        reply["referenced_message"]
+    );
+
+    let referenced = &reply["referenced_message"];
+    assert_eq!(referenced["id"], original_id.as_str());
+    assert_eq!(referenced["author"]["username"], "alice");
+    assert_eq!(referenced["content"].as_str().unwrap().len(), 100);
+    assert_eq!(reply["mentions"], serde_json::json!([alice.user.id]));
+    assert_eq!(reply["mention_roles"], serde_json::json!([role_id]));
+    let resolved = reply["resolved_mentions"].as_array().unwrap();
+    assert_eq!(resolved.len(), 3);
+    assert_eq!(resolved[0]["type"], "user");
+    assert_eq!(resolved[0]["username"], "alice");
+    assert_eq!(resolved[1]["type"], "role");
+    assert_eq!(resolved[1]["name"], "Helpers");
+    assert_eq!(resolved[2]["type"], "channel");
+    assert_eq!(resolved[2]["name"], "general");
+
+    // History carries the same context.
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let body = parse_body(response).await;
+    let listed = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["id"] == reply["id"])
+        .unwrap();
+    assert_eq!(
+        listed["referenced_message"]["author"]["id"],
+        alice.user.id.as_str()
+    );
+    assert_eq!(listed["resolved_mentions"].as_array().unwrap().len(), 3);
+    let original = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["id"] == original_id.as_str())
+        .unwrap();
+    assert!(original["referenced_message"].is_null());
+}
+
+#[tokio::test]
+async fn test_message_search_pagination() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "SearchSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    // Create 3 messages
+    for i in 0..3 {
+        let msg = accordserver::models::message::CreateMessage {
+            content: format!("message {i}"),
+            tts: None,
+            embeds: None,
+            reply_to: None,
+            thread_id: None,
+            title: None,
+            poll: None,
+            sticker_ids: None,
+        };
+        accordserver::db::messages::create_message(
+            server.pool(),
+            &channel_id,
+            &alice.user.id,
+            Some(&space_id),
+            &msg,
+        )
+        .await
+        .unwrap();
+    }
+
+    // Search with limit=2
+    let app = server.router();
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/messages/search?query=message&limit=2"),
+        &alice.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 2);
+    assert_eq!(body["cursor"]["has_more"], true);
+
+    // Use cursor for next page
+    let cursor = body["cursor"]["after"].as_str().unwrap();
+    let app = server.router();
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/messages/search?query=message&limit=2&cursor={cursor}"),
+        &alice.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(body["cursor"]["has_more"], false);
+}
+
+#[tokio::test]
+async fn test_message_search_non_member_forbidden() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "PrivateSpace").await;
+    let _channel_id = server.create_channel(&space_id, "general").await;
+
+    // Bob is not a member — should get 403
+    let app = server.router();
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/messages/search?query=hello"),
+        &bob.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_message_search_empty_filters_bad_request() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "SearchSpace").await;
+
+    // No filters → 400
+    let app = server.router();
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/messages/search"),
+        &alice.auth_header(),
+    );
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+// ---------------------------------------------------------------------------
+// Read State / Unread Tests
+// ---------------------------------------------------------------------------
+
+/// Regression test: a thread reply must not bump channels.last_message_id, so
+/// the channel stops appearing in get_unread_channels for users who have already
+/// acked the channel's main timeline. Previously, every message insert bumped
+/// the pointer, which caused a channel to silently re-highlight as unread after
+/// any thread activity once the client reconnected and re-read the unread list.
+#[tokio::test]
+async fn test_thread_reply_does_not_mark_channel_unread() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server
+        .create_space(&alice.user.id, "ThreadUnreadSpace")
+        .await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let channel_id = server.create_channel(&space_id, "announcements").await;
+
+    // Alice posts a top-level message.
+    let parent = accordserver::db::messages::create_message(
+        server.pool(),
+        &channel_id,
+        &alice.user.id,
+        Some(&space_id),
+        &accordserver::models::message::CreateMessage {
+            content: "official announcement".to_string(),
+            tts: None,
+            embeds: None,
+            reply_to: None,
+            thread_id: None,
+            title: None,
+            poll: None,
+            sticker_ids: None,
+        },
+    )
+    .await
+    .unwrap();
+
+    // Bob acks the channel at that message — he has now read everything.
+    let is_postgres = accordserver::db::url_is_postgres(
+        &std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite::memory:".to_string()),
+    );
+    accordserver::db::read_states::ack_channel(
+        server.pool(),
+        &bob.user.id,
+        &channel_id,
+        &parent.id,
+        is_postgres,
+    )
+    .await
+    .unwrap();
+
+    let unread_before =
+        accordserver::db::read_states::get_unread_channels(server.pool(), &bob.user.id)
+            .await
+            .unwrap();
+    assert!(
+        !unread_before.iter().any(|u| u.channel_id == channel_id),
+        "channel should not be unread immediately after ack"
+    );
+
+    // Alice posts a thread reply against the parent message.
+    accordserver::db::messages::create_message(
+        server.pool(),
+        &channel_id,
+        &alice.user.id,
+        Some(&space_id),
+        &accordserver::models::message::CreateMessage {
+            content: "thread reply".to_string(),
+            tts: None,
+            embeds: None,
+            reply_to: None,
+            thread_id: Some(parent.id.clone()),
+            title: None,
+            poll: None,
+            sticker_ids: None,
+        },
+    )
+    .await
+    .unwrap();
+
+    // Bob still has no unseen top-level content, so the channel must not be
+    // reported as unread.
+    let unread_after =
+        accordserver::db::read_states::get_unread_channels(server.pool(), &bob.user.id)
+            .await
+            .unwrap();
+    assert!(
+        !unread_after.iter().any(|u| u.channel_id == channel_id),
+        "channel should not become unread from a thread reply alone"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Message Share Link Tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_message_share_links() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "ShareSpace").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    let msg = accordserver::db::messages::create_message(
+        server.pool(),
+        &channel_id,
+        &alice.user.id,
+        Some(&space_id),
+        &accordserver::models::message::CreateMessage {
+            content: "look @everyone".to_string(),
+            tts: None,
+            embeds: None,
+            reply_to: None,
+            thread_id: None,
+            title: None,
+            poll: None,
+            sticker_ids: None,
+        },
+    )
+    .await
+    .unwrap();
+    let share_uri = format!("/api/v1/channels/{channel_id}/messages/{}/share", msg.id);
+
+    // Sharing is off until the space opts in.
+    let response = server
+        .router()
+        .oneshot(authenticated_request(
+            Method::POST,
+            &share_uri,
+            &bob.auth_header(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "sharing_disabled"
+    );
+
+    let response = server
+        .router()
+        .oneshot(authenticated_json_request(
+            Method::PATCH,
+            &format!("/api/v1/spaces/{space_id}"),
+            &alice.auth_header(),
+            &serde_json::json!({ "message_sharing": true }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = server
+        .router()
+        .oneshot(authenticated_request(
+            Method::POST,
+            &share_uri,
+            &bob.auth_header(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let share = parse_body(response).await["data"].clone();
+    let token = share["token"].as_str().unwrap().to_string();
+
+    // Asking again hands back the same link.
+    let response = server
+        .router()
+        .oneshot(authenticated_request(
+            Method::POST,
+            &share_uri,
+            &bob.auth_header(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(parse_body(response).await["data"]["id"], share["id"]);
+
+    // Anyone can render it, with mentions defused.
+    let public = |token: &str| {
+        Request::builder()
+            .uri(format!("/api/v1/shared-messages/{token}"))
+            .body(Body::empty())
+            .unwrap()
+    };
+    let response = server.router().oneshot(public(&token)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["content"], "look @\u{200b}everyone");
+    assert_eq!(body["data"]["author"]["username"], "alice");
+    assert_eq!(body["data"]["space"]["name"], "ShareSpace");
+
+    // A tampered token fails its signature check.
+    let forged = format!("{}x", token);
+    let response = server.router().oneshot(public(&forged)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // The author sees every link to their message and can revoke them.
+    let response = server
+        .router()
+        .oneshot(authenticated_request(
+            Method::GET,
+            &format!("{share_uri}s"),
+            &alice.auth_header(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(
+        parse_body(response).await["data"].as_array().unwrap().len(),
+        1
+    );
+
+    let response = server
+        .router()
+        .oneshot(authenticated_request(
+            Method::DELETE,
+            &format!("{share_uri}s/{}", share["id"].as_str().unwrap()),
+            &alice.auth_header(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = server.router().oneshot(public(&token)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+// ---------------------------------------------------------------------------
+// Emoji Tests
+// ---------------------------------------------------------------------------
+
+/// A tiny 1x1 red PNG encoded as base64 data URI.
+fn test_png_data_uri() -> String {
+    // Minimal valid PNG: 1x1 pixel, red
+    let png_bytes: &[u8] = &[
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, // PNG signature
+        0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, // IHDR chunk
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, // 1x1
+        0x08, 0x02, 0x00, 0x00, 0x00, 0x90, 0x77, 0x53, 0xDE, // 8-bit RGB
+        0x00, 0x00, 0x00, 0x0C, 0x49, 0x44, 0x41, 0x54, // IDAT chunk
+        0x08, 0xD7, 0x63, 0xF8, 0xCF, 0xC0, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0xE2, 0x21, 0xBC,
+        0x33, // compressed pixel data
+        0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, // IEND chunk
+        0xAE, 0x42, 0x60, 0x82,
+    ];
+    let b64 = simple_base64_encode(png_bytes);
+    format!("data:image/png;base64,{b64}")
+}
+
+/// A minimal OGG data URI for audio testing.
+fn test_ogg_data_uri() -> String {
+    // Just enough bytes to pass validation (not a real OGG, but the server only validates mime type)
+    let fake_ogg: &[u8] = &[0x4F, 0x67, 0x67, 0x53, 0x00, 0x02, 0x00, 0x00];
+    let b64 = simple_base64_encode(fake_ogg);
+    format!("data:audio/ogg;base64,{b64}")
+}
+
+fn simple_base64_encode(data: &[u8]) -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::new();
+    for chunk in data.chunks(3) {
+        let b0 = chunk[0] as u32;
+        let b1 = if chunk.len() > 1 { chunk[1] as u32 } else { 0 };
+        let b2 = if chunk.len() > 2 { chunk[2] as u32 } else { 0 };
+        let triple = (b0 << 16) | (b1 << 8) | b2;
+        result.push(CHARS[((triple >> 18) & 0x3F) as usize] as char);
+        result.push(CHARS[((triple >> 12) & 0x3F) as usize] as char);
+        if chunk.len() > 1 {
+            result.push(CHARS[((triple >> 6) & 0x3F) as usize] as char);
+        } else {
+            result.push('=');
+        }
+        if chunk.len() > 2 {
+            result.push(CHARS[(triple & 0x3F) as usize] as char);
+        } else {
+            result.push('=');
+        }
+    }
+    result
+}
+
+#[tokio::test]
+async fn test_emoji_create_with_image() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "EmojiSpace").await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/emojis"),
+        &alice.auth_header(),
+        &serde_json::json!({
+            "name": "test_emoji",
+            "image": test_png_data_uri()
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let emoji = &body["data"];
+    assert_eq!(emoji["name"], "test_emoji");
+    assert!(
+        emoji["image_url"].as_str().is_some(),
+        "expected image_url in response"
+    );
+    let image_url = emoji["image_url"].as_str().unwrap();
+    assert!(
+        image_url.starts_with("/cdn/emojis/"),
+        "image_url should start with /cdn/emojis/"
+    );
+    assert!(
+        image_url.ends_with(".png"),
+        "image_url should end with .png"
+    );
+}
+
+#[tokio::test]
+async fn test_emoji_list_has_image_url() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "EmojiSpace").await;
+
+    // Create an emoji
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/emojis"),
+        &alice.auth_header(),
+        &serde_json::json!({
+            "name": "list_emoji",
+            "image": test_png_data_uri()
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // List emojis
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/emojis"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let emojis = body["data"].as_array().unwrap();
+    assert_eq!(emojis.len(), 1);
+    assert!(emojis[0]["image_url"].as_str().is_some());
+}
+
+#[tokio::test]
+async fn test_emoji_delete_cleans_up_file() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "EmojiSpace").await;
+
+    // Create emoji
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/emojis"),
+        &alice.auth_header(),
+        &serde_json::json!({
+            "name": "deleteme",
+            "image": test_png_data_uri()
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let emoji_id = body["data"]["id"].as_str().unwrap().to_string();
+    let image_url = body["data"]["image_url"].as_str().unwrap().to_string();
+
+    // Verify the file exists on disk
+    let file_path = server
+        .state
+        .storage_path
+        .join(image_url.strip_prefix("/cdn/").unwrap());
+    assert!(file_path.exists(), "emoji file should exist on disk");
+
+    // Delete emoji
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/spaces/{space_id}/emojis/{emoji_id}"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Verify the file is cleaned up
+    assert!(
+        !file_path.exists(),
+        "emoji file should be deleted from disk"
+    );
+}
+
+#[tokio::test]
+async fn test_cdn_serves_emoji_image() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "EmojiSpace").await;
+
+    // Create emoji
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/emojis"),
+        &alice.auth_header(),
+        &serde_json::json!({
+            "name": "cdntest",
+            "image": test_png_data_uri()
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let image_url = body["data"]["image_url"].as_str().unwrap().to_string();
+
+    // Fetch the image via CDN endpoint
+    let req = Request::builder()
+        .uri(&image_url)
+        .body(Body::empty())
+        .unwrap();
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(!bytes.is_empty(), "CDN should serve the image file");
+}
+
+#[tokio::test]
+async fn test_emoji_moderation_queue() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "EmojiSpace").await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    let upload = |name: &str| {
+        authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/spaces/{space_id}/emojis"),
+            &bob.auth_header(),
+            &serde_json::json!({ "name": name, "image": test_png_data_uri() }),
+        )
+    };
+
+    // Without moderation, members lacking manage_emojis can't upload.
+    let response = server.router().oneshot(upload("wave")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "emoji_moderation": true }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = server.router().oneshot(upload("wave")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let wave = parse_body(response).await["data"].clone();
+    assert_eq!(wave["pending"], true);
+    let wave_id = wave["id"].as_str().unwrap().to_string();
+
+    let response = server.router().oneshot(upload("nope")).await.unwrap();
+    let nope_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Pending uploads stay out of the public list.
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/emojis"),
+        &bob.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert!(body["data"].as_array().unwrap().is_empty());
+
+    // Only reviewers can see the queue or act on it.
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/emojis/pending"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/emojis/pending"),
+        &alice.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/emojis/{wave_id}/approve"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/emojis/{wave_id}/approve"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(parse_body(response).await["data"]["pending"], false);
+
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/emojis/{nope_id}/reject"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/emojis"),
+        &bob.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let emojis = body["data"].as_array().unwrap();
+    assert_eq!(emojis.len(), 1);
+    assert_eq!(emojis[0]["id"], wave_id.as_str());
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/emojis/{nope_id}"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+// ---------------------------------------------------------------------------
+// Soundboard Tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_soundboard_crud() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "SoundSpace").await;
+
+    // Create sound
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/soundboard"),
+        &alice.auth_header(),
+        &serde_json::json!({
+            "name": "airhorn",
+            "audio": test_ogg_data_uri(),
+            "volume": 0.8
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let sound = &body["data"];
+    let sound_id = sound["id"].as_str().unwrap().to_string();
+    assert_eq!(sound["name"], "airhorn");
+    assert_eq!(sound["volume"], 0.8);
+    assert!(sound["audio_url"].as_str().is_some());
+
+    // List sounds
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/soundboard"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let sounds = body["data"].as_array().unwrap();
+    assert_eq!(sounds.len(), 1);
+    assert_eq!(sounds[0]["name"], "airhorn");
+
+    // Get sound
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/soundboard/{sound_id}"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["name"], "airhorn");
+
+    // Update sound
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}/soundboard/{sound_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "name": "renamed-horn", "volume": 1.5 }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["name"], "renamed-horn");
+    assert_eq!(body["data"]["volume"], 1.5);
+
+    // Delete sound
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/spaces/{space_id}/soundboard/{sound_id}"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // List again — should be empty
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/soundboard"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn test_space_emoji_and_sound_slot_limits() {
+    let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "SlotSpace").await;
+
+    let get_space = || {
+        authenticated_request(
+            Method::GET,
+            &format!("/api/v1/spaces/{space_id}"),
+            &alice.auth_header(),
+        )
+    };
+    let body = parse_body(server.router().oneshot(get_space()).await.unwrap()).await;
+    assert_eq!(body["data"]["max_emojis"], 50);
+    assert_eq!(body["data"]["max_sounds"], 8);
+
+    // An instance admin gives this space a single emoji slot and the
+    // instance default drops to one sound.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/admin/spaces/{space_id}"),
+        &admin.auth_header(),
+        &serde_json::json!({ "max_emojis": 1 }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/admin/settings",
+        &admin.auth_header(),
+        &serde_json::json!({ "max_sounds_per_space": 1 }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(server.router().oneshot(get_space()).await.unwrap()).await;
+    assert_eq!(body["data"]["max_emojis"], 1);
+    assert_eq!(body["data"]["max_sounds"], 1);
+
+    let create_emoji = |name: &str| {
+        authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/spaces/{space_id}/emojis"),
+            &alice.auth_header(),
+            &serde_json::json!({ "name": name, "image": test_png_data_uri() }),
+        )
+    };
+    let response = server
+        .router()
+        .oneshot(create_emoji("first"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = server
+        .router()
+        .oneshot(create_emoji("second"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = parse_body(response).await;
+    assert_eq!(body["error"]["code"], "emoji_limit_reached");
+    assert_eq!(body["error"]["current"], 1);
+    assert_eq!(body["error"]["max"], 1);
+
+    let create_sound = |name: &str| {
+        authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/spaces/{space_id}/soundboard"),
+            &alice.auth_header(),
+            &serde_json::json!({ "name": name, "audio": test_ogg_data_uri() }),
+        )
+    };
+    let response = server.router().oneshot(create_sound("one")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = server.router().oneshot(create_sound("two")).await.unwrap();
+    let body = parse_body(response).await;
+    assert_eq!(body["error"]["code"], "sound_limit_reached");
+    assert_eq!(body["error"]["max"], 1);
+
+    // Clearing the override falls back to the instance default.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/admin/spaces/{space_id}"),
+        &admin.auth_header(),
+        &serde_json::json!({ "max_emojis": null }),
+    );
+    server.router().oneshot(req).await.unwrap();
+    let response = server
+        .router()
+        .oneshot(create_emoji("second"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_soundboard_play() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "SoundSpace").await;
+
+    // Create sound
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/soundboard"),
+        &alice.auth_header(),
+        &serde_json::json!({
+            "name": "horn",
+            "audio": test_ogg_data_uri()
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let sound_id = body["data"]["id"].as_str().unwrap().to_string();
+    let play = || {
+        authenticated_request(
+            Method::POST,
+            &format!("/api/v1/spaces/{space_id}/soundboard/{sound_id}/play"),
+            &alice.auth_header(),
+        )
+    };
+
+    // Sounds play into the caller's voice channel, so they must be in one.
+    let response = server.router().oneshot(play()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let vc_id = server.create_voice_channel(&space_id, "Lounge").await;
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        &alice.auth_header(),
+        &serde_json::json!({ "self_mute": false, "self_deaf": false }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let mut events = server
+        .state
+        .gateway_tx
+        .read()
+        .await
+        .as_ref()
+        .unwrap()
+        .subscribe();
+    let response = server.router().oneshot(play()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let played = events.try_recv().unwrap();
+    assert_eq!(played.event["type"], "soundboard.play");
+    assert_eq!(played.event["data"]["channel_id"], vc_id);
+    assert_eq!(played.event["data"]["sound_id"], sound_id);
+    assert_eq!(played.event["data"]["user_id"], alice.user.id);
+}
+
+// ---------------------------------------------------------------------------
+// Sticker Tests
+// ---------------------------------------------------------------------------
+
+/// The 1x1 PNG with an `acTL` chunk after its header, making it an APNG.
+fn test_apng_data_uri() -> String {
+    let mut bytes = tiny_png_bytes();
+    let actl = [
+        0x00, 0x00, 0x00, 0x08, b'a', b'c', b'T', b'L', 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    bytes.splice(33..33, actl);
+    format!("data:image/png;base64,{}", simple_base64_encode(&bytes))
+}
+
+#[tokio::test]
+async fn test_sticker_formats_served_with_content_types() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "StickerSpace").await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    let create = |user: &TestUser, body: serde_json::Value| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/spaces/{space_id}/stickers"),
+            &user.auth_header(),
+            &body,
+        );
+        async move { app.oneshot(req).await.unwrap() }
+    };
+    let lottie = format!(
+        "data:application/json;base64,{}",
+        simple_base64_encode(br#"{"v":"5.7.4","fr":30,"layers":[]}"#)
+    );
+    for (name, image, format, content_type) in [
+        ("wave", test_png_data_uri(), "png", "image/png"),
+        ("dance", test_apng_data_uri(), "apng", "image/apng"),
+        ("confetti", lottie, "lottie", "application/json"),
+    ] {
+        let response = create(
+            &alice,
+            serde_json::json!({ "name": name, "tags": "fun", "image": image }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let sticker = parse_body(response).await["data"].clone();
+        assert_eq!(sticker["format"], format);
+        assert_eq!(sticker["tags"], "fun");
+
+        let url = sticker["image_url"].as_str().unwrap();
+        assert!(url.starts_with(&format!("/cdn/stickers/{space_id}/")));
+        let req = Request::builder().uri(url).body(Body::empty()).unwrap();
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], content_type);
+    }
+
+    // Only PNGs and Lottie JSON are accepted, and only from managers.
+    let response = create(
+        &alice,
+        serde_json::json!({ "name": "bad", "image": "data:application/json;base64,e30=" }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = create(
+        &alice,
+        serde_json::json!({ "name": "x", "image": test_png_data_uri() }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = create(
+        &bob,
+        serde_json::json!({ "name": "bobs", "image": test_png_data_uri() }),
     )
-    .await
-    .unwrap();
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
-    // Search for "hello"
-    let app = server.router();
     let req = authenticated_request(
         Method::GET,
-        &format!("/api/v1/spaces/{space_id}/messages/search?query=hello"),
+        &format!("/api/v1/spaces/{space_id}/stickers"),
+        &bob.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let stickers = body["data"].as_array().unwrap();
+    assert_eq!(stickers.len(), 3);
+    let sticker_id = stickers[0]["id"].as_str().unwrap().to_string();
+    let url = stickers[0]["image_url"].as_str().unwrap().to_string();
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}/stickers/{sticker_id}"),
         &alice.auth_header(),
+        &serde_json::json!({ "name": "hello", "description": "Waving hand" }),
     );
-    let response = app.oneshot(req).await.unwrap();
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["name"], "hello");
+    assert_eq!(body["data"]["description"], "Waving hand");
+
+    // Deleting the sticker removes its file.
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/spaces/{space_id}/stickers/{sticker_id}"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let data = body["data"].as_array().unwrap();
-    assert_eq!(data.len(), 1);
-    assert_eq!(data[0]["content"], "hello world");
+    let path = server
+        .state
+        .storage_path
+        .join(url.trim_start_matches("/cdn/"));
+    assert!(!path.exists());
 }
 
 #[tokio::test]
-async fn test_message_search_author_filter() {
+async fn test_message_stickers_and_slot_limit() {
     let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
     let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "SearchSpace").await;
-    server.add_member(&space_id, &bob.user.id).await;
+    let space_id = server.create_space(&alice.user.id, "StickerSpace").await;
+    let other_space = server.create_space(&alice.user.id, "Elsewhere").await;
     let channel_id = server.create_channel(&space_id, "general").await;
 
-    // Alice sends a message
-    let msg = accordserver::models::message::CreateMessage {
-        content: "from alice".to_string(),
-        tts: None,
-        embeds: None,
-        reply_to: None,
-        thread_id: None,
-        title: None,
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/admin/spaces/{space_id}"),
+        &admin.auth_header(),
+        &serde_json::json!({ "max_stickers": 1 }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+
+    let create = |space_id: &str, name: &str| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/spaces/{space_id}/stickers"),
+            &alice.auth_header(),
+            &serde_json::json!({ "name": name, "image": test_png_data_uri() }),
+        );
+        async move { app.oneshot(req).await.unwrap() }
     };
-    accordserver::db::messages::create_message(
-        server.pool(),
-        &channel_id,
-        &alice.user.id,
-        Some(&space_id),
-        &msg,
-    )
-    .await
-    .unwrap();
+    let response = create(&space_id, "first").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let sticker_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let response = create(&space_id, "second").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = parse_body(response).await;
+    assert_eq!(body["error"]["code"], "sticker_limit_reached");
+    let response = create(&other_space, "foreign").await;
+    let foreign_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
 
-    // Bob sends a message
-    let msg2 = accordserver::models::message::CreateMessage {
-        content: "from bob".to_string(),
-        tts: None,
-        embeds: None,
-        reply_to: None,
-        thread_id: None,
-        title: None,
+    let send = |sticker_ids: serde_json::Value| {
+        let app = server.router();
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/messages"),
+            &alice.auth_header(),
+            &serde_json::json!({ "content": "", "sticker_ids": sticker_ids }),
+        );
+        async move { app.oneshot(req).await.unwrap() }
     };
-    accordserver::db::messages::create_message(
-        server.pool(),
-        &channel_id,
-        &bob.user.id,
-        Some(&space_id),
-        &msg2,
-    )
-    .await
-    .unwrap();
+    for bad in [
+        serde_json::json!([foreign_id]),
+        serde_json::json!([sticker_id, sticker_id]),
+    ] {
+        let response = send(bad).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = parse_body(response).await;
+        assert_eq!(body["error"]["errors"][0]["field"], "sticker_ids");
+    }
+
+    let response = send(serde_json::json!([sticker_id])).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["sticker_items"][0]["id"], sticker_id.as_str());
+    assert_eq!(body["data"]["sticker_items"][0]["name"], "first");
+    assert_eq!(body["data"]["sticker_items"][0]["format"], "png");
 
-    // Search by author_id (bob)
-    let app = server.router();
     let req = authenticated_request(
         Method::GET,
-        &format!(
-            "/api/v1/spaces/{space_id}/messages/search?author_id={}",
-            bob.user.id
-        ),
+        &format!("/api/v1/channels/{channel_id}/messages"),
         &alice.auth_header(),
     );
-    let response = app.oneshot(req).await.unwrap();
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(
+        body["data"][0]["sticker_items"][0]["id"],
+        sticker_id.as_str()
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Voice REST Endpoint Tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_voice_info_returns_backend() {
+    let server = TestServer::new().await;
+    let req = Request::builder()
+        .uri("/api/v1/voice/info")
+        .body(Body::empty())
+        .unwrap();
+    let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let data = body["data"].as_array().unwrap();
-    assert_eq!(data.len(), 1);
-    assert_eq!(data[0]["content"], "from bob");
+    assert_eq!(body["backend"], "livekit");
 }
 
 #[tokio::test]
-async fn test_message_search_pinned_filter() {
+async fn test_voice_join_voice_channel_success() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "SearchSpace").await;
-    let channel_id = server.create_channel(&space_id, "general").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
-    let msg = accordserver::models::message::CreateMessage {
-        content: "pinned message".to_string(),
-        tts: None,
-        embeds: None,
-        reply_to: None,
-        thread_id: None,
-        title: None,
-    };
-    let created = accordserver::db::messages::create_message(
-        server.pool(),
-        &channel_id,
-        &alice.user.id,
-        Some(&space_id),
-        &msg,
-    )
-    .await
-    .unwrap();
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        &alice.auth_header(),
+        &serde_json::json!({ "self_mute": false, "self_deaf": false }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let data = &body["data"];
+    assert_eq!(data["backend"], "livekit");
+    assert_eq!(data["voice_state"]["user_id"], alice.user.id);
+    assert_eq!(data["voice_state"]["channel_id"], vc_id);
+    assert_eq!(data["voice_state"]["self_mute"], false);
+    assert_eq!(data["voice_state"]["self_deaf"], false);
+    assert!(data["token"].as_str().is_some());
+}
 
-    let msg2 = accordserver::models::message::CreateMessage {
-        content: "unpinned message".to_string(),
-        tts: None,
-        embeds: None,
-        reply_to: None,
-        thread_id: None,
-        title: None,
-    };
-    accordserver::db::messages::create_message(
-        server.pool(),
-        &channel_id,
-        &alice.user.id,
-        Some(&space_id),
-        &msg2,
-    )
-    .await
-    .unwrap();
+#[tokio::test]
+async fn test_voice_join_text_channel_rejected() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let text_id = server.create_channel(&space_id, "general").await;
 
-    // Pin the first message
-    accordserver::db::messages::pin_message(
-        server.pool(),
-        &channel_id,
-        &created.id,
-        server.state.db_is_postgres,
-    )
-    .await
-    .unwrap();
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{text_id}/voice/join"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
 
-    // Search for pinned messages
-    let app = server.router();
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/spaces/{space_id}/messages/search?pinned=true"),
+#[tokio::test]
+async fn test_voice_join_with_self_mute_and_deaf() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
         &alice.auth_header(),
+        &serde_json::json!({ "self_mute": true, "self_deaf": true }),
     );
-    let response = app.oneshot(req).await.unwrap();
+    let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let data = body["data"].as_array().unwrap();
-    assert_eq!(data.len(), 1);
-    assert_eq!(data[0]["content"], "pinned message");
+    assert_eq!(body["data"]["voice_state"]["self_mute"], true);
+    assert_eq!(body["data"]["voice_state"]["self_deaf"], true);
 }
 
 #[tokio::test]
-async fn test_message_search_pagination() {
+async fn test_voice_leave_after_join() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "SearchSpace").await;
-    let channel_id = server.create_channel(&space_id, "general").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
-    // Create 3 messages
-    for i in 0..3 {
-        let msg = accordserver::models::message::CreateMessage {
-            content: format!("message {i}"),
-            tts: None,
-            embeds: None,
-            reply_to: None,
-            thread_id: None,
-            title: None,
-        };
-        accordserver::db::messages::create_message(
-            server.pool(),
-            &channel_id,
-            &alice.user.id,
-            Some(&space_id),
-            &msg,
-        )
-        .await
-        .unwrap();
-    }
+    // Join first
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
 
-    // Search with limit=2
-    let app = server.router();
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/spaces/{space_id}/messages/search?query=message&limit=2"),
+    // Leave
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/channels/{vc_id}/voice/leave"),
         &alice.auth_header(),
     );
-    let response = app.oneshot(req).await.unwrap();
+    let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let data = body["data"].as_array().unwrap();
-    assert_eq!(data.len(), 2);
-    assert_eq!(body["cursor"]["has_more"], true);
+    assert_eq!(body["data"]["ok"], true);
+}
+
+#[tokio::test]
+async fn test_voice_status_empty() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
-    // Use cursor for next page
-    let cursor = body["cursor"]["after"].as_str().unwrap();
-    let app = server.router();
     let req = authenticated_request(
         Method::GET,
-        &format!("/api/v1/spaces/{space_id}/messages/search?query=message&limit=2&cursor={cursor}"),
+        &format!("/api/v1/channels/{vc_id}/voice-status"),
         &alice.auth_header(),
     );
-    let response = app.oneshot(req).await.unwrap();
+    let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let data = body["data"].as_array().unwrap();
-    assert_eq!(data.len(), 1);
-    assert_eq!(body["cursor"]["has_more"], false);
+    let states = body["data"].as_array().unwrap();
+    assert!(states.is_empty());
 }
 
 #[tokio::test]
-async fn test_message_search_non_member_forbidden() {
+async fn test_voice_status_after_join() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "PrivateSpace").await;
-    let _channel_id = server.create_channel(&space_id, "general").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
-    // Bob is not a member — should get 403
-    let app = server.router();
+    // Join voice
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Check status
     let req = authenticated_request(
         Method::GET,
-        &format!("/api/v1/spaces/{space_id}/messages/search?query=hello"),
-        &bob.auth_header(),
+        &format!("/api/v1/channels/{vc_id}/voice-status"),
+        &alice.auth_header(),
     );
-    let response = app.oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let states = body["data"].as_array().unwrap();
+    assert_eq!(states.len(), 1);
+    assert_eq!(states[0]["user_id"], alice.user.id);
+    assert_eq!(states[0]["channel_id"], vc_id);
 }
 
 #[tokio::test]
-async fn test_message_search_empty_filters_bad_request() {
+async fn test_voice_token_refresh() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "SearchSpace").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+    let other_vc = server.create_voice_channel(&space_id, "other").await;
+    let refresh_uri = format!("/api/v1/channels/{vc_id}/voice/refresh-token");
 
-    // No filters → 400
-    let app = server.router();
+    let req = authenticated_request(Method::POST, &refresh_uri, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let joined = parse_body(response).await;
+    assert!(joined["data"]["expires_at"].is_string());
+    let session_id = joined["data"]["voice_state"]["session_id"].clone();
+
+    let req = authenticated_request(Method::POST, &refresh_uri, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["session_id"], session_id);
+    assert_eq!(body["data"]["channel_id"], vc_id.as_str());
+    assert!(!body["data"]["token"].as_str().unwrap().is_empty());
+    assert!(body["data"]["expires_at"].is_string());
+    assert!(server.state.voice_tokens.contains_key(&alice.user.id));
+
+    // Only for the call they're actually in.
     let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/spaces/{space_id}/messages/search"),
+        Method::POST,
+        &format!("/api/v1/channels/{other_vc}/voice/refresh-token"),
         &alice.auth_header(),
     );
-    let response = app.oneshot(req).await.unwrap();
+    let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
-}
 
-// ---------------------------------------------------------------------------
-// Read State / Unread Tests
-// ---------------------------------------------------------------------------
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/channels/{vc_id}/voice/leave"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!server.state.voice_tokens.contains_key(&alice.user.id));
+}
 
-/// Regression test: a thread reply must not bump channels.last_message_id, so
-/// the channel stops appearing in get_unread_channels for users who have already
-/// acked the channel's main timeline. Previously, every message insert bumped
-/// the pointer, which caused a channel to silently re-highlight as unread after
-/// any thread activity once the client reconnected and re-read the unread list.
 #[tokio::test]
-async fn test_thread_reply_does_not_mark_channel_unread() {
+async fn test_screen_share_stream_grant() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server
-        .create_space(&alice.user.id, "ThreadUnreadSpace")
-        .await;
-    server.add_member(&space_id, &bob.user.id).await;
-    let channel_id = server.create_channel(&space_id, "announcements").await;
-
-    // Alice posts a top-level message.
-    let parent = accordserver::db::messages::create_message(
-        server.pool(),
-        &channel_id,
-        &alice.user.id,
-        Some(&space_id),
-        &accordserver::models::message::CreateMessage {
-            content: "official announcement".to_string(),
-            tts: None,
-            embeds: None,
-            reply_to: None,
-            thread_id: None,
-            title: None,
-        },
-    )
-    .await
-    .unwrap();
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+    let stream_uri = format!("/api/v1/channels/{vc_id}/voice/stream");
 
-    // Bob acks the channel at that message — he has now read everything.
-    let is_postgres = accordserver::db::url_is_postgres(
-        &std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite::memory:".to_string()),
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "stream_max_height": 1000 }),
     );
-    accordserver::db::read_states::ack_channel(
-        server.pool(),
-        &bob.user.id,
-        &channel_id,
-        &parent.id,
-        is_postgres,
-    )
-    .await
-    .unwrap();
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
-    let unread_before =
-        accordserver::db::read_states::get_unread_channels(server.pool(), &bob.user.id)
-            .await
-            .unwrap();
-    assert!(
-        !unread_before.iter().any(|u| u.channel_id == channel_id),
-        "channel should not be unread immediately after ack"
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "stream_max_height": 1080, "stream_max_fps": 60 }),
     );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
 
-    // Alice posts a thread reply against the parent message.
-    accordserver::db::messages::create_message(
-        server.pool(),
-        &channel_id,
-        &alice.user.id,
-        Some(&space_id),
-        &accordserver::models::message::CreateMessage {
-            content: "thread reply".to_string(),
-            tts: None,
-            embeds: None,
-            reply_to: None,
-            thread_id: Some(parent.id.clone()),
-            title: None,
-        },
-    )
-    .await
-    .unwrap();
+    // Streaming needs a seat in the call.
+    let req = authenticated_request(Method::POST, &stream_uri, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
-    // Bob still has no unseen top-level content, so the channel must not be
-    // reported as unread.
-    let unread_after =
-        accordserver::db::read_states::get_unread_channels(server.pool(), &bob.user.id)
-            .await
-            .unwrap();
-    assert!(
-        !unread_after.iter().any(|u| u.channel_id == channel_id),
-        "channel should not become unread from a thread reply alone"
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(Method::POST, &stream_uri, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["voice_state"]["self_stream"], true);
+    assert_eq!(body["data"]["max_height"], 1080);
+    assert_eq!(body["data"]["max_fps"], 60);
+    assert_eq!(
+        body["data"]["identity"],
+        format!("stream_{}", alice.user.id)
     );
+    assert!(!body["data"]["token"].as_str().unwrap().is_empty());
+
+    let req = authenticated_request(Method::DELETE, &stream_uri, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(parse_body(response).await["data"]["self_stream"], false);
 }
 
-// ---------------------------------------------------------------------------
-// Emoji Tests
-// ---------------------------------------------------------------------------
+#[tokio::test]
+async fn test_voice_stats_report_and_read() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let carol = server.create_user_with_token("carol").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+    let stats_uri = format!("/api/v1/channels/{vc_id}/voice/stats");
+    let report = serde_json::json!({
+        "rtt_ms": 42.5,
+        "jitter_ms": 3.0,
+        "packet_loss": 0.05,
+        "bitrate": 64000
+    });
+
+    // Only people in the call can report.
+    let req = authenticated_json_request(Method::POST, &stats_uri, &alice.auth_header(), &report);
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
-/// A tiny 1x1 red PNG encoded as base64 data URI.
-fn test_png_data_uri() -> String {
-    // Minimal valid PNG: 1x1 pixel, red
-    let png_bytes: &[u8] = &[
-        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, // PNG signature
-        0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, // IHDR chunk
-        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, // 1x1
-        0x08, 0x02, 0x00, 0x00, 0x00, 0x90, 0x77, 0x53, 0xDE, // 8-bit RGB
-        0x00, 0x00, 0x00, 0x0C, 0x49, 0x44, 0x41, 0x54, // IDAT chunk
-        0x08, 0xD7, 0x63, 0xF8, 0xCF, 0xC0, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0xE2, 0x21, 0xBC,
-        0x33, // compressed pixel data
-        0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, // IEND chunk
-        0xAE, 0x42, 0x60, 0x82,
-    ];
-    let b64 = simple_base64_encode(png_bytes);
-    format!("data:image/png;base64,{b64}")
+    for user in [&alice, &bob] {
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{vc_id}/voice/join"),
+            &user.auth_header(),
+            &serde_json::json!({}),
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &stats_uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "packet_loss": 1.5 }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let req = authenticated_json_request(Method::POST, &stats_uri, &alice.auth_header(), &report);
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(Method::GET, &stats_uri, &bob.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 2);
+    let stats_for = |user_id: &str| {
+        data.iter()
+            .find(|s| s["user_id"] == user_id)
+            .unwrap()
+            .clone()
+    };
+    let alice_stats = stats_for(&alice.user.id);
+    assert_eq!(alice_stats["rtt_ms"], 42.5);
+    assert_eq!(alice_stats["bitrate"], 64000);
+    assert_eq!(alice_stats["quality"], "fair");
+    let bob_stats = stats_for(&bob.user.id);
+    assert!(bob_stats["rtt_ms"].is_null());
+    assert_eq!(bob_stats["quality"], "unknown");
+
+    // Non-members can't look.
+    let req = authenticated_request(Method::GET, &stats_uri, &carol.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
-/// A minimal OGG data URI for audio testing.
-fn test_ogg_data_uri() -> String {
-    // Just enough bytes to pass validation (not a real OGG, but the server only validates mime type)
-    let fake_ogg: &[u8] = &[0x4F, 0x67, 0x67, 0x53, 0x00, 0x02, 0x00, 0x00];
-    let b64 = simple_base64_encode(fake_ogg);
-    format!("data:audio/ogg;base64,{b64}")
-}
+#[tokio::test]
+async fn test_server_mute_and_deafen_apply_to_voice() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
-fn simple_base64_encode(data: &[u8]) -> String {
-    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
-    let mut result = String::new();
-    for chunk in data.chunks(3) {
-        let b0 = chunk[0] as u32;
-        let b1 = if chunk.len() > 1 { chunk[1] as u32 } else { 0 };
-        let b2 = if chunk.len() > 2 { chunk[2] as u32 } else { 0 };
-        let triple = (b0 << 16) | (b1 << 8) | b2;
-        result.push(CHARS[((triple >> 18) & 0x3F) as usize] as char);
-        result.push(CHARS[((triple >> 12) & 0x3F) as usize] as char);
-        if chunk.len() > 1 {
-            result.push(CHARS[((triple >> 6) & 0x3F) as usize] as char);
-        } else {
-            result.push('=');
-        }
-        if chunk.len() > 2 {
-            result.push(CHARS[(triple & 0x3F) as usize] as char);
-        } else {
-            result.push('=');
-        }
-    }
-    result
+    let join = || {
+        authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{vc_id}/voice/join"),
+            &bob.auth_header(),
+            &serde_json::json!({}),
+        )
+    };
+    let response = server.router().oneshot(join()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
+        &alice.auth_header(),
+        &serde_json::json!({ "mute": true, "deaf": true }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{vc_id}/voice-status"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let body = parse_body(response).await;
+    assert_eq!(body["data"][0]["mute"], true);
+    assert_eq!(body["data"][0]["deaf"], true);
+
+    // Rejoining doesn't shed a server mute.
+    let response = server.router().oneshot(join()).await.unwrap();
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["voice_state"]["mute"], true);
+
+    // Members can't mute others without mute_members.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}/members/{}", alice.user.id),
+        &bob.auth_header(),
+        &serde_json::json!({ "mute": true }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
 #[tokio::test]
-async fn test_emoji_create_with_image() {
+async fn test_stage_channel_speaker_flow() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "EmojiSpace").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "StageSpace").await;
+    server.add_member(&space_id, &bob.user.id).await;
 
     let req = authenticated_json_request(
         Method::POST,
-        &format!("/api/v1/spaces/{space_id}/emojis"),
+        &format!("/api/v1/spaces/{space_id}/channels"),
         &alice.auth_header(),
-        &serde_json::json!({
-            "name": "test_emoji",
-            "image": test_png_data_uri()
-        }),
+        &serde_json::json!({ "name": "town-hall", "type": "stage" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let stage_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let patch = |user: &TestUser, target: &str, body: serde_json::Value| {
+        authenticated_json_request(
+            Method::PATCH,
+            &format!("/api/v1/channels/{stage_id}/voice-states/{target}"),
+            &user.auth_header(),
+            &body,
+        )
+    };
+
+    // Joiners start in the audience.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{stage_id}/voice/join"),
+        &bob.auth_header(),
+        &serde_json::json!({}),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let emoji = &body["data"];
-    assert_eq!(emoji["name"], "test_emoji");
-    assert!(
-        emoji["image_url"].as_str().is_some(),
-        "expected image_url in response"
+    assert_eq!(body["data"]["voice_state"]["suppress"], true);
+
+    // The audience can't promote itself without an invite.
+    let req = patch(&bob, "@me", serde_json::json!({ "suppress": false }));
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Raise a hand, and a moderator brings it up to speak.
+    let req = patch(&bob, "@me", serde_json::json!({ "request_to_speak": true }));
+    let response = server.router().oneshot(req).await.unwrap();
+    let body = parse_body(response).await;
+    assert!(body["data"]["request_to_speak_timestamp"].is_string());
+
+    let req = patch(
+        &alice,
+        &bob.user.id,
+        serde_json::json!({ "suppress": false }),
     );
-    let image_url = emoji["image_url"].as_str().unwrap();
-    assert!(
-        image_url.starts_with("/cdn/emojis/"),
-        "image_url should start with /cdn/emojis/"
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["suppress"], false);
+    assert!(body["data"]["request_to_speak_timestamp"].is_null());
+
+    // Back to the audience, then invited up without asking.
+    let req = patch(
+        &alice,
+        &bob.user.id,
+        serde_json::json!({ "suppress": true }),
     );
-    assert!(
-        image_url.ends_with(".png"),
-        "image_url should end with .png"
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(parse_body(response).await["data"]["suppress"], true);
+
+    let req = patch(
+        &alice,
+        &bob.user.id,
+        serde_json::json!({ "suppress": false }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["suppress"], true);
+    assert_eq!(body["data"]["invited_to_speak"], true);
+
+    let req = patch(&bob, "@me", serde_json::json!({ "suppress": false }));
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["suppress"], false);
+    assert_eq!(body["data"]["invited_to_speak"], false);
+
+    // Members can't moderate the stage.
+    let req = patch(
+        &bob,
+        &alice.user.id,
+        serde_json::json!({ "suppress": false }),
     );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
 #[tokio::test]
-async fn test_emoji_list_has_image_url() {
+async fn test_voice_status_cleared_after_leave() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "EmojiSpace").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
-    // Create an emoji
+    // Join
     let req = authenticated_json_request(
         Method::POST,
-        &format!("/api/v1/spaces/{space_id}/emojis"),
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
+    );
+    server.router().oneshot(req).await.unwrap();
+
+    // Leave
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/channels/{vc_id}/voice/leave"),
+        &alice.auth_header(),
+    );
+    server.router().oneshot(req).await.unwrap();
+
+    // Status should be empty again
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{vc_id}/voice-status"),
         &alice.auth_header(),
-        &serde_json::json!({
-            "name": "list_emoji",
-            "image": test_png_data_uri()
-        }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let states = body["data"].as_array().unwrap();
+    assert!(states.is_empty());
+}
+
+#[tokio::test]
+async fn test_voice_regions_returns_data() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
 
-    // List emojis
     let req = authenticated_request(
         Method::GET,
-        &format!("/api/v1/spaces/{space_id}/emojis"),
+        &format!("/api/v1/spaces/{space_id}/voice-regions"),
         &alice.auth_header(),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let emojis = body["data"].as_array().unwrap();
-    assert_eq!(emojis.len(), 1);
-    assert!(emojis[0]["image_url"].as_str().is_some());
+    let regions = body["data"].as_array().unwrap();
+    assert!(!regions.is_empty());
+    assert_eq!(regions[0]["id"], "livekit");
 }
 
 #[tokio::test]
-async fn test_emoji_delete_cleans_up_file() {
+async fn test_voice_join_non_member_forbidden() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "EmojiSpace").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
-    // Create emoji
+    // Bob (non-member) tries to join voice → 403
     let req = authenticated_json_request(
         Method::POST,
-        &format!("/api/v1/spaces/{space_id}/emojis"),
-        &alice.auth_header(),
-        &serde_json::json!({
-            "name": "deleteme",
-            "image": test_png_data_uri()
-        }),
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        &bob.auth_header(),
+        &serde_json::json!({}),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let emoji_id = body["data"]["id"].as_str().unwrap().to_string();
-    let image_url = body["data"]["image_url"].as_str().unwrap().to_string();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
 
-    // Verify the file exists on disk
-    let file_path = server
-        .state
-        .storage_path
-        .join(image_url.strip_prefix("/cdn/").unwrap());
-    assert!(file_path.exists(), "emoji file should exist on disk");
+#[tokio::test]
+async fn test_voice_leave_non_member_forbidden() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
-    // Delete emoji
+    // Bob (non-member) tries to leave voice → 403
     let req = authenticated_request(
         Method::DELETE,
-        &format!("/api/v1/spaces/{space_id}/emojis/{emoji_id}"),
-        &alice.auth_header(),
+        &format!("/api/v1/channels/{vc_id}/voice/leave"),
+        &bob.auth_header(),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
 
-    // Verify the file is cleaned up
-    assert!(
-        !file_path.exists(),
-        "emoji file should be deleted from disk"
+#[tokio::test]
+async fn test_voice_status_non_member_forbidden() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+
+    // Bob (non-member) tries to check voice status → 403
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{vc_id}/voice-status"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_voice_regions_non_member_forbidden() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+
+    // Bob (non-member) tries to list voice regions → 403
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/voice-regions"),
+        &bob.auth_header(),
     );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
 #[tokio::test]
-async fn test_cdn_serves_emoji_image() {
+async fn test_voice_multiple_users_in_channel() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "EmojiSpace").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
-    // Create emoji
+    // Both join
     let req = authenticated_json_request(
         Method::POST,
-        &format!("/api/v1/spaces/{space_id}/emojis"),
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
+    );
+    server.router().oneshot(req).await.unwrap();
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        &bob.auth_header(),
+        &serde_json::json!({}),
+    );
+    server.router().oneshot(req).await.unwrap();
+
+    // Status should show both users
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{vc_id}/voice-status"),
         &alice.auth_header(),
-        &serde_json::json!({
-            "name": "cdntest",
-            "image": test_png_data_uri()
-        }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let image_url = body["data"]["image_url"].as_str().unwrap().to_string();
+    let states = body["data"].as_array().unwrap();
+    assert_eq!(states.len(), 2);
+}
 
-    // Fetch the image via CDN endpoint
+#[tokio::test]
+async fn test_voice_join_unauthenticated() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+
+    // No auth header
     let req = Request::builder()
-        .uri(&image_url)
-        .body(Body::empty())
+        .method(Method::POST)
+        .uri(format!("/api/v1/channels/{vc_id}/voice/join"))
+        .header("Content-Type", "application/json")
+        .body(Body::from(
+            serde_json::to_vec(&serde_json::json!({})).unwrap(),
+        ))
         .unwrap();
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
-        .await
-        .unwrap();
-    assert!(!bytes.is_empty(), "CDN should serve the image file");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
 
 // ---------------------------------------------------------------------------
-// Soundboard Tests
+// Avatar Upload Tests
 // ---------------------------------------------------------------------------
 
 #[tokio::test]
-async fn test_soundboard_crud() {
+async fn test_user_avatar_upload() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "SoundSpace").await;
 
-    // Create sound
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/spaces/{space_id}/soundboard"),
+        Method::PATCH,
+        "/api/v1/users/@me",
         &alice.auth_header(),
-        &serde_json::json!({
-            "name": "airhorn",
-            "audio": test_ogg_data_uri(),
-            "volume": 0.8
-        }),
+        &serde_json::json!({ "avatar": test_png_data_uri() }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let sound = &body["data"];
-    let sound_id = sound["id"].as_str().unwrap().to_string();
-    assert_eq!(sound["name"], "airhorn");
-    assert_eq!(sound["volume"], 0.8);
-    assert!(sound["audio_url"].as_str().is_some());
-
-    // List sounds
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/spaces/{space_id}/soundboard"),
-        &alice.auth_header(),
+    let avatar = body["data"]["avatar"].as_str().unwrap();
+    assert!(
+        avatar.starts_with("/cdn/avatars/"),
+        "avatar should be a CDN path, got: {avatar}"
     );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let sounds = body["data"].as_array().unwrap();
-    assert_eq!(sounds.len(), 1);
-    assert_eq!(sounds[0]["name"], "airhorn");
+    assert!(avatar.ends_with(".png"), "avatar should end with .png");
 
-    // Get sound
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/spaces/{space_id}/soundboard/{sound_id}"),
-        &alice.auth_header(),
-    );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    assert_eq!(body["data"]["name"], "airhorn");
+    // Verify the file exists on disk
+    let file_path = server
+        .state
+        .storage_path
+        .join(avatar.strip_prefix("/cdn/").unwrap());
+    assert!(file_path.exists(), "avatar file should exist on disk");
+}
 
-    // Update sound
+#[tokio::test]
+async fn test_user_avatar_replace() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+
+    // Upload first avatar
     let req = authenticated_json_request(
         Method::PATCH,
-        &format!("/api/v1/spaces/{space_id}/soundboard/{sound_id}"),
+        "/api/v1/users/@me",
         &alice.auth_header(),
-        &serde_json::json!({ "name": "renamed-horn", "volume": 1.5 }),
+        &serde_json::json!({ "avatar": test_png_data_uri() }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    assert_eq!(body["data"]["name"], "renamed-horn");
-    assert_eq!(body["data"]["volume"], 1.5);
+    let first_avatar = body["data"]["avatar"].as_str().unwrap().to_string();
 
-    // Delete sound
-    let req = authenticated_request(
-        Method::DELETE,
-        &format!("/api/v1/spaces/{space_id}/soundboard/{sound_id}"),
-        &alice.auth_header(),
-    );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
+    let first_path = server
+        .state
+        .storage_path
+        .join(first_avatar.strip_prefix("/cdn/").unwrap());
+    assert!(first_path.exists(), "first avatar should exist");
 
-    // List again — should be empty
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/spaces/{space_id}/soundboard"),
+    // Upload replacement avatar
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/users/@me",
         &alice.auth_header(),
+        &serde_json::json!({ "avatar": test_png_data_uri() }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    assert_eq!(body["data"].as_array().unwrap().len(), 0);
+    let second_avatar = body["data"]["avatar"].as_str().unwrap().to_string();
+    assert!(second_avatar.starts_with("/cdn/avatars/"));
+
+    // New file should exist
+    let second_path = server
+        .state
+        .storage_path
+        .join(second_avatar.strip_prefix("/cdn/").unwrap());
+    assert!(second_path.exists(), "replacement avatar should exist");
 }
 
 #[tokio::test]
-async fn test_soundboard_play() {
+async fn test_user_avatar_remove() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "SoundSpace").await;
 
-    // Create sound
+    // Upload avatar
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/spaces/{space_id}/soundboard"),
+        Method::PATCH,
+        "/api/v1/users/@me",
         &alice.auth_header(),
-        &serde_json::json!({
-            "name": "horn",
-            "audio": test_ogg_data_uri()
-        }),
+        &serde_json::json!({ "avatar": test_png_data_uri() }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let sound_id = body["data"]["id"].as_str().unwrap().to_string();
+    let avatar_path = body["data"]["avatar"].as_str().unwrap().to_string();
 
-    // Play sound
-    let req = authenticated_request(
-        Method::POST,
-        &format!("/api/v1/spaces/{space_id}/soundboard/{sound_id}/play"),
-        &alice.auth_header(),
+    let file_path = server
+        .state
+        .storage_path
+        .join(avatar_path.strip_prefix("/cdn/").unwrap());
+    assert!(
+        file_path.exists(),
+        "avatar file should exist before removal"
     );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-}
-
-// ---------------------------------------------------------------------------
-// Voice REST Endpoint Tests
-// ---------------------------------------------------------------------------
-
-#[tokio::test]
-async fn test_voice_info_returns_backend() {
-    let server = TestServer::new().await;
-    let req = Request::builder()
-        .uri("/api/v1/voice/info")
-        .body(Body::empty())
-        .unwrap();
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    assert_eq!(body["backend"], "livekit");
-}
-
-#[tokio::test]
-async fn test_voice_join_voice_channel_success() {
-    let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
-    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
+    // Remove avatar by sending empty string
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        Method::PATCH,
+        "/api/v1/users/@me",
         &alice.auth_header(),
-        &serde_json::json!({ "self_mute": false, "self_deaf": false }),
+        &serde_json::json!({ "avatar": "" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let data = &body["data"];
-    assert_eq!(data["backend"], "livekit");
-    assert_eq!(data["voice_state"]["user_id"], alice.user.id);
-    assert_eq!(data["voice_state"]["channel_id"], vc_id);
-    assert_eq!(data["voice_state"]["self_mute"], false);
-    assert_eq!(data["voice_state"]["self_deaf"], false);
-    assert!(data["token"].as_str().is_some());
+    assert!(
+        body["data"]["avatar"].is_null(),
+        "avatar should be null after removal"
+    );
+
+    // Verify the file is deleted
+    assert!(
+        !file_path.exists(),
+        "avatar file should be deleted from disk"
+    );
 }
 
 #[tokio::test]
-async fn test_voice_join_text_channel_rejected() {
+async fn test_member_avatar_upload() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
-    let text_id = server.create_channel(&space_id, "general").await;
+    let space_id = server.create_space(&alice.user.id, "AvatarSpace").await;
 
+    // Upload member avatar via own member endpoint
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{text_id}/voice/join"),
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}/members/@me"),
         &alice.auth_header(),
-        &serde_json::json!({}),
+        &serde_json::json!({ "avatar": test_png_data_uri() }),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let avatar = body["data"]["avatar"].as_str().unwrap();
+    assert!(
+        avatar.starts_with("/cdn/avatars/"),
+        "member avatar should be a CDN path"
+    );
+    assert!(avatar.ends_with(".png"));
 }
 
 #[tokio::test]
-async fn test_voice_join_with_self_mute_and_deaf() {
+async fn test_member_banner_and_bio() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
-    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+    let space_id = server.create_space(&alice.user.id, "ProfileSpace").await;
+    let uri = format!("/api/v1/spaces/{space_id}/members/@me");
 
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        Method::PATCH,
+        &uri,
         &alice.auth_header(),
-        &serde_json::json!({ "self_mute": true, "self_deaf": true }),
+        &serde_json::json!({ "banner": test_png_data_uri(), "bio": "Work account" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    assert_eq!(body["data"]["voice_state"]["self_mute"], true);
-    assert_eq!(body["data"]["voice_state"]["self_deaf"], true);
+    let banner = body["data"]["banner"].as_str().unwrap();
+    assert!(banner.starts_with("/cdn/banners/") && banner.ends_with(".png"));
+    assert_eq!(body["data"]["bio"], "Work account");
+    assert!(body["data"]["avatar"].is_null());
+
+    // The account's own profile is untouched.
+    let req = authenticated_request(Method::GET, "/api/v1/users/@me", &alice.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert!(body["data"]["banner"].is_null());
+    assert!(body["data"]["bio"].is_null());
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "bio": "x".repeat(2001) }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Empty strings fall back to the account's.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &uri,
+        &alice.auth_header(),
+        &serde_json::json!({ "banner": "", "bio": "" }),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert!(body["data"]["banner"].is_null());
+    assert!(body["data"]["bio"].is_null());
 }
 
 #[tokio::test]
-async fn test_voice_leave_after_join() {
+async fn test_list_members_with_user_embeds_public_user() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
-    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "MembersSpace").await;
+    server.add_member(&space_id, &bob.user.id).await;
 
-    // Join first
-    let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{vc_id}/voice/join"),
+    // Default: no embedded user object (clients fetch users separately).
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/members"),
         &alice.auth_header(),
-        &serde_json::json!({}),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let members = body["data"].as_array().unwrap();
+    assert_eq!(members.len(), 2);
+    assert!(
+        members.iter().all(|m| m.get("user").is_none()),
+        "members must not embed a user object without with_user"
+    );
 
-    // Leave
+    // with_user=true: each member carries its public user object, resolved in
+    // a single batched query.
     let req = authenticated_request(
-        Method::DELETE,
-        &format!("/api/v1/channels/{vc_id}/voice/leave"),
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/members?with_user=true"),
         &alice.auth_header(),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    assert_eq!(body["data"]["ok"], true);
+    let members = body["data"].as_array().unwrap();
+    assert_eq!(members.len(), 2);
+
+    let usernames: Vec<String> = members
+        .iter()
+        .map(|m| {
+            let user = m.get("user").expect("member should embed a user object");
+            // Embedded user is the public shape — sensitive fields are stripped.
+            assert!(user.get("is_admin").is_none(), "must not leak is_admin");
+            assert!(
+                user.get("mfa_enabled").is_none(),
+                "must not leak mfa_enabled"
+            );
+            user["username"].as_str().unwrap().to_string()
+        })
+        .collect();
+    assert!(usernames.contains(&"alice".to_string()));
+    assert!(usernames.contains(&"bob".to_string()));
 }
 
 #[tokio::test]
-async fn test_voice_status_empty() {
+async fn test_space_icon_upload() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
-    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+    let space_id = server.create_space(&alice.user.id, "IconSpace").await;
 
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/channels/{vc_id}/voice-status"),
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}"),
         &alice.auth_header(),
+        &serde_json::json!({ "icon": test_png_data_uri() }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let states = body["data"].as_array().unwrap();
-    assert!(states.is_empty());
+    let icon = body["data"]["icon"].as_str().unwrap();
+    assert!(
+        icon.starts_with("/cdn/icons/"),
+        "space icon should be a CDN path, got: {icon}"
+    );
+    assert!(icon.ends_with(".png"));
+
+    // Verify the file exists on disk
+    let file_path = server
+        .state
+        .storage_path
+        .join(icon.strip_prefix("/cdn/").unwrap());
+    assert!(file_path.exists(), "icon file should exist on disk");
 }
 
 #[tokio::test]
-async fn test_voice_status_after_join() {
+async fn test_avatar_invalid_format() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
-    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
-    // Join voice
+    // Send an invalid data URI (text/plain instead of image)
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        Method::PATCH,
+        "/api/v1/users/@me",
         &alice.auth_header(),
-        &serde_json::json!({}),
+        &serde_json::json!({ "avatar": "data:text/plain;base64,SGVsbG8=" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
 
-    // Check status
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/channels/{vc_id}/voice-status"),
+#[tokio::test]
+async fn test_avatar_too_large() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+
+    // Create a data URI that exceeds 2 MB
+    let large_data = vec![0u8; 3 * 1024 * 1024]; // 3 MB
+    let b64 = simple_base64_encode(&large_data);
+    let data_uri = format!("data:image/png;base64,{b64}");
+
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/users/@me",
         &alice.auth_header(),
+        &serde_json::json!({ "avatar": data_uri }),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let states = body["data"].as_array().unwrap();
-    assert_eq!(states.len(), 1);
-    assert_eq!(states[0]["user_id"], alice.user.id);
-    assert_eq!(states[0]["channel_id"], vc_id);
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
 }
 
 #[tokio::test]
-async fn test_voice_status_cleared_after_leave() {
+async fn test_space_icon_remove() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
-    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+    let space_id = server.create_space(&alice.user.id, "IconSpace").await;
 
-    // Join
+    // Upload icon
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}"),
         &alice.auth_header(),
-        &serde_json::json!({}),
+        &serde_json::json!({ "icon": test_png_data_uri() }),
     );
-    server.router().oneshot(req).await.unwrap();
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let icon_path = body["data"]["icon"].as_str().unwrap().to_string();
 
-    // Leave
-    let req = authenticated_request(
-        Method::DELETE,
-        &format!("/api/v1/channels/{vc_id}/voice/leave"),
-        &alice.auth_header(),
-    );
-    server.router().oneshot(req).await.unwrap();
+    let file_path = server
+        .state
+        .storage_path
+        .join(icon_path.strip_prefix("/cdn/").unwrap());
+    assert!(file_path.exists(), "icon file should exist");
 
-    // Status should be empty again
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/channels/{vc_id}/voice-status"),
+    // Remove icon
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}"),
         &alice.auth_header(),
+        &serde_json::json!({ "icon": "" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let states = body["data"].as_array().unwrap();
-    assert!(states.is_empty());
+    assert!(
+        body["data"]["icon"].is_null(),
+        "icon should be null after removal"
+    );
+    assert!(!file_path.exists(), "icon file should be deleted");
 }
 
 #[tokio::test]
-async fn test_voice_regions_returns_data() {
+async fn test_cdn_serves_avatar_image() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
 
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/spaces/{space_id}/voice-regions"),
+    // Upload avatar
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/users/@me",
         &alice.auth_header(),
+        &serde_json::json!({ "avatar": test_png_data_uri() }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let regions = body["data"].as_array().unwrap();
-    assert!(!regions.is_empty());
-    assert_eq!(regions[0]["id"], "livekit");
+    let avatar_url = body["data"]["avatar"].as_str().unwrap().to_string();
+
+    // Fetch via CDN
+    let req = Request::builder()
+        .uri(&avatar_url)
+        .body(Body::empty())
+        .unwrap();
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(!bytes.is_empty(), "CDN should serve the avatar file");
 }
 
+// ---------------------------------------------------------------------------
+// Server settings tests
+// ---------------------------------------------------------------------------
+
 #[tokio::test]
-async fn test_voice_join_non_member_forbidden() {
+async fn test_get_settings() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
-    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
-    // Bob (non-member) tries to join voice → 403
+    let req = authenticated_request(Method::GET, "/api/v1/settings", &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["max_emoji_size"], 262144);
+    assert_eq!(body["data"]["max_avatar_size"], 2097152);
+    assert_eq!(body["data"]["max_sound_size"], 2097152);
+    assert_eq!(body["data"]["max_attachment_size"], 26214400);
+    assert_eq!(body["data"]["max_attachments_per_message"], 10);
+}
+
+#[tokio::test]
+async fn test_update_settings_admin() {
+    let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
+
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{vc_id}/voice/join"),
-        &bob.auth_header(),
-        &serde_json::json!({}),
+        Method::PATCH,
+        "/api/v1/admin/settings",
+        &admin.auth_header(),
+        &serde_json::json!({
+            "max_emoji_size": 512000,
+            "max_attachments_per_message": 5
+        }),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["max_emoji_size"], 512000);
+    assert_eq!(body["data"]["max_attachments_per_message"], 5);
+    // Unchanged fields keep defaults
+    assert_eq!(body["data"]["max_avatar_size"], 2097152);
 }
 
 #[tokio::test]
-async fn test_voice_leave_non_member_forbidden() {
+async fn test_update_settings_non_admin_forbidden() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
-    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
 
-    // Bob (non-member) tries to leave voice → 403
-    let req = authenticated_request(
-        Method::DELETE,
-        &format!("/api/v1/channels/{vc_id}/voice/leave"),
-        &bob.auth_header(),
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/admin/settings",
+        &alice.auth_header(),
+        &serde_json::json!({ "max_emoji_size": 512000 }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
 #[tokio::test]
-async fn test_voice_status_non_member_forbidden() {
+async fn test_upload_respects_custom_limit() {
     let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
-    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+    let admin = server.create_admin_with_token("admin").await;
+    let space_id = server.create_space(&admin.user.id, "test-space").await;
 
-    // Bob (non-member) tries to check voice status → 403
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/channels/{vc_id}/voice-status"),
-        &bob.auth_header(),
+    // Lower the emoji limit to 10 bytes
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/admin/settings",
+        &admin.auth_header(),
+        &serde_json::json!({ "max_emoji_size": 10 }),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Attempt to upload an emoji (the PNG is larger than 10 bytes)
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/emojis"),
+        &admin.auth_header(),
+        &serde_json::json!({
+            "name": "test_emoji",
+            "image": test_png_data_uri()
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
 }
 
-#[tokio::test]
-async fn test_voice_regions_non_member_forbidden() {
-    let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+// ---------------------------------------------------------------------------
+// Attachment upload regression test (GitHub issue #9)
+//
+// Verifies that the URL returned by POST /channels/{id}/messages/upload
+// resolves to a file that is actually served by /cdn. Previously the URL in
+// the response could disagree with the on-disk path (different filename
+// sanitization, or — under client-side message ID confusion — a different
+// directory name entirely) causing the client to get a 404 when fetching
+// an image it had just successfully uploaded.
+// ---------------------------------------------------------------------------
 
-    // Bob (non-member) tries to list voice regions → 403
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/spaces/{space_id}/voice-regions"),
-        &bob.auth_header(),
+fn build_multipart_upload_body(
+    boundary: &str,
+    payload_json: &serde_json::Value,
+    filename: &str,
+    content_type: &str,
+    file_bytes: &[u8],
+) -> Vec<u8> {
+    let mut body: Vec<u8> = Vec::new();
+    let payload_str = serde_json::to_string(payload_json).unwrap();
+
+    body.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
+    body.extend_from_slice(
+        b"Content-Disposition: form-data; name=\"payload_json\"\r\n\
+          Content-Type: application/json\r\n\r\n",
     );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    body.extend_from_slice(payload_str.as_bytes());
+    body.extend_from_slice(b"\r\n");
+
+    body.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
+    body.extend_from_slice(
+        format!(
+            "Content-Disposition: form-data; name=\"files[0]\"; filename=\"{filename}\"\r\n\
+             Content-Type: {content_type}\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(file_bytes);
+    body.extend_from_slice(b"\r\n");
+
+    body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+    body
+}
+
+fn tiny_png_bytes() -> Vec<u8> {
+    vec![
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x00, 0x00, 0x00, 0x90,
+        0x77, 0x53, 0xDE, 0x00, 0x00, 0x00, 0x0C, 0x49, 0x44, 0x41, 0x54, 0x08, 0xD7, 0x63, 0xF8,
+        0xCF, 0xC0, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0xE2, 0x21, 0xBC, 0x33, 0x00, 0x00, 0x00,
+        0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82,
+    ]
 }
 
 #[tokio::test]
-async fn test_voice_multiple_users_in_channel() {
+async fn test_attachment_upload_url_resolves_via_cdn() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
-    server.add_member(&space_id, &bob.user.id).await;
-    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+    let space_id = server.create_space(&alice.user.id, "AttachSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
 
-    // Both join
-    let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{vc_id}/voice/join"),
-        &alice.auth_header(),
-        &serde_json::json!({}),
+    let png_bytes = tiny_png_bytes();
+
+    let boundary = "----accordtestboundary";
+    let body = build_multipart_upload_body(
+        boundary,
+        &serde_json::json!({ "content": "look at this picture" }),
+        "image.png",
+        "image/png",
+        &png_bytes,
     );
-    server.router().oneshot(req).await.unwrap();
 
-    let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{vc_id}/voice/join"),
-        &bob.auth_header(),
-        &serde_json::json!({}),
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("/api/v1/channels/{channel_id}/messages/upload"))
+        .header("Authorization", alice.auth_header())
+        .header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .body(Body::from(body))
+        .unwrap();
+
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(
+        response.status(),
+        StatusCode::OK,
+        "multipart upload should succeed"
     );
-    server.router().oneshot(req).await.unwrap();
+    let upload_body = parse_body(response).await;
 
-    // Status should show both users
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/channels/{vc_id}/voice-status"),
-        &alice.auth_header(),
+    let attachments = upload_body["data"]["attachments"]
+        .as_array()
+        .expect("response should include attachments array");
+    assert_eq!(attachments.len(), 1);
+    let url = attachments[0]["url"]
+        .as_str()
+        .expect("attachment should have a url")
+        .to_string();
+    assert!(
+        url.starts_with("/cdn/attachments/"),
+        "unexpected url shape: {url}"
     );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let states = body["data"].as_array().unwrap();
-    assert_eq!(states.len(), 2);
+
+    // The URL the client just received must resolve via /cdn. This is the
+    // exact failure mode reported in issue #9: upload returns 200 but the
+    // subsequent GET returns 404 because the URL points to a different
+    // path than where the file was actually written.
+    let cdn_req = Request::builder()
+        .method(Method::GET)
+        .uri(&url)
+        .body(Body::empty())
+        .unwrap();
+    let cdn_response = server.router().oneshot(cdn_req).await.unwrap();
+    assert_eq!(
+        cdn_response.status(),
+        StatusCode::OK,
+        "uploaded attachment URL {url} did not resolve"
+    );
+    let served = axum::body::to_bytes(cdn_response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(&served[..], &png_bytes[..]);
 }
 
 #[tokio::test]
-async fn test_voice_join_unauthenticated() {
+async fn test_attachment_upload_url_resolves_with_special_filename() {
+    // A filename with characters that get rewritten by the server's
+    // sanitizer (spaces, parentheses) must still produce a URL the client
+    // can resolve. Previously the response URL used the raw filename while
+    // the file on disk used the sanitized filename, producing a 404.
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
-    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+    let space_id = server.create_space(&alice.user.id, "AttachSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let png_bytes = tiny_png_bytes();
+
+    let boundary = "----accordtestboundary2";
+    let body = build_multipart_upload_body(
+        boundary,
+        &serde_json::json!({ "content": "with spaces" }),
+        "my photo (1).png",
+        "image/png",
+        &png_bytes,
+    );
 
-    // No auth header
     let req = Request::builder()
         .method(Method::POST)
-        .uri(format!("/api/v1/channels/{vc_id}/voice/join"))
-        .header("Content-Type", "application/json")
-        .body(Body::from(
-            serde_json::to_vec(&serde_json::json!({})).unwrap(),
-        ))
+        .uri(format!("/api/v1/channels/{channel_id}/messages/upload"))
+        .header("Authorization", alice.auth_header())
+        .header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .body(Body::from(body))
         .unwrap();
+
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
-}
+    assert_eq!(response.status(), StatusCode::OK);
+    let upload_body = parse_body(response).await;
+    let url = upload_body["data"]["attachments"][0]["url"]
+        .as_str()
+        .expect("attachment should have a url")
+        .to_string();
 
-// ---------------------------------------------------------------------------
-// Avatar Upload Tests
-// ---------------------------------------------------------------------------
+    let cdn_req = Request::builder()
+        .method(Method::GET)
+        .uri(&url)
+        .body(Body::empty())
+        .unwrap();
+    let cdn_response = server.router().oneshot(cdn_req).await.unwrap();
+    assert_eq!(
+        cdn_response.status(),
+        StatusCode::OK,
+        "URL {url} returned by upload did not resolve via /cdn"
+    );
+}
 
 #[tokio::test]
-async fn test_user_avatar_upload() {
+async fn test_attachment_description_set_edited_and_searchable() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "AttachSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
 
-    let req = authenticated_json_request(
-        Method::PATCH,
-        "/api/v1/users/@me",
-        &alice.auth_header(),
-        &serde_json::json!({ "avatar": test_png_data_uri() }),
+    let boundary = "----accordtestboundary3";
+    let body = build_multipart_upload_body(
+        boundary,
+        &serde_json::json!({
+            "content": "",
+            "attachments": [{ "id": "0", "description": "a tabby cat asleep" }]
+        }),
+        "cat.png",
+        "image/png",
+        &tiny_png_bytes(),
     );
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("/api/v1/channels/{channel_id}/messages/upload"))
+        .header("Authorization", alice.auth_header())
+        .header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .body(Body::from(body))
+        .unwrap();
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let avatar = body["data"]["avatar"].as_str().unwrap();
-    assert!(
-        avatar.starts_with("/cdn/avatars/"),
-        "avatar should be a CDN path, got: {avatar}"
-    );
-    assert!(avatar.ends_with(".png"), "avatar should end with .png");
-
-    // Verify the file exists on disk
-    let file_path = server
-        .state
-        .storage_path
-        .join(avatar.strip_prefix("/cdn/").unwrap());
-    assert!(file_path.exists(), "avatar file should exist on disk");
-}
-
-#[tokio::test]
-async fn test_user_avatar_replace() {
-    let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
+    let msg = parse_body(response).await["data"].clone();
+    let message_id = msg["id"].as_str().unwrap().to_string();
+    let attachment_id = msg["attachments"][0]["id"].as_str().unwrap().to_string();
+    assert_eq!(msg["attachments"][0]["description"], "a tabby cat asleep");
 
-    // Upload first avatar
-    let req = authenticated_json_request(
-        Method::PATCH,
-        "/api/v1/users/@me",
+    // Search matches the alt text of an otherwise empty message.
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/messages/search?query=tabby"),
         &alice.auth_header(),
-        &serde_json::json!({ "avatar": test_png_data_uri() }),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let first_avatar = body["data"]["avatar"].as_str().unwrap().to_string();
+    let results = parse_body(response).await;
+    assert_eq!(results["data"].as_array().unwrap().len(), 1);
+    assert_eq!(results["data"][0]["id"], message_id.as_str());
 
-    let first_path = server
-        .state
-        .storage_path
-        .join(first_avatar.strip_prefix("/cdn/").unwrap());
-    assert!(first_path.exists(), "first avatar should exist");
-
-    // Upload replacement avatar
     let req = authenticated_json_request(
         Method::PATCH,
-        "/api/v1/users/@me",
+        &format!("/api/v1/channels/{channel_id}/messages/{message_id}"),
         &alice.auth_header(),
-        &serde_json::json!({ "avatar": test_png_data_uri() }),
+        &serde_json::json!({
+            "attachments": [{ "id": attachment_id, "description": "a ginger cat" }]
+        }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let second_avatar = body["data"]["avatar"].as_str().unwrap().to_string();
-    assert!(second_avatar.starts_with("/cdn/avatars/"));
+    assert_eq!(
+        body["data"]["attachments"][0]["description"],
+        "a ginger cat"
+    );
 
-    // New file should exist
-    let second_path = server
-        .state
-        .storage_path
-        .join(second_avatar.strip_prefix("/cdn/").unwrap());
-    assert!(second_path.exists(), "replacement avatar should exist");
+    // Unknown attachments and overlong descriptions are refused.
+    for attachments in [
+        serde_json::json!([{ "id": "nope", "description": "x" }]),
+        serde_json::json!([{ "id": attachment_id, "description": "x".repeat(1025) }]),
+    ] {
+        let req = authenticated_json_request(
+            Method::PATCH,
+            &format!("/api/v1/channels/{channel_id}/messages/{message_id}"),
+            &alice.auth_header(),
+            &serde_json::json!({ "attachments": attachments }),
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
 }
 
+// ---------------------------------------------------------------------------
+// Member timeout (#33)
+// ---------------------------------------------------------------------------
+
+const FUTURE_TS: &str = "2999-01-01T00:00:00Z";
+const PAST_TS: &str = "2000-01-01T00:00:00Z";
+
 #[tokio::test]
-async fn test_user_avatar_remove() {
+async fn test_member_timeout_persists_and_blocks_then_clears() {
     let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
+    let alice = server.create_user_with_token("alice").await; // owner
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Space").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &bob.user.id).await;
 
-    // Upload avatar
+    // Owner sets a future timeout on bob.
     let req = authenticated_json_request(
         Method::PATCH,
-        "/api/v1/users/@me",
+        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
         &alice.auth_header(),
-        &serde_json::json!({ "avatar": test_png_data_uri() }),
+        &serde_json::json!({ "communication_disabled_until": FUTURE_TS }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let avatar_path = body["data"]["avatar"].as_str().unwrap().to_string();
-
-    let file_path = server
-        .state
-        .storage_path
-        .join(avatar_path.strip_prefix("/cdn/").unwrap());
     assert!(
-        file_path.exists(),
-        "avatar file should exist before removal"
+        body["data"]["timed_out_until"].as_str().is_some(),
+        "timeout should be persisted and returned"
     );
 
-    // Remove avatar by sending empty string
+    // Bob is blocked from sending messages while timed out.
     let req = authenticated_json_request(
-        Method::PATCH,
-        "/api/v1/users/@me",
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &bob.auth_header(),
+        &serde_json::json!({ "content": "hello" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Bob is also blocked from reactions.
+    // (Send a message as the owner first so there's something to react to.)
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
         &alice.auth_header(),
-        &serde_json::json!({ "avatar": "" }),
+        &serde_json::json!({ "content": "owner msg" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    assert!(
-        body["data"]["avatar"].is_null(),
-        "avatar should be null after removal"
-    );
-
-    // Verify the file is deleted
-    assert!(
-        !file_path.exists(),
-        "avatar file should be deleted from disk"
+    let msg_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let req = authenticated_request(
+        Method::PUT,
+        &format!("/api/v1/channels/{channel_id}/messages/{msg_id}/reactions/%F0%9F%91%8D/@me"),
+        &bob.auth_header(),
     );
-}
-
-#[tokio::test]
-async fn test_member_avatar_upload() {
-    let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "AvatarSpace").await;
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
-    // Upload member avatar via own member endpoint
+    // Clearing the timeout with an explicit null restores access.
     let req = authenticated_json_request(
         Method::PATCH,
-        &format!("/api/v1/spaces/{space_id}/members/@me"),
+        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
         &alice.auth_header(),
-        &serde_json::json!({ "avatar": test_png_data_uri() }),
+        &serde_json::json!({ "communication_disabled_until": serde_json::Value::Null }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    let avatar = body["data"]["avatar"].as_str().unwrap();
-    assert!(
-        avatar.starts_with("/cdn/avatars/"),
-        "member avatar should be a CDN path"
+    assert!(body["data"]["timed_out_until"].is_null());
+
+    // Bob can now send again.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &bob.auth_header(),
+        &serde_json::json!({ "content": "back" }),
     );
-    assert!(avatar.ends_with(".png"));
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
 }
 
 #[tokio::test]
-async fn test_list_members_with_user_embeds_public_user() {
+async fn test_member_timeout_requires_moderate_permission() {
     let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
+    let alice = server.create_user_with_token("alice").await; // owner
     let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "MembersSpace").await;
+    let carol = server.create_user_with_token("carol").await;
+    let space_id = server.create_space(&alice.user.id, "Space").await;
     server.add_member(&space_id, &bob.user.id).await;
+    server.add_member(&space_id, &carol.user.id).await;
 
-    // Default: no embedded user object (clients fetch users separately).
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/spaces/{space_id}/members"),
-        &alice.auth_header(),
-    );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let members = body["data"].as_array().unwrap();
-    assert_eq!(members.len(), 2);
-    assert!(
-        members.iter().all(|m| m.get("user").is_none()),
-        "members must not embed a user object without with_user"
-    );
-
-    // with_user=true: each member carries its public user object, resolved in
-    // a single batched query.
-    let req = authenticated_request(
-        Method::GET,
-        &format!("/api/v1/spaces/{space_id}/members?with_user=true"),
-        &alice.auth_header(),
+    // Bob (a plain member without moderate_members) cannot time out carol.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}/members/{}", carol.user.id),
+        &bob.auth_header(),
+        &serde_json::json!({ "communication_disabled_until": FUTURE_TS }),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let members = body["data"].as_array().unwrap();
-    assert_eq!(members.len(), 2);
-
-    let usernames: Vec<String> = members
-        .iter()
-        .map(|m| {
-            let user = m.get("user").expect("member should embed a user object");
-            // Embedded user is the public shape — sensitive fields are stripped.
-            assert!(user.get("is_admin").is_none(), "must not leak is_admin");
-            assert!(
-                user.get("mfa_enabled").is_none(),
-                "must not leak mfa_enabled"
-            );
-            user["username"].as_str().unwrap().to_string()
-        })
-        .collect();
-    assert!(usernames.contains(&"alice".to_string()));
-    assert!(usernames.contains(&"bob".to_string()));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
 #[tokio::test]
-async fn test_space_icon_upload() {
+async fn test_member_timeout_expired_does_not_block() {
     let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "IconSpace").await;
+    let alice = server.create_user_with_token("alice").await; // owner
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Space").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &bob.user.id).await;
 
+    // A timeout in the past is already expired and must not block.
     let req = authenticated_json_request(
         Method::PATCH,
-        &format!("/api/v1/spaces/{space_id}"),
+        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
         &alice.auth_header(),
-        &serde_json::json!({ "icon": test_png_data_uri() }),
+        &serde_json::json!({ "communication_disabled_until": PAST_TS }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let icon = body["data"]["icon"].as_str().unwrap();
-    assert!(
-        icon.starts_with("/cdn/icons/"),
-        "space icon should be a CDN path, got: {icon}"
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &bob.auth_header(),
+        &serde_json::json!({ "content": "still works" }),
     );
-    assert!(icon.ends_with(".png"));
-
-    // Verify the file exists on disk
-    let file_path = server
-        .state
-        .storage_path
-        .join(icon.strip_prefix("/cdn/").unwrap());
-    assert!(file_path.exists(), "icon file should exist on disk");
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
 }
 
 #[tokio::test]
-async fn test_avatar_invalid_format() {
+async fn test_member_timeout_rejects_invalid_timestamp() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Space").await;
+    server.add_member(&space_id, &bob.user.id).await;
 
-    // Send an invalid data URI (text/plain instead of image)
     let req = authenticated_json_request(
         Method::PATCH,
-        "/api/v1/users/@me",
+        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
         &alice.auth_header(),
-        &serde_json::json!({ "avatar": "data:text/plain;base64,SGVsbG8=" }),
+        &serde_json::json!({ "communication_disabled_until": "not-a-date" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 }
 
 #[tokio::test]
-async fn test_avatar_too_large() {
+async fn test_member_timeout_blocks_voice_join() {
     let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
-
-    // Create a data URI that exceeds 2 MB
-    let large_data = vec![0u8; 3 * 1024 * 1024]; // 3 MB
-    let b64 = simple_base64_encode(&large_data);
-    let data_uri = format!("data:image/png;base64,{b64}");
+    let alice = server.create_user_with_token("alice").await; // owner
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Space").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice").await;
+    server.add_member(&space_id, &bob.user.id).await;
 
     let req = authenticated_json_request(
         Method::PATCH,
-        "/api/v1/users/@me",
+        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
         &alice.auth_header(),
-        &serde_json::json!({ "avatar": data_uri }),
+        &serde_json::json!({ "communication_disabled_until": FUTURE_TS }),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        &bob.auth_header(),
+        &serde_json::json!({}),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
+// ---------------------------------------------------------------------------
+// Group DM management
+// ---------------------------------------------------------------------------
+
 #[tokio::test]
-async fn test_space_icon_remove() {
+async fn test_group_dm_owner_manages_recipients_icon_and_ownership() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "IconSpace").await;
+    let bob = server.create_user_with_token("bob").await;
+    let carol = server.create_user_with_token("carol").await;
+    let dave = server.create_user_with_token("dave").await;
+    let space_id = server.create_space(&alice.user.id, "Friends").await;
+    for user in [&bob, &carol, &dave] {
+        server.add_member(&space_id, &user.user.id).await;
+    }
 
-    // Upload icon
     let req = authenticated_json_request(
-        Method::PATCH,
-        &format!("/api/v1/spaces/{space_id}"),
+        Method::POST,
+        "/api/v1/users/@me/channels",
         &alice.auth_header(),
-        &serde_json::json!({ "icon": test_png_data_uri() }),
+        &serde_json::json!({ "recipients": [bob.user.id, carol.user.id] }),
     );
+    let group = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let group_id = group["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(group["data"]["type"], "group_dm");
+    assert_eq!(group["data"]["owner_id"], alice.user.id);
+
+    // Only the owner adds people.
+    let add_dave = format!("/api/v1/channels/{group_id}/recipients/{}", dave.user.id);
+    let req = authenticated_request(Method::PUT, &add_dave, &bob.auth_header());
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let icon_path = body["data"]["icon"].as_str().unwrap().to_string();
-
-    let file_path = server
-        .state
-        .storage_path
-        .join(icon_path.strip_prefix("/cdn/").unwrap());
-    assert!(file_path.exists(), "icon file should exist");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let req = authenticated_request(Method::PUT, &add_dave, &alice.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["recipients"].as_array().unwrap().len(), 4);
 
-    // Remove icon
+    // Rename, set an icon, and hand the group to bob in one edit.
     let req = authenticated_json_request(
         Method::PATCH,
-        &format!("/api/v1/spaces/{space_id}"),
+        &format!("/api/v1/channels/{group_id}"),
         &alice.auth_header(),
-        &serde_json::json!({ "icon": "" }),
+        &serde_json::json!({
+            "name": "Weekend plans",
+            "icon": test_png_data_uri(),
+            "owner_id": bob.user.id,
+        }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    assert!(
-        body["data"]["icon"].is_null(),
-        "icon should be null after removal"
-    );
-    assert!(!file_path.exists(), "icon file should be deleted");
-}
-
-#[tokio::test]
-async fn test_cdn_serves_avatar_image() {
-    let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
+    assert_eq!(body["data"]["name"], "Weekend plans");
+    assert_eq!(body["data"]["owner_id"], bob.user.id);
+    assert!(body["data"]["icon"]
+        .as_str()
+        .unwrap()
+        .starts_with("/cdn/icons/"));
 
-    // Upload avatar
+    // Ownership can only go to someone in the group.
+    let outsider = server.create_user_with_token("erin").await;
     let req = authenticated_json_request(
         Method::PATCH,
-        "/api/v1/users/@me",
-        &alice.auth_header(),
-        &serde_json::json!({ "avatar": test_png_data_uri() }),
+        &format!("/api/v1/channels/{group_id}"),
+        &bob.auth_header(),
+        &serde_json::json!({ "owner_id": outsider.user.id }),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let avatar_url = body["data"]["avatar"].as_str().unwrap().to_string();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
-    // Fetch via CDN
-    let req = Request::builder()
-        .uri(&avatar_url)
-        .body(Body::empty())
-        .unwrap();
+    // The new owner removes alice, who loses access.
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/channels/{group_id}/recipients/{}", alice.user.id),
+        &bob.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["recipients"].as_array().unwrap().len(), 3);
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{group_id}"),
+        &alice.auth_header(),
+    );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
-        .await
-        .unwrap();
-    assert!(!bytes.is_empty(), "CDN should serve the avatar file");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
-// ---------------------------------------------------------------------------
-// Server settings tests
-// ---------------------------------------------------------------------------
-
 #[tokio::test]
-async fn test_get_settings() {
+async fn test_open_dm_twice_returns_the_same_channel() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Friends").await;
+    server.add_member(&space_id, &bob.user.id).await;
 
-    let req = authenticated_request(Method::GET, "/api/v1/settings", &alice.auth_header());
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    assert_eq!(body["data"]["max_emoji_size"], 262144);
-    assert_eq!(body["data"]["max_avatar_size"], 2097152);
-    assert_eq!(body["data"]["max_sound_size"], 2097152);
-    assert_eq!(body["data"]["max_attachment_size"], 26214400);
-    assert_eq!(body["data"]["max_attachments_per_message"], 10);
+    let mut ids = Vec::new();
+    for _ in 0..2 {
+        let req = authenticated_json_request(
+            Method::POST,
+            "/api/v1/users/@me/channels",
+            &alice.auth_header(),
+            &serde_json::json!({ "recipient_id": bob.user.id }),
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = parse_body(response).await;
+        assert_eq!(body["data"]["nsfw"], false);
+        ids.push(body["data"]["id"].as_str().unwrap().to_string());
+    }
+    assert_eq!(ids[0], ids[1]);
 }
 
+// ---------------------------------------------------------------------------
+// DM voice calls + signaling (#32)
+// ---------------------------------------------------------------------------
+
 #[tokio::test]
-async fn test_update_settings_admin() {
+async fn test_voice_join_dm_channel_success() {
     let server = TestServer::new().await;
-    let admin = server.create_admin_with_token("admin").await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let dm_id = server.create_dm(&alice.user.id, &bob.user.id).await;
 
     let req = authenticated_json_request(
-        Method::PATCH,
-        "/api/v1/admin/settings",
-        &admin.auth_header(),
-        &serde_json::json!({
-            "max_emoji_size": 512000,
-            "max_attachments_per_message": 5
-        }),
+        Method::POST,
+        &format!("/api/v1/channels/{dm_id}/voice/join"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    assert_eq!(body["data"]["max_emoji_size"], 512000);
-    assert_eq!(body["data"]["max_attachments_per_message"], 5);
-    // Unchanged fields keep defaults
-    assert_eq!(body["data"]["max_avatar_size"], 2097152);
+    let data = &body["data"];
+    assert_eq!(data["backend"], "livekit");
+    assert_eq!(data["voice_state"]["channel_id"], dm_id);
+    assert!(data["voice_state"]["space_id"].is_null());
+    assert!(data["token"].as_str().is_some());
 }
 
 #[tokio::test]
-async fn test_update_settings_non_admin_forbidden() {
+async fn test_voice_join_dm_non_participant_rejected() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let carol = server.create_user_with_token("carol").await;
+    let dm_id = server.create_dm(&alice.user.id, &bob.user.id).await;
 
     let req = authenticated_json_request(
-        Method::PATCH,
-        "/api/v1/admin/settings",
-        &alice.auth_header(),
-        &serde_json::json!({ "max_emoji_size": 512000 }),
+        Method::POST,
+        &format!("/api/v1/channels/{dm_id}/voice/join"),
+        &carol.auth_header(),
+        &serde_json::json!({}),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
 #[tokio::test]
-async fn test_upload_respects_custom_limit() {
+async fn test_call_ring_dm_success() {
     let server = TestServer::new().await;
-    let admin = server.create_admin_with_token("admin").await;
-    let space_id = server.create_space(&admin.user.id, "test-space").await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let dm_id = server.create_dm(&alice.user.id, &bob.user.id).await;
 
-    // Lower the emoji limit to 10 bytes
     let req = authenticated_json_request(
-        Method::PATCH,
-        "/api/v1/admin/settings",
-        &admin.auth_header(),
-        &serde_json::json!({ "max_emoji_size": 10 }),
+        Method::POST,
+        &format!("/api/v1/channels/{dm_id}/call/ring"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_call_ring_non_dm_rejected() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Space").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
 
-    // Attempt to upload an emoji (the PNG is larger than 10 bytes)
     let req = authenticated_json_request(
         Method::POST,
-        &format!("/api/v1/spaces/{space_id}/emojis"),
-        &admin.auth_header(),
-        &serde_json::json!({
-            "name": "test_emoji",
-            "image": test_png_data_uri()
-        }),
+        &format!("/api/v1/channels/{channel_id}/call/ring"),
+        &alice.auth_header(),
+        &serde_json::json!({}),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 }
 
-// ---------------------------------------------------------------------------
-// Attachment upload regression test (GitHub issue #9)
-//
-// Verifies that the URL returned by POST /channels/{id}/messages/upload
-// resolves to a file that is actually served by /cdn. Previously the URL in
-// the response could disagree with the on-disk path (different filename
-// sanitization, or — under client-side message ID confusion — a different
-// directory name entirely) causing the client to get a 404 when fetching
-// an image it had just successfully uploaded.
-// ---------------------------------------------------------------------------
-
-fn build_multipart_upload_body(
-    boundary: &str,
-    payload_json: &serde_json::Value,
-    filename: &str,
-    content_type: &str,
-    file_bytes: &[u8],
-) -> Vec<u8> {
-    let mut body: Vec<u8> = Vec::new();
-    let payload_str = serde_json::to_string(payload_json).unwrap();
-
-    body.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
-    body.extend_from_slice(
-        b"Content-Disposition: form-data; name=\"payload_json\"\r\n\
-          Content-Type: application/json\r\n\r\n",
-    );
-    body.extend_from_slice(payload_str.as_bytes());
-    body.extend_from_slice(b"\r\n");
+#[tokio::test]
+async fn test_call_decline_and_cancel_dm() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let dm_id = server.create_dm(&alice.user.id, &bob.user.id).await;
 
-    body.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
-    body.extend_from_slice(
-        format!(
-            "Content-Disposition: form-data; name=\"files[0]\"; filename=\"{filename}\"\r\n\
-             Content-Type: {content_type}\r\n\r\n"
-        )
-        .as_bytes(),
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/channels/{dm_id}/call/decline"),
+        &bob.auth_header(),
     );
-    body.extend_from_slice(file_bytes);
-    body.extend_from_slice(b"\r\n");
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
 
-    body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
-    body
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/channels/{dm_id}/call/cancel"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
 }
 
-fn tiny_png_bytes() -> Vec<u8> {
-    vec![
-        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44,
-        0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x00, 0x00, 0x00, 0x90,
-        0x77, 0x53, 0xDE, 0x00, 0x00, 0x00, 0x0C, 0x49, 0x44, 0x41, 0x54, 0x08, 0xD7, 0x63, 0xF8,
-        0xCF, 0xC0, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0xE2, 0x21, 0xBC, 0x33, 0x00, 0x00, 0x00,
-        0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82,
-    ]
-}
+// =========================================================================
+// Instance blocklists
+// =========================================================================
 
 #[tokio::test]
-async fn test_attachment_upload_url_resolves_via_cdn() {
+async fn test_instance_blocklists_enforced() {
     let server = TestServer::new().await;
+    let admin = server.create_admin_with_token("admin").await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "AttachSpace").await;
-    let channel_id = server.create_channel(&space_id, "general").await;
-
-    let png_bytes = tiny_png_bytes();
+    let space_id = server.create_space(&admin.user.id, "Home").await;
 
-    let boundary = "----accordtestboundary";
-    let body = build_multipart_upload_body(
-        boundary,
-        &serde_json::json!({ "content": "look at this picture" }),
-        "image.png",
-        "image/png",
-        &png_bytes,
+    // Non-admins can't read or edit the lists.
+    let req = authenticated_request(
+        Method::GET,
+        "/api/v1/admin/blocklists/email_domain",
+        &alice.auth_header(),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::FORBIDDEN
     );
 
+    // Email domains: subdomains are covered too.
+    let req = authenticated_json_request(
+        Method::PUT,
+        "/api/v1/admin/blocklists/email_domain/@Spam.Example",
+        &admin.auth_header(),
+        &serde_json::json!({ "reason": "throwaway provider" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(parse_body(response).await["data"]["value"], "spam.example");
     let req = Request::builder()
         .method(Method::POST)
-        .uri(format!("/api/v1/channels/{channel_id}/messages/upload"))
-        .header("Authorization", alice.auth_header())
-        .header(
-            "Content-Type",
-            format!("multipart/form-data; boundary={boundary}"),
-        )
-        .body(Body::from(body))
+        .uri("/api/v1/auth/register")
+        .header("Content-Type", "application/json")
+        .body(Body::from(
+            serde_json::to_vec(&serde_json::json!({
+                "username": "spammer",
+                "password": "securepassword123",
+                "email": "x@mail.spam.example"
+            }))
+            .unwrap(),
+        ))
         .unwrap();
-
     let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
     assert_eq!(
-        response.status(),
-        StatusCode::OK,
-        "multipart upload should succeed"
+        parse_body(response).await["error"]["code"],
+        "email_domain_blocked"
     );
-    let upload_body = parse_body(response).await;
 
-    let attachments = upload_body["data"]["attachments"]
-        .as_array()
-        .expect("response should include attachments array");
-    assert_eq!(attachments.len(), 1);
-    let url = attachments[0]["url"]
+    // File hashes.
+    let data_uri = test_png_data_uri();
+    let png = accordserver::storage::base64_decode(data_uri.split_once(",").unwrap().1).unwrap();
+    let hash = accordserver::blocklist::hash_bytes(&png);
+    let req = authenticated_request(
+        Method::PUT,
+        &format!("/api/v1/admin/blocklists/file_hash/{hash}"),
+        &admin.auth_header(),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/users/@me",
+        &alice.auth_header(),
+        &serde_json::json!({ "avatar": data_uri }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(parse_body(response).await["error"]["code"], "file_blocked");
+
+    // Globally banned users can't accept invites.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/spaces/{space_id}/invites"),
+        &admin.auth_header(),
+        &serde_json::json!({}),
+    );
+    let code = parse_body(server.router().oneshot(req).await.unwrap()).await["data"]["code"]
         .as_str()
-        .expect("attachment should have a url")
+        .unwrap()
         .to_string();
-    assert!(
-        url.starts_with("/cdn/attachments/"),
-        "unexpected url shape: {url}"
+    let req = authenticated_request(
+        Method::PUT,
+        &format!("/api/v1/admin/blocklists/user/{}", alice.user.id),
+        &admin.auth_header(),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/invites/{code}/accept"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "user_globally_banned"
     );
 
-    // The URL the client just received must resolve via /cdn. This is the
-    // exact failure mode reported in issue #9: upload returns 200 but the
-    // subsequent GET returns 404 because the URL points to a different
-    // path than where the file was actually written.
-    let cdn_req = Request::builder()
-        .method(Method::GET)
-        .uri(&url)
-        .body(Body::empty())
-        .unwrap();
-    let cdn_response = server.router().oneshot(cdn_req).await.unwrap();
+    // Lifting the ban lets them in.
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/admin/blocklists/user/{}", alice.user.id),
+        &admin.auth_header(),
+    );
     assert_eq!(
-        cdn_response.status(),
-        StatusCode::OK,
-        "uploaded attachment URL {url} did not resolve"
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/invites/{code}/accept"),
+        &alice.auth_header(),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
     );
-    let served = axum::body::to_bytes(cdn_response.into_body(), usize::MAX)
-        .await
-        .unwrap();
-    assert_eq!(&served[..], &png_bytes[..]);
 }
 
 #[tokio::test]
-async fn test_attachment_upload_url_resolves_with_special_filename() {
-    // A filename with characters that get rewritten by the server's
-    // sanitizer (spaces, parentheses) must still produce a URL the client
-    // can resolve. Previously the response URL used the raw filename while
-    // the file on disk used the sanitized filename, producing a 404.
+async fn test_user_settings_sync() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "AttachSpace").await;
-    let channel_id = server.create_channel(&space_id, "general").await;
-
-    let png_bytes = tiny_png_bytes();
 
-    let boundary = "----accordtestboundary2";
-    let body = build_multipart_upload_body(
-        boundary,
-        &serde_json::json!({ "content": "with spaces" }),
-        "my photo (1).png",
-        "image/png",
-        &png_bytes,
+    let req = authenticated_request(
+        Method::GET,
+        "/api/v1/users/@me/settings",
+        &alice.auth_header(),
     );
-
-    let req = Request::builder()
-        .method(Method::POST)
-        .uri(format!("/api/v1/channels/{channel_id}/messages/upload"))
-        .header("Authorization", alice.auth_header())
-        .header(
-            "Content-Type",
-            format!("multipart/form-data; boundary={boundary}"),
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["version"], 0);
+    assert_eq!(body["data"]["settings"], serde_json::json!({}));
+
+    let patch = |body: serde_json::Value| {
+        authenticated_json_request(
+            Method::PATCH,
+            "/api/v1/users/@me/settings",
+            &alice.auth_header(),
+            &body,
         )
-        .body(Body::from(body))
+    };
+    let response = server
+        .router()
+        .oneshot(patch(serde_json::json!({
+            "settings": { "theme": "dark", "locale": "en-GB" },
+            "version": 0
+        })))
+        .await
         .unwrap();
-
-    let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
-    let upload_body = parse_body(response).await;
-    let url = upload_body["data"]["attachments"][0]["url"]
-        .as_str()
-        .expect("attachment should have a url")
-        .to_string();
+    assert_eq!(parse_body(response).await["data"]["version"], 1);
+
+    // Another device still at version 0 loses.
+    let response = server
+        .router()
+        .oneshot(patch(serde_json::json!({
+            "settings": { "theme": "light" },
+            "version": 0
+        })))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "settings_version_conflict"
+    );
 
-    let cdn_req = Request::builder()
-        .method(Method::GET)
-        .uri(&url)
-        .body(Body::empty())
+    // Unversioned writes merge; null removes a key.
+    let response = server
+        .router()
+        .oneshot(patch(serde_json::json!({
+            "settings": { "locale": null, "collapsed_categories": ["1"] }
+        })))
+        .await
         .unwrap();
-    let cdn_response = server.router().oneshot(cdn_req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(body["data"]["version"], 2);
     assert_eq!(
-        cdn_response.status(),
-        StatusCode::OK,
-        "URL {url} returned by upload did not resolve via /cdn"
+        body["data"]["settings"],
+        serde_json::json!({ "theme": "dark", "collapsed_categories": ["1"] })
     );
 }
 
-// ---------------------------------------------------------------------------
-// Member timeout (#33)
-// ---------------------------------------------------------------------------
-
-const FUTURE_TS: &str = "2999-01-01T00:00:00Z";
-const PAST_TS: &str = "2000-01-01T00:00:00Z";
-
 #[tokio::test]
-async fn test_member_timeout_persists_and_blocks_then_clears() {
+async fn test_space_folders() {
     let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await; // owner
+    let alice = server.create_user_with_token("alice").await;
     let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "Space").await;
-    let channel_id = server.create_channel(&space_id, "general").await;
-    server.add_member(&space_id, &bob.user.id).await;
+    let a = server.create_space(&alice.user.id, "A").await;
+    let b = server.create_space(&alice.user.id, "B").await;
+    let c = server.create_space(&bob.user.id, "C").await;
+    server.add_member(&c, &alice.user.id).await;
+    let other = server.create_space(&bob.user.id, "Not mine").await;
+
+    let put = |folders: serde_json::Value| {
+        authenticated_json_request(
+            Method::PUT,
+            "/api/v1/users/@me/settings/spaces",
+            &alice.auth_header(),
+            &serde_json::json!({ "folders": folders }),
+        )
+    };
 
-    // Owner sets a future timeout on bob.
-    let req = authenticated_json_request(
-        Method::PATCH,
-        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
-        &alice.auth_header(),
-        &serde_json::json!({ "communication_disabled_until": FUTURE_TS }),
-    );
-    let response = server.router().oneshot(req).await.unwrap();
+    // Spaces the user isn't in, and duplicates, are rejected.
+    for folders in [
+        serde_json::json!([{ "space_ids": [other] }]),
+        serde_json::json!([{ "space_ids": [a] }, { "name": "Dup", "space_ids": [a, b] }]),
+    ] {
+        let response = server.router().oneshot(put(folders)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let response = server
+        .router()
+        .oneshot(put(serde_json::json!([
+            { "space_ids": [c] },
+            { "name": "Mine", "color": 16711680, "space_ids": [b, a] }
+        ])))
+        .await
+        .unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    assert!(
-        body["data"]["timed_out_until"].as_str().is_some(),
-        "timeout should be persisted and returned"
-    );
-
-    // Bob is blocked from sending messages while timed out.
-    let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{channel_id}/messages"),
-        &bob.auth_header(),
-        &serde_json::json!({ "content": "hello" }),
-    );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(body["data"]["folders"][0]["id"].is_null());
+    assert!(body["data"]["folders"][1]["id"].is_string());
 
-    // Bob is also blocked from reactions.
-    // (Send a message as the owner first so there's something to react to.)
-    let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{channel_id}/messages"),
+    // Leaving a space drops it from the stored layout.
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/spaces/{c}/members/@me"),
         &alice.auth_header(),
-        &serde_json::json!({ "content": "owner msg" }),
     );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let msg_id = parse_body(response).await["data"]["id"]
-        .as_str()
-        .unwrap()
-        .to_string();
+    server.router().oneshot(req).await.unwrap();
     let req = authenticated_request(
-        Method::PUT,
-        &format!("/api/v1/channels/{channel_id}/messages/{msg_id}/reactions/%F0%9F%91%8D/@me"),
-        &bob.auth_header(),
+        Method::GET,
+        "/api/v1/users/@me/settings/spaces",
+        &alice.auth_header(),
     );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let folders = body["data"]["folders"].as_array().unwrap();
+    assert_eq!(folders.len(), 1);
+    assert_eq!(folders[0]["name"], "Mine");
+    assert_eq!(folders[0]["space_ids"], serde_json::json!([b, a]));
+}
+
+#[tokio::test]
+async fn test_channel_states_batch_sync() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let outsider = server.create_user_with_token("outsider").await;
+    let space_id = server.create_space(&alice.user.id, "Workspace").await;
+    let general = server.create_channel(&space_id, "general").await;
+    let random = server.create_channel(&space_id, "random").await;
 
-    // Clearing the timeout with an explicit null restores access.
     let req = authenticated_json_request(
         Method::PATCH,
-        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
+        "/api/v1/users/@me/channel-states",
         &alice.auth_header(),
-        &serde_json::json!({ "communication_disabled_until": serde_json::Value::Null }),
+        &serde_json::json!({ "channels": [
+            { "channel_id": general, "collapsed": true },
+            { "channel_id": random, "muted": true, "collapsed": true },
+        ] }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let body = parse_body(response).await;
-    assert!(body["data"]["timed_out_until"].is_null());
+    assert_eq!(body["data"]["muted"], serde_json::json!([random]));
+    assert_eq!(body["data"]["collapsed"].as_array().unwrap().len(), 2);
 
-    // Bob can now send again.
+    // Omitted fields are left alone.
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{channel_id}/messages"),
-        &bob.auth_header(),
-        &serde_json::json!({ "content": "back" }),
+        Method::PATCH,
+        "/api/v1/users/@me/channel-states",
+        &alice.auth_header(),
+        &serde_json::json!({ "channels": [{ "channel_id": random, "collapsed": false }] }),
     );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-}
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = authenticated_request(
+        Method::GET,
+        "/api/v1/users/@me/channel-states",
+        &alice.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["muted"], serde_json::json!([random]));
+    assert_eq!(body["data"]["collapsed"], serde_json::json!([general]));
 
-#[tokio::test]
-async fn test_member_timeout_requires_moderate_permission() {
-    let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await; // owner
-    let bob = server.create_user_with_token("bob").await;
-    let carol = server.create_user_with_token("carol").await;
-    let space_id = server.create_space(&alice.user.id, "Space").await;
-    server.add_member(&space_id, &bob.user.id).await;
-    server.add_member(&space_id, &carol.user.id).await;
+    // The same state backs the existing mute list.
+    let req = authenticated_request(Method::GET, "/api/v1/users/@me/mutes", &alice.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"][0]["channel_id"], random.as_str());
 
-    // Bob (a plain member without moderate_members) cannot time out carol.
+    // Duplicates are rejected, and outsiders can't touch the space's channels.
     let req = authenticated_json_request(
         Method::PATCH,
-        &format!("/api/v1/spaces/{space_id}/members/{}", carol.user.id),
-        &bob.auth_header(),
-        &serde_json::json!({ "communication_disabled_until": FUTURE_TS }),
+        "/api/v1/users/@me/channel-states",
+        &alice.auth_header(),
+        &serde_json::json!({ "channels": [
+            { "channel_id": general, "muted": true },
+            { "channel_id": general, "muted": false },
+        ] }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::BAD_REQUEST
+    );
+    let req = authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/users/@me/channel-states",
+        &outsider.auth_header(),
+        &serde_json::json!({ "channels": [{ "channel_id": general, "muted": true }] }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::FORBIDDEN
     );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
 #[tokio::test]
-async fn test_member_timeout_expired_does_not_block() {
+async fn test_message_drafts() {
     let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await; // owner
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "Space").await;
+    let alice = server.create_user_with_token("alice").await;
+    let outsider = server.create_user_with_token("outsider").await;
+    let space_id = server.create_space(&alice.user.id, "Drafty").await;
     let channel_id = server.create_channel(&space_id, "general").await;
-    server.add_member(&space_id, &bob.user.id).await;
+    let draft_url = format!("/api/v1/channels/{channel_id}/draft");
+
+    let req = authenticated_request(Method::GET, &draft_url, &alice.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert!(body["data"].is_null());
 
-    // A timeout in the past is already expired and must not block.
     let req = authenticated_json_request(
-        Method::PATCH,
-        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
+        Method::PUT,
+        &draft_url,
         &alice.auth_header(),
-        &serde_json::json!({ "communication_disabled_until": PAST_TS }),
+        &serde_json::json!({ "content": "half a thought" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        parse_body(response).await["data"]["content"],
+        "half a thought"
+    );
+
+    let req = authenticated_request(
+        Method::GET,
+        "/api/v1/users/@me/drafts",
+        &alice.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert_eq!(body["data"][0]["channel_id"], channel_id.as_str());
+
+    // Bounded like messages, and only for channels you can see.
+    let req = authenticated_json_request(
+        Method::PUT,
+        &draft_url,
+        &alice.auth_header(),
+        &serde_json::json!({ "content": "x".repeat(4001) }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::BAD_REQUEST
+    );
+    let req = authenticated_json_request(
+        Method::PUT,
+        &draft_url,
+        &outsider.auth_header(),
+        &serde_json::json!({ "content": "hi" }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::FORBIDDEN
+    );
 
+    // Sending a message clears the draft.
     let req = authenticated_json_request(
         Method::POST,
         &format!("/api/v1/channels/{channel_id}/messages"),
-        &bob.auth_header(),
-        &serde_json::json!({ "content": "still works" }),
+        &alice.auth_header(),
+        &serde_json::json!({ "content": "half a thought, finished" }),
     );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = authenticated_request(Method::GET, &draft_url, &alice.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert!(body["data"].is_null());
 }
 
 #[tokio::test]
-async fn test_member_timeout_rejects_invalid_timestamp() {
+async fn test_voice_chat_retention() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "Space").await;
-    server.add_member(&space_id, &bob.user.id).await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let text_id = server.create_channel(&space_id, "general").await;
+    let vc_id = server.create_voice_channel(&space_id, "voice-chat").await;
+    let messages_url = format!("/api/v1/channels/{vc_id}/messages");
 
+    // Only voice channels have a voice chat to configure.
     let req = authenticated_json_request(
         Method::PATCH,
-        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
+        &format!("/api/v1/channels/{text_id}"),
         &alice.auth_header(),
-        &serde_json::json!({ "communication_disabled_until": "not-a-date" }),
+        &serde_json::json!({ "voice_chat_retention": "clear_on_empty" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
-}
 
-#[tokio::test]
-async fn test_member_timeout_blocks_voice_join() {
-    let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await; // owner
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "Space").await;
-    let vc_id = server.create_voice_channel(&space_id, "voice").await;
-    server.add_member(&space_id, &bob.user.id).await;
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{vc_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "voice_chat_retention": "forever" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
     let req = authenticated_json_request(
         Method::PATCH,
-        &format!("/api/v1/spaces/{space_id}/members/{}", bob.user.id),
+        &format!("/api/v1/channels/{vc_id}"),
         &alice.auth_header(),
-        &serde_json::json!({ "communication_disabled_until": FUTURE_TS }),
+        &serde_json::json!({ "voice_chat_retention": "clear_on_empty" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        parse_body(response).await["data"]["voice_chat_retention"],
+        "clear_on_empty"
+    );
 
+    // Chat during the call survives until the last participant leaves.
     let req = authenticated_json_request(
         Method::POST,
         &format!("/api/v1/channels/{vc_id}/voice/join"),
-        &bob.auth_header(),
+        &alice.auth_header(),
         &serde_json::json!({}),
     );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
-}
-
-// ---------------------------------------------------------------------------
-// DM voice calls + signaling (#32)
-// ---------------------------------------------------------------------------
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = authenticated_json_request(
+        Method::POST,
+        &messages_url,
+        &alice.auth_header(),
+        &serde_json::json!({ "content": "can you hear me?" }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = authenticated_request(Method::GET, &messages_url, &alice.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
 
-#[tokio::test]
-async fn test_voice_join_dm_channel_success() {
-    let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let dm_id = server.create_dm(&alice.user.id, &bob.user.id).await;
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/channels/{vc_id}/voice/leave"),
+        &alice.auth_header(),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = authenticated_request(Method::GET, &messages_url, &alice.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert!(body["data"].as_array().unwrap().is_empty());
 
+    // With history off, nothing is served even mid-call.
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{vc_id}"),
+        &alice.auth_header(),
+        &serde_json::json!({ "voice_chat_retention": "off" }),
+    );
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
     let req = authenticated_json_request(
         Method::POST,
-        &format!("/api/v1/channels/{dm_id}/voice/join"),
+        &messages_url,
         &alice.auth_header(),
-        &serde_json::json!({}),
+        &serde_json::json!({ "content": "gone with the call" }),
     );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
-    let body = parse_body(response).await;
-    let data = &body["data"];
-    assert_eq!(data["backend"], "livekit");
-    assert_eq!(data["voice_state"]["channel_id"], dm_id);
-    assert!(data["voice_state"]["space_id"].is_null());
-    assert!(data["token"].as_str().is_some());
+    assert_eq!(
+        server.router().oneshot(req).await.unwrap().status(),
+        StatusCode::OK
+    );
+    let req = authenticated_request(Method::GET, &messages_url, &alice.auth_header());
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert!(body["data"].as_array().unwrap().is_empty());
 }
 
 #[tokio::test]
-async fn test_voice_join_dm_non_participant_rejected() {
+async fn test_image_attachment_gets_thumbnail() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let carol = server.create_user_with_token("carol").await;
-    let dm_id = server.create_dm(&alice.user.id, &bob.user.id).await;
+    let space_id = server.create_space(&alice.user.id, "AttachSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
 
-    let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{dm_id}/voice/join"),
-        &carol.auth_header(),
-        &serde_json::json!({}),
+    let mut png_bytes = Vec::new();
+    image::DynamicImage::new_rgb8(800, 400)
+        .write_to(
+            &mut std::io::Cursor::new(&mut png_bytes),
+            image::ImageFormat::Png,
+        )
+        .unwrap();
+
+    let boundary = "----accordtestboundary";
+    let body = build_multipart_upload_body(
+        boundary,
+        &serde_json::json!({ "content": "wide shot" }),
+        "wide.png",
+        "image/png",
+        &png_bytes,
     );
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("/api/v1/channels/{channel_id}/messages/upload"))
+        .header("Authorization", alice.auth_header())
+        .header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .body(Body::from(body))
+        .unwrap();
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.status(), StatusCode::OK);
+    let attachment = parse_body(response).await["data"]["attachments"][0].clone();
+    assert_eq!(attachment["width"], 800);
+    assert_eq!(attachment["height"], 400);
+    let proxy_url = attachment["proxy_url"].as_str().unwrap().to_string();
+    assert_ne!(proxy_url, attachment["url"].as_str().unwrap());
+    assert!(
+        proxy_url.ends_with(".webp"),
+        "unexpected proxy_url: {proxy_url}"
+    );
+
+    let req = Request::builder()
+        .method(Method::GET)
+        .uri(&proxy_url)
+        .body(Body::empty())
+        .unwrap();
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let served = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let thumb = image::load_from_memory(&served).unwrap();
+    assert_eq!((thumb.width(), thumb.height()), (256, 128));
+
+    // Small images are their own preview.
+    let mut dot_bytes = Vec::new();
+    image::DynamicImage::new_rgb8(16, 16)
+        .write_to(
+            &mut std::io::Cursor::new(&mut dot_bytes),
+            image::ImageFormat::Png,
+        )
+        .unwrap();
+    let body = build_multipart_upload_body(
+        boundary,
+        &serde_json::json!({ "content": "dot" }),
+        "dot.png",
+        "image/png",
+        &dot_bytes,
+    );
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("/api/v1/channels/{channel_id}/messages/upload"))
+        .header("Authorization", alice.auth_header())
+        .header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .body(Body::from(body))
+        .unwrap();
+    let response = server.router().oneshot(req).await.unwrap();
+    let attachment = parse_body(response).await["data"]["attachments"][0].clone();
+    assert_eq!(attachment["proxy_url"], attachment["url"]);
 }
 
 #[tokio::test]
-async fn test_call_ring_dm_success() {
+async fn test_space_widget_is_opt_in_and_public() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let dm_id = server.create_dm(&alice.user.id, &bob.user.id).await;
+    let space_id = server.create_space(&alice.user.id, "WidgetSpace").await;
+    let voice_id = server.create_voice_channel(&space_id, "Lounge").await;
+    let text_id = server.create_channel(&space_id, "general").await;
+    accordserver::presence::set_presence(&server.state, &alice.user.id, "online", vec![]).await;
+
+    let widget_req = || {
+        Request::builder()
+            .method(Method::GET)
+            .uri(format!("/api/v1/spaces/{space_id}/widget.json"))
+            .body(Body::empty())
+            .unwrap()
+    };
+    let response = server.router().oneshot(widget_req()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "widget_disabled"
+    );
 
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{dm_id}/call/ring"),
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}"),
         &alice.auth_header(),
-        &serde_json::json!({}),
+        &serde_json::json!({ "widget_enabled": true, "widget_channel_id": text_id }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
-}
-
-#[tokio::test]
-async fn test_call_ring_non_dm_rejected() {
-    let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
-    let space_id = server.create_space(&alice.user.id, "Space").await;
-    let channel_id = server.create_channel(&space_id, "general").await;
 
+    let response = server.router().oneshot(widget_req()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let widget = parse_body(response).await["data"].clone();
+    assert_eq!(widget["name"], "WidgetSpace");
+    assert_eq!(widget["presence_count"], 1);
+    assert_eq!(widget["members"][0]["id"], alice.user.id.as_str());
+    let channels = widget["channels"].as_array().unwrap();
+    assert_eq!(channels.len(), 1);
+    assert_eq!(channels[0]["id"], voice_id.as_str());
+
+    // The invite is made once and then reused.
+    let invite = widget["instant_invite"].as_str().unwrap().to_string();
+    assert!(invite.contains("/invite/"));
+    let response = server.router().oneshot(widget_req()).await.unwrap();
+    assert_eq!(parse_body(response).await["data"]["instant_invite"], invite);
+
+    // Channels outside the space can't be the invite target.
+    let other_space = server.create_space(&alice.user.id, "Other").await;
+    let other_channel = server.create_channel(&other_space, "general").await;
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{channel_id}/call/ring"),
+        Method::PATCH,
+        &format!("/api/v1/spaces/{space_id}"),
         &alice.auth_header(),
-        &serde_json::json!({}),
+        &serde_json::json!({ "widget_channel_id": other_channel }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 }
 
 #[tokio::test]
-async fn test_call_decline_and_cancel_dm() {
+async fn test_data_export_includes_relationships() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
     let bob = server.create_user_with_token("bob").await;
-    let dm_id = server.create_dm(&alice.user.id, &bob.user.id).await;
 
-    let req = authenticated_request(
-        Method::POST,
-        &format!("/api/v1/channels/{dm_id}/call/decline"),
-        &bob.auth_header(),
+    let req = authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/users/@me/relationships/{}", bob.user.id),
+        &alice.auth_header(),
+        &serde_json::json!({ "type": 2 }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
 
     let req = authenticated_request(
-        Method::POST,
-        &format!("/api/v1/channels/{dm_id}/call/cancel"),
+        Method::GET,
+        "/api/v1/users/@me/data-export",
         &alice.auth_header(),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    let relationships = body["data"]["relationships"].as_array().unwrap();
+    assert_eq!(relationships.len(), 1);
+    assert_eq!(relationships[0]["target_id"], bob.user.id);
+    assert_eq!(relationships[0]["type"], 2);
 }
@@ -1,7 +1,8 @@
 mod common;
 
 use common::{
-    authenticated_json_request, authenticated_request, json_request, parse_body, TestServer,
+    authenticated_json_request, authenticated_request, json_request, parse_body, SoftPasskey,
+    TestServer,
 };
 use http::{Method, StatusCode};
 use serde_json::json;
@@ -695,6 +696,102 @@ async fn test_expired_token() {
     assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
 
+#[tokio::test]
+async fn test_bot_token_ip_allowlist() {
+    let server = TestServer::new().await;
+    let (owner, bot) = server.create_bot_with_token("owner", "TestBot").await;
+    let from = |ip: &str| {
+        let mut req = authenticated_request(Method::GET, "/api/v1/users/@me", &bot.auth_header());
+        req.headers_mut()
+            .insert("X-Forwarded-For", ip.parse().unwrap());
+        req
+    };
+
+    let req = authenticated_json_request(
+        Method::PUT,
+        "/api/v1/applications/@me/ip-allowlist",
+        &owner.auth_header(),
+        &json!({ "ranges": ["not a range"] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let req = authenticated_json_request(
+        Method::PUT,
+        "/api/v1/applications/@me/ip-allowlist",
+        &owner.auth_header(),
+        &json!({ "ranges": ["203.0.113.0/24", "2001:db8::1"] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_body(response).await;
+    assert_eq!(
+        body["data"]["ranges"],
+        json!(["203.0.113.0/24", "2001:db8::1/128"])
+    );
+
+    let response = server.router().oneshot(from("203.0.113.9")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // A leaked token used elsewhere gets a distinct error, not a plain 401.
+    let response = server.router().oneshot(from("198.51.100.1")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = parse_body(response).await;
+    assert_eq!(body["error"]["code"], "ip_not_allowed");
+
+    // The bot can't lift its own restriction.
+    let mut req = authenticated_json_request(
+        Method::PUT,
+        "/api/v1/applications/@me/ip-allowlist",
+        &bot.auth_header(),
+        &json!({ "ranges": [] }),
+    );
+    req.headers_mut()
+        .insert("X-Forwarded-For", "203.0.113.9".parse().unwrap());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Clearing the list lifts it.
+    let req = authenticated_json_request(
+        Method::PUT,
+        "/api/v1/applications/@me/ip-allowlist",
+        &owner.auth_header(),
+        &json!({ "ranges": [] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = server.router().oneshot(from("198.51.100.1")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_bot_token_without_application_still_authenticates() {
+    let server = TestServer::new().await;
+    let (_owner, bot) = server.create_bot_with_token("owner", "OrphanBot").await;
+
+    // Rows written before foreign keys were enforced can leave a token
+    // whose application is gone.
+    sqlx::query("PRAGMA foreign_keys=OFF")
+        .execute(server.pool())
+        .await
+        .unwrap();
+    sqlx::query(&accordserver::db::q(
+        "DELETE FROM applications WHERE bot_user_id = ?",
+    ))
+    .bind(&bot.user.id)
+    .execute(server.pool())
+    .await
+    .unwrap();
+    sqlx::query("PRAGMA foreign_keys=ON")
+        .execute(server.pool())
+        .await
+        .unwrap();
+
+    let req = authenticated_request(Method::GET, "/api/v1/users/@me", &bot.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
 // =========================================================================
 // 4. Auth Endpoint Security Tests
 // =========================================================================
@@ -1033,6 +1130,52 @@ async fn test_create_role_rejects_unknown_permissions() {
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 }
 
+#[tokio::test]
+async fn test_role_and_overwrite_updates_name_unknown_permissions() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "TestSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    let role_id = server.create_role(&space_id, "Helpers", &[]).await;
+
+    for (method, uri, body) in [
+        (
+            Method::PATCH,
+            format!("/api/v1/spaces/{space_id}/roles/{role_id}"),
+            serde_json::json!({ "permissions": ["manage_channel", "view_channel", "kick"] }),
+        ),
+        (
+            Method::PUT,
+            format!("/api/v1/channels/{channel_id}/permissions/{role_id}"),
+            serde_json::json!({ "type": "role", "allow": ["manage_channel", "kick"], "deny": [] }),
+        ),
+    ] {
+        let req = authenticated_json_request(method, &uri, &alice.auth_header(), &body);
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+        let body = parse_body(response).await;
+        assert_eq!(
+            body["error"]["message"], "unknown permissions: manage_channel, kick",
+            "{uri}"
+        );
+    }
+
+    // Nothing was granted by the half-valid role update.
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/roles"),
+        &alice.auth_header(),
+    );
+    let roles = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let helpers = roles["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|r| r["id"] == role_id.as_str())
+        .unwrap();
+    assert_eq!(helpers["permissions"], serde_json::json!([]));
+}
+
 #[tokio::test]
 async fn test_update_role_cannot_grant_permissions_actor_lacks() {
     let server = TestServer::new().await;
@@ -1617,195 +1760,483 @@ async fn test_member_without_send_messages_cannot_post() {
 }
 
 #[tokio::test]
-async fn test_member_without_manage_messages_cannot_delete_others_message() {
+async fn test_channel_overwrite_denies_create_invites() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
     let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "MsgSpace").await;
-    let channel_id = server.create_channel(&space_id, "general").await;
+    let space_id = server.create_space(&alice.user.id, "InviteSpace").await;
+    let open_channel = server.create_channel(&space_id, "general").await;
+    let locked_channel = server.create_channel(&space_id, "staff").await;
     server.add_member(&space_id, &bob.user.id).await;
 
-    // Alice sends a message
+    // @everyone has create_invites by default; deny it on one channel only.
+    let everyone_role_id = get_everyone_role_id(&server, &space_id, &alice.auth_header()).await;
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{channel_id}/messages"),
+        Method::PUT,
+        &format!("/api/v1/channels/{locked_channel}/permissions/{everyone_role_id}"),
         &alice.auth_header(),
-        &json!({ "content": "alice's message" }),
+        &json!({ "type": "role", "allow": [], "deny": ["create_invites"] }),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    let body = parse_body(response).await;
-    let msg_id = body["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(response.status(), StatusCode::OK);
 
-    // Bob (no manage_messages) tries to delete Alice's message → 403
-    let req = authenticated_request(
-        Method::DELETE,
-        &format!("/api/v1/channels/{channel_id}/messages/{msg_id}"),
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{locked_channel}/invites"),
         &bob.auth_header(),
+        &json!({}),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{open_channel}/invites"),
+        &bob.auth_header(),
+        &json!({}),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
 }
 
 #[tokio::test]
-async fn test_member_without_manage_messages_cannot_edit_others_message() {
+async fn test_announcement_locked_channel_is_view_only() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
     let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "MsgSpace").await;
-    let channel_id = server.create_channel(&space_id, "general").await;
+    let space_id = server.create_space(&alice.user.id, "NewsSpace").await;
+    let channel_id = server.create_channel(&space_id, "news").await;
     server.add_member(&space_id, &bob.user.id).await;
+    let everyone_role_id = get_everyone_role_id(&server, &space_id, &alice.auth_header()).await;
 
-    // Alice sends a message
+    // Keep an unrelated deny on @everyone to check it survives the lock.
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{channel_id}/messages"),
+        Method::PUT,
+        &format!("/api/v1/channels/{channel_id}/permissions/{everyone_role_id}"),
         &alice.auth_header(),
-        &json!({ "content": "alice's message" }),
+        &json!({ "type": "role", "allow": [], "deny": ["create_invites"] }),
     );
-    let response = server.router().oneshot(req).await.unwrap();
-    let body = parse_body(response).await;
-    let msg_id = body["data"]["id"].as_str().unwrap().to_string();
+    server.router().oneshot(req).await.unwrap();
 
-    // Bob (no manage_messages) tries to edit Alice's message → 403
     let req = authenticated_json_request(
         Method::PATCH,
-        &format!("/api/v1/channels/{channel_id}/messages/{msg_id}"),
+        &format!("/api/v1/channels/{channel_id}"),
+        &alice.auth_header(),
+        &json!({ "announcement_locked": true }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let channel = parse_body(response).await["data"].clone();
+    assert_eq!(channel["announcement_locked"], true);
+    let overwrite = channel["permission_overwrites"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|o| o["id"] == everyone_role_id.as_str())
+        .unwrap()
+        .clone();
+    let deny: Vec<&str> = overwrite["deny"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p.as_str().unwrap())
+        .collect();
+    assert!(deny.contains(&"create_invites"));
+    assert!(deny.contains(&"send_messages"));
+    assert!(deny.contains(&"add_reactions"));
+
+    // Bob can still read but not post.
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
         &bob.auth_header(),
-        &json!({ "content": "hacked" }),
+        &json!({ "content": "hi" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::FORBIDDEN);
-}
 
-#[tokio::test]
-async fn test_channel_overwrite_deny_blocks_send() {
-    let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "OverwriteSpace").await;
-    let channel_id = server.create_channel(&space_id, "restricted").await;
-    server.add_member(&space_id, &bob.user.id).await;
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
 
-    // Set member-specific overwrite denying send_messages for Bob
+    // Editing the overwrite by hand clears the flag.
     let req = authenticated_json_request(
         Method::PUT,
-        &format!("/api/v1/channels/{channel_id}/permissions/{}", bob.user.id),
+        &format!("/api/v1/channels/{channel_id}/permissions/{everyone_role_id}"),
         &alice.auth_header(),
-        &json!({ "type": "member", "allow": [], "deny": ["send_messages"] }),
+        &json!({ "type": "role", "allow": [], "deny": ["add_reactions"] }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
 
-    // Bob tries to send → 403
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}"),
+        &bob.auth_header(),
+    );
+    let channel = parse_body(server.router().oneshot(req).await.unwrap()).await["data"].clone();
+    assert_eq!(channel["announcement_locked"], false);
+
+    // Members without manage_roles can't toggle the lock.
     let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{channel_id}/messages"),
+        Method::PATCH,
+        &format!("/api/v1/channels/{channel_id}"),
         &bob.auth_header(),
-        &json!({ "content": "should fail" }),
+        &json!({ "announcement_locked": true }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
 
 #[tokio::test]
-async fn test_channel_overwrite_member_overrides_role() {
+async fn test_lock_and_unlock_channel_restores_everyone_overwrite() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
     let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "OverrideSpace").await;
-    let channel_id = server.create_channel(&space_id, "restricted").await;
+    let space_id = server.create_space(&alice.user.id, "LockSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
     server.add_member(&space_id, &bob.user.id).await;
+    let everyone_role_id = get_everyone_role_id(&server, &space_id, &alice.auth_header()).await;
 
-    // Create a role that allows send_messages and assign it to Bob
-    let role_id = server
-        .create_role(&space_id, "talker", &["send_messages", "view_channel"])
-        .await;
-    server.assign_role(&space_id, &bob.user.id, &role_id).await;
-
-    // Role overwrite allows send_messages
+    // @everyone is explicitly allowed to send beforehand.
     let req = authenticated_json_request(
         Method::PUT,
-        &format!("/api/v1/channels/{channel_id}/permissions/{role_id}"),
+        &format!("/api/v1/channels/{channel_id}/permissions/{everyone_role_id}"),
         &alice.auth_header(),
-        &json!({ "type": "role", "allow": ["send_messages"], "deny": [] }),
+        &json!({ "type": "role", "allow": ["send_messages"], "deny": ["create_invites"] }),
+    );
+    server.router().oneshot(req).await.unwrap();
+
+    // Members without manage_roles can't lock.
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/lock"),
+        &bob.auth_header(),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
-    // But member-specific overwrite denies it (should take precedence)
-    let req = authenticated_json_request(
-        Method::PUT,
-        &format!("/api/v1/channels/{channel_id}/permissions/{}", bob.user.id),
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/lock"),
         &alice.auth_header(),
-        &json!({ "type": "member", "allow": [], "deny": ["send_messages"] }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
 
-    // Bob tries to send → 403 (member overwrite takes precedence over role)
     let req = authenticated_json_request(
         Method::POST,
         &format!("/api/v1/channels/{channel_id}/messages"),
         &bob.auth_header(),
-        &json!({ "content": "should fail" }),
+        &json!({ "content": "hi" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::FORBIDDEN);
-}
-
-// =========================================================================
-// 10. Message Ownership & Cross-Channel
-//
-// Tests that message operations respect authorship and channel boundaries.
-// =========================================================================
 
-#[tokio::test]
-async fn test_non_author_cannot_edit_message() {
-    let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
-    let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "MsgOwn").await;
-    let channel_id = server.create_channel(&space_id, "general").await;
-    server.add_member(&space_id, &bob.user.id).await;
+    let req = authenticated_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/lock"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(
+        parse_body(response).await["error"]["code"],
+        "channel_locked"
+    );
 
-    // Alice sends a message
-    let req = authenticated_json_request(
+    let req = authenticated_request(
         Method::POST,
-        &format!("/api/v1/channels/{channel_id}/messages"),
+        &format!("/api/v1/channels/{channel_id}/unlock"),
         &alice.auth_header(),
-        &json!({ "content": "alice's" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    let body = parse_body(response).await;
-    let msg_id = body["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(response.status(), StatusCode::OK);
+    let channel = parse_body(response).await["data"].clone();
+    let overwrite = channel["permission_overwrites"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|o| o["id"] == everyone_role_id.as_str())
+        .unwrap()
+        .clone();
+    assert_eq!(overwrite["allow"], json!(["send_messages"]));
+    assert_eq!(overwrite["deny"], json!(["create_invites"]));
 
-    // Bob (member, not author, no manage_messages) edits → 403
     let req = authenticated_json_request(
-        Method::PATCH,
-        &format!("/api/v1/channels/{channel_id}/messages/{msg_id}"),
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
         &bob.auth_header(),
-        &json!({ "content": "tampered" }),
+        &json!({ "content": "hi" }),
     );
     let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/audit-log?action_type=channel_lock"),
+        &alice.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
 }
 
 #[tokio::test]
-async fn test_non_author_cannot_delete_message() {
+async fn test_purge_deletes_recent_messages_from_one_member() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
     let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "MsgOwn").await;
+    let space_id = server.create_space(&alice.user.id, "PurgeSpace").await;
     let channel_id = server.create_channel(&space_id, "general").await;
     server.add_member(&space_id, &bob.user.id).await;
 
-    let req = authenticated_json_request(
-        Method::POST,
-        &format!("/api/v1/channels/{channel_id}/messages"),
-        &alice.auth_header(),
-        &json!({ "content": "alice's" }),
-    );
-    let response = server.router().oneshot(req).await.unwrap();
+    for (user, content) in [(&bob, "spam 1"), (&bob, "spam 2"), (&alice, "keep me")] {
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/messages"),
+            &user.auth_header(),
+            &json!({ "content": content }),
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let purge = |auth: &str, query: &str| {
+        authenticated_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/purge?{query}"),
+            auth,
+        )
+    };
+    let bob_query = format!("user_id={}&seconds=600", bob.user.id);
+
+    let response = server
+        .router()
+        .oneshot(purge(&bob.auth_header(), &bob_query))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = server
+        .router()
+        .oneshot(purge(&alice.auth_header(), "seconds=99999999"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = server
+        .router()
+        .oneshot(purge(&alice.auth_header(), &bob_query))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(parse_body(response).await["data"]["deleted"], 2);
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let messages = body["data"].as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["content"], "keep me");
+}
+
+#[tokio::test]
+async fn test_member_without_manage_messages_cannot_delete_others_message() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "MsgSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    // Alice sends a message
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+        &json!({ "content": "alice's message" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let body = parse_body(response).await;
+    let msg_id = body["data"]["id"].as_str().unwrap().to_string();
+
+    // Bob (no manage_messages) tries to delete Alice's message → 403
+    let req = authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/channels/{channel_id}/messages/{msg_id}"),
+        &bob.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_member_without_manage_messages_cannot_edit_others_message() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "MsgSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    // Alice sends a message
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+        &json!({ "content": "alice's message" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let body = parse_body(response).await;
+    let msg_id = body["data"]["id"].as_str().unwrap().to_string();
+
+    // Bob (no manage_messages) tries to edit Alice's message → 403
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{channel_id}/messages/{msg_id}"),
+        &bob.auth_header(),
+        &json!({ "content": "hacked" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_channel_overwrite_deny_blocks_send() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "OverwriteSpace").await;
+    let channel_id = server.create_channel(&space_id, "restricted").await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    // Set member-specific overwrite denying send_messages for Bob
+    let req = authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/channels/{channel_id}/permissions/{}", bob.user.id),
+        &alice.auth_header(),
+        &json!({ "type": "member", "allow": [], "deny": ["send_messages"] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Bob tries to send → 403
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &bob.auth_header(),
+        &json!({ "content": "should fail" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_channel_overwrite_member_overrides_role() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "OverrideSpace").await;
+    let channel_id = server.create_channel(&space_id, "restricted").await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    // Create a role that allows send_messages and assign it to Bob
+    let role_id = server
+        .create_role(&space_id, "talker", &["send_messages", "view_channel"])
+        .await;
+    server.assign_role(&space_id, &bob.user.id, &role_id).await;
+
+    // Role overwrite allows send_messages
+    let req = authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/channels/{channel_id}/permissions/{role_id}"),
+        &alice.auth_header(),
+        &json!({ "type": "role", "allow": ["send_messages"], "deny": [] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // But member-specific overwrite denies it (should take precedence)
+    let req = authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/channels/{channel_id}/permissions/{}", bob.user.id),
+        &alice.auth_header(),
+        &json!({ "type": "member", "allow": [], "deny": ["send_messages"] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Bob tries to send → 403 (member overwrite takes precedence over role)
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &bob.auth_header(),
+        &json!({ "content": "should fail" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+// =========================================================================
+// 10. Message Ownership & Cross-Channel
+//
+// Tests that message operations respect authorship and channel boundaries.
+// =========================================================================
+
+#[tokio::test]
+async fn test_non_author_cannot_edit_message() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "MsgOwn").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    // Alice sends a message
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+        &json!({ "content": "alice's" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    let body = parse_body(response).await;
+    let msg_id = body["data"]["id"].as_str().unwrap().to_string();
+
+    // Bob (member, not author, no manage_messages) edits → 403
+    let req = authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/channels/{channel_id}/messages/{msg_id}"),
+        &bob.auth_header(),
+        &json!({ "content": "tampered" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_non_author_cannot_delete_message() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "MsgOwn").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+        &json!({ "content": "alice's" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
     let body = parse_body(response).await;
     let msg_id = body["data"]["id"].as_str().unwrap().to_string();
 
@@ -2156,6 +2587,8 @@ async fn test_non_participant_cannot_read_dm_messages() {
     let alice = server.create_user_with_token("alice").await;
     let bob = server.create_user_with_token("bob").await;
     let charlie = server.create_user_with_token("charlie").await;
+    let space_id = server.create_space(&alice.user.id, "Shared").await;
+    server.add_member(&space_id, &bob.user.id).await;
 
     // Create DM between Alice and Bob
     let req = authenticated_json_request(
@@ -2180,40 +2613,112 @@ async fn test_non_participant_cannot_read_dm_messages() {
 }
 
 #[tokio::test]
-async fn test_group_dm_exceed_participant_limit() {
-    let server = TestServer::new().await;
-    let alice = server.create_user_with_token("alice").await;
-
-    // Create 10 other users (alice + 10 = 11, exceeds 10 max)
-    let mut recipient_ids = Vec::new();
-    for i in 0..10 {
-        let user = server.create_user_with_token(&format!("user{i}")).await;
-        recipient_ids.push(serde_json::Value::String(user.user.id));
-    }
-
-    let req = authenticated_json_request(
-        Method::POST,
-        "/api/v1/users/@me/channels",
-        &alice.auth_header(),
-        &json!({ "recipients": recipient_ids }),
-    );
-    let response = server.router().oneshot(req).await.unwrap();
-    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
-}
-
-// =========================================================================
-// 14. Reaction Authorization
-//
-// Tests that reaction endpoints enforce membership and permission checks.
-// =========================================================================
-
-#[tokio::test]
-async fn test_non_member_cannot_add_reaction() {
+async fn test_dm_requires_friendship_or_shared_space() {
     let server = TestServer::new().await;
     let alice = server.create_user_with_token("alice").await;
     let bob = server.create_user_with_token("bob").await;
-    let space_id = server.create_space(&alice.user.id, "ReactSpace").await;
-    let channel_id = server.create_channel(&space_id, "general").await;
+    let charlie = server.create_user_with_token("charlie").await;
+
+    let open_dm = |from: &str, to: &str| {
+        authenticated_json_request(
+            Method::POST,
+            "/api/v1/users/@me/channels",
+            from,
+            &json!({ "recipient_id": to }),
+        )
+    };
+
+    // Strangers can't open a DM, and a pending request isn't enough.
+    let response = server
+        .router()
+        .oneshot(open_dm(&alice.auth_header(), &bob.user.id))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = parse_body(response).await;
+    assert_eq!(body["error"]["code"], "dm_requires_relationship");
+
+    let befriend = |from: &str, to: &str| {
+        authenticated_json_request(
+            Method::PUT,
+            &format!("/api/v1/users/@me/relationships/{to}"),
+            from,
+            &json!({ "type": 1 }),
+        )
+    };
+    let response = server
+        .router()
+        .oneshot(befriend(&alice.auth_header(), &bob.user.id))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = server
+        .router()
+        .oneshot(open_dm(&alice.auth_header(), &bob.user.id))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Once accepted, either side can open it.
+    let response = server
+        .router()
+        .oneshot(befriend(&bob.auth_header(), &alice.user.id))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = server
+        .router()
+        .oneshot(open_dm(&bob.auth_header(), &alice.user.id))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Sharing a space is enough without a friendship.
+    let space_id = server.create_space(&alice.user.id, "Shared").await;
+    server.add_member(&space_id, &charlie.user.id).await;
+    let response = server
+        .router()
+        .oneshot(open_dm(&charlie.auth_header(), &alice.user.id))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_group_dm_exceed_participant_limit() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+
+    // Create 10 other users (alice + 10 = 11, exceeds 10 max)
+    let mut recipient_ids = Vec::new();
+    for i in 0..10 {
+        let user = server.create_user_with_token(&format!("user{i}")).await;
+        recipient_ids.push(serde_json::Value::String(user.user.id));
+    }
+
+    let req = authenticated_json_request(
+        Method::POST,
+        "/api/v1/users/@me/channels",
+        &alice.auth_header(),
+        &json!({ "recipients": recipient_ids }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+// =========================================================================
+// 14. Reaction Authorization
+//
+// Tests that reaction endpoints enforce membership and permission checks.
+// =========================================================================
+
+#[tokio::test]
+async fn test_non_member_cannot_add_reaction() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "ReactSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
 
     // Alice sends a message
     let req = authenticated_json_request(
@@ -2767,6 +3272,31 @@ async fn test_register_ip_rate_limit_blocked_after_5_attempts() {
     );
 }
 
+#[tokio::test]
+async fn test_register_ip_rate_limit_ignores_spoofed_forwarded_hops() {
+    let server = TestServer::new().await;
+
+    // A client can prepend anything to X-Forwarded-For; only the hop the
+    // proxy appended counts.
+    for i in 0..6 {
+        let mut req = json_request(
+            Method::POST,
+            "/api/v1/auth/register",
+            &json!({
+                "username": format!("spoofer{}", i),
+                "password": "securepassword1"
+            }),
+        );
+        req.headers_mut().insert(
+            "X-Forwarded-For",
+            format!("10.9.9.{i}, 203.0.113.7").parse().unwrap(),
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        let limited = response.status() == StatusCode::TOO_MANY_REQUESTS;
+        assert_eq!(limited, i == 5, "attempt {}", i + 1);
+    }
+}
+
 // =========================================================================
 // Session limit tests
 // =========================================================================
@@ -2955,6 +3485,87 @@ async fn test_seed_endpoint_works_with_feature() {
     assert!(body["data"]["user"]["token"].is_string());
 }
 
+/// A scenario document builds the whole fixture in one call and returns
+/// usable tokens and IDs.
+#[cfg(feature = "test-seed")]
+#[tokio::test]
+async fn test_seed_scenario() {
+    use axum::body::Body;
+    use http::Request;
+
+    let server = TestServer::new().await;
+    let seed = |scenario: serde_json::Value| {
+        Request::builder()
+            .method(Method::POST)
+            .uri("/test/seed")
+            .header("Content-Type", "application/json")
+            .body(Body::from(scenario.to_string()))
+            .unwrap()
+    };
+
+    let req = seed(json!({
+        "users": [{ "username": "ana" }, { "username": "ben", "display_name": "Ben" }],
+        "spaces": [{
+            "key": "guild",
+            "name": "Guild",
+            "owner": "ana",
+            "members": ["ben"],
+            "roles": [{ "name": "Mods", "permissions": ["manage_messages"], "members": ["ben"] }],
+            "channels": [
+                { "name": "general", "messages": [
+                    { "author": "ana", "content": "hello" },
+                    { "author": "ben", "content": "hi!" }
+                ] },
+                { "name": "Lounge", "type": "voice", "voice_states": [{ "user": "ben", "self_mute": true }] }
+            ]
+        }]
+    }));
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let data = parse_body(response).await["data"].clone();
+    let ben_auth = format!("Bearer {}", data["users"]["ben"]["token"].as_str().unwrap());
+    let guild = &data["spaces"]["guild"];
+    let general = guild["channels"]["general"]["id"].as_str().unwrap();
+    assert_eq!(
+        guild["channels"]["general"]["message_ids"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{general}/messages"),
+        &ben_auth,
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        parse_body(response).await["data"].as_array().unwrap().len(),
+        2
+    );
+
+    let lounge = guild["channels"]["Lounge"]["id"].as_str().unwrap();
+    let req = authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{lounge}/voice-status"),
+        &ben_auth,
+    );
+    let body = parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+
+    // Dangling references are rejected before anything is written.
+    let req = seed(json!({
+        "users": [{ "username": "cy" }],
+        "spaces": [{ "name": "Broken", "owner": "cy", "channels": [
+            { "name": "general", "messages": [{ "author": "nobody", "content": "?" }] }
+        ] }]
+    }));
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
 // =========================================================================
 // 19. User Profile Data Scoping
 //
@@ -3160,3 +3771,471 @@ async fn test_concurrent_mfa_tickets_are_invalidated() {
         "invalidated ticket must be rejected"
     );
 }
+
+#[tokio::test]
+async fn test_elevated_mfa_level_requires_recent_verification() {
+    let server = TestServer::new().await;
+    let reg_body = json!({ "username": "alice_stepup", "password": "correct-horse-battery" });
+    let resp = server
+        .router()
+        .oneshot(json_request(
+            Method::POST,
+            "/api/v1/auth/register",
+            &reg_body,
+        ))
+        .await
+        .unwrap();
+    let body = parse_body(resp).await;
+    let user_id = body["data"]["user"]["id"].as_str().unwrap().to_string();
+    let auth = format!("Bearer {}", body["data"]["token"].as_str().unwrap());
+
+    let post = |uri: &'static str, body: serde_json::Value| {
+        let app = server.router();
+        let auth = auth.clone();
+        async move {
+            app.oneshot(authenticated_json_request(Method::POST, uri, &auth, &body))
+                .await
+                .unwrap()
+        }
+    };
+
+    // Enable TOTP and confirm it with a real code.
+    let resp = post(
+        "/api/v1/auth/mfa/totp/enable",
+        json!({ "password": "correct-horse-battery" }),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let secret = parse_body(resp).await["data"]["secret"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let totp = totp_rs::TOTP::new(
+        totp_rs::Algorithm::SHA1,
+        6,
+        1,
+        30,
+        data_encoding::BASE32_NOPAD
+            .decode(secret.as_bytes())
+            .unwrap(),
+    )
+    .unwrap();
+    let resp = post(
+        "/api/v1/auth/mfa/totp/verify",
+        json!({ "code": totp.generate_current().unwrap() }),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let backup_codes = parse_body(resp).await["data"]["backup_codes"].clone();
+
+    let space_id = server.create_space(&user_id, "Locked Down").await;
+    let channel_id = server.create_channel(&space_id, "doomed").await;
+    let resp = server
+        .router()
+        .oneshot(authenticated_json_request(
+            Method::PATCH,
+            &format!("/api/v1/spaces/{space_id}"),
+            &auth,
+            &json!({ "mfa_level": "elevated" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Let the verification from enabling 2FA go stale.
+    sqlx::query(&accordserver::db::q(
+        "UPDATE user_tokens SET mfa_verified_at = '2020-01-01T00:00:00+00:00' WHERE user_id = ?",
+    ))
+    .bind(&user_id)
+    .execute(server.pool())
+    .await
+    .unwrap();
+
+    let delete_channel = || {
+        server.router().oneshot(authenticated_request(
+            Method::DELETE,
+            &format!("/api/v1/channels/{channel_id}"),
+            &auth,
+        ))
+    };
+    let resp = delete_channel().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert_eq!(parse_body(resp).await["error"]["code"], "mfa_required");
+
+    // A second session of the same user.
+    let other_token = accordserver::middleware::auth::generate_token();
+    accordserver::db::sessions::create_session(
+        server.pool(),
+        &user_id,
+        &accordserver::middleware::auth::create_token_hash(&other_token),
+        "2999-01-01T00:00:00+00:00",
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let other_auth = format!("Bearer {other_token}");
+
+    let resp = post(
+        "/api/v1/auth/mfa/verify",
+        json!({ "code": backup_codes[0] }),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Verifying on one session doesn't step up the other.
+    let other_channel = server.create_channel(&space_id, "spared").await;
+    let resp = server
+        .router()
+        .oneshot(authenticated_request(
+            Method::DELETE,
+            &format!("/api/v1/channels/{other_channel}"),
+            &other_auth,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert_eq!(parse_body(resp).await["error"]["code"], "mfa_required");
+
+    let resp = delete_channel().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_elevated_mfa_level_rejects_moderators_without_totp() {
+    let server = TestServer::new().await;
+    let owner = server.create_user_with_token("owner").await;
+    let moderator = server.create_user_with_token("moderator").await;
+    let space_id = server.create_space(&owner.user.id, "Locked Down").await;
+    server.add_member(&space_id, &moderator.user.id).await;
+    let role_id = server
+        .create_role(&space_id, "Mods", &["manage_channels"])
+        .await;
+    server
+        .assign_role(&space_id, &moderator.user.id, &role_id)
+        .await;
+    let channel_id = server.create_channel(&space_id, "doomed").await;
+
+    let resp = server
+        .router()
+        .oneshot(authenticated_json_request(
+            Method::PATCH,
+            &format!("/api/v1/spaces/{space_id}"),
+            &owner.auth_header(),
+            &json!({ "mfa_level": "elevated" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = server
+        .router()
+        .oneshot(authenticated_request(
+            Method::DELETE,
+            &format!("/api/v1/channels/{channel_id}"),
+            &moderator.auth_header(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert_eq!(parse_body(resp).await["error"]["code"], "mfa_not_enrolled");
+}
+
+/// Registers `username` over HTTP and adds a passkey to the account.
+/// Returns the bearer header and the passkey.
+async fn register_with_passkey(server: &TestServer, username: &str) -> (String, SoftPasskey) {
+    let resp = server
+        .router()
+        .oneshot(json_request(
+            Method::POST,
+            "/api/v1/auth/register",
+            &json!({ "username": username, "password": "correct-horse-battery" }),
+        ))
+        .await
+        .unwrap();
+    let body = parse_body(resp).await;
+    let auth = format!("Bearer {}", body["data"]["token"].as_str().unwrap());
+
+    let resp = server
+        .router()
+        .oneshot(authenticated_json_request(
+            Method::POST,
+            "/api/v1/auth/passkeys/register/start",
+            &auth,
+            &json!({ "password": "correct-horse-battery" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let options = parse_body(resp).await["data"].clone();
+    assert_eq!(options["publicKey"]["rp"]["id"], "localhost");
+
+    let passkey = SoftPasskey::new();
+    let resp = server
+        .router()
+        .oneshot(authenticated_json_request(
+            Method::POST,
+            "/api/v1/auth/passkeys/register/finish",
+            &auth,
+            &json!({ "name": "Laptop", "credential": passkey.create(&options) }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    (auth, passkey)
+}
+
+#[tokio::test]
+async fn test_passkey_registration_and_passwordless_login() {
+    let server = TestServer::new().await;
+
+    // Adding a passkey needs the password.
+    let (auth, mut passkey) = register_with_passkey(&server, "alice_passkey").await;
+    let resp = server
+        .router()
+        .oneshot(authenticated_json_request(
+            Method::POST,
+            "/api/v1/auth/passkeys/register/start",
+            &auth,
+            &json!({ "password": "wrong" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = server
+        .router()
+        .oneshot(authenticated_request(
+            Method::GET,
+            "/api/v1/auth/passkeys",
+            &auth,
+        ))
+        .await
+        .unwrap();
+    let passkeys = parse_body(resp).await["data"].clone();
+    assert_eq!(passkeys.as_array().unwrap().len(), 1);
+    assert_eq!(passkeys[0]["name"], "Laptop");
+    let passkey_id = passkeys[0]["id"].as_str().unwrap().to_string();
+
+    let login_start = || {
+        server.router().oneshot(json_request(
+            Method::POST,
+            "/api/v1/auth/passkeys/login/start",
+            &json!({ "username": "alice_passkey" }),
+        ))
+    };
+    let login_finish = |credential: &serde_json::Value| {
+        server.router().oneshot(json_request(
+            Method::POST,
+            "/api/v1/auth/passkeys/login/finish",
+            &json!({ "credential": credential }),
+        ))
+    };
+
+    let options = parse_body(login_start().await.unwrap()).await["data"].clone();
+    assert_eq!(
+        options["publicKey"]["allowCredentials"][0]["id"],
+        SoftPasskey::b64(&passkey.credential_id)
+    );
+    let assertion = passkey.get(&options);
+    let resp = login_finish(&assertion).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = parse_body(resp).await;
+    assert_eq!(body["data"]["user"]["username"], "alice_passkey");
+    assert!(body["data"]["token"].as_str().is_some());
+
+    // Challenges are single-use.
+    let resp = login_finish(&assertion).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // A different key can't answer for the registered credential.
+    let options = parse_body(login_start().await.unwrap()).await["data"].clone();
+    let mut impostor = SoftPasskey::new();
+    impostor.credential_id = passkey.credential_id.clone();
+    impostor.sign_count = passkey.sign_count;
+    let resp = login_finish(&impostor.get(&options)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = server
+        .router()
+        .oneshot(authenticated_request(
+            Method::DELETE,
+            &format!("/api/v1/auth/passkeys/{passkey_id}"),
+            &auth,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let options = parse_body(login_start().await.unwrap()).await["data"].clone();
+    let resp = login_finish(&passkey.get(&options)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_passkey_answers_login_mfa_instead_of_code() {
+    let server = TestServer::new().await;
+    let (_, mut passkey) = register_with_passkey(&server, "bob_passkey").await;
+    sqlx::query(&accordserver::db::q(
+        "UPDATE users SET totp_enabled = TRUE, totp_secret = 'DUMMYBASE32SECRET' WHERE username = ?",
+    ))
+    .bind("bob_passkey")
+    .execute(server.pool())
+    .await
+    .unwrap();
+
+    let resp = server
+        .router()
+        .oneshot(json_request(
+            Method::POST,
+            "/api/v1/auth/login",
+            &json!({ "username": "bob_passkey", "password": "correct-horse-battery" }),
+        ))
+        .await
+        .unwrap();
+    let body = parse_body(resp).await;
+    assert_eq!(body["data"]["mfa_required"], true);
+    let ticket = body["data"]["ticket"].as_str().unwrap().to_string();
+
+    let resp = server
+        .router()
+        .oneshot(json_request(
+            Method::POST,
+            "/api/v1/auth/login/mfa",
+            &json!({ "ticket": ticket, "passkey": passkey.get(&body["data"]["passkey"]) }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = parse_body(resp).await;
+    assert_eq!(body["data"]["user"]["username"], "bob_passkey");
+    assert!(body["data"]["token"].as_str().is_some());
+}
+
+#[tokio::test]
+async fn test_blocked_user_cannot_dm_or_react() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Shared").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    let dm_id = server.create_dm(&alice.user.id, &bob.user.id).await;
+
+    let send = |channel: &str, from: &str| {
+        authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel}/messages"),
+            from,
+            &json!({ "content": "hi" }),
+        )
+    };
+    let response = server
+        .router()
+        .oneshot(send(&channel_id, &alice.auth_header()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let message_id = parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = server
+        .router()
+        .oneshot(authenticated_json_request(
+            Method::PUT,
+            &format!("/api/v1/users/@me/relationships/{}", bob.user.id),
+            &alice.auth_header(),
+            &json!({ "type": 2 }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = server
+        .router()
+        .oneshot(send(&dm_id, &bob.auth_header()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = parse_body(response).await;
+    assert_eq!(body["error"]["code"], "blocked_by_recipient");
+
+    let response = server
+        .router()
+        .oneshot(authenticated_request(
+            Method::PUT,
+            &format!(
+                "/api/v1/channels/{channel_id}/messages/{message_id}/reactions/%F0%9F%91%8D/@me"
+            ),
+            &bob.auth_header(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = parse_body(response).await;
+    assert_eq!(body["error"]["code"], "blocked_by_author");
+
+    // The blocker can still use the DM.
+    let response = server
+        .router()
+        .oneshot(send(&dm_id, &alice.auth_header()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_voice_chat_uses_its_own_send_permission() {
+    let server = TestServer::new().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "VoiceSpace").await;
+    let text_id = server.create_channel(&space_id, "general").await;
+    let voice_id = server.create_voice_channel(&space_id, "lounge").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let everyone_role_id = get_everyone_role_id(&server, &space_id, &alice.auth_header()).await;
+
+    // Denying send_messages_in_voice silences the voice chat only.
+    let req = authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/channels/{voice_id}/permissions/{everyone_role_id}"),
+        &alice.auth_header(),
+        &json!({ "type": "role", "allow": [], "deny": ["send_messages_in_voice"] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    for (channel_id, expected) in [
+        (&voice_id, StatusCode::FORBIDDEN),
+        (&text_id, StatusCode::OK),
+    ] {
+        let req = authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/messages"),
+            &bob.auth_header(),
+            &json!({ "content": "hello" }),
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), expected);
+    }
+
+    // And send_messages doesn't reach into voice channels.
+    let req = authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/channels/{voice_id}/permissions/{everyone_role_id}"),
+        &alice.auth_header(),
+        &json!({ "type": "role", "allow": [], "deny": ["send_messages"] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let req = authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{voice_id}/messages"),
+        &bob.auth_header(),
+        &json!({ "content": "still here" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
@@ -1,10 +1,13 @@
 mod common;
 
+use accordserver::gateway::intents;
 use common::TestServer;
 use futures_util::{SinkExt, StreamExt};
+use http::{Method, StatusCode};
 use tokio::net::TcpListener;
 use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::Message;
+use tower::ServiceExt;
 
 async fn spawn_server() -> String {
     let app = common::test_app().await;
@@ -29,6 +32,15 @@ async fn spawn_test_server() -> (TestServer, String) {
 async fn connect_and_identify(
     ws_url: &str,
     token: &str,
+) -> tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>> {
+    connect_with_intents(ws_url, token, &["messages", "voice_states"]).await
+}
+
+/// [connect_and_identify] with an explicit intent list.
+async fn connect_with_intents(
+    ws_url: &str,
+    token: &str,
+    intents: &[&str],
 ) -> tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>> {
     let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
 
@@ -42,7 +54,7 @@ async fn connect_and_identify(
         "op": 2,
         "data": {
             "token": token,
-            "intents": ["messages", "voice_states"]
+            "intents": intents
         }
     });
     ws.send(Message::Text(identify.to_string().into()))
@@ -335,8 +347,8 @@ async fn test_ws_voice_join_denied_without_connect_permission() {
         &accordserver::models::permission::PermissionOverwrite {
             id: bob.user.id.clone(),
             overwrite_type: "member".to_string(),
-            allow: vec![],
-            deny: vec!["connect".to_string()],
+            allow: accordserver::models::permission::Permissions::empty(),
+            deny: accordserver::models::permission::Permissions::CONNECT,
         },
     )
     .await
@@ -518,3 +530,1325 @@ async fn test_ws_heartbeat_ack() {
 
     ws.close(None).await.unwrap();
 }
+
+// ---------------------------------------------------------------------------
+// Gateway RESUME Tests
+// ---------------------------------------------------------------------------
+
+/// Connect and IDENTIFY, returning the socket and the READY session_id.
+async fn identify_with_session(
+    ws_url: &str,
+    token: &str,
+) -> (
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
+    String,
+) {
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    let identify = serde_json::json!({
+        "op": 2,
+        "data": { "token": token, "intents": ["messages"] }
+    });
+    ws.send(Message::Text(identify.to_string().into()))
+        .await
+        .unwrap();
+    let msg = ws.next().await.unwrap().unwrap();
+    let ready: serde_json::Value = serde_json::from_str(&msg.into_text().unwrap()).unwrap();
+    assert_eq!(ready["type"], "ready");
+    let session_id = ready["data"]["session_id"].as_str().unwrap().to_string();
+    (ws, session_id)
+}
+
+#[tokio::test]
+async fn test_ws_resume_replays_missed_events() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "ResumeSpace").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let (ws, session_id) = identify_with_session(&ws_url, &alice.gateway_token()).await;
+
+    // Drop the connection without a close frame, as a network failure would.
+    drop(ws);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    // An event dispatched while disconnected should be buffered.
+    let req = common::authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+        &serde_json::json!({ "content": "while you were away" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Reconnect and RESUME from seq 1 (the READY).
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    let resume = serde_json::json!({
+        "op": 3,
+        "data": {
+            "token": alice.gateway_token(),
+            "session_id": session_id,
+            "seq": 1
+        }
+    });
+    ws.send(Message::Text(resume.to_string().into()))
+        .await
+        .unwrap();
+
+    let (created, others) = recv_event_type(&mut ws, "message.create", 5).await;
+    let created = created.expect("missed message.create should be replayed");
+    assert!(created["seq"].as_u64().unwrap() > 1);
+    assert_eq!(created["data"]["content"], "while you were away");
+    let resumed = if others.iter().any(|e| e["type"] == "resumed") {
+        true
+    } else {
+        recv_event_type(&mut ws, "resumed", 5).await.0.is_some()
+    };
+    assert!(resumed, "should receive a resumed event");
+
+    ws.close(None).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_ws_resume_unknown_session_is_invalid() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    let resume = serde_json::json!({
+        "op": 3,
+        "data": {
+            "token": alice.gateway_token(),
+            "session_id": "no-such-session",
+            "seq": 1
+        }
+    });
+    ws.send(Message::Text(resume.to_string().into()))
+        .await
+        .unwrap();
+
+    let msg = ws.next().await.unwrap().unwrap();
+    let json: serde_json::Value = serde_json::from_str(&msg.into_text().unwrap()).unwrap();
+    assert_eq!(json["op"], 7, "expected INVALID_SESSION opcode (7)");
+    assert_eq!(json["data"]["resumable"], false);
+}
+
+#[tokio::test]
+async fn test_ws_resume_rejects_other_users_session() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+
+    let (_alice_ws, session_id) = identify_with_session(&ws_url, &alice.gateway_token()).await;
+
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    let resume = serde_json::json!({
+        "op": 3,
+        "data": {
+            "token": bob.gateway_token(),
+            "session_id": session_id,
+            "seq": 1
+        }
+    });
+    ws.send(Message::Text(resume.to_string().into()))
+        .await
+        .unwrap();
+
+    let msg = ws.next().await.unwrap().unwrap();
+    let json: serde_json::Value = serde_json::from_str(&msg.into_text().unwrap()).unwrap();
+    assert_eq!(json["op"], 7, "expected INVALID_SESSION opcode (7)");
+}
+
+#[tokio::test]
+async fn test_ws_zlib_stream_compression() {
+    use flate2::{Decompress, FlushDecompress};
+
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    // HELLO is sent before negotiation, so it is always plain text.
+    let hello = ws.next().await.unwrap().unwrap();
+    assert!(hello.is_text());
+
+    let identify = serde_json::json!({
+        "op": 2,
+        "data": {
+            "token": alice.gateway_token(),
+            "intents": ["messages"],
+            "compress": "zlib-stream"
+        }
+    });
+    ws.send(Message::Text(identify.to_string().into()))
+        .await
+        .unwrap();
+
+    // Every later frame is a binary chunk of one shared zlib stream.
+    let mut inflater = Decompress::new(true);
+    let mut inflate = |msg: Message| -> serde_json::Value {
+        let Message::Binary(chunk) = msg else {
+            panic!("expected a binary frame, got {msg:?}");
+        };
+        assert!(chunk.ends_with(&[0x00, 0x00, 0xff, 0xff]));
+        let mut out = Vec::with_capacity(64 * 1024);
+        inflater
+            .decompress_vec(&chunk, &mut out, FlushDecompress::Sync)
+            .unwrap();
+        serde_json::from_slice(&out).unwrap()
+    };
+
+    let ready = inflate(ws.next().await.unwrap().unwrap());
+    assert_eq!(ready["type"], "ready");
+
+    ws.send(Message::Text(r#"{"op":1}"#.into())).await.unwrap();
+    let ack = inflate(ws.next().await.unwrap().unwrap());
+    assert_eq!(ack["op"], 4);
+}
+
+#[tokio::test]
+async fn test_ws_request_members_returns_chunks() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let bobby = server.create_user_with_token("bobby").await;
+    let space_id = server.create_space(&alice.user.id, "Members").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    server.add_member(&space_id, &bobby.user.id).await;
+
+    let mut ws = connect_with_intents(&ws_url, &alice.gateway_token(), &["members"]).await;
+
+    // Whole member list
+    let request = serde_json::json!({
+        "op": 10,
+        "data": { "space_id": space_id, "nonce": "all" }
+    });
+    ws.send(Message::Text(request.to_string().into()))
+        .await
+        .unwrap();
+    let (chunk, _) = recv_event_type(&mut ws, "space.members_chunk", 10).await;
+    let chunk = chunk.expect("expected space.members_chunk");
+    assert_eq!(chunk["data"]["nonce"], "all");
+    assert_eq!(chunk["data"]["chunk_index"], 0);
+    assert_eq!(chunk["data"]["chunk_count"], 1);
+    assert_eq!(chunk["data"]["members"].as_array().unwrap().len(), 3);
+
+    // Prefix query, capped by limit
+    let request = serde_json::json!({
+        "op": 10,
+        "data": { "space_id": space_id, "query": "BOB", "limit": 1, "nonce": "bob" }
+    });
+    ws.send(Message::Text(request.to_string().into()))
+        .await
+        .unwrap();
+    let (chunk, _) = recv_event_type(&mut ws, "space.members_chunk", 10).await;
+    let chunk = chunk.expect("expected space.members_chunk");
+    assert_eq!(chunk["data"]["nonce"], "bob");
+    let members = chunk["data"]["members"].as_array().unwrap();
+    assert_eq!(members.len(), 1);
+    assert!(members[0]["user"]["username"]
+        .as_str()
+        .unwrap()
+        .starts_with("bob"));
+}
+
+#[tokio::test]
+async fn test_ws_identify_capabilities() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Caps").await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    let identify = serde_json::json!({
+        "op": 2,
+        "data": {
+            "token": alice.gateway_token(),
+            "intents": ["presences", "members"],
+            "capabilities": {
+                "wants_presence_batching": true,
+                "member_list_v2": true,
+                "some_future_flag": true
+            }
+        }
+    });
+    ws.send(Message::Text(identify.to_string().into()))
+        .await
+        .unwrap();
+    let msg = ws.next().await.unwrap().unwrap();
+    let ready: serde_json::Value = serde_json::from_str(&msg.into_text().unwrap()).unwrap();
+    assert_eq!(
+        ready["data"]["capabilities"],
+        serde_json::json!({
+            "supports_compression": false,
+            "supports_msgpack": false,
+            "wants_presence_batching": true,
+            "member_list_v2": true,
+            "lazy_spaces": false,
+            "omit_blocked_messages": false
+        })
+    );
+
+    // Presence updates arrive batched.
+    let _bob_ws = connect_and_identify(&ws_url, &bob.gateway_token()).await;
+    let (batch, skipped) = recv_event_type(&mut ws, "presence.update_batch", 10).await;
+    let batch = batch.expect("expected presence.update_batch");
+    assert!(skipped.iter().all(|e| e["type"] != "presence.update"));
+    assert_eq!(batch["data"]["presences"][0]["user_id"], bob.user.id);
+
+    // Member chunks list users separately.
+    let request = serde_json::json!({ "op": 10, "data": { "space_id": space_id } });
+    ws.send(Message::Text(request.to_string().into()))
+        .await
+        .unwrap();
+    let (chunk, _) = recv_event_type(&mut ws, "space.members_chunk", 10).await;
+    let data = &chunk.expect("expected space.members_chunk")["data"];
+    assert_eq!(data["members"].as_array().unwrap().len(), 2);
+    assert!(data["members"][0].get("user").is_none());
+    assert_eq!(data["users"].as_array().unwrap().len(), 2);
+    assert!(data["presences"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|p| p["user_id"] == bob.user.id));
+
+    // MessagePack sessions get binary frames.
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    let identify = serde_json::json!({
+        "op": 2,
+        "data": {
+            "token": bob.gateway_token(),
+            "intents": [],
+            "capabilities": { "supports_msgpack": true }
+        }
+    });
+    ws.send(Message::Text(identify.to_string().into()))
+        .await
+        .unwrap();
+    let Message::Binary(ready) = ws.next().await.unwrap().unwrap() else {
+        panic!("expected a binary READY frame");
+    };
+    // A map whose keys include "ready".
+    assert!(ready[0] & 0xf0 == 0x80 || ready[0] == 0xde);
+    assert!(ready.windows(6).any(|w| w == b"\xa5ready"));
+}
+
+#[tokio::test]
+async fn test_ws_lazy_spaces_sync() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Lazy").await;
+    server.add_member(&space_id, &bob.user.id).await;
+
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    let identify = serde_json::json!({
+        "op": 2,
+        "data": {
+            "token": alice.gateway_token(),
+            "intents": ["spaces"],
+            "capabilities": { "lazy_spaces": true }
+        }
+    });
+    ws.send(Message::Text(identify.to_string().into()))
+        .await
+        .unwrap();
+    let msg = ws.next().await.unwrap().unwrap();
+    let ready: serde_json::Value = serde_json::from_str(&msg.into_text().unwrap()).unwrap();
+    assert_eq!(ready["data"]["capabilities"]["lazy_spaces"], true);
+    assert_eq!(ready["data"]["spaces"].as_array().unwrap().len(), 1);
+    assert!(ready["data"]["roles"].as_array().unwrap().is_empty());
+    assert!(ready["data"]["members"].as_array().unwrap().is_empty());
+
+    // Spaces the user isn't in are ignored.
+    let subscribe = serde_json::json!({
+        "op": 11,
+        "data": { "space_ids": [space_id, space_id, "999999"] }
+    });
+    ws.send(Message::Text(subscribe.to_string().into()))
+        .await
+        .unwrap();
+    let (sync, _) = recv_event_type(&mut ws, "space.sync", 10).await;
+    let data = &sync.expect("expected space.sync")["data"];
+    assert_eq!(data["space_id"], space_id);
+    assert_eq!(data["space"]["id"], space_id);
+    assert!(!data["roles"].as_array().unwrap().is_empty());
+    assert_eq!(data["members"].as_array().unwrap().len(), 2);
+    assert_eq!(data["users"].as_array().unwrap().len(), 2);
+    assert!(data["presences"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|p| p["user_id"] == alice.user.id));
+}
+
+#[tokio::test]
+async fn test_ws_request_members_ignores_foreign_space() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let mallory = server.create_user_with_token("mallory").await;
+    let space_id = server.create_space(&alice.user.id, "Private").await;
+
+    let mut ws = connect_with_intents(&ws_url, &mallory.gateway_token(), &["members"]).await;
+    let request = serde_json::json!({
+        "op": 10,
+        "data": { "space_id": space_id }
+    });
+    ws.send(Message::Text(request.to_string().into()))
+        .await
+        .unwrap();
+    let got_chunk = tokio::time::timeout(std::time::Duration::from_millis(500), async {
+        while let Some(Ok(msg)) = ws.next().await {
+            if let Ok(text) = msg.into_text() {
+                if text.contains("space.members_chunk") {
+                    return true;
+                }
+            }
+        }
+        false
+    })
+    .await
+    .unwrap_or(false);
+    assert!(!got_chunk, "non-members must not receive member chunks");
+}
+
+/// Whether any `space.members_chunk` arrives within half a second.
+async fn receives_member_chunk(
+    ws: &mut tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+) -> bool {
+    tokio::time::timeout(std::time::Duration::from_millis(500), async {
+        while let Some(Ok(msg)) = ws.next().await {
+            if let Ok(text) = msg.into_text() {
+                if text.contains("space.members_chunk") {
+                    return true;
+                }
+            }
+        }
+        false
+    })
+    .await
+    .unwrap_or(false)
+}
+
+#[tokio::test]
+async fn test_ws_request_members_requires_members_intent() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Members").await;
+
+    let mut ws = connect_and_identify(&ws_url, &alice.gateway_token()).await;
+    let request = serde_json::json!({ "op": 10, "data": { "space_id": space_id } });
+    ws.send(Message::Text(request.to_string().into()))
+        .await
+        .unwrap();
+    assert!(
+        !receives_member_chunk(&mut ws).await,
+        "sessions without the members intent must not receive member chunks"
+    );
+}
+
+#[tokio::test]
+async fn test_ws_request_members_is_rate_limited() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Members").await;
+
+    let mut ws = connect_with_intents(&ws_url, &alice.gateway_token(), &["members"]).await;
+    for n in 0..=accordserver::gateway::MEMBER_REQUEST_LIMIT {
+        let request = serde_json::json!({
+            "op": 10,
+            "data": { "space_id": space_id, "nonce": n.to_string() }
+        });
+        ws.send(Message::Text(request.to_string().into()))
+            .await
+            .unwrap();
+    }
+
+    let mut nonces = Vec::new();
+    while let Ok(Some(Ok(msg))) =
+        tokio::time::timeout(std::time::Duration::from_millis(500), ws.next()).await
+    {
+        let event: serde_json::Value = serde_json::from_str(&msg.into_text().unwrap()).unwrap();
+        if event["type"] == "space.members_chunk" {
+            nonces.push(event["data"]["nonce"].as_str().unwrap().to_string());
+        }
+    }
+    assert_eq!(
+        nonces.len(),
+        accordserver::gateway::MEMBER_REQUEST_LIMIT as usize
+    );
+    assert!(!nonces.contains(&accordserver::gateway::MEMBER_REQUEST_LIMIT.to_string()));
+}
+
+#[tokio::test]
+async fn test_ws_slash_command_interaction_round_trip() {
+    let (server, ws_url) = spawn_test_server().await;
+    let (_owner, bot) = server.create_bot_with_token("botowner", "DiceBot").await;
+    let alice = server.create_user_with_token("alice").await;
+    let space_id = server.create_space(&alice.user.id, "Commands").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &bot.user.id).await;
+    let app_id: String = sqlx::query_scalar(&accordserver::db::q(
+        "SELECT id FROM applications WHERE bot_user_id = ?",
+    ))
+    .bind(&bot.user.id)
+    .fetch_one(server.pool())
+    .await
+    .unwrap();
+
+    // The bot registers a global command.
+    let req = common::authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/applications/{app_id}/commands"),
+        &bot.auth_header(),
+        &serde_json::json!({
+            "name": "roll",
+            "description": "Roll a die",
+            "options": [{ "name": "sides", "description": "Sides", "type": "integer", "required": true }]
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let command_id = common::parse_body(response).await["data"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Members see it in the space's command list.
+    let req = common::authenticated_request(
+        Method::GET,
+        &format!("/api/v1/spaces/{space_id}/commands"),
+        &alice.auth_header(),
+    );
+    let body = common::parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"][0]["name"], "roll");
+
+    let mut ws = connect_and_identify(&ws_url, &bot.gateway_token()).await;
+
+    // Options are checked against the command's schema.
+    let req = common::authenticated_json_request(
+        Method::POST,
+        "/api/v1/interactions",
+        &alice.auth_header(),
+        &serde_json::json!({
+            "command_id": command_id,
+            "channel_id": channel_id,
+            "options": [{ "name": "sides", "value": "six" }]
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let req = common::authenticated_json_request(
+        Method::POST,
+        "/api/v1/interactions",
+        &alice.auth_header(),
+        &serde_json::json!({
+            "command_id": command_id,
+            "channel_id": channel_id,
+            "options": [{ "name": "sides", "value": 6 }]
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = common::parse_body(response).await;
+    assert!(body["data"].get("token").is_none());
+
+    let (event, _) = recv_event_type(&mut ws, "interaction.create", 10).await;
+    let event = event.expect("bot should receive interaction.create");
+    assert_eq!(event["data"]["data"]["name"], "roll");
+    assert_eq!(event["data"]["user_id"], alice.user.id);
+    let interaction_id = event["data"]["id"].as_str().unwrap().to_string();
+    let token = event["data"]["token"].as_str().unwrap().to_string();
+
+    // A wrong token is rejected.
+    let req = common::json_request(
+        Method::POST,
+        &format!("/api/v1/interactions/{interaction_id}/wrong/callback"),
+        &serde_json::json!({ "type": "channel_message", "data": { "content": "4" } }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // The response becomes a real message from the bot.
+    let req = common::json_request(
+        Method::POST,
+        &format!("/api/v1/interactions/{interaction_id}/{token}/callback"),
+        &serde_json::json!({ "type": "channel_message", "data": { "content": "You rolled 4" } }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = common::parse_body(response).await;
+    assert_eq!(body["data"]["author_id"], bot.user.id);
+    assert_eq!(body["data"]["channel_id"], channel_id);
+
+    // Only one message per interaction.
+    let req = common::json_request(
+        Method::POST,
+        &format!("/api/v1/interactions/{interaction_id}/{token}/callback"),
+        &serde_json::json!({ "type": "channel_message", "data": { "content": "again" } }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    ws.close(None).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_ws_ephemeral_interaction_response() {
+    let (server, ws_url) = spawn_test_server().await;
+    let (_owner, bot) = server.create_bot_with_token("botowner", "SecretBot").await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Whispers").await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    server.add_member(&space_id, &bot.user.id).await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let app_id: String = sqlx::query_scalar(&accordserver::db::q(
+        "SELECT id FROM applications WHERE bot_user_id = ?",
+    ))
+    .bind(&bot.user.id)
+    .fetch_one(server.pool())
+    .await
+    .unwrap();
+
+    let req = common::authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/applications/{app_id}/commands"),
+        &bot.auth_header(),
+        &serde_json::json!({ "name": "balance", "description": "Your balance" }),
+    );
+    let body = common::parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let command_id = body["data"]["id"].as_str().unwrap().to_string();
+
+    let mut bot_ws = connect_and_identify(&ws_url, &bot.gateway_token()).await;
+    let mut alice_ws = connect_and_identify(&ws_url, &alice.gateway_token()).await;
+    let mut bob_ws = connect_and_identify(&ws_url, &bob.gateway_token()).await;
+
+    let req = common::authenticated_json_request(
+        Method::POST,
+        "/api/v1/interactions",
+        &alice.auth_header(),
+        &serde_json::json!({ "command_id": command_id, "channel_id": channel_id }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let (event, _) = recv_event_type(&mut bot_ws, "interaction.create", 10).await;
+    let event = event.expect("bot should receive interaction.create");
+    let interaction_id = event["data"]["id"].as_str().unwrap().to_string();
+    let token = event["data"]["token"].as_str().unwrap().to_string();
+
+    let req = common::json_request(
+        Method::POST,
+        &format!("/api/v1/interactions/{interaction_id}/{token}/callback"),
+        &serde_json::json!({
+            "type": "channel_message",
+            "data": { "content": "You have 42 coins", "ephemeral": true }
+        }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let message = common::parse_body(response).await["data"].clone();
+    let message_id = message["id"].as_str().unwrap().to_string();
+    assert_eq!(message["flags"], 4);
+
+    let (event, _) = recv_event_type(&mut alice_ws, "message.create", 10).await;
+    assert_eq!(
+        event.expect("invoker sees the response")["data"]["id"],
+        message_id
+    );
+
+    // Bob's first message.create is the next public message, not the response.
+    let req = common::authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+        &serde_json::json!({ "content": "ping" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let (event, _) = recv_event_type(&mut bob_ws, "message.create", 10).await;
+    assert_eq!(event.expect("bob sees ping")["data"]["content"], "ping");
+
+    // It's not in history, and only alice can fetch it directly.
+    let req = common::authenticated_request(
+        Method::GET,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+    );
+    let body = common::parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let ids: Vec<&str> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|m| m["id"].as_str())
+        .collect();
+    assert!(!ids.contains(&message_id.as_str()));
+    let message_uri = format!("/api/v1/channels/{channel_id}/messages/{message_id}");
+    let req = common::authenticated_request(Method::GET, &message_uri, &bob.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let req = common::authenticated_request(Method::GET, &message_uri, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The reaper removes it once it expires.
+    let later = (chrono::Utc::now() + chrono::Duration::hours(1))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    let deleted =
+        accordserver::db::messages::delete_expired_ephemeral_messages(server.pool(), &later)
+            .await
+            .unwrap();
+    assert_eq!(deleted, 1);
+    let req = common::authenticated_request(Method::GET, &message_uri, &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    bot_ws.close(None).await.unwrap();
+    alice_ws.close(None).await.unwrap();
+    bob_ws.close(None).await.unwrap();
+}
+
+/// Read frames until the server's close frame, returning its code.
+async fn recv_close_code(
+    ws: &mut tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+) -> Option<u16> {
+    for _ in 0..5 {
+        match tokio::time::timeout(std::time::Duration::from_secs(5), ws.next()).await {
+            Ok(Some(Ok(Message::Close(frame)))) => return frame.map(|f| u16::from(f.code)),
+            Ok(Some(Ok(_))) => continue,
+            _ => return None,
+        }
+    }
+    None
+}
+
+#[tokio::test]
+async fn test_ws_close_codes() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+
+    let identify = |token: &str, intents: serde_json::Value| {
+        serde_json::json!({ "op": 2, "data": { "token": token, "intents": intents } }).to_string()
+    };
+
+    // Bad token: INVALID_SESSION, then 4004.
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    ws.send(Message::Text(
+        identify("Bearer nope", serde_json::json!(["messages"])).into(),
+    ))
+    .await
+    .unwrap();
+    assert_eq!(recv_close_code(&mut ws).await, Some(4004));
+
+    // Unknown intent: 4013.
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    ws.send(Message::Text(
+        identify(&alice.gateway_token(), serde_json::json!(["guilds"])).into(),
+    ))
+    .await
+    .unwrap();
+    assert_eq!(recv_close_code(&mut ws).await, Some(4013));
+
+    // The admin intent from a non-admin: 4014.
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    ws.send(Message::Text(
+        identify(&alice.gateway_token(), serde_json::json!(["admin"])).into(),
+    ))
+    .await
+    .unwrap();
+    assert_eq!(recv_close_code(&mut ws).await, Some(4014));
+
+    // Malformed payload after IDENTIFY: 4002.
+    let mut ws = connect_and_identify(&ws_url, &alice.gateway_token()).await;
+    ws.send(Message::Text("not json".into())).await.unwrap();
+    assert_eq!(recv_close_code(&mut ws).await, Some(4002));
+
+    // Second IDENTIFY: 4005.
+    let mut ws = connect_and_identify(&ws_url, &alice.gateway_token()).await;
+    ws.send(Message::Text(
+        identify(&alice.gateway_token(), serde_json::json!(["messages"])).into(),
+    ))
+    .await
+    .unwrap();
+    assert_eq!(recv_close_code(&mut ws).await, Some(4005));
+
+    // Unknown opcode: 4001.
+    let mut ws = connect_and_identify(&ws_url, &alice.gateway_token()).await;
+    ws.send(Message::Text(
+        serde_json::json!({ "op": 99 }).to_string().into(),
+    ))
+    .await
+    .unwrap();
+    assert_eq!(recv_close_code(&mut ws).await, Some(4001));
+
+    // Server shutdown: 4016.
+    let mut ws = connect_and_identify(&ws_url, &alice.gateway_token()).await;
+    if let Some(ref dispatcher) = *server.state.dispatcher.read().await {
+        dispatcher.shutdown();
+    }
+    assert_eq!(recv_close_code(&mut ws).await, Some(4016));
+}
+
+#[tokio::test]
+async fn test_shutdown_drains_gateway_and_voice() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Drain").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let vc_id = server.create_voice_channel(&space_id, "lounge").await;
+
+    let mut ws = connect_and_identify(&ws_url, &alice.gateway_token()).await;
+
+    // Bob joined over HTTP, so no gateway session will clean up after him.
+    let req = common::authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{vc_id}/voice/join"),
+        &bob.auth_header(),
+        &serde_json::json!({}),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(server.state.voice_states.len(), 1);
+
+    let drain = accordserver::shutdown::drain_gateway(&server.state);
+    let (_, code) = tokio::join!(drain, recv_close_code(&mut ws));
+    assert_eq!(code, Some(4016));
+
+    assert!(server.state.voice_states.is_empty());
+    let dispatcher = server.state.dispatcher.read().await;
+    if let Some(ref dispatcher) = *dispatcher {
+        assert!(dispatcher.sessions().is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_ws_blocked_user_messages() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Blocks").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let req = common::authenticated_json_request(
+        Method::PUT,
+        &format!("/api/v1/users/@me/relationships/{}", bob.user.id),
+        &alice.auth_header(),
+        &serde_json::json!({ "type": 2 }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let mut flagged = connect_and_identify(&ws_url, &alice.gateway_token()).await;
+    let (mut omitted, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = omitted.next().await.unwrap().unwrap();
+    let identify = serde_json::json!({
+        "op": 2,
+        "data": {
+            "token": alice.gateway_token(),
+            "intents": ["messages", "message_content"],
+            "capabilities": { "omit_blocked_messages": true }
+        }
+    });
+    omitted
+        .send(Message::Text(identify.to_string().into()))
+        .await
+        .unwrap();
+    let _ = omitted.next().await.unwrap().unwrap();
+
+    for (author, content) in [(&bob, "from bob"), (&alice, "from alice")] {
+        let req = common::authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/messages"),
+            &author.auth_header(),
+            &serde_json::json!({ "content": content }),
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let (event, _) = recv_event_type(&mut flagged, "message.create", 10).await;
+    let data = &event.expect("expected message.create")["data"];
+    assert_eq!(data["author_id"], bob.user.id);
+    assert_eq!(data["blocked"], true);
+
+    let (event, _) = recv_event_type(&mut omitted, "message.create", 10).await;
+    let data = &event.expect("expected message.create")["data"];
+    assert_eq!(data["author_id"], alice.user.id);
+    assert!(data.get("blocked").is_none());
+}
+
+#[tokio::test]
+async fn test_ws_user_settings_update() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let mut ws = connect_and_identify(&ws_url, &alice.gateway_token()).await;
+
+    let req = common::authenticated_json_request(
+        Method::PATCH,
+        "/api/v1/users/@me/settings",
+        &alice.auth_header(),
+        &serde_json::json!({ "settings": { "theme": "dark" } }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let (event, _) = recv_event_type(&mut ws, "user_settings.update", 10).await;
+    let data = &event.expect("expected user_settings.update")["data"];
+    assert_eq!(data["version"], 1);
+    assert_eq!(data["settings"]["theme"], "dark");
+
+    let space_id = server.create_space(&alice.user.id, "Folders").await;
+    let req = common::authenticated_json_request(
+        Method::PUT,
+        "/api/v1/users/@me/settings/spaces",
+        &alice.auth_header(),
+        &serde_json::json!({ "folders": [{ "name": "Work", "space_ids": [space_id] }] }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let (event, _) = recv_event_type(&mut ws, "user_settings.spaces_update", 10).await;
+    let data = &event.expect("expected user_settings.spaces_update")["data"];
+    assert_eq!(data["folders"][0]["name"], "Work");
+
+    // New sessions get the layout in READY.
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    let identify = serde_json::json!({
+        "op": 2,
+        "data": { "token": alice.gateway_token(), "intents": [] }
+    });
+    ws.send(Message::Text(identify.to_string().into()))
+        .await
+        .unwrap();
+    let msg = ws.next().await.unwrap().unwrap();
+    let ready: serde_json::Value = serde_json::from_str(&msg.into_text().unwrap()).unwrap();
+    assert_eq!(ready["data"]["space_folders"][0]["space_ids"][0], space_id);
+}
+
+#[tokio::test]
+async fn test_revoking_session_closes_its_gateway_connection() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+
+    // A second login for alice, as from another device.
+    let other_token = accordserver::middleware::auth::generate_token();
+    accordserver::db::sessions::create_session(
+        server.pool(),
+        &alice.user.id,
+        &accordserver::middleware::auth::create_token_hash(&other_token),
+        "2099-12-31T23:59:59",
+        Some("Phone"),
+        None,
+    )
+    .await
+    .unwrap();
+    let other_auth = format!("Bearer {other_token}");
+
+    let mut ws = connect_and_identify(&ws_url, &alice.gateway_token()).await;
+    let mut other_ws = connect_and_identify(&ws_url, &other_auth).await;
+
+    let req = common::authenticated_request(Method::GET, "/api/v1/users/@me/sessions", &other_auth);
+    let sessions = common::parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let sessions = sessions["data"].as_array().unwrap().clone();
+    assert_eq!(sessions.len(), 2);
+    let current = sessions.iter().find(|s| s["current"] == true).unwrap();
+    assert_eq!(current["device"], "Phone");
+    let revoked = sessions.iter().find(|s| s["current"] == false).unwrap();
+    let revoked_id = revoked["id"].as_str().unwrap();
+
+    let req = common::authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/users/@me/sessions/{revoked_id}"),
+        &other_auth,
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(recv_close_code(&mut ws).await, Some(4004));
+
+    // The revoked token is dead; the other session carries on.
+    let req = common::authenticated_request(Method::GET, "/api/v1/users/@me", &alice.auth_header());
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let hb = serde_json::json!({ "op": 1 });
+    other_ws
+        .send(Message::Text(hb.to_string().into()))
+        .await
+        .unwrap();
+    let msg = tokio::time::timeout(std::time::Duration::from_secs(5), other_ws.next())
+        .await
+        .expect("the other session should stay connected")
+        .unwrap()
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_str(&msg.into_text().unwrap()).unwrap();
+    assert_eq!(json["op"], 4);
+
+    let req = common::authenticated_request(
+        Method::DELETE,
+        &format!("/api/v1/users/@me/sessions/{revoked_id}"),
+        &other_auth,
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_ws_typing_is_coalesced() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let admin = server.create_admin_with_token("admin").await;
+    let space_id = server.create_space(&alice.user.id, "Typing").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let general = server.create_channel(&space_id, "general").await;
+    let random = server.create_channel(&space_id, "random").await;
+
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    let identify = serde_json::json!({
+        "op": 2,
+        "data": { "token": bob.gateway_token(), "intents": ["message_typing"] }
+    });
+    ws.send(Message::Text(identify.to_string().into()))
+        .await
+        .unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+
+    // The repeat in #general falls inside the first event's window.
+    for channel_id in [&general, &general, &random] {
+        let req = common::authenticated_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/typing"),
+            &alice.auth_header(),
+        );
+        let response = server.router().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let (first, _) = recv_event_type(&mut ws, "typing.start", 5).await;
+    assert_eq!(
+        first.expect("expected typing.start")["data"]["channel_id"],
+        general
+    );
+    let (second, _) = recv_event_type(&mut ws, "typing.start", 5).await;
+    assert_eq!(
+        second.expect("expected typing.start")["data"]["channel_id"],
+        random
+    );
+
+    let req =
+        common::authenticated_request(Method::GET, "/api/v1/admin/metrics", &admin.auth_header());
+    let metrics = common::parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(metrics["data"]["typing"]["sent"], 2);
+    assert_eq!(metrics["data"]["typing"]["coalesced"], 1);
+}
+
+#[tokio::test]
+async fn test_ws_login_ticket_handoff() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+
+    let req = common::json_request(
+        Method::POST,
+        "/api/v1/auth/login-tickets",
+        &serde_json::json!({ "device_name": "Living Room TV" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = common::parse_body(response).await;
+    let ticket = body["data"]["ticket"].as_str().unwrap().to_string();
+    let secret = body["data"]["secret"].as_str().unwrap().to_string();
+
+    let wait = |secret: &str| {
+        serde_json::json!({ "op": 12, "data": { "ticket": ticket, "secret": secret } }).to_string()
+    };
+
+    // Knowing the ticket from the QR code isn't enough to receive the token.
+    let (mut snoop, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = snoop.next().await.unwrap().unwrap();
+    snoop
+        .send(Message::Text(wait("guess").into()))
+        .await
+        .unwrap();
+    assert_eq!(recv_close_code(&mut snoop).await, Some(4004));
+
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    ws.send(Message::Text(wait(&secret).into())).await.unwrap();
+    let hb = serde_json::json!({ "op": 1 });
+    ws.send(Message::Text(hb.to_string().into())).await.unwrap();
+    let msg = ws.next().await.unwrap().unwrap();
+    let json: serde_json::Value = serde_json::from_str(&msg.into_text().unwrap()).unwrap();
+    assert_eq!(json["op"], 4);
+
+    // The phone sees which device it's signing in, then approves.
+    let uri = format!("/api/v1/auth/login-tickets/{ticket}");
+    let req = common::authenticated_request(Method::GET, &uri, &alice.auth_header());
+    let body = common::parse_body(server.router().oneshot(req).await.unwrap()).await;
+    assert_eq!(body["data"]["device_name"], "Living Room TV");
+
+    let req = common::authenticated_request(
+        Method::POST,
+        &format!("{uri}/approve"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let (approved, _) = recv_event_type(&mut ws, "login_ticket.approved", 5).await;
+    let approved = approved.expect("expected login_ticket.approved");
+    assert_eq!(approved["data"]["user"]["id"], alice.user.id);
+    assert_eq!(recv_close_code(&mut ws).await, Some(1000));
+
+    // The handed-off token is a regular session named after the device.
+    let token = format!("Bearer {}", approved["data"]["token"].as_str().unwrap());
+    let req = common::authenticated_request(Method::GET, "/api/v1/users/@me/sessions", &token);
+    let sessions = common::parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let current = sessions["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["current"] == true)
+        .cloned()
+        .unwrap();
+    assert_eq!(current["device"], "Living Room TV");
+
+    // A ticket signs in one device only.
+    let req = common::authenticated_request(
+        Method::POST,
+        &format!("{uri}/approve"),
+        &alice.auth_header(),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_ws_admin_intent_receives_instance_events() {
+    let (server, ws_url) = spawn_test_server().await;
+    let admin = server.create_admin_with_token("admin").await;
+    let bob = server.create_user_with_token("bob").await;
+
+    let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+    let _ = ws.next().await.unwrap().unwrap();
+    let identify = serde_json::json!({
+        "op": 2,
+        "data": { "token": admin.gateway_token(), "intents": ["admin"] }
+    });
+    ws.send(Message::Text(identify.to_string().into()))
+        .await
+        .unwrap();
+    let (ready, _) = recv_event_type(&mut ws, "ready", 5).await;
+    assert!(ready.is_some());
+
+    let req = common::authenticated_json_request(
+        Method::POST,
+        "/api/v1/spaces",
+        &bob.auth_header(),
+        &serde_json::json!({ "name": "Bob's Space" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let (event, _) = recv_event_type(&mut ws, "admin.space_create", 10).await;
+    let event = event.expect("admin should get admin.space_create");
+    assert_eq!(event["data"]["name"], "Bob's Space");
+    assert_eq!(event["data"]["owner_id"], bob.user.id);
+
+    ws.close(None).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_ws_admin_report_update_reaches_only_admins() {
+    let (server, ws_url) = spawn_test_server().await;
+    let admin = server.create_admin_with_token("admin").await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Reports").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+
+    let connect = |token: String, intents: serde_json::Value| {
+        let ws_url = ws_url.clone();
+        async move {
+            let (mut ws, _) = connect_async(format!("{ws_url}/ws")).await.unwrap();
+            let _ = ws.next().await.unwrap().unwrap();
+            let identify = serde_json::json!({
+                "op": 2,
+                "data": { "token": token, "intents": intents }
+            });
+            ws.send(Message::Text(identify.to_string().into()))
+                .await
+                .unwrap();
+            let (ready, _) = recv_event_type(&mut ws, "ready", 5).await;
+            assert!(ready.is_some());
+            ws
+        }
+    };
+    let mut admin_ws = connect(admin.gateway_token(), serde_json::json!(["admin"])).await;
+    let everything_else: Vec<&str> = intents::ALL_INTENTS
+        .iter()
+        .copied()
+        .filter(|i| *i != intents::ADMIN_INTENT)
+        .collect();
+    let mut bob_ws = connect(bob.gateway_token(), serde_json::json!(everything_else)).await;
+
+    let mut alice_ws = connect(alice.gateway_token(), serde_json::json!(["moderation"])).await;
+
+    let req = common::authenticated_json_request(
+        Method::POST,
+        "/api/v1/reports",
+        &alice.auth_header(),
+        &serde_json::json!({
+            "target_type": "user",
+            "target_id": bob.user.id,
+            "space_id": space_id,
+            "category": "fraud",
+        }),
+    );
+    let body = common::parse_body(server.router().oneshot(req).await.unwrap()).await;
+    let report_id = body["data"]["id"].as_str().unwrap().to_string();
+    let req = common::authenticated_json_request(
+        Method::PATCH,
+        &format!("/api/v1/admin/reports/{report_id}"),
+        &admin.auth_header(),
+        &serde_json::json!({ "status": "dismissed" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The owner moderates the space and sees reports filed in it.
+    let (event, _) = recv_event_type(&mut alice_ws, "report.create", 10).await;
+    assert_eq!(
+        event.expect("moderators get report.create")["data"]["id"],
+        report_id.as_str()
+    );
+    let (event, _) = recv_event_type(&mut admin_ws, "admin.report_update", 10).await;
+    let event = event.expect("admin should get admin.report_update");
+    assert_eq!(event["data"]["status"], "dismissed");
+
+    // Everything bob was sent before this message arrives ahead of it.
+    let req = common::authenticated_json_request(
+        Method::POST,
+        &format!("/api/v1/channels/{channel_id}/messages"),
+        &alice.auth_header(),
+        &serde_json::json!({ "content": "after the report" }),
+    );
+    let response = server.router().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let (event, others) = recv_event_type(&mut bob_ws, "message.create", 20).await;
+    assert!(event.is_some());
+    // Bob is neither an admin nor a moderator of the space.
+    assert!(
+        others.iter().all(|e| {
+            let event_type = e["type"].as_str().unwrap_or("");
+            !event_type.starts_with("admin.") && !event_type.starts_with("report.")
+        }),
+        "a non-admin session received a report event: {others:?}"
+    );
+
+    admin_ws.close(None).await.unwrap();
+    alice_ws.close(None).await.unwrap();
+    bob_ws.close(None).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_ws_muted_space_still_delivers_mentions() {
+    let (server, ws_url) = spawn_test_server().await;
+    let alice = server.create_user_with_token("alice").await;
+    let bob = server.create_user_with_token("bob").await;
+    let space_id = server.create_space(&alice.user.id, "Quiet").await;
+    server.add_member(&space_id, &bob.user.id).await;
+    let channel_id = server.create_channel(&space_id, "general").await;
+    let mut ws = connect_and_identify(&ws_url, &bob.gateway_token()).await;
+
+    let settings_uri = format!("/api/v1/users/@me/spaces/{space_id}/settings");
+    let patch = |body: serde_json::Value| {
+        common::authenticated_json_request(Method::PATCH, &settings_uri, &bob.auth_header(), &body)
+    };
+    let response = server
+        .router()
+        .oneshot(patch(
+            serde_json::json!({ "muted": true, "suppress_everyone": true }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let (event, _) = recv_event_type(&mut ws, "notification_settings.update", 10).await;
+    let data = &event.expect("expected notification_settings.update")["data"];
+    assert_eq!(data["muted"], true);
+    assert_eq!(data["suppress_everyone"], true);
+
+    let post = |content: String| {
+        common::authenticated_json_request(
+            Method::POST,
+            &format!("/api/v1/channels/{channel_id}/messages"),
+            &alice.auth_header(),
+            &serde_json::json!({ "content": content }),
+        )
+    };
+    for content in [
+        "nobody in particular".to_string(),
+        "@everyone suppressed".to_string(),
+        format!("hey <@{}>", bob.user.id),
+    ] {
+        let response = server.router().oneshot(post(content)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let (event, _) = recv_event_type(&mut ws, "message.create", 10).await;
+    let data = &event.expect("expected message.create")["data"];
+    assert_eq!(data["content"], format!("hey <@{}>", bob.user.id));
+    assert_eq!(data["mentioned"], true);
+
+    // Role mentions count only for members holding the role.
+    let helpers = server.create_role(&space_id, "Helpers", &[]).await;
+    let others = server.create_role(&space_id, "Others", &[]).await;
+    server.assign_role(&space_id, &bob.user.id, &helpers).await;
+    for content in [format!("ping <@&{others}>"), format!("ping <@&{helpers}>")] {
+        let response = server.router().oneshot(post(content)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let (event, _) = recv_event_type(&mut ws, "message.create", 10).await;
+    let data = &event.expect("expected message.create")["data"];
+    assert_eq!(data["content"], format!("ping <@&{helpers}>"));
+    assert_eq!(data["mentioned"], true);
+
+    // Keeping the channel unmuted lets everything through again.
+    let response = server
+        .router()
+        .oneshot(patch(serde_json::json!({
+            "channel_overrides": [{ "channel_id": channel_id, "muted": false }]
+        })))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = common::parse_body(response).await;
+    assert_eq!(body["data"]["channel_overrides"][0]["muted"], false);
+    let response = server
+        .router()
+        .oneshot(post("back in the loop".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let (event, _) = recv_event_type(&mut ws, "message.create", 10).await;
+    let data = &event.expect("expected message.create")["data"];
+    assert_eq!(data["content"], "back in the loop");
+    assert!(data.get("mentioned").is_none());
+}